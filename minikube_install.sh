@@ -21,11 +21,14 @@ minikube cp /etc/quark/config.json minikube:/etc/quark/config.json
 docker exec $MINIKUBE_DOCKER_ID mkdir -p /var/log/quark
 #docker exec $MINIKUBE_DOCKER_ID rm /var/log/quark/quark.log
 
-## copy vdso.so, as qkernel needs it
+## copy vdso.so and vdso32.so, as qkernel needs them
 # adhoc only
 docker exec $MINIKUBE_DOCKER_ID rm -f /usr/local/bin/vdso.so
 minikube cp /usr/local/bin/vdso.so minikube:/usr/local/bin/vdso.so
 docker exec $MINIKUBE_DOCKER_ID chmod 755 /usr/local/bin/vdso.so
+docker exec $MINIKUBE_DOCKER_ID rm -f /usr/local/bin/vdso32.so
+minikube cp /usr/local/bin/vdso32.so minikube:/usr/local/bin/vdso32.so
+docker exec $MINIKUBE_DOCKER_ID chmod 755 /usr/local/bin/vdso32.so
 
 ## copy runsc-shim so that we can try to use it with quark
 minikube cp /usr/local/bin/containerd-shim-runsc-v1 minikube:/usr/local/bin/containerd-shim-runsc-v1
@@ -22,6 +22,8 @@ use super::qlib::linux_def::*;
 use super::qlib::perf_tunning::*;
 use super::qlib::singleton::*;
 use super::qlib::vcpu_mgr::*;
+use super::syscalls::sys_compat::*;
+use super::syscalls::syscalls::*;
 use super::task::*;
 use super::threadmgr::task_sched::*;
 use super::MainRun;
@@ -81,6 +83,7 @@ extern "C" {
     pub fn simd_fp_handler();
     pub fn virtualization_handler();
     pub fn security_handler();
+    pub fn int80_handler();
 }
 
 pub static IDT: Singleton<idt::Idt> = Singleton::<idt::Idt>::New();
@@ -127,6 +130,11 @@ pub unsafe fn InitSingleton() {
 
     idt.set_handler(30, security_handler).set_stack_index(0);
 
+    // the syscall gate of the 32 bit compat processes
+    idt.set_handler(0x80, int80_handler)
+        .set_stack_index(0)
+        .set_privilege_level(3);
+
     IDT.Init(idt);
 }
 
@@ -173,6 +181,10 @@ pub fn ExceptionHandler(ev: ExceptionStackVec, ptRegs: &mut PtRegs, errorCode: u
         && ev != ExceptionStackVec::SIMDFloatingPointException
     {
         currTask.SaveFp();
+    } else {
+        // the fp registers hold the unsaved state of the task, no saved state
+        // can skip its reload on this vcpu
+        CPULocal::Myself().SetFpToken(0);
     }
 
     match ev {
@@ -220,7 +232,7 @@ pub fn ExceptionHandler(ev: ExceptionStackVec, ptRegs: &mut PtRegs, errorCode: u
         | ExceptionStackVec::SIMDFloatingPointException => {
             let info = SignalInfo {
                 Signo: Signal::SIGFPE,
-                Code: 7, // FPE_FLTINV (invalid operation).
+                Code: FPExceptionCode(ev == ExceptionStackVec::SIMDFloatingPointException),
                 ..Default::default()
             };
 
@@ -238,9 +250,15 @@ pub fn ExceptionHandler(ev: ExceptionStackVec, ptRegs: &mut PtRegs, errorCode: u
                 .expect("DivByZeroHandler send signal fail");
         }
         ExceptionStackVec::Debug | ExceptionStackVec::Breakpoint => {
+            // int3 is reported as SI_KERNEL like Linux does
+            let code = if ev == ExceptionStackVec::Breakpoint {
+                SignalInfo::SIGNAL_INFO_KERNEL
+            } else {
+                SignalInfo::TRAP_BRKPT
+            };
             let info = SignalInfo {
                 Signo: Signal::SIGTRAP,
-                Code: 1,
+                Code: code,
                 ..Default::default()
             };
 
@@ -263,8 +281,9 @@ pub fn ExceptionHandler(ev: ExceptionStackVec, ptRegs: &mut PtRegs, errorCode: u
                 ..Default::default()
             };
 
+            // the faulting address is not known for a #GP
             let sigfault = info.SigFault();
-            sigfault.addr = ptRegs.rip;
+            sigfault.addr = 0;
             let thread = currTask.Thread();
             thread.forceSignal(Signal(info.Signo), false);
             thread
@@ -285,11 +304,11 @@ pub fn ExceptionHandler(ev: ExceptionStackVec, ptRegs: &mut PtRegs, errorCode: u
 
             let info = SignalInfo {
                 Signo: Signal::SIGILL,
-                Code: 1,
+                Code: SignalInfo::ILL_ILLOPN,
                 ..Default::default()
             };
 
-            let sigfault = info.SigFault(); // ILL_ILLOPC (illegal opcode).
+            let sigfault = info.SigFault();
             sigfault.addr = ptRegs.rip;
             let thread = currTask.Thread();
             thread.forceSignal(Signal(info.Signo), false);
@@ -300,12 +319,12 @@ pub fn ExceptionHandler(ev: ExceptionStackVec, ptRegs: &mut PtRegs, errorCode: u
         ExceptionStackVec::AlignmentCheck => {
             let info = SignalInfo {
                 Signo: Signal::SIGBUS,
-                Code: 2, // BUS_ADRERR (physical address does not exist).
+                Code: SignalInfo::BUS_ADRALN,
                 ..Default::default()
             };
 
-            let sigfault = info.SigFault(); // ILL_ILLOPC (illegal opcode).
-            sigfault.addr = ptRegs.rip;
+            let sigfault = info.SigFault();
+            sigfault.addr = 0;
             let thread = currTask.Thread();
             thread.forceSignal(Signal(info.Signo), false);
             thread
@@ -330,9 +349,58 @@ pub fn ExceptionHandler(ev: ExceptionStackVec, ptRegs: &mut PtRegs, errorCode: u
 
 pub fn ReturnToApp(pt: &mut PtRegs) -> ! {
     let kernalRsp = pt as *const _ as u64;
+    // sysret always returns to the 64 bit code segment
+    if pt.cs == UCODE32 as u64 {
+        IRet(kernalRsp)
+    }
+
     SyscallRet(kernalRsp);
 }
 
+// Int80Handler runs the syscall of a 32 bit compat process, the arguments
+// are in ebx, ecx, edx, esi, edi and ebp.
+#[no_mangle]
+pub extern "C" fn Int80Handler(ptRegs: &mut PtRegs) {
+    let currTask = Task::Current();
+
+    let mut rflags = ptRegs.eflags;
+    rflags &= !USER_FLAGS_CLEAR;
+    rflags |= USER_FLAGS_SET;
+    ptRegs.eflags = rflags;
+
+    if SHARESPACE.config.read().KernelPagetable {
+        Task::SetKernelPageTable();
+    }
+
+    currTask.AccountTaskLeave(SchedState::RunningApp);
+
+    let nr = ptRegs.rax & 0xffff_ffff;
+    ptRegs.orig_rax = nr;
+    let args = SyscallArguments {
+        arg0: ptRegs.rbx & 0xffff_ffff,
+        arg1: ptRegs.rcx & 0xffff_ffff,
+        arg2: ptRegs.rdx & 0xffff_ffff,
+        arg3: ptRegs.rsi & 0xffff_ffff,
+        arg4: ptRegs.rdi & 0xffff_ffff,
+        arg5: ptRegs.rbp & 0xffff_ffff,
+    };
+
+    currTask.SaveFp();
+    currTask.DoStop();
+
+    let state = CompatSysCall(currTask, nr, &args);
+    MainRun(currTask, state);
+
+    currTask.RestoreFp();
+    if SHARESPACE.config.read().KernelPagetable {
+        currTask.SwitchPageTable();
+    }
+
+    CPULocal::Myself().SetEnterAppTimestamp(TSC.Rdtsc());
+    currTask.mm.HandleTlbShootdown();
+    IRet(ptRegs as *const _ as u64);
+}
+
 #[no_mangle]
 pub extern "C" fn DivByZeroHandler(sf: &mut PtRegs) {
     ExceptionHandler(ExceptionStackVec::DivideByZero, sf, 0);
@@ -410,6 +478,7 @@ bitflags! {
         const USER_MODE = 1 << 2;
         const MALFORMED_TABLE = 1 << 3;
         const INSTRUCTION_FETCH = 1 << 4;
+        const PROTECTION_KEY = 1 << 5;
     }
 }
 
@@ -476,7 +545,27 @@ pub extern "C" fn PageFaultHandler(ptRegs: &mut PtRegs, errorCode: u64) {
         );
     }
 
+    // the page may have been installed by another thread of the process after
+    // the fault, check it without the mapping lock
+    let errbits = PageFaultErrorCode::from_bits(errorCode).unwrap();
+    if errbits & PageFaultErrorCode::PROTECTION_VIOLATION != PageFaultErrorCode::PROTECTION_VIOLATION {
+        let write = errbits & PageFaultErrorCode::CAUSED_BY_WRITE == PageFaultErrorCode::CAUSED_BY_WRITE;
+        let exec = errbits & PageFaultErrorCode::INSTRUCTION_FETCH == PageFaultErrorCode::INSTRUCTION_FETCH;
+        if currTask.mm.PageMapped(cr2, write, exec) {
+            if fromUser {
+                currTask.AccountTaskEnter(SchedState::RunningApp);
+                if SHARESPACE.config.read().KernelPagetable {
+                    currTask.SwitchPageTable();
+                }
+            }
+
+            return;
+        }
+    }
+
     let signal;
+    let code;
+    let mut pkey = 0;
     // no need loop, just need to enable break
     loop {
         let _ml = currTask.mm.MappingWriteLock();
@@ -489,25 +578,34 @@ pub extern "C" fn PageFaultHandler(ptRegs: &mut PtRegs, errorCode: u64) {
                     print!("the map is {}", &map);
                 }
 
-                //todo: when to send sigbus/SIGSEGV
                 signal = Signal::SIGSEGV;
+                code = SignalInfo::SEGV_MAPERR;
                 break;
             }
             Some(vma) => vma.clone(),
         };
 
-        let errbits = PageFaultErrorCode::from_bits(errorCode).unwrap();
         if vma.kernel == true {
             let map = currTask.mm.GetSnapshotLocked(currTask, false);
             error!("the map2 is {}", &map);
 
             signal = Signal::SIGSEGV;
+            code = SignalInfo::SEGV_MAPERR;
             break;
         }
 
         if !vma.effectivePerms.Read() {
             // has no read permission
             signal = Signal::SIGSEGV;
+            code = SignalInfo::SEGV_ACCERR;
+            break;
+        }
+
+        // the pkru of the task denies the access to the protection key of the page
+        if errbits & PageFaultErrorCode::PROTECTION_KEY == PageFaultErrorCode::PROTECTION_KEY {
+            signal = Signal::SIGSEGV;
+            code = SignalInfo::SEGV_PKUERR;
+            pkey = vma.pkey;
             break;
         }
 
@@ -523,12 +621,18 @@ pub extern "C" fn PageFaultHandler(ptRegs: &mut PtRegs, errorCode: u64) {
         {
             //error!("InstallPage 1, range is {:x?}, address is {:x}, vma.growsDown is {}",
             //    &range, pageAddr, vma.growsDown);
+            let major = match &vma.mappable {
+                Some(mappable) => !mappable.ChunkMapped(pageAddr - range.Start() + vma.offset),
+                None => false,
+            };
+
             match currTask
                 .mm
                 .InstallPageLocked(currTask, &vma, pageAddr, &range)
             {
                 Err(Error::FileMapError) => {
                     signal = Signal::SIGBUS;
+                    code = SignalInfo::BUS_ADRERR;
                     break;
                 }
                 Err(e) => {
@@ -536,6 +640,7 @@ pub extern "C" fn PageFaultHandler(ptRegs: &mut PtRegs, errorCode: u64) {
                 }
                 _ => (),
             };
+            currTask.AccountPageFault(major);
 
             for i in 1..8 {
                 let addr = if vma.growsDown {
@@ -569,17 +674,20 @@ pub extern "C" fn PageFaultHandler(ptRegs: &mut PtRegs, errorCode: u64) {
 
         if vma.private == false {
             signal = Signal::SIGSEGV;
+            code = SignalInfo::SEGV_ACCERR;
             break;
         }
 
         if (errbits & PageFaultErrorCode::CAUSED_BY_WRITE) == PageFaultErrorCode::CAUSED_BY_WRITE {
             if !vma.effectivePerms.Write() && fromUser {
                 signal = Signal::SIGSEGV;
+                code = SignalInfo::SEGV_ACCERR;
                 break;
             }
 
             currTask.mm.CopyOnWriteLocked(pageAddr, &vma);
             currTask.mm.TlbShootdown();
+            currTask.AccountPageFault(false);
             if fromUser {
                 //PerfGoto(PerfType::User);
                 currTask.AccountTaskEnter(SchedState::RunningApp);
@@ -590,13 +698,16 @@ pub extern "C" fn PageFaultHandler(ptRegs: &mut PtRegs, errorCode: u64) {
             }
         } else {
             signal = Signal::SIGSEGV;
+            code = SignalInfo::SEGV_ACCERR;
             break;
         }
 
         return;
     }
 
-    HandleFault(currTask, fromUser, errorCode, cr2, ptRegs, signal);
+    HandleFault(
+        currTask, fromUser, errorCode, cr2, ptRegs, signal, code, pkey,
+    );
 }
 
 pub fn HandleFault(
@@ -606,6 +717,8 @@ pub fn HandleFault(
     cr2: u64,
     sf: &mut PtRegs,
     signal: i32,
+    code: i32,
+    pkey: i32,
 ) -> ! {
     if !user {
         let map = task.mm.GetSnapshotLocked(task, false);
@@ -617,29 +730,22 @@ pub fn HandleFault(
 
     task.SaveFp();
 
-    let mut info = SignalInfo {
-        Signo: signal, //Signal::SIGBUS,
+    let info = SignalInfo {
+        Signo: signal,
+        Code: code,
         ..Default::default()
     };
 
     let sigfault = info.SigFault();
     sigfault.addr = cr2;
-    //let read = errorCode & (1<<1) == 0;
-    let write = errorCode & (1 << 1) != 0;
-    let execute = errorCode & (1 << 4) != 0;
-
-    if !write && !execute {
-        info.Code = 1; // SEGV_MAPERR.
-    } else {
-        info.Code = 2; // SEGV_ACCERR.
-    }
+    sigfault.pkey = pkey as u32;
 
     let thread = task.Thread();
     // Synchronous signal. Send it to ourselves. Assume the signal is
     // legitimate and force it (work around the signal being ignored or
     // blocked) like Linux does. Conveniently, this is even the correct
     // behavior for SIGTRAP from single-stepping.
-    thread.forceSignal(Signal(Signal::SIGSEGV), false);
+    thread.forceSignal(Signal(signal), false);
     thread
         .SendSignal(&info)
         .expect("PageFaultHandler send signal fail");
@@ -650,6 +756,43 @@ pub fn HandleFault(
     ReturnToApp(sf);
 }
 
+// FPExceptionCode returns the FPE_* code of the pending unmasked x87 or
+// SIMD floating point exception. The fp state is still live in the registers.
+pub fn FPExceptionCode(simd: bool) -> i32 {
+    let err: u32 = if simd {
+        let mut mxcsr: u32 = 0;
+        unsafe {
+            llvm_asm!("stmxcsr ($0)" :: "r" (&mut mxcsr as *mut u32) : "memory" : "volatile");
+        }
+        // the mask bits are the flag bits shifted by 7
+        !(mxcsr >> 7) & mxcsr
+    } else {
+        let mut cwd: u16 = 0;
+        let mut swd: u16 = 0;
+        unsafe {
+            llvm_asm!("fnstcw ($0)" :: "r" (&mut cwd as *mut u16) : "memory" : "volatile");
+            llvm_asm!("fnstsw ($0)" :: "r" (&mut swd as *mut u16) : "memory" : "volatile");
+        }
+        (swd & !cwd) as u32
+    };
+
+    if err & 0x1 != 0 {
+        // invalid operation, also covers x87 stack faults
+        return SignalInfo::FPE_FLTINV;
+    } else if err & 0x4 != 0 {
+        return SignalInfo::FPE_FLTDIV;
+    } else if err & 0x8 != 0 {
+        return SignalInfo::FPE_FLTOVF;
+    } else if err & 0x12 != 0 {
+        // underflow or denormal operand
+        return SignalInfo::FPE_FLTUND;
+    } else if err & 0x20 != 0 {
+        return SignalInfo::FPE_FLTRES;
+    }
+
+    return 0;
+}
+
 // x87 Floating-Point Exception
 #[no_mangle]
 pub extern "C" fn X87FPHandler(sf: &mut PtRegs) {
@@ -711,7 +854,9 @@ pub extern "C" fn VirtualizationHandler(ptRegs: &mut PtRegs) {
             CPULocal::Myself().SetEnterAppTimestamp(TSC.Rdtsc());
             CPULocal::SetKernelStack(currTask.GetKernelSp());
             let kernalRsp = ptRegs as *const _ as u64;
-            if !(ptRegs.rip == ptRegs.rcx && ptRegs.r11 == ptRegs.eflags) {
+            if ptRegs.cs == UCODE32 as u64
+                || !(ptRegs.rip == ptRegs.rcx && ptRegs.r11 == ptRegs.eflags)
+            {
                 IRet(kernalRsp)
             } else {
                 SyscallRet(kernalRsp)
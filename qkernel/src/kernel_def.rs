@@ -30,6 +30,7 @@ use super::qlib::common::*;
 use super::qlib::kernel::memmgr::pma::*;
 use super::qlib::kernel::task::*;
 use super::qlib::kernel::taskMgr;
+use super::qlib::kernel::tracing::*;
 use super::qlib::linux_def::*;
 use super::qlib::loader::*;
 use super::qlib::mem::list_allocator::*;
@@ -222,6 +223,7 @@ pub fn switch(from: TaskId, to: TaskId) {
     if !SHARESPACE.config.read().KernelPagetable {
         toCtx.SwitchPageTable();
     }
+    fromCtx.SaveFS();
     toCtx.SetFS();
 
     fromCtx.mm.VcpuLeave();
@@ -231,6 +233,15 @@ pub fn switch(from: TaskId, to: TaskId) {
     toCtx.Check();
     //debug!("switch {:x}->{:x}", from.data, to.data);
 
+    ChargeRuntime(&fromCtx.context);
+    toCtx
+        .context
+        .runStart
+        .store(TSC.Rdtsc() as u64, Ordering::Relaxed);
+    CPULocal::Myself()
+        .coreCookie
+        .store(toCtx.context.CoreCookie(), Ordering::Relaxed);
+
     unsafe {
         context_swap(fromCtx.GetContext(), toCtx.GetContext(), 1, 0);
     }
@@ -294,6 +305,8 @@ impl HostSpace {
 
     pub fn Call(msg: &mut Msg, _mustAsync: bool) -> u64 {
         let current = Task::Current().GetTaskId();
+        TRACER.Record(PerfType::QCall, true, current.Addr());
+        defer!(TRACER.Record(PerfType::QCall, false, current.Addr()));
 
         let qMsg = QMsg {
             taskId: current,
@@ -312,6 +325,8 @@ impl HostSpace {
 
     pub fn HCall(msg: &mut Msg, lock: bool) -> u64 {
         let taskId = Task::Current().GetTaskId();
+        TRACER.Record(PerfType::QCall, true, taskId.Addr());
+        defer!(TRACER.Record(PerfType::QCall, false, taskId.Addr()));
 
         let mut event = QMsg {
             taskId: taskId,
@@ -338,9 +353,36 @@ pub fn child_clone(userSp: u64) {
     let kernalRsp = pt as *const _ as u64;
     CPULocal::Myself().SetEnterAppTimestamp(TSC.Rdtsc());
     currTask.mm.HandleTlbShootdown();
+    if currTask.mm.IsCompat() {
+        IRet(kernalRsp)
+    }
+
     SyscallRet(kernalRsp)
 }
 
+// StartRestoredTask returns a restored thread to the application. The thread
+// is stopped at any point of the application, so all the registers are
+// restored with iret.
+pub fn StartRestoredTask() {
+    let currTask = Task::Current();
+    super::MainRun(currTask, TaskRunState::RunApp);
+
+    let pt = currTask.GetPtRegs();
+    CPULocal::SetUserStack(pt.rsp);
+    CPULocal::SetKernelStack(currTask.GetKernelSp());
+
+    currTask.AccountTaskEnter(SchedState::RunningApp);
+    currTask.RestoreFp();
+    if SHARESPACE.config.read().KernelPagetable {
+        currTask.SwitchPageTable();
+    }
+
+    let kernalRsp = pt as *const _ as u64;
+    CPULocal::Myself().SetEnterAppTimestamp(TSC.Rdtsc());
+    currTask.mm.HandleTlbShootdown();
+    IRet(kernalRsp)
+}
+
 extern "C" {
     pub fn initX86FPState(data: u64, useXsave: bool);
 }
@@ -354,20 +396,39 @@ impl HostAllocator {
         return Self {
             listHeapAddr: AtomicU64::new(0),
             initialized: AtomicBool::new(true),
+            vcpuCache: AtomicBool::new(false),
         };
     }
 
     pub fn Init(&self, heapAddr: u64) {
         self.listHeapAddr.store(heapAddr, Ordering::SeqCst)
     }
+
+    // EnableVcpuCache can only be called after the gs of vcpu#0 is set and
+    // before the other vcpus are released
+    pub fn EnableVcpuCache(&self) {
+        self.vcpuCache.store(true, Ordering::SeqCst)
+    }
 }
 
 unsafe impl GlobalAlloc for HostAllocator {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if self.vcpuCache.load(Ordering::Relaxed) {
+            return CPULocal::Myself()
+                .allocator
+                .alloc(self.Allocator(), layout);
+        }
+
         return self.Allocator().alloc(layout);
     }
 
     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if self.vcpuCache.load(Ordering::Relaxed) {
+            return CPULocal::Myself()
+                .allocator
+                .dealloc(self.Allocator(), ptr, layout);
+        }
+
         self.Allocator().dealloc(ptr, layout);
     }
 }
@@ -94,6 +94,7 @@ use self::qlib::kernel::Kernel;
 use self::qlib::kernel::SignalDef;
 use self::qlib::kernel::TSC;
 
+use self::qlib::kernel::crash::*;
 use self::qlib::kernel::vcpu::*;
 use vcpu::CPU_LOCAL;
 
@@ -128,6 +129,7 @@ use self::task::*;
 use self::threadmgr::task_sched::*;
 //use self::vcpu::*;
 use self::qlib::kernel::Scale;
+use self::qlib::kernel::strace::*;
 use self::qlib::kernel::VcpuFreqInit;
 use self::quring::*;
 //use self::heap::QAllocator;
@@ -172,8 +174,7 @@ pub fn SingletonInit() {
 
         PAGE_MGR.SetValue(SHARESPACE.GetPageMgrAddr());
         LOADER.Init(Loader::default());
-        KERNEL_STACK_ALLOCATOR.Init(AlignedAllocator::New(
-            MemoryDef::DEFAULT_STACK_SIZE as usize,
+        KERNEL_STACK_ALLOCATOR.Init(KernelStackAllocator::New(
             MemoryDef::DEFAULT_STACK_SIZE as usize,
         ));
         EXIT_CODE.Init(AtomicI32::new(0));
@@ -187,12 +188,22 @@ pub fn SingletonInit() {
             featureSet.HasFeature(Feature(X86Feature::X86FeatureXSAVEOPT as i32)),
             Ordering::Release,
         );
+        if featureSet.HasFeature(Feature(X86Feature::X86FeatureOSPKE as i32)) {
+            // the pkru is the state component 9
+            let (_, offset, _, _) = HostID(0xd, 9);
+            PKRU_OFFSET.store(offset as usize, Ordering::Release);
+            SUPPORT_PKU.store(true, Ordering::Release);
+        }
 
         UID.Init(AtomicU64::new(1));
         perflog::THREAD_COUNTS.Init(QMutex::new(perflog::ThreadPerfCounters::default()));
 
         fs::file::InitSingleton();
         fs::filesystems::InitSingleton();
+        fs::dev::host_device::InitSingleton();
+        fs::dev::loop_dev::InitSingleton();
+        fs::dev::host_block::InitSingleton();
+        fs::host::dirnotify::InitSingleton();
         interrupt::InitSingleton();
         kernel::abstract_socket_namespace::InitSingleton();
         kernel::futex::InitSingleton();
@@ -200,11 +211,13 @@ pub fn SingletonInit() {
         kernel::epoll::epoll::InitSingleton();
         kernel::timer::InitSingleton();
         loader::vdso::InitSingleton();
+        loader::binfmt::InitSingleton();
         socket::socket::InitSingleton();
         syscalls::sys_rlimit::InitSingleton();
         task::InitSingleton();
 
         qlib::InitSingleton();
+        syscalls::syscalls::InitSingleton();
     }
 }
 
@@ -278,6 +291,18 @@ pub extern "C" fn syscall_handler(
         );
     }
 
+    let trace = if STRACE.Enabled() {
+        tid = currTask.Thread().lock().id;
+        pid = currTask.Thread().ThreadGroup().ID();
+        STRACE.Match(pid, nr)
+    } else {
+        false
+    };
+
+    if trace {
+        Strace::SyscallEnter(tid, pid, nr, &[arg0, arg1, arg2, arg3, arg4, arg5]);
+    }
+
     currTask.SaveFp();
 
     let enterAppTimestamp = CPULocal::Myself().ResetEnterAppTimestamp() as i64;
@@ -326,6 +351,10 @@ pub extern "C" fn syscall_handler(
         );
     }
 
+    if trace {
+        Strace::SyscallExit(tid, pid, nr, res, startTime, TSC.Rdtsc());
+    }
+
     let kernalRsp = pt as *const _ as u64;
 
     //PerfGoto(PerfType::User);
@@ -408,12 +437,19 @@ pub fn MainRun(currTask: &mut Task, mut state: TaskRunState) {
         };
 
         if state == TaskRunState::RunSyscallRet {
+            currTask.DoStop();
+
+            // A killable stop, e.g. a vfork parent waiting for its child, is
+            // ended by SIGKILL. Handle it instead of returning to the app.
+            if currTask.Thread().Interrupted(true) {
+                state = TaskRunState::RunInterrupt;
+                continue;
+            }
+
             break;
         }
     }
 
-    currTask.DoStop();
-
     let pt = currTask.GetPtRegs();
 
     CPULocal::SetUserStack(pt.rsp);
@@ -423,6 +459,40 @@ pub fn MainRun(currTask: &mut Task, mut state: TaskRunState) {
     //PerfGofrom(PerfType::KernelHandling);
 }
 
+// InitKernelStackArea takes the kernel stack area from the heap and unmaps the
+// guard page of each stack in it. The stacks come from the heap without the
+// guard pages if it fails.
+fn InitKernelStackArea() {
+    let guardOffset = Task::StackGuardOffset();
+    if guardOffset + MemoryDef::PAGE_SIZE > MemoryDef::DEFAULT_STACK_SIZE / 2 {
+        error!("InitKernelStackArea: the Task takes too much of the kernel stack");
+        return;
+    }
+
+    let size = MemoryDef::KERNEL_STACK_AREA_SIZE;
+    let layout = alloc::alloc::Layout::from_size_align(
+        size as usize,
+        MemoryDef::HUGE_PAGE_SIZE as usize,
+    )
+    .unwrap();
+    let start = unsafe { alloc::alloc::alloc(layout) as u64 };
+    if start == 0 {
+        error!("InitKernelStackArea: fail to allocate the stack area");
+        return;
+    }
+
+    match KERNEL_PAGETABLE.MapKernelStacks(
+        start,
+        start + size,
+        MemoryDef::DEFAULT_STACK_SIZE,
+        guardOffset,
+        &*PAGE_MGR,
+    ) {
+        Ok(()) => KERNEL_STACK_ALLOCATOR.InitArea(start, start + size),
+        Err(e) => error!("InitKernelStackArea: fail to map the stack area {:?}", e),
+    }
+}
+
 fn InitGs(id: u64) {
     SetGs(&CPU_LOCAL[id as usize] as *const _ as u64);
     SwapGs();
@@ -457,9 +527,11 @@ pub extern "C" fn rust_main(
     if id == 0 {
         GLOBAL_ALLOCATOR.Init(heapStart);
         SHARESPACE.SetValue(shareSpaceAddr);
+        // before any Task is created, the canary is in each of them
+        InitStackCanary(SHARESPACE.kernelSeed.load(Ordering::Relaxed));
         SingletonInit();
 
-        //VCPU_ALLOCATOR.Initializated();
+        GLOBAL_ALLOCATOR.EnableVcpuCache();
         InitTsc();
         InitTimeKeeper(vdsoParamAddr);
 
@@ -472,6 +544,9 @@ pub extern "C" fn rust_main(
             kpt.InitVsyscall(vsyscallPages);
         }
 
+        // before the other vcpus run and any user page table is created
+        InitKernelStackArea();
+
         GlobalIOMgr().InitPollHostEpoll(SHARESPACE.HostHostEpollfd());
         SetVCPCount(vcpuCnt as usize);
         VDSO.Initialization(vdsoParamAddr);
@@ -537,6 +612,11 @@ fn StartExecProcess(fd: i32, process: Process) {
 
     let currTask = Task::Current();
     currTask.AccountTaskEnter(SchedState::RunningApp);
+    currTask.RestoreFp();
+
+    if currTask.mm.IsCompat() {
+        EnterUser32(entry, userStackAddr, kernelStackAddr);
+    }
 
     EnterUser(entry, userStackAddr, kernelStackAddr);
 }
@@ -544,6 +624,11 @@ fn StartExecProcess(fd: i32, process: Process) {
 fn StartSubContainerProcess(elfEntry: u64, userStackAddr: u64, kernelStackAddr: u64) {
     let currTask = Task::Current();
     currTask.AccountTaskEnter(SchedState::RunningApp);
+    currTask.RestoreFp();
+
+    if currTask.mm.IsCompat() {
+        EnterUser32(elfEntry, userStackAddr, kernelStackAddr);
+    }
 
     EnterUser(elfEntry, userStackAddr, kernelStackAddr);
 }
@@ -588,6 +673,11 @@ fn StartRootContainer(_para: *const u8) {
     //CreateTask(StartExecProcess, ptr::null());
     let currTask = Task::Current();
     currTask.AccountTaskEnter(SchedState::RunningApp);
+    currTask.RestoreFp();
+    if currTask.mm.IsCompat() {
+        EnterUser32(entry, userStackAddr, kernelStackAddr);
+    }
+
     EnterUser(entry, userStackAddr, kernelStackAddr);
 
     //can't reach this
@@ -606,15 +696,22 @@ fn panic(info: &PanicInfo) -> ! {
     });*/
 
     print!("get panic : {:?}", info.message());
-    if let Some(location) = info.location() {
+    let msg = if let Some(location) = info.location() {
         print!(
             "panic occurred in file '{}' at line {}",
             location.file(),
             location.line(),
         );
+        format!(
+            "{:?} in file '{}' at line {}",
+            info.message(),
+            location.file(),
+            location.line()
+        )
     } else {
         print!("panic occurred but can't get location information...");
-    }
+        format!("{:?}", info.message())
+    };
 
     /*for i in 0..CPU_LOCAL.len() {
         error!("CPU  #{} is {:#x?}", i, CPU_LOCAL[i]);
@@ -627,6 +724,9 @@ fn panic(info: &PanicInfo) -> ! {
 
     //self::Kernel::HostSpace::Panic(&format!("get panic: {:?}", info));
     //self::Kernel::HostSpace::Panic("get panic ...");
+
+    // qvisor writes the crash dump and exits the sandbox
+    CrashInfo::Panic(&msg);
     loop {}
 }
 
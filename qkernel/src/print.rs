@@ -72,10 +72,12 @@ macro_rules! print {
 #[macro_export]
 macro_rules! error {
     ($($arg:tt)*) => ({
+        // the error messages are always kept in the kernel log ring for dmesg
+        let s = &format!($($arg)*);
+        $crate::qlib::kernel::kernel::kmsg::KMSG.Log($crate::qlib::kernel::kernel::kmsg::LOG_ERR, s);
         if $crate::SHARESPACE.config.read().DebugLevel >= $crate::qlib::config::DebugLevel::Error {
             //$crate::qlib::perf_tunning::PerfGoto($crate::qlib::perf_tunning::PerfType::Print);
             let prefix = $crate::print::PrintPrefix();
-            let s = &format!($($arg)*);
 
             if $crate::SHARESPACE.config.read().SyncPrint() {
                 let str = format!("[ERROR] {} {}", prefix, s);
@@ -97,6 +99,7 @@ macro_rules! info {
             //$crate::qlib::perf_tunning::PerfGoto($crate::qlib::perf_tunning::PerfType::Print);
             let prefix = $crate::print::PrintPrefix();
             let s = &format!($($arg)*);
+            $crate::qlib::kernel::kernel::kmsg::KMSG.Log($crate::qlib::kernel::kernel::kmsg::LOG_INFO, s);
 
             if $crate::SHARESPACE.config.read().SyncPrint() {
                 let str = format!("[INFO] {} {}", prefix, s);
@@ -117,6 +120,7 @@ macro_rules! debug {
             //$crate::qlib::perf_tunning::PerfGoto($crate::qlib::perf_tunning::PerfType::Print);
             let prefix = $crate::print::PrintPrefix();
             let s = &format!($($arg)*);
+            $crate::qlib::kernel::kernel::kmsg::KMSG.Log($crate::qlib::kernel::kernel::kmsg::LOG_DEBUG, s);
 
             if $crate::SHARESPACE.config.read().SyncPrint() {
                 let str = format!("[DEBUG] {} {}", prefix, s);
@@ -15,15 +15,22 @@
 pub mod sys_aio;
 pub mod sys_capability;
 pub mod sys_chmod;
+pub mod sys_compat;
 pub mod sys_epoll;
 pub mod sys_eventfd;
+pub mod sys_fhandle;
 pub mod sys_file;
 pub mod sys_futex;
 pub mod sys_getdents;
 pub mod sys_identity;
+pub mod sys_ioprio;
 pub mod sys_membarrier;
 pub mod sys_mempolicy;
 pub mod sys_mmap;
+pub mod sys_mount;
+pub mod sys_mqueue;
+pub mod sys_msgqueue;
+pub mod sys_pidfd;
 pub mod sys_pipe;
 pub mod sys_poll;
 pub mod sys_prctl;
@@ -32,12 +39,15 @@ pub mod sys_read;
 pub mod sys_rlimit;
 pub mod sys_rusage;
 pub mod sys_seccomp;
+pub mod sys_sem;
+pub mod sys_shm;
 pub mod sys_signal;
 pub mod sys_socket;
 pub mod sys_splice;
 pub mod sys_stat;
 pub mod sys_sync;
 pub mod sys_sysinfo;
+pub mod sys_syslog;
 pub mod sys_thread;
 pub mod sys_time;
 pub mod sys_timer;
@@ -23,6 +23,7 @@ use super::super::qlib::linux_def::*;
 use super::super::quring::uring_async::*;
 use super::super::syscalls::syscalls::*;
 use super::super::task::*;
+use super::super::threadmgr::task_usermem::*;
 use super::super::IOURING;
 use super::super::SHARESPACE;
 use super::sys_poll::*;
@@ -41,14 +42,13 @@ pub fn SysIoSetup(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     // Linux uses the native long as the aio ID.
     //
     // The context pointer _must_ be zero initially.
-    //let idPtr = task.GetTypeMut(idAddr)?;
-    let idIn: u64 = task.CopyInObj(idAddr)?;
+    let idIn: u64 = UserPtr::New(idAddr).Read(task)?;
     if idIn != 0 {
         return Err(Error::SysError(SysErr::EINVAL));
     }
 
     let id = task.mm.NewAIOContext(task, nrEvents as usize)?;
-    task.CopyOutObj(&id, idAddr)?;
+    UserPtr::New(idAddr).Write(task, &id)?;
     return Ok(0);
 }
 
@@ -110,7 +110,7 @@ pub fn SysIoGetevents(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
             }
         }
 
-        match task.CopyOutObj(&event, eventsAddr) {
+        match UserPtr::New(eventsAddr).Write(task, &event) {
             Err(e) => {
                 if count > 0 {
                     return Ok(count as i64);
@@ -167,7 +167,7 @@ pub fn SysIOSubmit(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     }
 
     for i in 0..nrEvents as usize {
-        let cbAddr: u64 = match task.CopyInObj(addr) {
+        let cbAddr: u64 = match UserPtr::New(addr).Read(task) {
             Err(e) => {
                 if i > 0 {
                     // Some successful.
@@ -180,7 +180,7 @@ pub fn SysIOSubmit(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
         };
 
         // Copy in this callback.
-        let cb: IOCallback = match task.CopyInObj(cbAddr) {
+        let cb: IOCallback = match UserPtr::New(cbAddr).Read(task) {
             Err(e) => {
                 if i > 0 {
                     // Some successful.
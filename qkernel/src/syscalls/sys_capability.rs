@@ -17,6 +17,7 @@ use super::super::qlib::common::*;
 use super::super::qlib::linux_def::*;
 use super::super::syscalls::syscalls::*;
 use super::super::task::*;
+use super::super::threadmgr::task_usermem::*;
 use super::super::threadmgr::thread::*;
 
 pub fn LookupCaps(task: &Task, tid: ThreadID) -> Result<(CapSet, CapSet, CapSet)> {
@@ -46,7 +47,7 @@ pub fn SysCapget(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let hdrAddr = args.arg0 as u64;
     let dataAddr = args.arg1 as u64;
 
-    let mut hdr = task.CopyInObj::<CapUserHeader>(hdrAddr)?;
+    let mut hdr = UserPtr::<CapUserHeader>::New(hdrAddr).Read(task)?;
 
     // hdr.Pid doesn't need to be valid if this capget() is a "version probe"
     // (hdr.Version is unrecognized and dataAddr is null), so we can't do the
@@ -65,8 +66,7 @@ pub fn SysCapget(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
                 Inheritable: i.0 as u32,
             };
 
-            //*task.GetTypeMut(dataAddr)? = data;
-            task.CopyOutObj(&data, dataAddr)?;
+            UserPtr::New(dataAddr).Write(task, &data)?;
 
             return Ok(0);
         }
@@ -89,14 +89,12 @@ pub fn SysCapget(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
                 },
             ];
 
-            //*task.GetTypeMut(dataAddr)? = data;
-            task.CopyOutObj(&data, dataAddr)?;
+            UserPtr::New(dataAddr).Write(task, &data)?;
             return Ok(0);
         }
         _ => {
             hdr.Version = HIGHEST_CAPABILITY_VERSION;
-            //*task.GetTypeMut(hdrAddr)? = hdr;
-            task.CopyOutObj(&hdr, hdrAddr)?;
+            UserPtr::New(hdrAddr).Write(task, &hdr)?;
 
             if dataAddr != 0 {
                 return Err(Error::SysError(SysErr::EINVAL));
@@ -112,7 +110,7 @@ pub fn SysCapet(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let hdrAddr = args.arg0 as u64;
     let dataAddr = args.arg1 as u64;
 
-    let mut hdr = task.CopyInObj::<CapUserHeader>(hdrAddr)?;
+    let mut hdr = UserPtr::<CapUserHeader>::New(hdrAddr).Read(task)?;
 
     match hdr.Version {
         LINUX_CAPABILITY_VERSION_1 => {
@@ -121,7 +119,7 @@ pub fn SysCapet(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
                 return Err(Error::SysError(SysErr::EPERM));
             }
 
-            let data = task.CopyInObj::<CapUserData>(dataAddr)?;
+            let data = UserPtr::<CapUserData>::New(dataAddr).Read(task)?;
 
             let p = CapSet(data.Permitted as u64 & ALL_CAP.0);
             let i = CapSet(data.Inheritable as u64 & ALL_CAP.0);
@@ -135,7 +133,7 @@ pub fn SysCapet(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
                 return Err(Error::SysError(SysErr::EPERM));
             }
 
-            let data: [CapUserData; 2] = task.CopyInObj(dataAddr)?;
+            let data: [CapUserData; 2] = UserPtr::New(dataAddr).Read(task)?;
             let p =
                 CapSet((data[0].Permitted as u64 | (data[1].Permitted as u64) << 32) & ALL_CAP.0);
             let i = CapSet(
@@ -148,7 +146,7 @@ pub fn SysCapet(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
         }
         _ => {
             hdr.Version = HIGHEST_CAPABILITY_VERSION;
-            match task.CopyOutObj(&hdr, hdrAddr) {
+            match UserPtr::New(hdrAddr).Write(task, &hdr) {
                 Err(_) => return Err(Error::SysError(SysErr::EINVAL)),
                 Ok(()) => (),
             };
@@ -0,0 +1,719 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// The ia32 compat syscalls. A 32 bit process enters the kernel with int 0x80,
+// the syscall number is in eax and the arguments are in ebx, ecx, edx, esi,
+// edi and ebp. Most of the syscalls have the same layout as the x86_64 ones
+// and are dispatched to the native handlers, the ones with 64 bit offsets or
+// long/pointer sized fields in the structs are translated here.
+
+use super::super::kernel::timer::*;
+use super::super::qlib::common::*;
+use super::super::qlib::linux_def::*;
+use super::super::qlib::SysCallID;
+use super::super::syscalls::syscalls::*;
+use super::super::task::*;
+use super::super::threadmgr::task_usermem::*;
+use super::super::SignalDef::*;
+use super::sys_file::*;
+use super::sys_mmap::*;
+use super::sys_read::*;
+use super::sys_signal::*;
+use super::sys_socket::*;
+use super::sys_stat::*;
+use super::sys_thread::*;
+use super::sys_time::*;
+use super::sys_tls::*;
+use super::sys_write::*;
+
+pub enum CompatCall {
+    // same layout as the x86_64 syscall
+    Native(SysCallID),
+    // needs the translation
+    Compat(SyscallFn),
+    NotSupported,
+}
+
+// struct stat64 of i386, see arch/x86/include/uapi/asm/stat.h
+#[repr(C, packed)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Stat64 {
+    pub st_dev: u64,
+    pub pad0: u32,
+    pub __st_ino: u32,
+    pub st_mode: u32,
+    pub st_nlink: u32,
+    pub st_uid: u32,
+    pub st_gid: u32,
+    pub st_rdev: u64,
+    pub pad3: u32,
+    pub st_size: i64,
+    pub st_blksize: u32,
+    pub st_blocks: u64,
+    pub st_atime: u32,
+    pub st_atime_nsec: u32,
+    pub st_mtime: u32,
+    pub st_mtime_nsec: u32,
+    pub st_ctime: u32,
+    pub st_ctime_nsec: u32,
+    pub st_ino: u64,
+}
+
+impl Stat64 {
+    pub fn New(s: &LibcStat) -> Self {
+        return Self {
+            st_dev: s.st_dev,
+            __st_ino: s.st_ino as u32,
+            st_mode: s.st_mode,
+            st_nlink: s.st_nlink as u32,
+            st_uid: s.st_uid,
+            st_gid: s.st_gid,
+            st_rdev: s.st_rdev,
+            st_size: s.st_size,
+            st_blksize: s.st_blksize as u32,
+            st_blocks: s.st_blocks as u64,
+            st_atime: s.st_atime as u32,
+            st_atime_nsec: s.st_atime_nsec as u32,
+            st_mtime: s.st_mtime as u32,
+            st_mtime_nsec: s.st_mtime_nsec as u32,
+            st_ctime: s.st_ctime as u32,
+            st_ctime_nsec: s.st_ctime_nsec as u32,
+            st_ino: s.st_ino,
+            ..Default::default()
+        };
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Timespec32 {
+    pub tv_sec: i32,
+    pub tv_nsec: i32,
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Timeval32 {
+    pub tv_sec: i32,
+    pub tv_usec: i32,
+}
+
+// struct mmap_arg_struct of the old i386 mmap
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct MmapArg32 {
+    pub addr: u32,
+    pub len: u32,
+    pub prot: u32,
+    pub flags: u32,
+    pub fd: u32,
+    pub offset: u32,
+}
+
+// CompatSysCall runs the ia32 syscall nr.
+pub fn CompatSysCall(task: &mut Task, nr: u64, args: &SyscallArguments) -> TaskRunState {
+    match Ia32SysCall(nr) {
+        CompatCall::Native(id) => return SysCall(task, id as u64, args),
+        CompatCall::Compat(func) => {
+            let res = func(task, args);
+            return SysCallResult(task, nr, res);
+        }
+        CompatCall::NotSupported => {
+            info!("ia32 syscall {} is not supported", nr);
+            return SysCallResult(task, nr, Err(Error::SysError(SysErr::ENOSYS)));
+        }
+    }
+}
+
+// Ia32SysCall maps the syscall number of arch/x86/entry/syscalls/syscall_32.tbl
+pub fn Ia32SysCall(nr: u64) -> CompatCall {
+    use self::CompatCall::*;
+
+    let call = match nr {
+        1 => Native(SysCallID::sys_exit),
+        2 => Native(SysCallID::sys_fork),
+        3 => Native(SysCallID::sys_read),
+        4 => Native(SysCallID::sys_write),
+        5 => Native(SysCallID::sys_open),
+        6 => Native(SysCallID::sys_close),
+        7 => Compat(SysWaitpid32),
+        8 => Native(SysCallID::sys_creat),
+        9 => Native(SysCallID::sys_link),
+        10 => Native(SysCallID::sys_unlink),
+        11 => Native(SysCallID::sys_execve),
+        12 => Native(SysCallID::sys_chdir),
+        13 => Compat(SysTime32),
+        14 => Native(SysCallID::sys_mknod),
+        15 => Native(SysCallID::sys_chmod),
+        19 => Compat(SysLseek32),
+        20 => Native(SysCallID::sys_getpid),
+        21 => Native(SysCallID::sys_mount),
+        27 => Native(SysCallID::sys_alarm),
+        29 => Native(SysCallID::sys_pause),
+        33 => Native(SysCallID::sys_access),
+        36 => Native(SysCallID::sys_sync),
+        37 => Native(SysCallID::sys_kill),
+        38 => Native(SysCallID::sys_rename),
+        39 => Native(SysCallID::sys_mkdir),
+        40 => Native(SysCallID::sys_rmdir),
+        41 => Native(SysCallID::sys_dup),
+        42 => Native(SysCallID::sys_pipe),
+        45 => Native(SysCallID::sys_brk),
+        52 => Native(SysCallID::sys_umount2),
+        54 => Native(SysCallID::sys_ioctl),
+        57 => Native(SysCallID::sys_setpgid),
+        60 => Native(SysCallID::sys_umask),
+        61 => Native(SysCallID::sys_chroot),
+        63 => Native(SysCallID::sys_dup2),
+        64 => Native(SysCallID::sys_getppid),
+        65 => Native(SysCallID::sys_getpgrp),
+        66 => Native(SysCallID::sys_setsid),
+        67 => Compat(SysSigaction32),
+        74 => Native(SysCallID::sys_sethostname),
+        78 => Compat(SysGettimeofday32),
+        83 => Native(SysCallID::sys_symlink),
+        85 => Native(SysCallID::sys_readlink),
+        90 => Compat(SysOldMmap32),
+        91 => Native(SysCallID::sys_munmap),
+        92 => Compat(SysTruncate32),
+        93 => Compat(SysFtruncate32),
+        94 => Native(SysCallID::sys_fchmod),
+        96 => Native(SysCallID::sys_getpriority),
+        97 => Native(SysCallID::sys_setpriority),
+        102 => Compat(SysSocketcall32),
+        114 => Native(SysCallID::sys_wait4),
+        118 => Native(SysCallID::sys_fsync),
+        119 => Compat(SysSigreturn32),
+        120 => Compat(SysClone32),
+        122 => Native(SysCallID::sys_uname),
+        125 => Native(SysCallID::sys_mprotect),
+        132 => Native(SysCallID::sys_getpgid),
+        133 => Native(SysCallID::sys_fchdir),
+        140 => Compat(SysLlseek32),
+        143 => Native(SysCallID::sys_flock),
+        144 => Native(SysCallID::sys_msync),
+        145 => Native(SysCallID::sys_readv),
+        146 => Native(SysCallID::sys_writev),
+        147 => Native(SysCallID::sys_getsid),
+        148 => Native(SysCallID::sys_fdatasync),
+        150 => Native(SysCallID::sys_mlock),
+        151 => Native(SysCallID::sys_munlock),
+        152 => Native(SysCallID::sys_mlockall),
+        153 => Native(SysCallID::sys_munlockall),
+        158 => Native(SysCallID::sys_sched_yield),
+        163 => Native(SysCallID::sys_mremap),
+        168 => Native(SysCallID::sys_poll),
+        172 => Native(SysCallID::sys_prctl),
+        173 => Compat(SysRtSigreturn32),
+        174 => Compat(SysRtSigaction32),
+        175 => Native(SysCallID::sys_rt_sigprocmask),
+        176 => Native(SysCallID::sys_rt_sigpending),
+        180 => Compat(SysPread64_32),
+        181 => Compat(SysPwrite64_32),
+        183 => Native(SysCallID::sys_getcwd),
+        186 => Compat(SysSigaltstack32),
+        190 => Native(SysCallID::sys_vfork),
+        192 => Compat(SysMmap2),
+        193 => Compat(SysTruncate64_32),
+        194 => Compat(SysFtruncate64_32),
+        195 => Compat(SysStat64),
+        196 => Compat(SysLstat64),
+        197 => Compat(SysFstat64),
+        198 => Native(SysCallID::sys_lchown),
+        199 => Native(SysCallID::sys_getuid),
+        200 => Native(SysCallID::sys_getgid),
+        201 => Native(SysCallID::sys_geteuid),
+        202 => Native(SysCallID::sys_getegid),
+        203 => Native(SysCallID::sys_setreuid),
+        204 => Native(SysCallID::sys_setregid),
+        205 => Native(SysCallID::sys_getgroups),
+        206 => Native(SysCallID::sys_setgroups),
+        207 => Native(SysCallID::sys_fchown),
+        208 => Native(SysCallID::sys_setresuid),
+        209 => Native(SysCallID::sys_getresuid),
+        210 => Native(SysCallID::sys_setresgid),
+        211 => Native(SysCallID::sys_getresgid),
+        212 => Native(SysCallID::sys_chown),
+        213 => Native(SysCallID::sys_setuid),
+        214 => Native(SysCallID::sys_setgid),
+        215 => Native(SysCallID::sys_setfsuid),
+        216 => Native(SysCallID::sys_setfsgid),
+        217 => Native(SysCallID::sys_pivot_root),
+        218 => Native(SysCallID::sys_mincore),
+        219 => Native(SysCallID::sys_madvise),
+        220 => Native(SysCallID::sys_getdents64),
+        221 => Native(SysCallID::sys_fcntl),
+        224 => Native(SysCallID::sys_gettid),
+        238 => Native(SysCallID::sys_tkill),
+        243 => Compat(SysSetThreadArea),
+        244 => Compat(SysGetThreadArea),
+        252 => Native(SysCallID::sys_exit_group),
+        254 => Native(SysCallID::sys_epoll_create),
+        255 => Native(SysCallID::sys_epoll_ctl),
+        256 => Native(SysCallID::sys_epoll_wait),
+        258 => Native(SysCallID::sys_set_tid_address),
+        265 => Compat(SysClockGetTime32),
+        270 => Native(SysCallID::sys_tgkill),
+        291 => Native(SysCallID::sys_inotify_init),
+        292 => Native(SysCallID::sys_inotify_add_watch),
+        293 => Native(SysCallID::sys_inotify_rm_watch),
+        295 => Native(SysCallID::sys_openat),
+        296 => Native(SysCallID::sys_mkdirat),
+        297 => Native(SysCallID::sys_mknodat),
+        298 => Native(SysCallID::sys_fchownat),
+        300 => Compat(SysFstatat64),
+        301 => Native(SysCallID::sys_unlinkat),
+        302 => Native(SysCallID::sys_renameat),
+        303 => Native(SysCallID::sys_linkat),
+        304 => Native(SysCallID::sys_symlinkat),
+        305 => Native(SysCallID::sys_readlinkat),
+        306 => Native(SysCallID::sys_fchmodat),
+        307 => Native(SysCallID::sys_faccessat),
+        322 => Native(SysCallID::sys_timerfd_create),
+        328 => Native(SysCallID::sys_eventfd2),
+        329 => Native(SysCallID::sys_epoll_create1),
+        330 => Native(SysCallID::sys_dup3),
+        331 => Native(SysCallID::sys_pipe2),
+        332 => Native(SysCallID::sys_inotify_init1),
+        340 => Native(SysCallID::sys_prlimit64),
+        344 => Native(SysCallID::sys_syncfs),
+        353 => Native(SysCallID::sys_renameat2),
+        355 => Native(SysCallID::sys_getrandom),
+        356 => Native(SysCallID::sys_memfd_create),
+        358 => Native(SysCallID::sys_stub_execveat),
+        359 => Native(SysCallID::sys_socket),
+        360 => Native(SysCallID::sys_socketpair),
+        361 => Native(SysCallID::sys_bind),
+        362 => Native(SysCallID::sys_connect),
+        363 => Native(SysCallID::sys_listen),
+        364 => Native(SysCallID::sys_accept4),
+        365 => Native(SysCallID::sys_getsockopt),
+        366 => Native(SysCallID::sys_setsockopt),
+        367 => Native(SysCallID::sys_getsockname),
+        368 => Native(SysCallID::sys_getpeername),
+        369 => Native(SysCallID::sys_sendto),
+        371 => Native(SysCallID::sys_recvfrom),
+        373 => Native(SysCallID::sys_shutdown),
+        375 => Native(SysCallID::sys_membarrier),
+        383 => Native(SysCallID::sys_statx),
+        // the msghdr, the futex timeout and the other structs with the
+        // pointers or the longs are not translated yet
+        _ => NotSupported,
+    };
+
+    return call;
+}
+
+// the 64 bit value passed in 2 registers
+fn Compat64(lo: u64, hi: u64) -> i64 {
+    return ((hi << 32) | (lo & 0xffff_ffff)) as i64;
+}
+
+pub fn SysWaitpid32(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let args = SyscallArguments {
+        arg0: args.arg0,
+        arg1: args.arg1,
+        arg2: args.arg2,
+        arg3: 0,
+        arg4: 0,
+        arg5: 0,
+    };
+
+    return SysWait4(task, &args);
+}
+
+pub fn SysTime32(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let addr = args.arg0 as u64;
+
+    let now = (REALTIME_CLOCK.Now().0 / 1_000_000_000) as i32;
+    if addr != 0 {
+        UserPtr::New(addr).Write(task, &now)?;
+    }
+
+    return Ok(now as i64);
+}
+
+pub fn SysLseek32(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let fd = args.arg0 as i32;
+    let offset = args.arg1 as i32 as i64;
+    let whence = args.arg2 as i32;
+
+    let res = Lseek(task, fd, offset, whence)?;
+    if res > i32::MAX as i64 {
+        return Err(Error::SysError(SysErr::EOVERFLOW));
+    }
+
+    return Ok(res);
+}
+
+// _llseek(fd, offset_high, offset_low, result, whence)
+pub fn SysLlseek32(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let fd = args.arg0 as i32;
+    let offset = Compat64(args.arg2, args.arg1);
+    let resultAddr = args.arg3 as u64;
+    let whence = args.arg4 as i32;
+
+    let res = Lseek(task, fd, offset, whence)?;
+    UserPtr::New(resultAddr).Write(task, &res)?;
+    return Ok(0);
+}
+
+pub fn SysGettimeofday32(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let tvAddr = args.arg0 as u64;
+    let tzAddr = args.arg1 as u64;
+
+    if tvAddr != 0 {
+        let now = REALTIME_CLOCK.Now().0;
+        let tv = Timeval32 {
+            tv_sec: (now / 1_000_000_000) as i32,
+            tv_usec: ((now % 1_000_000_000) / 1000) as i32,
+        };
+        UserPtr::New(tvAddr).Write(task, &tv)?;
+    }
+
+    if tzAddr != 0 {
+        let timezone: [u32; 2] = [0; 2];
+        UserPtr::New(tzAddr).Write(task, &timezone)?;
+    }
+
+    return Ok(0);
+}
+
+pub fn SysClockGetTime32(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let clockID = args.arg0 as i32;
+    let addr = args.arg1 as u64;
+
+    let clock = GetClock(task, clockID)?;
+    let ts = clock.Now().Timespec();
+    let ts = Timespec32 {
+        tv_sec: ts.tv_sec as i32,
+        tv_nsec: ts.tv_nsec as i32,
+    };
+
+    UserPtr::New(addr).Write(task, &ts)?;
+    return Ok(0);
+}
+
+pub fn SysOldMmap32(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let arg: MmapArg32 = UserPtr::New(args.arg0 as u64).Read(task)?;
+    if arg.offset as u64 & (MemoryDef::PAGE_SIZE - 1) != 0 {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    let args = SyscallArguments {
+        arg0: arg.addr as u64,
+        arg1: arg.len as u64,
+        arg2: arg.prot as u64,
+        arg3: arg.flags as u64,
+        arg4: arg.fd as u64,
+        arg5: arg.offset as u64,
+    };
+
+    return SysMmap(task, &args);
+}
+
+// mmap2 takes the offset in pages
+pub fn SysMmap2(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let args = SyscallArguments {
+        arg0: args.arg0,
+        arg1: args.arg1,
+        arg2: args.arg2,
+        arg3: args.arg3,
+        arg4: args.arg4,
+        arg5: (args.arg5 & 0xffff_ffff) * MemoryDef::PAGE_SIZE,
+    };
+
+    return SysMmap(task, &args);
+}
+
+pub fn SysTruncate32(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let args = SyscallArguments {
+        arg0: args.arg0,
+        arg1: args.arg1 as i32 as i64 as u64,
+        arg2: 0,
+        arg3: 0,
+        arg4: 0,
+        arg5: 0,
+    };
+
+    return SysTruncate(task, &args);
+}
+
+pub fn SysFtruncate32(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let args = SyscallArguments {
+        arg0: args.arg0,
+        arg1: args.arg1 as i32 as i64 as u64,
+        arg2: 0,
+        arg3: 0,
+        arg4: 0,
+        arg5: 0,
+    };
+
+    return SysFtruncate(task, &args);
+}
+
+// truncate64(path, length_low, length_high)
+pub fn SysTruncate64_32(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let args = SyscallArguments {
+        arg0: args.arg0,
+        arg1: Compat64(args.arg1, args.arg2) as u64,
+        arg2: 0,
+        arg3: 0,
+        arg4: 0,
+        arg5: 0,
+    };
+
+    return SysTruncate(task, &args);
+}
+
+pub fn SysFtruncate64_32(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let args = SyscallArguments {
+        arg0: args.arg0,
+        arg1: Compat64(args.arg1, args.arg2) as u64,
+        arg2: 0,
+        arg3: 0,
+        arg4: 0,
+        arg5: 0,
+    };
+
+    return SysFtruncate(task, &args);
+}
+
+// pread64(fd, buf, count, offset_low, offset_high)
+pub fn SysPread64_32(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let args = SyscallArguments {
+        arg0: args.arg0,
+        arg1: args.arg1,
+        arg2: args.arg2,
+        arg3: Compat64(args.arg3, args.arg4) as u64,
+        arg4: 0,
+        arg5: 0,
+    };
+
+    return SysPread64(task, &args);
+}
+
+pub fn SysPwrite64_32(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let args = SyscallArguments {
+        arg0: args.arg0,
+        arg1: args.arg1,
+        arg2: args.arg2,
+        arg3: Compat64(args.arg3, args.arg4) as u64,
+        arg4: 0,
+        arg5: 0,
+    };
+
+    return SysPwrite64(task, &args);
+}
+
+fn copyOutStat64(task: &Task, s: &LibcStat, statAddr: u64) -> Result<i64> {
+    let s = Stat64::New(s);
+    UserPtr::New(statAddr).Write(task, &s)?;
+    return Ok(0);
+}
+
+pub fn SysStat64(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let s = StatAt(task, ATType::AT_FDCWD, args.arg0 as u64, 0)?;
+    return copyOutStat64(task, &s, args.arg1 as u64);
+}
+
+pub fn SysLstat64(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let s = StatAt(
+        task,
+        ATType::AT_FDCWD,
+        args.arg0 as u64,
+        ATType::AT_SYMLINK_NOFOLLOW,
+    )?;
+    return copyOutStat64(task, &s, args.arg1 as u64);
+}
+
+pub fn SysFstat64(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let file = task.GetFile(args.arg0 as i32)?;
+    let uattr = file.UnstableAttr(task)?;
+    let s = NewLibcStat(task, &file.Dirent.Inode().StableAttr(), &uattr);
+    return copyOutStat64(task, &s, args.arg1 as u64);
+}
+
+pub fn SysFstatat64(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let s = StatAt(task, args.arg0 as i32, args.arg1 as u64, args.arg3 as i32)?;
+    return copyOutStat64(task, &s, args.arg2 as u64);
+}
+
+// clone(flags, newsp, parent_tid, tls, child_tid), the tls and the child_tid
+// are swapped against x86_64
+pub fn SysClone32(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let pid = task.Clone(args.arg0, args.arg1, args.arg2, args.arg4, args.arg3)?;
+    return Ok(pid as i64);
+}
+
+fn setSignalAct32(task: &mut Task, signum: u64, act: Option<SigAct>) -> Result<SigAct> {
+    let t = task.Thread();
+    let tg = t.ThreadGroup();
+    return tg.SetSignalAct(Signal(signum as i32), &act);
+}
+
+// rt_sigaction(signum, act, oldact, sigsetsize) with struct compat_sigaction
+pub fn SysRtSigaction32(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let signum = args.arg0;
+    let actAddr = args.arg1;
+    let oldActAddr = args.arg2;
+    let sigsetsize = args.arg3 as u32;
+
+    if sigsetsize != SIGNAL_SET_SIZE as u32 {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    let mut act = None;
+    if actAddr != 0 {
+        let act32: SigAct32 = UserPtr::New(actAddr).Read(task)?;
+        act = Some(act32.SigAct());
+    }
+
+    let oldact = setSignalAct32(task, signum, act)?;
+    if oldActAddr != 0 {
+        UserPtr::New(oldActAddr).Write(task, &SigAct32::New(&oldact))?;
+    }
+
+    return Ok(0);
+}
+
+// sigaction(signum, act, oldact) with struct old_sigaction
+pub fn SysSigaction32(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let signum = args.arg0;
+    let actAddr = args.arg1;
+    let oldActAddr = args.arg2;
+
+    let mut act = None;
+    if actAddr != 0 {
+        let act32: OldSigAct32 = UserPtr::New(actAddr).Read(task)?;
+        act = Some(act32.SigAct());
+    }
+
+    let oldact = setSignalAct32(task, signum, act)?;
+    if oldActAddr != 0 {
+        UserPtr::New(oldActAddr).Write(task, &OldSigAct32::New(&oldact))?;
+    }
+
+    return Ok(0);
+}
+
+pub fn SysSigreturn32(task: &mut Task, _args: &SyscallArguments) -> Result<i64> {
+    return task.SignalReturn32(false);
+}
+
+pub fn SysRtSigreturn32(task: &mut Task, _args: &SyscallArguments) -> Result<i64> {
+    return task.SignalReturn32(true);
+}
+
+pub fn SysSigaltstack32(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let setaddr = args.arg0;
+    let oldaddr = args.arg1;
+
+    let old = SignalStack32::New(&task.SignalStack());
+
+    if setaddr != 0 {
+        let alt: SignalStack32 = UserPtr::New(setaddr).Read(task)?;
+        let alt = alt.SignalStack();
+        let mode = alt.flags & !SignalStack::FLAG_AUTODISARM;
+        if mode != 0 && mode != SignalStack::FLAG_ON_STACK && mode != SignalStack::FLAG_DISABLE {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        if alt.IsEnable() && alt.size < SignalStack::MIN_SIZE {
+            return Err(Error::SysError(SysErr::ENOMEM));
+        }
+
+        if !task.SetSignalStack(alt) {
+            return Err(Error::SysError(SysErr::EPERM));
+        }
+    }
+
+    if oldaddr != 0 {
+        UserPtr::New(oldaddr).Write(task, &old)?;
+    }
+
+    return Ok(0);
+}
+
+// the calls of socketcall, see include/uapi/linux/net.h
+pub const SYS_SOCKET: u64 = 1;
+pub const SYS_BIND: u64 = 2;
+pub const SYS_CONNECT: u64 = 3;
+pub const SYS_LISTEN: u64 = 4;
+pub const SYS_ACCEPT: u64 = 5;
+pub const SYS_GETSOCKNAME: u64 = 6;
+pub const SYS_GETPEERNAME: u64 = 7;
+pub const SYS_SOCKETPAIR: u64 = 8;
+pub const SYS_SEND: u64 = 9;
+pub const SYS_RECV: u64 = 10;
+pub const SYS_SENDTO: u64 = 11;
+pub const SYS_RECVFROM: u64 = 12;
+pub const SYS_SHUTDOWN: u64 = 13;
+pub const SYS_SETSOCKOPT: u64 = 14;
+pub const SYS_GETSOCKOPT: u64 = 15;
+pub const SYS_SENDMSG: u64 = 16;
+pub const SYS_RECVMSG: u64 = 17;
+pub const SYS_ACCEPT4: u64 = 18;
+
+// the number of the args of each call
+const SOCKETCALL_NARGS: [usize; 19] = [0, 3, 3, 3, 2, 3, 3, 3, 4, 4, 4, 6, 6, 2, 5, 5, 3, 3, 4];
+
+// socketcall(call, args) runs the socket call with the args in an array of
+// 32 bit longs, it is how the old libc call the socket syscalls.
+pub fn SysSocketcall32(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let call = args.arg0;
+    let addr = args.arg1;
+
+    if call < SYS_SOCKET || call > SYS_ACCEPT4 {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    let nargs = SOCKETCALL_NARGS[call as usize];
+    let mut a: [u32; 6] = [0; 6];
+    for i in 0..nargs {
+        a[i] = UserPtr::New(addr + 4 * i as u64).Read(task)?;
+    }
+
+    let args = SyscallArguments {
+        arg0: a[0] as u64,
+        arg1: a[1] as u64,
+        arg2: a[2] as u64,
+        arg3: a[3] as u64,
+        arg4: a[4] as u64,
+        arg5: a[5] as u64,
+    };
+
+    match call {
+        SYS_SOCKET => return SysSocket(task, &args),
+        SYS_BIND => return SysBind(task, &args),
+        SYS_CONNECT => return SysConnect(task, &args),
+        SYS_LISTEN => return SysListen(task, &args),
+        SYS_ACCEPT => return SysAccept(task, &args),
+        SYS_GETSOCKNAME => return SysGetSockName(task, &args),
+        SYS_GETPEERNAME => return SysGetPeerName(task, &args),
+        SYS_SOCKETPAIR => return SysSocketPair(task, &args),
+        // send and recv are sendto and recvfrom without the address
+        SYS_SEND | SYS_SENDTO => return SysSendTo(task, &args),
+        SYS_RECV | SYS_RECVFROM => return SysRecvFrom(task, &args),
+        SYS_SHUTDOWN => return SysShutdown(task, &args),
+        SYS_SETSOCKOPT => return SysSetSockOpt(task, &args),
+        SYS_GETSOCKOPT => return SysGetSockOpt(task, &args),
+        SYS_ACCEPT4 => return SysAccept4(task, &args),
+        _ => {
+            // the msghdr is not translated yet
+            info!("ia32 socketcall {} is not supported", call);
+            return Err(Error::SysError(SysErr::ENOSYS));
+        }
+    }
+}
@@ -26,6 +26,7 @@ use super::super::qlib::linux::time::*;
 use super::super::qlib::linux_def::*;
 use super::super::syscalls::syscalls::*;
 use super::super::task::*;
+use super::super::threadmgr::task_usermem::*;
 use super::super::SignalDef::*;
 
 // CreateEpoll implements the epoll_create(2) linux syscall.
@@ -251,7 +252,7 @@ pub fn SysEpollCtl(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let mut data: [i32; 2] = [0, 0];
 
     if op != LibcConst::EPOLL_CTL_DEL as i32 {
-        let e: EpollEvent = task.CopyInObj(eventAddr)?;
+        let e: EpollEvent = UserPtr::New(eventAddr).Read(task)?;
 
         if e.Events & LibcConst::EPOLLONESHOT as u32 != 0 {
             flags |= ONE_SHOT;
@@ -295,11 +296,7 @@ pub fn CopyOutEvents(task: &Task, addr: u64, e: &[Event]) -> Result<()> {
 
     //error!("epool CopyOutEvents events is {:x?}", e);
     for i in 0..e.len() {
-        /*let output : &mut Event = task.GetTypeMut(addr + (i * itemLen) as u64)?;
-        output.Events = e[i].Events;
-        output.Data[0] = e[i].Data[0];
-        output.Data[1] = e[i].Data[1];*/
-        task.CopyOutObj(&e[i], addr + (i * itemLen) as u64)?;
+        UserPtr::New(addr + (i * itemLen) as u64).Write(task, &e[i])?;
     }
 
     return Ok(());
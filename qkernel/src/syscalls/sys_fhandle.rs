@@ -0,0 +1,186 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::super::fs::host::hostinodeop::*;
+use super::super::fs::host::*;
+use super::super::qlib::auth::*;
+use super::super::qlib::common::*;
+use super::super::qlib::linux::fhandle::*;
+use super::super::qlib::linux_def::*;
+use super::super::syscalls::syscalls::*;
+use super::super::task::*;
+use super::super::threadmgr::task_usermem::*;
+use super::sys_file::*;
+
+// NameToHandleAt implements linux syscall name_to_handle_at(2). Only the
+// files of the host mounts have handles. A handle keeps the path of the file
+// in its mount, a file renamed after the handle is made needs a new one.
+pub fn SysNameToHandleAt(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let dirFd = args.arg0 as i32;
+    let addr = args.arg1 as u64;
+    let handleAddr = args.arg2 as u64;
+    let mountIdAddr = args.arg3 as u64;
+    let flags = args.arg4 as i32;
+
+    if flags & !(ATType::AT_SYMLINK_FOLLOW | ATType::AT_EMPTY_PATH) != 0 {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    let resolve = flags & ATType::AT_SYMLINK_FOLLOW != 0;
+    let allowEmpty = flags & ATType::AT_EMPTY_PATH != 0;
+
+    let (path, _) = copyInPath(task, addr, allowEmpty)?;
+    let d = direntAt(task, dirFd, &path, resolve)?;
+
+    let header: FileHandleHeader = UserPtr::New(handleAddr).Read(task)?;
+    if header.HandleBytes > MAX_HANDLE_SZ {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    let inode = d.Inode();
+    let iops = inode.lock().InodeOp.clone();
+    if iops.as_any().downcast_ref::<HostInodeOp>().is_none() {
+        return Err(Error::SysError(SysErr::EOPNOTSUPP));
+    }
+
+    if header.HandleBytes < HostFileHandle::HANDLE_BYTES {
+        // tell the caller the size it needs
+        let header = FileHandleHeader {
+            HandleBytes: HostFileHandle::HANDLE_BYTES,
+            HandleType: header.HandleType,
+        };
+        UserPtr::New(handleAddr).Write(task, &header)?;
+        return Err(Error::SysError(SysErr::EOVERFLOW));
+    }
+
+    let mount = match task.mountNS.FindMount(&d) {
+        None => return Err(Error::SysError(SysErr::ENOENT)),
+        Some(m) => m,
+    };
+    let (mountId, mountRoot) = {
+        let m = mount.lock();
+        (m.Id, m.root.clone())
+    };
+
+    let (path, reachable) = d.FullName(&mountRoot);
+    if !reachable {
+        return Err(Error::SysError(SysErr::ENOENT));
+    }
+
+    let msrc = inode.lock().MountSource.clone();
+    let mops = msrc.lock().MountSourceOperations.clone();
+    let nonce = {
+        let mut mops = mops.lock();
+        mops.SaveInodeMapping(&inode, &path);
+        match mops.as_any().downcast_ref::<SuperOperations>() {
+            None => return Err(Error::SysError(SysErr::EOPNOTSUPP)),
+            Some(s) => s.handleNonce,
+        }
+    };
+
+    let handle = HostFileHandle {
+        HandleBytes: HostFileHandle::HANDLE_BYTES,
+        HandleType: FILEID_QUARK_HOST,
+        MountId: mountId,
+        InodeId: inode.StableAttr().InodeId,
+        Nonce: nonce,
+    };
+    UserPtr::New(handleAddr).Write(task, &handle)?;
+    UserPtr::New(mountIdAddr).Write(task, &(mountId as i32))?;
+    return Ok(0);
+}
+
+// OpenByHandleAt implements linux syscall open_by_handle_at(2). The handle
+// must be one given by name_to_handle_at for the mount of mountFd, and the
+// file is found again by walking its path from the mount root, so a handle
+// can't reach a file out of the mount.
+pub fn SysOpenByHandleAt(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let mountFd = args.arg0 as i32;
+    let handleAddr = args.arg1 as u64;
+    let flags = args.arg2 as u32;
+
+    let t = task.Thread();
+    let root = t.Kernel().RootUserNamespace();
+    if !t.HasCapabilityIn(Capability::CAP_DAC_READ_SEARCH, &root) {
+        return Err(Error::SysError(SysErr::EPERM));
+    }
+
+    let header: FileHandleHeader = UserPtr::New(handleAddr).Read(task)?;
+    if header.HandleBytes == 0 || header.HandleBytes > MAX_HANDLE_SZ {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    if header.HandleType != FILEID_QUARK_HOST || header.HandleBytes != HostFileHandle::HANDLE_BYTES
+    {
+        return Err(Error::SysError(SysErr::ESTALE));
+    }
+
+    let handle: HostFileHandle = UserPtr::New(handleAddr).Read(task)?;
+
+    let mountDirent = if mountFd == ATType::AT_FDCWD {
+        task.Workdir()
+    } else {
+        task.GetFile(mountFd)?.Dirent.clone()
+    };
+
+    let mount = match task.mountNS.FindMount(&mountDirent) {
+        None => return Err(Error::SysError(SysErr::ESTALE)),
+        Some(m) => m,
+    };
+    let (mountId, mountRoot) = {
+        let m = mount.lock();
+        (m.Id, m.root.clone())
+    };
+
+    if mountId != handle.MountId {
+        return Err(Error::SysError(SysErr::ESTALE));
+    }
+
+    let msrc = mountRoot.Inode().lock().MountSource.clone();
+    let mops = msrc.lock().MountSourceOperations.clone();
+    let path = {
+        let mops = mops.lock();
+        match mops.as_any().downcast_ref::<SuperOperations>() {
+            None => return Err(Error::SysError(SysErr::ESTALE)),
+            Some(s) => {
+                if s.handleNonce != handle.Nonce {
+                    return Err(Error::SysError(SysErr::ESTALE));
+                }
+
+                match s.InodePath(handle.InodeId) {
+                    None => return Err(Error::SysError(SysErr::ESTALE)),
+                    Some(p) => p,
+                }
+            }
+        }
+    };
+
+    let mut remainTraversals = MAX_SYMLINK_TRAVERSALS;
+    let d =
+        match task
+            .mountNS
+            .FindDirent(task, &mountRoot, None, &path, &mut remainTraversals, false)
+        {
+            Err(_) => return Err(Error::SysError(SysErr::ESTALE)),
+            Ok(d) => d,
+        };
+
+    // the file at the path is renamed or replaced
+    if d.Inode().StableAttr().InodeId != handle.InodeId {
+        return Err(Error::SysError(SysErr::ESTALE));
+    }
+
+    let fd = openDirent(task, &d, flags, false, false)?;
+    return Ok(fd as i64);
+}
@@ -15,22 +15,24 @@
 use alloc::string::String;
 use alloc::string::ToString;
 
+use super::super::fs::dev::loop_dev::*;
 use super::super::fs::dirent::*;
 use super::super::fs::file::*;
 use super::super::fs::flags::*;
+use super::super::fs::host::hostinodeop::HostInodeOp;
 use super::super::fs::inode::*;
 use super::super::fs::lock::*;
+use super::super::fs::tmpfs::tmpfs_file::TmpfsFileInodeOp;
 use super::super::kernel::fasync::*;
 use super::super::kernel::fd_table::*;
-use super::super::kernel::pipe::reader::*;
-use super::super::kernel::pipe::reader_writer::*;
-use super::super::kernel::pipe::writer::*;
+use super::super::kernel::pipe::pipe::*;
 use super::super::kernel::time::*;
 use super::super::kernel_def::*;
 use super::super::qlib::auth::cap_set::*;
 use super::super::qlib::auth::id::*;
 use super::super::qlib::auth::*;
 use super::super::qlib::common::*;
+use super::super::qlib::limits::*;
 use super::super::qlib::linux::fcntl::*;
 use super::super::qlib::linux::time::*;
 use super::super::qlib::linux_def::*;
@@ -38,7 +40,9 @@ use super::super::qlib::path::*;
 use super::super::qlib::range::*;
 use super::super::syscalls::syscalls::*;
 use super::super::task::*;
+use super::super::threadmgr::task_usermem::*;
 use super::super::util::cstring::*;
+use super::super::Kernel::HostSpace;
 
 fn fileOpAt(
     task: &Task,
@@ -132,6 +136,33 @@ pub fn copyInPath(task: &Task, addr: u64, allowEmpty: bool) -> Result<(String, b
     return Ok((path.to_string(), dirPath));
 }
 
+// direntAt returns the dirent of a dirfd and path pair, an empty path is
+// the file of dirFd itself.
+pub fn direntAt(task: &Task, dirFd: i32, path: &str, resolve: bool) -> Result<Dirent> {
+    if path.len() == 0 {
+        if dirFd == ATType::AT_FDCWD {
+            return Ok(task.Workdir());
+        }
+
+        let file = task.GetFile(dirFd)?;
+        return Ok(file.Dirent.clone());
+    }
+
+    let mut ret = None;
+    fileOpOn(
+        task,
+        dirFd,
+        path,
+        resolve,
+        &mut |_root: &Dirent, d: &Dirent, _remainingTraversals: u32| -> Result<()> {
+            ret = Some(d.clone());
+            return Ok(());
+        },
+    )?;
+
+    return Ok(ret.unwrap());
+}
+
 pub fn SysOpenAt(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let dirFd = args.arg0 as i32;
     let addr = args.arg1 as u64;
@@ -197,66 +228,76 @@ pub fn openAt(task: &Task, dirFd: i32, addr: u64, flags: u32) -> Result<i32> {
         &path,
         resolve,
         &mut |_root: &Dirent, d: &Dirent, _remainingTraversals: u32| -> Result<()> {
-            let mut inode = d.Inode();
-            inode.CheckPermission(task, &PermMask::FromFlags(flags))?;
+            fd = openDirent(task, d, flags, resolve, dirPath)?;
+            return Ok(());
+        },
+    )?;
 
-            if inode.StableAttr().IsSymlink() && !resolve {
-                return Err(Error::SysError(SysErr::ELOOP));
-            }
+    return Ok(fd);
+}
 
-            let mut fileFlags = FileFlags::FromFlags(flags);
-            fileFlags.LargeFile = true;
+// openDirent opens the file of the resolved dirent d and returns the new fd.
+pub fn openDirent(
+    task: &Task,
+    d: &Dirent,
+    flags: u32,
+    resolve: bool,
+    dirPath: bool,
+) -> Result<i32> {
+    let mut inode = d.Inode();
+    inode.CheckPermission(task, &PermMask::FromFlags(flags))?;
 
-            if inode.StableAttr().IsDir() {
-                if fileFlags.Write {
-                    return Err(Error::SysError(SysErr::EISDIR));
-                }
-            } else {
-                if fileFlags.Directory {
-                    return Err(Error::SysError(SysErr::ENOTDIR));
-                }
+    if inode.StableAttr().IsSymlink() && !resolve {
+        return Err(Error::SysError(SysErr::ELOOP));
+    }
 
-                if dirPath {
-                    return Err(Error::SysError(SysErr::ENOTDIR));
-                }
-            }
+    let mut fileFlags = FileFlags::FromFlags(flags);
+    fileFlags.LargeFile = true;
 
-            if inode.StableAttr().IsSocket() {
-                if !fileFlags.Path {
-                    return Err(Error::SysError(SysErr::ENXIO));
-                } else if fileFlags.Read || fileFlags.Write {
-                    return Err(Error::SysError(SysErr::ENXIO));
-                }
-            }
+    if inode.StableAttr().IsDir() {
+        if fileFlags.Write {
+            return Err(Error::SysError(SysErr::EISDIR));
+        }
+    } else {
+        if fileFlags.Directory {
+            return Err(Error::SysError(SysErr::ENOTDIR));
+        }
 
-            if flags & Flags::O_TRUNC as u32 != 0 {
-                if inode.StableAttr().IsDir() {
-                    return Err(Error::SysError(SysErr::EISDIR));
-                }
+        if dirPath {
+            return Err(Error::SysError(SysErr::ENOTDIR));
+        }
+    }
 
-                inode.Truncate(task, d, 0)?;
-            }
+    if inode.StableAttr().IsSocket() {
+        if !fileFlags.Path {
+            return Err(Error::SysError(SysErr::ENXIO));
+        } else if fileFlags.Read || fileFlags.Write {
+            return Err(Error::SysError(SysErr::ENXIO));
+        }
+    }
 
-            let file = match inode.GetFile(task, &d, &fileFlags) {
-                Err(err) => return Err(ConvertIntr(err, Error::ERESTARTSYS)),
-                Ok(f) => f,
-            };
+    if flags & Flags::O_TRUNC as u32 != 0 {
+        if inode.StableAttr().IsDir() {
+            return Err(Error::SysError(SysErr::EISDIR));
+        }
 
-            let newFd = task.NewFDFrom(
-                0,
-                &file,
-                &FDFlags {
-                    CloseOnExec: flags & Flags::O_CLOEXEC as u32 != 0,
-                },
-            )?;
+        inode.Truncate(task, d, 0)?;
+    }
 
-            fd = newFd;
+    let file = match inode.GetFile(task, &d, &fileFlags) {
+        Err(err) => return Err(ConvertIntr(err, Error::ERESTARTSYS)),
+        Ok(f) => f,
+    };
 
-            return Ok(());
+    let newFd = task.NewFDFrom(
+        0,
+        &file,
+        &FDFlags {
+            CloseOnExec: flags & Flags::O_CLOEXEC as u32 != 0,
         },
     )?;
 
-    return Ok(fd);
+    return Ok(newFd);
 }
 
 // Mknod implements the linux syscall mknod(2).
@@ -582,6 +623,17 @@ pub fn SysIoctl(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let request = args.arg1 as u64;
     let val = args.arg2 as u64;
 
+    // the loop-control ioctls return the loop device number
+    let file = task.GetFile(fd)?;
+    if file.FileOp.FopsType() == FileOpsType::LoopControlFileOperations {
+        let fops = file.FileOp.clone();
+        let control = fops
+            .as_any()
+            .downcast_ref::<LoopControlFileOperations>()
+            .expect("SysIoctl: not loop-control");
+        return control.Control(task, request, val);
+    }
+
     Ioctl(task, fd, request, val)?;
     return Ok(0);
 }
@@ -605,7 +657,7 @@ pub fn Ioctl(task: &mut Task, fd: i32, request: u64, val: u64) -> Result<()> {
             return Ok(());
         }
         IoCtlCmd::FIONBIO => {
-            let set: u32 = task.CopyInObj(val)?;
+            let set: u32 = UserPtr::New(val).Read(task)?;
 
             let mut flags = file.Flags();
 
@@ -619,11 +671,12 @@ pub fn Ioctl(task: &mut Task, fd: i32, request: u64, val: u64) -> Result<()> {
             return Ok(());
         }
         IoCtlCmd::FIOASYNC => {
-            let set: u32 = task.CopyInObj(val)?;
+            let set: u32 = UserPtr::New(val).Read(task)?;
 
             let mut flags = file.Flags();
 
             if set != 0 {
+                FileAsyncFor(task, &file, fd);
                 flags.Async = true;
             } else {
                 flags.Async = false;
@@ -633,14 +686,13 @@ pub fn Ioctl(task: &mut Task, fd: i32, request: u64, val: u64) -> Result<()> {
             return Ok(());
         }
         IoCtlCmd::FIOSETOWN | IoCtlCmd::SIOCSPGRP => {
-            let set: i32 = task.CopyInObj(val)?;
-            FSetOwner(task, &file, set)?;
+            let set: i32 = UserPtr::New(val).Read(task)?;
+            FSetOwner(task, &file, fd, set)?;
             return Ok(());
         }
         IoCtlCmd::FIOGETOWN | IoCtlCmd::SIOCGPGRP => {
             let who = FGetOwn(task, &file);
-            //*task.GetTypeMut(val)? = who;
-            task.CopyOutObj(&who, val)?;
+            UserPtr::New(val).Write(task, &who)?;
             return Ok(());
         }
         _ => return file.Ioctl(task, fd, request, val),
@@ -822,6 +874,10 @@ pub fn SysDup3(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let newfd = args.arg1 as i32;
     let flags = args.arg2 as u32;
 
+    if flags & !(Flags::O_CLOEXEC as u32) != 0 {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
     if oldfd == newfd {
         return Err(Error::SysError(SysErr::EINVAL));
     }
@@ -830,16 +886,19 @@ pub fn SysDup3(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
 }
 
 pub fn Dup3(task: &mut Task, oldfd: i32, newfd: i32, flags: u32) -> Result<i64> {
-    let oldFile = task.GetFile(oldfd)?;
-
-    task.NewFDAt(
+    let replaced = task.Dup3(
+        oldfd,
         newfd,
-        &oldFile,
         &FDFlags {
             CloseOnExec: flags & Flags::O_CLOEXEC as u32 != 0,
         },
     )?;
 
+    // As in Linux, errors from closing the replaced file are not reported.
+    if let Some(file) = replaced {
+        file.Flush(task).ok();
+    }
+
     return Ok(newfd as i64);
 }
 
@@ -911,18 +970,31 @@ pub fn FGetOwn(task: &mut Task, file: &File) -> i32 {
     return owner.PID;
 }
 
+// FileAsyncFor returns the file's FileAsync, creating it if needed. fd is
+// the descriptor reported in si_fd once F_SETSIG is set. Sockets also start
+// watching for out-of-band data, which sends SIGURG to the owner.
+pub fn FileAsyncFor(task: &Task, file: &File, fd: i32) -> FileAsync {
+    let a = file.Async(task, Some(FileAsync::default())).unwrap();
+    a.SetFd(fd);
+    if file.Dirent.Inode().StableAttr().IsSocket() {
+        a.RegisterUrgent(task, file);
+    }
+
+    return a;
+}
+
 // fSetOwn sets the file's owner with the semantics of F_SETOWN in Linux.
 //
 // If who is positive, it represents a PID. If negative, it represents a PGID.
 // If the PID or PGID is invalid, the owner is silently unset.
-pub fn FSetOwner(task: &Task, file: &File, who: i32) -> Result<()> {
+pub fn FSetOwner(task: &Task, file: &File, fd: i32, who: i32) -> Result<()> {
     // F_SETOWN flips the sign of negative values, an operation that is guarded
     // against overflow.
     if who == core::i32::MIN {
         return Err(Error::SysError(SysErr::EINVAL));
     }
 
-    let a = file.Async(task, Some(FileAsync::default())).unwrap();
+    let a = FileAsyncFor(task, file, fd);
     if who == 0 {
         a.Unset(task);
         return Ok(());
@@ -957,6 +1029,10 @@ pub fn SysFcntl(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     match cmd {
         Cmd::F_DUPFD | Cmd::F_DUPFD_CLOEXEC => {
             let from = val as i32;
+            if from < 0 || from >= task.FileLimit() {
+                return Err(Error::SysError(SysErr::EINVAL));
+            }
+
             let fd = task.NewFDFrom(
                 from,
                 &file,
@@ -980,6 +1056,10 @@ pub fn SysFcntl(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
         Cmd::F_GETFL => Ok(file.Flags().ToLinux() as i64),
         Cmd::F_SETFL => {
             let flags = val as u32;
+            if flags & Flags::O_ASYNC as u32 != 0 {
+                FileAsyncFor(task, &file, fd);
+            }
+
             file.SetFlags(task, FileFlags::FromFlags(flags).SettableFileFlags());
             Ok(0)
         }
@@ -994,7 +1074,7 @@ pub fn SysFcntl(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
             }
 
             let flockAddr = val;
-            let flock: FlockStruct = task.CopyInObj(flockAddr)?;
+            let flock: FlockStruct = UserPtr::New(flockAddr).Read(task)?;
 
             let sw = match flock.l_whence {
                 0 => SeekWhence::SEEK_SET,
@@ -1075,20 +1155,36 @@ pub fn SysFcntl(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
         }
         Cmd::F_GETOWN => return Ok(FGetOwn(task, &file) as i64),
         Cmd::F_SETOWN => {
-            FSetOwner(task, &file, val as i32)?;
+            FSetOwner(task, &file, fd, val as i32)?;
+            return Ok(0);
+        }
+        Cmd::F_GETSIG => {
+            let signal = match file.Async(task, None) {
+                None => 0,
+                Some(a) => a.Signal(),
+            };
+            return Ok(signal as i64);
+        }
+        Cmd::F_SETSIG => {
+            // 0 restores the default SIGIO without siginfo details.
+            let signal = val as i32;
+            if signal < 0 || signal > Signal::SIGNAL_MAX {
+                return Err(Error::SysError(SysErr::EINVAL));
+            }
+
+            FileAsyncFor(task, &file, fd).SetSignal(signal);
             return Ok(0);
         }
         Cmd::F_GETOWN_EX => {
             let addr = val;
             let owner = FGetOwnEx(task, &file);
-            //*task.GetTypeMut(addr)? = owner;
-            task.CopyOutObj(&owner, addr)?;
+            UserPtr::New(addr).Write(task, &owner)?;
             return Ok(0);
         }
         Cmd::F_SETOWN_EX => {
             let addr = val;
-            let owner: FOwnerEx = task.CopyInObj(addr)?;
-            let a = file.Async(task, Some(FileAsync::default())).unwrap();
+            let owner: FOwnerEx = UserPtr::New(addr).Read(task)?;
+            let a = FileAsyncFor(task, &file, fd);
 
             match owner.Type {
                 F_OWNER_TID => {
@@ -1139,39 +1235,22 @@ pub fn SysFcntl(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
                 _ => return Err(Error::SysError(SysErr::EINVAL)),
             }
         }
-        Cmd::F_GET_SEALS => {
-            panic!("Fcntl: F_GET_SEALS not implement")
-        }
-        Cmd::F_ADD_SEALS => {
-            panic!("Fcntl: F_ADD_SEALS not implement")
+        Cmd::F_GET_SEALS | Cmd::F_ADD_SEALS => {
+            return FileSeals(&file, cmd, val);
         }
         Cmd::F_GETPIPE_SZ => {
-            let fops = file.FileOp.clone();
-
-            let pipe = if let Some(ops) = fops.as_any().downcast_ref::<Reader>() {
-                ops.pipe.clone()
-            } else if let Some(ops) = fops.as_any().downcast_ref::<Writer>() {
-                ops.pipe.clone()
-            } else if let Some(ops) = fops.as_any().downcast_ref::<ReaderWriter>() {
-                ops.pipe.clone()
-            } else {
-                return Err(Error::SysError(SysErr::EINVAL));
+            let pipe = match FilePipe(&file) {
+                None => return Err(Error::SysError(SysErr::EINVAL)),
+                Some(p) => p,
             };
 
             let n = pipe.PipeSize();
             return Ok(n as i64);
         }
         Cmd::F_SETPIPE_SZ => {
-            let fops = file.FileOp.clone();
-
-            let pipe = if let Some(ops) = fops.as_any().downcast_ref::<Reader>() {
-                ops.pipe.clone()
-            } else if let Some(ops) = fops.as_any().downcast_ref::<Writer>() {
-                ops.pipe.clone()
-            } else if let Some(ops) = fops.as_any().downcast_ref::<ReaderWriter>() {
-                ops.pipe.clone()
-            } else {
-                return Err(Error::SysError(SysErr::EINVAL));
+            let pipe = match FilePipe(&file) {
+                None => return Err(Error::SysError(SysErr::EINVAL)),
+                Some(p) => p,
             };
 
             let n = pipe.SetPipeSize(val as i64)?;
@@ -1181,6 +1260,37 @@ pub fn SysFcntl(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     }
 }
 
+// FileSeals implements F_GET_SEALS and F_ADD_SEALS. The seals of a memfd file
+// live in its host memfd. The other tmpfs files can't be sealed, as the linux
+// shmem files not created by memfd_create(2) which start with F_SEAL_SEAL.
+fn FileSeals(file: &File, cmd: i32, val: u64) -> Result<i64> {
+    let inode = file.Dirent.Inode();
+    let iops = inode.lock().InodeOp.clone();
+    if iops.as_any().downcast_ref::<TmpfsFileInodeOp>().is_some() {
+        if cmd == Cmd::F_GET_SEALS {
+            return Ok(SealFlags::F_SEAL_SEAL as i64);
+        }
+
+        return Err(Error::SysError(SysErr::EPERM));
+    }
+
+    let hostFd = match iops.as_any().downcast_ref::<HostInodeOp>() {
+        None => return Err(Error::SysError(SysErr::EINVAL)),
+        Some(h) => h.HostFd(),
+    };
+
+    if cmd == Cmd::F_ADD_SEALS && !file.Flags().Write {
+        return Err(Error::SysError(SysErr::EPERM));
+    }
+
+    let ret = HostSpace::Fcntl(hostFd, cmd, val);
+    if ret < 0 {
+        return Err(Error::SysError(-ret as i32));
+    }
+
+    return Ok(ret);
+}
+
 const _FADV_NORMAL: i32 = 0;
 const _FADV_RANDOM: i32 = 1;
 const _FADV_SEQUENTIAL: i32 = 2;
@@ -1583,7 +1693,7 @@ fn readlinkAt(task: &Task, dirFd: i32, addr: u64, bufAddr: u64, size: u32) -> Re
                 buffer = &buffer[..size]
             }
 
-            task.CopyOutSlice(buffer, bufAddr, buffer.len())?;
+            UserSlice::New(bufAddr, buffer.len()).Write(task, buffer)?;
             copied = buffer.len();
             Ok(())
         },
@@ -1664,6 +1774,16 @@ pub fn SysUnlinkat(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     return unlinkAt(task, dirfd, addr);
 }
 
+// checkFileSizeLimit fails when a file would grow past RLIMIT_FSIZE.
+fn checkFileSizeLimit(task: &Task, size: i64) -> Result<()> {
+    let limit = task.Thread().ThreadGroup().Limits().Get(LimitType::FileSize).Cur;
+    if size as u64 > limit {
+        return Err(Error::ErrExceedsFileSizeLimit);
+    }
+
+    return Ok(());
+}
+
 pub fn SysTruncate(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let addr = args.arg0 as u64;
     let len = args.arg1 as i64;
@@ -1701,6 +1821,7 @@ pub fn SysTruncate(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
                 },
             )?;
 
+            checkFileSizeLimit(task, len)?;
             return inode.Truncate(task, d, len);
         },
     )?;
@@ -1726,6 +1847,7 @@ pub fn SysFtruncate(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
         return Err(Error::SysError(SysErr::EINVAL));
     }
 
+    checkFileSizeLimit(task, len)?;
     let dirent = file.Dirent.clone();
     inode.Truncate(task, &dirent, len)?;
 
@@ -1885,12 +2007,26 @@ pub fn SysFchownat(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     );
 }
 
-fn utime(task: &Task, dirfd: i32, addr: u64, ts: &InterTimeSpec, resolve: bool) -> Result<i64> {
+fn utime(
+    task: &Task,
+    dirfd: i32,
+    addr: u64,
+    ts: &InterTimeSpec,
+    resolve: bool,
+    allowEmpty: bool,
+) -> Result<i64> {
     let setTimestamp = &mut |_root: &Dirent, d: &Dirent, _remainingTraversals: u32| -> Result<()> {
         let mut inode = d.Inode();
+        if inode.lock().MountSource.lock().Flags.ReadOnly {
+            return Err(Error::SysError(SysErr::EROFS));
+        }
+
+        // as in linux, a caller other than the owner can only set both times
+        // to the current time, with the write permission
         if !inode.CheckOwnership(task) {
-            if (ts.ATimeOmit || !ts.ATimeSetSystemTime) && (ts.MTimeOmit || !ts.MTimeSetSystemTime)
-            {
+            let touch =
+                !ts.ATimeOmit && ts.ATimeSetSystemTime && !ts.MTimeOmit && ts.MTimeSetSystemTime;
+            if !touch {
                 return Err(Error::SysError(SysErr::EPERM));
             }
 
@@ -1920,7 +2056,14 @@ fn utime(task: &Task, dirfd: i32, addr: u64, ts: &InterTimeSpec, resolve: bool)
         return Ok(0);
     }
 
-    let (path, _) = copyInPath(task, addr, false)?;
+    let (path, _) = copyInPath(task, addr, allowEmpty)?;
+    if path.len() == 0 {
+        let f = task.GetFile(dirfd)?;
+
+        let root = task.Root().clone();
+        setTimestamp(&root, &f.Dirent.clone(), MAX_SYMLINK_TRAVERSALS)?;
+        return Ok(0);
+    }
 
     fileOpOn(task, dirfd, &path.to_string(), resolve, setTimestamp)?;
     return Ok(0);
@@ -1933,14 +2076,14 @@ pub fn SysUtime(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let mut ts = InterTimeSpec::default();
 
     if timesAddr != 0 {
-        let times: Utime = task.CopyInObj(timesAddr)?;
+        let times: Utime = UserPtr::New(timesAddr).Read(task)?;
         ts.ATime = Time::FromSec(times.Actime);
         ts.ATimeSetSystemTime = false;
         ts.MTime = Time::FromSec(times.Modtime);
         ts.MTimeSetSystemTime = false;
     }
 
-    return utime(task, ATType::AT_FDCWD, filenameAddr, &ts, true);
+    return utime(task, ATType::AT_FDCWD, filenameAddr, &ts, true, false);
 }
 
 pub fn SysUtimes(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
@@ -1950,14 +2093,14 @@ pub fn SysUtimes(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let mut ts = InterTimeSpec::default();
 
     if timesAddr != 0 {
-        let times: [Timeval; 2] = task.CopyInObj(timesAddr)?;
+        let times: [Timeval; 2] = UserPtr::New(timesAddr).Read(task)?;
         ts.ATime = Time::FromTimeval(&times[0]);
         ts.ATimeSetSystemTime = false;
         ts.MTime = Time::FromTimeval(&times[1]);
         ts.MTimeSetSystemTime = false;
     }
 
-    return utime(task, ATType::AT_FDCWD, filenameAddr, &ts, true);
+    return utime(task, ATType::AT_FDCWD, filenameAddr, &ts, true, false);
 }
 
 // timespecIsValid checks that the timespec is valid for use in utimensat.
@@ -1973,10 +2116,14 @@ pub fn SysUtimensat(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let timesAddr = args.arg2 as u64;
     let flags = args.arg3 as i32;
 
+    if flags & !(ATType::AT_SYMLINK_NOFOLLOW | ATType::AT_EMPTY_PATH) != 0 {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
     let mut ts = InterTimeSpec::default();
 
     if timesAddr != 0 {
-        let times: [Timespec; 2] = task.CopyInObj(timesAddr)?;
+        let times: [Timespec; 2] = UserPtr::New(timesAddr).Read(task)?;
 
         if !TimespecIsValid(&times[0]) || !TimespecIsValid(&times[1]) {
             return Err(Error::SysError(SysErr::EINVAL));
@@ -2003,6 +2150,7 @@ pub fn SysUtimensat(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
         filenameAddr,
         &ts,
         flags & ATType::AT_SYMLINK_NOFOLLOW == 0,
+        flags & ATType::AT_EMPTY_PATH != 0,
     );
 }
 
@@ -2015,9 +2163,9 @@ pub fn SysFutimesat(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     const E6: i64 = 1_000_000;
 
     if timesAddr != 0 {
-        let times: [Timeval; 2] = task.CopyInObj(timesAddr)?;
+        let times: [Timeval; 2] = UserPtr::New(timesAddr).Read(task)?;
 
-        if times[0].Usec > E6 || times[0].Usec < 0 || times[1].Usec > E6 || times[1].Usec < 0 {
+        if times[0].Usec >= E6 || times[0].Usec < 0 || times[1].Usec >= E6 || times[1].Usec < 0 {
             return Err(Error::SysError(SysErr::EINVAL));
         }
 
@@ -2027,10 +2175,17 @@ pub fn SysFutimesat(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
         ts.MTimeSetSystemTime = false;
     }
 
-    return utime(task, dirfd, filenameAddr, &ts, true);
+    return utime(task, dirfd, filenameAddr, &ts, true, false);
 }
 
-fn renameAt(task: &Task, oldDirfd: i32, oldAddr: u64, newDirfd: i32, newAddr: u64) -> Result<i64> {
+fn renameAt(
+    task: &Task,
+    oldDirfd: i32,
+    oldAddr: u64,
+    newDirfd: i32,
+    newAddr: u64,
+    flags: u32,
+) -> Result<i64> {
     let (newPath, _) = copyInPath(task, newAddr, false)?;
     let (oldPath, _) = copyInPath(task, oldAddr, false)?;
 
@@ -2074,7 +2229,9 @@ fn renameAt(task: &Task, oldDirfd: i32, oldAddr: u64, newDirfd: i32, newAddr: u6
                         _ => (),
                     }
 
-                    return Dirent::Rename(task, root, oldParent, oldName, newParent, newName);
+                    return Dirent::Rename(
+                        task, root, oldParent, oldName, newParent, newName, flags,
+                    );
                 },
             );
         },
@@ -2087,7 +2244,14 @@ pub fn SysRename(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let oldAddr = args.arg0 as u64;
     let newAddr = args.arg1 as u64;
 
-    return renameAt(task, ATType::AT_FDCWD, oldAddr, ATType::AT_FDCWD, newAddr);
+    return renameAt(
+        task,
+        ATType::AT_FDCWD,
+        oldAddr,
+        ATType::AT_FDCWD,
+        newAddr,
+        0,
+    );
 }
 
 pub fn SysRenameat(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
@@ -2096,7 +2260,26 @@ pub fn SysRenameat(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let newDirfd = args.arg2 as i32;
     let newAddr = args.arg3 as u64;
 
-    return renameAt(task, oldDirfd, oldAddr, newDirfd, newAddr);
+    return renameAt(task, oldDirfd, oldAddr, newDirfd, newAddr, 0);
+}
+
+pub fn SysRenameat2(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let oldDirfd = args.arg0 as i32;
+    let oldAddr = args.arg1 as u64;
+    let newDirfd = args.arg2 as i32;
+    let newAddr = args.arg3 as u64;
+    let flags = args.arg4 as u32;
+
+    if flags & !(RenameFlags::RENAME_NOREPLACE | RenameFlags::RENAME_EXCHANGE) != 0 {
+        // RENAME_WHITEOUT is only meaningful to overlay filesystem implementations.
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    if flags & RenameFlags::RENAME_NOREPLACE != 0 && flags & RenameFlags::RENAME_EXCHANGE != 0 {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    return renameAt(task, oldDirfd, oldAddr, newDirfd, newAddr, flags);
 }
 
 // Fallocate implements linux system call fallocate(2).
@@ -2140,6 +2323,8 @@ pub fn SysFallocate(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
         return Err(Error::SysError(SysErr::EFBIG));
     }
 
+    checkFileSizeLimit(task, size)?;
+
     let dirent = file.Dirent.clone();
     inode.Allocate(task, &dirent, offset, len)?;
 
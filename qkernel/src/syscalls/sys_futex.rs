@@ -22,6 +22,7 @@ use super::super::qlib::linux_def::*;
 use super::super::syscalls::syscalls::*;
 use super::super::task::*;
 use super::super::threadmgr::task_syscall::*;
+use super::super::threadmgr::task_usermem::*;
 
 // futexWaitRestartBlock encapsulates the state required to restart futex(2)
 // via restart_syscall(2).
@@ -202,7 +203,7 @@ pub fn SysFutex(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
             // WAIT{_BITSET} wait forever if the timeout isn't passed.
             let forever = timeout == 0;
             let timespec = if !forever {
-                Some(task.CopyInObj::<Timespec>(timeout)?)
+                Some(UserPtr::<Timespec>::New(timeout).Read(task)?)
             } else {
                 None
             };
@@ -282,7 +283,7 @@ pub fn SysFutex(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
             let timespec = if forever {
                 None
             } else {
-                Some(task.CopyInObj::<Timespec>(timeout)?)
+                Some(UserPtr::<Timespec>::New(timeout).Read(task)?)
             };
 
             FutexLockPI(task, timespec, addr, private)?;
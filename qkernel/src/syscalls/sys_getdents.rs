@@ -13,6 +13,7 @@
 // limitations under the License.
 
 use alloc::vec::Vec;
+use core::slice;
 
 use super::super::fd::*;
 use super::super::fs::dentry::*;
@@ -21,6 +22,7 @@ use super::super::qlib::linux_def::*;
 use super::super::qlib::mem::io::*;
 use super::super::syscalls::syscalls::*;
 use super::super::task::*;
+use super::super::threadmgr::task_usermem::*;
 
 pub fn SysGetDents(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let fd = args.arg0 as i32;
@@ -81,7 +83,7 @@ fn getDents(
     match err {
         Ok(()) => {
             let buf = &writer.data;
-            task.CopyOutSlice(buf, addr, size as usize)?;
+            UserSlice::New(addr, size as usize).Write(task, buf)?;
             return Ok(buf.len() as i64);
         }
         Err(Error::EOF) => return Ok(0),
@@ -149,25 +151,25 @@ impl Dirent {
     }
 }
 
-fn Serialize64(task: &Task, dir: &Dirent, w: &mut IOWriter) -> Result<i32> {
-    let addr = &dir.Hdr as *const _ as u64;
+fn Serialize64(_task: &Task, dir: &Dirent, w: &mut IOWriter) -> Result<i32> {
+    // the header lives in kernel memory, it is not a user address
+    let addr = &dir.Hdr as *const _ as *const u8;
     let size = 18; //mem::size_of::<DirentHdr>();
-                   //let slice = task.GetSlice::<u8>(addr, size)?;
-    let buf = task.CopyInVec::<u8>(addr, size)?;
+    let buf = unsafe { slice::from_raw_parts(addr, size) };
 
-    let n1 = w.Write(&buf)?;
+    let n1 = w.Write(buf)?;
     let n3 = w.Write(&[dir.Hdr.Type; 1])?;
     let n2 = w.Write(&dir.Name)?;
     return Ok((n1 + n2 + n3) as i32);
 }
 
-fn Serialize(task: &Task, dir: &Dirent, w: &mut IOWriter) -> Result<i32> {
-    let addr = &dir.Hdr as *const _ as u64;
+fn Serialize(_task: &Task, dir: &Dirent, w: &mut IOWriter) -> Result<i32> {
+    // the header lives in kernel memory, it is not a user address
+    let addr = &dir.Hdr as *const _ as *const u8;
     let size = 18; //mem::size_of::<OldDirentHdr>();
-                   //let slice = task.GetSlice::<u8>(addr, size)?;
-    let buf = task.CopyInVec::<u8>(addr, size)?;
+    let buf = unsafe { slice::from_raw_parts(addr, size) };
 
-    let n1 = w.Write(&buf)?;
+    let n1 = w.Write(buf)?;
     let n2 = w.Write(&dir.Name)?;
     let n3 = w.Write(&[dir.Hdr.Type; 1])?;
     return Ok((n1 + n2 + n3) as i32);
@@ -19,6 +19,7 @@ use super::super::qlib::common::*;
 use super::super::qlib::linux_def::*;
 use super::super::syscalls::syscalls::*;
 use super::super::task::*;
+use super::super::threadmgr::task_usermem::*;
 
 const MAX_NGROUPS: i32 = 65536;
 
@@ -50,9 +51,9 @@ pub fn SysGetresuid(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let euid = c.lock().EffectiveKUID.In(&userns).OrOverflow();
     let suid = c.lock().SavedKUID.In(&userns).OrOverflow();
 
-    task.CopyOutObj(&ruid, ruidAddr)?;
-    task.CopyOutObj(&euid, euidAddr)?;
-    task.CopyOutObj(&suid, suidAddr)?;
+    UserPtr::New(ruidAddr).Write(task, &ruid)?;
+    UserPtr::New(euidAddr).Write(task, &euid)?;
+    UserPtr::New(suidAddr).Write(task, &suid)?;
     return Ok(0);
 }
 
@@ -84,9 +85,9 @@ pub fn SysGetresgid(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let egid = c.lock().EffectiveKGID.In(&userns).OrOverflow();
     let sgid = c.lock().SavedKGID.In(&userns).OrOverflow();
 
-    task.CopyOutObj(&rgid, rgidAddr)?;
-    task.CopyOutObj(&egid, egidAddr)?;
-    task.CopyOutObj(&sgid, sgidAddr)?;
+    UserPtr::New(rgidAddr).Write(task, &rgid)?;
+    UserPtr::New(egidAddr).Write(task, &egid)?;
+    UserPtr::New(sgidAddr).Write(task, &sgid)?;
     return Ok(0);
 }
 
@@ -173,7 +174,7 @@ pub fn SysGetgroups(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
         gids.push(kgid.In(&userns).OrOverflow());
     }
 
-    task.CopyOutSlice(&gids[..], addr, gids.len())?;
+    UserSlice::New(addr, gids.len()).Write(task, &gids[..])?;
 
     return Ok(kgidslen as i64);
 }
@@ -193,7 +194,7 @@ pub fn SysSetgroups(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
         return Ok(0);
     }
 
-    let gids: Vec<GID> = task.CopyInVec(addr, size as usize)?;
+    let gids: Vec<GID> = UserSlice::New(addr, size as usize).Read(task)?;
     task.Thread().SetExtraGIDs(&gids[..])?;
     return Ok(0);
 }
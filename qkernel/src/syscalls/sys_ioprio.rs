@@ -0,0 +1,155 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::vec::Vec;
+
+use super::super::qlib::auth::id::*;
+use super::super::qlib::auth::*;
+use super::super::qlib::common::*;
+use super::super::qlib::linux::ioprio::*;
+use super::super::qlib::linux_def::*;
+use super::super::syscalls::syscalls::*;
+use super::super::task::*;
+use super::super::threadmgr::thread::*;
+
+// ioprioTargets returns the tasks selected by which and who, ESRCH if there
+// are none.
+fn ioprioTargets(task: &Task, which: i32, who: i32) -> Result<Vec<Thread>> {
+    let t = task.Thread();
+    let pidns = t.PIDNamespace();
+
+    let targets: Vec<Thread> = match which {
+        IOPRIO_WHO_PROCESS => {
+            if who == 0 {
+                vec![t.clone()]
+            } else {
+                match pidns.TaskWithID(who) {
+                    None => Vec::new(),
+                    Some(ot) => vec![ot],
+                }
+            }
+        }
+        IOPRIO_WHO_PGRP => {
+            let pgid = if who == 0 {
+                match t.ThreadGroup().ProcessGroup() {
+                    None => return Err(Error::SysError(SysErr::ESRCH)),
+                    Some(pg) => pidns.IDOfProcessGroup(&pg),
+                }
+            } else {
+                who
+            };
+
+            pidns
+                .Tasks()
+                .into_iter()
+                .filter(|ot| match ot.ThreadGroup().ProcessGroup() {
+                    None => false,
+                    Some(pg) => pidns.IDOfProcessGroup(&pg) == pgid,
+                })
+                .collect()
+        }
+        IOPRIO_WHO_USER => {
+            let creds = t.Credentials();
+            let kuid = if who == 0 {
+                creds.lock().RealKUID
+            } else {
+                creds.lock().UserNamespace.MapToKUID(UID(who as u32))
+            };
+
+            if !kuid.Ok() {
+                return Err(Error::SysError(SysErr::ESRCH));
+            }
+
+            pidns
+                .Tasks()
+                .into_iter()
+                .filter(|ot| ot.Credentials().lock().RealKUID == kuid)
+                .collect()
+        }
+        _ => return Err(Error::SysError(SysErr::EINVAL)),
+    };
+
+    if targets.len() == 0 {
+        return Err(Error::SysError(SysErr::ESRCH));
+    }
+
+    return Ok(targets);
+}
+
+// IoprioSet implements the linux syscall ioprio_set(2).
+pub fn SysIoprioSet(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let which = args.arg0 as i32;
+    let who = args.arg1 as i32;
+    let ioprio = IOPrio(args.arg2 as i32);
+
+    let t = task.Thread();
+    match ioprio.Class() {
+        IOPRIO_CLASS_RT | IOPRIO_CLASS_BE => {
+            if ioprio.Class() == IOPRIO_CLASS_RT
+                && !t.HasCapability(Capability::CAP_SYS_NICE)
+                && !t.HasCapability(Capability::CAP_SYS_ADMIN)
+            {
+                return Err(Error::SysError(SysErr::EPERM));
+            }
+
+            if ioprio.Data() >= IOPRIO_NR_LEVELS {
+                return Err(Error::SysError(SysErr::EINVAL));
+            }
+        }
+        IOPRIO_CLASS_IDLE => (),
+        IOPRIO_CLASS_NONE => {
+            if ioprio.Data() != 0 {
+                return Err(Error::SysError(SysErr::EINVAL));
+            }
+        }
+        _ => return Err(Error::SysError(SysErr::EINVAL)),
+    }
+
+    let creds = t.Credentials();
+    let (uid, euid) = {
+        let c = creds.lock();
+        (c.RealKUID, c.EffectiveKUID)
+    };
+    let privileged = t.HasCapability(Capability::CAP_SYS_NICE);
+
+    for ot in ioprioTargets(task, which, who)? {
+        // The caller must own the target, or have CAP_SYS_NICE.
+        let tuid = ot.Credentials().lock().RealKUID;
+        if tuid != uid && tuid != euid && !privileged {
+            return Err(Error::SysError(SysErr::EPERM));
+        }
+
+        ot.SetIOPrio(ioprio);
+    }
+
+    return Ok(0);
+}
+
+// IoprioGet implements the linux syscall ioprio_get(2). For a process group
+// or a user it returns the highest priority of their tasks.
+pub fn SysIoprioGet(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let which = args.arg0 as i32;
+    let who = args.arg1 as i32;
+
+    let mut ret: Option<IOPrio> = None;
+    for ot in ioprioTargets(task, which, who)? {
+        let ioprio = ot.IOPrio();
+        ret = match ret {
+            None => Some(ioprio),
+            Some(r) => Some(r.Best(&ioprio)),
+        };
+    }
+
+    return Ok(ret.unwrap().0 as i64);
+}
@@ -18,6 +18,7 @@ use super::super::qlib::common::*;
 use super::super::qlib::linux_def::*;
 use super::super::syscalls::syscalls::*;
 use super::super::task::*;
+use super::super::threadmgr::task_usermem::*;
 
 // We unconditionally report a single NUMA node. This also means that our
 // "nodemask_t" is a single unsigned long (uint64).
@@ -50,8 +51,7 @@ pub fn CopyInNodemask(task: &Task, addr: u64, maxnode: u32) -> Result<u64> {
     // Copy in the whole nodemask.
     let numU64 = ((bits + 63) / 64) as usize;
 
-    //let val : &[u64] = task.GetSlice(addr, numU64)?;
-    let val: Vec<u64> = task.CopyInVec(addr, numU64)?;
+    let val: Vec<u64> = UserSlice::New(addr, numU64).Read(task)?;
     if val[0] & !ALLOW_NODE_MASK != 0 {
         return Err(Error::SysError(SysErr::EINVAL));
     }
@@ -74,8 +74,7 @@ pub fn CopyOutNodemask(task: &Task, addr: u64, maxnode: u32, val: u64) -> Result
     }
 
     // Copy out the first unsigned long in the nodemask.
-    //*task.GetTypeMut(addr)? = val;
-    task.CopyOutObj(&val, addr)?;
+    UserPtr::New(addr).Write(task, &val)?;
 
     // Zero out remaining unsigned longs in the nodemask.
     if bits > 64 {
@@ -83,8 +82,7 @@ pub fn CopyOutNodemask(task: &Task, addr: u64, maxnode: u32, val: u64) -> Result
 
         let remU64 = (bits - 65) / 64;
         for _i in 0..remU64 as usize {
-            //*task.GetTypeMut(remAddr)? = 0;
-            task.CopyOutObj(&(0 as u64), remAddr)?;
+            UserPtr::New(remAddr).Write(task, &(0 as u64))?;
             remAddr += 8;
         }
     }
@@ -148,16 +146,13 @@ pub fn SysGetMempolicy(task: &mut Task, args: &SyscallArguments) -> Result<i64>
             // get_mempolicy() will allocate a page as if the thread had
             // performed a read (load) access to that address, and return the
             // ID of the node where that page was allocated."
-            //*task.GetTypeMut(addr)? = 0 as u8;
-            task.CopyOutObj(&(0 as u8), addr)?;
+            UserPtr::New(addr).Write(task, &(0 as u8))?;
 
             policy = MPOL_DEFAULT; // maxNodes == 1
         }
 
         if mode != 0 {
-            //let mode = task.GetTypeMut(mode)?;
-            //*mode = policy;
-            task.CopyOutObj(&policy, mode)?;
+            UserPtr::New(mode).Write(task, &policy)?;
         }
 
         if nodemask != 0 {
@@ -186,9 +181,7 @@ pub fn SysGetMempolicy(task: &mut Task, args: &SyscallArguments) -> Result<i64>
     }
 
     if mode != 0 {
-        //let mode = task.GetTypeMut(mode)?;
-        //*mode = policy;
-        task.CopyOutObj(&policy, mode)?;
+        UserPtr::New(mode).Write(task, &policy)?;
     }
 
     if nodemask != 0 {
@@ -14,6 +14,7 @@
 
 use alloc::string::ToString;
 use alloc::sync::Arc;
+use core::sync::atomic::Ordering;
 
 use super::super::fs::host::hostinodeop::*;
 use super::super::memmgr::mm::*;
@@ -21,9 +22,11 @@ use super::super::memmgr::syscalls::*;
 use super::super::memmgr::*;
 use super::super::qlib::addr::*;
 use super::super::qlib::common::*;
+use super::super::qlib::kernel::SUPPORT_PKU;
 use super::super::qlib::linux_def::*;
 use super::super::syscalls::syscalls::*;
 use super::super::task::*;
+use super::super::threadmgr::task_usermem::*;
 
 pub fn SysMmap(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let addr = args.arg0 as u64;
@@ -57,6 +60,7 @@ pub fn SysMmap(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
         MaxPerms: AccessType::AnyAccess(),
         GrowsDown: flags & MmapFlags::MAP_GROWSDOWN != 0,
         Precommit: flags & MmapFlags::MAP_POPULATE != 0,
+        NoReserve: flags & MmapFlags::MAP_NORESERVE != 0,
         MLockMode: MLockMode::default(),
         Kernel: false,
         Mapping: None,
@@ -68,6 +72,8 @@ pub fn SysMmap(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
         opts.MLockMode = MLockMode::MlockEager;
     }
 
+    task.mm.CheckWX(&opts.Perms)?;
+
     if !anon {
         let file = task.GetFile(fd)?;
         let flags = file.Flags();
@@ -111,12 +117,68 @@ pub fn SysMprotect(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let accessType = AccessType(prot);
     let growDown = prot & MmapProt::PROT_GROWSDOWN != 0;
 
-    match task.mm.MProtect(addr, len, &accessType, growDown) {
+    task.mm.CheckWX(&accessType)?;
+    match task.mm.MProtect(addr, len, &accessType, growDown, -1) {
         Err(e) => return Err(e),
         _ => return Ok(0),
     }
 }
 
+pub fn SysPkeyMprotect(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let addr = args.arg0 as u64;
+    let len = args.arg1 as u64;
+    let prot = args.arg2 as u64;
+    let pkey = args.arg3 as i32;
+
+    if pkey != -1 && !task.mm.mapping.lock().PkeyAllocated(pkey) {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    let accessType = AccessType(prot);
+    let growDown = prot & MmapProt::PROT_GROWSDOWN != 0;
+
+    task.mm.CheckWX(&accessType)?;
+    task.mm.MProtect(addr, len, &accessType, growDown, pkey)?;
+    return Ok(0);
+}
+
+pub fn SysPkeyAlloc(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let flags = args.arg0 as u64;
+    let rights = args.arg1 as u64;
+
+    if flags != 0 || rights & !PkeyRights::PKEY_ACCESS_MASK != 0 {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    // without the pku all the keys but the default one are in use
+    if !SUPPORT_PKU.load(Ordering::Relaxed) {
+        return Err(Error::SysError(SysErr::ENOSPC));
+    }
+
+    let pkey = match task.mm.mapping.lock().AllocPkey() {
+        None => return Err(Error::SysError(SysErr::ENOSPC)),
+        Some(pkey) => pkey,
+    };
+
+    // the rights only apply to the calling thread, the pkru saved at the
+    // syscall entry is restored on the return to the user
+    let fpstate = &task.context.X86fpstate;
+    let shift = 2 * pkey as u32;
+    let pkru = fpstate.PKRU() & !((PkeyRights::PKEY_ACCESS_MASK as u32) << shift);
+    fpstate.SetPKRU(pkru | ((rights as u32) << shift));
+    return Ok(pkey as i64);
+}
+
+pub fn SysPkeyFree(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let pkey = args.arg0 as i32;
+
+    if !task.mm.mapping.lock().FreePkey(pkey) {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    return Ok(0);
+}
+
 pub fn SysUnmap(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let addr = args.arg0 as u64;
     let len = args.arg1 as u64;
@@ -400,6 +462,6 @@ pub fn SysMincore(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     };
 
     let output = task.mm.MinCore(task, &range);
-    task.CopyOutSlice(&output, vec, output.len())?;
+    UserSlice::New(vec, output.len()).Write(task, &output)?;
     return Ok(0);
 }
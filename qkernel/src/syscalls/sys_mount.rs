@@ -0,0 +1,377 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::string::String;
+
+use super::super::fs::dirent::*;
+use super::super::fs::filesystems::*;
+use super::super::fs::fs_context::*;
+use super::super::kernel::fd_table::*;
+use super::super::qlib::auth::cap_set::*;
+use super::super::qlib::common::*;
+use super::super::qlib::linux::mount::*;
+use super::super::qlib::linux_def::*;
+use super::super::syscalls::syscalls::*;
+use super::super::task::*;
+use super::sys_file::*;
+
+// the mount flags which only change the propagation of a mount
+const PROPAGATION_FLAGS: u64 =
+    LibcConst::MS_SHARED | LibcConst::MS_PRIVATE | LibcConst::MS_SLAVE | LibcConst::MS_UNBINDABLE;
+
+const UMOUNT_NOFOLLOW: u64 = 0x8;
+
+fn CopyInMountString(task: &Task, addr: u64) -> Result<String> {
+    if addr == 0 {
+        return Ok(String::new());
+    }
+
+    let (str, err) = task.CopyInString(addr, PATH_MAX);
+    err?;
+    return Ok(str);
+}
+
+pub fn SysMount(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let sourceAddr = args.arg0 as u64;
+    let targetAddr = args.arg1 as u64;
+    let typeAddr = args.arg2 as u64;
+    let flags = args.arg3 as u64;
+    let dataAddr = args.arg4 as u64;
+
+    if !task.Creds().HasCapability(Capability::CAP_SYS_ADMIN) {
+        return Err(Error::SysError(SysErr::EPERM));
+    }
+
+    let (target, _) = copyInPath(task, targetAddr, false)?;
+
+    // The sandbox has a single mount namespace, there is no propagation
+    // to change.
+    if flags & PROPAGATION_FLAGS != 0 {
+        return Ok(0);
+    }
+
+    if flags & (LibcConst::MS_REMOUNT | LibcConst::MS_BIND | LibcConst::MS_MOVE) != 0 {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    let fsType = CopyInMountString(task, typeAddr)?;
+    let source = CopyInMountString(task, sourceAddr)?;
+    let data = CopyInMountString(task, dataAddr)?;
+
+    let fs = match FindFilesystem(&fsType) {
+        None => return Err(Error::SysError(SysErr::ENODEV)),
+        Some(fs) => fs,
+    };
+
+    if !fs.lock().AllowUserMount() {
+        return Err(Error::SysError(SysErr::EPERM));
+    }
+
+    let mountFlags = MountSourceFlags {
+        ReadOnly: flags & LibcConst::MS_RDONLY != 0,
+        NoAtime: flags & LibcConst::MS_NOATIME != 0,
+        NoExec: flags & LibcConst::MS_NOEXEC != 0,
+        ..Default::default()
+    };
+
+    let mut mountPoint = None;
+    fileOpOn(
+        task,
+        ATType::AT_FDCWD,
+        &target,
+        true,
+        &mut |_root: &Dirent, d: &Dirent, _remainingTraversals: u32| -> Result<()> {
+            if !d.Inode().StableAttr().IsDir() {
+                return Err(Error::SysError(SysErr::ENOTDIR));
+            }
+
+            mountPoint = Some(d.clone());
+            Ok(())
+        },
+    )?;
+
+    let inode = fs.lock().Mount(task, &source, &mountFlags, &data)?;
+    if source.len() > 0 {
+        inode.lock().MountSource.lock().Source = source;
+    }
+    task.mountNS.Mount(&mountPoint.unwrap(), &inode)?;
+    return Ok(0);
+}
+
+pub fn SysUmount2(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let addr = args.arg0 as u64;
+    let flags = args.arg1 as u64;
+
+    if flags & !(LibcConst::MNT_FORCE | LibcConst::MNT_DETACH | UMOUNT_NOFOLLOW) != 0 {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    if !task.Creds().HasCapability(Capability::CAP_SYS_ADMIN) {
+        return Err(Error::SysError(SysErr::EPERM));
+    }
+
+    let (path, _) = copyInPath(task, addr, false)?;
+    let resolve = flags & UMOUNT_NOFOLLOW == 0;
+
+    let mut mountPoint = None;
+    fileOpOn(
+        task,
+        ATType::AT_FDCWD,
+        &path,
+        resolve,
+        &mut |_root: &Dirent, d: &Dirent, _remainingTraversals: u32| -> Result<()> {
+            mountPoint = Some(d.clone());
+            Ok(())
+        },
+    )?;
+
+    let detachOnly = flags & LibcConst::MNT_DETACH != 0;
+    task.mountNS.Unmount(&mountPoint.unwrap(), detachOnly)?;
+    return Ok(0);
+}
+
+// Fsopen implements linux syscall fsopen(2).
+pub fn SysFsopen(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let nameAddr = args.arg0 as u64;
+    let flags = args.arg1 as u32;
+
+    if !task.Creds().HasCapability(Capability::CAP_SYS_ADMIN) {
+        return Err(Error::SysError(SysErr::EPERM));
+    }
+
+    if flags & !FSOPEN_CLOEXEC != 0 {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    let fsType = CopyInMountString(task, nameAddr)?;
+    let fs = match FindFilesystem(&fsType) {
+        None => return Err(Error::SysError(SysErr::ENODEV)),
+        Some(fs) => fs,
+    };
+
+    if !fs.lock().AllowUserMount() {
+        return Err(Error::SysError(SysErr::EPERM));
+    }
+
+    let file = FsContextOperations::NewFsContext(task, &fs);
+    let fd = task.NewFDFrom(
+        0,
+        &file,
+        &FDFlags {
+            CloseOnExec: flags & FSOPEN_CLOEXEC != 0,
+        },
+    )?;
+
+    return Ok(fd as i64);
+}
+
+// Fsconfig implements linux syscall fsconfig(2).
+pub fn SysFsconfig(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let fd = args.arg0 as i32;
+    let cmd = args.arg1 as u32;
+    let keyAddr = args.arg2 as u64;
+    let valueAddr = args.arg3 as u64;
+    let aux = args.arg4 as i32;
+
+    let file = task.GetFile(fd)?;
+    let fops = match file.FileOp.as_any().downcast_ref::<FsContextOperations>() {
+        None => return Err(Error::SysError(SysErr::EINVAL)),
+        Some(fops) => fops,
+    };
+
+    match cmd {
+        FSCONFIG_SET_FLAG => {
+            if keyAddr == 0 || valueAddr != 0 || aux != 0 {
+                return Err(Error::SysError(SysErr::EINVAL));
+            }
+
+            let key = CopyInMountString(task, keyAddr)?;
+            fops.SetFlag(&key)?;
+        }
+        // the filesystems of the sandbox take the path of their source as
+        // a string
+        FSCONFIG_SET_STRING | FSCONFIG_SET_PATH | FSCONFIG_SET_PATH_EMPTY => {
+            if keyAddr == 0 || valueAddr == 0 {
+                return Err(Error::SysError(SysErr::EINVAL));
+            }
+
+            if cmd == FSCONFIG_SET_STRING && aux != 0 {
+                return Err(Error::SysError(SysErr::EINVAL));
+            }
+
+            let key = CopyInMountString(task, keyAddr)?;
+            let value = CopyInMountString(task, valueAddr)?;
+            fops.SetString(&key, &value)?;
+        }
+        FSCONFIG_CMD_CREATE | FSCONFIG_CMD_CREATE_EXCL => {
+            if keyAddr != 0 || valueAddr != 0 || aux != 0 {
+                return Err(Error::SysError(SysErr::EINVAL));
+            }
+
+            if !task.Creds().HasCapability(Capability::CAP_SYS_ADMIN) {
+                return Err(Error::SysError(SysErr::EPERM));
+            }
+
+            fops.Create(task)?;
+        }
+        _ => return Err(Error::SysError(SysErr::EOPNOTSUPP)),
+    }
+
+    return Ok(0);
+}
+
+// Fsmount implements linux syscall fsmount(2). The returned fd is the root
+// of a detached mount, move_mount(2) attaches it.
+pub fn SysFsmount(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let fd = args.arg0 as i32;
+    let flags = args.arg1 as u32;
+    let attrFlags = args.arg2 as u32;
+
+    if !task.Creds().HasCapability(Capability::CAP_SYS_ADMIN) {
+        return Err(Error::SysError(SysErr::EPERM));
+    }
+
+    if flags & !FSMOUNT_CLOEXEC != 0 {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    let supported = MOUNT_ATTR_RDONLY
+        | MOUNT_ATTR_NOSUID
+        | MOUNT_ATTR_NODEV
+        | MOUNT_ATTR_NOEXEC
+        | MOUNT_ATTR__ATIME
+        | MOUNT_ATTR_NODIRATIME;
+    if attrFlags & !supported != 0 {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    let atime = attrFlags & MOUNT_ATTR__ATIME;
+    if atime != MOUNT_ATTR_RELATIME && atime != MOUNT_ATTR_NOATIME && atime != MOUNT_ATTR_STRICTATIME
+    {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    let file = task.GetFile(fd)?;
+    let inode = match file.FileOp.as_any().downcast_ref::<FsContextOperations>() {
+        None => return Err(Error::SysError(SysErr::EINVAL)),
+        Some(fops) => fops.TakeRoot()?,
+    };
+
+    {
+        let msrc = inode.lock().MountSource.clone();
+        let mut msrc = msrc.lock();
+        if attrFlags & MOUNT_ATTR_RDONLY != 0 {
+            msrc.Flags.ReadOnly = true;
+        }
+        if attrFlags & MOUNT_ATTR_NOEXEC != 0 {
+            msrc.Flags.NoExec = true;
+        }
+        if atime == MOUNT_ATTR_NOATIME {
+            msrc.Flags.NoAtime = true;
+        }
+    }
+
+    let root = Dirent::NewDetachedMount(&inode);
+    let mut openFlags = (Flags::O_PATH | Flags::O_DIRECTORY) as u32;
+    if flags & FSMOUNT_CLOEXEC != 0 {
+        openFlags |= Flags::O_CLOEXEC as u32;
+    }
+
+    let fd = openDirent(task, &root, openFlags, true, false)?;
+    return Ok(fd as i64);
+}
+
+// MoveMount implements linux syscall move_mount(2). Only the detached mounts
+// of fsmount(2) and open_tree(2) are attached, a mount in the mount tree
+// isn't moved as MS_MOVE of mount(2).
+pub fn SysMoveMount(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let fromDirFd = args.arg0 as i32;
+    let fromAddr = args.arg1 as u64;
+    let toDirFd = args.arg2 as i32;
+    let toAddr = args.arg3 as u64;
+    let flags = args.arg4 as u32;
+
+    if !task.Creds().HasCapability(Capability::CAP_SYS_ADMIN) {
+        return Err(Error::SysError(SysErr::EPERM));
+    }
+
+    if flags & !MOVE_MOUNT__MASK != 0 {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    let (fromPath, _) = copyInPath(task, fromAddr, flags & MOVE_MOUNT_F_EMPTY_PATH != 0)?;
+    let (toPath, _) = copyInPath(task, toAddr, flags & MOVE_MOUNT_T_EMPTY_PATH != 0)?;
+
+    let from = direntAt(task, fromDirFd, &fromPath, flags & MOVE_MOUNT_F_SYMLINKS != 0)?;
+    let to = direntAt(task, toDirFd, &toPath, flags & MOVE_MOUNT_T_SYMLINKS != 0)?;
+
+    if !to.Inode().StableAttr().IsDir() {
+        return Err(Error::SysError(SysErr::ENOTDIR));
+    }
+
+    if !from.TakeDetachedMount() {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    match task.mountNS.Mount(&to, &from.Inode()) {
+        Err(e) => {
+            // the mount stays detached for another try
+            from.SetDetachedMount();
+            return Err(e);
+        }
+        Ok(()) => return Ok(0),
+    }
+}
+
+// OpenTree implements linux syscall open_tree(2). OPEN_TREE_CLONE clones
+// only the mount of the path, AT_RECURSIVE isn't supported.
+pub fn SysOpenTree(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let dirFd = args.arg0 as i32;
+    let addr = args.arg1 as u64;
+    let flags = args.arg2 as u32;
+
+    let supported = OPEN_TREE_CLONE
+        | OPEN_TREE_CLOEXEC
+        | ATType::AT_EMPTY_PATH as u32
+        | AT_NO_AUTOMOUNT
+        | ATType::AT_SYMLINK_NOFOLLOW as u32;
+    if flags & !supported != 0 {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    let clone = flags & OPEN_TREE_CLONE != 0;
+    if clone && !task.Creds().HasCapability(Capability::CAP_SYS_ADMIN) {
+        return Err(Error::SysError(SysErr::EPERM));
+    }
+
+    let (path, dirPath) = copyInPath(task, addr, flags & ATType::AT_EMPTY_PATH as u32 != 0)?;
+    let resolve = flags & ATType::AT_SYMLINK_NOFOLLOW as u32 == 0;
+    let mut d = direntAt(task, dirFd, &path, resolve)?;
+
+    if clone {
+        if !d.Inode().StableAttr().IsDir() {
+            return Err(Error::SysError(SysErr::ENOTDIR));
+        }
+
+        d = Dirent::NewDetachedMount(&d.Inode());
+    }
+
+    let mut openFlags = Flags::O_PATH as u32;
+    if flags & OPEN_TREE_CLOEXEC != 0 {
+        openFlags |= Flags::O_CLOEXEC as u32;
+    }
+
+    let fd = openDirent(task, &d, openFlags, resolve, dirPath)?;
+    return Ok(fd as i64);
+}
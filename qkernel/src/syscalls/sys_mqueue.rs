@@ -0,0 +1,284 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::super::fs::file::*;
+use super::super::fs::flags::*;
+use super::super::kernel::fd_table::*;
+use super::super::kernel::mqueue::*;
+use super::super::kernel::time::*;
+use super::super::qlib::common::*;
+use super::super::qlib::linux::mqueue::*;
+use super::super::qlib::linux::time::*;
+use super::super::qlib::linux_def::*;
+use super::super::syscalls::syscalls::*;
+use super::super::task::*;
+use super::super::threadmgr::task_usermem::*;
+use super::super::SignalDef::*;
+
+// MqOpen implements linux syscall mq_open(2). The name is looked up in the
+// ipc namespace of the caller.
+pub fn SysMqOpen(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let nameAddr = args.arg0 as u64;
+    let flag = args.arg1 as i32;
+    let mode = args.arg2 as u32;
+    let attrAddr = args.arg3 as u64;
+
+    let name = CopyInQueueName(task, nameAddr)?;
+
+    if flag & Flags::O_ACCMODE == Flags::O_ACCMODE {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    let create = flag & Flags::O_CREAT != 0;
+    let exclusive = flag & Flags::O_EXCL != 0;
+    let mode = FileMode((mode & !task.Umask() & 0o777) as u16);
+
+    let mut attr = None;
+    if create && attrAddr != 0 {
+        let a: MqAttr = UserPtr::New(attrAddr).Read(task)?;
+        attr = Some(a);
+    }
+
+    let flags = FileFlags {
+        NonBlocking: flag & Flags::O_NONBLOCK != 0,
+        Read: flag & Flags::O_ACCMODE != Flags::O_WRONLY,
+        Write: flag & Flags::O_ACCMODE != Flags::O_RDONLY,
+        ..Default::default()
+    };
+
+    let r = task.Thread().IPCNamespace().MqueueRegistry();
+    let q = r.FindOrCreate(task, &name, &flags, create, exclusive, mode, attr)?;
+
+    let file = NewMQueueFile(task, &name, &q, &flags);
+    let fd = task.NewFDFrom(
+        0,
+        &file,
+        &FDFlags {
+            CloseOnExec: flag & Flags::O_CLOEXEC != 0,
+        },
+    )?;
+
+    return Ok(fd as i64);
+}
+
+// MqUnlink implements linux syscall mq_unlink(2).
+pub fn SysMqUnlink(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let nameAddr = args.arg0 as u64;
+
+    let name = CopyInQueueName(task, nameAddr)?;
+    let r = task.Thread().IPCNamespace().MqueueRegistry();
+    r.Remove(task, &name)?;
+    return Ok(0);
+}
+
+// MqTimedsend implements linux syscall mq_timedsend(2).
+pub fn SysMqTimedsend(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let fd = args.arg0 as i32;
+    let msgAddr = args.arg1 as u64;
+    let size = args.arg2 as u64;
+    let priority = args.arg3 as u32;
+    let timespecAddr = args.arg4 as u64;
+
+    let deadline = CopyInDeadline(task, timespecAddr)?;
+    let (file, q) = GetMQueue(task, fd)?;
+    if !file.Flags().Write {
+        return Err(Error::SysError(SysErr::EBADF));
+    }
+
+    if priority >= MQ_PRIO_MAX {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    if size as i64 > q.Attr().MqMsgsize {
+        return Err(Error::SysError(SysErr::EMSGSIZE));
+    }
+
+    let text: Vec<u8> = UserSlice::New(msgAddr, size as usize).Read(task)?;
+    let wait = !file.Flags().NonBlocking;
+
+    loop {
+        if !q.Send(task, text.clone(), priority, wait)? {
+            return Ok(0);
+        }
+
+        let res = task.blocker.BlockWithRealTimer(true, deadline);
+        q.AbortSend(task);
+        match res {
+            Ok(()) => (),
+            Err(Error::ErrInterrupted) => {
+                return Err(Error::SysError(SysErr::ERESTARTSYS));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// MqTimedreceive implements linux syscall mq_timedreceive(2).
+pub fn SysMqTimedreceive(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let fd = args.arg0 as i32;
+    let msgAddr = args.arg1 as u64;
+    let size = args.arg2 as u64;
+    let priorityAddr = args.arg3 as u64;
+    let timespecAddr = args.arg4 as u64;
+
+    let deadline = CopyInDeadline(task, timespecAddr)?;
+    let (file, q) = GetMQueue(task, fd)?;
+    if !file.Flags().Read {
+        return Err(Error::SysError(SysErr::EBADF));
+    }
+
+    let wait = !file.Flags().NonBlocking;
+
+    let msg = loop {
+        match q.Receive(task, size, wait)? {
+            Some(msg) => break msg,
+            None => (),
+        }
+
+        let res = task.blocker.BlockWithRealTimer(true, deadline);
+        q.AbortReceive(task);
+        match res {
+            Ok(()) => (),
+            Err(Error::ErrInterrupted) => {
+                return Err(Error::SysError(SysErr::ERESTARTSYS));
+            }
+            Err(e) => return Err(e),
+        }
+    };
+
+    UserSlice::New(msgAddr, msg.Text.len()).Write(task, &msg.Text)?;
+    if priorityAddr != 0 {
+        UserPtr::New(priorityAddr).Write(task, &msg.Priority)?;
+    }
+
+    return Ok(msg.Text.len() as i64);
+}
+
+// MqNotify implements linux syscall mq_notify(2).
+pub fn SysMqNotify(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let fd = args.arg0 as i32;
+    let sevAddr = args.arg1 as u64;
+
+    let (_, q) = GetMQueue(task, fd)?;
+    if sevAddr == 0 {
+        q.Notify(task, None)?;
+        return Ok(0);
+    }
+
+    let sev: Sigevent = UserPtr::New(sevAddr).Read(task)?;
+    match sev.Notify {
+        SIGEV_NONE => (),
+        SIGEV_SIGNAL => {
+            if !Signal(sev.Signo).IsValid() {
+                return Err(Error::SysError(SysErr::EINVAL));
+            }
+        }
+        SIGEV_THREAD => {
+            // the notification through a netlink socket isn't supported
+            info!("mq_notify SIGEV_THREAD is not supported");
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+        _ => return Err(Error::SysError(SysErr::EINVAL)),
+    }
+
+    q.Notify(task, Some(sev))?;
+    return Ok(0);
+}
+
+// MqGetsetattr implements linux syscall mq_getsetattr(2).
+pub fn SysMqGetsetattr(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let fd = args.arg0 as i32;
+    let newAttrAddr = args.arg1 as u64;
+    let oldAttrAddr = args.arg2 as u64;
+
+    let (file, q) = GetMQueue(task, fd)?;
+
+    let mut newAttr = None;
+    if newAttrAddr != 0 {
+        let attr: MqAttr = UserPtr::New(newAttrAddr).Read(task)?;
+        if attr.MqFlags & !(Flags::O_NONBLOCK as i64) != 0 {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        newAttr = Some(attr);
+    }
+
+    let flags = file.Flags();
+    let mut oldAttr = q.Attr();
+    if flags.NonBlocking {
+        oldAttr.MqFlags = Flags::O_NONBLOCK as i64;
+    }
+
+    if let Some(attr) = newAttr {
+        let mut settable = flags.SettableFileFlags();
+        settable.NonBlocking = attr.MqFlags != 0;
+        file.SetFlags(task, settable);
+    }
+
+    if oldAttrAddr != 0 {
+        UserPtr::New(oldAttrAddr).Write(task, &oldAttr)?;
+    }
+
+    return Ok(0);
+}
+
+// CopyInQueueName copies in the name of a queue, the leading slash of the
+// mq_open(3) name is removed by libc
+fn CopyInQueueName(task: &Task, addr: u64) -> Result<String> {
+    let (name, err) = task.CopyInString(addr, PATH_MAX);
+    err?;
+
+    if name.len() == 0 {
+        return Err(Error::SysError(SysErr::ENOENT));
+    }
+
+    if name.len() > NAME_MAX {
+        return Err(Error::SysError(SysErr::ENAMETOOLONG));
+    }
+
+    if name.contains('/') || name == "." || name == ".." {
+        return Err(Error::SysError(SysErr::EACCES));
+    }
+
+    return Ok(name);
+}
+
+// CopyInDeadline copies in the absolute CLOCK_REALTIME timeout of
+// mq_timedsend(2) and mq_timedreceive(2)
+fn CopyInDeadline(task: &Task, addr: u64) -> Result<Option<Time>> {
+    if addr == 0 {
+        return Ok(None);
+    }
+
+    let ts: Timespec = UserPtr::New(addr).Read(task)?;
+    if !ts.IsValid() {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    return Ok(Some(Time(ts.ToDuration()?)));
+}
+
+// GetMQueue returns the file of the fd and its message queue
+fn GetMQueue(task: &Task, fd: i32) -> Result<(File, MQueue)> {
+    let file = task.GetFile(fd)?;
+    let q = match file.FileOp.as_any().downcast_ref::<MQueueOperations>() {
+        None => return Err(Error::SysError(SysErr::EBADF)),
+        Some(ops) => ops.queue.clone(),
+    };
+
+    return Ok((file, q));
+}
@@ -0,0 +1,189 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::vec::Vec;
+
+use super::super::kernel::msgqueue::*;
+use super::super::qlib::common::*;
+use super::super::qlib::linux::ipc::*;
+use super::super::qlib::linux::msgqueue::*;
+use super::super::qlib::linux_def::*;
+use super::super::syscalls::syscalls::*;
+use super::super::task::*;
+use super::super::threadmgr::task_usermem::*;
+
+// Msgget implements linux syscall msgget(2). The key is looked up in the ipc
+// namespace of the caller.
+pub fn SysMsgget(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let key = args.arg0 as i32;
+    let flag = args.arg1 as i32;
+
+    let private = key == IPC_PRIVATE;
+    let create = flag & IPC_CREAT as i32 != 0;
+    let exclusive = flag & IPC_EXCL as i32 != 0;
+    let mode = FileMode((flag & 0o777) as u16);
+
+    let r = task.Thread().IPCNamespace().MsgqueueRegistry();
+    let q = r.FindOrCreate(task, key, &mode, private, create, exclusive)?;
+    let id = q.lock().id;
+    return Ok(id as i64);
+}
+
+// Msgsnd implements linux syscall msgsnd(2).
+pub fn SysMsgsnd(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let id = args.arg0 as i32;
+    let msgAddr = args.arg1 as u64;
+    let size = args.arg2 as i64;
+    let flag = args.arg3 as i32;
+
+    if id < 0 || size < 0 || size as u64 > MSGMAX {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    // struct msgbuf is the long mtype followed by the text
+    let typ: i64 = UserPtr::New(msgAddr).Read(task)?;
+    if typ < 1 {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    let text: Vec<u8> = UserSlice::New(msgAddr + 8, size as usize).Read(task)?;
+    let msg = Message {
+        Type: typ,
+        Text: text,
+    };
+
+    let q = FindQueue(task, id)?;
+    let creds = task.Creds();
+    let pid = task.Thread().ThreadGroup().ID();
+    let wait = flag & IPC_NOWAIT as i32 == 0;
+
+    loop {
+        if !q.Send(task, &msg, &creds, pid, wait)? {
+            return Ok(0);
+        }
+
+        let (_, res) = task.blocker.BlockWithMonoTimeout(true, None);
+        q.AbortWait(task);
+        match res {
+            Ok(()) => (),
+            Err(Error::ErrInterrupted) => {
+                return Err(Error::SysError(SysErr::ERESTARTNOHAND));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// Msgrcv implements linux syscall msgrcv(2).
+pub fn SysMsgrcv(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let id = args.arg0 as i32;
+    let msgAddr = args.arg1 as u64;
+    let size = args.arg2 as i64;
+    let typ = args.arg3 as i64;
+    let flag = args.arg4 as i32;
+
+    if id < 0 || size < 0 {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    let wait = flag & IPC_NOWAIT as i32 == 0;
+    let opts = ReceiveOpts {
+        Except: flag & MSG_EXCEPT != 0,
+        NoError: flag & MSG_NOERROR != 0,
+        Copy: flag & MSG_COPY != 0,
+    };
+
+    // MSG_COPY takes the index of the message in the queue as the type
+    if opts.Copy && (wait || opts.Except) {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    let q = FindQueue(task, id)?;
+    let creds = task.Creds();
+    let pid = task.Thread().ThreadGroup().ID();
+
+    let msg = loop {
+        match q.Receive(task, typ, size as u64, &opts, &creds, pid, wait)? {
+            Some(msg) => break msg,
+            None => (),
+        }
+
+        let (_, res) = task.blocker.BlockWithMonoTimeout(true, None);
+        q.AbortWait(task);
+        match res {
+            Ok(()) => (),
+            Err(Error::ErrInterrupted) => {
+                return Err(Error::SysError(SysErr::ERESTARTNOHAND));
+            }
+            Err(e) => return Err(e),
+        }
+    };
+
+    // the text is truncated with MSG_NOERROR
+    let len = core::cmp::min(msg.Text.len(), size as usize);
+    UserPtr::New(msgAddr).Write(task, &msg.Type)?;
+    UserSlice::New(msgAddr + 8, len).Write(task, &msg.Text[..len])?;
+    return Ok(len as i64);
+}
+
+// Msgctl implements linux syscall msgctl(2).
+pub fn SysMsgctl(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let id = args.arg0 as i32;
+    let cmd = args.arg1 as i32;
+    let buf = args.arg2 as u64;
+
+    let r = task.Thread().IPCNamespace().MsgqueueRegistry();
+
+    match cmd {
+        IPC_INFO => {
+            let info = r.IPCInfo();
+            UserPtr::New(buf).Write(task, &info)?;
+            return Ok(r.HighestIndex() as i64);
+        }
+        MSG_INFO => {
+            let info = r.MsgInfo();
+            UserPtr::New(buf).Write(task, &info)?;
+            return Ok(r.HighestIndex() as i64);
+        }
+        IPC_RMID => {
+            r.Remove(id, &task.Creds())?;
+            return Ok(0);
+        }
+        IPC_STAT => {
+            let q = FindQueue(task, id)?;
+            let ds = q.IPCStat(task)?;
+            UserPtr::New(buf).Write(task, &ds)?;
+            return Ok(0);
+        }
+        IPC_SET => {
+            let ds: MsqidDS = UserPtr::New(buf).Read(task)?;
+            let q = FindQueue(task, id)?;
+            q.Set(task, &ds)?;
+            return Ok(0);
+        }
+        _ => {
+            info!("msgctl cmd {} is not supported", cmd);
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+    }
+}
+
+// FindQueue returns the message queue of the id in the ipc namespace of the task
+fn FindQueue(task: &Task, id: i32) -> Result<MsgQueue> {
+    let r = task.Thread().IPCNamespace().MsgqueueRegistry();
+    match r.FindByID(id) {
+        None => return Err(Error::SysError(SysErr::EINVAL)),
+        Some(q) => return Ok(q),
+    }
+}
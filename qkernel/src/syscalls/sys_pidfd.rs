@@ -0,0 +1,56 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::super::fs::flags::*;
+use super::super::kernel::fd_table::*;
+use super::super::kernel::pidfd::*;
+use super::super::qlib::common::*;
+use super::super::qlib::linux_def::*;
+use super::super::syscalls::syscalls::*;
+use super::super::task::*;
+
+// PidfdOpen implements the linux syscall pidfd_open(2).
+pub fn SysPidfdOpen(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let pid = args.arg0 as i32;
+    let flags = args.arg1 as i32;
+
+    if flags & !PIDFD_NONBLOCK != 0 || pid <= 0 {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    let target = match task.Thread().PIDNamespace().TaskWithID(pid) {
+        None => return Err(Error::SysError(SysErr::ESRCH)),
+        Some(t) => t,
+    };
+
+    // pid must refer to a thread group leader.
+    let tg = target.ThreadGroup();
+    if tg.Leader() != Some(target) {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    let file = PidfdOperations::NewPidfd(task, &tg);
+    file.SetFlags(
+        task,
+        SettableFileFlags {
+            NonBlocking: flags & PIDFD_NONBLOCK != 0,
+            ..Default::default()
+        },
+    );
+
+    // The close-on-exec flag is always set on a pidfd.
+    let fd = task.NewFDFrom(0, &file, &FDFlags { CloseOnExec: true })?;
+
+    return Ok(fd as i64);
+}
@@ -19,9 +19,11 @@ use super::super::qlib::common::*;
 use super::super::qlib::linux_def::*;
 use super::super::syscalls::syscalls::*;
 use super::super::task::*;
+use super::super::threadmgr::task_usermem::*;
 
 pub fn Pipe2(task: &mut Task, addr: u64, flags: i32) -> Result<i64> {
-    if flags & !(Flags::O_NONBLOCK | Flags::O_CLOEXEC) != 0 {
+    // O_DIRECT makes a packet mode pipe
+    if flags & !(Flags::O_NONBLOCK | Flags::O_CLOEXEC | Flags::O_DIRECT) != 0 {
         return Err(Error::SysError(SysErr::EINVAL));
     }
 
@@ -32,8 +34,6 @@ pub fn Pipe2(task: &mut Task, addr: u64, flags: i32) -> Result<i64> {
     w.SetFlags(task, FileFlags::FromFlags(flags as u32).SettableFileFlags());
     w.flags.lock().0.NonSeekable = true;
 
-    //let fds : &mut [i32; 2] = task.GetTypeMut(addr)?;
-
     let mut fds: [i32; 2] = [0, 0];
     let rfd = task.NewFDFrom(
         0,
@@ -53,7 +53,7 @@ pub fn Pipe2(task: &mut Task, addr: u64, flags: i32) -> Result<i64> {
 
     fds[0] = rfd;
     fds[1] = wfd;
-    task.CopyOutObj(&fds, addr)?;
+    UserPtr::New(addr).Write(task, &fds)?;
 
     info!("Pipe2 the fds is {:?}", &fds);
 
@@ -25,6 +25,7 @@ use super::super::qlib::linux_def::*;
 use super::super::syscalls::syscalls::*;
 use super::super::task::*;
 use super::super::threadmgr::task_syscall::*;
+use super::super::threadmgr::task_usermem::*;
 use super::super::SignalDef::*;
 
 // fileCap is the maximum allowable files for poll & select.
@@ -83,7 +84,7 @@ pub fn DoSelect(
     let mut e = Vec::with_capacity(byteCount);
 
     if readfds != 0 {
-        r = task.CopyInVec::<u8>(readfds, byteCount)?;
+        r = UserSlice::<u8>::New(readfds, byteCount).Read(task)?;
 
         if bitsPartial != 0 {
             r[byteCount - 1] &= !(0xff << bitsPartial)
@@ -95,7 +96,7 @@ pub fn DoSelect(
     }
 
     if writefds != 0 {
-        w = task.CopyInVec::<u8>(writefds, byteCount)?;
+        w = UserSlice::<u8>::New(writefds, byteCount).Read(task)?;
 
         if bitsPartial != 0 {
             w[byteCount - 1] &= !(0xff << bitsPartial)
@@ -107,7 +108,7 @@ pub fn DoSelect(
     }
 
     if exceptfds != 0 {
-        e = task.CopyInVec::<u8>(exceptfds, byteCount)?;
+        e = UserSlice::<u8>::New(exceptfds, byteCount).Read(task)?;
 
         if bitsPartial != 0 {
             e[byteCount - 1] &= !(0xff << bitsPartial)
@@ -210,15 +211,15 @@ pub fn DoSelect(
 
     // Copy updated vectors back.
     if readfds != 0 {
-        task.CopyOutSlice(&r, readfds, byteCount)?;
+        UserSlice::New(readfds, byteCount).Write(task, &r)?;
     }
 
     if writefds != 0 {
-        task.CopyOutSlice(&w, writefds, byteCount)?;
+        UserSlice::New(writefds, byteCount).Write(task, &w)?;
     }
 
     if exceptfds != 0 {
-        task.CopyOutSlice(&e, exceptfds, byteCount)?;
+        UserSlice::New(exceptfds, byteCount).Write(task, &e)?;
     }
 
     return Ok(bitSetCount);
@@ -378,7 +379,7 @@ pub fn SysSelect(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     // Use a negative Duration to indicate "no timeout".
     let mut timeout = -1 as Duration;
     if timeValAddr != 0 {
-        let timeval: Timeval = task.CopyInObj(timeValAddr)?;
+        let timeval: Timeval = UserPtr::New(timeValAddr).Read(task)?;
         if timeval.Sec < 0 || timeval.Usec < 0 {
             return Err(Error::SysError(SysErr::EINVAL));
         }
@@ -525,7 +526,7 @@ pub fn DoPoll(task: &Task, addr: u64, nfds: u32, timeout: Duration) -> (Duration
     }
 
     let mut pfd: Vec<PollFd> = if addr != 0 {
-        match task.CopyInVec(addr, nfds as usize) {
+        match UserSlice::New(addr, nfds as usize).Read(task) {
             Err(e) => return (timeout, Err(e)),
             Ok(pfd) => pfd,
         }
@@ -556,7 +557,7 @@ pub fn DoPoll(task: &Task, addr: u64, nfds: u32, timeout: Duration) -> (Duration
     // The poll entries are copied out regardless of whether
     // any are set or not. This aligns with the Linux behavior.
     if nfds > 0 {
-        match task.CopyOutSlice(&pfd, addr, pfd.len()) {
+        match UserSlice::New(addr, pfd.len()).Write(task, &pfd) {
             Err(e) => return (remainingTimeout, Err(e)),
             Ok(()) => (),
         }
@@ -577,10 +578,8 @@ pub fn CopyOutTimevalRemaining(
 
     let remaining = TimeoutRemain(task, startNs, timeout);
     let tvRemaining = Timeval::FromNs(remaining);
-    //let timeval : &mut Timeval = task.GetTypeMut(timeValAddr)?;
-    //*timeval = tvRemaining;
 
-    task.CopyOutObj(&tvRemaining, timeValAddr)?;
+    UserPtr::New(timeValAddr).Write(task, &tvRemaining)?;
     return Ok(());
 }
 
@@ -596,10 +595,8 @@ pub fn CopyOutTimespecRemaining(
 
     let remaining = TimeoutRemain(task, startNs, timeout);
     let tsRemaining = Timespec::FromNs(remaining);
-    //let ts : &mut Timespec = task.GetTypeMut(timespecAddr)?;
-    //*ts = tsRemaining;
 
-    task.CopyOutObj(&tsRemaining, timespecAddr)?;
+    UserPtr::New(timespecAddr).Write(task, &tsRemaining)?;
     return Ok(());
 }
 
@@ -616,7 +613,7 @@ pub fn TimeoutRemain(_task: &Task, startNs: i64, timeout: Duration) -> Duration
 pub fn CopyTimespecIntoDuration(task: &Task, timespecAddr: u64) -> Result<Duration> {
     let mut timeout = -1 as Duration;
     if timespecAddr != 0 {
-        let timespec: Timespec = task.CopyInObj(timespecAddr)?;
+        let timespec: Timespec = UserPtr::New(timespecAddr).Read(task)?;
         if !timespec.IsValid() {
             return Err(Error::SysError(SysErr::EINVAL));
         }
@@ -12,14 +12,19 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use alloc::vec::Vec;
+
 use super::super::loader::loader::*;
 use super::super::memmgr::metadata::*;
 use super::super::threadmgr::pid_namespace::*;
+use super::super::threadmgr::task_sched::*;
+use super::super::threadmgr::thread::*;
 use super::super::qlib::auth::cap_set::*;
 use super::super::qlib::common::*;
 use super::super::qlib::linux_def::*;
 use super::super::syscalls::syscalls::*;
 use super::super::task::*;
+use super::super::threadmgr::task_usermem::*;
 use super::sys_seccomp::*;
 
 // PR_* flags, from <linux/pcrtl.h> for prctl(2).
@@ -153,6 +158,19 @@ pub const PR_MPX_ENABLE_MANAGEMENT: i32 = 43;
 // Protection eXtensions (MPX) bounds tables.
 pub const PR_MPX_DISABLE_MANAGEMENT: i32 = 44;
 
+// PR_SCHED_CORE manages the core scheduling cookie of the tasks, the vcpus
+// running the tasks of a cookie are placed close to each other by qvisor.
+pub const PR_SCHED_CORE: i32 = 62;
+pub const PR_SCHED_CORE_GET: i32 = 0;
+pub const PR_SCHED_CORE_CREATE: i32 = 1;
+pub const PR_SCHED_CORE_SHARE_TO: i32 = 2;
+pub const PR_SCHED_CORE_SHARE_FROM: i32 = 3;
+
+// the scopes of PR_SCHED_CORE
+pub const PIDTYPE_PID: i32 = 0;
+pub const PIDTYPE_TGID: i32 = 1;
+pub const PIDTYPE_PGID: i32 = 2;
+
 // From <asm/prctl.h>
 // Flags are used in syscall arch_prctl(2).
 pub const ARCH_SET_GS: i32 = 0x1001;
@@ -166,6 +184,85 @@ pub const SUID_DUMP_DISABLE: i32 = 0;
 pub const SUID_DUMP_USER: i32 = 1;
 pub const SUID_DUMP_ROOT: i32 = 2;
 
+// SchedCoreTargets returns the tasks of the scope typ of the task pid.
+fn SchedCoreTargets(task: &Task, pid: i32, typ: i32) -> Result<Vec<Thread>> {
+    let pidns = task.Thread().PIDNamespace();
+    let target = if pid == 0 {
+        task.Thread()
+    } else {
+        match pidns.TaskWithID(pid) {
+            None => return Err(Error::SysError(SysErr::ESRCH)),
+            Some(t) => t,
+        }
+    };
+
+    match typ {
+        PIDTYPE_PID => return Ok(vec![target]),
+        PIDTYPE_TGID => {
+            let tg = target.ThreadGroup();
+            let tasks = tg.lock().tasks.iter().cloned().collect();
+            return Ok(tasks);
+        }
+        PIDTYPE_PGID => {
+            let pg = target.ThreadGroup().ProcessGroup();
+            let tasks = pidns
+                .Tasks()
+                .into_iter()
+                .filter(|t| t.ThreadGroup().ProcessGroup() == pg)
+                .collect();
+            return Ok(tasks);
+        }
+        _ => return Err(Error::SysError(SysErr::EINVAL)),
+    }
+}
+
+// SchedCore implements prctl(PR_SCHED_CORE).
+fn SchedCore(task: &Task, args: &SyscallArguments) -> Result<i64> {
+    let op = args.arg1 as i32;
+    let pid = args.arg2 as i32;
+    let typ = args.arg3 as i32;
+    let addr = args.arg4 as u64;
+
+    if op != PR_SCHED_CORE_GET && addr != 0 {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    match op {
+        PR_SCHED_CORE_GET => {
+            if typ != PIDTYPE_PID || addr == 0 {
+                return Err(Error::SysError(SysErr::EINVAL));
+            }
+
+            let targets = SchedCoreTargets(task, pid, typ)?;
+            let cookie = targets[0].CoreCookie();
+            UserPtr::New(addr).Write(task, &cookie)?;
+        }
+        PR_SCHED_CORE_CREATE => {
+            let cookie = NewCoreCookie();
+            for t in SchedCoreTargets(task, pid, typ)? {
+                t.SetCoreCookie(cookie);
+            }
+        }
+        PR_SCHED_CORE_SHARE_TO => {
+            let cookie = task.Thread().CoreCookie();
+            for t in SchedCoreTargets(task, pid, typ)? {
+                t.SetCoreCookie(cookie);
+            }
+        }
+        PR_SCHED_CORE_SHARE_FROM => {
+            if typ != PIDTYPE_PID {
+                return Err(Error::SysError(SysErr::EINVAL));
+            }
+
+            let targets = SchedCoreTargets(task, pid, typ)?;
+            task.Thread().SetCoreCookie(targets[0].CoreCookie());
+        }
+        _ => return Err(Error::SysError(SysErr::EINVAL)),
+    }
+
+    return Ok(0);
+}
+
 pub fn SysPrctl(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let option = args.arg0 as i32;
 
@@ -184,8 +281,7 @@ pub fn SysPrctl(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
             let addr = args.arg1 as u64;
             let sig = thread.ParentDeathSignal();
 
-            task.CopyOutObj(&sig.0, addr)?;
-            //*task.GetTypeMut(addr)? = sig.0;
+            UserPtr::New(addr).Write(task, &sig.0)?;
             return Ok(0);
         }
         PR_GET_DUMPABLE => {
@@ -255,7 +351,7 @@ pub fn SysPrctl(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
                 len += 1;
             }
 
-            task.CopyOutSlice(&buf[0..len], addr, len)?;
+            UserSlice::New(addr, len).Write(task, &buf[0..len])?;
         }
         PR_SET_MM => {
             if !thread
@@ -363,14 +459,25 @@ pub fn SysPrctl(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
 
             return Err(Error::SysError(SysErr::EINVAL));
         }
+        PR_SCHED_CORE => {
+            return SchedCore(task, args);
+        }
+        PR_SET_TIMERSLACK => {
+            // 0 restores the default slack, the slack is an unsigned long
+            // in Linux and is capped here as the timers use i64 ns
+            let slack = core::cmp::min(args.arg1 as u64, core::i64::MAX as u64) as i64;
+            thread.SetTimerSlack(slack);
+            return Ok(0);
+        }
+        PR_GET_TIMERSLACK => {
+            return Ok(thread.TimerSlack());
+        }
         PR_GET_TIMING
         | PR_SET_TIMING
         | PR_GET_TSC
         | PR_SET_TSC
         | PR_TASK_PERF_EVENTS_DISABLE
         | PR_TASK_PERF_EVENTS_ENABLE
-        | PR_GET_TIMERSLACK
-        | PR_SET_TIMERSLACK
         | PR_MCE_KILL
         | PR_MCE_KILL_GET
         | PR_GET_TID_ADDRESS
@@ -16,6 +16,7 @@ use super::super::qlib::common::*;
 use super::super::qlib::linux_def::*;
 use super::super::syscalls::syscalls::*;
 use super::super::task::Task;
+use super::super::threadmgr::task_usermem::*;
 use super::super::Kernel::HostSpace;
 
 pub fn SysGetRandom(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
@@ -39,7 +40,7 @@ pub fn SysGetRandom(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
         return Err(Error::SysError(-ret as i32));
     }
 
-    task.CopyOutSlice(&buf.buf[0..ret as usize], addr, length as usize)?;
+    UserSlice::New(addr, length as usize).Write(task, &buf.buf[0..ret as usize])?;
 
     return Ok(ret as i64);
 }
@@ -17,10 +17,12 @@ use alloc::collections::btree_set::BTreeSet;
 //use super::super::Kernel;
 use super::super::qlib::common::*;
 use super::super::qlib::limits::*;
+use super::super::qlib::linux::limits::NR_OPEN;
 use super::super::qlib::linux_def::*;
 use super::super::qlib::singleton::*;
 use super::super::syscalls::syscalls::*;
 use super::super::task::Task;
+use super::super::threadmgr::task_usermem::*;
 use super::super::threadmgr::thread::*;
 
 pub static SETABLE_LIMITS: Singleton<BTreeSet<LimitType>> = Singleton::<BTreeSet<LimitType>>::New();
@@ -35,10 +37,10 @@ pub unsafe fn InitSingleton() {
             LimitType::FileSize,
             LimitType::MemoryLocked,
             LimitType::Stack,
+            LimitType::ProcessCount,
             // These are not enforced, but we include them here to avoid returning
             // EPERM, since some apps expect them to succeed.
             LimitType::Core,
-            LimitType::ProcessCount,
         ]
         .iter()
         .cloned()
@@ -46,8 +48,15 @@ pub unsafe fn InitSingleton() {
     );
 }
 
-pub fn PrLimit64(thread: &Thread, resource: LimitType, newLimit: Option<Limit>) -> Result<Limit> {
-    let tg = thread.ThreadGroup();
+// PrLimit64 gets or sets a limit of the thread group of target on behalf of
+// thread.
+pub fn PrLimit64(
+    thread: &Thread,
+    target: &Thread,
+    resource: LimitType,
+    newLimit: Option<Limit>,
+) -> Result<Limit> {
+    let tg = target.ThreadGroup();
     let limits = tg.Limits();
 
     if newLimit.is_none() {
@@ -66,10 +75,16 @@ pub fn PrLimit64(thread: &Thread, resource: LimitType, newLimit: Option<Limit>)
     let root = kernel.RootUserNamespace();
     let privileged = thread.HasCapabilityIn(Capability::CAP_SYS_RESOURCE, &root);
 
-    let oldLim = limits.Set(resource, newLimit.unwrap(), privileged)?;
+    let newLimit = newLimit.unwrap();
+    // Even a privileged process can't raise RLIMIT_NOFILE above fs.nr_open.
+    if resource == LimitType::NumberOfFiles && newLimit.Max > NR_OPEN {
+        return Err(Error::SysError(SysErr::EPERM));
+    }
+
+    let oldLim = limits.Set(resource, newLimit, privileged)?;
 
     if resource == LimitType::CPU {
-        thread.NotifyRlimitCPUUpdated()
+        target.NotifyRlimitCPUUpdated()
     }
 
     return Ok(oldLim);
@@ -107,11 +122,11 @@ pub fn SysGetrlimit(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
         Some(r) => r,
     };
 
-    let lim = PrLimit64(&task.Thread(), resource, None)?;
+    let thread = task.Thread();
+    let lim = PrLimit64(&thread, &thread, resource, None)?;
     let rlim = RLimit64::FromLimit(&lim);
 
-    //*task.GetTypeMut(addr)? = rlim;
-    task.CopyOutObj(&rlim, addr)?;
+    UserPtr::New(addr).Write(task, &rlim)?;
     return Ok(0);
 }
 
@@ -124,9 +139,10 @@ pub fn SysSetrlimit(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
         Some(r) => r,
     };
 
-    let rlim: RLimit64 = task.CopyInObj(addr)?;
+    let rlim: RLimit64 = UserPtr::New(addr).Read(task)?;
 
-    PrLimit64(&task.Thread(), resource, Some(rlim.ToLimit()))?;
+    let thread = task.Thread();
+    PrLimit64(&thread, &thread, resource, Some(rlim.ToLimit()))?;
 
     return Ok(0);
 }
@@ -143,7 +159,7 @@ pub fn SysPrlimit64(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     };
 
     let newlim = if newRlimAddr != 0 {
-        let nrl: RLimit64 = task.CopyInObj(newRlimAddr)?;
+        let nrl: RLimit64 = UserPtr::New(newRlimAddr).Read(task)?;
         Some(nrl.ToLimit())
     } else {
         None
@@ -170,10 +186,10 @@ pub fn SysPrlimit64(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     // saved set user IDs of the target process must match the real user ID of
     // the caller and the real, effective, and saved set group IDs of the
     // target process must match the real group ID of the caller."
-    if !thread.HasCapabilityIn(Capability::CAP_SYS_RESOURCE, &pidns.UserNamespace()) {
-        let cred = thread.Credentials();
-        let tcred = ot.Credentials();
-
+    let cred = thread.Credentials();
+    let tcred = ot.Credentials();
+    let tuserns = tcred.lock().UserNamespace.clone();
+    if !thread.HasCapabilityIn(Capability::CAP_SYS_RESOURCE, &tuserns) {
         if cred != tcred {
             let credlock = cred.lock();
             let tcredlock = tcred.lock();
@@ -190,12 +206,11 @@ pub fn SysPrlimit64(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
         }
     }
 
-    let oldLim = PrLimit64(&ot, resource, newlim)?;
+    let oldLim = PrLimit64(&thread, &ot, resource, newlim)?;
 
     if oldRlimAddr != 0 {
         let rlim = RLimit64::FromLimit(&oldLim);
-        //*task.GetTypeMut(oldRlimAddr)? = rlim;
-        task.CopyOutObj(&rlim, oldRlimAddr)?;
+        UserPtr::New(oldRlimAddr).Write(task, &rlim)?;
     }
 
     return Ok(0);
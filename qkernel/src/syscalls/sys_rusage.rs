@@ -18,14 +18,18 @@ use super::super::qlib::linux::time::*;
 use super::super::qlib::linux_def::*;
 use super::super::syscalls::syscalls::*;
 use super::super::task::Task;
+use super::super::threadmgr::task_usermem::*;
+use super::super::threadmgr::thread::Thread;
 
-fn GetUsage(task: &Task, which: i32) -> Result<Rusage> {
+// GetUsage returns the rusage of the thread t, it is also used by wait4 and
+// waitid for the waited child with RUSAGE_BOTH.
+pub fn GetUsage(t: &Thread, which: i32) -> Result<Rusage> {
     let cs = match which {
-        RUSAGE_SELF => task.Thread().ThreadGroup().CPUStats(),
-        RUSAGE_CHILDREN => task.Thread().ThreadGroup().JoinedChildCPUStats(),
-        RUSAGE_THREAD => task.Thread().CPUStats(),
+        RUSAGE_SELF => t.ThreadGroup().CPUStats(),
+        RUSAGE_CHILDREN => t.ThreadGroup().JoinedChildCPUStats(),
+        RUSAGE_THREAD => t.CPUStats(),
         RUSAGE_BOTH => {
-            let tg = task.Thread().ThreadGroup();
+            let tg = t.ThreadGroup();
             let mut cs = tg.CPUStats();
             cs.Accumulate(&tg.JoinedChildCPUStats());
             cs
@@ -37,7 +41,10 @@ fn GetUsage(task: &Task, which: i32) -> Result<Rusage> {
         UTime: Timeval::FromNs(cs.UserTime),
         STime: Timeval::FromNs(cs.SysTime),
         NVCSw: cs.VoluntarySwitches as i64,
-        MaxRSS: (task.Thread().MaxRSS(which) / 1024) as i64,
+        NIvCSw: cs.InvoluntarySwitches as i64,
+        MinFlt: cs.MinorFaults as i64,
+        MajFlt: cs.MajorFaults as i64,
+        MaxRSS: (t.MaxRSS(which) / 1024) as i64,
         ..Default::default()
     });
 }
@@ -49,12 +56,12 @@ fn GetUsage(task: &Task, which: i32) -> Result<Rusage> {
 //
 //	y    struct timeval ru_utime; /* user CPU time used */
 //	y    struct timeval ru_stime; /* system CPU time used */
-//	p    long   ru_maxrss;        /* maximum resident set size */
+//	y    long   ru_maxrss;        /* maximum resident set size */
 //	*    long   ru_ixrss;         /* integral shared memory size */
 //	*    long   ru_idrss;         /* integral unshared data size */
 //	*    long   ru_isrss;         /* integral unshared stack size */
-//	p    long   ru_minflt;        /* page reclaims (soft page faults) */
-//	p    long   ru_majflt;        /* page faults (hard page faults) */
+//	y    long   ru_minflt;        /* page reclaims (soft page faults) */
+//	y    long   ru_majflt;        /* page faults (hard page faults) */
 //	*    long   ru_nswap;         /* swaps */
 //	p    long   ru_inblock;       /* block input operations */
 //	p    long   ru_oublock;       /* block output operations */
@@ -71,9 +78,8 @@ pub fn SysGetrusage(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
         return Err(Error::SysError(SysErr::EINVAL));
     }
 
-    let ru = GetUsage(task, which)?;
-    //*task.GetTypeMut(addr)? = ru;
-    task.CopyOutObj(&ru, addr)?;
+    let ru = GetUsage(&task.Thread(), which)?;
+    UserPtr::New(addr).Write(task, &ru)?;
     return Ok(0);
 }
 
@@ -95,7 +101,6 @@ pub fn SysTimes(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
         CSTime: ClockTFromDuration(cs2.SysTime),
     };
 
-    //*task.GetTypeMut(addr)? = r;
-    task.CopyOutObj(&r, addr)?;
+    UserPtr::New(addr).Write(task, &r)?;
     return Ok(0);
 }
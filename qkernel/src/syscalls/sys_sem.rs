@@ -0,0 +1,228 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::vec::Vec;
+
+use super::super::kernel::semaphore::*;
+use super::super::qlib::auth::id::*;
+use super::super::qlib::auth::*;
+use super::super::qlib::common::*;
+use super::super::qlib::linux::ipc::*;
+use super::super::qlib::linux::sem::*;
+use super::super::qlib::linux::time::*;
+use super::super::qlib::linux_def::*;
+use super::super::syscalls::syscalls::*;
+use super::super::task::*;
+use super::super::threadmgr::task_usermem::*;
+
+// SEM_OPS_MAX is the max count of the ops of a semop call (SEMOPM)
+pub const SEM_OPS_MAX: i32 = 500;
+
+// Semget implements linux syscall semget(2). The key is looked up in the ipc
+// namespace of the caller.
+pub fn SysSemget(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let key = args.arg0 as i32;
+    let nsems = args.arg1 as i32;
+    let flag = args.arg2 as i32;
+
+    let private = key == IPC_PRIVATE;
+    let create = flag & IPC_CREAT as i32 != 0;
+    let exclusive = flag & IPC_EXCL as i32 != 0;
+    let mode = FileMode((flag & 0o777) as u16);
+
+    let r = task.Thread().IPCNamespace().SemaphoreRegistry();
+    let set = r.FindOrCreate(task, key, nsems, mode, private, create, exclusive)?;
+    let id = set.lock().id;
+    return Ok(id as i64);
+}
+
+// Semop implements linux syscall semop(2).
+pub fn SysSemop(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let id = args.arg0 as i32;
+    let sembufAddr = args.arg1 as u64;
+    let nsops = args.arg2 as i32;
+
+    return Semtimedop(task, id, sembufAddr, nsops, None);
+}
+
+// Semtimedop implements linux syscall semtimedop(2).
+pub fn SysSemtimedop(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let id = args.arg0 as i32;
+    let sembufAddr = args.arg1 as u64;
+    let nsops = args.arg2 as i32;
+    let timespecAddr = args.arg3 as u64;
+
+    if timespecAddr == 0 {
+        return Semtimedop(task, id, sembufAddr, nsops, None);
+    }
+
+    let ts: Timespec = UserPtr::New(timespecAddr).Read(task)?;
+    if !ts.IsValid() {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    return Semtimedop(task, id, sembufAddr, nsops, Some(ts.ToDuration()?));
+}
+
+fn Semtimedop(
+    task: &mut Task,
+    id: i32,
+    sembufAddr: u64,
+    nsops: i32,
+    timeout: Option<Duration>,
+) -> Result<i64> {
+    if nsops <= 0 {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    if nsops > SEM_OPS_MAX {
+        return Err(Error::SysError(SysErr::E2BIG));
+    }
+
+    let ops: Vec<Sembuf> = UserSlice::New(sembufAddr, nsops as usize).Read(task)?;
+
+    let set = FindSet(task, id)?;
+    let creds = task.Creds();
+    let pid = task.Thread().ThreadGroup().ID();
+
+    let mut timeout = timeout;
+    loop {
+        let (waiterId, num) = set.ExecuteOps(task, &ops, &creds, pid)?;
+        if waiterId == 0 {
+            return Ok(0);
+        }
+
+        let (remain, res) = task.blocker.BlockWithMonoTimeout(true, timeout);
+        set.AbortWait(task, num, waiterId);
+        match res {
+            Ok(()) => (),
+            Err(Error::SysError(SysErr::ETIMEDOUT)) => {
+                return Err(Error::SysError(SysErr::EAGAIN));
+            }
+            Err(Error::ErrInterrupted) => {
+                return Err(Error::SysError(SysErr::ERESTARTNOHAND));
+            }
+            Err(e) => return Err(e),
+        }
+
+        if timeout.is_some() {
+            timeout = Some(remain);
+        }
+    }
+}
+
+// Semctl implements linux syscall semctl(2).
+pub fn SysSemctl(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let id = args.arg0 as i32;
+    let num = args.arg1 as i32;
+    let cmd = args.arg2 as i32;
+    let arg = args.arg3 as u64;
+
+    let creds = task.Creds();
+    let pid = task.Thread().ThreadGroup().ID();
+
+    match cmd {
+        IPC_RMID => {
+            let r = task.Thread().IPCNamespace().SemaphoreRegistry();
+            r.RemoveId(id, &creds)?;
+            return Ok(0);
+        }
+        IPC_SET => {
+            let ds: SemidDS = UserPtr::New(arg).Read(task)?;
+
+            let userns = creds.lock().UserNamespace.clone();
+            let uid = userns.MapToKUID(UID(ds.SemPerm.UID));
+            let gid = userns.MapToKGID(GID(ds.SemPerm.GID));
+            if !uid.Ok() || !gid.Ok() {
+                return Err(Error::SysError(SysErr::EINVAL));
+            }
+
+            let owner = FileOwner { UID: uid, GID: gid };
+            let perms = FilePermissions::FromMode(FileMode(ds.SemPerm.Mode & 0o777));
+            let set = FindSet(task, id)?;
+            set.Change(task, &creds, &owner, &perms)?;
+            return Ok(0);
+        }
+        IPC_STAT => {
+            let set = FindSet(task, id)?;
+            let ds = set.GetStat(&creds)?;
+            UserPtr::New(arg).Write(task, &ds)?;
+            return Ok(0);
+        }
+        GETVAL => {
+            let set = FindSet(task, id)?;
+            let val = set.GetVal(num, &creds)?;
+            return Ok(val as i64);
+        }
+        SETVAL => {
+            let val = arg as i32;
+            if val < 0 || val > VALUE_MAX as i32 {
+                return Err(Error::SysError(SysErr::ERANGE));
+            }
+
+            let set = FindSet(task, id)?;
+            set.SetVal(task, num, val as i16, &creds, pid)?;
+            return Ok(0);
+        }
+        GETALL => {
+            let set = FindSet(task, id)?;
+            let vals: Vec<u16> = set.GetValAll(&creds)?.iter().map(|v| *v as u16).collect();
+            UserSlice::New(arg, vals.len()).Write(task, &vals)?;
+            return Ok(0);
+        }
+        SETALL => {
+            let set = FindSet(task, id)?;
+            let vals: Vec<u16> = UserSlice::New(arg, set.Size()).Read(task)?;
+            let mut sems = Vec::with_capacity(vals.len());
+            for v in vals {
+                if v > VALUE_MAX as u16 {
+                    return Err(Error::SysError(SysErr::ERANGE));
+                }
+
+                sems.push(v as i16);
+            }
+
+            set.SetValAll(task, &sems, &creds, pid)?;
+            return Ok(0);
+        }
+        GETPID => {
+            let set = FindSet(task, id)?;
+            let pid = set.GetPID(num, &creds)?;
+            return Ok(pid as i64);
+        }
+        GETNCNT => {
+            let set = FindSet(task, id)?;
+            let cnt = set.GetWaiterCnt(num, false, &creds)?;
+            return Ok(cnt as i64);
+        }
+        GETZCNT => {
+            let set = FindSet(task, id)?;
+            let cnt = set.GetWaiterCnt(num, true, &creds)?;
+            return Ok(cnt as i64);
+        }
+        _ => {
+            info!("semctl cmd {} is not supported", cmd);
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+    }
+}
+
+// FindSet returns the semaphore set of the id in the ipc namespace of the task
+fn FindSet(task: &Task, id: i32) -> Result<Set> {
+    let r = task.Thread().IPCNamespace().SemaphoreRegistry();
+    match r.FindByID(id) {
+        None => return Err(Error::SysError(SysErr::EINVAL)),
+        Some(set) => return Ok(set),
+    }
+}
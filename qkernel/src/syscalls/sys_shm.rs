@@ -0,0 +1,137 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::super::kernel::shm::*;
+use super::super::qlib::common::*;
+use super::super::qlib::linux::ipc::*;
+use super::super::qlib::linux::shm::*;
+use super::super::qlib::linux_def::*;
+use super::super::syscalls::syscalls::*;
+use super::super::task::*;
+use super::super::threadmgr::task_usermem::*;
+
+// Shmget implements linux syscall shmget(2). The key is looked up in the ipc
+// namespace of the caller.
+pub fn SysShmget(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let key = args.arg0 as i32;
+    let size = args.arg1 as u64;
+    let flag = args.arg2 as i32;
+
+    let private = key == IPC_PRIVATE;
+    let create = flag & IPC_CREAT as i32 != 0;
+    let exclusive = flag & IPC_EXCL as i32 != 0;
+    let mode = FileMode((flag & 0o777) as u16);
+
+    let pid = task.Thread().ThreadGroup().ID();
+    let r = task.Thread().IPCNamespace().ShmRegistry();
+    let shm = r.FindOrCreate(task, pid, key, size, &mode, private, create, exclusive)?;
+    let id = shm.lock().id;
+    return Ok(id as i64);
+}
+
+// Shmat implements linux syscall shmat(2).
+pub fn SysShmat(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let id = args.arg0 as i32;
+    let addr = args.arg1 as u64;
+    let flag = args.arg2 as i32;
+
+    let shm = FindShm(task, id)?;
+    let opts = AttachOpts {
+        Execute: flag & SHM_EXEC as i32 != 0,
+        ReadOnly: flag & SHM_RDONLY as i32 != 0,
+        Remap: flag & SHM_REMAP as i32 != 0,
+        Round: flag & SHM_RND as i32 != 0,
+    };
+
+    let mut mopts = shm.ConfigureAttach(task, addr, &opts)?;
+    let addr = task.mm.MMap(task, &mut mopts)?;
+
+    let pid = task.Thread().ThreadGroup().ID();
+    shm.Attached(task, pid);
+    return Ok(addr as i64);
+}
+
+// Shmdt implements linux syscall shmdt(2).
+pub fn SysShmdt(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let addr = args.arg0 as u64;
+
+    let mappable = task.mm.DetachShm(task, addr)?;
+
+    let pid = task.Thread().ThreadGroup().ID();
+    let r = task.Thread().IPCNamespace().ShmRegistry();
+    r.Detached(task, &mappable, pid);
+    return Ok(0);
+}
+
+// Shmctl implements linux syscall shmctl(2).
+pub fn SysShmctl(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let id = args.arg0 as i32;
+    let cmd = args.arg1 as i32;
+    let buf = args.arg2 as u64;
+
+    let r = task.Thread().IPCNamespace().ShmRegistry();
+
+    match cmd {
+        IPC_INFO => {
+            let info = r.IPCInfo();
+            UserPtr::New(buf).Write(task, &info)?;
+            return Ok(r.HighestIndex() as i64);
+        }
+        SHM_INFO_CMD => {
+            let info = r.ShmInfo();
+            UserPtr::New(buf).Write(task, &info)?;
+            return Ok(r.HighestIndex() as i64);
+        }
+        _ => (),
+    }
+
+    let shm = FindShm(task, id)?;
+    match cmd {
+        IPC_STAT => {
+            let ds = shm.IPCStat(task)?;
+            UserPtr::New(buf).Write(task, &ds)?;
+            return Ok(0);
+        }
+        IPC_SET => {
+            let ds: ShmidDS = UserPtr::New(buf).Read(task)?;
+            shm.Set(task, &ds)?;
+            return Ok(0);
+        }
+        IPC_RMID => {
+            r.RemoveShm(task, &shm)?;
+            return Ok(0);
+        }
+        SHM_LOCK_CMD | SHM_UNLOCK_CMD => {
+            // the memory locking isn't supported, as mlock(2)
+            return Ok(0);
+        }
+        _ => {
+            info!("shmctl cmd {} is not supported", cmd);
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+    }
+}
+
+const SHM_LOCK_CMD: i32 = SHM_LOCK as i32;
+const SHM_UNLOCK_CMD: i32 = SHM_UNLOCK as i32;
+const SHM_INFO_CMD: i32 = SHM_INFO as i32;
+
+// FindShm returns the segment of the id in the ipc namespace of the task
+fn FindShm(task: &Task, id: i32) -> Result<Shm> {
+    let r = task.Thread().IPCNamespace().ShmRegistry();
+    match r.FindByID(id) {
+        None => return Err(Error::SysError(SysErr::EINVAL)),
+        Some(shm) => return Ok(shm),
+    }
+}
@@ -21,6 +21,7 @@ use super::super::syscalls::syscalls::*;
 use super::super::task::*;
 use super::super::threadmgr::thread::*;
 use super::super::threadmgr::pid_namespace::*;
+use super::super::threadmgr::task_usermem::*;
 use super::super::SignalDef::*;
 use super::sys_poll::*;
 
@@ -84,7 +85,7 @@ pub fn SysRtSigaction(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
 
     let mut newactptr: Option<SigAct> = None;
     if sigAction != 0 {
-        let sigAction1 = task.CopyInObj::<SigAct>(sigAction)?;
+        let sigAction1 = UserPtr::<SigAct>::New(sigAction).Read(task)?;
         //info!("signum is {} the signaction is {:?}, ", signum, sigAction1);
         newactptr = Some(sigAction1);
     }
@@ -94,8 +95,7 @@ pub fn SysRtSigaction(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let oldact = tg.SetSignalAct(Signal(signum as i32), &newactptr)?;
 
     if oldSigAction != 0 {
-        //*task.GetTypeMut(oldSigAction)? = oldact;
-        task.CopyOutObj(&oldact, oldSigAction)?;
+        UserPtr::New(oldSigAction).Write(task, &oldact)?;
     }
 
     return Ok(0);
@@ -118,7 +118,7 @@ pub fn SysRtSigProcMask(task: &mut Task, args: &SyscallArguments) -> Result<i64>
     let t = task.Thread();
     let oldMask = t.SignalMask().0;
     if setaddr != 0 {
-        let mask: u64 = task.CopyInObj(setaddr)?;
+        let mask: u64 = UserPtr::New(setaddr).Read(task)?;
 
         match how {
             SigHow::SIG_BLOCK => t.SetSignalMask(SignalSet(oldMask | mask)),
@@ -129,7 +129,7 @@ pub fn SysRtSigProcMask(task: &mut Task, args: &SyscallArguments) -> Result<i64>
     }
 
     if oldaddr != 0 {
-        task.CopyOutObj(&oldMask, oldaddr)?;
+        UserPtr::New(oldaddr).Write(task, &oldMask)?;
     }
 
     return Ok(0);
@@ -141,13 +141,20 @@ pub fn SysSigaltstack(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
 
     let alt = task.SignalStack();
     if oldaddr != 0 {
-        //let oldStack = task.GetTypeMut::<SignalStack>(oldaddr)?;
-        //*oldStack = alt;
-        task.CopyOutObj(&alt, oldaddr)?;
+        UserPtr::New(oldaddr).Write(task, &alt)?;
     }
 
     if setaddr != 0 {
-        let alt = task.CopyInObj::<SignalStack>(setaddr)?;
+        let alt = UserPtr::<SignalStack>::New(setaddr).Read(task)?;
+        let mode = alt.flags & !SignalStack::FLAG_AUTODISARM;
+        if mode != 0 && mode != SignalStack::FLAG_ON_STACK && mode != SignalStack::FLAG_DISABLE {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        if alt.IsEnable() && alt.size < SignalStack::MIN_SIZE {
+            return Err(Error::SysError(SysErr::ENOMEM));
+        }
+
         // The signal stack cannot be changed if the task is currently
         // on the stack. This is enforced at the lowest level because
         // these semantics apply to changing the signal stack via a
@@ -168,7 +175,7 @@ pub fn SysPause(task: &mut Task, _args: &SyscallArguments) -> Result<i64> {
 pub fn SysRtSigsuspend(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let sigset = args.arg0 as u64;
 
-    let mask = SignalSet(task.CopyInObj::<u64>(sigset)?);
+    let mask = SignalSet(UserPtr::<u64>::New(sigset).Read(task)?);
     let mask = SignalSet(mask.0 & !UnblockableSignals().0);
 
     let thread = task.Thread();
@@ -202,8 +209,7 @@ pub fn SysRtSigtimedwait(task: &mut Task, args: &SyscallArguments) -> Result<i64
 
     if siginfo != 0 {
         si.FixSignalCodeForUser();
-        //*task.GetTypeMut(siginfo)? = *si;
-        task.CopyOutObj::<SignalInfo>(&*si, siginfo)?;
+        UserPtr::<SignalInfo>::New(siginfo).Write(task, &*si)?;
     }
 
     return Ok(si.Signo as i64);
@@ -215,12 +221,16 @@ pub fn SysRtSigqueueinfo(task: &mut Task, args: &SyscallArguments) -> Result<i64
     let sig = args.arg1 as i32;
     let infoAddr = args.arg2 as u64;
 
+    if sig != 0 && !Signal(sig).IsValid() {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
     // Copy in the info.
     //
     // We must ensure that the Signo is set (Linux overrides this in the
     // same way), and that the code is in the allowed set. This same logic
     // appears below in RtSigtgqueueinfo and should be kept in sync.
-    let mut info: SignalInfo = task.CopyInObj(infoAddr)?;
+    let mut info: SignalInfo = UserPtr::New(infoAddr).Read(task)?;
     info.Signo = sig;
 
     let t = task.Thread();
@@ -264,12 +274,16 @@ pub fn SysRtTgsigqueueinfo(task: &mut Task, args: &SyscallArguments) -> Result<i
         return Err(Error::SysError(SysErr::EINVAL));
     }
 
+    if sig != 0 && !Signal(sig).IsValid() {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
     // Copy in the info.
     //
     // We must ensure that the Signo is set (Linux overrides this in the
     // same way), and that the code is in the allowed set. This same logic
     // appears below in RtSigtgqueueinfo and should be kept in sync.
-    let mut info: SignalInfo = task.CopyInObj(infoAddr)?;
+    let mut info: SignalInfo = UserPtr::New(infoAddr).Read(task)?;
     info.Signo = sig;
 
     let t = task.Thread();
@@ -334,7 +348,7 @@ pub fn SysKill(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
             };
 
             let sigRt = info.SigRt();
-            sigRt.pid = pidns.IDOfTask(&t);
+            sigRt.pid = target.PIDNamespace().IDOfThreadGroup(&t.ThreadGroup());
             let tuserns = target.UserNamespace();
             sigRt.uid = creds.lock().RealKUID.In(&tuserns).OrOverflow().0;
             match target.SendGroupSignal(&info) {
@@ -383,7 +397,7 @@ pub fn SysKill(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
             };
 
             let sigRt = info.SigRt();
-            sigRt.pid = pidns.IDOfTask(&t);
+            sigRt.pid = leader.PIDNamespace().IDOfThreadGroup(&t.ThreadGroup());
             let tuserns = leader.UserNamespace();
             sigRt.uid = creds.lock().RealKUID.In(&tuserns).OrOverflow().0;
             match tg.SendSignal(&info) {
@@ -458,7 +472,7 @@ pub fn SysKill(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
                 };
 
                 let sigRt = info.SigRt();
-                sigRt.pid = pidns.IDOfTask(&t);
+                sigRt.pid = leader.PIDNamespace().IDOfThreadGroup(&t.ThreadGroup());
                 let tuserns = leader.UserNamespace();
                 sigRt.uid = creds.lock().RealKUID.In(&tuserns).OrOverflow().0;
                 // See note above regarding ESRCH race above.
@@ -29,6 +29,7 @@ use super::super::qlib::linux::time::*;
 use super::super::syscalls::syscalls::*;
 //use super::super::qlib::linux::socket::*;
 use super::super::kernel::timer::*;
+use super::super::threadmgr::task_usermem::*;
 
 // minListenBacklog is the minimum reasonable backlog for listening sockets.
 const MIN_LISTEN_BACKLOG: u32 = 8;
@@ -118,7 +119,7 @@ pub fn SysSocketPair(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let fd2 = task.NewFDFrom(0, &s2, &fdFlags)?;
 
     let fds = [fd1, fd2];
-    task.CopyOutSlice(&fds, socks, 2)?;
+    UserSlice::New(socks, 2).Write(task, &fds)?;
 
     return Ok(0);
 }
@@ -130,7 +131,7 @@ pub fn CaptureAddress(task: &Task, addr: u64, addrlen: u32) -> Result<Vec<u8>> {
 
     //task.CheckPermission(addr, addrlen as u64, false, false)?;
 
-    return task.CopyInVec(addr, addrlen as usize);
+    return UserSlice::New(addr, addrlen as usize).Read(task);
 }
 
 #[derive(Debug)]
@@ -189,7 +190,7 @@ pub fn Accept4(task: &Task, fd: i32, addr: u64, addrlen: u64, flags: i32) -> Res
     let len = if addrlen == 0 {
         0
     } else {
-        let len = task.CopyInObj::<i32>(addrlen)?;
+        let len = UserPtr::<i32>::New(addrlen).Read(task)?;
 
         if len < 0 {
             return Err(Error::SysError(SysErr::EINVAL));
@@ -217,9 +218,8 @@ pub fn Accept4(task: &Task, fd: i32, addr: u64, addrlen: u64, flags: i32) -> Res
     };
 
     if peerRequested {
-        task.CopyOutSlice(addrstr, addr, lenCopy as usize)?;
-        //*task.GetTypeMut::<i32>(addrlen)? = len as i32;
-        task.CopyOutObj(&(len as i32), addrlen)?
+        UserSlice::New(addr, lenCopy as usize).Write(task, addrstr)?;
+        UserPtr::New(addrlen).Write(task, &(len as i32))?
     }
 
     return Ok(nfd);
@@ -246,7 +246,7 @@ pub fn SysBind(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
         return Err(Error::SysError(SysErr::EINVAL));
     }
 
-    let addrstr = task.CopyInVec(addr, addrlen as usize)?;
+    let addrstr = UserSlice::New(addr, addrlen as usize).Read(task)?;
     let res = sock.Bind(task, &addrstr);
 
     return res;
@@ -308,7 +308,7 @@ pub fn SysGetSockOpt(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let sock = file.FileOp.clone();
 
     let optlen = if optLenAddr != 0 {
-        let optlen = task.CopyInObj::<i32>(optLenAddr)?;
+        let optlen = UserPtr::<i32>::New(optLenAddr).Read(task)?;
 
         if optlen < 0 {
             return Err(Error::SysError(SysErr::EINVAL));
@@ -327,9 +327,8 @@ pub fn SysGetSockOpt(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     }
 
     let len = res as usize;
-    task.CopyOutSlice(&optVal[..len], optValAddr, len)?;
-    //*task.GetTypeMut(optLenAddr)? = len as i32;
-    task.CopyOutObj(&(len as i32), optLenAddr)?;
+    UserSlice::New(optValAddr, len).Write(task, &optVal[..len])?;
+    UserPtr::New(optLenAddr).Write(task, &(len as i32))?;
 
     return Ok(0);
 }
@@ -351,7 +350,7 @@ pub fn SysSetSockOpt(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
         return Err(Error::SysError(SysErr::EINVAL));
     }
 
-    let optVal = task.CopyInVec(optValAddr, optLen as usize)?;
+    let optVal = UserSlice::New(optValAddr, optLen as usize).Read(task)?;
     let res = sock.SetSockOpt(task, level, name, &optVal[..optLen as usize])?;
 
     return Ok(res);
@@ -367,7 +366,7 @@ pub fn SysGetSockName(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let sock = file.FileOp.clone();
 
     let mut buf: [u8; MAX_ADDR_LEN as usize] = [0; MAX_ADDR_LEN as usize];
-    let len = task.CopyInObj::<i32>(addrlen)?;
+    let len = UserPtr::<i32>::New(addrlen).Read(task)?;
 
     let len = if len > MAX_ADDR_LEN as i32 {
         MAX_ADDR_LEN as i32
@@ -376,14 +375,13 @@ pub fn SysGetSockName(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     };
 
     let mut outputlen = sock.GetSockName(task, &mut buf[0..len as usize])? as usize;
-    //*(task.GetTypeMut::<i32>(addrlen)?) = outputlen as i32;
-    task.CopyOutObj(&(outputlen as i32), addrlen)?;
+    UserPtr::New(addrlen).Write(task, &(outputlen as i32))?;
 
     if len < outputlen as i32 {
         outputlen = len as usize;
     }
 
-    task.CopyOutSlice(&buf[..outputlen as usize], addr, outputlen as usize)?;
+    UserSlice::New(addr, outputlen as usize).Write(task, &buf[..outputlen as usize])?;
 
     return Ok(0);
 }
@@ -402,14 +400,13 @@ pub fn SysGetPeerName(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
 
     //info!("SysGetPeerName buf is {}", &buf[..outputlen as usize]);
 
-    let len = task.CopyInObj::<i32>(addrlen)?;
+    let len = UserPtr::<i32>::New(addrlen).Read(task)?;
     if len < outputlen as i32 {
         outputlen = len as usize;
     }
 
-    task.CopyOutSlice(&buf[..outputlen as usize], addr, addrlen as usize)?;
-    //*(task.GetTypeMut::<i32>(addrlen)?) = outputlen as i32;
-    task.CopyOutObj(&(outputlen as i32), addrlen)?;
+    UserSlice::New(addr, addrlen as usize).Write(task, &buf[..outputlen as usize])?;
+    UserPtr::New(addrlen).Write(task, &(outputlen as i32))?;
     return Ok(0);
 }
 
@@ -420,9 +417,7 @@ fn recvSingleMsg(
     flags: i32,
     deadline: Option<Time>,
 ) -> Result<i64> {
-    //let msg = task.GetTypeMut::<MsgHdr>(msgPtr)?;
-
-    let mut msg: MsgHdr = task.CopyInObj(msgPtr)?;
+    let mut msg: MsgHdr = UserPtr::New(msgPtr).Read(task)?;
     if msg.iovLen > UIO_MAXIOV {
         return Err(Error::SysError(SysErr::EMSGSIZE));
     }
@@ -452,7 +447,7 @@ fn recvSingleMsg(
         }
 
         msg.msgFlags = mflags;
-        task.CopyOutObj(&msg, msgPtr)?;
+        UserPtr::New(msgPtr).Write(task, &msg)?;
 
         return Ok(n as i64);
     }
@@ -529,22 +524,19 @@ fn recvSingleMsg(
             return Err(Error::SysError(SysErr::ERANGE));
         }
         sender.Marsh(&mut addressVec[..], senderLen)?;
-        task.CopyOutSlice(&addressVec[0..senderLen], msg.msgName, msg.nameLen as usize)?;
+        UserSlice::New(msg.msgName, msg.nameLen as usize).Write(task, &addressVec[0..senderLen])?;
         msg.nameLen = senderLen as u32;
     }
     if msg.msgControl != 0 && msg.msgControlLen != 0 {
-        task.CopyOutSlice(
-            &controlMessageBuffer[0..msg.msgControlLen as usize],
-            msg.msgControl,
-            msg.msgControlLen,
-        )?;
+        UserSlice::New(msg.msgControl, msg.msgControlLen as usize)
+            .Write(task, &controlMessageBuffer[0..msg.msgControlLen as usize])?;
     } else {
         msg.msgControlLen = 0;
     }
 
     msg.msgFlags = mflags;
 
-    task.CopyOutObj(&msg, msgPtr)?;
+    UserPtr::New(msgPtr).Write(task, &msg)?;
     return Ok(n);
 }
 
@@ -555,7 +547,7 @@ fn sendSingleMsg(
     flags: i32,
     deadline: Option<Time>,
 ) -> Result<i64> {
-    let msg = task.CopyInObj::<MsgHdr>(msgPtr)?;
+    let msg = UserPtr::<MsgHdr>::New(msgPtr).Read(task)?;
 
     if msg.msgControlLen > MAX_CONTROL_LEN as usize {
         return Err(Error::SysError(SysErr::ENOBUFS));
@@ -565,8 +557,9 @@ fn sendSingleMsg(
         return Err(Error::SysError(SysErr::EMSGSIZE));
     }
 
-    let msgVec: Vec<u8> = task.CopyInVec(msg.msgName, msg.nameLen as usize)?;
-    let controlVec: Vec<u8> = task.CopyInVec(msg.msgControl, msg.msgControlLen as usize)?;
+    let msgVec: Vec<u8> = UserSlice::New(msg.msgName, msg.nameLen as usize).Read(task)?;
+    let controlVec: Vec<u8> =
+        UserSlice::New(msg.msgControl, msg.msgControlLen as usize).Read(task)?;
 
     let mut pMsg = msg;
     if msg.nameLen > 0 {
@@ -580,7 +573,7 @@ fn sendSingleMsg(
     let src = task.IovsFromAddr(msg.iov, msg.iovLen)?;
 
     let res = sock.SendMsg(task, &src, flags, &mut pMsg, deadline)?;
-    task.CopyOutObj(&msg, msgPtr)?;
+    UserPtr::New(msgPtr).Write(task, &msg)?;
     return Ok(res);
 }
 
@@ -631,7 +624,12 @@ pub fn SysRecvMMsg(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
 
     let sock = file.FileOp.clone();
 
-    if flags & !(MsgType::BASE_RECV_FLAGS | MsgType::MSG_CMSG_CLOEXEC | MsgType::MSG_ERRQUEUE) != 0
+    if flags
+        & !(MsgType::BASE_RECV_FLAGS
+            | MsgType::MSG_CMSG_CLOEXEC
+            | MsgType::MSG_ERRQUEUE
+            | MsgType::MSG_WAITFORONE)
+        != 0
     {
         return Err(Error::SysError(SysErr::EINVAL));
     }
@@ -648,7 +646,7 @@ pub fn SysRecvMMsg(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
 
     let mut deadline = None;
     if timeout != 0 {
-        let timePtr = task.CopyInObj::<Timespec>(timeout)?;
+        let timePtr = UserPtr::<Timespec>::New(timeout).Read(task)?;
 
         let now = MonotonicNow();
         deadline = Some(Time(now + timePtr.ToNs()?));
@@ -664,10 +662,28 @@ pub fn SysRecvMMsg(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
         }
     }
 
+    if vlen == 0 {
+        return Ok(0);
+    }
+
     let mut count = 0;
     let mut res = 0;
-    //let msgs = task.GetSliceMut::<MMsgHdr>(msgPtr, vlen as usize)?;
-    let mut msgs = task.CopyInVec::<MMsgHdr>(msgPtr, vlen as usize)?;
+    let mut msgs = UserSlice::<MMsgHdr>::New(msgPtr, vlen as usize).Read(task)?;
+
+    // host sockets receive the whole vector in one host call
+    if MMsgBatchable(&msgs) {
+        match sock.RecvMMsg(task, &mut msgs, flags, deadline) {
+            Err(Error::SysError(SysErr::ENOSYS)) => (),
+            Err(e) => return Err(e),
+            Ok(count) => {
+                UserSlice::New(msgPtr, vlen as usize).Write(task, &msgs[..count as usize])?;
+                return Ok(count);
+            }
+        }
+    }
+
+    let waitForOne = flags & MsgType::MSG_WAITFORONE != 0;
+    flags &= !MsgType::MSG_WAITFORONE;
 
     //info!("SysRecvMMsg 1 vlen is {}", vlen);
     for i in 0..vlen as usize {
@@ -694,17 +710,34 @@ pub fn SysRecvMMsg(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
 
         msgs[i].msgLen = res as u32;
         count += 1;
+
+        // MSG_WAITFORONE only blocks for the first message
+        if waitForOne {
+            flags |= MsgType::MSG_DONTWAIT;
+        }
     }
 
     if count == 0 {
         return Err(Error::SysError(-res as i32));
     }
 
-    task.CopyOutSlice(&msgs, msgPtr, vlen as usize)?;
+    UserSlice::New(msgPtr, vlen as usize).Write(task, &msgs)?;
 
     return Ok(count);
 }
 
+// MMsgBatchable checks the limits which recvSingleMsg/sendSingleMsg check per
+// message, so that a batch doesn't fail on a message the loop wouldn't reach.
+fn MMsgBatchable(msgs: &[MMsgHdr]) -> bool {
+    for msg in msgs {
+        if msg.msgHdr.iovLen > UIO_MAXIOV || msg.msgHdr.msgControlLen > MAX_CONTROL_LEN {
+            return false;
+        }
+    }
+
+    return true;
+}
+
 pub const BASE_RECV_FLAGS: i32 = MsgType::MSG_OOB
     | MsgType::MSG_DONTROUTE
     | MsgType::MSG_DONTWAIT
@@ -745,7 +778,7 @@ pub fn SysRecvFrom(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
 
     let mut nameLen: i32 = 0;
     if nameLenPtr != 0 {
-        nameLen = task.CopyInObj(nameLenPtr)?;
+        nameLen = UserPtr::New(nameLenPtr).Read(task)?;
     }
 
     //todo: handle the msg.nameLen > 1024
@@ -775,16 +808,14 @@ pub fn SysRecvFrom(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
             if nameLen < senderLen as i32 {
                 return Err(Error::SysError(SysErr::ERANGE));
             }
-            //let slices = task.GetSliceMut::<u8>(namePtr, nameLen as usize)?;
-            //sender.Marsh(slices, senderLen)?;
             let mut dataBuf = DataBuff::New(nameLen as usize);
             sender.Marsh(&mut dataBuf.buf, senderLen)?;
-            task.CopyOutSlice(&mut dataBuf.buf, namePtr, nameLen as usize)?;
+            UserSlice::New(namePtr, nameLen as usize).Write(task, &mut dataBuf.buf)?;
             //task.CopyOutSlice(&msgVec[0..pMsg.nameLen as usize], namePtr, nameLen as usize)?;
-            task.CopyOutObj(&(senderLen as u32), nameLenPtr)?;
+            UserPtr::New(nameLenPtr).Write(task, &(senderLen as u32))?;
         } else {
             // has only type
-            task.CopyOutObj(&(0 as u32), nameLenPtr)?;
+            UserPtr::New(nameLenPtr).Write(task, &(0 as u32))?;
         }
     }
 
@@ -864,18 +895,44 @@ pub fn SysSendMMsg(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
         flags |= MsgType::MSG_DONTWAIT
     }
 
+    if vlen == 0 {
+        return Ok(0);
+    }
+
     let mut count = 0;
     let mut res = 0;
-    //let msgs = task.GetSliceMut::<MMsgHdr>(msgPtr, vlen as usize)?;
-    let mut msgs = task.CopyInVec::<MMsgHdr>(msgPtr, vlen as usize)?;
+    let mut msgs = UserSlice::<MMsgHdr>::New(msgPtr, vlen as usize).Read(task)?;
+
+    // host sockets send the whole vector in one host call
+    if MMsgBatchable(&msgs) {
+        match sock.SendMMsg(task, &mut msgs, flags, deadline) {
+            Err(Error::SysError(SysErr::ENOSYS)) => (),
+            Err(e) => return Err(e),
+            Ok(count) => {
+                UserSlice::New(msgPtr, vlen as usize).Write(task, &msgs[..count as usize])?;
+                return Ok(count);
+            }
+        }
+    }
+
     for i in 0..vlen as usize {
-        res = sendSingleMsg(
+        res = match sendSingleMsg(
             task,
             &sock,
             &(msgs[i].msgHdr) as *const MsgHdr as u64,
             flags,
             deadline,
-        )?;
+        ) {
+            Err(e) => {
+                // as Linux, the error is reported by the next call
+                if count > 0 {
+                    break;
+                }
+
+                return Err(e);
+            }
+            Ok(n) => n,
+        };
 
         if res < 0 {
             break;
@@ -889,7 +946,7 @@ pub fn SysSendMMsg(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
         return Err(Error::SysError(-res as i32));
     }
 
-    task.CopyOutSlice(&msgs, msgPtr, vlen as usize)?;
+    UserSlice::New(msgPtr, vlen as usize).Write(task, &msgs)?;
 
     return Ok(count);
 }
@@ -917,8 +974,7 @@ pub fn SysSendTo(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let mut pMsg = MsgHdr::default();
 
     let _msgVec = if namePtr != 0 && nameLen > 0 {
-        //let vec = task.GetSlice::<u8>(namePtr, nameLen as usize)?.to_vec();
-        let vec = task.CopyInVec::<u8>(namePtr, nameLen as usize)?;
+        let vec = UserSlice::<u8>::New(namePtr, nameLen as usize).Read(task)?;
         pMsg.msgName = vec.as_ptr() as u64;
         pMsg.nameLen = nameLen;
         Some(vec)
@@ -21,6 +21,7 @@ use super::super::qlib::linux_def::*;
 use super::super::qlib::mem::block::*;
 use super::super::syscalls::syscalls::*;
 use super::super::task::*;
+use super::super::threadmgr::task_usermem::*;
 
 // Splice moves data to this file, directly from another.
 //
@@ -86,10 +87,10 @@ pub fn Splice(task: &Task, dst: &File, src: &File, opts: &mut SpliceOpts) -> Res
         }
 
         // Enforce file limits.
-        let (limit, ok) = dst.checkLimit(opts.DstStart);
+        let (limit, ok) = dst.checkLimit(task, opts.DstStart);
         if ok && limit == 0 {
             return Err(Error::ErrExceedsFileSizeLimit);
-        } else if ok && limit < opts.DstStart {
+        } else if ok && limit < opts.Length {
             opts.Length = limit; // Cap the write.
         }
     }
@@ -415,7 +416,7 @@ pub fn SysSplice(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
         if outOffset != 0 {
             let offset: i64 = if outOffset != 0 {
                 opts.DstOffset = true;
-                task.CopyInObj(outOffset)?
+                UserPtr::New(outOffset).Read(task)?
             } else {
                 0
             };
@@ -430,7 +431,7 @@ pub fn SysSplice(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
 
         let offset: i64 = if inOffset != 0 {
             opts.SrcOffset = true;
-            task.CopyInObj(inOffset)?
+            UserPtr::New(inOffset).Read(task)?
         } else {
             0
         };
@@ -453,6 +454,46 @@ pub fn SysSplice(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     return DoSplice(task, &dst, &src, &mut opts, nonBlocking);
 }
 
+// Tee implements linux syscall tee(2).
+pub fn SysTee(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let inFD = args.arg0 as i32;
+    let outFD = args.arg1 as i32;
+    let count = args.arg2 as i64;
+    let flags = args.arg3 as i32;
+
+    // Check for invalid flags.
+    if flags & !(SPLICE_F_MOVE | SPLICE_F_NONBLOCK | SPLICE_F_MORE | SPLICE_F_GIFT) != 0 {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    let nonBlocking = (flags & SPLICE_F_NONBLOCK) != 0;
+
+    let dst = task.GetFile(outFD)?;
+    let src = task.GetFile(inFD)?;
+
+    let srcInode = src.Dirent.Inode();
+    let srcAttr = srcInode.StableAttr();
+    let dstInode = dst.Dirent.Inode();
+    let dstAttr = dstInode.StableAttr();
+
+    // Both files must be pipes, and not the same one.
+    if !srcAttr.IsPipe() || !dstAttr.IsPipe() {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    if srcAttr.DeviceId == dstAttr.DeviceId && srcAttr.InodeId == dstAttr.InodeId {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    let mut opts = SpliceOpts {
+        Length: count,
+        Dup: true,
+        ..Default::default()
+    };
+
+    return DoSplice(task, &dst, &src, &mut opts, nonBlocking);
+}
+
 pub fn SysSendfile(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let outFD = args.arg0 as i32;
     let inFD = args.arg1 as i32;
@@ -485,7 +526,7 @@ pub fn SysSendfile(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
             return Err(Error::SysError(SysErr::ESPIPE));
         }
 
-        let offset: i64 = task.CopyInObj(offsetAddr)?;
+        let offset: i64 = UserPtr::New(offsetAddr).Read(task)?;
 
         n = DoSplice(
             task,
@@ -502,8 +543,7 @@ pub fn SysSendfile(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
             outFile.Flags().NonBlocking,
         )?;
 
-        //*task.GetTypeMut(offsetAddr)? = offset + n;
-        task.CopyOutObj(&(offset + n), offsetAddr)?;
+        UserPtr::New(offsetAddr).Write(task, &(offset + n))?;
     } else {
         n = DoSplice(
             task,
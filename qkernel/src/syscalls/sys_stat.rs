@@ -15,11 +15,14 @@
 use super::super::fs::attr::*;
 use super::super::fs::dirent::*;
 use super::super::fs::file::*;
+use super::super::fs::inode::*;
+use super::super::fs::mount::*;
 use super::super::qlib::common::*;
 use super::super::qlib::device::*;
 use super::super::qlib::linux_def::*;
 use super::super::syscalls::syscalls::*;
 use super::super::task::*;
+use super::super::threadmgr::task_usermem::*;
 use super::sys_file::*;
 
 pub fn SysStat(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
@@ -158,8 +161,7 @@ pub fn SysStatx(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
 
         let uattr = file.UnstableAttr(task)?;
         let inode = file.Dirent.Inode();
-        let sattr = inode.StableAttr();
-        statx(task, &sattr, &uattr, statxAddr)?;
+        statx(task, &inode, &uattr, mask, statxAddr)?;
         return Ok(0);
     }
 
@@ -180,7 +182,7 @@ pub fn SysStatx(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
 
             let uattr = inode.UnstableAttr(task)?;
 
-            statx(task, &sattr, &uattr, statxAddr)?;
+            statx(task, &inode, &uattr, mask, statxAddr)?;
             return Ok(());
         },
     )?;
@@ -254,9 +256,48 @@ fn fstat(task: &Task, f: &File, statAddr: u64) -> Result<()> {
     return Ok(());
 }
 
+// StatAt returns the stat of the path like fstatat(2) without copying it
+// out, the compat syscalls translate it to their own layout.
+pub fn StatAt(task: &Task, fd: i32, addr: u64, flags: i32) -> Result<LibcStat> {
+    let (path, dirPath) = copyInPath(task, addr, flags & ATType::AT_EMPTY_PATH != 0)?;
+    if path.len() == 0 {
+        let file = task.GetFile(fd)?;
+        let uattr = file.UnstableAttr(task)?;
+        return Ok(NewLibcStat(task, &file.Dirent.Inode().StableAttr(), &uattr));
+    }
+
+    let resolve = dirPath || flags & ATType::AT_SYMLINK_NOFOLLOW == 0;
+
+    let mut s = LibcStat::default();
+    fileOpOn(
+        task,
+        fd,
+        &path,
+        resolve,
+        &mut |_root: &Dirent, d: &Dirent, _remainingTraversals: u32| -> Result<()> {
+            let inode = d.Inode();
+            if dirPath && !inode.StableAttr().IsDir() {
+                return Err(Error::SysError(SysErr::ENOTDIR));
+            }
+
+            let uattr = inode.UnstableAttr(task)?;
+            s = NewLibcStat(task, &inode.StableAttr(), &uattr);
+            return Ok(());
+        },
+    )?;
+
+    return Ok(s);
+}
+
 fn copyOutStat(task: &Task, statAddr: u64, sattr: &StableAttr, uattr: &UnstableAttr) -> Result<()> {
-    //let mut s: &mut LibcStat = task.GetTypeMut(statAddr)?;
+    let s = NewLibcStat(task, sattr, uattr);
+
+    UserPtr::New(statAddr).Write(task, &s)?;
+    //info!("copyOutStat stat is {:x?}", s);
+    return Ok(());
+}
 
+pub fn NewLibcStat(task: &Task, sattr: &StableAttr, uattr: &UnstableAttr) -> LibcStat {
     let mut s: LibcStat = LibcStat::default();
     //*s = LibcStat::default();
     let creds = task.creds.clone();
@@ -285,21 +326,37 @@ fn copyOutStat(task: &Task, statAddr: u64, sattr: &StableAttr, uattr: &UnstableA
     s.st_ctime = ctime.tv_sec;
     s.st_ctime_nsec = ctime.tv_nsec;
 
-    task.CopyOutObj(&s, statAddr)?;
-    //info!("copyOutStat stat is {:x?}", s);
-    return Ok(());
+    return s;
 }
 
-fn statx(task: &Task, sattr: &StableAttr, uattr: &UnstableAttr, statxAddr: u64) -> Result<()> {
+fn statx(
+    task: &Task,
+    inode: &Inode,
+    uattr: &UnstableAttr,
+    mask: u32,
+    statxAddr: u64,
+) -> Result<()> {
+    let sattr = inode.StableAttr();
     let (devMajor, devMinor) = DecodeDeviceId(sattr.DeviceId as u32);
 
     let creds = task.creds.clone();
     let ns = creds.lock().UserNamespace.clone();
 
-    //let out: &mut Statx = task.GetTypeMut::<Statx>(statxAddr)?;
+    // the host files are only asked for the birth time when it is wanted
+    let mut birthTime = uattr.BirthTime;
+    if birthTime.IsZero() && mask & StatxMask::STATX_BTIME != 0 {
+        birthTime = inode.BirthTime(task)?;
+    }
+
+    let mut stxMask = StatxMask::STATX_BASIC_STATS;
+    let mut btime = StatxTimestamp::default();
+    if !birthTime.IsZero() {
+        stxMask |= StatxMask::STATX_BTIME;
+        btime = birthTime.StatxTimestamp();
+    }
 
     let s = Statx {
-        stx_mask: StatxMask::STATX_BASIC_STATS,
+        stx_mask: stxMask,
         stx_blksize: sattr.BlockSize as u32,
         stx_attributes: 0,
         stx_nlink: uattr.Links as u32,
@@ -312,7 +369,7 @@ fn statx(task: &Task, sattr: &StableAttr, uattr: &UnstableAttr, statxAddr: u64)
         stx_attributes_mask: 0,
         stx_blocks: uattr.Usage as u64 / 512,
         stx_atime: uattr.AccessTime.StatxTimestamp(),
-        stx_btime: StatxTimestamp::default(),
+        stx_btime: btime,
         stx_ctime: uattr.StatusChangeTime.StatxTimestamp(),
         stx_mtime: uattr.ModificationTime.StatxTimestamp(),
         stx_rdev_major: sattr.DeviceFileMajor as u32,
@@ -324,33 +381,63 @@ fn statx(task: &Task, sattr: &StableAttr, uattr: &UnstableAttr, statxAddr: u64)
 
     //*out = s;
 
-    task.CopyOutObj(&s, statxAddr)?;
+    UserPtr::New(statxAddr).Write(task, &s)?;
     return Ok(());
 }
 
 fn statfsImpl(task: &Task, d: &Dirent, addr: u64) -> Result<()> {
     let inode = d.Inode();
-    let sattr = inode.lock().StableAttr().clone();
 
-    let info = inode.StatFS(task)?;
+    // the inodes without their own statfs, such as the device files, get
+    // the type of their mount
+    let mut info = match inode.StatFS(task) {
+        Err(Error::SysError(SysErr::ENOSYS)) => FsInfo::default(),
+        Err(e) => return Err(e),
+        Ok(info) => info,
+    };
+
+    let msrc = inode.lock().MountSource.clone();
+    let (magic, mflags) = {
+        let msrc = msrc.lock();
+        (msrc.FsMagic(), msrc.Flags)
+    };
+
+    if let Some(magic) = magic {
+        info.Type = magic;
+    }
+
+    let blockSize = if info.BlockSize == 0 {
+        MemoryDef::PAGE_SIZE as i64
+    } else {
+        info.BlockSize
+    };
+
+    let mut flags = LibcStatfs::ST_VALID;
+    if mflags.ReadOnly {
+        flags |= LibcStatfs::ST_RDONLY;
+    }
+    if mflags.NoExec {
+        flags |= LibcStatfs::ST_NOEXEC;
+    }
+    if mflags.NoAtime {
+        flags |= LibcStatfs::ST_NOATIME;
+    }
 
     let statfs = LibcStatfs {
         Type: info.Type,
-        BlockSize: sattr.BlockSize,
+        BlockSize: blockSize,
         Blocks: info.TotalBlocks,
         BlocksFree: info.FreeBlocks,
-        BlocksAvailable: info.FreeBlocks,
+        BlocksAvailable: info.AvailBlocks,
         Files: info.TotalFiles,
         FilesFree: info.FreeFiles,
         NameLength: NAME_MAX as u64,
-        FragmentSize: sattr.BlockSize,
+        FragmentSize: blockSize,
+        Flags: flags,
         ..Default::default()
     };
 
-    //let out: &mut LibcStatfs = task.GetTypeMut::<LibcStatfs>(addr)?;
-    //*out = statfs;
-
-    task.CopyOutObj(&statfs, addr)?;
+    UserPtr::New(addr).Write(task, &statfs)?;
 
     return Ok(());
 }
@@ -14,13 +14,19 @@
 
 use super::super::fs::file::*;
 use super::super::fs::host::hostinodeop::*;
+use super::super::fs::mount::*;
 use super::super::qlib::common::*;
 use super::super::syscalls::syscalls::*;
 use super::super::task::*;
 use super::super::Kernel::HostSpace;
 
 // Sync implements linux system call sync(2).
-pub fn SysSync(_task: &mut Task, _args: &SyscallArguments) -> Result<i64> {
+pub fn SysSync(task: &mut Task, _args: &SyscallArguments) -> Result<i64> {
+    // wait for the writes in flight so that the host sync covers them
+    for barrier in task.mountNS.SyncBarriers() {
+        barrier.Write(task);
+    }
+
     HostSpace::SysSync();
     return Ok(0);
 }
@@ -30,7 +36,27 @@ pub fn SysSyncFs(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let fd = args.arg0 as i32;
 
     let file = task.GetFile(fd)?;
-    let inode = file.Dirent.Inode();
+    let mut inode = file.Dirent.Inode();
+
+    // wait for the writes in flight to the mount, the internal filesystems
+    // have nothing more to flush
+    let msrc = inode.lock().MountSource.clone();
+    MountSource::SyncBarrier(&msrc).Write(task);
+
+    // the writes to an overlay file go to its upper layer
+    let overlay = inode.lock().Overlay.clone();
+    if let Some(overlay) = overlay {
+        let upper = overlay.read().upper.clone();
+        match upper {
+            Some(upper) => inode = upper,
+            None => {
+                // the file isn't copied up, the upper filesystem has no fd here
+                HostSpace::SysSync();
+                return Ok(0);
+            }
+        }
+    }
+
     let iops = inode.lock().InodeOp.clone();
     match iops.as_any().downcast_ref::<HostInodeOp>() {
         None => return Ok(0),
@@ -54,7 +80,7 @@ pub fn SysSyncFileRange(task: &mut Task, args: &SyscallArguments) -> Result<i64>
     match iops.as_any().downcast_ref::<HostInodeOp>() {
         None => return Ok(0),
         Some(h) => {
-            h.SyncFileRange(offset, nbytes, uflags)?;
+            h.SyncFileRange(task, offset, nbytes, uflags)?;
             return Ok(0);
         }
     }
@@ -18,6 +18,7 @@ use super::super::qlib::qmsg::qcall::StatmInfo;
 use super::super::qlib::usage::memory::*;
 use super::super::syscalls::syscalls::*;
 use super::super::task::*;
+use super::super::threadmgr::task_usermem::*;
 
 pub fn SysInfo(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let addr = args.arg0 as u64;
@@ -37,15 +38,13 @@ pub fn SysInfo(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let totalUsage = statm.rss;
     let totalSize = TotalMemory(0, totalUsage);
 
-    //let sysInfo: &mut LibcSysinfo = task.GetTypeMut(addr)?;
     info.procs = task.Thread().PIDNamespace().Tasks().len() as u16;
     info.uptime = Task::MonoTimeNow().Seconds() as i64;
     info.totalram = totalSize; //super::super::ALLOCATOR.Total() as u64;
     info.freeram = totalSize - totalUsage; // super::super::ALLOCATOR.Free() as u64;
     info.mem_unit = 1;
 
-    //*sysInfo = info;
-    task.CopyOutObj(&info, addr)?;
+    UserPtr::New(addr).Write(task, &info)?;
     //error!("SysInfo output is {:?}", &info);
 
     //return Ok(ret)
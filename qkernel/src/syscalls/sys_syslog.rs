@@ -0,0 +1,77 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::super::kernel::kmsg::*;
+use super::super::qlib::common::*;
+use super::super::qlib::linux_def::*;
+use super::super::syscalls::syscalls::*;
+use super::super::task::*;
+use super::super::threadmgr::task_usermem::*;
+
+pub const SYSLOG_ACTION_CLOSE: i32 = 0;
+pub const SYSLOG_ACTION_OPEN: i32 = 1;
+pub const SYSLOG_ACTION_READ: i32 = 2;
+pub const SYSLOG_ACTION_READ_ALL: i32 = 3;
+pub const SYSLOG_ACTION_READ_CLEAR: i32 = 4;
+pub const SYSLOG_ACTION_CLEAR: i32 = 5;
+pub const SYSLOG_ACTION_CONSOLE_OFF: i32 = 6;
+pub const SYSLOG_ACTION_CONSOLE_ON: i32 = 7;
+pub const SYSLOG_ACTION_CONSOLE_LEVEL: i32 = 8;
+pub const SYSLOG_ACTION_SIZE_UNREAD: i32 = 9;
+pub const SYSLOG_ACTION_SIZE_BUFFER: i32 = 10;
+
+// SysSyslog implements syslog(2) over the kernel log ring.
+pub fn SysSyslog(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let command = args.arg0 as i32;
+    let addr = args.arg1 as u64;
+    let size = args.arg2 as i32;
+
+    CheckSyslogPermission(
+        task,
+        command == SYSLOG_ACTION_READ_ALL || command == SYSLOG_ACTION_SIZE_BUFFER,
+    )?;
+
+    match command {
+        SYSLOG_ACTION_READ | SYSLOG_ACTION_READ_ALL | SYSLOG_ACTION_READ_CLEAR => {
+            if size < 0 {
+                return Err(Error::SysError(SysErr::EINVAL));
+            }
+
+            if size == 0 {
+                return Ok(0);
+            }
+
+            let output = match command {
+                SYSLOG_ACTION_READ => KMSG.Read(size as usize),
+                SYSLOG_ACTION_READ_ALL => KMSG.Syslog(size as usize, false),
+                _ => KMSG.Syslog(size as usize, true),
+            };
+
+            UserSlice::New(addr, output.len()).Write(task, output.as_bytes())?;
+            return Ok(output.len() as i64);
+        }
+        SYSLOG_ACTION_CLEAR => {
+            KMSG.Clear();
+            return Ok(0);
+        }
+        SYSLOG_ACTION_CLOSE
+        | SYSLOG_ACTION_OPEN
+        | SYSLOG_ACTION_CONSOLE_OFF
+        | SYSLOG_ACTION_CONSOLE_ON
+        | SYSLOG_ACTION_CONSOLE_LEVEL => return Ok(0),
+        SYSLOG_ACTION_SIZE_UNREAD => return Ok(KMSG.UnreadBytes() as i64),
+        SYSLOG_ACTION_SIZE_BUFFER => return Ok(KMSG_BUF_SIZE as i64),
+        _ => return Err(Error::SysError(SysErr::EINVAL)),
+    }
+}
@@ -23,19 +23,26 @@ pub use xmas_elf::symbol_table::{Entry, Entry64};
 pub use xmas_elf::{P32, P64};
 
 use super::super::asm::*;
+use super::super::kernel::audit::*;
 use super::super::kernel::cpuset::*;
+use super::super::kernel::pidfd::*;
+use super::super::fs::dirent::*;
+use super::super::loader::elf::*;
 use super::super::loader::loader::*;
 use super::super::memmgr::mm::*;
 use super::super::qlib::common::*;
+use super::super::qlib::linux::rusage::*;
 use super::super::qlib::linux_def::*;
 use super::super::qlib::LoadAddr;
 use super::super::syscalls::syscalls::*;
 use super::super::task::*;
+use super::sys_rusage::GetUsage;
 use super::super::taskMgr::*;
 use super::super::threadmgr::task_clone::*;
 use super::super::threadmgr::task_exec::*;
 use super::super::threadmgr::task_exit::*;
 use super::super::threadmgr::task_sched::*;
+use super::super::threadmgr::task_usermem::*;
 use super::super::threadmgr::thread::*;
 use super::super::vcpu::*;
 use super::super::SignalDef::*;
@@ -108,11 +115,9 @@ pub fn SysGetRobustList(task: &mut Task, args: &SyscallArguments) -> Result<i64>
     };
 
     // todo: check whether the current thread has permission to get the RobustList from target thread
-    //*task.GetTypeMut::<u64>(headAddr)? = thread.lock().robust_list_head;
-    //*task.GetTypeMut::<i64>(lenAddr)? = ROBUST_LIST_LEN as i64;
-
-    task.CopyOutObj(&(thread.lock().robust_list_head as u64), headAddr)?;
-    task.CopyOutObj(&(ROBUST_LIST_LEN as i64), lenAddr)?;
+    let head = thread.lock().robust_list_head as u64;
+    UserPtr::<u64>::New(headAddr).Write(task, &head)?;
+    UserPtr::<i64>::New(lenAddr).Write(task, &(ROBUST_LIST_LEN as i64))?;
 
     return Ok(0);
 }
@@ -134,6 +139,102 @@ pub fn SysExecve(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let argvAddr = args.arg1 as u64;
     let envvAddr = args.arg2 as u64;
 
+    return Execveat(task, ATType::AT_FDCWD, filenameAddr, argvAddr, envvAddr, 0);
+}
+
+// Execveat implements linux syscall execveat(2).
+pub fn SysExecveat(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let dirfd = args.arg0 as i32;
+    let filenameAddr = args.arg1 as u64;
+    let argvAddr = args.arg2 as u64;
+    let envvAddr = args.arg3 as u64;
+    let flags = args.arg4 as i32;
+
+    return Execveat(task, dirfd, filenameAddr, argvAddr, envvAddr, flags);
+}
+
+// ResolveExecutable resolves the execveat target. It returns the name seen
+// by the new program and whether the file is reachable by that name after
+// the exec. For a descriptor based exec the name is /dev/fd/N[/path] as on
+// linux, which isn't reachable if the descriptor is close-on-exec.
+fn ResolveExecutable(
+    task: &Task,
+    dirfd: i32,
+    fileName: &str,
+    flags: i32,
+) -> Result<(String, Dirent, bool)> {
+    let resolve = flags & ATType::AT_SYMLINK_NOFOLLOW == 0;
+
+    if fileName.len() == 0 {
+        if flags & ATType::AT_EMPTY_PATH == 0 {
+            return Err(Error::SysError(SysErr::ENOENT));
+        }
+
+        // fexecve, the descriptor may be opened with O_PATH or be a memfd
+        let (file, fdFlags) = task.GetFileAll(dirfd)?;
+        if file.Dirent.Inode().StableAttr().IsDir() {
+            return Err(Error::SysError(SysErr::EACCES));
+        }
+
+        return Ok((
+            format!("/dev/fd/{}", dirfd),
+            file.Dirent.clone(),
+            !fdFlags.CloseOnExec,
+        ));
+    }
+
+    let root = task.Root();
+    let mut remainingTraversals = MAX_SYMLINK_TRAVERSALS;
+    let (name, rel, reachable) = if fileName.as_bytes()[0] == '/' as u8 {
+        (None, None, true)
+    } else if dirfd == ATType::AT_FDCWD {
+        (None, Some(task.Workdir()), true)
+    } else {
+        let (file, fdFlags) = task.GetFileAll(dirfd)?;
+        if !file.Dirent.Inode().StableAttr().IsDir() {
+            return Err(Error::SysError(SysErr::ENOTDIR));
+        }
+
+        (
+            Some(format!("/dev/fd/{}/{}", dirfd, fileName)),
+            Some(file.Dirent.clone()),
+            !fdFlags.CloseOnExec,
+        )
+    };
+
+    let d = task
+        .mountNS
+        .FindDirent(task, &root, rel, fileName, &mut remainingTraversals, resolve)?;
+
+    let attr = d.Inode().StableAttr();
+    if attr.IsSymlink() {
+        return Err(Error::SysError(SysErr::ELOOP));
+    }
+
+    if attr.IsDir() {
+        return Err(Error::SysError(SysErr::EACCES));
+    }
+
+    let name = match name {
+        Some(n) => n,
+        None => d.MyFullName(),
+    };
+
+    return Ok((name, d, reachable));
+}
+
+pub fn Execveat(
+    task: &mut Task,
+    dirfd: i32,
+    filenameAddr: u64,
+    argvAddr: u64,
+    envvAddr: u64,
+    flags: i32,
+) -> Result<i64> {
+    if flags & !(ATType::AT_EMPTY_PATH | ATType::AT_SYMLINK_NOFOLLOW) != 0 {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
     let (fileName, err) = task.CopyInString(filenameAddr, PATH_MAX);
     match err {
         Err(e) => return Err(e),
@@ -159,22 +260,28 @@ pub fn SysExecve(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     }
     info!("in the execve: the cmd is {} \n envs is {:?}", &cmd, &envs);
 
-    let fileName = {
-        let fscontex = task.fsContext.clone();
-        let cwd = fscontex.lock().cwd.clone();
-        let root = fscontex.lock().root.clone();
-        let mut remainingTraversals = 40;
-        let d = task.mountNS.FindDirent(
-            task,
-            &root,
-            Some(cwd),
-            &fileName,
-            &mut remainingTraversals,
-            true,
-        )?;
-        d.MyFullName()
-    };
+    let (fileName, executable, reachable) = ResolveExecutable(task, dirfd, &fileName, flags)?;
+
+    // A script gets its own name as an argument, the interpreter can't open
+    // /dev/fd/N once the close-on-exec descriptor is gone.
+    if !reachable {
+        let (file, _) = OpenDirent(task, &executable)?;
+        let mut magic = [0u8; 2];
+        let n = ReadAll(task, &file, &mut magic, 0)?;
+        if n == 2 && SliceCompare(&magic, INTERPRETER_SCRIPT_MAGIC.as_bytes()) {
+            return Err(Error::SysError(SysErr::ENOENT));
+        }
+    }
 
+    if AuditEnabled() {
+        AuditExecve(task, &fileName, &argv);
+    }
+
+    // Errors in the binary are reported before the point of no return below,
+    // the old image is gone once the new one is being loaded.
+    let (elfFile, elfDirent, argv) = OpenElf(task, &fileName, Some(executable), &mut argv)?;
+
+    let secure;
     {
         let t = task.Thread().clone();
         let tg = t.lock().tg.clone();
@@ -280,14 +387,19 @@ pub fn SysExecve(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
             tg.lock().execed = true;
         }
 
-        let fdtbl = t.lock().fdTbl.clone();
-        fdtbl.lock().RemoveCloseOnExec();
-
         t.ExitRobustList(task);
 
-        t.lock().updateCredsForExecLocked();
+        secure = t.lock().updateCredsForExecLocked();
+        task.creds = t.Creds();
 
-        t.UnstopVforkParent();
+        // The clear_child_tid address is in the old address space, see
+        // Linux's kernel/fork.c:mm_release.
+        if let Some(addr) = task.tidInfo.clear_child_tid.take() {
+            let val: u32 = 0;
+            if UserPtr::New(addr).Write(task, &val).is_ok() {
+                task.futexMgr.Wake(task, addr, false, !0, 1).ok();
+            }
+        }
 
         SetFs(0);
         task.context.fs = 0;
@@ -305,12 +417,46 @@ pub fn SysExecve(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
 
         // make the old mm exist before switch pagetable
         core::mem::drop(oldMM);
+
+        // Close the close-on-exec descriptors along with the old address
+        // space. A table shared through CLONE_FILES is unshared first, the
+        // other process keeps its descriptors.
+        let fdtbl = task.fdTbl.Fork();
+        fdtbl.lock().RemoveCloseOnExec();
+        task.fdTbl = fdtbl.clone();
+        t.lock().fdTbl = fdtbl;
+
+        // The vfork parent can run again only once we stop using its address
+        // space, see Linux's fs/exec.c:exec_mmap.
+        t.UnstopVforkParent();
     }
 
-    let (entry, usersp, kernelsp) = Load(task, &fileName, &mut argv, &envv, &Vec::new())?;
+    let (entry, usersp, kernelsp) = match Load(
+        task,
+        &fileName,
+        &elfFile,
+        &elfDirent,
+        &argv,
+        &envv,
+        &Vec::new(),
+        secure,
+    ) {
+        Err(e) => {
+            // There is nothing to return to, see Linux's fs/exec.c:bprm_execve.
+            info!("SysExecve: loading {} failed with {:?}", &fileName, e);
+            task.Thread().PrepareGroupExit(ExitStatus::New(0, Signal::SIGSEGV));
+            return Err(Error::SysCallRetCtrl(TaskRunState::RunExit));
+        }
+        Ok(r) => r,
+    };
 
     //need to clean object on stack before enter_user as the stack will be destroyed
     task.AccountTaskEnter(SchedState::RunningApp);
+    task.RestoreFp();
+
+    if task.mm.IsCompat() {
+        EnterUser32(entry, usersp, kernelsp);
+    }
 
     EnterUser(entry, usersp, kernelsp);
 
@@ -386,8 +532,8 @@ pub fn SysWaitid(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let idtype = args.arg0 as i32;
     let id = args.arg1 as i32;
     let infop = args.arg2;
-    let options = args.arg3 as u32;
-    let _rusageAddr = args.arg4;
+    let mut options = args.arg3 as u32;
+    let rusageAddr = args.arg4;
 
     if options
         & !(WaitOption::WNOHANG
@@ -413,12 +559,53 @@ pub fn SysWaitid(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
         ..Default::default()
     };
 
+    // A nonblocking pidfd makes the wait nonblocking, and it fails with
+    // EAGAIN instead of returning 0 if the process hasn't changed state.
+    let mut pidfdNonblock = false;
     match idtype {
-        IDType::P_ALL | IDType::P_PID => {
+        IDType::P_ALL => (),
+        IDType::P_PID => {
+            if id <= 0 {
+                return Err(Error::SysError(SysErr::EINVAL));
+            }
             wopts.SpecificTID = id;
         }
         IDType::P_PGID => {
-            wopts.SpecificPGID = id;
+            if id < 0 {
+                return Err(Error::SysError(SysErr::EINVAL));
+            }
+
+            // "P_PGID ... Since Linux 5.4, if id is zero, then wait for any
+            // child that is in the same process group as the caller's process
+            // group at the time of the call." - waitid(2)
+            wopts.SpecificPGID = if id == 0 {
+                let pg = task.Thread().ThreadGroup().ProcessGroup().unwrap();
+                task.Thread().PIDNamespace().IDOfProcessGroup(&pg)
+            } else {
+                id
+            };
+        }
+        IDType::P_PIDFD => {
+            if id < 0 {
+                return Err(Error::SysError(SysErr::EINVAL));
+            }
+
+            let file = task.GetFile(id)?;
+            let tg = match file.FileOp.as_any().downcast_ref::<PidfdOperations>() {
+                None => return Err(Error::SysError(SysErr::EINVAL)),
+                Some(pidfd) => pidfd.tg.clone(),
+            };
+
+            let tid = task.Thread().PIDNamespace().IDOfThreadGroup(&tg);
+            if tid == 0 {
+                return Err(Error::SysError(SysErr::ECHILD));
+            }
+            wopts.SpecificTID = tid;
+
+            if file.Flags().NonBlocking && options & WaitOption::WNOHANG == 0 {
+                options |= WaitOption::WNOHANG;
+                pidfdNonblock = true;
+            }
         }
         _ => return Err(Error::SysError(SysErr::EINVAL)),
     }
@@ -440,9 +627,13 @@ pub fn SysWaitid(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
             // unspecified." - waitid(2). But Linux's waitid actually zeroes
             // out the fields it would set for a successful waitid in this case
             // as well.
+            if pidfdNonblock {
+                return Err(Error::SysError(SysErr::EAGAIN));
+            }
+
             if infop != 0 {
                 let si = SignalInfo::default();
-                task.CopyOutObj(&si, infop)?;
+                UserPtr::New(infop).Write(task, &si)?;
             }
 
             return Ok(0);
@@ -451,10 +642,10 @@ pub fn SysWaitid(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
         Ok(wr) => wr,
     };
 
-    //todo: handle rusageAddr
-    /*if rusageAddr != 0 {
-
-    }*/
+    if rusageAddr != 0 {
+        let ru = GetUsage(&wr.Thread, RUSAGE_BOTH)?;
+        UserPtr::New(rusageAddr).Write(task, &ru)?;
+    }
 
     if infop == 0 {
         return Ok(0);
@@ -476,18 +667,18 @@ pub fn SysWaitid(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     if s.Exited() {
         siCode = SignalInfo::CLD_EXITED;
         sigChld.status = s.ExitStatus();
-    } else if s.Signaled() {
-        siCode = SignalInfo::CLD_KILLED;
-        sigChld.status = s.Signal();
     } else if s.CoreDump() {
         siCode = SignalInfo::CLD_DUMPED;
         sigChld.status = s.Signal();
+    } else if s.Signaled() {
+        siCode = SignalInfo::CLD_KILLED;
+        sigChld.status = s.Signal();
     } else if s.Stopped() {
         if wr.Event == EVENT_TRACEE_STOP {
             siCode = SignalInfo::CLD_TRAPPED;
             sigChld.status = s.TrapCause();
         } else {
-            siCode = SignalInfo::CLD_TRAPPED;
+            siCode = SignalInfo::CLD_STOPPED;
             sigChld.status = s.StopSignal();
         }
     } else if s.Continued() {
@@ -499,11 +690,11 @@ pub fn SysWaitid(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
 
     si.Code = siCode;
 
-    task.CopyOutObj(&si, infop)?;
+    UserPtr::New(infop).Write(task, &si)?;
     return Ok(0);
 }
 
-pub fn wait4(task: &Task, pid: i32, statusAddr: u64, options: u32, _rusage: u64) -> Result<i64> {
+pub fn wait4(task: &Task, pid: i32, statusAddr: u64, options: u32, rusageAddr: u64) -> Result<i64> {
     if options
         & !(WaitOption::WNOHANG
             | WaitOption::WUNTRACED
@@ -554,12 +745,12 @@ pub fn wait4(task: &Task, pid: i32, statusAddr: u64, options: u32, _rusage: u64)
 
     if statusAddr != 0 {
         //task.CopyInObject(statusAddr, &wr.Status as * const _ as u64, 4)?;
-        task.CopyOutObj(&wr.Status, statusAddr)?;
+        UserPtr::New(statusAddr).Write(task, &wr.Status)?;
+    }
+    if rusageAddr != 0 {
+        let ru = GetUsage(&wr.Thread, RUSAGE_BOTH)?;
+        UserPtr::New(rusageAddr).Write(task, &ru)?;
     }
-    //todo: handle rusageAddr
-    /*if rusageAddr != 0 {
-
-    }*/
 
     return Ok(wr.TID as i64);
 }
@@ -664,7 +855,7 @@ pub fn SysSchedSetaffinity(task: &mut Task, args: &SyscallArguments) -> Result<i
         size = mask.Size();
     }
 
-    let arr = task.CopyInVec::<u8>(maskAddr, size)?;
+    let arr = UserSlice::<u8>::New(maskAddr, size).Read(task)?;
     for i in 0..size {
         mask.0[i] = arr[0];
     }
@@ -705,7 +896,7 @@ pub fn SysSchedGetaffinity(task: &mut Task, args: &SyscallArguments) -> Result<i
 
     // info!("SysSchedGetaffinity cpu count is {}", mask.NumCPUs());
 
-    task.CopyOutSlice(&mask.0[..], maskAddr, mask.0.len())?;
+    UserSlice::New(maskAddr, mask.0.len()).Write(task, &mask.0[..])?;
     // NOTE: The syscall interface is slightly different than the glibc
     // interface. The raw sched_getaffinity syscall returns the number of
     // bytes used to represent a cpu mask.
@@ -718,16 +909,12 @@ pub fn SysGetcpu(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let node = args.arg1 as u64;
     // third argument to this system call is nowadays unused.
 
-    if cpu != 0 {
-        let id = task.CPU();
-        //*task.GetTypeMut(cpu)? = id;
-        task.CopyOutObj(&id, cpu)?;
-    }
+    let id = task.CPU();
+    UserPtr::New(cpu).WriteIfNotNull(task, &id)?;
 
     if node != 0 {
-        let val: u32 = 0;
-        //*task.GetTypeMut(node)? = val;
-        task.CopyOutObj(&val, node)?;
+        let val = SHARESPACE.numa.lock().NodeOf(task.CPU() as usize) as u32;
+        UserPtr::<u32>::New(node).Write(task, &val)?;
     }
 
     return Ok(0);
@@ -756,12 +943,31 @@ pub fn SysSetpgid(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
             return Err(Error::SysError(SysErr::EINVAL));
         }
 
-        // Setpgid only operates on child threadgroups.
-        if tg != task.Thread().ThreadGroup()
-            && (tgLeader.Parent().is_none()
-                || tgLeader.Parent().unwrap().ThreadGroup() != task.Thread().ThreadGroup())
-        {
-            return Err(Error::SysError(SysErr::EINVAL));
+        // Setpgid only operates on ourselves and child threadgroups.
+        let localtg = task.Thread().ThreadGroup();
+        if tg != localtg {
+            if tgLeader.Parent().is_none() || tgLeader.Parent().unwrap().ThreadGroup() != localtg {
+                return Err(Error::SysError(SysErr::ESRCH));
+            }
+
+            if tg.Session() != localtg.Session() {
+                return Err(Error::SysError(SysErr::EPERM));
+            }
+
+            // The child has already called execve.
+            if tg.lock().execed {
+                return Err(Error::SysError(SysErr::EACCES));
+            }
+        }
+    }
+
+    // "EPERM An attempt was made to move a process into a process group in a
+    // different session, or to change the process group ID of one of the
+    // children of the calling process and the child was in a different
+    // session, or to change the process group ID of a session leader"
+    if let Some(session) = tg.Session() {
+        if session.lock().leader == tg {
+            return Err(Error::SysError(SysErr::EPERM));
         }
     }
 
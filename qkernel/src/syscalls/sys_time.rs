@@ -14,6 +14,7 @@
 
 use alloc::boxed::Box;
 
+use super::super::kernel::time::*;
 use super::super::kernel::timer::timer::*;
 use super::super::kernel::timer::*;
 use super::super::qlib::common::*;
@@ -23,6 +24,7 @@ use super::super::syscalls::syscalls::*;
 use super::super::task::*;
 use super::super::taskMgr::*;
 use super::super::threadmgr::task_syscall::*;
+use super::super::threadmgr::task_usermem::*;
 use super::super::threadmgr::thread::*;
 use super::super::Kernel::HostSpace;
 use super::sys_poll::TIMEOUT_PROCESS_TIME;
@@ -102,39 +104,47 @@ pub fn GetClock(task: &Task, clockId: i32) -> Result<Clock> {
     }
 
     match clockId {
-        CLOCK_REALTIME | CLOCK_REALTIME_COARSE => return Ok(REALTIME_CLOCK.clone()),
+        CLOCK_REALTIME | CLOCK_REALTIME_COARSE | CLOCK_REALTIME_ALARM => {
+            return Ok(REALTIME_CLOCK.clone())
+        }
 
-        CLOCK_MONOTONIC | CLOCK_MONOTONIC_COARSE | CLOCK_MONOTONIC_RAW | CLOCK_BOOTTIME => {
+        CLOCK_MONOTONIC | CLOCK_MONOTONIC_COARSE | CLOCK_MONOTONIC_RAW => {
             return Ok(MONOTONIC_CLOCK.clone())
         }
 
+        // Unlike the monotonic clock, the boottime counts the time the host
+        // was suspended.
+        CLOCK_BOOTTIME | CLOCK_BOOTTIME_ALARM => return Ok(BOOTTIME_CLOCK.clone()),
+        CLOCK_TAI => return Ok(TAI_CLOCK.clone()),
+
         CLOCK_PROCESS_CPUTIME_ID => return Ok(task.Thread().ThreadGroup().CPUClock()),
         CLOCK_THREAD_CPUTIME_ID => return Ok(task.Thread().CPUClock()),
         _ => return Err(Error::SysError(SysErr::EINVAL)),
     }
 }
 
+// CheckAlarmClock checks that the task may arm timers on the alarm clocks.
+pub fn CheckAlarmClock(task: &Task, clockId: i32) -> Result<()> {
+    if clockId == CLOCK_REALTIME_ALARM || clockId == CLOCK_BOOTTIME_ALARM {
+        if !task.Thread().HasCapability(Capability::CAP_WAKE_ALARM) {
+            return Err(Error::SysError(SysErr::EPERM));
+        }
+    }
+
+    return Ok(());
+}
+
 pub fn SysClockGetRes(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let clockID = args.arg0 as i32;
     let addr = args.arg1 as u64;
 
     GetClock(task, clockID)?;
 
-    if addr == 0 {
-        return Ok(0);
-    }
-
-    /*let ts : &mut Timespec = task.GetTypeMut(addr)?;
-    *ts = Timespec {
-        tv_sec: 0,
-        tv_nsec: 1,
-    };*/
-
     let ts = Timespec {
         tv_sec: 0,
         tv_nsec: 1,
     };
-    task.CopyOutObj(&ts, addr)?;
+    UserPtr::<Timespec>::New(addr).WriteIfNotNull(task, &ts)?;
 
     return Ok(0);
 }
@@ -146,11 +156,9 @@ pub fn SysClockGetTime(task: &mut Task, args: &SyscallArguments) -> Result<i64>
     //let clockID = 1;
 
     let clock = GetClock(task, clockID)?;
-    //let ts : &mut Timespec = task.GetTypeMut(addr)?;
-    //*ts = clock.Now().Timespec();
 
     let ts = clock.Now().Timespec();
-    task.CopyOutObj(&ts, addr)?;
+    UserPtr::<Timespec>::New(addr).Write(task, &ts)?;
     //info!("SysClockGetTime: output is {:?}", ts);
 
     return Ok(0);
@@ -168,10 +176,7 @@ pub fn SysTime(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
         return Ok(now);
     }
 
-    //let ptr : &mut i64 = task.GetTypeMut(addr)?;
-    //*ptr = now;
-
-    task.CopyOutObj(&now, addr)?;
+    UserPtr::<i64>::New(addr).Write(task, &now)?;
 
     return Ok(0);
 }
@@ -180,7 +185,7 @@ pub fn SysNanoSleep(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let req = args.arg0 as u64;
     let rem = args.arg1 as u64;
 
-    let ts: Timespec = task.CopyInObj(req)?;
+    let ts: Timespec = UserPtr::New(req).Read(task)?;
 
     if !ts.IsValid() {
         return Err(Error::SysError(SysErr::EINVAL));
@@ -207,7 +212,7 @@ pub fn SysClockNanosleep(task: &mut Task, args: &SyscallArguments) -> Result<i64
     let addr = args.arg2 as u64;
     let rem = args.arg3 as u64;
 
-    let ts: Timespec = task.CopyInObj(addr)?;
+    let ts: Timespec = UserPtr::New(addr).Read(task)?;
 
     if !ts.IsValid() {
         return Err(Error::SysError(SysErr::EINVAL));
@@ -217,12 +222,20 @@ pub fn SysClockNanosleep(task: &mut Task, args: &SyscallArguments) -> Result<i64
 
     // Only allow clock constants also allowed by Linux.
     if clockID >= 0 {
-        if clockID != CLOCK_REALTIME
-            && clockID != CLOCK_MONOTONIC
-            && clockID != CLOCK_PROCESS_CPUTIME_ID
-        {
-            return Err(Error::SysError(SysErr::EINVAL));
+        match clockID {
+            CLOCK_REALTIME
+            | CLOCK_MONOTONIC
+            | CLOCK_BOOTTIME
+            | CLOCK_TAI
+            | CLOCK_PROCESS_CPUTIME_ID => (),
+            CLOCK_REALTIME_ALARM | CLOCK_BOOTTIME_ALARM => CheckAlarmClock(task, clockID)?,
+            CLOCK_MONOTONIC_RAW | CLOCK_REALTIME_COARSE | CLOCK_MONOTONIC_COARSE => {
+                return Err(Error::SysError(SysErr::EOPNOTSUPP))
+            }
+            _ => return Err(Error::SysError(SysErr::EINVAL)),
         }
+    } else if IsCPUClockPerThread(clockID) {
+        return Err(Error::SysError(SysErr::EINVAL));
     }
 
     let clock = GetClock(task, clockID)?;
@@ -242,25 +255,33 @@ pub fn NansleepUntil(task: &mut Task, clock: Clock, end: i64, rem: u64, needRest
     let timer = task.blocker.GetTimerWithClock(&clock);
 
     let now = clock.Now();
-    let dur = end -now.0;
+    let dur = end - now.0;
 
     if dur < TIMEOUT_PROCESS_TIME {
         Yield();
         return Ok(0);
     }
 
-    let (remaining, res) = task.blocker.BlockWithTimeout(timer, false, Some(dur));
-
-    if rem != 0 && remaining != 0 {
-        let timeleft = Timespec::FromNs(remaining);
-        //*task.GetTypeMut(rem)? = timeleft;
-        task.CopyOutObj(&timeleft, rem)?;
-    }
+    // Keep the deadline on the clock, an absolute sleep on the realtime
+    // clock ends with the new time when the clock is set.
+    let res = task.blocker.BlockWithTimer(timer, false, Some(Time(end)));
 
     match res {
         Err(Error::ErrInterrupted) => {
+            // The absolute sleep restarts with the same deadline and doesn't
+            // report the remaining time.
             if !needRestartBlock {
-                return Err(Error::SysError(SysErr::ERESTART));
+                return Err(Error::SysError(SysErr::ERESTARTNOHAND));
+            }
+
+            let remaining = end - clock.Now().0;
+            if remaining <= 0 {
+                return Ok(0);
+            }
+
+            if rem != 0 {
+                let timeleft = Timespec::FromNs(remaining);
+                UserPtr::<Timespec>::New(rem).Write(task, &timeleft)?;
             }
 
             let b = Box::new(NanosleepRestartBlock {
@@ -306,19 +327,8 @@ pub fn SysGettimeofday(task: &mut Task, args: &SyscallArguments) -> Result<i64>
         return Err(Error::SysError(-ret as i32));
     }
 
-    if tvAddr != 0 {
-        //let tv : &mut Timeval = task.GetTypeMut(tvAddr)?;
-        //*tv = timeV;
-
-        task.CopyOutObj(&timeV, tvAddr)?;
-    }
-
-    if tzAddr != 0 {
-        //let tz : &mut [u32; 2] = task.GetTypeMut(tzAddr)?;
-        //*tz = timezone;
-
-        task.CopyOutObj(&timezone, tzAddr)?;
-    }
+    UserPtr::<Timeval>::New(tvAddr).WriteIfNotNull(task, &timeV)?;
+    UserPtr::<[u32; 2]>::New(tzAddr).WriteIfNotNull(task, &timezone)?;
 
     return Ok(0);
 }
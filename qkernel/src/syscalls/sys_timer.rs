@@ -16,6 +16,7 @@ use super::super::qlib::common::*;
 use super::super::qlib::linux::time::*;
 use super::super::syscalls::syscalls::*;
 use super::super::task::*;
+use super::super::threadmgr::task_usermem::*;
 use super::super::SignalDef::*;
 use super::sys_time::*;
 
@@ -30,7 +31,7 @@ pub fn CopyItimerValIn(task: &Task, addr: u64) -> Result<ItimerVal> {
         return Ok(ItimerVal::default());
     }
 
-    let itv: ItimerVal = task.CopyInObj(addr)?;
+    let itv: ItimerVal = UserPtr::New(addr).Read(task)?;
     return Ok(itv);
 }
 
@@ -42,8 +43,7 @@ pub fn CopyItimerValOut(task: &mut Task, addr: u64, itv: &ItimerVal) -> Result<(
         return Ok(());
     }
 
-    //*task.GetTypeMut(addr)? = *itv;
-    task.CopyOutObj(itv, addr)?;
+    UserPtr::New(addr).Write(task, itv)?;
     return Ok(());
 }
 
@@ -103,18 +103,16 @@ pub fn SysTimerCreate(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let timerIDp = args.arg2 as u64;
 
     let c = GetClock(task, clockID)?;
+    CheckAlarmClock(task, clockID)?;
 
     let mut sev = Sigevent::default();
     if sevp != 0 {
-        sev = task.CopyInObj(sevp)?;
+        sev = UserPtr::New(sevp).Read(task)?;
     }
 
-    let id = task.Thread().IntervalTimerCreate(&c, &mut sev)?;
+    let id = task.Thread().IntervalTimerCreate(&c, clockID, &mut sev)?;
 
-    //let timerID = task.GetTypeMut(timerIDp)?;
-    //*timerID = id;
-
-    task.CopyOutObj(&id, timerIDp)?;
+    UserPtr::New(timerIDp).Write(task, &id)?;
     return Ok(0);
 }
 
@@ -125,14 +123,13 @@ pub fn SysTimerSettime(task: &mut Task, args: &SyscallArguments) -> Result<i64>
     let newValAddr = args.arg2 as u64;
     let oldValAddr = args.arg3 as u64;
 
-    let newVal: Itimerspec = task.CopyInObj(newValAddr)?;
+    let newVal: Itimerspec = UserPtr::New(newValAddr).Read(task)?;
 
     let oldVal =
         task.Thread()
             .IntervalTimerSettime(timerID, &newVal, flags & TIMER_ABSTIME != 0)?;
     if oldValAddr != 0 {
-        //*task.GetTypeMut(oldValAddr)? = oldVal;
-        task.CopyOutObj(&oldVal, oldValAddr)?;
+        UserPtr::New(oldValAddr).Write(task, &oldVal)?;
     }
 
     return Ok(0);
@@ -144,8 +141,7 @@ pub fn SysTimerGettime(task: &mut Task, args: &SyscallArguments) -> Result<i64>
     let curValAddr = args.arg1 as u64;
 
     let curVal = task.Thread().IntervalTimerGettime(timerID)?;
-    //*task.GetTypeMut(curValAddr)? = curVal;
-    task.CopyOutObj(&curVal, curValAddr)?;
+    UserPtr::New(curValAddr).Write(task, &curVal)?;
     return Ok(0);
 }
 
@@ -21,6 +21,8 @@ use super::super::qlib::linux::time::*;
 use super::super::qlib::linux_def::*;
 use super::super::syscalls::syscalls::*;
 use super::super::task::*;
+use super::super::threadmgr::task_usermem::*;
+use super::sys_time::*;
 
 // TimerfdCreate implements Linux syscall timerfd_create(2).
 pub fn SysTimerfdCreate(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
@@ -31,6 +33,7 @@ pub fn SysTimerfdCreate(task: &mut Task, args: &SyscallArguments) -> Result<i64>
         return Err(Error::SysError(SysErr::EINVAL));
     }
 
+    CheckAlarmClock(task, clockID)?;
     let f = NewTimerfd(task, clockID)?;
 
     f.SetFlags(
@@ -71,17 +74,15 @@ pub fn SysTimerfdSettime(task: &mut Task, args: &SyscallArguments) -> Result<i64
         None => return Err(Error::SysError(SysErr::EINVAL)),
     };
 
-    let newVal: Itimerspec = task.CopyInObj(newValAddr)?;
+    let newVal: Itimerspec = UserPtr::New(newValAddr).Read(task)?;
     let clock = tf.Clock();
     let newS = Setting::FromItimerspec(&newVal, flags & TFD_TIMER_ABSTIME != 0, &clock)?;
 
     let (tm, oldS) = tf.SetTime(&newS);
     if oldValAddr != 0 {
         let oldVal = ItimerspecFromSetting(tm, oldS);
-        //let v : &mut Itimerspec = task.GetTypeMut(oldValAddr)?;
-        //*v = oldVal;
 
-        task.CopyOutObj(&oldVal, oldValAddr)?;
+        UserPtr::New(oldValAddr).Write(task, &oldVal)?;
     }
 
     return Ok(0);
@@ -101,10 +102,8 @@ pub fn SysTimerfdGettime(task: &mut Task, args: &SyscallArguments) -> Result<i64
 
     let (tm, s) = tf.GetTime();
     let curVal = ItimerspecFromSetting(tm, s);
-    //let v :&mut Itimerspec = task.GetTypeMut(curValAddr)?;
-    //*v = curVal;
 
-    task.CopyOutObj(&curVal, curValAddr)?;
+    UserPtr::New(curValAddr).Write(task, &curVal)?;
 
     return Ok(0);
 }
@@ -14,11 +14,13 @@
 
 use core::mem;
 
+use super::super::arch::x86_64::arch_x86::*;
 use super::super::arch::x86_64::context::*;
 use super::super::qlib::common::*;
 use super::super::qlib::linux_def::*;
 use super::super::syscalls::syscalls::*;
 use super::super::task::Task;
+use super::super::threadmgr::task_usermem::*;
 use super::super::vcpu::*;
 
 pub fn IsValidSegmentBase(addr: u64) -> bool {
@@ -33,6 +35,8 @@ pub fn SysArchPrctl(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
         && code != PrCtlEnum::ARCH_SET_FS as u64
         && code != PrCtlEnum::ARCH_GET_FS as u64
         && code != PrCtlEnum::ARCH_GET_GS as u64
+        && !(PrCtlEnum::ARCH_SHSTK_ENABLE as u64..=PrCtlEnum::ARCH_SHSTK_STATUS as u64)
+            .contains(&code)
     {
         return Err(Error::SysError(SysErr::EINVAL));
     }
@@ -54,15 +58,67 @@ pub fn SysArchPrctl(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
             //info!("after ARCH_SET_FS, the input value is {:x}, the get fs result is {:x}", addr, ReadMsr(MSR::MSR_FS_BASE as u32));
         }
         PrCtlEnum::ARCH_GET_FS => {
-            //*task.GetTypeMut::<u64>(addr)? = GetFs();
-            task.CopyOutObj(&GetFs(), addr)?;
+            UserPtr::New(addr).Write(task, &GetFs())?;
         }
         PrCtlEnum::ARCH_GET_GS => {
-            //*task.GetTypeMut::<u64>(addr)? = GetGs();
-            task.CopyOutObj(&GetGs(), addr)?;
+            UserPtr::New(addr).Write(task, &GetGs())?;
             //unsafe {*(addr as *mut u64) = ReadMsr(MSR::MSR_KERNEL_GS_BASE as u32)}
         }
+        // The guest kernel doesn't turn on CET, so no shadow stack feature is
+        // ever enabled. The status and the lock work like on a linux without
+        // user shadow stacks, so a SHSTK marked binary falls back to run
+        // without it.
+        PrCtlEnum::ARCH_SHSTK_STATUS => {
+            let features: u64 = 0;
+            UserPtr::New(addr).Write(task, &features)?;
+        }
+        PrCtlEnum::ARCH_SHSTK_LOCK => (),
+        PrCtlEnum::ARCH_SHSTK_UNLOCK => {
+            // only allowed through ptrace
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+        PrCtlEnum::ARCH_SHSTK_ENABLE => {
+            if addr & !(ARCH_SHSTK_SHSTK | ARCH_SHSTK_WRSS) != 0 {
+                return Err(Error::SysError(SysErr::EINVAL));
+            }
+
+            info!("ARCH_SHSTK_ENABLE {:x}: no user shadow stack support", addr);
+            return Err(Error::SysError(SysErr::EOPNOTSUPP));
+        }
+        PrCtlEnum::ARCH_SHSTK_DISABLE => {
+            if addr & !(ARCH_SHSTK_SHSTK | ARCH_SHSTK_WRSS) != 0 {
+                return Err(Error::SysError(SysErr::EINVAL));
+            }
+        }
     }
 
     return Ok(0);
 }
+
+// SysSetThreadArea implements the ia32 set_thread_area(2), the descriptor is
+// loaded at once in the gdt of the current vcpu.
+pub fn SysSetThreadArea(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let addr = args.arg0 as u64;
+
+    let mut info: UserDesc = UserPtr::New(addr).Read(task)?;
+    let allocate = info.entry_number == u32::MAX;
+    task.context.tls32.SetDesc(&mut info)?;
+    if allocate {
+        UserPtr::New(addr).Write(task, &info.entry_number)?;
+    }
+
+    // the selectors in use are still the ones of the task
+    task.context.tls32.Save();
+    task.context.tls32.Load();
+    return Ok(0);
+}
+
+// SysGetThreadArea implements the ia32 get_thread_area(2).
+pub fn SysGetThreadArea(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let addr = args.arg0 as u64;
+
+    let entry: u32 = UserPtr::New(addr).Read(task)?;
+    let info = task.context.tls32.GetDesc(entry)?;
+    UserPtr::New(addr).Write(task, &info)?;
+    return Ok(0);
+}
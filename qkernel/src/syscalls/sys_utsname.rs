@@ -13,11 +13,15 @@
 // limitations under the License.
 
 use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
 
 use super::super::qlib::common::*;
+use super::super::qlib::kernel::kernel::uts_namespace::*;
 use super::super::qlib::linux_def::*;
 use super::super::syscalls::syscalls::*;
 use super::super::task::*;
+use super::super::threadmgr::task_usermem::*;
 use super::super::version::*;
 
 // UTSLen is the maximum length of strings contained in fields of
@@ -90,10 +94,7 @@ pub fn SysUname(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     u.Machine[0.."x86_64".len()].clone_from_slice("x86_64".as_bytes());
     u.Domainname[0..uts.DomainName().len()].clone_from_slice(uts.DomainName().as_bytes());
 
-    //let va : &mut UtsName = task.GetTypeMut(va)?;
-    //*va = u;
-
-    task.CopyOutObj(&u, va)?;
+    UserPtr::New(va).Write(task, &u)?;
 
     /*info!("Sysname is {}", UtsNameString(&va.Sysname));
     info!("Nodename is {}", UtsNameString(&va.Nodename));
@@ -105,30 +106,34 @@ pub fn SysUname(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     return Ok(0);
 }
 
-// Setdomainname implements Linux syscall setdomainname.
-pub fn SysSetdomainname(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
-    let nameAddr = args.arg0 as u64;
-    let size = args.arg1 as i32;
-
-    let utsns = task.Thread().UTSNamespace();
-    {
-        let creds = task.Creds();
-        let userNS = creds.lock().UserNamespace.clone();
-        if !creds.HasCapabilityIn(Capability::CAP_SYS_ADMIN, &userNS) {
-            return Err(Error::SysError(SysErr::EPERM));
-        }
+// CopyInUTSName checks the caller may change the names of its uts namespace
+// and copies in the new name of exactly size bytes, which needn't be NUL
+// terminated.
+fn CopyInUTSName(task: &Task, utsns: &UTSNamespace, addr: u64, size: i32) -> Result<String> {
+    let creds = task.Creds();
+    if !creds.HasCapabilityIn(Capability::CAP_SYS_ADMIN, &utsns.UserNamespace()) {
+        return Err(Error::SysError(SysErr::EPERM));
     }
 
     if size < 0 || size > UTS_LEN as i32 {
         return Err(Error::SysError(SysErr::EINVAL));
     }
 
-    let (name, err) = task.CopyInString(nameAddr, UTS_LEN - 1);
-    match err {
-        Ok(()) => (),
-        Err(e) => return Err(e),
-    };
+    let mut buf: Vec<u8> = UserSlice::New(addr, size as usize).Read(task)?;
+    if let Some(end) = buf.iter().position(|c| *c == 0) {
+        buf.truncate(end);
+    }
+
+    return Ok(String::from_utf8_lossy(&buf).to_string());
+}
 
+// Setdomainname implements Linux syscall setdomainname.
+pub fn SysSetdomainname(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
+    let nameAddr = args.arg0 as u64;
+    let size = args.arg1 as i32;
+
+    let utsns = task.Thread().UTSNamespace();
+    let name = CopyInUTSName(task, &utsns, nameAddr, size)?;
     utsns.SetDomainName(name);
     return Ok(0);
 }
@@ -139,24 +144,7 @@ pub fn SysSethostname(task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     let size = args.arg1 as i32;
 
     let utsns = task.Thread().UTSNamespace();
-    {
-        let creds = task.Creds();
-        let userNS = creds.lock().UserNamespace.clone();
-        if !creds.HasCapabilityIn(Capability::CAP_SYS_ADMIN, &userNS) {
-            return Err(Error::SysError(SysErr::EPERM));
-        }
-    }
-
-    if size < 0 || size > UTS_LEN as i32 {
-        return Err(Error::SysError(SysErr::EINVAL));
-    }
-
-    let (name, err) = task.CopyInString(nameAddr, UTS_LEN - 1);
-    match err {
-        Ok(()) => (),
-        Err(e) => return Err(e),
-    };
-
+    let name = CopyInUTSName(task, &utsns, nameAddr, size)?;
     utsns.SetHostName(name);
     return Ok(0);
 }
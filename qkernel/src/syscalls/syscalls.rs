@@ -17,13 +17,18 @@ use super::super::syscalls::sys_capability::*;
 use super::super::syscalls::sys_chmod::*;
 use super::super::syscalls::sys_epoll::*;
 use super::super::syscalls::sys_eventfd::*;
+use super::super::syscalls::sys_fhandle::*;
 use super::super::syscalls::sys_file::*;
 use super::super::syscalls::sys_futex::*;
 use super::super::syscalls::sys_getdents::*;
 use super::super::syscalls::sys_identity::*;
+use super::super::syscalls::sys_ioprio::*;
 use super::super::syscalls::sys_membarrier::*;
 use super::super::syscalls::sys_mempolicy::*;
 use super::super::syscalls::sys_mmap::*;
+use super::super::syscalls::sys_mount::*;
+use super::super::syscalls::sys_mqueue::*;
+use super::super::syscalls::sys_msgqueue::*;
 use super::super::syscalls::sys_pipe::*;
 use super::super::syscalls::sys_poll::*;
 use super::super::syscalls::sys_prctl::*;
@@ -31,13 +36,17 @@ use super::super::syscalls::sys_random::*;
 use super::super::syscalls::sys_read::*;
 use super::super::syscalls::sys_rlimit::*;
 use super::super::syscalls::sys_rusage::*;
+use super::super::syscalls::sys_sem::*;
+use super::super::syscalls::sys_shm::*;
 use super::super::syscalls::sys_signal::*;
 use super::super::syscalls::sys_socket::*;
 use super::super::syscalls::sys_splice::*;
 use super::super::syscalls::sys_stat::*;
 use super::super::syscalls::sys_sync::*;
 use super::super::syscalls::sys_sysinfo::*;
+use super::super::syscalls::sys_syslog::*;
 use super::super::syscalls::sys_thread::*;
+use super::super::syscalls::sys_pidfd::*;
 use super::super::syscalls::sys_time::*;
 use super::super::syscalls::sys_timer::*;
 use super::super::syscalls::sys_timerfd::*;
@@ -46,10 +55,102 @@ use super::super::syscalls::sys_utsname::*;
 use super::super::syscalls::sys_write::*;
 use super::super::syscalls::sys_memfd::*;
 
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use super::super::kernel::audit::*;
 use super::super::qlib::common::*;
 use super::super::qlib::linux_def::*;
+use super::super::qlib::metric::*;
+use super::super::qlib::singleton::*;
 use super::super::qlib::SysCallID;
 use super::super::task::*;
+use super::super::LoadVcpuFreq;
+use super::super::SignalDef::*;
+use super::super::SHARESPACE;
+use super::super::TSC;
+
+// SyscallMetrics are the metrics of a syscall, they are only registered when
+// the SyscallMetrics config is set
+pub struct SyscallMetrics {
+    pub calls: Arc<U64Metric>,
+    // latency is the histogram of the time spent in the syscall function in ns
+    pub latency: Arc<HistogramMetric>,
+}
+
+impl SyscallMetrics {
+    pub fn Record(&self, cycles: i64) {
+        self.calls.Incr();
+        let ns = cycles.max(0) as u128 * 1_000_000_000 / LoadVcpuFreq() as u128;
+        self.latency.Record(ns as u64);
+    }
+}
+
+// SyscallStat is the state of a syscall, SYSCALL_STATS is indexed by the
+// syscall number like SYS_CALL_TABLE
+pub struct SyscallStat {
+    pub metrics: Option<SyscallMetrics>,
+    // stub is set when the SyscallStub config stubs the syscall
+    pub stub: bool,
+    // stubLogged is set by the first call of a stubbed syscall
+    pub stubLogged: AtomicBool,
+}
+
+pub static SYSCALL_STATS: Singleton<Vec<SyscallStat>> = Singleton::<Vec<SyscallStat>>::New();
+
+// the metrics are registered after qlib::InitSingleton
+pub unsafe fn InitSingleton() {
+    let stubEnabled = SHARESPACE.config.read().SyscallStub;
+    let metricsEnabled = SHARESPACE.config.read().SyscallMetrics;
+    let mut stats = Vec::with_capacity(SYS_CALL_TABLE.len());
+    for nr in 0..SYS_CALL_TABLE.len() {
+        let metrics = if metricsEnabled {
+            let callId: SysCallID = core::mem::transmute(nr as u64);
+            let calls = NewU64Metric(
+                &format!("/syscalls/{:?}/calls", callId),
+                false,
+                "Number of the calls of the syscall.",
+            );
+            let latency = NewHistogramMetric(
+                &format!("/syscalls/{:?}/latency", callId),
+                false,
+                "Latency of the syscall in ns.",
+            );
+            Some(SyscallMetrics {
+                calls: calls,
+                latency: latency,
+            })
+        } else {
+            None
+        };
+
+        stats.push(SyscallStat {
+            metrics: metrics,
+            stub: stubEnabled && IsRareSyscall(nr),
+            stubLogged: AtomicBool::new(false),
+        });
+    }
+
+    SYSCALL_STATS.Init(stats);
+}
+
+// RARE_SYSCALLS are the implemented syscalls which the common workloads don't
+// call, the SyscallStub config stubs them together with the unimplemented ones
+pub const RARE_SYSCALLS: &[SysCallID] = &[
+    SysCallID::sys_mbind,
+    SysCallID::sys_set_mempolicy,
+    SysCallID::sys_get_mempolicy,
+];
+
+pub fn IsRareSyscall(nr: usize) -> bool {
+    let notImplement = NotImplementSyscall as SyscallFn as usize;
+    if SYS_CALL_TABLE[nr] as usize == notImplement {
+        return true;
+    }
+
+    return RARE_SYSCALLS.iter().any(|id| *id as usize == nr);
+}
 
 //#[repr(align(128))]
 #[derive(Debug)]
@@ -62,11 +163,55 @@ pub struct SyscallArguments {
     pub arg5: u64,
 }
 
+impl SyscallArguments {
+    pub fn Array(&self) -> [u64; 6] {
+        return [
+            self.arg0, self.arg1, self.arg2, self.arg3, self.arg4, self.arg5,
+        ];
+    }
+}
+
 #[inline]
 pub fn SysCall(task: &mut Task, nr: u64, args: &SyscallArguments) -> TaskRunState {
     let idx = nr as usize;
     let func = SYS_CALL_TABLE.get(idx).unwrap();
-    match func(task, args) {
+    let stat = &SYSCALL_STATS[idx];
+    let res = match &stat.metrics {
+        None => SyscallRun(task, nr, args, stat.stub, func),
+        Some(metrics) => {
+            let start = TSC.Rdtsc();
+            let res = SyscallRun(task, nr, args, stat.stub, func);
+            metrics.Record(TSC.Rdtsc() - start);
+            res
+        }
+    };
+    if AuditEnabled() {
+        AuditSyscall(task, nr, &args.Array(), &res);
+    }
+
+    return SysCallResult(task, nr, res);
+}
+
+#[inline]
+fn SyscallRun(
+    task: &mut Task,
+    nr: u64,
+    args: &SyscallArguments,
+    stub: bool,
+    func: &SyscallFn,
+) -> Result<i64> {
+    if stub {
+        return SysStub(task, nr, args);
+    }
+
+    return func(task, args);
+}
+
+// SysCallResult stores the syscall result for the return to the user and
+// returns the next state of the task.
+#[inline]
+pub fn SysCallResult(task: &mut Task, nr: u64, res: Result<i64>) -> TaskRunState {
+    match res {
         Err(Error::SysCallRetCtrlWithRet(state, ret)) => {
             task.SetReturn(ret);
             return state;
@@ -83,6 +228,15 @@ pub fn SysCall(task: &mut Task, nr: u64, args: &SyscallArguments) -> TaskRunStat
             task.SetReturn(-e as u64);
             return TaskRunState::RunApp;
         }
+        Err(Error::ErrExceedsFileSizeLimit) => {
+            // A write past RLIMIT_FSIZE raises SIGXFSZ and fails with EFBIG
+            // when the signal doesn't kill the task.
+            let info = SignalInfo::SignalInfoPriv(Signal(Signal::SIGXFSZ));
+            task.Thread().SendSignal(&info).expect("SysCallResult send SIGXFSZ fail");
+            task.haveSyscallReturn = true;
+            task.SetReturn(-SysErr::EFBIG as u64);
+            return TaskRunState::RunApp;
+        }
         Err(Error::SysCallNotImplement) => {
             let callId: SysCallID = unsafe { core::mem::transmute(nr as u64) };
             panic!("Sycall not implement syscall is {:?}", callId);
@@ -126,9 +280,9 @@ pub const SYS_CALL_TABLE: &'static [SyscallFn] = &[
     SysMsync,            //sys_msync,
     SysMincore,          //sys_mincore,
     SysMadvise,          //sys_madvise,
-    NotImplementSyscall, //sys_shmget,
-    NotImplementSyscall, //sys_shmat,   //30
-    NotImplementSyscall, //sys_shmctl,
+    SysShmget,           //sys_shmget,
+    SysShmat,            //sys_shmat,   //30
+    SysShmctl,           //sys_shmctl,
     SysDup,              //sys_dup,
     SysDup2,             //sys_dup2,
     SysPause,            //sys_pause,
@@ -161,14 +315,14 @@ pub const SYS_CALL_TABLE: &'static [SyscallFn] = &[
     SysWait4,            //sys_wait4,
     SysKill,             //sys_kill,
     SysUname,            //sys_uname,
-    NotImplementSyscall, //sys_semget,
-    NotImplementSyscall, //sys_semop,
-    NotImplementSyscall, //sys_semctl,
-    NotImplementSyscall, //sys_shmdt,
-    NotImplementSyscall, //sys_msgget,
-    NotImplementSyscall, //sys_msgsnd,
-    NotImplementSyscall, //sys_msgrcv,    //70
-    NotImplementSyscall, //sys_msgctl,
+    SysSemget,           //sys_semget,
+    SysSemop,            //sys_semop,
+    SysSemctl,           //sys_semctl,
+    SysShmdt,            //sys_shmdt,
+    SysMsgget,           //sys_msgget,
+    SysMsgsnd,           //sys_msgsnd,
+    SysMsgrcv,           //sys_msgrcv,    //70
+    SysMsgctl,           //sys_msgctl,
     SysFcntl,            //sys_fcntl,
     SysFlock,            //sys_flock,
     SysFsync,            //sys_fsync,
@@ -200,7 +354,7 @@ pub const SYS_CALL_TABLE: &'static [SyscallFn] = &[
     SysTimes,            //sys_times,    //100
     SysNoSupport,        //sys_ptrace,
     SysGetuid,           //sys_getuid,
-    NotImplementSyscall, //sys_syslog,
+    SysSyslog,           //sys_syslog,
     SysGetgid,           //sys_getgid,
     SysSetuid,           //sys_setuid,
     SysSetgid,           //sys_setgid,
@@ -262,8 +416,8 @@ pub const SYS_CALL_TABLE: &'static [SyscallFn] = &[
     SysSync,             //sys_sync,
     NotImplementSyscall, //sys_acct,
     NotImplementSyscall, //sys_settimeofday,
-    NotImplementSyscall, //sys_mount,
-    NotImplementSyscall, //sys_umount2,
+    SysMount,            //sys_mount,
+    SysUmount2,          //sys_umount2,
     NotImplementSyscall, //sys_swapon,
     NotImplementSyscall, //sys_swapoff,
     NotImplementSyscall, //sys_reboot,
@@ -317,7 +471,7 @@ pub const SYS_CALL_TABLE: &'static [SyscallFn] = &[
     SysGetDents64,       //sys_getdents64,
     SysSetTidAddr,       //sys_set_tid_address,
     SysRestartSyscall,   //sys_restart_syscall,
-    NotImplementSyscall, //sys_semtimedop,    //220
+    SysSemtimedop,       //sys_semtimedop,    //220
     SysFadvise64,        //sys_fadvise64,
     SysTimerCreate,      //sys_timer_create,
     SysTimerSettime,     //sys_timer_settime,
@@ -337,19 +491,19 @@ pub const SYS_CALL_TABLE: &'static [SyscallFn] = &[
     SysMbind,            //sys_mbind, just workaround
     SysSetMempolicy,     //sys_set_mempolicy,
     SysGetMempolicy,     //sys_get_mempolicy,
-    SysNoSupport,       //sys_mq_open,    //240
-    SysNoSupport,       //sys_mq_unlink,
-    SysNoSupport,       //sys_mq_timedsend,
-    SysNoSupport,       //sys_mq_timedreceive,
-    SysNoSupport,       //sys_mq_notify,
-    SysNoSupport,       //sys_mq_getsetattr,
+    SysMqOpen,           //sys_mq_open,    //240
+    SysMqUnlink,         //sys_mq_unlink,
+    SysMqTimedsend,      //sys_mq_timedsend,
+    SysMqTimedreceive,   //sys_mq_timedreceive,
+    SysMqNotify,         //sys_mq_notify,
+    SysMqGetsetattr,     //sys_mq_getsetattr,
     NotImplementSyscall, //sys_kexec_load,
     SysWaitid,           //sys_waitid,
     NotImplementSyscall, //sys_add_key,
     NotImplementSyscall, //sys_request_key,
     NotImplementSyscall, //sys_keyctl,    //250
-    NotImplementSyscall, //sys_ioprio_set,
-    NotImplementSyscall, //sys_ioprio_get,
+    SysIoprioSet,        //sys_ioprio_set,
+    SysIoprioGet,        //sys_ioprio_get,
     NotImplementSyscall, //sys_inotify_init,
     NotImplementSyscall, //sys_inotify_add_watch,
     NotImplementSyscall, //sys_inotify_rm_watch,
@@ -373,7 +527,7 @@ pub const SYS_CALL_TABLE: &'static [SyscallFn] = &[
     SysSetRobustList,    //sys_set_robust_list,
     SysGetRobustList,    //sys_get_robust_list,
     SysSplice,           //sys_splice,
-    NotImplementSyscall, //sys_tee,
+    SysTee,              //sys_tee,
     SysSyncFileRange,    //sys_sync_file_range,
     NotImplementSyscall, //sys_vmsplice,
     NotImplementSyscall, //sys_move_pages,
@@ -400,8 +554,8 @@ pub const SYS_CALL_TABLE: &'static [SyscallFn] = &[
     NotImplementSyscall, //sys_fanotify_init,  //300
     NotImplementSyscall, //sys_fanotify_mark,
     SysPrlimit64,        //sys_prlimit64,
-    NotImplementSyscall, //sys_name_to_handle_at,
-    NotImplementSyscall, //sys_open_by_handle_at,
+    SysNameToHandleAt,   //sys_name_to_handle_at,
+    SysOpenByHandleAt,   //sys_open_by_handle_at,
     NotImplementSyscall, //sys_clock_adjtime,
     SysSyncFs,           //sys_syncfs,
     SysSendMMsg,         //sys_sendmmsg,
@@ -413,22 +567,22 @@ pub const SYS_CALL_TABLE: &'static [SyscallFn] = &[
     NotImplementSyscall, //sys_finit_module,
     NotImplementSyscall, //sys_sched_setattr,
     NotImplementSyscall, //sys_sched_getattr,
-    SysNoSupport, //sys_renameat2,
+    SysRenameat2,        //sys_renameat2,
     NotImplementSyscall, //sys_seccomp,
     SysGetRandom,        //sys_getrandom,
     SysMemfdCreate,     //sys_memfd_create,
     NotImplementSyscall, //sys_kexec_file_load,//320
     NotImplementSyscall, //sys_bpf,
-    NotImplementSyscall, //sys_stub_execveat,
+    SysExecveat,         //sys_stub_execveat,
     NotImplementSyscall, //sys_userfaultfd,
     SysMembarrier,       //sys_membarrier,
     SysMlock2,           //mlock2,
     SysNoSys,            //sys_copy_file_range,
     SysPreadv2,          //sys_preadv2,
     SysPWritev2,         //sys_pwritev2,
-    NotImplementSyscall, //sys_pkey_mprotect,
-    NotImplementSyscall, //sys_pkey_alloc,//330
-    NotImplementSyscall, //sys_pkey_free, 331
+    SysPkeyMprotect,     //sys_pkey_mprotect,
+    SysPkeyAlloc,        //sys_pkey_alloc,//330
+    SysPkeyFree,         //sys_pkey_free, 331
     SysStatx,            //sys_statx, 332
     NotImplementSyscall, //	333
     NotImplementSyscall, //	334
@@ -525,13 +679,13 @@ pub const SYS_CALL_TABLE: &'static [SyscallFn] = &[
     NotImplementSyscall, //	425
     NotImplementSyscall, //	426
     NotImplementSyscall, //	427
-    NotImplementSyscall, //	428
-    NotImplementSyscall, //	429
-    NotImplementSyscall, //	430
-    NotImplementSyscall, //	431
-    NotImplementSyscall, //	432
+    SysOpenTree,         //	428 sys_open_tree
+    SysMoveMount,        //	429 sys_move_mount
+    SysFsopen,           //	430 sys_fsopen
+    SysFsconfig,         //	431 sys_fsconfig
+    SysFsmount,          //	432 sys_fsmount
     NotImplementSyscall, //	433
-    NotImplementSyscall, //	434
+    SysPidfdOpen,        //	434 sys_pidfd_open
     NotImplementSyscall, //	435
     NotImplementSyscall, //	436
     NotImplementSyscall, //	437
@@ -559,6 +713,24 @@ pub fn SysObsolete(_task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     //return Err(Error::SysError(SysErr::ENOTSUP));
 }
 
+// SysStub fails the syscalls stubbed by the SyscallStub config with ENOSYS.
+// The first call of each is logged to show what the workload needs, the
+// /syscalls/<name>/calls metric counts all of them when SyscallMetrics is set.
+pub fn SysStub(task: &mut Task, nr: u64, args: &SyscallArguments) -> Result<i64> {
+    let stat = &SYSCALL_STATS[nr as usize];
+    if !stat.stubLogged.swap(true, Ordering::Relaxed) {
+        let callId: SysCallID = unsafe { core::mem::transmute(nr) };
+        error!(
+            "stub syscall {:?} called by pid {} {:x?}",
+            callId,
+            task.Thread().ThreadGroup().ID(),
+            args
+        );
+    }
+
+    return Err(Error::SysError(SysErr::ENOSYS));
+}
+
 pub fn SysNoSys(_task: &mut Task, args: &SyscallArguments) -> Result<i64> {
     error!("No support syscall {:x?}", args);
     return Err(Error::SysError(SysErr::ENOSYS));
@@ -285,6 +285,13 @@ impl PageOpts {
         return self;
     }
 
+    // SetPkey sets the memory protection key in the bits 59..62 of the entry
+    pub fn SetPkey(&mut self, pkey: i32) -> &mut Self {
+        let bits = self.0.bits() & !(0xf << 59) | ((pkey as u64 & 0xf) << 59);
+        self.0 = PageTableFlags::from_bits_truncate(bits);
+        return self;
+    }
+
     pub fn Val(&self) -> PageTableFlags {
         return self.0;
     }
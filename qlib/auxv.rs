@@ -70,8 +70,16 @@ pub enum AuxVec {
     // AT_EXECFN is the path used to execute the program.
     AT_EXECFN = 31,
 
+    // AT_SYSINFO is the address of __kernel_vsyscall in the VDSO of a 32 bit
+    // process.
+    AT_SYSINFO = 32,
+
     // AT_SYSINFO_EHDR is the address of the VDSO.
     AT_SYSINFO_EHDR = 33,
+
+    // AT_MINSIGSTKSZ is the minimal stack size required by the kernel to
+    // deliver a signal to user space.
+    AT_MINSIGSTKSZ = 51,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -46,34 +46,38 @@ pub enum RingeBufAllocator {
     HeapAllocator,
     //ShareAllocator {headTailAddr: u64, bufAddr: u64},
     ShareAllocator (u64, u64),
+    // ShareAttachAllocator is a ring in share memory which is in use already,
+    // its head and tail are kept
+    ShareAttachAllocator (u64, u64),
 }
 
 impl RingeBufAllocator {
     pub fn AllocHeadTail(&self) -> &'static [AtomicU32] {
         match self {
             Self::HeapAllocator => return HeapAllocator::AllocHeadTail(),
-            Self::ShareAllocator(headTailAddr, _) => return ShareAllocator::AllocHeadTail(*headTailAddr)
+            Self::ShareAllocator(headTailAddr, _) => return ShareAllocator::AllocHeadTail(*headTailAddr),
+            Self::ShareAttachAllocator(headTailAddr, _) => return ShareAllocator::AttachHeadTail(*headTailAddr)
         }
     }
 
     pub fn FreeHeadTail(&self, data: &'static [AtomicU32]) {
         match self {
             Self::HeapAllocator => return HeapAllocator::FreeHeadTail(data),
-            Self::ShareAllocator(_, _) => return ShareAllocator::FreeHeadTail(data)
+            Self::ShareAllocator(_, _) | Self::ShareAttachAllocator(_, _) => return ShareAllocator::FreeHeadTail(data)
         }
     }
 
     pub fn AlllocBuf(&self, pageCount: usize) -> u64 {
         match self {
             Self::HeapAllocator => return HeapAllocator::AlllocBuf(pageCount),
-            Self::ShareAllocator(_, buffAddr) => return ShareAllocator::AlllocBuf(*buffAddr)
+            Self::ShareAllocator(_, buffAddr) | Self::ShareAttachAllocator(_, buffAddr) => return ShareAllocator::AlllocBuf(*buffAddr)
         }
     }
 
     pub fn FreeBuf(&self, addr: u64, size: usize) {
         match self {
             Self::HeapAllocator => return HeapAllocator::FreeBuf(addr, size),
-            Self::ShareAllocator(_, _) => return ShareAllocator::FreeBuf(addr, size)
+            Self::ShareAllocator(_, _) | Self::ShareAttachAllocator(_, _) => return ShareAllocator::FreeBuf(addr, size)
         }
     }
 }
@@ -142,6 +146,12 @@ impl ShareAllocator {
         return slice
      }
 
+    pub fn AttachHeadTail(headTailAddr: u64) -> &'static [AtomicU32] {
+        let ptr = headTailAddr as *mut AtomicU32;
+        let slice = unsafe { slice::from_raw_parts(ptr, 2 as usize) };
+        return slice
+    }
+
     pub fn FreeHeadTail(_data: &'static [AtomicU32]) {
         // println!("ShareAllocator::FreeHeadTail");
     }
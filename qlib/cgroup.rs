@@ -0,0 +1,134 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+// the cpu.max period when the spec doesn't give one, it is the linux default
+pub const CPU_PERIOD_DEFAULT: u64 = 100_000;
+pub const CPU_WEIGHT_DEFAULT: u64 = 100;
+
+// the cgroup v2 controllers limited by the sandbox
+pub const CGROUP_CONTROLLERS: &str = "cpuset cpu io memory pids";
+
+// IoMax is the io.max limits of a block device, 0 is unlimited
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct IoMax {
+    pub major: i64,
+    pub minor: i64,
+    pub rbps: u64,
+    pub wbps: u64,
+    pub riops: u64,
+    pub wiops: u64,
+}
+
+impl IoMax {
+    pub fn String(&self) -> String {
+        return format!(
+            "{}:{} rbps={} wbps={} riops={} wiops={}",
+            self.major,
+            self.minor,
+            MaxString(self.rbps),
+            MaxString(self.wbps),
+            MaxString(self.riops),
+            MaxString(self.wiops)
+        );
+    }
+}
+
+// CgroupLimits are the resource limits of the sandbox in the cgroup v2 format.
+// qvisor gets them from the resources of the OCI spec and sets them on the host
+// cgroup, qkernel shows the same limits in the guest /sys/fs/cgroup.
+#[derive(Debug, Default, Clone)]
+pub struct CgroupLimits {
+    // CpuQuota is the cpu time in us for each CpuPeriod, 0 is unlimited
+    pub CpuQuota: u64,
+    pub CpuPeriod: u64,
+    // CpuWeight is 0 if the spec doesn't give the cpu shares
+    pub CpuWeight: u64,
+    // the memory limits are in bytes, 0 is unlimited
+    pub MemoryMax: u64,
+    pub MemoryLow: u64,
+    // SwapMax is None if the swap is unlimited
+    pub SwapMax: Option<u64>,
+    // Cpus and Mems are the host cpus and numa nodes of the sandbox
+    pub Cpus: String,
+    pub Mems: String,
+    pub IoMax: Vec<IoMax>,
+    pub PidsMax: u64,
+}
+
+impl CgroupLimits {
+    // SharesToWeight converts the cgroup v1 cpu.shares [2, 262144] into the
+    // cgroup v2 cpu.weight [1, 10000] as runc does.
+    pub fn SharesToWeight(shares: u64) -> u64 {
+        if shares == 0 {
+            return 0;
+        }
+
+        let shares = core::cmp::min(core::cmp::max(shares, 2), 262144);
+        return 1 + ((shares - 2) * 9999) / 262142;
+    }
+
+    pub fn CpuMax(&self) -> String {
+        let period = if self.CpuPeriod == 0 {
+            CPU_PERIOD_DEFAULT
+        } else {
+            self.CpuPeriod
+        };
+
+        return format!("{} {}", MaxString(self.CpuQuota), period);
+    }
+
+    pub fn CpuWeight(&self) -> String {
+        if self.CpuWeight == 0 {
+            return format!("{}", CPU_WEIGHT_DEFAULT);
+        }
+
+        return format!("{}", self.CpuWeight);
+    }
+
+    pub fn MemoryMax(&self) -> String {
+        return MaxString(self.MemoryMax);
+    }
+
+    pub fn MemoryLow(&self) -> String {
+        return format!("{}", self.MemoryLow);
+    }
+
+    pub fn SwapMax(&self) -> String {
+        return match self.SwapMax {
+            None => "max".to_string(),
+            Some(swap) => format!("{}", swap),
+        };
+    }
+
+    pub fn PidsMax(&self) -> String {
+        return MaxString(self.PidsMax);
+    }
+
+    // IoMax is the content of io.max, one line for each device
+    pub fn IoMax(&self) -> String {
+        let lines: Vec<String> = self.IoMax.iter().map(|io| io.String()).collect();
+        return lines.join("\n");
+    }
+}
+
+pub fn MaxString(val: u64) -> String {
+    if val == 0 {
+        return "max".to_string();
+    }
+
+    return format!("{}", val);
+}
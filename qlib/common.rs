@@ -23,12 +23,14 @@ pub const SEG_UDATA: u16 = 3;
 pub const SEG_UCODE64: u16 = 4;
 pub const SEG_TSS: u16 = 5;
 pub const SEG_TSS_HI: u16 = 6;
+pub const SEG_UCODE32: u16 = 7;
 
 pub const KCODE: u16 = SEG_KCODE << 3;
 pub const KDATA: u16 = SEG_KDATA << 3;
 pub const UDATA: u16 = (SEG_UDATA << 3) | 3;
 pub const UCODE64: u16 = (SEG_UCODE64 << 3) | 3;
 pub const TSS: u16 = SEG_TSS << 3;
+pub const UCODE32: u16 = (SEG_UCODE32 << 3) | 3;
 
 pub const CR0_PE: u64 = 1 << 0;
 pub const CR0_MP: u64 = 1 << 1;
@@ -53,6 +55,7 @@ pub const CR4_PCIDE: u64 = 1 << 17;
 pub const CR4_OSXSAVE: u64 = 1 << 18;
 pub const CR4_SMEP: u64 = 1 << 20;
 pub const CR4_SMAP: u64 = 1 << 21;
+pub const CR4_PKE: u64 = 1 << 22;
 
 pub const RFLAGS_CF: u64 = 1 << 0;
 pub const RFLAGS_RESERVED: u64 = 1 << 1;
@@ -12,6 +12,11 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::common::*;
+
 #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct Config {
     pub DebugLevel: DebugLevel,
@@ -33,10 +38,83 @@ pub struct Config {
     pub UringEpollCtl: bool,
     pub EnableRDMA: bool,
     pub RDMAPort: u8,
+    // RDMANotifyBatch and RDMANotifyDelay coalesce the rdma_srv data
+    // notifications: a sleeping client is woken after RDMANotifyBatch
+    // notifications or RDMANotifyDelay microseconds. A batch of 1 or a delay
+    // of 0 wakes it up for each notification. RDMANotifyAdaptive scales the
+    // batch down to 1 by the observed notification rate of the client.
+    pub RDMANotifyBatch: u32,
+    pub RDMANotifyDelay: u64,
+    pub RDMANotifyAdaptive: bool,
     pub PerSandboxLog: bool,
     pub ReserveCpuCount: usize,
     pub EnableMemInfo: bool,
     pub ShimMode: bool,
+    pub Audit: bool,
+    // GdbPort is the local tcp port of the gdb stub of qvisor, 0 disables it
+    pub GdbPort: u16,
+    // GdbSocket enables the gdb stub on the unix socket
+    // /var/run/quark/gdb-<sandbox id>.sock
+    pub GdbSocket: bool,
+    // WatchdogTimeout is the seconds after which a vcpu or a task making no
+    // progress in the kernel is reported, 0 disables the watchdog
+    pub WatchdogTimeout: u64,
+    pub WatchdogAction: WatchdogAction,
+    // VcpuAutoScale parks the idle vcpus and brings them back with the load,
+    // MinVcpuCount vcpus are always online
+    pub VcpuAutoScale: bool,
+    pub MinVcpuCount: usize,
+    // HugePage is the host page backing of the guest heap
+    pub HugePage: HugePageBacking,
+    // NumaAware places the vcpus by the host numa nodes and gives the guest
+    // the matching numa topology
+    pub NumaAware: bool,
+    // HostSeccomp is the seccomp allowlist of the sandbox process, installed
+    // after the vm is created, HostDropCaps drops its unneeded capabilities
+    pub HostSeccomp: HostSeccompMode,
+    pub HostDropCaps: bool,
+    // HostDevices are the host device classes which can be passed into the
+    // guest /dev through the linux.devices of the spec
+    pub HostDevices: HostDeviceSet,
+    // EnforceWX denies the mmap and mprotect calls which make a page both
+    // writable and executable, except for the executables in the
+    // "dev.quark.wx-allow" annotation of the container
+    pub EnforceWX: bool,
+    // KernelASLR randomizes the qkernel image base, the guest kernel heap
+    // base, where the kernel stacks also come from, and the kernel stack
+    // canary at each boot. The boot heap base is randomized whatever it is.
+    pub KernelASLR: bool,
+    // VcpuGangSchedule moves the vcpus running the tasks of a guest core
+    // scheduling cookie, prctl(PR_SCHED_CORE), onto the host cores sharing
+    // a cache
+    pub VcpuGangSchedule: bool,
+    // ZeroCopyIO passes the pinned user pages of the large host file
+    // reads/writes to the host uring directly instead of copying them
+    // through a kernel buffer
+    pub ZeroCopyIO: bool,
+    // ReadinessCache answers the host fd polls from the events delivered by
+    // the host epoll thread when they show the fd isn't ready, instead of
+    // polling the host
+    pub ReadinessCache: bool,
+    // HostFileCache is the dentry cache policy of the host mounts which
+    // don't have a "cache" mount option
+    pub HostFileCache: HostFileCache,
+    // ShmChannel gives the guest /dev/qchannel, a byte stream channel on
+    // share memory with another sandbox of the host attached to the same
+    // channel name
+    pub ShmChannel: bool,
+    // EntropyRefill is the seconds between the injections of the host
+    // entropy into the guest entropy pool, 0 disables them
+    pub EntropyRefill: u64,
+    // OvercommitMemory is the vm.overcommit_memory of the sandbox at boot, 0
+    // is heuristic, 1 always overcommits and 2 is the strict accounting
+    pub OvercommitMemory: i32,
+    // SyscallStub fails the unimplemented and the rarely used syscalls with
+    // ENOSYS instead of running them, the first call of each is logged
+    pub SyscallStub: bool,
+    // SyscallMetrics records the count and the latency of each syscall in the
+    // /syscalls metrics, it reads the tsc twice on each syscall
+    pub SyscallMetrics: bool,
 }
 
 impl Config {
@@ -49,7 +127,60 @@ impl Config {
     }
 }
 
-impl Config {}
+// RUNTIME_CONFIG_FLAGS are the bool fields which can be changed on a running
+// sandbox through ConfigUpdate. The other fields are only read at boot.
+pub const RUNTIME_CONFIG_FLAGS: &[&str] = &[
+    "PrintException",
+    "PerfDebug",
+    "UringStatx",
+    "UringEpollCtl",
+];
+
+/// ConfigUpdate is the change set applied to the config of a running sandbox.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ConfigUpdate {
+    pub DebugLevel: Option<DebugLevel>,
+    pub LogLevel: Option<LogLevel>,
+    // Flags are (field name, value) pairs of RUNTIME_CONFIG_FLAGS
+    pub Flags: Vec<(String, bool)>,
+    // Trace starts or stops the span tracing, it is not a config field.
+    pub Trace: Option<bool>,
+}
+
+impl Config {
+    // Update applies the update. Nothing is changed if any flag is not
+    // runtime changeable.
+    pub fn Update(&mut self, update: &ConfigUpdate) -> Result<()> {
+        for (name, _) in &update.Flags {
+            if !RUNTIME_CONFIG_FLAGS.contains(&name.as_str()) {
+                return Err(Error::Common(format!(
+                    "config {} can't be changed at runtime",
+                    name
+                )));
+            }
+        }
+
+        if let Some(level) = update.DebugLevel {
+            self.DebugLevel = level;
+        }
+
+        if let Some(level) = update.LogLevel {
+            self.LogLevel = level;
+        }
+
+        for (name, val) in &update.Flags {
+            match name.as_str() {
+                "PrintException" => self.PrintException = *val,
+                "PerfDebug" => self.PerfDebug = *val,
+                "UringStatx" => self.UringStatx = *val,
+                "UringEpollCtl" => self.UringEpollCtl = *val,
+                _ => (),
+            }
+        }
+
+        return Ok(());
+    }
+}
 
 impl Default for Config {
     fn default() -> Self {
@@ -73,10 +204,36 @@ impl Default for Config {
             UringEpollCtl: false,
             EnableRDMA: false,
             RDMAPort: 1,
+            RDMANotifyBatch: 1,
+            RDMANotifyDelay: 0,
+            RDMANotifyAdaptive: false,
             PerSandboxLog: false,
             ReserveCpuCount: 2,
             EnableMemInfo: true,
             ShimMode: false,
+            Audit: false,
+            GdbPort: 0,
+            GdbSocket: false,
+            WatchdogTimeout: 0,
+            WatchdogAction: WatchdogAction::Log,
+            VcpuAutoScale: false,
+            MinVcpuCount: 2,
+            HugePage: HugePageBacking::None,
+            NumaAware: false,
+            HostSeccomp: HostSeccompMode::Disabled,
+            HostDropCaps: false,
+            HostDevices: HostDeviceSet::default(),
+            EnforceWX: false,
+            KernelASLR: true,
+            VcpuGangSchedule: false,
+            ZeroCopyIO: false,
+            ReadinessCache: true,
+            HostFileCache: HostFileCache::Shared,
+            ShmChannel: false,
+            EntropyRefill: 60,
+            OvercommitMemory: 0,
+            SyscallStub: false,
+            SyscallMetrics: false,
         };
     }
 }
@@ -117,3 +274,144 @@ pub enum LogType {
     Sync,
     Async,
 }
+
+// WatchdogAction is what the watchdog does after it reports a hang
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum WatchdogAction {
+    Log,
+    // Kill sends SIGKILL to the thread group of the hung task
+    Kill,
+    Panic,
+}
+
+impl Default for WatchdogAction {
+    fn default() -> Self {
+        return Self::Log;
+    }
+}
+
+// HugePageBacking is the host page backing of the guest heap. The heap part
+// used before the config is loaded is always on 4KB pages, the hugetlb pages
+// must be reserved on the host, e.g. through /proc/sys/vm/nr_hugepages.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum HugePageBacking {
+    None,
+    // Thp advises the transparent hugepages for the heap
+    Thp,
+    // HugeTlb2M and HugeTlb1G map the heap with MAP_HUGETLB, the heap falls
+    // back to Thp if the host has not enough free hugepages
+    HugeTlb2M,
+    HugeTlb1G,
+}
+
+impl Default for HugePageBacking {
+    fn default() -> Self {
+        return Self::None;
+    }
+}
+
+// HostFileCache is how the dentries cached for a host mount follow the
+// changes made to the host files outside of the sandbox. The file attributes
+// are always read from the host.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum HostFileCache {
+    // Shared watches the cached host directories with inotify, a directory
+    // which can't be watched is revalidated on each walk
+    Shared,
+    // Exclusive trusts the cache, the host files are only changed by the
+    // sandbox
+    Exclusive,
+    // Revalidate checks each cached dentry with the host on each walk
+    Revalidate,
+}
+
+impl Default for HostFileCache {
+    fn default() -> Self {
+        return Self::Shared;
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum HostSeccompMode {
+    Disabled,
+    // Log logs the syscalls out of the allowlist in the host audit log, it is
+    // used to check the allowlist with a workload
+    Log,
+    // Enforce kills the sandbox on a syscall out of the allowlist
+    Enforce,
+}
+
+impl Default for HostSeccompMode {
+    fn default() -> Self {
+        return Self::Disabled;
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct HostDeviceSet {
+    pub Fuse: bool,
+    pub Uinput: bool,
+    // Nvidia proxies the ioctls of the nvidia devices and maps the device
+    // memory set up by RM_MAP_MEMORY. The uvm device memory can't be mapped.
+    pub Nvidia: bool,
+    pub Tun: bool,
+}
+
+impl HostDeviceSet {
+    // Class returns the class of the host device path if the class is allowed
+    pub fn Class(&self, path: &str) -> Option<HostDeviceClass> {
+        let class = HostDeviceClass::Of(path)?;
+        let allowed = match class {
+            HostDeviceClass::Fuse => self.Fuse,
+            HostDeviceClass::Uinput => self.Uinput,
+            HostDeviceClass::Nvidia => self.Nvidia,
+            HostDeviceClass::Tun => self.Tun,
+        };
+
+        if !allowed {
+            return None;
+        }
+
+        return Some(class);
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum HostDeviceClass {
+    Fuse,
+    Uinput,
+    Nvidia,
+    Tun,
+}
+
+impl HostDeviceClass {
+    pub fn Of(path: &str) -> Option<Self> {
+        match path {
+            "/dev/fuse" => return Some(Self::Fuse),
+            "/dev/uinput" | "/dev/input/uinput" => return Some(Self::Uinput),
+            "/dev/net/tun" => return Some(Self::Tun),
+            "/dev/nvidiactl" | "/dev/nvidia-uvm" | "/dev/nvidia-uvm-tools"
+            | "/dev/nvidia-modeset" => return Some(Self::Nvidia),
+            _ => (),
+        }
+
+        // the gpus are /dev/nvidia0, /dev/nvidia1, ...
+        match path.strip_prefix("/dev/nvidia") {
+            Some(n) if n.len() > 0 && n.bytes().all(|c| c.is_ascii_digit()) => {
+                return Some(Self::Nvidia)
+            }
+            _ => return None,
+        }
+    }
+
+    // IoctlTypes are the ioctl types (the _IOC_TYPE byte of the request) the
+    // guest can pass through to the host device
+    pub fn IoctlTypes(&self) -> &'static [u8] {
+        match self {
+            Self::Fuse => return &[229],
+            Self::Uinput => return &[b'U'],
+            Self::Nvidia => return &[b'F'],
+            Self::Tun => return &[b'T'],
+        }
+    }
+}
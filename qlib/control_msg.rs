@@ -17,7 +17,9 @@ use alloc::vec::Vec;
 use core::sync::atomic::AtomicU64;
 use core::sync::atomic::Ordering;
 
+use super::auth::cap_set::*;
 use super::auth::id::*;
+use super::config::*;
 use super::loader::*;
 use super::singleton::*;
 
@@ -101,6 +103,133 @@ pub struct CreateArgs {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct StartArgs {
     pub process: Process,
+    // pidsLimit is the max task count of the container, 0 is unlimited
+    pub pidsLimit: u64,
+    // cpuWeight is the cgroup cpu.weight of the container, 0 is the default
+    pub cpuWeight: u64,
+}
+
+/// StraceArgs is payload for Strace control msg to quark sandbox,
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct StraceArgs {
+    // Enable turns the syscall tracing on or off.
+    pub Enable: bool,
+
+    // Pids limits the tracing to the thread groups with the given pids.
+    // If empty, all thread groups are traced.
+    pub Pids: Vec<i32>,
+
+    // Syscalls limits the tracing to the given syscall numbers.
+    // If empty, all syscalls are traced.
+    pub Syscalls: Vec<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceOp {
+    // Start drops the recorded spans and starts a new recording.
+    Start,
+    Stop,
+    // Dump writes the recorded spans in Chrome trace format to the fd of the
+    // request.
+    Dump,
+}
+
+/// TraceArgs is payload for Trace control msg to quark sandbox,
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TraceArgs {
+    pub Op: TraceOp,
+
+    // RingSize is the span event count kept for each vcpu, 0 means the default.
+    pub RingSize: usize,
+
+    // fds is the output fd of Dump, the dump doesn't fit in a ucall resp
+    pub fds: Vec<i32>,
+}
+
+/// MetricsArgs is payload for Metrics control msg to quark sandbox,
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct MetricsArgs {
+    // fds is the output fd of the metrics values in json, they don't fit in
+    // a ucall resp
+    pub fds: Vec<i32>,
+}
+
+/// CheckpointArgs is payload for Checkpoint control msg to quark sandbox,
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CheckpointArgs {
+    // LeaveRunning resumes the sandbox after the checkpoint, otherwise it
+    // stays paused.
+    pub LeaveRunning: bool,
+
+    // PreDump only saves the memory without pausing the sandbox, it is the
+    // first rounds of the iterative pre-copy of a live migration.
+    pub PreDump: bool,
+
+    // Incremental only saves the pages written since the previous image.
+    pub Incremental: bool,
+
+    // fds is the image file fd
+    pub fds: Vec<i32>,
+}
+
+/// RestoreArgs is payload for Restore control msg to quark sandbox,
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RestoreArgs {
+    // cid is the container the processes are restored in
+    pub cid: String,
+
+    // the credentials of the restored processes are from the process of the
+    // container spec, the ones of the image are not trusted
+    pub Uid: u32,
+    pub Gid: u32,
+    pub AdditionalGids: Vec<u32>,
+    pub Caps: TaskCaps,
+
+    // Stream is set if the image fd is a migration stream of the pre-dump and
+    // incremental images, whose pages are staged in the guest memory
+    pub Stream: bool,
+
+    // fds are the image file fd and the stdin, stdout and stderr of the
+    // restored processes
+    pub fds: Vec<i32>,
+}
+
+/// BalloonArgs is payload for Balloon control msg to quark sandbox,
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BalloonArgs {
+    // TargetSize is the bytes of the guest memory to give back to the host
+    pub TargetSize: u64,
+}
+
+/// FsFreezeArgs is payload for FsFreeze control msg to quark sandbox,
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FsFreezeArgs {
+    // Freeze syncs the mounts of the sandbox and blocks the writes to them,
+    // otherwise the frozen mounts are thawed.
+    pub Freeze: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct BalloonInfo {
+    // Size is the bytes taken by the balloon, it may be less than the target
+    // when the guest doesn't have enough free memory
+    pub Size: u64,
+    // Free is the free bytes of the guest heap
+    pub Free: u64,
+}
+
+/// ContainerInfo is the state of a container in the sandbox
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ContainerInfo {
+    pub ID: String,
+    // Status is "created", "running" or "stopped"
+    pub Status: String,
+    // Pid is the pid of the container init process, 0 if it isn't started
+    pub Pid: i32,
+    // Tasks is the count of the live tasks of the container
+    pub Tasks: u64,
+    // Memory is the memory charged to the container in bytes
+    pub Memory: u64,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -117,6 +246,15 @@ pub enum Payload {
     CreateSubContainer(CreateArgs),
     StartSubContainer(StartArgs),
     WaitAll,
+    Strace(StraceArgs),
+    Trace(TraceArgs),
+    UpdateConfig(ConfigUpdate),
+    Checkpoint(CheckpointArgs),
+    Restore(RestoreArgs),
+    Balloon(BalloonArgs),
+    ContainerState(Cid),
+    FsFreeze(FsFreezeArgs),
+    Metrics(MetricsArgs),
 }
 
 #[derive(Serialize, Deserialize, Debug, Default)]
@@ -144,6 +282,17 @@ pub enum UCallResp {
     CreateSubContainerResp,
     StartSubContainerResp,
     WaitAllResp(WaitAllResp),
+    StraceResp,
+    TraceResp,
+    UpdateConfigResp(Config),
+    // the count of the saved pages
+    CheckpointResp(u64),
+    // the pids of the restored processes
+    RestoreResp(Vec<i32>),
+    BalloonResp(BalloonInfo),
+    ContainerStateResp(ContainerInfo),
+    FsFreezeResp(u64),
+    MetricsResp,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -126,6 +126,7 @@ pub unsafe fn InitSingleton() {
         (X86Feature::X86FeatureAVX512VBMI as i32, "avx512vbmi"),
         (X86Feature::X86FeatureUMIP as i32, "umip"),
         (X86Feature::X86FeaturePKU as i32, "pku"),
+        (X86Feature::X86FeatureOSPKE as i32, "ospke"),
         // Block 4.
         (X86Feature::X86FeatureXSAVEOPT as i32, "xsaveopt"),
         (X86Feature::X86FeatureXSAVEC as i32, "xsavec"),
@@ -312,6 +313,7 @@ pub enum X86Feature {
     X86FeatureAVX512VBMI,
     X86FeatureUMIP,
     X86FeaturePKU,
+    X86FeatureOSPKE,
 
     // Block 4 constants are for xsave capabilities in CPUID.(EAX=0DH,ECX=01H):EAX.
     // The CPUID leaf is available only if 'X86FeatureXSAVE' is present.
@@ -160,6 +160,16 @@ impl IOMgr {
 pub struct FdWaitIntern {
     pub queue: Queue,
     pub mask: EventMask,
+
+    // events of the host epoll mask known not to be ready. It is only valid
+    // while the fd is in the host epoll, whose edge events clear it.
+    pub notReady: EventMask,
+    // bumped by each host epoll event, so that a poll result racing with an
+    // event doesn't get cached
+    pub seq: u64,
+    // an epoll_ctl of the fd is in the uring. The uring doesn't keep the
+    // order of the ops, so the next one waits for its completion.
+    pub ctlPending: bool,
 }
 
 impl fmt::Debug for FdWaitIntern {
@@ -183,7 +193,11 @@ impl Deref for FdWaitInfo {
 
 impl FdWaitInfo {
     pub fn New(queue: Queue, mask: EventMask) -> Self {
-        let intern = FdWaitIntern { queue, mask };
+        let intern = FdWaitIntern {
+            queue,
+            mask,
+            ..Default::default()
+        };
 
         return Self(Arc::new(QMutex::new(intern)));
     }
@@ -192,6 +206,10 @@ impl FdWaitInfo {
         let op;
         let mask = {
             let mut fi = self.lock();
+            if fi.ctlPending {
+                // EpollCtlDone will pick up the change
+                return Ok(());
+            }
 
             let mask = fi.queue.Events();
 
@@ -213,6 +231,8 @@ impl FdWaitInfo {
             }
 
             fi.mask = mask;
+            fi.notReady &= mask;
+            fi.ctlPending = true;
 
             mask
         };
@@ -223,6 +243,11 @@ impl FdWaitInfo {
         return Ok(());
     }
 
+    pub fn EpollCtlDone(&self, fd: i32, epollfd: i32) -> Result<()> {
+        self.lock().ctlPending = false;
+        return self.UpdateFDAsync(fd, epollfd);
+    }
+
     pub fn UpdateFDSync(&self, fd: i32) -> Result<()> {
         let mask = {
             let fi = self.lock();
@@ -239,10 +264,39 @@ impl FdWaitInfo {
     }
 
     pub fn Notify(&self, mask: EventMask) {
-        let queue = self.lock().queue.clone();
+        let queue = {
+            let mut fi = self.lock();
+            fi.seq += 1;
+            fi.notReady = 0;
+            fi.queue.clone()
+        };
         queue.Notify(EventMaskFromLinux(mask as u32));
     }
 
+    // CachedReadiness returns Ok when none of mask is ready, otherwise the
+    // sequence to pass to UpdateReadiness after polling the host
+    pub fn CachedReadiness(&self, mask: EventMask) -> core::result::Result<(), u64> {
+        let fi = self.lock();
+        if mask != 0 && fi.mask & mask == mask && fi.notReady & mask == mask {
+            return Ok(());
+        }
+
+        return Err(fi.seq);
+    }
+
+    pub fn UpdateReadiness(&self, seq: u64, mask: EventMask, ready: EventMask) {
+        let mut fi = self.lock();
+        if fi.seq != seq {
+            return;
+        }
+
+        if ready == 0 {
+            fi.notReady |= mask & fi.mask;
+        } else {
+            fi.notReady &= !ready;
+        }
+    }
+
     fn waitfd(fd: i32, mask: EventMask) -> Result<()> {
         HostSpace::WaitFDAsync(fd, mask);
 
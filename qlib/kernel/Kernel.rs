@@ -22,8 +22,10 @@ use super::super::qmsg::*;
 use super::super::socket_buf::*;
 use super::super::*;
 use super::asm::*;
+use super::crash::*;
 use super::task::*;
 use super::taskMgr;
+use super::util::cstring::CString;
 
 extern "C" {
     pub fn rdtsc() -> i64;
@@ -80,18 +82,33 @@ impl HostSpace {
         return HostSpace::Call(&mut msg, false) as i64;
     }
 
+    pub fn AuditLog(addr: u64, len: usize) -> i64 {
+        let mut msg = Msg::AuditLog(AuditLog { addr, len });
+
+        return HostSpace::HCall(&mut msg, false) as i64;
+    }
+
+    // BalloonRelease asks the host to drop the huge pages taken by the balloon,
+    // pages is the address of an array of count huge page addresses.
+    pub fn BalloonRelease(pages: u64, count: usize) -> i64 {
+        let mut msg = Msg::BalloonRelease(BalloonRelease { addr: pages, count });
+
+        return HostSpace::HCall(&mut msg, false) as i64;
+    }
+
     pub fn EventfdWrite(fd: i32) -> i64 {
         let mut msg = Msg::EventfdWrite(EventfdWrite { fd });
 
         return HostSpace::HCall(&mut msg, false) as i64;
     }
 
-    pub fn RenameAt(olddirfd: i32, oldpath: u64, newdirfd: i32, newpath: u64) -> i64 {
+    pub fn RenameAt(olddirfd: i32, oldpath: u64, newdirfd: i32, newpath: u64, flags: u32) -> i64 {
         let mut msg = Msg::RenameAt(RenameAt {
             olddirfd,
             oldpath,
             newdirfd,
             newpath,
+            flags,
         });
 
         return HostSpace::HCall(&mut msg, false) as i64;
@@ -228,6 +245,28 @@ impl HostSpace {
         return HostSpace::Call(&mut msg, false) as i64;
     }
 
+    pub fn IORecvMMsg(fd: i32, msgvec: u64, vlen: u32, flags: i32) -> i64 {
+        let mut msg = Msg::IORecvMMsg(IORecvMMsg {
+            fd,
+            msgvec,
+            vlen,
+            flags,
+        });
+
+        return HostSpace::Call(&mut msg, false) as i64;
+    }
+
+    pub fn IOSendMMsg(fd: i32, msgvec: u64, vlen: u32, flags: i32) -> i64 {
+        let mut msg = Msg::IOSendMMsg(IOSendMMsg {
+            fd,
+            msgvec,
+            vlen,
+            flags,
+        });
+
+        return HostSpace::Call(&mut msg, false) as i64;
+    }
+
     pub fn GetTimeOfDay(tv: u64, tz: u64) -> i64 {
         let mut msg = Msg::GetTimeOfDay(GetTimeOfDay { tv, tz });
 
@@ -257,6 +296,12 @@ impl HostSpace {
         return HostSpace::Call(&mut msg, false) as i64;
     }
 
+    pub fn NvProxyIoctl(fd: i32, cmd: u64, argp: u64) -> i64 {
+        let mut msg = Msg::NvProxyIoctl(NvProxyIoctl { fd, cmd, argp });
+
+        return HostSpace::Call(&mut msg, false) as i64;
+    }
+
     pub fn Fstatfs(fd: i32, buf: u64) -> i64 {
         let mut msg = Msg::Fstatfs(Fstatfs { fd, buf });
 
@@ -269,6 +314,23 @@ impl HostSpace {
         return HostSpace::HCall(&mut msg, true) as i64;
     }
 
+    // ResetSocket aborts the connection with a RST and closes the fd. It
+    // doesn't block so it can be used when processing uring completions.
+    pub fn ResetSocket(fd: i32) -> i64 {
+        // struct linger { l_onoff: 1, l_linger: 0 }
+        let linger: [i32; 2] = [1, 0];
+        let mut msg = Msg::SetSockOpt(SetSockOpt {
+            sockfd: fd,
+            level: LibcConst::SOL_SOCKET as i32,
+            optname: LibcConst::SO_LINGER as i32,
+            optval: &linger as *const _ as u64,
+            optlen: 8,
+        });
+
+        HostSpace::HCall(&mut msg, false);
+        return HostSpace::Close(fd);
+    }
+
     pub fn FAccessAt(dirfd: i32, pathname: u64, mode: i32, flags: i32) -> i64 {
         let mut msg = Msg::FAccessAt(FAccessAt {
             dirfd,
@@ -365,6 +427,34 @@ impl HostSpace {
         return HostSpace::Call(&mut msg, false) as i64;
     }
 
+    pub fn InotifyInit() -> i64 {
+        let mut msg = Msg::InotifyInit(InotifyInit {});
+
+        return HostSpace::Call(&mut msg, false) as i64;
+    }
+
+    pub fn InotifyAddWatch(fd: i32, dirfd: i32, mask: u32) -> i64 {
+        let mut msg = Msg::InotifyAddWatch(InotifyAddWatch { fd, dirfd, mask });
+
+        return HostSpace::Call(&mut msg, false) as i64;
+    }
+
+    pub fn InotifyRmWatch(fd: i32, wd: i32) -> i64 {
+        let mut msg = Msg::InotifyRmWatch(InotifyRmWatch { fd, wd });
+
+        return HostSpace::Call(&mut msg, false) as i64;
+    }
+
+    pub fn ChannelOpen(name: &str, fds: &mut [i32; 3]) -> i64 {
+        let name = CString::New(name);
+        let mut msg = Msg::ChannelOpen(ChannelOpen {
+            name: name.Ptr(),
+            fds: &mut fds[0] as *mut _ as u64,
+        });
+
+        return HostSpace::Call(&mut msg, false) as i64;
+    }
+
     pub fn SyncFileRange(fd: i32, offset: i64, nbytes: i64, flags: u32) -> i64 {
         let mut msg = Msg::SyncFileRange(SyncFileRange {
             fd,
@@ -421,6 +511,14 @@ impl HostSpace {
         return HostSpace::Call(&mut msg, false) as i64;
     }
 
+    // HostEntropy returns the bits of entropy the host credits to the guest
+    // pool.
+    pub fn HostEntropy(bits: i32) -> i64 {
+        let mut msg = Msg::HostEntropy(HostEntropy { bits });
+
+        return HostSpace::HCall(&mut msg, false) as i64;
+    }
+
     pub fn Statm(statm: &mut StatmInfo) -> i64 {
         let mut msg = Msg::Statm(Statm {
             buf: statm as *const _ as u64,
@@ -542,6 +640,21 @@ impl HostSpace {
         HyperCall64(HYPERCALL_PANIC, &msg as *const _ as u64, 0, 0);
     }
 
+    // PanicWithInfo also passes the crash info for qvisor to write the crash dump
+    pub fn PanicWithInfo(str: &str, info: &CrashInfo) {
+        let msg = Print {
+            level: DebugLevel::Error,
+            str: str,
+        };
+
+        HyperCall64(
+            HYPERCALL_PANIC,
+            &msg as *const _ as u64,
+            info as *const _ as u64,
+            0,
+        );
+    }
+
     pub fn TryOpenAt(dirfd: i32, name: u64, addr: u64) -> i64 {
         let mut msg = Msg::TryOpenAt(TryOpenAt {
             dirfd: dirfd,
@@ -774,6 +887,36 @@ impl HostSpace {
         HostSpace::HCall(&mut msg, true);
     }
 
+    // MMapDevice maps the exact device range, which needn't be PMD aligned
+    pub fn MMapDevice(len: u64, fd: i32, offset: u64, prot: i32) -> i64 {
+        assert!(
+            len % MemoryDef::PAGE_SIZE == 0 && offset % MemoryDef::PAGE_SIZE == 0,
+            "offset is {:x}, len is {:x}",
+            offset,
+            len
+        );
+        let mut msg = Msg::MMapFile(MMapFile {
+            len,
+            fd,
+            offset,
+            prot,
+        });
+
+        return HostSpace::HCall(&mut msg, true) as i64;
+    }
+
+    pub fn MUnmapDevice(addr: u64, len: u64) {
+        assert!(
+            addr % MemoryDef::PAGE_SIZE == 0 && len % MemoryDef::PAGE_SIZE == 0,
+            "addr is {:x}, len is {:x}",
+            addr,
+            len
+        );
+        let mut msg = Msg::MUnmap(qmsg::qcall::MUnmap { addr, len });
+
+        HostSpace::HCall(&mut msg, true);
+    }
+
     pub fn WaitFDAsync(fd: i32, mask: EventMask) {
         let msg = HostOutputMsg::WaitFDAsync(WaitFDAsync { fd, mask });
 
@@ -14,12 +14,16 @@
 
 use alloc::boxed::Box;
 use alloc::collections::linked_list::LinkedList;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
 use core::fmt;
 
 use super::super::common::*;
 use super::super::linux_def::*;
+use super::kernel::kernel::UserCharge;
 use super::kernel::posixtimer::*;
 use super::task::*;
+use super::threadmgr::task_usermem::*;
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, Default)]
@@ -137,6 +141,16 @@ pub struct SigChld {
 pub struct SigFault {
     pub addr: u64,
     pub lsb: u16,
+    pub _pad: [u16; 3],
+    // the protection key of a SEGV_PKUERR fault
+    pub pkey: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct SigPoll {
+    pub band: i64,
+    pub fd: i32,
 }
 
 #[repr(C)]
@@ -222,6 +236,11 @@ impl SignalInfo {
         return unsafe { &mut *(addr as *mut SigFault) };
     }
 
+    pub fn SigPoll(&mut self) -> &mut SigPoll {
+        let addr = &self.fields[0] as *const _ as u64;
+        return unsafe { &mut *(addr as *mut SigPoll) };
+    }
+
     // SignalInfoUser (properly SI_USER) indicates that a signal was sent from
     // a kill() or raise() syscall.
     pub const SIGNAL_INFO_USER: i32 = 0;
@@ -268,6 +287,49 @@ impl SignalInfo {
 
     // TRAP_BRKPT indicates a breakpoint trap.
     pub const TRAP_BRKPT: i32 = 1;
+
+    // SI_QUEUE indicates that the signal was sent by sigqueue().
+    pub const SIGNAL_INFO_QUEUE: i32 = -1;
+
+    // SI_MESGQ indicates that the signal was sent by a message queue.
+    pub const SIGNAL_INFO_MESGQ: i32 = -3;
+
+    // ILL_* codes are only meaningful for SIGILL.
+    pub const ILL_ILLOPC: i32 = 1;
+    pub const ILL_ILLOPN: i32 = 2;
+
+    // FPE_* codes are only meaningful for SIGFPE.
+    pub const FPE_INTDIV: i32 = 1;
+    pub const FPE_INTOVF: i32 = 2;
+    pub const FPE_FLTDIV: i32 = 3;
+    pub const FPE_FLTOVF: i32 = 4;
+    pub const FPE_FLTUND: i32 = 5;
+    pub const FPE_FLTRES: i32 = 6;
+    pub const FPE_FLTINV: i32 = 7;
+
+    // SEGV_* codes are only meaningful for SIGSEGV.
+
+    // SEGV_MAPERR indicates the address is not mapped.
+    pub const SEGV_MAPERR: i32 = 1;
+
+    // SEGV_ACCERR indicates the mapping doesn't permit the access.
+    pub const SEGV_ACCERR: i32 = 2;
+
+    // SEGV_PKUERR indicates the protection key of the mapping doesn't permit
+    // the access.
+    pub const SEGV_PKUERR: i32 = 4;
+
+    // BUS_* codes are only meaningful for SIGBUS.
+    pub const BUS_ADRALN: i32 = 1;
+    pub const BUS_ADRERR: i32 = 2;
+
+    // POLL_* codes are only meaningful for SIGIO or a signal set by F_SETSIG.
+    pub const POLL_IN: i32 = 1;
+    pub const POLL_OUT: i32 = 2;
+    pub const POLL_MSG: i32 = 3;
+    pub const POLL_ERR: i32 = 4;
+    pub const POLL_PRI: i32 = 5;
+    pub const POLL_HUP: i32 = 6;
 }
 
 pub const UC_FP_XSTATE: u64 = 1;
@@ -370,6 +432,219 @@ impl SigContext {
     }
 }
 
+// compat_siginfo_t of a 32 bit process, the pointer and long fields of the
+// union are 32 bit and it starts at offset 12.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct SignalInfo32 {
+    pub Signo: i32,
+    pub Errno: i32,
+    pub Code: i32,
+    pub fields: [u8; 128 - 12],
+}
+
+impl SignalInfo32 {
+    fn PutU32(&mut self, offset: usize, val: u32) {
+        self.fields[offset..offset + 4].copy_from_slice(&val.to_le_bytes());
+    }
+
+    // New translates the union by the layout of the signal, as linux's
+    // copy_siginfo_to_user32.
+    pub fn New(info: &SignalInfo) -> Self {
+        let mut info = *info;
+        info.FixSignalCodeForUser();
+        let mut info32 = Self {
+            Signo: info.Signo,
+            Errno: info.Errno,
+            Code: info.Code,
+            fields: [0; 128 - 12],
+        };
+
+        if info.Code == SignalInfo::SIGNAL_INFO_TIMER {
+            let timer = *info.SigTimer();
+            info32.PutU32(0, timer.tid as u32);
+            info32.PutU32(4, timer.overrun as u32);
+            info32.PutU32(8, timer.sigval as u32);
+            return info32;
+        }
+
+        if info.Code == SignalInfo::SIGNAL_INFO_QUEUE || info.Code == SignalInfo::SIGNAL_INFO_MESGQ
+        {
+            let rt = *info.SigRt();
+            info32.PutU32(0, rt.pid as u32);
+            info32.PutU32(4, rt.uid);
+            info32.PutU32(8, rt.sigval as u32);
+            return info32;
+        }
+
+        if info.Code > 0 {
+            match info.Signo {
+                Signal::SIGCHLD => {
+                    // all the fields are 32 bit
+                    info32.fields[0..20].copy_from_slice(&info.fields[0..20]);
+                    return info32;
+                }
+                Signal::SIGSEGV
+                | Signal::SIGBUS
+                | Signal::SIGILL
+                | Signal::SIGFPE
+                | Signal::SIGTRAP => {
+                    let fault = *info.SigFault();
+                    info32.PutU32(0, fault.addr as u32);
+                    info32.fields[4..6].copy_from_slice(&fault.lsb.to_le_bytes());
+                    info32.PutU32(8, fault.pkey);
+                    return info32;
+                }
+                Signal::SIGIO => {
+                    let poll = *info.SigPoll();
+                    info32.PutU32(0, poll.band as u32);
+                    info32.PutU32(4, poll.fd as u32);
+                    return info32;
+                }
+                _ => (),
+            }
+        }
+
+        // kill: the pid and the uid
+        info32.fields[0..8].copy_from_slice(&info.fields[0..8]);
+        return info32;
+    }
+}
+
+// compat_stack_t
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct SignalStack32 {
+    pub addr: u32,
+    pub flags: u32,
+    pub size: u32,
+}
+
+impl SignalStack32 {
+    pub fn New(s: &SignalStack) -> Self {
+        return Self {
+            addr: s.addr as u32,
+            flags: s.flags,
+            size: s.size as u32,
+        };
+    }
+
+    pub fn SignalStack(&self) -> SignalStack {
+        return SignalStack {
+            addr: self.addr as u64,
+            flags: self.flags,
+            size: self.size as u64,
+        };
+    }
+}
+
+// struct sigcontext_32, see arch/x86/include/uapi/asm/sigcontext.h. The
+// selectors are 16 bit with a 16 bit pad.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct SigContext32 {
+    pub gs: u32,
+    pub fs: u32,
+    pub es: u32,
+    pub ds: u32,
+    pub edi: u32,
+    pub esi: u32,
+    pub ebp: u32,
+    pub esp: u32,
+    pub ebx: u32,
+    pub edx: u32,
+    pub ecx: u32,
+    pub eax: u32,
+    pub trapno: u32,
+    pub err: u32,
+    pub eip: u32,
+    pub cs: u32,
+    pub eflags: u32,
+    pub espAtSignal: u32,
+    pub ss: u32,
+    // Pointer to the fp state.
+    pub fpstate: u32,
+    pub oldmask: u32,
+    pub cr2: u32,
+}
+
+impl SigContext32 {
+    pub fn New(ptRegs: &PtRegs, fs: u16, gs: u16, oldMask: u64, cr2: u64, fpstate: u64) -> Self {
+        return Self {
+            gs: gs as u32,
+            fs: fs as u32,
+            es: ptRegs.ss as u32,
+            ds: ptRegs.ss as u32,
+            edi: ptRegs.rdi as u32,
+            esi: ptRegs.rsi as u32,
+            ebp: ptRegs.rbp as u32,
+            esp: ptRegs.rsp as u32,
+            ebx: ptRegs.rbx as u32,
+            edx: ptRegs.rdx as u32,
+            ecx: ptRegs.rcx as u32,
+            eax: ptRegs.rax as u32,
+            trapno: 0,
+            err: 0,
+            eip: ptRegs.rip as u32,
+            cs: ptRegs.cs as u32,
+            eflags: ptRegs.eflags as u32,
+            espAtSignal: ptRegs.rsp as u32,
+            ss: ptRegs.ss as u32,
+            fpstate: fpstate as u32,
+            oldmask: oldMask as u32,
+            cr2: cr2 as u32,
+        };
+    }
+}
+
+// struct ucontext_ia32, the sigset is 4 bytes aligned.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct UContext32 {
+    pub Flags: u32,
+    pub Link: u32,
+    pub Stack: SignalStack32,
+    pub MContext: SigContext32,
+    pub Sigset: [u32; 2],
+}
+
+// struct rt_sigframe_ia32, the frame of a SA_SIGINFO handler. The fp state is
+// below the frame.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct RtSigFrame32 {
+    pub pretcode: u32,
+    pub sig: i32,
+    pub pinfo: u32,
+    pub puc: u32,
+    pub info: SignalInfo32,
+    pub uc: UContext32,
+    pub retcode: [u8; 8],
+}
+
+impl RtSigFrame32 {
+    // movl $__NR_rt_sigreturn, %eax; int $0x80, only read by the debuggers
+    pub const RETCODE: [u8; 8] = [0xb8, 173, 0, 0, 0, 0xcd, 0x80, 0];
+}
+
+// struct sigframe_ia32, the frame of a handler without SA_SIGINFO.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct SigFrame32 {
+    pub pretcode: u32,
+    pub sig: i32,
+    pub sc: SigContext32,
+    // struct _fpstate_32, kept for the layout, the fp state is below the frame
+    pub fpstateUnused: [u32; 156],
+    pub extramask: [u32; 1],
+    pub retcode: [u8; 8],
+}
+
+impl SigFrame32 {
+    // popl %eax; movl $__NR_sigreturn, %eax; int $0x80
+    pub const RETCODE: [u8; 8] = [0x58, 0xb8, 119, 0, 0, 0, 0xcd, 0x80];
+}
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone, Default)]
 pub struct SigFlag(pub u64);
@@ -461,6 +736,67 @@ impl fmt::Debug for SigAct {
     }
 }
 
+// struct sigaction of the ia32 rt_sigaction, see compat_sigaction
+#[derive(Copy, Clone, Default)]
+#[repr(C)]
+pub struct SigAct32 {
+    pub handler: u32,
+    pub flags: u32,
+    pub restorer: u32,
+    pub mask: [u32; 2],
+}
+
+impl SigAct32 {
+    pub fn New(act: &SigAct) -> Self {
+        return Self {
+            handler: act.handler as u32,
+            flags: act.flags.0 as u32,
+            restorer: act.restorer as u32,
+            mask: [act.mask as u32, (act.mask >> 32) as u32],
+        };
+    }
+
+    pub fn SigAct(&self) -> SigAct {
+        return SigAct {
+            handler: self.handler as u64,
+            flags: SigFlag(self.flags as u64),
+            restorer: self.restorer as u64,
+            mask: self.mask[0] as u64 | (self.mask[1] as u64) << 32,
+        };
+    }
+}
+
+// struct old_sigaction of the ia32 sigaction, the mask only holds the
+// first 32 signals.
+#[derive(Copy, Clone, Default)]
+#[repr(C)]
+pub struct OldSigAct32 {
+    pub handler: u32,
+    pub mask: u32,
+    pub flags: u32,
+    pub restorer: u32,
+}
+
+impl OldSigAct32 {
+    pub fn New(act: &SigAct) -> Self {
+        return Self {
+            handler: act.handler as u32,
+            mask: act.mask as u32,
+            flags: act.flags.0 as u32,
+            restorer: act.restorer as u32,
+        };
+    }
+
+    pub fn SigAct(&self) -> SigAct {
+        return SigAct {
+            handler: self.handler as u64,
+            flags: SigFlag(self.flags as u64),
+            restorer: self.restorer as u64,
+            mask: self.mask as u64,
+        };
+    }
+}
+
 impl SigAct {
     // SignalActDefault is SIG_DFL and specifies that the default behavior for
     // a signal should be taken.
@@ -530,20 +866,20 @@ pub struct SignalQueue {
 }
 
 impl SignalQueue {
-    pub const RT_SIG_CAP: usize = 32;
-
     pub fn Len(&mut self) -> u64 {
         return self.signals.len() as u64;
     }
 
-    pub fn Enque(&mut self, info: Box<SignalInfo>, timer: Option<IntervalTimer>) -> bool {
-        if self.signals.len() == Self::RT_SIG_CAP {
-            return false;
-        }
-
+    pub fn Enque(
+        &mut self,
+        info: Box<SignalInfo>,
+        timer: Option<IntervalTimer>,
+        charge: Option<Arc<UserCharge>>,
+    ) -> bool {
         self.signals.push_back(PendingSignal {
             sigInfo: info,
             timer: timer,
+            charge: charge,
         });
 
         return true;
@@ -556,6 +892,10 @@ impl SignalQueue {
     pub fn Clear(&mut self) {
         self.signals.clear();
     }
+
+    pub fn Infos(&self) -> Vec<SignalInfo> {
+        return self.signals.iter().map(|s| *s.sigInfo).collect();
+    }
 }
 
 pub const SIGNAL_COUNT: usize = 64;
@@ -567,6 +907,7 @@ pub const RT_SIGNAL_START: usize = 32; // 32 ~ 64
 pub struct PendingSignal {
     pub sigInfo: Box<SignalInfo>,
     pub timer: Option<IntervalTimer>,
+    pub charge: Option<Arc<UserCharge>>,
 }
 
 pub struct PendingSignals {
@@ -631,7 +972,14 @@ impl Default for PendingSignals {
 }
 
 impl PendingSignals {
-    pub fn Enque(&mut self, info: Box<SignalInfo>, timer: Option<IntervalTimer>) -> Result<bool> {
+    // Enque queues the signal. Standard signals coalesce with an already
+    // pending instance, realtime signals queue in FIFO order.
+    pub fn Enque(
+        &mut self,
+        info: Box<SignalInfo>,
+        timer: Option<IntervalTimer>,
+        charge: Option<Arc<UserCharge>>,
+    ) -> Result<bool> {
         let sig = Signal(info.Signo);
         if sig.IsStandard() {
             match &self.stdSignals[sig.Index()] {
@@ -642,6 +990,7 @@ impl PendingSignals {
             self.stdSignals[sig.Index()] = Some(PendingSignal {
                 sigInfo: info,
                 timer: timer,
+                charge: charge,
             });
             self.pendingSet.Add(sig);
 
@@ -649,12 +998,16 @@ impl PendingSignals {
         } else if sig.IsRealtime() {
             let q = &mut self.rtSignals[sig.Index() - 31];
             self.pendingSet.Add(sig);
-            return Ok(q.Enque(info, timer));
+            return Ok(q.Enque(info, timer, charge));
         } else {
             return Err(Error::InvalidInput);
         }
     }
 
+    pub fn IsPending(&self, sig: Signal) -> bool {
+        return self.pendingSet.0 & SignalSet::New(sig).0 != 0;
+    }
+
     pub fn HasSignal(&self, mask: SignalSet) -> bool {
         let set = SignalSet(self.pendingSet.0 & !(mask.0));
 
@@ -708,6 +1061,22 @@ impl PendingSignals {
         }
     }
 
+    // Infos returns the pending signals in the order they are dequeued.
+    pub fn Infos(&self) -> Vec<SignalInfo> {
+        let mut infos = Vec::new();
+        for ps in self.stdSignals.iter() {
+            if let Some(ps) = ps {
+                infos.push(*ps.sigInfo);
+            }
+        }
+
+        for q in self.rtSignals.iter() {
+            infos.append(&mut q.Infos());
+        }
+
+        return infos;
+    }
+
     pub fn Discard(&mut self, sig: Signal) {
         self.pendingSet.0 &= !(1 << sig.Index());
 
@@ -753,6 +1122,11 @@ impl Default for SignalStack {
 impl SignalStack {
     pub const FLAG_ON_STACK: u32 = 1;
     pub const FLAG_DISABLE: u32 = 2;
+    // SS_AUTODISARM: disable the stack while a handler runs on it
+    pub const FLAG_AUTODISARM: u32 = 1 << 31;
+
+    // MINSIGSTKSZ
+    pub const MIN_SIZE: u64 = 2048;
 
     pub fn Contains(&self, sp: u64) -> bool {
         return self.addr < sp && sp <= self.addr + self.size;
@@ -766,6 +1140,10 @@ impl SignalStack {
         return self.flags & Self::FLAG_DISABLE == 0;
     }
 
+    pub fn AutoDisarm(&self) -> bool {
+        return self.flags & Self::FLAG_AUTODISARM != 0;
+    }
+
     pub fn Top(&self) -> u64 {
         return self.addr + self.size;
     }
@@ -823,7 +1201,7 @@ pub struct SigMask {
 }
 
 pub fn CopyInSigSetWithSize(task: &Task, addr: u64) -> Result<(u64, usize)> {
-    let mask: SigMask = task.CopyInObj(addr)?;
+    let mask: SigMask = UserPtr::New(addr).Read(task)?;
     return Ok((mask.addr, mask.len));
 }
 
@@ -838,6 +1216,6 @@ pub fn CopyInSigSet(task: &Task, sigSetAddr: u64, size: usize) -> Result<SignalS
         return Err(Error::SysError(SysErr::EINVAL));
     }
 
-    let mask: u64 = task.CopyInObj(sigSetAddr)?;
+    let mask: u64 = UserPtr::New(sigSetAddr).Read(task)?;
     return Ok(SignalSet(mask & !UnblockableSignals().0));
 }
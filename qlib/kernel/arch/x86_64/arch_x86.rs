@@ -14,13 +14,17 @@
 
 use crate::qlib::mutex::*;
 use alloc::sync::Arc;
+use core::sync::atomic::AtomicU64;
 use core::sync::atomic::AtomicUsize;
 use core::sync::atomic::Ordering;
 
+use super::super::super::super::common::*;
 use super::super::super::super::cpuid::*;
+use super::super::super::super::linux_def::*;
 use super::super::super::asm::*;
 use super::super::super::SignalDef::*;
 use super::super::super::FP_STATE;
+use super::super::super::PKRU_OFFSET;
 //use super::super::super::super::super::kernel_def::*;
 
 // System-related constants for x86.
@@ -110,6 +114,239 @@ pub const USER_DS: u64 = 0x2b; // guest ring 3 data selector
 pub const FS_TLS_SEL: u64 = 0x63; // Linux FS thread-local storage selector
 pub const GS_TLS_SEL: u64 = 0x6b; // Linux GS thread-local storage selector
 
+// The tls descriptors of a 32 bit process are the gdt entries 12-14, as
+// linux's x86_64 GDT_ENTRY_TLS_MIN. FS_TLS_SEL selects the first one.
+pub const GDT_ENTRY_TLS_MIN: usize = 12;
+pub const GDT_ENTRY_TLS_ENTRIES: usize = 3;
+
+// struct user_desc of set_thread_area, see arch/x86/include/uapi/asm/ldt.h
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct UserDesc {
+    pub entry_number: u32,
+    pub base_addr: u32,
+    pub limit: u32,
+    pub flags: u32,
+}
+
+impl UserDesc {
+    pub const SEG_32BIT: u32 = 1 << 0;
+    pub const CONTENTS_SHIFT: u32 = 1;
+    pub const CONTENTS_MASK: u32 = 3 << Self::CONTENTS_SHIFT;
+    pub const READ_EXEC_ONLY: u32 = 1 << 3;
+    pub const LIMIT_IN_PAGES: u32 = 1 << 4;
+    pub const SEG_NOT_PRESENT: u32 = 1 << 5;
+    pub const USEABLE: u32 = 1 << 6;
+    pub const LM: u32 = 1 << 7;
+
+    // IsEmpty returns true if the desc clears the entry, see linux's
+    // LDT_empty and LDT_zero.
+    pub fn IsEmpty(&self) -> bool {
+        if self.base_addr != 0 || self.limit != 0 {
+            return false;
+        }
+
+        let flags = self.flags & !Self::LM;
+        return flags == 0 || flags == Self::READ_EXEC_ONLY | Self::SEG_NOT_PRESENT;
+    }
+
+    // Valid is tls_desc_okay of linux, only the 32 bit data segments are
+    // allowed.
+    pub fn Valid(&self) -> bool {
+        if self.IsEmpty() {
+            return true;
+        }
+
+        if self.flags & Self::SEG_32BIT == 0 {
+            return false;
+        }
+
+        if (self.flags & Self::CONTENTS_MASK) >> Self::CONTENTS_SHIFT > 1 {
+            return false;
+        }
+
+        return self.flags & Self::LM == 0;
+    }
+
+    // Desc encodes the segment descriptor as linux's fill_ldt.
+    pub fn Desc(&self) -> u64 {
+        if self.IsEmpty() {
+            return 0;
+        }
+
+        let base = self.base_addr as u64;
+        let limit = self.limit as u64;
+        let flags = self.flags;
+
+        let mut typ = 1; // accessed
+        if flags & Self::READ_EXEC_ONLY == 0 {
+            typ |= 1 << 1;
+        }
+        typ |= (((flags & Self::CONTENTS_MASK) >> Self::CONTENTS_SHIFT) as u64) << 2;
+
+        let mut desc = (limit & 0xffff) | (base & 0xff_ffff) << 16;
+        desc |= typ << 40;
+        desc |= 1 << 44; // code or data segment
+        desc |= 3 << 45; // dpl 3
+        if flags & Self::SEG_NOT_PRESENT == 0 {
+            desc |= 1 << 47;
+        }
+        desc |= ((limit >> 16) & 0xf) << 48;
+        if flags & Self::USEABLE != 0 {
+            desc |= 1 << 52;
+        }
+        if flags & Self::SEG_32BIT != 0 {
+            desc |= 1 << 54;
+        }
+        if flags & Self::LIMIT_IN_PAGES != 0 {
+            desc |= 1 << 55;
+        }
+        desc |= (base >> 24) << 56;
+
+        return desc;
+    }
+
+    // FromDesc decodes the segment descriptor of the entry, as linux's
+    // fill_user_desc.
+    pub fn FromDesc(entry: u32, desc: u64) -> Self {
+        let mut info = Self {
+            entry_number: entry,
+            ..Default::default()
+        };
+
+        if desc == 0 {
+            info.flags = Self::READ_EXEC_ONLY | Self::SEG_NOT_PRESENT;
+            return info;
+        }
+
+        info.base_addr = ((desc >> 16) & 0xff_ffff | (desc >> 56) << 24) as u32;
+        info.limit = (desc & 0xffff | ((desc >> 48) & 0xf) << 16) as u32;
+
+        let typ = (desc >> 40) & 0xf;
+        if typ & (1 << 1) == 0 {
+            info.flags |= Self::READ_EXEC_ONLY;
+        }
+        info.flags |= (((typ >> 2) & 3) as u32) << Self::CONTENTS_SHIFT;
+        if desc & (1 << 47) == 0 {
+            info.flags |= Self::SEG_NOT_PRESENT;
+        }
+        if desc & (1 << 52) != 0 {
+            info.flags |= Self::USEABLE;
+        }
+        if desc & (1 << 54) != 0 {
+            info.flags |= Self::SEG_32BIT;
+        }
+        if desc & (1 << 55) != 0 {
+            info.flags |= Self::LIMIT_IN_PAGES;
+        }
+
+        return info;
+    }
+}
+
+// Tls32 is the thread local storage of a 32 bit task. The descriptors set by
+// set_thread_area live in the gdt of the vcpu, so they are written there
+// with the fs and gs selectors of the task when it is switched in.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Tls32 {
+    pub compat: bool,
+    pub desc: [u64; GDT_ENTRY_TLS_ENTRIES],
+    pub fs: u16,
+    pub gs: u16,
+}
+
+impl Tls32 {
+    pub const DESC_PRESENT: u64 = 1 << 47;
+
+    // Reset sets up the tls of a task which execs the program.
+    pub fn Reset(&mut self, compat: bool) {
+        *self = Self {
+            compat: compat,
+            ..Default::default()
+        };
+    }
+
+    // Save reads the selectors of the task switched out, the kernel doesn't
+    // change them.
+    pub fn Save(&mut self) {
+        self.fs = GetFsSel();
+        self.gs = GetGsSel();
+    }
+
+    // Load writes the descriptors in the gdt of the current vcpu and loads
+    // the selectors.
+    pub fn Load(&self) {
+        let gdt = GdtBase();
+        for i in 0..GDT_ENTRY_TLS_ENTRIES {
+            let addr = gdt + ((GDT_ENTRY_TLS_MIN + i) * 8) as u64;
+            unsafe { *(addr as *mut u64) = self.desc[i] }
+        }
+
+        SetFsSel(self.ValidSel(self.fs));
+        SetUserGsSel(self.ValidSel(self.gs));
+    }
+
+    // ValidSel returns the selector to load for sel. The user may only hold
+    // the null one, the data segment and the tls entries, a tls selector
+    // whose descriptor is cleared or not present would fault. There is no
+    // ldt.
+    fn ValidSel(&self, sel: u16) -> u16 {
+        if sel == UDATA {
+            return sel;
+        }
+
+        if sel & 0x7 != 0x3 {
+            return 0;
+        }
+
+        let idx = (sel >> 3) as usize;
+        if idx < GDT_ENTRY_TLS_MIN
+            || idx >= GDT_ENTRY_TLS_MIN + GDT_ENTRY_TLS_ENTRIES
+            || self.desc[idx - GDT_ENTRY_TLS_MIN] & Self::DESC_PRESENT == 0
+        {
+            return 0;
+        }
+
+        return sel;
+    }
+
+    // SetDesc sets the entry of info, entry_number -1 picks a free one and
+    // it is returned in info.
+    pub fn SetDesc(&mut self, info: &mut UserDesc) -> Result<()> {
+        if !info.Valid() {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        let mut idx = info.entry_number as usize;
+        if info.entry_number == u32::MAX {
+            idx = match self.desc.iter().position(|d| *d == 0) {
+                None => return Err(Error::SysError(SysErr::ESRCH)),
+                Some(i) => i + GDT_ENTRY_TLS_MIN,
+            };
+            info.entry_number = idx as u32;
+        }
+
+        if idx < GDT_ENTRY_TLS_MIN || idx >= GDT_ENTRY_TLS_MIN + GDT_ENTRY_TLS_ENTRIES {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        self.desc[idx - GDT_ENTRY_TLS_MIN] = info.Desc();
+        return Ok(());
+    }
+
+    pub fn GetDesc(&self, entry: u32) -> Result<UserDesc> {
+        let idx = entry as usize;
+        if idx < GDT_ENTRY_TLS_MIN || idx >= GDT_ENTRY_TLS_MIN + GDT_ENTRY_TLS_ENTRIES {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        return Ok(UserDesc::FromDesc(
+            entry,
+            self.desc[idx - GDT_ENTRY_TLS_MIN],
+        ));
+    }
+}
+
 // MXCSR_DEFAULT is the reset value of MXCSR (Intel SDM Vol. 2, Ch. 3.2
 // "LDMXCSR")
 pub const MXCSR_DEFAULT: u32 = 0x1f80;
@@ -125,6 +362,9 @@ pub const MXCSR_OFFSET: usize = 24;
 pub struct X86fpstate {
     pub data: [u8; 4096],
     pub size: AtomicUsize,
+    // token identifies the state in the fp registers of the vcpu which saved
+    // it last, it is 0 when the state has been changed in memory since
+    pub token: AtomicU64,
 }
 
 impl Default for X86fpstate {
@@ -171,6 +411,10 @@ impl X86fpstate {
     // start of the FXSAVE area.
     pub const MXCSR_MASK_OFFSET : usize = 28;
 
+    // initPKRU is the pkru of a new state, which denies the access to all the
+    // protection keys except the key 0 like Linux does.
+    pub const INIT_PKRU : u32 = 0x55555554;
+
     fn New() -> Self {
         let (size, _align) = HostFeatureSet().ExtendedStateSize();
 
@@ -181,10 +425,13 @@ impl X86fpstate {
         return Self {
             data: [0; 4096],
             size: AtomicUsize::new(size as usize),
+            token: AtomicU64::new(0),
         };
     }
 
     pub fn SanitizeUser(&self) {
+        self.Modified();
+
         // Force reserved bits in MXCSR to 0. This is consistent with Linux.
         self.SanitizeMXCSR();
 
@@ -242,7 +489,48 @@ impl X86fpstate {
     }
 
     pub fn Load() -> Self {
-        return FP_STATE.Fork();
+        let f = FP_STATE.Fork();
+        f.SetPKRU(Self::INIT_PKRU);
+        return f;
+    }
+
+    // PKRU returns the pkru saved in the state, it is 0 when the pkru component
+    // is in its init state
+    pub fn PKRU(&self) -> u32 {
+        let offset = PKRU_OFFSET.load(Ordering::Relaxed);
+        if offset == 0 || offset + 4 > self.Size() {
+            return 0;
+        }
+
+        let xstateBVAddr = &self.data[Self::XSTATE_BVOFFSET] as * const _ as u64;
+        let xstateBV : u64 = unsafe {
+            *(xstateBVAddr as * const u64)
+        };
+
+        if xstateBV & XSAVEFeature::XSAVEFeaturePKRU as u64 == 0 {
+            return 0;
+        }
+
+        let pkruAddr = &self.data[offset] as * const _ as u64;
+        return unsafe {
+            *(pkruAddr as * const u32)
+        };
+    }
+
+    // SetPKRU sets the pkru restored to the register on the return to the user
+    pub fn SetPKRU(&self, pkru: u32) {
+        let offset = PKRU_OFFSET.load(Ordering::Relaxed);
+        if offset == 0 || offset + 4 > self.Size() {
+            return;
+        }
+
+        self.Modified();
+        let pkruAddr = &self.data[offset] as * const _ as u64;
+        let xstateBVAddr = &self.data[Self::XSTATE_BVOFFSET] as * const _ as u64;
+        unsafe {
+            *(pkruAddr as * mut u32) = pkru;
+            *(xstateBVAddr as * mut u64) |= XSAVEFeature::XSAVEFeaturePKRU as u64;
+        };
     }
 
     pub fn Size(&self) -> usize {
@@ -250,6 +538,8 @@ impl X86fpstate {
     }
 
     pub fn Slice(&self) -> &'static mut [u8] {
+        // the caller may change the state
+        self.Modified();
         let ptr = &self.data[0] as * const _ as u64 as *mut u8;
         let buf = unsafe { core::slice::from_raw_parts_mut(ptr, self.Size()) };
         return buf
@@ -259,6 +549,7 @@ impl X86fpstate {
         return Self {
             data: [0; 4096],
             size: AtomicUsize::new(4096),
+            token: AtomicU64::new(0),
         };
     }
 
@@ -291,6 +582,20 @@ impl X86fpstate {
     pub fn RestoreFp(&self) {
         LoadFloatingPoint(self.FloatingPointData());
     }
+
+    pub fn Token(&self) -> u64 {
+        return self.token.load(Ordering::Relaxed);
+    }
+
+    pub fn SetToken(&self, token: u64) {
+        self.token.store(token, Ordering::Relaxed);
+    }
+
+    // Modified drops the token after the state in memory is changed, the next
+    // RestoreFp of the task reloads the registers
+    pub fn Modified(&self) {
+        self.token.store(0, Ordering::Relaxed);
+    }
 }
 
 pub struct State {
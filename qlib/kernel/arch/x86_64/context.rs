@@ -86,6 +86,22 @@ pub const PREFERRED_TOP_DOWN_BASE_MIN: u64 =
 // randomization to stay above PREFERRED_TOP_DOWN_BASE_MIN.
 pub const MIN_MMAP_RAND64: u64 = (1 << 26) * MemoryDef::PAGE_SIZE;
 
+// MAX_ADDR32 is the maximum userspace address for a 32-bit compat process.
+// It is TASK_SIZE (IA32_PAGE_OFFSET) in Linux.
+pub const MAX_ADDR32: u64 = 0xffffe000;
+
+// MAX_STACK_RAND32 is STACK_RND_MASK_32 pages, 8 MB.
+pub const MAX_STACK_RAND32: u64 = 0x7ff * MemoryDef::PAGE_SIZE;
+
+// MAX_MMAP_RAND32 is the mmap randomization with mmap_rnd_compat_bits 8.
+pub const MAX_MMAP_RAND32: u64 = (1 << 8) * MemoryDef::PAGE_SIZE;
+
+// MIN_GAP32 is MIN_GAP in Linux for a 32-bit compat process.
+pub const MIN_GAP32: u64 = (128 << 20) + MAX_STACK_RAND32;
+
+// PREFERRED_PIELOAD_ADDR32 is ELF_ET_DYN_BASE in Linux for an ia32 process.
+pub const PREFERRED_PIELOAD_ADDR32: u64 = 0x400000;
+
 pub struct Context64 {
     pub state: State,
 }
@@ -196,6 +212,7 @@ impl Context64 {
             // randomization to avoiding eating into the gap.
             MaxStackRand: maxRand,
             sharedLoadsOffset: 0,
+            compat: false,
         };
 
         // Final sanity check on the layout.
@@ -206,6 +223,54 @@ impl Context64 {
         return Ok(l);
     }
 
+    // NewMmapLayout32 is the mmap layout of a 32-bit compat process. All of it
+    // is below MAX_ADDR32 so that the pointers fit in 32 bits.
+    pub fn NewMmapLayout32(min: u64, r: &LimitSet) -> Result<MmapLayout> {
+        let min = Addr(min).RoundUp()?.0;
+        let max = Addr(MAX_ADDR32).RoundDown()?.0;
+
+        let stackSize = r.Get(LimitType::Stack);
+
+        let maxGap = (max / 6) * 5;
+        let mut gap = stackSize.Cur;
+        if gap < MIN_GAP32 {
+            gap = MIN_GAP32;
+        }
+
+        if gap > maxGap {
+            gap = maxGap;
+        }
+
+        let mut defaultDir = MMAP_TOP_DOWN;
+        if stackSize.Cur == INFINITY {
+            defaultDir = MMAP_BOTTOM_UP;
+        }
+
+        let rnd = MMapRand(MAX_MMAP_RAND32)?;
+        let l = MmapLayout {
+            MinAddr: min,
+            MaxAddr: max,
+            BottomUpBase: Addr(max / 3 + rnd).RoundDown()?.0,
+            TopDownBase: Addr(max - gap - rnd).RoundDown()?.0,
+            DefaultDirection: defaultDir,
+            MaxStackRand: MAX_MMAP_RAND32,
+            sharedLoadsOffset: 0,
+            compat: true,
+        };
+
+        if !l.Valid() {
+            panic!("Invalid MmapLayout32: {:?}", l);
+        }
+
+        return Ok(l);
+    }
+
+    // PIELoadAddress32 is the PIE load address of a 32-bit compat process.
+    pub fn PIELoadAddress32(_l: &MmapLayout) -> Result<u64> {
+        let addr = PREFERRED_PIELOAD_ADDR32 + MMapRand(MAX_MMAP_RAND32)?;
+        return Ok(Addr(addr).RoundDown().unwrap().0);
+    }
+
     // PIELoadAddress implements Context.PIELoadAddress.
     pub fn PIELoadAddress(l: &MmapLayout) -> Result<u64> {
         let mut base = PREFERRED_PIELOAD_ADDR;
@@ -21,6 +21,7 @@
 .globl simd_fp_handler
 .globl virtualization_handler
 .globl security_handler
+.globl int80_handler
 
 .extern syscall_handler, CopyData,
 
@@ -44,6 +45,7 @@
 .extern SIMDFPHandler
 .extern VirtualizationHandler
 .extern SecurityHandler
+.extern Int80Handler
 
 .intel_syntax noprefix
 
@@ -404,6 +406,10 @@ virtualization_handler:
 security_handler:
     HandlerWithoutErrorCode SecurityHandler
 
+// int 0x80 from 32 bit compat tasks, the frame layout is same as exceptions
+int80_handler:
+    HandlerWithoutErrorCode Int80Handler
+
 initX86FPState:
     // Save MXCSR (callee-save)
     STMXCSR     [rsp - 8]
@@ -14,6 +14,7 @@
 
 //use super::super::perf_tunning::*;
 
+use super::super::common::{UCODE32, UDATA};
 use super::{SUPPORT_XSAVE, SUPPORT_XSAVEOPT};
 use core::sync::atomic::Ordering;
 
@@ -118,6 +119,51 @@ pub fn EnterUser(entry: u64, userStackAddr: u64, kernelStackAddr: u64) {
     }
 }
 
+// EnterUser32 enters a 32 bit compat process. sysret can't load the 32 bit
+// code segment so it goes through iretq.
+#[inline]
+pub fn EnterUser32(entry: u64, userStackAddr: u64, kernelStackAddr: u64) -> ! {
+    unsafe {
+        llvm_asm!("
+            fninit
+            mov gs:0, rdx
+
+            /* iretq frame: ss, rsp, rflags, cs, rip */
+            push r9
+            push rsi
+            push 0x202
+            push r8
+            push rdi
+
+            /* clean up registers */
+            xor rax, rax
+            xor rbx, rbx
+            xor rcx, rcx
+            xor rdx, rdx
+            xor rdi, rdi
+            xor rsi, rsi
+            xor rbp, rbp
+            xor r8, r8
+            xor r9, r9
+            xor r10, r10
+            xor r11, r11
+            xor r12, r12
+            xor r13, r13
+            xor r14, r14
+            xor r15, r15
+
+            swapgs
+            iretq
+              "
+              :
+              : "{rdi}"(entry), "{rsi}"(userStackAddr), "{rdx}"(kernelStackAddr),
+                "{r8}"(UCODE32 as u64), "{r9}"(UDATA as u64)
+              : "memory"
+              : "intel", "volatile");
+        ::core::intrinsics::unreachable();
+    }
+}
+
 #[inline]
 pub fn SyscallRet(kernelRsp: u64) -> ! {
     unsafe {
@@ -200,6 +246,13 @@ pub fn GetRsp() -> u64 {
     return rsp;
 }
 
+#[inline]
+pub fn GetRbp() -> u64 {
+    let rbp: u64;
+    unsafe { llvm_asm!("mov %rbp, $0" : "=r" (rbp) : : "memory" : "volatile" ) };
+    return rbp;
+}
+
 #[inline]
 pub fn Invlpg(addr: u64) {
     if !super::SHARESPACE.config.read().KernelPagetable {
@@ -316,6 +369,64 @@ pub fn SetRflags(val: u64) {
     };
 }
 
+// GdtBase returns the address of the gdt of the current vcpu.
+#[inline]
+pub fn GdtBase() -> u64 {
+    // the 2 bytes limit and the 8 bytes base
+    let mut gdtr: [u16; 5] = [0; 5];
+    unsafe {
+        llvm_asm!("sgdt ($0)" : : "r"(&mut gdtr as *mut _ as u64) : "memory" : "volatile");
+    }
+
+    return (gdtr[1] as u64)
+        | (gdtr[2] as u64) << 16
+        | (gdtr[3] as u64) << 32
+        | (gdtr[4] as u64) << 48;
+}
+
+#[inline]
+pub fn GetFsSel() -> u16 {
+    let sel: u16;
+    unsafe {
+        llvm_asm!("movw %fs, $0" : "=r"(sel) : : "memory" : "volatile");
+    }
+    return sel;
+}
+
+#[inline]
+pub fn GetGsSel() -> u16 {
+    let sel: u16;
+    unsafe {
+        llvm_asm!("movw %gs, $0" : "=r"(sel) : : "memory" : "volatile");
+    }
+    return sel;
+}
+
+// SetFsSel loads the fs selector, the fs base is loaded from its descriptor.
+#[inline]
+pub fn SetFsSel(sel: u16) {
+    unsafe {
+        llvm_asm!("movw $0, %fs" : : "r"(sel) : "memory" : "volatile");
+    }
+}
+
+// SetUserGsSel loads the gs selector of the user. The gs base of the kernel
+// is the active one, so the load is done between 2 swapgs with the
+// interrupts off and the base of the descriptor goes to the user gs base.
+#[inline]
+pub fn SetUserGsSel(sel: u16) {
+    unsafe {
+        llvm_asm!("
+            pushfq
+            cli
+            swapgs
+            movw $0, %gs
+            swapgs
+            popfq
+        " : : "r"(sel) : "memory" : "volatile");
+    }
+}
+
 pub fn SaveFloatingPoint(addr: u64) {
     if SUPPORT_XSAVEOPT.load(Ordering::Acquire) {
         xsaveopt(addr);
@@ -488,3 +599,13 @@ pub fn xgetbv() -> u64 {
     let val = ((val_h as u64) << 32) | ((val_l as u64) & 0xffff);
     return val;
 }
+
+// wrpkru is emitted as bytes as the assembler may not know it
+pub fn wrpkru(val: u32) {
+    unsafe {
+        llvm_asm!("\
+            .byte 0x0f, 0x01, 0xef
+        " : : "{eax}"(val), "{rcx}"(0u64), "{rdx}"(0u64)
+        : "memory" : "intel", "volatile")
+    };
+}
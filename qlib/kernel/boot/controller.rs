@@ -21,9 +21,14 @@ use super::super::super::super::kernel_def::{
 };
 use super::super::super::common::*;
 use super::super::super::control_msg::*;
+use super::super::super::metric::*;
 use super::super::super::vcpu_mgr::*;
+use super::super::kernel::checkpoint::*;
+use super::super::memmgr::balloon::*;
+use super::super::strace::*;
 use super::super::task::*;
 use super::super::taskMgr;
+use super::super::tracing::*;
 use super::super::Kernel;
 use super::super::SetWaitContainerfd;
 use super::super::WaitContainerfd;
@@ -64,7 +69,14 @@ pub fn HandleSignal(signalArgs: &SignalArgs) {
             }
         }
         SignalDeliveryMode::DeliverToAllProcesses => {
-            match LOADER.Lock(task).unwrap().SignalAll(signalArgs.Signo) {
+            let loader = LOADER.Lock(task).unwrap();
+            // the signal to a sub container only goes to its own processes
+            let res = if signalArgs.CID.len() == 0 || signalArgs.CID == loader.sandboxID {
+                loader.SignalAll(signalArgs.Signo)
+            } else {
+                loader.SignalAllProcesses(&signalArgs.CID, signalArgs.Signo)
+            };
+            match res {
                 Err(e) => {
                     info!("signal DeliverToAllProcesses fail with error {:?}", e);
                 }
@@ -183,7 +195,11 @@ pub fn ControlMsgHandler(fd: *const u8) {
             }
         }
         Payload::StartSubContainer(startArgs) => {
-            match LOADER.StartSubContainer(startArgs.process) {
+            match LOADER.StartSubContainer(
+                startArgs.process,
+                startArgs.pidsLimit,
+                startArgs.cpuWeight,
+            ) {
                 Ok((_, entry, userStackAddr, kernelStackAddr)) => {
                     WriteControlMsgResp(fd, &UCallResp::StartSubContainerResp, true);
                     StartSubContainerProcess(entry, userStackAddr, kernelStackAddr);
@@ -196,6 +212,163 @@ pub fn ControlMsgHandler(fd: *const u8) {
         Payload::WaitAll => {
             SetWaitContainerfd(fd);
         }
+        Payload::Strace(straceArgs) => {
+            match STRACE.Configure(&straceArgs) {
+                Ok(()) => WriteControlMsgResp(fd, &UCallResp::StraceResp, true),
+                Err(e) => {
+                    WriteControlMsgResp(fd, &UCallResp::UCallRespErr(format!("{:?}", e)), true);
+                }
+            }
+        }
+        Payload::Trace(traceArgs) => {
+            let res = match traceArgs.Op {
+                TraceOp::Start => {
+                    TRACER.Start(SHARESPACE.scheduler.vcpuCnt, traceArgs.RingSize);
+                    Ok(())
+                }
+                TraceOp::Stop => {
+                    TRACER.Stop();
+                    Ok(())
+                }
+                TraceOp::Dump => {
+                    let outputFd = traceArgs.fds[0];
+                    let output = TRACER.Dump();
+                    let res = WriteAll(outputFd, output.as_ptr() as u64, output.len());
+                    Kernel::HostSpace::Close(outputFd);
+                    res
+                }
+            };
+
+            match res {
+                Ok(()) => WriteControlMsgResp(fd, &UCallResp::TraceResp, true),
+                Err(e) => {
+                    WriteControlMsgResp(fd, &UCallResp::UCallRespErr(format!("{:?}", e)), true);
+                }
+            }
+        }
+        Payload::Checkpoint(args) => {
+            let kernel = LOADER.Lock(task).unwrap().kernel.clone();
+            let imageFd = args.fds[0];
+            if !args.PreDump {
+                kernel.Pause();
+            }
+            let res = Checkpoint(&kernel, imageFd, args.PreDump, args.Incremental);
+            Kernel::HostSpace::Close(imageFd);
+            // the sandbox is only left paused after a successful checkpoint
+            if !args.PreDump && (args.LeaveRunning || res.is_err()) {
+                kernel.Unpause();
+            }
+
+            match res {
+                Ok(pages) => WriteControlMsgResp(fd, &UCallResp::CheckpointResp(pages), true),
+                Err(e) => {
+                    WriteControlMsgResp(fd, &UCallResp::UCallRespErr(format!("{:?}", e)), true);
+                }
+            }
+        }
+        Payload::Restore(args) => {
+            let kernel = LOADER.Lock(task).unwrap().kernel.clone();
+            let res = Restore(&kernel, &args);
+            Kernel::HostSpace::Close(args.fds[0]);
+
+            match res {
+                Ok(pids) => WriteControlMsgResp(fd, &UCallResp::RestoreResp(pids), true),
+                Err(e) => {
+                    WriteControlMsgResp(fd, &UCallResp::UCallRespErr(format!("{:?}", e)), true);
+                }
+            }
+        }
+        Payload::Balloon(args) => match BALLOON.SetTarget(args.TargetSize) {
+            Ok(size) => {
+                let info = BalloonInfo {
+                    Size: size,
+                    Free: BALLOON.FreeMem(),
+                };
+                WriteControlMsgResp(fd, &UCallResp::BalloonResp(info), true);
+            }
+            Err(e) => {
+                WriteControlMsgResp(fd, &UCallResp::UCallRespErr(format!("{:?}", e)), true);
+            }
+        },
+        Payload::FsFreeze(args) => {
+            let kernel = LOADER.Lock(task).unwrap().kernel.clone();
+            let res = if args.Freeze {
+                kernel.FreezeFilesystems(task)
+            } else {
+                kernel.ThawFilesystems()
+            };
+
+            match res {
+                Ok(count) => {
+                    WriteControlMsgResp(fd, &UCallResp::FsFreezeResp(count as u64), true);
+                }
+                Err(e) => {
+                    WriteControlMsgResp(fd, &UCallResp::UCallRespErr(format!("{:?}", e)), true);
+                }
+            }
+        }
+        Payload::Metrics(args) => {
+            let outputFd = args.fds[0];
+            let metrics = ALL_METRICS.lock().Export();
+            let output: Vec<u8> = serde_json::to_vec(&metrics).expect("Metrics ser fail...");
+            let res = WriteAll(outputFd, output.as_ptr() as u64, output.len());
+            Kernel::HostSpace::Close(outputFd);
+
+            match res {
+                Ok(()) => WriteControlMsgResp(fd, &UCallResp::MetricsResp, true),
+                Err(e) => {
+                    WriteControlMsgResp(fd, &UCallResp::UCallRespErr(format!("{:?}", e)), true);
+                }
+            }
+        }
+        Payload::ContainerState(cid) => {
+            let kernel = LOADER.Lock(task).unwrap().kernel.clone();
+            match kernel.Container(&cid) {
+                None => {
+                    let err = format!("container {} doesn't exist", &cid);
+                    WriteControlMsgResp(fd, &UCallResp::UCallRespErr(err), true);
+                }
+                Some(c) => {
+                    let pid = match c.Init() {
+                        None => 0,
+                        Some(tg) => kernel.RootPIDNamespace().IDOfThreadGroup(&tg),
+                    };
+                    let info = ContainerInfo {
+                        ID: cid,
+                        Status: c.Status().String(),
+                        Pid: pid,
+                        Tasks: c.TaskCount(),
+                        Memory: c.CgroupMemoryStat(&kernel).Current(),
+                    };
+                    WriteControlMsgResp(fd, &UCallResp::ContainerStateResp(info), true);
+                }
+            }
+        }
+        Payload::UpdateConfig(update) => {
+            // the config lock must be released before any logging
+            let res = {
+                let mut config = SHARESPACE.config.write();
+                match config.Update(&update) {
+                    Ok(()) => Ok(*config),
+                    Err(e) => Err(e),
+                }
+            };
+
+            match res {
+                Ok(config) => {
+                    match update.Trace {
+                        Some(true) => TRACER.Start(SHARESPACE.scheduler.vcpuCnt, 0),
+                        Some(false) => TRACER.Stop(),
+                        None => (),
+                    }
+                    info!("UpdateConfig {:?}", &update);
+                    WriteControlMsgResp(fd, &UCallResp::UpdateConfigResp(config), true);
+                }
+                Err(e) => {
+                    WriteControlMsgResp(fd, &UCallResp::UCallRespErr(format!("{:?}", e)), true);
+                }
+            }
+        }
     }
 
     // free curent task in the waitfn context
@@ -29,9 +29,11 @@ use super::super::fs::host::fs::*;
 use super::super::fs::host::util::*;
 use super::super::fs::inode::*;
 use super::super::fs::mount::*;
+use super::super::fs::mount_overlay::*;
 use super::super::fs::overlay::*;
 use super::super::fs::ramfs::tree::*;
 use super::super::task::*;
+use super::super::SHARESPACE;
 
 use super::*;
 
@@ -67,6 +69,7 @@ fn CreateRootMount(
         &WhitelistFileSystem::New(),
         &mf,
         false,
+        SHARESPACE.config.read().HostFileCache,
     );
     let hostRoot = Inode::NewHostInode(&Arc::new(QMutex::new(ms)), fd, &fstat, writeable)?;
 
@@ -112,7 +115,7 @@ fn GetMountNameAndOptions(_conf: &config::Config, m: &oci::Mount) -> Result<(Str
         }
         TMPFS => {
             fsName = m.typ.to_string();
-            opts = ParseAndFilterOptions(&m.options, &vec!["mode", "uid", "gid"])?;
+            opts = ParseAndFilterOptions(&m.options, &vec!["mode", "uid", "gid", "size"])?;
         }
         _ => {
             info!("ignoring unknown filesystem type {}", m.typ);
@@ -145,7 +148,12 @@ pub fn InitTestSpec() -> oci::Spec {
     };
 }
 
-pub fn InitRootFs(task: &mut Task, root: &str) -> Result<MountNs> {
+pub fn InitRootFs(
+    task: &mut Task,
+    root: &str,
+    maskedPaths: &Vec<String>,
+    readonlyPaths: &Vec<String>,
+) -> Result<MountNs> {
     let config = config::Config {
         RootDir: root.to_string(),
         Debug: true,
@@ -153,7 +161,13 @@ pub fn InitRootFs(task: &mut Task, root: &str) -> Result<MountNs> {
 
     debug!("init rootfs under {} for container", root);
 
-    return SetupContainerFS(task, &InitTestSpec(), &config);
+    let mns = SetupContainerFS(task, &InitTestSpec(), &config)?;
+    let root = mns.Root();
+
+    // as runc, the readonly paths are applied before the masked paths
+    ReadonlyPaths(task, &mns, &root, readonlyPaths)?;
+    MaskPaths(task, &mns, &root, maskedPaths)?;
+    return Ok(mns);
 }
 
 // This function will be used by both root container and subcontainer
@@ -195,6 +209,8 @@ fn CompileMounts(spec: &oci::Spec) -> Vec<oci::Mount> {
         options: Vec::new(),
     });
 
+    mounts.push(ShmMount(spec));
+
     mounts.push(oci::Mount {
         destination: "/proc".to_string(),
         typ: PROCFS.to_string(),
@@ -254,6 +270,29 @@ fn CompileMounts(spec: &oci::Spec) -> Vec<oci::Mount> {
     return mandatoryMounts;
 }
 
+// ShmMount is the tmpfs for the POSIX shared memory of the container. The
+// options of the spec's /dev/shm tmpfs, e.g. its size, are kept. As the other
+// tmpfs, the files are released with the mount namespace of the container.
+fn ShmMount(spec: &oci::Spec) -> oci::Mount {
+    for m in &spec.mounts {
+        if Clean(&m.destination).as_str() == "/dev/shm" && m.typ.as_str() == TMPFS {
+            return oci::Mount {
+                destination: "/dev/shm".to_string(),
+                typ: TMPFS.to_string(),
+                source: m.source.clone(),
+                options: m.options.clone(),
+            };
+        }
+    }
+
+    return oci::Mount {
+        destination: "/dev/shm".to_string(),
+        typ: TMPFS.to_string(),
+        source: "shm".to_string(),
+        options: vec!["mode=1777".to_string(), "noexec".to_string()],
+    };
+}
+
 fn MountSubmounts(
     task: &Task,
     config: &config::Config,
@@ -331,6 +370,9 @@ fn MountSubmount(
         info!("adding submount overlay over {}", m.destination);
         inode = AddSubmountOverlay(task, &inode, &submounts)?;
     }
+    if m.source.len() > 0 {
+        inode.lock().MountSource.lock().Source = m.source.clone();
+    }
     MakeMountPoint(task, mns, root, &m.destination)?;
     let mut maxTraversals = 0;
     let dirent = mns.FindDirent(
@@ -347,6 +389,94 @@ fn MountSubmount(
     return Ok(());
 }
 
+// FindPath returns the dirent of the path, or None if it doesn't exist.
+fn FindPath(task: &Task, mns: &MountNs, root: &Dirent, path: &str) -> Result<Option<Dirent>> {
+    let mut maxTraversals = 0;
+    match mns.FindDirent(
+        task,
+        root,
+        Some(root.clone()),
+        path,
+        &mut maxTraversals,
+        true,
+    ) {
+        Ok(dirent) => return Ok(Some(dirent)),
+        Err(Error::SysError(SysErr::ENOENT)) => return Ok(None),
+        Err(e) => return Err(e),
+    }
+}
+
+// MaskPaths hides the masked paths of the spec, e.g. /proc/kcore: a
+// directory is covered by an empty readonly tmpfs and a file by /dev/null.
+// The paths which don't exist are skipped.
+fn MaskPaths(task: &Task, mns: &MountNs, root: &Dirent, paths: &Vec<String>) -> Result<()> {
+    for path in paths {
+        let dirent = match FindPath(task, mns, root, path)? {
+            None => continue,
+            Some(dirent) => dirent,
+        };
+
+        let inode = if dirent.Inode().StableAttr().IsDir() {
+            let mf = MountSourceFlags {
+                ReadOnly: true,
+                ..Default::default()
+            };
+            MustFindFilesystem(TMPFS).lock().Mount(
+                task,
+                &"none".to_string(),
+                &mf,
+                &"".to_string(),
+            )?
+        } else {
+            match FindPath(task, mns, root, "/dev/null")? {
+                None => return Err(Error::SysError(SysErr::ENOENT)),
+                Some(null) => null.Inode(),
+            }
+        };
+
+        mns.Mount(&dirent, &inode)?;
+        info!("masked {}", path);
+    }
+
+    return Ok(());
+}
+
+// ReadonlyPaths makes the readonly paths of the spec readonly, e.g.
+// /proc/sys. The path is covered by an overlay of its own inode whose mount
+// source is readonly, so that the writes fail with EROFS. The paths which
+// don't exist are skipped.
+fn ReadonlyPaths(task: &Task, mns: &MountNs, root: &Dirent, paths: &Vec<String>) -> Result<()> {
+    for path in paths {
+        let dirent = match FindPath(task, mns, root, path)? {
+            None => continue,
+            Some(dirent) => dirent,
+        };
+
+        // an overlay can't be the upper of another overlay, take the inode
+        // it shows instead
+        let inode = dirent.Inode();
+        let overlay = inode.lock().Overlay.clone();
+        let upper = match overlay {
+            None => inode.clone(),
+            Some(overlay) => overlay.read().Inode(),
+        };
+
+        let mf = MountSourceFlags {
+            ReadOnly: true,
+            ..Default::default()
+        };
+        let upperMsrc = upper.lock().MountSource.clone();
+        let msrc = NewOverlayMountSource(&upperMsrc, &upperMsrc, &mf);
+        let entry = OverlayEntry::New(task, Some(upper), None, false)?;
+        let readonly = NewOverlayInode(task, entry, &msrc);
+
+        mns.Mount(&dirent, &readonly)?;
+        info!("made {} readonly", path);
+    }
+
+    return Ok(());
+}
+
 fn mountFlags(opts: &Vec<String>) -> MountSourceFlags {
     let mut mf = MountSourceFlags::default();
 
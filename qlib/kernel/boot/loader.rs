@@ -30,12 +30,16 @@ use super::super::super::cpuid::*;
 use super::super::super::limits::*;
 use super::super::super::linux_def::*;
 use super::super::super::loader::*;
+use super::super::fs::dev::host_block::*;
+use super::super::fs::dev::host_device::*;
 use super::super::fs::host::tty::*;
 use super::super::fs::mount::*;
+use super::super::kernel::container::*;
 use super::super::kernel::ipc_namespace::*;
 use super::super::kernel::kernel::*;
 use super::super::kernel::uts_namespace::*;
 use super::super::kernel::waiter::qlock::*;
+use super::super::memmgr::overcommit::*;
 use super::super::task::*;
 use super::super::threadmgr::thread::*;
 use super::super::threadmgr::thread_group::*;
@@ -53,6 +57,15 @@ impl Process {
             AmbientCaps: CapSet(self.Caps.AmbientCaps.0),
         };
     }
+
+    // DomainName is the "kernel.domainname" sysctl of the spec, the hostname
+    // if it is not set.
+    pub fn DomainName(&self) -> String {
+        return match self.Sysctls.get("kernel.domainname") {
+            Some(domain) => domain.to_string(),
+            None => self.HostName.to_string(),
+        };
+    }
 }
 
 #[derive(Eq, Debug)]
@@ -105,10 +118,21 @@ impl Deref for Loader {
 impl Loader {
     pub fn WaitContainer(&self, cid: String) -> Result<u32> {
         let task = Task::Current();
-        let (tg, _) = self
-            .Lock(task)?
-            .ThreadGroupFromID(&ExecID { cid: cid, pid: 0 })
-            .expect("WaitContainer: there is no root container");
+        let tg = {
+            let loader = self.Lock(task)?;
+            match loader.kernel.Container(&cid).and_then(|c| c.Init()) {
+                Some(tg) => tg,
+                None => match loader.ThreadGroupFromID(&ExecID { cid: cid.clone(), pid: 0 }) {
+                    Some((tg, _)) => tg,
+                    None => {
+                        return Err(Error::Common(format!(
+                            "Loader::WaitContainer container {} isn't started",
+                            cid
+                        )))
+                    }
+                },
+            }
+        };
 
         let task = Task::Current();
         tg.WaitExited(task);
@@ -214,6 +238,7 @@ impl Loader {
         task.creds = procArgs.Credentials.clone();
         let kernel = self.Lock(task)?.kernel.clone();
         let (tg, tid) = kernel.CreateProcess(procArgs)?;
+        kernel.Container(&procArgs.ContainerID).unwrap().SetInit(&tg);
         let paths = GetPath(&procArgs.Envv);
         procArgs.Filename = task.mountNS.ResolveExecutablePath(
             task,
@@ -296,10 +321,17 @@ impl Loader {
         return Ok(());
     }
 
-    pub fn StartSubContainer(&self, processSpec: Process) -> Result<(i32, u64, u64, u64)> {
+    pub fn StartSubContainer(
+        &self,
+        processSpec: Process,
+        pidsLimit: u64,
+        cpuWeight: u64,
+    ) -> Result<(i32, u64, u64, u64)> {
         let task = Task::Current();
         let mut lockedLoader = self.Lock(task)?;
         let kernel = lockedLoader.kernel.clone();
+        // the sub containers are in the cgroup of the sandbox
+        let parent = kernel.Container(&lockedLoader.sandboxID);
         let cid = processSpec.ID.clone();
         let execId = ExecID { cid: cid, pid: 0 };
         let mut process = match lockedLoader.processes.get_mut(&execId) {
@@ -326,16 +358,55 @@ impl Loader {
             Some(&processSpec.TaskCaps()),
             &userns,
         );
-        let rootMounts = InitRootFs(Task::Current(), &processSpec.Root)
-            .expect("in loader::StartSubContainer, InitRootfs fail");
-        kernel
-            .mounts
-            .write()
-            .insert(processSpec.ID.clone(), rootMounts);
+        RegisterHostDevices(&processSpec.Devices);
+        RegisterBlockVolumes(&processSpec.Volumes);
+        let rootMounts = InitRootFs(
+            Task::Current(),
+            &processSpec.Root,
+            &processSpec.MaskedPaths,
+            &processSpec.ReadonlyPaths,
+        )
+        .expect("in loader::StartSubContainer, InitRootfs fail");
+
+        // the containers of a pod share the ipc namespace, a sub container with
+        // its own hostname gets its own uts namespace
+        let rootUTSNamespace = kernel.RootUTSNamesapce();
+        let domainName = processSpec.DomainName();
+        let utsns = if (processSpec.HostName.len() > 0
+            && processSpec.HostName != rootUTSNamespace.HostName())
+            || domainName != rootUTSNamespace.DomainName()
+        {
+            UTSNamespace::New(
+                processSpec.HostName.to_string(),
+                domainName,
+                userns.clone(),
+            )
+        } else {
+            rootUTSNamespace
+        };
+
+        let container = Container::New(
+            &processSpec.ID,
+            rootMounts,
+            utsns,
+            kernel.RootIPCNamespace(),
+            pidsLimit,
+            &processSpec.WXAllowList,
+            cpuWeight,
+            parent,
+        );
+        kernel.AddContainer(container.clone())?;
 
         //todo: investigate PID namespace and whether we need it.
         let mut createProcessArgs = NewProcess(processSpec, &creds, &kernel);
-        let (tg, tid) = kernel.CreateProcess(&mut createProcessArgs)?;
+        let (tg, tid) = match kernel.CreateProcess(&mut createProcessArgs) {
+            Ok(r) => r,
+            Err(e) => {
+                kernel.RemoveContainer(&container.id);
+                return Err(e);
+            }
+        };
+        container.SetInit(&tg);
 
         let mut ttyFileOps = None;
         if createProcessArgs.Terminal {
@@ -440,8 +511,9 @@ impl LoaderInternal {
         );
 
         let hostName = process.HostName.to_string();
+        let domainName = process.DomainName();
 
-        let utsns = UTSNamespace::New(hostName.to_string(), hostName.to_string(), userns.clone());
+        let utsns = UTSNamespace::New(hostName, domainName, userns.clone());
         let ipcns = IPCNamespace::New(&userns);
 
         let kernalArgs = InitKernalArgs {
@@ -455,10 +527,33 @@ impl LoaderInternal {
 
         let kernel = Kernel::Init(kernalArgs);
         *SHARESPACE.kernel.lock() = Some(kernel.clone());
-
-        let rootMounts =
-            InitRootFs(Task::Current(), &process.Root).expect("in loader::New, InitRootfs fail");
-        kernel.mounts.write().insert(sandboxID.clone(), rootMounts);
+        OVERCOMMIT.Init();
+
+        RegisterHostDevices(&process.Devices);
+        RegisterBlockVolumes(&process.Volumes);
+        let rootMounts = InitRootFs(
+            Task::Current(),
+            &process.Root,
+            &process.MaskedPaths,
+            &process.ReadonlyPaths,
+        )
+        .expect("in loader::New, InitRootfs fail");
+        // the root container is the cgroup of the sandbox, its pids limit
+        // bounds the tasks of all the containers of the pod
+        let pidsLimit = SHARESPACE.cgroup.lock().PidsMax;
+        let container = Container::New(
+            &sandboxID,
+            rootMounts,
+            kernel.RootUTSNamesapce(),
+            kernel.RootIPCNamespace(),
+            pidsLimit,
+            &process.WXAllowList,
+            0,
+            None,
+        );
+        kernel
+            .AddContainer(container)
+            .expect("in loader::New, AddContainer fail");
 
         let processArgs = NewProcess(process, &creds, &kernel);
         self.kernel = kernel;
@@ -649,18 +744,27 @@ impl LoaderInternal {
 
         l.processes.remove(&execId);
 
+        // the root container is kept until the sandbox exits
+        if cid != l.sandboxID {
+            l.kernel.RemoveContainer(&cid);
+        }
+
         info!("Container {} destroyed", &cid);
         return Ok(());
     }
 }
 
 pub fn NewProcess(process: Process, creds: &auth::Credentials, k: &Kernel) -> CreateProcessArgs {
-    let utsns = k.rootUTSNamespace.clone();
-    let ipcns = k.rootIPCNamespace.clone();
+    // the processes of a container are in the namespaces of the container
+    let (utsns, ipcns) = match k.Container(&process.ID) {
+        Some(c) => (c.utsns.clone(), c.ipcns.clone()),
+        None => (k.rootUTSNamespace.clone(), k.rootIPCNamespace.clone()),
+    };
     let mut stdiofds: [i32; 3] = [0; 3];
     for i in 0..3 {
         stdiofds[i] = process.Stdiofds[i];
     }
+    let umask = process.Umask.unwrap_or(0o22);
 
     return CreateProcessArgs {
         Filename: process.Args[0].to_string(),
@@ -668,7 +772,7 @@ pub fn NewProcess(process: Process, creds: &auth::Credentials, k: &Kernel) -> Cr
         Envv: process.Envs,
         WorkingDirectory: process.Cwd,
         Credentials: creds.clone(),
-        Umask: 0o22,
+        Umask: umask,
         Limits: LimitSet(Arc::new(QMutex::new(process.limitSet))),
         MaxSymlinkTraversals: MAX_SYMLINK_TRAVERSALS,
         UTSNamespace: utsns,
@@ -0,0 +1,339 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::vec::Vec;
+
+use super::super::common::*;
+use super::super::linux::bpf::*;
+use super::super::linux_def::*;
+
+// the opcodes accepted by sk_chk_filter of Linux
+const VALID_OPCODES: &[u16] = &[
+    BPF_LD | BPF_W | BPF_ABS,
+    BPF_LD | BPF_H | BPF_ABS,
+    BPF_LD | BPF_B | BPF_ABS,
+    BPF_LD | BPF_W | BPF_IND,
+    BPF_LD | BPF_H | BPF_IND,
+    BPF_LD | BPF_B | BPF_IND,
+    BPF_LD | BPF_W | BPF_LEN,
+    BPF_LD | BPF_IMM,
+    BPF_LD | BPF_MEM,
+    BPF_LDX | BPF_W | BPF_IMM,
+    BPF_LDX | BPF_W | BPF_MEM,
+    BPF_LDX | BPF_W | BPF_LEN,
+    BPF_LDX | BPF_B | BPF_MSH,
+    BPF_ST,
+    BPF_STX,
+    BPF_ALU | BPF_ADD | BPF_K,
+    BPF_ALU | BPF_ADD | BPF_X,
+    BPF_ALU | BPF_SUB | BPF_K,
+    BPF_ALU | BPF_SUB | BPF_X,
+    BPF_ALU | BPF_MUL | BPF_K,
+    BPF_ALU | BPF_MUL | BPF_X,
+    BPF_ALU | BPF_DIV | BPF_K,
+    BPF_ALU | BPF_DIV | BPF_X,
+    BPF_ALU | BPF_MOD | BPF_K,
+    BPF_ALU | BPF_MOD | BPF_X,
+    BPF_ALU | BPF_AND | BPF_K,
+    BPF_ALU | BPF_AND | BPF_X,
+    BPF_ALU | BPF_OR | BPF_K,
+    BPF_ALU | BPF_OR | BPF_X,
+    BPF_ALU | BPF_XOR | BPF_K,
+    BPF_ALU | BPF_XOR | BPF_X,
+    BPF_ALU | BPF_LSH | BPF_K,
+    BPF_ALU | BPF_LSH | BPF_X,
+    BPF_ALU | BPF_RSH | BPF_K,
+    BPF_ALU | BPF_RSH | BPF_X,
+    BPF_ALU | BPF_NEG,
+    BPF_JMP | BPF_JA,
+    BPF_JMP | BPF_JEQ | BPF_K,
+    BPF_JMP | BPF_JEQ | BPF_X,
+    BPF_JMP | BPF_JGT | BPF_K,
+    BPF_JMP | BPF_JGT | BPF_X,
+    BPF_JMP | BPF_JGE | BPF_K,
+    BPF_JMP | BPF_JGE | BPF_X,
+    BPF_JMP | BPF_JSET | BPF_K,
+    BPF_JMP | BPF_JSET | BPF_X,
+    BPF_RET | BPF_K,
+    BPF_RET | BPF_A,
+    BPF_MISC | BPF_TAX,
+    BPF_MISC | BPF_TXA,
+];
+
+// BpfProgram is a classic BPF program checked by Compile. The jumps of a
+// checked program only go forward and end at a return, so Exec always
+// terminates.
+#[derive(Debug, Clone)]
+pub struct BpfProgram {
+    pub instructions: Vec<BPFInstruction>,
+}
+
+impl BpfProgram {
+    // Compile checks the program as Linux: the opcodes are known, the jumps
+    // stay in the program, the scratch memory is in range, there is no
+    // division by a constant zero and the last instruction is a return.
+    pub fn Compile(instructions: Vec<BPFInstruction>) -> Result<Self> {
+        let len = instructions.len();
+        if len == 0 || len > BPF_MAXINSNS {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        for (pc, i) in instructions.iter().enumerate() {
+            if !VALID_OPCODES.contains(&i.OpCode) {
+                return Err(Error::SysError(SysErr::EINVAL));
+            }
+
+            let rest = len - pc - 1;
+            let valid = match i.OpCode & BPF_CLASS_MASK {
+                BPF_LD | BPF_LDX => {
+                    i.OpCode & BPF_MODE_MASK != BPF_MEM || (i.K as usize) < BPF_MEMWORDS
+                }
+                BPF_ST | BPF_STX => (i.K as usize) < BPF_MEMWORDS,
+                BPF_ALU => {
+                    let op = i.OpCode & BPF_OP_MASK;
+                    !((op == BPF_DIV || op == BPF_MOD)
+                        && i.OpCode & BPF_SRC_MASK == BPF_K
+                        && i.K == 0)
+                }
+                BPF_JMP => {
+                    if i.OpCode & BPF_OP_MASK == BPF_JA {
+                        (i.K as usize) < rest
+                    } else {
+                        (i.JumpIfTrue as usize) < rest && (i.JumpIfFalse as usize) < rest
+                    }
+                }
+                _ => true,
+            };
+
+            if !valid {
+                return Err(Error::SysError(SysErr::EINVAL));
+            }
+        }
+
+        if instructions[len - 1].OpCode & BPF_CLASS_MASK != BPF_RET {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        return Ok(Self { instructions });
+    }
+
+    pub fn Len(&self) -> usize {
+        return self.instructions.len();
+    }
+
+    // Load reads the big endian value of size bytes at off of the input.
+    fn Load(input: &[u8], off: u32, size: usize) -> Option<u32> {
+        let off = off as usize;
+        let end = off.checked_add(size)?;
+        if end > input.len() {
+            return None;
+        }
+
+        let mut val = 0;
+        for b in &input[off..end] {
+            val = (val << 8) | *b as u32;
+        }
+
+        return Some(val);
+    }
+
+    // Exec runs the program on the input and returns the value of its return
+    // instruction. As Linux, a load out of the input or a division by zero
+    // returns 0, which drops the packet of a socket filter. The ancillary
+    // data loads at the negative SKF_AD_OFF offsets are not supported and
+    // are out of the input.
+    pub fn Exec(&self, input: &[u8]) -> u32 {
+        let mut a: u32 = 0;
+        let mut x: u32 = 0;
+        let mut mem = [0u32; BPF_MEMWORDS];
+        let mut pc = 0;
+
+        loop {
+            let i = self.instructions[pc];
+            pc += 1;
+
+            let size = match i.OpCode & BPF_SIZE_MASK {
+                BPF_W => 4,
+                BPF_H => 2,
+                _ => 1,
+            };
+
+            match i.OpCode & BPF_CLASS_MASK {
+                BPF_LD => {
+                    a = match i.OpCode & BPF_MODE_MASK {
+                        BPF_ABS => match Self::Load(input, i.K, size) {
+                            None => return 0,
+                            Some(v) => v,
+                        },
+                        BPF_IND => match Self::Load(input, x.wrapping_add(i.K), size) {
+                            None => return 0,
+                            Some(v) => v,
+                        },
+                        BPF_LEN => input.len() as u32,
+                        BPF_MEM => mem[i.K as usize],
+                        _ => i.K,
+                    }
+                }
+                BPF_LDX => {
+                    x = match i.OpCode & BPF_MODE_MASK {
+                        BPF_MSH => match Self::Load(input, i.K, 1) {
+                            None => return 0,
+                            Some(v) => (v & 0xf) << 2,
+                        },
+                        BPF_LEN => input.len() as u32,
+                        BPF_MEM => mem[i.K as usize],
+                        _ => i.K,
+                    }
+                }
+                BPF_ST => mem[i.K as usize] = a,
+                BPF_STX => mem[i.K as usize] = x,
+                BPF_ALU => {
+                    let val = if i.OpCode & BPF_SRC_MASK == BPF_X {
+                        x
+                    } else {
+                        i.K
+                    };
+
+                    a = match i.OpCode & BPF_OP_MASK {
+                        BPF_ADD => a.wrapping_add(val),
+                        BPF_SUB => a.wrapping_sub(val),
+                        BPF_MUL => a.wrapping_mul(val),
+                        BPF_DIV => match a.checked_div(val) {
+                            None => return 0,
+                            Some(v) => v,
+                        },
+                        BPF_MOD => match a.checked_rem(val) {
+                            None => return 0,
+                            Some(v) => v,
+                        },
+                        BPF_AND => a & val,
+                        BPF_OR => a | val,
+                        BPF_XOR => a ^ val,
+                        BPF_LSH => a.checked_shl(val).unwrap_or(0),
+                        BPF_RSH => a.checked_shr(val).unwrap_or(0),
+                        _ => a.wrapping_neg(),
+                    }
+                }
+                BPF_JMP => {
+                    let val = if i.OpCode & BPF_SRC_MASK == BPF_X {
+                        x
+                    } else {
+                        i.K
+                    };
+
+                    let cond = match i.OpCode & BPF_OP_MASK {
+                        BPF_JA => {
+                            pc += i.K as usize;
+                            continue;
+                        }
+                        BPF_JEQ => a == val,
+                        BPF_JGT => a > val,
+                        BPF_JGE => a >= val,
+                        _ => a & val != 0,
+                    };
+
+                    if cond {
+                        pc += i.JumpIfTrue as usize;
+                    } else {
+                        pc += i.JumpIfFalse as usize;
+                    }
+                }
+                BPF_RET => {
+                    if i.OpCode & BPF_RVAL_MASK == BPF_A {
+                        return a;
+                    }
+
+                    return i.K;
+                }
+                _ => {
+                    if i.OpCode & BPF_MISCOP_MASK == BPF_TXA {
+                        a = x;
+                    } else {
+                        x = a;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn Run(prog: Vec<BPFInstruction>, input: &[u8]) -> u32 {
+        return BpfProgram::Compile(prog).unwrap().Exec(input);
+    }
+
+    #[test]
+    fn test_Compile() {
+        // no return at the end
+        assert!(BpfProgram::Compile(vec![BPFInstruction::Stmt(BPF_LD | BPF_IMM, 1)]).is_err());
+        // jump out of the program
+        assert!(BpfProgram::Compile(vec![
+            BPFInstruction::Jump(BPF_JMP | BPF_JEQ | BPF_K, 0, 1, 0),
+            BPFInstruction::Stmt(BPF_RET | BPF_K, 0),
+        ])
+        .is_err());
+        // scratch memory out of range
+        assert!(BpfProgram::Compile(vec![
+            BPFInstruction::Stmt(BPF_ST, BPF_MEMWORDS as u32),
+            BPFInstruction::Stmt(BPF_RET | BPF_K, 0),
+        ])
+        .is_err());
+        // division by a constant zero
+        assert!(BpfProgram::Compile(vec![
+            BPFInstruction::Stmt(BPF_ALU | BPF_DIV | BPF_K, 0),
+            BPFInstruction::Stmt(BPF_RET | BPF_A, 0),
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_Exec() {
+        // accept the whole packet if the ethertype is IPv4
+        let prog = vec![
+            BPFInstruction::Stmt(BPF_LD | BPF_H | BPF_ABS, 12),
+            BPFInstruction::Jump(BPF_JMP | BPF_JEQ | BPF_K, 0x0800, 0, 1),
+            BPFInstruction::Stmt(BPF_RET | BPF_K, 0xffff),
+            BPFInstruction::Stmt(BPF_RET | BPF_K, 0),
+        ];
+        let mut packet = [0u8; 14];
+        packet[12] = 0x08;
+        assert_eq!(Run(prog.clone(), &packet), 0xffff);
+        packet[12] = 0x86;
+        assert_eq!(Run(prog.clone(), &packet), 0);
+        // a load out of the packet drops it
+        assert_eq!(Run(prog, &packet[..12]), 0);
+
+        // the IP header length by BPF_MSH, then an indirect load
+        let prog = vec![
+            BPFInstruction::Stmt(BPF_LDX | BPF_B | BPF_MSH, 0),
+            BPFInstruction::Stmt(BPF_LD | BPF_B | BPF_IND, 0),
+            BPFInstruction::Stmt(BPF_RET | BPF_A, 0),
+        ];
+        let mut packet = [0u8; 24];
+        packet[0] = 0x45;
+        packet[20] = 7;
+        assert_eq!(Run(prog, &packet), 7);
+
+        // a division by a zero X returns 0
+        let prog = vec![
+            BPFInstruction::Stmt(BPF_LD | BPF_IMM, 10),
+            BPFInstruction::Stmt(BPF_ALU | BPF_DIV | BPF_X, 0),
+            BPFInstruction::Stmt(BPF_RET | BPF_K, 1),
+        ];
+        assert_eq!(Run(prog, &[]), 0);
+    }
+}
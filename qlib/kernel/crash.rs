@@ -0,0 +1,175 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::Ordering;
+
+use super::super::linux_def::*;
+use super::super::vcpu_mgr::*;
+use super::asm::*;
+use super::kernel::kmsg::*;
+use super::task::*;
+use super::Kernel::HostSpace;
+
+// the max bytes of the kernel stack copied into the crash info
+pub const CRASH_STACK_DUMP_SIZE: u64 = 8 * 1024;
+// the max frames of the backtrace
+pub const CRASH_BACKTRACE_DEPTH: usize = 32;
+// the max kernel log messages copied into the crash info
+pub const CRASH_KMSG_COUNT: usize = 256;
+
+static PANICKING: AtomicBool = AtomicBool::new(false);
+
+// CrashInfo is the qkernel state collected by the panicking vcpu. qvisor reads
+// it from the guest memory and writes it into the crash dump with the vcpu
+// registers and the qcall history.
+#[derive(Debug, Default, Serialize)]
+pub struct CrashInfo {
+    pub Message: String,
+    pub VcpuId: usize,
+    pub TaskId: u64,
+    // KernelStackAddr is the address of the first word of KernelStack
+    pub KernelStackAddr: u64,
+    pub KernelStack: Vec<u64>,
+    // Backtrace are the return addresses found by the frame pointers. They
+    // can be resolved with addr2line on the qkernel binary.
+    pub Backtrace: Vec<u64>,
+    // Vmas are the vmas of the current mm in the /proc/pid/maps format
+    pub Vmas: Vec<String>,
+    // Kmsg are the newest kernel log messages
+    pub Kmsg: Vec<String>,
+}
+
+impl CrashInfo {
+    // Panic reports the panic with the crash info to qvisor, which exits the
+    // sandbox. A panic during the collection is reported without crash info.
+    pub fn Panic(msg: &str) {
+        if PANICKING.swap(true, Ordering::SeqCst) {
+            HostSpace::Panic(msg);
+            return;
+        }
+
+        let info = Self::Collect(msg);
+        HostSpace::PanicWithInfo(msg, &info);
+    }
+
+    // Collect must not block, the locks may be held by the panicking vcpu
+    // itself. The parts behind a busy lock are left empty.
+    pub fn Collect(msg: &str) -> Self {
+        let mut info = Self {
+            Message: msg.to_string(),
+            VcpuId: CPULocal::CpuId(),
+            ..Default::default()
+        };
+
+        let task = Task::Current();
+        info.TaskId = task.taskId;
+
+        let stackStart = task.taskId;
+        let stackEnd = task.taskId + MemoryDef::DEFAULT_STACK_SIZE;
+        let rsp = GetRsp();
+        // the panic may happen on an interrupt stack
+        if rsp >= stackStart && rsp < stackEnd {
+            let end = core::cmp::min(stackEnd, rsp + CRASH_STACK_DUMP_SIZE);
+            info.KernelStackAddr = rsp & !7;
+            let mut addr = info.KernelStackAddr;
+            while addr + 8 <= end {
+                info.KernelStack.push(unsafe { *(addr as *const u64) });
+                addr += 8;
+            }
+        }
+
+        info.Backtrace = FrameBacktrace(GetRbp(), stackStart, stackEnd);
+        info.Vmas = Self::Vmas(task);
+        info.Kmsg = Self::Kmsg();
+        return info;
+    }
+
+    fn Vmas(task: &Task) -> Vec<String> {
+        let mut vmas = Vec::new();
+        let mapping = match task.mm.mapping.try_lock() {
+            None => {
+                vmas.push("the mapping lock is busy".to_string());
+                return vmas;
+            }
+            Some(m) => m,
+        };
+
+        let mut seg = mapping.vmas.FirstSeg();
+        while !seg.IsTail() {
+            let vma = seg.Value();
+            let range = seg.Range();
+            seg = seg.NextSeg();
+
+            vmas.push(format!(
+                "{:08x}-{:08x} {}{} {:08x} {}{}",
+                range.Start(),
+                range.End(),
+                vma.realPerms.String(),
+                if vma.private { "p" } else { "s" },
+                vma.offset,
+                vma.hint,
+                if vma.kernel { " [kernel]" } else { "" }
+            ));
+        }
+
+        return vmas;
+    }
+
+    fn Kmsg() -> Vec<String> {
+        let ring = match KMSG.ring.try_lock() {
+            None => return vec!["the kmsg lock is busy".to_string()],
+            Some(r) => r,
+        };
+
+        let start = if ring.entries.len() > CRASH_KMSG_COUNT {
+            ring.entries.len() - CRASH_KMSG_COUNT
+        } else {
+            0
+        };
+
+        return ring.entries[start..]
+            .iter()
+            .map(|e| e.SyslogFormat().trim_end().to_string())
+            .collect();
+    }
+}
+
+// FrameBacktrace walks the frame pointers from rbp and returns the return
+// addresses. The walk stops when a frame is out of the stack.
+pub fn FrameBacktrace(rbp: u64, stackStart: u64, stackEnd: u64) -> Vec<u64> {
+    let inStack = |addr: u64| addr >= stackStart && addr + 16 <= stackEnd;
+
+    let mut ret = Vec::new();
+    let mut rbp = rbp;
+    for _i in 0..CRASH_BACKTRACE_DEPTH {
+        if !inStack(rbp) {
+            break;
+        }
+
+        ret.push(unsafe { *((rbp + 8) as *const u64) });
+
+        let next = unsafe { *(rbp as *const u64) };
+        // the frames go up the stack
+        if next <= rbp {
+            break;
+        }
+        rbp = next;
+    }
+
+    return ret;
+}
@@ -23,7 +23,9 @@ pub enum InodeFileType {
     Host,
     Mock,
     Full,
+    Kmsg,
     Null,
+    Passthrough,
     Random,
     TTY,
     Zero,
@@ -160,6 +162,10 @@ impl StableAttr {
         return self.Type == InodeType::CharacterDevice;
     }
 
+    pub fn IsBlockDevice(&self) -> bool {
+        return self.Type == InodeType::BlockDevice;
+    }
+
     pub fn DentAttr(&self) -> DentAttr {
         return DentAttr {
             Type: self.Type,
@@ -177,6 +183,9 @@ pub struct UnstableAttr {
     pub AccessTime: Time,
     pub ModificationTime: Time,
     pub StatusChangeTime: Time,
+    // BirthTime is the creation time, it is zero if the filesystem doesn't
+    // keep it
+    pub BirthTime: Time,
     pub Links: u64,
 }
 
@@ -232,6 +241,7 @@ pub fn WithCurrentTime(task: &Task, u: &UnstableAttr) -> UnstableAttr {
     res.AccessTime = t;
     res.ModificationTime = t;
     res.StatusChangeTime = t;
+    res.BirthTime = t;
     return res;
 }
 
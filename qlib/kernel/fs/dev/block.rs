@@ -0,0 +1,368 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::qlib::mutex::*;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::Any;
+
+use super::super::super::super::auth::*;
+use super::super::super::super::common::*;
+use super::super::super::super::device::*;
+use super::super::super::super::linux_def::*;
+use super::super::super::kernel::waiter::*;
+use super::super::super::task::*;
+use super::super::super::threadmgr::task_usermem::*;
+use super::super::attr::*;
+use super::super::dentry::*;
+use super::super::dirent::*;
+use super::super::file::*;
+use super::super::flags::*;
+use super::super::fsutil::file::*;
+use super::super::fsutil::inode::simple_file_inode::*;
+use super::super::host::hostinodeop::*;
+use super::super::inode::*;
+use super::super::mount::*;
+
+pub struct BlkCmd {}
+
+impl BlkCmd {
+    pub const BLKROGET: u64 = 0x125e;
+    pub const BLKGETSIZE: u64 = 0x1260;
+    pub const BLKFLSBUF: u64 = 0x1261;
+    pub const BLKSSZGET: u64 = 0x1268;
+    pub const BLKBSZGET: u64 = 0x80081270;
+    pub const BLKGETSIZE64: u64 = 0x80081272;
+}
+
+pub const SECTOR_SIZE: u64 = 512;
+
+// BlockDevice is a device of the in-guest block layer. The file operations
+// of /dev nodes do the bounds checks and the generic BLK* ioctls, the
+// device only moves the bytes.
+pub trait BlockDevice: Send + Sync {
+    fn as_any(&self) -> &Any;
+
+    // Size returns the size of the device in bytes
+    fn Size(&self, task: &Task) -> Result<u64>;
+
+    fn ReadOnly(&self) -> bool;
+
+    fn ReadAt(&self, task: &Task, dsts: &mut [IoVec], offset: u64) -> Result<i64>;
+
+    fn WriteAt(&self, task: &Task, srcs: &[IoVec], offset: u64) -> Result<i64>;
+
+    fn Flush(&self, task: &Task) -> Result<()>;
+
+    fn BlockSize(&self) -> u64 {
+        return SECTOR_SIZE;
+    }
+
+    // Ioctl handles the device specific ioctls
+    fn Ioctl(&self, _task: &Task, _request: u64, _val: u64) -> Result<()> {
+        return Err(Error::SysError(SysErr::ENOTTY));
+    }
+}
+
+// LimitIovs trims the iovecs to at most max bytes
+pub fn LimitIovs(iovs: &[IoVec], max: usize) -> Vec<IoVec> {
+    let mut ret = Vec::new();
+    let mut left = max;
+    for iov in iovs {
+        if left == 0 {
+            break;
+        }
+
+        let iov = iov.TakeFirst(left);
+        left -= iov.Len();
+        ret.push(iov);
+    }
+
+    return ret;
+}
+
+// ReadFull reads len(buf) bytes of the device at offset into a kernel
+// buffer, a short read is EIO
+pub fn ReadFull(task: &Task, dev: &Arc<BlockDevice>, buf: &mut [u8], offset: u64) -> Result<()> {
+    let size = dev.Size(task)?;
+    if offset + buf.len() as u64 > size {
+        return Err(Error::SysError(SysErr::EIO));
+    }
+
+    let mut cnt = 0;
+    while cnt < buf.len() {
+        let mut iovs: [IoVec; 1] = [IoVec {
+            start: &buf[cnt] as *const _ as u64,
+            len: buf.len() - cnt,
+        }];
+
+        let n = dev.ReadAt(task, &mut iovs, offset + cnt as u64)? as usize;
+        if n == 0 {
+            return Err(Error::SysError(SysErr::EIO));
+        }
+
+        cnt += n;
+    }
+
+    return Ok(());
+}
+
+// BlockDeviceOf returns the block device of a /dev block node
+pub fn BlockDeviceOf(inode: &Inode) -> Option<Arc<BlockDevice>> {
+    let iops = inode.lock().InodeOp.clone();
+    let node = iops.as_any().downcast_ref::<SimpleFileInode<BlockNode>>()?;
+    let dev = node.read().data.dev.clone();
+    return Some(dev);
+}
+
+pub fn NewBlockDeviceInode(
+    task: &Task,
+    dev: &Arc<BlockDevice>,
+    major: u16,
+    minor: u32,
+    perms: &FilePermissions,
+    msrc: &Arc<QMutex<MountSource>>,
+) -> Inode {
+    let iops = SimpleFileInode::New(
+        task,
+        &ROOT_OWNER,
+        perms,
+        FSMagic::TMPFS_MAGIC,
+        false,
+        BlockNode { dev: dev.clone() },
+    );
+
+    let deviceId = DEV_DEVICE.lock().id.DeviceID();
+    let inodeId = DEV_DEVICE.lock().NextIno();
+
+    let stableAttr = StableAttr {
+        Type: InodeType::BlockDevice,
+        DeviceId: deviceId,
+        InodeId: inodeId,
+        BlockSize: MemoryDef::PAGE_SIZE as i64,
+        DeviceFileMajor: major,
+        DeviceFileMinor: minor,
+    };
+
+    return Inode::New(&Arc::new(iops), msrc, &stableAttr);
+}
+
+pub struct BlockNode {
+    pub dev: Arc<BlockDevice>,
+}
+
+impl SimpleFileTrait for BlockNode {
+    fn GetFile(
+        &self,
+        _task: &Task,
+        _dir: &Inode,
+        dirent: &Dirent,
+        flags: FileFlags,
+    ) -> Result<File> {
+        if flags.Write && self.dev.ReadOnly() {
+            return Err(Error::SysError(SysErr::EROFS));
+        }
+
+        let fops = BlockFileOperations {
+            dev: self.dev.clone(),
+        };
+
+        return Ok(File::New(dirent, &flags, fops));
+    }
+}
+
+pub struct BlockFileOperations {
+    pub dev: Arc<BlockDevice>,
+}
+
+impl BlockFileOperations {
+    // Iovs limits the iovecs of an access at offset to the device size
+    fn Iovs(&self, task: &Task, iovs: &[IoVec], offset: i64) -> Result<Vec<IoVec>> {
+        if offset < 0 {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        let size = self.dev.Size(task)?;
+        let offset = offset as u64;
+        let mut len = IoVec::NumBytes(iovs) as u64;
+        if offset >= size {
+            len = 0;
+        } else if offset + len > size {
+            len = size - offset;
+        }
+
+        return Ok(LimitIovs(iovs, len as usize));
+    }
+}
+
+impl Waitable for BlockFileOperations {
+    fn Readiness(&self, _task: &Task, mask: EventMask) -> EventMask {
+        return mask;
+    }
+
+    fn EventRegister(&self, _task: &Task, _e: &WaitEntry, _mask: EventMask) {}
+
+    fn EventUnregister(&self, _task: &Task, _e: &WaitEntry) {}
+}
+
+impl SpliceOperations for BlockFileOperations {}
+
+impl FileOperations for BlockFileOperations {
+    fn as_any(&self) -> &Any {
+        return self;
+    }
+
+    fn FopsType(&self) -> FileOpsType {
+        return FileOpsType::BlockFileOperations;
+    }
+
+    fn Seekable(&self) -> bool {
+        return true;
+    }
+
+    fn Seek(&self, task: &Task, f: &File, whence: i32, current: i64, offset: i64) -> Result<i64> {
+        // SEEK_END is relative to the device size, not the inode size
+        if whence == SeekWhence::SEEK_END {
+            let size = self.dev.Size(task)? as i64;
+            if size + offset < 0 {
+                return Err(Error::SysError(SysErr::EINVAL));
+            }
+
+            return Ok(size + offset);
+        }
+
+        return SeekWithDirCursor(task, f, whence, current, offset, None);
+    }
+
+    fn ReadDir(
+        &self,
+        _task: &Task,
+        _f: &File,
+        _offset: i64,
+        _serializer: &mut DentrySerializer,
+    ) -> Result<i64> {
+        return Err(Error::SysError(SysErr::ENOTDIR));
+    }
+
+    fn ReadAt(
+        &self,
+        task: &Task,
+        _f: &File,
+        dsts: &mut [IoVec],
+        offset: i64,
+        _blocking: bool,
+    ) -> Result<i64> {
+        let mut iovs = self.Iovs(task, dsts, offset)?;
+        if iovs.len() == 0 {
+            return Ok(0);
+        }
+
+        return self.dev.ReadAt(task, &mut iovs, offset as u64);
+    }
+
+    fn WriteAt(
+        &self,
+        task: &Task,
+        _f: &File,
+        srcs: &[IoVec],
+        offset: i64,
+        _blocking: bool,
+    ) -> Result<i64> {
+        if self.dev.ReadOnly() {
+            return Err(Error::SysError(SysErr::EPERM));
+        }
+
+        let iovs = self.Iovs(task, srcs, offset)?;
+        if iovs.len() == 0 {
+            return Err(Error::SysError(SysErr::ENOSPC));
+        }
+
+        return self.dev.WriteAt(task, &iovs, offset as u64);
+    }
+
+    fn Append(&self, task: &Task, f: &File, srcs: &[IoVec]) -> Result<(i64, i64)> {
+        let n = self.WriteAt(task, f, srcs, 0, false)?;
+        return Ok((n, 0));
+    }
+
+    fn Fsync(
+        &self,
+        task: &Task,
+        _f: &File,
+        _start: i64,
+        _end: i64,
+        _syncType: SyncType,
+    ) -> Result<()> {
+        return self.dev.Flush(task);
+    }
+
+    fn Flush(&self, _task: &Task, _f: &File) -> Result<()> {
+        return Ok(());
+    }
+
+    fn UnstableAttr(&self, task: &Task, f: &File) -> Result<UnstableAttr> {
+        let inode = f.Dirent.Inode();
+        return inode.UnstableAttr(task);
+    }
+
+    fn Ioctl(&self, task: &Task, _f: &File, _fd: i32, request: u64, val: u64) -> Result<()> {
+        match request {
+            BlkCmd::BLKGETSIZE64 => {
+                let size = self.dev.Size(task)?;
+                UserPtr::New(val).Write(task, &size)?;
+                return Ok(());
+            }
+            BlkCmd::BLKGETSIZE => {
+                let sectors = self.dev.Size(task)? / SECTOR_SIZE;
+                UserPtr::New(val).Write(task, &sectors)?;
+                return Ok(());
+            }
+            BlkCmd::BLKSSZGET => {
+                let size = SECTOR_SIZE as i32;
+                UserPtr::New(val).Write(task, &size)?;
+                return Ok(());
+            }
+            BlkCmd::BLKBSZGET => {
+                let size = self.dev.BlockSize() as i32;
+                UserPtr::New(val).Write(task, &size)?;
+                return Ok(());
+            }
+            BlkCmd::BLKROGET => {
+                let ro: i32 = if self.dev.ReadOnly() { 1 } else { 0 };
+                UserPtr::New(val).Write(task, &ro)?;
+                return Ok(());
+            }
+            BlkCmd::BLKFLSBUF => {
+                return self.dev.Flush(task);
+            }
+            _ => return self.dev.Ioctl(task, request, val),
+        }
+    }
+
+    fn IterateDir(
+        &self,
+        _task: &Task,
+        _d: &Dirent,
+        _dirCtx: &mut DirCtx,
+        _offset: i32,
+    ) -> (i32, Result<i64>) {
+        return (0, Err(Error::SysError(SysErr::ENOTDIR)));
+    }
+
+    fn Mappable(&self) -> Result<HostInodeOp> {
+        return Err(Error::SysError(SysErr::ENODEV));
+    }
+}
+
+impl SockOperations for BlockFileOperations {}
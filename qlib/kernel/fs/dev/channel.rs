@@ -0,0 +1,530 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::qlib::mutex::*;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::sync::atomic::fence;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::AtomicU32;
+use core::sync::atomic::Ordering;
+
+use super::super::super::super::auth::*;
+use super::super::super::super::bytestream::*;
+use super::super::super::super::common::*;
+use super::super::super::super::device::*;
+use super::super::super::super::linux_def::*;
+use super::super::super::super::mem::block::*;
+use super::super::super::super::qmsg::qcall::{ValidChannelName, SHM_CHANNEL_SIZE};
+use super::super::super::fd::*;
+use super::super::super::guestfdnotifier::*;
+use super::super::super::kernel::waiter::*;
+use super::super::super::task::*;
+use super::super::super::threadmgr::task_usermem::*;
+use super::super::super::Kernel::HostSpace;
+use super::super::attr::*;
+use super::super::dentry::*;
+use super::super::dirent::*;
+use super::super::file::*;
+use super::super::flags::*;
+use super::super::fsutil::file::*;
+use super::super::fsutil::inode::simple_file_inode::*;
+use super::super::host::hostinodeop::*;
+use super::super::inode::*;
+use super::super::mount::*;
+use super::loop_dev::MISC_MAJOR;
+
+pub const QCHANNEL_MINOR: u32 = 245;
+
+// _IOW('Q', 1, struct qchannel_attach)
+pub const QCHANNEL_ATTACH: u64 = 0x40205101;
+
+// the share memory file is a header page and a ring for each side
+const CHANNEL_RING_PAGES: usize = 128;
+
+// QChannelAttach is the argument of QCHANNEL_ATTACH, a nul terminated name
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct QChannelAttach {
+    pub name: [u8; 32],
+}
+
+// ChannelHeader is the first page of the share memory file. Ring i is read
+// by side i, waiting[i] is set when side i wants a doorbell.
+#[repr(C)]
+pub struct ChannelHeader {
+    pub slots: [AtomicU32; 2],
+    pub waiting: [AtomicU32; 2],
+    pub rings: [[AtomicU32; 2]; 2],
+}
+
+pub fn NewChannelDevice(task: &Task, msrc: &Arc<QMutex<MountSource>>) -> Inode {
+    let iops = SimpleFileInode::New(
+        task,
+        &ROOT_OWNER,
+        &FilePermissions::FromMode(FileMode(0o0666)),
+        FSMagic::TMPFS_MAGIC,
+        true,
+        ChannelNode {},
+    );
+
+    let deviceId = DEV_DEVICE.lock().id.DeviceID();
+    let inodeId = DEV_DEVICE.lock().NextIno();
+
+    let stableAttr = StableAttr {
+        Type: InodeType::CharacterDevice,
+        DeviceId: deviceId,
+        InodeId: inodeId,
+        BlockSize: MemoryDef::PAGE_SIZE as i64,
+        DeviceFileMajor: MISC_MAJOR,
+        DeviceFileMinor: QCHANNEL_MINOR,
+    };
+
+    return Inode::New(&Arc::new(iops), msrc, &stableAttr);
+}
+
+pub struct ChannelNode {}
+
+impl SimpleFileTrait for ChannelNode {
+    fn GetFile(
+        &self,
+        _task: &Task,
+        _dir: &Inode,
+        dirent: &Dirent,
+        flags: FileFlags,
+    ) -> Result<File> {
+        return Ok(File::New(dirent, &flags, ChannelFileOperations::default()));
+    }
+}
+
+// ChannelEndpoint is one side of a channel. The data goes through the share
+// memory rings, the host fifos are only used to wake up a waiting side.
+pub struct ChannelEndpoint {
+    pub side: usize,
+    pub addr: u64,
+    // the share memory file and the doorbells of side 0 and 1
+    pub fds: [i32; 3],
+    pub inbound: QMutex<RingBuf>,
+    pub outbound: QMutex<RingBuf>,
+    pub peerGone: AtomicBool,
+    pub peerSeen: AtomicBool,
+}
+
+impl Drop for ChannelEndpoint {
+    fn drop(&mut self) {
+        self.Header().slots[self.side].store(0, Ordering::SeqCst);
+        // the peer sees the end of the channel
+        self.Kick();
+        HostSpace::MUnmap(self.addr, SHM_CHANNEL_SIZE);
+        for fd in &self.fds {
+            HostSpace::Close(*fd);
+        }
+    }
+}
+
+impl ChannelEndpoint {
+    pub fn Attach(name: &str, queue: &Queue) -> Result<Self> {
+        let mut fds = [-1; 3];
+        let ret = HostSpace::ChannelOpen(name, &mut fds);
+        if ret < 0 {
+            return Err(Error::SysError(-ret as i32));
+        }
+
+        let prot = (LibcConst::PROT_READ | LibcConst::PROT_WRITE) as i32;
+        let addr = HostSpace::MMapFile(SHM_CHANNEL_SIZE, fds[0], 0, prot);
+        if addr < 0 {
+            for fd in &fds {
+                HostSpace::Close(*fd);
+            }
+            return Err(Error::SysError(-addr as i32));
+        }
+
+        let addr = addr as u64;
+        let header = unsafe { &*(addr as *const ChannelHeader) };
+        let side = match (0..2).find(|i| {
+            header.slots[*i]
+                .compare_exchange(0, 1, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+        }) {
+            Some(side) => side,
+            None => {
+                HostSpace::MUnmap(addr, SHM_CHANNEL_SIZE);
+                for fd in &fds {
+                    HostSpace::Close(*fd);
+                }
+                return Err(Error::SysError(SysErr::EBUSY));
+            }
+        };
+
+        let ring = |i: usize| {
+            let headtail = &header.rings[i] as *const _ as u64;
+            let buf = addr + (1 + i * CHANNEL_RING_PAGES) as u64 * MemoryDef::PAGE_SIZE;
+            RingBuf::New(
+                CHANNEL_RING_PAGES,
+                RingeBufAllocator::ShareAttachAllocator(headtail, buf),
+            )
+        };
+
+        // drop what is left from the last user of the side
+        let inbound = ring(side);
+        let tail = inbound.headtail[1].load(Ordering::Acquire);
+        inbound.headtail[0].store(tail, Ordering::Release);
+        header.waiting[side].store(0, Ordering::SeqCst);
+
+        SetWaitInfo(fds[1 + side], queue.clone());
+
+        return Ok(Self {
+            side: side,
+            addr: addr,
+            fds: fds,
+            inbound: QMutex::new(inbound),
+            outbound: QMutex::new(ring(1 - side)),
+            peerGone: AtomicBool::new(false),
+            peerSeen: AtomicBool::new(false),
+        });
+    }
+
+    pub fn Header(&self) -> &'static ChannelHeader {
+        return unsafe { &*(self.addr as *const ChannelHeader) };
+    }
+
+    pub fn Bell(&self, side: usize) -> i32 {
+        return self.fds[1 + side];
+    }
+
+    // the peer is gone once it has been attached and detached
+    pub fn PeerGone(&self) -> bool {
+        if self.peerGone.load(Ordering::Acquire) {
+            return true;
+        }
+
+        let attached = self.Header().slots[1 - self.side].load(Ordering::SeqCst) == 1;
+        if attached {
+            self.peerSeen.store(true, Ordering::Release);
+            return false;
+        }
+
+        if self.peerSeen.load(Ordering::Acquire) {
+            self.peerGone.store(true, Ordering::Release);
+            return true;
+        }
+
+        return false;
+    }
+
+    // wake up the peer if it is waiting for the channel
+    pub fn Kick(&self) {
+        fence(Ordering::SeqCst);
+        let peer = 1 - self.side;
+        if self.Header().waiting[peer].swap(0, Ordering::SeqCst) == 1 {
+            let buf = [1u8];
+            IOWrite(self.Bell(peer), &[IoVec::New(&buf)]).ok();
+        }
+    }
+
+    // empty the doorbell and ask the peer for the next one
+    pub fn Rearm(&self) {
+        let header = self.Header();
+        if header.waiting[self.side].load(Ordering::SeqCst) == 1 {
+            return;
+        }
+
+        let buf = [0u8; 64];
+        loop {
+            match IORead(self.Bell(self.side), &[IoVec::New(&buf)]) {
+                Ok(n) if n > 0 => (),
+                _ => break,
+            }
+        }
+
+        header.waiting[self.side].store(1, Ordering::SeqCst);
+    }
+
+    pub fn Events(&self) -> EventMask {
+        let gone = self.PeerGone();
+        let mut ready = 0;
+        if self.inbound.lock().AvailableDataSize() > 0 || gone {
+            ready |= READABLE_EVENT;
+        }
+
+        if gone {
+            ready |= EVENT_HUP;
+        } else if self.outbound.lock().AvailableSpace() > 0 {
+            ready |= WRITEABLE_EVENT;
+        }
+
+        return ready;
+    }
+
+    pub fn Read(&self, task: &Task, dsts: &[IoVec]) -> Result<i64> {
+        let count = {
+            let ring = self.inbound.lock();
+            let size = core::cmp::min(ring.AvailableDataSize(), IoVec::NumBytes(dsts));
+            if size == 0 {
+                if self.PeerGone() {
+                    return Ok(0);
+                }
+
+                return Err(Error::SysError(SysErr::EWOULDBLOCK));
+            }
+
+            let mut buf: Vec<u8> = vec![0; size];
+            let (_, n) = ring.read(&mut buf)?;
+            buf.truncate(n);
+            buf
+        };
+
+        self.Kick();
+        let n = task.CopyDataOutToIovs(&count, dsts, true)?;
+        return Ok(n as i64);
+    }
+
+    pub fn Write(&self, task: &Task, srcs: &[IoVec]) -> Result<i64> {
+        if self.PeerGone() {
+            return Err(Error::SysError(SysErr::EPIPE));
+        }
+
+        let n = {
+            let mut ring = self.outbound.lock();
+            let size = core::cmp::min(ring.AvailableSpace(), IoVec::NumBytes(srcs));
+            if size == 0 {
+                return Err(Error::SysError(SysErr::EWOULDBLOCK));
+            }
+
+            let mut buf: Vec<u8> = vec![0; size];
+            let len = task.CopyDataInFromIovs(&mut buf, srcs, true)?;
+            let (_, n) = ring.write(&buf[..len])?;
+            n
+        };
+
+        self.Kick();
+        return Ok(n as i64);
+    }
+}
+
+// ChannelFileOperations is /dev/qchannel. QCHANNEL_ATTACH joins the file to a
+// side of the named channel, a channel has 2 sides which can be in different
+// sandboxes on the same host.
+#[derive(Default)]
+pub struct ChannelFileOperations {
+    pub queue: Queue,
+    pub waiters: QMutex<usize>,
+    pub endpoint: QMutex<Option<Arc<ChannelEndpoint>>>,
+}
+
+impl ChannelFileOperations {
+    pub fn Endpoint(&self) -> Result<Arc<ChannelEndpoint>> {
+        match self.EndpointOpt() {
+            None => return Err(Error::SysError(SysErr::ENOTCONN)),
+            Some(ep) => return Ok(ep),
+        }
+    }
+
+    pub fn Attach(&self, task: &Task, val: u64) -> Result<()> {
+        let arg: QChannelAttach = UserPtr::New(val).Read(task)?;
+        let len = match arg.name.iter().position(|c| *c == 0) {
+            None => return Err(Error::SysError(SysErr::EINVAL)),
+            Some(len) => len,
+        };
+
+        let name = match String::from_utf8(arg.name[..len].to_vec()) {
+            Err(_) => return Err(Error::SysError(SysErr::EINVAL)),
+            Ok(name) => name,
+        };
+
+        if !ValidChannelName(&name) {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        if self.endpoint.lock().is_some() {
+            return Err(Error::SysError(SysErr::EISCONN));
+        }
+
+        let ep = Arc::new(ChannelEndpoint::Attach(&name, &self.queue)?);
+        {
+            let mut endpoint = self.endpoint.lock();
+            if endpoint.is_some() {
+                return Err(Error::SysError(SysErr::EISCONN));
+            }
+            *endpoint = Some(ep.clone());
+        }
+
+        if *self.waiters.lock() > 0 {
+            ep.Rearm();
+            UpdateFD(ep.Bell(ep.side)).unwrap();
+        }
+
+        self.queue.Notify(READABLE_EVENT | WRITEABLE_EVENT);
+        return Ok(());
+    }
+
+    fn EndpointOpt(&self) -> Option<Arc<ChannelEndpoint>> {
+        return self.endpoint.lock().clone();
+    }
+
+    fn Rearm(&self, ep: &ChannelEndpoint) {
+        if *self.waiters.lock() > 0 {
+            ep.Rearm();
+        }
+    }
+}
+
+impl Waitable for ChannelFileOperations {
+    fn Readiness(&self, _task: &Task, mask: EventMask) -> EventMask {
+        match self.EndpointOpt() {
+            None => return 0,
+            Some(ep) => return mask & ep.Events(),
+        }
+    }
+
+    // the doorbell fifo is always writable, the peer rings it when it reads
+    // or writes the channel, so the waiters only wait for it to be readable
+    fn EventRegister(&self, task: &Task, e: &WaitEntry, _mask: EventMask) {
+        self.queue.EventRegister(task, e, READABLE_EVENT);
+        *self.waiters.lock() += 1;
+        if let Some(ep) = self.EndpointOpt() {
+            ep.Rearm();
+            UpdateFD(ep.Bell(ep.side)).unwrap();
+        }
+    }
+
+    fn EventUnregister(&self, task: &Task, e: &WaitEntry) {
+        self.queue.EventUnregister(task, e);
+        let waiters = {
+            let mut waiters = self.waiters.lock();
+            *waiters -= 1;
+            *waiters
+        };
+
+        if let Some(ep) = self.EndpointOpt() {
+            if waiters == 0 {
+                ep.Header().waiting[ep.side].store(0, Ordering::SeqCst);
+            }
+            UpdateFD(ep.Bell(ep.side)).unwrap();
+        }
+    }
+}
+
+impl SpliceOperations for ChannelFileOperations {}
+
+impl FileOperations for ChannelFileOperations {
+    fn as_any(&self) -> &Any {
+        return self;
+    }
+
+    fn FopsType(&self) -> FileOpsType {
+        return FileOpsType::ChannelFileOperations;
+    }
+
+    fn Seekable(&self) -> bool {
+        return false;
+    }
+
+    fn Seek(
+        &self,
+        _task: &Task,
+        _f: &File,
+        _whence: i32,
+        _current: i64,
+        _offset: i64,
+    ) -> Result<i64> {
+        return Err(Error::SysError(SysErr::ESPIPE));
+    }
+
+    fn ReadDir(
+        &self,
+        _task: &Task,
+        _f: &File,
+        _offset: i64,
+        _serializer: &mut DentrySerializer,
+    ) -> Result<i64> {
+        return Err(Error::SysError(SysErr::ENOTDIR));
+    }
+
+    fn ReadAt(
+        &self,
+        task: &Task,
+        _f: &File,
+        dsts: &mut [IoVec],
+        _offset: i64,
+        _blocking: bool,
+    ) -> Result<i64> {
+        let ep = self.Endpoint()?;
+        self.Rearm(&ep);
+        return ep.Read(task, dsts);
+    }
+
+    fn WriteAt(
+        &self,
+        task: &Task,
+        _f: &File,
+        srcs: &[IoVec],
+        _offset: i64,
+        _blocking: bool,
+    ) -> Result<i64> {
+        let ep = self.Endpoint()?;
+        self.Rearm(&ep);
+        return ep.Write(task, srcs);
+    }
+
+    fn Append(&self, task: &Task, f: &File, srcs: &[IoVec]) -> Result<(i64, i64)> {
+        let n = self.WriteAt(task, f, srcs, 0, false)?;
+        return Ok((n, 0));
+    }
+
+    fn Fsync(
+        &self,
+        _task: &Task,
+        _f: &File,
+        _start: i64,
+        _end: i64,
+        _syncType: SyncType,
+    ) -> Result<()> {
+        return Ok(());
+    }
+
+    fn Flush(&self, _task: &Task, _f: &File) -> Result<()> {
+        return Ok(());
+    }
+
+    fn UnstableAttr(&self, task: &Task, f: &File) -> Result<UnstableAttr> {
+        let inode = f.Dirent.Inode();
+        return inode.UnstableAttr(task);
+    }
+
+    fn Ioctl(&self, task: &Task, _f: &File, _fd: i32, request: u64, val: u64) -> Result<()> {
+        match request {
+            QCHANNEL_ATTACH => return self.Attach(task, val),
+            _ => return Err(Error::SysError(SysErr::ENOTTY)),
+        }
+    }
+
+    fn IterateDir(
+        &self,
+        _task: &Task,
+        _d: &Dirent,
+        _dirCtx: &mut DirCtx,
+        _offset: i32,
+    ) -> (i32, Result<i64>) {
+        return (0, Err(Error::SysError(SysErr::ENOTDIR)));
+    }
+
+    fn Mappable(&self) -> Result<HostInodeOp> {
+        return Err(Error::SysError(SysErr::ENODEV));
+    }
+}
+
+impl SockOperations for ChannelFileOperations {}
@@ -22,12 +22,18 @@ use super::super::super::super::device::*;
 use super::super::super::super::linux_def::*;
 use super::super::super::task::*;
 use super::super::super::uid::NewUID;
+use super::super::super::SHARESPACE;
 use super::super::attr::*;
 use super::super::inode::*;
 use super::super::mount::*;
 use super::super::ramfs::dir::*;
 use super::super::ramfs::symlink::*;
+use super::channel::*;
 use super::full::*;
+use super::host_block::*;
+use super::host_device::*;
+use super::kmsg::*;
+use super::loop_dev::*;
 use super::null::*;
 use super::random::*;
 use super::tty::*;
@@ -41,6 +47,7 @@ const ZERO_DEV_MINOR: u32 = 5;
 const FULL_DEV_MINOR: u32 = 7;
 const RANDOM_DEV_MINOR: u32 = 8;
 const URANDOM_DEV_MINOR: u32 = 9;
+const KMSG_DEV_MINOR: u32 = 11;
 
 fn NewTTYDevice(iops: &Arc<TTYDevice>, msrc: &Arc<QMutex<MountSource>>) -> Inode {
     let deviceId = DEV_DEVICE.lock().id.DeviceID();
@@ -142,6 +149,31 @@ fn NewFullDevice(iops: &Arc<FullDevice>, msrc: &Arc<QMutex<MountSource>>) -> Ino
     return Inode(Arc::new(QMutex::new(inodeInternal)));
 }
 
+fn NewKmsgDevice(iops: &Arc<KmsgDevice>, msrc: &Arc<QMutex<MountSource>>) -> Inode {
+    let deviceId = DEV_DEVICE.lock().id.DeviceID();
+    let inodeId = DEV_DEVICE.lock().NextIno();
+
+    let stableAttr = StableAttr {
+        Type: InodeType::CharacterDevice,
+        DeviceId: deviceId,
+        InodeId: inodeId,
+        BlockSize: MemoryDef::PAGE_SIZE as i64,
+        DeviceFileMajor: MEM_DEV_MAJOR,
+        DeviceFileMinor: KMSG_DEV_MINOR,
+    };
+
+    let inodeInternal = InodeIntern {
+        UniqueId: NewUID(),
+        InodeOp: iops.clone(),
+        StableAttr: stableAttr,
+        LockCtx: LockCtx::default(),
+        MountSource: msrc.clone(),
+        Overlay: None,
+    };
+
+    return Inode(Arc::new(QMutex::new(inodeInternal)));
+}
+
 fn NewRandomDevice(iops: &Arc<RandomDevice>, msrc: &Arc<QMutex<MountSource>>, minor: u32) -> Inode {
     let deviceId = DEV_DEVICE.lock().id.DeviceID();
     let inodeId = DEV_DEVICE.lock().NextIno();
@@ -268,6 +300,14 @@ pub fn NewDev(task: &Task, msrc: &Arc<QMutex<MountSource>>) -> Inode {
         ),
     );
 
+    contents.insert(
+        "kmsg".to_string(),
+        NewKmsgDevice(
+            &Arc::new(KmsgDevice::New(task, &ROOT_OWNER, &FileMode(0o0644))),
+            msrc,
+        ),
+    );
+
     // This is not as good as /dev/random in linux because go
     // runtime uses sys_random and /dev/urandom internally.
     // According to 'man 4 random', this will be sufficient unless
@@ -312,6 +352,27 @@ pub fn NewDev(task: &Task, msrc: &Arc<QMutex<MountSource>>) -> Inode {
     let ttyDevice = TTYDevice::New(task, &ROOT_OWNER, &FileMode(0o0666));
     contents.insert("tty".to_string(), NewTTYDevice(&Arc::new(ttyDevice), msrc));
 
+    // The loop devices for mounting filesystem images in the sandbox.
+    for i in 0..LOOP_DEVICE_COUNT {
+        contents.insert(format!("loop{}", i), NewLoopDevice(task, i as u32, msrc));
+    }
+    contents.insert("loop-control".to_string(), NewLoopControl(task, msrc));
+
+    // The share memory channel to the sandboxes on the same host.
+    if SHARESPACE.config.read().ShmChannel {
+        contents.insert("qchannel".to_string(), NewChannelDevice(task, msrc));
+    }
+
+    // The block volumes served by qvisor.
+    for (name, inode) in HostBlockNodes(task, msrc) {
+        contents.entry(name).or_insert(inode);
+    }
+
+    // The host devices allowed by the config and asked for by the spec.
+    for (name, inode) in HostDeviceNodes(task, msrc) {
+        contents.entry(name).or_insert(inode);
+    }
+
     let iops = Dir::New(
         task,
         contents,
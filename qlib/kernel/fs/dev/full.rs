@@ -176,6 +176,7 @@ impl InodeOperations for FullDevice {
         _newParent: &Inode,
         _newname: &str,
         _replacement: bool,
+        _flags: u32,
     ) -> Result<()> {
         return Err(Error::SysError(SysErr::EINVAL));
     }
@@ -0,0 +1,146 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::qlib::mutex::*;
+use alloc::collections::btree_map::BTreeMap;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::sync::Arc;
+use core::any::Any;
+
+use super::super::super::super::common::*;
+use super::super::super::super::linux_def::*;
+use super::super::super::super::loader::BlockVolume;
+use super::super::super::super::singleton::*;
+use super::super::super::fd::*;
+use super::super::super::task::*;
+use super::super::super::Kernel::HostSpace;
+use super::super::inode::*;
+use super::super::mount::*;
+use super::block::*;
+
+// the major number linux gives to virtio-blk disks
+pub const VIRTBLK_MAJOR: u16 = 254;
+// every disk takes 16 minors for its partitions
+pub const VIRTBLK_MINORS: u32 = 16;
+
+// HostBlockDevice is a raw block volume served by qvisor: a host image
+// file or block device opened at sandbox start. It gives a workload a
+// private writable disk without sharing a host directory.
+pub struct HostBlockDevice {
+    pub name: String,
+    pub hostfd: i32,
+    pub size: u64,
+    pub readOnly: bool,
+}
+
+impl BlockDevice for HostBlockDevice {
+    fn as_any(&self) -> &Any {
+        return self;
+    }
+
+    fn Size(&self, _task: &Task) -> Result<u64> {
+        return Ok(self.size);
+    }
+
+    fn ReadOnly(&self) -> bool {
+        return self.readOnly;
+    }
+
+    fn ReadAt(&self, _task: &Task, dsts: &mut [IoVec], offset: u64) -> Result<i64> {
+        return IOReadAt(self.hostfd, dsts, offset);
+    }
+
+    fn WriteAt(&self, _task: &Task, srcs: &[IoVec], offset: u64) -> Result<i64> {
+        if self.readOnly {
+            return Err(Error::SysError(SysErr::EROFS));
+        }
+
+        return IOWriteAt(self.hostfd, srcs, offset);
+    }
+
+    fn Flush(&self, _task: &Task) -> Result<()> {
+        let ret = HostSpace::FSync(self.hostfd);
+        if ret < 0 {
+            return Err(Error::SysError(-ret as i32));
+        }
+
+        return Ok(());
+    }
+
+    fn BlockSize(&self) -> u64 {
+        return MemoryDef::PAGE_SIZE;
+    }
+}
+
+// HOST_BLOCK_DEVICES are the block volumes of the containers registered at
+// load time, keyed by the node name in /dev
+pub static HOST_BLOCK_DEVICES: Singleton<QMutex<BTreeMap<String, Arc<HostBlockDevice>>>> =
+    Singleton::<QMutex<BTreeMap<String, Arc<HostBlockDevice>>>>::New();
+
+pub unsafe fn InitSingleton() {
+    HOST_BLOCK_DEVICES.Init(QMutex::new(BTreeMap::new()));
+}
+
+fn ValidVolumeName(name: &str) -> bool {
+    return name.len() > 0
+        && name.len() <= 32
+        && name
+            .bytes()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit());
+}
+
+// RegisterBlockVolumes adds the block volumes of a container process to the
+// registry
+pub fn RegisterBlockVolumes(volumes: &[BlockVolume]) {
+    let mut registry = HOST_BLOCK_DEVICES.lock();
+    for v in volumes {
+        if !ValidVolumeName(&v.Name) {
+            info!("block volume name {} is not valid", &v.Name);
+            continue;
+        }
+
+        if registry.contains_key(&v.Name) {
+            info!("block volume {} is registered already", &v.Name);
+            continue;
+        }
+
+        let dev = HostBlockDevice {
+            name: v.Name.to_string(),
+            hostfd: v.HostFd,
+            size: v.Size,
+            readOnly: v.ReadOnly,
+        };
+        registry.insert(v.Name.to_string(), Arc::new(dev));
+    }
+}
+
+// HostBlockNodes returns the /dev entries of the registered block volumes
+pub fn HostBlockNodes(task: &Task, msrc: &Arc<QMutex<MountSource>>) -> BTreeMap<String, Inode> {
+    let mut nodes = BTreeMap::new();
+    for (i, (name, d)) in HOST_BLOCK_DEVICES.lock().iter().enumerate() {
+        let dev: Arc<BlockDevice> = d.clone();
+        let inode = NewBlockDeviceInode(
+            task,
+            &dev,
+            VIRTBLK_MAJOR,
+            i as u32 * VIRTBLK_MINORS,
+            &FilePermissions::FromMode(FileMode(0o0660)),
+            msrc,
+        );
+        nodes.insert(name.to_string(), inode);
+    }
+
+    return nodes;
+}
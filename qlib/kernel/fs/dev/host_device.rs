@@ -0,0 +1,634 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::qlib::mutex::*;
+use alloc::collections::btree_map::BTreeMap;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::ops::Deref;
+
+use super::super::super::super::auth::*;
+use super::super::super::super::common::*;
+use super::super::super::super::config::HostDeviceClass;
+use super::super::super::super::device::*;
+use super::super::super::super::linux_def::*;
+use super::super::super::super::loader::HostDevice;
+use super::super::super::super::nvproxy::IsFrontendDevice;
+use super::super::super::super::singleton::*;
+use super::super::super::kernel::time::*;
+use super::super::super::kernel::waiter::*;
+use super::super::super::socket::unix::transport::unix::*;
+use super::super::super::task::*;
+use super::super::super::threadmgr::task_usermem::*;
+use super::super::super::uid::*;
+use super::super::super::Kernel::HostSpace;
+use super::super::super::SHARESPACE;
+use super::super::attr::*;
+use super::super::dentry::*;
+use super::super::dirent::*;
+use super::super::file::*;
+use super::super::flags::*;
+use super::super::fsutil::inode::*;
+use super::super::host::hostfileop::*;
+use super::super::host::hostinodeop::*;
+use super::super::host::util::*;
+use super::super::inode::*;
+use super::super::mount::*;
+use super::super::ramfs::dir::*;
+use super::nvproxy;
+
+// HOST_DEVICES are the host devices of the containers registered at load
+// time, keyed by the guest path
+pub static HOST_DEVICES: Singleton<QMutex<BTreeMap<String, HostDevice>>> =
+    Singleton::<QMutex<BTreeMap<String, HostDevice>>>::New();
+
+pub unsafe fn InitSingleton() {
+    HOST_DEVICES.Init(QMutex::new(BTreeMap::new()));
+}
+
+// RegisterHostDevices adds the host devices of a container process to the
+// registry. The list is checked again against the config allowlist as the
+// process spec comes from the host.
+pub fn RegisterHostDevices(devices: &[HostDevice]) {
+    let allowed = SHARESPACE.config.read().HostDevices;
+    let mut registry = HOST_DEVICES.lock();
+    for d in devices {
+        if allowed.Class(&d.Path).is_none() {
+            info!("host device {} is not in the allowlist", &d.Path);
+            continue;
+        }
+
+        registry.insert(d.Path.to_string(), d.clone());
+    }
+}
+
+// HostDeviceNodes returns the /dev entries of the registered host devices.
+// Devices in a sub directory of /dev, e.g. /dev/net/tun, get a read only
+// directory holding them.
+pub fn HostDeviceNodes(task: &Task, msrc: &Arc<QMutex<MountSource>>) -> BTreeMap<String, Inode> {
+    let mut nodes = BTreeMap::new();
+    let mut subdirs: BTreeMap<String, BTreeMap<String, Inode>> = BTreeMap::new();
+
+    for (path, d) in HOST_DEVICES.lock().iter() {
+        let rel = match path.strip_prefix("/dev/") {
+            None => continue,
+            Some(rel) => rel,
+        };
+
+        let parts: Vec<&str> = rel.split('/').collect();
+        match parts.len() {
+            1 => {
+                nodes.insert(parts[0].to_string(), NewPassthroughDevice(task, d, msrc));
+            }
+            2 => {
+                subdirs
+                    .entry(parts[0].to_string())
+                    .or_insert_with(BTreeMap::new)
+                    .insert(parts[1].to_string(), NewPassthroughDevice(task, d, msrc));
+            }
+            _ => {
+                info!("host device {} is nested too deep in /dev", path);
+            }
+        }
+    }
+
+    for (name, contents) in subdirs {
+        nodes.insert(name, NewDeviceDirectory(task, contents, msrc));
+    }
+
+    return nodes;
+}
+
+fn NewDeviceDirectory(
+    task: &Task,
+    contents: BTreeMap<String, Inode>,
+    msrc: &Arc<QMutex<MountSource>>,
+) -> Inode {
+    let iops = Dir::New(
+        task,
+        contents,
+        &ROOT_OWNER,
+        &FilePermissions::FromMode(FileMode(0o0555)),
+    );
+
+    let deviceId = DEV_DEVICE.lock().id.DeviceID();
+    let inodeId = DEV_DEVICE.lock().NextIno();
+
+    let stableAttr = StableAttr {
+        Type: InodeType::Directory,
+        DeviceId: deviceId,
+        InodeId: inodeId,
+        BlockSize: MemoryDef::PAGE_SIZE as i64,
+        DeviceFileMajor: 0,
+        DeviceFileMinor: 0,
+    };
+
+    let inodeInternal = InodeIntern {
+        UniqueId: NewUID(),
+        InodeOp: Arc::new(iops),
+        StableAttr: stableAttr,
+        LockCtx: LockCtx::default(),
+        MountSource: msrc.clone(),
+        Overlay: None,
+    };
+
+    return Inode(Arc::new(QMutex::new(inodeInternal)));
+}
+
+fn NewPassthroughDevice(task: &Task, d: &HostDevice, msrc: &Arc<QMutex<MountSource>>) -> Inode {
+    let iops = PassthroughDevice::New(task, d);
+
+    let deviceId = DEV_DEVICE.lock().id.DeviceID();
+    let inodeId = DEV_DEVICE.lock().NextIno();
+
+    let stableAttr = StableAttr {
+        Type: InodeType::CharacterDevice,
+        DeviceId: deviceId,
+        InodeId: inodeId,
+        BlockSize: MemoryDef::PAGE_SIZE as i64,
+        DeviceFileMajor: d.Major as u16,
+        DeviceFileMinor: d.Minor,
+    };
+
+    let inodeInternal = InodeIntern {
+        UniqueId: NewUID(),
+        InodeOp: Arc::new(iops),
+        StableAttr: stableAttr,
+        LockCtx: LockCtx::default(),
+        MountSource: msrc.clone(),
+        Overlay: None,
+    };
+
+    return Inode(Arc::new(QMutex::new(inodeInternal)));
+}
+
+pub struct PassthroughDeviceInternal {
+    pub attr: InodeSimpleAttributesInternal,
+    pub path: String,
+    pub class: HostDeviceClass,
+}
+
+// PassthroughDevice is a host device exposed in the guest /dev. Its mode and
+// owner come from the spec, each open opens the host device and the guest
+// file proxies to the host fd.
+pub struct PassthroughDevice(pub QRwLock<PassthroughDeviceInternal>);
+
+impl Deref for PassthroughDevice {
+    type Target = QRwLock<PassthroughDeviceInternal>;
+
+    fn deref(&self) -> &QRwLock<PassthroughDeviceInternal> {
+        &self.0
+    }
+}
+
+impl PassthroughDevice {
+    pub fn New(task: &Task, d: &HostDevice) -> Self {
+        let owner = FileOwner {
+            UID: KUID(d.UID),
+            GID: KGID(d.GID),
+        };
+
+        let attr = InodeSimpleAttributesInternal::New(
+            task,
+            &owner,
+            &FilePermissions::FromMode(FileMode(d.Mode as u16)),
+            FSMagic::TMPFS_MAGIC,
+        );
+
+        // the device is registered only when its class is allowed
+        let class = HostDeviceClass::Of(&d.Path).unwrap();
+        let internal = PassthroughDeviceInternal {
+            attr: attr,
+            path: d.Path.to_string(),
+            class: class,
+        };
+
+        return Self(QRwLock::new(internal));
+    }
+}
+
+impl InodeOperations for PassthroughDevice {
+    fn as_any(&self) -> &Any {
+        return self;
+    }
+
+    fn IopsType(&self) -> IopsType {
+        return IopsType::PassthroughDevice;
+    }
+
+    fn InodeType(&self) -> InodeType {
+        return InodeType::CharacterDevice;
+    }
+
+    fn InodeFileType(&self) -> InodeFileType {
+        return InodeFileType::Passthrough;
+    }
+
+    fn WouldBlock(&self) -> bool {
+        return true;
+    }
+
+    fn Lookup(&self, _task: &Task, _dir: &Inode, _name: &str) -> Result<Dirent> {
+        return Err(Error::SysError(SysErr::ENOTDIR));
+    }
+
+    fn Create(
+        &self,
+        _task: &Task,
+        _dir: &mut Inode,
+        _name: &str,
+        _flags: &FileFlags,
+        _perm: &FilePermissions,
+    ) -> Result<File> {
+        return Err(Error::SysError(SysErr::ENOTDIR));
+    }
+
+    fn CreateDirectory(
+        &self,
+        _task: &Task,
+        _dir: &mut Inode,
+        _name: &str,
+        _perm: &FilePermissions,
+    ) -> Result<()> {
+        return Err(Error::SysError(SysErr::ENOTDIR));
+    }
+
+    fn CreateLink(
+        &self,
+        _task: &Task,
+        _dir: &mut Inode,
+        _oldname: &str,
+        _newname: &str,
+    ) -> Result<()> {
+        return Err(Error::SysError(SysErr::ENOTDIR));
+    }
+
+    fn CreateHardLink(
+        &self,
+        _task: &Task,
+        _dir: &mut Inode,
+        _target: &Inode,
+        _name: &str,
+    ) -> Result<()> {
+        return Err(Error::SysError(SysErr::ENOTDIR));
+    }
+
+    fn CreateFifo(
+        &self,
+        _task: &Task,
+        _dir: &mut Inode,
+        _name: &str,
+        _perm: &FilePermissions,
+    ) -> Result<()> {
+        return Err(Error::SysError(SysErr::ENOTDIR));
+    }
+
+    fn Remove(&self, _task: &Task, _dir: &mut Inode, _name: &str) -> Result<()> {
+        return Err(Error::SysError(SysErr::ENOTDIR));
+    }
+
+    fn RemoveDirectory(&self, _task: &Task, _dir: &mut Inode, _name: &str) -> Result<()> {
+        return Err(Error::SysError(SysErr::ENOTDIR));
+    }
+
+    fn Rename(
+        &self,
+        _task: &Task,
+        _dir: &mut Inode,
+        _oldParent: &Inode,
+        _oldname: &str,
+        _newParent: &Inode,
+        _newname: &str,
+        _replacement: bool,
+        _flags: u32,
+    ) -> Result<()> {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    fn Bind(
+        &self,
+        _task: &Task,
+        _dir: &Inode,
+        _name: &str,
+        _data: &BoundEndpoint,
+        _perms: &FilePermissions,
+    ) -> Result<Dirent> {
+        return Err(Error::SysError(SysErr::ENOTDIR));
+    }
+
+    fn BoundEndpoint(&self, _task: &Task, _inode: &Inode, _path: &str) -> Option<BoundEndpoint> {
+        return None;
+    }
+
+    fn GetFile(&self, task: &Task, dir: &Inode, dirent: &Dirent, flags: FileFlags) -> Result<File> {
+        let (path, class) = {
+            let d = self.read();
+            (d.path.to_string(), d.class)
+        };
+
+        let (fd, writeable, fstat) = TryOpenAt(-100, &path)?;
+        if flags.Write && !writeable {
+            HostSpace::Close(fd);
+            return Err(Error::SysError(SysErr::EACCES));
+        }
+
+        let msrc = dir.lock().MountSource.clone();
+        let mops = msrc.lock().MountSourceOperations.clone();
+        let iops = HostInodeOp::New(&mops, fd, fstat.WouldBlock(), &fstat, writeable);
+        if class == HostDeviceClass::Nvidia && IsFrontendDevice(&path) {
+            iops.EnableExactMapping();
+        }
+
+        let fops = PassthroughFileOperations {
+            fileOps: iops.GetHostFileOp(task),
+            class: class,
+        };
+
+        return Ok(File::NewHostFile(
+            dirent,
+            &flags,
+            Arc::new(fops),
+            fstat.WouldBlock(),
+        ));
+    }
+
+    fn UnstableAttr(&self, _task: &Task) -> Result<UnstableAttr> {
+        let u = self.read().attr.unstable;
+        return Ok(u);
+    }
+
+    fn Getxattr(&self, _dir: &Inode, _name: &str) -> Result<String> {
+        return Err(Error::SysError(SysErr::EOPNOTSUPP));
+    }
+
+    fn Setxattr(&self, _dir: &mut Inode, _name: &str, _value: &str) -> Result<()> {
+        return Err(Error::SysError(SysErr::EOPNOTSUPP));
+    }
+
+    fn Listxattr(&self, _dir: &Inode) -> Result<Vec<String>> {
+        return Err(Error::SysError(SysErr::EOPNOTSUPP));
+    }
+
+    fn Check(&self, task: &Task, inode: &Inode, reqPerms: &PermMask) -> Result<bool> {
+        return ContextCanAccessFile(task, inode, reqPerms);
+    }
+
+    fn SetPermissions(&self, task: &Task, _dir: &mut Inode, p: FilePermissions) -> bool {
+        self.write().attr.unstable.SetPermissions(task, &p);
+        return true;
+    }
+
+    fn SetOwner(&self, task: &Task, _dir: &mut Inode, owner: &FileOwner) -> Result<()> {
+        self.write().attr.unstable.SetOwner(task, owner);
+        return Ok(());
+    }
+
+    fn SetTimestamps(&self, task: &Task, _dir: &mut Inode, ts: &InterTimeSpec) -> Result<()> {
+        self.write().attr.unstable.SetTimestamps(task, ts);
+        return Ok(());
+    }
+
+    fn Truncate(&self, _task: &Task, _dir: &mut Inode, _size: i64) -> Result<()> {
+        return Ok(());
+    }
+
+    fn Allocate(&self, _task: &Task, _dir: &mut Inode, _offset: i64, _length: i64) -> Result<()> {
+        return Ok(());
+    }
+
+    fn ReadLink(&self, _task: &Task, _dir: &Inode) -> Result<String> {
+        return Err(Error::SysError(SysErr::ENOLINK));
+    }
+
+    fn GetLink(&self, _task: &Task, _dir: &Inode) -> Result<Dirent> {
+        return Err(Error::SysError(SysErr::ENOLINK));
+    }
+
+    fn AddLink(&self, _task: &Task) {
+        self.write().attr.unstable.Links += 1;
+    }
+
+    fn DropLink(&self, _task: &Task) {
+        self.write().attr.unstable.Links -= 1;
+    }
+
+    fn IsVirtual(&self) -> bool {
+        return true;
+    }
+
+    fn Sync(&self) -> Result<()> {
+        return Err(Error::SysError(SysErr::ENOSYS));
+    }
+
+    fn StatFS(&self, _task: &Task) -> Result<FsInfo> {
+        return Err(Error::SysError(SysErr::ENOSYS));
+    }
+
+    fn Mappable(&self) -> Result<HostInodeOp> {
+        return Err(Error::SysError(SysErr::ENODEV));
+    }
+}
+
+const IOC_TYPESHIFT: u64 = 8;
+const IOC_SIZESHIFT: u64 = 16;
+const IOC_DIRSHIFT: u64 = 30;
+const IOC_SIZEMASK: u64 = 0x3fff;
+
+const IOC_WRITE: u64 = 1;
+const IOC_READ: u64 = 2;
+
+// PassthroughFileOperations is an open host device. Read, write and poll go
+// to the host fd as for a host file, the ioctls of the device class are
+// proxied with the argument copied by the size encoded in the request.
+pub struct PassthroughFileOperations {
+    pub fileOps: Arc<HostFileOp>,
+    pub class: HostDeviceClass,
+}
+
+impl PassthroughFileOperations {
+    pub fn HostFd(&self) -> i32 {
+        return self.fileOps.InodeOp.HostFd();
+    }
+}
+
+impl Waitable for PassthroughFileOperations {
+    fn Readiness(&self, task: &Task, mask: EventMask) -> EventMask {
+        return self.fileOps.Readiness(task, mask);
+    }
+
+    fn EventRegister(&self, task: &Task, e: &WaitEntry, mask: EventMask) {
+        self.fileOps.EventRegister(task, e, mask);
+    }
+
+    fn EventUnregister(&self, task: &Task, e: &WaitEntry) {
+        self.fileOps.EventUnregister(task, e);
+    }
+}
+
+impl SpliceOperations for PassthroughFileOperations {}
+
+impl FileOperations for PassthroughFileOperations {
+    fn as_any(&self) -> &Any {
+        return self;
+    }
+
+    fn FopsType(&self) -> FileOpsType {
+        return FileOpsType::PassthroughFileOperations;
+    }
+
+    fn Seekable(&self) -> bool {
+        return false;
+    }
+
+    fn Seek(
+        &self,
+        _task: &Task,
+        _f: &File,
+        _whence: i32,
+        _current: i64,
+        _offset: i64,
+    ) -> Result<i64> {
+        return Err(Error::SysError(SysErr::ESPIPE));
+    }
+
+    fn ReadDir(
+        &self,
+        _task: &Task,
+        _f: &File,
+        _offset: i64,
+        _serializer: &mut DentrySerializer,
+    ) -> Result<i64> {
+        return Err(Error::SysError(SysErr::ENOTDIR));
+    }
+
+    fn ReadAt(
+        &self,
+        task: &Task,
+        f: &File,
+        dsts: &mut [IoVec],
+        offset: i64,
+        blocking: bool,
+    ) -> Result<i64> {
+        return self.fileOps.ReadAt(task, f, dsts, offset, blocking);
+    }
+
+    fn WriteAt(
+        &self,
+        task: &Task,
+        f: &File,
+        srcs: &[IoVec],
+        offset: i64,
+        blocking: bool,
+    ) -> Result<i64> {
+        return self.fileOps.WriteAt(task, f, srcs, offset, blocking);
+    }
+
+    fn Append(&self, task: &Task, f: &File, srcs: &[IoVec]) -> Result<(i64, i64)> {
+        let n = self.WriteAt(task, f, srcs, 0, false)?;
+        return Ok((n, 0));
+    }
+
+    fn Fsync(
+        &self,
+        _task: &Task,
+        _f: &File,
+        _start: i64,
+        _end: i64,
+        _syncType: SyncType,
+    ) -> Result<()> {
+        return Ok(());
+    }
+
+    fn Flush(&self, _task: &Task, _f: &File) -> Result<()> {
+        return Ok(());
+    }
+
+    fn UnstableAttr(&self, task: &Task, f: &File) -> Result<UnstableAttr> {
+        let inode = f.Dirent.Inode();
+        return inode.UnstableAttr(task);
+    }
+
+    fn Ioctl(&self, task: &Task, _f: &File, _fd: i32, request: u64, val: u64) -> Result<()> {
+        // the nvidia ioctls carry nested pointers and fds, they go through
+        // the vetted handlers of nvproxy
+        if self.class == HostDeviceClass::Nvidia {
+            return nvproxy::Ioctl(task, self.HostFd(), request, val);
+        }
+
+        let typ = ((request >> IOC_TYPESHIFT) & 0xff) as u8;
+        if !self.class.IoctlTypes().contains(&typ) {
+            return Err(Error::SysError(SysErr::ENOTTY));
+        }
+
+        let dir = request >> IOC_DIRSHIFT;
+        let size = ((request >> IOC_SIZESHIFT) & IOC_SIZEMASK) as usize;
+        let fd = self.HostFd();
+
+        // the argument is a value, not a pointer
+        if dir == 0 || size == 0 {
+            let ret = HostSpace::IoCtl(fd, request, val);
+            if ret < 0 {
+                return Err(Error::SysError(-ret as i32));
+            }
+
+            return Ok(());
+        }
+
+        let mut buf: Vec<u8> = if dir & IOC_WRITE != 0 {
+            UserSlice::New(val, size).Read(task)?
+        } else {
+            vec![0; size]
+        };
+
+        let ret = HostSpace::IoCtl(fd, request, &mut buf[0] as *mut _ as u64);
+        if ret < 0 {
+            return Err(Error::SysError(-ret as i32));
+        }
+
+        if dir & IOC_READ != 0 {
+            UserSlice::New(val, size).Write(task, &buf)?;
+        }
+
+        return Ok(());
+    }
+
+    fn IterateDir(
+        &self,
+        _task: &Task,
+        _d: &Dirent,
+        _dirCtx: &mut DirCtx,
+        _offset: i32,
+    ) -> (i32, Result<i64>) {
+        return (0, Err(Error::SysError(SysErr::ENOTDIR)));
+    }
+
+    fn Mappable(&self) -> Result<HostInodeOp> {
+        // the nvidia frontend devices map only the exact range set up by
+        // RM_MAP_MEMORY, the uvm device memory can't be mapped by a host fd
+        if self.fileOps.InodeOp.ExactMapping() {
+            return Ok(self.fileOps.InodeOp.clone());
+        }
+
+        if self.class == HostDeviceClass::Nvidia {
+            info!("nvproxy: mmap of the nvidia device is not supported");
+        }
+
+        return Err(Error::SysError(SysErr::ENODEV));
+    }
+}
+
+impl SockOperations for PassthroughFileOperations {}
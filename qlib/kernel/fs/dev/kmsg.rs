@@ -0,0 +1,479 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::qlib::mutex::*;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::ops::Deref;
+
+use super::super::super::super::auth::*;
+use super::super::super::super::common::*;
+use super::super::super::super::linux_def::*;
+use super::super::super::kernel::kmsg::*;
+use super::super::super::kernel::time::*;
+use super::super::super::kernel::waiter::qlock::*;
+use super::super::super::kernel::waiter::*;
+use super::super::super::socket::unix::transport::unix::*;
+use super::super::super::task::*;
+use super::super::super::uid::*;
+use super::super::host::hostinodeop::*;
+
+use super::super::attr::*;
+use super::super::dentry::*;
+use super::super::dirent::*;
+use super::super::file::*;
+use super::super::flags::*;
+use super::super::fsutil::file::*;
+use super::super::fsutil::inode::*;
+use super::super::inode::*;
+use super::super::mount::*;
+
+pub struct KmsgDevice(pub QRwLock<InodeSimpleAttributesInternal>);
+
+impl Default for KmsgDevice {
+    fn default() -> Self {
+        return Self(QRwLock::new(Default::default()));
+    }
+}
+
+impl Deref for KmsgDevice {
+    type Target = QRwLock<InodeSimpleAttributesInternal>;
+
+    fn deref(&self) -> &QRwLock<InodeSimpleAttributesInternal> {
+        &self.0
+    }
+}
+
+impl KmsgDevice {
+    pub fn New(task: &Task, owner: &FileOwner, mode: &FileMode) -> Self {
+        let attr = InodeSimpleAttributesInternal::New(
+            task,
+            owner,
+            &FilePermissions::FromMode(*mode),
+            FSMagic::TMPFS_MAGIC,
+        );
+        return Self(QRwLock::new(attr));
+    }
+}
+
+impl InodeOperations for KmsgDevice {
+    fn as_any(&self) -> &Any {
+        return self;
+    }
+
+    fn IopsType(&self) -> IopsType {
+        return IopsType::KmsgDevice;
+    }
+
+    fn InodeFileType(&self) -> InodeFileType {
+        return InodeFileType::Kmsg;
+    }
+
+    fn InodeType(&self) -> InodeType {
+        return InodeType::CharacterDevice;
+    }
+
+    fn WouldBlock(&self) -> bool {
+        return true;
+    }
+
+    fn Lookup(&self, _task: &Task, _dir: &Inode, _name: &str) -> Result<Dirent> {
+        return Err(Error::SysError(SysErr::ENOTDIR));
+    }
+
+    fn Create(
+        &self,
+        _task: &Task,
+        _dir: &mut Inode,
+        _name: &str,
+        _flags: &FileFlags,
+        _perm: &FilePermissions,
+    ) -> Result<File> {
+        return Err(Error::SysError(SysErr::ENOTDIR));
+    }
+
+    fn CreateDirectory(
+        &self,
+        _task: &Task,
+        _dir: &mut Inode,
+        _name: &str,
+        _perm: &FilePermissions,
+    ) -> Result<()> {
+        return Err(Error::SysError(SysErr::ENOTDIR));
+    }
+
+    fn Bind(
+        &self,
+        _task: &Task,
+        _dir: &Inode,
+        _name: &str,
+        _data: &BoundEndpoint,
+        _perms: &FilePermissions,
+    ) -> Result<Dirent> {
+        return Err(Error::SysError(SysErr::ENOTDIR));
+    }
+
+    fn BoundEndpoint(&self, _task: &Task, _inode: &Inode, _path: &str) -> Option<BoundEndpoint> {
+        return None;
+    }
+
+    fn CreateLink(
+        &self,
+        _task: &Task,
+        _dir: &mut Inode,
+        _oldname: &str,
+        _newname: &str,
+    ) -> Result<()> {
+        return Err(Error::SysError(SysErr::ENOTDIR));
+    }
+
+    fn CreateHardLink(
+        &self,
+        _task: &Task,
+        _dir: &mut Inode,
+        _target: &Inode,
+        _name: &str,
+    ) -> Result<()> {
+        return Err(Error::SysError(SysErr::ENOTDIR));
+    }
+
+    fn CreateFifo(
+        &self,
+        _task: &Task,
+        _dir: &mut Inode,
+        _name: &str,
+        _perm: &FilePermissions,
+    ) -> Result<()> {
+        return Err(Error::SysError(SysErr::ENOTDIR));
+    }
+
+    fn Remove(&self, _task: &Task, _dir: &mut Inode, _name: &str) -> Result<()> {
+        return Err(Error::SysError(SysErr::ENOTDIR));
+    }
+
+    fn RemoveDirectory(&self, _task: &Task, _dir: &mut Inode, _name: &str) -> Result<()> {
+        return Err(Error::SysError(SysErr::ENOTDIR));
+    }
+
+    fn Rename(
+        &self,
+        _task: &Task,
+        _dir: &mut Inode,
+        _oldParent: &Inode,
+        _oldname: &str,
+        _newParent: &Inode,
+        _newname: &str,
+        _replacement: bool,
+        _flags: u32,
+    ) -> Result<()> {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    fn GetFile(
+        &self,
+        task: &Task,
+        _dir: &Inode,
+        dirent: &Dirent,
+        flags: FileFlags,
+    ) -> Result<File> {
+        // as Linux, opening for read is checked as reading all the messages
+        // with syslog(2), a write only open is allowed for everyone
+        if flags.Read {
+            CheckSyslogPermission(task, true)?;
+        }
+
+        // a new reader starts from the oldest message in the ring
+        let fops = KmsgFileOperations {
+            seq: QMutex::new(KMSG.FirstSeq()),
+        };
+
+        let f = FileInternal {
+            UniqueId: NewUID(),
+            Dirent: dirent.clone(),
+            flags: QMutex::new((flags, None)),
+            offset: QLock::New(0),
+            FileOp: Arc::new(fops),
+        };
+
+        return Ok(File(Arc::new(f)));
+    }
+
+    fn UnstableAttr(&self, _task: &Task) -> Result<UnstableAttr> {
+        let u = self.read().unstable;
+        return Ok(u);
+    }
+
+    fn Getxattr(&self, _dir: &Inode, _name: &str) -> Result<String> {
+        return Err(Error::SysError(SysErr::EOPNOTSUPP));
+    }
+
+    fn Setxattr(&self, _dir: &mut Inode, _name: &str, _value: &str) -> Result<()> {
+        return Err(Error::SysError(SysErr::EOPNOTSUPP));
+    }
+
+    fn Listxattr(&self, _dir: &Inode) -> Result<Vec<String>> {
+        return Err(Error::SysError(SysErr::EOPNOTSUPP));
+    }
+
+    fn Check(&self, task: &Task, inode: &Inode, reqPerms: &PermMask) -> Result<bool> {
+        return ContextCanAccessFile(task, inode, reqPerms);
+    }
+
+    fn SetPermissions(&self, task: &Task, _dir: &mut Inode, p: FilePermissions) -> bool {
+        self.write().unstable.SetPermissions(task, &p);
+        return true;
+    }
+
+    fn SetOwner(&self, task: &Task, _dir: &mut Inode, owner: &FileOwner) -> Result<()> {
+        self.write().unstable.SetOwner(task, owner);
+        return Ok(());
+    }
+
+    fn SetTimestamps(&self, task: &Task, _dir: &mut Inode, ts: &InterTimeSpec) -> Result<()> {
+        self.write().unstable.SetTimestamps(task, ts);
+        return Ok(());
+    }
+
+    fn Truncate(&self, _task: &Task, _dir: &mut Inode, _size: i64) -> Result<()> {
+        return Ok(());
+    }
+
+    fn Allocate(&self, _task: &Task, _dir: &mut Inode, _offset: i64, _length: i64) -> Result<()> {
+        return Ok(());
+    }
+
+    fn ReadLink(&self, _task: &Task, _dir: &Inode) -> Result<String> {
+        return Err(Error::SysError(SysErr::ENOLINK));
+    }
+
+    fn GetLink(&self, _task: &Task, _dir: &Inode) -> Result<Dirent> {
+        return Err(Error::SysError(SysErr::ENOLINK));
+    }
+
+    fn AddLink(&self, _task: &Task) {
+        self.write().unstable.Links += 1;
+    }
+
+    fn DropLink(&self, _task: &Task) {
+        self.write().unstable.Links -= 1;
+    }
+
+    fn IsVirtual(&self) -> bool {
+        return true;
+    }
+
+    fn Sync(&self) -> Result<()> {
+        return Err(Error::SysError(SysErr::ENOSYS));
+    }
+
+    fn StatFS(&self, _task: &Task) -> Result<FsInfo> {
+        return Err(Error::SysError(SysErr::ENOSYS));
+    }
+
+    fn Mappable(&self) -> Result<HostInodeOp> {
+        return Err(Error::SysError(SysErr::ENODEV));
+    }
+}
+
+// KmsgFileOperations implements /dev/kmsg. Each read returns one message in
+// "level,seq,timestamp,flags;message" format. The read doesn't block when there
+// is no new message, it fails with EAGAIN as a non-blocking read, and the
+// readers wait for new messages with poll/epoll.
+pub struct KmsgFileOperations {
+    // seq is the sequence number of the next message to read
+    pub seq: QMutex<u64>,
+}
+
+impl Waitable for KmsgFileOperations {
+    fn Readiness(&self, _task: &Task, mask: EventMask) -> EventMask {
+        let mut ready = WRITEABLE_EVENT;
+        if *self.seq.lock() < KMSG.NextSeq() {
+            ready |= READABLE_EVENT;
+        }
+
+        return mask & ready;
+    }
+
+    fn EventRegister(&self, task: &Task, e: &WaitEntry, mask: EventMask) {
+        KMSG.Queue().EventRegister(task, e, mask);
+    }
+
+    fn EventUnregister(&self, task: &Task, e: &WaitEntry) {
+        KMSG.Queue().EventUnregister(task, e);
+    }
+}
+
+impl SpliceOperations for KmsgFileOperations {}
+
+impl FileOperations for KmsgFileOperations {
+    fn as_any(&self) -> &Any {
+        return self;
+    }
+
+    fn FopsType(&self) -> FileOpsType {
+        return FileOpsType::KmsgFileOperations;
+    }
+
+    fn Seekable(&self) -> bool {
+        return true;
+    }
+
+    // Seek only supports moving to the oldest message with SEEK_SET and to the
+    // end of the log with SEEK_END, both with zero offset.
+    fn Seek(
+        &self,
+        _task: &Task,
+        _f: &File,
+        whence: i32,
+        _current: i64,
+        offset: i64,
+    ) -> Result<i64> {
+        if offset != 0 {
+            return Err(Error::SysError(SysErr::ESPIPE));
+        }
+
+        if whence == SeekWhence::SEEK_SET {
+            *self.seq.lock() = KMSG.FirstSeq();
+        } else if whence == SeekWhence::SEEK_END {
+            *self.seq.lock() = KMSG.NextSeq();
+        } else {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        return Ok(0);
+    }
+
+    fn ReadDir(
+        &self,
+        _task: &Task,
+        _f: &File,
+        _offset: i64,
+        _serializer: &mut DentrySerializer,
+    ) -> Result<i64> {
+        return Err(Error::SysError(SysErr::ENOTDIR));
+    }
+
+    fn ReadAt(
+        &self,
+        task: &Task,
+        _f: &File,
+        dsts: &mut [IoVec],
+        _offset: i64,
+        _blocking: bool,
+    ) -> Result<i64> {
+        let mut seq = self.seq.lock();
+        let entry = match KMSG.Get(*seq) {
+            None => return Err(Error::SysError(SysErr::EAGAIN)),
+            Some(e) => e,
+        };
+
+        // the messages are overwritten before read, linux reports EPIPE once
+        // and continues from the oldest message
+        if entry.Seq != *seq {
+            *seq = entry.Seq;
+            return Err(Error::SysError(SysErr::EPIPE));
+        }
+
+        let record = entry.KmsgFormat();
+        let size = IoVec::NumBytes(dsts);
+        if size < record.len() {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        let done = task.CopyDataOutToIovs(record.as_bytes(), dsts, false)?;
+        *seq = entry.Seq + 1;
+        return Ok(done as i64);
+    }
+
+    // WriteAt adds a message to the kernel log. An optional "<level>" prefix
+    // sets the message level, the default is LOG_INFO.
+    fn WriteAt(
+        &self,
+        task: &Task,
+        _f: &File,
+        srcs: &[IoVec],
+        _offset: i64,
+        _blocking: bool,
+    ) -> Result<i64> {
+        let size = IoVec::NumBytes(srcs);
+        if size > KMSG_LINE_MAX {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        let mut buf = DataBuff::New(size);
+        let len = task.CopyDataInFromIovs(&mut buf.buf, srcs, true)?;
+
+        let msg = String::from_utf8_lossy(&buf.buf[..len]).into_owned();
+        let mut level = LOG_INFO;
+        let mut text = msg.as_str();
+        if text.starts_with("<") {
+            if let Some(end) = text.find('>') {
+                if let Ok(l) = text[1..end].parse::<i32>() {
+                    level = l & 0x7;
+                    text = &text[end + 1..];
+                }
+            }
+        }
+
+        KMSG.Log(level, text);
+        return Ok(len as i64);
+    }
+
+    fn Append(&self, task: &Task, f: &File, srcs: &[IoVec]) -> Result<(i64, i64)> {
+        let n = self.WriteAt(task, f, srcs, 0, false)?;
+        return Ok((n, 0));
+    }
+
+    fn Fsync(
+        &self,
+        _task: &Task,
+        _f: &File,
+        _start: i64,
+        _end: i64,
+        _syncType: SyncType,
+    ) -> Result<()> {
+        return Ok(());
+    }
+
+    fn Flush(&self, _task: &Task, _f: &File) -> Result<()> {
+        return Ok(());
+    }
+
+    fn UnstableAttr(&self, task: &Task, f: &File) -> Result<UnstableAttr> {
+        let inode = f.Dirent.Inode();
+        return inode.UnstableAttr(task);
+    }
+
+    fn Ioctl(&self, _task: &Task, _f: &File, _fd: i32, _request: u64, _val: u64) -> Result<()> {
+        return Err(Error::SysError(SysErr::ENOTTY));
+    }
+
+    fn IterateDir(
+        &self,
+        _task: &Task,
+        _d: &Dirent,
+        _dirCtx: &mut DirCtx,
+        _offset: i32,
+    ) -> (i32, Result<i64>) {
+        return (0, Err(Error::SysError(SysErr::ENOTDIR)));
+    }
+
+    fn Mappable(&self) -> Result<HostInodeOp> {
+        return Err(Error::SysError(SysErr::ENODEV));
+    }
+}
+
+impl SockOperations for KmsgFileOperations {}
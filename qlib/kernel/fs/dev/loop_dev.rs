@@ -0,0 +1,525 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::qlib::mutex::*;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::ops::Deref;
+
+use super::super::super::super::auth::*;
+use super::super::super::super::common::*;
+use super::super::super::super::device::*;
+use super::super::super::super::linux_def::*;
+use super::super::super::super::singleton::*;
+use super::super::super::kernel::waiter::*;
+use super::super::super::task::*;
+use super::super::super::threadmgr::task_usermem::*;
+use super::super::attr::*;
+use super::super::dentry::*;
+use super::super::dirent::*;
+use super::super::file::*;
+use super::super::flags::*;
+use super::super::fsutil::file::*;
+use super::super::fsutil::inode::simple_file_inode::*;
+use super::super::host::hostinodeop::*;
+use super::super::inode::*;
+use super::super::mount::*;
+use super::block::*;
+
+pub const LOOP_MAJOR: u16 = 7;
+pub const LOOP_CONTROL_MINOR: u32 = 237;
+pub const MISC_MAJOR: u16 = 10;
+
+// the loop devices are created with /dev, LOOP_CTL_ADD can't add more
+pub const LOOP_DEVICE_COUNT: usize = 8;
+
+pub struct LoopCmd {}
+
+impl LoopCmd {
+    pub const LOOP_SET_FD: u64 = 0x4C00;
+    pub const LOOP_CLR_FD: u64 = 0x4C01;
+    pub const LOOP_SET_STATUS64: u64 = 0x4C04;
+    pub const LOOP_GET_STATUS64: u64 = 0x4C05;
+    pub const LOOP_SET_CAPACITY: u64 = 0x4C07;
+    pub const LOOP_SET_DIRECT_IO: u64 = 0x4C08;
+    pub const LOOP_SET_BLOCK_SIZE: u64 = 0x4C09;
+    pub const LOOP_CONFIGURE: u64 = 0x4C0A;
+
+    pub const LOOP_CTL_ADD: u64 = 0x4C80;
+    pub const LOOP_CTL_REMOVE: u64 = 0x4C81;
+    pub const LOOP_CTL_GET_FREE: u64 = 0x4C82;
+
+}
+
+pub const LO_FLAGS_READ_ONLY: u32 = 1;
+pub const LO_FLAGS_AUTOCLEAR: u32 = 4;
+
+pub const LO_NAME_SIZE: usize = 64;
+pub const LO_KEY_SIZE: usize = 32;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct LoopInfo64 {
+    pub lo_device: u64,
+    pub lo_inode: u64,
+    pub lo_rdevice: u64,
+    pub lo_offset: u64,
+    pub lo_sizelimit: u64,
+    pub lo_number: u32,
+    pub lo_encrypt_type: u32,
+    pub lo_encrypt_key_size: u32,
+    pub lo_flags: u32,
+    pub lo_file_name: [u8; LO_NAME_SIZE],
+    pub lo_crypt_name: [u8; LO_NAME_SIZE],
+    pub lo_encrypt_key: [u8; LO_KEY_SIZE],
+    pub lo_init: [u64; 2],
+}
+
+impl Default for LoopInfo64 {
+    fn default() -> Self {
+        return Self {
+            lo_device: 0,
+            lo_inode: 0,
+            lo_rdevice: 0,
+            lo_offset: 0,
+            lo_sizelimit: 0,
+            lo_number: 0,
+            lo_encrypt_type: 0,
+            lo_encrypt_key_size: 0,
+            lo_flags: 0,
+            lo_file_name: [0; LO_NAME_SIZE],
+            lo_crypt_name: [0; LO_NAME_SIZE],
+            lo_encrypt_key: [0; LO_KEY_SIZE],
+            lo_init: [0; 2],
+        };
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct LoopConfig {
+    pub fd: u32,
+    pub block_size: u32,
+    pub info: LoopInfo64,
+    pub reserved: [u64; 8],
+}
+
+pub struct LoopDeviceInternal {
+    pub number: u32,
+    pub backing: Option<File>,
+    pub info: LoopInfo64,
+}
+
+// LoopDevice is /dev/loopN, a block device over a guest file. It is the
+// source of the filesystem images mounted inside the sandbox.
+pub struct LoopDevice(QMutex<LoopDeviceInternal>);
+
+impl Deref for LoopDevice {
+    type Target = QMutex<LoopDeviceInternal>;
+
+    fn deref(&self) -> &QMutex<LoopDeviceInternal> {
+        &self.0
+    }
+}
+
+pub static LOOP_DEVICES: Singleton<Vec<Arc<LoopDevice>>> = Singleton::<Vec<Arc<LoopDevice>>>::New();
+
+pub unsafe fn InitSingleton() {
+    let mut devices = Vec::with_capacity(LOOP_DEVICE_COUNT);
+    for i in 0..LOOP_DEVICE_COUNT {
+        devices.push(Arc::new(LoopDevice::New(i as u32)));
+    }
+
+    LOOP_DEVICES.Init(devices);
+}
+
+impl LoopDevice {
+    pub fn New(number: u32) -> Self {
+        let internal = LoopDeviceInternal {
+            number: number,
+            backing: None,
+            info: LoopInfo64 {
+                lo_number: number,
+                ..Default::default()
+            },
+        };
+
+        return Self(QMutex::new(internal));
+    }
+
+    pub fn IsBound(&self) -> bool {
+        return self.lock().backing.is_some();
+    }
+
+    // Backing returns the backing file with the offset and the size limit
+    pub fn Backing(&self) -> Result<(File, u64, u64)> {
+        let d = self.lock();
+        match &d.backing {
+            None => return Err(Error::SysError(SysErr::ENXIO)),
+            Some(f) => return Ok((f.clone(), d.info.lo_offset, d.info.lo_sizelimit)),
+        }
+    }
+
+    fn SetFd(&self, task: &Task, fd: i32, info: Option<&LoopInfo64>) -> Result<()> {
+        let file = task.GetFile(fd)?;
+        let inode = file.Dirent.Inode();
+        if !inode.StableAttr().IsRegular() && !inode.StableAttr().IsBlockDevice() {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        let mut d = self.lock();
+        if d.backing.is_some() {
+            return Err(Error::SysError(SysErr::EBUSY));
+        }
+
+        let mut newInfo = match info {
+            None => LoopInfo64::default(),
+            Some(info) => *info,
+        };
+
+        newInfo.lo_number = d.number;
+        newInfo.lo_inode = inode.StableAttr().InodeId;
+        newInfo.lo_device = inode.StableAttr().DeviceId as u64;
+        if !file.Flags().Write {
+            newInfo.lo_flags |= LO_FLAGS_READ_ONLY;
+        }
+
+        if newInfo.lo_file_name[0] == 0 {
+            let name = file.Dirent.MyFullName();
+            let n = core::cmp::min(name.len(), LO_NAME_SIZE - 1);
+            newInfo.lo_file_name[..n].copy_from_slice(&name.as_bytes()[..n]);
+        }
+
+        d.backing = Some(file);
+        d.info = newInfo;
+        return Ok(());
+    }
+
+    fn Clear(&self) -> Result<()> {
+        let mut d = self.lock();
+        if d.backing.is_none() {
+            return Err(Error::SysError(SysErr::ENXIO));
+        }
+
+        d.backing = None;
+        d.info = LoopInfo64 {
+            lo_number: d.number,
+            ..Default::default()
+        };
+        return Ok(());
+    }
+
+    fn SetStatus(&self, info: &LoopInfo64) -> Result<()> {
+        let mut d = self.lock();
+        if d.backing.is_none() {
+            return Err(Error::SysError(SysErr::ENXIO));
+        }
+
+        // the backing file and the read only flag can't be changed here
+        let readOnly = d.info.lo_flags & LO_FLAGS_READ_ONLY;
+        d.info.lo_offset = info.lo_offset;
+        d.info.lo_sizelimit = info.lo_sizelimit;
+        d.info.lo_flags = (info.lo_flags & LO_FLAGS_AUTOCLEAR) | readOnly;
+        d.info.lo_file_name = info.lo_file_name;
+        return Ok(());
+    }
+}
+
+impl BlockDevice for LoopDevice {
+    fn as_any(&self) -> &Any {
+        return self;
+    }
+
+    fn Size(&self, task: &Task) -> Result<u64> {
+        let (file, offset, limit) = self.Backing()?;
+        let fileSize = file.UnstableAttr(task)?.Size as u64;
+        let mut size = if fileSize > offset {
+            fileSize - offset
+        } else {
+            0
+        };
+
+        if limit > 0 && limit < size {
+            size = limit;
+        }
+
+        return Ok(size);
+    }
+
+    fn ReadOnly(&self) -> bool {
+        return self.lock().info.lo_flags & LO_FLAGS_READ_ONLY != 0;
+    }
+
+    fn ReadAt(&self, task: &Task, dsts: &mut [IoVec], offset: u64) -> Result<i64> {
+        let (file, base, _) = self.Backing()?;
+        return file.Preadv(task, dsts, (base + offset) as i64);
+    }
+
+    fn WriteAt(&self, task: &Task, srcs: &[IoVec], offset: u64) -> Result<i64> {
+        let (file, base, _) = self.Backing()?;
+        return file.Pwritev(task, srcs, (base + offset) as i64);
+    }
+
+    fn Flush(&self, _task: &Task) -> Result<()> {
+        return Ok(());
+    }
+
+    fn Ioctl(&self, task: &Task, request: u64, val: u64) -> Result<()> {
+        match request {
+            LoopCmd::LOOP_SET_FD => {
+                return self.SetFd(task, val as i32, None);
+            }
+            LoopCmd::LOOP_CONFIGURE => {
+                let config: LoopConfig = UserPtr::New(val).Read(task)?;
+                return self.SetFd(task, config.fd as i32, Some(&config.info));
+            }
+            LoopCmd::LOOP_CLR_FD => {
+                return self.Clear();
+            }
+            LoopCmd::LOOP_SET_STATUS64 => {
+                let info: LoopInfo64 = UserPtr::New(val).Read(task)?;
+                return self.SetStatus(&info);
+            }
+            LoopCmd::LOOP_GET_STATUS64 => {
+                if !self.IsBound() {
+                    return Err(Error::SysError(SysErr::ENXIO));
+                }
+
+                let info = self.lock().info;
+                UserPtr::New(val).Write(task, &info)?;
+                return Ok(());
+            }
+            LoopCmd::LOOP_SET_CAPACITY
+            | LoopCmd::LOOP_SET_DIRECT_IO
+            | LoopCmd::LOOP_SET_BLOCK_SIZE => {
+                if !self.IsBound() {
+                    return Err(Error::SysError(SysErr::ENXIO));
+                }
+
+                return Ok(());
+            }
+            _ => return Err(Error::SysError(SysErr::ENOTTY)),
+        }
+    }
+}
+
+// GetFreeLoopDevice returns the number of the first unbound loop device
+pub fn GetFreeLoopDevice() -> Result<i64> {
+    for d in LOOP_DEVICES.iter() {
+        if !d.IsBound() {
+            return Ok(d.lock().number as i64);
+        }
+    }
+
+    return Err(Error::SysError(SysErr::ENOSPC));
+}
+
+pub fn NewLoopDevice(task: &Task, number: u32, msrc: &Arc<QMutex<MountSource>>) -> Inode {
+    let dev: Arc<BlockDevice> = LOOP_DEVICES[number as usize].clone();
+    return NewBlockDeviceInode(
+        task,
+        &dev,
+        LOOP_MAJOR,
+        number,
+        &FilePermissions::FromMode(FileMode(0o0660)),
+        msrc,
+    );
+}
+
+pub fn NewLoopControl(task: &Task, msrc: &Arc<QMutex<MountSource>>) -> Inode {
+    let iops = SimpleFileInode::New(
+        task,
+        &ROOT_OWNER,
+        &FilePermissions::FromMode(FileMode(0o0660)),
+        FSMagic::TMPFS_MAGIC,
+        false,
+        LoopControlNode {},
+    );
+
+    let deviceId = DEV_DEVICE.lock().id.DeviceID();
+    let inodeId = DEV_DEVICE.lock().NextIno();
+
+    let stableAttr = StableAttr {
+        Type: InodeType::CharacterDevice,
+        DeviceId: deviceId,
+        InodeId: inodeId,
+        BlockSize: MemoryDef::PAGE_SIZE as i64,
+        DeviceFileMajor: MISC_MAJOR,
+        DeviceFileMinor: LOOP_CONTROL_MINOR,
+    };
+
+    return Inode::New(&Arc::new(iops), msrc, &stableAttr);
+}
+
+pub struct LoopControlNode {}
+
+impl SimpleFileTrait for LoopControlNode {
+    fn GetFile(
+        &self,
+        _task: &Task,
+        _dir: &Inode,
+        dirent: &Dirent,
+        flags: FileFlags,
+    ) -> Result<File> {
+        return Ok(File::New(dirent, &flags, LoopControlFileOperations {}));
+    }
+}
+
+// LoopControlFileOperations is /dev/loop-control. Its ioctls return a device
+// number, so SysIoctl calls Control instead of Ioctl for it.
+pub struct LoopControlFileOperations {}
+
+impl LoopControlFileOperations {
+    pub fn Control(&self, _task: &Task, request: u64, val: u64) -> Result<i64> {
+        match request {
+            LoopCmd::LOOP_CTL_GET_FREE => return GetFreeLoopDevice(),
+            LoopCmd::LOOP_CTL_ADD => {
+                if val as usize >= LOOP_DEVICE_COUNT {
+                    return Err(Error::SysError(SysErr::ENOSPC));
+                }
+
+                return Err(Error::SysError(SysErr::EEXIST));
+            }
+            LoopCmd::LOOP_CTL_REMOVE => {
+                if val as usize >= LOOP_DEVICE_COUNT {
+                    return Err(Error::SysError(SysErr::ENODEV));
+                }
+
+                if LOOP_DEVICES[val as usize].IsBound() {
+                    return Err(Error::SysError(SysErr::EBUSY));
+                }
+
+                // the devices are static, removing one is a no-op
+                return Ok(0);
+            }
+            _ => return Err(Error::SysError(SysErr::ENOTTY)),
+        }
+    }
+}
+
+impl Waitable for LoopControlFileOperations {
+    fn Readiness(&self, _task: &Task, mask: EventMask) -> EventMask {
+        return mask;
+    }
+
+    fn EventRegister(&self, _task: &Task, _e: &WaitEntry, _mask: EventMask) {}
+
+    fn EventUnregister(&self, _task: &Task, _e: &WaitEntry) {}
+}
+
+impl SpliceOperations for LoopControlFileOperations {}
+
+impl FileOperations for LoopControlFileOperations {
+    fn as_any(&self) -> &Any {
+        return self;
+    }
+
+    fn FopsType(&self) -> FileOpsType {
+        return FileOpsType::LoopControlFileOperations;
+    }
+
+    fn Seekable(&self) -> bool {
+        return false;
+    }
+
+    fn Seek(
+        &self,
+        _task: &Task,
+        _f: &File,
+        _whence: i32,
+        _current: i64,
+        _offset: i64,
+    ) -> Result<i64> {
+        return Err(Error::SysError(SysErr::ESPIPE));
+    }
+
+    fn ReadDir(
+        &self,
+        _task: &Task,
+        _f: &File,
+        _offset: i64,
+        _serializer: &mut DentrySerializer,
+    ) -> Result<i64> {
+        return Err(Error::SysError(SysErr::ENOTDIR));
+    }
+
+    fn ReadAt(
+        &self,
+        _task: &Task,
+        _f: &File,
+        _dsts: &mut [IoVec],
+        _offset: i64,
+        _blocking: bool,
+    ) -> Result<i64> {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    fn WriteAt(
+        &self,
+        _task: &Task,
+        _f: &File,
+        _srcs: &[IoVec],
+        _offset: i64,
+        _blocking: bool,
+    ) -> Result<i64> {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    fn Append(&self, task: &Task, f: &File, srcs: &[IoVec]) -> Result<(i64, i64)> {
+        let n = self.WriteAt(task, f, srcs, 0, false)?;
+        return Ok((n, 0));
+    }
+
+    fn Fsync(
+        &self,
+        _task: &Task,
+        _f: &File,
+        _start: i64,
+        _end: i64,
+        _syncType: SyncType,
+    ) -> Result<()> {
+        return Ok(());
+    }
+
+    fn Flush(&self, _task: &Task, _f: &File) -> Result<()> {
+        return Ok(());
+    }
+
+    fn UnstableAttr(&self, task: &Task, f: &File) -> Result<UnstableAttr> {
+        let inode = f.Dirent.Inode();
+        return inode.UnstableAttr(task);
+    }
+
+    fn Ioctl(&self, task: &Task, _f: &File, _fd: i32, request: u64, val: u64) -> Result<()> {
+        self.Control(task, request, val)?;
+        return Ok(());
+    }
+
+    fn IterateDir(
+        &self,
+        _task: &Task,
+        _d: &Dirent,
+        _dirCtx: &mut DirCtx,
+        _offset: i32,
+    ) -> (i32, Result<i64>) {
+        return (0, Err(Error::SysError(SysErr::ENOTDIR)));
+    }
+
+    fn Mappable(&self) -> Result<HostInodeOp> {
+        return Err(Error::SysError(SysErr::ENODEV));
+    }
+}
+
+impl SockOperations for LoopControlFileOperations {}
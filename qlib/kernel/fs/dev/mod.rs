@@ -12,10 +12,17 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod block;
+pub mod channel;
 pub mod dev;
 pub mod fs;
 pub mod full;
+pub mod host_block;
+pub mod host_device;
+pub mod kmsg;
+pub mod loop_dev;
 pub mod null;
+pub mod nvproxy;
 pub mod random;
 pub mod tty;
 pub mod zero;
@@ -161,6 +161,7 @@ impl InodeOperations for NullDevice {
         _newParent: &Inode,
         _newname: &str,
         _replacement: bool,
+        _flags: u32,
     ) -> Result<()> {
         return Err(Error::SysError(SysErr::EINVAL));
     }
@@ -0,0 +1,218 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::vec::Vec;
+use core::mem::size_of;
+
+use super::super::super::super::common::*;
+use super::super::super::super::config::HostDeviceClass;
+use super::super::super::super::linux_def::*;
+use super::super::super::super::nvproxy::*;
+use super::super::super::task::*;
+use super::super::super::threadmgr::task_usermem::*;
+use super::super::super::Kernel::HostSpace;
+use super::host_device::*;
+
+// Ioctl forwards an ioctl of a nvidia device to the host. The argument is
+// copied into qkernel memory, with its nested parameters and nvidia fds
+// translated, and the vetted qcall runs it on the host fd.
+pub fn Ioctl(task: &Task, fd: i32, request: u64, val: u64) -> Result<()> {
+    let size = match IoctlArgSize(request) {
+        None => {
+            info!("nvproxy: ioctl {:x} is not supported", request);
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+        Some(size) => size,
+    };
+
+    if size == 0 {
+        return Call(fd, request, 0);
+    }
+
+    if IsFrontendIoctl(request) {
+        match FrontendNr(request) {
+            NV_ESC_RM_CONTROL => return RmControl(task, fd, request, val, size),
+            NV_ESC_RM_ALLOC => return RmAlloc(task, fd, request, val, size),
+            _ => (),
+        }
+    }
+
+    let mut buf: Vec<u8> = UserSlice::New(val, size).Read(task)?;
+
+    let mut guestFd = None;
+    if let Some(offset) = IoctlFdOffset(request) {
+        if offset + 4 > size {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        let f = ReadFd(&buf, offset);
+        WriteFd(&mut buf, offset, HostFdOf(task, f)?);
+        guestFd = Some((offset, f));
+    }
+
+    let res = Call(fd, request, &mut buf[0] as *mut _ as u64);
+    if let Some((offset, f)) = guestFd {
+        WriteFd(&mut buf, offset, f);
+    }
+    res?;
+
+    if IsFrontendIoctl(request) && FrontendNr(request) == NV_ESC_RM_MAP_MEMORY {
+        SetMmapContext(task, &buf)?;
+    }
+
+    UserSlice::New(val, size).Write(task, &buf)?;
+    return Ok(());
+}
+
+// SetMmapContext records the range RM_MAP_MEMORY set up on its fd, the next
+// guest mmap of the fd maps it
+fn SetMmapContext(task: &Task, buf: &[u8]) -> Result<()> {
+    if buf.len() != size_of::<Nvos33ParametersWithFd>() {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    let params = unsafe { *(&buf[0] as *const _ as u64 as *const Nvos33ParametersWithFd) };
+    // the driver failed, there is nothing to map
+    if params.Status != 0 {
+        return Ok(());
+    }
+
+    let file = task.GetFile(params.Fd)?;
+    let fops = file.FileOp.clone();
+    match fops.as_any().downcast_ref::<PassthroughFileOperations>() {
+        Some(f) if f.class == HostDeviceClass::Nvidia => {
+            return f
+                .fileOps
+                .InodeOp
+                .SetMmapContext(params.PLinearAddress, params.Length);
+        }
+        _ => return Err(Error::SysError(SysErr::EINVAL)),
+    }
+}
+
+fn Call(fd: i32, request: u64, argp: u64) -> Result<()> {
+    let ret = HostSpace::NvProxyIoctl(fd, request, argp);
+    if ret < 0 {
+        return Err(Error::SysError(-ret as i32));
+    }
+
+    return Ok(());
+}
+
+fn ReadFd(buf: &[u8], offset: usize) -> i32 {
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(&buf[offset..offset + 4]);
+    return i32::from_le_bytes(bytes);
+}
+
+fn WriteFd(buf: &mut [u8], offset: usize, fd: i32) {
+    buf[offset..offset + 4].copy_from_slice(&fd.to_le_bytes());
+}
+
+// HostFdOf returns the host fd of a guest fd which is an open nvidia device
+fn HostFdOf(task: &Task, fd: i32) -> Result<i32> {
+    let file = task.GetFile(fd)?;
+    let fops = file.FileOp.clone();
+    match fops.as_any().downcast_ref::<PassthroughFileOperations>() {
+        Some(f) if f.class == HostDeviceClass::Nvidia => return Ok(f.HostFd()),
+        _ => return Err(Error::SysError(SysErr::EINVAL)),
+    }
+}
+
+// CopyInParams copies in the nested parameters of a control or an alloc
+fn CopyInParams(task: &Task, addr: u64, size: usize) -> Result<Option<Vec<u8>>> {
+    if addr == 0 || size == 0 {
+        return Ok(None);
+    }
+
+    if size > NV_MAX_PARAMS_SIZE {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    let buf: Vec<u8> = UserSlice::New(addr, size).Read(task)?;
+    return Ok(Some(buf));
+}
+
+fn ParamsAddr(params: &mut Option<Vec<u8>>, guestAddr: u64) -> u64 {
+    match params {
+        None => return guestAddr,
+        Some(buf) => return &mut buf[0] as *mut _ as u64,
+    }
+}
+
+fn CopyOutParams(task: &Task, params: &Option<Vec<u8>>, addr: u64) -> Result<()> {
+    if let Some(buf) = params {
+        UserSlice::New(addr, buf.len()).Write(task, buf)?;
+    }
+
+    return Ok(());
+}
+
+fn RmControl(task: &Task, fd: i32, request: u64, val: u64, size: usize) -> Result<()> {
+    if size != size_of::<Nvos54Parameters>() {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    let mut ioctlParams: Nvos54Parameters = UserPtr::New(val).Read(task)?;
+    let guestParams = ioctlParams.Params;
+    let mut params = CopyInParams(task, guestParams, ioctlParams.ParamsSize as usize)?;
+
+    ioctlParams.Params = ParamsAddr(&mut params, guestParams);
+    let res = Call(fd, request, &mut ioctlParams as *mut _ as u64);
+    ioctlParams.Params = guestParams;
+    res?;
+
+    CopyOutParams(task, &params, guestParams)?;
+    UserPtr::New(val).Write(task, &ioctlParams)?;
+    return Ok(());
+}
+
+fn RmAlloc(task: &Task, fd: i32, request: u64, val: u64, size: usize) -> Result<()> {
+    if size == size_of::<Nvos21Parameters>() {
+        let mut ioctlParams: Nvos21Parameters = UserPtr::New(val).Read(task)?;
+        let guestParams = ioctlParams.PAllocParms;
+        let mut params = CopyInParams(task, guestParams, ioctlParams.ParamsSize as usize)?;
+
+        ioctlParams.PAllocParms = ParamsAddr(&mut params, guestParams);
+        let res = Call(fd, request, &mut ioctlParams as *mut _ as u64);
+        ioctlParams.PAllocParms = guestParams;
+        res?;
+
+        CopyOutParams(task, &params, guestParams)?;
+        UserPtr::New(val).Write(task, &ioctlParams)?;
+        return Ok(());
+    }
+
+    if size == size_of::<Nvos64Parameters>() {
+        let mut ioctlParams: Nvos64Parameters = UserPtr::New(val).Read(task)?;
+        let guestParams = ioctlParams.PAllocParms;
+        let guestRights = ioctlParams.PRightsRequested;
+        let mut params = CopyInParams(task, guestParams, ioctlParams.ParamsSize as usize)?;
+        let mut rights = CopyInParams(task, guestRights, RS_ACCESS_MASK_SIZE)?;
+
+        ioctlParams.PAllocParms = ParamsAddr(&mut params, guestParams);
+        ioctlParams.PRightsRequested = ParamsAddr(&mut rights, guestRights);
+        let res = Call(fd, request, &mut ioctlParams as *mut _ as u64);
+        ioctlParams.PAllocParms = guestParams;
+        ioctlParams.PRightsRequested = guestRights;
+        res?;
+
+        CopyOutParams(task, &params, guestParams)?;
+        CopyOutParams(task, &rights, guestRights)?;
+        UserPtr::New(val).Write(task, &ioctlParams)?;
+        return Ok(());
+    }
+
+    return Err(Error::SysError(SysErr::EINVAL));
+}
@@ -24,11 +24,13 @@ use super::super::super::super::common::*;
 use super::super::super::super::linux_def::*;
 use super::super::super::super::mem::io::*;
 use super::super::super::super::mem::seq::*;
+use super::super::super::kernel::entropy::*;
 use super::super::super::kernel::time::*;
 use super::super::super::kernel::waiter::qlock::*;
 use super::super::super::kernel::waiter::*;
 use super::super::super::socket::unix::transport::unix::*;
 use super::super::super::task::*;
+use super::super::super::threadmgr::task_usermem::*;
 use super::super::super::uid::*;
 use super::super::super::Kernel;
 use super::super::host::hostinodeop::*;
@@ -164,6 +166,7 @@ impl InodeOperations for RandomDevice {
         _newParent: &Inode,
         _newname: &str,
         _replacement: bool,
+        _flags: u32,
     ) -> Result<()> {
         return Err(Error::SysError(SysErr::EINVAL));
     }
@@ -382,8 +385,65 @@ impl FileOperations for RandomFileOperations {
         return inode.UnstableAttr(task);
     }
 
-    fn Ioctl(&self, _task: &Task, _f: &File, _fd: i32, _request: u64, _val: u64) -> Result<()> {
-        return Err(Error::SysError(SysErr::ENOTTY));
+    fn Ioctl(&self, task: &Task, _f: &File, _fd: i32, request: u64, val: u64) -> Result<()> {
+        match request {
+            IoCtlCmd::RNDGETENTCNT => {
+                let count = ENTROPY_POOL.EntropyAvail();
+                UserPtr::New(val).Write(task, &count)?;
+                return Ok(());
+            }
+            IoCtlCmd::RNDGETPOOL => return Err(Error::SysError(SysErr::EINVAL)),
+            IoCtlCmd::RNDADDTOENTCNT
+            | IoCtlCmd::RNDADDENTROPY
+            | IoCtlCmd::RNDZAPENTCNT
+            | IoCtlCmd::RNDCLEARPOOL
+            | IoCtlCmd::RNDRESEEDCRNG => (),
+            _ => return Err(Error::SysError(SysErr::ENOTTY)),
+        }
+
+        if !task.Creds().HasCapability(Capability::CAP_SYS_ADMIN) {
+            return Err(Error::SysError(SysErr::EPERM));
+        }
+
+        match request {
+            IoCtlCmd::RNDADDTOENTCNT => {
+                let bits: i32 = UserPtr::New(val).Read(task)?;
+                if bits < 0 {
+                    return Err(Error::SysError(SysErr::EINVAL));
+                }
+                ENTROPY_POOL.Credit(bits);
+            }
+            IoCtlCmd::RNDADDENTROPY => {
+                // struct rand_pool_info: the entropy count and the size of the
+                // buffer following them
+                let info: [i32; 2] = UserPtr::New(val).Read(task)?;
+                let (bits, size) = (info[0], info[1]);
+                if bits < 0 || size < 0 {
+                    return Err(Error::SysError(SysErr::EINVAL));
+                }
+
+                // the bytes come from the host, the buffer only has to be
+                // readable
+                let mut addr = val + 8;
+                let mut left = size as usize;
+                while left > 0 {
+                    let n = if left > MemoryDef::PAGE_SIZE as usize {
+                        MemoryDef::PAGE_SIZE as usize
+                    } else {
+                        left
+                    };
+                    UserSlice::<u8>::New(addr, n).Read(task)?;
+                    addr += n as u64;
+                    left -= n;
+                }
+
+                ENTROPY_POOL.Credit(bits);
+            }
+            IoCtlCmd::RNDZAPENTCNT | IoCtlCmd::RNDCLEARPOOL => ENTROPY_POOL.Clear(),
+            _ => ENTROPY_POOL.Refill(),
+        }
+
+        return Ok(());
     }
 
     fn IterateDir(
@@ -157,6 +157,7 @@ impl InodeOperations for TTYDevice {
         _newParent: &Inode,
         _newname: &str,
         _replacement: bool,
+        _flags: u32,
     ) -> Result<()> {
         return Err(Error::SysError(SysErr::EINVAL));
     }
@@ -25,6 +25,7 @@ use core::ops::Deref;
 use spin::*;
 
 use super::super::super::common::*;
+use super::super::super::config::HostFileCache;
 use super::super::super::linux_def::*;
 use super::super::super::singleton::*;
 use super::super::socket::unix::transport::unix::*;
@@ -33,6 +34,7 @@ use super::super::uid::*;
 use super::dentry::*;
 use super::file::*;
 use super::flags::*;
+use super::host::dirnotify::*;
 use super::inode::*;
 use super::mount::*;
 
@@ -41,6 +43,25 @@ pub unsafe fn InitSingleton() {
     RENAME.Init(RwLock::new(()));
 }
 
+// HostWatch is whether the host directories backing a dirent are watched for
+// changes. Only the watched directories cache negative dentries, unless the
+// host mount is exclusive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HostWatch {
+    Unknown,
+    Watched,
+    Unwatchable,
+}
+
+// NegativeDirent is an entry of the negative dentry cache of a mount source,
+// it records that name doesn't exist in parent.
+#[derive(Clone)]
+pub struct NegativeDirent {
+    pub parent: Weak<(QMutex<InterDirent>, u64)>,
+    pub name: String,
+    pub key: u64,
+}
+
 #[derive(Clone)]
 pub struct Dirent(pub Arc<(QMutex<InterDirent>, u64)>);
 
@@ -91,6 +112,8 @@ impl Dirent {
                 Children: BTreeMap::new(),
                 frozen: false,
                 mounted: false,
+                negative: BTreeMap::new(),
+                hostWatch: HostWatch::Unknown,
             }),
             NewUID(),
         )));
@@ -104,6 +127,8 @@ impl Dirent {
             Children: BTreeMap::new(),
             frozen: false,
             mounted: false,
+            negative: BTreeMap::new(),
+            hostWatch: HostWatch::Unknown,
         });
         return Self(Arc::new((iDirent, NewUID())));
     }
@@ -300,6 +325,132 @@ impl Dirent {
         return None;
     }
 
+    // IsNegativeChild returns whether name is cached as not existing in the
+    // directory.
+    fn IsNegativeChild(&self, name: &str) -> bool {
+        let key = match (self.0).0.lock().negative.get(name) {
+            None => return false,
+            Some(key) => *key,
+        };
+
+        let msrc = self.Inode().lock().MountSource.clone();
+        let cached = msrc.lock().TouchNegative(key);
+        if !cached {
+            (self.0).0.lock().RemoveNegative(name, key);
+        }
+
+        return cached;
+    }
+
+    // AddNegativeChild caches that the lookup of name, started at the notifier
+    // sequence seq, found nothing. The directory is only watched by the first
+    // negative lookup, as the host could change it before the watch is added.
+    // The exclusive host mounts cache it without watch.
+    fn AddNegativeChild(&self, task: &Task, name: &str, seq: u64) {
+        match HostCacheMode(&self.Inode()) {
+            Some(HostFileCache::Exclusive) => (),
+            Some(HostFileCache::Revalidate) => return,
+            _ => {
+                let watch = (self.0).0.lock().hostWatch;
+                match watch {
+                    HostWatch::Unwatchable => return,
+                    HostWatch::Unknown => {
+                        HOST_DIR_NOTIFIER.Watch(task, self);
+                        return;
+                    }
+                    HostWatch::Watched => (),
+                }
+            }
+        }
+
+        let key = NewUID();
+        let negative = NegativeDirent {
+            parent: Arc::downgrade(&self.0),
+            name: name.to_string(),
+            key: key,
+        };
+
+        let old = (self.0).0.lock().negative.insert(name.to_string(), key);
+        let msrc = self.Inode().lock().MountSource.clone();
+        if let Some(old) = old {
+            msrc.lock().DropNegative(old);
+        }
+
+        // the evicted entry may belong to any directory of the mount source,
+        // take its lock only after the mount source lock is released
+        let evicted = msrc.lock().AddNegative(key, negative);
+        if let Some(evicted) = evicted {
+            if let Some(parent) = evicted.parent.upgrade() {
+                parent.0.lock().RemoveNegative(&evicted.name, evicted.key);
+            }
+        }
+
+        // the events read since the lookup might be about name
+        if HOST_DIR_NOTIFIER.Seq() != seq {
+            self.DropNegativeChild(name);
+        }
+    }
+
+    pub fn DropNegativeChild(&self, name: &str) {
+        let key = match (self.0).0.lock().negative.remove(name) {
+            None => return,
+            Some(key) => key,
+        };
+
+        let msrc = self.Inode().lock().MountSource.clone();
+        msrc.lock().DropNegative(key);
+    }
+
+    pub fn DropNegativeChildren(&self) {
+        let negative = core::mem::take(&mut (self.0).0.lock().negative);
+        if negative.len() == 0 {
+            return;
+        }
+
+        let msrc = self.Inode().lock().MountSource.clone();
+        let mut msrc = msrc.lock();
+        for (_, key) in negative {
+            msrc.DropNegative(key);
+        }
+    }
+
+    // DropHostChild forgets the cached child name after it is changed on the
+    // host, the next walk looks it up again.
+    pub fn DropHostChild(&self, name: &str) {
+        let child = match self.getChild(name) {
+            None => return,
+            Some(child) => child,
+        };
+
+        if (child.0).0.lock().mounted {
+            return;
+        }
+
+        self.RemoveChild(&name.to_string());
+        child.DropExtendedReference();
+    }
+
+    // hostChildStale returns whether the cached child name has been removed or
+    // replaced on the host. The watched directories of the shared mounts are
+    // trusted, as the host changes of them drop the child.
+    fn hostChildStale(&self, task: &Task, name: &str, child: &Dirent) -> bool {
+        let inode = self.Inode();
+        match HostCacheMode(&inode) {
+            None | Some(HostFileCache::Exclusive) => return false,
+            Some(HostFileCache::Shared) => {
+                if (self.0).0.lock().hostWatch == HostWatch::Watched {
+                    return false;
+                }
+
+                // the changes before the watch is added are not reported
+                HOST_DIR_NOTIFIER.Watch(task, self);
+            }
+            Some(HostFileCache::Revalidate) => (),
+        }
+
+        return HostChildChanged(&inode, name, &child.Inode());
+    }
+
     fn walk(&self, task: &Task, root: &Dirent, name: &str) -> Result<Dirent> {
         let inode = self.Inode();
         if !inode.StableAttr().IsDir() {
@@ -320,27 +471,19 @@ impl Dirent {
         }
 
         let child = self.GetCacheChild(name);
-        let remove = match child {
+        match child {
             Some(cd) => {
                 let mounted = cd.0.lock().mounted;
-                /*let subInode = cd.0.lock().Inode.clone();
-                let mountSource = subInode.lock().MountSource.clone();
-                let mountsourceOpations = mountSource.lock().MountSourceOperations.clone();*/
-
-                // very likely there is a deadlock in the Revalidate(..). As there is no revalidate=true case
-                // work around now. and todo: fix it
-                let revalidate = false; //mountsourceOpations.lock().Revalidate(name, &inode, &subInode);
-                if mounted || !revalidate {
-                    return Ok(Dirent(cd.clone()));
+                let cd = Dirent(cd);
+                if mounted || !self.hostChildStale(task, name, &cd) {
+                    return Ok(cd);
                 }
 
-                false
+                self.DropHostChild(name);
+            }
+            None => {
+                (self.0).0.lock().Children.remove(name);
             }
-            None => true,
-        };
-
-        if remove {
-            (self.0).0.lock().Children.remove(name);
         }
 
         let frozen = (self.0).0.lock().frozen;
@@ -348,7 +491,25 @@ impl Dirent {
             return Err(Error::SysError(SysErr::ENOENT));
         }
 
-        let c = inode.Lookup(task, name)?;
+        if self.IsNegativeChild(name) {
+            return Err(Error::SysError(SysErr::ENOENT));
+        }
+
+        // the shared host directories are watched before the lookup, so that
+        // the host changes after it are not missed
+        if HostCacheMode(&inode) == Some(HostFileCache::Shared) {
+            HOST_DIR_NOTIFIER.Watch(task, self);
+        }
+
+        let seq = HOST_DIR_NOTIFIER.Seq();
+        let c = match inode.Lookup(task, name) {
+            Err(Error::SysError(SysErr::ENOENT)) => {
+                self.AddNegativeChild(task, name, seq);
+                return Err(Error::SysError(SysErr::ENOENT));
+            }
+            Err(e) => return Err(e),
+            Ok(c) => c,
+        };
 
         assert!(&(c.0).0.lock().Name == name, "lookup get mismatch name");
 
@@ -360,6 +521,7 @@ impl Dirent {
     pub fn Walk(&self, task: &Task, root: &Dirent, name: &str) -> Result<Dirent> {
         //error!("Walk 1 {}", name);
         //defer!(error!("Walk 2 {}", name));
+        HOST_DIR_NOTIFIER.Poll();
         let _a = RENAME.read();
 
         return self.walk(task, root, name);
@@ -429,6 +591,7 @@ impl Dirent {
             return Err(Error::SysError(SysErr::ENOENT));
         }
 
+        self.DropNegativeChild(name);
         let file = inode.Create(task, self, name, flags, perms)?;
 
         let child = file.Dirent.clone();
@@ -469,6 +632,7 @@ impl Dirent {
             (self.0).0.lock().Children.remove(name);
         }
 
+        self.DropNegativeChild(name);
         return create();
     }
 
@@ -643,6 +807,30 @@ impl Dirent {
         return d.mounted || d.IsRoot();
     }
 
+    // NewDetachedMount makes the root of a mount made by fsmount(2) or
+    // open_tree(2), which isn't in the mount tree until move_mount(2).
+    pub fn NewDetachedMount(inode: &Inode) -> Self {
+        let d = Self::New(inode, "/");
+        (d.0).0.lock().mounted = true;
+        return d;
+    }
+
+    // TakeDetachedMount clears the detached mount for attaching it, a
+    // detached mount is attached only once.
+    pub fn TakeDetachedMount(&self) -> bool {
+        let mut d = (self.0).0.lock();
+        if !(d.mounted && d.IsRoot()) {
+            return false;
+        }
+
+        d.mounted = false;
+        return true;
+    }
+
+    pub fn SetDetachedMount(&self) {
+        (self.0).0.lock().mounted = true;
+    }
+
     pub fn Mount(&self, inode: &Inode) -> Result<Dirent> {
         if inode.lock().StableAttr().IsSymlink() {
             return Err(Error::SysError(SysErr::ENOENT));
@@ -754,15 +942,21 @@ impl Dirent {
         oldName: &str,
         newParent: &Dirent,
         newName: &str,
+        flags: u32,
     ) -> Result<()> {
         let _a = RENAME.write();
 
         if Arc::ptr_eq(oldParent, newParent) {
             if oldName == newName {
+                if flags & RenameFlags::RENAME_NOREPLACE != 0 {
+                    oldParent.walk(task, root, oldName)?;
+                    return Err(Error::SysError(SysErr::EEXIST));
+                }
+
                 return Ok(());
             }
 
-            return Self::renameOfOneDirent(task, root, oldParent, oldName, newName);
+            return Self::renameOfOneDirent(task, root, oldParent, oldName, newName, flags);
         }
 
         let mut child = newParent.clone();
@@ -821,51 +1015,56 @@ impl Dirent {
             return Err(Error::SysError(SysErr::EINVAL));
         }
 
-        let renamedInode = renamed.Inode();
-        if renamedInode.StableAttr().IsDir() {
-            renamedInode.CheckPermission(
-                task,
-                &PermMask {
-                    write: true,
-                    execute: false,
-                    read: false,
-                },
-            )?;
-        }
+        Self::checkRenamedDir(task, &renamed)?;
 
         let exist;
+        let mut exchanged = None;
         match newParent.walk(task, root, newName) {
             Ok(replaced) => {
+                if flags & RenameFlags::RENAME_NOREPLACE != 0 {
+                    return Err(Error::SysError(SysErr::EEXIST));
+                }
+
                 newParent.mayDelete(task, &replaced)?;
                 if replaced.IsMountPoint() {
                     return Err(Error::SysError(SysErr::EBUSY));
                 }
 
-                let oldIsDir = renamedInode.StableAttr().IsDir();
-                let replacedInode = replaced.Inode();
-                let newIsDir = replacedInode.StableAttr().IsDir();
-
-                if !newIsDir && oldIsDir {
-                    return Err(Error::SysError(SysErr::ENOTDIR));
-                }
+                if flags & RenameFlags::RENAME_EXCHANGE != 0 {
+                    // The exchanged entry moves too, so it must not become its own ancestor.
+                    if oldParent.DescendantOf(&replaced) {
+                        return Err(Error::SysError(SysErr::EINVAL));
+                    }
 
-                if newIsDir && !oldIsDir {
-                    return Err(Error::SysError(SysErr::EISDIR));
+                    Self::checkRenamedDir(task, &replaced)?;
+                    exchanged = Some(replaced);
+                } else {
+                    Self::checkReplaced(&renamed, &replaced)?;
+                    replaced.DropExtendedReference();
+                    replaced.flush();
                 }
 
-                replaced.DropExtendedReference();
-                replaced.flush();
-
                 exist = true;
             }
             Err(Error::SysError(SysErr::ENOENT)) => {
+                if flags & RenameFlags::RENAME_EXCHANGE != 0 {
+                    return Err(Error::SysError(SysErr::ENOENT));
+                }
+
                 exist = false;
             }
             Err(e) => return Err(e),
         }
 
+        newParent.DropNegativeChild(newName);
         let mut newInode = renamed.Inode();
-        newInode.Rename(task, oldParent, &renamed, newParent, newName, exist)?;
+        newInode.Rename(task, oldParent, &renamed, newParent, newName, exist, flags)?;
+
+        if let Some(replaced) = exchanged {
+            Self::exchangeChildren(oldParent, oldName, &renamed, newParent, newName, &replaced);
+            return Ok(());
+        }
+
         (renamed.0).0.lock().Name = newName.to_string();
 
         (newParent.0).0.lock().Children.remove(newName);
@@ -883,12 +1082,81 @@ impl Dirent {
         return Ok(());
     }
 
+    // Renaming a directory rewrites its ".." entry, which needs write permission on it.
+    fn checkRenamedDir(task: &Task, renamed: &Dirent) -> Result<()> {
+        let inode = renamed.Inode();
+        if inode.StableAttr().IsDir() {
+            inode.CheckPermission(
+                task,
+                &PermMask {
+                    write: true,
+                    execute: false,
+                    read: false,
+                },
+            )?;
+        }
+
+        return Ok(());
+    }
+
+    fn checkReplaced(renamed: &Dirent, replaced: &Dirent) -> Result<()> {
+        let oldIsDir = renamed.Inode().StableAttr().IsDir();
+        let newIsDir = replaced.Inode().StableAttr().IsDir();
+
+        if !newIsDir && oldIsDir {
+            return Err(Error::SysError(SysErr::ENOTDIR));
+        }
+
+        if newIsDir && !oldIsDir {
+            return Err(Error::SysError(SysErr::EISDIR));
+        }
+
+        return Ok(());
+    }
+
+    // exchangeChildren swaps the cached dirents after a RENAME_EXCHANGE.
+    fn exchangeChildren(
+        oldParent: &Dirent,
+        oldName: &str,
+        renamed: &Dirent,
+        newParent: &Dirent,
+        newName: &str,
+        replaced: &Dirent,
+    ) {
+        {
+            let mut r = (renamed.0).0.lock();
+            r.Name = newName.to_string();
+            r.Parent = Some(newParent.clone());
+        }
+
+        {
+            let mut r = (replaced.0).0.lock();
+            r.Name = oldName.to_string();
+            r.Parent = Some(oldParent.clone());
+        }
+
+        (oldParent.0)
+            .0
+            .lock()
+            .Children
+            .insert(oldName.to_string(), Arc::downgrade(&replaced.0));
+        (newParent.0)
+            .0
+            .lock()
+            .Children
+            .insert(newName.to_string(), Arc::downgrade(&renamed.0));
+
+        renamed.flush();
+        replaced.flush();
+    }
+
     fn renameOfOneDirent(
         task: &Task,
         root: &Dirent,
         parent: &Dirent,
         oldName: &str,
         newName: &str,
+        flags: u32,
     ) -> Result<()> {
         let inode = parent.Inode();
 
@@ -913,51 +1181,50 @@ impl Dirent {
             return Err(Error::SysError(SysErr::EBUSY));
         }
 
-        let renamedInode = renamed.Inode();
-        if renamedInode.StableAttr().IsDir() {
-            renamedInode.CheckPermission(
-                task,
-                &PermMask {
-                    write: true,
-                    execute: false,
-                    read: false,
-                },
-            )?;
-        }
+        Self::checkRenamedDir(task, &renamed)?;
 
         let exist;
+        let mut exchanged = None;
         match parent.walk(task, root, newName) {
             Ok(replaced) => {
+                if flags & RenameFlags::RENAME_NOREPLACE != 0 {
+                    return Err(Error::SysError(SysErr::EEXIST));
+                }
+
                 parent.mayDelete(task, &replaced)?;
                 if replaced.IsMountPoint() {
                     return Err(Error::SysError(SysErr::EBUSY));
                 }
 
-                let oldIsDir = renamedInode.StableAttr().IsDir();
-                let replacedInode = replaced.Inode();
-                let newIsDir = replacedInode.StableAttr().IsDir();
-
-                if !newIsDir && oldIsDir {
-                    return Err(Error::SysError(SysErr::ENOTDIR));
-                }
-
-                if newIsDir && !oldIsDir {
-                    return Err(Error::SysError(SysErr::EISDIR));
+                if flags & RenameFlags::RENAME_EXCHANGE != 0 {
+                    Self::checkRenamedDir(task, &replaced)?;
+                    exchanged = Some(replaced);
+                } else {
+                    Self::checkReplaced(&renamed, &replaced)?;
+                    replaced.DropExtendedReference();
+                    replaced.flush();
                 }
 
-                replaced.DropExtendedReference();
-                replaced.flush();
-
                 exist = true;
             }
             Err(Error::SysError(SysErr::ENOENT)) => {
+                if flags & RenameFlags::RENAME_EXCHANGE != 0 {
+                    return Err(Error::SysError(SysErr::ENOENT));
+                }
+
                 exist = false;
             }
             Err(e) => return Err(e),
         }
 
+        parent.DropNegativeChild(newName);
         let mut newInode = renamed.Inode();
-        newInode.Rename(task, parent, &renamed, parent, newName, exist)?;
+        newInode.Rename(task, parent, &renamed, parent, newName, exist, flags)?;
+
+        if let Some(replaced) = exchanged {
+            Self::exchangeChildren(parent, oldName, &renamed, parent, newName, &replaced);
+            return Ok(());
+        }
 
         (renamed.0).0.lock().Name = newName.to_string();
 
@@ -1133,6 +1400,11 @@ pub struct InterDirent {
 
     pub frozen: bool,
     pub mounted: bool,
+
+    // negative keeps the names known not to exist, with their keys in the
+    // negative dentry cache of the mount source
+    pub negative: BTreeMap<String, u64>,
+    pub hostWatch: HostWatch,
 }
 
 impl Default for InterDirent {
@@ -1144,6 +1416,8 @@ impl Default for InterDirent {
             Children: BTreeMap::new(),
             frozen: false,
             mounted: false,
+            negative: BTreeMap::new(),
+            hostWatch: HostWatch::Unknown,
         };
     }
 }
@@ -1157,6 +1431,8 @@ impl InterDirent {
             Children: BTreeMap::new(),
             frozen: false,
             mounted: false,
+            negative: BTreeMap::new(),
+            hostWatch: HostWatch::Unknown,
         };
     }
 
@@ -1168,6 +1444,8 @@ impl InterDirent {
             Children: BTreeMap::new(),
             frozen: false,
             mounted: false,
+            negative: BTreeMap::new(),
+            hostWatch: HostWatch::Unknown,
         };
     }
 
@@ -1177,4 +1455,12 @@ impl InterDirent {
             _ => return false,
         }
     }
+
+    // RemoveNegative drops the negative entry of name if it is still the one
+    // of key.
+    pub fn RemoveNegative(&mut self, name: &str, key: u64) {
+        if self.negative.get(name) == Some(&key) {
+            self.negative.remove(name);
+        }
+    }
 }
@@ -0,0 +1,291 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::qlib::mutex::*;
+use alloc::collections::btree_map::BTreeMap;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use super::super::super::super::auth::id::*;
+use super::super::super::super::auth::*;
+use super::super::super::super::common::*;
+use super::super::super::super::device::*;
+use super::super::super::super::linux_def::*;
+use super::super::super::fd::*;
+use super::super::super::kernel::time::*;
+use super::super::super::task::*;
+use super::super::attr::*;
+use super::super::dev::block::*;
+use super::super::dirent::*;
+use super::super::file::*;
+use super::super::filesystems::*;
+use super::super::flags::*;
+use super::super::fsutil::inode::simple_file_inode::*;
+use super::super::host::fs::*;
+use super::super::host::hostinodeop::*;
+use super::super::inode::*;
+use super::super::mount::*;
+use super::super::ramfs::dir::*;
+use super::super::ramfs::symlink::*;
+use super::image::*;
+
+// the size of the chunks copied from the image into a file's memfd
+const COPY_CHUNK_SIZE: usize = 64 * 1024;
+
+// the deepest directory nesting of an image
+const MAX_DEPTH: usize = 128;
+
+pub struct ErofsFileSystem {}
+
+impl Filesystem for ErofsFileSystem {
+    fn Name(&self) -> String {
+        return "erofs".to_string();
+    }
+
+    fn Flags(&self) -> FilesystemFlags {
+        return FILESYSTEM_REQUIRES_DEV;
+    }
+
+    fn Mount(
+        &mut self,
+        task: &Task,
+        device: &str,
+        flags: &MountSourceFlags,
+        data: &str,
+    ) -> Result<Inode> {
+        info!("erofs file system mount {}...", device);
+
+        let options = WhitelistFileSystem::GenericMountSourceOptions(data);
+        if options.len() > 0 {
+            info!("unsupported erofs mount options: {:?}", options);
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        if device.len() == 0 {
+            return Err(Error::SysError(SysErr::ENOTBLK));
+        }
+
+        let root = task.Root();
+        let mut remainingTraversals = 40;
+        let d = task.mountNS.FindDirent(
+            task,
+            &root,
+            Some(task.Workdir()),
+            device,
+            &mut remainingTraversals,
+            true,
+        )?;
+
+        let dev = match BlockDeviceOf(&d.Inode()) {
+            None => return Err(Error::SysError(SysErr::ENOTBLK)),
+            Some(dev) => dev,
+        };
+
+        let image = Arc::new(ErofsImage::New(task, dev)?);
+
+        // the image is never written
+        let mut flags = *flags;
+        flags.ReadOnly = true;
+        let msrc = Arc::new(QMutex::new(MountSource::NewCachingMountSource(
+            self, &flags,
+        )));
+
+        let builder = ErofsTreeBuilder {
+            image: image.clone(),
+            msrc: msrc,
+            device: NewAnonDevice(),
+        };
+
+        let rootInode = image.ReadInode(task, image.rootNid)?;
+        return builder.NewInode(task, &rootInode, 0);
+    }
+
+    fn AllowUserMount(&self) -> bool {
+        return true;
+    }
+
+    fn AllowUserList(&self) -> bool {
+        return true;
+    }
+}
+
+// ErofsTreeBuilder builds the inode tree of an image at mount time. The
+// metadata lives in memory, the file content is read on open.
+pub struct ErofsTreeBuilder {
+    pub image: Arc<ErofsImage>,
+    pub msrc: Arc<QMutex<MountSource>>,
+    pub device: Arc<QMutex<Device>>,
+}
+
+impl ErofsTreeBuilder {
+    fn UnstableAttr(&self, inode: &ErofsInode) -> UnstableAttr {
+        let time = Time::FromUnix(inode.mtime as i64, inode.mtimeNsec as i64);
+        return UnstableAttr {
+            Size: inode.size as i64,
+            Usage: inode.size as i64,
+            Perms: FilePermissions::FromMode(FileMode(inode.Perms())),
+            Owner: FileOwner {
+                UID: KUID(inode.uid),
+                GID: KGID(inode.gid),
+            },
+            AccessTime: time,
+            ModificationTime: time,
+            StatusChangeTime: time,
+            BirthTime: Time::default(),
+            Links: inode.nlink as u64,
+        };
+    }
+
+    fn StableAttr(&self, typ: InodeType) -> StableAttr {
+        let mut device = self.device.lock();
+        return StableAttr {
+            Type: typ,
+            DeviceId: device.DeviceID(),
+            InodeId: device.NextIno(),
+            BlockSize: self.image.BlockSize() as i64,
+            DeviceFileMajor: 0,
+            DeviceFileMinor: 0,
+        };
+    }
+
+    pub fn NewInode(&self, task: &Task, inode: &ErofsInode, depth: usize) -> Result<Inode> {
+        let attr = self.UnstableAttr(inode);
+
+        match inode.FileType() {
+            ModeType::S_IFDIR => {
+                if depth > MAX_DEPTH {
+                    return Err(Error::SysError(SysErr::ELOOP));
+                }
+
+                let mut contents = BTreeMap::new();
+                for (name, nid, _) in self.image.ReadDir(task, inode)? {
+                    let child = self.image.ReadInode(task, nid)?;
+                    match child.FileType() {
+                        ModeType::S_IFDIR | ModeType::S_IFREG | ModeType::S_IFLNK => (),
+                        typ => {
+                            info!("erofs: skip {} with file type {:o}", name, typ);
+                            continue;
+                        }
+                    }
+
+                    contents.insert(name, self.NewInode(task, &child, depth + 1)?);
+                }
+
+                let iops = Dir::New(task, contents, &attr.Owner, &attr.Perms);
+                let sattr = self.StableAttr(InodeType::Directory);
+                return Ok(Inode::New(&Arc::new(iops), &self.msrc, &sattr));
+            }
+            ModeType::S_IFLNK => {
+                let target = self.image.ReadAll(task, inode)?;
+                let target = match String::from_utf8(target) {
+                    Ok(t) => t,
+                    Err(_) => return Err(Error::SysError(SysErr::EIO)),
+                };
+
+                let iops = Symlink::New(task, &attr.Owner, &target);
+                let sattr = self.StableAttr(InodeType::Symlink);
+                return Ok(Inode::New(&Arc::new(iops), &self.msrc, &sattr));
+            }
+            ModeType::S_IFREG => {
+                let node = ErofsFileNode {
+                    image: self.image.clone(),
+                    inode: *inode,
+                    memfd: QMutex::new(None),
+                };
+
+                let iops = SimpleFileInode::NewWithUnstable(
+                    &attr,
+                    FSMagic::EROFS_SUPER_MAGIC_V1,
+                    false,
+                    node,
+                );
+                let sattr = self.StableAttr(InodeType::RegularFile);
+                return Ok(Inode::New(&Arc::new(iops), &self.msrc, &sattr));
+            }
+            _ => return Err(Error::SysError(SysErr::EOPNOTSUPP)),
+        }
+    }
+}
+
+// ErofsFileNode is a regular file of an image. Its content is copied into
+// a memfd on the first open so the file can be mmapped and executed.
+pub struct ErofsFileNode {
+    pub image: Arc<ErofsImage>,
+    pub inode: ErofsInode,
+    pub memfd: QMutex<Option<HostInodeOp>>,
+}
+
+impl ErofsFileNode {
+    fn Materialize(&self, task: &Task) -> Result<HostInodeOp> {
+        let mut memfd = self.memfd.lock();
+        if let Some(iops) = memfd.as_ref() {
+            return Ok(iops.clone());
+        }
+
+        let size = self.inode.size;
+        let iops = HostInodeOp::NewMemfdIops(size as i64)?;
+
+        let mut buf: Vec<u8> = Vec::with_capacity(COPY_CHUNK_SIZE);
+        buf.resize(COPY_CHUNK_SIZE, 0);
+        let mut offset = 0;
+        while offset < size {
+            let n = self.image.ReadData(task, &self.inode, &mut buf, offset)?;
+            if n == 0 {
+                return Err(Error::SysError(SysErr::EIO));
+            }
+
+            let mut written = 0;
+            while written < n {
+                let iovs = [IoVec {
+                    start: &buf[written] as *const _ as u64,
+                    len: n - written,
+                }];
+                let cnt = IOWriteAt(iops.HostFd(), &iovs, offset + written as u64)?;
+                if cnt <= 0 {
+                    return Err(Error::SysError(SysErr::EIO));
+                }
+                written += cnt as usize;
+            }
+
+            offset += n as u64;
+        }
+
+        *memfd = Some(iops.clone());
+        return Ok(iops);
+    }
+}
+
+impl SimpleFileTrait for ErofsFileNode {
+    fn GetFile(
+        &self,
+        task: &Task,
+        _dir: &Inode,
+        dirent: &Dirent,
+        flags: FileFlags,
+    ) -> Result<File> {
+        if flags.Write {
+            return Err(Error::SysError(SysErr::EROFS));
+        }
+
+        let iops = self.Materialize(task)?;
+        return Ok(File::NewHostFile(
+            dirent,
+            &flags,
+            iops.GetHostFileOp(task),
+            false,
+        ));
+    }
+}
@@ -0,0 +1,323 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use super::super::super::super::common::*;
+use super::super::super::super::linux_def::*;
+use super::super::super::task::*;
+use super::super::dev::block::*;
+
+pub const EROFS_SUPER_OFFSET: u64 = 1024;
+pub const EROFS_SUPER_SIZE: usize = 128;
+pub const EROFS_ISLOTBITS: u64 = 5;
+pub const EROFS_NULL_ADDR: u32 = 0xffffffff;
+
+pub const EROFS_INODE_LAYOUT_COMPACT: u16 = 0;
+pub const EROFS_INODE_LAYOUT_EXTENDED: u16 = 1;
+
+pub const EROFS_INODE_FLAT_PLAIN: u16 = 0;
+pub const EROFS_INODE_FLAT_COMPRESSION_LEGACY: u16 = 1;
+pub const EROFS_INODE_FLAT_INLINE: u16 = 2;
+pub const EROFS_INODE_FLAT_COMPRESSION: u16 = 3;
+pub const EROFS_INODE_CHUNK_BASED: u16 = 4;
+
+pub const EROFS_INODE_COMPACT_SIZE: u64 = 32;
+pub const EROFS_INODE_EXTENDED_SIZE: u64 = 64;
+
+pub const EROFS_DIRENT_SIZE: usize = 12;
+
+pub const EROFS_FT_UNKNOWN: u8 = 0;
+pub const EROFS_FT_REG_FILE: u8 = 1;
+pub const EROFS_FT_DIR: u8 = 2;
+pub const EROFS_FT_CHRDEV: u8 = 3;
+pub const EROFS_FT_BLKDEV: u8 = 4;
+pub const EROFS_FT_FIFO: u8 = 5;
+pub const EROFS_FT_SOCK: u8 = 6;
+pub const EROFS_FT_SYMLINK: u8 = 7;
+
+// the incompat features this reader understands: lz4 0cfg, which is
+// only used by compressed inodes, and the device table
+pub const EROFS_FEATURE_INCOMPAT_SUPPORTED: u32 = 0x1 | 0x8;
+
+fn GetU16(buf: &[u8], off: usize) -> u16 {
+    return u16::from_le_bytes([buf[off], buf[off + 1]]);
+}
+
+fn GetU32(buf: &[u8], off: usize) -> u32 {
+    let mut b = [0; 4];
+    b.copy_from_slice(&buf[off..off + 4]);
+    return u32::from_le_bytes(b);
+}
+
+fn GetU64(buf: &[u8], off: usize) -> u64 {
+    let mut b = [0; 8];
+    b.copy_from_slice(&buf[off..off + 8]);
+    return u64::from_le_bytes(b);
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ErofsInode {
+    pub nid: u64,
+    pub ino: u32,
+    pub mode: u16,
+    pub nlink: u32,
+    pub size: u64,
+    pub uid: u32,
+    pub gid: u32,
+    pub mtime: u64,
+    pub mtimeNsec: u32,
+    pub dataLayout: u16,
+    pub rawBlkAddr: u32,
+    // the image offset of the inline tail, for FLAT_INLINE
+    pub inlineOffset: u64,
+}
+
+impl ErofsInode {
+    pub fn FileType(&self) -> u16 {
+        return self.mode & ModeType::S_IFMT;
+    }
+
+    pub fn Perms(&self) -> u16 {
+        return self.mode & !ModeType::S_IFMT;
+    }
+}
+
+// ErofsImage reads the metadata and the data of an erofs image from a
+// block device.
+pub struct ErofsImage {
+    pub dev: Arc<BlockDevice>,
+    pub blkszBits: u8,
+    pub rootNid: u64,
+    pub metaBlkAddr: u32,
+    pub buildTime: u64,
+    pub buildTimeNsec: u32,
+}
+
+impl ErofsImage {
+    pub fn New(task: &Task, dev: Arc<BlockDevice>) -> Result<Self> {
+        let mut sb = [0u8; EROFS_SUPER_SIZE];
+        ReadFull(task, &dev, &mut sb, EROFS_SUPER_OFFSET)?;
+
+        if GetU32(&sb, 0) as u64 != FSMagic::EROFS_SUPER_MAGIC_V1 {
+            info!("erofs: bad superblock magic {:x}", GetU32(&sb, 0));
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        let blkszBits = sb[12];
+        if blkszBits < 9 || blkszBits > 16 {
+            info!("erofs: unsupported block size bits {}", blkszBits);
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        let incompat = GetU32(&sb, 80);
+        if incompat & !EROFS_FEATURE_INCOMPAT_SUPPORTED != 0 {
+            info!("erofs: unsupported incompat features {:x}", incompat);
+            return Err(Error::SysError(SysErr::EOPNOTSUPP));
+        }
+
+        return Ok(Self {
+            dev: dev,
+            blkszBits: blkszBits,
+            rootNid: GetU16(&sb, 14) as u64,
+            metaBlkAddr: GetU32(&sb, 40),
+            buildTime: GetU64(&sb, 24),
+            buildTimeNsec: GetU32(&sb, 32),
+        });
+    }
+
+    pub fn BlockSize(&self) -> u64 {
+        return 1 << self.blkszBits;
+    }
+
+    pub fn BlockAddr(&self, blkaddr: u32) -> u64 {
+        return (blkaddr as u64) << self.blkszBits;
+    }
+
+    pub fn ReadAt(&self, task: &Task, buf: &mut [u8], offset: u64) -> Result<()> {
+        return ReadFull(task, &self.dev, buf, offset);
+    }
+
+    pub fn ReadInode(&self, task: &Task, nid: u64) -> Result<ErofsInode> {
+        let iloc = self.BlockAddr(self.metaBlkAddr) + (nid << EROFS_ISLOTBITS);
+        let mut buf = [0u8; EROFS_INODE_EXTENDED_SIZE as usize];
+        self.ReadAt(task, &mut buf[..EROFS_INODE_COMPACT_SIZE as usize], iloc)?;
+
+        let format = GetU16(&buf, 0);
+        let xattrCount = GetU16(&buf, 2);
+        let mut inode = ErofsInode {
+            nid: nid,
+            mode: GetU16(&buf, 4),
+            dataLayout: (format >> 1) & 0x7,
+            rawBlkAddr: GetU32(&buf, 16),
+            ino: GetU32(&buf, 20),
+            ..Default::default()
+        };
+
+        let isize = if format & 0x1 == EROFS_INODE_LAYOUT_COMPACT {
+            inode.nlink = GetU16(&buf, 6) as u32;
+            inode.size = GetU32(&buf, 8) as u64;
+            inode.uid = GetU16(&buf, 24) as u32;
+            inode.gid = GetU16(&buf, 26) as u32;
+            // compact inodes take the build time of the image
+            inode.mtime = self.buildTime;
+            inode.mtimeNsec = self.buildTimeNsec;
+            EROFS_INODE_COMPACT_SIZE
+        } else {
+            self.ReadAt(
+                task,
+                &mut buf[EROFS_INODE_COMPACT_SIZE as usize..],
+                iloc + EROFS_INODE_COMPACT_SIZE,
+            )?;
+            inode.size = GetU64(&buf, 8);
+            inode.uid = GetU32(&buf, 24);
+            inode.gid = GetU32(&buf, 28);
+            inode.mtime = GetU64(&buf, 32);
+            inode.mtimeNsec = GetU32(&buf, 40);
+            inode.nlink = GetU32(&buf, 44);
+            EROFS_INODE_EXTENDED_SIZE
+        };
+
+        let xattrSize = if xattrCount == 0 {
+            0
+        } else {
+            12 + (xattrCount as u64 - 1) * 4
+        };
+
+        inode.inlineOffset = iloc + isize + xattrSize;
+
+        match inode.dataLayout {
+            EROFS_INODE_FLAT_PLAIN | EROFS_INODE_FLAT_INLINE => (),
+            layout => {
+                info!("erofs: nid {} has unsupported data layout {}", nid, layout);
+                return Err(Error::SysError(SysErr::EOPNOTSUPP));
+            }
+        }
+
+        return Ok(inode);
+    }
+
+    // ReadData reads the content of the inode at offset into buf and returns
+    // the bytes read
+    pub fn ReadData(
+        &self,
+        task: &Task,
+        inode: &ErofsInode,
+        buf: &mut [u8],
+        offset: u64,
+    ) -> Result<usize> {
+        if offset >= inode.size {
+            return Ok(0);
+        }
+
+        let blksz = self.BlockSize();
+        let end = core::cmp::min(inode.size, offset + buf.len() as u64);
+
+        // with FLAT_INLINE the last block is stored right after the inode
+        let tailStart = if inode.dataLayout == EROFS_INODE_FLAT_INLINE {
+            (inode.size / blksz) * blksz
+        } else {
+            inode.size
+        };
+
+        let mut pos = offset;
+        while pos < end {
+            let done = (pos - offset) as usize;
+            if pos >= tailStart {
+                let len = (end - pos) as usize;
+                let at = inode.inlineOffset + (pos - tailStart);
+                self.ReadAt(task, &mut buf[done..done + len], at)?;
+                pos += len as u64;
+                continue;
+            }
+
+            if inode.rawBlkAddr == EROFS_NULL_ADDR {
+                return Err(Error::SysError(SysErr::EIO));
+            }
+
+            let len = (core::cmp::min(end, tailStart) - pos) as usize;
+            let at = self.BlockAddr(inode.rawBlkAddr) + pos;
+            self.ReadAt(task, &mut buf[done..done + len], at)?;
+            pos += len as u64;
+        }
+
+        return Ok((end - offset) as usize);
+    }
+
+    pub fn ReadAll(&self, task: &Task, inode: &ErofsInode) -> Result<Vec<u8>> {
+        let mut buf = Vec::with_capacity(inode.size as usize);
+        buf.resize(inode.size as usize, 0);
+        self.ReadData(task, inode, &mut buf, 0)?;
+        return Ok(buf);
+    }
+
+    // ReadDir returns the name, the nid and the file type of the entries of
+    // a directory, without "." and ".."
+    pub fn ReadDir(&self, task: &Task, inode: &ErofsInode) -> Result<Vec<(String, u64, u8)>> {
+        let blksz = self.BlockSize() as usize;
+        let data = self.ReadAll(task, inode)?;
+
+        let mut entries = Vec::new();
+        for block in data.chunks(blksz) {
+            if block.len() < EROFS_DIRENT_SIZE {
+                return Err(Error::SysError(SysErr::EIO));
+            }
+
+            let count = GetU16(block, 8) as usize / EROFS_DIRENT_SIZE;
+            if count == 0 || count * EROFS_DIRENT_SIZE > block.len() {
+                return Err(Error::SysError(SysErr::EIO));
+            }
+
+            for i in 0..count {
+                let d = &block[i * EROFS_DIRENT_SIZE..];
+                let nid = GetU64(d, 0);
+                let nameoff = GetU16(d, 8) as usize;
+                let fileType = d[10];
+
+                let nameEnd = if i + 1 < count {
+                    GetU16(block, (i + 1) * EROFS_DIRENT_SIZE + 8) as usize
+                } else {
+                    // the last name ends at the block end or at a NUL
+                    let mut end = block.len();
+                    for j in nameoff..block.len() {
+                        if block[j] == 0 {
+                            end = j;
+                            break;
+                        }
+                    }
+                    end
+                };
+
+                if nameoff >= nameEnd || nameEnd > block.len() {
+                    return Err(Error::SysError(SysErr::EIO));
+                }
+
+                let name = match core::str::from_utf8(&block[nameoff..nameEnd]) {
+                    Ok(n) => n,
+                    Err(_) => return Err(Error::SysError(SysErr::EIO)),
+                };
+
+                if name == "." || name == ".." {
+                    continue;
+                }
+
+                entries.push((String::from(name), nid, fileType));
+            }
+        }
+
+        return Ok(entries);
+    }
+}
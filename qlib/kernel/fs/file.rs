@@ -23,7 +23,10 @@ use core::ops::Deref;
 
 use super::super::super::auth::*;
 use super::super::super::common::*;
+use super::super::super::config::HostFileCache;
+use super::super::super::limits::*;
 use super::super::super::linux_def::*;
+use super::super::super::mem::block::Iovs;
 use super::super::super::metric::*;
 use super::super::super::range::*;
 use super::super::kernel::time::*;
@@ -168,6 +171,29 @@ pub trait SockOperations: Sync + Send {
         return Err(Error::SysError(SysErr::ENOTSOCK));
     }
 
+    // RecvMMsg and SendMMsg handle the whole recvmmsg/sendmmsg vector in one
+    // batch. msgs are the guest copies, their msgLen is set for each message.
+    //return: the message count, ENOSYS falls back to RecvMsg/SendMsg
+    fn RecvMMsg(
+        &self,
+        _task: &Task,
+        _msgs: &mut [MMsgHdr],
+        _flags: i32,
+        _deadline: Option<Time>,
+    ) -> Result<i64> {
+        return Err(Error::SysError(SysErr::ENOSYS));
+    }
+
+    fn SendMMsg(
+        &self,
+        _task: &Task,
+        _msgs: &mut [MMsgHdr],
+        _flags: i32,
+        _deadline: Option<Time>,
+    ) -> Result<i64> {
+        return Err(Error::SysError(SysErr::ENOSYS));
+    }
+
     fn SetRecvTimeout(&self, _nanoseconds: i64) {
         return;
     }
@@ -217,7 +243,12 @@ pub enum FileOpsType {
     MockFileOperations,
     TimerOperations,
     FullFileOperations,
+    KmsgFileOperations,
     NullFileOperations,
+    PassthroughFileOperations,
+    BlockFileOperations,
+    LoopControlFileOperations,
+    ChannelFileOperations,
     RandomFileOperations,
     TTYFileOperations,
     ZeroFileOperations,
@@ -245,6 +276,9 @@ pub enum FileOpsType {
     ReadonlyFileOperations,
     DynamicDirFileOperations,
     SignalOperation,
+    PidfdOperations,
+    FsContextOperations,
+    MQueueOperations,
 }
 
 pub trait FileOperations: Sync + Send + Waitable + SockOperations + SpliceOperations {
@@ -369,6 +403,10 @@ impl Drop for File {
                 f.1.as_ref().unwrap().Unregister(task, self)
             }
 
+            if f.1.is_some() {
+                f.1.as_ref().unwrap().UnregisterUrgent(task, self)
+            }
+
             f.1 = None;
         }
     }
@@ -526,6 +564,7 @@ impl File {
                     &WhitelistFileSystem::New(),
                     &MountSourceFlags::default(),
                     false,
+                    HostFileCache::default(),
                 );
                 let inode =
                     Inode::NewHostInode(&Arc::new(QMutex::new(msrc)), fd, &fstat, fileFlags.Write)?;
@@ -567,6 +606,7 @@ impl File {
             &WhitelistFileSystem::New(),
             &MountSourceFlags::default(),
             false,
+            HostFileCache::default(),
         );
 
         let inode =
@@ -712,9 +752,26 @@ impl File {
     // checkLimit checks the offset that the write will be performed at. The
     // returned boolean indicates that the write must be limited. The returned
     // integer indicates the new maximum write length.
-    pub fn checkLimit(&self, _offset: i64) -> (i64, bool) {
-        //todo: implement this
-        return (0, false);
+    pub fn checkLimit(&self, task: &Task, offset: i64) -> (i64, bool) {
+        // RLIMIT_FSIZE only applies to regular files.
+        if !self.Dirent.Inode().StableAttr().IsRegular() {
+            return (0, false);
+        }
+
+        let fileSize = match &task.thread {
+            None => return (0, false),
+            Some(t) => t.ThreadGroup().Limits().Get(LimitType::FileSize).Cur,
+        };
+
+        if fileSize > core::i64::MAX as u64 {
+            return (0, false);
+        }
+
+        if offset >= fileSize as i64 {
+            return (0, true);
+        }
+
+        return (fileSize as i64 - offset, true);
     }
 
     pub fn Writev(&self, task: &Task, srcs: &[IoVec]) -> Result<i64> {
@@ -727,6 +784,20 @@ impl File {
         if seekable {
             let mut offsetLock = self.offset.Lock(task)?;
             if self.flags.lock().0.Append {
+                let end = self.offsetForAppend(task)?;
+                let (limit, ok) = self.checkLimit(task, end);
+                if ok && limit == 0 {
+                    return Err(Error::ErrExceedsFileSizeLimit);
+                }
+
+                let limited;
+                let srcs = if ok {
+                    limited = Iovs(srcs).First(limit as usize);
+                    &limited[..]
+                } else {
+                    srcs
+                };
+
                 let (cnt, len) = fops.Append(task, self, srcs)?;
                 *offsetLock = len;
                 return Ok(cnt);
@@ -734,11 +805,20 @@ impl File {
 
             let current = *offsetLock;
 
-            let (limit, ok) = self.checkLimit(current);
+            let (limit, ok) = self.checkLimit(task, current);
             if ok && limit == 0 {
                 return Err(Error::ErrExceedsFileSizeLimit);
             }
 
+            // The write is cut short at the file size limit.
+            let limited;
+            let srcs = if ok {
+                limited = Iovs(srcs).First(limit as usize);
+                &limited[..]
+            } else {
+                srcs
+            };
+
             let blocking = self.Blocking();
             let n = fops.WriteAt(task, self, srcs, current, blocking)?;
             if n > 0 {
@@ -770,11 +850,19 @@ impl File {
              return Ok(cnt)
          }*/
 
-        let (limit, ok) = self.checkLimit(offset);
+        let (limit, ok) = self.checkLimit(task, offset);
         if ok && limit == 0 {
             return Err(Error::ErrExceedsFileSizeLimit);
         }
 
+        let limited;
+        let srcs = if ok {
+            limited = Iovs(srcs).First(limit as usize);
+            &limited[..]
+        } else {
+            srcs
+        };
+
         let blocking = self.Blocking();
         let n = fops.WriteAt(task, self, srcs, offset, blocking)?;
 
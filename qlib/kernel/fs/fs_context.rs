@@ -0,0 +1,278 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::qlib::mutex::*;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::Any;
+
+use super::super::super::common::*;
+use super::super::super::linux_def::*;
+use super::super::kernel::waiter::*;
+use super::super::task::*;
+use super::anon::*;
+use super::attr::*;
+use super::dentry::*;
+use super::dirent::*;
+use super::file::*;
+use super::filesystems::*;
+use super::flags::*;
+use super::host::hostinodeop::*;
+use super::inode::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FsContextPhase {
+    // the parameters are being set by fsconfig
+    Config,
+    // FSCONFIG_CMD_CREATE has made the filesystem instance
+    Created,
+    // fsmount has taken the filesystem instance
+    Mounted,
+}
+
+pub struct FsContext {
+    pub fs: Arc<QMutex<Filesystem>>,
+    pub source: String,
+    // options are the "key" and "key=value" parameters, they are passed to the
+    // filesystem as the data of mount(2)
+    pub options: Vec<String>,
+    pub flags: MountSourceFlags,
+    pub phase: FsContextPhase,
+    pub root: Option<Inode>,
+}
+
+// FsContextOperations is the file returned by fsopen(2). It keeps the
+// parameters of a filesystem instance until FSCONFIG_CMD_CREATE makes it,
+// then fsmount(2) takes the instance.
+pub struct FsContextOperations {
+    pub ctx: QMutex<FsContext>,
+}
+
+impl FsContextOperations {
+    pub fn NewFsContext(task: &Task, fs: &Arc<QMutex<Filesystem>>) -> File {
+        let inode = NewAnonInode(task);
+        let dirent = Dirent::New(&inode, "anon_inode:[fscontext]");
+
+        let fops = Self {
+            ctx: QMutex::new(FsContext {
+                fs: fs.clone(),
+                source: String::new(),
+                options: Vec::new(),
+                flags: MountSourceFlags::default(),
+                phase: FsContextPhase::Config,
+                root: None,
+            }),
+        };
+
+        return File::New(
+            &dirent,
+            &FileFlags {
+                Read: true,
+                Write: true,
+                ..Default::default()
+            },
+            fops,
+        );
+    }
+
+    // SetFlag sets a parameter without value, FSCONFIG_SET_FLAG.
+    pub fn SetFlag(&self, key: &str) -> Result<()> {
+        let mut ctx = self.ctx.lock();
+        if ctx.phase != FsContextPhase::Config {
+            return Err(Error::SysError(SysErr::EBUSY));
+        }
+
+        match key {
+            "ro" => ctx.flags.ReadOnly = true,
+            "rw" => ctx.flags.ReadOnly = false,
+            "source" => return Err(Error::SysError(SysErr::EINVAL)),
+            _ => ctx.options.push(key.to_string()),
+        }
+
+        return Ok(());
+    }
+
+    // SetString sets a parameter with value, FSCONFIG_SET_STRING.
+    pub fn SetString(&self, key: &str, value: &str) -> Result<()> {
+        let mut ctx = self.ctx.lock();
+        if ctx.phase != FsContextPhase::Config {
+            return Err(Error::SysError(SysErr::EBUSY));
+        }
+
+        if key == "source" {
+            // a filesystem has one source
+            if ctx.source.len() > 0 {
+                return Err(Error::SysError(SysErr::EINVAL));
+            }
+
+            ctx.source = value.to_string();
+        } else {
+            ctx.options.push(format!("{}={}", key, value));
+        }
+
+        return Ok(());
+    }
+
+    // Create makes the filesystem instance, FSCONFIG_CMD_CREATE.
+    pub fn Create(&self, task: &Task) -> Result<()> {
+        let (fs, source, flags, data) = {
+            let ctx = self.ctx.lock();
+            if ctx.phase != FsContextPhase::Config {
+                return Err(Error::SysError(SysErr::EBUSY));
+            }
+
+            (
+                ctx.fs.clone(),
+                ctx.source.clone(),
+                ctx.flags,
+                ctx.options.join(","),
+            )
+        };
+
+        let inode = fs.lock().Mount(task, &source, &flags, &data)?;
+        if source.len() > 0 {
+            inode.lock().MountSource.lock().Source = source;
+        }
+
+        let mut ctx = self.ctx.lock();
+        if ctx.phase != FsContextPhase::Config {
+            return Err(Error::SysError(SysErr::EBUSY));
+        }
+
+        ctx.root = Some(inode);
+        ctx.phase = FsContextPhase::Created;
+        return Ok(());
+    }
+
+    // TakeRoot gives the filesystem instance to fsmount, only once.
+    pub fn TakeRoot(&self) -> Result<Inode> {
+        let mut ctx = self.ctx.lock();
+        match ctx.phase {
+            FsContextPhase::Config => return Err(Error::SysError(SysErr::EINVAL)),
+            FsContextPhase::Mounted => return Err(Error::SysError(SysErr::EBUSY)),
+            FsContextPhase::Created => {
+                ctx.phase = FsContextPhase::Mounted;
+                return Ok(ctx.root.take().unwrap());
+            }
+        }
+    }
+}
+
+impl SpliceOperations for FsContextOperations {}
+
+impl FileOperations for FsContextOperations {
+    fn as_any(&self) -> &Any {
+        return self;
+    }
+
+    fn FopsType(&self) -> FileOpsType {
+        return FileOpsType::FsContextOperations;
+    }
+
+    fn Seekable(&self) -> bool {
+        return false;
+    }
+
+    fn Seek(
+        &self,
+        _task: &Task,
+        _f: &File,
+        _whence: i32,
+        _current: i64,
+        _offset: i64,
+    ) -> Result<i64> {
+        return Err(Error::SysError(SysErr::ESPIPE));
+    }
+
+    fn ReadDir(
+        &self,
+        _task: &Task,
+        _f: &File,
+        _offset: i64,
+        _serializer: &mut DentrySerializer,
+    ) -> Result<i64> {
+        return Err(Error::SysError(SysErr::ENOTDIR));
+    }
+
+    // there are no error messages kept for the reader
+    fn ReadAt(
+        &self,
+        _task: &Task,
+        _f: &File,
+        _dsts: &mut [IoVec],
+        _offset: i64,
+        _blocking: bool,
+    ) -> Result<i64> {
+        return Err(Error::SysError(SysErr::ENODATA));
+    }
+
+    fn WriteAt(
+        &self,
+        _task: &Task,
+        _f: &File,
+        _srcs: &[IoVec],
+        _offset: i64,
+        _blocking: bool,
+    ) -> Result<i64> {
+        return Err(Error::SysError(SysErr::EOPNOTSUPP));
+    }
+
+    fn Append(&self, _task: &Task, _f: &File, _srcs: &[IoVec]) -> Result<(i64, i64)> {
+        return Err(Error::SysError(SysErr::EOPNOTSUPP));
+    }
+
+    fn Fsync(
+        &self,
+        _task: &Task,
+        _f: &File,
+        _start: i64,
+        _end: i64,
+        _syncType: SyncType,
+    ) -> Result<()> {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    fn Flush(&self, _task: &Task, _f: &File) -> Result<()> {
+        return Ok(());
+    }
+
+    fn UnstableAttr(&self, task: &Task, f: &File) -> Result<UnstableAttr> {
+        let inode = f.Dirent.Inode();
+        return inode.UnstableAttr(task);
+    }
+
+    fn Ioctl(&self, _task: &Task, _f: &File, _fd: i32, _request: u64, _val: u64) -> Result<()> {
+        return Err(Error::SysError(SysErr::ENOTTY));
+    }
+
+    fn IterateDir(
+        &self,
+        _task: &Task,
+        _d: &Dirent,
+        _dirCtx: &mut DirCtx,
+        _offset: i32,
+    ) -> (i32, Result<i64>) {
+        return (0, Err(Error::SysError(SysErr::ENOTDIR)));
+    }
+
+    fn Mappable(&self) -> Result<HostInodeOp> {
+        return Err(Error::SysError(SysErr::ENODEV));
+    }
+}
+
+impl Waitable for FsContextOperations {}
+
+impl SockOperations for FsContextOperations {}
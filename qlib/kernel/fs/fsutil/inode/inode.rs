@@ -166,7 +166,7 @@ impl InodeNotDirectoryInternal {
         return Err(Error::SysError(SysErr::ENOTDIR))
     }
 
-    fn Rename(&self, _task: &Task, _dir: &mut Inode, _oldParent: &Inode, _oldname: &str, _newParent: &Inode, _newname: &str, _replacement: bool) -> Result<()> {
+    fn Rename(&self, _task: &Task, _dir: &mut Inode, _oldParent: &Inode, _oldname: &str, _newParent: &Inode, _newname: &str, _replacement: bool, _flags: u32) -> Result<()> {
         return Err(Error::SysError(SysErr::EINVAL))
     }
 }
@@ -198,7 +198,7 @@ impl InodeNoopTruncate {
 pub struct InodeNotRenameable {}
 
 impl InodeNotRenameable {
-    fn Rename(&self, _task: &Task, _dir: &mut Inode, _oldParent: &Inode, _oldname: &str, _newParent: &Inode, _newname: &str, _replacement: bool) -> Result<()> {
+    fn Rename(&self, _task: &Task, _dir: &mut Inode, _oldParent: &Inode, _oldname: &str, _newParent: &Inode, _newname: &str, _replacement: bool, _flags: u32) -> Result<()> {
         return Err(Error::SysError(SysErr::EINVAL))
     }
 }
@@ -89,6 +89,7 @@ pub type Rename = fn(
     newParent: &Inode,
     newname: &str,
     replacement: bool,
+    flags: u32,
 ) -> Result<()>;
 pub type GetFile = fn(
     _data: &InodeOpsData,
@@ -219,6 +220,7 @@ fn InodeNotDirectory_Rename(
     _newParent: &Inode,
     _newname: &str,
     _replacement: bool,
+    _flags: u32,
 ) -> Result<()> {
     return Err(Error::SysError(SysErr::EINVAL));
 }
@@ -259,6 +261,7 @@ fn InodeNotRenameable_Rename(
     _newParent: &Inode,
     _newname: &str,
     _replacement: bool,
+    _flags: u32,
 ) -> Result<()> {
     return Err(Error::SysError(SysErr::EINVAL));
 }
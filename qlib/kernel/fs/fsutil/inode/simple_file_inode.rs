@@ -211,6 +211,7 @@ impl<T: 'static + SimpleFileTrait> InodeOperations for SimpleFileInode<T> {
         _newParent: &Inode,
         _newname: &str,
         _replacement: bool,
+        _flags: u32,
     ) -> Result<()> {
         return Err(Error::SysError(SysErr::EINVAL));
     }
@@ -0,0 +1,420 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::qlib::mutex::*;
+use alloc::collections::btree_map::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::sync::Weak;
+use alloc::vec::Vec;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering;
+
+use super::super::super::super::common::*;
+use super::super::super::super::config::HostFileCache;
+use super::super::super::super::linux::inotify::*;
+use super::super::super::super::linux_def::*;
+use super::super::super::super::singleton::*;
+use super::super::super::fd::*;
+use super::super::super::guestfdnotifier::*;
+use super::super::super::kernel::waiter::*;
+use super::super::super::task::*;
+use super::super::super::Kernel::HostSpace;
+use super::super::dirent::*;
+use super::super::inode::*;
+use super::hostinodeop::*;
+use super::util::*;
+use super::SuperOperations;
+
+pub static HOST_DIR_NOTIFIER: Singleton<HostDirNotifier> = Singleton::<HostDirNotifier>::New();
+pub unsafe fn InitSingleton() {
+    HOST_DIR_NOTIFIER.Init(HostDirNotifier::default());
+}
+
+// MAX_HOST_DIR_WATCHES bounds the host directories watched for the dentry
+// cache, the directories beyond it don't cache negative dentries and their
+// cached dentries are revalidated on each walk.
+pub const MAX_HOST_DIR_WATCHES: usize = 4096;
+
+pub const INOTIFY_READ_SIZE: usize = 4096;
+
+pub const HOST_DIR_WATCH_MASK: u32 = IN_CREATE
+    | IN_DELETE
+    | IN_MOVED_FROM
+    | IN_MOVED_TO
+    | IN_DELETE_SELF
+    | IN_MOVE_SELF
+    | IN_ONLYDIR;
+
+#[derive(Default)]
+pub struct HostDirNotifierIntern {
+    // fd is the host inotify fd, it is created by the first watch
+    pub fd: Option<i32>,
+    // unavailable is set if the host inotify fd can't be created
+    pub unavailable: bool,
+    pub queue: Queue,
+    pub entry: Option<WaitEntry>,
+    pub watches: BTreeMap<i32, Vec<Weak<(QMutex<InterDirent>, u64)>>>,
+}
+
+// HostDirNotifier watches the host directories walked by the dirents with a
+// host inotify fd, so that the dentries cached for them are dropped when the
+// directories are changed outside of the sandbox.
+#[derive(Default)]
+pub struct HostDirNotifier {
+    pub watching: AtomicBool,
+    // seq is increased each time events are read
+    pub seq: AtomicU64,
+    pub intern: QMutex<HostDirNotifierIntern>,
+}
+
+impl HostDirNotifier {
+    pub fn Seq(&self) -> u64 {
+        return self.seq.load(Ordering::Acquire);
+    }
+
+    // Watch adds the host watches of the directory d and returns whether it
+    // is watched.
+    pub fn Watch(&self, task: &Task, d: &Dirent) -> bool {
+        let watch = (d.0).0.lock().hostWatch;
+        match watch {
+            HostWatch::Watched => return true,
+            HostWatch::Unwatchable => return false,
+            HostWatch::Unknown => (),
+        }
+
+        let fds = HostDirFds(&d.Inode());
+        let watched = fds.len() > 0 && self.addWatches(task, d, &fds);
+        (d.0).0.lock().hostWatch = if watched {
+            HostWatch::Watched
+        } else {
+            HostWatch::Unwatchable
+        };
+
+        return watched;
+    }
+
+    fn addWatches(&self, task: &Task, d: &Dirent, fds: &[i32]) -> bool {
+        let mut intern = self.intern.lock();
+        let fd = match intern.fd {
+            Some(fd) => fd,
+            None => {
+                if intern.unavailable {
+                    return false;
+                }
+
+                let fd = HostSpace::InotifyInit() as i32;
+                if fd < 0 {
+                    info!("HostDirNotifier: inotify_init fail with {}", -fd);
+                    intern.unavailable = true;
+                    return false;
+                }
+
+                let entry = WaitEntry::New();
+                SetWaitInfo(fd, intern.queue.clone());
+                intern.queue.EventRegister(task, &entry, READABLE_EVENT);
+                UpdateFD(fd).unwrap();
+                intern.entry = Some(entry);
+                intern.fd = Some(fd);
+                self.watching.store(true, Ordering::Release);
+                fd
+            }
+        };
+
+        if intern.watches.len() + fds.len() > MAX_HOST_DIR_WATCHES {
+            intern.Prune(fd);
+            if intern.watches.len() + fds.len() > MAX_HOST_DIR_WATCHES {
+                return false;
+            }
+        }
+
+        for dirfd in fds {
+            let wd = HostSpace::InotifyAddWatch(fd, *dirfd, HOST_DIR_WATCH_MASK) as i32;
+            if wd < 0 {
+                return false;
+            }
+
+            let dirents = intern.watches.entry(wd).or_insert_with(Vec::new);
+            dirents.retain(|w| w.strong_count() > 0);
+            dirents.push(Arc::downgrade(&d.0));
+        }
+
+        // the watches are new to the events read before
+        self.seq.fetch_add(1, Ordering::AcqRel);
+        return true;
+    }
+
+    // Poll applies the host directory changes queued on the inotify fd. It is
+    // cheap when there is none as the readiness of the fd is cached.
+    pub fn Poll(&self) {
+        if !self.watching.load(Ordering::Acquire) {
+            return;
+        }
+
+        let fd = match self.intern.lock().fd {
+            None => return,
+            Some(fd) => fd,
+        };
+
+        if NonBlockingPoll(fd, READABLE_EVENT) & READABLE_EVENT == 0 {
+            return;
+        }
+
+        let mut buf = Vec::with_capacity(INOTIFY_READ_SIZE);
+        buf.resize(INOTIFY_READ_SIZE, 0);
+        loop {
+            let iov = IoVec::New(&buf);
+            let cnt = match IORead(fd, &[iov]) {
+                Err(_) => return,
+                Ok(0) => return,
+                Ok(cnt) => cnt as usize,
+            };
+
+            self.seq.fetch_add(1, Ordering::AcqRel);
+            let events = ParseInotifyEvents(&buf[..cnt]);
+            self.apply(fd, &events);
+        }
+    }
+
+    fn apply(&self, fd: i32, events: &[(InotifyEvent, String)]) {
+        let mut changes = Vec::new();
+        let mut overflow = Vec::new();
+        {
+            let mut intern = self.intern.lock();
+            for (event, name) in events {
+                if event.Mask & IN_Q_OVERFLOW != 0 {
+                    // events are lost, none of the negative dentries can be trusted
+                    for (_, dirents) in &intern.watches {
+                        overflow.extend(dirents.iter().filter_map(|w| w.upgrade()));
+                    }
+                    continue;
+                }
+
+                let dirents: Vec<_> = match intern.watches.get(&event.Wd) {
+                    None => continue,
+                    Some(dirents) => dirents.iter().filter_map(|w| w.upgrade()).collect(),
+                };
+
+                if event.Mask & IN_IGNORED != 0 {
+                    intern.watches.remove(&event.Wd);
+                } else if dirents.len() == 0 {
+                    HostSpace::InotifyRmWatch(fd, event.Wd);
+                    intern.watches.remove(&event.Wd);
+                }
+
+                for d in dirents {
+                    changes.push((d, event.Mask, name.clone()));
+                }
+            }
+        }
+
+        // the dirents are locked after the notifier lock is released
+        for d in overflow {
+            Dirent(d).DropNegativeChildren();
+        }
+
+        for (d, mask, name) in changes {
+            let d = Dirent(d);
+            if mask & (IN_IGNORED | IN_DELETE_SELF | IN_MOVE_SELF) != 0 {
+                d.DropNegativeChildren();
+                if mask & IN_IGNORED != 0 {
+                    (d.0).0.lock().hostWatch = HostWatch::Unknown;
+                }
+                continue;
+            }
+
+            if mask & (IN_CREATE | IN_MOVED_TO) != 0 {
+                d.DropNegativeChild(&name);
+            }
+
+            if mask & (IN_DELETE | IN_MOVED_FROM | IN_MOVED_TO) != 0 {
+                d.DropHostChild(&name);
+            }
+        }
+    }
+}
+
+impl HostDirNotifierIntern {
+    // Prune removes the watches of the directories without dirent.
+    pub fn Prune(&mut self, fd: i32) {
+        let mut unused = Vec::new();
+        for (wd, dirents) in &mut self.watches {
+            dirents.retain(|w| w.strong_count() > 0);
+            if dirents.len() == 0 {
+                unused.push(*wd);
+            }
+        }
+
+        for wd in unused {
+            HostSpace::InotifyRmWatch(fd, wd);
+            self.watches.remove(&wd);
+        }
+    }
+}
+
+// HostDirFds returns the host fds of the directories backing inode, the
+// layers of an overlay included. It is empty if a layer is not on the host.
+pub fn HostDirFds(inode: &Inode) -> Vec<i32> {
+    let mut fds = Vec::new();
+    if !hostDirFds(inode, &mut fds) {
+        return Vec::new();
+    }
+
+    return fds;
+}
+
+fn hostDirFds(inode: &Inode, fds: &mut Vec<i32>) -> bool {
+    let overlay = inode.lock().Overlay.clone();
+    if let Some(overlay) = overlay {
+        let (upper, lower) = {
+            let o = overlay.read();
+            (o.upper.clone(), o.lower.clone())
+        };
+
+        for layer in [upper, lower].iter() {
+            if let Some(layer) = layer {
+                if !hostDirFds(layer, fds) {
+                    return false;
+                }
+            }
+        }
+
+        return true;
+    }
+
+    let iops = inode.lock().InodeOp.clone();
+    match iops.as_any().downcast_ref::<HostInodeOp>() {
+        None => return false,
+        Some(h) => {
+            fds.push(h.HostFd());
+            return true;
+        }
+    }
+}
+
+// HostCacheMode returns the cache mode of the host mount backing inode, it
+// is None if no layer of the inode is on the host.
+pub fn HostCacheMode(inode: &Inode) -> Option<HostFileCache> {
+    let overlay = inode.lock().Overlay.clone();
+    if let Some(overlay) = overlay {
+        let (upper, lower) = {
+            let o = overlay.read();
+            (o.upper.clone(), o.lower.clone())
+        };
+
+        for layer in [upper, lower].iter() {
+            if let Some(layer) = layer {
+                if let Some(mode) = HostCacheMode(layer) {
+                    return Some(mode);
+                }
+            }
+        }
+
+        return None;
+    }
+
+    let iops = inode.lock().InodeOp.clone();
+    let mops = match iops.as_any().downcast_ref::<HostInodeOp>() {
+        None => return None,
+        Some(h) => h.lock().mops.clone(),
+    };
+
+    let mops = mops.lock();
+    match mops.as_any().downcast_ref::<SuperOperations>() {
+        None => return None,
+        Some(s) => return Some(s.cacheMode),
+    }
+}
+
+// HostChildChanged returns whether name in the host directory of parent is
+// no longer the host file of child, i.e. it is removed or replaced.
+pub fn HostChildChanged(parent: &Inode, name: &str, child: &Inode) -> bool {
+    let (dirfd, fd) = match hostFdPair(parent, child) {
+        None => return false,
+        Some(fds) => fds,
+    };
+
+    let mut cstat = LibcStat::default();
+    if Fstat(fd, &mut cstat) < 0 {
+        return false;
+    }
+
+    let mut nstat = LibcStat::default();
+    if Fstatat(dirfd, name, &mut nstat, ATType::AT_SYMLINK_NOFOLLOW) < 0 {
+        return true;
+    }
+
+    return nstat.st_dev != cstat.st_dev || nstat.st_ino != cstat.st_ino;
+}
+
+// hostFdPair returns the host fds of the directory and the file of the same
+// layer, the upper layer of an overlay first.
+fn hostFdPair(parent: &Inode, child: &Inode) -> Option<(i32, i32)> {
+    let poverlay = parent.lock().Overlay.clone();
+    let coverlay = child.lock().Overlay.clone();
+    match (poverlay, coverlay) {
+        (Some(p), Some(c)) => {
+            let (pupper, plower) = {
+                let o = p.read();
+                (o.upper.clone(), o.lower.clone())
+            };
+            let (cupper, clower) = {
+                let o = c.read();
+                (o.upper.clone(), o.lower.clone())
+            };
+
+            match (pupper, cupper) {
+                (Some(p), Some(c)) => return hostFdPair(&p, &c),
+                (None, Some(_)) => return None,
+                _ => (),
+            }
+
+            match (plower, clower) {
+                (Some(p), Some(c)) => return hostFdPair(&p, &c),
+                _ => return None,
+            }
+        }
+        (None, None) => {
+            let piops = parent.lock().InodeOp.clone();
+            let ciops = child.lock().InodeOp.clone();
+            let p = piops.as_any().downcast_ref::<HostInodeOp>()?;
+            let c = ciops.as_any().downcast_ref::<HostInodeOp>()?;
+            return Some((p.HostFd(), c.HostFd()));
+        }
+        _ => return None,
+    }
+}
+
+// ParseInotifyEvents splits the buffer read from an inotify fd into events
+// and the names they carry.
+pub fn ParseInotifyEvents(buf: &[u8]) -> Vec<(InotifyEvent, String)> {
+    let mut events = Vec::new();
+    let mut offset = 0;
+    while offset + InotifyEvent::SIZE <= buf.len() {
+        let event =
+            unsafe { core::ptr::read_unaligned(&buf[offset] as *const u8 as *const InotifyEvent) };
+
+        let start = offset + InotifyEvent::SIZE;
+        let end = core::cmp::min(start + event.Len as usize, buf.len());
+        let name = &buf[start..end];
+        let len = name.iter().position(|c| *c == 0).unwrap_or(name.len());
+        let name = String::from_utf8_lossy(&name[..len]).into_owned();
+
+        events.push((event, name));
+        offset = start + event.Len as usize;
+    }
+
+    return events;
+}
@@ -20,9 +20,11 @@ use alloc::sync::Arc;
 use alloc::vec::Vec;
 
 use super::super::super::super::common::*;
+use super::super::super::super::config::HostFileCache;
 use super::super::super::super::linux_def::*;
 use super::super::super::super::path::*;
 use super::super::super::task::*;
+use super::super::super::SHARESPACE;
 use super::super::filesystems::*;
 use super::super::inode::*;
 use super::super::mount::*;
@@ -33,6 +35,7 @@ pub const FILESYSTEM_NAME: &str = "whitelistfs";
 pub const WHITELIST_KEY: &str = "whitelist";
 pub const ROOT_PATH_KEY: &str = "root";
 pub const DONT_TRANSLATE_OWNERSHIP_KEY: &str = "dont_translate_ownership";
+pub const CACHE_KEY: &str = "cache";
 
 pub struct WhitelistFileSystem {
     pub paths: Vec<String>,
@@ -70,6 +73,16 @@ impl WhitelistFileSystem {
     }
 }
 
+// ParseCacheMode parses the value of the cache mount option.
+pub fn ParseCacheMode(v: &str) -> Result<HostFileCache> {
+    match v {
+        "shared" => return Ok(HostFileCache::Shared),
+        "exclusive" => return Ok(HostFileCache::Exclusive),
+        "revalidate" => return Ok(HostFileCache::Revalidate),
+        _ => return Err(Error::Common(format!("invalid cache mount option {}", v))),
+    }
+}
+
 pub fn InstallWhitelist(task: &Task, m: &MountNs, inputPaths: &Vec<String>) -> Result<()> {
     if inputPaths.len() == 0 || (inputPaths.len() == 1 && inputPaths[0].as_str() == "") {
         return Ok(());
@@ -224,14 +237,25 @@ impl Filesystem for WhitelistFileSystem {
             options.remove(&DONT_TRANSLATE_OWNERSHIP_KEY.to_string());
         }
 
+        let cacheMode = match options.remove(&CACHE_KEY.to_string()) {
+            None => SHARESPACE.config.read().HostFileCache,
+            Some(v) => ParseCacheMode(&v)?,
+        };
+
         if options.len() > 0 {
             return Err(Error::Common("unsupported mount options".to_string()));
         }
 
         let owner = task.Creds().FileOwner();
 
-        let msrc =
-            MountSource::NewHostMountSource(&rootPath, &owner, self, flags, dontTranslateOwnership);
+        let msrc = MountSource::NewHostMountSource(
+            &rootPath,
+            &owner,
+            self,
+            flags,
+            dontTranslateOwnership,
+            cacheMode,
+        );
 
         let mut fstat = LibcStat::default();
         let ret = Fstat(fd, &mut fstat);
@@ -51,7 +51,10 @@ pub enum HostFileBuf {
 
 pub struct HostFileOp {
     pub InodeOp: HostInodeOp,
-    pub DirCursor: QMutex<String>,
+    // DirEntries is the listing of the directory read at offset 0, the later
+    // reads of the open directory are served from it. The offset of the file
+    // is the position in the listing.
+    pub DirEntries: QMutex<Option<Arc<Vec<(String, DentAttr)>>>>,
     //pub Buf: HostFileBuf,
 }
 
@@ -97,6 +100,22 @@ impl HostFileOp {
     fn ReadDirAll(&self, task: &Task) -> Result<BTreeMap<String, DentAttr>> {
         return self.InodeOp.ReadDirAll(task);
     }
+
+    // DirEntries returns the listing of the directory, it is read from the host
+    // again when the directory is read from the start.
+    fn DirEntries(&self, task: &Task, offset: i32) -> Result<Arc<Vec<(String, DentAttr)>>> {
+        let mut cached = self.DirEntries.lock();
+        if offset != 0 {
+            if let Some(entries) = cached.as_ref() {
+                return Ok(entries.clone());
+            }
+        }
+
+        let entries: Vec<(String, DentAttr)> = self.ReadDirAll(task)?.into_iter().collect();
+        let entries = Arc::new(entries);
+        *cached = Some(entries.clone());
+        return Ok(entries);
+    }
 }
 
 impl Waitable for HostFileOp {
@@ -163,10 +182,27 @@ impl FileOperations for HostFileOp {
     }
 
     fn Seek(&self, task: &Task, f: &File, whence: i32, current: i64, offset: i64) -> Result<i64> {
-        let mut dirCursor = self.DirCursor.lock();
-        let mut cursor = "".to_string();
-        let newOffset = SeekWithDirCursor(task, f, whence, current, offset, Some(&mut cursor))?;
-        *dirCursor = cursor;
+        if self.InodeOp.InodeType() != InodeType::Directory {
+            return SeekWithDirCursor(task, f, whence, current, offset, None);
+        }
+
+        // a directory offset is a position in its listing, so that seekdir can
+        // go back to any position given by telldir
+        let newOffset = if whence == SeekWhence::SEEK_SET {
+            offset
+        } else if whence == SeekWhence::SEEK_CUR {
+            match current.checked_add(offset) {
+                None => return Err(Error::SysError(SysErr::EINVAL)),
+                Some(off) => off,
+            }
+        } else {
+            return Err(Error::SysError(SysErr::EINVAL));
+        };
+
+        if newOffset < 0 {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
         return Ok(newOffset);
     }
 
@@ -178,16 +214,13 @@ impl FileOperations for HostFileOp {
         serializer: &mut DentrySerializer,
     ) -> Result<i64> {
         let root = task.Root();
-        let mut dirCursor = self.DirCursor.lock();
 
         let mut dirCtx = DirCtx {
             Serializer: serializer,
-            DirCursor: (*dirCursor).to_string(),
+            DirCursor: "".to_string(),
         };
 
-        let res = DirentReadDir(task, &file.Dirent, self, &root, &mut dirCtx, offset)?;
-        *dirCursor = dirCtx.DirCursor;
-        return Ok(res);
+        return DirentReadDir(task, &file.Dirent, self, &root, &mut dirCtx, offset);
     }
 
     fn ReadAt(
@@ -252,17 +285,26 @@ impl FileOperations for HostFileOp {
         dirCtx: &mut DirCtx,
         offset: i32,
     ) -> (i32, Result<i64>) {
-        let entries = match self.ReadDirAll(task) {
+        let entries = match self.DirEntries(task, offset) {
             Err(e) => return (offset, Err(e)),
             Ok(entires) => entires,
         };
 
-        let dentryMap = DentMap { Entries: entries };
+        let mut count = 0;
+        for (name, attr) in entries.iter().skip(offset as usize) {
+            match dirCtx.DirEmit(task, name, attr) {
+                Err(e) => {
+                    if count > 0 {
+                        break;
+                    }
+                    return (offset, Err(e));
+                }
+                Ok(()) => (),
+            }
+            count += 1;
+        }
 
-        return match dirCtx.ReadDir(task, &dentryMap) {
-            Err(e) => (offset, Err(e)),
-            Ok(count) => (offset + count as i32, Ok(0)),
-        };
+        return (offset + count, Ok(0));
     }
 
     fn Mappable(&self) -> Result<HostInodeOp> {
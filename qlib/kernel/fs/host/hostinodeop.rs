@@ -24,6 +24,7 @@ use core::ops::Deref;
 use super::super::super::super::addr::*;
 use super::super::super::super::auth::*;
 use super::super::super::super::common::*;
+use super::super::super::super::config::HostFileCache;
 use super::super::super::super::linux::time::*;
 use super::super::super::super::linux_def::*;
 use super::super::super::super::mem::areaset::*;
@@ -47,11 +48,16 @@ use super::super::file::*;
 use super::super::filesystems::*;
 use super::super::flags::*;
 use super::super::inode::*;
+use super::super::tmpfs::fs::TmpfsCharge;
 use super::fs::*;
 use super::hostfileop::*;
 use super::util::*;
 use super::*;
 
+// ZERO_COPY_THRESHOLD is the io size from which the host file reads/writes pin
+// the user pages instead of copying through a kernel buffer
+pub const ZERO_COPY_THRESHOLD: usize = 1 << 20;
+
 pub struct MappableInternal {
     //addr mapping from file offset to physical address
     pub f2pmap: BTreeMap<u64, u64>,
@@ -62,6 +68,24 @@ pub struct MappableInternal {
 
     // file offset to ref count mapping
     pub chunkrefs: BTreeMap<u64, i32>,
+
+    // exact is set for the host device files which can only be mapped by the
+    // exact file range the driver set up, e.g. the nvidia devices after
+    // RM_MAP_MEMORY. Such a range is mapped on the host when the guest maps
+    // it and unmapped when all the guest mappings of it are gone.
+    pub exact: bool,
+    // mmapContext is the file range the next new mapping of an exact file
+    // must map
+    pub mmapContext: Option<Range>,
+    // exactMaps are the host mapped ranges of an exact file by file offset
+    pub exactMaps: BTreeMap<u64, ExactMapping>,
+}
+
+pub struct ExactMapping {
+    pub len: u64,
+    pub phyAddr: u64,
+    // refs is the count of the guest pages mapping the range
+    pub refs: i32,
 }
 
 impl MappableInternal {
@@ -70,6 +94,100 @@ impl MappableInternal {
             //error!("MappableInternal clean phyAddr {:x?}/{:x?}", phyAddr, offset);
             HostSpace::MUnmap(*phyAddr, CHUNK_SIZE);
         }
+
+        for (_offset, m) in &self.exactMaps {
+            HostSpace::MUnmapDevice(m.phyAddr, m.len);
+        }
+    }
+
+    // ExactMappingOf returns the file offset of the host mapped range holding
+    // the file offset
+    pub fn ExactMappingOf(&self, offset: u64) -> Option<u64> {
+        let (start, m) = self.exactMaps.range(..=offset).next_back()?;
+        if offset >= *start + m.len {
+            return None;
+        }
+
+        return Some(*start);
+    }
+
+    pub fn ExactPhyAddr(&self, offset: u64) -> Option<u64> {
+        let start = self.ExactMappingOf(offset)?;
+        return Some(self.exactMaps[&start].phyAddr + offset - start);
+    }
+
+    // ExactRanges returns the host mapped ranges covering the file range fr as
+    // (file offset, physical address, len), None if a part isn't mapped
+    pub fn ExactRanges(&self, fr: &Range) -> Option<Vec<(u64, u64, u64)>> {
+        let mut ranges = Vec::new();
+        let mut offset = fr.Start();
+        while offset < fr.End() {
+            let start = self.ExactMappingOf(offset)?;
+            let m = &self.exactMaps[&start];
+            let end = core::cmp::min(fr.End(), start + m.len);
+            ranges.push((offset, m.phyAddr + offset - start, end - offset));
+            offset = end;
+        }
+
+        return Some(ranges);
+    }
+
+    // ExactIncrRefOn takes the page refs of the file range fr. A range which
+    // isn't mapped on the host yet must be the one set up for the next mmap.
+    pub fn ExactIncrRefOn(&mut self, fd: i32, fr: &Range, prot: i32) -> Result<()> {
+        if let Some(ranges) = self.ExactRanges(fr) {
+            for (offset, _, len) in ranges {
+                let start = self.ExactMappingOf(offset).unwrap();
+                let m = self.exactMaps.get_mut(&start).unwrap();
+                m.refs += (len / MemoryDef::PAGE_SIZE) as i32;
+            }
+
+            return Ok(());
+        }
+
+        match self.mmapContext {
+            Some(r) if r.Start() == fr.Start() && r.Len() == fr.Len() => (),
+            _ => return Err(Error::SysError(SysErr::EINVAL)),
+        }
+
+        let ret = HostSpace::MMapDevice(fr.Len(), fd, fr.Start(), prot);
+        if ret < 0 {
+            return Err(Error::SysError(-ret as i32));
+        }
+
+        // the driver takes the context with the mmap
+        self.mmapContext = None;
+        self.exactMaps.insert(
+            fr.Start(),
+            ExactMapping {
+                len: fr.Len(),
+                phyAddr: ret as u64,
+                refs: (fr.Len() / MemoryDef::PAGE_SIZE) as i32,
+            },
+        );
+        return Ok(());
+    }
+
+    pub fn ExactDecrRefOn(&mut self, fr: &Range) {
+        let ranges = match self.ExactRanges(fr) {
+            None => panic!("Mappable::ExactDecrRefOn {:x?} is not mapped", fr),
+            Some(ranges) => ranges,
+        };
+
+        for (offset, _, len) in ranges {
+            let start = self.ExactMappingOf(offset).unwrap();
+            let m = self.exactMaps.get_mut(&start).unwrap();
+            m.refs -= (len / MemoryDef::PAGE_SIZE) as i32;
+            if m.refs == 0 {
+                HostSpace::MUnmapDevice(m.phyAddr, m.len);
+                self.exactMaps.remove(&start);
+            } else if m.refs < 0 {
+                panic!(
+                    "Mappable::ExactDecrRefOn get negative refs {}, fr is {:x?}",
+                    m.refs, fr
+                )
+            }
+        }
     }
 
     pub fn IncrRefOn(&mut self, fr: &Range) {
@@ -152,6 +270,9 @@ impl Default for MappableInternal {
             f2pmap: BTreeMap::new(),
             mapping: AreaSet::New(0, core::u64::MAX),
             chunkrefs: BTreeMap::new(),
+            exact: false,
+            mmapContext: None,
+            exactMaps: BTreeMap::new(),
         };
     }
 }
@@ -185,6 +306,9 @@ pub struct HostInodeOpIntern {
     pub mappable: Option<Mappable>,
     pub bufWriteLock: QAsyncLock,
     pub hasMappable: bool,
+
+    // the charge of a tmpfs file to the size limit of its mount
+    pub charge: Option<TmpfsCharge>,
 }
 
 impl Default for HostInodeOpIntern {
@@ -201,6 +325,7 @@ impl Default for HostInodeOpIntern {
             size: 0,
             bufWriteLock: QAsyncLock::default(),
             hasMappable: false,
+            charge: None,
         };
     }
 }
@@ -245,6 +370,7 @@ impl HostInodeOpIntern {
             size: fstat.st_size,
             bufWriteLock: QAsyncLock::default(),
             hasMappable: false,
+            charge: None,
         };
 
         if ret.CanMap() {
@@ -285,12 +411,26 @@ impl HostInodeOpIntern {
 
     //get phyaddress ranges for the file range
     pub fn MapInternal(&mut self, task: &Task, fr: &Range) -> Result<Vec<IoVec>> {
+        let mappable = self.Mappable();
+        if mappable.lock().exact {
+            let ranges = match mappable.lock().ExactRanges(fr) {
+                None => return Err(Error::FileMapError),
+                Some(ranges) => ranges,
+            };
+
+            let mut res = Vec::new();
+            for (_, phyAddr, len) in ranges {
+                res.push(IoVec::NewFromAddr(phyAddr, len as usize));
+            }
+
+            return Ok(res);
+        }
+
         let mut chunkStart = fr.Start() & !HUGE_PAGE_MASK;
 
         self.Fill(task, chunkStart, fr.End())?;
         let mut res = Vec::new();
 
-        let mappable = self.Mappable();
         let mappableLock = mappable.lock();
 
         while chunkStart < fr.End() {
@@ -317,21 +457,48 @@ impl HostInodeOpIntern {
 
     // map one page from file offsetFile to phyAddr
     pub fn MapFilePage(&mut self, task: &Task, fileOffset: u64) -> Result<u64> {
+        // the device range is mapped on the host with the guest mapping
+        let mappable = self.Mappable();
+        if mappable.lock().exact {
+            match mappable.lock().ExactPhyAddr(fileOffset) {
+                None => return Err(Error::FileMapError),
+                Some(phyAddr) => return Ok(phyAddr),
+            }
+        }
+
         let filesize = self.size as u64;
         if filesize <= fileOffset {
             return Err(Error::FileMapError)
         }
 
+        // a fault allocates the page of a tmpfs file
+        match &mut self.charge {
+            None => (),
+            Some(charge) => charge.Charge(fileOffset, fileOffset + PAGE_SIZE)?,
+        }
+
         let chunkStart = fileOffset & !HUGE_PAGE_MASK;
         self.Fill(task, chunkStart, fileOffset + PAGE_SIZE)?;
 
-        let mappable = self.Mappable();
         let mappableLock = mappable.lock();
 
         let phyAddr = mappableLock.f2pmap.get(&chunkStart).unwrap();
         return Ok(phyAddr + (fileOffset - chunkStart));
     }
 
+    // whether the host chunk holding fileOffset is already mapped, a fault on
+    // an unmapped chunk needs the host to map it
+    pub fn ChunkMapped(&mut self, fileOffset: u64) -> bool {
+        let mappable = self.Mappable();
+        let mappableLock = mappable.lock();
+        if mappableLock.exact {
+            return mappableLock.ExactPhyAddr(fileOffset).is_some();
+        }
+
+        let chunkStart = fileOffset & !HUGE_PAGE_MASK;
+        return mappableLock.f2pmap.get(&chunkStart).is_some();
+    }
+
     //fill the holes for the file range by mmap
     //start must be Hugepage aligned
     fn Fill(&mut self, _task: &Task, start: u64, end: u64) -> Result<()> {
@@ -542,6 +709,7 @@ impl HostInodeOp {
             &WhitelistFileSystem::New(),
             &MountSourceFlags::default(),
             false,
+            HostFileCache::default(),
         );
         let intern = Arc::new(QMutex::new(HostInodeOpIntern::New(
             &msrc.MountSourceOperations.clone(),
@@ -566,10 +734,15 @@ impl HostInodeOp {
         return Ok(());
     }
 
-    pub fn SyncFileRange(&self, offset: i64, nbytes: i64, flags: u32) -> Result<()> {
+    pub fn SyncFileRange(&self, task: &Task, offset: i64, nbytes: i64, flags: u32) -> Result<()> {
         let fd = self.HostFd();
 
-        let ret = HostSpace::SyncFileRange(fd, offset, nbytes, flags);
+        // the uring sqe only has 32 bits for the length
+        let ret = if self.UringEnable() && nbytes >= 0 && nbytes <= u32::MAX as i64 {
+            IOURING.SyncFileRange(task, fd, offset, nbytes as u32, flags)
+        } else {
+            HostSpace::SyncFileRange(fd, offset, nbytes, flags)
+        };
         if ret < 0 {
             return Err(Error::SysError(-ret as i32));
         }
@@ -577,6 +750,11 @@ impl HostInodeOp {
         return Ok(());
     }
 
+    // the host fd is registered with the uring as a fixed file
+    pub fn UringEnable(&self) -> bool {
+        return SHARESPACE.config.read().UringIO && self.InodeType() == InodeType::RegularFile;
+    }
+
     pub fn Downgrade(&self) -> HostInodeOpWeak {
         return HostInodeOpWeak(Arc::downgrade(&self.0));
     }
@@ -585,6 +763,40 @@ impl HostInodeOp {
         return self.lock().HostFd;
     }
 
+    // MappableId identifies the file memory shared by all the mappings of the
+    // file, which hold the same HostInodeOp.
+    pub fn MappableId(&self) -> u64 {
+        return Arc::as_ptr(&self.0) as u64;
+    }
+
+    // BirthTime gets the creation time of the host file, it is zero if the
+    // host filesystem doesn't keep it.
+    pub fn BirthTime(&self, task: &Task) -> Result<Time> {
+        use super::super::super::util::cstring::*;
+
+        let mut s: Statx = Default::default();
+        let hostfd = self.lock().HostFd;
+        let str = CString::New("");
+        let ret = IOURING.Statx(
+            task,
+            hostfd,
+            str.Ptr(),
+            &mut s as *mut _ as u64,
+            ATType::AT_EMPTY_PATH,
+            StatxMask::STATX_BTIME,
+        );
+
+        if ret < 0 {
+            return Err(Error::SysError(-ret as i32));
+        }
+
+        if s.stx_mask & StatxMask::STATX_BTIME == 0 {
+            return Ok(Time::default());
+        }
+
+        return Ok(Time::FromStatxTimestamp(&s.stx_btime));
+    }
+
     pub fn UpdateMaxLen(&self, size: i64) {
         let mut h = self.lock();
         if h.size < size {
@@ -603,12 +815,29 @@ impl HostInodeOp {
     pub fn GetHostFileOp(&self, _task: &Task) -> Arc<HostFileOp> {
         let hostFileOp = HostFileOp {
             InodeOp: self.clone(),
-            DirCursor: QMutex::new("".to_string()),
+            DirEntries: QMutex::new(None),
             //Buf: HostFileBuf::None,
         };
         return Arc::new(hostFileOp);
     }
 
+    // ChargeRange charges the pages of [start, end) that are allocated by a
+    // write or fallocate to the tmpfs quota of the file.
+    pub fn ChargeRange(&self, start: u64, end: u64) -> Result<()> {
+        match &mut self.lock().charge {
+            None => return Ok(()),
+            Some(charge) => return charge.Charge(start, end),
+        }
+    }
+
+    // UnchargeFrom releases the charge of the pages truncated from size on.
+    pub fn UnchargeFrom(&self, size: u64) {
+        match &mut self.lock().charge {
+            None => (),
+            Some(charge) => charge.Truncate(size),
+        }
+    }
+
     // return (st_size, st_blocks)
     pub fn Size(&self) -> Result<(i64, i64)> {
         let mut s: LibcStat = Default::default();
@@ -660,6 +889,13 @@ impl HostInodeOp {
         let hostIops = self.clone();
 
         let size = IoVec::NumBytes(dsts);
+        if self.ZeroCopyEnable(size, true) {
+            match self.ZeroCopyRead(task, dsts, offset)? {
+                Some(count) => return Ok(count),
+                None => (),
+            }
+        }
+
         let size = if size >= MemoryDef::HUGE_PAGE_SIZE as usize {
             MemoryDef::HUGE_PAGE_SIZE as usize
         } else {
@@ -741,17 +977,106 @@ impl HostInodeOp {
         return self.lock().BufWriteLock();
     }
 
+    // ZeroCopyEnable decides whether the io goes to the host uring with the
+    // pinned user pages. The reads of the regular files are served by the file
+    // mapping when MmapRead is set.
+    pub fn ZeroCopyEnable(&self, size: usize, read: bool) -> bool {
+        let config = SHARESPACE.config.read();
+        if !config.ZeroCopyIO || !config.UringIO || (read && config.MmapRead) {
+            return false;
+        }
+
+        return size >= ZERO_COPY_THRESHOLD && self.InodeType() == InodeType::RegularFile;
+    }
+
+    // ZeroCopyRead reads the host file into the pinned user pages. It returns
+    // None when the io has to fall back to the copy path, e.g. the user range is
+    // partially mapped or the host file doesn't support io_uring.
+    pub fn ZeroCopyRead(&self, task: &Task, dsts: &[IoVec], offset: i64) -> Result<Option<i64>> {
+        let mut iovs = Vec::new();
+        let _pinned = match task.PinIovs(dsts, true, &mut iovs) {
+            Err(_) => return Ok(None),
+            Ok(pinned) => pinned,
+        };
+
+        if iovs.len() == 0 || iovs.len() > UIO_MAXIOV {
+            return Ok(None);
+        }
+
+        if self.BufWriteEnable() {
+            // wait for the buffered writes ahead of the read
+            self.BufWriteLock().Lock(task);
+        }
+
+        let ret = IOURING.Readv(task, self.HostFd(), &iovs, offset);
+        if ret < 0 {
+            if ret as i32 == -SysErr::EINVAL {
+                return Ok(None);
+            }
+
+            return Err(Error::SysError(-ret as i32));
+        }
+
+        return Ok(Some(ret));
+    }
+
+    // ZeroCopyWrite writes the pinned user pages to the host file
+    pub fn ZeroCopyWrite(&self, task: &Task, srcs: &[IoVec], offset: i64) -> Result<Option<i64>> {
+        let mut iovs = Vec::new();
+        let _pinned = match task.PinIovs(srcs, false, &mut iovs) {
+            Err(_) => return Ok(None),
+            Ok(pinned) => pinned,
+        };
+
+        if iovs.len() == 0 || iovs.len() > UIO_MAXIOV {
+            return Ok(None);
+        }
+
+        // hold the lock so that the write is ordered after the buffered writes
+        let _lock = if self.BufWriteEnable() {
+            Some(self.BufWriteLock().Lock(task))
+        } else {
+            None
+        };
+
+        let ret = IOURING.Writev(task, self.HostFd(), &iovs, offset);
+        if ret < 0 {
+            if ret as i32 == -SysErr::EINVAL {
+                return Ok(None);
+            }
+
+            return Err(Error::SysError(-ret as i32));
+        }
+
+        self.UpdateMaxLen(offset + ret);
+        return Ok(Some(ret));
+    }
+
     pub fn WriteAt(
         &self,
         task: &Task,
-        _f: &File,
+        f: &File,
         srcs: &[IoVec],
         offset: i64,
         _blocking: bool,
     ) -> Result<i64> {
         let hostIops = self.clone();
 
+        // sync and freeze of the mount wait for the writes holding the barrier
+        let barrier = f.Dirent.Inode().lock().MountSource.lock().WriteBarrier();
+
         let size = IoVec::NumBytes(srcs);
+        if offset >= 0 {
+            self.ChargeRange(offset as u64, (offset as u64).saturating_add(size as u64))?;
+        }
+
+        if self.ZeroCopyEnable(size, false) {
+            let _barrier = barrier.Read(task);
+            match self.ZeroCopyWrite(task, srcs, offset)? {
+                Some(count) => return Ok(count),
+                None => (),
+            }
+        }
 
         let size = if size >= MemoryDef::HUGE_PAGE_SIZE as usize {
             MemoryDef::HUGE_PAGE_SIZE as usize
@@ -775,10 +1100,12 @@ impl HostInodeOp {
                 offset
             };
 
+            let barrier = barrier.Read(task);
             if SHARESPACE.config.read().UringIO {
                 let ret = if self.BufWriteEnable() {
                     let lock = self.BufWriteLock().Lock(task);
-                    let count = IOURING.BufFileWrite(hostIops.HostFd(), buf, offset, lock);
+                    let count =
+                        IOURING.BufFileWrite(hostIops.HostFd(), buf, offset, lock, barrier);
                     count
                 } else {
                     IOURING.Write(
@@ -833,9 +1160,14 @@ impl HostInodeOp {
             let len = task.CopyDataInFromIovs(&mut buf.buf, srcs, true)?;
             let iovs = buf.Iovs(len);
 
+            let end = hostIops.lock().size as u64;
+            hostIops.ChargeRange(end, end + len as u64)?;
+
             let iovsAddr = &iovs[0] as *const _ as u64;
             let iovcnt = 1;
 
+            let barrier = f.Dirent.Inode().lock().MountSource.lock().WriteBarrier();
+            let _barrier = barrier.Read(task);
             let (count, len) = HostSpace::IOAppend(hostIops.HostFd(), iovsAddr, iovcnt);
             if count < 0 {
                 return Err(Error::SysError(-count as i32));
@@ -867,8 +1199,7 @@ impl HostInodeOp {
             false
         };
 
-        let ret = if SHARESPACE.config.read().UringIO && self.InodeType() == InodeType::RegularFile
-        {
+        let ret = if self.UringEnable() {
             if self.BufWriteEnable() {
                 // try to gain the lock once, release immediately
                 self.BufWriteLock().Lock(task);
@@ -897,6 +1228,43 @@ impl HostInodeOp {
 
     /*********************************start of mappable****************************************************************/
 
+    // EnableExactMapping makes the file mappable only by the exact ranges set
+    // up through SetMmapContext, for the host devices whose memory can't be
+    // mapped by chunks
+    pub fn EnableExactMapping(&self) {
+        let mappable = Mappable::default();
+        mappable.lock().exact = true;
+        self.lock().mappable = Some(mappable);
+    }
+
+    pub fn ExactMapping(&self) -> bool {
+        match &self.lock().mappable {
+            None => return false,
+            Some(mappable) => return mappable.lock().exact,
+        }
+    }
+
+    // SetMmapContext sets the file range the next new mapping of an exact
+    // file must map
+    pub fn SetMmapContext(&self, offset: u64, len: u64) -> Result<()> {
+        if offset & MemoryDef::PAGE_MASK != 0 || len == 0 {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        let len = match Addr(len).RoundUp() {
+            Err(_) => return Err(Error::SysError(SysErr::EINVAL)),
+            Ok(len) => len.0,
+        };
+
+        let mappable = match &self.lock().mappable {
+            Some(mappable) if mappable.lock().exact => mappable.clone(),
+            _ => return Err(Error::SysError(SysErr::EINVAL)),
+        };
+
+        mappable.lock().mmapContext = Some(Range::New(offset, len));
+        return Ok(());
+    }
+
     //add mapping between file offset and the <MappingSpace(i.e. memorymanager), Virtual Address>
     pub fn AddMapping(
         &self,
@@ -918,11 +1286,25 @@ impl HostInodeOp {
             None
         };*/
 
-        let mappable = self.lock().Mappable();
+        let (mappable, fd, prot) = {
+            let intern = self.lock();
+            let prot = if intern.Writeable {
+                (MmapProt::PROT_WRITE | MmapProt::PROT_READ) as i32
+            } else {
+                MmapProt::PROT_READ as i32
+            };
+            (intern.mappable.clone().unwrap(), intern.HostFd, prot)
+        };
         let mut mappableLock = mappable.lock();
 
+        let fr = Range::New(offset, ar.Len());
+        if mappableLock.exact {
+            mappableLock.ExactIncrRefOn(fd, &fr, prot)?;
+        } else {
+            mappableLock.IncrRefOn(&fr);
+        }
+
         mappableLock.mapping.AddMapping(ms, ar, offset, writeable);
-        mappableLock.IncrRefOn(&Range::New(offset, ar.Len()));
         return Ok(());
     }
 
@@ -939,7 +1321,11 @@ impl HostInodeOp {
         mappableLock
             .mapping
             .RemoveMapping(ms, ar, offset, writeable);
-        mappableLock.DecrRefOn(&Range::New(offset, ar.Len()));
+        if mappableLock.exact {
+            mappableLock.ExactDecrRefOn(&Range::New(offset, ar.Len()));
+        } else {
+            mappableLock.DecrRefOn(&Range::New(offset, ar.Len()));
+        }
         return Ok(());
     }
 
@@ -969,6 +1355,10 @@ impl HostInodeOp {
         return self.lock().MapFilePage(task, fileOffset);
     }
 
+    pub fn ChunkMapped(&self, fileOffset: u64) -> bool {
+        return self.lock().ChunkMapped(fileOffset);
+    }
+
     pub fn MSync(&self, fr: &Range, msyncType: MSyncType) -> Result<()> {
         let ranges = self.GetPhyRanges(fr);
         for r in &ranges {
@@ -1030,6 +1420,23 @@ impl HostInodeOp {
         let mappable = self.lock().Mappable();
         let mappableLock = mappable.lock();
 
+        if mappableLock.exact {
+            let mut offset = fr.Start();
+            while offset < fr.End() {
+                match mappableLock.ExactMappingOf(offset) {
+                    None => offset += PAGE_SIZE,
+                    Some(start) => {
+                        let m = &mappableLock.exactMaps[&start];
+                        let end = core::cmp::min(fr.End(), start + m.len);
+                        rs.push(Range::New(m.phyAddr + offset - start, end - offset));
+                        offset = end;
+                    }
+                }
+            }
+
+            return rs;
+        }
+
         while chunkStart < fr.End() {
             match mappableLock.f2pmap.get(&chunkStart) {
                 None => (),
@@ -1218,6 +1625,7 @@ impl InodeOperations for HostInodeOp {
         newParent: &Inode,
         newname: &str,
         _replacement: bool,
+        flags: u32,
     ) -> Result<()> {
         let oldParent = match oldParent
             .lock()
@@ -1239,7 +1647,7 @@ impl InodeOperations for HostInodeOp {
             None => panic!("&InodeOp isn't a HostInodeOp!"),
         };
 
-        let ret = RenameAt(oldParent, oldname, newParent, newname);
+        let ret = RenameAt(oldParent, oldname, newParent, newname, flags);
 
         if ret < 0 {
             return Err(Error::SysError(-ret as i32));
@@ -1310,7 +1718,7 @@ impl InodeOperations for HostInodeOp {
                 str.Ptr(),
                 &mut s as *mut _ as u64,
                 ATType::AT_EMPTY_PATH,
-                StatxMask::STATX_BASIC_STATS,
+                StatxMask::STATX_BASIC_STATS | StatxMask::STATX_BTIME,
             );
 
             if ret < 0 {
@@ -1405,7 +1813,11 @@ impl InodeOperations for HostInodeOp {
     }
 
     fn Allocate(&self, task: &Task, _dir: &mut Inode, offset: i64, length: i64) -> Result<()> {
-        let ret = Fallocate(self.HostFd(), 0, offset, length);
+        let ret = if self.UringEnable() {
+            IOURING.Fallocate(task, self.HostFd(), 0, offset, length)
+        } else {
+            Fallocate(self.HostFd(), 0, offset, length)
+        };
 
         if ret < 0 {
             return Err(Error::SysError(-ret as i32));
@@ -1460,6 +1872,8 @@ impl InodeOperations for HostInodeOp {
         fsInfo.FreeBlocks = statfs.BlocksFree;
         fsInfo.TotalFiles = statfs.Files;
         fsInfo.FreeFiles = statfs.FilesFree;
+        fsInfo.BlockSize = statfs.BlockSize;
+        fsInfo.AvailBlocks = statfs.BlocksAvailable;
 
         return Ok(fsInfo);
     }
@@ -13,6 +13,7 @@
 // limitations under the License.
 
 pub mod dirent;
+pub mod dirnotify;
 pub mod fs;
 pub mod hostfileop;
 pub mod hostinodeop;
@@ -22,23 +23,41 @@ pub mod tty;
 pub mod util;
 //pub mod control;
 
-use alloc::collections::btree_map::BTreeMap;
 use alloc::string::String;
 use alloc::string::ToString;
 use core::any::Any;
 
 use self::hostinodeop::*;
 use super::super::super::auth::*;
+use super::super::super::config::HostFileCache;
+use super::super::super::lrc_cache::*;
 use super::dirent::*;
 use super::inode::*;
 use super::mount::*;
 
+// INODE_MAPPING_CACHE_SIZE is the max number of inode paths kept for the
+// file handles. The handles stay valid after the inode is released, so the
+// least recently saved paths are dropped instead, and their handles go stale.
+pub const INODE_MAPPING_CACHE_SIZE: u64 = 4096;
+
 pub struct SuperOperations {
     pub mountSourceOperations: SimpleMountSourceOperations,
     pub root: String,
-    pub inodeMapping: BTreeMap<u64, String>,
+    pub inodeMapping: LruCache<String>,
     pub mounter: FileOwner,
     pub dontTranslateOwnership: bool,
+    // handleNonce is put in the file handles of the mount source, so that only
+    // the handles given by name_to_handle_at are accepted
+    pub handleNonce: u64,
+    pub cacheMode: HostFileCache,
+}
+
+impl SuperOperations {
+    // InodePath returns the path, relative to the mount root, that the inode
+    // had when its file handle was made.
+    pub fn InodePath(&self, inodeId: u64) -> Option<String> {
+        return self.inodeMapping.Get(inodeId);
+    }
 }
 
 impl DirentOperations for SuperOperations {
@@ -66,7 +85,7 @@ impl MountSourceOperations for SuperOperations {
     fn Destroy(&mut self) {}
 
     fn ResetInodeMappings(&mut self) {
-        self.inodeMapping.clear();
+        self.inodeMapping.Clear();
     }
 
     fn SaveInodeMapping(&mut self, inode: &Inode, path: &str) {
@@ -77,7 +96,7 @@ impl MountSourceOperations for SuperOperations {
             .downcast_ref::<HostInodeOp>()
             .expect("ReadDirAll: not HostInodeOp")
             .StableAttr();
-        self.inodeMapping.insert(sattr.InodeId, path.to_string());
+        self.inodeMapping.Add(sattr.InodeId, path.to_string());
     }
 }
 
@@ -129,6 +148,7 @@ mod tests {
             &WhitelistFileSystem::New(),
             &MountSourceFlags::default(),
             false,
+            HostFileCache::default(),
         );
         let root = Inode::NewHostInode(&Arc::new(QMutex::new(ms)), fd)?;
 
@@ -25,7 +25,7 @@ use super::super::super::kernel::waiter::*;
 use super::super::super::quring::QUring;
 use super::super::super::task::*;
 use super::super::super::threadmgr::processgroup::*;
-use super::super::super::threadmgr::session::*;
+use super::super::super::threadmgr::task_usermem::*;
 use super::super::super::SHARESPACE;
 
 use super::super::attr::*;
@@ -34,6 +34,7 @@ use super::super::dirent::*;
 use super::super::file::*;
 use super::super::host::hostinodeop::*;
 use super::super::inode::*;
+use super::super::tty::job_control::*;
 use super::hostfileop::*;
 
 use super::ioctl::*;
@@ -372,51 +373,22 @@ impl KernelTermios {
 
     pub fn IsEOF(&self, c: u8) -> bool {
         return c == self.ControlCharacters[Self::VEOF as usize]
-            && self.ControlCharacters[Self::VEOF as usize] == DISABLED_CHAR;
+            && self.ControlCharacters[Self::VEOF as usize] != DISABLED_CHAR;
     }
 }
 
 pub struct TTYFileOpsInternal {
     pub fileOps: Arc<HostFileOp>,
     pub termios: KernelTermios,
-    pub session: Option<Session>,
-    pub fgProcessgroup: Option<ProcessGroup>,
+    pub jc: JobControl,
     pub fd: i32,
     pub buf: Arc<SocketBuff>,
     pub queue: Queue,
 }
 
 impl TTYFileOpsInternal {
-    fn checkChange(&self, _task: &Task, _sig: Signal) -> Result<()> {
-        return Ok(());
-        /*let thread = match &task.thread {
-            // No task? Linux does not have an analog for this case, but
-            // tty_check_change is more of a blacklist of cases than a
-            // whitelist, and is surprisingly permissive. Allowing the
-            // change seems most appropriate.
-            None => return Ok(()),
-            Some(ref t) => t.clone(),
-        };
-
-        let tg = thread.lock().tg.clone();
-        let pg = tg.ProcessGroup();
-
-        // If the session for the task is different than the session for the
-        // controlling TTY, then the change is allowed. Seems like a bad idea,
-        // but that's exactly what linux does.
-        if tg.Session() != self.fgProcessgroup.Session() {
-            return Ok(())
-        }
-
-        // If we are the foreground process group, then the change is allowed.
-        if pg == self.fgProcessgroup {
-            return Ok(())
-        }
-
-        // We are not the foreground process group.
-
-        // Is the provided signal blocked or ignored?
-        if (thread.SignalMask() & SignalSet::SignalSetOf(&sig).0 != 0 || tg.SignalHandlers())*/
+    fn checkChange(&self, task: &Task, sig: Signal) -> Result<()> {
+        return self.jc.CheckChange(task, sig);
     }
 }
 
@@ -440,8 +412,7 @@ impl TTYFileOps {
         let internal = TTYFileOpsInternal {
             fileOps: fops,
             termios: DEFAULT_SLAVE_TERMIOS,
-            session: None,
-            fgProcessgroup: None,
+            jc: JobControl::New(),
             fd: fd,
             buf: Arc::new(SocketBuff::Init(MemoryDef::DEFAULT_BUF_PAGE_COUNT)),
             queue: queue,
@@ -461,17 +432,11 @@ impl TTYFileOps {
     }
 
     pub fn InitForegroundProcessGroup(&self, pg: &ProcessGroup) {
-        let mut t = self.lock();
-        if t.fgProcessgroup.is_some() {
-            panic!("foreground process group is already set");
-        }
-
-        t.fgProcessgroup = Some(pg.clone());
-        t.session = Some(pg.Session());
+        self.lock().jc.InitForegroundProcessGroup(pg);
     }
 
     pub fn ForegroundProcessGroup(&self) -> Option<ProcessGroup> {
-        return self.lock().fgProcessgroup.clone();
+        return self.lock().jc.ForegroundProcessGroup();
     }
 }
 
@@ -644,82 +609,46 @@ impl FileOperations for TTYFileOps {
             IoCtlCmd::TCGETS => {
                 let mut term = Termios::default();
                 ioctlGetTermios(fd, &mut term)?;
-                task.CopyOutObj(&term, val)?;
+                UserPtr::New(val).Write(task, &term)?;
                 return Ok(());
             }
 
             IoCtlCmd::TCSETS | IoCtlCmd::TCSETSW | IoCtlCmd::TCSETSF => {
                 self.lock().checkChange(task, Signal(Signal::SIGTTOU))?;
 
-                let t: Termios = task.CopyInObj(val)?;
+                let t: Termios = UserPtr::New(val).Read(task)?;
                 ioctlSetTermios(fd, ioctl, &t)?;
                 self.lock().termios.FromTermios(&t);
                 return Ok(());
             }
             IoCtlCmd::TIOCGPGRP => {
-                let thread = task.Thread();
-                let tg = thread.ThreadGroup();
-                let pidns = tg.PIDNamespace();
-
-                let internal = self.lock();
-
-                let pgid = pidns.IDOfProcessGroup(internal.fgProcessgroup.as_ref().unwrap());
-                info!("TIOCGPGRP pgid is {}, val is {:x}", pgid, val);
-
-                task.CopyOutObj(&pgid, val)?;
-
-                return Ok(());
+                let jc = self.lock().jc.clone();
+                return jc.GetForegroundProcessGroup(task, val);
             }
             IoCtlCmd::TIOCSPGRP => {
-                let thread = match &task.thread {
-                    None => return Err(Error::SysError(SysErr::ENOTTY)),
-                    Some(ref t) => t.clone(),
-                };
-
-                let mut t = self.lock();
-                match t.checkChange(task, Signal(Signal::SIGTTOU)) {
-                    // drivers/tty/tty_io.c:tiocspgrp() converts -EIO from
-                    // tty_check_change() to -ENOTTY.
-                    Err(Error::SysError(SysErr::EIO)) => {
-                        return Err(Error::SysError(SysErr::ENOTTY))
-                    }
-                    Err(e) => return Err(e),
-                    Ok(()) => (),
-                }
-
-                let tg = thread.ThreadGroup();
-                let session = tg.Session();
-                if session != t.session {
-                    return Err(Error::SysError(SysErr::ENOTTY));
-                }
-
-                let pgid: i32 = task.CopyInObj(val)?;
-                if pgid < 0 {
-                    return Err(Error::SysError(SysErr::EINVAL));
-                }
-
-                let pidns = tg.PIDNamespace();
-                let pg = match pidns.ProcessGroupWithID(pgid) {
-                    None => return Err(Error::SysError(SysErr::ESRCH)),
-                    Some(pg) => pg,
-                };
-
-                // Check that new process group is in the TTY session.
-                if pg.Session() != t.session.clone().unwrap() {
-                    return Err(Error::SysError(SysErr::EPERM));
-                }
-
-                t.fgProcessgroup = Some(pg);
-                return Ok(());
+                let jc = self.lock().jc.clone();
+                return jc.SetForegroundProcessGroup(task, val);
+            }
+            IoCtlCmd::TIOCSCTTY => {
+                let jc = self.lock().jc.clone();
+                return jc.SetControllingTTY(task, val != 0);
+            }
+            IoCtlCmd::TIOCNOTTY => {
+                let jc = self.lock().jc.clone();
+                return jc.ReleaseControllingTTY(task);
+            }
+            IoCtlCmd::TIOCGSID => {
+                let jc = self.lock().jc.clone();
+                return jc.GetSessionID(task, val);
             }
             IoCtlCmd::TIOCGWINSZ => {
                 let mut win = Winsize::default();
                 ioctlGetWinsize(fd, &mut win)?;
-                task.CopyOutObj(&win, val)?;
+                UserPtr::New(val).Write(task, &win)?;
                 return Ok(());
             }
             IoCtlCmd::TIOCSWINSZ => {
-                let w: Winsize = task.CopyInObj(val)?;
+                let w: Winsize = UserPtr::New(val).Read(task)?;
                 return ioctlSetWinsize(fd, &w);
             }
             IoCtlCmd::TIOCSETD
@@ -733,9 +662,6 @@ impl FileOperations for TTYFileOps {
             | IoCtlCmd::TIOCEXCL
             | IoCtlCmd::TIOCNXCL
             | IoCtlCmd::TIOCGEXCL
-            | IoCtlCmd::TIOCNOTTY
-            | IoCtlCmd::TIOCSCTTY
-            | IoCtlCmd::TIOCGSID
             | IoCtlCmd::TIOCGETD
             | IoCtlCmd::TIOCVHANGUP
             | IoCtlCmd::TIOCGDEV
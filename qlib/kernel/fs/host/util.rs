@@ -123,6 +123,12 @@ impl Statx {
     }
 
     pub fn UnstableAttr(&self, mo: &Arc<QMutex<MountSourceOperations>>) -> UnstableAttr {
+        let birthTime = if self.stx_mask & StatxMask::STATX_BTIME != 0 {
+            Time::FromStatxTimestamp(&self.stx_btime)
+        } else {
+            Time::default()
+        };
+
         return UnstableAttr {
             Size: self.stx_size as i64,
             Usage: self.stx_blocks as i64 * 512,
@@ -131,6 +137,7 @@ impl Statx {
             AccessTime: Time::FromStatxTimestamp(&self.stx_atime),
             ModificationTime: Time::FromStatxTimestamp(&self.stx_mtime),
             StatusChangeTime: Time::FromStatxTimestamp(&self.stx_ctime),
+            BirthTime: birthTime,
             Links: self.stx_nlink as u64,
         };
     }
@@ -233,6 +240,7 @@ impl LibcStat {
             AccessTime: Time::FromUnix(self.st_atime, self.st_atime_nsec),
             ModificationTime: Time::FromUnix(self.st_mtime, self.st_mtime_nsec),
             StatusChangeTime: Time::FromUnix(self.st_ctime, self.st_ctime_nsec),
+            BirthTime: Time::default(),
             Links: self.st_nlink,
         };
     }
@@ -311,11 +319,11 @@ pub fn UnLinkAt(dirfd: i32, pathname: &str, flags: i32) -> i64 {
     return HostSpace::Unlinkat(dirfd, cstr.Ptr(), flags);
 }
 
-pub fn RenameAt(olddirfd: i32, oldpath: &str, newdirfd: i32, newpath: &str) -> i64 {
+pub fn RenameAt(olddirfd: i32, oldpath: &str, newdirfd: i32, newpath: &str, flags: u32) -> i64 {
     let oldpath = CString::New(oldpath);
     let newpath = CString::New(newpath);
 
-    return HostSpace::RenameAt(olddirfd, oldpath.Ptr(), newdirfd, newpath.Ptr());
+    return HostSpace::RenameAt(olddirfd, oldpath.Ptr(), newdirfd, newpath.Ptr(), flags);
 }
 
 pub fn Fchmod(fd: i32, mode: u32) -> i64 {
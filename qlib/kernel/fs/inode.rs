@@ -88,7 +88,9 @@ pub fn ContextCanAccessFile(task: &Task, inode: &Inode, reqPerms: &PermMask) ->
 pub enum IopsType {
     MockInodeOperations,
     FullDevice,
+    KmsgDevice,
     NullDevice,
+    PassthroughDevice,
     RandomDevice,
     TTYDevice,
     ZeroDevice,
@@ -163,6 +165,7 @@ pub trait InodeOperations: Sync + Send {
         newParent: &Inode,
         newname: &str,
         replacement: bool,
+        flags: u32,
     ) -> Result<()>;
     fn Bind(
         &self,
@@ -418,6 +421,7 @@ impl Inode {
         newParent: &Dirent,
         newname: &str,
         replacement: bool,
+        flags: u32,
     ) -> Result<()> {
         let isOverlay = self.lock().Overlay.is_some();
         if isOverlay {
@@ -430,6 +434,7 @@ impl Inode {
                 newParent,
                 newname,
                 replacement,
+                flags,
             );
         }
 
@@ -447,6 +452,7 @@ impl Inode {
             &newInode,
             newname,
             replacement,
+            flags,
         );
         return res;
     }
@@ -505,6 +511,31 @@ impl Inode {
         return res;
     }
 
+    // BirthTime returns the creation time of the inode, it is zero if the
+    // filesystem doesn't keep it. Only the host files are asked for it, the
+    // others keep it in their attributes.
+    pub fn BirthTime(&self, task: &Task) -> Result<Time> {
+        let overlay = self.lock().Overlay.clone();
+        if let Some(overlay) = overlay {
+            let layer = {
+                let o = overlay.read();
+                o.upper.clone().or(o.lower.clone())
+            };
+
+            return match layer {
+                None => Ok(Time::default()),
+                Some(layer) => layer.BirthTime(task),
+            };
+        }
+
+        let op = self.lock().InodeOp.clone();
+        if let Some(h) = op.as_any().downcast_ref::<HostInodeOp>() {
+            return h.BirthTime(task);
+        }
+
+        return Ok(op.UnstableAttr(task)?.BirthTime);
+    }
+
     pub fn Getxattr(&self, name: &str) -> Result<String> {
         let isOverlay = self.lock().Overlay.is_some();
         if isOverlay {
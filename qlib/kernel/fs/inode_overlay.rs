@@ -291,6 +291,7 @@ pub fn overlayRename(
     newParent: &Dirent,
     newName: &str,
     replacement: bool,
+    flags: u32,
 ) -> Result<()> {
     let renamedInode = renamed.Inode();
     let oldParentInode = oldParent.Inode();
@@ -302,6 +303,11 @@ pub fn overlayRename(
         return Err(Error::SysError(SysErr::EXDEV));
     }
 
+    // Exchanging would also have to swap whiteouts and copy up the replaced entry.
+    if flags & RenameFlags::RENAME_EXCHANGE != 0 {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
     let mut replacement = replacement;
 
     if replacement {
@@ -378,6 +384,7 @@ pub fn overlayRename(
         &newParentUpper,
         newName,
         replacement,
+        flags,
     )?;
 
     let lowerExists = renamedInode
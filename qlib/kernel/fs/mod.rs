@@ -21,9 +21,11 @@ pub mod attr;
 pub mod copy_up;
 pub mod dentry;
 pub mod dev;
+pub mod erofs;
 pub mod file_overlay;
 pub mod filesystems;
 pub mod flags;
+pub mod fs_context;
 pub mod fsutil;
 pub mod host;
 pub mod inode_overlay;
@@ -44,4 +46,5 @@ pub fn Init() {
     self::procfs::Init();
     self::sys::Init();
     self::tmpfs::Init();
+    self::erofs::Init();
 }
@@ -27,9 +27,12 @@ use core::sync::atomic::Ordering;
 use super::super::super::auth::userns::*;
 use super::super::super::auth::*;
 use super::super::super::common::*;
+use super::super::super::config::HostFileCache;
 use super::super::super::linux_def::*;
 use super::super::super::lrc_cache::*;
 use super::super::super::path::*;
+use super::super::kernel::waiter::qlock::*;
+use super::super::kernel_util::*;
 use super::super::task::*;
 use super::dirent::*;
 use super::filesystems::*;
@@ -275,6 +278,37 @@ impl MountNs {
         return ret;
     }
 
+    // SyncBarriers returns the sync barriers of the mounts in the namespace
+    pub fn SyncBarriers(&self) -> Vec<QAsyncRwLock> {
+        let mut ret = Vec::new();
+
+        for (_, mp) in self.mounts.lock().iter() {
+            if mp.lock().IsUndo() {
+                continue;
+            }
+
+            let msrc = mp.lock().root.Inode().lock().MountSource.clone();
+            ret.push(MountSource::SyncBarrier(&msrc));
+        }
+
+        return ret;
+    }
+
+    // MountSources returns the mount sources of the mounts in the namespace
+    pub fn MountSources(&self) -> Vec<Arc<QMutex<MountSource>>> {
+        let mut ret = Vec::new();
+
+        for (_, mp) in self.mounts.lock().iter() {
+            if mp.lock().IsUndo() {
+                continue;
+            }
+
+            ret.push(mp.lock().root.Inode().lock().MountSource.clone());
+        }
+
+        return ret;
+    }
+
     pub fn InitPath<'a>(
         &self,
         root: &Dirent,
@@ -575,20 +609,31 @@ const DEFAULT_DIRENT_CACHE_SIZE: u64 = 1024;
 //#[derive(Clone)]
 pub struct MountSource {
     pub FileSystemType: String,
+    // Source is the device or source string of the mount as passed to
+    // mount(2), "none" if there is none.
+    pub Source: String,
     pub Flags: MountSourceFlags,
     pub MountSourceOperations: Arc<QMutex<MountSourceOperations>>,
     pub fscache: LruCache<Dirent>,
+    // negcache bounds the negative dentries cached under the mount source
+    pub negcache: LruCache<NegativeDirent>,
     frozen: Vec<Dirent>,
+    // writeBarrier is held for read by the writes in flight to the mount, sync
+    // takes it for write to drain them and a freeze holds it to block new ones
+    pub writeBarrier: QAsyncRwLock,
 }
 
 impl Default for MountSource {
     fn default() -> Self {
         return Self {
             FileSystemType: "".to_string(),
+            Source: "none".to_string(),
             Flags: MountSourceFlags::default(),
             MountSourceOperations: Arc::new(QMutex::new(SimpleMountSourceOperations::default())),
             fscache: LruCache::New(DEFAULT_DIRENT_CACHE_SIZE),
+            negcache: LruCache::New(DEFAULT_DIRENT_CACHE_SIZE),
             frozen: Vec::new(),
+            writeBarrier: QAsyncRwLock::default(),
         };
     }
 }
@@ -611,6 +656,20 @@ impl DirentOperations for MountSource {
 }
 
 impl MountSource {
+    // FsMagic is the statfs(2) type of the filesystem of the mount, None for
+    // the host mounts which report the type of the host filesystem.
+    pub fn FsMagic(&self) -> Option<u64> {
+        match self.FileSystemType.as_str() {
+            "proc" => return Some(FSMagic::PROC_SUPER_MAGIC),
+            "sysfs" => return Some(FSMagic::SYSFS_MAGIC),
+            "tmpfs" | "devtmpfs" => return Some(FSMagic::TMPFS_MAGIC),
+            "devpts" => return Some(FSMagic::DEVPTS_SUPER_MAGIC),
+            "overlayfs" => return Some(FSMagic::OVERLAYFS_SUPER_MAGIC),
+            "erofs" => return Some(FSMagic::EROFS_SUPER_MAGIC_V1),
+            _ => return None,
+        }
+    }
+
     pub fn New(
         mops: &Arc<QMutex<MountSourceOperations>>,
         filesystem: &Filesystem,
@@ -625,9 +684,12 @@ impl MountSource {
         return Self {
             Flags: flags.clone(),
             FileSystemType: fsType.to_string(),
+            Source: "none".to_string(),
             MountSourceOperations: mops.clone(),
             fscache: LruCache::New(DEFAULT_DIRENT_CACHE_SIZE),
+            negcache: LruCache::New(DEFAULT_DIRENT_CACHE_SIZE),
             frozen: Vec::new(),
+            writeBarrier: QAsyncRwLock::default(),
         };
     }
 
@@ -640,9 +702,12 @@ impl MountSource {
         return Self {
             Flags: flags.clone(),
             FileSystemType: fsType.to_string(),
+            Source: "none".to_string(),
             MountSourceOperations: mops.clone(),
             fscache: LruCache::New(DEFAULT_DIRENT_CACHE_SIZE),
+            negcache: LruCache::New(DEFAULT_DIRENT_CACHE_SIZE),
             frozen: Vec::new(),
+            writeBarrier: QAsyncRwLock::default(),
         };
     }
 
@@ -655,9 +720,12 @@ impl MountSource {
         return Self {
             Flags: flags.clone(),
             FileSystemType: fsType.to_string(),
+            Source: "none".to_string(),
             MountSourceOperations: mops.clone(),
             fscache: LruCache::New(DEFAULT_DIRENT_CACHE_SIZE),
+            negcache: LruCache::New(DEFAULT_DIRENT_CACHE_SIZE),
             frozen: Vec::new(),
+            writeBarrier: QAsyncRwLock::default(),
         };
     }
 
@@ -667,13 +735,16 @@ impl MountSource {
         filesystem: &Filesystem,
         flags: &MountSourceFlags,
         dontTranslateOwnership: bool,
+        cacheMode: HostFileCache,
     ) -> Self {
         let mops = Arc::new(QMutex::new(SuperOperations {
             mountSourceOperations: Default::default(),
             root: root.to_string(),
-            inodeMapping: BTreeMap::new(),
+            inodeMapping: LruCache::New(INODE_MAPPING_CACHE_SIZE),
             mounter: mounter.clone(),
             dontTranslateOwnership: dontTranslateOwnership,
+            handleNonce: RandU64().expect("NewHostMountSource: get random fail"),
+            cacheMode: cacheMode,
         }));
 
         let fsType = filesystem.Name();
@@ -681,9 +752,12 @@ impl MountSource {
         return Self {
             Flags: flags.clone(),
             FileSystemType: fsType.to_string(),
+            Source: "none".to_string(),
             MountSourceOperations: mops,
             fscache: LruCache::New(DEFAULT_DIRENT_CACHE_SIZE),
+            negcache: LruCache::New(DEFAULT_DIRENT_CACHE_SIZE),
             frozen: Vec::new(),
+            writeBarrier: QAsyncRwLock::default(),
         };
     }
 
@@ -699,9 +773,12 @@ impl MountSource {
         return Self {
             Flags: flags.clone(),
             FileSystemType: fsType.to_string(),
+            Source: "none".to_string(),
             MountSourceOperations: mops,
             fscache: LruCache::New(DEFAULT_DIRENT_CACHE_SIZE),
+            negcache: LruCache::New(DEFAULT_DIRENT_CACHE_SIZE),
             frozen: Vec::new(),
+            writeBarrier: QAsyncRwLock::default(),
         };
     }
 
@@ -717,9 +794,12 @@ impl MountSource {
         return Self {
             Flags: flags.clone(),
             FileSystemType: fsType.to_string(),
+            Source: "none".to_string(),
             MountSourceOperations: mops,
             fscache: LruCache::New(DEFAULT_DIRENT_CACHE_SIZE),
+            negcache: LruCache::New(DEFAULT_DIRENT_CACHE_SIZE),
             frozen: Vec::new(),
+            writeBarrier: QAsyncRwLock::default(),
         };
     }
 
@@ -735,9 +815,12 @@ impl MountSource {
         return Self {
             Flags: flags.clone(),
             FileSystemType: fsType.to_string(),
+            Source: "none".to_string(),
             MountSourceOperations: mops,
             fscache: LruCache::New(DEFAULT_DIRENT_CACHE_SIZE),
+            negcache: LruCache::New(DEFAULT_DIRENT_CACHE_SIZE),
             frozen: Vec::new(),
+            writeBarrier: QAsyncRwLock::default(),
         };
     }
 
@@ -751,9 +834,12 @@ impl MountSource {
         return Self {
             Flags: MountSourceFlags::default(),
             FileSystemType: "".to_string(),
+            Source: "none".to_string(),
             MountSourceOperations: mops,
             fscache: LruCache::New(DEFAULT_DIRENT_CACHE_SIZE),
+            negcache: LruCache::New(DEFAULT_DIRENT_CACHE_SIZE),
             frozen: Vec::new(),
+            writeBarrier: QAsyncRwLock::default(),
         };
     }
 
@@ -768,7 +854,7 @@ impl MountSource {
 
     pub fn ExtendReference(&mut self, dirent: &Dirent) {
         let id = dirent.ID();
-        self.fscache.Add(id, dirent.clone())
+        self.fscache.Add(id, dirent.clone());
     }
 
     pub fn DropExtendReference(&mut self, dirent: &Dirent) {
@@ -776,9 +862,45 @@ impl MountSource {
         self.fscache.Remove(id);
     }
 
+    // AddNegative caches a negative dentry and returns the one evicted for it.
+    pub fn AddNegative(&mut self, key: u64, negative: NegativeDirent) -> Option<NegativeDirent> {
+        return self.negcache.Add(key, negative);
+    }
+
+    pub fn TouchNegative(&mut self, key: u64) -> bool {
+        return self.negcache.Touch(key);
+    }
+
+    pub fn DropNegative(&mut self, key: u64) {
+        self.negcache.Remove(key);
+    }
+
     pub fn Froze(&mut self, dirent: &Dirent) {
         self.frozen.push(dirent.clone());
     }
+
+    pub fn WriteBarrier(&self) -> QAsyncRwLock {
+        return self.writeBarrier.clone();
+    }
+
+    // SyncBarrier returns the write barrier that a sync of the mount source
+    // drains. The writes to an overlay go to its upper layer.
+    pub fn SyncBarrier(msrc: &Arc<QMutex<MountSource>>) -> QAsyncRwLock {
+        let mops = msrc.lock().MountSourceOperations.clone();
+        let upper = match mops
+            .lock()
+            .as_any()
+            .downcast_ref::<OverlayMountSourceOperations>()
+        {
+            None => None,
+            Some(o) => Some(o.upper.clone()),
+        };
+
+        match upper {
+            None => return msrc.lock().WriteBarrier(),
+            Some(upper) => return Self::SyncBarrier(&upper),
+        }
+    }
 }
 
 pub trait DirentOperations {
@@ -845,6 +967,12 @@ pub struct FsInfo {
 
     // FreeFiles is the number of free file nodes.
     pub FreeFiles: u64,
+
+    // BlockSize is the block size of the filesystem, 0 is the page size.
+    pub BlockSize: i64,
+
+    // AvailBlocks is the number of free blocks for the unprivileged users.
+    pub AvailBlocks: u64,
 }
 
 #[cfg(test1)]
@@ -1170,6 +1298,7 @@ mod tests {
             _newParent: &Inode,
             _newname: &str,
             _replacement: bool,
+            _flags: u32,
         ) -> Result<()> {
             self.lock().renameCalled = true;
             return Ok(());
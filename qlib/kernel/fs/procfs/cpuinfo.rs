@@ -15,21 +15,71 @@
 use crate::qlib::mutex::*;
 use alloc::string::ToString;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 
+use super::super::super::super::auth::*;
+use super::super::super::super::common::*;
+use super::super::super::super::linux_def::*;
 use super::super::super::kernel::kernel::*;
 use super::super::super::task::*;
+use super::super::attr::*;
+use super::super::dirent::*;
+use super::super::file::*;
+use super::super::flags::*;
+use super::super::fsutil::file::readonly_file::*;
+use super::super::fsutil::inode::simple_file_inode::*;
 use super::super::inode::*;
 use super::super::mount::*;
 use super::inode::*;
 
 pub fn NewCPUInfo(task: &Task, msrc: &Arc<QMutex<MountSource>>) -> Inode {
-    let kernel = GetKernel();
-    let features = kernel.featureSet.clone();
+    let v = NewCPUInfoSimpleFileInode(
+        task,
+        &ROOT_OWNER,
+        &FilePermissions::FromMode(FileMode(0o444)),
+        FSMagic::PROC_SUPER_MAGIC,
+    );
+    return NewProcInode(&Arc::new(v), msrc, InodeType::SpecialFile, None);
+}
+
+pub fn NewCPUInfoSimpleFileInode(
+    task: &Task,
+    owner: &FileOwner,
+    perms: &FilePermissions,
+    typ: u64,
+) -> SimpleFileInode<CPUInfoData> {
+    let fs = CPUInfoData { k: GetKernel() };
+    return SimpleFileInode::New(task, owner, perms, typ, false, fs);
+}
+
+// CPUInfoData lists the online cpus, which change with the vcpu autoscaling
+pub struct CPUInfoData {
+    pub k: Kernel,
+}
 
-    let mut cpuInfo = "".to_string();
-    for i in 0..kernel.applicationCores {
-        cpuInfo += &features.lock().CPUInfo(i as u32);
+impl CPUInfoData {
+    pub fn GenSnapshot(&self, _task: &Task) -> Vec<u8> {
+        let features = self.k.featureSet.clone();
+
+        let mut cpuInfo = "".to_string();
+        for i in 0..self.k.OnlineCores() {
+            cpuInfo += &features.lock().CPUInfo(i as u32);
+        }
+
+        return cpuInfo.as_bytes().to_vec();
     }
+}
 
-    return NewStaticProcInode(task, msrc, &Arc::new(cpuInfo.as_bytes().to_vec()));
+impl SimpleFileTrait for CPUInfoData {
+    fn GetFile(
+        &self,
+        task: &Task,
+        _dir: &Inode,
+        dirent: &Dirent,
+        flags: FileFlags,
+    ) -> Result<File> {
+        let fops = NewSnapshotReadonlyFileOperations(self.GenSnapshot(task));
+        let file = File::New(dirent, &flags, fops);
+        return Ok(file);
+    }
 }
@@ -141,6 +141,7 @@ impl<T: 'static + DirDataNode> InodeOperations for DirNode<T> {
         newParent: &Inode,
         newname: &str,
         replacement: bool,
+        flags: u32,
     ) -> Result<()> {
         return self.dir.Rename(
             task,
@@ -150,6 +151,7 @@ impl<T: 'static + DirDataNode> InodeOperations for DirNode<T> {
             newParent,
             newname,
             replacement,
+            flags,
         );
     }
 
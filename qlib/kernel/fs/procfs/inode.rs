@@ -130,6 +130,7 @@ impl InodeOperations for TaskOwnedInodeOps {
         newParent: &Inode,
         newname: &str,
         replacement: bool,
+        flags: u32,
     ) -> Result<()> {
         return self.iops.Rename(
             task,
@@ -139,6 +140,7 @@ impl InodeOperations for TaskOwnedInodeOps {
             newParent,
             newname,
             replacement,
+            flags,
         );
     }
 
@@ -351,6 +353,7 @@ impl InodeOperations for StaticFileInodeOps {
         _newParent: &Inode,
         _newname: &str,
         _replacement: bool,
+        _flags: u32,
     ) -> Result<()> {
         return Err(Error::SysError(SysErr::EINVAL));
     }
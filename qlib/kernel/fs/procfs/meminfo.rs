@@ -26,6 +26,7 @@ use super::super::mount::*;
 use super::super::super::super::auth::*;
 use super::super::super::super::common::*;
 use super::super::super::super::linux_def::*;
+use super::super::super::memmgr::overcommit::*;
 use super::super::super::task::*;
 use super::super::super::Kernel::HostSpace;
 use super::super::fsutil::file::readonly_file::*;
@@ -79,6 +80,11 @@ impl ReadonlyFileNode for MeminfoFileNode {
         s += &format!("AnonPages:      {:>8} kB\n", info.totalram / 100 / 1024);
         s += &format!("Mapped:         {:>8} kB\n", info.totalram / 100 / 1024);
         s += &format!("Shmem:                 0 kB\n");
+        s += &format!(
+            "CommitLimit:    {:>8} kB\n",
+            OVERCOMMIT.CommitLimit() / 1024
+        );
+        s += &format!("Committed_AS:   {:>8} kB\n", OVERCOMMIT.Committed() / 1024);
 
         // it always change 0 to 2, that's weird
         //s += &format!("Shmem:          {} kB\n", 0);
@@ -25,6 +25,7 @@ pub mod filesystems;
 pub mod loadavg;
 pub mod meminfo;
 pub mod mounts;
+pub mod net;
 pub mod stat;
 pub mod sys;
 pub mod uptime;
@@ -0,0 +1,137 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::qlib::mutex::*;
+use alloc::collections::btree_map::BTreeMap;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use super::super::super::super::auth::*;
+use super::super::super::super::common::*;
+use super::super::super::super::linux_def::*;
+use super::super::super::socket::netdevice::*;
+use super::super::super::task::*;
+use super::super::attr::*;
+use super::super::dirent::*;
+use super::super::file::*;
+use super::super::flags::*;
+use super::super::fsutil::file::readonly_file::*;
+use super::super::fsutil::inode::simple_file_inode::*;
+use super::super::inode::*;
+use super::super::mount::*;
+use super::super::ramfs::dir::*;
+use super::dir_proc::*;
+use super::inode::*;
+
+// ProcNetDirNode represents a /proc/net directory.
+pub struct ProcNetDirNode {}
+
+impl DirDataNode for ProcNetDirNode {
+    fn Lookup(&self, d: &Dir, task: &Task, dir: &Inode, name: &str) -> Result<Dirent> {
+        return d.Lookup(task, dir, name);
+    }
+
+    fn GetFile(
+        &self,
+        d: &Dir,
+        task: &Task,
+        dir: &Inode,
+        dirent: &Dirent,
+        flags: FileFlags,
+    ) -> Result<File> {
+        return d.GetFile(task, dir, dirent, flags);
+    }
+}
+
+pub fn NewNet(task: &Task, msrc: &Arc<QMutex<MountSource>>) -> Inode {
+    let mut contents = BTreeMap::new();
+    contents.insert("dev".to_string(), NewNetDev(task, msrc));
+
+    let netDir = DirNode {
+        dir: Dir::New(
+            task,
+            contents,
+            &ROOT_OWNER,
+            &FilePermissions::FromMode(FileMode(0o0555)),
+        ),
+        data: ProcNetDirNode {},
+    };
+
+    return NewProcInode(&Arc::new(netDir), msrc, InodeType::SpecialDirectory, None);
+}
+
+pub fn NewNetDev(task: &Task, msrc: &Arc<QMutex<MountSource>>) -> Inode {
+    let v = SimpleFileInode::New(
+        task,
+        &ROOT_OWNER,
+        &FilePermissions::FromMode(FileMode(0o444)),
+        FSMagic::PROC_SUPER_MAGIC,
+        false,
+        NetDevData {},
+    );
+    return NewProcInode(&Arc::new(v), msrc, InodeType::SpecialFile, None);
+}
+
+// NetDevData is /proc/net/dev, the counters of the network devices.
+pub struct NetDevData {}
+
+impl NetDevData {
+    pub fn GenSnapshot(&self, _task: &Task) -> Vec<u8> {
+        let mut ret = String::new();
+        ret += "Inter-|   Receive                                                |  Transmit\n";
+        ret += " face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed\n";
+
+        for dev in NetDevices() {
+            let s = dev.stats;
+            ret += &format!(
+                "{:>6}: {:>7} {:>7} {:>4} {:>4} {:>4} {:>5} {:>10} {:>9} {:>8} {:>7} {:>4} {:>4} {:>4} {:>5} {:>7} {:>10}\n",
+                dev.name,
+                s.rxBytes,
+                s.rxPackets,
+                s.rxErrs,
+                s.rxDrop,
+                s.rxFifo,
+                s.rxFrame,
+                s.rxCompressed,
+                s.rxMulticast,
+                s.txBytes,
+                s.txPackets,
+                s.txErrs,
+                s.txDrop,
+                s.txFifo,
+                s.txColls,
+                s.txCarrier,
+                s.txCompressed
+            );
+        }
+
+        return ret.as_bytes().to_vec();
+    }
+}
+
+impl SimpleFileTrait for NetDevData {
+    fn GetFile(
+        &self,
+        task: &Task,
+        _dir: &Inode,
+        dirent: &Dirent,
+        flags: FileFlags,
+    ) -> Result<File> {
+        let fops = NewSnapshotReadonlyFileOperations(self.GenSnapshot(task));
+        let file = File::New(dirent, &flags, fops);
+        return Ok(file);
+    }
+}
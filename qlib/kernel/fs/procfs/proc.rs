@@ -51,6 +51,7 @@ use super::filesystems::*;
 use super::loadavg::*;
 use super::meminfo::*;
 use super::mounts::*;
+use super::net::*;
 use super::stat::*;
 use super::uptime::*;
 
@@ -127,6 +128,7 @@ pub fn NewProc(
     contents.insert("filesystems".to_string(), NewFileSystem(task, msrc));
     contents.insert("loadavg".to_string(), NewLoadAvg(task, msrc));
     contents.insert("mounts".to_string(), NewMounts(task, msrc));
+    contents.insert("net".to_string(), NewNet(task, msrc));
     contents.insert("self".to_string(), NewProcessSelf(task, &pidns, msrc));
     contents.insert("stat".to_string(), NewStatData(task, msrc));
     contents.insert("thread-self".to_string(), NewThreadSelf(task, &pidns, msrc));
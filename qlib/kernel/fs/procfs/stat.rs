@@ -123,7 +123,7 @@ impl StatData {
             "todo: fix self.k.ApplicationCores() is {}",
             self.k.ApplicationCores()
         );
-        let cores = self.k.applicationCores;
+        let cores = self.k.OnlineCores();
         for i in 0..cores as usize {
             buf += &format!("cpu{} {}\n", i, cpu.ToString());
         }
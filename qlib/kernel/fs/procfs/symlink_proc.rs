@@ -159,6 +159,7 @@ impl<T: 'static + ReadLinkNode> InodeOperations for SymlinkNode<T> {
         newParent: &Inode,
         newname: &str,
         replacement: bool,
+        flags: u32,
     ) -> Result<()> {
         return self.link.Rename(
             task,
@@ -168,6 +169,7 @@ impl<T: 'static + ReadLinkNode> InodeOperations for SymlinkNode<T> {
             newParent,
             newname,
             replacement,
+            flags,
         );
     }
 
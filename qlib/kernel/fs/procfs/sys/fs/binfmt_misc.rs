@@ -0,0 +1,223 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::qlib::mutex::*;
+use alloc::collections::btree_map::BTreeMap;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use super::super::super::super::super::super::auth::*;
+use super::super::super::super::super::super::common::*;
+use super::super::super::super::super::super::linux_def::*;
+use super::super::super::super::super::loader::binfmt;
+use super::super::super::super::super::loader::binfmt::*;
+use super::super::super::super::super::task::*;
+use super::super::super::super::attr::*;
+use super::super::super::super::dirent::*;
+use super::super::super::super::file::*;
+use super::super::super::super::flags::*;
+use super::super::super::super::fsutil::file::readonly_file::*;
+use super::super::super::super::fsutil::inode::simple_file_inode::*;
+use super::super::super::super::inode::*;
+use super::super::super::super::mount::*;
+use super::super::super::super::ramfs::dir::*;
+use super::super::super::dir_proc::*;
+use super::super::super::inode::*;
+
+const REGISTER: &str = "register";
+const STATUS: &str = "status";
+
+// BinfmtMiscDirNode is /proc/sys/fs/binfmt_misc. The handler files follow
+// the registry, they are added and removed when the directory is used.
+pub struct BinfmtMiscDirNode {}
+
+impl BinfmtMiscDirNode {
+    fn Sync(&self, d: &Dir, task: &Task, dir: &Inode) {
+        let names = BINFMT_MISC.lock().Names();
+        let children = d.Children();
+
+        for (name, _) in &children {
+            if name == REGISTER || name == STATUS || names.contains(name) {
+                continue;
+            }
+
+            d.write().removeChild(task, name).ok();
+        }
+
+        let msrc = dir.lock().MountSource.clone();
+        for name in names {
+            if children.contains_key(&name) {
+                continue;
+            }
+
+            let mut inode =
+                NewBinfmtFile(task, BinfmtFile::Handler(name.to_string()), 0o644, &msrc);
+            d.AddChild(task, &name, &mut inode);
+        }
+    }
+}
+
+impl DirDataNode for BinfmtMiscDirNode {
+    fn Lookup(&self, d: &Dir, task: &Task, dir: &Inode, name: &str) -> Result<Dirent> {
+        self.Sync(d, task, dir);
+        return d.Lookup(task, dir, name);
+    }
+
+    fn GetFile(
+        &self,
+        d: &Dir,
+        task: &Task,
+        dir: &Inode,
+        dirent: &Dirent,
+        flags: FileFlags,
+    ) -> Result<File> {
+        self.Sync(d, task, dir);
+        return d.GetFile(task, dir, dirent, flags);
+    }
+}
+
+pub fn NewBinfmtMisc(task: &Task, msrc: &Arc<QMutex<MountSource>>) -> Inode {
+    let mut contents = BTreeMap::new();
+    contents.insert(
+        REGISTER.to_string(),
+        NewBinfmtFile(task, BinfmtFile::Register, 0o200, msrc),
+    );
+    contents.insert(
+        STATUS.to_string(),
+        NewBinfmtFile(task, BinfmtFile::Status, 0o644, msrc),
+    );
+
+    let dir = DirNode {
+        dir: Dir::New(
+            task,
+            contents,
+            &ROOT_OWNER,
+            &FilePermissions::FromMode(FileMode(0o0755)),
+        ),
+        data: BinfmtMiscDirNode {},
+    };
+
+    return NewProcInode(&Arc::new(dir), msrc, InodeType::SpecialDirectory, None);
+}
+
+fn NewBinfmtFile(
+    task: &Task,
+    file: BinfmtFile,
+    mode: u16,
+    msrc: &Arc<QMutex<MountSource>>,
+) -> Inode {
+    let iops = SimpleFileInode::New(
+        task,
+        &ROOT_OWNER,
+        &FilePermissions::FromMode(FileMode(mode)),
+        FSMagic::PROC_SUPER_MAGIC,
+        false,
+        file,
+    );
+
+    return NewProcInode(&Arc::new(iops), msrc, InodeType::SpecialFile, None);
+}
+
+#[derive(Clone)]
+pub enum BinfmtFile {
+    Register,
+    Status,
+    Handler(String),
+}
+
+impl SimpleFileTrait for BinfmtFile {
+    fn GetFile(
+        &self,
+        _task: &Task,
+        _dir: &Inode,
+        dirent: &Dirent,
+        flags: FileFlags,
+    ) -> Result<File> {
+        let fops = ReadonlyFileOperations { node: self.clone() };
+        return Ok(File::New(dirent, &flags, fops));
+    }
+}
+
+impl ReadonlyFileNode for BinfmtFile {
+    fn ReadAt(
+        &self,
+        task: &Task,
+        _f: &File,
+        dsts: &mut [IoVec],
+        offset: i64,
+        _blocking: bool,
+    ) -> Result<i64> {
+        if offset < 0 {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        let buf = match self {
+            BinfmtFile::Register => return Err(Error::SysError(SysErr::EINVAL)),
+            BinfmtFile::Status => {
+                if BINFMT_MISC.lock().enabled {
+                    "enabled\n".to_string()
+                } else {
+                    "disabled\n".to_string()
+                }
+            }
+            BinfmtFile::Handler(name) => match BINFMT_MISC.lock().Find(name) {
+                None => return Err(Error::SysError(SysErr::ENOENT)),
+                Some(e) => e.Status(),
+            },
+        };
+
+        if offset as usize > buf.len() {
+            return Ok(0);
+        }
+
+        let n = task.CopyDataOutToIovs(&buf.as_bytes()[offset as usize..], dsts, true)?;
+        return Ok(n as i64);
+    }
+
+    fn WriteAt(
+        &self,
+        task: &Task,
+        _f: &File,
+        srcs: &[IoVec],
+        _offset: i64,
+        _blocking: bool,
+    ) -> Result<i64> {
+        if !task.Creds().HasCapability(Capability::CAP_SYS_ADMIN) {
+            return Err(Error::SysError(SysErr::EPERM));
+        }
+
+        let size = IoVec::NumBytes(srcs);
+        if size > BINFMT_MAX_REGISTER {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        let mut buf: Vec<u8> = Vec::with_capacity(size);
+        buf.resize(size, 0);
+        let n = task.CopyDataInFromIovs(&mut buf, srcs, true)?;
+        let data = match core::str::from_utf8(&buf[..n]) {
+            Ok(s) => s,
+            Err(_) => return Err(Error::SysError(SysErr::EINVAL)),
+        };
+
+        match self {
+            BinfmtFile::Register => binfmt::Register(task, data)?,
+            BinfmtFile::Status => binfmt::Control(None, data)?,
+            BinfmtFile::Handler(name) => binfmt::Control(Some(name), data)?,
+        }
+
+        return Ok(n as i64);
+    }
+}
@@ -0,0 +1,47 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::qlib::mutex::*;
+use alloc::collections::btree_map::BTreeMap;
+use alloc::string::ToString;
+use alloc::sync::Arc;
+
+use super::super::super::super::super::super::auth::*;
+use super::super::super::super::super::super::linux_def::*;
+use super::super::super::super::super::task::*;
+use super::super::super::super::attr::*;
+use super::super::super::super::inode::*;
+use super::super::super::super::mount::*;
+use super::super::super::super::ramfs::dir::*;
+use super::super::super::dir_proc::*;
+use super::super::super::inode::*;
+use super::super::sys::*;
+use super::binfmt_misc::*;
+
+pub fn NewFs(task: &Task, msrc: &Arc<QMutex<MountSource>>) -> Inode {
+    let mut contents = BTreeMap::new();
+    contents.insert("binfmt_misc".to_string(), NewBinfmtMisc(task, msrc));
+
+    let taskDir = DirNode {
+        dir: Dir::New(
+            task,
+            contents,
+            &ROOT_OWNER,
+            &FilePermissions::FromMode(FileMode(0o0555)),
+        ),
+        data: ProcSysDirNode {},
+    };
+
+    return NewProcInode(&Arc::new(taskDir), msrc, InodeType::SpecialDirectory, None);
+}
@@ -0,0 +1,131 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::qlib::mutex::*;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::Ordering;
+
+use super::super::super::super::super::super::auth::*;
+use super::super::super::super::super::super::common::*;
+use super::super::super::super::super::super::linux_def::*;
+use super::super::super::super::super::kernel::kmsg::*;
+use super::super::super::super::super::task::*;
+use super::super::super::super::attr::*;
+use super::super::super::super::dirent::*;
+use super::super::super::super::file::*;
+use super::super::super::super::flags::*;
+use super::super::super::super::fsutil::file::readonly_file::*;
+use super::super::super::super::fsutil::inode::simple_file_inode::*;
+use super::super::super::super::inode::*;
+use super::super::super::super::mount::*;
+use super::super::super::inode::*;
+
+pub fn NewDmesgRestrict(task: &Task, msrc: &Arc<QMutex<MountSource>>) -> Inode {
+    let v = SimpleFileInode::New(
+        task,
+        &ROOT_OWNER,
+        &FilePermissions::FromMode(FileMode(0o644)),
+        FSMagic::PROC_SUPER_MAGIC,
+        false,
+        DmesgRestrictSimpleFileTrait {},
+    );
+    return NewProcInode(&Arc::new(v), msrc, InodeType::SpecialFile, None);
+}
+
+pub struct DmesgRestrictSimpleFileTrait {}
+
+impl SimpleFileTrait for DmesgRestrictSimpleFileTrait {
+    fn GetFile(
+        &self,
+        _task: &Task,
+        _dir: &Inode,
+        dirent: &Dirent,
+        flags: FileFlags,
+    ) -> Result<File> {
+        let restrict = DMESG_RESTRICT.load(Ordering::Relaxed) as i32;
+        let fops = ReadonlyFileOperations {
+            node: DmesgRestrictData {
+                data: format!("{}\n", restrict).into_bytes(),
+            },
+        };
+        return Ok(File::New(dirent, &flags, fops));
+    }
+}
+
+// DmesgRestrictData is kernel.dmesg_restrict, only a task with CAP_SYS_ADMIN
+// can change it as Linux.
+pub struct DmesgRestrictData {
+    pub data: Vec<u8>,
+}
+
+impl ReadonlyFileNode for DmesgRestrictData {
+    fn ReadAt(
+        &self,
+        task: &Task,
+        _f: &File,
+        dsts: &mut [IoVec],
+        offset: i64,
+        _blocking: bool,
+    ) -> Result<i64> {
+        if offset < 0 {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        if offset as usize > self.data.len() {
+            return Ok(0);
+        }
+
+        let n = task.CopyDataOutToIovs(&self.data[offset as usize..], dsts, true)?;
+        return Ok(n as i64);
+    }
+
+    fn WriteAt(
+        &self,
+        task: &Task,
+        _f: &File,
+        srcs: &[IoVec],
+        offset: i64,
+        _blocking: bool,
+    ) -> Result<i64> {
+        if !task.Creds().HasCapability(Capability::CAP_SYS_ADMIN) {
+            return Err(Error::SysError(SysErr::EPERM));
+        }
+
+        // as Linux, a write past the start of the file is ignored
+        let size = IoVec::NumBytes(srcs);
+        if offset != 0 || size == 0 {
+            return Ok(size as i64);
+        }
+
+        let size = if size > 24 { 24 } else { size };
+        let mut buf: Vec<u8> = Vec::with_capacity(size);
+        buf.resize(size, 0);
+        let n = task.CopyDataInFromIovs(&mut buf, srcs, true)?;
+        let v = match core::str::from_utf8(&buf[..n]) {
+            Ok(s) => match s.trim().parse::<i64>() {
+                Ok(v) => v,
+                Err(_) => return Err(Error::SysError(SysErr::EINVAL)),
+            },
+            Err(_) => return Err(Error::SysError(SysErr::EINVAL)),
+        };
+
+        if v != 0 && v != 1 {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        DMESG_RESTRICT.store(v == 1, Ordering::Relaxed);
+        return Ok(n as i64);
+    }
+}
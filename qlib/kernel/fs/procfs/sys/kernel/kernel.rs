@@ -0,0 +1,115 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::qlib::mutex::*;
+use alloc::collections::btree_map::BTreeMap;
+use alloc::string::ToString;
+use alloc::sync::Arc;
+
+use super::super::super::super::super::super::auth::*;
+use super::super::super::super::super::super::common::*;
+use super::super::super::super::super::super::linux_def::*;
+use super::super::super::super::super::task::*;
+use super::super::super::super::attr::*;
+use super::super::super::super::dirent::*;
+use super::super::super::super::file::*;
+use super::super::super::super::flags::*;
+use super::super::super::super::fsutil::file::readonly_file::*;
+use super::super::super::super::fsutil::inode::simple_file_inode::*;
+use super::super::super::super::inode::*;
+use super::super::super::super::mount::*;
+use super::super::super::super::ramfs::dir::*;
+use super::super::super::dir_proc::*;
+use super::super::super::inode::*;
+use super::dmesg_restrict::*;
+use super::random::*;
+
+// ProcSysDirNode represents a /proc/sys directory.
+pub struct ProcSysDirNode {}
+
+impl DirDataNode for ProcSysDirNode {
+    fn Lookup(&self, d: &Dir, task: &Task, dir: &Inode, name: &str) -> Result<Dirent> {
+        return d.Lookup(task, dir, name);
+    }
+
+    fn GetFile(
+        &self,
+        d: &Dir,
+        task: &Task,
+        dir: &Inode,
+        dirent: &Dirent,
+        flags: FileFlags,
+    ) -> Result<File> {
+        return d.GetFile(task, dir, dirent, flags);
+    }
+}
+
+// UTSData is hostname or domainname of the uts namespace of the reader
+pub struct UTSData {
+    pub domain: bool,
+}
+
+impl SimpleFileTrait for UTSData {
+    fn GetFile(
+        &self,
+        task: &Task,
+        _dir: &Inode,
+        dirent: &Dirent,
+        flags: FileFlags,
+    ) -> Result<File> {
+        let utsns = task.Thread().UTSNamespace();
+        let name = if self.domain {
+            utsns.DomainName()
+        } else {
+            utsns.HostName()
+        };
+
+        let data = format!("{}\n", name);
+        let fops = NewSnapshotReadonlyFileOperations(data.as_bytes().to_vec());
+        let file = File::New(dirent, &flags, fops);
+        return Ok(file);
+    }
+}
+
+pub fn NewUTSFile(task: &Task, msrc: &Arc<QMutex<MountSource>>, domain: bool) -> Inode {
+    let v = SimpleFileInode::New(
+        task,
+        &ROOT_OWNER,
+        &FilePermissions::FromMode(FileMode(0o444)),
+        FSMagic::PROC_SUPER_MAGIC,
+        false,
+        UTSData { domain: domain },
+    );
+    return NewProcInode(&Arc::new(v), msrc, InodeType::SpecialFile, None);
+}
+
+pub fn NewKernel(task: &Task, msrc: &Arc<QMutex<MountSource>>) -> Inode {
+    let mut contents = BTreeMap::new();
+    contents.insert("dmesg_restrict".to_string(), NewDmesgRestrict(task, msrc));
+    contents.insert("domainname".to_string(), NewUTSFile(task, msrc, true));
+    contents.insert("hostname".to_string(), NewUTSFile(task, msrc, false));
+    contents.insert("random".to_string(), NewRandom(task, msrc));
+
+    let taskDir = DirNode {
+        dir: Dir::New(
+            task,
+            contents,
+            &ROOT_OWNER,
+            &FilePermissions::FromMode(FileMode(0o0555)),
+        ),
+        data: ProcSysDirNode {},
+    };
+
+    return NewProcInode(&Arc::new(taskDir), msrc, InodeType::SpecialDirectory, None);
+}
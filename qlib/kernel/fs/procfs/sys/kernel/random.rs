@@ -0,0 +1,230 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use crate::qlib::mutex::*;
+use alloc::collections::btree_map::BTreeMap;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::Ordering;
+
+use super::super::super::super::super::super::auth::*;
+use super::super::super::super::super::super::common::*;
+use super::super::super::super::super::super::linux_def::*;
+use super::super::super::super::super::kernel::entropy::*;
+use super::super::super::super::super::task::*;
+use super::super::super::super::attr::*;
+use super::super::super::super::dirent::*;
+use super::super::super::super::file::*;
+use super::super::super::super::flags::*;
+use super::super::super::super::fsutil::file::readonly_file::*;
+use super::super::super::super::fsutil::inode::simple_file_inode::*;
+use super::super::super::super::inode::*;
+use super::super::super::super::mount::*;
+use super::super::super::super::ramfs::dir::*;
+use super::super::super::dir_proc::*;
+use super::super::super::inode::*;
+use super::kernel::*;
+
+pub fn NewRandom(task: &Task, msrc: &Arc<QMutex<MountSource>>) -> Inode {
+    let mut contents = BTreeMap::new();
+    contents.insert(
+        "boot_id".to_string(),
+        NewRandomFile(task, RandomSysctl::BootId, msrc),
+    );
+    contents.insert(
+        "entropy_avail".to_string(),
+        NewRandomFile(task, RandomSysctl::EntropyAvail, msrc),
+    );
+    contents.insert(
+        "poolsize".to_string(),
+        NewRandomFile(task, RandomSysctl::PoolSize, msrc),
+    );
+    contents.insert(
+        "urandom_min_reseed_secs".to_string(),
+        NewRandomFile(task, RandomSysctl::UrandomMinReseedSecs, msrc),
+    );
+    contents.insert(
+        "uuid".to_string(),
+        NewRandomFile(task, RandomSysctl::Uuid, msrc),
+    );
+    contents.insert(
+        "write_wakeup_threshold".to_string(),
+        NewRandomFile(task, RandomSysctl::WriteWakeupThreshold, msrc),
+    );
+
+    let dir = DirNode {
+        dir: Dir::New(
+            task,
+            contents,
+            &ROOT_OWNER,
+            &FilePermissions::FromMode(FileMode(0o0555)),
+        ),
+        data: ProcSysDirNode {},
+    };
+
+    return NewProcInode(&Arc::new(dir), msrc, InodeType::SpecialDirectory, None);
+}
+
+fn NewRandomFile(task: &Task, sysctl: RandomSysctl, msrc: &Arc<QMutex<MountSource>>) -> Inode {
+    let mode = if sysctl.Writable() { 0o644 } else { 0o444 };
+    let iops = SimpleFileInode::New(
+        task,
+        &ROOT_OWNER,
+        &FilePermissions::FromMode(FileMode(mode)),
+        FSMagic::PROC_SUPER_MAGIC,
+        false,
+        sysctl,
+    );
+
+    return NewProcInode(&Arc::new(iops), msrc, InodeType::SpecialFile, None);
+}
+
+// RandomSysctl is a file of /proc/sys/kernel/random.
+#[derive(Clone, Copy)]
+pub enum RandomSysctl {
+    BootId,
+    EntropyAvail,
+    PoolSize,
+    UrandomMinReseedSecs,
+    Uuid,
+    WriteWakeupThreshold,
+}
+
+impl RandomSysctl {
+    fn Writable(&self) -> bool {
+        match self {
+            RandomSysctl::UrandomMinReseedSecs | RandomSysctl::WriteWakeupThreshold => true,
+            _ => false,
+        }
+    }
+
+    fn Get(&self) -> String {
+        match self {
+            RandomSysctl::BootId => return ENTROPY_POOL.BootId(),
+            RandomSysctl::EntropyAvail => return format!("{}", ENTROPY_POOL.EntropyAvail()),
+            RandomSysctl::PoolSize => return format!("{}", POOL_BITS),
+            RandomSysctl::UrandomMinReseedSecs => {
+                return format!("{}", ENTROPY_POOL.reseedSecs.load(Ordering::Relaxed))
+            }
+            // as Linux, each open gets a new uuid
+            RandomSysctl::Uuid => return RandomUUID(),
+            RandomSysctl::WriteWakeupThreshold => {
+                return format!(
+                    "{}",
+                    ENTROPY_POOL.writeWakeupThreshold.load(Ordering::Relaxed)
+                )
+            }
+        }
+    }
+
+    fn Set(&self, v: i32) -> Result<()> {
+        match self {
+            RandomSysctl::UrandomMinReseedSecs => {
+                ENTROPY_POOL.reseedSecs.store(v, Ordering::Relaxed)
+            }
+            RandomSysctl::WriteWakeupThreshold => {
+                if v < 0 {
+                    return Err(Error::SysError(SysErr::EINVAL));
+                }
+                ENTROPY_POOL
+                    .writeWakeupThreshold
+                    .store(v, Ordering::Relaxed);
+            }
+            _ => return Err(Error::SysError(SysErr::EACCES)),
+        }
+
+        return Ok(());
+    }
+}
+
+impl SimpleFileTrait for RandomSysctl {
+    fn GetFile(
+        &self,
+        _task: &Task,
+        _dir: &Inode,
+        dirent: &Dirent,
+        flags: FileFlags,
+    ) -> Result<File> {
+        let node = RandomData {
+            sysctl: *self,
+            data: format!("{}\n", self.Get()).into_bytes(),
+        };
+        let fops = ReadonlyFileOperations { node: node };
+        return Ok(File::New(dirent, &flags, fops));
+    }
+}
+
+// RandomData is the content of a /proc/sys/kernel/random file when it is
+// opened.
+pub struct RandomData {
+    pub sysctl: RandomSysctl,
+    pub data: Vec<u8>,
+}
+
+impl ReadonlyFileNode for RandomData {
+    fn ReadAt(
+        &self,
+        task: &Task,
+        _f: &File,
+        dsts: &mut [IoVec],
+        offset: i64,
+        _blocking: bool,
+    ) -> Result<i64> {
+        if offset < 0 {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        if offset as usize > self.data.len() {
+            return Ok(0);
+        }
+
+        let n = task.CopyDataOutToIovs(&self.data[offset as usize..], dsts, true)?;
+        return Ok(n as i64);
+    }
+
+    fn WriteAt(
+        &self,
+        task: &Task,
+        _f: &File,
+        srcs: &[IoVec],
+        offset: i64,
+        _blocking: bool,
+    ) -> Result<i64> {
+        if !self.sysctl.Writable() {
+            return Err(Error::SysError(SysErr::EACCES));
+        }
+
+        // as Linux, a write past the start of the file is ignored
+        let size = IoVec::NumBytes(srcs);
+        if offset != 0 || size == 0 {
+            return Ok(size as i64);
+        }
+
+        let size = if size > 16 { 16 } else { size };
+        let mut buf: Vec<u8> = Vec::with_capacity(size);
+        buf.resize(size, 0);
+        let n = task.CopyDataInFromIovs(&mut buf, srcs, true)?;
+        let v = match core::str::from_utf8(&buf[..n]) {
+            Ok(s) => match s.trim().parse::<i32>() {
+                Ok(v) => v,
+                Err(_) => return Err(Error::SysError(SysErr::EINVAL)),
+            },
+            Err(_) => return Err(Error::SysError(SysErr::EINVAL)),
+        };
+
+        self.sysctl.Set(v)?;
+        return Ok(n as i64);
+    }
+}
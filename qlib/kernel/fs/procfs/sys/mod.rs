@@ -12,5 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod fs;
+pub mod kernel;
+pub mod net;
 pub mod sys;
 pub mod vm;
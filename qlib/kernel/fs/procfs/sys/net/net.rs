@@ -0,0 +1,213 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::qlib::mutex::*;
+use alloc::collections::btree_map::BTreeMap;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::Ordering;
+
+use super::super::super::super::super::super::auth::*;
+use super::super::super::super::super::super::common::*;
+use super::super::super::super::super::super::linux_def::*;
+use super::super::super::super::super::socket::socket::*;
+use super::super::super::super::super::task::*;
+use super::super::super::super::attr::*;
+use super::super::super::super::dirent::*;
+use super::super::super::super::file::*;
+use super::super::super::super::flags::*;
+use super::super::super::super::fsutil::file::readonly_file::*;
+use super::super::super::super::fsutil::inode::simple_file_inode::*;
+use super::super::super::super::inode::*;
+use super::super::super::super::mount::*;
+use super::super::super::super::ramfs::dir::*;
+use super::super::super::dir_proc::*;
+use super::super::super::inode::*;
+
+// ProcSysNetDirNode represents a /proc/sys/net directory.
+pub struct ProcSysNetDirNode {}
+
+impl DirDataNode for ProcSysNetDirNode {
+    fn Lookup(&self, d: &Dir, task: &Task, dir: &Inode, name: &str) -> Result<Dirent> {
+        return d.Lookup(task, dir, name);
+    }
+
+    fn GetFile(
+        &self,
+        d: &Dir,
+        task: &Task,
+        dir: &Inode,
+        dirent: &Dirent,
+        flags: FileFlags,
+    ) -> Result<File> {
+        return d.GetFile(task, dir, dirent, flags);
+    }
+}
+
+fn NewNetDir(
+    task: &Task,
+    msrc: &Arc<QMutex<MountSource>>,
+    contents: BTreeMap<String, Inode>,
+) -> Inode {
+    let dir = DirNode {
+        dir: Dir::New(
+            task,
+            contents,
+            &ROOT_OWNER,
+            &FilePermissions::FromMode(FileMode(0o0555)),
+        ),
+        data: ProcSysNetDirNode {},
+    };
+
+    return NewProcInode(&Arc::new(dir), msrc, InodeType::SpecialDirectory, None);
+}
+
+pub fn NewNet(task: &Task, msrc: &Arc<QMutex<MountSource>>) -> Inode {
+    let mut core = BTreeMap::new();
+    core.insert(
+        "somaxconn".to_string(),
+        NewSysctlFile(task, Sysctl::Somaxconn, msrc),
+    );
+
+    let mut ipv4 = BTreeMap::new();
+    ipv4.insert(
+        "tcp_abort_on_overflow".to_string(),
+        NewSysctlFile(task, Sysctl::TcpAbortOnOverflow, msrc),
+    );
+
+    let mut contents = BTreeMap::new();
+    contents.insert("core".to_string(), NewNetDir(task, msrc, core));
+    contents.insert("ipv4".to_string(), NewNetDir(task, msrc, ipv4));
+
+    return NewNetDir(task, msrc, contents);
+}
+
+fn NewSysctlFile(task: &Task, sysctl: Sysctl, msrc: &Arc<QMutex<MountSource>>) -> Inode {
+    let iops = SimpleFileInode::New(
+        task,
+        &ROOT_OWNER,
+        &FilePermissions::FromMode(FileMode(0o644)),
+        FSMagic::PROC_SUPER_MAGIC,
+        false,
+        sysctl,
+    );
+
+    return NewProcInode(&Arc::new(iops), msrc, InodeType::SpecialFile, None);
+}
+
+// Sysctl is a writable /proc/sys/net integer.
+#[derive(Clone, Copy)]
+pub enum Sysctl {
+    Somaxconn,
+    TcpAbortOnOverflow,
+}
+
+impl Sysctl {
+    fn Get(&self) -> i32 {
+        match self {
+            Sysctl::Somaxconn => return NET_SOMAXCONN.load(Ordering::Relaxed),
+            Sysctl::TcpAbortOnOverflow => {
+                return NET_TCP_ABORT_ON_OVERFLOW.load(Ordering::Relaxed) as i32
+            }
+        }
+    }
+
+    fn Set(&self, v: i32) -> Result<()> {
+        match self {
+            Sysctl::Somaxconn => {
+                if v < 0 {
+                    return Err(Error::SysError(SysErr::EINVAL));
+                }
+                NET_SOMAXCONN.store(v, Ordering::Relaxed);
+            }
+            Sysctl::TcpAbortOnOverflow => {
+                if v != 0 && v != 1 {
+                    return Err(Error::SysError(SysErr::EINVAL));
+                }
+                NET_TCP_ABORT_ON_OVERFLOW.store(v == 1, Ordering::Relaxed);
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+impl SimpleFileTrait for Sysctl {
+    fn GetFile(
+        &self,
+        _task: &Task,
+        _dir: &Inode,
+        dirent: &Dirent,
+        flags: FileFlags,
+    ) -> Result<File> {
+        let fops = ReadonlyFileOperations { node: *self };
+        return Ok(File::New(dirent, &flags, fops));
+    }
+}
+
+impl ReadonlyFileNode for Sysctl {
+    fn ReadAt(
+        &self,
+        task: &Task,
+        _f: &File,
+        dsts: &mut [IoVec],
+        offset: i64,
+        _blocking: bool,
+    ) -> Result<i64> {
+        if offset < 0 {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        let buf = format!("{}\n", self.Get());
+        if offset as usize > buf.len() {
+            return Ok(0);
+        }
+
+        let n = task.CopyDataOutToIovs(&buf.as_bytes()[offset as usize..], dsts, true)?;
+        return Ok(n as i64);
+    }
+
+    fn WriteAt(
+        &self,
+        task: &Task,
+        _f: &File,
+        srcs: &[IoVec],
+        offset: i64,
+        _blocking: bool,
+    ) -> Result<i64> {
+        // as Linux, a write past the start of the file is ignored
+        let size = IoVec::NumBytes(srcs);
+        if offset != 0 || size == 0 {
+            return Ok(size as i64);
+        }
+
+        // one integer, optional sign and a newline
+        let size = if size > 16 { 16 } else { size };
+        let mut buf: Vec<u8> = Vec::with_capacity(size);
+        buf.resize(size, 0);
+        let n = task.CopyDataInFromIovs(&mut buf, srcs, true)?;
+        let v = match core::str::from_utf8(&buf[..n]) {
+            Ok(s) => match s.trim().parse::<i32>() {
+                Ok(v) => v,
+                Err(_) => return Err(Error::SysError(SysErr::EINVAL)),
+            },
+            Err(_) => return Err(Error::SysError(SysErr::EINVAL)),
+        };
+
+        self.Set(v)?;
+        return Ok(n as i64);
+    }
+}
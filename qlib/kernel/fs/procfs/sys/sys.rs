@@ -30,6 +30,9 @@ use super::super::super::mount::*;
 use super::super::super::ramfs::dir::*;
 use super::super::dir_proc::*;
 use super::super::inode::*;
+use super::fs::fs::*;
+use super::kernel::kernel::*;
+use super::net::net::*;
 use super::vm::vm::*;
 
 // ProcSysDirNode represents a /proc/sys directory.
@@ -54,6 +57,9 @@ impl DirDataNode for ProcSysDirNode {
 
 pub fn NewSys(task: &Task, msrc: &Arc<QMutex<MountSource>>) -> Inode {
     let mut contents = BTreeMap::new();
+    contents.insert("fs".to_string(), NewFs(task, msrc));
+    contents.insert("kernel".to_string(), NewKernel(task, msrc));
+    contents.insert("net".to_string(), NewNet(task, msrc));
     contents.insert("vm".to_string(), NewVm(task, msrc));
 
     let taskDir = DirNode {
@@ -11,17 +11,151 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
-
 use crate::qlib::mutex::*;
+use alloc::string::String;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::Ordering;
 
+use super::super::super::super::super::super::auth::*;
+use super::super::super::super::super::super::common::*;
+use super::super::super::super::super::super::linux_def::*;
+use super::super::super::super::super::memmgr::overcommit::*;
 use super::super::super::super::super::task::*;
+use super::super::super::super::attr::*;
+use super::super::super::super::dirent::*;
+use super::super::super::super::file::*;
+use super::super::super::super::flags::*;
+use super::super::super::super::fsutil::file::readonly_file::*;
+use super::super::super::super::fsutil::inode::simple_file_inode::*;
 use super::super::super::super::inode::*;
 use super::super::super::super::mount::*;
 use super::super::super::inode::*;
 
-pub fn NewOvercommit(task: &Task, msrc: &Arc<QMutex<MountSource>>) -> Inode {
-    let overcommmit = format!("{}\n", 0);
+pub fn NewOvercommit(
+    task: &Task,
+    sysctl: OvercommitSysctl,
+    msrc: &Arc<QMutex<MountSource>>,
+) -> Inode {
+    let iops = SimpleFileInode::New(
+        task,
+        &ROOT_OWNER,
+        &FilePermissions::FromMode(FileMode(0o644)),
+        FSMagic::PROC_SUPER_MAGIC,
+        false,
+        sysctl,
+    );
+
+    return NewProcInode(&Arc::new(iops), msrc, InodeType::SpecialFile, None);
+}
+
+// OvercommitSysctl is a vm.overcommit_* file of /proc/sys/vm.
+#[derive(Clone, Copy)]
+pub enum OvercommitSysctl {
+    Memory,
+    Ratio,
+    Kbytes,
+}
+
+impl OvercommitSysctl {
+    fn Get(&self) -> String {
+        match self {
+            OvercommitSysctl::Memory => return format!("{}", OVERCOMMIT.Mode()),
+            OvercommitSysctl::Ratio => {
+                return format!("{}", OVERCOMMIT.ratio.load(Ordering::Relaxed))
+            }
+            OvercommitSysctl::Kbytes => {
+                return format!("{}", OVERCOMMIT.kbytes.load(Ordering::Relaxed))
+            }
+        }
+    }
+
+    fn Set(&self, v: i64) -> Result<()> {
+        if v < 0 {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        match self {
+            OvercommitSysctl::Memory => return OVERCOMMIT.SetMode(v as i32),
+            OvercommitSysctl::Ratio => OVERCOMMIT.SetRatio(v as u64),
+            OvercommitSysctl::Kbytes => OVERCOMMIT.SetKbytes(v as u64),
+        }
+
+        return Ok(());
+    }
+}
+
+impl SimpleFileTrait for OvercommitSysctl {
+    fn GetFile(
+        &self,
+        _task: &Task,
+        _dir: &Inode,
+        dirent: &Dirent,
+        flags: FileFlags,
+    ) -> Result<File> {
+        let node = OvercommitData {
+            sysctl: *self,
+            data: format!("{}\n", self.Get()).into_bytes(),
+        };
+        let fops = ReadonlyFileOperations { node: node };
+        return Ok(File::New(dirent, &flags, fops));
+    }
+}
+
+// OvercommitData is the content of a vm.overcommit_* file when it is opened.
+pub struct OvercommitData {
+    pub sysctl: OvercommitSysctl,
+    pub data: Vec<u8>,
+}
+
+impl ReadonlyFileNode for OvercommitData {
+    fn ReadAt(
+        &self,
+        task: &Task,
+        _f: &File,
+        dsts: &mut [IoVec],
+        offset: i64,
+        _blocking: bool,
+    ) -> Result<i64> {
+        if offset < 0 {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        if offset as usize > self.data.len() {
+            return Ok(0);
+        }
+
+        let n = task.CopyDataOutToIovs(&self.data[offset as usize..], dsts, true)?;
+        return Ok(n as i64);
+    }
+
+    fn WriteAt(
+        &self,
+        task: &Task,
+        _f: &File,
+        srcs: &[IoVec],
+        offset: i64,
+        _blocking: bool,
+    ) -> Result<i64> {
+        // as Linux, a write past the start of the file is ignored
+        let size = IoVec::NumBytes(srcs);
+        if offset != 0 || size == 0 {
+            return Ok(size as i64);
+        }
+
+        let size = if size > 24 { 24 } else { size };
+        let mut buf: Vec<u8> = Vec::with_capacity(size);
+        buf.resize(size, 0);
+        let n = task.CopyDataInFromIovs(&mut buf, srcs, true)?;
+        let v = match core::str::from_utf8(&buf[..n]) {
+            Ok(s) => match s.trim().parse::<i64>() {
+                Ok(v) => v,
+                Err(_) => return Err(Error::SysError(SysErr::EINVAL)),
+            },
+            Err(_) => return Err(Error::SysError(SysErr::EINVAL)),
+        };
 
-    return NewStaticProcInode(task, msrc, &Arc::new(overcommmit.as_bytes().to_vec()));
+        self.sysctl.Set(v)?;
+        return Ok(n as i64);
+    }
 }
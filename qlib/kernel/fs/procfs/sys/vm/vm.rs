@@ -56,7 +56,18 @@ impl DirDataNode for ProcSysDirNode {
 pub fn NewVm(task: &Task, msrc: &Arc<QMutex<MountSource>>) -> Inode {
     let mut contents = BTreeMap::new();
     contents.insert("mmap_min_addr".to_string(), NewMinAddrData(task, msrc));
-    contents.insert("overcommit_memory".to_string(), NewOvercommit(task, msrc));
+    contents.insert(
+        "overcommit_kbytes".to_string(),
+        NewOvercommit(task, OvercommitSysctl::Kbytes, msrc),
+    );
+    contents.insert(
+        "overcommit_memory".to_string(),
+        NewOvercommit(task, OvercommitSysctl::Memory, msrc),
+    );
+    contents.insert(
+        "overcommit_ratio".to_string(),
+        NewOvercommit(task, OvercommitSysctl::Ratio, msrc),
+    );
 
     let taskDir = DirNode {
         dir: Dir::New(
@@ -21,6 +21,7 @@ use super::super::super::super::super::auth::*;
 use super::super::super::super::super::common::*;
 use super::super::super::super::super::linux_def::*;
 use super::super::super::super::task::*;
+use super::super::super::super::threadmgr::task_usermem::*;
 use super::super::super::super::threadmgr::thread::*;
 use super::super::super::attr::*;
 use super::super::super::dirent::*;
@@ -158,7 +159,7 @@ impl ReadonlyFileNode for ExecArgReadonlyFileNode {
             length = IoVec::NumBytes(dsts) as u64;
         }
 
-        let data: Vec<u8> = task.CopyInVec(start, length as usize)?;
+        let data: Vec<u8> = UserSlice::New(start, length as usize).Read(task)?;
         let mut buf = &data[..];
 
         // On Linux, if the NUL byte at the end of the argument vector has been
@@ -183,7 +184,7 @@ impl ReadonlyFileNode for ExecArgReadonlyFileNode {
                     lengthEnvv = MemoryDef::PAGE_SIZE as usize - buf.len();
                 }
 
-                let envvData = task.CopyInVec(envv.Start(), lengthEnvv as usize)?;
+                let envvData = UserSlice::New(envv.Start(), lengthEnvv as usize).Read(task)?;
                 let mut copyNE = envvData.len();
                 for i in 0..envvData.len() {
                     if envvData[i] == 0 {
@@ -25,7 +25,6 @@ use super::super::super::super::super::usage::io::*;
 use super::super::super::super::task::*;
 use super::super::super::super::threadmgr::task_acct::*;
 use super::super::super::super::threadmgr::thread::*;
-use super::super::super::super::threadmgr::thread_group::*;
 use super::super::super::attr::*;
 use super::super::super::dirent::*;
 use super::super::super::file::*;
@@ -36,10 +35,16 @@ use super::super::super::inode::*;
 use super::super::super::mount::*;
 use super::super::inode::*;
 
-pub fn NewIO(task: &Task, thread: &Thread, msrc: &Arc<QMutex<MountSource>>) -> Inode {
+pub fn NewIO(
+    task: &Task,
+    thread: &Thread,
+    showSubtasks: bool,
+    msrc: &Arc<QMutex<MountSource>>,
+) -> Inode {
     let v = NewIOSimpleFileInode(
         task,
         thread,
+        showSubtasks,
         &ROOT_OWNER,
         &FilePermissions::FromMode(FileMode(0o400)),
         FSMagic::PROC_SUPER_MAGIC,
@@ -55,27 +60,38 @@ pub fn NewIO(task: &Task, thread: &Thread, msrc: &Arc<QMutex<MountSource>>) -> I
 pub fn NewIOSimpleFileInode(
     task: &Task,
     thread: &Thread,
+    showSubtasks: bool,
     owner: &FileOwner,
     perms: &FilePermissions,
     typ: u64,
 ) -> SimpleFileInode<IOData> {
-    let tg = thread.ThreadGroup();
-    let io = IOData { tg: tg };
+    let io = IOData {
+        t: thread.clone(),
+        tgstats: showSubtasks,
+    };
 
     return SimpleFileInode::New(task, owner, perms, typ, false, io);
 }
 
 pub struct IOData {
-    tg: ThreadGroup,
+    t: Thread,
+
+    // If tgstats is true, report the usage of the whole thread group,
+    // otherwise the usage of t only.
+    tgstats: bool,
 }
 
 impl IOData {
     pub fn GenSnapshot(&self) -> Vec<u8> {
         let io = IO::default();
-        io.Accumulate(&self.tg.IOUsage());
+        if self.tgstats {
+            io.Accumulate(&self.t.ThreadGroup().IOUsage());
+        } else {
+            io.Accumulate(&self.t.IOUsage());
+        }
 
         let mut buf = "".to_string();
-        buf += &format!("char: {}\n", io.CharsRead.load(Ordering::SeqCst));
+        buf += &format!("rchar: {}\n", io.CharsRead.load(Ordering::SeqCst));
         buf += &format!("wchar: {}\n", io.CharsWritten.load(Ordering::SeqCst));
         buf += &format!("syscr: {}\n", io.ReadSyscalls.load(Ordering::SeqCst));
         buf += &format!("syscw: {}\n", io.WriteSyscalls.load(Ordering::SeqCst));
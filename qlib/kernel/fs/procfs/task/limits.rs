@@ -0,0 +1,137 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::qlib::mutex::*;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use super::super::super::super::super::auth::*;
+use super::super::super::super::super::common::*;
+use super::super::super::super::super::limits::*;
+use super::super::super::super::super::linux_def::*;
+use super::super::super::super::task::*;
+use super::super::super::super::threadmgr::thread::*;
+use super::super::super::attr::*;
+use super::super::super::dirent::*;
+use super::super::super::file::*;
+use super::super::super::flags::*;
+use super::super::super::fsutil::file::readonly_file::*;
+use super::super::super::fsutil::inode::simple_file_inode::*;
+use super::super::super::inode::*;
+use super::super::super::mount::*;
+use super::super::inode::*;
+
+// LIMIT_NAMES is the name and unit of each resource limit, in the order of
+// fs/proc/base.c:lnames in Linux.
+const LIMIT_NAMES: [(LimitType, &str, &str); 16] = [
+    (LimitType::CPU, "Max cpu time", "seconds"),
+    (LimitType::FileSize, "Max file size", "bytes"),
+    (LimitType::Data, "Max data size", "bytes"),
+    (LimitType::Stack, "Max stack size", "bytes"),
+    (LimitType::Core, "Max core file size", "bytes"),
+    (LimitType::Rss, "Max resident set", "bytes"),
+    (LimitType::ProcessCount, "Max processes", "processes"),
+    (LimitType::NumberOfFiles, "Max open files", "files"),
+    (LimitType::MemoryLocked, "Max locked memory", "bytes"),
+    (LimitType::AS, "Max address space", "bytes"),
+    (LimitType::Locks, "Max file locks", "locks"),
+    (LimitType::SignalsPending, "Max pending signals", "signals"),
+    (LimitType::MessageQueueBytes, "Max msgqueue size", "bytes"),
+    (LimitType::Nice, "Max nice priority", ""),
+    (LimitType::RealTimePriority, "Max realtime priority", ""),
+    (LimitType::Rttime, "Max realtime timeout", "us"),
+];
+
+pub fn NewLimits(task: &Task, thread: &Thread, msrc: &Arc<QMutex<MountSource>>) -> Inode {
+    let v = NewLimitsSimpleFileInode(
+        task,
+        thread,
+        &ROOT_OWNER,
+        &FilePermissions::FromMode(FileMode(0o444)),
+        FSMagic::PROC_SUPER_MAGIC,
+    );
+    return NewProcInode(
+        &Arc::new(v),
+        msrc,
+        InodeType::SpecialFile,
+        Some(thread.clone()),
+    );
+}
+
+pub fn NewLimitsSimpleFileInode(
+    task: &Task,
+    thread: &Thread,
+    owner: &FileOwner,
+    perms: &FilePermissions,
+    typ: u64,
+) -> SimpleFileInode<LimitsData> {
+    let io = LimitsData { t: thread.clone() };
+
+    return SimpleFileInode::New(task, owner, perms, typ, false, io);
+}
+
+pub struct LimitsData {
+    t: Thread,
+}
+
+fn limitString(v: u64) -> String {
+    if v == INFINITY {
+        return "unlimited".to_string();
+    }
+
+    return format!("{}", v);
+}
+
+impl LimitsData {
+    pub fn GenSnapshot(&self) -> Vec<u8> {
+        let limits = self.t.ThreadGroup().Limits();
+
+        let mut buf = format!(
+            "{:<25} {:<20} {:<20} {:<10}\n",
+            "Limit", "Soft Limit", "Hard Limit", "Units"
+        );
+        for (typ, name, unit) in LIMIT_NAMES.iter() {
+            let l = limits.Get(*typ);
+            buf += &format!(
+                "{:<25} {:<20} {:<20} ",
+                name,
+                limitString(l.Cur),
+                limitString(l.Max)
+            );
+            if unit.len() > 0 {
+                buf += &format!("{:<10}\n", unit);
+            } else {
+                buf += "\n";
+            }
+        }
+
+        return buf.as_bytes().to_vec();
+    }
+}
+
+impl SimpleFileTrait for LimitsData {
+    fn GetFile(
+        &self,
+        _task: &Task,
+        _dir: &Inode,
+        dirent: &Dirent,
+        flags: FileFlags,
+    ) -> Result<File> {
+        let fops = NewSnapshotReadonlyFileOperations(self.GenSnapshot());
+        let file = File::New(dirent, &flags, fops);
+        return Ok(file);
+    }
+}
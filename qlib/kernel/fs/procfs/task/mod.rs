@@ -18,8 +18,10 @@ pub mod exe;
 pub mod exec_args;
 pub mod fds;
 pub mod io;
+pub mod limits;
 pub mod maps;
 pub mod mounts;
+pub mod schedstat;
 pub mod stat;
 pub mod statm;
 pub mod status;
@@ -13,6 +13,7 @@
 // limitations under the License.
 
 use crate::qlib::mutex::*;
+use alloc::string::String;
 use alloc::string::ToString;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
@@ -25,6 +26,7 @@ use super::super::super::super::threadmgr::thread::*;
 use super::super::super::attr::*;
 use super::super::super::dirent::*;
 use super::super::super::file::*;
+use super::super::super::filesystems::*;
 use super::super::super::flags::*;
 use super::super::super::fsutil::file::readonly_file::*;
 use super::super::super::fsutil::inode::simple_file_inode::*;
@@ -42,7 +44,9 @@ pub fn ForEachMount(thread: &Thread, mountns: MountNs, f: &mut FnMut(&str, &Arc<
         Some(mnt) => mnt,
     };
 
-    let ms = mountns.AllMountsUnder(&mnt);
+    let mut ms = mountns.AllMountsUnder(&mnt);
+    // list the mounts in the order they were made, parents before children
+    ms.sort_by_key(|m| m.lock().Id);
 
     for m in ms {
         let mroot = m.lock().Root();
@@ -56,6 +60,41 @@ pub fn ForEachMount(thread: &Thread, mountns: MountNs, f: &mut FnMut(&str, &Arc<
     }
 }
 
+// EscapeMountString escapes the characters which would break the space
+// separated fields of mountinfo and mounts the way linux does, as octal.
+pub fn EscapeMountString(s: &str) -> String {
+    let mut ret = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            ' ' | '\t' | '\n' | '\\' => ret += &format!("\\{:03o}", c as u32),
+            _ => ret.push(c),
+        }
+    }
+
+    return ret;
+}
+
+// MountOptions returns the per mount options of a mount source.
+pub fn MountOptions(flags: &MountSourceFlags) -> String {
+    let mut opts = if flags.ReadOnly {
+        "ro".to_string()
+    } else {
+        "rw".to_string()
+    };
+
+    if flags.NoExec {
+        opts += ",noexec";
+    }
+
+    if flags.NoAtime {
+        opts += ",noatime";
+    } else {
+        opts += ",relatime";
+    }
+
+    return opts;
+}
+
 pub fn NewMountInfoFile(task: &Task, thread: &Thread, msrc: &Arc<QMutex<MountSource>>) -> Inode {
     let v = NewMountInfoFileSimpleFileInode(
         task,
@@ -131,27 +170,16 @@ impl MountInfoFile {
                 ret += "/ ";
 
                 // (5) Mount point (relative to process root).
-                ret += &format!("{} ", mountPath);
+                ret += &format!("{} ", EscapeMountString(mountPath));
 
                 // (6) Mount options.
                 let mountSource = mroot.Inode().lock().MountSource.clone();
                 let flags = mountSource.lock().Flags;
-                let mut opts = "rw".to_string();
-                if flags.ReadOnly {
-                    opts = "ro".to_string();
-                }
-
-                if flags.NoAtime {
-                    opts += ",noatime";
-                }
-
-                if flags.NoExec {
-                    opts += ",noexec";
-                }
-
-                ret += &format!("{} ", opts);
+                ret += &format!("{} ", MountOptions(&flags));
 
                 // (7) Optional fields: zero or more fields of the form "tag[:value]".
+                // There is a single mount namespace and every mount is private,
+                // so there are no shared or master propagation tags.
                 // (8) Separator: the end of the optional fields is marked by a single hyphen.
                 ret += "- ";
 
@@ -159,11 +187,11 @@ impl MountInfoFile {
                 ret += &format!("{} ", &mountSource.lock().FileSystemType);
 
                 // (10) Mount source: filesystem-specific information or "none".
-                ret += "none ";
+                ret += &format!("{} ", EscapeMountString(&mountSource.lock().Source));
 
-                // (11) Superblock options. Only "ro/rw" is supported for now,
-                // and is the same as the filesystem option.
-                ret += &format!("{}\n", opts);
+                // (11) Superblock options. Only "ro/rw" is supported for now.
+                let sbOpts = if flags.ReadOnly { "ro" } else { "rw" };
+                ret += &format!("{}\n", sbOpts);
             },
         );
 
@@ -232,14 +260,12 @@ impl MountsFile {
                 let mountSource = mroot.Inode().lock().MountSource.clone();
                 let flags = mountSource.lock().Flags;
 
-                let opts = if flags.ReadOnly { "ro" } else { "rw" };
-
                 ret += &format!(
                     "{} {} {} {} {} {}\n",
-                    "none",
-                    mountPath,
+                    EscapeMountString(&mountSource.lock().Source),
+                    EscapeMountString(mountPath),
                     mountSource.lock().FileSystemType,
-                    opts,
+                    MountOptions(&flags),
                     0,
                     0
                 );
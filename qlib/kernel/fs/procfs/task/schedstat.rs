@@ -0,0 +1,96 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::qlib::mutex::*;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use super::super::super::super::super::auth::*;
+use super::super::super::super::super::common::*;
+use super::super::super::super::super::linux_def::*;
+use super::super::super::super::task::*;
+use super::super::super::super::threadmgr::thread::*;
+use super::super::super::attr::*;
+use super::super::super::dirent::*;
+use super::super::super::file::*;
+use super::super::super::flags::*;
+use super::super::super::fsutil::file::readonly_file::*;
+use super::super::super::fsutil::inode::simple_file_inode::*;
+use super::super::super::inode::*;
+use super::super::super::mount::*;
+use super::super::inode::*;
+
+pub fn NewSchedStat(task: &Task, thread: &Thread, msrc: &Arc<QMutex<MountSource>>) -> Inode {
+    let v = NewSchedStatSimpleFileInode(
+        task,
+        thread,
+        &ROOT_OWNER,
+        &FilePermissions::FromMode(FileMode(0o444)),
+        FSMagic::PROC_SUPER_MAGIC,
+    );
+    return NewProcInode(
+        &Arc::new(v),
+        msrc,
+        InodeType::SpecialFile,
+        Some(thread.clone()),
+    );
+}
+
+pub fn NewSchedStatSimpleFileInode(
+    task: &Task,
+    thread: &Thread,
+    owner: &FileOwner,
+    perms: &FilePermissions,
+    typ: u64,
+) -> SimpleFileInode<SchedStatData> {
+    let io = SchedStatData { t: thread.clone() };
+
+    return SimpleFileInode::New(task, owner, perms, typ, false, io);
+}
+
+// SchedStatData is the data for /proc/[pid]/schedstat: the time spent on the
+// cpu in ns, the time spent runnable in the ready queue in ns and the number
+// of timeslices run.
+pub struct SchedStatData {
+    t: Thread,
+}
+
+impl SchedStatData {
+    pub fn GenSnapshot(&self, _task: &Task) -> Vec<u8> {
+        let cs = self.t.CPUStats();
+        let output: String = format!(
+            "{} {} {}\n",
+            cs.UserTime + cs.SysTime,
+            cs.RunDelay,
+            cs.Timeslices
+        );
+
+        return output.as_bytes().to_vec();
+    }
+}
+
+impl SimpleFileTrait for SchedStatData {
+    fn GetFile(
+        &self,
+        task: &Task,
+        _dir: &Inode,
+        dirent: &Dirent,
+        flags: FileFlags,
+    ) -> Result<File> {
+        let fops = NewSnapshotReadonlyFileOperations(self.GenSnapshot(task));
+        let file = File::New(dirent, &flags, fops);
+        return Ok(file);
+    }
+}
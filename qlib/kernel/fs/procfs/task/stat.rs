@@ -114,24 +114,28 @@ impl StatData {
         );
         output += &format!("0 0 " /* tty_nr tpgid */);
         output += &format!("0 " /* flags */);
-        output += &format!("0 0 0 0 " /* minflt cminflt majflt cmajflt */);
 
         let cputime = if self.tgstats {
             self.t.ThreadGroup().CPUStats()
         } else {
             self.t.CPUStats()
         };
+        let childtime = self.t.ThreadGroup().JoinedChildCPUStats();
+        output += &format!(
+            "{} {} {} {} ", /* minflt cminflt majflt cmajflt */
+            cputime.MinorFaults, childtime.MinorFaults, cputime.MajorFaults, childtime.MajorFaults
+        );
+
         output += &format!(
             "{} {} ",
             ClockTFromDuration(cputime.UserTime),
             ClockTFromDuration(cputime.SysTime)
         );
 
-        let cputime = self.t.ThreadGroup().JoinedChildCPUStats();
         output += &format!(
             "{} {} ",
-            ClockTFromDuration(cputime.UserTime),
-            ClockTFromDuration(cputime.SysTime)
+            ClockTFromDuration(childtime.UserTime),
+            ClockTFromDuration(childtime.SysTime)
         );
 
         output += &format!("{} {} ", self.t.Priority(), self.t.Niceness());
@@ -163,7 +167,15 @@ impl StatData {
         output += &format!("{} ", self.t.ThreadGroup().Limits().Get(LimitType::Rss).Cur);
 
         output += &format!("0 0 0 0 0 " /* startcode endcode startstack kstkesp kstkeip */);
-        output += &format!("0 0 0 0 0 " /* signal blocked sigignore sigcatch wchan */);
+        let (pending, shared) = self.t.PendingSignalSets();
+        let (ignored, caught) = self.t.ThreadGroup().SignalHandlers().IgnoredAndCaught();
+        output += &format!(
+            "{} {} {} {} 0 ", /* signal blocked sigignore sigcatch wchan */
+            pending.0 | shared.0,
+            self.t.SignalMask().0,
+            ignored.0,
+            caught.0
+        );
         output += &format!("0 0 " /* nswap cnswap */);
 
         let terminationSignal = if Some(self.t.clone()) == self.t.ThreadGroup().Leader() {
@@ -173,7 +185,10 @@ impl StatData {
         };
         output += &format!("{} ", terminationSignal.0);
         output += &format!("0 0 0 " /* processor rt_priority policy */);
-        output += &format!("0 0 0 " /* delayacct_blkio_ticks guest_time cguest_time */);
+        output += &format!(
+            "{} 0 0 ", /* delayacct_blkio_ticks guest_time cguest_time */
+            ClockTFromDuration(cputime.IODelay)
+        );
         output += &format!(
             "0 0 0 0 0 0 0 " /* start_data end_data start_brk arg_start arg_end env_start env_end */
         );
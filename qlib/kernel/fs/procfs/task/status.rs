@@ -78,8 +78,8 @@ impl StatusData {
         let mut ret = "".to_string();
 
         ret += &format!("Name:\t{}\n", self.thread.Name());
-        // todo: handle thread state
-        //ret += &format!("State:\t{}\n", self.thread.Name());
+        let state = self.thread.lock().StateStatus().to_string();
+        ret += &format!("State:\t{}\n", state);
 
         let tg = self.thread.ThreadGroup();
         ret += &format!("Tgid:\t{}\n", self.pidns.IDOfThreadGroup(&tg));
@@ -100,14 +100,31 @@ impl StatusData {
 
         let mm = self.thread.lock().memoryMgr.clone();
 
-        let _ml = mm.MappingReadLock();
-
-        let vss = mm.VirtualMemorySizeLocked();
-        let rss = mm.ResidentSetSizeLocked();
+        let (vss, maxVss, rss, maxRss) = {
+            let _ml = mm.MappingReadLock();
+            (
+                mm.VirtualMemorySizeLocked(),
+                mm.MaxVirtualMemorySizeLocked(),
+                mm.ResidentSetSizeLocked(),
+                mm.MaxResidentSetSizeLocked(),
+            )
+        };
+        ret += &format!("VmPeak:\t{} kB\n", maxVss >> 10);
         ret += &format!("VmSize:\t{} kB\n", vss >> 10);
+        ret += &format!("VmHWM:\t{} kB\n", maxRss >> 10);
         ret += &format!("VmRSS:\t{} kB\n", rss >> 10);
+        // there is no swap in the sandbox
+        ret += &format!("VmSwap:\t{} kB\n", 0);
         ret += &format!("Threads:\t{}\n", tg.Count());
 
+        let (pending, shared) = self.thread.PendingSignalSets();
+        let (ignored, caught) = tg.SignalHandlers().IgnoredAndCaught();
+        ret += &format!("SigPnd:\t{:016x}\n", pending.0);
+        ret += &format!("ShdPnd:\t{:016x}\n", shared.0);
+        ret += &format!("SigBlk:\t{:016x}\n", self.thread.SignalMask().0);
+        ret += &format!("SigIgn:\t{:016x}\n", ignored.0);
+        ret += &format!("SigCgt:\t{:016x}\n", caught.0);
+
         let creds = self.thread.Credentials();
         ret += &format!("CapInh:\t{:016x}\n", creds.lock().InheritableCaps.0);
         ret += &format!("CapPrm:\t{:016x}\n", creds.lock().PermittedCaps.0);
@@ -115,10 +132,20 @@ impl StatusData {
         ret += &format!("CapBnd:\t{:016x}\n", creds.lock().BoundingCaps.0);
         ret += &format!("Seccomp:\t{}\n", 0);
 
-        //ret += &format!("Mems_allowed:\t{}\n",
-        //                "00000000,00000000,00000000,00000000,00000000,00000000,00000000,00000000,00000000,00000000,00000000,00000000,00000000,00000000,00000000,00000000,00000000,00000000,00000000,00000000,00000000,00000000,00000000,00000000,00000000,00000000,00000000,00000000,00000000,00000000,00000000,00000001");
-        ret += &format!("Mems_allowed:1\t\n");
-        ret += &format!("Mems_allowed_list:0\t\n");
+        let cpus = self.thread.CPUMask();
+        ret += &format!("Cpus_allowed:\t{}\n", cpus.MaskString());
+        ret += &format!("Cpus_allowed_list:\t{}\n", cpus.ListString());
+
+        // a single numa node, printed with the 1024 node mask width of linux
+        ret += &format!("Mems_allowed:\t{}00000001\n", "00000000,".repeat(31));
+        ret += &format!("Mems_allowed_list:\t0\n");
+
+        let stats = self.thread.CPUStats();
+        ret += &format!("voluntary_ctxt_switches:\t{}\n", stats.VoluntarySwitches);
+        ret += &format!(
+            "nonvoluntary_ctxt_switches:\t{}\n",
+            stats.InvoluntarySwitches
+        );
 
         return ret.as_bytes().to_vec();
     }
@@ -39,8 +39,10 @@ use super::exe::*;
 use super::exec_args::*;
 use super::fds::*;
 use super::io::*;
+use super::limits::*;
 use super::maps::*;
 use super::mounts::*;
+use super::schedstat::*;
 use super::stat::*;
 use super::statm::*;
 use super::status::*;
@@ -92,13 +94,15 @@ impl ProcNode {
         contents.insert("fd".to_string(), NewFdDir(task, thread, msrc));
         contents.insert("fdinfo".to_string(), NewFdInfoDir(task, thread, msrc));
         contents.insert("gid_map".to_string(), NewIdMap(task, thread, msrc, true));
-        contents.insert("io".to_string(), NewIO(task, thread, msrc));
+        contents.insert("io".to_string(), NewIO(task, thread, showSubtasks, msrc));
+        contents.insert("limits".to_string(), NewLimits(task, thread, msrc));
         contents.insert("maps".to_string(), NewMaps(task, thread, msrc));
         contents.insert(
             "mountinfo".to_string(),
             NewMountInfoFile(task, thread, msrc),
         );
         contents.insert("mounts".to_string(), NewMountsFile(task, thread, msrc));
+        contents.insert("schedstat".to_string(), NewSchedStat(task, thread, msrc));
         contents.insert(
             "stat".to_string(),
             NewStat(task, thread, showSubtasks, self.lock().pidns.clone(), msrc),
@@ -421,6 +421,7 @@ impl InodeOperations for Dir {
         newParent: &Inode,
         newname: &str,
         replacement: bool,
+        flags: u32,
     ) -> Result<()> {
         let oldParentInodeOp = oldParent.lock().InodeOp.clone();
         let newParentInodeOp = newParent.lock().InodeOp.clone();
@@ -432,6 +433,7 @@ impl InodeOperations for Dir {
             newParentInodeOp,
             newname,
             replacement,
+            flags,
         );
     }
 
@@ -541,7 +543,10 @@ impl InodeOperations for Dir {
     }
 
     fn StatFS(&self, _task: &Task) -> Result<FsInfo> {
-        return Err(Error::SysError(SysErr::ENOSYS));
+        return Ok(FsInfo {
+            Type: self.read().fsType,
+            ..Default::default()
+        });
     }
 
     fn Mappable(&self) -> Result<HostInodeOp> {
@@ -688,6 +693,7 @@ pub fn Rename(
     newParent: Arc<InodeOperations>,
     newName: &str,
     replacement: bool,
+    flags: u32,
 ) -> Result<()> {
     let op = match oldParent.as_any().downcast_ref::<Dir>() {
         None => return Err(Error::SysError(SysErr::EXDEV)),
@@ -703,6 +709,10 @@ pub fn Rename(
         return Err(Error::SysError(SysErr::ENAMETOOLONG));
     }
 
+    if flags & RenameFlags::RENAME_EXCHANGE != 0 {
+        return Exchange(task, &op, oldName, &np, newName);
+    }
+
     let mut npLocked = np.write();
 
     if replacement {
@@ -741,3 +751,35 @@ pub fn Rename(
 
     return Ok(());
 }
+
+// Exchange atomically swaps the inodes named oldName and newName.
+fn Exchange(task: &Task, op: &Dir, oldName: &str, np: &Dir, newName: &str) -> Result<()> {
+    let mut npLocked = np.write();
+
+    if !npLocked.children.contains_key(newName) {
+        return Err(Error::SysError(SysErr::ENOENT));
+    }
+
+    if Arc::ptr_eq(op, np) {
+        if !npLocked.children.contains_key(oldName) {
+            return Err(Error::SysError(SysErr::ENOENT));
+        }
+
+        let old = npLocked.removeChild(task, oldName)?;
+        let new = npLocked.removeChild(task, newName)?;
+        npLocked.addChild(task, newName, &old);
+        npLocked.addChild(task, oldName, &new);
+    } else {
+        let mut opLocked = op.write();
+        if !opLocked.children.contains_key(oldName) {
+            return Err(Error::SysError(SysErr::ENOENT));
+        }
+
+        let old = opLocked.removeChild(task, oldName)?;
+        let new = npLocked.removeChild(task, newName)?;
+        npLocked.addChild(task, newName, &old);
+        opLocked.addChild(task, oldName, &new);
+    }
+
+    return Ok(());
+}
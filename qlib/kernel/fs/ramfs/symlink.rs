@@ -174,6 +174,7 @@ impl InodeOperations for Symlink {
         _newParent: &Inode,
         _newname: &str,
         _replacement: bool,
+        _flags: u32,
     ) -> Result<()> {
         return Err(Error::SysError(SysErr::EINVAL));
     }
@@ -293,7 +294,10 @@ impl InodeOperations for Symlink {
     }
 
     fn StatFS(&self, _task: &Task) -> Result<FsInfo> {
-        return Err(Error::SysError(SysErr::ENOSYS));
+        return Ok(FsInfo {
+            Type: self.read().fsType,
+            ..Default::default()
+        });
     }
 
     fn Mappable(&self) -> Result<HostInodeOp> {
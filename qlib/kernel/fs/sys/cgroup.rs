@@ -0,0 +1,144 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use crate::qlib::mutex::*;
+use alloc::collections::btree_map::BTreeMap;
+use alloc::string::ToString;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use super::super::super::super::auth::*;
+use super::super::super::super::cgroup::*;
+use super::super::super::super::common::*;
+use super::super::super::super::linux_def::*;
+use super::super::super::super::numa::*;
+use super::super::super::kernel::container::*;
+use super::super::super::kernel::kernel::*;
+use super::super::super::task::*;
+use super::super::super::SHARESPACE;
+use super::super::dirent::*;
+use super::super::file::*;
+use super::super::flags::*;
+use super::super::fsutil::file::readonly_file::*;
+use super::super::fsutil::inode::simple_file_inode::*;
+use super::super::inode::*;
+use super::super::mount::*;
+use super::devices::*;
+use super::sys::*;
+
+// NewCgroupDir is the read only cgroup v2 root of the sandbox with the limits
+// which qvisor sets on the host cgroup, so that the applications reading them,
+// e.g. the jvm, size themselves by the sandbox limits. The memory and pids
+// usage files are those of the container of the reader.
+pub fn NewCgroupDir(task: &Task, msrc: &Arc<QMutex<MountSource>>) -> Inode {
+    let limits = SHARESPACE.cgroup.lock().clone();
+    let cores: Vec<usize> = (0..GetKernel().applicationCores).collect();
+    let nodes: Vec<usize> = (0..SHARESPACE.numa.lock().NodeCnt()).collect();
+
+    let files = [
+        ("cgroup.controllers", CGROUP_CONTROLLERS.to_string()),
+        ("cgroup.subtree_control", "".to_string()),
+        ("cpu.max", limits.CpuMax()),
+        ("cpu.weight", limits.CpuWeight()),
+        ("cpuset.cpus.effective", CpuList(&cores)),
+        ("cpuset.mems.effective", CpuList(&nodes)),
+        ("io.max", limits.IoMax()),
+        ("memory.low", limits.MemoryLow()),
+        ("memory.max", limits.MemoryMax()),
+        ("memory.swap.max", limits.SwapMax()),
+    ];
+
+    let mut m = BTreeMap::new();
+    for (name, data) in files.iter() {
+        let data = if data.len() == 0 {
+            data.to_string()
+        } else {
+            format!("{}\n", data)
+        };
+        m.insert(name.to_string(), NewStatic(task, msrc, data));
+    }
+
+    let dynamic = [
+        ("memory.current", CgroupFile::MemoryCurrent),
+        ("memory.stat", CgroupFile::MemoryStat),
+        ("pids.current", CgroupFile::PidsCurrent),
+        ("pids.events", CgroupFile::PidsEvents),
+        ("pids.max", CgroupFile::PidsMax),
+    ];
+    for (name, file) in dynamic.iter() {
+        m.insert(name.to_string(), NewCgroupFile(task, msrc, *file));
+    }
+
+    return NewDir(task, msrc, m);
+}
+
+// CgroupFile is a file of the cgroup of the container of the reader
+#[derive(Debug, Clone, Copy)]
+pub enum CgroupFile {
+    MemoryCurrent,
+    MemoryStat,
+    PidsCurrent,
+    PidsEvents,
+    PidsMax,
+}
+
+pub fn NewCgroupFile(task: &Task, msrc: &Arc<QMutex<MountSource>>, file: CgroupFile) -> Inode {
+    let v = SimpleFileInode::New(
+        task,
+        &ROOT_OWNER,
+        &FilePermissions::FromMode(FileMode(0o444)),
+        FSMagic::PROC_SUPER_MAGIC,
+        false,
+        CgroupData { file: file },
+    );
+    return NewFile(&Arc::new(v), msrc);
+}
+
+// CgroupData is the content of a CgroupFile sampled at open. The root
+// container sees the cgroup of the sandbox, which includes the sub containers
+// of the pod.
+pub struct CgroupData {
+    pub file: CgroupFile,
+}
+
+impl CgroupData {
+    pub fn Data(&self, c: &Container) -> String {
+        let kernel = GetKernel();
+        return match self.file {
+            CgroupFile::MemoryCurrent => format!("{}\n", c.CgroupMemoryStat(&kernel).Current()),
+            CgroupFile::MemoryStat => c.CgroupMemoryStat(&kernel).String(),
+            CgroupFile::PidsCurrent => format!("{}\n", c.TaskCount()),
+            CgroupFile::PidsEvents => c.PidsEvents(),
+            CgroupFile::PidsMax => format!("{}\n", c.PidsMax()),
+        };
+    }
+}
+
+impl SimpleFileTrait for CgroupData {
+    fn GetFile(
+        &self,
+        task: &Task,
+        _dir: &Inode,
+        dirent: &Dirent,
+        flags: FileFlags,
+    ) -> Result<File> {
+        let data = match GetKernel().Container(&task.Thread().ContainerID()) {
+            None => return Err(Error::SysError(SysErr::ENOENT)),
+            Some(c) => self.Data(&c),
+        };
+
+        let fops = NewSnapshotReadonlyFileOperations(data.into_bytes());
+        let file = File::New(dirent, &flags, fops);
+        return Ok(file);
+    }
+}
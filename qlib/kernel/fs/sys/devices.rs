@@ -14,15 +14,20 @@
 
 use crate::qlib::mutex::*;
 use alloc::collections::btree_map::BTreeMap;
+use alloc::string::String;
 use alloc::string::ToString;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 
 use super::super::super::super::auth::*;
 use super::super::super::super::common::*;
+use super::super::super::super::cpuid::*;
 use super::super::super::super::linux_def::*;
+use super::super::super::super::numa::*;
+use super::super::super::kernel::cpuset::*;
 use super::super::super::kernel::kernel::*;
 use super::super::super::task::*;
+use super::super::super::SHARESPACE;
 use super::super::dirent::*;
 use super::super::file::*;
 use super::super::flags::*;
@@ -78,18 +83,330 @@ impl SimpleFileTrait for PossibleData {
     }
 }
 
+pub fn NewOnline(task: &Task, msrc: &Arc<QMutex<MountSource>>) -> Inode {
+    let v = SimpleFileInode::New(
+        task,
+        &ROOT_OWNER,
+        &FilePermissions::FromMode(FileMode(0o400)),
+        FSMagic::PROC_SUPER_MAGIC,
+        false,
+        OnlineData {},
+    );
+    return NewFile(&Arc::new(v), msrc);
+}
+
+// OnlineData is the online cpus, the parked vcpus are offline
+pub struct OnlineData {}
+
+impl OnlineData {
+    pub fn GenSnapshot(&self, _task: &Task) -> Vec<u8> {
+        let kernel = GetKernel();
+        let maxCore = kernel.OnlineCores() - 1;
+
+        let ret = format!("0-{}\n", maxCore);
+        return ret.as_bytes().to_vec();
+    }
+}
+
+impl SimpleFileTrait for OnlineData {
+    fn GetFile(
+        &self,
+        task: &Task,
+        _dir: &Inode,
+        dirent: &Dirent,
+        flags: FileFlags,
+    ) -> Result<File> {
+        let fops = NewSnapshotReadonlyFileOperations(self.GenSnapshot(task));
+        let file = File::New(dirent, &flags, fops);
+        return Ok(file);
+    }
+}
+
+// CpuCache is a cache level of the host cpu as reported by cpuid
+#[derive(Debug, Clone, Copy)]
+pub struct CpuCache {
+    pub level: u32,
+    pub typ: &'static str,
+    pub lineSize: u32,
+    pub partitions: u32,
+    pub ways: u32,
+    pub sets: u32,
+}
+
+impl CpuCache {
+    pub fn Size(&self) -> u32 {
+        return self.lineSize * self.partitions * self.ways * self.sets;
+    }
+}
+
+// CpuCaches reads the cache hierarchy from the deterministic cache parameters
+// leaf, 4 on intel and 0x8000001d on amd.
+pub fn CpuCaches() -> Vec<CpuCache> {
+    let (maxLeaf, _, _, _) = HostID(0, 0);
+    let (maxExtLeaf, _, _, _) = HostID(0x8000_0000, 0);
+
+    let leaf = if maxLeaf >= 4 && HostID(4, 0).0 & 0x1f != 0 {
+        4
+    } else if maxExtLeaf >= 0x8000_001d {
+        0x8000_001d
+    } else {
+        return Vec::new();
+    };
+
+    let mut caches = Vec::new();
+    for i in 0..16 {
+        let (ax, bx, cx, _) = HostID(leaf, i);
+        let typ = match ax & 0x1f {
+            0 => break,
+            1 => "Data",
+            2 => "Instruction",
+            3 => "Unified",
+            _ => continue,
+        };
+
+        caches.push(CpuCache {
+            level: (ax >> 5) & 0x7,
+            typ: typ,
+            lineSize: (bx & 0xfff) + 1,
+            partitions: ((bx >> 12) & 0x3ff) + 1,
+            ways: ((bx >> 22) & 0x3ff) + 1,
+            sets: cx + 1,
+        });
+    }
+
+    return caches;
+}
+
+fn CpuMap(cpus: &[usize], cores: usize) -> String {
+    let mut set = CPUSet::New(cores);
+    for cpu in cpus {
+        set.Set(*cpu);
+    }
+
+    return set.MaskString();
+}
+
+// NewCpuN is /sys/devices/system/cpu/cpuN. Every vcpu is a core of its own
+// and the cpus of a numa node make up a package, the caches above L2 are
+// shared by the package.
+pub fn NewCpuN(
+    task: &Task,
+    msrc: &Arc<QMutex<MountSource>>,
+    cpu: usize,
+    caches: &[CpuCache],
+) -> Inode {
+    let numa = SHARESPACE.numa.lock().clone();
+    let cores = GetKernel().applicationCores;
+    let node = numa.NodeOf(cpu);
+    let siblings = numa.Cpus(node, cores);
+    let me = [cpu];
+
+    let mut topology = BTreeMap::new();
+    let files = [
+        ("core_id", format!("{}", cpu)),
+        ("physical_package_id", format!("{}", node)),
+        ("die_id", "0".to_string()),
+        ("core_cpus", CpuMap(&me, cores)),
+        ("core_cpus_list", CpuList(&me)),
+        ("thread_siblings", CpuMap(&me, cores)),
+        ("thread_siblings_list", CpuList(&me)),
+        ("core_siblings", CpuMap(&siblings, cores)),
+        ("core_siblings_list", CpuList(&siblings)),
+        ("package_cpus", CpuMap(&siblings, cores)),
+        ("package_cpus_list", CpuList(&siblings)),
+    ];
+    for (name, data) in files.iter() {
+        topology.insert(name.to_string(), NewStatic(task, msrc, format!("{}\n", data)));
+    }
+
+    let mut cache = BTreeMap::new();
+    for (i, c) in caches.iter().enumerate() {
+        let shared: &[usize] = if c.level > 2 { &siblings } else { &me };
+        let files = [
+            ("level", format!("{}", c.level)),
+            ("type", c.typ.to_string()),
+            ("size", format!("{}K", c.Size() >> 10)),
+            ("coherency_line_size", format!("{}", c.lineSize)),
+            ("physical_line_partition", format!("{}", c.partitions)),
+            ("ways_of_associativity", format!("{}", c.ways)),
+            ("number_of_sets", format!("{}", c.sets)),
+            ("shared_cpu_map", CpuMap(shared, cores)),
+            ("shared_cpu_list", CpuList(shared)),
+        ];
+
+        let mut index = BTreeMap::new();
+        for (name, data) in files.iter() {
+            index.insert(name.to_string(), NewStatic(task, msrc, format!("{}\n", data)));
+        }
+        cache.insert(format!("index{}", i), NewDir(task, msrc, index));
+    }
+
+    let mut m = BTreeMap::new();
+    m.insert("topology".to_string(), NewDir(task, msrc, topology));
+    m.insert("cache".to_string(), NewDir(task, msrc, cache));
+    // like linux, the boot cpu can't be offlined and has no online file
+    if cpu > 0 {
+        let v = SimpleFileInode::New(
+            task,
+            &ROOT_OWNER,
+            &FilePermissions::FromMode(FileMode(0o444)),
+            FSMagic::PROC_SUPER_MAGIC,
+            false,
+            CpuOnlineData { cpu: cpu },
+        );
+        m.insert("online".to_string(), NewFile(&Arc::new(v), msrc));
+    }
+
+    return NewDir(task, msrc, m);
+}
+
+// OfflineData is the parked vcpus
+pub struct OfflineData {}
+
+impl SimpleFileTrait for OfflineData {
+    fn GetFile(
+        &self,
+        _task: &Task,
+        _dir: &Inode,
+        dirent: &Dirent,
+        flags: FileFlags,
+    ) -> Result<File> {
+        let kernel = GetKernel();
+        let offline: Vec<usize> = (kernel.OnlineCores()..kernel.applicationCores).collect();
+        let data = format!("{}\n", CpuList(&offline));
+        let fops = NewSnapshotReadonlyFileOperations(data.as_bytes().to_vec());
+        let file = File::New(dirent, &flags, fops);
+        return Ok(file);
+    }
+}
+
+// CpuOnlineData is cpuN/online, 0 when the vcpu is parked
+pub struct CpuOnlineData {
+    pub cpu: usize,
+}
+
+impl SimpleFileTrait for CpuOnlineData {
+    fn GetFile(
+        &self,
+        _task: &Task,
+        _dir: &Inode,
+        dirent: &Dirent,
+        flags: FileFlags,
+    ) -> Result<File> {
+        let online = if self.cpu < GetKernel().OnlineCores() {
+            1
+        } else {
+            0
+        };
+        let data = format!("{}\n", online);
+        let fops = NewSnapshotReadonlyFileOperations(data.as_bytes().to_vec());
+        let file = File::New(dirent, &flags, fops);
+        return Ok(file);
+    }
+}
+
 pub fn NewCPU(task: &Task, msrc: &Arc<QMutex<MountSource>>) -> Inode {
     let mut m = BTreeMap::new();
 
-    m.insert("online".to_string(), NewPossible(task, msrc));
+    m.insert("online".to_string(), NewOnline(task, msrc));
     m.insert("possible".to_string(), NewPossible(task, msrc));
     m.insert("present".to_string(), NewPossible(task, msrc));
 
     let kernel = GetKernel();
     let cores = kernel.applicationCores;
+    let offline = SimpleFileInode::New(
+        task,
+        &ROOT_OWNER,
+        &FilePermissions::FromMode(FileMode(0o444)),
+        FSMagic::PROC_SUPER_MAGIC,
+        false,
+        OfflineData {},
+    );
+    m.insert("offline".to_string(), NewFile(&Arc::new(offline), msrc));
+    m.insert(
+        "kernel_max".to_string(),
+        NewStatic(task, msrc, format!("{}\n", cores - 1)),
+    );
+
+    let caches = CpuCaches();
     for i in 0..cores {
         let name = format!("cpu{}", i);
-        m.insert(name, NewDir(task, msrc, BTreeMap::new()));
+        m.insert(name, NewCpuN(task, msrc, i, &caches));
+    }
+
+    return NewDir(task, msrc, m);
+}
+
+pub fn NewStatic(task: &Task, msrc: &Arc<QMutex<MountSource>>, data: String) -> Inode {
+    let v = SimpleFileInode::New(
+        task,
+        &ROOT_OWNER,
+        &FilePermissions::FromMode(FileMode(0o444)),
+        FSMagic::PROC_SUPER_MAGIC,
+        false,
+        StaticData {
+            data: data.into_bytes(),
+        },
+    );
+    return NewFile(&Arc::new(v), msrc);
+}
+
+// StaticData is a file with the content fixed at boot
+pub struct StaticData {
+    pub data: Vec<u8>,
+}
+
+impl SimpleFileTrait for StaticData {
+    fn GetFile(
+        &self,
+        _task: &Task,
+        _dir: &Inode,
+        dirent: &Dirent,
+        flags: FileFlags,
+    ) -> Result<File> {
+        let fops = NewSnapshotReadonlyFileOperations(self.data.clone());
+        let file = File::New(dirent, &flags, fops);
+        return Ok(file);
+    }
+}
+
+// NewNode is the guest numa topology given by qvisor, all the cpus are in node
+// 0 without the numa placement
+pub fn NewNode(task: &Task, msrc: &Arc<QMutex<MountSource>>) -> Inode {
+    let numa = SHARESPACE.numa.lock().clone();
+    let cores = GetKernel().applicationCores;
+    let nodeCnt = numa.NodeCnt();
+
+    let mut m = BTreeMap::new();
+    let nodes: Vec<usize> = (0..nodeCnt).collect();
+    let nodes = format!("{}\n", CpuList(&nodes));
+    for name in ["online", "possible", "has_cpu", "has_memory", "has_normal_memory"].iter() {
+        m.insert(name.to_string(), NewStatic(task, msrc, nodes.clone()));
+    }
+
+    for node in 0..nodeCnt {
+        let mut n = BTreeMap::new();
+        let cpus = numa.Cpus(node, cores);
+        n.insert(
+            "cpulist".to_string(),
+            NewStatic(task, msrc, format!("{}\n", CpuList(&cpus))),
+        );
+
+        let distance: Vec<String> = (0..nodeCnt)
+            .map(|i| {
+                if i == node {
+                    format!("{}", LOCAL_DISTANCE)
+                } else {
+                    format!("{}", REMOTE_DISTANCE)
+                }
+            })
+            .collect();
+        n.insert(
+            "distance".to_string(),
+            NewStatic(task, msrc, format!("{}\n", distance.join(" "))),
+        );
+
+        m.insert(format!("node{}", node), NewDir(task, msrc, n));
     }
 
     return NewDir(task, msrc, m);
@@ -99,7 +416,7 @@ pub fn NewSystemDir(task: &Task, msrc: &Arc<QMutex<MountSource>>) -> Inode {
     let mut m = BTreeMap::new();
 
     m.insert("cpu".to_string(), NewCPU(task, msrc));
-    //m.insert("node".to_string(), NewCPU(task, msrc));
+    m.insert("node".to_string(), NewNode(task, msrc));
     return NewDir(task, msrc, m);
 }
 
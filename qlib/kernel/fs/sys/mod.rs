@@ -12,8 +12,10 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod cgroup;
 pub mod devices;
 pub mod fs;
+pub mod net;
 pub mod sys;
 
 use crate::qlib::mutex::*;
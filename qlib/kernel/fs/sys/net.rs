@@ -0,0 +1,63 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::qlib::mutex::*;
+use alloc::collections::btree_map::BTreeMap;
+use alloc::string::ToString;
+use alloc::sync::Arc;
+
+use super::super::super::super::linux_def::*;
+use super::super::super::socket::netdevice::*;
+use super::super::super::task::*;
+use super::super::inode::*;
+use super::super::mount::*;
+use super::devices::*;
+use super::sys::*;
+
+// NewNetClassDir is /sys/class/net, the attributes are read from the network
+// devices when sysfs is mounted.
+pub fn NewNetClassDir(task: &Task, msrc: &Arc<QMutex<MountSource>>) -> Inode {
+    let mut m = BTreeMap::new();
+    for intf in NetDevices() {
+        let carrier = if intf.flags as u64 & LibcConst::IFF_RUNNING != 0 {
+            1
+        } else {
+            0
+        };
+
+        let files = [
+            ("address", intf.Address()),
+            ("addr_len", format!("{}", intf.address.len())),
+            ("carrier", format!("{}", carrier)),
+            ("flags", format!("{:#x}", intf.flags)),
+            ("ifindex", format!("{}", intf.index)),
+            ("iflink", format!("{}", intf.index)),
+            ("mtu", format!("{}", intf.mtu)),
+            ("operstate", intf.OperState().to_string()),
+            ("tx_queue_len", format!("{}", intf.txQueueLen)),
+            ("type", format!("{}", intf.hwType)),
+        ];
+
+        let mut dir = BTreeMap::new();
+        for (name, data) in files.iter() {
+            dir.insert(
+                name.to_string(),
+                NewStatic(task, msrc, format!("{}\n", data)),
+            );
+        }
+        m.insert(intf.name.clone(), NewDir(task, msrc, dir));
+    }
+
+    return NewDir(task, msrc, m);
+}
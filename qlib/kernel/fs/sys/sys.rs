@@ -26,7 +26,9 @@ use super::super::attr::*;
 use super::super::inode::*;
 use super::super::mount::*;
 use super::super::ramfs::dir::*;
+use super::cgroup::*;
 use super::devices::*;
+use super::net::*;
 
 pub fn NewFile<T: InodeOperations + 'static>(
     iops: &Arc<T>,
@@ -84,12 +86,15 @@ pub fn NewSys(task: &Task, msrc: &Arc<QMutex<MountSource>>) -> Inode {
         "power_supply".to_string(),
         NewDir(task, msrc, BTreeMap::new()),
     );
+    classContent.insert("net".to_string(), NewNetClassDir(task, msrc));
     content.insert("class".to_string(), NewDir(task, msrc, classContent));
 
     content.insert("dev".to_string(), NewDir(task, msrc, BTreeMap::new()));
     content.insert("devices".to_string(), NewDevicesDir(task, msrc));
     content.insert("firmware".to_string(), NewDir(task, msrc, BTreeMap::new()));
-    content.insert("fs".to_string(), NewDir(task, msrc, BTreeMap::new()));
+    let mut fsContent = BTreeMap::new();
+    fsContent.insert("cgroup".to_string(), NewCgroupDir(task, msrc));
+    content.insert("fs".to_string(), NewDir(task, msrc, fsContent));
     content.insert("kernel".to_string(), NewDir(task, msrc, BTreeMap::new()));
     content.insert("module".to_string(), NewDir(task, msrc, BTreeMap::new()));
     content.insert("power".to_string(), NewDir(task, msrc, BTreeMap::new()));
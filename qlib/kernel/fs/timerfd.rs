@@ -82,7 +82,8 @@ pub fn NewTimerfd(task: &Task, clockId: i32) -> Result<File> {
 
     let clock = match clockId {
         CLOCK_MONOTONIC => MONOTONIC_CLOCK.clone(),
-        CLOCK_REALTIME => REALTIME_CLOCK.clone(),
+        CLOCK_REALTIME | CLOCK_REALTIME_ALARM => REALTIME_CLOCK.clone(),
+        CLOCK_BOOTTIME | CLOCK_BOOTTIME_ALARM => BOOTTIME_CLOCK.clone(),
         _ => return Err(Error::SysError(SysErr::EINVAL)),
     };
 
@@ -17,11 +17,17 @@ use alloc::collections::btree_map::BTreeMap;
 use alloc::string::String;
 use alloc::string::ToString;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering;
 
 use super::super::super::super::auth::id::*;
 use super::super::super::super::common::*;
 use super::super::super::super::linux_def::*;
 use super::super::super::task::*;
+use super::super::super::Kernel::HostSpace;
+use super::super::dirent::*;
 use super::super::filesystems::*;
 use super::super::host::fs::*;
 use super::super::inode::*;
@@ -37,12 +43,230 @@ pub const ROOT_UIDKEY: &str = "uid";
 // GID for the root directory.
 pub const ROOT_GIDKEY: &str = "gid";
 
+// Size limit of the file data, bytes with an optional k, m or g suffix or a
+// percentage of the memory.
+pub const SIZE_KEY: &str = "size";
+
 // Permissions that exceed modeMask will be rejected.
 pub const MODE_MASK: u16 = 0o1777;
 
 // Default permissions are read/write/execute.
 pub const DEFAULT_MODE: u16 = 0o777;
 
+// TmpfsQuota bounds the file data of a tmpfs mounted with the size option.
+// The files are charged for the pages that writes, fallocate and page faults
+// allocate, see TmpfsCharge.
+pub struct TmpfsQuota {
+    pub limit: u64,
+    pub used: AtomicU64,
+}
+
+impl TmpfsQuota {
+    pub fn New(limit: u64) -> Self {
+        return Self {
+            limit: Self::PageRound(limit),
+            used: AtomicU64::new(0),
+        };
+    }
+
+    pub fn PageRound(size: u64) -> u64 {
+        return (size + MemoryDef::PAGE_MASK) & !MemoryDef::PAGE_MASK;
+    }
+
+    // Reserve charges len bytes to the quota, it fails with ENOSPC past the
+    // limit.
+    pub fn Reserve(&self, len: u64) -> Result<()> {
+        let delta = len;
+        let mut used = self.used.load(Ordering::SeqCst);
+        loop {
+            if used + delta > self.limit {
+                return Err(Error::SysError(SysErr::ENOSPC));
+            }
+
+            match self
+                .used
+                .compare_exchange(used, used + delta, Ordering::SeqCst, Ordering::SeqCst)
+            {
+                Ok(_) => return Ok(()),
+                Err(v) => used = v,
+            }
+        }
+    }
+
+    pub fn Release(&self, len: u64) {
+        self.used.fetch_sub(len, Ordering::SeqCst);
+    }
+
+    pub fn FsInfo(&self) -> FsInfo {
+        let pages = self.limit / MemoryDef::PAGE_SIZE;
+        let freePages = pages - self.used.load(Ordering::SeqCst) / MemoryDef::PAGE_SIZE;
+        return FsInfo {
+            Type: FSMagic::TMPFS_MAGIC,
+            TotalBlocks: pages,
+            FreeBlocks: freePages,
+            TotalFiles: pages,
+            FreeFiles: pages,
+            BlockSize: MemoryDef::PAGE_SIZE as i64,
+            AvailBlocks: freePages,
+        };
+    }
+}
+
+// TmpfsMountSourceOperations carries the quota of a tmpfs mount to its inodes.
+pub struct TmpfsMountSourceOperations {
+    pub quota: Arc<TmpfsQuota>,
+}
+
+impl DirentOperations for TmpfsMountSourceOperations {
+    fn Revalidate(&self, _name: &str, _parent: &Inode, _child: &Inode) -> bool {
+        return false;
+    }
+
+    fn Keep(&self, _dirent: &Dirent) -> bool {
+        return false;
+    }
+
+    fn CacheReadDir(&self) -> bool {
+        return false;
+    }
+}
+
+impl MountSourceOperations for TmpfsMountSourceOperations {
+    fn as_any(&self) -> &Any {
+        return self;
+    }
+
+    fn Destroy(&mut self) {}
+
+    fn ResetInodeMappings(&mut self) {}
+
+    fn SaveInodeMapping(&mut self, _inode: &Inode, _path: &str) {}
+}
+
+// TmpfsCharge is the charge of a tmpfs file to the quota of its mount. It
+// keeps the page ranges of the file that are charged, as start -> end, so a
+// page is charged once however often it is written or faulted.
+pub struct TmpfsCharge {
+    pub quota: Arc<TmpfsQuota>,
+    pub ranges: BTreeMap<u64, u64>,
+}
+
+impl Drop for TmpfsCharge {
+    fn drop(&mut self) {
+        let total: u64 = self.ranges.iter().map(|(start, end)| end - start).sum();
+        self.quota.Release(total);
+    }
+}
+
+impl TmpfsCharge {
+    pub fn New(quota: Arc<TmpfsQuota>) -> Self {
+        return Self {
+            quota: quota,
+            ranges: BTreeMap::new(),
+        };
+    }
+
+    // Charge charges the pages of [start, end) which are not charged yet.
+    pub fn Charge(&mut self, start: u64, end: u64) -> Result<()> {
+        let mut start = start & !MemoryDef::PAGE_MASK;
+        let mut end = TmpfsQuota::PageRound(end);
+        if start >= end {
+            return Ok(());
+        }
+
+        // the charged ranges overlapping or adjacent to [start, end)
+        let mut merged = Vec::new();
+        let mut delta = end - start;
+        for (s, e) in self.ranges.range(..=end).rev() {
+            if *e < start {
+                break;
+            }
+
+            let overlap = core::cmp::min(*e, end).saturating_sub(core::cmp::max(*s, start));
+            delta -= overlap;
+            merged.push(*s);
+        }
+
+        self.quota.Reserve(delta)?;
+
+        for s in merged {
+            let e = self.ranges.remove(&s).unwrap();
+            start = core::cmp::min(start, s);
+            end = core::cmp::max(end, e);
+        }
+        self.ranges.insert(start, end);
+        return Ok(());
+    }
+
+    // Truncate releases the charged pages from size on.
+    pub fn Truncate(&mut self, size: u64) {
+        let size = TmpfsQuota::PageRound(size);
+        let mut released = 0;
+        let cut: Vec<(u64, u64)> = self
+            .ranges
+            .iter()
+            .filter(|(_, e)| **e > size)
+            .map(|(s, e)| (*s, *e))
+            .collect();
+        for (s, e) in cut {
+            self.ranges.remove(&s);
+            if s < size {
+                self.ranges.insert(s, size);
+                released += e - size;
+            } else {
+                released += e - s;
+            }
+        }
+
+        self.quota.Release(released);
+    }
+}
+
+// TmpfsQuotaOf returns the quota of the tmpfs mount, None if it has no size limit.
+pub fn TmpfsQuotaOf(msrc: &Arc<QMutex<MountSource>>) -> Option<Arc<TmpfsQuota>> {
+    let mops = msrc.lock().MountSourceOperations.clone();
+    let mops = mops.lock();
+    return match mops.as_any().downcast_ref::<TmpfsMountSourceOperations>() {
+        None => None,
+        Some(ops) => Some(ops.quota.clone()),
+    };
+}
+
+// ParseSize parses the value of the size option as linux memparse, plus the
+// percentage of the memory which tmpfs accepts.
+pub fn ParseSize(s: &str) -> Result<u64> {
+    let (num, shift, percent) = match s.chars().last() {
+        Some('k') | Some('K') => (&s[..s.len() - 1], 10, false),
+        Some('m') | Some('M') => (&s[..s.len() - 1], 20, false),
+        Some('g') | Some('G') => (&s[..s.len() - 1], 30, false),
+        Some('%') => (&s[..s.len() - 1], 0, true),
+        _ => (s, 0, false),
+    };
+
+    let n = match num.parse::<u64>() {
+        Ok(v) => v,
+        Err(e) => {
+            info!("size value not parsable 'size={}': {:?}", s, e);
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+    };
+
+    if percent {
+        let mut info = LibcSysinfo::default();
+        let ret = HostSpace::Sysinfo(&mut info as *mut _ as u64);
+        if ret < 0 {
+            return Err(Error::SysError(-ret as i32));
+        }
+
+        return Ok(info.totalram * info.mem_unit as u64 / 100 * n);
+    }
+
+    return match n.checked_mul(1 << shift) {
+        None => Err(Error::SysError(SysErr::EINVAL)),
+        Some(v) => Ok(v),
+    };
+}
+
 pub struct TmpfsFileSystem {}
 
 impl Filesystem for TmpfsFileSystem {
@@ -72,7 +296,7 @@ impl Filesystem for TmpfsFileSystem {
         match options.remove(MODE_KEY) {
             None => (),
             Some(m) => {
-                let i = match m.parse::<u16>() {
+                let i = match u16::from_str_radix(&m, 8) {
                     Ok(v) => v,
                     Err(e) => {
                         info!("mode value not parsable 'mode={}': {:?}", m, e);
@@ -123,6 +347,11 @@ impl Filesystem for TmpfsFileSystem {
             }
         }
 
+        let quota = match options.remove(SIZE_KEY) {
+            None => None,
+            Some(s) => Some(Arc::new(TmpfsQuota::New(ParseSize(&s)?))),
+        };
+
         // Fail if the caller passed us more options than we can parse. They may be
         // expecting us to set something we can't set.
         if options.len() > 0 {
@@ -130,7 +359,14 @@ impl Filesystem for TmpfsFileSystem {
             return Err(Error::SysError(SysErr::EINVAL));
         }
 
-        let msrc = MountSource::NewCachingMountSource(self, flags);
+        let mut msrc = MountSource::NewCachingMountSource(self, flags);
+        match quota {
+            None => (),
+            Some(quota) => {
+                msrc.MountSourceOperations =
+                    Arc::new(QMutex::new(TmpfsMountSourceOperations { quota: quota }));
+            }
+        }
 
         let inode = NewTmpfsDir(
             task,
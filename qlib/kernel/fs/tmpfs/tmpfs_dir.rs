@@ -26,6 +26,7 @@ use super::super::super::super::linux_def::*;
 use super::super::super::kernel::time::*;
 use super::super::super::socket::unix::transport::unix::*;
 use super::super::super::task::*;
+use super::super::super::Kernel::HostSpace;
 use super::super::attr::*;
 use super::super::dirent::*;
 use super::super::file::*;
@@ -34,18 +35,38 @@ use super::super::host::hostinodeop::*;
 use super::super::inode::*;
 use super::super::mount::*;
 use super::super::ramfs::dir::*;
+use super::fs::*;
 use super::tmpfs_fifo::*;
 use super::tmpfs_file::*;
 use super::tmpfs_socket::*;
 use super::tmpfs_symlink::*;
 
-pub const TMPFS_FSINFO: FsInfo = FsInfo {
-    Type: FSMagic::TMPFS_MAGIC,
-    TotalBlocks: 0,
-    FreeBlocks: 0,
-    TotalFiles: 0,
-    FreeFiles: 0,
-};
+// TmpfsFsInfo reports the default size of a linux tmpfs, half of the memory.
+// The file data of tmpfs is in the host memory, the free blocks are bounded
+// by the free memory of the host.
+pub fn TmpfsFsInfo() -> FsInfo {
+    let mut info = LibcSysinfo::default();
+    let ret = HostSpace::Sysinfo(&mut info as *mut _ as u64);
+    if ret < 0 || info.mem_unit == 0 {
+        return FsInfo {
+            Type: FSMagic::TMPFS_MAGIC,
+            ..Default::default()
+        };
+    }
+
+    let unit = info.mem_unit as u64;
+    let pages = info.totalram * unit / 2 / MemoryDef::PAGE_SIZE;
+    let freePages = core::cmp::min(info.freeram * unit / MemoryDef::PAGE_SIZE, pages);
+    return FsInfo {
+        Type: FSMagic::TMPFS_MAGIC,
+        TotalBlocks: pages,
+        FreeBlocks: freePages,
+        TotalFiles: pages,
+        FreeFiles: pages,
+        BlockSize: MemoryDef::PAGE_SIZE as i64,
+        AvailBlocks: freePages,
+    };
+}
 
 pub fn TmpfsRename(
     task: &Task,
@@ -54,6 +75,7 @@ pub fn TmpfsRename(
     newParent: &Inode,
     newname: &str,
     _replacement: bool,
+    _flags: u32,
 ) -> Result<()> {
     let oldInode = oldParent.lock().InodeOp.clone();
     let op = match oldInode.as_any().downcast_ref::<TmpfsDir>() {
@@ -74,6 +96,7 @@ pub fn TmpfsRename(
         Arc::new(np.0.clone()),
         newname,
         _replacement,
+        _flags,
     )
 }
 
@@ -85,7 +108,7 @@ pub fn NewTmpfsDir(
     msrc: Arc<QMutex<MountSource>>,
 ) -> Inode {
     let d = Dir::New(task, contents, owner, perms);
-    let d = TmpfsDir(d);
+    let d = TmpfsDir(d, TmpfsQuotaOf(&msrc));
 
     let createOps = d.NewCreateOps();
     d.0.write().CreateOps = createOps;
@@ -104,7 +127,7 @@ pub fn NewTmpfsDir(
     return Inode::New(&Arc::new(d), &msrc, &attr);
 }
 
-pub struct TmpfsDir(pub Dir);
+pub struct TmpfsDir(pub Dir, pub Option<Arc<TmpfsQuota>>);
 
 fn NewDirFn(task: &Task, dir: &Inode, perms: &FilePermissions) -> Result<Inode> {
     let msrc = dir.lock().MountSource.clone();
@@ -259,8 +282,17 @@ impl InodeOperations for TmpfsDir {
         newParent: &Inode,
         newname: &str,
         replacement: bool,
+        flags: u32,
     ) -> Result<()> {
-        return TmpfsRename(task, oldParent, oldname, newParent, newname, replacement);
+        return TmpfsRename(
+            task,
+            oldParent,
+            oldname,
+            newParent,
+            newname,
+            replacement,
+            flags,
+        );
     }
 
     fn Bind(
@@ -347,7 +379,10 @@ impl InodeOperations for TmpfsDir {
     }
 
     fn StatFS(&self, _task: &Task) -> Result<FsInfo> {
-        return Ok(TMPFS_FSINFO);
+        match &self.1 {
+            None => return Ok(TmpfsFsInfo()),
+            Some(quota) => return Ok(quota.FsInfo()),
+        }
     }
 
     fn Mappable(&self) -> Result<HostInodeOp> {
@@ -156,8 +156,17 @@ impl InodeOperations for TmpfsFifoInodeOp {
         newParent: &Inode,
         newname: &str,
         replacement: bool,
+        flags: u32,
     ) -> Result<()> {
-        return TmpfsRename(task, oldParent, oldname, newParent, newname, replacement);
+        return TmpfsRename(
+            task,
+            oldParent,
+            oldname,
+            newParent,
+            newname,
+            replacement,
+            flags,
+        );
     }
 
     fn Bind(
@@ -245,7 +254,7 @@ impl InodeOperations for TmpfsFifoInodeOp {
     }
 
     fn StatFS(&self, _task: &Task) -> Result<FsInfo> {
-        return Ok(TMPFS_FSINFO);
+        return Ok(TmpfsFsInfo());
     }
 
     fn Mappable(&self) -> Result<HostInodeOp> {
@@ -34,6 +34,7 @@ use super::super::flags::*;
 use super::super::host::hostinodeop::*;
 use super::super::inode::*;
 use super::super::mount::*;
+use super::fs::*;
 use super::tmpfs_dir::*;
 
 pub fn NewTmpfsFileInode(
@@ -55,9 +56,16 @@ pub fn NewTmpfsFileInode(
         Some(iops) => iops.clone(),
     };
 
+    let quota = TmpfsQuotaOf(msrc);
+    match &quota {
+        None => (),
+        Some(q) => hostiops.lock().charge = Some(TmpfsCharge::New(q.clone())),
+    }
+
     let ops = TmpfsFileInodeOp {
         inodeops: hostiops,
         uattr: Arc::new(QMutex::new(uattr)),
+        quota: quota,
     };
 
     let deviceId = TMPFS_DEVICE.lock().DeviceID();
@@ -77,6 +85,8 @@ pub fn NewTmpfsFileInode(
 pub struct TmpfsFileInodeOp {
     pub inodeops: HostInodeOp,
     pub uattr: Arc<QMutex<UnstableAttr>>,
+    // quota of the mount, the file is charged to it by inodeops
+    pub quota: Option<Arc<TmpfsQuota>>,
 }
 
 impl InodeOperations for TmpfsFileInodeOp {
@@ -167,8 +177,17 @@ impl InodeOperations for TmpfsFileInodeOp {
         newParent: &Inode,
         newname: &str,
         replacement: bool,
+        flags: u32,
     ) -> Result<()> {
-        return TmpfsRename(task, oldParent, oldname, newParent, newname, replacement);
+        return TmpfsRename(
+            task,
+            oldParent,
+            oldname,
+            newParent,
+            newname,
+            replacement,
+            flags,
+        );
     }
 
     fn Bind(
@@ -234,10 +253,15 @@ impl InodeOperations for TmpfsFileInodeOp {
     }
 
     fn Truncate(&self, task: &Task, dir: &mut Inode, size: i64) -> Result<()> {
-        return self.inodeops.Truncate(task, dir, size);
+        // growing the file leaves a hole, only the truncated pages are released
+        self.inodeops.Truncate(task, dir, size)?;
+        self.inodeops.UnchargeFrom(size as u64);
+        return Ok(());
     }
 
     fn Allocate(&self, task: &Task, dir: &mut Inode, offset: i64, length: i64) -> Result<()> {
+        self.inodeops
+            .ChargeRange(offset as u64, (offset + length) as u64)?;
         return self.inodeops.Allocate(task, dir, offset, length);
     }
 
@@ -266,7 +290,10 @@ impl InodeOperations for TmpfsFileInodeOp {
     }
 
     fn StatFS(&self, _task: &Task) -> Result<FsInfo> {
-        return Ok(TMPFS_FSINFO);
+        match &self.quota {
+            None => return Ok(TmpfsFsInfo()),
+            Some(quota) => return Ok(quota.FsInfo()),
+        }
     }
 
     fn Mappable(&self) -> Result<HostInodeOp> {
@@ -149,8 +149,17 @@ impl InodeOperations for TmpfsSocket {
         newParent: &Inode,
         newname: &str,
         replacement: bool,
+        flags: u32,
     ) -> Result<()> {
-        return TmpfsRename(task, oldParent, oldname, newParent, newname, replacement);
+        return TmpfsRename(
+            task,
+            oldParent,
+            oldname,
+            newParent,
+            newname,
+            replacement,
+            flags,
+        );
     }
 
     fn Bind(
@@ -237,7 +246,7 @@ impl InodeOperations for TmpfsSocket {
     }
 
     fn StatFS(&self, _task: &Task) -> Result<FsInfo> {
-        return Ok(TMPFS_FSINFO);
+        return Ok(TmpfsFsInfo());
     }
 
     fn Mappable(&self) -> Result<HostInodeOp> {
@@ -148,8 +148,17 @@ impl InodeOperations for TmpfsSymlink {
         newParent: &Inode,
         newname: &str,
         replacement: bool,
+        flags: u32,
     ) -> Result<()> {
-        return TmpfsRename(task, oldParent, oldname, newParent, newname, replacement);
+        return TmpfsRename(
+            task,
+            oldParent,
+            oldname,
+            newParent,
+            newname,
+            replacement,
+            flags,
+        );
     }
 
     fn Bind(
@@ -236,7 +245,7 @@ impl InodeOperations for TmpfsSymlink {
     }
 
     fn StatFS(&self, _task: &Task) -> Result<FsInfo> {
-        return Ok(TMPFS_FSINFO);
+        return Ok(TmpfsFsInfo());
     }
 
     fn Mappable(&self) -> Result<HostInodeOp> {
@@ -26,6 +26,7 @@ use super::super::super::super::common::*;
 use super::super::super::super::device::*;
 use super::super::super::super::linux_def::*;
 use super::super::super::kernel::time::*;
+use super::super::super::kernel::waiter::qlock::*;
 use super::super::super::kernel::waiter::*;
 use super::super::super::socket::unix::transport::unix::*;
 use super::super::super::task::*;
@@ -40,6 +41,7 @@ use super::super::host::hostinodeop::*;
 use super::super::inode::*;
 use super::super::mount::*;
 use super::master::*;
+use super::slave::*;
 use super::terminal::*;
 
 pub fn NewDir(task: &Task, msrc: &Arc<QMutex<MountSource>>) -> Inode {
@@ -59,7 +61,6 @@ pub fn NewDir(task: &Task, msrc: &Arc<QMutex<MountSource>>) -> Inode {
         master: Inode::default(),
         slaves: BTreeMap::new(),
         dentryMap: DentMap::New(BTreeMap::new()),
-        next: 0,
     })));
 
     let master = NewMasterNode(
@@ -114,7 +115,6 @@ pub struct DirInodeOperationsInternal {
     pub master: Inode,
     pub slaves: BTreeMap<u32, Inode>,
     pub dentryMap: DentMap,
-    pub next: u32,
 }
 
 #[derive(Clone)]
@@ -129,28 +129,56 @@ impl Deref for DirInodeOperations {
 }
 
 impl DirInodeOperations {
-    pub fn allocateTerminal(&self, task: &Task) -> Result<Terminal> {
-        let mut internal = self.lock();
+    // allocateTerminal creates a new pty pair with the lowest free index,
+    // as linux does, and adds the slave to the directory.
+    pub fn allocateTerminal(&self, task: &Task) -> Result<Arc<Terminal>> {
+        let creds = task.creds.clone();
+        let owner = FileOwner {
+            UID: creds.lock().EffectiveKUID,
+            GID: creds.lock().EffectiveKGID,
+        };
 
-        let n = internal.next;
-        if n == (1 << 32 - 1) as u32 {
-            //MaxUint32
-            return Err(Error::SysError(SysErr::ENOMEM));
-        }
+        let mut internal = self.lock();
 
-        if internal.slaves.contains_key(&n) {
-            panic!("pty index collision; index {} already exists", n);
+        let mut n: u32 = 0;
+        while internal.slaves.contains_key(&n) {
+            if n == core::u32::MAX {
+                return Err(Error::SysError(SysErr::ENOMEM));
+            }
+            n += 1;
         }
 
-        let t = Terminal::New(self, n);
-        internal.next += 1;
+        let t = Arc::new(Terminal::New(self, n));
+        let msrc = internal.msrc.clone();
+        let slave = NewSlaveNode(
+            task,
+            self,
+            &msrc,
+            &t,
+            &owner,
+            &FilePermissions::FromMode(FileMode(0o600)),
+        );
 
-        let creds = task.creds.clone();
-        let _uid = creds.lock().EffectiveKUID;
-        let _gid = creds.lock().EffectiveKGID;
+        let stableAttr = slave.lock().StableAttr().clone();
+        internal.dentryMap.Add(
+            &n.to_string(),
+            &DentAttr {
+                Type: stableAttr.Type,
+                InodeId: stableAttr.InodeId,
+            },
+        );
+        internal.slaves.insert(n, slave);
 
         return Ok(t);
     }
+
+    // MasterClose removes the slave of pty n once its master is closed.
+    // Files already open on the slave keep working until they are closed.
+    pub fn MasterClose(&self, n: u32) {
+        let mut internal = self.lock();
+        internal.slaves.remove(&n);
+        internal.dentryMap.Remove(&n.to_string());
+    }
 }
 
 impl InodeOperations for DirInodeOperations {
@@ -262,6 +290,7 @@ impl InodeOperations for DirInodeOperations {
         _newParent: &Inode,
         _newname: &str,
         _replacement: bool,
+        _flags: u32,
     ) -> Result<()> {
         return Err(Error::SysError(SysErr::EINVAL));
     }
@@ -285,10 +314,23 @@ impl InodeOperations for DirInodeOperations {
         &self,
         _task: &Task,
         _dir: &Inode,
-        _dirent: &Dirent,
-        _flags: FileFlags,
+        dirent: &Dirent,
+        flags: FileFlags,
     ) -> Result<File> {
-        return Err(Error::SysError(SysErr::EPERM));
+        let fileOp = Arc::new(DirFileOperations {
+            di: self.clone(),
+            DirCursor: QMutex::new("".to_string()),
+        });
+
+        let internal = FileInternal {
+            UniqueId: NewUID(),
+            Dirent: dirent.clone(),
+            flags: QMutex::new((flags, None)),
+            offset: QLock::New(0),
+            FileOp: fileOp,
+        };
+
+        return Ok(File(Arc::new(internal)));
     }
 
     fn UnstableAttr(&self, _task: &Task) -> Result<UnstableAttr> {
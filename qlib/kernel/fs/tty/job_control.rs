@@ -0,0 +1,344 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use crate::qlib::mutex::*;
+use alloc::sync::Arc;
+use core::ops::Deref;
+
+use super::super::super::super::common::*;
+use super::super::super::super::linux_def::*;
+use super::super::super::task::*;
+use super::super::super::threadmgr::processgroup::*;
+use super::super::super::threadmgr::session::*;
+use super::super::super::threadmgr::task_usermem::*;
+use super::super::super::uid::*;
+use super::super::super::SignalDef::*;
+
+// JobControl tracks the session a terminal is the controlling terminal of
+// and the foreground process group of that session. It is shared by the
+// host console and the devpts terminals.
+#[derive(Default)]
+pub struct JobControlInternal {
+    pub session: Option<Session>,
+    pub fgProcessgroup: Option<ProcessGroup>,
+}
+
+#[derive(Clone)]
+pub struct JobControl {
+    pub uid: UniqueID,
+    pub data: Arc<QMutex<JobControlInternal>>,
+}
+
+impl Deref for JobControl {
+    type Target = Arc<QMutex<JobControlInternal>>;
+
+    fn deref(&self) -> &Arc<QMutex<JobControlInternal>> {
+        &self.data
+    }
+}
+
+impl Default for JobControl {
+    fn default() -> Self {
+        return Self::New();
+    }
+}
+
+impl JobControl {
+    pub fn New() -> Self {
+        return Self {
+            uid: NewUID(),
+            data: Arc::new(QMutex::new(JobControlInternal::default())),
+        };
+    }
+
+    // InitForegroundProcessGroup makes the terminal the controlling terminal
+    // of pg's session with pg as the foreground process group. It is used by
+    // the loader for the container console.
+    pub fn InitForegroundProcessGroup(&self, pg: &ProcessGroup) {
+        let mut jc = self.lock();
+        if jc.fgProcessgroup.is_some() {
+            panic!("foreground process group is already set");
+        }
+
+        let session = pg.Session();
+        session.lock().tty = Some(self.clone());
+        jc.fgProcessgroup = Some(pg.clone());
+        jc.session = Some(session);
+    }
+
+    pub fn ForegroundProcessGroup(&self) -> Option<ProcessGroup> {
+        return self.lock().fgProcessgroup.clone();
+    }
+
+    // CheckChange checks that the calling task may read from, write to or
+    // change the attributes of the terminal, the same as tty_check_change in
+    // drivers/tty/tty_io.c. A background process group gets sig and the call
+    // is restarted once it is continued.
+    pub fn CheckChange(&self, task: &Task, sig: Signal) -> Result<()> {
+        let thread = match &task.thread {
+            // No task? Linux does not have an analog for this case, but
+            // tty_check_change is more of a blacklist of cases than a
+            // whitelist, and is surprisingly permissive. Allowing the
+            // change seems most appropriate.
+            None => return Ok(()),
+            Some(ref t) => t.clone(),
+        };
+
+        let (session, fgProcessgroup) = {
+            let jc = self.lock();
+            (jc.session.clone(), jc.fgProcessgroup.clone())
+        };
+
+        // If the session for the task is different than the session for the
+        // controlling TTY, then the change is allowed. Seems like a bad idea,
+        // but that's exactly what linux does.
+        let tg = thread.ThreadGroup();
+        if session.is_none() || tg.Session() != session {
+            return Ok(());
+        }
+
+        // If we are the foreground process group, then the change is allowed.
+        let pg = match tg.ProcessGroup() {
+            None => return Ok(()),
+            Some(pg) => pg,
+        };
+
+        if Some(pg.clone()) == fgProcessgroup {
+            return Ok(());
+        }
+
+        // We are not the foreground process group.
+
+        // Is the provided signal blocked or ignored?
+        if thread.SignalMask().0 & sig.Mask() != 0 || tg.SignalHandlers().IsIgored(sig) {
+            // If the signal is SIGTTIN, then we are attempting to read
+            // from the TTY. Don't send the signal and return EIO.
+            if sig.0 == Signal::SIGTTIN {
+                return Err(Error::SysError(SysErr::EIO));
+            }
+
+            // Otherwise, we are writing or changing terminal state. This is allowed.
+            return Ok(());
+        }
+
+        // If the process group is an orphan, return EIO.
+        if pg.IsOrphan() {
+            return Err(Error::SysError(SysErr::EIO));
+        }
+
+        // Otherwise, send the signal to the process group and return ERESTARTSYS.
+        pg.SendSignal(&SignalInfo::SignalInfoPriv(sig))?;
+        return Err(Error::SysError(SysErr::ERESTARTSYS));
+    }
+
+    // SetControllingTTY implements TIOCSCTTY. steal is the ioctl argument;
+    // a non-zero value lets a CAP_SYS_ADMIN caller take the terminal away
+    // from another session.
+    pub fn SetControllingTTY(&self, task: &Task, steal: bool) -> Result<()> {
+        let thread = match &task.thread {
+            None => return Err(Error::SysError(SysErr::ENOTTY)),
+            Some(ref t) => t.clone(),
+        };
+
+        let tg = thread.ThreadGroup();
+        let (session, pg) = match (tg.Session(), tg.ProcessGroup()) {
+            (Some(s), Some(pg)) => (s, pg),
+            _ => return Err(Error::SysError(SysErr::EPERM)),
+        };
+
+        let mut jc = self.lock();
+
+        // Nothing to do if this is already our controlling terminal.
+        if jc.session.as_ref() == Some(&session) {
+            return Ok(());
+        }
+
+        // Only a session leader without a controlling terminal can acquire one.
+        if session.lock().leader != tg || session.lock().tty.is_some() {
+            return Err(Error::SysError(SysErr::EPERM));
+        }
+
+        if let Some(old) = jc.session.clone() {
+            if !steal || !thread.HasCapability(Capability::CAP_SYS_ADMIN) {
+                return Err(Error::SysError(SysErr::EPERM));
+            }
+
+            old.lock().tty = None;
+        }
+
+        session.lock().tty = Some(self.clone());
+        jc.session = Some(session);
+        jc.fgProcessgroup = Some(pg);
+        return Ok(());
+    }
+
+    // ReleaseControllingTTY implements TIOCNOTTY. When the session leader gives
+    // up the terminal, the foreground process group gets SIGHUP and SIGCONT.
+    pub fn ReleaseControllingTTY(&self, task: &Task) -> Result<()> {
+        let thread = match &task.thread {
+            None => return Err(Error::SysError(SysErr::ENOTTY)),
+            Some(ref t) => t.clone(),
+        };
+
+        let tg = thread.ThreadGroup();
+        let session = tg.Session();
+
+        let fgProcessgroup = {
+            let mut jc = self.lock();
+            if session.is_none() || jc.session != session {
+                return Err(Error::SysError(SysErr::ENOTTY));
+            }
+
+            let session = session.unwrap();
+            if session.lock().leader != tg {
+                return Ok(());
+            }
+
+            session.lock().tty = None;
+            jc.session = None;
+            jc.fgProcessgroup.take()
+        };
+
+        if let Some(pg) = fgProcessgroup {
+            pg.SendSignal(&SignalInfo::SignalInfoPriv(Signal(Signal::SIGHUP)))?;
+            pg.SendSignal(&SignalInfo::SignalInfoPriv(Signal(Signal::SIGCONT)))?;
+        }
+
+        return Ok(());
+    }
+
+    // SessionLeaderExit disassociates the terminal from session when its
+    // leader exits and hangs up the foreground process group.
+    pub fn SessionLeaderExit(&self, session: &Session) {
+        let fgProcessgroup = {
+            let mut jc = self.lock();
+            if jc.session.as_ref() != Some(session) {
+                return;
+            }
+
+            jc.session = None;
+            jc.fgProcessgroup.take()
+        };
+
+        session.lock().tty = None;
+        if let Some(pg) = fgProcessgroup {
+            match pg.SendSignal(&SignalInfo::SignalInfoPriv(Signal(Signal::SIGHUP))) {
+                Err(e) => info!("tty: fail to hang up the foreground process group: {:?}", e),
+                Ok(()) => (),
+            }
+        }
+    }
+
+    // GetForegroundProcessGroup implements TIOCGPGRP.
+    pub fn GetForegroundProcessGroup(&self, task: &Task, dstAddr: u64) -> Result<()> {
+        let thread = task.Thread();
+        let tg = thread.ThreadGroup();
+        let pidns = tg.PIDNamespace();
+
+        let pg = {
+            let jc = self.lock();
+            if jc.session.is_none() || tg.Session() != jc.session {
+                return Err(Error::SysError(SysErr::ENOTTY));
+            }
+
+            match jc.fgProcessgroup.clone() {
+                None => return Err(Error::SysError(SysErr::ENOTTY)),
+                Some(pg) => pg,
+            }
+        };
+
+        let pgid = pidns.IDOfProcessGroup(&pg);
+        UserPtr::New(dstAddr).Write(task, &pgid)?;
+        return Ok(());
+    }
+
+    // SetForegroundProcessGroup implements TIOCSPGRP.
+    pub fn SetForegroundProcessGroup(&self, task: &Task, srcAddr: u64) -> Result<()> {
+        let thread = match &task.thread {
+            None => return Err(Error::SysError(SysErr::ENOTTY)),
+            Some(ref t) => t.clone(),
+        };
+
+        match self.CheckChange(task, Signal(Signal::SIGTTOU)) {
+            // drivers/tty/tty_io.c:tiocspgrp() converts -EIO from
+            // tty_check_change() to -ENOTTY.
+            Err(Error::SysError(SysErr::EIO)) => return Err(Error::SysError(SysErr::ENOTTY)),
+            Err(e) => return Err(e),
+            Ok(()) => (),
+        }
+
+        let tg = thread.ThreadGroup();
+        let session = tg.Session();
+
+        let mut jc = self.lock();
+        if jc.session.is_none() || session != jc.session {
+            return Err(Error::SysError(SysErr::ENOTTY));
+        }
+
+        let pgid: i32 = UserPtr::New(srcAddr).Read(task)?;
+        if pgid < 0 {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        let pidns = tg.PIDNamespace();
+        let pg = match pidns.ProcessGroupWithID(pgid) {
+            None => return Err(Error::SysError(SysErr::ESRCH)),
+            Some(pg) => pg,
+        };
+
+        // Check that new process group is in the TTY session.
+        if Some(pg.Session()) != session {
+            return Err(Error::SysError(SysErr::EPERM));
+        }
+
+        jc.fgProcessgroup = Some(pg);
+        return Ok(());
+    }
+
+    // GetSessionID implements TIOCGSID.
+    pub fn GetSessionID(&self, task: &Task, dstAddr: u64) -> Result<()> {
+        let thread = task.Thread();
+        let tg = thread.ThreadGroup();
+        let pidns = tg.PIDNamespace();
+
+        let session = {
+            let jc = self.lock();
+            if jc.session.is_none() || tg.Session() != jc.session {
+                return Err(Error::SysError(SysErr::ENOTTY));
+            }
+
+            jc.session.clone().unwrap()
+        };
+
+        let sid = pidns.IDOfSession(&session);
+        UserPtr::New(dstAddr).Write(task, &sid)?;
+        return Ok(());
+    }
+
+    // SignalForeground sends sig to the foreground process group, e.g. SIGINT
+    // for ^C or SIGWINCH after a window size change.
+    pub fn SignalForeground(&self, sig: Signal) {
+        let pg = match self.ForegroundProcessGroup() {
+            None => return,
+            Some(pg) => pg,
+        };
+
+        match pg.SendSignal(&SignalInfo::SignalInfoPriv(sig)) {
+            Err(e) => info!(
+                "tty: fail to send {:?} to the foreground process group: {:?}",
+                sig, e
+            ),
+            Ok(()) => (),
+        }
+    }
+}
@@ -14,10 +14,12 @@
 
 use crate::qlib::mutex::*;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 
 use super::super::super::super::common::*;
 use super::super::super::super::linux_def::*;
 use super::super::super::task::*;
+use super::super::super::threadmgr::task_usermem::*;
 //use super::super::super::mem::seq::*;
 use super::super::host::tty::*;
 use super::queue::*;
@@ -40,6 +42,10 @@ pub struct LineDiscipline {
     pub outQueue: Arc<QMutex<Queue>>,
     pub termios: KernelTermios,
     pub column: i32,
+
+    // signals generated by the ISIG characters. They are sent to the
+    // foreground process group by the caller once the locks are released.
+    pub signals: Vec<Signal>,
 }
 
 impl LineDiscipline {
@@ -50,6 +56,7 @@ impl LineDiscipline {
             outQueue: Arc::new(QMutex::new(Queue::NewOutputQueue())),
             termios: termios,
             column: 0,
+            signals: Vec::new(),
         };
 
         return ld;
@@ -57,46 +64,165 @@ impl LineDiscipline {
 
     pub fn GetTermios(&self, task: &Task, dstAddr: u64) -> Result<()> {
         let t = self.termios.ToTermios();
-        task.CopyOutObj(&t, dstAddr)?;
+        UserPtr::New(dstAddr).Write(task, &t)?;
         return Ok(());
     }
 
     pub fn SetTermios(&mut self, task: &Task, srcAddr: u64) -> Result<()> {
         let oldCanonEnabled = self.termios.LEnabled(LocalFlags::ICANON);
 
-        let t: Termios = task.CopyInObj(srcAddr)?;
+        let t: Termios = UserPtr::New(srcAddr).Read(task)?;
 
         self.termios.FromTermios(&t);
 
+        // If canonical mode is turned off, move the line being edited to
+        // the read buffer. Anything already in the read buffer is now readable.
         if oldCanonEnabled && !self.termios.LEnabled(LocalFlags::ICANON) {
-            //l.inQueue.pushWaitBuf(l)
-            self.inQueue.lock().readable = true;
-            //l.slaveWaiter.Notify(waiter.EventIn)
-        }
-
-        //todo: don't understand
-        /*
-            // If canonical mode is turned off, move bytes from inQueue's wait
-        // buffer to its read buffer. Anything already in the read buffer is
-        // now readable.
-        if oldCanonEnabled && !l.termios.LEnabled(linux.ICANON) {
-            l.inQueue.pushWaitBuf(l)
-            l.inQueue.readable = true
-            l.slaveWaiter.Notify(waiter.EventIn)
+            let mut inQueue = self.inQueue.lock();
+            inQueue.PushLine();
+            inQueue.readable = inQueue.buf.AvailableDataSize() > 0;
         }
-            */
 
         return Ok(());
     }
 
     pub fn GetWindowSize(&self, task: &Task, dstAddr: u64) -> Result<()> {
-        task.CopyOutObj(&self.size, dstAddr)?;
+        UserPtr::New(dstAddr).Write(task, &self.size)?;
         return Ok(());
     }
 
-    pub fn SetWindowSize(&mut self, task: &Task, srcAddr: u64) -> Result<()> {
-        self.size = task.CopyInObj(srcAddr)?;
-        return Ok(());
+    // SetWindowSize returns whether the size changed, in which case the
+    // foreground process group should get SIGWINCH.
+    pub fn SetWindowSize(&mut self, task: &Task, srcAddr: u64) -> Result<bool> {
+        let size: WindowSize = UserPtr::New(srcAddr).Read(task)?;
+        let changed = size.Rows != self.size.Rows || size.Cols != self.size.Cols;
+        self.size = size;
+        return Ok(changed);
+    }
+
+    pub fn TakeSignals(&mut self) -> Vec<Signal> {
+        return core::mem::replace(&mut self.signals, Vec::new());
+    }
+
+    // SignalOf returns the signal generated by c when ISIG is set.
+    pub fn SignalOf(&self, c: u8) -> Option<Signal> {
+        if c == DISABLED_CHAR || !self.termios.LEnabled(LocalFlags::ISIG) {
+            return None;
+        }
+
+        let cc = &self.termios.ControlCharacters;
+        if c == cc[ControlFlagIndex::VINTR] {
+            return Some(Signal(Signal::SIGINT));
+        } else if c == cc[ControlFlagIndex::VQUIT] {
+            return Some(Signal(Signal::SIGQUIT));
+        } else if c == cc[ControlFlagIndex::VSUSP] {
+            return Some(Signal(Signal::SIGTSTP));
+        }
+
+        return None;
+    }
+
+    // IsEraseChar returns whether c is one of the line editing characters
+    // of canonical mode.
+    pub fn IsEraseChar(&self, c: u8) -> bool {
+        if c == DISABLED_CHAR || !self.termios.LEnabled(LocalFlags::ICANON) {
+            return false;
+        }
+
+        let cc = &self.termios.ControlCharacters;
+        return c == cc[ControlFlagIndex::VERASE]
+            || c == cc[ControlFlagIndex::VKILL]
+            || (c == cc[ControlFlagIndex::VWERASE] && self.termios.LEnabled(LocalFlags::IEXTEN));
+    }
+
+    // EraseInput removes the last character (VERASE), the last word (VWERASE)
+    // or the whole line (VKILL) from the line being edited and echoes the erase.
+    pub fn EraseInput(&mut self, q: &mut Queue, c: u8) {
+        let cc = self.termios.ControlCharacters;
+        let kill = c == cc[ControlFlagIndex::VKILL];
+        let word = !kill && c != cc[ControlFlagIndex::VERASE];
+
+        let mut erased = 0;
+        let mut seenWord = false;
+        while q.line.len() > 0 {
+            let n = self.LastCharLen(&q.line);
+            let last = q.line[q.line.len() - 1];
+            let isSpace = n == 1 && (last == ' ' as u8 || last == '\t' as u8);
+            if word {
+                if isSpace && seenWord {
+                    break;
+                }
+
+                if !isSpace {
+                    seenWord = true;
+                }
+            }
+
+            let len = q.line.len();
+            q.line.truncate(len - n);
+            erased += 1;
+
+            if !kill && !word {
+                break;
+            }
+        }
+
+        if !self.termios.LEnabled(LocalFlags::ECHO) {
+            return;
+        }
+
+        if kill && !self.termios.LEnabled(LocalFlags::ECHOKE) {
+            if self.termios.LEnabled(LocalFlags::ECHOK) {
+                self.Echo(&[c, '\n' as u8]);
+            }
+            return;
+        }
+
+        if !kill && !self.termios.LEnabled(LocalFlags::ECHOE) {
+            self.Echo(&[c]);
+            return;
+        }
+
+        for _i in 0..erased {
+            self.Echo(&['\x08' as u8, ' ' as u8, '\x08' as u8]);
+        }
+    }
+
+    // EchoControl echoes an ISIG character as ^X when ECHOCTL is set.
+    pub fn EchoControl(&mut self, c: u8) {
+        if !self.termios.LEnabled(LocalFlags::ECHO) || !self.termios.LEnabled(LocalFlags::ECHOCTL) {
+            return;
+        }
+
+        if c < 0x20 {
+            self.Echo(&['^' as u8, c + 0x40]);
+        }
+    }
+
+    pub fn Echo(&mut self, bytes: &[u8]) {
+        let mut bytes = bytes.to_vec();
+        let outQueue = self.outQueue.clone();
+        // The echo is dropped when the output queue is full.
+        outQueue.lock().Write(&mut bytes, self).ok();
+    }
+
+    // LastCharLen returns the length of the last character in line, taking
+    // multi-byte UTF-8 characters into account when IUTF8 is set.
+    fn LastCharLen(&self, line: &[u8]) -> usize {
+        if line.len() == 0 {
+            return 0;
+        }
+
+        if !self.termios.IEnabled(InputFlags::IUTF8) {
+            return 1;
+        }
+
+        let mut n = 1;
+        while n < line.len() && n < 4 && line[line.len() - n] & 0xc0 == 0x80 {
+            n += 1;
+        }
+
+        return n;
     }
 
     pub fn InputQueueReadSize(&self, task: &Task, dstAddr: u64) -> Result<()> {
@@ -104,13 +230,10 @@ impl LineDiscipline {
     }
 
     pub fn InputQueueRead(&self, _task: &Task, dst: &mut [u8]) -> Result<i64> {
+        // A readable but empty input queue means VEOF was typed at the start
+        // of a line, so the read returns 0.
         let n = self.inQueue.lock().Read(dst)?;
-
-        if n > 0 {
-            return Ok(n);
-        }
-
-        return Err(Error::SysError(SysErr::EAGAIN));
+        return Ok(n);
     }
 
     pub fn InputQueueWrite(&mut self, _task: &Task, src: &mut [u8]) -> Result<i64> {
@@ -151,7 +274,7 @@ impl LineDiscipline {
 
     pub fn ShouldDiscard(&self, q: &Queue, cBytes: &[u8]) -> bool {
         return self.termios.LEnabled(LocalFlags::ICANON)
-            && q.buf.AvailableDataSize() + cBytes.len() > CANON_MAX_BYTES
+            && q.buf.AvailableDataSize() + q.line.len() + cBytes.len() > CANON_MAX_BYTES
             && !self.termios.IsTerminating(cBytes);
     }
 
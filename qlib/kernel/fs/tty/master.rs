@@ -24,9 +24,11 @@ use super::super::super::super::common::*;
 use super::super::super::super::device::*;
 use super::super::super::super::linux_def::*;
 use super::super::super::kernel::time::*;
+use super::super::super::kernel::waiter::qlock::*;
 use super::super::super::kernel::waiter::*;
 use super::super::super::socket::unix::transport::unix::*;
 use super::super::super::task::*;
+use super::super::super::threadmgr::task_usermem::*;
 use super::super::super::uid::NewUID;
 use super::super::attr::*;
 use super::super::dentry::*;
@@ -215,6 +217,7 @@ impl InodeOperations for MasterInodeOperations {
         _newParent: &Inode,
         _newname: &str,
         _replacement: bool,
+        _flags: u32,
     ) -> Result<()> {
         return Err(Error::SysError(SysErr::EINVAL));
     }
@@ -234,14 +237,28 @@ impl InodeOperations for MasterInodeOperations {
         return None;
     }
 
+    // GetFile allocates a new pty pair on each open of ptmx.
     fn GetFile(
         &self,
-        _task: &Task,
+        task: &Task,
         _dir: &Inode,
-        _dirent: &Dirent,
-        _flags: FileFlags,
+        dirent: &Dirent,
+        flags: FileFlags,
     ) -> Result<File> {
-        return Err(Error::SysError(SysErr::ENXIO));
+        let d = self.read().d.clone();
+        let t = d.allocateTerminal(task)?;
+
+        let fileOp = Arc::new(MasterFileOperations { d: d, t: t });
+
+        let internal = FileInternal {
+            UniqueId: NewUID(),
+            Dirent: dirent.clone(),
+            flags: QMutex::new((flags, None)),
+            offset: QLock::New(0),
+            FileOp: fileOp,
+        };
+
+        return Ok(File(Arc::new(internal)));
     }
 
     fn ReadLink(&self, _task: &Task, _dir: &Inode) -> Result<String> {
@@ -313,17 +330,24 @@ pub struct MasterFileOperations {
     pub t: Arc<Terminal>,
 }
 
+impl Drop for MasterFileOperations {
+    fn drop(&mut self) {
+        self.t.MasterClose();
+        self.d.MasterClose(self.t.n);
+    }
+}
+
 impl Waitable for MasterFileOperations {
-    fn Readiness(&self, _task: &Task, _mask: EventMask) -> EventMask {
-        panic!("MasterFileOperations doesn't support Waitable::Readiness");
+    fn Readiness(&self, _task: &Task, mask: EventMask) -> EventMask {
+        return self.t.MasterReadiness(mask);
     }
 
-    fn EventRegister(&self, _task: &Task, _e: &WaitEntry, _mask: EventMask) {
-        panic!("MasterFileOperations doesn't support Waitable::EventRegister");
+    fn EventRegister(&self, task: &Task, e: &WaitEntry, mask: EventMask) {
+        self.t.masterWaiter.EventRegister(task, e, mask)
     }
 
-    fn EventUnregister(&self, _task: &Task, _e: &WaitEntry) {
-        panic!("MasterFileOperations doesn't support Waitable::EventUnregister");
+    fn EventUnregister(&self, task: &Task, e: &WaitEntry) {
+        self.t.masterWaiter.EventUnregister(task, e)
     }
 }
 
@@ -378,11 +402,7 @@ impl FileOperations for MasterFileOperations {
             size = buf.len();
         }
 
-        let cnt = self
-            .t
-            .ld
-            .lock()
-            .OutputQueueRead(task, &mut buf[..size as usize])? as usize;
+        let cnt = self.t.OutputQueueRead(task, &mut buf[..size as usize])? as usize;
         let res = task.CopyDataOutToIovs(&buf[0..cnt], dsts, false)?;
 
         assert!(res == cnt as usize, "MasterFileOperations:ReadAt fail");
@@ -403,8 +423,6 @@ impl FileOperations for MasterFileOperations {
 
         let res = self
             .t
-            .ld
-            .lock()
             .InputQueueWrite(task, &mut buf.buf[0..len as usize])?;
         return Ok(res);
     }
@@ -439,29 +457,29 @@ impl FileOperations for MasterFileOperations {
         match cmd {
             IoCtlCmd::FIONREAD => return self.t.ld.lock().OutputQueueReadSize(task, val),
             IoCtlCmd::TCGETS => return self.t.ld.lock().GetTermios(task, val),
-            IoCtlCmd::TCSETS => return self.t.ld.lock().SetTermios(task, val),
+            IoCtlCmd::TCSETS => return self.t.SetTermios(task, val),
             IoCtlCmd::TCSETSW => {
                 //This should drain the output queue first.
-                return self.t.ld.lock().SetTermios(task, val);
+                return self.t.SetTermios(task, val);
             }
             IoCtlCmd::TIOCGPTN => {
                 let n = self.t.n;
-                task.CopyOutObj(&n, val)?;
-                return Ok(());
-            }
-            IoCtlCmd::TIOCSPTLCK => {
-                //Implement pty locking. For now just pretend we do.
+                UserPtr::New(val).Write(task, &n)?;
                 return Ok(());
             }
+            IoCtlCmd::TIOCSPTLCK => return self.t.SetLocked(task, val),
             IoCtlCmd::TIOCGWINSZ => {
                 //This should drain the output queue first.
                 return self.t.ld.lock().GetWindowSize(task, val);
             }
             IoCtlCmd::TIOCSWINSZ => {
                 //This should drain the output queue first.
-                return self.t.ld.lock().SetWindowSize(task, val);
+                return self.t.SetWindowSize(task, val);
             }
-            _ => return Err(Error::SysError(SysErr::ENOTTY)),
+            _ => match self.t.JobControlIoctl(task, cmd, val) {
+                Some(res) => return res,
+                None => return Err(Error::SysError(SysErr::ENOTTY)),
+            },
         }
     }
 
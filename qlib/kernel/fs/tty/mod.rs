@@ -14,6 +14,7 @@
 
 pub mod dir;
 pub mod fs;
+pub mod job_control;
 pub mod line_discipline;
 pub mod master;
 pub mod queue;
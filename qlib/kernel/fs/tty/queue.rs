@@ -12,10 +12,13 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use alloc::vec::Vec;
+
 use super::super::super::super::bytestream::*;
 use super::super::super::super::common::*;
 use super::super::super::super::linux_def::*;
 use super::super::super::task::*;
+use super::super::super::threadmgr::task_usermem::*;
 use super::super::host::tty::*;
 use super::line_discipline::*;
 
@@ -26,6 +29,10 @@ pub struct Queue {
     pub buf: ByteStream,
     pub transform: fn(l: &mut LineDiscipline, q: &mut Queue, buf: &mut [u8]) -> usize,
     pub readable: bool,
+
+    // line is the input line being edited in canonical mode. It is moved
+    // to buf once it is terminated.
+    pub line: Vec<u8>,
 }
 
 impl Queue {
@@ -34,6 +41,7 @@ impl Queue {
             buf: ByteStream::Init(WAIT_BUF_DEFAULT_PAGE_COUNT),
             transform: inputQTransform,
             readable: false,
+            line: Vec::new(),
         };
     }
 
@@ -42,6 +50,7 @@ impl Queue {
             buf: ByteStream::Init(WAIT_BUF_DEFAULT_PAGE_COUNT),
             transform: outputQTransform,
             readable: false,
+            line: Vec::new(),
         };
     }
 
@@ -52,7 +61,7 @@ impl Queue {
             0
         };
 
-        task.CopyOutObj(&size, dstAddr)?;
+        UserPtr::New(dstAddr).Write(task, &size)?;
         return Ok(());
     }
 
@@ -75,6 +84,15 @@ impl Queue {
         return Ok(n as i64);
     }
 
+    pub fn PushLine(&mut self) {
+        if self.line.len() == 0 {
+            return;
+        }
+
+        let line = core::mem::replace(&mut self.line, Vec::new());
+        self.buf.write(&line).unwrap();
+    }
+
     pub fn Write(&mut self, src: &mut [u8], l: &mut LineDiscipline) -> Result<i64> {
         if self.buf.AvailableSpace() == 0 {
             return Err(Error::SysError(SysErr::EAGAIN));
@@ -182,7 +200,7 @@ pub fn inputQTransform(l: &mut LineDiscipline, q: &mut Queue, buf: &mut [u8]) ->
     let mut ret = 0;
 
     let mut buf = buf;
-    while buf.len() > 0 && q.buf.AvailableDataSize() < CANON_MAX_BYTES {
+    while buf.len() > 0 && q.buf.AvailableDataSize() + q.line.len() < CANON_MAX_BYTES {
         let size = l.Peek(buf);
         let mut cBytes = buf[..size].to_vec();
 
@@ -206,13 +224,34 @@ pub fn inputQTransform(l: &mut LineDiscipline, q: &mut Queue, buf: &mut [u8]) ->
             _ => (),
         }
 
+        if size == 1 {
+            let c = cBytes[0];
+            if let Some(sig) = l.SignalOf(c) {
+                buf = &mut buf[size..];
+                ret += size;
+                if !l.termios.LEnabled(LocalFlags::NOFLSH) {
+                    q.line.clear();
+                }
+                l.EchoControl(c);
+                l.signals.push(sig);
+                continue;
+            }
+
+            if l.IsEraseChar(c) {
+                buf = &mut buf[size..];
+                ret += size;
+                l.EraseInput(q, c);
+                continue;
+            }
+        }
+
         if l.ShouldDiscard(q, &cBytes) {
             buf = &mut buf[size..];
             ret += size;
             continue;
         }
 
-        if q.buf.AvailableDataSize() + size > maxBytes {
+        if q.buf.AvailableDataSize() + q.line.len() + size > maxBytes {
             break;
         }
 
@@ -220,11 +259,16 @@ pub fn inputQTransform(l: &mut LineDiscipline, q: &mut Queue, buf: &mut [u8]) ->
         ret += size;
 
         if l.termios.LEnabled(LocalFlags::ICANON) && l.termios.IsEOF(cBytes[0]) {
+            q.PushLine();
             q.readable = true;
             break;
         }
 
-        q.buf.write(&cBytes).unwrap();
+        if l.termios.LEnabled(LocalFlags::ICANON) {
+            q.line.extend_from_slice(&cBytes);
+        } else {
+            q.buf.write(&cBytes).unwrap();
+        }
 
         if l.termios.LEnabled(LocalFlags::ECHO) {
             let outQueue = l.outQueue.clone();
@@ -232,6 +276,7 @@ pub fn inputQTransform(l: &mut LineDiscipline, q: &mut Queue, buf: &mut [u8]) ->
         }
 
         if l.termios.LEnabled(LocalFlags::ICANON) && l.termios.IsTerminating(&cBytes) {
+            q.PushLine();
             q.readable = true;
             break;
         }
@@ -28,6 +28,7 @@ use super::super::super::kernel::waiter::qlock::*;
 use super::super::super::kernel::waiter::*;
 use super::super::super::socket::unix::transport::unix::*;
 use super::super::super::task::*;
+use super::super::super::threadmgr::task_usermem::*;
 use super::super::super::uid::NewUID;
 use super::super::attr::*;
 use super::super::dentry::*;
@@ -35,6 +36,7 @@ use super::super::dirent::*;
 use super::super::file::*;
 use super::super::flags::*;
 use super::super::host::hostinodeop::*;
+use super::super::host::tty::LocalFlags;
 use super::super::inode::*;
 use super::super::mount::*;
 use super::dir::*;
@@ -43,6 +45,7 @@ use super::terminal::*;
 pub fn NewSlaveNode(
     task: &Task,
     d: &DirInodeOperations,
+    msrc: &Arc<QMutex<MountSource>>,
     t: &Arc<Terminal>,
     owner: &FileOwner,
     p: &FilePermissions,
@@ -75,13 +78,12 @@ pub fn NewSlaveNode(
         DeviceFileMinor: t.n,
     };
 
-    let msrc = d.lock().msrc.clone();
     let inodeInternal = InodeIntern {
         UniqueId: NewUID(),
         InodeOp: Arc::new(iops),
         StableAttr: stableAttr,
         LockCtx: LockCtx::default(),
-        MountSource: msrc,
+        MountSource: msrc.clone(),
         Overlay: None,
     };
 
@@ -219,6 +221,7 @@ impl InodeOperations for SlaveInodeOperations {
         _newParent: &Inode,
         _newname: &str,
         _replacement: bool,
+        _flags: u32,
     ) -> Result<()> {
         return Err(Error::SysError(SysErr::EINVAL));
     }
@@ -245,6 +248,11 @@ impl InodeOperations for SlaveInodeOperations {
         dirent: &Dirent,
         flags: FileFlags,
     ) -> Result<File> {
+        // The slave can't be opened until the master unlocks it.
+        if self.read().t.IsLocked() {
+            return Err(Error::SysError(SysErr::EIO));
+        }
+
         let fileOp = Arc::new(SlaveFileOperations { d: self.clone() });
 
         let internal = FileInternal {
@@ -327,16 +335,19 @@ pub struct SlaveFileOperations {
 }
 
 impl Waitable for SlaveFileOperations {
-    fn Readiness(&self, _task: &Task, _mask: EventMask) -> EventMask {
-        panic!("SlaveFileOperations doesn't support Waitable::Readiness");
+    fn Readiness(&self, _task: &Task, mask: EventMask) -> EventMask {
+        let t = self.d.read().t.clone();
+        return t.SlaveReadiness(mask);
     }
 
-    fn EventRegister(&self, _task: &Task, _e: &WaitEntry, _mask: EventMask) {
-        panic!("SlaveFileOperations doesn't support Waitable::EventRegister");
+    fn EventRegister(&self, task: &Task, e: &WaitEntry, mask: EventMask) {
+        let t = self.d.read().t.clone();
+        t.slaveWaiter.EventRegister(task, e, mask)
     }
 
-    fn EventUnregister(&self, _task: &Task, _e: &WaitEntry) {
-        panic!("SlaveFileOperations doesn't support Waitable::EventUnregister");
+    fn EventUnregister(&self, task: &Task, e: &WaitEntry) {
+        let t = self.d.read().t.clone();
+        t.slaveWaiter.EventUnregister(task, e)
     }
 }
 
@@ -384,6 +395,9 @@ impl FileOperations for SlaveFileOperations {
         _offset: i64,
         _blocking: bool,
     ) -> Result<i64> {
+        let t = self.d.read().t.clone();
+        t.jc.CheckChange(task, Signal(Signal::SIGTTIN))?;
+
         let mut buf: [u8; 4096] = [0; 4096];
 
         let mut size = IoVec::NumBytes(dsts);
@@ -391,13 +405,7 @@ impl FileOperations for SlaveFileOperations {
             size = buf.len();
         }
 
-        let cnt = self
-            .d
-            .read()
-            .t
-            .ld
-            .lock()
-            .InputQueueRead(task, &mut buf[..size as usize])? as usize;
+        let cnt = t.InputQueueRead(task, &mut buf[..size as usize])? as usize;
 
         let res = task.CopyDataOutToIovs(&buf[0..cnt], dsts, false)?;
         assert!(res == cnt, "MasterFileOperations:ReadAt fail");
@@ -412,17 +420,16 @@ impl FileOperations for SlaveFileOperations {
         _offset: i64,
         _blocking: bool,
     ) -> Result<i64> {
+        let t = self.d.read().t.clone();
+        if t.ld.lock().termios.LEnabled(LocalFlags::TOSTOP) {
+            t.jc.CheckChange(task, Signal(Signal::SIGTTOU))?;
+        }
+
         let size = IoVec::NumBytes(srcs);
         let mut buf = DataBuff::New(size);
         let len = task.CopyDataInFromIovs(&mut buf.buf, srcs, true)?;
 
-        let res = self
-            .d
-            .read()
-            .t
-            .ld
-            .lock()
-            .OutputQueueWrite(task, &mut buf.buf[0..len as usize])?;
+        let res = t.OutputQueueWrite(task, &mut buf.buf[0..len as usize])?;
         return Ok(res);
     }
 
@@ -456,14 +463,20 @@ impl FileOperations for SlaveFileOperations {
         match cmd {
             IoCtlCmd::FIONREAD => return self.d.read().t.ld.lock().InputQueueReadSize(task, val),
             IoCtlCmd::TCGETS => return self.d.read().t.ld.lock().GetTermios(task, val),
-            IoCtlCmd::TCSETS => return self.d.read().t.ld.lock().SetTermios(task, val),
+            IoCtlCmd::TCSETS => {
+                let t = self.d.read().t.clone();
+                t.jc.CheckChange(task, Signal(Signal::SIGTTOU))?;
+                return t.SetTermios(task, val);
+            }
             IoCtlCmd::TCSETSW => {
                 //This should drain the output queue first.
-                return self.d.read().t.ld.lock().SetTermios(task, val);
+                let t = self.d.read().t.clone();
+                t.jc.CheckChange(task, Signal(Signal::SIGTTOU))?;
+                return t.SetTermios(task, val);
             }
             IoCtlCmd::TIOCGPTN => {
                 let n = self.d.read().t.n;
-                task.CopyOutObj(&n, val)?;
+                UserPtr::New(val).Write(task, &n)?;
                 return Ok(());
             }
             IoCtlCmd::TIOCSPTLCK => {
@@ -476,9 +489,16 @@ impl FileOperations for SlaveFileOperations {
             }
             IoCtlCmd::TIOCSWINSZ => {
                 //This should drain the output queue first.
-                return self.d.read().t.ld.lock().SetWindowSize(task, val);
+                let t = self.d.read().t.clone();
+                return t.SetWindowSize(task, val);
+            }
+            _ => {
+                let t = self.d.read().t.clone();
+                match t.JobControlIoctl(task, cmd, val) {
+                    Some(res) => return res,
+                    None => return Err(Error::SysError(SysErr::ENOTTY)),
+                }
             }
-            _ => return Err(Error::SysError(SysErr::ENOTTY)),
         }
     }
 
@@ -14,15 +14,32 @@
 
 use crate::qlib::mutex::*;
 use alloc::sync::Arc;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::Ordering;
 
 use super::super::host::tty::*;
+use super::super::super::super::common::*;
+use super::super::super::super::linux_def::*;
+use super::super::super::kernel::waiter::Queue;
+use super::super::super::task::*;
+use super::super::super::threadmgr::task_usermem::*;
 use super::dir::*;
+use super::job_control::*;
 use super::line_discipline::*;
 
 pub struct Terminal {
     pub n: u32,
     pub d: DirInodeOperations,
     pub ld: Arc<QMutex<LineDiscipline>>,
+    pub jc: JobControl,
+
+    pub masterWaiter: Queue,
+    pub slaveWaiter: Queue,
+
+    // locked is cleared by TIOCSPTLCK (unlockpt); the slave can't be opened
+    // before that.
+    pub locked: AtomicBool,
+    pub masterClosed: AtomicBool,
 }
 
 impl Terminal {
@@ -31,6 +48,151 @@ impl Terminal {
             d: d.clone(),
             n: n,
             ld: Arc::new(QMutex::new(LineDiscipline::New(DEFAULT_SLAVE_TERMIOS))),
+            jc: JobControl::New(),
+            masterWaiter: Queue::default(),
+            slaveWaiter: Queue::default(),
+            locked: AtomicBool::new(true),
+            masterClosed: AtomicBool::new(false),
+        };
+    }
+
+    pub fn IsLocked(&self) -> bool {
+        return self.locked.load(Ordering::SeqCst);
+    }
+
+    pub fn SetLocked(&self, task: &Task, srcAddr: u64) -> Result<()> {
+        let lock: i32 = UserPtr::New(srcAddr).Read(task)?;
+        self.locked.store(lock != 0, Ordering::SeqCst);
+        return Ok(());
+    }
+
+    pub fn IsMasterClosed(&self) -> bool {
+        return self.masterClosed.load(Ordering::SeqCst);
+    }
+
+    // MasterClose hangs up the slave side once the last master file is closed.
+    pub fn MasterClose(&self) {
+        self.masterClosed.store(true, Ordering::SeqCst);
+        self.slaveWaiter.Notify(READABLE_EVENT | WRITEABLE_EVENT | EVENT_HUP);
+    }
+
+    pub fn MasterReadiness(&self, mask: EventMask) -> EventMask {
+        let ld = self.ld.lock();
+        let mut ready = 0;
+        if ld.outQueue.lock().readable {
+            ready |= READABLE_EVENT;
+        }
+
+        if ld.inQueue.lock().buf.AvailableSpace() > 0 {
+            ready |= WRITEABLE_EVENT;
+        }
+
+        return mask & ready;
+    }
+
+    pub fn SlaveReadiness(&self, mask: EventMask) -> EventMask {
+        if self.IsMasterClosed() {
+            return mask & (READABLE_EVENT | WRITEABLE_EVENT | EVENT_HUP);
+        }
+
+        let ld = self.ld.lock();
+        let mut ready = 0;
+        if ld.inQueue.lock().readable {
+            ready |= READABLE_EVENT;
+        }
+
+        if ld.outQueue.lock().buf.AvailableSpace() > 0 {
+            ready |= WRITEABLE_EVENT;
+        }
+
+        return mask & ready;
+    }
+
+    // InputQueueRead reads the slave's input.
+    pub fn InputQueueRead(&self, task: &Task, dst: &mut [u8]) -> Result<i64> {
+        let res = self.ld.lock().InputQueueRead(task, dst);
+        match res {
+            Ok(n) if n > 0 => self.masterWaiter.Notify(WRITEABLE_EVENT),
+            // The master is gone and nothing is left to read.
+            Err(Error::SysError(SysErr::EAGAIN)) if self.IsMasterClosed() => {
+                return Err(Error::SysError(SysErr::EIO))
+            }
+            _ => (),
+        }
+
+        return res;
+    }
+
+    // OutputQueueRead reads the slave's output from the master.
+    pub fn OutputQueueRead(&self, task: &Task, dst: &mut [u8]) -> Result<i64> {
+        let n = self.ld.lock().OutputQueueRead(task, dst)?;
+        self.slaveWaiter.Notify(WRITEABLE_EVENT);
+        return Ok(n);
+    }
+
+    pub fn OutputQueueWrite(&self, task: &Task, src: &mut [u8]) -> Result<i64> {
+        if self.IsMasterClosed() {
+            return Err(Error::SysError(SysErr::EIO));
+        }
+
+        let n = self.ld.lock().OutputQueueWrite(task, src)?;
+        self.masterWaiter.Notify(READABLE_EVENT);
+        return Ok(n);
+    }
+
+    pub fn SetTermios(&self, task: &Task, srcAddr: u64) -> Result<()> {
+        self.ld.lock().SetTermios(task, srcAddr)?;
+        // Turning off canonical mode may make the pending input readable.
+        self.slaveWaiter.Notify(READABLE_EVENT);
+        return Ok(());
+    }
+
+    // InputQueueWrite writes the master's input to the line discipline and
+    // sends the signals generated by the ISIG characters to the foreground
+    // process group.
+    pub fn InputQueueWrite(&self, task: &Task, src: &mut [u8]) -> Result<i64> {
+        let (res, signals) = {
+            let mut ld = self.ld.lock();
+            let res = ld.InputQueueWrite(task, src);
+            (res, ld.TakeSignals())
         };
+
+        if let Ok(n) = res {
+            if n > 0 {
+                self.slaveWaiter.Notify(READABLE_EVENT);
+                // The input may have been echoed.
+                self.masterWaiter.Notify(READABLE_EVENT);
+            }
+        }
+
+        for sig in signals {
+            self.jc.SignalForeground(sig);
+        }
+
+        return res;
+    }
+
+    pub fn SetWindowSize(&self, task: &Task, srcAddr: u64) -> Result<()> {
+        let changed = self.ld.lock().SetWindowSize(task, srcAddr)?;
+        if changed {
+            self.jc.SignalForeground(Signal(Signal::SIGWINCH));
+        }
+
+        return Ok(());
+    }
+
+    // JobControlIoctl handles the job control ioctls shared by the master
+    // and the slave. It returns None for other requests.
+    pub fn JobControlIoctl(&self, task: &Task, request: u64, val: u64) -> Option<Result<()>> {
+        let res = match request {
+            IoCtlCmd::TIOCGPGRP => self.jc.GetForegroundProcessGroup(task, val),
+            IoCtlCmd::TIOCSPGRP => self.jc.SetForegroundProcessGroup(task, val),
+            IoCtlCmd::TIOCSCTTY => self.jc.SetControllingTTY(task, val != 0),
+            IoCtlCmd::TIOCNOTTY => self.jc.ReleaseControllingTTY(task),
+            IoCtlCmd::TIOCGSID => self.jc.GetSessionID(task, val),
+            _ => return None,
+        };
+
+        return Some(res);
     }
 }
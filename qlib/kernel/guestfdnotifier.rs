@@ -29,6 +29,10 @@ pub fn UpdateFD(fd: i32) -> Result<()> {
 }
 
 pub fn NonBlockingPoll(fd: i32, mask: EventMask) -> EventMask {
+    if SHARESPACE.config.read().ReadinessCache {
+        return GlobalIOMgr().NonBlockingPoll(fd, mask);
+    }
+
     return HostSpace::NonBlockingPoll(fd, mask) as EventMask;
 }
 
@@ -83,6 +87,22 @@ impl IOMgr {
         }
     }
 
+    pub fn NonBlockingPoll(&self, fd: i32, mask: EventMask) -> EventMask {
+        let fi = match self.FdWaitInfo(fd) {
+            None => return HostSpace::NonBlockingPoll(fd, mask) as EventMask,
+            Some(fi) => fi,
+        };
+
+        let seq = match fi.CachedReadiness(mask) {
+            Ok(()) => return 0,
+            Err(seq) => seq,
+        };
+
+        let ready = HostSpace::NonBlockingPoll(fd, mask) as EventMask;
+        fi.UpdateReadiness(seq, mask, ready);
+        return ready;
+    }
+
     pub fn FdWaitInfo(&self, fd: i32) -> Option<FdWaitInfo> {
         let fdInfo = match self.GetByHost(fd) {
             Some(info) => info,
@@ -103,6 +123,17 @@ impl IOMgr {
         return fi.UpdateFDAsync(fd, epollfd);
     }
 
+    // called when the uring completes an epoll_ctl of the fd
+    pub fn EpollCtlDone(&self, fd: i32) {
+        let fi = match self.FdWaitInfo(fd) {
+            None => return,
+            Some(fi) => fi,
+        };
+
+        let epollfd = self.Epollfd();
+        fi.EpollCtlDone(fd, epollfd).ok();
+    }
+
     pub fn UpdateFDSync(&self, fd: i32) -> Result<()> {
         let fi = match self.FdWaitInfo(fd) {
             None => return Ok(()),
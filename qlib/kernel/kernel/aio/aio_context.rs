@@ -27,6 +27,7 @@ use super::super::super::super::linux_def::*;
 use super::super::super::memmgr::mm::*;
 use super::super::super::memmgr::*;
 use super::super::super::task::*;
+use super::super::super::threadmgr::task_usermem::*;
 use super::super::waiter::*;
 
 pub struct AIOMapping {}
@@ -129,6 +130,7 @@ impl MemoryManager {
             MaxPerms: AccessType::ReadOnly(),
             GrowsDown: false,
             Precommit: false,
+            NoReserve: false,
             MLockMode: MLockMode::default(),
             Kernel: false,
             Mapping: Some(Arc::new(AIOMapping {})),
@@ -179,7 +181,7 @@ impl MemoryManager {
 
         // Protect against 'ids' that are inaccessible (Linux also reads 4 bytes
         // from id).
-        let _buf: [u8; 4] = match task.CopyInObj(id) {
+        let _buf: [u8; 4] = match UserPtr::New(id).Read(task) {
             Err(_) => return None,
             Ok(t) => t,
         };
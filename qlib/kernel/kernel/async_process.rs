@@ -20,8 +20,10 @@ use super::super::super::mutex::*;
 use super::super::task::*;
 use super::super::LoadVcpuFreq;
 use super::super::TSC;
+use super::entropy::*;
 use super::kernel::*;
 use super::timer::timer::*;
+use super::watchdog::*;
 
 pub struct AsyncProcess {
     pub lastTsc: AtomicI64,
@@ -53,6 +55,8 @@ impl AsyncProcess {
         let curr = TSC.Rdtsc();
         if curr - self.lastTsc.load(Ordering::Relaxed) > TSC_GAP {
             self.lastTsc.store(curr, Ordering::Relaxed);
+            WATCHDOG.Check();
+            ENTROPY_POOL.Check();
             if let Some(mut processTime) = self.lastProcessTime.try_lock() {
                 let currTime = Task::MonoTimeNow().0 / MILLISECOND;
                 if currTime - *processTime >= CyclesPerTick() {
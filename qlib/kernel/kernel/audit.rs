@@ -0,0 +1,161 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::super::super::common::*;
+use super::super::super::linux_def::*;
+use super::super::super::SysCallID;
+use super::super::task::*;
+use super::super::Kernel::HostSpace;
+use super::super::SHARESPACE;
+use super::timer::RealNow;
+
+// AUDIT_SYSCALLS is the set of syscalls whose every invocation is recorded
+// when audit is enabled, independent of the result.
+pub const AUDIT_SYSCALLS: &[SysCallID] = &[
+    SysCallID::sys_ptrace,
+    SysCallID::sys_setuid,
+    SysCallID::sys_setgid,
+    SysCallID::sys_setreuid,
+    SysCallID::sys_setregid,
+    SysCallID::sys_setresuid,
+    SysCallID::sys_setresgid,
+    SysCallID::sys_setgroups,
+    SysCallID::sys_capset,
+    SysCallID::sys_chroot,
+    SysCallID::sys_pivot_root,
+    SysCallID::sys_mount,
+    SysCallID::sys_umount2,
+    SysCallID::sys_sethostname,
+    SysCallID::sys_setdomainname,
+    SysCallID::sys_unshare,
+    SysCallID::sys_setns,
+    SysCallID::sys_init_module,
+    SysCallID::sys_finit_module,
+    SysCallID::sys_delete_module,
+    SysCallID::sys_kexec_load,
+    SysCallID::sys_reboot,
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditType {
+    // Execve records a successful lookup of the execve target with its argv
+    Execve,
+    // PermissionDenied records a syscall which failed with EPERM or EACCES
+    PermissionDenied,
+    // Syscall records a syscall in AUDIT_SYSCALLS
+    Syscall,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AuditRecord {
+    pub Type: AuditType,
+    pub ContainerID: String,
+    // Pid is the thread group id in its own pid namespace
+    pub Pid: i32,
+    pub Tid: i32,
+    pub Uid: u32,
+    pub Syscall: String,
+    pub Args: [u64; 6],
+    pub Ret: i64,
+    #[serde(default)]
+    pub Filename: String,
+    #[serde(default)]
+    pub Argv: Vec<String>,
+    // Timestamp is the realtime clock in nanoseconds
+    pub Timestamp: i64,
+}
+
+impl AuditRecord {
+    pub fn New(task: &Task, typ: AuditType, nr: u64, args: &[u64; 6], ret: i64) -> Self {
+        let thread = task.Thread();
+        let tg = thread.ThreadGroup();
+        let creds = thread.Creds();
+        let uid = creds.lock().EffectiveKUID.0;
+        let callId: SysCallID = unsafe { core::mem::transmute(nr) };
+
+        return Self {
+            Type: typ,
+            ContainerID: thread.ContainerID(),
+            Pid: tg.ID(),
+            Tid: thread.lock().id,
+            Uid: uid,
+            Syscall: format!("{:?}", callId),
+            Args: *args,
+            Ret: ret,
+            Filename: String::new(),
+            Argv: Vec::new(),
+            Timestamp: RealNow(),
+        };
+    }
+
+    pub fn Emit(&self) {
+        let data = match serde_json::to_vec(self) {
+            Ok(d) => d,
+            Err(e) => {
+                error!("AuditRecord::Emit ser fail {:?}", e);
+                return;
+            }
+        };
+
+        let ret = HostSpace::AuditLog(&data[0] as *const _ as u64, data.len());
+        if ret < 0 {
+            error!(
+                "AuditRecord::Emit write fail {}, record {:?}",
+                -ret, self.Type
+            );
+        }
+    }
+}
+
+#[inline]
+pub fn AuditEnabled() -> bool {
+    return SHARESPACE.config.read().Audit;
+}
+
+// AuditSyscall is called by the syscall dispatcher after the syscall handler returns.
+pub fn AuditSyscall(task: &Task, nr: u64, args: &[u64; 6], res: &Result<i64>) {
+    let ret = match res {
+        Ok(ret) => *ret,
+        Err(Error::SysError(e)) => -*e as i64,
+        // control flow returns such as restart and exit are not syscall results
+        Err(_) => return,
+    };
+
+    let typ = if ret == -SysErr::EPERM as i64 || ret == -SysErr::EACCES as i64 {
+        AuditType::PermissionDenied
+    } else if AUDIT_SYSCALLS.iter().any(|id| *id as u64 == nr) {
+        AuditType::Syscall
+    } else {
+        return;
+    };
+
+    AuditRecord::New(task, typ, nr, args, ret).Emit();
+}
+
+pub fn AuditExecve(task: &Task, filename: &str, argv: &[String]) {
+    let args = [0; 6];
+    let mut record = AuditRecord::New(
+        task,
+        AuditType::Execve,
+        SysCallID::sys_execve as u64,
+        &args,
+        0,
+    );
+    record.Filename = filename.to_string();
+    record.Argv = argv.to_vec();
+    record.Emit();
+}
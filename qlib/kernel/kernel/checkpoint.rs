@@ -0,0 +1,1662 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::collections::btree_map::BTreeMap;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::mem;
+use core::slice;
+use core::sync::atomic::Ordering;
+
+use super::super::super::super::kernel_def::*;
+use super::super::super::addr::*;
+use super::super::super::auth::cap_set::*;
+use super::super::super::auth::id::*;
+use super::super::super::auth::*;
+use super::super::super::common::*;
+use super::super::super::control_msg::RestoreArgs;
+use super::super::super::limits::*;
+use super::super::super::linux::time::*;
+use super::super::super::linux_def::*;
+use super::super::super::mutex::*;
+use super::super::super::task_mgr::*;
+use super::super::super::vcpu_mgr::*;
+use super::super::arch::x86_64::arch_x86::*;
+use super::super::fs::attr::InodeType;
+use super::super::fs::dirent::*;
+use super::super::fs::file::*;
+use super::super::fs::flags::*;
+use super::super::fs::host::hostinodeop::*;
+use super::super::loader::loader::*;
+use super::super::memmgr::mm::*;
+use super::super::memmgr::*;
+use super::super::mm::*;
+use super::super::socket::socket::{NewSocket, NET_SOMAXCONN};
+use super::super::task::*;
+use super::super::taskMgr;
+use super::super::threadmgr::pid_namespace::*;
+use super::super::threadmgr::task_clone::*;
+use super::super::threadmgr::task_signals::*;
+use super::super::threadmgr::thread::*;
+use super::super::threadmgr::thread_group::*;
+use super::super::vcpu::*;
+use super::super::PAGE_MGR;
+use super::super::Kernel::HostSpace;
+use super::super::SignalDef::*;
+use super::fd_table::*;
+use super::kernel::*;
+use super::posixtimer::*;
+use super::timer::timer::*;
+use super::timer::*;
+use super::waiter::waitgroup::*;
+
+// The checkpoint image is laid out as
+//   CheckpointHeader | manifest json | page data
+// The page data is the content of the pages listed in VmaImage::Pages, in the
+// order of the processes and vmas in the manifest.
+//
+// For the iterative pre-copy of a live migration, several images are written
+// to the same stream: a pre-dump with all the pages while the sandbox keeps
+// running, then incremental pre-dumps with the pages written since the previous
+// image, and at last an incremental image of the paused sandbox with the thread
+// and fd state. The dirty pages are tracked by the dirty bit of the page table
+// entries, which is cleared whenever a page is saved. The writes of the kernel
+// and the host io through the physical addresses set the dirty bit with
+// MemoryManager::MarkDirty, from the first pre-dump until the final image,
+// while DIRTY_TRACKING is set.
+pub const CHECKPOINT_MAGIC: u64 = 0x54504b4348435551; // "QUCHCKPT"
+pub const CHECKPOINT_VERSION: u64 = 3;
+
+// the manifest of a migration stream is bounded, it comes from the network
+const STREAM_MANIFEST_MAX: u64 = 64 << 20;
+
+// the host io of the paused tasks must complete in the time, otherwise the
+// checkpoint fails
+const HOST_WRITES_TIMEOUT: i64 = 10 * SECOND;
+
+// the address of the sockets, a sockaddr_storage
+const SOCKET_ADDR_LEN: usize = 128;
+
+// the vdso vmas are not saved, they are mapped again on restore
+const VVAR_NAME: &str = "[vvar]";
+const VDSO_NAME: &str = "[vdso]";
+
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct CheckpointHeader {
+    pub magic: u64,
+    pub version: u64,
+    pub manifestLen: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct CheckpointManifest {
+    pub Version: u64,
+    // RealTime is the realtime clock in nanoseconds when the checkpoint is
+    // taken. The timers of the realtime clocks are rebased by it on restore,
+    // so they expire at the same wall time. The other timers keep the time
+    // which is left, the sandbox doesn't run between the checkpoint and the
+    // restore.
+    pub RealTime: i64,
+    // PreDump images only have the memory of the processes, they are taken
+    // while the sandbox is running.
+    pub PreDump: bool,
+    // Incremental images only have the pages written since the previous image,
+    // the other pages are in the previous images.
+    pub Incremental: bool,
+    pub Processes: Vec<ProcessImage>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ProcessImage {
+    pub Pid: i32,
+    pub ContainerID: String,
+    pub Name: String,
+    pub Uid: u32,
+    pub Gid: u32,
+    pub Caps: TaskCaps,
+    pub Cwd: String,
+    pub Executable: String,
+    pub BrkStart: u64,
+    pub BrkEnd: u64,
+    pub BrkMemEnd: u64,
+    pub Threads: Vec<ThreadImage>,
+    pub Vmas: Vec<VmaImage>,
+    pub Fds: Vec<FdImage>,
+    // ParentPid is the pid of the parent process, 0 if it isn't in the image.
+    // The parents are restored before their children.
+    pub ParentPid: i32,
+    pub SigActions: Vec<SigActImage>,
+    // PendingSignals are the siginfo of the signals pending on the thread
+    // group, in the order they are dequeued
+    pub PendingSignals: Vec<Vec<u8>>,
+    pub Itimers: Vec<ItimerImage>,
+    pub Timers: Vec<TimerImage>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct SigActImage {
+    pub Signo: i32,
+    pub Handler: u64,
+    pub Flags: u64,
+    pub Restorer: u64,
+    pub Mask: u64,
+}
+
+// ItimerImage is a setitimer(2) timer, the times are in nanoseconds
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ItimerImage {
+    pub Which: i32,
+    pub Value: i64,
+    pub Interval: i64,
+}
+
+// TimerImage is a timer_create(2) timer, the times are in nanoseconds. Tid is
+// the target of SIGEV_THREAD_ID.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct TimerImage {
+    pub Id: i32,
+    pub ClockID: i32,
+    pub Notify: i32,
+    pub Signo: i32,
+    pub Sigval: u64,
+    pub Tid: i32,
+    pub Value: i64,
+    pub Interval: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ThreadImage {
+    pub Tid: i32,
+    // Regs is the user PtRegs of the stopped thread
+    pub Regs: Vec<u64>,
+    pub FsBase: u64,
+    pub SignalMask: u64,
+    // ClearTid is the set_tid_address(2) address, 0 if it is not set
+    pub ClearTid: u64,
+    // FpState is the xsave area of the thread
+    pub FpState: Vec<u8>,
+    // PendingSignals are the siginfo of the signals pending on the thread
+    pub PendingSignals: Vec<Vec<u8>>,
+    // the sigaltstack(2) of the thread
+    pub AltStackAddr: u64,
+    pub AltStackFlags: u32,
+    pub AltStackSize: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct VmaImage {
+    pub Start: u64,
+    pub End: u64,
+    pub Perms: String,
+    pub Private: bool,
+    pub GrowsDown: bool,
+    pub Anonymous: bool,
+    pub Offset: u64,
+    pub Name: String,
+    // Pages are the addresses of the present pages saved in the image. Only
+    // private or anonymous vmas are saved, the others are backed by the files.
+    // For an incremental image, the pages which are not written since the
+    // previous image are not saved.
+    pub Pages: Vec<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct FdImage {
+    pub Fd: i32,
+    pub Path: String,
+    pub Flags: i32,
+    pub CloseOnExec: bool,
+    pub Offset: i64,
+    // Socket is set for a socket, which is created again instead of reopened
+    pub Socket: Option<SocketImage>,
+}
+
+// SocketImage is an unbound or listening socket. Addr is the bound address,
+// empty if the socket isn't bound.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct SocketImage {
+    pub Domain: i32,
+    pub Type: i32,
+    pub Protocol: i32,
+    pub Addr: Vec<u8>,
+    pub Listening: bool,
+}
+
+fn SignalInfoBytes(info: &SignalInfo) -> Vec<u8> {
+    let data = unsafe {
+        slice::from_raw_parts(info as *const _ as *const u8, mem::size_of::<SignalInfo>())
+    };
+    return data.to_vec();
+}
+
+fn SignalInfoFromBytes(data: &[u8]) -> Result<SignalInfo> {
+    if data.len() != mem::size_of::<SignalInfo>() {
+        return Err(Error::Common(format!(
+            "Restore siginfo has {} bytes",
+            data.len()
+        )));
+    }
+
+    let mut info = SignalInfo::default();
+    unsafe {
+        core::ptr::copy_nonoverlapping(data.as_ptr(), &mut info as *mut _ as *mut u8, data.len());
+    }
+
+    if !Signal(info.Signo).IsValid() {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    return Ok(info);
+}
+
+// TimerClock returns the clock of a timer_create(2) timer. The timers on the
+// cpu clocks of a thread or of another process aren't saved, their clocks
+// can't be found again on restore.
+fn TimerClock(tg: &ThreadGroup, clockID: i32) -> Result<Clock> {
+    match clockID {
+        CLOCK_REALTIME | CLOCK_REALTIME_COARSE | CLOCK_REALTIME_ALARM => {
+            return Ok(REALTIME_CLOCK.clone())
+        }
+        CLOCK_MONOTONIC | CLOCK_MONOTONIC_COARSE | CLOCK_MONOTONIC_RAW => {
+            return Ok(MONOTONIC_CLOCK.clone())
+        }
+        CLOCK_BOOTTIME | CLOCK_BOOTTIME_ALARM => return Ok(BOOTTIME_CLOCK.clone()),
+        CLOCK_TAI => return Ok(TAI_CLOCK.clone()),
+        CLOCK_PROCESS_CPUTIME_ID => return Ok(tg.CPUClock()),
+        _ => {
+            return Err(Error::Common(format!(
+                "Checkpoint doesn't support the timers of clock {}",
+                clockID
+            )))
+        }
+    }
+}
+
+fn IsRealtimeClock(clockID: i32) -> bool {
+    match clockID {
+        CLOCK_REALTIME | CLOCK_REALTIME_COARSE | CLOCK_REALTIME_ALARM | CLOCK_TAI => true,
+        _ => false,
+    }
+}
+
+// SignalImages saves the signal actions, the signals pending on the thread
+// group and the timers of the process of lead.
+fn SignalImages(root: &PIDNamespace, lead: &Thread, process: &mut ProcessImage) -> Result<()> {
+    let tg = lead.ThreadGroup();
+    for (signo, act) in tg.SignalHandlers().lock().actions.iter() {
+        process.SigActions.push(SigActImage {
+            Signo: *signo,
+            Handler: act.handler,
+            Flags: act.flags.0,
+            Restorer: act.restorer,
+            Mask: act.mask,
+        });
+    }
+
+    process.PendingSignals = tg
+        .lock()
+        .pendingSignals
+        .Infos()
+        .iter()
+        .map(|info| SignalInfoBytes(info))
+        .collect();
+
+    for which in &[ITIMER_REAL, ITIMER_VIRTUAL, ITIMER_PROF] {
+        let itv = lead.Getitimer(*which)?;
+        let value = itv.Value.ToDuration();
+        if value == 0 {
+            continue;
+        }
+
+        process.Itimers.push(ItimerImage {
+            Which: *which,
+            Value: value,
+            Interval: itv.Interval.ToDuration(),
+        });
+    }
+
+    let timers: Vec<IntervalTimer> = tg.lock().timers.values().cloned().collect();
+    for it in &timers {
+        let (id, clockID, signo, sigval, group, target) = {
+            let it = it.lock();
+            (
+                it.id,
+                it.clockID,
+                it.signo.0,
+                it.sigval,
+                it.group,
+                it.target.clone(),
+            )
+        };
+
+        TimerClock(&tg, clockID)?;
+        let (notify, tid) = match &target {
+            None => (SIGEV_NONE, 0),
+            Some(_) if group => (SIGEV_SIGNAL, 0),
+            Some(t) => (SIGEV_THREAD_ID, root.IDOfTask(t)),
+        };
+
+        let its = lead.IntervalTimerGettime(id)?;
+        process.Timers.push(TimerImage {
+            Id: id,
+            ClockID: clockID,
+            Notify: notify,
+            Signo: signo,
+            Sigval: sigval,
+            Tid: tid,
+            Value: its.Value.ToNs()?,
+            Interval: its.Interval.ToNs()?,
+        });
+    }
+
+    return Ok(());
+}
+
+fn ThreadImageOf(thread: &Thread, tid: i32) -> ThreadImage {
+    let taskId = thread.lock().taskId;
+    let task = TaskId::New(taskId).GetTask();
+    let regs = task.GetPtRegs();
+    let words = unsafe {
+        slice::from_raw_parts(
+            regs as *const _ as *const u64,
+            mem::size_of::<PtRegs>() / mem::size_of::<u64>(),
+        )
+    };
+
+    // the stopped thread has saved its fp registers when entering the kernel
+    let fpstate = &task.context.X86fpstate;
+    let fpdata =
+        unsafe { slice::from_raw_parts(fpstate.FloatingPointData() as *const u8, fpstate.Size()) };
+    let pendingSignals = thread
+        .lock()
+        .pendingSignals
+        .Infos()
+        .iter()
+        .map(|info| SignalInfoBytes(info))
+        .collect();
+
+    return ThreadImage {
+        Tid: tid,
+        Regs: words.to_vec(),
+        FsBase: task.context.fs,
+        SignalMask: thread.SignalMask().0,
+        ClearTid: task.tidInfo.clear_child_tid.unwrap_or(0),
+        FpState: fpdata.to_vec(),
+        PendingSignals: pendingSignals,
+        AltStackAddr: task.signalStack.addr,
+        AltStackFlags: task.signalStack.flags,
+        AltStackSize: task.signalStack.size,
+    };
+}
+
+// VmaImages returns the vmas of mm and the guest physical addresses of the
+// saved pages. The dirty bits of the saved vmas are cleared, if incremental is
+// set only the dirty pages are saved.
+fn VmaImages(
+    task: &Task,
+    mm: &MemoryManager,
+    incremental: bool,
+    pages: &mut Vec<u64>,
+) -> Result<Vec<VmaImage>> {
+    let _ml = mm.MappingReadLock();
+    let mapping = mm.mapping.lock();
+
+    let mut vmas = Vec::new();
+    let mut seg = mapping.vmas.FirstSeg();
+    while !seg.IsTail() {
+        let vma = seg.Value();
+        let range = seg.Range();
+        seg = seg.NextSeg();
+
+        if vma.kernel {
+            continue;
+        }
+
+        // the file mappings are reopened by the path on restore
+        let name = match (&vma.mappable, &vma.id) {
+            (Some(_), Some(id)) => id.MappedName(task),
+            _ => vma.hint.to_string(),
+        };
+
+        let mut image = VmaImage {
+            Start: range.Start(),
+            End: range.End(),
+            Perms: vma.realPerms.String(),
+            Private: vma.private,
+            GrowsDown: vma.growsDown,
+            Anonymous: vma.mappable.is_none() || vma.id.is_none(),
+            Offset: vma.offset,
+            Name: name,
+            Pages: Vec::new(),
+        };
+
+        // the vdso pages are mapped from the kernel again on restore
+        let vdso = image.Name == VVAR_NAME || image.Name == VDSO_NAME;
+        if (image.Private || image.Anonymous) && !vdso {
+            // the pages which are not mapped have never been touched
+            mm.pagetable.write().pt.CollectDirty(
+                Addr(range.Start()),
+                Addr(range.End()),
+                |addr, phyAddr, dirty| {
+                    if dirty || !incremental {
+                        image.Pages.push(addr);
+                        pages.push(phyAddr);
+                    }
+                },
+            )?;
+        }
+
+        vmas.push(image);
+    }
+
+    return Ok(vmas);
+}
+
+// SocketImageOf returns the image of the socket fd, which is created, bound
+// and put in listening again on restore. A connected socket fails the
+// checkpoint, its connection can't be restored.
+fn SocketImageOf(task: &Task, file: &File, fd: i32) -> Result<SocketImage> {
+    let sock = file.FileOp.clone();
+    let mut addr = [0u8; SOCKET_ADDR_LEN];
+    if sock.GetPeerName(task, &mut addr).is_ok() {
+        return Err(Error::Common(format!(
+            "Checkpoint doesn't support the connected socket fd {}",
+            fd
+        )));
+    }
+
+    let getInt = |name: u64| -> Result<i32> {
+        let mut val = [0u8; 4];
+        sock.GetSockOpt(task, LibcConst::SOL_SOCKET as i32, name as i32, &mut val)?;
+        return Ok(i32::from_ne_bytes(val));
+    };
+
+    let domain = getInt(LibcConst::SO_DOMAIN)?;
+    let len = core::cmp::min(sock.GetSockName(task, &mut addr)? as usize, addr.len());
+    // the unbound sockets have the any address with port 0 or no unix path
+    let bound = match domain {
+        AFType::AF_INET | AFType::AF_INET6 => len >= 4 && (addr[2] != 0 || addr[3] != 0),
+        AFType::AF_UNIX => len > 2,
+        _ => {
+            return Err(Error::Common(format!(
+                "Checkpoint doesn't support the socket fd {} of family {}",
+                fd, domain
+            )))
+        }
+    };
+
+    return Ok(SocketImage {
+        Domain: domain,
+        Type: getInt(LibcConst::SO_TYPE)?,
+        Protocol: getInt(LibcConst::SO_PROTOCOL)?,
+        Addr: if bound {
+            addr[..len].to_vec()
+        } else {
+            Vec::new()
+        },
+        Listening: getInt(LibcConst::SO_ACCEPTCONN)? != 0,
+    });
+}
+
+// FdImages returns the fds of the thread, which are restored by reopening
+// their paths. The unbound and listening sockets are created again. The fds
+// which can't be reopened, e.g. connected sockets, pipes, eventfds and
+// timerfds, fail the checkpoint, except the stdio which is attached to the
+// stdio of the restore.
+fn FdImages(task: &Task, thread: &Thread) -> Result<Vec<FdImage>> {
+    let fdTbl = thread.lock().fdTbl.clone();
+    let mut fds = Vec::new();
+    for fd in fdTbl.GetFDs() {
+        let (file, flags) = match fdTbl.Get(fd) {
+            Err(_) => continue,
+            Ok(f) => f,
+        };
+
+        let path = file.Dirent.MyFullName();
+        let mut socket = None;
+        let reopenable = match file.Dirent.Inode().StableAttr().Type {
+            InodeType::RegularFile
+            | InodeType::Directory
+            | InodeType::SpecialDirectory
+            | InodeType::CharacterDevice => path.starts_with("/"),
+            InodeType::Socket if fd >= 3 => {
+                socket = Some(SocketImageOf(task, &file, fd)?);
+                true
+            }
+            _ => false,
+        };
+
+        if !reopenable && fd >= 3 {
+            return Err(Error::Common(format!(
+                "Checkpoint doesn't support fd {} {}",
+                fd, &path
+            )));
+        }
+
+        fds.push(FdImage {
+            Fd: fd,
+            Path: path,
+            Flags: file.Flags().ToLinux(),
+            CloseOnExec: flags.CloseOnExec,
+            Offset: file.Offset(task).unwrap_or(0),
+            Socket: socket,
+        })
+    }
+
+    return Ok(fds);
+}
+
+// WriteAll writes the buffer [addr, addr + len) to the host fd.
+pub fn WriteAll(fd: i32, addr: u64, len: usize) -> Result<()> {
+    let mut done = 0;
+    while done < len {
+        let iov = IoVec {
+            start: addr + done as u64,
+            len: len - done,
+        };
+
+        let ret = HostSpace::IOWrite(fd, &iov as *const _ as u64, 1);
+        if ret < 0 {
+            return Err(Error::SysError(-ret as i32));
+        }
+
+        if ret == 0 {
+            return Err(Error::SysError(SysErr::EIO));
+        }
+
+        done += ret as usize;
+    }
+
+    return Ok(());
+}
+
+// Checkpoint writes the image of all the processes of the kernel to the host
+// fd and returns the count of the saved pages. The kernel must be paused by the
+// caller unless preDump is set.
+pub fn Checkpoint(kernel: &Kernel, fd: i32, preDump: bool, incremental: bool) -> Result<u64> {
+    if preDump && !incremental {
+        // the first pre-copy round starts the tracking, the pages are read
+        // after they are collected so the writes racing with it are saved
+        DIRTY_TRACKING.store(true, Ordering::SeqCst);
+    }
+
+    let res = CheckpointImage(kernel, fd, preDump, incremental);
+    if res.is_err() || !preDump {
+        // the migration ends with the final image or an error
+        DIRTY_TRACKING.store(false, Ordering::SeqCst);
+    }
+
+    return res;
+}
+
+fn CheckpointImage(kernel: &Kernel, fd: i32, preDump: bool, incremental: bool) -> Result<u64> {
+    let task = Task::Current();
+    let ts = kernel.TaskSet();
+    let root = ts.Root();
+
+    if !preDump {
+        // the tasks stop after their host io completes, wait for it so that the
+        // written pages are in the final image
+        let deadline = MonotonicNow() + HOST_WRITES_TIMEOUT;
+        loop {
+            let writes = HOST_WRITES.load(Ordering::SeqCst);
+            if writes == 0 {
+                break;
+            }
+
+            if MonotonicNow() > deadline {
+                return Err(Error::Common(format!(
+                    "Checkpoint {} host writes don't complete in {} seconds",
+                    writes,
+                    HOST_WRITES_TIMEOUT / SECOND
+                )));
+            }
+
+            taskMgr::Yield();
+        }
+    }
+
+    let mut manifest = CheckpointManifest {
+        Version: CHECKPOINT_VERSION,
+        RealTime: RealNow(),
+        PreDump: preDump,
+        Incremental: incremental,
+        Processes: Vec::new(),
+    };
+
+    let mut pages = Vec::new();
+    for tg in root.ThreadGroups() {
+        let pid = root.IDOfThreadGroup(&tg);
+        // If tg has already been reaped ignore it.
+        if pid == 0 {
+            continue;
+        }
+
+        let lead = match tg.Leader() {
+            None => continue,
+            Some(t) => t,
+        };
+
+        let creds = lead.Creds();
+        let caps = {
+            let creds = creds.lock();
+            TaskCaps {
+                PermittedCaps: creds.PermittedCaps,
+                InheritableCaps: creds.InheritableCaps,
+                EffectiveCaps: creds.EffectiveCaps,
+                BoundingCaps: creds.BoundingCaps,
+                AmbientCaps: CapSet::default(),
+            }
+        };
+
+        let parentPid = match lead.lock().parent.clone() {
+            None => 0,
+            Some(p) => root.IDOfThreadGroup(&p.ThreadGroup()),
+        };
+
+        let mm = lead.MemoryManager();
+        let brk = mm.mapping.lock().brkInfo;
+        let executable = match &mm.metadata.lock().executable {
+            None => String::new(),
+            Some(d) => d.MyFullName(),
+        };
+
+        let mut process = ProcessImage {
+            Pid: pid,
+            ContainerID: lead.ContainerID(),
+            Name: lead.Name(),
+            Uid: creds.lock().RealKUID.0,
+            Gid: creds.lock().RealKGID.0,
+            Caps: caps,
+            Cwd: lead.lock().fsc.WorkDirectory().MyFullName(),
+            Executable: executable,
+            BrkStart: brk.brkStart,
+            BrkEnd: brk.brkEnd,
+            BrkMemEnd: brk.brkMemEnd,
+            ParentPid: parentPid,
+            ..Default::default()
+        };
+
+        process.Vmas = VmaImages(task, &mm, incremental, &mut pages)?;
+        // the running vcpus must see the cleared dirty bits before the pages are
+        // saved, otherwise their later writes will be missed
+        mm.TlbShootdown();
+
+        if !preDump {
+            let threads: Vec<Thread> = tg.lock().tasks.iter().cloned().collect();
+            for thread in &threads {
+                let tid = root.IDOfTask(thread);
+                process.Threads.push(ThreadImageOf(thread, tid));
+            }
+
+            process.Fds = FdImages(task, &lead)?;
+            SignalImages(&root, &lead, &mut process)?;
+        }
+
+        manifest.Processes.push(process);
+    }
+
+    let data = serde_json::to_vec(&manifest)
+        .map_err(|e| Error::Common(format!("Checkpoint ser manifest fail {:?}", e)))?;
+
+    let header = CheckpointHeader {
+        magic: CHECKPOINT_MAGIC,
+        version: CHECKPOINT_VERSION,
+        manifestLen: data.len() as u64,
+    };
+
+    WriteAll(
+        fd,
+        &header as *const _ as u64,
+        mem::size_of::<CheckpointHeader>(),
+    )?;
+    WriteAll(fd, &data[0] as *const _ as u64, data.len())?;
+
+    // the guest physical memory is identity mapped, the host writes the page
+    // content directly
+    for phyAddr in &pages {
+        WriteAll(fd, *phyAddr, MemoryDef::PAGE_SIZE as usize)?;
+    }
+
+    info!(
+        "Checkpoint {} processes {} pages written, preDump {} incremental {}",
+        manifest.Processes.len(),
+        pages.len(),
+        preDump,
+        incremental
+    );
+    return Ok(pages.len() as u64);
+}
+
+fn ReadAll(fd: i32, addr: u64, len: usize, offset: u64) -> Result<()> {
+    let mut done = 0;
+    while done < len {
+        let iov = IoVec {
+            start: addr + done as u64,
+            len: len - done,
+        };
+
+        let ret = HostSpace::IOReadAt(fd, &iov as *const _ as u64, 1, offset + done as u64);
+        if ret < 0 {
+            return Err(Error::SysError(-ret as i32));
+        }
+
+        if ret == 0 {
+            return Err(Error::Common(format!(
+                "Restore image is truncated at {}",
+                offset + done as u64
+            )));
+        }
+
+        done += ret as usize;
+    }
+
+    return Ok(());
+}
+
+// ReadManifest reads the manifest of the image in the host fd and returns it
+// with the offset of the page data.
+pub fn ReadManifest(fd: i32) -> Result<(CheckpointManifest, u64)> {
+    let mut header = CheckpointHeader::default();
+    ReadAll(
+        fd,
+        &mut header as *mut _ as u64,
+        mem::size_of::<CheckpointHeader>(),
+        0,
+    )?;
+
+    if header.magic != CHECKPOINT_MAGIC || header.version != CHECKPOINT_VERSION {
+        return Err(Error::Common(format!(
+            "Restore unknown image magic {:x} version {}",
+            header.magic, header.version
+        )));
+    }
+
+    let offset = mem::size_of::<CheckpointHeader>() as u64;
+    let mut data = vec![0u8; header.manifestLen as usize];
+    ReadAll(fd, &mut data[0] as *mut _ as u64, data.len(), offset)?;
+
+    let manifest: CheckpointManifest = serde_json::from_slice(&data)
+        .map_err(|e| Error::Common(format!("Restore deser manifest fail {:?}", e)))?;
+    return Ok((manifest, offset + header.manifestLen));
+}
+
+// ReadStream reads len bytes from the stream in the host fd to addr.
+fn ReadStream(fd: i32, addr: u64, len: usize) -> Result<()> {
+    let mut done = 0;
+    while done < len {
+        let iov = IoVec {
+            start: addr + done as u64,
+            len: len - done,
+        };
+
+        let ret = HostSpace::IORead(fd, &iov as *const _ as u64, 1);
+        if ret < 0 {
+            return Err(Error::SysError(-ret as i32));
+        }
+
+        if ret == 0 {
+            return Err(Error::Common(
+                "Restore migration stream is truncated".to_string(),
+            ));
+        }
+
+        done += ret as usize;
+    }
+
+    return Ok(());
+}
+
+// StagedPages are the pages received from a migration stream. They are kept in
+// guest pages keyed by the pid and the address, the pages of a later round
+// overwrite the earlier ones, until the final image is restored.
+#[derive(Default)]
+pub struct StagedPages {
+    pub pages: BTreeMap<(i32, u64), u64>,
+}
+
+impl Drop for StagedPages {
+    fn drop(&mut self) {
+        for (_, page) in &self.pages {
+            PAGE_MGR.DerefPage(*page);
+        }
+    }
+}
+
+impl StagedPages {
+    fn Page(&mut self, pid: i32, addr: u64) -> Result<u64> {
+        if let Some(page) = self.pages.get(&(pid, addr)) {
+            return Ok(*page);
+        }
+
+        let page = PAGE_MGR.AllocPage(true)?;
+        self.pages.insert((pid, addr), page);
+        return Ok(page);
+    }
+
+    // Prune frees the pages out of the vmas of the final image and lists the
+    // remaining pages in the vmas, as the image only has the last dirty pages.
+    fn Prune(&mut self, manifest: &mut CheckpointManifest) {
+        let mut pages = BTreeMap::new();
+        for process in &mut manifest.Processes {
+            for vma in &mut process.Vmas {
+                let vdso = vma.Name == VVAR_NAME || vma.Name == VDSO_NAME;
+                if !(vma.Private || vma.Anonymous) || vdso {
+                    continue;
+                }
+
+                vma.Pages.clear();
+                let keys: Vec<(i32, u64)> = self
+                    .pages
+                    .range((process.Pid, vma.Start)..(process.Pid, vma.End))
+                    .map(|(k, _)| *k)
+                    .collect();
+                for key in keys {
+                    let page = self.pages.remove(&key).unwrap();
+                    vma.Pages.push(key.1);
+                    pages.insert(key, page);
+                }
+            }
+        }
+
+        for (_, page) in &self.pages {
+            PAGE_MGR.DerefPage(*page);
+        }
+
+        self.pages = pages;
+    }
+
+    // Pages returns the staged pages of the process pid keyed by the address
+    fn Pages(&self, pid: i32) -> BTreeMap<u64, u64> {
+        return self
+            .pages
+            .range((pid, 0)..(pid, core::u64::MAX))
+            .map(|(k, page)| (k.1, *page))
+            .collect();
+    }
+}
+
+// ReadStreamImage reads the next image of the migration stream in the host fd
+// and stages its pages.
+fn ReadStreamImage(fd: i32, staged: &mut StagedPages) -> Result<CheckpointManifest> {
+    let mut header = CheckpointHeader::default();
+    ReadStream(
+        fd,
+        &mut header as *mut _ as u64,
+        mem::size_of::<CheckpointHeader>(),
+    )?;
+
+    if header.magic != CHECKPOINT_MAGIC || header.version != CHECKPOINT_VERSION {
+        return Err(Error::Common(format!(
+            "Restore unknown image magic {:x} version {}",
+            header.magic, header.version
+        )));
+    }
+
+    if header.manifestLen == 0 || header.manifestLen > STREAM_MANIFEST_MAX {
+        return Err(Error::Common(format!(
+            "Restore manifest len {} is out of range",
+            header.manifestLen
+        )));
+    }
+
+    let mut data = vec![0u8; header.manifestLen as usize];
+    ReadStream(fd, &mut data[0] as *mut _ as u64, data.len())?;
+
+    let manifest: CheckpointManifest = serde_json::from_slice(&data)
+        .map_err(|e| Error::Common(format!("Restore deser manifest fail {:?}", e)))?;
+
+    for process in &manifest.Processes {
+        for vma in &process.Vmas {
+            for addr in &vma.Pages {
+                if *addr < vma.Start || *addr >= vma.End || *addr & (MemoryDef::PAGE_SIZE - 1) != 0
+                {
+                    return Err(Error::SysError(SysErr::EFAULT));
+                }
+
+                let page = staged.Page(process.Pid, *addr)?;
+                ReadStream(fd, page, MemoryDef::PAGE_SIZE as usize)?;
+            }
+        }
+    }
+
+    return Ok(manifest);
+}
+
+// ReadStreamImages reads the images of a migration stream until the final
+// image of the paused sandbox and returns it with the staged pages.
+pub fn ReadStreamImages(fd: i32) -> Result<(CheckpointManifest, StagedPages)> {
+    let mut staged = StagedPages::default();
+    let mut rounds = 0;
+    loop {
+        let mut manifest = ReadStreamImage(fd, &mut staged)?;
+        rounds += 1;
+        if rounds == 1 && manifest.Incremental {
+            return Err(Error::Common(
+                "Restore migration stream starts with an incremental image".to_string(),
+            ));
+        }
+
+        if !manifest.PreDump {
+            staged.Prune(&mut manifest);
+            info!(
+                "Restore migration stream {} rounds {} pages",
+                rounds,
+                staged.pages.len()
+            );
+            return Ok((manifest, staged));
+        }
+    }
+}
+
+// RestorePages are where the saved pages of a process are read from
+pub enum RestorePages {
+    // the image fd and the offset of the first page of the process
+    File(i32, u64),
+    // the pages staged by a migration stream keyed by the address
+    Staged(BTreeMap<u64, u64>),
+}
+
+impl RestorePages {
+    // Read copies the saved page of addr to phyAddr, the pages are read in the
+    // order of the image.
+    fn Read(&mut self, addr: u64, phyAddr: u64) -> Result<()> {
+        match self {
+            RestorePages::File(fd, offset) => {
+                ReadAll(*fd, phyAddr, MemoryDef::PAGE_SIZE as usize, *offset)?;
+                *offset += MemoryDef::PAGE_SIZE;
+            }
+            RestorePages::Staged(pages) => {
+                let page = match pages.get(&addr) {
+                    None => {
+                        return Err(Error::Common(format!(
+                            "Restore page {:x} isn't received",
+                            addr
+                        )))
+                    }
+                    Some(p) => *p,
+                };
+
+                unsafe {
+                    core::ptr::copy_nonoverlapping(
+                        page as *const u8,
+                        phyAddr as *mut u8,
+                        MemoryDef::PAGE_SIZE as usize,
+                    );
+                }
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+pub struct RestoreProcessArgs {
+    pub image: ProcessImage,
+    // pages are taken by the process task when it restores the vmas
+    pub pages: QMutex<Option<RestorePages>>,
+    pub cid: String,
+    pub uid: u32,
+    pub gid: u32,
+    pub additionalGids: Vec<u32>,
+    pub caps: TaskCaps,
+    pub stdiofds: [i32; 3],
+    // stdio are the files of stdiofds, they are shared by the processes
+    pub stdio: Arc<QMutex<Vec<File>>>,
+    // parent is the restored parent of the process
+    pub parent: Option<Thread>,
+    // realTime is the realtime clock of the checkpoint
+    pub realTime: i64,
+    pub done: WaitGroup,
+    pub result: QMutex<Result<i32>>,
+}
+
+// ProcessDepth returns the count of the ancestors of pid in the image
+fn ProcessDepth(parents: &BTreeMap<i32, i32>, pid: i32) -> usize {
+    let mut depth = 0;
+    let mut pid = pid;
+    while let Some(ppid) = parents.get(&pid) {
+        // a broken image may have a loop
+        if depth > parents.len() {
+            break;
+        }
+
+        pid = *ppid;
+        depth += 1;
+    }
+
+    return depth;
+}
+
+// Restore recreates the processes of the image in the image fd of args in the
+// container args.cid and returns their new pids. The processes keep their
+// pids, tids and parents unless the pids are taken. They get the credentials
+// of args, their stdio is attached to the stdio fds of args unless the files
+// can be reopened. If args.Stream is set the fd is
+// a migration stream of a pre-dump and the incremental images.
+pub fn Restore(kernel: &Kernel, args: &RestoreArgs) -> Result<Vec<i32>> {
+    let stdiofds = [args.fds[1], args.fds[2], args.fds[3]];
+    let stdio = Arc::new(QMutex::new(Vec::new()));
+    let res = RestoreImage(kernel, args, stdiofds, &stdio);
+    // the stdio fds are owned by the files after the first process is restored
+    if stdio.lock().len() == 0 {
+        for fd in &stdiofds {
+            HostSpace::Close(*fd);
+        }
+    }
+
+    return res;
+}
+
+fn RestoreImage(
+    kernel: &Kernel,
+    restoreArgs: &RestoreArgs,
+    stdiofds: [i32; 3],
+    stdio: &Arc<QMutex<Vec<File>>>,
+) -> Result<Vec<i32>> {
+    let fd = restoreArgs.fds[0];
+    if restoreArgs.Stream {
+        let (manifest, staged) = ReadStreamImages(fd)?;
+        let mut sources = Vec::new();
+        for image in &manifest.Processes {
+            sources.push(RestorePages::Staged(staged.Pages(image.Pid)));
+        }
+
+        // the staged pages are freed after they are copied to the processes
+        return RestoreProcesses(kernel, restoreArgs, manifest, sources, stdiofds, stdio);
+    }
+
+    let (manifest, mut offset) = ReadManifest(fd)?;
+    if manifest.PreDump || manifest.Incremental {
+        return Err(Error::Common(
+            "Restore needs a full image, the pre-dump and incremental images are only restored from a migration stream"
+                .to_string(),
+        ));
+    }
+
+    let mut sources = Vec::new();
+    for image in &manifest.Processes {
+        sources.push(RestorePages::File(fd, offset));
+        for vma in &image.Vmas {
+            offset += vma.Pages.len() as u64 * MemoryDef::PAGE_SIZE;
+        }
+    }
+
+    return RestoreProcesses(kernel, restoreArgs, manifest, sources, stdiofds, stdio);
+}
+
+fn RestoreProcesses(
+    kernel: &Kernel,
+    restoreArgs: &RestoreArgs,
+    manifest: CheckpointManifest,
+    sources: Vec<RestorePages>,
+    stdiofds: [i32; 3],
+    stdio: &Arc<QMutex<Vec<File>>>,
+) -> Result<Vec<i32>> {
+    let task = Task::Current();
+    let cid = &restoreArgs.cid;
+    if kernel.Container(cid).is_none() {
+        return Err(Error::Common(format!("container {} doesn't exist", cid)));
+    }
+
+    let parents: BTreeMap<i32, i32> = manifest
+        .Processes
+        .iter()
+        .map(|p| (p.Pid, p.ParentPid))
+        .collect();
+    let mut processes: Vec<(ProcessImage, RestorePages)> = manifest
+        .Processes
+        .into_iter()
+        .zip(sources.into_iter())
+        .collect();
+    processes.sort_by_key(|(image, _)| ProcessDepth(&parents, image.Pid));
+
+    let root = kernel.TaskSet().Root();
+    let mut leaders: BTreeMap<i32, Thread> = BTreeMap::new();
+    let mut pids = Vec::new();
+    for (image, pages) in processes.into_iter() {
+        let oldPid = image.Pid;
+        let parent = leaders.get(&image.ParentPid).cloned();
+        let args = Arc::new(RestoreProcessArgs {
+            image: image,
+            pages: QMutex::new(Some(pages)),
+            cid: cid.to_string(),
+            uid: restoreArgs.Uid,
+            gid: restoreArgs.Gid,
+            additionalGids: restoreArgs.AdditionalGids.clone(),
+            caps: restoreArgs.Caps,
+            stdiofds: stdiofds,
+            stdio: stdio.clone(),
+            parent: parent,
+            realTime: manifest.RealTime,
+            done: WaitGroup::New(1),
+            result: QMutex::new(Ok(0)),
+        });
+
+        taskMgr::CreateTask(
+            RestoreProcessHandler as u64,
+            Arc::into_raw(args.clone()) as *const u8,
+            false,
+        );
+        // the process task reads its pages before it is done
+        task.blocker.WaitGroupWait(task, &args.done);
+
+        let pid = args.result.lock().clone()?;
+        info!("Restore process {} as pid {}", oldPid, pid);
+        if let Some(leader) = root.TaskWithID(pid) {
+            leaders.insert(oldPid, leader);
+        }
+        pids.push(pid);
+    }
+
+    return Ok(pids);
+}
+
+fn RestoreProcessHandler(para: *const u8) {
+    let args = unsafe { Arc::from_raw(para as *const RestoreProcessArgs) };
+    let task = Task::Current();
+
+    let res = RestoreProcess(task, &args);
+    let created = task.thread.is_some();
+    if let Err(e) = &res {
+        error!("Restore process {} fail {:?}", args.image.Pid, e);
+        if created {
+            // the half restored process is killed before it returns to the
+            // application
+            let tg = task.Thread().ThreadGroup();
+            tg.SendSignal(&SignalInfo::SignalInfoPriv(Signal(Signal::SIGKILL)))
+                .ok();
+        }
+    }
+
+    *args.result.lock() = res;
+    args.done.Done();
+    core::mem::drop(args);
+
+    if !created {
+        CPULocal::SetPendingFreeStack(task.taskId);
+        taskMgr::SwitchToNewTask();
+    }
+
+    StartRestoredTask();
+}
+
+fn Perms(perms: &str) -> AccessType {
+    return AccessType::New(
+        perms.contains('r'),
+        perms.contains('w'),
+        perms.contains('x'),
+    );
+}
+
+fn FindDirent(task: &Task, path: &str) -> Result<Dirent> {
+    let root = task.fsContext.RootDirectory();
+    let cwd = task.fsContext.WorkDirectory();
+    let mut remainingTraversals = MAX_SYMLINK_TRAVERSALS;
+    return task
+        .mountNS
+        .FindDirent(task, &root, Some(cwd), path, &mut remainingTraversals, true);
+}
+
+// OpenFile opens the file of path with the credentials of the restored task
+fn OpenFile(task: &Task, path: &str, flags: i32) -> Result<File> {
+    let d = FindDirent(task, path)?;
+    d.Inode()
+        .CheckPermission(task, &PermMask::FromFlags(flags as u32))?;
+    let flags = FileFlags::FromFlags(flags as u32);
+    return d.Inode().GetFile(task, &d, &flags);
+}
+
+// SetRegs loads the saved registers to the PtRegs of a task. Only the 64 bit
+// code segment is accepted, the stack segment and the privileged eflags are not
+// taken from the image.
+fn SetRegs(pt: &mut PtRegs, words: &[u64]) -> Result<()> {
+    if words.len() != mem::size_of::<PtRegs>() / mem::size_of::<u64>() {
+        return Err(Error::Common(format!(
+            "Restore regs has {} words",
+            words.len()
+        )));
+    }
+
+    let regs = unsafe { slice::from_raw_parts_mut(pt as *mut _ as *mut u64, words.len()) };
+    regs.copy_from_slice(words);
+
+    if pt.cs != USER_CS {
+        return Err(Error::Common(
+            "Restore only supports 64 bit processes".to_string(),
+        ));
+    }
+
+    pt.ss = USER_DS;
+    pt.eflags = EflagsDef::EFLAGS_IF | (pt.eflags & EflagsDef::EFLAGS_RESTOREABLE);
+    pt.orig_rax = core::u64::MAX;
+    return Ok(());
+}
+
+fn RestoreThread(task: &mut Task, image: &ThreadImage) -> Result<()> {
+    if !IsValidSegmentBase(image.FsBase) {
+        return Err(Error::SysError(SysErr::EPERM));
+    }
+
+    SetRegs(task.GetPtRegs(), &image.Regs)?;
+    task.context.fs = image.FsBase;
+    if image.ClearTid != 0 {
+        task.SetClearTID(image.ClearTid);
+    }
+
+    let fpstate = task.context.X86fpstate.Slice();
+    let len = core::cmp::min(fpstate.len(), image.FpState.len());
+    fpstate[..len].copy_from_slice(&image.FpState[..len]);
+    task.context.X86fpstate.SanitizeUser();
+
+    let mask = SignalSet(image.SignalMask & !UNBLOCKED_SIGNALS.0);
+    task.Thread().SetSignalMask(mask);
+    task.signalStack = SignalStack {
+        addr: image.AltStackAddr,
+        flags: image.AltStackFlags & (SignalStack::FLAG_DISABLE | SignalStack::FLAG_AUTODISARM),
+        size: image.AltStackSize,
+    };
+    return Ok(());
+}
+
+// RestoreSignals restores the signal actions, the timers and at last the
+// pending signals of the process of task. threads are the restored threads by
+// their tids in the image.
+fn RestoreSignals(
+    task: &Task,
+    args: &RestoreProcessArgs,
+    threads: &BTreeMap<i32, Thread>,
+) -> Result<()> {
+    let image = &args.image;
+    let lead = task.Thread();
+    let tg = lead.ThreadGroup();
+    for act in &image.SigActions {
+        let sigAct = SigAct {
+            handler: act.Handler,
+            flags: SigFlag(act.Flags),
+            restorer: act.Restorer,
+            mask: act.Mask,
+        };
+        tg.SetSignalAct(Signal(act.Signo), &Some(sigAct))?;
+    }
+
+    for itimer in &image.Itimers {
+        let itv = ItimerVal {
+            Interval: Timeval::FromNs(itimer.Interval),
+            Value: Timeval::FromNs(itimer.Value),
+        };
+        lead.Setitimer(itimer.Which, &itv)?;
+    }
+
+    let elapsed = RealNow() - args.realTime;
+    for timer in &image.Timers {
+        let clock = TimerClock(&tg, timer.ClockID)?;
+        let mut sigev = Sigevent {
+            Value: timer.Sigval,
+            Signo: timer.Signo,
+            Notify: timer.Notify,
+            ..Default::default()
+        };
+
+        if timer.Notify == SIGEV_THREAD_ID {
+            sigev.Tid = match threads.get(&timer.Tid) {
+                None => return Err(Error::SysError(SysErr::EINVAL)),
+                Some(t) => tg.PIDNamespace().IDOfTask(t),
+            };
+        }
+
+        lead.IntervalTimerCreateWithID(&clock, timer.ClockID, &mut sigev, timer.Id)?;
+
+        let mut value = timer.Value;
+        if value > 0 && IsRealtimeClock(timer.ClockID) {
+            // the expired timers fire once as soon as they are restored
+            value = core::cmp::max(value - elapsed, 1);
+        }
+
+        if value > 0 {
+            let its = Itimerspec {
+                Interval: Timespec::FromNs(timer.Interval),
+                Value: Timespec::FromNs(value),
+            };
+            lead.IntervalTimerSettime(timer.Id, &its, false)?;
+        }
+    }
+
+    for data in &image.PendingSignals {
+        lead.SendGroupSignal(&SignalInfoFromBytes(data)?)?;
+    }
+
+    for thread in &image.Threads {
+        let t = match threads.get(&thread.Tid) {
+            None => continue,
+            Some(t) => t,
+        };
+
+        for data in &thread.PendingSignals {
+            t.SendSignal(&SignalInfoFromBytes(data)?)?;
+        }
+    }
+
+    return Ok(());
+}
+
+fn RestoreVma(task: &mut Task, image: &VmaImage, pages: &mut RestorePages) -> Result<()> {
+    let perms = Perms(&image.Perms);
+    let len = image.End - image.Start;
+    if image.Name == VVAR_NAME {
+        MapVDSOParamPage(task, image.Start, GetVDSOParamPageAddr())?;
+        return Ok(());
+    }
+
+    if image.Name == VDSO_NAME {
+        MapVDSOPage(
+            task,
+            image.Start,
+            GetVDSOParamPageAddr() + MemoryDef::PAGE_SIZE,
+        )?;
+        return Ok(());
+    }
+
+    let mut opts = MMapOpts::NewAnonOptions(image.Name.to_string())?;
+    opts.Length = len;
+    opts.Addr = image.Start;
+    opts.Offset = image.Offset;
+    opts.Fixed = true;
+    opts.Unmap = true;
+    opts.Private = image.Private;
+    opts.GrowsDown = image.GrowsDown;
+    opts.MaxPerms = AccessType::AnyAccess();
+    // the saved pages are written before the permissions are set
+    opts.Perms = perms;
+    if image.Pages.len() > 0 {
+        opts.Perms.SetWrite();
+    }
+
+    if !image.Anonymous {
+        // like mmap(2), a shared mapping of the file is only writable if the
+        // file is opened for writing, which needs the write permission of the
+        // restored credentials
+        let flags = if !image.Private && perms.Write() {
+            OpenFlags::O_RDWR
+        } else {
+            OpenFlags::O_RDONLY
+        };
+
+        let file = OpenFile(task, &image.Name, flags)?;
+        if !image.Private && !perms.Write() {
+            opts.MaxPerms.ClearWrite();
+        }
+
+        if !opts.MaxPerms.SupersetOf(&opts.Perms) {
+            return Err(Error::SysError(SysErr::EACCES));
+        }
+
+        opts.Mappable = Some(file.Mappable()?);
+        opts.Mapping = Some(Arc::new(file));
+    } else if !image.Private {
+        opts.Mappable = Some(HostInodeOp::NewMemfdIops(len as i64)?);
+    }
+
+    task.mm.MMap(task, &mut opts)?;
+
+    for addr in &image.Pages {
+        if *addr < image.Start || *addr >= image.End {
+            return Err(Error::SysError(SysErr::EFAULT));
+        }
+
+        task.mm
+            .FixPermission(task, *addr, MemoryDef::PAGE_SIZE, true, false)?;
+        let (phyAddr, _) = task.mm.VirtualToPhy(*addr)?;
+        pages.Read(*addr, phyAddr)?;
+    }
+
+    if image.Pages.len() > 0 {
+        task.mm
+            .MProtect(image.Start, len, &perms, image.GrowsDown, -1)?;
+    }
+
+    return Ok(());
+}
+
+// RestoreSocket creates the socket of image again, binds it to the saved
+// address and puts it in listening. A unix socket file left by the
+// checkpointed process must be removed before the restore.
+fn RestoreSocket(task: &Task, image: &SocketImage, flags: i32) -> Result<Arc<File>> {
+    let file = NewSocket(task, image.Domain, image.Type, image.Protocol)?;
+    file.SetFlags(
+        task,
+        SettableFileFlags {
+            NonBlocking: flags & Flags::O_NONBLOCK != 0,
+            ..Default::default()
+        },
+    );
+    file.flags.lock().0.NonSeekable = true;
+
+    if image.Addr.len() > 0 {
+        file.FileOp.Bind(task, &image.Addr)?;
+    }
+
+    if image.Listening {
+        file.FileOp
+            .Listen(task, NET_SOMAXCONN.load(Ordering::Relaxed))?;
+    }
+
+    return Ok(file);
+}
+
+fn RestoreFds(task: &mut Task, args: &RestoreProcessArgs) -> Result<()> {
+    let image = &args.image;
+    let mut stdio = args.stdio.lock();
+    if stdio.len() == 0 {
+        for i in 0..args.stdiofds.len() {
+            let file = task.NewFileFromHostFd(i as i32, args.stdiofds[i], false)?;
+            file.flags.lock().0.NonBlocking = false; //need to clean the stdio nonblocking
+            stdio.push(file);
+        }
+    } else {
+        for i in 0..stdio.len() {
+            task.NewFDAt(i as i32, &stdio[i], &FDFlags::default())?;
+        }
+    }
+
+    for fdImage in &image.Fds {
+        if let Some(socket) = &fdImage.Socket {
+            let file = RestoreSocket(task, socket, fdImage.Flags)?;
+            let flags = FDFlags {
+                CloseOnExec: fdImage.CloseOnExec,
+            };
+            task.NewFDAt(fdImage.Fd, &file, &flags)?;
+            continue;
+        }
+
+        let file = match OpenFile(task, &fdImage.Path, fdImage.Flags) {
+            Ok(f) => f,
+            // the stdio which can't be reopened, e.g. a pipe of the host,
+            // is attached to the stdio of the restore
+            Err(_) if fdImage.Fd < 3 => continue,
+            Err(e) => {
+                info!("Restore can't reopen fd {} {}", fdImage.Fd, &fdImage.Path);
+                return Err(e);
+            }
+        };
+
+        if fdImage.Offset != 0 {
+            file.Seek(task, SeekWhence::SEEK_SET, fdImage.Offset)?;
+        }
+
+        let flags = FDFlags {
+            CloseOnExec: fdImage.CloseOnExec,
+        };
+        task.NewFDAt(fdImage.Fd, &file, &flags)?;
+    }
+
+    return Ok(());
+}
+
+fn RestoreProcess(task: &mut Task, args: &RestoreProcessArgs) -> Result<i32> {
+    let image = &args.image;
+    let leader = match image.Threads.first() {
+        None => {
+            return Err(Error::Common(format!(
+                "Restore process {} has no thread",
+                image.Pid
+            )))
+        }
+        Some(t) => t,
+    };
+
+    let kernel = GetKernel();
+    let container = match kernel.Container(&args.cid) {
+        None => {
+            return Err(Error::Common(format!(
+                "container {} doesn't exist",
+                &args.cid
+            )))
+        }
+        Some(c) => c,
+    };
+
+    if image.Uid != args.uid || image.Gid != args.gid {
+        info!(
+            "Restore process {} as {}/{} of the spec, the image has {}/{}",
+            image.Pid, args.uid, args.gid, image.Uid, image.Gid
+        );
+    }
+
+    let userns = kernel.RootUserNamespace();
+    let extraKGIDs: Vec<KGID> = args.additionalGids.iter().map(|gid| KGID(*gid)).collect();
+    let creds = Credentials::NewUserCredentials(
+        KUID(args.uid),
+        KGID(args.gid),
+        &extraKGIDs,
+        Some(&args.caps),
+        &userns,
+    );
+
+    let limits = match container.Init() {
+        Some(tg) => tg.Limits().GetCopy(),
+        None => NewLinuxLimitSet(),
+    };
+
+    // CreateProcess expects the working directory to exist
+    let mut remainingTraversals = MAX_SYMLINK_TRAVERSALS;
+    let root = container.Root();
+    let cwd = match container.mounts.FindDirent(
+        task,
+        &root,
+        None,
+        &image.Cwd,
+        &mut remainingTraversals,
+        true,
+    ) {
+        Ok(_) => image.Cwd.to_string(),
+        Err(_) => "/".to_string(),
+    };
+
+    let mut procArgs = CreateProcessArgs {
+        Filename: image.Name.to_string(),
+        Argv: vec![image.Name.to_string()],
+        WorkingDirectory: cwd,
+        Credentials: creds.clone(),
+        Umask: 0o22,
+        Limits: limits,
+        MaxSymlinkTraversals: MAX_SYMLINK_TRAVERSALS,
+        UTSNamespace: container.utsns.clone(),
+        IPCNamespace: container.ipcns.clone(),
+        ContainerID: args.cid.to_string(),
+        Stdiofds: args.stdiofds,
+        Parent: args.parent.clone(),
+        Tid: image.Pid,
+        ..Default::default()
+    };
+
+    task.creds = creds;
+    let (_tg, pid) = kernel.CreateProcess(&mut procArgs)?;
+    task.Thread().lock().name = image.Name.to_string();
+
+    let mut pages = match args.pages.lock().take() {
+        None => return Err(Error::SysError(SysErr::EINVAL)),
+        Some(p) => p,
+    };
+    for vma in &image.Vmas {
+        RestoreVma(task, vma, &mut pages)?;
+    }
+
+    task.mm.mapping.lock().brkInfo = BrkInfo {
+        brkStart: image.BrkStart,
+        brkEnd: image.BrkEnd,
+        brkMemEnd: image.BrkMemEnd,
+    };
+
+    if image.Executable.len() > 0 {
+        if let Ok(d) = FindDirent(task, &image.Executable) {
+            task.mm.SetExecutable(&d);
+        }
+    }
+
+    RestoreFds(task, args)?;
+    RestoreThread(task, leader)?;
+    SetFs(task.context.fs);
+
+    let mut threads = BTreeMap::new();
+    threads.insert(leader.Tid, task.Thread());
+
+    // the other threads are cloned from the leader before it runs, they start
+    // after the signals are restored
+    let mut cloned = Vec::new();
+    let mut res = Ok(());
+    for thread in &image.Threads[1..] {
+        let flags = CloneOp::CLONE_VM
+            | CloneOp::CLONE_FS
+            | CloneOp::CLONE_FILES
+            | CloneOp::CLONE_SIGHAND
+            | CloneOp::CLONE_THREAD
+            | CloneOp::CLONE_SYSVSEM;
+        let mut opts = CloneOptions::New(flags as u64, 0, 0, 0, 0, false)?;
+        opts.Tid = thread.Tid;
+        let childTask = match task.CloneVM(&opts, 0) {
+            Err(e) => {
+                res = Err(e);
+                break;
+            }
+            Ok((_, t)) => t,
+        };
+        let cTask = unsafe { &mut (*childTask) };
+        unsafe {
+            *(cTask.context.rsp as *mut u64) = StartRestoredTask as u64;
+        }
+        cloned.push(cTask.taskId);
+
+        res = RestoreThread(cTask, thread);
+        if res.is_err() {
+            break;
+        }
+        threads.insert(thread.Tid, cTask.Thread());
+    }
+
+    if res.is_ok() {
+        res = RestoreSignals(task, args, &threads);
+    }
+
+    // on error the threads start to exit with the SIGKILL of the process
+    for taskId in cloned {
+        taskMgr::NewTask(TaskId::New(taskId));
+    }
+
+    res?;
+    return Ok(pid);
+}
@@ -0,0 +1,303 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::qlib::mutex::*;
+use alloc::collections::btree_set::BTreeSet;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::ops::Deref;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering;
+
+use super::super::super::cgroup::MaxString;
+use super::super::super::cgroup::CPU_WEIGHT_DEFAULT;
+use super::super::super::common::*;
+use super::super::super::linux_def::*;
+use super::super::fs::dirent::*;
+use super::super::fs::mount::*;
+use super::super::fs::tmpfs::fs::*;
+use super::super::threadmgr::thread::*;
+use super::super::threadmgr::thread_group::*;
+use super::ipc_namespace::*;
+use super::kernel::*;
+use super::uts_namespace::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContainerStatus {
+    Created,
+    Running,
+    Stopped,
+}
+
+impl ContainerStatus {
+    pub fn String(&self) -> String {
+        return match self {
+            Self::Created => "created".to_string(),
+            Self::Running => "running".to_string(),
+            Self::Stopped => "stopped".to_string(),
+        };
+    }
+}
+
+// MemoryStat is the memory charged to a container, in bytes.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MemoryStat {
+    // anon is the resident memory of the processes
+    pub anon: u64,
+    // shmem is the file data of the size limited tmpfs mounts
+    pub shmem: u64,
+    // kernelStack is the kernel stacks of the tasks
+    pub kernelStack: u64,
+}
+
+impl MemoryStat {
+    pub fn File(&self) -> u64 {
+        return self.shmem;
+    }
+
+    pub fn Kernel(&self) -> u64 {
+        return self.kernelStack;
+    }
+
+    // Current is the cgroup memory.current
+    pub fn Current(&self) -> u64 {
+        return self.anon + self.File() + self.Kernel();
+    }
+
+    pub fn Add(&mut self, other: &MemoryStat) {
+        self.anon += other.anon;
+        self.shmem += other.shmem;
+        self.kernelStack += other.kernelStack;
+    }
+
+    // String is the content of the cgroup memory.stat
+    pub fn String(&self) -> String {
+        return format!(
+            "anon {}\nfile {}\nkernel {}\nkernel_stack {}\nshmem {}\n",
+            self.anon,
+            self.File(),
+            self.Kernel(),
+            self.kernelStack,
+            self.shmem
+        );
+    }
+}
+
+// ContainerInternal is a container of the sandbox, i.e. the root container or
+// a sub container of the pod. The tasks of a container share its root
+// filesystem and its uts and ipc namespaces, and are counted against its pids
+// limit as the cgroup pids controller does.
+pub struct ContainerInternal {
+    pub id: String,
+    pub mounts: MountNs,
+    pub utsns: UTSNamespace,
+    pub ipcns: IPCNamespace,
+
+    // parent is the container whose cgroup holds the cgroup of the container,
+    // i.e. the root container for the sub containers of the pod. The tasks of
+    // the container are charged to the parent as well.
+    pub parent: Option<Container>,
+
+    // pidsLimit is the max task count of the container, 0 is unlimited
+    pub pidsLimit: u64,
+    pub tasks: AtomicU64,
+    // pidsEvents is the count of the forks failed by the pids limit
+    pub pidsEvents: AtomicU64,
+
+    pub status: QMutex<ContainerStatus>,
+    // init is the thread group started by the container start
+    pub init: QMutex<Option<ThreadGroup>>,
+
+    // wxAllowList are the executables exempted from the W^X enforcement
+    pub wxAllowList: Vec<String>,
+
+    // cpuWeight is the cgroup cpu.weight of the container, 0 is the default.
+    // It scales the scheduling weight of the container tasks.
+    pub cpuWeight: u64,
+}
+
+#[derive(Clone)]
+pub struct Container(Arc<ContainerInternal>);
+
+impl Deref for Container {
+    type Target = Arc<ContainerInternal>;
+
+    fn deref(&self) -> &Arc<ContainerInternal> {
+        &self.0
+    }
+}
+
+impl Container {
+    pub fn New(
+        id: &str,
+        mounts: MountNs,
+        utsns: UTSNamespace,
+        ipcns: IPCNamespace,
+        pidsLimit: u64,
+        wxAllowList: &[String],
+        cpuWeight: u64,
+        parent: Option<Container>,
+    ) -> Self {
+        let internal = ContainerInternal {
+            id: id.to_string(),
+            mounts: mounts,
+            utsns: utsns,
+            ipcns: ipcns,
+            parent: parent,
+            pidsLimit: pidsLimit,
+            tasks: AtomicU64::new(0),
+            pidsEvents: AtomicU64::new(0),
+            status: QMutex::new(ContainerStatus::Created),
+            init: QMutex::new(None),
+            wxAllowList: wxAllowList.to_vec(),
+            cpuWeight: cpuWeight,
+        };
+
+        return Self(Arc::new(internal));
+    }
+
+    pub fn Root(&self) -> Dirent {
+        return self.mounts.Root();
+    }
+
+    pub fn Status(&self) -> ContainerStatus {
+        return *self.status.lock();
+    }
+
+    pub fn SetStopped(&self) {
+        *self.status.lock() = ContainerStatus::Stopped;
+    }
+
+    pub fn Init(&self) -> Option<ThreadGroup> {
+        return self.init.lock().clone();
+    }
+
+    pub fn SetInit(&self, tg: &ThreadGroup) {
+        *self.init.lock() = Some(tg.clone());
+        *self.status.lock() = ContainerStatus::Running;
+    }
+
+    // WXAllowed returns whether the executable at path, seen in the
+    // container, may map writable and executable pages.
+    pub fn WXAllowed(&self, path: &str) -> bool {
+        return self.wxAllowList.iter().any(|p| p == path);
+    }
+
+    pub fn CpuWeight(&self) -> u64 {
+        if self.cpuWeight == 0 {
+            return CPU_WEIGHT_DEFAULT;
+        }
+
+        return self.cpuWeight;
+    }
+
+    // TaskCount is the cgroup pids.current, it includes the tasks of the
+    // child containers
+    pub fn TaskCount(&self) -> u64 {
+        return self.tasks.load(Ordering::SeqCst);
+    }
+
+    pub fn PidsMax(&self) -> String {
+        return MaxString(self.pidsLimit);
+    }
+
+    // PidsEvents is the content of the cgroup pids.events
+    pub fn PidsEvents(&self) -> String {
+        return format!("max {}\n", self.pidsEvents.load(Ordering::SeqCst));
+    }
+
+    // AddTask counts a new task of the container and of its parents, it fails
+    // with EAGAIN when a pids limit is reached
+    pub fn AddTask(&self) -> Result<()> {
+        let mut cnt = self.tasks.load(Ordering::SeqCst);
+        loop {
+            if self.pidsLimit > 0 && cnt >= self.pidsLimit {
+                self.pidsEvents.fetch_add(1, Ordering::SeqCst);
+                return Err(Error::SysError(SysErr::EAGAIN));
+            }
+
+            match self
+                .tasks
+                .compare_exchange(cnt, cnt + 1, Ordering::SeqCst, Ordering::SeqCst)
+            {
+                Ok(_) => break,
+                Err(c) => cnt = c,
+            }
+        }
+
+        if let Some(parent) = &self.parent {
+            if let Err(e) = parent.AddTask() {
+                self.tasks.fetch_sub(1, Ordering::SeqCst);
+                return Err(e);
+            }
+        }
+
+        return Ok(());
+    }
+
+    pub fn RemoveTask(&self) {
+        self.tasks.fetch_sub(1, Ordering::SeqCst);
+        if let Some(parent) = &self.parent {
+            parent.RemoveTask();
+        }
+    }
+
+    // MemoryStat rolls up the memory of the container from the memory managers
+    // of its processes and from its tmpfs mounts.
+    pub fn MemoryStat(&self, k: &Kernel) -> MemoryStat {
+        let mut stat = MemoryStat::default();
+
+        // the threads of a process, and the vfork children until they exec,
+        // share the memory manager
+        let mut mms = BTreeSet::new();
+        for tg in k.TaskSet().Root().ThreadGroups() {
+            if tg.lock().containerID != self.id {
+                continue;
+            }
+
+            let threads: Vec<Thread> = tg.lock().tasks.iter().cloned().collect();
+            for thread in &threads {
+                stat.kernelStack += MemoryDef::DEFAULT_STACK_SIZE;
+                let mm = thread.MemoryManager();
+                if mms.insert(mm.uid) {
+                    stat.anon += mm.ResidentSetSize();
+                }
+            }
+        }
+
+        // a tmpfs mounted more than once is charged once
+        let mut quotas = BTreeSet::new();
+        for msrc in self.mounts.MountSources() {
+            if let Some(quota) = TmpfsQuotaOf(&msrc) {
+                if quotas.insert(Arc::as_ptr(&quota) as u64) {
+                    stat.shmem += quota.used.load(Ordering::SeqCst);
+                }
+            }
+        }
+
+        return stat;
+    }
+
+    // CgroupMemoryStat is the memory charged to the cgroup of the container,
+    // the root container is the cgroup of the whole sandbox
+    pub fn CgroupMemoryStat(&self, k: &Kernel) -> MemoryStat {
+        if self.parent.is_none() {
+            return k.SandboxMemoryStat();
+        }
+
+        return self.MemoryStat(k);
+    }
+}
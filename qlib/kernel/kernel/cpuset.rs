@@ -12,8 +12,11 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use alloc::string::String;
 use alloc::vec::Vec;
 
+use super::super::super::numa::*;
+
 pub const BITS_PER_BYTE: usize = 8;
 pub const BYTES_PER_LONG: usize = 8; // only for 64-bit architectures
 
@@ -125,4 +128,27 @@ impl CPUSet {
             }
         }
     }
+
+    // MaskString formats the set as comma separated 32 bit hex words, the
+    // highest word first, as in /proc/[pid]/status Cpus_allowed.
+    pub fn MaskString(&self) -> String {
+        let mut words = Vec::new();
+        for chunk in self.0.chunks(4) {
+            let mut word = 0u32;
+            for (i, v) in chunk.iter().enumerate() {
+                word |= (*v as u32) << (i * BITS_PER_BYTE);
+            }
+            words.push(format!("{:08x}", word));
+        }
+
+        words.reverse();
+        return words.join(",");
+    }
+
+    // ListString formats the set as a cpu range list such as "0-3,6".
+    pub fn ListString(&self) -> String {
+        let mut cpus = Vec::new();
+        self.ForEachCPU(|cpu| cpus.push(cpu));
+        return CpuList(&cpus);
+    }
 }
@@ -0,0 +1,143 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use alloc::string::String;
+use core::sync::atomic::AtomicI32;
+use core::sync::atomic::AtomicI64;
+use core::sync::atomic::Ordering;
+
+use super::super::super::mutex::*;
+use super::super::kernel_util::*;
+use super::super::Kernel::HostSpace;
+use super::super::Scale;
+use super::super::SHARESPACE;
+use super::super::TSC;
+
+pub static ENTROPY_POOL: EntropyPool = EntropyPool::New();
+
+// POOL_BITS is the size of the input pool, as /proc/sys/kernel/random/poolsize.
+pub const POOL_BITS: i32 = 256;
+
+// EntropyPool accounts the entropy of the guest. The random bytes come from
+// the host, the pool is credited by the entropy the host injects
+// periodically and by the privileged writers of RNDADDENTROPY.
+pub struct EntropyPool {
+    // count is the entropy in the pool in bits
+    pub count: AtomicI32,
+    pub writeWakeupThreshold: AtomicI32,
+    pub reseedSecs: AtomicI32,
+    // lastRefill is the tsc of the last host entropy injection
+    pub lastRefill: AtomicI64,
+    pub bootId: QMutex<Option<String>>,
+}
+
+impl EntropyPool {
+    pub const fn New() -> Self {
+        return Self {
+            count: AtomicI32::new(POOL_BITS),
+            writeWakeupThreshold: AtomicI32::new(POOL_BITS),
+            reseedSecs: AtomicI32::new(60),
+            lastRefill: AtomicI64::new(0),
+            bootId: QMutex::new(None),
+        };
+    }
+
+    pub fn EntropyAvail(&self) -> i32 {
+        return self.count.load(Ordering::Relaxed);
+    }
+
+    // Credit adds bits of entropy to the pool, which never holds more than
+    // POOL_BITS.
+    pub fn Credit(&self, bits: i32) {
+        let mut count = self.count.load(Ordering::Relaxed);
+        loop {
+            let mut new = count.saturating_add(bits);
+            if new > POOL_BITS {
+                new = POOL_BITS;
+            } else if new < 0 {
+                new = 0;
+            }
+
+            match self
+                .count
+                .compare_exchange(count, new, Ordering::SeqCst, Ordering::Relaxed)
+            {
+                Ok(_) => return,
+                Err(c) => count = c,
+            }
+        }
+    }
+
+    pub fn Clear(&self) {
+        self.count.store(0, Ordering::SeqCst);
+    }
+
+    // Refill injects the entropy of the host into the pool.
+    pub fn Refill(&self) {
+        self.lastRefill.store(TSC.Rdtsc(), Ordering::Relaxed);
+        let bits = HostSpace::HostEntropy(POOL_BITS);
+        if bits > 0 {
+            self.Credit(bits as i32);
+        }
+    }
+
+    // Check refills the pool every EntropyRefill seconds. It is called by the
+    // vcpus from the async process.
+    pub fn Check(&self) {
+        let interval = SHARESPACE.config.read().EntropyRefill;
+        if interval == 0 {
+            return;
+        }
+
+        let now = TSC.Rdtsc();
+        let last = self.lastRefill.load(Ordering::Relaxed);
+        if Scale(now - last) < interval as i64 * 1000_000 {
+            return;
+        }
+
+        // only one vcpu does the refill
+        if self
+            .lastRefill
+            .compare_exchange(last, now, Ordering::SeqCst, Ordering::Relaxed)
+            .is_err()
+        {
+            return;
+        }
+
+        self.Refill();
+    }
+
+    // BootId is the random uuid of this boot.
+    pub fn BootId(&self) -> String {
+        let mut bootId = self.bootId.lock();
+        if bootId.is_none() {
+            *bootId = Some(RandomUUID());
+        }
+
+        return bootId.clone().unwrap();
+    }
+}
+
+// RandomUUID returns a version 4 uuid.
+pub fn RandomUUID() -> String {
+    let hi = (RandU64().unwrap_or(0) & !0xf000) | 0x4000;
+    let lo = (RandU64().unwrap_or(0) & !(0xc << 60)) | (0x8 << 60);
+    return format!(
+        "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+        hi >> 32,
+        (hi >> 16) & 0xffff,
+        hi & 0xffff,
+        lo >> 48,
+        lo & 0xffff_ffff_ffff
+    );
+}
@@ -37,6 +37,14 @@ pub struct FileAsyncInternal {
     pub recipientPG: Option<ProcessGroup>,
     pub recipientTG: Option<ThreadGroup>,
     pub recipientT: Option<Thread>,
+
+    // signal is the signal set by F_SETSIG, or 0 for SIGIO.
+    pub signal: i32,
+    // fd is reported in si_fd when signal is set.
+    pub fd: i32,
+
+    // urgent delivers SIGURG for socket out-of-band data.
+    pub urgent: WaitEntry,
 }
 
 #[derive(Clone, Default)]
@@ -50,46 +58,107 @@ impl Deref for FileAsync {
     }
 }
 
-impl FileAsync {
-    pub fn Callback(&self) {
-        let a = self.lock();
+// pollCode returns the si_code and si_band for an event mask, following
+// band_table in fs/fcntl.c.
+fn pollCode(mask: EventMask) -> (i32, i64) {
+    let (code, band) = if mask & EVENT_ERR != 0 {
+        (SignalInfo::POLL_ERR, PollConst::POLLERR)
+    } else if mask & EVENT_HUP != 0 {
+        (
+            SignalInfo::POLL_HUP,
+            PollConst::POLLHUP | PollConst::POLLERR,
+        )
+    } else if mask & EVENT_PRI != 0 {
+        (
+            SignalInfo::POLL_PRI,
+            PollConst::POLLPRI | PollConst::POLLRDBAND,
+        )
+    } else if mask & READABLE_EVENT != 0 {
+        (
+            SignalInfo::POLL_IN,
+            PollConst::POLLIN | PollConst::POLLRDNORM | PollConst::POLLMSG,
+        )
+    } else {
+        (
+            SignalInfo::POLL_OUT,
+            PollConst::POLLOUT | PollConst::POLLWRNORM | PollConst::POLLWRBAND,
+        )
+    };
 
-        /*match a.e.lock().context {
-            WaitContext::None => return,
-            _ =>(),
-        }*/
+    return (code, band as i64);
+}
+
+impl FileAsyncInternal {
+    // Logic from sigio_perm in fs/fcntl.c.
+    fn mayNotify(&self, t: &Thread) -> bool {
+        let c = t.Credentials();
+
+        let threadC = c.lock();
+        let reqC = self.requester.lock();
+        return reqC.EffectiveKUID.0 == 0
+            || reqC.EffectiveKUID == threadC.SavedKUID
+            || reqC.EffectiveKUID == threadC.RealKUID
+            || reqC.RealKUID == threadC.SavedKUID
+            || reqC.RealKUID == threadC.RealKUID;
+    }
 
-        let mut t = a.recipientT.clone();
-        let mut tg = a.recipientTG.clone();
+    fn notifyThreadGroup(&self, tg: &ThreadGroup, info: &SignalInfo) {
+        let leader = match tg.Leader() {
+            None => return,
+            Some(t) => t,
+        };
 
-        if a.recipientPG.is_some() {
-            tg = Some(a.recipientPG.as_ref().unwrap().Originator());
+        if self.mayNotify(&leader) {
+            // The thread group may be exiting; there is no one to report to.
+            let _ = tg.SendSignal(info);
         }
+    }
 
-        if tg.is_some() {
-            t = tg.unwrap().Leader();
+    // send delivers info to the owner, as send_sigio_to_task does for each
+    // task it picks.
+    fn send(&self, info: &SignalInfo) {
+        if let Some(t) = &self.recipientT {
+            if self.mayNotify(t) {
+                let _ = t.SendSignal(info);
+            }
+            return;
         }
 
-        if t.is_none() {
-            // No recipient has been registered.
+        if let Some(tg) = &self.recipientTG {
+            self.notifyThreadGroup(tg, info);
             return;
         }
 
-        let t = t.unwrap();
+        if let Some(pg) = &self.recipientPG {
+            for tg in pg.ThreadGroups() {
+                self.notifyThreadGroup(&tg, info);
+            }
+        }
+    }
+}
 
-        let c = t.Credentials();
+impl FileAsync {
+    pub fn Callback(&self, mask: EventMask) {
+        let a = self.lock();
 
-        let threadC = c.lock();
-        let reqC = a.requester.lock();
-        // Logic from sigio_perm in fs/fcntl.c.
-        if reqC.EffectiveKUID.0 == 0
-            || reqC.EffectiveKUID == threadC.SavedKUID
-            || reqC.EffectiveKUID == threadC.RealKUID
-            || reqC.RealKUID == threadC.SavedKUID
-            || reqC.RealKUID == threadC.RealKUID
-        {
-            t.SendSignal(&SignalInfoPriv(SIGIO.0)).unwrap();
+        if a.signal == 0 {
+            a.send(&SignalInfoPriv(SIGIO.0));
+            return;
         }
+
+        // With F_SETSIG the handler can tell which file and event fired.
+        let (code, band) = pollCode(mask);
+        let mut info = SignalInfoPriv(a.signal);
+        info.Code = code;
+        let poll = info.SigPoll();
+        poll.band = band;
+        poll.fd = a.fd;
+        a.send(&info);
+    }
+
+    pub fn UrgentCallback(&self) {
+        let a = self.lock();
+        a.send(&SignalInfoPriv(SIGURG.0));
     }
 
     // Register sets the file which will be monitored for IO events.
@@ -104,7 +173,11 @@ impl FileAsync {
         }
 
         a.e.lock().context = WaitContext::FileAsync(self.clone());
-        w.EventRegister(task, &a.e, READABLE_EVENT | WRITEABLE_EVENT | EVENT_ERR | EVENT_HUP);
+        w.EventRegister(
+            task,
+            &a.e,
+            READABLE_EVENT | WRITEABLE_EVENT | EVENT_PRI | EVENT_ERR | EVENT_HUP,
+        );
     }
 
     // Unregister stops monitoring a file.
@@ -122,6 +195,44 @@ impl FileAsync {
         a.e.lock().context = WaitContext::None;
     }
 
+    // RegisterUrgent makes out-of-band data on a socket send SIGURG to the
+    // owner. Unlike SIGIO this doesn't depend on O_ASYNC.
+    pub fn RegisterUrgent(&self, task: &Task, w: &Waitable) {
+        let a = self.lock();
+
+        match a.urgent.lock().context {
+            WaitContext::None => (),
+            _ => return,
+        }
+
+        a.urgent.lock().context = WaitContext::FileAsyncUrgent(self.clone());
+        w.EventRegister(task, &a.urgent, EVENT_PRI);
+    }
+
+    pub fn UnregisterUrgent(&self, task: &Task, w: &Waitable) {
+        let a = self.lock();
+
+        match a.urgent.lock().context {
+            WaitContext::None => return,
+            _ => (),
+        }
+
+        w.EventUnregister(task, &a.urgent);
+        a.urgent.lock().context = WaitContext::None;
+    }
+
+    pub fn Signal(&self) -> i32 {
+        return self.lock().signal;
+    }
+
+    pub fn SetSignal(&self, signal: i32) {
+        self.lock().signal = signal;
+    }
+
+    pub fn SetFd(&self, fd: i32) {
+        self.lock().fd = fd;
+    }
+
     // Owner returns who is currently getting signals. All return values will be
     // nil if no one is set to receive signals.
     pub fn Owner(&self) -> (Option<Thread>, Option<ThreadGroup>, Option<ProcessGroup>) {
@@ -130,12 +130,20 @@ impl FDTableInternal {
         self.descTbl.insert(fd, fdesc);
     }
 
-    pub fn NewFDFrom(&mut self, fd: i32, file: &File, flags: &FDFlags) -> Result<i32> {
-        let fds = self.NewFDs(fd, &[file.clone()], flags)?;
+    pub fn NewFDFrom(&mut self, fd: i32, file: &File, flags: &FDFlags, end: i32) -> Result<i32> {
+        let fds = self.NewFDs(fd, &[file.clone()], flags, end)?;
         return Ok(fds[0]);
     }
 
-    pub fn NewFDs(&mut self, fd: i32, files: &[File], flags: &FDFlags) -> Result<Vec<i32>> {
+    // NewFDs allocates the lowest free fds >= fd and < end, where end is the
+    // caller's RLIMIT_NOFILE.
+    pub fn NewFDs(
+        &mut self,
+        fd: i32,
+        files: &[File],
+        flags: &FDFlags,
+        end: i32,
+    ) -> Result<Vec<i32>> {
         if fd < 0 {
             return Err(Error::SysError(SysErr::EINVAL));
         }
@@ -145,8 +153,6 @@ impl FDTableInternal {
             fd = self.next;
         }
 
-        let end = core::i32::MAX;
-
         let mut fds = Vec::new();
         let mut i = fd;
 
@@ -188,32 +194,26 @@ impl FDTableInternal {
         return Ok(());
     }
 
-    pub fn Dup(&mut self, fd: i32) -> Result<i32> {
+    pub fn Dup(&mut self, fd: i32, end: i32) -> Result<i32> {
         if fd < 0 {
             return Err(Error::SysError(SysErr::EBADF));
         }
 
-        let (f, flags) = self.Get(fd)?;
-        return self.NewFDFrom(0, &f, &flags);
+        // The new descriptor never inherits FD_CLOEXEC.
+        let (f, _) = self.Get(fd)?;
+        return self.NewFDFrom(0, &f, &FDFlags::default(), end);
     }
 
-    pub fn Dup2(&mut self, oldfd: i32, newfd: i32) -> Result<i32> {
-        if oldfd < 0 {
-            return Err(Error::SysError(SysErr::EBADF));
-        }
-
-        if newfd < 0 {
-            return Err(Error::SysError(SysErr::EBADF));
-        }
-
-        self.Remove(newfd);
-
-        let (f, flags) = self.Get(oldfd)?;
-        self.NewFDAt(newfd, &f, &flags)?;
-        return Ok(newfd);
+    pub fn Dup2(&mut self, oldfd: i32, newfd: i32) -> Result<Option<File>> {
+        return self.Dup3(oldfd, newfd, &FDFlags::default());
     }
 
-    pub fn Dup3(&mut self, oldfd: i32, newfd: i32, flags: i32) -> Result<i32> {
+    // Dup3 points newfd at oldfd's file with the given fd flags. Any file
+    // already at newfd is replaced in the same step, so another thread never
+    // sees newfd closed or without its flags; the replaced file is returned
+    // for the caller to close outside the table lock. oldfd is checked before
+    // anything changes.
+    pub fn Dup3(&mut self, oldfd: i32, newfd: i32, flags: &FDFlags) -> Result<Option<File>> {
         if oldfd < 0 {
             return Err(Error::SysError(SysErr::EBADF));
         }
@@ -222,13 +222,10 @@ impl FDTableInternal {
             return Err(Error::SysError(SysErr::EBADF));
         }
 
-        self.Remove(newfd);
-        let closeOnExec = Flags(flags).CloseOnExec();
-
-        let (f, mut flags) = self.Get(oldfd)?;
-        flags.CloseOnExec = closeOnExec;
-        self.NewFDAt(newfd, &f, &flags)?;
-        return Ok(newfd);
+        let (f, _) = self.Get(oldfd)?;
+        let replaced = self.descTbl.remove(&newfd).map(|d| d.file);
+        self.set(newfd, &f, flags);
+        return Ok(replaced);
     }
 
     pub fn SetFlags(&mut self, fd: i32, flags: &FDFlags) -> Result<()> {
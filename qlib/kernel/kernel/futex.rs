@@ -55,7 +55,9 @@ pub enum KeyKind {
     KindSharedPrivate,
 
     // KindSharedMappable indicates a shared futex on a memory mapping other
-    // than a private anonymous memory mapping.
+    // than a private anonymous memory mapping. It is identified by the mappable
+    // and the offset in it, so the processes sharing the memory through fork,
+    // a file or a shm object find the same futex.
     KindSharedMappable,
 }
 
@@ -71,9 +73,14 @@ pub struct Key {
     // Kind is the type of the Key.
     pub Kind: KeyKind,
 
-    // If Kind is KindPrivate or KindSharedPrivate, addr is the represented
-    // physical memory address. Otherwise, is the user address
+    // If Kind is KindPrivate or KindSharedPrivate, addr is the user address.
+    // Otherwise, it is the offset in the mappable, or the physical address of
+    // a shared mapping without mappable.
     pub Addr: u64,
+
+    // Mappable is the identity of the mappable for KindSharedMappable, 0 for
+    // the other kinds.
+    pub Mappable: u64,
 }
 
 pub const BUCKET_COUNT: usize = 1 << BUCKET_COUNT_BITS;
@@ -81,7 +88,7 @@ pub const BUCKET_COUNT_BITS: usize = 10;
 
 impl Key {
     pub fn HashCode(&self) -> usize {
-        let addr = self.Addr as usize;
+        let addr = (self.Addr ^ self.Mappable) as usize;
         let h1 = (addr >> 2) + (addr >> 12) + (addr >> 22);
         let h2 = (addr >> 32) + (addr >> 42);
         return (h1 + h2) % BUCKET_COUNT;
@@ -120,32 +127,7 @@ impl Target for Task {
     }
 
     fn GetSharedKey(&self, addr: u64) -> Result<Key> {
-        /*let vmas = self.mm.vmas.clone();
-        let vma = match vmas.lock().Get(addr) {
-            None => return Err(Error::SysError(SysErr::EFAULT)),
-            Some(v) => v.clone(),
-        };*/
-        let _ml = self.mm.MappingWriteLock();
-
-        let (vma, _) = match self.mm.GetVmaAndRangeLocked(addr) {
-            None => return Err(Error::SysError(SysErr::EFAULT)),
-            Some(v) => v.clone(),
-        };
-
-        let private = vma.private;
-        if private {
-            return Ok(Key {
-                Kind: KeyKind::KindSharedPrivate,
-                Addr: addr,
-            });
-        }
-
-        let (phyAdr, _) = self.mm.VirtualToPhyLocked(addr)?;
-
-        return Ok(Key {
-            Kind: KeyKind::KindSharedMappable,
-            Addr: phyAdr,
-        });
+        return self.mm.GetSharedFutexKey(self, addr);
     }
 }
 
@@ -219,6 +201,7 @@ fn Getkey(t: &Target, addr: u64, private: bool) -> Result<Key> {
         return Ok(Key {
             Kind: KeyKind::KindPrivate,
             Addr: addr,
+            Mappable: 0,
         });
     }
 
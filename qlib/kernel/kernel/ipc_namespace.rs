@@ -12,15 +12,19 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use super::semaphore;
-//use super::shm;
 use super::super::super::auth::userns::*;
+use super::mqueue;
+use super::msgqueue;
+use super::semaphore;
+use super::shm;
 
 #[derive(Clone, Default)]
 pub struct IPCNamespace {
     pub userNS: UserNameSpace,
     pub semphores: semaphore::Registry,
-    //pub shms: shm::Registry,
+    pub shms: shm::Registry,
+    pub queues: msgqueue::Registry,
+    pub mqueues: mqueue::Registry,
 }
 
 impl IPCNamespace {
@@ -28,7 +32,9 @@ impl IPCNamespace {
         return Self {
             userNS: userNS.clone(),
             semphores: semaphore::Registry::New(userNS),
-            //shms: shm::Registry::New(userNS)
+            shms: shm::Registry::New(userNS),
+            queues: msgqueue::Registry::New(userNS),
+            mqueues: mqueue::Registry::New(userNS),
         };
     }
 
@@ -36,7 +42,15 @@ impl IPCNamespace {
         return self.semphores.clone();
     }
 
-    /*pub fn ShmRegistry(&self) -> shm::Registry {
-        return self.shms.clone()
-    }*/
+    pub fn ShmRegistry(&self) -> shm::Registry {
+        return self.shms.clone();
+    }
+
+    pub fn MsgqueueRegistry(&self) -> msgqueue::Registry {
+        return self.queues.clone();
+    }
+
+    pub fn MqueueRegistry(&self) -> mqueue::Registry {
+        return self.mqueues.clone();
+    }
 }
@@ -34,7 +34,6 @@ use super::super::super::linux_def::*;
 use super::super::super::path::*;
 use super::super::super::singleton::*;
 use super::super::fs::dirent::*;
-use super::super::fs::mount::*;
 use super::super::loader::loader::*;
 use super::super::task::*;
 use super::super::threadmgr::pid_namespace::*;
@@ -44,8 +43,10 @@ use super::super::threadmgr::thread::*;
 use super::super::threadmgr::thread_group::*;
 use super::super::threadmgr::threads::*;
 use super::super::uid::NewUID;
+use super::super::Kernel::HostSpace;
 use super::super::SignalDef::*;
 use super::super::SHARESPACE;
+use super::container::*;
 use super::cpuset::*;
 use super::fd_table::*;
 use super::ipc_namespace::*;
@@ -56,6 +57,7 @@ use super::timer::timekeeper::*;
 use super::timer::timer::*;
 use super::timer::*;
 use super::uts_namespace::*;
+use super::waiter::qlock::*;
 
 pub static ASYNC_PROCESS_TIMER: Singleton<Timer> = Singleton::<Timer>::New();
 
@@ -85,6 +87,39 @@ pub struct StaticInfo {
     pub cpu: i32,
 }
 
+// UserCharge is one object, e.g. a queued signal or a task, counted against a
+// per-user limit such as RLIMIT_SIGPENDING or RLIMIT_NPROC. The count is
+// released when the charge is dropped.
+#[derive(Debug)]
+pub struct UserCharge(Arc<AtomicU64>);
+
+impl UserCharge {
+    pub fn New(count: &Arc<AtomicU64>) -> Arc<Self> {
+        count.fetch_add(1, Ordering::SeqCst);
+        return Arc::new(Self(count.clone()));
+    }
+
+    pub fn TryNew(count: &Arc<AtomicU64>, limit: u64) -> Option<Arc<Self>> {
+        let mut curr = count.load(Ordering::SeqCst);
+        loop {
+            if curr >= limit {
+                return None;
+            }
+
+            match count.compare_exchange(curr, curr + 1, Ordering::SeqCst, Ordering::SeqCst) {
+                Ok(_) => return Some(Arc::new(Self(count.clone()))),
+                Err(c) => curr = c,
+            }
+        }
+    }
+}
+
+impl Drop for UserCharge {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
 #[derive(Default)]
 pub struct KernelInternal {
     // extMu serializes external changes to the Kernel with calls to
@@ -106,8 +141,9 @@ pub struct KernelInternal {
     pub applicationCores: usize,
     //pub useHostCores: bool,
 
-    // mounts holds the states of the virtual filesystem, one for each container mountNS.
-    pub mounts: QRwLock<BTreeMap<String, MountNs>>,
+    // containers are the containers of the sandbox keyed by the container id,
+    // each holds the virtual filesystem of the container.
+    pub containers: QRwLock<BTreeMap<String, Container>>,
 
     // globalInit is the thread group whose leader has ID 1 in the root PID
     // namespace. globalInit is stored separately so that it is accessible even
@@ -140,6 +176,18 @@ pub struct KernelInternal {
 
     pub platform: DefaultPlatform,
     pub lastProcessTime: QMutex<i64>,
+
+    // sigPending counts the queued signals of each real user, keyed by the
+    // KUID, for RLIMIT_SIGPENDING.
+    pub sigPending: QMutex<BTreeMap<u32, Arc<AtomicU64>>>,
+
+    // userTasks counts the tasks of each real user, keyed by the KUID, for
+    // RLIMIT_NPROC.
+    pub userTasks: QMutex<BTreeMap<u32, Arc<AtomicU64>>>,
+
+    // fsFreeze holds the mount barriers taken by FreezeFilesystems, so that
+    // the writes to the mounts block until ThawFilesystems.
+    pub fsFreeze: QMutex<Option<Vec<QAsyncWriteLockGuard>>>,
 }
 
 impl KernelInternal {
@@ -201,8 +249,11 @@ impl Kernel {
             rootUTSNamespace: args.RootUTSNamespace,
             rootIPCNamespace: args.RootIPCNamespace,
             applicationCores: args.ApplicationCores as usize - 1,
-            mounts: QRwLock::new(BTreeMap::new()),
+            containers: QRwLock::new(BTreeMap::new()),
             globalInit: QMutex::new(None),
+            sigPending: QMutex::new(BTreeMap::new()),
+            userTasks: QMutex::new(BTreeMap::new()),
+            fsFreeze: QMutex::new(None),
             cpuClock: AtomicU64::new(0),
             staticInfo: QMutex::new(StaticInfo {
                 ApplicationCores: args.ApplicationCores,
@@ -253,8 +304,26 @@ impl Kernel {
         return Ok(());
     }
 
+    // ApplicationCores follows the online vcpus, as qvisor may park vcpus or
+    // bring them back at runtime when the vcpu autoscaling is enabled.
     pub fn ApplicationCores(&self) -> u32 {
-        return self.staticInfo.lock().ApplicationCores;
+        let online = SHARESPACE.scheduler.OnlineVcpuCnt() as u32;
+        let mut info = self.staticInfo.lock();
+        info.ApplicationCores = online;
+        return online;
+    }
+
+    // OnlineCores is the count of the online cpus shown in /proc/cpuinfo and
+    // /sys/devices/system/cpu/online, it is applicationCores less the parked
+    // vcpus.
+    pub fn OnlineCores(&self) -> usize {
+        let scheduler = &SHARESPACE.scheduler;
+        let parked = scheduler.vcpuCnt - scheduler.OnlineVcpuCnt();
+        if self.applicationCores > parked + 1 {
+            return self.applicationCores - parked;
+        }
+
+        return 1;
     }
 
     // TaskSet returns the TaskSet.
@@ -282,9 +351,70 @@ impl Kernel {
         return self.rootIPCNamespace.clone();
     }
 
+    pub fn SigPendingCount(&self, kuid: KUID) -> Arc<AtomicU64> {
+        return self
+            .sigPending
+            .lock()
+            .entry(kuid.0)
+            .or_insert_with(|| Arc::new(AtomicU64::new(0)))
+            .clone();
+    }
+
+    pub fn UserTaskCount(&self, kuid: KUID) -> Arc<AtomicU64> {
+        return self
+            .userTasks
+            .lock()
+            .entry(kuid.0)
+            .or_insert_with(|| Arc::new(AtomicU64::new(0)))
+            .clone();
+    }
+
+    pub fn AddContainer(&self, container: Container) -> Result<()> {
+        let mut containers = self.containers.write();
+        if containers.contains_key(&container.id) {
+            return Err(Error::Common(format!(
+                "container {} already exists",
+                &container.id
+            )));
+        }
+
+        containers.insert(container.id.clone(), container);
+        return Ok(());
+    }
+
+    pub fn Container(&self, cid: &str) -> Option<Container> {
+        return self.containers.read().get(cid).cloned();
+    }
+
+    // SandboxMemoryStat is the memory of the sandbox cgroup, i.e. the sum of
+    // all the containers of the pod
+    pub fn SandboxMemoryStat(&self) -> MemoryStat {
+        let containers: Vec<Container> = self.containers.read().values().cloned().collect();
+        let mut stat = MemoryStat::default();
+        for c in &containers {
+            stat.Add(&c.MemoryStat(self));
+        }
+
+        return stat;
+    }
+
+    pub fn RemoveContainer(&self, cid: &str) -> Option<Container> {
+        return self.containers.write().remove(cid);
+    }
+
     pub fn CreateProcess(&self, args: &mut CreateProcessArgs) -> Result<(ThreadGroup, ThreadID)> {
         self.extMu.lock();
 
+        let container = match self.Container(&args.ContainerID) {
+            None => {
+                return Err(Error::Common(format!(
+                    "container {} doesn't exist",
+                    &args.ContainerID
+                )))
+            }
+            Some(c) => c,
+        };
+
         let root = self.tasks.Root();
         let tg = self.newThreadGroup(
             &root,
@@ -298,7 +428,8 @@ impl Kernel {
         {
             let mut tglock = tg.lock();
             tglock.liveThreads.Add(1);
-            tglock.root = true;
+            // a process restored with its parent is reaped by the parent
+            tglock.root = args.Parent.is_none();
         }
 
         if args.Filename.as_str() == "" {
@@ -316,7 +447,7 @@ impl Kernel {
         }
 
         let task = Task::Current();
-        let mns = self.mounts.read().get(&args.ContainerID).unwrap().clone();
+        let mns = container.mounts.clone();
         let root = mns.Root();
         // TODO: check this, what is the relationship between task/process/container process
         task.fsContext.SetRootDirectory(&root);
@@ -338,7 +469,8 @@ impl Kernel {
         let config = TaskConfig {
             TaskId: task.taskId,
             Kernel: self.clone(),
-            Parent: None,
+            Parent: args.Parent.clone(),
+            Tid: args.Tid,
             InheritParent: None,
             ThreadGroup: tg.clone(),
             SignalMask: SignalSet(0),
@@ -356,8 +488,20 @@ impl Kernel {
             ContainerID: args.ContainerID.to_string(),
         };
 
+        container.AddTask()?;
         let ts = self.tasks.clone();
-        ts.NewTask(&config, true, self)?;
+        let t = match ts.NewTask(&config, true, self) {
+            Ok(t) => t,
+            Err(e) => {
+                container.RemoveTask();
+                return Err(e);
+            }
+        };
+
+        // Like Linux, a new process of the container is not checked against
+        // RLIMIT_NPROC but counts towards it.
+        let realKUID = config.Credentials.lock().RealKUID;
+        t.lock().userCharge = Some(UserCharge::New(&self.UserTaskCount(realKUID)));
 
         let root = ts.Root();
         let tgid = root.IDOfThreadGroup(&tg);
@@ -403,7 +547,17 @@ impl Kernel {
         assert!(threads.len() == 1, "ThreadGroup start has multiple threads");*/
 
         let task = Task::Current();
-        return Load(task, fileName, args, envs, &Vec::new());
+        let (file, executable, argv) = OpenElf(task, fileName, None, args)?;
+        return Load(
+            task,
+            fileName,
+            &file,
+            &executable,
+            &argv,
+            envs,
+            &Vec::new(),
+            false,
+        );
 
         //return Thread::Start(fileName, envs, args);
     }
@@ -423,6 +577,51 @@ impl Kernel {
         self.tasks.EndExternalStop();
     }
 
+    // SyncBarriers returns the sync barriers of the mounts of all the
+    // containers. A mount source shared by the containers is returned once.
+    fn SyncBarriers(&self) -> Vec<QAsyncRwLock> {
+        let containers: Vec<Container> = self.containers.read().values().cloned().collect();
+        let mut ret: Vec<QAsyncRwLock> = Vec::new();
+        for c in &containers {
+            for barrier in c.mounts.SyncBarriers() {
+                if !ret.iter().any(|b| Arc::ptr_eq(&b.locked, &barrier.locked)) {
+                    ret.push(barrier);
+                }
+            }
+        }
+
+        return ret;
+    }
+
+    // FreezeFilesystems waits for the writes in flight to all the mounts, syncs
+    // the host filesystems and keeps the mount barriers so that new writes block
+    // until ThawFilesystems. It returns the count of the frozen mounts.
+    pub fn FreezeFilesystems(&self, task: &Task) -> Result<usize> {
+        if self.fsFreeze.lock().is_some() {
+            return Err(Error::SysError(SysErr::EBUSY));
+        }
+
+        let mut guards = Vec::new();
+        for barrier in self.SyncBarriers() {
+            guards.push(barrier.Write(task));
+        }
+
+        HostSpace::SysSync();
+
+        let count = guards.len();
+        *self.fsFreeze.lock() = Some(guards);
+        return Ok(count);
+    }
+
+    // ThawFilesystems releases the mounts frozen by FreezeFilesystems and
+    // returns their count.
+    pub fn ThawFilesystems(&self) -> Result<usize> {
+        match self.fsFreeze.lock().take() {
+            None => return Err(Error::SysError(SysErr::EINVAL)),
+            Some(guards) => return Ok(guards.len()),
+        }
+    }
+
     pub fn SignalAll(&self, info: &SignalInfo) -> Result<()> {
         self.extMu.lock();
         let tasks = self.tasks.read();
@@ -518,7 +717,7 @@ impl<'a> Context for CreateProcessContext<'a> {
 
         let containerID = &self.args.ContainerID;
 
-        let root = self.k.mounts.read().get(containerID).unwrap().root.clone();
+        let root = self.k.Container(containerID).unwrap().Root();
 
         return root;
     }
@@ -604,4 +803,9 @@ pub struct CreateProcessArgs {
     pub Stdiofds: [i32; 3],
     pub Terminal: bool,
     pub ExecId: Option<String>,
+
+    // Parent is the parent of the process, None for a process of the
+    // container. Tid is its pid if it is free, 0 to allocate one.
+    pub Parent: Option<Thread>,
+    pub Tid: ThreadID,
 }
@@ -0,0 +1,323 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::hint::spin_loop;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::Ordering;
+
+use super::super::super::common::*;
+use super::super::super::linux_def::*;
+use super::super::super::mutex::*;
+use super::super::task::*;
+use super::super::Timestamp;
+use super::waiter::*;
+
+// the max message bytes kept in the kernel log ring, the oldest messages are
+// dropped when it is exceeded
+pub const KMSG_BUF_SIZE: usize = 128 * 1024;
+
+// the max bytes of one message, a longer /dev/kmsg write fails with EINVAL
+// and a longer kernel message is truncated
+pub const KMSG_LINE_MAX: usize = 1024;
+
+// the try count of the ring lock before a kernel message is dropped
+const KMSG_LOCK_SPIN: usize = 10000;
+
+// syslog levels, the same as the KERN_* levels of linux
+pub const LOG_EMERG: i32 = 0;
+pub const LOG_ALERT: i32 = 1;
+pub const LOG_CRIT: i32 = 2;
+pub const LOG_ERR: i32 = 3;
+pub const LOG_WARNING: i32 = 4;
+pub const LOG_NOTICE: i32 = 5;
+pub const LOG_INFO: i32 = 6;
+pub const LOG_DEBUG: i32 = 7;
+
+pub static KMSG: Kmsg = Kmsg::New();
+
+// DMESG_RESTRICT is kernel.dmesg_restrict, when set reading the kernel log
+// requires CAP_SYSLOG
+pub static DMESG_RESTRICT: AtomicBool = AtomicBool::new(false);
+
+// CheckSyslogPermission checks the permission of a syslog(2) action or of
+// reading /dev/kmsg as Linux check_syslog_permissions. Without
+// dmesg_restrict, reading all the messages and the buffer size are allowed
+// for everyone, which is marked by unrestricted.
+pub fn CheckSyslogPermission(task: &Task, unrestricted: bool) -> Result<()> {
+    if unrestricted && !DMESG_RESTRICT.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+
+    let creds = task.Creds();
+    // CAP_SYS_ADMIN is still accepted for compatibility as Linux does
+    if creds.HasCapability(Capability::CAP_SYSLOG) || creds.HasCapability(Capability::CAP_SYS_ADMIN)
+    {
+        return Ok(());
+    }
+
+    return Err(Error::SysError(SysErr::EPERM));
+}
+
+#[derive(Debug, Clone)]
+pub struct KmsgEntry {
+    // Seq is the sequence number of the message since the sandbox boot
+    pub Seq: u64,
+    pub Level: i32,
+    // Timestamp is in microseconds since the first kernel message
+    pub Timestamp: i64,
+    pub Msg: String,
+}
+
+impl KmsgEntry {
+    // KmsgFormat returns the record as read from /dev/kmsg,
+    // i.e. "level,seq,timestamp,flags;message\n"
+    pub fn KmsgFormat(&self) -> String {
+        return format!(
+            "{},{},{},-;{}\n",
+            self.Level, self.Seq, self.Timestamp, self.Msg
+        );
+    }
+
+    // SyslogFormat returns the record as read by syslog(2),
+    // i.e. "<level>[seconds.microseconds] message\n"
+    pub fn SyslogFormat(&self) -> String {
+        return format!(
+            "<{}>[{:5}.{:06}] {}\n",
+            self.Level,
+            self.Timestamp / 1000_000,
+            self.Timestamp % 1000_000,
+            self.Msg
+        );
+    }
+}
+
+pub struct KmsgRing {
+    pub entries: Vec<KmsgEntry>,
+    pub bytes: usize,
+    pub nextSeq: u64,
+    pub baseTs: i64,
+    // clearSeq is the first sequence number returned by syslog(2) after
+    // SYSLOG_ACTION_CLEAR
+    pub clearSeq: u64,
+    // readSeq is the next sequence number returned by SYSLOG_ACTION_READ
+    pub readSeq: u64,
+}
+
+impl KmsgRing {
+    pub fn FirstSeq(&self) -> u64 {
+        match self.entries.first() {
+            None => self.nextSeq,
+            Some(e) => e.Seq,
+        }
+    }
+
+    fn Push(&mut self, level: i32, now: i64, msg: &str) {
+        if self.nextSeq == 0 {
+            self.baseTs = now;
+        }
+
+        let entry = KmsgEntry {
+            Seq: self.nextSeq,
+            Level: level,
+            Timestamp: now - self.baseTs,
+            Msg: msg.to_string(),
+        };
+
+        self.nextSeq += 1;
+        self.bytes += entry.Msg.len();
+        self.entries.push(entry);
+
+        if self.bytes > KMSG_BUF_SIZE {
+            // drop a batch of the oldest messages to amortize the move
+            let mut count = 0;
+            let mut bytes = self.bytes;
+            while bytes > KMSG_BUF_SIZE * 3 / 4 && count < self.entries.len() {
+                bytes -= self.entries[count].Msg.len();
+                count += 1;
+            }
+
+            self.entries.drain(0..count);
+            self.bytes = bytes;
+        }
+    }
+
+    fn Index(&self, seq: u64) -> usize {
+        let first = self.FirstSeq();
+        if seq < first {
+            return 0;
+        }
+
+        return (seq - first) as usize;
+    }
+}
+
+pub struct Kmsg {
+    pub ring: QMutex<KmsgRing>,
+    // queue is notified when a message is added. It is created by the first
+    // /dev/kmsg waiter as the Queue can't be built in the static.
+    pub queue: QMutex<Option<Queue>>,
+}
+
+impl Kmsg {
+    pub const fn New() -> Self {
+        return Self {
+            ring: QMutex::new(KmsgRing {
+                entries: Vec::new(),
+                bytes: 0,
+                nextSeq: 0,
+                baseTs: 0,
+                clearSeq: 0,
+                readSeq: 0,
+            }),
+            queue: QMutex::new(None),
+        };
+    }
+
+    // Queue returns the wait queue of the /dev/kmsg readers.
+    pub fn Queue(&self) -> Queue {
+        let mut queue = self.queue.lock();
+        if queue.is_none() {
+            *queue = Some(Queue::default());
+        }
+
+        return queue.as_ref().unwrap().clone();
+    }
+
+    // Log adds one message to the ring. It must not log through error!/info!
+    // as it is called by them. The ring lock is only spun on for a while, the
+    // message is dropped when it is still held, e.g. by an error! in a ring
+    // operation or by an interrupted lock holder on the same vcpu.
+    pub fn Log(&self, level: i32, msg: &str) {
+        let mut msg = msg.trim_end_matches('\n');
+        if msg.len() > KMSG_LINE_MAX {
+            let mut end = KMSG_LINE_MAX;
+            while !msg.is_char_boundary(end) {
+                end -= 1;
+            }
+            msg = &msg[..end];
+        }
+
+        let now = Timestamp();
+        {
+            let mut ring = None;
+            for _ in 0..KMSG_LOCK_SPIN {
+                ring = self.ring.try_lock();
+                if ring.is_some() {
+                    break;
+                }
+                spin_loop();
+            }
+
+            match ring {
+                None => return,
+                Some(mut ring) => ring.Push(level, now, msg),
+            }
+        }
+
+        let queue = match self.queue.try_lock() {
+            None => return,
+            Some(q) => q.clone(),
+        };
+
+        if let Some(queue) = queue {
+            queue.Notify(READABLE_EVENT);
+        }
+    }
+
+    // FirstSeq returns the sequence number of the oldest message in the ring
+    pub fn FirstSeq(&self) -> u64 {
+        return self.ring.lock().FirstSeq();
+    }
+
+    pub fn NextSeq(&self) -> u64 {
+        return self.ring.lock().nextSeq;
+    }
+
+    // Get returns the first message whose sequence number is not less than seq.
+    pub fn Get(&self, seq: u64) -> Option<KmsgEntry> {
+        let ring = self.ring.lock();
+        let idx = ring.Index(seq);
+        return ring.entries.get(idx).cloned();
+    }
+
+    // Syslog returns the formatted messages from the clear point for syslog(2).
+    // The newest messages are kept if they don't fit in size bytes.
+    pub fn Syslog(&self, size: usize, clear: bool) -> String {
+        let mut ring = self.ring.lock();
+        let start = ring.Index(ring.clearSeq);
+        let mut lines = Vec::new();
+        let mut total = 0;
+        for entry in ring.entries[start..].iter().rev() {
+            let line = entry.SyslogFormat();
+            if total + line.len() > size {
+                break;
+            }
+            total += line.len();
+            lines.push(line);
+        }
+
+        if clear {
+            ring.clearSeq = ring.nextSeq;
+        }
+
+        let mut output = String::with_capacity(total);
+        for line in lines.iter().rev() {
+            output += line;
+        }
+
+        return output;
+    }
+
+    // Read returns the formatted unread messages for SYSLOG_ACTION_READ and
+    // advances the read position.
+    pub fn Read(&self, size: usize) -> String {
+        let mut ring = self.ring.lock();
+        let mut output = String::new();
+        let mut idx = ring.Index(ring.readSeq);
+        while idx < ring.entries.len() {
+            let line = ring.entries[idx].SyslogFormat();
+            if output.len() + line.len() > size {
+                break;
+            }
+            output += &line;
+            idx += 1;
+        }
+
+        ring.readSeq = match ring.entries.get(idx) {
+            None => ring.nextSeq,
+            Some(e) => e.Seq,
+        };
+
+        return output;
+    }
+
+    pub fn Clear(&self) {
+        let mut ring = self.ring.lock();
+        ring.clearSeq = ring.nextSeq;
+    }
+
+    // UnreadBytes returns the byte count of the messages which are not read
+    // by SYSLOG_ACTION_READ yet.
+    pub fn UnreadBytes(&self) -> usize {
+        let ring = self.ring.lock();
+        let start = ring.Index(ring.readSeq);
+        return ring.entries[start..]
+            .iter()
+            .map(|e| e.SyslogFormat().len())
+            .sum();
+    }
+}
@@ -21,20 +21,29 @@ pub mod waiter;
 //pub mod ktime;
 pub mod semaphore;
 pub mod uts_namespace;
-//pub mod shm;
+pub mod shm;
 pub mod abstract_socket_namespace;
 pub mod aio;
+pub mod audit;
+pub mod checkpoint;
+pub mod container;
 pub mod async_process;
 pub mod async_wait;
 pub mod cpuset;
 pub mod epoll;
+pub mod entropy;
 pub mod eventfd;
 pub mod fasync;
 pub mod fs_context;
 pub mod futex;
 pub mod ipc_namespace;
 pub mod kernel;
+pub mod kmsg;
+pub mod mqueue;
+pub mod msgqueue;
 pub mod pipe;
 pub mod platform;
 pub mod signal_handler;
+pub mod pidfd;
 pub mod signalfd;
+pub mod watchdog;
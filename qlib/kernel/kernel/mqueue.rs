@@ -0,0 +1,581 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::qlib::mutex::*;
+use alloc::collections::btree_map::BTreeMap;
+use alloc::collections::vec_deque::VecDeque;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::ops::Deref;
+
+use super::super::super::auth::userns::*;
+use super::super::super::auth::*;
+use super::super::super::common::*;
+use super::super::super::linux::mqueue::*;
+use super::super::super::linux_def::*;
+use super::super::kernel::waiter::*;
+use super::super::task::*;
+use super::super::threadmgr::thread_group::*;
+use super::super::SignalDef::*;
+
+use super::super::fs::anon::*;
+use super::super::fs::attr::*;
+use super::super::fs::dentry::*;
+use super::super::fs::dirent::*;
+use super::super::fs::file::*;
+use super::super::fs::flags::*;
+use super::super::fs::fsutil::file::*;
+use super::super::fs::host::hostinodeop::*;
+
+// Registry holds the POSIX message queues of an ipc namespace by their
+// names, it is the /dev/mqueue of the namespace
+#[derive(Default)]
+pub struct RegistryInternal {
+    pub userNS: UserNameSpace,
+    pub queues: BTreeMap<String, MQueue>,
+}
+
+#[derive(Clone, Default)]
+pub struct Registry(Arc<QMutex<RegistryInternal>>);
+
+impl Deref for Registry {
+    type Target = Arc<QMutex<RegistryInternal>>;
+
+    fn deref(&self) -> &Arc<QMutex<RegistryInternal>> {
+        &self.0
+    }
+}
+
+impl Registry {
+    pub fn New(userNS: &UserNameSpace) -> Self {
+        let internal = RegistryInternal {
+            userNS: userNS.clone(),
+            queues: BTreeMap::new(),
+        };
+
+        return Self(Arc::new(QMutex::new(internal)));
+    }
+
+    // FindOrCreate implements the queue lookup of mq_open(2), the access of
+    // the flags is checked against the queue
+    pub fn FindOrCreate(
+        &self,
+        task: &Task,
+        name: &str,
+        flags: &FileFlags,
+        create: bool,
+        exclusive: bool,
+        mode: FileMode,
+        attr: Option<MqAttr>,
+    ) -> Result<MQueue> {
+        let mut me = self.lock();
+        let creds = task.Creds();
+
+        match me.queues.get(name) {
+            None => (),
+            Some(q) => {
+                if create && exclusive {
+                    return Err(Error::SysError(SysErr::EEXIST));
+                }
+
+                let req = PermMask {
+                    read: flags.Read,
+                    write: flags.Write,
+                    ..Default::default()
+                };
+
+                if !q.lock().checkPermission(&creds, &req) {
+                    return Err(Error::SysError(SysErr::EACCES));
+                }
+
+                return Ok(q.clone());
+            }
+        }
+
+        if !create {
+            return Err(Error::SysError(SysErr::ENOENT));
+        }
+
+        let resource = creds.HasCapabilityIn(Capability::CAP_SYS_RESOURCE, &me.userNS);
+        if me.queues.len() as u64 >= DFLT_QUEUESMAX && !resource {
+            return Err(Error::SysError(SysErr::ENOSPC));
+        }
+
+        let (maxMessageCount, maxMessageSize) = match attr {
+            None => (DFLT_MSG, DFLT_MSGSIZE),
+            Some(attr) => {
+                if attr.MqMaxmsg <= 0 || attr.MqMsgsize <= 0 {
+                    return Err(Error::SysError(SysErr::EINVAL));
+                }
+
+                let (msgMax, msgSizeMax) = if resource {
+                    (HARD_MSGMAX, HARD_MSGSIZEMAX)
+                } else {
+                    (DFLT_MSGMAX, DFLT_MSGSIZEMAX)
+                };
+
+                if attr.MqMaxmsg > msgMax || attr.MqMsgsize > msgSizeMax {
+                    return Err(Error::SysError(SysErr::EINVAL));
+                }
+
+                (attr.MqMaxmsg, attr.MqMsgsize)
+            }
+        };
+
+        let internal = MQueueInternal {
+            userNS: me.userNS.clone(),
+            owner: task.FileOwner(),
+            perms: FilePermissions::FromMode(mode),
+            messages: VecDeque::new(),
+            byteCount: 0,
+            maxMessageCount: maxMessageCount,
+            maxMessageSize: maxMessageSize,
+            subscriber: None,
+            receivers: 0,
+            queue: Queue::default(),
+        };
+
+        let q = MQueue(Arc::new(QMutex::new(internal)));
+        me.queues.insert(name.to_string(), q.clone());
+        return Ok(q);
+    }
+
+    // Remove implements mq_unlink(2), the opened queue stays usable until
+    // its files are closed
+    pub fn Remove(&self, task: &Task, name: &str) -> Result<()> {
+        let mut me = self.lock();
+
+        let q = match me.queues.get(name) {
+            None => return Err(Error::SysError(SysErr::ENOENT)),
+            Some(q) => q.clone(),
+        };
+
+        // the queues are in a sticky directory writable to all
+        let creds = task.Creds();
+        let owner = q.lock().owner.UID;
+        if owner != creds.lock().EffectiveKUID
+            && !creds.HasCapabilityIn(Capability::CAP_FOWNER, &me.userNS)
+        {
+            return Err(Error::SysError(SysErr::EACCES));
+        }
+
+        me.queues.remove(name);
+        return Ok(());
+    }
+}
+
+pub struct MQMessage {
+    pub Priority: u32,
+    pub Text: Vec<u8>,
+}
+
+// Subscriber is the process registered by mq_notify(2)
+pub struct Subscriber {
+    pub tg: ThreadGroupWeak,
+    pub sev: Sigevent,
+}
+
+pub struct MQueueInternal {
+    pub userNS: UserNameSpace,
+    pub owner: FileOwner,
+    pub perms: FilePermissions,
+
+    // messages is ordered by the priority, the highest first, and the
+    // sending order in a priority
+    pub messages: VecDeque<MQMessage>,
+    pub byteCount: u64,
+    pub maxMessageCount: i64,
+    pub maxMessageSize: i64,
+
+    pub subscriber: Option<Subscriber>,
+
+    // receivers is the count of the tasks blocked in mq_timedreceive, the
+    // subscriber isn't notified when there is one
+    pub receivers: u32,
+
+    // queue is notified with READABLE_EVENT when a message is sent and
+    // WRITEABLE_EVENT when a message is received
+    pub queue: Queue,
+}
+
+impl MQueueInternal {
+    pub fn checkPermission(&self, creds: &Credentials, req: &PermMask) -> bool {
+        let mut p = self.perms.Other;
+        if self.owner.UID == creds.lock().EffectiveKUID {
+            p = self.perms.User;
+        } else if creds.InGroup(self.owner.GID) {
+            p = self.perms.Group;
+        }
+
+        if p.SupersetOf(req) {
+            return true;
+        }
+
+        return creds.HasCapabilityIn(Capability::CAP_DAC_OVERRIDE, &self.userNS);
+    }
+
+    // takeSubscriber returns the live subscriber and unregisters it
+    fn takeSubscriber(&mut self) -> Option<(ThreadGroup, Sigevent)> {
+        let s = self.subscriber.take()?;
+        let tg = s.tg.Upgrade()?;
+        return Some((tg, s.sev));
+    }
+
+    fn hasSubscriber(&self) -> bool {
+        return match &self.subscriber {
+            None => false,
+            Some(s) => s.tg.Upgrade().is_some(),
+        };
+    }
+}
+
+#[derive(Clone)]
+pub struct MQueue(Arc<QMutex<MQueueInternal>>);
+
+impl Deref for MQueue {
+    type Target = Arc<QMutex<MQueueInternal>>;
+
+    fn deref(&self) -> &Arc<QMutex<MQueueInternal>> {
+        &self.0
+    }
+}
+
+impl MQueue {
+    // Send queues the message. When the queue is full and wait is set, the
+    // task is registered to the queue and true is returned, the caller
+    // blocks and then calls AbortSend and retries.
+    pub fn Send(&self, task: &Task, text: Vec<u8>, priority: u32, wait: bool) -> Result<bool> {
+        let notify;
+        {
+            let mut me = self.lock();
+
+            if text.len() as i64 > me.maxMessageSize {
+                return Err(Error::SysError(SysErr::EMSGSIZE));
+            }
+
+            if me.messages.len() as i64 >= me.maxMessageCount {
+                if !wait {
+                    return Err(Error::SysError(SysErr::EAGAIN));
+                }
+
+                me.queue
+                    .EventRegister(task, &task.blocker.generalEntry, WRITEABLE_EVENT);
+                return Ok(true);
+            }
+
+            // the subscriber is notified when a message arrives at an empty
+            // queue and no one is waiting to receive it
+            notify = if me.messages.len() == 0 && me.receivers == 0 {
+                me.takeSubscriber()
+            } else {
+                None
+            };
+
+            let mut idx = me.messages.len();
+            for i in 0..me.messages.len() {
+                if me.messages[i].Priority < priority {
+                    idx = i;
+                    break;
+                }
+            }
+
+            me.byteCount += text.len() as u64;
+            me.messages.insert(
+                idx,
+                MQMessage {
+                    Priority: priority,
+                    Text: text,
+                },
+            );
+            me.queue.Notify(READABLE_EVENT);
+        }
+
+        if let Some((tg, sev)) = notify {
+            if sev.Notify == SIGEV_SIGNAL {
+                let mut info = SignalInfo {
+                    Signo: sev.Signo,
+                    Code: SignalInfo::SIGNAL_INFO_MESGQ,
+                    ..Default::default()
+                };
+
+                let sigRt = info.SigRt();
+                sigRt.pid = tg
+                    .PIDNamespace()
+                    .IDOfThreadGroup(&task.Thread().ThreadGroup());
+                let tuserns = tg.PIDNamespace().UserNamespace();
+                sigRt.uid = task.Creds().lock().RealKUID.In(&tuserns).OrOverflow().0;
+                sigRt.sigval = sev.Value;
+                tg.SendSignal(&info).ok();
+            }
+        }
+
+        return Ok(false);
+    }
+
+    // AbortSend unregisters the task registered by Send
+    pub fn AbortSend(&self, task: &Task) {
+        let me = self.lock();
+        me.queue.EventUnregister(task, &task.blocker.generalEntry);
+    }
+
+    // Receive takes the first message of the highest priority. When the
+    // queue is empty and wait is set, the task is registered to the queue and
+    // None is returned, the caller blocks and then calls AbortReceive and
+    // retries.
+    pub fn Receive(&self, task: &Task, maxSize: u64, wait: bool) -> Result<Option<MQMessage>> {
+        let mut me = self.lock();
+
+        if (maxSize as i64) < me.maxMessageSize {
+            return Err(Error::SysError(SysErr::EMSGSIZE));
+        }
+
+        match me.messages.pop_front() {
+            None => {
+                if !wait {
+                    return Err(Error::SysError(SysErr::EAGAIN));
+                }
+
+                me.receivers += 1;
+                me.queue
+                    .EventRegister(task, &task.blocker.generalEntry, READABLE_EVENT);
+                return Ok(None);
+            }
+            Some(msg) => {
+                me.byteCount -= msg.Text.len() as u64;
+                me.queue.Notify(WRITEABLE_EVENT);
+                return Ok(Some(msg));
+            }
+        }
+    }
+
+    // AbortReceive unregisters the task registered by Receive
+    pub fn AbortReceive(&self, task: &Task) {
+        let mut me = self.lock();
+        me.receivers -= 1;
+        me.queue.EventUnregister(task, &task.blocker.generalEntry);
+    }
+
+    // Notify implements mq_notify(2), sev None unregisters the subscriber
+    // of the caller
+    pub fn Notify(&self, task: &Task, sev: Option<Sigevent>) -> Result<()> {
+        let mut me = self.lock();
+        let tg = task.Thread().ThreadGroup();
+
+        match sev {
+            None => {
+                let own = match &me.subscriber {
+                    None => false,
+                    Some(s) => s.tg.Upgrade() == Some(tg),
+                };
+
+                if own {
+                    me.subscriber = None;
+                }
+            }
+            Some(sev) => {
+                if me.hasSubscriber() {
+                    return Err(Error::SysError(SysErr::EBUSY));
+                }
+
+                me.subscriber = Some(Subscriber {
+                    tg: tg.Downgrade(),
+                    sev: sev,
+                });
+            }
+        }
+
+        return Ok(());
+    }
+
+    pub fn Attr(&self) -> MqAttr {
+        let me = self.lock();
+        return MqAttr {
+            MqMaxmsg: me.maxMessageCount,
+            MqMsgsize: me.maxMessageSize,
+            MqCurmsgs: me.messages.len() as i64,
+            ..Default::default()
+        };
+    }
+
+    // Status returns the content read from the queue file
+    pub fn Status(&self) -> String {
+        let me = self.lock();
+        let (notify, signo, pid) = match &me.subscriber {
+            None => (0, 0, 0),
+            Some(s) => match s.tg.Upgrade() {
+                None => (0, 0, 0),
+                Some(tg) => {
+                    let signo = if s.sev.Notify == SIGEV_SIGNAL {
+                        s.sev.Signo
+                    } else {
+                        0
+                    };
+                    (s.sev.Notify, signo, tg.ID())
+                }
+            },
+        };
+
+        return format!(
+            "QSIZE:{:<10} NOTIFY:{:<5} SIGNO:{:<5} NOTIFY_PID:{:<6}\n",
+            me.byteCount, notify, signo, pid
+        );
+    }
+}
+
+pub fn NewMQueueFile(task: &Task, name: &str, q: &MQueue, flags: &FileFlags) -> File {
+    let inode = NewAnonInode(task);
+    let dirent = Dirent::New(&inode, name);
+
+    return File::New(&dirent, flags, MQueueOperations { queue: q.clone() });
+}
+
+// MQueueOperations is the file of an opened POSIX message queue
+#[derive(Clone)]
+pub struct MQueueOperations {
+    pub queue: MQueue,
+}
+
+impl Waitable for MQueueOperations {
+    fn Readiness(&self, _task: &Task, mask: EventMask) -> EventMask {
+        let q = self.queue.lock();
+
+        let mut ready = 0;
+        if q.messages.len() > 0 {
+            ready |= READABLE_EVENT;
+        }
+
+        if (q.messages.len() as i64) < q.maxMessageCount {
+            ready |= WRITEABLE_EVENT;
+        }
+
+        return mask & ready;
+    }
+
+    fn EventRegister(&self, task: &Task, e: &WaitEntry, mask: EventMask) {
+        let q = self.queue.lock().queue.clone();
+        q.EventRegister(task, e, mask)
+    }
+
+    fn EventUnregister(&self, task: &Task, e: &WaitEntry) {
+        let q = self.queue.lock().queue.clone();
+        q.EventUnregister(task, e)
+    }
+}
+
+impl SpliceOperations for MQueueOperations {}
+
+impl FileOperations for MQueueOperations {
+    fn as_any(&self) -> &Any {
+        return self;
+    }
+
+    fn FopsType(&self) -> FileOpsType {
+        return FileOpsType::MQueueOperations;
+    }
+
+    fn Seekable(&self) -> bool {
+        return true;
+    }
+
+    fn Seek(&self, task: &Task, f: &File, whence: i32, current: i64, offset: i64) -> Result<i64> {
+        return SeekWithDirCursor(task, f, whence, current, offset, None);
+    }
+
+    fn ReadDir(
+        &self,
+        _task: &Task,
+        _f: &File,
+        _offset: i64,
+        _serializer: &mut DentrySerializer,
+    ) -> Result<i64> {
+        return Err(Error::SysError(SysErr::ENOTDIR));
+    }
+
+    fn ReadAt(
+        &self,
+        task: &Task,
+        _f: &File,
+        dsts: &mut [IoVec],
+        offset: i64,
+        _blocking: bool,
+    ) -> Result<i64> {
+        let status = self.queue.Status();
+        let buf = status.as_bytes();
+        if offset < 0 || offset as usize >= buf.len() {
+            return Ok(0);
+        }
+
+        let n = task.CopyDataOutToIovs(&buf[offset as usize..], dsts, false)?;
+        return Ok(n as i64);
+    }
+
+    fn WriteAt(
+        &self,
+        _task: &Task,
+        _f: &File,
+        _srcs: &[IoVec],
+        _offset: i64,
+        _blocking: bool,
+    ) -> Result<i64> {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    fn Append(&self, task: &Task, f: &File, srcs: &[IoVec]) -> Result<(i64, i64)> {
+        let n = self.WriteAt(task, f, srcs, 0, false)?;
+        return Ok((n, 0));
+    }
+
+    fn Fsync(
+        &self,
+        _task: &Task,
+        _f: &File,
+        _start: i64,
+        _end: i64,
+        _syncType: SyncType,
+    ) -> Result<()> {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    fn Flush(&self, _task: &Task, _f: &File) -> Result<()> {
+        return Ok(());
+    }
+
+    fn UnstableAttr(&self, task: &Task, f: &File) -> Result<UnstableAttr> {
+        let inode = f.Dirent.Inode();
+        return inode.UnstableAttr(task);
+    }
+
+    fn Ioctl(&self, _task: &Task, _f: &File, _fd: i32, _request: u64, _val: u64) -> Result<()> {
+        return Err(Error::SysError(SysErr::ENOTTY));
+    }
+
+    fn IterateDir(
+        &self,
+        _task: &Task,
+        _d: &Dirent,
+        _dirCtx: &mut DirCtx,
+        _offset: i32,
+    ) -> (i32, Result<i64>) {
+        return (0, Err(Error::SysError(SysErr::ENOTDIR)));
+    }
+
+    fn Mappable(&self) -> Result<HostInodeOp> {
+        return Err(Error::SysError(SysErr::ENODEV));
+    }
+}
+
+impl SockOperations for MQueueOperations {}
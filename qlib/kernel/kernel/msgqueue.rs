@@ -0,0 +1,576 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::qlib::mutex::*;
+use alloc::collections::btree_map::BTreeMap;
+use alloc::collections::vec_deque::VecDeque;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::ops::Deref;
+
+use super::super::super::auth::id::*;
+use super::super::super::auth::userns::*;
+use super::super::super::auth::*;
+use super::super::super::common::*;
+use super::super::super::linux::ipc::*;
+use super::super::super::linux::msgqueue::*;
+use super::super::super::linux_def::*;
+use super::super::task::*;
+use super::time::*;
+use super::waiter::Queue;
+use super::waiter::Waitable;
+
+type Key = i32;
+type ID = i32;
+
+// Message is a message of a SysV message queue
+#[derive(Clone, Debug, Default)]
+pub struct Message {
+    pub Type: i64,
+    pub Text: Vec<u8>,
+}
+
+#[derive(Default)]
+pub struct RegistryInternal {
+    pub userNS: UserNameSpace,
+    pub queues: BTreeMap<ID, MsgQueue>,
+    pub keysToQueues: BTreeMap<Key, MsgQueue>,
+    pub lastIDUsed: ID,
+}
+
+impl RegistryInternal {
+    fn newQueue(
+        &mut self,
+        task: &Task,
+        key: Key,
+        creator: &FileOwner,
+        perms: &FilePermissions,
+    ) -> Result<MsgQueue> {
+        let q = MsgQueue(Arc::new(QMutex::new(MsgQueueInternal {
+            userNS: self.userNS.clone(),
+            id: 0,
+            key: key,
+            creator: *creator,
+            owner: *creator,
+            perms: *perms,
+            dead: false,
+            queue: Queue::default(),
+            messages: VecDeque::new(),
+            byteCount: 0,
+            maxBytes: MSGMNB,
+            sendTime: Time(0),
+            receiveTime: Time(0),
+            changeTime: task.Now(),
+            sendPID: 0,
+            receivePID: 0,
+        })));
+
+        let mut id = self.lastIDUsed + 1;
+        while id != self.lastIDUsed {
+            if id < 0 {
+                id = 0;
+            }
+
+            if self.queues.contains_key(&id) {
+                id += 1;
+                continue;
+            }
+
+            self.lastIDUsed = id;
+            q.lock().id = id;
+            self.queues.insert(id, q.clone());
+            if key != IPC_PRIVATE {
+                self.keysToQueues.insert(key, q.clone());
+            }
+            return Ok(q);
+        }
+
+        info!("Message queue ids exhuasted, they may be leaking");
+        return Err(Error::SysError(SysErr::ENOSPC));
+    }
+}
+
+impl Drop for RegistryInternal {
+    // the ipc namespace is gone, destroy its queues so that the blocked
+    // senders and receivers are woken up
+    fn drop(&mut self) {
+        for (_, q) in &self.queues {
+            q.lock().destroy();
+        }
+
+        self.queues.clear();
+        self.keysToQueues.clear();
+    }
+}
+
+// Registry holds the SysV message queues of an ipc namespace
+#[derive(Clone, Default)]
+pub struct Registry(Arc<QMutex<RegistryInternal>>);
+
+impl Deref for Registry {
+    type Target = Arc<QMutex<RegistryInternal>>;
+
+    fn deref(&self) -> &Arc<QMutex<RegistryInternal>> {
+        &self.0
+    }
+}
+
+impl Registry {
+    pub fn New(userNS: &UserNameSpace) -> Self {
+        let internal = RegistryInternal {
+            userNS: userNS.clone(),
+            queues: BTreeMap::new(),
+            keysToQueues: BTreeMap::new(),
+            lastIDUsed: 0,
+        };
+
+        return Self(Arc::new(QMutex::new(internal)));
+    }
+
+    pub fn FindByID(&self, id: ID) -> Option<MsgQueue> {
+        let me = self.lock();
+        return match me.queues.get(&id) {
+            None => None,
+            Some(q) => Some(q.clone()),
+        };
+    }
+
+    pub fn FindOrCreate(
+        &self,
+        task: &Task,
+        key: Key,
+        mode: &FileMode,
+        private: bool,
+        create: bool,
+        exclusive: bool,
+    ) -> Result<MsgQueue> {
+        let mut me = self.lock();
+
+        if !private {
+            match me.keysToQueues.get(&key) {
+                None => (),
+                Some(q) => {
+                    {
+                        let q = q.lock();
+                        if !q.checkPermission(&task.Creds(), &PermMask::FromMode(*mode)) {
+                            return Err(Error::SysError(SysErr::EACCES));
+                        }
+
+                        if create && exclusive {
+                            return Err(Error::SysError(SysErr::EEXIST));
+                        }
+                    }
+
+                    return Ok(q.clone());
+                }
+            }
+
+            if !create {
+                return Err(Error::SysError(SysErr::ENOENT));
+            }
+        }
+
+        if me.queues.len() >= MSGMNI as usize {
+            return Err(Error::SysError(SysErr::ENOSPC));
+        }
+
+        let creator = task.FileOwner();
+        let perms = FilePermissions::FromMode(*mode);
+        return me.newQueue(task, key, &creator, &perms);
+    }
+
+    // Remove implements msgctl IPC_RMID, the blocked senders and receivers
+    // of the queue get EIDRM
+    pub fn Remove(&self, id: ID, creds: &Credentials) -> Result<()> {
+        let mut me = self.lock();
+
+        let q = match me.queues.get(&id) {
+            None => return Err(Error::SysError(SysErr::EINVAL)),
+            Some(q) => q.clone(),
+        };
+
+        let mut q = q.lock();
+        if !q.checkOwnership(creds) {
+            return Err(Error::SysError(SysErr::EPERM));
+        }
+
+        me.queues.remove(&id);
+        if q.key != IPC_PRIVATE {
+            me.keysToQueues.remove(&q.key);
+        }
+
+        q.destroy();
+        return Ok(());
+    }
+
+    pub fn IPCInfo(&self) -> MsgInfo {
+        return MsgInfo {
+            MsgPool: MSGPOOL as i32,
+            MsgMap: MSGMAP as i32,
+            MsgMax: MSGMAX as i32,
+            MsgMnb: MSGMNB as i32,
+            MsgMni: MSGMNI as i32,
+            MsgSsz: MSGSSZ as i32,
+            MsgTql: MSGTQL as i32,
+            MsgSeg: MSGSEG as u16,
+        };
+    }
+
+    // MsgInfo returns the MSG_INFO of msgctl, which reports the current usage
+    // in the fields of the limits
+    pub fn MsgInfo(&self) -> MsgInfo {
+        let me = self.lock();
+
+        let mut messages = 0;
+        let mut bytes = 0;
+        for (_, q) in &me.queues {
+            let q = q.lock();
+            messages += q.messages.len();
+            bytes += q.byteCount;
+        }
+
+        let mut info = self.IPCInfo();
+        info.MsgPool = me.queues.len() as i32;
+        info.MsgMap = messages as i32;
+        info.MsgTql = bytes as i32;
+        return info;
+    }
+
+    // HighestIndex returns the highest id in use, 0 if there is none
+    pub fn HighestIndex(&self) -> i32 {
+        let me = self.lock();
+        return match me.queues.keys().next_back() {
+            None => 0,
+            Some(id) => *id,
+        };
+    }
+}
+
+pub struct MsgQueueInternal {
+    // userNS is the user namespace of the ipc namespace owning the queue
+    pub userNS: UserNameSpace,
+    pub id: ID,
+    pub key: Key,
+    pub creator: FileOwner,
+    pub owner: FileOwner,
+    pub perms: FilePermissions,
+
+    // dead is set when the queue is removed
+    pub dead: bool,
+
+    // queue is notified with EVENT_IN when a message is sent and EVENT_OUT
+    // when a message is received, both when the queue is removed
+    pub queue: Queue,
+
+    pub messages: VecDeque<Message>,
+
+    // byteCount is the size of the texts of the messages in the queue
+    pub byteCount: u64,
+
+    // maxBytes is the max byteCount of the queue, and the max count of the
+    // messages as well
+    pub maxBytes: u64,
+
+    pub sendTime: Time,
+    pub receiveTime: Time,
+    pub changeTime: Time,
+    pub sendPID: i32,
+    pub receivePID: i32,
+}
+
+impl MsgQueueInternal {
+    pub fn checkOwnership(&self, creds: &Credentials) -> bool {
+        let effectiveKUID = creds.lock().EffectiveKUID;
+
+        if self.owner.UID == effectiveKUID || self.creator.UID == effectiveKUID {
+            return true;
+        }
+
+        return creds.HasCapabilityIn(Capability::CAP_SYS_ADMIN, &self.userNS);
+    }
+
+    pub fn checkPermission(&self, creds: &Credentials, req: &PermMask) -> bool {
+        let mut p = self.perms.Other;
+        if self.owner.UID == creds.lock().EffectiveKUID {
+            p = self.perms.User;
+        } else if creds.InGroup(self.owner.GID) {
+            p = self.perms.Group;
+        }
+
+        if p.SupersetOf(req) {
+            return true;
+        }
+
+        return creds.HasCapabilityIn(Capability::CAP_IPC_OWNER, &self.userNS);
+    }
+
+    fn destroy(&mut self) {
+        self.dead = true;
+        self.messages.clear();
+        self.byteCount = 0;
+        self.queue.Notify(EVENT_IN | EVENT_OUT);
+    }
+
+    // findMessage returns the index of the message msgrcv(2) receives for
+    // the type
+    fn findMessage(&self, typ: i64, except: bool) -> Option<usize> {
+        if typ == 0 {
+            if self.messages.len() == 0 {
+                return None;
+            }
+
+            return Some(0);
+        }
+
+        if typ > 0 {
+            for i in 0..self.messages.len() {
+                if (self.messages[i].Type == typ) != except {
+                    return Some(i);
+                }
+            }
+
+            return None;
+        }
+
+        // the first message of the lowest type not above -typ
+        let max = match typ.checked_neg() {
+            None => i64::MAX,
+            Some(t) => t,
+        };
+
+        let mut idx = None;
+        let mut lowest = max;
+        for i in 0..self.messages.len() {
+            let t = self.messages[i].Type;
+            if t < lowest || (t == lowest && idx.is_none()) {
+                lowest = t;
+                idx = Some(i);
+            }
+        }
+
+        return idx;
+    }
+}
+
+#[derive(Clone)]
+pub struct MsgQueue(Arc<QMutex<MsgQueueInternal>>);
+
+impl Deref for MsgQueue {
+    type Target = Arc<QMutex<MsgQueueInternal>>;
+
+    fn deref(&self) -> &Arc<QMutex<MsgQueueInternal>> {
+        &self.0
+    }
+}
+
+// ReceiveOpts is the msgrcv(2) flags besides IPC_NOWAIT
+pub struct ReceiveOpts {
+    pub Except: bool,
+    pub NoError: bool,
+    pub Copy: bool,
+}
+
+impl MsgQueue {
+    // Send appends the message to the queue. When the queue is full and wait
+    // is set, the task is registered to the queue and true is returned, the
+    // caller blocks and then calls AbortWait and retries.
+    pub fn Send(
+        &self,
+        task: &Task,
+        msg: &Message,
+        creds: &Credentials,
+        pid: i32,
+        wait: bool,
+    ) -> Result<bool> {
+        let mut me = self.lock();
+
+        if me.dead {
+            return Err(Error::SysError(SysErr::EIDRM));
+        }
+
+        if !me.checkPermission(
+            creds,
+            &PermMask {
+                write: true,
+                ..Default::default()
+            },
+        ) {
+            return Err(Error::SysError(SysErr::EACCES));
+        }
+
+        let size = msg.Text.len() as u64;
+        if me.byteCount + size > me.maxBytes || me.messages.len() as u64 + 1 > me.maxBytes {
+            if !wait {
+                return Err(Error::SysError(SysErr::EAGAIN));
+            }
+
+            me.queue
+                .EventRegister(task, &task.blocker.generalEntry, EVENT_OUT);
+            return Ok(true);
+        }
+
+        me.messages.push_back(msg.clone());
+        me.byteCount += size;
+        me.sendTime = task.Now();
+        me.sendPID = pid;
+        me.queue.Notify(EVENT_IN);
+        return Ok(false);
+    }
+
+    // Receive takes the message of the type out of the queue, or copies the
+    // message at the index typ with MSG_COPY. When there is none and wait is
+    // set, the task is registered to the queue and None is returned, the
+    // caller blocks and then calls AbortWait and retries.
+    pub fn Receive(
+        &self,
+        task: &Task,
+        typ: i64,
+        maxSize: u64,
+        opts: &ReceiveOpts,
+        creds: &Credentials,
+        pid: i32,
+        wait: bool,
+    ) -> Result<Option<Message>> {
+        let mut me = self.lock();
+
+        if me.dead {
+            return Err(Error::SysError(SysErr::EIDRM));
+        }
+
+        if !me.checkPermission(
+            creds,
+            &PermMask {
+                read: true,
+                ..Default::default()
+            },
+        ) {
+            return Err(Error::SysError(SysErr::EACCES));
+        }
+
+        let idx = if opts.Copy {
+            if typ >= 0 && (typ as u64) < me.messages.len() as u64 {
+                Some(typ as usize)
+            } else {
+                None
+            }
+        } else {
+            me.findMessage(typ, opts.Except)
+        };
+
+        let idx = match idx {
+            Some(idx) => idx,
+            None => {
+                if !wait {
+                    return Err(Error::SysError(SysErr::ENOMSG));
+                }
+
+                me.queue
+                    .EventRegister(task, &task.blocker.generalEntry, EVENT_IN);
+                return Ok(None);
+            }
+        };
+
+        if me.messages[idx].Text.len() as u64 > maxSize && !opts.NoError {
+            return Err(Error::SysError(SysErr::E2BIG));
+        }
+
+        if opts.Copy {
+            return Ok(Some(me.messages[idx].clone()));
+        }
+
+        let msg = me.messages.remove(idx).unwrap();
+        me.byteCount -= msg.Text.len() as u64;
+        me.receiveTime = task.Now();
+        me.receivePID = pid;
+        me.queue.Notify(EVENT_OUT);
+        return Ok(Some(msg));
+    }
+
+    // AbortWait unregisters the task registered by Send or Receive
+    pub fn AbortWait(&self, task: &Task) {
+        let me = self.lock();
+        me.queue.EventUnregister(task, &task.blocker.generalEntry);
+    }
+
+    pub fn IPCStat(&self, task: &Task) -> Result<MsqidDS> {
+        let me = self.lock();
+        let creds = task.Creds();
+
+        if !me.checkPermission(
+            &creds,
+            &PermMask {
+                read: true,
+                ..Default::default()
+            },
+        ) {
+            return Err(Error::SysError(SysErr::EACCES));
+        }
+
+        let userns = creds.lock().UserNamespace.clone();
+        let ds = MsqidDS {
+            MsgPerm: IPCPerm {
+                Key: me.key as u32,
+                UID: userns.MapFromKUID(me.owner.UID).0,
+                GID: userns.MapFromKGID(me.owner.GID).0,
+                CUID: userns.MapFromKUID(me.creator.UID).0,
+                CGID: userns.MapFromKGID(me.creator.GID).0,
+                Mode: me.perms.LinuxMode() as u16,
+                Seq: 0,
+                ..Default::default()
+            },
+            MsgStime: me.sendTime.TimeT(),
+            MsgRtime: me.receiveTime.TimeT(),
+            MsgCtime: me.changeTime.TimeT(),
+            MsgCbytes: me.byteCount,
+            MsgQnum: me.messages.len() as u64,
+            MsgQbytes: me.maxBytes,
+            MsgLspid: me.sendPID,
+            MsgLrpid: me.receivePID,
+            ..Default::default()
+        };
+
+        return Ok(ds);
+    }
+
+    pub fn Set(&self, task: &Task, ds: &MsqidDS) -> Result<()> {
+        let mut me = self.lock();
+        let creds = task.Creds();
+
+        if !me.checkOwnership(&creds) {
+            return Err(Error::SysError(SysErr::EPERM));
+        }
+
+        if ds.MsgQbytes > MSGMNB && !creds.HasCapabilityIn(Capability::CAP_SYS_RESOURCE, &me.userNS)
+        {
+            return Err(Error::SysError(SysErr::EPERM));
+        }
+
+        let userns = creds.lock().UserNamespace.clone();
+        let uid = userns.MapToKUID(UID(ds.MsgPerm.UID));
+        let gid = userns.MapToKGID(GID(ds.MsgPerm.GID));
+        if !uid.Ok() || !gid.Ok() {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        me.perms = FilePermissions::FromMode(FileMode(ds.MsgPerm.Mode & 0o777));
+        me.owner.UID = uid;
+        me.owner.GID = gid;
+        me.maxBytes = ds.MsgQbytes;
+        me.changeTime = task.Now();
+
+        // the senders may fit in the queue now
+        me.queue.Notify(EVENT_OUT);
+        return Ok(());
+    }
+}
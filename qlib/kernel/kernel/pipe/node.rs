@@ -165,8 +165,17 @@ impl InodeOperations for PipeIops {
         newParent: &Inode,
         newname: &str,
         replacement: bool,
+        flags: u32,
     ) -> Result<()> {
-        return TmpfsRename(task, oldParent, oldname, newParent, newname, replacement);
+        return TmpfsRename(
+            task,
+            oldParent,
+            oldname,
+            newParent,
+            newname,
+            replacement,
+            flags,
+        );
     }
 
     fn Bind(
@@ -310,6 +319,8 @@ impl InodeOperations for PipeIops {
             FreeBlocks: 0,
             TotalFiles: 0,
             FreeFiles: 0,
+            BlockSize: 0,
+            AvailBlocks: 0,
         });
     }
 
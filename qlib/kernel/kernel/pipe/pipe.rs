@@ -13,7 +13,7 @@
 // limitations under the License.
 
 use crate::qlib::mutex::*;
-use alloc::collections::linked_list::LinkedList;
+use alloc::collections::vec_deque::VecDeque;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use core::ops::Deref;
@@ -55,6 +55,31 @@ pub const MAXIMUM_PIPE_SIZE: usize = 1048576;
 // It corresponds to limits.h:PIPE_BUF.
 pub const ATOMIC_IO_BYTES: usize = 4096;
 
+// the count of the drained buffers kept for reuse by a pipe
+pub const FREE_BUF_COUNT: usize = 2;
+
+// PipeSeg is a run of the queued bytes. A packet is written by one write
+// to a packet mode (O_DIRECT) pipe and is returned by at most one read.
+#[derive(Debug, Clone, Copy)]
+pub struct PipeSeg {
+    pub len: usize,
+    pub packet: bool,
+}
+
+// FilePipe returns the pipe of a pipe file.
+pub fn FilePipe(file: &File) -> Option<Pipe> {
+    let fops = file.FileOp.clone();
+    if let Some(ops) = fops.as_any().downcast_ref::<Reader>() {
+        return Some(ops.pipe.clone());
+    } else if let Some(ops) = fops.as_any().downcast_ref::<Writer>() {
+        return Some(ops.pipe.clone());
+    } else if let Some(ops) = fops.as_any().downcast_ref::<ReaderWriter>() {
+        return Some(ops.pipe.clone());
+    }
+
+    return None;
+}
+
 // NewConnectedPipe initializes a pipe and returns a pair of objects
 // representing the read and write ends of the pipe.
 pub fn NewConnectedPipe(task: &Task, sizeBytes: usize, atomicIOBytes: usize) -> (File, File) {
@@ -85,7 +110,14 @@ pub struct PipeInternal {
     pub id: u64,
 
     // data is the buffer queue of pipe contents.
-    pub data: LinkedList<Buffer>,
+    pub data: VecDeque<Buffer>,
+
+    // free holds drained buffers for reuse.
+    pub free: Vec<Buffer>,
+
+    // segs splits the queued bytes into packets and stream runs. It is
+    // empty while no packet is queued, i.e. all of the data is a stream.
+    pub segs: VecDeque<PipeSeg>,
 
     // max is the maximum size of the pipe in bytes. When this max has been
     // reached, writers will get EWOULDBLOCK.
@@ -111,7 +143,172 @@ impl PipeInternal {
         return (self.max - self.size) as usize;
     }
 
-    pub fn Write(&mut self, _task: &Task, src: BlockSeq, _atomicIOBytes: usize) -> Result<usize> {
+    pub fn NewBuff(&mut self) -> Buffer {
+        match self.free.pop() {
+            None => return NewBuff(),
+            Some(b) => {
+                b.borrow_mut().Reset();
+                return b;
+            }
+        }
+    }
+
+    pub fn ReturnBuff(&mut self, b: Buffer) {
+        if self.free.len() < FREE_BUF_COUNT {
+            self.free.push(b);
+        } else {
+            ReturnBuff(b);
+        }
+    }
+
+    // AppendBuffers copies src to the tail of the buffer queue.
+    fn AppendBuffers(&mut self, src: BlockSeq) -> Result<usize> {
+        let mut src = src;
+        let mut done = 0;
+        while src.NumBytes() > 0 {
+            // Need a new buffer?
+            if self.data.back().is_none() || self.data.back().unwrap().borrow().Full() {
+                let b = self.NewBuff();
+                self.data.push_back(b);
+            }
+
+            // Copy user data.
+            let n = src.CopyInTo(self.data.back_mut().unwrap())?;
+            done += n;
+            self.size += n;
+            src = src.DropFirst(n as u64);
+        }
+
+        return Ok(done);
+    }
+
+    // ReadBuffers consumes the head of the buffer queue into dst.
+    fn ReadBuffers(&mut self, dst: BlockSeq) -> Result<usize> {
+        let mut dst = dst;
+        let mut done = 0;
+        while dst.NumBytes() > 0 {
+            let n;
+            let empty;
+            {
+                let first = match self.data.front_mut() {
+                    None => break,
+                    Some(f) => f,
+                };
+
+                // Copy user data.
+                n = dst.CopyOutFrom(first)?;
+                empty = first.borrow().Empty();
+            }
+
+            done += n;
+            dst = dst.DropFirst(n as u64);
+            self.size -= n;
+            if empty {
+                let v = self.data.pop_front().unwrap();
+                self.ReturnBuff(v);
+            }
+        }
+
+        return Ok(done);
+    }
+
+    // Discard drops len bytes from the head of the buffer queue.
+    fn Discard(&mut self, len: usize) {
+        let mut len = len;
+        while len > 0 {
+            let n;
+            let empty;
+            {
+                let first = match self.data.front() {
+                    None => break,
+                    Some(f) => f,
+                };
+
+                let mut b = first.borrow_mut();
+                n = core::cmp::min(b.write - b.read, len);
+                b.read += n;
+                empty = b.Empty();
+            }
+
+            len -= n;
+            self.size -= n;
+            if empty {
+                let v = self.data.pop_front().unwrap();
+                self.ReturnBuff(v);
+            }
+        }
+    }
+
+    // Peek copies the queued data to dst without consuming it.
+    pub fn Peek(&self, dst: BlockSeq) -> usize {
+        let mut dst = dst;
+        let mut done = 0;
+        for b in self.data.iter() {
+            if dst.NumBytes() == 0 {
+                break;
+            }
+
+            let b = b.borrow();
+            let n = dst.CopyOut(&b.data[b.read..b.write]);
+            done += n;
+            dst = dst.DropFirst(n as u64);
+        }
+
+        return done;
+    }
+
+    // Read consumes the queued data into dst. It doesn't read past the end of
+    // a packet.
+    pub fn Read(&mut self, dst: BlockSeq) -> Result<usize> {
+        if self.segs.len() == 0 {
+            return self.ReadBuffers(dst);
+        }
+
+        let mut dst = dst;
+        let mut done = 0;
+        while dst.NumBytes() > 0 {
+            let seg = match self.segs.front_mut() {
+                None => {
+                    // the rest is stream data
+                    done += self.ReadBuffers(dst)?;
+                    break;
+                }
+                Some(seg) => *seg,
+            };
+
+            if seg.packet {
+                if done > 0 {
+                    break;
+                }
+
+                // the part of the packet which doesn't fit dst is dropped
+                let n = self.ReadBuffers(dst.TakeFirst(seg.len as u64))?;
+                self.Discard(seg.len - n);
+                self.segs.pop_front();
+                done += n;
+                break;
+            }
+
+            let n = self.ReadBuffers(dst.TakeFirst(seg.len as u64))?;
+            done += n;
+            dst = dst.DropFirst(n as u64);
+            if n == seg.len {
+                self.segs.pop_front();
+            } else {
+                self.segs.front_mut().unwrap().len -= n;
+            }
+        }
+
+        return Ok(done);
+    }
+
+    pub fn Write(
+        &mut self,
+        _task: &Task,
+        src: BlockSeq,
+        atomicIOBytes: usize,
+        packet: bool,
+    ) -> Result<usize> {
         let mut p = self;
 
         let mut src = src;
@@ -143,17 +340,40 @@ impl PipeInternal {
         }
 
         let mut done = 0;
-        while src.NumBytes() > 0 {
-            // Need a new buffer?
-            if p.data.back().is_none() || p.data.back().as_ref().unwrap().borrow().Full() {
-                p.data.push_back(NewBuff());
+        if packet {
+            if p.segs.len() == 0 && p.size > 0 {
+                let size = p.size;
+                p.segs.push_back(PipeSeg {
+                    len: size,
+                    packet: false,
+                });
             }
 
-            // Copy user data.
-            let n = src.CopyInTo(*p.data.back_mut().as_mut().unwrap())?;
-            done += n;
-            p.size += n;
-            src = src.DropFirst(n as u64);
+            // each atomicIOBytes chunk is a packet
+            while src.NumBytes() > 0 {
+                let n = p.AppendBuffers(src.TakeFirst(atomicIOBytes as u64))?;
+                if n == 0 {
+                    break;
+                }
+
+                p.segs.push_back(PipeSeg {
+                    len: n,
+                    packet: true,
+                });
+                done += n;
+                src = src.DropFirst(n as u64);
+            }
+        } else {
+            done = p.AppendBuffers(src)?;
+            if p.segs.len() > 0 {
+                match p.segs.back_mut() {
+                    Some(seg) if !seg.packet => seg.len += done,
+                    _ => p.segs.push_back(PipeSeg {
+                        len: done,
+                        packet: false,
+                    }),
+                }
+            }
         }
 
         if wanted > done {
@@ -337,37 +557,21 @@ impl Pipe {
             dst = dst.TakeFirst(p.size as u64);
         }
 
-        let mut done = 0;
-        while dst.NumBytes() > 0 {
-            let mut needPop = false;
-            let n;
-            {
-                // Pop the first buffer.
-                let first = match p.data.front_mut() {
-                    None => break,
-                    Some(f) => f,
-                };
-
-                // Copy user data.
-                n = dst.CopyOutFrom(first)?;
-                done += n;
-                dst = dst.DropFirst(n as u64);
+        return p.Read(dst);
+    }
 
-                // Empty buffer?
-                if first.borrow().Empty() {
-                    needPop = true;
-                }
+    // Peek copies the queued data to dst without consuming it, for tee(2).
+    pub fn Peek(&self, dst: BlockSeq) -> Result<usize> {
+        let p = self.intern.lock();
+        if p.size == 0 {
+            if !self.HasWriters() {
+                return Ok(0);
             }
 
-            p.size -= n;
-            if needPop {
-                // Push to the free list.
-                let v = p.data.pop_front().unwrap();
-                ReturnBuff(v);
-            }
+            return Err(Error::SysError(SysErr::EAGAIN));
         }
 
-        return Ok(done);
+        return Ok(p.Peek(dst));
     }
 
     pub fn ReadFrom(&self, task: &Task, src: &File, opts: &SpliceOpts) -> Result<usize> {
@@ -388,7 +592,8 @@ impl Pipe {
 
             let mut len = p.Available() as usize;
 
-            if len < 4096 {
+            // splice isn't bound by the PIPE_BUF atomicity
+            if len == 0 {
                 return Err(Error::SysError(SysErr::EAGAIN));
             }
 
@@ -410,18 +615,53 @@ impl Pipe {
         let readCount = sfops.ReadAt(task, src, &mut iovs, opts.SrcStart, blocking)?;
 
         let src = BlockSeq::New(&buf[0..readCount as usize]);
-        let writeCount = self.intern.lock().Write(task, src, self.atomicIOBytes)? as usize;
+        let writeCount = self
+            .intern
+            .lock()
+            .Write(task, src, self.atomicIOBytes, false)? as usize;
 
         assert!(readCount as usize == writeCount);
         return Ok(writeCount);
     }
 
+    // WriteTo duplicates the queued data into the pipe dst without consuming
+    // it. Only tee(2) is supported.
+    pub fn WriteTo(&self, task: &Task, dst: &Pipe, opts: &SpliceOpts) -> Result<usize> {
+        if !opts.Dup {
+            return Err(Error::SysError(SysErr::ENOSYS));
+        }
+
+        let len = {
+            let p = dst.intern.lock();
+            if !dst.HasReaders() {
+                return Err(Error::SysError(SysErr::EPIPE));
+            }
+
+            core::cmp::min(p.Available(), opts.Length as usize)
+        };
+
+        if len == 0 {
+            return Err(Error::SysError(SysErr::EAGAIN));
+        }
+
+        // copy through a buffer so the two pipes are never locked together
+        let mut buf = Vec::with_capacity(len);
+        buf.resize(len, 0);
+        let n = self.Peek(BlockSeq::New(&buf))?;
+        if n == 0 {
+            return Ok(0);
+        }
+
+        return dst.Write(task, BlockSeq::New(&buf[0..n]), false);
+    }
+
     // write writes data from sv into the pipe and returns the number of bytes
     // written. If no bytes are written because the pipe is full (or has less than
-    // atomicIOBytes free capacity), write returns ErrWouldBlock.
+    // atomicIOBytes free capacity), write returns ErrWouldBlock. A packet mode
+    // write queues the data as packets of up to atomicIOBytes.
     //
     // Precondition: this pipe must have writers.
-    pub fn Write(&self, task: &Task, src: BlockSeq) -> Result<usize> {
+    pub fn Write(&self, task: &Task, src: BlockSeq, packet: bool) -> Result<usize> {
         let mut p = self.intern.lock();
 
         // Can't write to a pipe with no readers.
@@ -437,7 +677,7 @@ impl Pipe {
             return Err(Error::SysError(SysErr::EPIPE));
         }
 
-        return p.Write(task, src, self.atomicIOBytes);
+        return p.Write(task, src, self.atomicIOBytes, packet);
     }
 
     // rOpen signals a new reader of the pipe.
@@ -498,7 +738,7 @@ impl Pipe {
     pub fn RReadinessLocked(&self, intern: &QMutexGuard<PipeInternal>) -> EventMask {
         let mut ready = 0;
 
-        if self.HasReaders() && intern.data.len() > 0 {
+        if self.HasReaders() && intern.size > 0 {
             ready |= READABLE_EVENT;
         }
 
@@ -558,9 +798,10 @@ impl Pipe {
         return self.intern.lock().max;
     }
 
-    // SetPipeSize implements PipeSize.SetPipeSize.
+    // SetPipeSize implements PipeSize.SetPipeSize. As Linux, the size is
+    // rounded up to a power of two pages.
     pub fn SetPipeSize(&self, size: i64) -> Result<usize> {
-        if size < 0 {
+        if size < 0 || size > core::u32::MAX as i64 {
             return Err(Error::SysError(SysErr::EINVAL));
         }
 
@@ -568,17 +809,28 @@ impl Pipe {
         if size < MINIMUM_PIPE_SIZE {
             size = MINIMUM_PIPE_SIZE;
         }
+        let size = size.next_power_of_two();
 
         if size > MAXIMUM_PIPE_SIZE {
-            return Err(Error::SysError(SysErr::EINVAL));
+            return Err(Error::SysError(SysErr::EPERM));
         }
 
-        let mut intern = self.intern.lock();
-        if size < intern.size {
-            return Err(Error::SysError(SysErr::EBUSY));
+        let grow = {
+            let mut intern = self.intern.lock();
+            if size < intern.size {
+                return Err(Error::SysError(SysErr::EBUSY));
+            }
+
+            let grow = size > intern.max;
+            intern.max = size;
+            grow
+        };
+
+        // blocked writers may fit now
+        if grow {
+            self.Notify(WRITEABLE_EVENT);
         }
 
-        intern.max = size;
         return Ok(size);
     }
 }
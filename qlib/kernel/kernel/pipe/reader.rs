@@ -25,6 +25,7 @@ use super::super::super::fs::file::*;
 use super::super::super::fs::host::hostinodeop::*;
 use super::super::super::kernel::waiter::*;
 use super::super::super::task::*;
+use super::super::super::threadmgr::task_usermem::*;
 use super::pipe::*;
 
 #[derive(Clone)]
@@ -49,7 +50,21 @@ impl Drop for Reader {
     }
 }
 
-impl SpliceOperations for Reader {}
+impl SpliceOperations for Reader {
+    fn WriteTo(&self, task: &Task, _file: &File, dst: &File, opts: &SpliceOpts) -> Result<i64> {
+        let dstPipe = match FilePipe(dst) {
+            None => return Err(Error::SysError(SysErr::ENOSYS)),
+            Some(p) => p,
+        };
+
+        let n = self.pipe.WriteTo(task, &dstPipe, opts)?;
+        if n > 0 {
+            dstPipe.Notify(READABLE_EVENT)
+        }
+
+        return Ok(n as i64);
+    }
+}
 
 impl FileOperations for Reader {
     fn as_any(&self) -> &Any {
@@ -115,7 +130,7 @@ impl FileOperations for Reader {
     fn WriteAt(
         &self,
         task: &Task,
-        _f: &File,
+        f: &File,
         srcs: &[IoVec],
         _offset: i64,
         _blocking: bool,
@@ -124,7 +139,7 @@ impl FileOperations for Reader {
         let size = IoVec::NumBytes(srcs);
         let mut buf = DataBuff::New(size);
         let len = task.CopyDataInFromIovs(&mut buf.buf, srcs, true)?;
-        let n = self.pipe.Write(task, buf.BlockSeqWithLen(len))?;
+        let n = self.pipe.Write(task, buf.BlockSeqWithLen(len), f.Flags().Direct)?;
         if n > 0 {
             self.pipe.Notify(READABLE_EVENT)
         }
@@ -165,7 +180,7 @@ impl FileOperations for Reader {
                 v = core::i32::MAX as usize
             }
 
-            task.CopyOutObj(&v, val)?;
+            UserPtr::New(val).Write(task, &v)?;
             return Ok(());
         }
         return Err(Error::SysError(SysErr::ENOTTY));
@@ -25,6 +25,7 @@ use super::super::super::fs::file::*;
 use super::super::super::fs::host::hostinodeop::*;
 use super::super::super::kernel::waiter::*;
 use super::super::super::task::*;
+use super::super::super::threadmgr::task_usermem::*;
 use super::pipe::*;
 
 #[derive(Clone)]
@@ -51,6 +52,20 @@ impl Drop for ReaderWriter {
 }
 
 impl SpliceOperations for ReaderWriter {
+    fn WriteTo(&self, task: &Task, _file: &File, dst: &File, opts: &SpliceOpts) -> Result<i64> {
+        let dstPipe = match FilePipe(dst) {
+            None => return Err(Error::SysError(SysErr::ENOSYS)),
+            Some(p) => p,
+        };
+
+        let n = self.pipe.WriteTo(task, &dstPipe, opts)?;
+        if n > 0 {
+            dstPipe.Notify(READABLE_EVENT)
+        }
+
+        return Ok(n as i64);
+    }
+
     fn ReadFrom(&self, task: &Task, _file: &File, src: &File, opts: &SpliceOpts) -> Result<i64> {
         let n = self.pipe.ReadFrom(task, src, opts)?;
         if n > 0 {
@@ -119,7 +134,7 @@ impl FileOperations for ReaderWriter {
     fn WriteAt(
         &self,
         task: &Task,
-        _f: &File,
+        f: &File,
         srcs: &[IoVec],
         _offset: i64,
         _blocking: bool,
@@ -128,7 +143,7 @@ impl FileOperations for ReaderWriter {
         let mut buf = DataBuff::New(size);
         let len = task.CopyDataInFromIovs(&mut buf.buf, srcs, true)?;
         let srcs = BlockSeq::New(&buf.buf[..len]);
-        let n = self.pipe.Write(task, srcs)?;
+        let n = self.pipe.Write(task, srcs, f.Flags().Direct)?;
         if n > 0 {
             self.pipe.Notify(READABLE_EVENT)
         }
@@ -170,7 +185,7 @@ impl FileOperations for ReaderWriter {
             }
 
             //*task.GetTypeMut(val)? = v as i32;
-            task.CopyOutObj(&v, val)?;
+            UserPtr::New(val).Write(task, &v)?;
             return Ok(());
         }
         return Err(Error::SysError(SysErr::ENOTTY));
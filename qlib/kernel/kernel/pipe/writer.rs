@@ -25,6 +25,7 @@ use super::super::super::fs::file::*;
 use super::super::super::fs::host::hostinodeop::*;
 use super::super::super::kernel::waiter::*;
 use super::super::super::task::*;
+use super::super::super::threadmgr::task_usermem::*;
 use super::pipe::*;
 
 #[derive(Clone)]
@@ -120,7 +121,7 @@ impl FileOperations for Writer {
     fn WriteAt(
         &self,
         task: &Task,
-        _f: &File,
+        f: &File,
         srcs: &[IoVec],
         _offset: i64,
         _blocking: bool,
@@ -131,7 +132,7 @@ impl FileOperations for Writer {
         let len = task.CopyDataInFromIovs(&mut buf.buf, srcs, true)?;
         let srcs = BlockSeq::New(&buf.buf[0..len]);
 
-        let n = match self.pipe.Write(task, srcs) {
+        let n = match self.pipe.Write(task, srcs, f.Flags().Direct) {
             Err(e) => {
                 return Err(e);
             }
@@ -184,7 +185,7 @@ impl FileOperations for Writer {
             }
 
             //*task.GetTypeMut(val)? = v as i32;
-            task.CopyOutObj(&v, val)?;
+            UserPtr::New(val).Write(task, &v)?;
             return Ok(());
         }
         return Err(Error::SysError(SysErr::ENOTTY));
@@ -21,6 +21,7 @@ use super::super::super::common::*;
 use super::super::super::linux::time::*;
 use super::super::super::linux_def::*;
 use super::super::threadmgr::thread::*;
+use super::super::threadmgr::thread_group::*;
 use super::super::SignalDef::*;
 use super::timer::timer;
 use super::timer::timer::*;
@@ -38,6 +39,9 @@ pub struct IntervalTimerInternal {
     pub sigval: u64,
     pub group: bool,
 
+    // clockID is the clock id of timer_create(2), it is immutable.
+    pub clockID: i32,
+
     // If sigpending is true, a signal to target is already queued, and timer
     // expirations should increment overrunCur instead of sending another
     // signal. sigpending is protected by target's signal mutex. (If target is
@@ -224,7 +228,12 @@ fn saturateI32FromU64(x: u64) -> i32 {
 }
 
 impl Thread {
-    pub fn IntervalTimerCreate(&self, c: &Clock, sigev: &mut Sigevent) -> Result<TimerID> {
+    pub fn IntervalTimerCreate(
+        &self,
+        c: &Clock,
+        clockID: i32,
+        sigev: &mut Sigevent,
+    ) -> Result<TimerID> {
         let tg = self.lock().tg.clone();
         let timerMu = tg.TimerMu();
         let _tm = timerMu.lock();
@@ -261,7 +270,48 @@ impl Thread {
 
         //if sigev is none
 
+        self.newIntervalTimerLocked(&tg, c, clockID, sigev, id)?;
+        return Ok(id);
+    }
+
+    // IntervalTimerCreateWithID creates the timer id, which is restored from a
+    // checkpoint. The later timers get the ids after it.
+    pub fn IntervalTimerCreateWithID(
+        &self,
+        c: &Clock,
+        clockID: i32,
+        sigev: &mut Sigevent,
+        id: TimerID,
+    ) -> Result<()> {
+        let tg = self.lock().tg.clone();
+        let timerMu = tg.TimerMu();
+        let _tm = timerMu.lock();
+
+        if id < 0 || tg.lock().timers.contains_key(&id) {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        self.newIntervalTimerLocked(&tg, c, clockID, sigev, id)?;
+
+        let mut tg = tg.lock();
+        if tg.nextTimerID <= id {
+            tg.nextTimerID = if id == core::i32::MAX { 0 } else { id + 1 };
+        }
+
+        return Ok(());
+    }
+
+    // Preconditions: the timer mutex of tg is locked.
+    fn newIntervalTimerLocked(
+        &self,
+        tg: &ThreadGroup,
+        c: &Clock,
+        clockID: i32,
+        sigev: &mut Sigevent,
+        id: TimerID,
+    ) -> Result<()> {
         let it = IntervalTimer::New(id, sigev.Value);
+        it.lock().clockID = clockID;
 
         match sigev.Notify {
             SIGEV_NONE => (),
@@ -280,7 +330,7 @@ impl Thread {
                         None => return Err(Error::SysError(SysErr::EINVAL)),
                         Some(t) => {
                             let targettg = t.ThreadGroup();
-                            if targettg != tg {
+                            if targettg != *tg {
                                 return Err(Error::SysError(SysErr::EINVAL));
                             }
                             it.lock().target = Some(t.clone())
@@ -303,7 +353,7 @@ impl Thread {
             TimerListener::IntervalTimer(it.clone()),
         ));
         tg.lock().timers.insert(id, it);
-        return Ok(id);
+        return Ok(());
     }
 
     // IntervalTimerDelete implements timer_delete(2).
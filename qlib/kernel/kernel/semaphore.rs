@@ -28,8 +28,9 @@ use super::super::super::linux::sem::*;
 use super::super::super::linux_def::*;
 use super::super::super::singleton::*;
 use super::super::task::*;
-//use super::super::fs::attr::*;
 use super::time::*;
+use super::waiter::Queue;
+use super::waiter::Waitable;
 
 pub static WAITER_ID: Singleton<AtomicU64> = Singleton::<AtomicU64>::New();
 pub unsafe fn InitSingleton() {
@@ -56,7 +57,7 @@ pub struct RegistryInternal {
 }
 
 impl RegistryInternal {
-    fn findByID(&self, id: i32) -> Option<Set> {
+    pub fn findByID(&self, id: i32) -> Option<Set> {
         match self.semaphores.get(&id) {
             None => None,
             Some(s) => Some(s.clone()),
@@ -86,7 +87,6 @@ impl RegistryInternal {
     fn newSet(
         &mut self,
         _task: &Task,
-        myself: Registry,
         key: i32,
         owner: &FileOwner,
         creator: &FileOwner,
@@ -94,7 +94,7 @@ impl RegistryInternal {
         nsems: i32,
     ) -> Result<Set> {
         let set = Set::New(
-            myself,
+            &self.userNS,
             key,
             owner.clone(),
             creator.clone(),
@@ -124,6 +124,18 @@ impl RegistryInternal {
     }
 }
 
+impl Drop for RegistryInternal {
+    // the ipc namespace is gone, destroy its sets so that the blocked waiters
+    // are woken up and the sets are reclaimed once they let go of them
+    fn drop(&mut self) {
+        for (_, set) in &self.semaphores {
+            set.lock().destroy();
+        }
+
+        self.semaphores.clear();
+    }
+}
+
 #[derive(Clone, Default)]
 pub struct Registry(Arc<QMutex<RegistryInternal>>);
 
@@ -160,8 +172,6 @@ impl Registry {
             return Err(Error::SysError(SysErr::EINVAL));
         }
 
-        let clone = self.clone();
-
         let mut me = self.lock();
 
         if !private {
@@ -204,7 +214,11 @@ impl Registry {
 
         let owner = task.FileOwner();
         let perms = FilePermissions::FromMode(mode);
-        return me.newSet(task, clone, key, &owner, &owner, &perms, nsems);
+        return me.newSet(task, key, &owner, &owner, &perms, nsems);
+    }
+
+    pub fn FindByID(&self, id: i32) -> Option<Set> {
+        return self.lock().findByID(id);
     }
 
     pub fn RemoveId(&self, id: i32, creds: &Credentials) -> Result<()> {
@@ -229,7 +243,10 @@ impl Registry {
 }
 
 pub struct SetInternal {
-    pub registry: Registry,
+    // userNS is the user namespace of the ipc namespace owning the set, the
+    // set doesn't hold its registry so that the registry can be reclaimed
+    // with the namespace.
+    pub userNS: UserNameSpace,
     pub id: i32,
     pub key: i32,
     pub creator: FileOwner,
@@ -240,11 +257,15 @@ pub struct SetInternal {
 
     pub sems: Vec<Sem>,
     pub dead: bool,
+
+    // queue is notified when a semaphore value changes or the set is removed,
+    // the blocked semop callers retry their ops then
+    pub queue: Queue,
 }
 
 impl<'a> SetInternal {
     fn findSem_mut(&mut self, num: i32) -> Option<&mut Sem> {
-        if num < 0 || num as usize > self.sems.len() {
+        if num < 0 || num as usize >= self.sems.len() {
             return None;
         }
 
@@ -252,7 +273,7 @@ impl<'a> SetInternal {
     }
 
     fn findSem(&self, num: i32) -> Option<&Sem> {
-        if num < 0 || num as usize > self.sems.len() {
+        if num < 0 || num as usize >= self.sems.len() {
             return None;
         }
 
@@ -269,7 +290,7 @@ impl<'a> SetInternal {
     }
 
     fn checkCapability(&self, creds: &Credentials) -> bool {
-        let res = creds.HasCapabilityIn(Capability::CAP_IPC_OWNER, &self.registry.lock().userNS);
+        let res = creds.HasCapabilityIn(Capability::CAP_IPC_OWNER, &self.userNS);
         let userns = creds.lock().UserNamespace.clone();
         return res && userns.MapFromKUID(self.owner.UID).Ok();
     }
@@ -292,12 +313,21 @@ impl<'a> SetInternal {
     fn destroy(&mut self) {
         self.dead = true;
         for s in &mut self.sems {
-            for (_, w) in &s.waiters {
-                w.Trigger();
-            }
-
             s.waiters.clear();
         }
+
+        self.queue.Notify(EVENT_IN);
+    }
+
+    fn wakeWaiters(&mut self) {
+        let mut woken = false;
+        for s in &mut self.sems {
+            woken |= s.wakeWaiters();
+        }
+
+        if woken {
+            self.queue.Notify(EVENT_IN);
+        }
     }
 
     fn executeOps(&mut self, task: &Task, ops: &[Sembuf], pid: i32) -> Result<(u64, i32)> {
@@ -330,8 +360,9 @@ impl<'a> SetInternal {
                             return Err(Error::SysError(SysErr::EWOULDBLOCK));
                         }
 
-                        let w = Waiter::New(op.SemOp);
+                        let w = Waiter::New(-op.SemOp);
                         let id = w.id;
+                        sem.waiters.insert(w.id, w);
                         return Ok((id, op.SemNum as i32));
                     }
                 } else {
@@ -346,10 +377,10 @@ impl<'a> SetInternal {
 
         for i in 0..tmpVals.len() {
             self.sems[i].value = tmpVals[i];
-            self.sems[i].wakeWaiters();
             self.sems[i].pid = pid;
         }
 
+        self.wakeWaiters();
         self.opTime = task.Now();
 
         return Ok((0, 0));
@@ -369,7 +400,7 @@ impl Deref for Set {
 
 impl Set {
     pub fn New(
-        r: Registry,
+        userNS: &UserNameSpace,
         key: i32,
         owner: FileOwner,
         creator: FileOwner,
@@ -377,7 +408,7 @@ impl Set {
         nsems: i32,
     ) -> Self {
         let mut internal = SetInternal {
-            registry: r.clone(),
+            userNS: userNS.clone(),
             id: 0,
             key: key,
             creator: creator,
@@ -387,6 +418,7 @@ impl Set {
             changeTime: Time::default(),
             sems: Vec::with_capacity(nsems as usize),
             dead: false,
+            queue: Queue::default(),
         };
 
         for _i in 0..nsems as usize {
@@ -434,7 +466,7 @@ impl Set {
 
         let mut me = self.lock();
 
-        if me.checkPerms(
+        if !me.checkPerms(
             creds,
             &PermMask {
                 write: true,
@@ -449,10 +481,10 @@ impl Set {
             Some(sem) => {
                 sem.value = val;
                 sem.pid = pid;
-                sem.wakeWaiters();
             }
         }
 
+        me.wakeWaiters();
         me.changeTime = task.Now();
         return Ok(());
     }
@@ -494,9 +526,9 @@ impl Set {
             let sem = &mut me.sems[i];
             sem.value = vals[i];
             sem.pid = pid;
-            sem.wakeWaiters();
         }
 
+        me.wakeWaiters();
         me.changeTime = task.Now();
         return Ok(());
     }
@@ -534,7 +566,7 @@ impl Set {
         }
 
         let mut vals = Vec::with_capacity(me.sems.len());
-        for i in 0..vals.len() {
+        for i in 0..me.sems.len() {
             vals.push(me.sems[i].value);
         }
 
@@ -560,6 +592,72 @@ impl Set {
         }
     }
 
+    // GetWaiterCnt returns the count of the waiters of the semaphore num, the
+    // ones waiting for it to be 0 if zero, or else the ones waiting for it to
+    // increase
+    pub fn GetWaiterCnt(&self, num: i32, zero: bool, creds: &Credentials) -> Result<u16> {
+        let me = self.lock();
+
+        if !me.checkPerms(
+            creds,
+            &PermMask {
+                read: true,
+                ..Default::default()
+            },
+        ) {
+            return Err(Error::SysError(SysErr::EACCES));
+        }
+
+        match me.findSem(num) {
+            None => return Err(Error::SysError(SysErr::ERANGE)),
+            Some(v) => {
+                let cnt = v
+                    .waiters
+                    .values()
+                    .filter(|w| (w.value == 0) == zero)
+                    .count();
+                return Ok(cnt as u16);
+            }
+        }
+    }
+
+    pub fn GetStat(&self, creds: &Credentials) -> Result<SemidDS> {
+        let me = self.lock();
+
+        if !me.checkPerms(
+            creds,
+            &PermMask {
+                read: true,
+                ..Default::default()
+            },
+        ) {
+            return Err(Error::SysError(SysErr::EACCES));
+        }
+
+        let userns = creds.lock().UserNamespace.clone();
+        let ds = SemidDS {
+            SemPerm: IPCPerm {
+                Key: me.key as u32,
+                UID: userns.MapFromKUID(me.owner.UID).0,
+                GID: userns.MapFromKGID(me.owner.GID).0,
+                CUID: userns.MapFromKUID(me.creator.UID).0,
+                CGID: userns.MapFromKGID(me.creator.GID).0,
+                Mode: me.perms.LinuxMode() as u16,
+                Seq: 0,
+                ..Default::default()
+            },
+            SemOTime: me.opTime.TimeT(),
+            SemCTime: me.changeTime.TimeT(),
+            SemNSems: me.sems.len() as u64,
+            ..Default::default()
+        };
+
+        return Ok(ds);
+    }
+
+    // ExecuteOps runs the ops atomically. When they have to wait, the task is
+    // registered to the set queue and the id and the semaphore of the waiter
+    // are returned, the caller blocks and then retries or calls AbortWait.
     pub fn ExecuteOps(
         &self,
         task: &Task,
@@ -596,14 +694,23 @@ impl Set {
             return Err(Error::SysError(SysErr::EACCES));
         }
 
-        return me.executeOps(task, ops, pid);
+        let (id, num) = me.executeOps(task, ops, pid)?;
+        if id != 0 {
+            me.queue
+                .EventRegister(task, &task.blocker.generalEntry, EVENT_IN);
+        }
+
+        return Ok((id, num));
     }
 
-    pub fn AbortWait(&self, num: i32, id: u64) {
+    // AbortWait drops the waiter of an ExecuteOps call after the task woke up
+    pub fn AbortWait(&self, task: &Task, num: i32, id: u64) {
         let mut me = self.lock();
 
-        let sem = &mut me.sems[num as usize];
-        sem.waiters.remove(&id);
+        me.queue.EventUnregister(task, &task.blocker.generalEntry);
+        if let Some(sem) = me.findSem_mut(num) {
+            sem.waiters.remove(&id);
+        }
     }
 }
 
@@ -624,8 +731,9 @@ impl Default for Sem {
 }
 
 impl Sem {
-    // wakeWaiters goes over all waiters and checks which of them can be notified.
-    pub fn wakeWaiters(&mut self) {
+    // wakeWaiters drops the waiters which can retry their ops, it returns
+    // whether there is any.
+    pub fn wakeWaiters(&mut self) -> bool {
         // Note that this will release all waiters waiting for 0 too.
         let mut ids = Vec::new();
 
@@ -634,13 +742,14 @@ impl Sem {
                 continue;
             }
 
-            w.Trigger();
             ids.push(*id);
         }
 
-        for id in ids {
-            self.waiters.remove(&id);
+        for id in &ids {
+            self.waiters.remove(id);
         }
+
+        return ids.len() > 0;
     }
 }
 
@@ -657,6 +766,4 @@ impl Waiter {
             value: val,
         };
     }
-
-    pub fn Trigger(&self) {}
 }
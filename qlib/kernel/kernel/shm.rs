@@ -12,32 +12,31 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use alloc::sync::Arc;
 use crate::qlib::mutex::*;
-use core::ops::Deref;
 use alloc::collections::btree_map::BTreeMap;
 use alloc::string::String;
-use core::fmt::Debug;
+use alloc::string::ToString;
+use alloc::sync::Arc;
+use core::ops::Deref;
 
-use super::super::PAGE_MGR;
+use super::super::super::addr::*;
+use super::super::super::auth::id::*;
 use super::super::super::auth::userns::*;
 use super::super::super::auth::*;
-use super::super::super::auth::id::*;
-use super::super::super::addr::*;
 use super::super::super::common::*;
-use super::super::super::linux_def::*;
-use super::super::task::*;
-use super::super::super::range::*;
 use super::super::super::device::*;
-use super::super::memmgr::*;
 use super::super::super::linux::ipc::*;
 use super::super::super::linux::shm::*;
+use super::super::super::linux_def::*;
+use super::super::fs::host::hostinodeop::*;
+use super::super::memmgr::*;
+use super::super::task::*;
 use super::time::*;
 
 type Key = i32;
 type ID = i32;
 
-#[derive(Default, Debug)]
+#[derive(Default)]
 pub struct RegistryInternal {
     pub userNS: UserNameSpace,
     pub shms: BTreeMap<ID, Shm>,
@@ -46,9 +45,80 @@ pub struct RegistryInternal {
     pub lastIDUsed: ID,
 }
 
-impl RegistryInternal {}
+impl RegistryInternal {
+    fn newShm(
+        &mut self,
+        task: &Task,
+        pid: i32,
+        key: Key,
+        creator: &FileOwner,
+        perms: &FilePermissions,
+        size: u64,
+    ) -> Result<Shm> {
+        let effectiveSize = Addr(size).MustRoundUp().0;
+        let mappable = HostInodeOp::NewMemfdIops(effectiveSize as i64)?;
+
+        let shm = Shm(Arc::new(QMutex::new(ShmInternal {
+            userNS: self.userNS.clone(),
+            id: 0,
+            creator: *creator,
+            size: size,
+            effectiveSize: effectiveSize,
+            mappable: Some(mappable),
+            key: key,
+            perms: *perms,
+            owner: *creator,
+            attachTime: Time(0),
+            detachTime: Time(0),
+            changeTime: task.Now(),
+            creatorPID: pid,
+            lastAttachDetachPID: 0,
+            pendingDestruction: false,
+            nattach: 0,
+        })));
+
+        let mut id = self.lastIDUsed + 1;
+        while id != self.lastIDUsed {
+            if id < 0 {
+                id = 0;
+            }
+
+            if self.shms.contains_key(&id) {
+                id += 1;
+                continue;
+            }
+
+            self.lastIDUsed = id;
+            shm.lock().id = id;
+            self.shms.insert(id, shm.clone());
+            if key != IPC_PRIVATE {
+                self.keysToShms.insert(key, shm.clone());
+            }
+            self.totalPages += effectiveSize / MemoryDef::PAGE_SIZE;
+            return Ok(shm);
+        }
+
+        info!("Shm ids exhuasted, they may be leaking");
+        return Err(Error::SysError(SysErr::ENOSPC));
+    }
+
+    fn remove(&mut self, s: &mut ShmInternal) {
+        if s.key != IPC_PRIVATE {
+            self.keysToShms.remove(&s.key);
+            s.key = IPC_PRIVATE;
+        }
+
+        if self.shms.remove(&s.id).is_some() {
+            self.totalPages -= s.effectiveSize / MemoryDef::PAGE_SIZE;
+        }
+
+        s.mappable = None;
+    }
+}
 
-#[derive(Clone, Default, Debug)]
+// Registry holds the SysV shared memory segments of an ipc namespace, the
+// segment keys and ids are only visible in the namespace
+#[derive(Clone, Default)]
 pub struct Registry(Arc<QMutex<RegistryInternal>>);
 
 impl Deref for Registry {
@@ -69,7 +139,7 @@ impl Registry {
             lastIDUsed: 0,
         };
 
-        return Self(Arc::new(QMutex::new(internal)))
+        return Self(Arc::new(QMutex::new(internal)));
     }
 
     pub fn FindByID(&self, id: ID) -> Option<Shm> {
@@ -77,126 +147,86 @@ impl Registry {
         return match me.shms.get(&id) {
             None => None,
             Some(shm) => Some(shm.clone()),
-        }
+        };
     }
 
-    fn dissociateKey(&self, shm: &Shm) {
-        let mut me = self.lock();
-        let mut s = shm.lock();
-
-        if s.key != IPC_PRIVATE {
-            me.keysToShms.remove(&s.key);
-            s.key = IPC_PRIVATE;
+    // FindByMappable returns the segment backed by the mappable, a segment
+    // removed from the registry isn't found
+    pub fn FindByMappable(&self, mappable: &HostInodeOp) -> Option<Shm> {
+        let me = self.lock();
+        for (_, shm) in &me.shms {
+            match &shm.lock().mappable {
+                Some(m) if m == mappable => return Some(shm.clone()),
+                _ => (),
+            }
         }
+
+        return None;
     }
 
-    pub fn FindOrCreate(&self, task: &Task, pid: i32, key: Key, size: u64,
-                        mode: &FileMode, private: bool, create: bool, exclusive: bool) -> Result<Shm> {
+    pub fn FindOrCreate(
+        &self,
+        task: &Task,
+        pid: i32,
+        key: Key,
+        size: u64,
+        mode: &FileMode,
+        private: bool,
+        create: bool,
+        exclusive: bool,
+    ) -> Result<Shm> {
         if (create || private) && (size < SHMMIN || size > SHMMAX) {
-            return Err(Error::SysError(SysErr::EINVAL))
+            return Err(Error::SysError(SysErr::EINVAL));
         }
 
-        {
-            let me = self.lock();
-            if me.shms.len() > SHMMNI as usize {
-                return Err(Error::SysError(SysErr::ENOSPC))
-            }
-
-            if !private {
-                match me.keysToShms.get(&key) {
-                    None => (),
-                    Some(s) => {
-                        {
-                            let shm = s.lock();
+        let mut me = self.lock();
+        if me.shms.len() >= SHMMNI as usize {
+            return Err(Error::SysError(SysErr::ENOSPC));
+        }
 
-                            if !shm.checkPermission(task, &PermMask::FromMode(mode.clone())) {
-                                return Err(Error::SysError(SysErr::EACCES))
-                            }
+        if !private {
+            match me.keysToShms.get(&key) {
+                None => (),
+                Some(s) => {
+                    {
+                        let shm = s.lock();
+                        let creds = task.Creds();
 
-                            if size > shm.size {
-                                return Err(Error::SysError(SysErr::EINVAL))
-                            }
+                        if !shm.checkPermission(&creds, &PermMask::FromMode(*mode)) {
+                            return Err(Error::SysError(SysErr::EACCES));
+                        }
 
-                            if create && exclusive {
-                                return Err(Error::SysError(SysErr::EEXIST))
-                            }
+                        if size > shm.size {
+                            return Err(Error::SysError(SysErr::EINVAL));
                         }
 
-                        return Ok(s.clone());
+                        if create && exclusive {
+                            return Err(Error::SysError(SysErr::EEXIST));
+                        }
                     }
-                };
 
-                if !create {
-                    return Err(Error::SysError(SysErr::ENOENT))
+                    return Ok(s.clone());
                 }
-            }
-
-            let sizeAligned = match Addr(size).RoundUp() {
-                Err(_) => return Err(Error::SysError(SysErr::EINVAL)),
-                Ok(addr) => addr.0,
             };
 
-            let numPages = sizeAligned / MemoryDef::PAGE_SIZE;
-            if me.totalPages + numPages > SHMALL {
-                return Err(Error::SysError(SysErr::ENOSPC));
+            if !create {
+                return Err(Error::SysError(SysErr::ENOENT));
             }
         }
 
-        let creator = task.FileOwner();
-        let perms = FilePermissions::FromMode(*mode);
-
-        return self.newShm(task, pid, key, &creator, &perms, size);
-    }
-
-    fn newShm(&self, task: &Task, pid: i32, key: Key, creator: &FileOwner, perms: &FilePermissions, size: u64) -> Result<Shm> {
-        let effectiveSize = Addr(size).MustRoundUp().0;
-        let addr = PAGE_MGR.MapAnon(effectiveSize)?;
-        let fr = Range::New(addr, effectiveSize);
-
-        PAGE_MGR.RefRange(&fr)?;
-
-        let shm = Shm(Arc::new(QMutex::new(ShmInternal {
-            registry: self.clone(),
-            id: 0,
-            creator: *creator,
-            size: size,
-            effectiveSize: effectiveSize,
-            fr: fr,
-            key: key,
-            perms: *perms,
-            owner: *creator,
-            attachTime: Time(0),
-            detachTime: Time(0),
-            changeTime: task.Now(),
-            creatorPID: pid,
-            lastAttachDetachPID: 0,
-            pendingDestruction: false,
-            refCount: 0,
-        })));
-
-        let mut me = self.lock();
-
-        let mut id = me.lastIDUsed + 1;
-        while id != me.lastIDUsed {
-            if id < 0 {
-                id = 0;
-            }
-
-            if me.shms.get(&id).is_some() {
-                id += 1;
-                continue;
-            }
+        let sizeAligned = match Addr(size).RoundUp() {
+            Err(_) => return Err(Error::SysError(SysErr::EINVAL)),
+            Ok(addr) => addr.0,
+        };
 
-            me.lastIDUsed = id;
-            shm.lock().id = id;
-            me.shms.insert(id, shm.clone());
-            me.keysToShms.insert(key, shm.clone());
-            me.totalPages += effectiveSize / MemoryDef::PAGE_SIZE;
-            return Ok(shm)
+        let numPages = sizeAligned / MemoryDef::PAGE_SIZE;
+        if me.totalPages + numPages > SHMALL {
+            return Err(Error::SysError(SysErr::ENOSPC));
         }
 
-        info!("Shm ids exhuasted, they may be leaking");
-        return Err(Error::SysError(SysErr::ENOSPC));
+        let creator = task.FileOwner();
+        let perms = FilePermissions::FromMode(*mode);
+        return me.newShm(task, pid, key, &creator, &perms, size);
     }
 
     pub fn IPCInfo(&self) -> ShmParams {
@@ -206,42 +236,91 @@ impl Registry {
             ShmMni: SHMMNI,
             ShmSeg: SHMSEG,
             ShmAll: SHMALL,
-        }
+            ..Default::default()
+        };
     }
 
     pub fn ShmInfo(&self) -> ShmInfo {
         let me = self.lock();
         return ShmInfo {
-            UsedIDs: me.lastIDUsed,
+            UsedIDs: me.shms.len() as i32,
             ShmTot: me.totalPages,
             ShmRss: me.totalPages,
             // We could probably get a better estimate from memory accounting.
             ShmSwp: 0,
             ..Default::default()
+        };
+    }
+
+    // HighestIndex returns the highest id in use, 0 if there is none
+    pub fn HighestIndex(&self) -> i32 {
+        let me = self.lock();
+        return match me.shms.keys().next_back() {
+            None => 0,
+            Some(id) => *id,
+        };
+    }
+
+    // RemoveShm implements shmctl IPC_RMID. The key is released at once, the
+    // segment is removed when it is no longer attached. Its memory is freed
+    // once the mappings of it are gone too, as they hold the memfd.
+    pub fn RemoveShm(&self, task: &Task, shm: &Shm) -> Result<()> {
+        let mut me = self.lock();
+        let mut s = shm.lock();
+
+        if !s.checkOwnership(&task.Creds()) {
+            return Err(Error::SysError(SysErr::EPERM));
         }
+
+        if s.key != IPC_PRIVATE {
+            me.keysToShms.remove(&s.key);
+            s.key = IPC_PRIVATE;
+        }
+
+        s.pendingDestruction = true;
+        if s.nattach == 0 {
+            me.remove(&mut s);
+        }
+
+        return Ok(());
     }
 
-    fn remove(&self, s: &Shm) {
+    // Detached records the shmdt of the segment backed by the mappable
+    pub fn Detached(&self, task: &Task, mappable: &HostInodeOp, pid: i32) {
+        let shm = match self.FindByMappable(mappable) {
+            // the segment is in another ipc namespace
+            None => return,
+            Some(shm) => shm,
+        };
+
         let mut me = self.lock();
-        let s = s.lock();
+        let mut s = shm.lock();
 
-        if s.key == IPC_PRIVATE {
-            panic!("Attempted to remove {:?} from the registry whose key is still associated", s);
+        // the attachments inherited by fork aren't counted
+        if s.nattach > 0 {
+            s.nattach -= 1;
         }
 
-        me.shms.remove(&s.id);
-        me.totalPages -= s.effectiveSize / MemoryDef::PAGE_SIZE;
+        s.detachTime = task.Now();
+        s.lastAttachDetachPID = pid;
+        if s.nattach == 0 && s.pendingDestruction {
+            me.remove(&mut s);
+        }
     }
 }
 
-#[derive(Clone, Debug)]
 pub struct ShmInternal {
-    pub registry: Registry,
+    // userNS is the user namespace of the ipc namespace owning the segment,
+    // the segment doesn't hold its registry so that the registry can be
+    // reclaimed with the namespace.
+    pub userNS: UserNameSpace,
     pub id: ID,
     pub creator: FileOwner,
     pub size: u64,
     pub effectiveSize: u64,
-    pub fr: Range,
+    // mappable is the memfd backing the segment, it is dropped when the
+    // segment is removed and the attached mappings keep their own reference
+    pub mappable: Option<HostInodeOp>,
     pub key: Key,
     pub perms: FilePermissions,
     pub owner: FileOwner,
@@ -252,26 +331,22 @@ pub struct ShmInternal {
     pub lastAttachDetachPID: i32,
     pub pendingDestruction: bool,
 
-    pub refCount: i64,
+    // nattach is the count of the shmat not detached by shmdt yet
+    pub nattach: i64,
 }
 
 impl ShmInternal {
-    pub fn checkOwnership(&self, task: &Task) -> bool {
-        let creds = task.creds.clone();
+    pub fn checkOwnership(&self, creds: &Credentials) -> bool {
         let effectiveKUID = creds.lock().EffectiveKUID;
 
         if self.owner.UID == effectiveKUID || self.creator.UID == effectiveKUID {
-            return true
+            return true;
         }
 
-        let userns = self.registry.lock().userNS.clone();
-
-        return creds.HasCapabilityIn(Capability::CAP_SYS_ADMIN, &userns)
+        return creds.HasCapabilityIn(Capability::CAP_SYS_ADMIN, &self.userNS);
     }
 
-    pub fn checkPermission(&self, task: &Task, req: &PermMask) -> bool {
-        let creds = task.creds.clone();
-
+    pub fn checkPermission(&self, creds: &Credentials, req: &PermMask) -> bool {
         let mut p = self.perms.Other;
         if self.owner.UID == creds.lock().EffectiveKUID {
             p = self.perms.User;
@@ -280,15 +355,14 @@ impl ShmInternal {
         }
 
         if p.SupersetOf(req) {
-            return true
+            return true;
         }
 
-        let ns = self.registry.lock().userNS.clone();
-        return creds.HasCapabilityIn(Capability::CAP_IPC_OWNER, &ns)
+        return creds.HasCapabilityIn(Capability::CAP_IPC_OWNER, &self.userNS);
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct Shm(Arc<QMutex<ShmInternal>>);
 
 impl Deref for Shm {
@@ -301,7 +375,7 @@ impl Deref for Shm {
 
 impl Mapping for Shm {
     fn MappedName(&self, _task: &Task) -> String {
-        return format!("SYSV{}", self.lock().key)
+        return format!("/SYSV{:08x} (deleted)", self.lock().key);
     }
 
     fn DeviceID(&self) -> u64 {
@@ -314,27 +388,102 @@ impl Mapping for Shm {
 }
 
 impl Shm {
-    pub fn DecRef(&self) {
-        {
-            let mut me = self.lock();
-            me.refCount -= 1;
-            if me.refCount != 0 {
-                return
+    pub fn EffectiveSize(&self) -> u64 {
+        return self.lock().effectiveSize;
+    }
+
+    // ConfigureAttach returns the mmap options of a shmat of the segment
+    pub fn ConfigureAttach(&self, task: &Task, addr: u64, opts: &AttachOpts) -> Result<MMapOpts> {
+        let me = self.lock();
+
+        let mappable = match &me.mappable {
+            None => return Err(Error::SysError(SysErr::EIDRM)),
+            Some(m) => m.clone(),
+        };
+
+        if Addr(addr).AddLen(me.effectiveSize).is_err() {
+            return Err(Error::SysError(SysErr::ENOMEM));
+        }
+
+        if opts.Remap && addr == 0 {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        // SHMLBA is the page size
+        let mut addr = addr;
+        if addr & MemoryDef::PAGE_MASK != 0 {
+            if !opts.Round {
+                return Err(Error::SysError(SysErr::EINVAL));
             }
+
+            addr &= !MemoryDef::PAGE_MASK;
+        }
+
+        let req = PermMask {
+            read: true,
+            write: !opts.ReadOnly,
+            execute: opts.Execute,
+        };
+        if !me.checkPermission(&task.Creds(), &req) {
+            return Err(Error::SysError(SysErr::EACCES));
         }
 
-        self.destroy()
+        let mut prot = MmapProt::PROT_READ;
+        if !opts.ReadOnly {
+            prot |= MmapProt::PROT_WRITE;
+        }
+
+        if opts.Execute {
+            prot |= MmapProt::PROT_EXEC;
+        }
+
+        let mut maxPerms = AccessType::AnyAccess();
+        if opts.ReadOnly {
+            maxPerms.ClearWrite();
+        }
+
+        return Ok(MMapOpts {
+            Length: me.size,
+            Addr: addr,
+            Offset: 0,
+            Fixed: addr != 0,
+            Unmap: opts.Remap,
+            Map32Bit: false,
+            Perms: AccessType(prot),
+            MaxPerms: maxPerms,
+            Private: false,
+            VDSO: false,
+            GrowsDown: false,
+            Precommit: false,
+            NoReserve: false,
+            MLockMode: MLockMode::default(),
+            Kernel: false,
+            Mapping: Some(Arc::new(self.clone())),
+            Mappable: Some(mappable),
+            Hint: "".to_string(),
+        });
     }
 
-    pub fn EffectiveSize(&self) -> u64 {
-        return self.lock().effectiveSize;
+    // Attached records a shmat of the segment
+    pub fn Attached(&self, task: &Task, pid: i32) {
+        let mut me = self.lock();
+        me.nattach += 1;
+        me.attachTime = task.Now();
+        me.lastAttachDetachPID = pid;
     }
 
     pub fn IPCStat(&self, task: &Task) -> Result<ShmidDS> {
         let me = self.lock();
+        let creds = task.Creds();
 
-        if !me.checkPermission(task, &PermMask { read: true, ..Default::default() }) {
-            return Err(Error::SysError(SysErr::EACCES))
+        if !me.checkPermission(
+            &creds,
+            &PermMask {
+                read: true,
+                ..Default::default()
+            },
+        ) {
+            return Err(Error::SysError(SysErr::EACCES));
         }
 
         let mut mode: u16 = 0;
@@ -342,7 +491,7 @@ impl Shm {
             mode |= SHM_DEST;
         }
 
-        let userns = task.creds.lock().UserNamespace.clone();
+        let userns = creds.lock().UserNamespace.clone();
         let ds = ShmidDS {
             ShmPerm: IPCPerm {
                 Key: me.key as u32,
@@ -360,27 +509,27 @@ impl Shm {
             ShmCtime: me.changeTime.TimeT(),
             ShmCpid: me.creatorPID,
             ShmLpid: me.lastAttachDetachPID,
-            ShmNattach: me.refCount,
+            ShmNattach: me.nattach,
             ..Default::default()
         };
 
-        return Ok(ds)
+        return Ok(ds);
     }
 
     pub fn Set(&self, task: &Task, ds: &ShmidDS) -> Result<()> {
         let mut me = self.lock();
+        let creds = task.Creds();
 
-        if !me.checkOwnership(task) {
-            return Err(Error::SysError(SysErr::EPERM))
+        if !me.checkOwnership(&creds) {
+            return Err(Error::SysError(SysErr::EPERM));
         }
 
-        let creds = task.creds.clone();
         let userns = creds.lock().UserNamespace.clone();
 
         let uid = userns.MapToKUID(UID(ds.ShmPerm.UID));
         let gid = userns.MapToKGID(GID(ds.ShmPerm.GID));
         if !uid.Ok() || !gid.Ok() {
-            return Err(Error::SysError(SysErr::EINVAL))
+            return Err(Error::SysError(SysErr::EINVAL));
         }
 
         let mode = FileMode(ds.ShmPerm.Mode & 0x1ff);
@@ -390,32 +539,7 @@ impl Shm {
         me.owner.GID = gid;
 
         me.changeTime = task.Now();
-        return Ok(())
-    }
-
-    pub fn MarkDestroyed(&self) {
-        self.lock().registry.dissociateKey(self);
-
-        let needDec = {
-            let mut me = self.lock();
-            if !me.pendingDestruction {
-                me.pendingDestruction = true;
-                true
-            } else {
-                false
-            }
-        };
-
-        if needDec {
-            self.DecRef()
-        }
-    }
-
-    pub fn destroy(&self) {
-        let me = self.lock();
-        PAGE_MGR.DerefRange(&me.fr).unwrap();
-        let registry = self.lock().registry.clone();
-        registry.remove(self)
+        return Ok(());
     }
 }
 
@@ -423,4 +547,6 @@ pub struct AttachOpts {
     pub Execute: bool,
     pub ReadOnly: bool,
     pub Remap: bool,
+    // Round is SHM_RND, the address is rounded down to SHMLBA
+    pub Round: bool,
 }
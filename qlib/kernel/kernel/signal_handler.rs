@@ -93,6 +93,22 @@ impl SignalHandlers {
         }
     }
 
+    // IgnoredAndCaught returns the signals set to SIG_IGN and the ones with a
+    // user handler, as SigIgn and SigCgt in /proc/[pid]/status.
+    pub fn IgnoredAndCaught(&self) -> (SignalSet, SignalSet) {
+        let mut ignored = SignalSet::default();
+        let mut caught = SignalSet::default();
+        for (i, act) in &self.lock().actions {
+            match act.handler {
+                SigAct::SIGNAL_ACT_DEFAULT => (),
+                SigAct::SIGNAL_ACT_IGNORE => ignored.Add(Signal(*i)),
+                _ => caught.Add(Signal(*i)),
+            }
+        }
+
+        return (ignored, caught);
+    }
+
     pub fn DequeAct(&self, sig: Signal) -> SigAct {
         let mut me = self.lock();
 
@@ -230,6 +230,13 @@ impl CalibratedClock {
 pub struct CalibratedClocks {
     pub monotonic: CalibratedClock,
     pub realtime: CalibratedClock,
+
+    // bootOffset is the offset of the host boottime to the host monotonic
+    // time, i.e. how long the host has been suspended.
+    pub bootOffset: i64,
+
+    // taiOffset is the offset of the host TAI clock to the host realtime.
+    pub taiOffset: i64,
 }
 
 impl CalibratedClocks {
@@ -237,9 +244,26 @@ impl CalibratedClocks {
         return Self {
             monotonic: CalibratedClock::New(MONOTONIC),
             realtime: CalibratedClock::New(REALTIME),
+            bootOffset: 0,
+            taiOffset: 0,
         };
     }
 
+    // UpdateOffsets samples the offsets of the host boottime and TAI clocks.
+    pub fn UpdateOffsets(&mut self) {
+        // The boottime is read first so the sample is never above the real
+        // offset. Keep the largest one so the boottime doesn't go backwards.
+        let boottime = ClockGetTime(BOOTTIME);
+        let offset = boottime - ClockGetTime(MONOTONIC);
+        if offset > self.bootOffset {
+            self.bootOffset = offset;
+        }
+
+        // The TAI offset is a whole number of seconds.
+        let offset = ClockGetTime(TAI) - ClockGetTime(REALTIME);
+        self.taiOffset = (offset + SECOND / 2) / SECOND * SECOND;
+    }
+
     pub fn Update_withSample(&mut self) -> (Parameters, bool, Parameters, bool) {
         let (monotonicParams, monotonicOk) = self.monotonic.Update();
         let (realtimeParams, realtimeOk) = self.realtime.Update();
@@ -276,6 +300,7 @@ impl CalibratedClocks {
 
         self.monotonic.write().updateParams(&monotonicParams);
         self.realtime.write().updateParams(&realtimeParams);
+        self.UpdateOffsets();
 
         return (monotonicParams, true, realtimeParams, true);
     }
@@ -284,6 +309,8 @@ impl CalibratedClocks {
         match id {
             MONOTONIC => self.monotonic.GetTime(),
             REALTIME => self.realtime.GetTime(),
+            BOOTTIME => Ok(self.monotonic.GetTime()? + self.bootOffset),
+            TAI => Ok(self.realtime.GetTime()? + self.taiOffset),
             _ => return Err(Error::SysError(SysErr::EINVAL)),
         }
     }
@@ -32,12 +32,16 @@ pub static TIME_KEEPER: TimerKeeperRef = TimerKeeperRef::New();
 
 pub static REALTIME_CLOCK: Singleton<Clock> = Singleton::<Clock>::New();
 pub static MONOTONIC_CLOCK: Singleton<Clock> = Singleton::<Clock>::New();
+pub static BOOTTIME_CLOCK: Singleton<Clock> = Singleton::<Clock>::New();
+pub static TAI_CLOCK: Singleton<Clock> = Singleton::<Clock>::New();
 pub static TIMER_STORE: TimerStoreRef = TimerStoreRef::New();
 
 pub unsafe fn InitSingleton() {
     TIME_KEEPER.SetValue(SHARESPACE.GetTimerKeeperAddr());
     REALTIME_CLOCK.Init(TIME_KEEPER.NewClock(REALTIME));
     MONOTONIC_CLOCK.Init(TIME_KEEPER.NewClock(MONOTONIC));
+    BOOTTIME_CLOCK.Init(TIME_KEEPER.NewClock(BOOTTIME));
+    TAI_CLOCK.Init(TIME_KEEPER.NewClock(TAI));
     TIMER_STORE.SetValue(SHARESPACE.GetTimerStoreAddr());
 }
 
@@ -76,6 +80,8 @@ pub type ClockID = i32;
 
 pub const REALTIME: ClockID = 0;
 pub const MONOTONIC: ClockID = 1;
+pub const BOOTTIME: ClockID = 7;
+pub const TAI: ClockID = 11;
 
 pub type TimerKeeperRef = ObjectRef<TimeKeeper>;
 pub type TimerStoreRef = ObjectRef<TimerStore>;
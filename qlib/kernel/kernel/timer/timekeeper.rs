@@ -96,6 +96,10 @@ pub struct TimeKeeperInternal {
     // It is set only once, by SetClocks.
     pub monotonicOffset: i64,
 
+    // bootOffset is how long the host has been suspended before SetClocks,
+    // which is not counted in the boottime.
+    pub bootOffset: i64,
+
     // params manages the parameter page.
     pub params: VDSOParamPage,
 
@@ -112,6 +116,7 @@ impl Default for TimeKeeperInternal {
             clocks: clocks,
             bootTime: Time::default(),
             monotonicOffset: 0,
+            bootOffset: 0,
             params: VDSOParamPage::default(),
             inited: false,
             timer: None,
@@ -139,6 +144,8 @@ impl TimeKeeperInternal {
             .expect("Unable to get current realtime");
 
         self.monotonicOffset = wantMonotonic - nowMonotonic;
+        self.clocks.UpdateOffsets();
+        self.bootOffset = self.clocks.bootOffset;
         self.bootTime = Time::FromNs(nowRealtime);
         self.inited = true;
         self.Update();
@@ -187,6 +194,8 @@ impl TimeKeeperInternal {
             Ok(mut now) => {
                 if c == MONOTONIC {
                     now += self.monotonicOffset;
+                } else if c == BOOTTIME {
+                    now += self.monotonicOffset - self.bootOffset;
                 }
 
                 return Ok(now);
@@ -324,6 +324,11 @@ pub struct TimerInternal {
     pub Id: u64,
     pub Expire: i64,
     pub State: TimerState,
+
+    // Deadline is the latest time the timer fires, Expire plus the Slack at
+    // the time it was armed.
+    pub Deadline: i64,
+    pub Slack: i64,
 }
 
 impl Default for TimerInternal {
@@ -338,6 +343,8 @@ impl Default for TimerInternal {
             Id: id,
             State: TimerState::default(),
             Expire: 0,
+            Deadline: 0,
+            Slack: 0,
         };
     }
 }
@@ -354,6 +361,8 @@ impl TimerInternal {
             Id: id,
             State: TimerState::default(),
             Expire: 0,
+            Deadline: 0,
+            Slack: 0,
         };
 
         return ret;
@@ -384,6 +393,13 @@ impl TimerInternal {
             expire: self.Expire,
         };
     }
+
+    pub fn DeadlineUnit(&self) -> TimerUnit {
+        return TimerUnit {
+            timerId: self.Id,
+            expire: self.Deadline,
+        };
+    }
 }
 
 #[derive(Clone, Default)]
@@ -440,6 +456,8 @@ impl Timer {
             Id: id,
             State: TimerState::default(),
             Expire: 0,
+            Deadline: 0,
+            Slack: 0,
         };
 
         let mut res = Self(Arc::new(QMutex::new(internal)));
@@ -458,6 +476,8 @@ impl Timer {
             Id: id,
             State: TimerState::default(),
             Expire: 0,
+            Deadline: 0,
+            Slack: 0,
         };
 
         let mut res = Self(Arc::new(QMutex::new(internal)));
@@ -484,6 +504,8 @@ impl Timer {
             Id: id,
             State: TimerState::default(),
             Expire: 0,
+            Deadline: 0,
+            Slack: 0,
         };
 
         let mut res = Self(Arc::new(QMutex::new(internal)));
@@ -638,6 +660,12 @@ impl Timer {
         return self.lock().clock.clone();
     }
 
+    // SetSlack lets the timer fire up to slack ns after its expiry, so that it
+    // can share a wakeup with the other timers. It applies from the next arming.
+    pub fn SetSlack(&self, slack: i64) {
+        self.lock().Slack = slack;
+    }
+
     // Stop prevents the Timer from firing.
     // It returns true if the call stops the timer, false if the timer has already
     // expired or been stopped.
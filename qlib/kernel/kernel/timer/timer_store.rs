@@ -14,6 +14,7 @@
 
 use crate::qlib::mutex::*;
 use alloc::collections::btree_map::BTreeMap;
+use alloc::collections::btree_set::BTreeSet;
 use alloc::string::String;
 use alloc::vec::Vec;
 use core::cmp::Ordering;
@@ -91,6 +92,8 @@ impl TimerStore {
 pub struct TimerStoreIntern {
     // expire time -> Timer
     pub timerSeq: BTreeMap<TimerUnit, Timer>, // order by expire time
+    // the timers ordered by deadline, nextExpire is the earliest deadline
+    pub deadlineSeq: BTreeSet<TimerUnit>,
     pub nextExpire: i64,
     pub uringExpire: i64,
     pub uringId: u64,
@@ -107,13 +110,12 @@ impl TimerStoreIntern {
 
     pub fn Trigger(&mut self) -> i64 {
         let mut now = MONOTONIC_CLOCK.Now().0;
-        while now + Self::PROCESS_TIME >= self.nextExpire  {
-            let timer = self.GetFirst(now + Self::PROCESS_TIME);
-            match timer {
-                Some(timer) => {
-                    timer.Fire(self);
-                }
-                None => break,
+        // a timer with slack waits for the earliest deadline, which then fires
+        // all the expired timers in one wakeup
+        while self.nextExpire != 0 && now + Self::PROCESS_TIME >= self.nextExpire {
+            while let Some(timer) = self.GetFirst(now + Self::PROCESS_TIME) {
+                timer.Fire(self);
+                now = MONOTONIC_CLOCK.Now().0;
             }
 
             now = MONOTONIC_CLOCK.Now().0;
@@ -140,6 +142,8 @@ impl TimerStoreIntern {
 
         if timer.Expire > 0 {
             self.timerSeq.remove(&timer.TimerUnit());
+            self.deadlineSeq.remove(&timer.DeadlineUnit());
+            self.UpdateNextExpire();
             return true;
         }
 
@@ -150,20 +154,28 @@ impl TimerStoreIntern {
         let mut tl = timer.lock();
         if tl.Expire > 0 {
             self.timerSeq.remove(&tl.TimerUnit());
+            self.deadlineSeq.remove(&tl.DeadlineUnit());
         }
 
         if timeout == 0 {
+            self.UpdateNextExpire();
             return;
         }
 
         let current = MONOTONIC_CLOCK.Now().0;
         tl.Expire = current + timeout;
-
-        if self.nextExpire == 0 || self.nextExpire > tl.Expire {
-            self.nextExpire = tl.Expire;
-        }
+        tl.Deadline = tl.Expire + tl.Slack;
 
         self.timerSeq.insert(tl.TimerUnit(), timer.clone());
+        self.deadlineSeq.insert(tl.DeadlineUnit());
+        self.UpdateNextExpire();
+    }
+
+    fn UpdateNextExpire(&mut self) {
+        self.nextExpire = match self.deadlineSeq.iter().next() {
+            None => 0,
+            Some(tu) => tu.expire,
+        };
     }
 
     pub fn RemoveUringTimer(&mut self) {
@@ -194,8 +206,13 @@ impl TimerStoreIntern {
 
     // return (Expire, Timer)
     pub fn GetFirst(&mut self, now: i64) -> Option<Timer> {
-        if self.nextExpire == 0 || self.nextExpire > now {
-            return None;
+        match self.timerSeq.first_key_value() {
+            None => return None,
+            Some((tu, _)) => {
+                if tu.expire > now {
+                    return None;
+                }
+            }
         }
 
         let timer = match self.timerSeq.pop_first() {
@@ -203,15 +220,8 @@ impl TimerStoreIntern {
             Some((_, timer)) => timer,
         };
 
-        match self.timerSeq.first_key_value() {
-            None => {
-                self.nextExpire = 0;
-            }
-            Some((tu, _)) => {
-                self.nextExpire = tu.expire;
-            }
-        }
-
+        self.deadlineSeq.remove(&timer.lock().DeadlineUnit());
+        self.UpdateNextExpire();
         return Some(timer);
     }
 }
@@ -30,6 +30,7 @@ pub enum WaitContext {
     EpollContext(PollEntry),
     // use Arc instead of Weak as the Unregister will be called in the File Drop
     FileAsync(FileAsync),
+    FileAsyncUrgent(FileAsync),
 }
 
 impl Default for WaitContext {
@@ -54,7 +55,7 @@ impl WaitContext {
         }
     }
 
-    pub fn CallBack(&self) {
+    pub fn CallBack(&self, mask: EventMask) {
         match self {
             WaitContext::EpollContext(p) => {
                 p.CallBack();
@@ -64,7 +65,10 @@ impl WaitContext {
                 context.waiter.Trigger(context.waiterID);
             }
             WaitContext::FileAsync(a) => {
-                a.Callback();
+                a.Callback(mask);
+            }
+            WaitContext::FileAsyncUrgent(a) => {
+                a.UrgentCallback();
             }
             _ => (),
         }
@@ -179,7 +183,7 @@ impl WaitEntry {
     pub fn Notify(&self, mask: EventMask) -> bool {
         let e = self.lock();
         if mask & e.mask != 0 {
-            e.context.CallBack();
+            e.context.CallBack(mask & e.mask);
             return true;
         }
 
@@ -17,6 +17,8 @@ use alloc::sync::Arc;
 use core::cell::UnsafeCell;
 use core::ops::Deref;
 use core::ops::DerefMut;
+use core::sync::atomic::AtomicU32;
+use core::sync::atomic::Ordering;
 
 use super::super::super::super::common::*;
 use super::queue::*;
@@ -215,6 +217,9 @@ impl Default for RWState {
 pub struct QAsyncRwLock {
     pub locked: Arc<QMutex<RWState>>,
     pub queue: Queue,
+    // writeWaiters counts the blocked writers, new readers wait behind them so
+    // that a stream of readers can't starve a writer. Updated under locked.
+    pub writeWaiters: Arc<AtomicU32>,
 }
 
 #[derive(Default)]
@@ -255,16 +260,17 @@ impl QAsyncRwLock {
             let getLock = {
                 let mut l = self.locked.lock();
 
+                let writeWaiting = self.writeWaiters.load(Ordering::SeqCst) > 0;
                 match *l {
-                    RWState::NoLock => {
+                    RWState::NoLock if !writeWaiting => {
                         *l = RWState::Read(1);
                         true
                     }
-                    RWState::Read(count) => {
+                    RWState::Read(count) if !writeWaiting => {
                         *l = RWState::Read(count + 1);
                         true
                     }
-                    RWState::Write => {
+                    _ => {
                         self.queue.EventRegister(task, &blocker.generalEntry, 1);
                         false
                     }
@@ -293,6 +299,7 @@ impl QAsyncRwLock {
 
     pub fn BlockWrite(&self, task: &Task) -> Result<QAsyncWriteLockGuard> {
         let blocker = task.blocker.clone();
+        let mut waiting = false;
 
         loop {
             let getLock = {
@@ -301,13 +308,16 @@ impl QAsyncRwLock {
                 match *l {
                     RWState::NoLock => {
                         *l = RWState::Write;
+                        if waiting {
+                            self.writeWaiters.fetch_sub(1, Ordering::SeqCst);
+                        }
                         true
                     }
-                    RWState::Read(_) => {
-                        self.queue.EventRegister(task, &blocker.generalEntry, 1);
-                        false
-                    }
-                    RWState::Write => {
+                    RWState::Read(_) | RWState::Write => {
+                        if !waiting {
+                            waiting = true;
+                            self.writeWaiters.fetch_add(1, Ordering::SeqCst);
+                        }
                         self.queue.EventRegister(task, &blocker.generalEntry, 1);
                         false
                     }
@@ -325,6 +335,12 @@ impl QAsyncRwLock {
             match blocker.BlockGeneral() {
                 Err(e) => {
                     self.queue.EventUnregister(task, &blocker.generalEntry);
+                    {
+                        let _l = self.locked.lock();
+                        self.writeWaiters.fetch_sub(1, Ordering::SeqCst);
+                    }
+                    // wake the readers waiting behind this writer
+                    self.queue.Notify(!0);
                     return Err(e);
                 }
                 Ok(()) => (),
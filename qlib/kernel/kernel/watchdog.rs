@@ -0,0 +1,237 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::collections::btree_map::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::sync::atomic::Ordering;
+
+use super::super::super::config::*;
+use super::super::super::linux_def::*;
+use super::super::super::mutex::*;
+use super::super::super::task_mgr::*;
+use super::super::super::vcpu_mgr::*;
+use super::super::crash::*;
+use super::super::threadmgr::task_sched::*;
+use super::super::threadmgr::thread::*;
+use super::super::Scale;
+use super::super::SignalDef::*;
+use super::super::SHARESPACE;
+use super::super::TSC;
+use super::kernel::*;
+
+pub static WATCHDOG: Watchdog = Watchdog::New();
+
+// VcpuSample is the progress of a vcpu when it is last seen to move
+#[derive(Debug, Default, Clone, Copy)]
+pub struct VcpuSample {
+    pub switchCount: u64,
+    pub currentTask: u64,
+    // since is the tsc of the first sample without progress
+    pub since: i64,
+    pub reported: bool,
+}
+
+#[derive(Default)]
+pub struct WatchdogInternal {
+    pub lastCheck: i64,
+    pub vcpus: Vec<VcpuSample>,
+    // reported are the hung tasks already reported, keyed by the task id with
+    // the timestamp of its sched state, so that each hang is reported once
+    pub reported: BTreeMap<u64, i64>,
+}
+
+pub struct Watchdog {
+    pub internal: QMutex<WatchdogInternal>,
+}
+
+impl Watchdog {
+    pub const fn New() -> Self {
+        return Self {
+            internal: QMutex::new(WatchdogInternal {
+                lastCheck: 0,
+                vcpus: Vec::new(),
+                reported: BTreeMap::new(),
+            }),
+        };
+    }
+
+    // Check samples the vcpus and the tasks every half timeout. It is called by
+    // the vcpus from the async process, so a hung vcpu is found by the others.
+    pub fn Check(&self) {
+        let (timeout, action) = {
+            let config = SHARESPACE.config.read();
+            (config.WatchdogTimeout, config.WatchdogAction)
+        };
+
+        if timeout == 0 {
+            return;
+        }
+
+        let mut w = match self.internal.try_lock() {
+            None => return,
+            Some(w) => w,
+        };
+
+        let now = TSC.Rdtsc();
+        let timeoutUs = timeout as i64 * 1000_000;
+        if Scale(now - w.lastCheck) < timeoutUs / 2 {
+            return;
+        }
+        w.lastCheck = now;
+
+        Self::CheckVcpus(&mut w, now, timeoutUs, action);
+        Self::CheckTasks(&mut w, now, timeoutUs, action);
+    }
+
+    // CheckVcpus finds the vcpus which run the same task in the kernel without a
+    // task switch for timeout.
+    fn CheckVcpus(w: &mut WatchdogInternal, now: i64, timeoutUs: i64, action: WatchdogAction) {
+        let vcpuArr = &SHARESPACE.scheduler.VcpuArr;
+        if w.vcpus.len() != vcpuArr.len() {
+            w.vcpus = vec![VcpuSample::default(); vcpuArr.len()];
+        }
+
+        // the vcpu running the check makes progress itself
+        let me = CPULocal::CpuId();
+        for i in 0..vcpuArr.len() {
+            let cpu = &vcpuArr[i];
+            let switchCount = cpu.switchCount.load(Ordering::Relaxed);
+            let currentTask = cpu.currentTask.load(Ordering::Relaxed);
+            let inKernel = cpu.State() == VcpuState::Running
+                && cpu.enterAppTimestamp.load(Ordering::Relaxed) == 0;
+
+            let sample = &mut w.vcpus[i];
+            if i == me
+                || !inKernel
+                || sample.switchCount != switchCount
+                || sample.currentTask != currentTask
+            {
+                *sample = VcpuSample {
+                    switchCount: switchCount,
+                    currentTask: currentTask,
+                    since: now,
+                    reported: false,
+                };
+                continue;
+            }
+
+            let stuck = Scale(now - sample.since);
+            if stuck < timeoutUs || sample.reported {
+                continue;
+            }
+
+            sample.reported = true;
+            error!(
+                "watchdog: vcpu {} runs task {:x} in the kernel without progress for {} ms",
+                i,
+                currentTask,
+                stuck / 1000
+            );
+
+            if currentTask != 0 {
+                error!(
+                    "watchdog: the user regs of task {:x} are {:x?}",
+                    currentTask,
+                    TaskId::New(currentTask).GetTask().GetPtRegs()
+                );
+            }
+
+            if action == WatchdogAction::Panic {
+                panic!("watchdog: vcpu {} hangs", i);
+            }
+        }
+    }
+
+    // CheckTasks finds the tasks staying in the kernel or in an uninterruptible
+    // sleep for timeout.
+    fn CheckTasks(w: &mut WatchdogInternal, now: i64, timeoutUs: i64, action: WatchdogAction) {
+        let kernel = match GetKernelOption() {
+            None => return,
+            Some(k) => k,
+        };
+
+        let root = kernel.TaskSet().Root();
+        let mut reported = BTreeMap::new();
+        for tg in root.ThreadGroups() {
+            let threads: Vec<Thread> = tg.lock().tasks.iter().cloned().collect();
+            for thread in &threads {
+                let sched = thread.lock().TaskSchedInfo();
+                if sched.State != SchedState::RunningSys
+                    && sched.State != SchedState::BlockedUninterruptible
+                {
+                    continue;
+                }
+
+                let stuck = Scale(now - sched.Timestamp);
+                if stuck < timeoutUs {
+                    continue;
+                }
+
+                let taskId = thread.lock().taskId;
+                reported.insert(taskId, sched.Timestamp);
+                if w.reported.get(&taskId) == Some(&sched.Timestamp) {
+                    continue;
+                }
+
+                error!(
+                    "watchdog: task {} ({}) stays {:?} for {} ms, backtrace {}",
+                    root.IDOfTask(thread),
+                    thread.Name(),
+                    sched.State,
+                    stuck / 1000,
+                    Self::Backtrace(taskId)
+                );
+
+                match action {
+                    WatchdogAction::Log => (),
+                    WatchdogAction::Kill => {
+                        let info = SignalInfo::SignalInfoPriv(Signal(Signal::SIGKILL));
+                        if let Err(e) = thread.SendGroupSignal(&info) {
+                            error!("watchdog: kill the hung task fail {:?}", e);
+                        }
+                    }
+                    WatchdogAction::Panic => {
+                        panic!("watchdog: task {} hangs", root.IDOfTask(thread));
+                    }
+                }
+            }
+        }
+
+        // forget the tasks which make progress again
+        w.reported = reported;
+    }
+
+    // Backtrace walks the frame pointers saved on the kernel stack of a task
+    // which is switched out. The addresses can be resolved with addr2line on
+    // the qkernel binary after the slide in the qvisor log is subtracted.
+    pub fn Backtrace(taskId: u64) -> String {
+        let task = TaskId::New(taskId).GetTask();
+        let stackStart = taskId;
+        let stackEnd = taskId + MemoryDef::DEFAULT_STACK_SIZE;
+
+        let mut output = String::new();
+        let rsp = task.context.rsp;
+        if rsp >= stackStart && rsp + 8 <= stackEnd {
+            // the return address of the context switch
+            output += &format!("{:x}", unsafe { *(rsp as *const u64) });
+        }
+
+        for ret in FrameBacktrace(task.context.rbp, stackStart, stackEnd) {
+            output += &format!(" {:x}", ret);
+        }
+
+        return output;
+    }
+}
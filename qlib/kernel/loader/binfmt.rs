@@ -0,0 +1,385 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::qlib::mutex::*;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+use super::super::super::common::*;
+use super::super::super::linux_def::*;
+use super::super::super::path::*;
+use super::super::super::singleton::*;
+use super::super::fs::dirent::*;
+use super::super::task::*;
+
+// the bytes of an executable a handler can match on, as linux's
+// BINPRM_BUF_SIZE
+pub const BINPRM_BUF_SIZE: usize = 256;
+
+// the longest registration string linux accepts
+pub const BINFMT_MAX_REGISTER: usize = 1920;
+
+pub static BINFMT_MISC: Singleton<QMutex<BinfmtMisc>> = Singleton::<QMutex<BinfmtMisc>>::New();
+
+pub unsafe fn InitSingleton() {
+    BINFMT_MISC.Init(QMutex::new(BinfmtMisc::New()));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinfmtKind {
+    Magic,
+    Extension,
+}
+
+// BinfmtEntry is a binfmt_misc handler as registered with
+// ":name:type:offset:magic:mask:interpreter:flags".
+#[derive(Clone)]
+pub struct BinfmtEntry {
+    pub name: String,
+    pub kind: BinfmtKind,
+    pub offset: usize,
+    // the magic bytes, or the file extension for BinfmtKind::Extension
+    pub magic: Vec<u8>,
+    pub mask: Option<Vec<u8>>,
+    pub interpreter: String,
+    pub enabled: bool,
+    // P: keep the original argv[0] after the file name
+    pub preserveArgv0: bool,
+    // F: the interpreter is opened at registration, so it is found in
+    // other mount namespaces and chroots
+    pub fixBinary: bool,
+    pub interpreterDirent: Option<Dirent>,
+}
+
+// Unescape decodes the \xHH and \\ escapes of a magic or mask field
+fn Unescape(s: &str) -> Result<Vec<u8>> {
+    let b = s.as_bytes();
+    let mut ret = Vec::with_capacity(b.len());
+    let mut i = 0;
+    while i < b.len() {
+        if b[i] != b'\\' {
+            ret.push(b[i]);
+            i += 1;
+            continue;
+        }
+
+        if i + 1 < b.len() && b[i + 1] == b'\\' {
+            ret.push(b'\\');
+            i += 2;
+            continue;
+        }
+
+        if i + 3 < b.len() && (b[i + 1] == b'x' || b[i + 1] == b'X') {
+            let hex = match core::str::from_utf8(&b[i + 2..i + 4]) {
+                Ok(h) => h,
+                Err(_) => return Err(Error::SysError(SysErr::EINVAL)),
+            };
+            match u8::from_str_radix(hex, 16) {
+                Ok(v) => ret.push(v),
+                Err(_) => return Err(Error::SysError(SysErr::EINVAL)),
+            }
+            i += 4;
+            continue;
+        }
+
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    return Ok(ret);
+}
+
+fn Hex(b: &[u8]) -> String {
+    let mut s = String::with_capacity(b.len() * 2);
+    for c in b {
+        s += &format!("{:02x}", c);
+    }
+
+    return s;
+}
+
+impl BinfmtEntry {
+    pub fn Parse(line: &str) -> Result<Self> {
+        let line = line.strip_suffix('\n').unwrap_or(line);
+        if line.len() < 2 || line.len() > BINFMT_MAX_REGISTER {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        // the first character is the field delimiter
+        let delim = line.chars().next().unwrap();
+        let fields: Vec<&str> = line[delim.len_utf8()..].split(delim).collect();
+        if fields.len() < 6 || fields.len() > 7 {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        let name = fields[0];
+        if name.len() == 0 || name.len() > 64 || name.contains('/') || name == "." || name == ".." {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        // "register" and "status" are the control files of the directory
+        if name == "register" || name == "status" {
+            return Err(Error::SysError(SysErr::EEXIST));
+        }
+
+        let kind = match fields[1] {
+            "M" => BinfmtKind::Magic,
+            "E" => BinfmtKind::Extension,
+            _ => return Err(Error::SysError(SysErr::EINVAL)),
+        };
+
+        let mut entry = Self {
+            name: name.to_string(),
+            kind: kind,
+            offset: 0,
+            magic: Vec::new(),
+            mask: None,
+            interpreter: fields[5].to_string(),
+            enabled: true,
+            preserveArgv0: false,
+            fixBinary: false,
+            interpreterDirent: None,
+        };
+
+        match kind {
+            BinfmtKind::Magic => {
+                if fields[2].len() > 0 {
+                    entry.offset = match fields[2].parse::<usize>() {
+                        Ok(v) => v,
+                        Err(_) => return Err(Error::SysError(SysErr::EINVAL)),
+                    };
+                }
+
+                entry.magic = Unescape(fields[3])?;
+                if entry.magic.len() == 0
+                    || entry.magic.len() > BINPRM_BUF_SIZE
+                    || BINPRM_BUF_SIZE - entry.magic.len() < entry.offset
+                {
+                    return Err(Error::SysError(SysErr::EINVAL));
+                }
+
+                if fields[4].len() > 0 {
+                    let mask = Unescape(fields[4])?;
+                    if mask.len() != entry.magic.len() {
+                        return Err(Error::SysError(SysErr::EINVAL));
+                    }
+                    entry.mask = Some(mask);
+                }
+            }
+            BinfmtKind::Extension => {
+                if fields[3].len() == 0 || fields[3].contains('/') {
+                    return Err(Error::SysError(SysErr::EINVAL));
+                }
+                entry.magic = fields[3].as_bytes().to_vec();
+            }
+        }
+
+        if entry.interpreter.len() == 0 {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        if fields.len() == 7 {
+            for c in fields[6].chars() {
+                match c {
+                    'P' => entry.preserveArgv0 = true,
+                    'F' => entry.fixBinary = true,
+                    // O and C pass the binary as an open fd and take its
+                    // credentials, which the loader doesn't do
+                    'O' | 'C' => {
+                        info!("binfmt_misc: flag {} of {} is not supported", c, name);
+                        return Err(Error::SysError(SysErr::EINVAL));
+                    }
+                    ' ' | '\t' => (),
+                    _ => return Err(Error::SysError(SysErr::EINVAL)),
+                }
+            }
+        }
+
+        return Ok(entry);
+    }
+
+    pub fn Matches(&self, filename: &str, hdr: &[u8]) -> bool {
+        if !self.enabled {
+            return false;
+        }
+
+        match self.kind {
+            BinfmtKind::Extension => {
+                let base = Base(filename).as_bytes();
+                let ext = &self.magic;
+                return base.len() > ext.len() + 1
+                    && base[base.len() - ext.len() - 1] == b'.'
+                    && &base[base.len() - ext.len()..] == &ext[..];
+            }
+            BinfmtKind::Magic => {
+                if hdr.len() < self.offset + self.magic.len() {
+                    return false;
+                }
+
+                let data = &hdr[self.offset..self.offset + self.magic.len()];
+                for i in 0..self.magic.len() {
+                    let mut b = data[i];
+                    if let Some(mask) = &self.mask {
+                        b &= mask[i];
+                    }
+
+                    if b != self.magic[i] {
+                        return false;
+                    }
+                }
+
+                return true;
+            }
+        }
+    }
+
+    // Status returns the content of /proc/sys/fs/binfmt_misc/<name>
+    pub fn Status(&self) -> String {
+        let mut s = String::new();
+        s += if self.enabled {
+            "enabled\n"
+        } else {
+            "disabled\n"
+        };
+        s += &format!("interpreter {}\n", self.interpreter);
+
+        let mut flags = String::new();
+        if self.preserveArgv0 {
+            flags += "P";
+        }
+        if self.fixBinary {
+            flags += "F";
+        }
+        s += &format!("flags: {}\n", flags);
+
+        match self.kind {
+            BinfmtKind::Extension => {
+                s += &format!("extension .{}\n", String::from_utf8_lossy(&self.magic));
+            }
+            BinfmtKind::Magic => {
+                s += &format!("offset {}\n", self.offset);
+                s += &format!("magic {}\n", Hex(&self.magic));
+                if let Some(mask) = &self.mask {
+                    s += &format!("mask {}\n", Hex(mask));
+                }
+            }
+        }
+
+        return s;
+    }
+}
+
+pub struct BinfmtMisc {
+    pub enabled: bool,
+    // the handlers in registration order, the latest is tried first
+    pub entries: Vec<BinfmtEntry>,
+}
+
+impl BinfmtMisc {
+    pub fn New() -> Self {
+        return Self {
+            enabled: true,
+            entries: Vec::new(),
+        };
+    }
+
+    pub fn Register(&mut self, entry: BinfmtEntry) -> Result<()> {
+        if self.Find(&entry.name).is_some() {
+            return Err(Error::SysError(SysErr::EEXIST));
+        }
+
+        self.entries.push(entry);
+        return Ok(());
+    }
+
+    pub fn Find(&self, name: &str) -> Option<&BinfmtEntry> {
+        return self.entries.iter().find(|e| e.name == name);
+    }
+
+    pub fn Names(&self) -> Vec<String> {
+        return self.entries.iter().map(|e| e.name.to_string()).collect();
+    }
+
+    pub fn Remove(&mut self, name: &str) {
+        self.entries.retain(|e| e.name != name);
+    }
+
+    pub fn SetEnabled(&mut self, name: &str, enabled: bool) -> Result<()> {
+        match self.entries.iter_mut().find(|e| e.name == name) {
+            None => return Err(Error::SysError(SysErr::ENOENT)),
+            Some(e) => e.enabled = enabled,
+        }
+
+        return Ok(());
+    }
+
+    // Match returns the handler of an executable with the given name and
+    // leading bytes
+    pub fn Match(&self, filename: &str, hdr: &[u8]) -> Option<BinfmtEntry> {
+        if !self.enabled {
+            return None;
+        }
+
+        for e in self.entries.iter().rev() {
+            if e.Matches(filename, hdr) {
+                return Some(e.clone());
+            }
+        }
+
+        return None;
+    }
+}
+
+// Register parses and adds a handler written to the register file
+pub fn Register(task: &Task, line: &str) -> Result<()> {
+    let mut entry = BinfmtEntry::Parse(line)?;
+    if entry.fixBinary {
+        let root = task.Root();
+        let mut remainingTraversals = 40;
+        let d = task.mountNS.FindDirent(
+            task,
+            &root,
+            Some(task.Workdir()),
+            &entry.interpreter,
+            &mut remainingTraversals,
+            true,
+        )?;
+        entry.interpreterDirent = Some(d);
+    }
+
+    return BINFMT_MISC.lock().Register(entry);
+}
+
+// Control handles the writes of "1", "0" and "-1" to the status file and the
+// handler files: enable, disable and remove. name is None for status,
+// where the write applies to all the handlers.
+pub fn Control(name: Option<&str>, data: &str) -> Result<()> {
+    let mut b = BINFMT_MISC.lock();
+    match (data.trim_end(), name) {
+        ("1", None) => b.enabled = true,
+        ("0", None) => b.enabled = false,
+        ("-1", None) => b.entries.clear(),
+        ("1", Some(name)) => b.SetEnabled(name, true)?,
+        ("0", Some(name)) => b.SetEnabled(name, false)?,
+        ("-1", Some(name)) => {
+            if b.Find(name).is_none() {
+                return Err(Error::SysError(SysErr::ENOENT));
+            }
+            b.Remove(name)
+        }
+        _ => return Err(Error::SysError(SysErr::EINVAL)),
+    }
+
+    return Ok(());
+}
@@ -16,7 +16,8 @@ use alloc::string::String;
 use alloc::string::ToString;
 use alloc::vec::Vec;
 pub use xmas_elf::header::HeaderPt2;
-use xmas_elf::program::ProgramHeader::Ph64;
+use xmas_elf::header::Machine;
+use xmas_elf::program::ProgramHeader::{Ph32, Ph64};
 use xmas_elf::program::ProgramHeader64;
 use xmas_elf::program::Type;
 use xmas_elf::*;
@@ -31,7 +32,8 @@ use super::super::arch::x86_64::context::*;
 use super::super::fs::file::*;
 use super::super::memmgr::*;
 use super::super::task::*;
-use super::super::util::cstring::*;
+use super::super::threadmgr::task_usermem::*;
+use super::loader::*;
 
 pub const ELF_MAGIC: &str = "\x7fELF";
 pub const INTERPRETER_SCRIPT_MAGIC: &str = "#!";
@@ -41,6 +43,18 @@ pub const LINUX_OS: OS = 0;
 
 pub type Arch = i32;
 pub const AMD64: Arch = 0;
+// IA32 is a 32 bit x86 binary which runs in the compat mode.
+pub const IA32: Arch = 1;
+
+// PT_GNU_PROPERTY describes the .note.gnu.property section.
+pub const PT_GNU_PROPERTY: u32 = 0x6474e553;
+pub const NT_GNU_PROPERTY_TYPE_0: u32 = 5;
+pub const GNU_PROPERTY_X86_FEATURE_1_AND: u32 = 0xc0000002;
+pub const GNU_PROPERTY_X86_FEATURE_1_IBT: u32 = 1 << 0;
+pub const GNU_PROPERTY_X86_FEATURE_1_SHSTK: u32 = 1 << 1;
+
+// the note buffer limit used by linux when parsing PT_GNU_PROPERTY
+pub const GNU_PROPERTY_NOTE_MAX: u64 = 1024;
 
 // elfInfo contains the metadata needed to load an ELF binary.
 pub struct ElfHeadersInfo {
@@ -56,6 +70,10 @@ pub struct ElfHeadersInfo {
     // phdrAddr is the offset of the program headers in the file.
     pub phdrAddr: u64,
 
+    // phdrVaddr is the unrelocated virtual address of the program headers,
+    // taken from PT_PHDR or the PT_LOAD segment which maps them.
+    pub phdrVaddr: u64,
+
     // phdrSize is the size of a single program header in the ELF.
     pub phdrSize: usize,
 
@@ -65,6 +83,12 @@ pub struct ElfHeadersInfo {
     // phdrs are the program headers.
     pub phdrs: Vec<ProgramHeader64>,
 
+    // maxAlign is the largest PT_LOAD alignment, at least a page.
+    pub maxAlign: u64,
+
+    // x86Features is the GNU_PROPERTY_X86_FEATURE_1_AND bitmap.
+    pub x86Features: u32,
+
     // sharedObject is true if the ELF represents a shared object.
     pub sharedObject: bool,
 }
@@ -100,49 +124,212 @@ pub fn ParseHeader(task: &mut Task, file: &File) -> Result<ElfHeadersInfo> {
     let phdrAddr;
     let phdrSize;
     let phdrNum;
+    let entry;
+    let arch;
 
     let isSharedObject = match &elfFile.header.pt2 {
         HeaderPt2::Header64(pt2) => {
+            arch = AMD64;
             phdrAddr = pt2.ph_offset;
             phdrSize = pt2.ph_entry_size;
             phdrNum = pt2.ph_count;
+            entry = pt2.entry_point;
             match pt2.type_.as_type() {
                 xmas_elf::header::Type::SharedObject => true,
                 xmas_elf::header::Type::Executable => false,
                 _ => return Err(Error::WrongELFFormat),
             }
         }
-        _ => return Err(Error::WrongELFFormat),
-    };
+        HeaderPt2::Header32(pt2) => {
+            match pt2.machine.as_machine() {
+                Machine::X86 => (),
+                m => {
+                    info!("ParseHeader: unsupported 32 bit machine {:?}", m);
+                    return Err(Error::SysError(SysErr::ENOEXEC));
+                }
+            }
 
-    let entry = match &elfFile.header.pt2 {
-        HeaderPt2::Header64(pt2) => pt2.entry_point,
-        _ => return Err(Error::WrongELFFormat),
+            arch = IA32;
+            phdrAddr = pt2.ph_offset as u64;
+            phdrSize = pt2.ph_entry_size;
+            phdrNum = pt2.ph_count;
+            entry = pt2.entry_point as u64;
+            match pt2.type_.as_type() {
+                xmas_elf::header::Type::SharedObject => true,
+                xmas_elf::header::Type::Executable => false,
+                _ => return Err(Error::WrongELFFormat),
+            }
+        }
     };
 
     let mut phdrs = Vec::new();
+    let mut phdrVaddr = None;
+    let mut property = None;
+    let mut maxAlign = MemoryDef::PAGE_SIZE;
 
     for p in elfFile.program_iter() {
-        if let Ph64(header) = p {
-            let headerType = header.get_type().map_err(Error::ELFLoadError)?;
-            if headerType == Type::Interp || headerType == Type::Load {
-                phdrs.push(*header);
+        let header = match p {
+            Ph64(header) => *header,
+            // widen the 32 bit program header so the rest of the loader is shared
+            Ph32(header) => ProgramHeader64 {
+                type_: header.type_,
+                flags: header.flags,
+                offset: header.offset as u64,
+                virtual_addr: header.virtual_addr as u64,
+                physical_addr: header.physical_addr as u64,
+                file_size: header.file_size as u64,
+                mem_size: header.mem_size as u64,
+                align: header.align as u64,
+            },
+        };
+
+        let headerType = header.get_type().map_err(Error::ELFLoadError)?;
+        match headerType {
+            Type::Interp => phdrs.push(header),
+            Type::Load => {
+                // only honor power of two alignments, like linux
+                if header.align > maxAlign && header.align.is_power_of_two() {
+                    maxAlign = header.align;
+                }
+                phdrs.push(header);
             }
+            Type::Phdr => phdrVaddr = Some(header.virtual_addr),
+            Type::OsSpecific(PT_GNU_PROPERTY) => property = Some(header),
+            _ => (),
         }
     }
 
+    // Without PT_PHDR, find the PT_LOAD segment which maps the program
+    // headers. This is what static binaries rely on for AT_PHDR.
+    let phdrVaddr = match phdrVaddr {
+        Some(v) => v,
+        None => {
+            let mut vaddr = None;
+            for header in &phdrs {
+                if header.get_type().map_err(Error::ELFLoadError)? != Type::Load {
+                    continue;
+                }
+
+                if header.offset <= phdrAddr && phdrAddr < header.offset + header.file_size {
+                    vaddr = Some(phdrAddr - header.offset + header.virtual_addr);
+                    break;
+                }
+            }
+
+            match vaddr {
+                Some(v) => v,
+                None => {
+                    // fall back to the first segment, as linux does
+                    let first = phdrs
+                        .iter()
+                        .find(|h| h.get_type().map(|t| t == Type::Load).unwrap_or(false));
+                    match first {
+                        Some(h) => h
+                            .virtual_addr
+                            .wrapping_sub(h.offset)
+                            .wrapping_add(phdrAddr),
+                        None => phdrAddr,
+                    }
+                }
+            }
+        }
+    };
+
+    let x86Features = match property {
+        None => 0,
+        Some(header) => ParseGnuProperty(task, file, &header, arch)?,
+    };
+
     return Ok(ElfHeadersInfo {
         os: LINUX_OS,
-        arch: AMD64,
+        arch: arch,
         entry: entry,
         phdrAddr: phdrAddr,
+        phdrVaddr: phdrVaddr,
         phdrSize: phdrSize as usize,
         phdrNum: phdrNum as usize,
         phdrs: phdrs,
+        maxAlign: maxAlign,
+        x86Features: x86Features,
         sharedObject: isSharedObject,
     });
 }
 
+fn NoteU32(buf: &[u8], offset: usize) -> Option<u32> {
+    let bytes = buf.get(offset..offset + 4)?;
+    return Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
+}
+
+// ParseGnuProperty reads the PT_GNU_PROPERTY note and returns the
+// GNU_PROPERTY_X86_FEATURE_1_AND bitmap, 0 if it is not present.
+pub fn ParseGnuProperty(
+    task: &mut Task,
+    file: &File,
+    header: &ProgramHeader64,
+    arch: Arch,
+) -> Result<u32> {
+    if header.file_size > GNU_PROPERTY_NOTE_MAX || header.file_size < 16 {
+        info!("PT_GNU_PROPERTY bad size {:x}", header.file_size);
+        return Err(Error::SysError(SysErr::ENOEXEC));
+    }
+
+    let mut buf: Vec<u8> = vec![0; header.file_size as usize];
+    let n = match ReadAll(task, file, &mut buf, header.offset) {
+        Err(e) => {
+            info!("Error: reading PT_GNU_PROPERTY {:?}", e);
+            return Err(Error::SysError(SysErr::ENOEXEC));
+        }
+        Ok(n) => n,
+    };
+
+    if n != buf.len() {
+        return Err(Error::SysError(SysErr::ENOEXEC));
+    }
+
+    // the note is Elf_Nhdr followed by "GNU\0" and the property array
+    let nameSize = NoteU32(&buf, 0).unwrap_or(0);
+    let descSize = NoteU32(&buf, 4).unwrap_or(0) as usize;
+    let noteType = NoteU32(&buf, 8).unwrap_or(0);
+    if nameSize != 4 || noteType != NT_GNU_PROPERTY_TYPE_0 || buf.get(12..16) != Some(b"GNU\0") {
+        info!("PT_GNU_PROPERTY is not a gnu property note");
+        return Err(Error::SysError(SysErr::ENOEXEC));
+    }
+
+    let propAlign = if arch == IA32 { 4 } else { 8 };
+    let descEnd = match 16usize.checked_add(descSize) {
+        Some(e) if e <= buf.len() => e,
+        _ => return Err(Error::SysError(SysErr::ENOEXEC)),
+    };
+
+    let mut offset = 16;
+    while offset + 8 <= descEnd {
+        let prType = NoteU32(&buf, offset).unwrap_or(0);
+        let dataSize = NoteU32(&buf, offset + 4).unwrap_or(0) as usize;
+        offset += 8;
+
+        if offset + dataSize > descEnd {
+            return Err(Error::SysError(SysErr::ENOEXEC));
+        }
+
+        if prType == GNU_PROPERTY_X86_FEATURE_1_AND {
+            if dataSize != 4 {
+                return Err(Error::SysError(SysErr::ENOEXEC));
+            }
+
+            return Ok(NoteU32(&buf, offset).unwrap_or(0));
+        }
+
+        // properties are sorted by type
+        if prType > GNU_PROPERTY_X86_FEATURE_1_AND {
+            break;
+        }
+
+        offset += (dataSize + propAlign - 1) & !(propAlign - 1);
+    }
+
+    return Ok(0);
+}
+
 pub fn PHFlagsAsPerms(header: &ProgramHeader64) -> AccessType {
     let flags = header.flags;
     let mut perms = 0;
@@ -212,7 +399,7 @@ pub fn MapSegment(
                 false,
             )
             .unwrap();
-        task.CopyOutSlice(&buf[0..cnt], vaddr, cnt)?;
+        UserSlice::New(vaddr, cnt).Write(task, &buf[0..cnt])?;
     }
 
     if header.mem_size > size {
@@ -263,6 +450,9 @@ pub struct LoadedElf {
     // phdrNum is the number of program headers.
     pub phdrNum: usize,
 
+    // x86Features is the GNU_PROPERTY_X86_FEATURE_1_AND bitmap.
+    pub x86Features: u32,
+
     // auxv contains a subset of ELF-specific auxiliary vector entries:
     // * AT_PHDR
     // * AT_PHENT
@@ -381,6 +571,11 @@ pub fn LoadParseElf(
                     }
                     Ok(a) => a.0,
                 };
+
+                if info.arch == IA32 && end > MAX_ADDR32 {
+                    info!("PT_LOAD header above 4GB for a 32 bit binary {:x}", end);
+                    return Err(Error::SysError(SysErr::ENOEXEC));
+                }
             }
             t => {
                 panic!("find unexpect type {:?}", t)
@@ -401,13 +596,21 @@ pub fn LoadParseElf(
             Ok(s) => s.0,
         };
 
-        offset = match task.mm.FindAvailableSeg(task, sharedLoadOffset, totalSize) {
+        // Segments asking for a large alignment (e.g. 2MB for huge pages)
+        // need the base aligned too, so reserve the slack to round up.
+        let align = info.maxAlign;
+        let hint = (sharedLoadOffset + align - 1) & !(align - 1);
+        offset = match task
+            .mm
+            .FindAvailableSeg(task, hint, totalSize + align - MemoryDef::PAGE_SIZE)
+        {
             Err(e) => {
                 info!("Error allocating address space for shared object: {:?}", e);
                 return Err(Error::SysError(SysErr::ENOEXEC));
             }
             Ok(s) => s,
         };
+        offset = (offset + align - 1) & !(align - 1);
 
         start += offset;
         end += offset;
@@ -434,17 +637,22 @@ pub fn LoadParseElf(
         }
     }
 
-    let phdrAddr = match Addr(start).AddLen(info.phdrAddr) {
+    let phdrAddr = match Addr(info.phdrVaddr).AddLen(offset) {
         Err(_) => {
             info!(
-                "ELF start address {:x} + phdr offset {:x} overflows",
-                start, info.phdrAddr
+                "ELF phdr address {:x} + load offset {:x} overflows",
+                info.phdrVaddr, offset
             );
             0
         }
         Ok(a) => a.0,
     };
 
+    if info.x86Features & (GNU_PROPERTY_X86_FEATURE_1_IBT | GNU_PROPERTY_X86_FEATURE_1_SHSTK) != 0 {
+        // CET is not enabled for applications, the markers are only recorded
+        info!("ELF is marked with x86 features {:x}", info.x86Features);
+    }
+
     return Ok(LoadedElf {
         os: info.os,
         arch: info.arch,
@@ -455,6 +663,7 @@ pub fn LoadParseElf(
         phdrAddr: phdrAddr,
         phdrSize: info.phdrSize,
         phdrNum: info.phdrNum,
+        x86Features: info.x86Features,
         auxv: Vec::new(),
     });
 }
@@ -463,12 +672,16 @@ pub fn LoadParseElf(
 pub fn LoadInitalElf(task: &mut Task, file: &File) -> Result<LoadedElf> {
     let mut info = ParseHeader(task, file)?;
 
-    let l = task
-        .mm
-        .SetMmapLayout(MIN_USER_ADDR, MAX_USER_ADDR, &LimitSet::default())?;
-    *task.mm.layout.lock() = l;
-
-    let loadAddr = Context64::PIELoadAddress(&l)?;
+    let loadAddr = if info.arch == IA32 {
+        let l = task.mm.SetMmapLayout32(MIN_USER_ADDR, &LimitSet::default())?;
+        Context64::PIELoadAddress32(&l)?
+    } else {
+        let l = task
+            .mm
+            .SetMmapLayout(MIN_USER_ADDR, MAX_USER_ADDR, &LimitSet::default())?;
+        *task.mm.layout.lock() = l;
+        Context64::PIELoadAddress(&l)?
+    };
 
     let le = LoadParseElf(task, file, &mut info, loadAddr)?;
     return Ok(le);
@@ -526,16 +739,20 @@ pub fn LoadElf(task: &mut Task, file: &File) -> Result<LoadedElf> {
 
     let mut interp = LoadedElf::default();
     if bin.interpreter.as_str() != "" {
-        let fileName = CString::New(&bin.interpreter);
-        let fd = task.Open(fileName.Ptr(), OpenFlags::O_RDONLY as u64, 0); //kernel address is same as phy address
-        if (fd as i64) < 0 {
-            info!(
-                "LoadElf Error opening interpreter {} with error {}",
-                &bin.interpreter, -fd as i64
-            );
-        }
+        // open the interpreter without a descriptor, which would otherwise
+        // leak into the new program
+        let interpreter = bin.interpreter.to_string();
+        let (interpFile, _) = match OpenPath(task, &interpreter, 40) {
+            Err(e) => {
+                info!(
+                    "LoadElf Error opening interpreter {} with error {:?}",
+                    &interpreter, e
+                );
+                return Err(e);
+            }
+            Ok(f) => f,
+        };
 
-        let interpFile = task.GetFile(fd as i32)?;
         interp = loadInterpreterELF(task, &interpFile, &bin)?;
 
         if interp.interpreter.as_str() != "" {
@@ -64,11 +64,14 @@ pub fn ParseInterpreterScript(
         Some(n) => (&line[..n], &line[n..]),
     };
 
-    let interp = String::from_utf8(line.to_vec()).unwrap();
-    let arg = String::from_utf8(right.to_vec())
-        .unwrap()
-        .trim()
-        .to_string();
+    let interp = match String::from_utf8(line.to_vec()) {
+        Ok(s) => s,
+        Err(_) => return Err(Error::SysError(SysErr::ENOEXEC)),
+    };
+    let arg = match String::from_utf8(right.to_vec()) {
+        Ok(s) => s.trim().to_string(),
+        Err(_) => return Err(Error::SysError(SysErr::ENOEXEC)),
+    };
 
     if interp.len() == 0 {
         return Err(Error::SysError(SysErr::ENOEXEC));
@@ -19,18 +19,24 @@ use alloc::vec::Vec;
 use super::super::super::addr::*;
 use super::super::super::auxv::*;
 use super::super::super::common::*;
+use super::super::super::limits::*;
 use super::super::super::linux_def::*;
 use super::super::super::path::*;
 use super::super::super::range::*;
 use super::super::fs::dirent::*;
 use super::super::fs::file::*;
 use super::super::fs::flags::*;
+use super::super::fs::inode::*;
+use super::super::kernel::kernel::*;
 use super::super::kernel::timer::*;
 use super::super::kernel_util::*;
 use super::super::memmgr::*;
 use super::super::stack::*;
 use super::super::task::*;
+use super::super::SHARESPACE;
+use super::binfmt::*;
 use super::elf::*;
+use super::vdso::*;
 //use super::super::memmgr::mm::*;
 use super::interpreter::*;
 
@@ -65,6 +71,7 @@ pub fn LoadVDSO(task: &mut Task) -> Result<u64> {
         task,
         paramVAddr + MemoryDef::PAGE_SIZE,
         vdsoParamPageAddr + MemoryDef::PAGE_SIZE,
+        2 * MemoryDef::PAGE_SIZE,
     )?;
 
     //info!("vdsoParamPageAddr is {:x}, phyaddr is {:x}", vdsoParamPageAddr, task.VirtualToPhy(paramVAddr)?);
@@ -75,6 +82,14 @@ pub fn LoadVDSO(task: &mut Task) -> Result<u64> {
     return Ok(vdsoVAddr);
 }
 
+// LoadCompatVDSO maps the 32 bit vdso of a compat process. It only holds the
+// syscall and the sigreturn trampolines, so there is no param page.
+pub fn LoadCompatVDSO(task: &mut Task) -> Result<u64> {
+    let vAddr = task.mm.FindAvailableSeg(task, 0, MemoryDef::PAGE_SIZE)?;
+    let vdsoVAddr = MapVDSOPage(task, vAddr, VDSO.CompatVDSOAddr(), MemoryDef::PAGE_SIZE)?;
+    return Ok(vdsoVAddr);
+}
+
 pub fn MapVDSOParamPage(task: &mut Task, virtualAddr: u64, vdsoParamPageAddr: u64) -> Result<u64> {
     let mut moptions = MMapOpts::NewAnonOptions("[vvar]".to_string())?;
     moptions.Length = MemoryDef::PAGE_SIZE;
@@ -91,9 +106,9 @@ pub fn MapVDSOParamPage(task: &mut Task, virtualAddr: u64, vdsoParamPageAddr: u6
     return Ok(addr);
 }
 
-pub fn MapVDSOPage(task: &mut Task, virtualAddr: u64, vdsoAddr: u64) -> Result<u64> {
+pub fn MapVDSOPage(task: &mut Task, virtualAddr: u64, vdsoAddr: u64, len: u64) -> Result<u64> {
     let mut moptions = MMapOpts::NewAnonOptions("[vdso]".to_string())?;
-    moptions.Length = 2 * MemoryDef::PAGE_SIZE;
+    moptions.Length = len;
     moptions.Addr = virtualAddr;
     moptions.Fixed = true;
     moptions.Perms = AccessType::Executable();
@@ -122,14 +137,8 @@ pub fn OpenPath(task: &mut Task, filename: &str, maxTraversals: u32) -> Result<(
         true,
     )?;
 
-    let perms = PermMask {
-        read: true,
-        execute: true,
-        ..Default::default()
-    };
-
     let inode = d.Inode();
-    inode.CheckPermission(task, &perms)?;
+    CheckExecutable(task, &inode)?;
 
     let len = filename.len();
     // If they claim it's a directory, then make sure.
@@ -140,6 +149,25 @@ pub fn OpenPath(task: &mut Task, filename: &str, maxTraversals: u32) -> Result<(
         return Err(Error::SysError(SysErr::ENOTDIR));
     }
 
+    return OpenDirent(task, &d);
+}
+
+fn CheckExecutable(task: &Task, inode: &Inode) -> Result<()> {
+    let perms = PermMask {
+        read: true,
+        execute: true,
+        ..Default::default()
+    };
+
+    return inode.CheckPermission(task, &perms);
+}
+
+// OpenDirent opens a resolved executable, e.g. the interpreter of a
+// binfmt_misc handler registered with the F flag.
+pub fn OpenDirent(task: &mut Task, d: &Dirent) -> Result<(File, Dirent)> {
+    let inode = d.Inode();
+    CheckExecutable(task, &inode)?;
+
     let file = inode.GetFile(
         task,
         &d,
@@ -149,44 +177,70 @@ pub fn OpenPath(task: &mut Task, filename: &str, maxTraversals: u32) -> Result<(
         },
     )?;
 
-    return Ok((file, d));
+    return Ok((file, d.clone()));
 }
 
-// loadPath resolves filename to a binary and loads it.
-pub fn LoadExecutable(
+// OpenElf resolves filename through interpreter scripts and binfmt_misc
+// handlers to the ELF binary to load and checks its headers, so that execve
+// can still fail cleanly. executable is the already resolved binary for
+// execveat, filename is only its name then.
+pub fn OpenElf(
     task: &mut Task,
     filename: &str,
+    executable: Option<Dirent>,
     argv: &mut Vec<String>,
-) -> Result<(LoadedElf, Dirent, Vec<String>)> {
+) -> Result<(File, Dirent, Vec<String>)> {
     let mut filename = filename.to_string();
 
     let mut tmp = Vec::new();
     tmp.append(argv);
     let mut argv = tmp;
 
+    // the execveat target, then the interpreter of a binfmt_misc handler
+    // with the F flag
+    let mut fixed: Option<Dirent> = executable;
+
     for _i in 0..MAX_LOADER_ATTEMPTS {
-        let (file, executable) = OpenPath(task, &filename, 40)?;
-        let mut hdr: [u8; 4] = [0; 4];
+        let (file, executable) = match fixed.take() {
+            None => OpenPath(task, &filename, 40)?,
+            Some(d) => OpenDirent(task, &d)?,
+        };
+        let mut buf: [u8; BINPRM_BUF_SIZE] = [0; BINPRM_BUF_SIZE];
 
-        match ReadAll(task, &file, &mut hdr, 0) {
+        let n = match ReadAll(task, &file, &mut buf, 0) {
             Err(e) => {
                 print!("Error loading ELF {:?}", e);
                 return Err(Error::SysError(SysErr::ENOEXEC));
             }
-            Ok(n) => {
-                if n < 4 {
-                    print!(
-                        "Error loading ELF, there is less than 4 bytes data, cnt is {}",
-                        n
-                    );
-                    return Err(Error::SysError(SysErr::ENOEXEC));
-                }
-            }
+            Ok(n) => n,
+        };
+
+        // a valid script can be 3 bytes, e.g. "#!a"
+        if n < 3 {
+            print!(
+                "Error loading executable, there is less than 3 bytes data, cnt is {}",
+                n
+            );
+            return Err(Error::SysError(SysErr::ENOEXEC));
+        }
+        let hdr = &buf[..n];
+
+        // binfmt_misc handlers go before the builtin formats, as on linux
+        let handler = BINFMT_MISC.lock().Match(&filename, hdr);
+        if let Some(handler) = handler {
+            info!(
+                "load {} with binfmt_misc handler {}",
+                filename, handler.name
+            );
+            argv = BinfmtArgv(&handler, &filename, argv);
+            filename = handler.interpreter.to_string();
+            fixed = handler.interpreterDirent.clone();
+            continue;
         }
 
-        if SliceCompare(&hdr, ELF_MAGIC.as_bytes()) {
-            let loaded = LoadElf(task, &file)?;
-            return Ok((loaded, executable, argv));
+        if n >= 4 && SliceCompare(&hdr[..4], ELF_MAGIC.as_bytes()) {
+            ParseHeader(task, &file)?;
+            return Ok((file, executable, argv));
         } else if SliceCompare(&hdr[..2], INTERPRETER_SCRIPT_MAGIC.as_bytes()) {
             info!("start to load script {}", filename);
             let (newpath, newargv) = match ParseInterpreterScript(task, &filename, &file, argv) {
@@ -202,18 +256,76 @@ pub fn LoadExecutable(
 
             //info!("load script filename is {} argv is {:?}", &filename, &argv);
         } else {
-            info!("unknow magic: {:?}", hdr);
+            info!("unknow magic: {:?}", &hdr[..core::cmp::min(n, 4)]);
             return Err(Error::SysError(SysErr::ENOEXEC));
         }
     }
 
-    return Err(Error::SysError(SysErr::ENOEXEC));
+    // linux gives up with ELOOP when the interpreters nest too deep
+    return Err(Error::SysError(SysErr::ELOOP));
+}
+
+// BinfmtArgv returns the argv for the interpreter of a binfmt_misc handler:
+// the interpreter, the file name, and the original arguments. argv[0] is
+// kept after the file name only with the P flag.
+pub fn BinfmtArgv(handler: &BinfmtEntry, filename: &str, argv: Vec<String>) -> Vec<String> {
+    let mut newargv = Vec::with_capacity(argv.len() + 2);
+    newargv.push(handler.interpreter.to_string());
+    newargv.push(filename.to_string());
+
+    let skip = if handler.preserveArgv0 { 0 } else { 1 };
+    for arg in argv.into_iter().skip(skip) {
+        newargv.push(arg);
+    }
+
+    return newargv;
+}
+
+// WXEnforced returns whether the W^X enforcement applies to executable,
+// i.e. it is on and the executable isn't in the allow list of the container.
+pub fn WXEnforced(task: &Task, executable: &Dirent) -> bool {
+    if !SHARESPACE.config.read().EnforceWX {
+        return false;
+    }
+
+    let cid = task.Thread().ContainerID();
+    let container = match GetKernel().Container(&cid) {
+        None => return true,
+        Some(c) => c,
+    };
+
+    let (path, _) = executable.FullName(&task.Root());
+    return !container.WXAllowed(&path);
 }
 
 pub const DEFAULT_STACK_SOFT_LIMIT: u64 = 8 * 1024 * 1024;
 
+// Stack growing isn't implemented, so the whole RLIMIT_STACK is mapped up-front.
+// The mapping is capped to stay within the stack gap of the mmap layout, and it
+// is at least ARG_MAX so that the arguments always fit.
+pub const MAX_STACK_SIZE: u64 = 128 << 20;
+pub const MIN_STACK_SIZE: u64 = 128 << 10;
+
+// StackSize returns the size of the initial stack of a new program of task.
+pub fn StackSize(task: &Task) -> u64 {
+    let limit = match &task.thread {
+        None => DEFAULT_STACK_SOFT_LIMIT,
+        Some(t) => t.ThreadGroup().Limits().Get(LimitType::Stack).Cur,
+    };
+
+    if limit > MAX_STACK_SIZE {
+        return MAX_STACK_SIZE;
+    }
+
+    if limit < MIN_STACK_SIZE {
+        return MIN_STACK_SIZE;
+    }
+
+    return Addr(limit).MustRoundUp().0;
+}
+
 pub fn CreateStack(task: &Task) -> Result<Range> {
-    let stackSize = DEFAULT_STACK_SOFT_LIMIT;
+    let stackSize = StackSize(task);
 
     let stackEnd = task.mm.MapStackAddr();
     let stackStart = stackEnd - stackSize;
@@ -235,24 +347,41 @@ pub fn CreateStack(task: &Task) -> Result<Range> {
 
 pub const TASK_COMM_LEN: usize = 16;
 
-// Load loads file with filename into memory.
+// Load loads the ELF file opened by OpenElf into memory. secure is the
+// AT_SECURE value of the new program.
 //return (entry: u64, usersp: u64, kernelsp: u64)
 pub fn Load(
     task: &mut Task,
     filename: &str,
-    argv: &mut Vec<String>,
+    file: &File,
+    executable: &Dirent,
+    argv: &[String],
     envv: &[String],
     extraAuxv: &[AuxEntry],
+    secure: bool,
 ) -> Result<(u64, u64, u64)> {
-    let vdsoAddr = LoadVDSO(task)?;
+    let loaded = LoadElf(task, file)?;
+
+    // the vdso is mapped after the elf, which sets up the 32 or 64 bit layout
+    let vdsoAddr = if loaded.arch == IA32 {
+        LoadCompatVDSO(task)?
+    } else {
+        LoadVDSO(task)?
+    };
+    task.mm.SetVDSO(vdsoAddr);
 
-    let (loaded, executable, tmpArgv) = LoadExecutable(task, filename, argv)?;
-    let argv = tmpArgv;
+    // the tls of the old program is dropped, a 32 bit one starts with the
+    // null selectors
+    task.context.tls32.Reset(loaded.arch == IA32);
+    if task.context.tls32.compat {
+        task.context.tls32.Load();
+    }
 
     let e = Addr(loaded.end).RoundUp()?.0;
 
     task.mm.BrkSetup(e);
-    task.mm.SetExecutable(&executable);
+    task.mm.SetExecutable(executable);
+    task.mm.SetWXEnforce(WXEnforced(task, executable));
 
     let mut name = Base(&filename);
     if name.len() > TASK_COMM_LEN - 1 {
@@ -266,7 +395,7 @@ pub fn Load(
     let mut stack = Stack::New(stackRange.End());
 
     let usersp = SetupUserStack(
-        task, &mut stack, &loaded, filename, &argv, envv, extraAuxv, vdsoAddr,
+        task, &mut stack, &loaded, filename, argv, envv, extraAuxv, vdsoAddr, secure,
     )?;
     let kernelsp = Task::TaskId().Addr() + MemoryDef::DEFAULT_STACK_SIZE - 0x10;
     let entry = loaded.entry;
@@ -284,9 +413,20 @@ pub fn SetupUserStack(
     envv: &[String],
     extraAuxv: &[AuxEntry],
     vdsoAddr: u64,
+    secure: bool,
 ) -> Result<u64> {
+    let compat = loaded.arch == IA32;
+
+    let creds = task.Thread().Creds();
+    let userns = creds.lock().UserNamespace.clone();
+    let uid = creds.lock().RealKUID.In(&userns).OrOverflow().0;
+    let euid = creds.lock().EffectiveKUID.In(&userns).OrOverflow().0;
+    let gid = creds.lock().RealKGID.In(&userns).OrOverflow().0;
+    let egid = creds.lock().EffectiveKGID.In(&userns).OrOverflow().0;
+
     /* auxv dagta */
-    let x86_64 = stack.PushStr(task, "x86_64")?;
+    let platform = if compat { "i686" } else { "x86_64" };
+    let platformAddr = stack.PushStr(task, platform)?;
 
     /* random */
     let (rand1, rand2) = RandU128().unwrap();
@@ -303,39 +443,45 @@ pub fn SetupUserStack(
     });
     auxv.push(AuxEntry {
         Key: AuxVec::AT_PLATFORM,
-        Val: x86_64,
+        Val: platformAddr,
     });
     auxv.push(AuxEntry {
         Key: AuxVec::AT_EXECFN,
         Val: execfn,
     });
+    // HWCAP2_FSGSBASE stays clear: the fs base is only saved through
+    // arch_prctl, a user wrfsbase would be lost on the next context switch.
     auxv.push(AuxEntry {
         Key: AuxVec::AT_HWCAP2,
         Val: 0,
     });
+    auxv.push(AuxEntry {
+        Key: AuxVec::AT_MINSIGSTKSZ,
+        Val: Task::SignalFrameSize(),
+    });
     auxv.push(AuxEntry {
         Key: AuxVec::AT_RANDOM,
         Val: randAddr,
     });
     auxv.push(AuxEntry {
         Key: AuxVec::AT_SECURE,
-        Val: 0,
+        Val: secure as u64,
     });
     auxv.push(AuxEntry {
         Key: AuxVec::AT_EGID,
-        Val: 0,
+        Val: egid as u64,
     });
     auxv.push(AuxEntry {
         Key: AuxVec::AT_GID,
-        Val: 0,
+        Val: gid as u64,
     });
     auxv.push(AuxEntry {
         Key: AuxVec::AT_EUID,
-        Val: 0,
+        Val: euid as u64,
     });
     auxv.push(AuxEntry {
         Key: AuxVec::AT_UID,
-        Val: 0,
+        Val: uid as u64,
     });
     auxv.push(AuxEntry {
         Key: AuxVec::AT_FLAGS,
@@ -357,6 +503,13 @@ pub fn SetupUserStack(
         Key: AuxVec::AT_SYSINFO_EHDR,
         Val: vdsoAddr,
     });
+    // the libc of a 32 bit process enters the kernel through __kernel_vsyscall
+    if compat {
+        auxv.push(AuxEntry {
+            Key: AuxVec::AT_SYSINFO,
+            Val: vdsoAddr + VDSO.read().compatVsyscall,
+        });
+    }
 
     for e in &loaded.auxv {
         auxv.push(*e)
@@ -366,7 +519,11 @@ pub fn SetupUserStack(
         auxv.push(*e)
     }
 
-    let l = stack.LoadEnv(task, envv, argv, &auxv)?;
+    let l = if compat {
+        stack.LoadEnv32(task, envv, argv, &auxv)?
+    } else {
+        stack.LoadEnv(task, envv, argv, &auxv)?
+    };
 
     task.mm.SetupStack(&l, &auxv);
 
@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod binfmt;
 pub mod elf;
 pub mod interpreter;
 pub mod loader;
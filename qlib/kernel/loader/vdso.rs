@@ -21,6 +21,8 @@ pub use xmas_elf::header::HeaderPt2;
 use xmas_elf::program::ProgramHeader::Ph64;
 use xmas_elf::program::ProgramHeader64;
 use xmas_elf::program::Type;
+use xmas_elf::sections::SectionData;
+use xmas_elf::symbol_table::Entry;
 use xmas_elf::*;
 
 use super::super::super::common::*;
@@ -39,6 +41,14 @@ pub struct VdsoInternal {
     pub vdsoAddr: u64,
     pub vdsoLen: usize,
     pub phdrs: Vec<ProgramHeader64>,
+
+    // the 32 bit compat vdso is the page after the 64 bit one
+    pub compatAddr: u64,
+    // the offsets of __kernel_vsyscall, __kernel_sigreturn and
+    // __kernel_rt_sigreturn in the compat vdso
+    pub compatVsyscall: u64,
+    pub compatSigreturn: u64,
+    pub compatRtSigreturn: u64,
 }
 
 impl VdsoInternal {
@@ -48,8 +58,63 @@ impl VdsoInternal {
 
         //todo: align with vdso.so's len
         self.vdsoLen = 2 * MemoryDef::PAGE_SIZE as usize;
+        self.compatAddr = self.vdsoAddr + self.vdsoLen as u64;
 
-        return self.LoadVDSO();
+        self.LoadVDSO()?;
+        return self.LoadCompatVDSO();
+    }
+
+    pub fn LoadCompatVDSO(&mut self) -> Result<()> {
+        let slice = unsafe {
+            slice::from_raw_parts(self.compatAddr as *const u8, MemoryDef::PAGE_SIZE as usize)
+        };
+        let elfFile =
+            ElfFile::new(&slice).map_err(|e| Error::IOError(format!("io::error is {:?}", e)))?;
+
+        let base = match &elfFile.header.pt2 {
+            HeaderPt2::Header32(_) => {
+                let mut base = None;
+                for p in elfFile.program_iter() {
+                    if p.get_type().map_err(Error::ELFLoadError)? == Type::Load {
+                        base = Some(p.virtual_addr());
+                        break;
+                    }
+                }
+
+                match base {
+                    None => return Err(Error::WrongELFFormat),
+                    Some(b) => b,
+                }
+            }
+            _ => return Err(Error::WrongELFFormat),
+        };
+
+        let dynsym = match elfFile.find_section_by_name(".dynsym") {
+            None => return Err(Error::WrongELFFormat),
+            Some(s) => s,
+        };
+
+        let syms = match dynsym.get_data(&elfFile).map_err(Error::ELFLoadError)? {
+            SectionData::DynSymbolTable32(syms) => syms,
+            _ => return Err(Error::WrongELFFormat),
+        };
+
+        for sym in syms {
+            let offset = sym.value() - base;
+            match sym.get_name(&elfFile).map_err(Error::ELFLoadError)? {
+                "__kernel_vsyscall" => self.compatVsyscall = offset,
+                "__kernel_sigreturn" => self.compatSigreturn = offset,
+                "__kernel_rt_sigreturn" => self.compatRtSigreturn = offset,
+                _ => (),
+            }
+        }
+
+        if self.compatVsyscall == 0 || self.compatSigreturn == 0 || self.compatRtSigreturn == 0 {
+            info!("VDSO::LoadCompatVDSO: the compat vdso misses the trampolines");
+            return Err(Error::WrongELFFormat);
+        }
+
+        return Ok(());
     }
 
     pub fn LoadVDSO(&mut self) -> Result<()> {
@@ -103,4 +168,8 @@ impl Vdso {
     pub fn VDSOAddr(&self) -> u64 {
         return self.read().vdsoAddr;
     }
+
+    pub fn CompatVDSOAddr(&self) -> u64 {
+        return self.read().compatAddr;
+    }
 }
@@ -51,6 +51,10 @@ pub struct MmapLayout {
     pub MaxStackRand: u64,
 
     pub sharedLoadsOffset: u64,
+
+    // compat is true if the layout is for a 32 bit (ia32) process, all the
+    // mappings must stay below 4GB.
+    pub compat: bool,
 }
 
 impl MmapLayout {
@@ -0,0 +1,124 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::vec::Vec;
+
+use super::super::super::common::*;
+use super::super::super::linux_def::*;
+use super::super::super::mem::list_allocator::*;
+use super::super::super::mutex::*;
+use super::super::Kernel::HostSpace;
+
+// The balloon takes free huge pages from the heap shared by qkernel and qvisor
+// and asks the host to drop them, so that the host memory of an idle sandbox
+// can be given to the other sandboxes of the node. The pages are returned to
+// the heap when the balloon deflates, the host faults in zero pages on touch.
+
+pub static BALLOON: Balloon = Balloon::New();
+
+// the free heap memory kept when the balloon inflates
+pub const BALLOON_MIN_FREE: u64 = 256 * MemoryDef::ONE_MB;
+
+pub struct Balloon {
+    // pages are the huge pages taken by the balloon
+    pub pages: QMutex<Vec<u64>>,
+}
+
+impl Balloon {
+    pub const fn New() -> Self {
+        return Self {
+            pages: QMutex::new(Vec::new()),
+        };
+    }
+
+    fn Heap() -> &'static ListAllocator {
+        return unsafe { &*(MemoryDef::HEAP_OFFSET as *const ListAllocator) };
+    }
+
+    // Size returns the bytes taken by the balloon.
+    pub fn Size(&self) -> u64 {
+        return self.pages.lock().len() as u64 * MemoryDef::HUGE_PAGE_SIZE;
+    }
+
+    // FreeMem returns the free bytes of the heap.
+    pub fn FreeMem(&self) -> u64 {
+        return Self::Heap().FreePages() * MemoryDef::PAGE_SIZE;
+    }
+
+    // SetTarget inflates or deflates the balloon to target bytes and returns
+    // its new size. The inflation stops early when the heap free memory drops
+    // to BALLOON_MIN_FREE or there is no free huge page left.
+    pub fn SetTarget(&self, target: u64) -> Result<u64> {
+        let target = (target / MemoryDef::HUGE_PAGE_SIZE) as usize;
+        let mut pages = self.pages.lock();
+        if pages.len() < target {
+            let count = target - pages.len();
+            Self::Inflate(&mut pages, count)?;
+        } else {
+            let count = pages.len() - target;
+            Self::Deflate(&mut pages, count);
+        }
+
+        info!(
+            "Balloon size is {:x}, heap free is {:x}",
+            pages.len() as u64 * MemoryDef::HUGE_PAGE_SIZE,
+            self.FreeMem()
+        );
+        return Ok(pages.len() as u64 * MemoryDef::HUGE_PAGE_SIZE);
+    }
+
+    fn Inflate(pages: &mut Vec<u64>, count: usize) -> Result<()> {
+        let heap = Self::Heap();
+        // reserve first so that pushing the pages doesn't allocate
+        pages.reserve(count);
+
+        let start = pages.len();
+        for _i in 0..count {
+            let free = heap.FreePages() * MemoryDef::PAGE_SIZE;
+            if free < BALLOON_MIN_FREE + MemoryDef::HUGE_PAGE_SIZE {
+                break;
+            }
+
+            match heap.AllocHugePage() {
+                None => break,
+                Some(addr) => pages.push(addr),
+            }
+        }
+
+        let taken = pages.len() - start;
+        if taken == 0 {
+            return Ok(());
+        }
+
+        let ret = HostSpace::BalloonRelease(&pages[start] as *const _ as u64, taken);
+        if ret < 0 {
+            for addr in pages.drain(start..) {
+                heap.FreeHugePage(addr);
+            }
+            return Err(Error::SysError(-ret as i32));
+        }
+
+        return Ok(());
+    }
+
+    fn Deflate(pages: &mut Vec<u64>, count: usize) {
+        let heap = Self::Heap();
+        for _i in 0..count {
+            match pages.pop() {
+                None => return,
+                Some(addr) => heap.FreeHugePage(addr),
+            }
+        }
+    }
+}
@@ -18,6 +18,7 @@ use alloc::sync::Arc;
 use alloc::sync::Weak;
 use alloc::vec::Vec;
 use core::ops::Deref;
+use core::sync::atomic::AtomicBool;
 use core::sync::atomic::AtomicU64;
 use core::sync::atomic::Ordering;
 use x86_64::structures::paging::PageTableFlags;
@@ -47,6 +48,7 @@ use super::super::PAGE_MGR;
 //use super::super::asm::*;
 use super::arch::*;
 use super::metadata::*;
+use super::overcommit::*;
 use super::syscalls::*;
 use super::vma::*;
 use super::*;
@@ -62,6 +64,13 @@ pub struct MMMapping {
     // usageAS is vmas.Span(), cached to accelerate RLIMIT_AS checks.
     pub usageAS: u64,
 
+    // maxAS is the peak of usageAS, reported as VmPeak.
+    pub maxAS: u64,
+
+    // committedAS is the combined size in bytes of all vmas with vma.account,
+    // charged to the overcommit policy.
+    pub committedAS: u64,
+
     // lockedAS is the combined size in bytes of all vmas with vma.mlockMode !=
     // memmap.MLockNone.
     pub lockedAS: u64,
@@ -69,8 +78,64 @@ pub struct MMMapping {
     // New VMAs created by MMap use whichever of memmap.MMapOpts.MLockMode or
     // defMLockMode is greater.
     pub defMLockMode: MLockMode,
+
+    // pkeys is the bitmap of the allocated memory protection keys, the key 0
+    // is the default key of all vmas and always allocated.
+    pub pkeys: u16,
+}
+
+impl MMMapping {
+    pub fn AddUsageAS(&mut self, len: u64) {
+        self.usageAS += len;
+        if self.usageAS > self.maxAS {
+            self.maxAS = self.usageAS;
+        }
+    }
+
+    // Commit charges len bytes of accounted vmas to the overcommit policy.
+    pub fn Commit(&mut self, len: u64) -> Result<()> {
+        OVERCOMMIT.Charge(len)?;
+        self.committedAS += len;
+        return Ok(());
+    }
+
+    pub fn Uncommit(&mut self, len: u64) {
+        OVERCOMMIT.Uncharge(len);
+        self.committedAS -= len;
+    }
+
+    pub fn AllocPkey(&mut self) -> Option<i32> {
+        for pkey in 1..MAX_PKEYS {
+            if self.pkeys & (1 << pkey) == 0 {
+                self.pkeys |= 1 << pkey;
+                return Some(pkey);
+            }
+        }
+
+        return None;
+    }
+
+    pub fn FreePkey(&mut self, pkey: i32) -> bool {
+        if pkey <= 0 || !self.PkeyAllocated(pkey) {
+            return false;
+        }
+
+        self.pkeys &= !(1 << pkey);
+        return true;
+    }
+
+    pub fn PkeyAllocated(&self, pkey: i32) -> bool {
+        if pkey < 0 || pkey >= MAX_PKEYS {
+            return false;
+        }
+
+        return self.pkeys & (1 << pkey) != 0;
+    }
 }
 
+// MAX_PKEYS is the number of the memory protection keys of x86
+pub const MAX_PKEYS: i32 = 16;
+
 impl Default for MMMapping {
     fn default() -> Self {
         let vmas = AreaSet::New(0, MemoryDef::LOWER_TOP);
@@ -78,8 +143,11 @@ impl Default for MMMapping {
             vmas: vmas,
             brkInfo: BrkInfo::default(),
             usageAS: 0,
+            committedAS: 0,
+            maxAS: 0,
             lockedAS: 0,
             defMLockMode: MLockMode::MlockNone,
+            pkeys: 1,
         };
         return mm;
     }
@@ -108,6 +176,14 @@ pub struct MMMetadata {
     // userspace.
     //
     pub dumpability: Dumpability,
+
+    // wxEnforce denies the writable and executable user mappings, it is set
+    // up by the loader from the config and the container allow list.
+    pub wxEnforce: bool,
+
+    // vdso is the address the vdso is mapped at. A 32 bit process returns
+    // from its signal handlers through the trampolines of the compat vdso.
+    pub vdso: u64,
 }
 
 #[derive(Default)]
@@ -139,6 +215,11 @@ pub struct MemoryManagerInternal {
     pub vcpuMapping: AtomicU64,
     pub tlbShootdownMask: AtomicU64,
 
+    // root is the root of pagetable.pt, cached out of the pagetable lock for
+    // the task switch. The root of a memory manager doesn't change once it
+    // runs.
+    pub root: AtomicU64,
+
     pub mappingLock: Arc<QMutex<()>>,
     pub mapping: QMutex<MMMapping>,
 
@@ -171,6 +252,38 @@ impl Drop for MemoryManager {
     }
 }
 
+// HOST_WRITES is the count of the writable PinnedPages, i.e. the host io which
+// is writing the user pages. The final round of a checkpoint waits for them.
+pub static HOST_WRITES: AtomicU64 = AtomicU64::new(0);
+
+// DIRTY_TRACKING is set while the pre-copy rounds of a migration collect the
+// dirty pages. The writes through the physical addresses are only marked dirty
+// in the page table meanwhile, otherwise they don't pay for it.
+pub static DIRTY_TRACKING: AtomicBool = AtomicBool::new(false);
+
+// PinnedPages holds a reference on the pages returned by
+// MemoryManager::PinIovs, they are released when it is dropped.
+pub struct PinnedPages {
+    pages: Vec<u64>,
+    // the user ranges written by the host io, they are marked dirty again when
+    // the io completes, in case a pre-copy round has collected them meanwhile
+    written: Vec<IoVec>,
+    mm: Option<MemoryManager>,
+}
+
+impl Drop for PinnedPages {
+    fn drop(&mut self) {
+        if let Some(mm) = self.mm.take() {
+            for iov in &self.written {
+                mm.MarkDirty(iov.start, iov.len as u64).ok();
+            }
+            HOST_WRITES.fetch_sub(1, Ordering::SeqCst);
+        }
+
+        PAGE_MGR.DerefPages(&self.pages);
+    }
+}
+
 #[derive(Clone)]
 pub struct MemoryManagerWeak {
     pub uid: UniqueID,
@@ -206,6 +319,9 @@ impl MemoryManager {
             id: None,
             numaPolicy: 0,
             numaNodemask: 0,
+            pkey: 0,
+            account: false,
+            noReserve: false,
         };
 
         let gap = vmas.FindGap(MemoryDef::PHY_LOWER_ADDR);
@@ -228,8 +344,11 @@ impl MemoryManager {
             vmas: vmas,
             brkInfo: BrkInfo::default(),
             usageAS: 0,
+            committedAS: 0,
+            maxAS: 0,
             lockedAS: 0,
             defMLockMode: MLockMode::MlockNone,
+            pkeys: 1,
         };
 
         let metadata = MMMetadata {
@@ -238,6 +357,8 @@ impl MemoryManager {
             auxv: Vec::new(),
             executable: None,
             dumpability: NOT_DUMPABLE,
+            wxEnforce: false,
+            vdso: 0,
         };
 
         let pt = if kernel {
@@ -246,6 +367,7 @@ impl MemoryManager {
             KERNEL_PAGETABLE.Fork(&*PAGE_MGR).unwrap()
         };
 
+        let root = pt.GetRoot();
         let pagetable = MMPagetable {
             pt: pt,
             sharedLoadsOffset: MemoryDef::SHARED_START,
@@ -266,6 +388,7 @@ impl MemoryManager {
             inited: true,
             vcpuMapping: AtomicU64::new(0),
             tlbShootdownMask: AtomicU64::new(0),
+            root: AtomicU64::new(root),
             mappingLock: Arc::new(QMutex::new(())),
             mapping: QMutex::new(mapping),
             pagetable: QRwLock::new(pagetable),
@@ -380,6 +503,8 @@ impl MemoryManager {
             vseg = vgap.NextSeg();
         }
 
+        let committedAS = mapping.committedAS;
+        mapping.Uncommit(committedAS);
         return Ok(());
     }
 
@@ -441,6 +566,9 @@ impl MemoryManager {
                 }
 
                 mapping.usageAS -= r.Len();
+                if vma.account {
+                    mapping.Uncommit(r.Len());
+                }
                 if vma.mlockMode != MLockMode::MlockNone {
                     mapping.lockedAS -= r.Len();
                 }
@@ -637,6 +765,30 @@ impl MemoryManager {
         self.metadata.lock().executable = Some(dirent.clone());
     }
 
+    pub fn SetVDSO(&self, addr: u64) {
+        self.metadata.lock().vdso = addr;
+    }
+
+    pub fn VDSO(&self) -> u64 {
+        return self.metadata.lock().vdso;
+    }
+
+    pub fn SetWXEnforce(&self, enforce: bool) {
+        self.metadata.lock().wxEnforce = enforce;
+    }
+
+    // CheckWX fails a user mmap or mprotect which makes pages writable and
+    // executable at the same time when W^X is enforced. The loader maps
+    // the ELF segments without the check, as linux does for its own mappings.
+    pub fn CheckWX(&self, perms: &AccessType) -> Result<()> {
+        if perms.Write() && perms.Exec() && self.metadata.lock().wxEnforce {
+            info!("W^X: deny writable and executable mapping");
+            return Err(Error::SysError(SysErr::EACCES));
+        }
+
+        return Ok(());
+    }
+
     pub fn MinCore(&self, _task: &Task, r: &Range) -> Vec<u8> {
         let _ml = self.MappingWriteLock();
 
@@ -863,8 +1015,19 @@ impl MemoryManager {
         return Ok(layout);
     }
 
+    pub fn SetMmapLayout32(&self, minUserAddr: u64, r: &LimitSet) -> Result<MmapLayout> {
+        let layout = Context64::NewMmapLayout32(minUserAddr, r)?;
+        *self.layout.lock() = layout;
+        return Ok(layout);
+    }
+
+    // IsCompat returns true if the memory manager holds a 32 bit compat process.
+    pub fn IsCompat(&self) -> bool {
+        return self.layout.lock().compat;
+    }
+
     pub fn GetRoot(&self) -> u64 {
-        return self.pagetable.read().pt.GetRoot();
+        return self.root.load(Ordering::Acquire);
     }
 
     pub fn GetVmaAndRangeLocked(&self, addr: u64) -> Option<(VMA, Range)> {
@@ -943,17 +1106,17 @@ impl MemoryManager {
                     if writeable {
                         let page = { super::super::PAGE_MGR.AllocPage(true).unwrap() };
                         CopyPage(page, phyAddr);
-                        self.MapPageWriteLocked(pageAddr, page, exec);
+                        self.MapPageWriteLocked(pageAddr, page, exec, vma.pkey);
                         super::super::PAGE_MGR.DerefPage(page);
                     } else {
-                        self.MapPageReadLocked(pageAddr, phyAddr, exec);
+                        self.MapPageReadLocked(pageAddr, phyAddr, exec, vma.pkey);
                     }
                 } else {
                     let writeable = vma.effectivePerms.Write();
                     if writeable {
-                        self.MapPageWriteLocked(pageAddr, phyAddr, exec);
+                        self.MapPageWriteLocked(pageAddr, phyAddr, exec, vma.pkey);
                     } else {
-                        self.MapPageReadLocked(pageAddr, phyAddr, exec);
+                        self.MapPageReadLocked(pageAddr, phyAddr, exec, vma.pkey);
                     }
                 }
 
@@ -966,9 +1129,9 @@ impl MemoryManager {
                 let phyAddr = super::super::PAGE_MGR.AllocPage(true).unwrap();
                 let writeable = vma.effectivePerms.Write();
                 if writeable {
-                    self.MapPageWriteLocked(pageAddr, phyAddr, exec);
+                    self.MapPageWriteLocked(pageAddr, phyAddr, exec, vma.pkey);
                 } else {
-                    self.MapPageReadLocked(pageAddr, phyAddr, exec);
+                    self.MapPageReadLocked(pageAddr, phyAddr, exec, vma.pkey);
                 }
 
                 super::super::PAGE_MGR.DerefPage(phyAddr);
@@ -977,25 +1140,25 @@ impl MemoryManager {
         }
     }
 
-    pub fn MapPageWriteLocked(&self, vAddr: u64, pAddr: u64, exec: bool) {
+    pub fn MapPageWriteLocked(&self, vAddr: u64, pAddr: u64, exec: bool, pkey: i32) {
         let pt = self.pagetable.write();
         pt.pt
             .MapPage(
                 Addr(vAddr),
                 Addr(pAddr),
-                PageOpts::New(true, true, exec).Val(),
+                PageOpts::New(true, true, exec).SetPkey(pkey).Val(),
                 &*PAGE_MGR,
             )
             .unwrap();
     }
 
-    pub fn MapPageReadLocked(&self, vAddr: u64, pAddr: u64, exec: bool) {
+    pub fn MapPageReadLocked(&self, vAddr: u64, pAddr: u64, exec: bool, pkey: i32) {
         let pt = self.pagetable.write();
         pt.pt
             .MapPage(
                 Addr(vAddr),
                 Addr(pAddr),
-                PageOpts::New(true, false, exec).Val(),
+                PageOpts::New(true, false, exec).SetPkey(pkey).Val(),
                 &*PAGE_MGR,
             )
             .unwrap();
@@ -1021,7 +1184,7 @@ impl MemoryManager {
         let exec = vma.effectivePerms.Exec();
         let page = { super::super::PAGE_MGR.AllocPage(false).unwrap() };
         CopyPage(page, phyAddr);
-        self.MapPageWriteLocked(pageAddr, page, exec);
+        self.MapPageWriteLocked(pageAddr, page, exec, vma.pkey);
     }
 
     pub fn CopyOnWrite(&self, pageAddr: u64, vma: &VMA) {
@@ -1032,6 +1195,111 @@ impl MemoryManager {
         //PerfGofrom(PerfType::PageFault);
     }
 
+    // PageMapped checks whether the user page of the address is mapped with
+    // the access permission. It only takes the pagetable read lock, so the
+    // read mostly paths don't contend with the mmap/munmap writers.
+    pub fn PageMapped(&self, vAddr: u64, write: bool, exec: bool) -> bool {
+        let pagetable = self.pagetable.read();
+        let entry = match pagetable.pt.VirtualToEntry(vAddr) {
+            Err(_) => return false,
+            Ok(e) => e,
+        };
+
+        let flags = entry.flags();
+        let mask = PageTableFlags::PRESENT | PageTableFlags::USER_ACCESSIBLE;
+        if flags & mask != mask {
+            return false;
+        }
+
+        if write && flags & PageTableFlags::WRITABLE != PageTableFlags::WRITABLE {
+            return false;
+        }
+
+        if exec && flags & PageTableFlags::NO_EXECUTE == PageTableFlags::NO_EXECUTE {
+            return false;
+        }
+
+        return true;
+    }
+
+    // V2PFast translates the user range with the pagetable only, without the
+    // mapping lock. It succeeds only when all the pages of the range are
+    // mapped with the required permission. Otherwise nothing is added to the
+    // output and the caller falls back to the locked path, which installs or
+    // cows the pages.
+    pub fn V2PFast(
+        &self,
+        start: u64,
+        len: u64,
+        output: &mut Vec<IoVec>,
+        writable: bool,
+        merge: bool,
+    ) -> bool {
+        if core::u64::MAX - start < len || start + len > MemoryDef::LOWER_TOP {
+            return false;
+        }
+
+        if MemoryDef::PHY_LOWER_ADDR <= start && start <= MemoryDef::PHY_UPPER_ADDR {
+            return false;
+        }
+
+        let mut mask = PageTableFlags::PRESENT | PageTableFlags::USER_ACCESSIBLE;
+        if writable {
+            mask |= PageTableFlags::WRITABLE;
+            // while the dirty pages are tracked, the clean pages go through
+            // the slow path which marks them dirty
+            if DIRTY_TRACKING.load(Ordering::Acquire) {
+                mask |= PageTableFlags::DIRTY;
+            }
+        }
+
+        let cnt = output.len();
+        let pagetable = self.pagetable.read();
+
+        let mut start = start;
+        let end = start + len;
+        while start < end {
+            let next = if Addr(start).IsPageAligned() {
+                start + MemoryDef::PAGE_SIZE
+            } else {
+                Addr(start).RoundUp().unwrap().0
+            };
+
+            let entry = match pagetable.pt.VirtualToEntry(start) {
+                Err(_) => {
+                    output.truncate(cnt);
+                    return false;
+                }
+                Ok(e) => e,
+            };
+
+            if entry.flags() & mask != mask {
+                output.truncate(cnt);
+                return false;
+            }
+
+            let iov = IoVec {
+                start: entry.addr().as_u64() + (start & (MemoryDef::PAGE_SIZE - 1)),
+                len: if end < next {
+                    (end - start) as usize
+                } else {
+                    (next - start) as usize
+                },
+            };
+
+            let last = output.len();
+            if merge && last > cnt && output[last - 1].End() == iov.start {
+                output[last - 1].len += iov.len;
+            } else {
+                output.push(iov);
+            }
+
+            start = next;
+        }
+
+        return true;
+    }
+
     pub fn V2P(
         &self,
         task: &Task,
@@ -1049,6 +1317,10 @@ impl MemoryManager {
             return Err(Error::SysError(SysErr::EFAULT));
         }
 
+        if self.V2PFast(start, len, output, writable, true) {
+            return Ok(());
+        }
+
         let _ml = self.MappingWriteLock();
 
         return self.V2PLocked(task, start, len, output, writable, allowPartial);
@@ -1075,6 +1347,9 @@ impl MemoryManager {
         }
 
         let len = self.FixPermissionLocked(task, start, len, writable, allowPartial)?;
+        if writable && len > 0 {
+            self.MarkDirty(start, len)?;
+        }
 
         let mut start = start;
         let end = start + len;
@@ -1164,69 +1439,102 @@ impl MemoryManager {
             return Ok(len);
         }
 
+        let end = vAddr + len;
         let mut addr = Addr(vAddr).RoundDown()?.0;
         let mut needTLBShootdown = false;
+        let mut ret = Ok(len);
         //error!("FixPermission vaddr {:x} addr {:x} len is {:x}", vAddr, addr, len);
-        while addr <= vAddr + len - 1 {
-            let (_, permission) = match self.VirtualToPhyLocked(addr) {
-                Err(Error::AddressNotMap(_)) => {
-                    match self.InstallPageWithAddrLocked(task, addr) {
-                        Err(_) => {
-                            if !allowPartial || addr < vAddr {
-                                return Err(Error::SysError(SysErr::EFAULT));
-                            }
 
-                            if needTLBShootdown {
-                                self.TlbShootdown();
-                            }
-                            return Ok(addr - vAddr);
-                        }
-                        Ok(()) => (),
-                    }
-                    self.VirtualToPhyLocked(addr)?
+        // walk the range vma by vma, the vma is looked up once for each of them
+        'vmas: while addr < end {
+            let (vma, range) = match self.GetVmaAndRangeLocked(addr) {
+                None => {
+                    ret = Self::PartialLen(vAddr, addr, allowPartial);
+                    break;
                 }
-                Err(e) => return Err(e),
-                Ok(ret) => ret,
+                Some(data) => data,
             };
 
-            let (vma, _) = match self.GetVmaAndRangeLocked(addr) {
-                None => {
-                    if !allowPartial || addr < vAddr {
-                        return Err(Error::SysError(SysErr::EFAULT));
-                    }
+            if writeReq && !vma.effectivePerms.Write() {
+                ret = Self::PartialLen(vAddr, addr, allowPartial);
+                break;
+            }
+
+            let cow = vma.maxPerms.Write();
+            let runEnd = if end < range.End() { end } else { range.End() };
+            while addr < runEnd {
+                addr = self.MappedRunLocked(addr, runEnd, cow);
+                if addr >= runEnd {
+                    break;
+                }
 
-                    if needTLBShootdown {
-                        self.TlbShootdown();
+                let permission = match self.VirtualToPhyLocked(addr) {
+                    Err(Error::AddressNotMap(_)) => {
+                        match self.InstallPageLocked(task, &vma, addr, &range) {
+                            Err(_) => {
+                                ret = Self::PartialLen(vAddr, addr, allowPartial);
+                                break 'vmas;
+                            }
+                            Ok(()) => (),
+                        }
+                        self.VirtualToPhyLocked(addr)?.1
                     }
+                    Err(e) => {
+                        ret = Err(e);
+                        break 'vmas;
+                    }
+                    Ok((_, permission)) => permission,
+                };
 
-                    return Ok(addr - vAddr);
+                if cow && !permission.Write() {
+                    self.CopyOnWriteLocked(addr, &vma);
+                    needTLBShootdown = true;
                 }
-                Some(vma) => vma.clone(),
-            };
 
-            if vma.maxPerms.Write() && !permission.Write() {
-                self.CopyOnWriteLocked(addr, &vma);
-                needTLBShootdown = true;
+                addr += MemoryDef::PAGE_SIZE;
             }
+        }
 
-            if writeReq && !vma.effectivePerms.Write() {
-                if !allowPartial || addr < vAddr {
-                    return Err(Error::SysError(SysErr::EFAULT));
-                }
+        // the tlb of the cowed pages is flushed once for the whole range
+        if needTLBShootdown {
+            self.TlbShootdown();
+        }
+        return ret;
+    }
 
-                if needTLBShootdown {
-                    self.TlbShootdown();
-                }
-                return Ok(addr - vAddr);
+    fn PartialLen(vAddr: u64, addr: u64, allowPartial: bool) -> Result<u64> {
+        if !allowPartial || addr < vAddr {
+            return Err(Error::SysError(SysErr::EFAULT));
+        }
+
+        return Ok(addr - vAddr);
+    }
+
+    // MappedRunLocked skips the pages from addr which are mapped and need no
+    // cow, under one pagetable read lock. It returns the first page which needs
+    // to be installed or cowed, or end.
+    fn MappedRunLocked(&self, addr: u64, end: u64, cow: bool) -> u64 {
+        let pagetable = self.pagetable.read();
+        let mut addr = addr;
+        while addr < end {
+            let entry = match pagetable.pt.VirtualToEntry(addr) {
+                Err(_) => return addr,
+                Ok(e) => e,
+            };
+
+            let flags = entry.flags();
+            if flags & PageTableFlags::PRESENT != PageTableFlags::PRESENT {
+                return addr;
+            }
+
+            if cow && flags & PageTableFlags::WRITABLE != PageTableFlags::WRITABLE {
+                return addr;
             }
 
             addr += MemoryDef::PAGE_SIZE;
         }
 
-        if needTLBShootdown {
-            self.TlbShootdown();
-        }
-        return Ok(len);
+        return end;
     }
 
     pub fn PopulateVMALocked(
@@ -1276,7 +1584,8 @@ impl MemoryManager {
                     AccessType(perms.0 & !MmapProt::PROT_WRITE)
                 };
 
-                if precommit && segAr.Len() < 0x200000 {
+                // the pages of a vma with a protection key are mapped by the page faults
+                if precommit && segAr.Len() < 0x200000 && vma.pkey == 0 {
                     self.pagetable.write().pt.MapFile(
                         task,
                         ar.Start(),
@@ -1325,6 +1634,7 @@ impl MemoryManager {
             oldar.Start(),
             &perms,
             true,
+            vma.pkey,
         )?;
 
         return Ok(());
@@ -1351,7 +1661,10 @@ impl MemoryManager {
             let mut mappingInternal2 = mm2.mapping.lock();
 
             mappingInternal2.brkInfo = mappingInternal1.brkInfo;
+            mappingInternal2.pkeys = mappingInternal1.pkeys;
             mappingInternal2.usageAS = mappingInternal1.usageAS;
+            // the child commits the accounted vmas of the parent
+            mappingInternal2.Commit(mappingInternal1.committedAS)?;
             mappingInternal2.lockedAS = 0;
             let range = mappingInternal1.vmas.range;
             mappingInternal2.vmas.Reset(range.Start(), range.Len());
@@ -1363,6 +1676,7 @@ impl MemoryManager {
             ptInternal2.curRSS = ptInternal1.curRSS;
             ptInternal2.maxRSS = ptInternal1.maxRSS;
             ptInternal2.pt = ptInternal1.pt.Fork(&*PAGE_MGR)?;
+            mm2.root.store(ptInternal2.pt.GetRoot(), Ordering::Release);
 
             let mut srcvseg = mappingInternal1.vmas.FirstSeg();
             let mut dstvgap = mappingInternal2.vmas.FirstGap();
@@ -1375,12 +1689,17 @@ impl MemoryManager {
             meta2.argv = meta1.argv;
             meta2.envv = meta1.envv;
             meta2.executable = meta1.executable.clone();
+            meta2.wxEnforce = meta1.wxEnforce;
+            meta2.vdso = meta1.vdso;
 
             while srcvseg.Ok() {
                 let mut vma = srcvseg.Value();
 
                 if vma.dontfork {
                     mappingInternal2.usageAS -= srcvseg.Range().Len();
+                    if vma.account {
+                        mappingInternal2.Uncommit(srcvseg.Range().Len());
+                    }
                     let tmp = srcvseg.NextSeg();
                     srcvseg = tmp;
                     continue;
@@ -1416,6 +1735,7 @@ impl MemoryManager {
                             &ptInternal2.pt,
                             vmaAR.Start(),
                             vmaAR.Len(),
+                            vma.pkey,
                             &*PAGE_MGR,
                         )?;
                     } else {
@@ -1517,10 +1837,82 @@ impl MemoryManager {
         output: &mut Vec<IoVec>,
         writable: bool,
     ) -> Result<()> {
+        if len > 0 && start != 0 && self.V2PFast(start, len, output, writable, false) {
+            return Ok(());
+        }
+
         let _ml = self.MappingWriteLock();
         return self.V2PIovLocked(task, start, len, output, writable);
     }
 
+    // PinIovs translates the user iovecs to the physical iovecs and takes a
+    // reference on their pages, so that the pages stay allocated while the host
+    // does the io without the mapping lock, e.g. when another thread munmaps
+    // them. When writable, the cow of the pages is broken first. The pages of
+    // the host file mappings are not refcounted by the PagePool and are kept by
+    // the file mapping itself.
+    pub fn PinIovs(
+        &self,
+        task: &Task,
+        iovs: &[IoVec],
+        writable: bool,
+        output: &mut Vec<IoVec>,
+    ) -> Result<PinnedPages> {
+        let _ml = self.MappingWriteLock();
+        for iov in iovs {
+            if iov.len == 0 {
+                continue;
+            }
+
+            if iov.start == 0 {
+                return Err(Error::SysError(SysErr::EFAULT));
+            }
+
+            self.V2PLocked(task, iov.start, iov.len as u64, output, writable, false)?;
+        }
+
+        let mut pages = Vec::new();
+        for iov in output.iter() {
+            let mut page = Addr(iov.start).RoundDown()?.0;
+            while page < iov.End() {
+                pages.push(page);
+                page += MemoryDef::PAGE_SIZE;
+            }
+        }
+
+        PAGE_MGR.RefPages(&pages);
+        let mut pinned = PinnedPages {
+            pages: pages,
+            written: Vec::new(),
+            mm: None,
+        };
+
+        if writable {
+            pinned.written = iovs.iter().filter(|iov| iov.len > 0).cloned().collect();
+            pinned.mm = Some(self.clone());
+            HOST_WRITES.fetch_add(1, Ordering::SeqCst);
+        }
+
+        return Ok(pinned);
+    }
+
+    // MarkDirty marks the user pages in [start, start + len) as written. The
+    // kernel and the host write the user memory through the physical addresses,
+    // so the page table doesn't see the writes. It is a no-op unless the dirty
+    // pages are tracked.
+    pub fn MarkDirty(&self, start: u64, len: u64) -> Result<()> {
+        if !DIRTY_TRACKING.load(Ordering::Acquire) {
+            return Ok(());
+        }
+
+        let end = Addr(start + len).RoundUp()?;
+        return self
+            .pagetable
+            .write()
+            .pt
+            .MarkDirty(Addr(start).RoundDown()?, end);
+    }
+
     pub fn V2PIovLocked(
         &self,
         task: &Task,
@@ -1529,7 +1921,10 @@ impl MemoryManager {
         output: &mut Vec<IoVec>,
         writable: bool,
     ) -> Result<()> {
-        self.FixPermissionLocked(task, start, len, writable, false)?;
+        let len = self.FixPermissionLocked(task, start, len, writable, false)?;
+        if writable && len > 0 {
+            self.MarkDirty(start, len)?;
+        }
 
         let mut start = start;
         let end = start + len;
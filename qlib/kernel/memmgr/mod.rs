@@ -13,11 +13,13 @@
 // limitations under the License.
 
 pub mod arch;
+pub mod balloon;
 mod mapping;
 pub mod mapping_set;
 pub mod memmap;
 pub mod metadata;
 pub mod mm;
+pub mod overcommit;
 pub mod pma;
 pub mod pmamgr;
 pub mod syscalls;
@@ -136,6 +138,10 @@ pub struct MMapOpts {
     // mapping (see platform.AddressSpace.MapFile).
     pub Precommit: bool,
 
+    // NoReserve is MAP_NORESERVE, the heuristic overcommit doesn't check the
+    // mapping.
+    pub NoReserve: bool,
+
     // MLockMode specifies the memory locking behavior of the mapping.
     pub MLockMode: MLockMode,
 
@@ -164,6 +170,7 @@ impl MMapOpts {
             VDSO: false,
             GrowsDown: false,
             Precommit: false,
+            NoReserve: false,
             MLockMode: MLockMode::default(),
             Kernel: false,
             Mapping: Some(NewAnonMapping(name.to_string())),
@@ -186,6 +193,7 @@ impl MMapOpts {
             VDSO: false,
             GrowsDown: false,
             Precommit: false,
+            NoReserve: false,
             MLockMode: MLockMode::default(),
             Kernel: false,
             Mapping: Some(Arc::new(file.clone())),
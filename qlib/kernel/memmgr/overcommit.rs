@@ -0,0 +1,165 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use core::sync::atomic::AtomicI32;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering;
+
+use super::super::super::common::*;
+use super::super::super::linux_def::*;
+use super::super::Kernel::HostSpace;
+use super::super::SHARESPACE;
+
+pub static OVERCOMMIT: Overcommit = Overcommit::New();
+
+// the modes of vm.overcommit_memory
+pub const OVERCOMMIT_GUESS: i32 = 0;
+pub const OVERCOMMIT_ALWAYS: i32 = 1;
+pub const OVERCOMMIT_NEVER: i32 = 2;
+
+// Overcommit is the vm.overcommit_* policy of the sandbox. As Linux, the
+// commit charge of a process is the size of its private writable mappings,
+// i.e. the committedAS of its memory manager.
+pub struct Overcommit {
+    // mode is vm.overcommit_memory, the config OvercommitMemory until it is
+    // set by the sysctl
+    pub mode: AtomicI32,
+    // ratio is vm.overcommit_ratio, the percent of the memory which can be
+    // committed in the strict mode
+    pub ratio: AtomicU64,
+    // kbytes is vm.overcommit_kbytes, it replaces ratio when it isn't 0
+    pub kbytes: AtomicU64,
+    // committed is the committedAS of all the memory managers, i.e.
+    // Committed_AS
+    pub committed: AtomicU64,
+    // totalRam is the memory of the sandbox in bytes, sampled at boot
+    pub totalRam: AtomicU64,
+}
+
+impl Overcommit {
+    pub const fn New() -> Self {
+        return Self {
+            mode: AtomicI32::new(-1),
+            ratio: AtomicU64::new(50),
+            kbytes: AtomicU64::new(0),
+            committed: AtomicU64::new(0),
+            totalRam: AtomicU64::new(0),
+        };
+    }
+
+    // Init samples the memory of the sandbox, the host memory or the memory
+    // limit of the sandbox cgroup if it is lower.
+    pub fn Init(&self) {
+        let mut info = LibcSysinfo::default();
+        let ret = HostSpace::Sysinfo(&mut info as *mut _ as u64);
+        if ret < 0 {
+            info!("Overcommit::Init sysinfo fail with error {}", ret);
+            return;
+        }
+
+        let mut total = info.totalram * info.mem_unit as u64;
+        let limit = SHARESPACE.cgroup.lock().MemoryMax;
+        if limit > 0 && limit < total {
+            total = limit;
+        }
+
+        self.totalRam.store(total, Ordering::Relaxed);
+    }
+
+    pub fn Mode(&self) -> i32 {
+        let mode = self.mode.load(Ordering::Relaxed);
+        if mode >= 0 {
+            return mode;
+        }
+
+        return SHARESPACE.config.read().OvercommitMemory;
+    }
+
+    pub fn SetMode(&self, mode: i32) -> Result<()> {
+        if mode < OVERCOMMIT_GUESS || mode > OVERCOMMIT_NEVER {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        self.mode.store(mode, Ordering::Relaxed);
+        return Ok(());
+    }
+
+    // as Linux, the ratio and the kbytes exclude each other
+    pub fn SetRatio(&self, ratio: u64) {
+        self.ratio.store(ratio, Ordering::Relaxed);
+        self.kbytes.store(0, Ordering::Relaxed);
+    }
+
+    pub fn SetKbytes(&self, kbytes: u64) {
+        self.kbytes.store(kbytes, Ordering::Relaxed);
+        self.ratio.store(0, Ordering::Relaxed);
+    }
+
+    // CommitLimit is the memory which can be committed in the strict mode. The
+    // sandbox has no swap.
+    pub fn CommitLimit(&self) -> u64 {
+        let kbytes = self.kbytes.load(Ordering::Relaxed);
+        if kbytes > 0 {
+            return kbytes * 1024;
+        }
+
+        let ratio = self.ratio.load(Ordering::Relaxed);
+        return self.totalRam.load(Ordering::Relaxed) / 100 * ratio;
+    }
+
+    pub fn Committed(&self) -> u64 {
+        return self.committed.load(Ordering::Relaxed);
+    }
+
+    // Charge commits len bytes, or fails with ENOMEM when the policy refuses
+    // them. The check and the charge are one atomic operation so that the
+    // concurrent commits can't pass CommitLimit together. The heuristic mode
+    // only refuses the commits larger than the memory of the sandbox.
+    pub fn Charge(&self, len: u64) -> Result<()> {
+        match self.Mode() {
+            OVERCOMMIT_ALWAYS => (),
+            OVERCOMMIT_NEVER => {
+                let limit = self.CommitLimit();
+                let mut committed = self.committed.load(Ordering::Relaxed);
+                loop {
+                    if committed + len > limit {
+                        return Err(Error::SysError(SysErr::ENOMEM));
+                    }
+
+                    match self.committed.compare_exchange(
+                        committed,
+                        committed + len,
+                        Ordering::Relaxed,
+                        Ordering::Relaxed,
+                    ) {
+                        Ok(_) => return Ok(()),
+                        Err(c) => committed = c,
+                    }
+                }
+            }
+            _ => {
+                let total = self.totalRam.load(Ordering::Relaxed);
+                if total > 0 && len > total {
+                    return Err(Error::SysError(SysErr::ENOMEM));
+                }
+            }
+        }
+
+        self.committed.fetch_add(len, Ordering::Relaxed);
+        return Ok(());
+    }
+
+    pub fn Uncharge(&self, len: u64) {
+        self.committed.fetch_sub(len, Ordering::Relaxed);
+    }
+}
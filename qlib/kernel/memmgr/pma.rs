@@ -99,6 +99,22 @@ impl PageMgr {
     pub fn DerefPage(&self, addr: u64) {
         self.lock().allocator.lock().Deref(addr).unwrap();
     }
+
+    pub fn RefPages(&self, pages: &[u64]) {
+        let intern = self.lock();
+        let mut allocator = intern.allocator.lock();
+        for page in pages {
+            allocator.Ref(*page).unwrap();
+        }
+    }
+
+    pub fn DerefPages(&self, pages: &[u64]) {
+        let intern = self.lock();
+        let mut allocator = intern.allocator.lock();
+        for page in pages {
+            allocator.Deref(*page).unwrap();
+        }
+    }
 }
 
 pub struct PageMgrInternal {
@@ -285,22 +301,24 @@ impl PageTables {
         oldStart: u64,
         at: &AccessType,
         user: bool,
+        pkey: i32,
     ) -> Result<()> {
-        let pageOpts = if user {
+        let mut pageOpts = if user {
             if at.Write() {
-                PageOpts::UserReadWrite().Val()
+                PageOpts::UserReadWrite()
             } else if at.Read() || at.Exec() {
-                PageOpts::UserReadOnly().Val()
+                PageOpts::UserReadOnly()
             } else {
-                PageOpts::UserNonAccessable().Val()
+                PageOpts::UserNonAccessable()
             }
         } else {
             if at.Write() {
-                PageOpts::KernelReadWrite().Val()
+                PageOpts::KernelReadWrite()
             } else {
-                PageOpts::KernelReadOnly().Val()
+                PageOpts::KernelReadOnly()
             }
         };
+        let pageOpts = pageOpts.SetPkey(pkey).Val();
 
         self.Remap(
             Addr(newAddrRange.Start()),
@@ -12,13 +12,16 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use alloc::vec::Vec;
 use core::u64;
 
 use super::super::super::addr::*;
 use super::super::super::common::*;
+use super::super::super::device::*;
 use super::super::super::linux::limits::*;
 use super::super::super::linux_def::*;
 use super::super::super::range::*;
+use super::super::fs::host::hostinodeop::*;
 use super::super::kernel::futex::*;
 use super::super::memmgr::mm::*;
 use super::super::memmgr::vma::*;
@@ -131,6 +134,7 @@ impl MemoryManager {
                 VDSO: false,
                 GrowsDown: true,
                 Precommit: false,
+                NoReserve: false,
                 MLockMode: MLockMode::default(),
                 Kernel: false,
                 Mapping: None,
@@ -166,6 +170,71 @@ impl MemoryManager {
         return ret;
     }
 
+    // DetachShm implements the memory part of shmdt(2). It unmaps the vmas of
+    // the SysV shared memory segment attached at addr and returns the mappable
+    // of the segment.
+    pub fn DetachShm(&self, _task: &Task, addr: u64) -> Result<HostInodeOp> {
+        let _ml = self.MappingWriteLock();
+
+        if addr != Addr(addr).RoundDown()?.0 {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        let shmDevice = SHM_DEVICE.lock().DeviceID();
+        let mut mappable: Option<HostInodeOp> = None;
+        let mut end = u64::MAX;
+        let mut ars = Vec::new();
+        {
+            let mapping = self.mapping.lock();
+            let mut vseg = mapping.vmas.LowerBoundSeg(addr);
+            while vseg.Ok() && vseg.Range().Start() < end {
+                let vr = vseg.Range();
+                let vma = vseg.Value();
+                vseg = vseg.NextSeg();
+
+                // the vmas of the segment keep their offset into the segment
+                // when it is partly unmapped or mprotected
+                let isShm = match &vma.id {
+                    Some(id) => id.DeviceID() == shmDevice,
+                    None => false,
+                };
+
+                if !isShm || vr.Start() < addr || vr.Start() - addr != vma.offset {
+                    continue;
+                }
+
+                match &mappable {
+                    None => {
+                        // the first vma found decides the segment to detach
+                        let m = vma.mappable.clone().unwrap();
+                        let (size, _) = m.Size()?;
+                        end = Addr(addr).AddLen(size as u64)?.0;
+                        mappable = Some(m);
+                    }
+                    Some(m) => {
+                        if vma.mappable.as_ref() != Some(m) {
+                            continue;
+                        }
+                    }
+                }
+
+                ars.push(vr);
+            }
+        }
+
+        let mappable = match mappable {
+            None => return Err(Error::SysError(SysErr::EINVAL)),
+            Some(m) => m,
+        };
+
+        for ar in &ars {
+            self.RemoveVMAsLocked(ar)?;
+        }
+
+        self.TlbShootdown();
+        return Ok(mappable);
+    }
+
     // MRemap implements the semantics of Linux's mremap(2).
     pub fn MRemap(
         &self,
@@ -257,6 +326,7 @@ impl MemoryManager {
                     VDSO: false,
                     GrowsDown: vma.growsDown,
                     Precommit: false,
+                    NoReserve: vma.noReserve,
                     MLockMode: MLockMode::default(),
                     Kernel: false,
                     Mapping: vma.id.clone(),
@@ -265,7 +335,16 @@ impl MemoryManager {
                 },
             ) {
                 Ok((vseg, ar)) => {
+                    // the grown part keeps the protection key of the vma
+                    if vma.pkey != 0 {
+                        let mut newVma = vseg.Value();
+                        newVma.pkey = vma.pkey;
+                        vseg.SetValue(newVma);
+                    }
                     self.PopulateVMALocked(task, &vseg, &ar, false, false)?; //to true?
+                    if vma.pkey != 0 {
+                        self.mapping.lock().vmas.MergeAdjacent(&ar);
+                    }
                     return Ok(oldAddr);
                 }
                 Err(e) => {
@@ -331,15 +410,39 @@ impl MemoryManager {
         }
 
         let vma = vseg.Value();
-        if vma.mappable.is_some() {
-            if core::u64::MAX - vma.offset < newAR.Len() {
-                return Err(Error::SysError(SysErr::EINVAL));
+
+        if vma.mappable.is_some() && core::u64::MAX - vma.offset < newAR.Len() {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        // the copy and the growth of an accounted vma are new commits
+        let mut commit = 0;
+        if vma.account {
+            if oldSize == 0 {
+                commit = newAR.Len();
+            } else if newAR.Len() > oldAR.Len() {
+                commit = newAR.Len() - oldAR.Len();
             }
+        }
 
+        if commit > 0 {
+            self.mapping.lock().Commit(commit)?;
+        }
+
+        if vma.mappable.is_some() {
             // Inform the Mappable, if any, of the new mapping.
             let mappable = vma.mappable.clone().unwrap();
             let offsetat = vseg.MappableOffsetAt(oldAR.Start());
-            mappable.CopyMapping(self, &oldAR, &newAR, offsetat, vma.CanWriteMappableLocked())?;
+            match mappable.CopyMapping(self, &oldAR, &newAR, offsetat, vma.CanWriteMappableLocked())
+            {
+                Err(e) => {
+                    if commit > 0 {
+                        self.mapping.lock().Uncommit(commit);
+                    }
+                    return Err(e);
+                }
+                Ok(()) => (),
+            }
         }
 
         if oldSize == 0 {
@@ -356,7 +459,7 @@ impl MemoryManager {
 
             let gap = self.mapping.lock().vmas.FindGap(newAR.Start());
             let vseg = self.mapping.lock().vmas.Insert(&gap, &newAR, vma);
-            self.mapping.lock().usageAS += newAR.Len();
+            self.mapping.lock().AddUsageAS(newAR.Len());
             self.PopulateVMALocked(task, &vseg, &newAR, false, false)?;
             return Ok(newAR.Start());
         }
@@ -382,8 +485,11 @@ impl MemoryManager {
         let gap = self.mapping.lock().vmas.FindGap(newAR.Start());
         let vseg = self.mapping.lock().vmas.Insert(&gap, &newAR, vma.clone());
 
-        let usageAS = self.mapping.lock().usageAS;
-        self.mapping.lock().usageAS = usageAS - oldAR.Len() + newAR.Len();
+        {
+            let mut mapping = self.mapping.lock();
+            mapping.usageAS -= oldAR.Len();
+            mapping.AddUsageAS(newAR.Len());
+        }
 
         // Now that pmas have been moved to newAR, we can notify vma.mappable that
         // oldAR is no longer mapped.
@@ -404,6 +510,7 @@ impl MemoryManager {
         len: u64,
         realPerms: &AccessType,
         growsDown: bool,
+        pkey: i32,
     ) -> Result<()> {
         let _ml = self.MappingWriteLock();
 
@@ -463,19 +570,31 @@ impl MemoryManager {
             vseg = mapping.vmas.Isolate(&vseg, &ar);
             // Update vma permissions.
             let mut vma = vseg.Value();
+            // a private vma is charged when it becomes writable, compare Linux's
+            // mm/mprotect.c:mprotect_fixup()
+            if realPerms.Write() && vma.private && !vma.account && !vma.noReserve && !vma.kernel {
+                mapping.Commit(vseg.Range().Len())?;
+                vma.account = true;
+            }
             vma.realPerms = *realPerms;
             vma.effectivePerms = effectivePerms;
+            // pkey -1 keeps the protection key of the vma
+            if pkey != -1 {
+                vma.pkey = pkey;
+            }
 
+            let vmaPkey = vma.pkey;
             vseg.SetValue(vma);
             let range = vseg.Range();
 
-            let pageopts = if effectivePerms.Write() {
-                PageOpts::UserReadWrite().Val()
+            let mut pageopts = if effectivePerms.Write() {
+                PageOpts::UserReadWrite()
             } else if effectivePerms.Read() || effectivePerms.Exec() {
-                PageOpts::UserReadOnly().Val()
+                PageOpts::UserReadOnly()
             } else {
-                PageOpts::UserNonAccessable().Val()
+                PageOpts::UserNonAccessable()
             };
+            let pageopts = pageopts.SetPkey(vmaPkey).Val();
 
             //change pagetable permission
             let mut end = range.End();
@@ -610,6 +729,7 @@ impl MemoryManager {
                     VDSO: false,
                     GrowsDown: false,
                     Precommit: false,
+                    NoReserve: false,
                     MLockMode: MLockMode::default(),
                     Kernel: false,
                     Mapping: None,
@@ -650,9 +770,23 @@ impl MemoryManager {
             return Ok(Key {
                 Kind: KeyKind::KindSharedPrivate,
                 Addr: addr,
+                Mappable: 0,
             });
         }
 
+        // the physical address of a shared page depends on whether the page is
+        // mapped in the process, the file offset doesn't
+        match &vma.mappable {
+            Some(mappable) => {
+                return Ok(Key {
+                    Kind: KeyKind::KindSharedMappable,
+                    Addr: vma.offset + addr - vseg.Range().Start(),
+                    Mappable: mappable.MappableId(),
+                });
+            }
+            None => (),
+        }
+
         self.V2PLocked(task, addr, 4, &mut task.GetMut().iovs, true, false)?;
         defer!(task.GetMut().iovs.clear());
         assert!(task.GetMut().iovs.len() == 1);
@@ -660,6 +794,7 @@ impl MemoryManager {
         return Ok(Key {
             Kind: KeyKind::KindSharedMappable,
             Addr: task.GetMut().iovs[0].start,
+            Mappable: 0,
         });
     }
 
@@ -750,13 +885,17 @@ impl MemoryManager {
         return self.VirtualMemorySizeLocked();
     }
 
+    pub fn MaxVirtualMemorySizeLocked(&self) -> u64 {
+        return self.mapping.lock().maxAS;
+    }
+
     pub fn ResidentSetSizeLocked(&self) -> u64 {
         return self.pagetable.read().curRSS;
     }
 
     pub fn ResidentSetSize(&self) -> u64 {
         let _ml = self.MappingReadLock();
-        return self.ResidentSetSize();
+        return self.ResidentSetSizeLocked();
     }
 
     pub fn MaxResidentSetSizeLocked(&self) -> u64 {
@@ -27,6 +27,7 @@ use super::super::super::mem::areaset::*;
 use super::super::super::range::*;
 use super::arch::*;
 use super::mm::*;
+use super::overcommit::*;
 use super::*;
 
 // map32Start/End are the bounds to which MAP_32BIT mappings are constrained,
@@ -274,17 +275,32 @@ impl MemoryManager {
             self.RemoveVMAsLocked(&ar)?;
         }
 
+        let noReserve = opts.NoReserve && OVERCOMMIT.Mode() != OVERCOMMIT_NEVER;
+        let account = !opts.Kernel && opts.Private && opts.Perms.Write() && !noReserve;
+
         let mut mapping = self.mapping.lock();
+        if account {
+            mapping.Commit(opts.Length)?;
+        }
+
         let gap = mapping.vmas.FindGap(ar.Start());
 
         if opts.Mappable.is_some() {
             let mappable = opts.Mappable.clone().unwrap();
-            mappable.AddMapping(
+            match mappable.AddMapping(
                 self,
                 &ar,
                 opts.Offset,
                 !opts.Private && opts.MaxPerms.Write(),
-            )?;
+            ) {
+                Err(e) => {
+                    if account {
+                        mapping.Uncommit(opts.Length);
+                    }
+                    return Err(e);
+                }
+                Ok(()) => (),
+            }
         }
 
         let vma = VMA {
@@ -303,9 +319,12 @@ impl MemoryManager {
             id: opts.Mapping.clone(),
             numaPolicy: 0,
             numaNodemask: 0,
+            pkey: 0,
+            account: account,
+            noReserve: noReserve,
         };
 
-        mapping.usageAS += opts.Length;
+        mapping.AddUsageAS(opts.Length);
         if opts.MLockMode != MLockMode::MlockNone {
             mapping.lockedAS += opts.Length;
         }
@@ -382,6 +401,17 @@ pub struct VMA {
 
     // numaNodemask is the NUMA nodemask for this vma set by mbind().
     pub numaNodemask: u64,
+
+    // pkey is the memory protection key of this vma set by pkey_mprotect().
+    pub pkey: i32,
+
+    // account is true if the vma is charged to the overcommit policy, i.e.
+    // Linux's VM_ACCOUNT.
+    pub account: bool,
+
+    // noReserve is true if the vma is MAP_NORESERVE and the overcommit policy
+    // isn't strict, i.e. Linux's VM_NORESERVE. Such a vma is never charged.
+    pub noReserve: bool,
 }
 
 impl fmt::Debug for VMA {
@@ -417,6 +447,9 @@ impl VMA {
             id: self.id.clone(),
             numaPolicy: 0,
             numaNodemask: 0,
+            pkey: self.pkey,
+            account: self.account,
+            noReserve: self.noReserve,
         };
 
         return copy;
@@ -527,6 +560,9 @@ impl AreaValue for VMA {
             || vma1.kernel != vma2.kernel
             || vma1.numaPolicy != vma2.numaPolicy
             || vma1.numaNodemask != vma2.numaNodemask
+            || vma1.pkey != vma2.pkey
+            || vma1.account != vma2.account
+            || vma1.noReserve != vma2.noReserve
             || vma1.hint != vma2.hint
         {
             return None;
@@ -13,7 +13,7 @@
 // limitations under the License.
 
 use core::sync::atomic::Ordering;
-use core::sync::atomic::{AtomicBool, AtomicI32, AtomicI64};
+use core::sync::atomic::{AtomicBool, AtomicI32, AtomicI64, AtomicUsize};
 
 use crate::qlib::fileinfo::*;
 
@@ -35,6 +35,8 @@ pub mod SignalDef;
 pub mod arch;
 pub mod asm;
 pub mod boot;
+pub mod bpf;
+pub mod crash;
 pub mod fd;
 pub mod fs;
 pub mod guestfdnotifier;
@@ -48,10 +50,12 @@ pub mod quring;
 pub mod seqcount;
 pub mod socket;
 pub mod stack;
+pub mod strace;
 pub mod task;
 pub mod taskMgr;
 pub mod tcpip;
 pub mod threadmgr;
+pub mod tracing;
 pub mod uid;
 pub mod util;
 pub mod vcpu;
@@ -64,8 +68,8 @@ pub static KERNEL_PAGETABLE: Singleton<PageTables> = Singleton::<PageTables>::Ne
 pub static PAGE_MGR: PageMgrRef = PageMgrRef::New();
 pub static LOADER: Singleton<Loader> = Singleton::<Loader>::New();
 pub static WAIT_CONTAINER_FD: AtomicI32 = AtomicI32::new(-1);
-pub static KERNEL_STACK_ALLOCATOR: Singleton<AlignedAllocator> =
-    Singleton::<AlignedAllocator>::New();
+pub static KERNEL_STACK_ALLOCATOR: Singleton<KernelStackAllocator> =
+    Singleton::<KernelStackAllocator>::New();
 
 pub static EXIT_CODE: Singleton<AtomicI32> = Singleton::<AtomicI32>::New();
 pub static VCPU_FREQ: AtomicI64 = AtomicI64::new(2_000_000_000); // default 2GHZ
@@ -73,6 +77,9 @@ pub static ASYNC_PROCESS: AsyncProcess = AsyncProcess::New();
 pub static FP_STATE: X86fpstate = X86fpstate::Init();
 pub static SUPPORT_XSAVE: AtomicBool = AtomicBool::new(false);
 pub static SUPPORT_XSAVEOPT: AtomicBool = AtomicBool::new(false);
+pub static SUPPORT_PKU: AtomicBool = AtomicBool::new(false);
+// PKRU_OFFSET is the offset of the pkru in the xsave area
+pub static PKRU_OFFSET: AtomicUsize = AtomicUsize::new(0);
 
 pub fn SetWaitContainerfd(fd: i32) {
     WAIT_CONTAINER_FD.store(fd, Ordering::SeqCst)
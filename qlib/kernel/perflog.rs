@@ -20,6 +20,7 @@ use core::mem;
 use super::super::perf_tunning::*;
 use super::super::singleton::*;
 use super::task::*;
+use super::tracing::*;
 use super::uid::*;
 
 pub static THREAD_COUNTS: Singleton<QMutex<ThreadPerfCounters>> =
@@ -98,12 +99,14 @@ impl ThreadPerfCounters {
 
 impl Task {
     pub fn PerfGoto(&self, typ: PerfType) {
+        TRACER.Record(typ, true, self.taskId);
         if let Some(counts) = &self.perfcounters {
             counts.Goto(0, typ)
         }
     }
 
     pub fn PerfGofrom(&self, typ: PerfType) {
+        TRACER.Record(typ, false, self.taskId);
         if let Some(counts) = &self.perfcounters {
             counts.Gofrom(0, typ)
         }
@@ -13,6 +13,7 @@
 // limitations under the License.
 
 pub mod uring_async;
+pub mod uring_future;
 pub mod uring_mgr;
 pub mod uring_op;
 
@@ -36,8 +36,13 @@ use super::super::kernel::waiter::qlock::*;
 use super::super::kernel::waiter::*;
 use super::super::socket::hostinet::socket::*;
 use super::super::task::*;
+use super::super::threadmgr::task_usermem::*;
+use super::super::GlobalIOMgr;
+use super::super::Kernel::HostSpace;
 use super::super::IOURING;
 use super::super::SHARESPACE;
+use super::uring_future::*;
+use super::uring_op::*;
 //use super::super::guestfdnotifier::GUEST_NOTIFIER;
 
 #[repr(align(128))]
@@ -64,6 +69,7 @@ pub enum AsyncOps {
     AsyncEpollCtl(AsyncEpollCtl),
     AsyncSend(AsyncSend),
     PollHostEpollWait(PollHostEpollWait),
+    AsyncUringCall(AsyncUringCall),
     None,
 }
 
@@ -92,6 +98,7 @@ impl AsyncOps {
             AsyncOps::AsyncEpollCtl(ref msg) => return msg.SEntry(),
             AsyncOps::AsyncSend(ref msg) => return msg.SEntry(),
             AsyncOps::PollHostEpollWait(ref msg) => return msg.SEntry(),
+            AsyncOps::AsyncUringCall(ref msg) => return msg.SEntry(),
             AsyncOps::None => (),
         };
 
@@ -122,6 +129,7 @@ impl AsyncOps {
             AsyncOps::AsyncEpollCtl(ref mut msg) => msg.Process(result),
             AsyncOps::AsyncSend(ref mut msg) => msg.Process(result),
             AsyncOps::PollHostEpollWait(ref mut msg) => msg.Process(result),
+            AsyncOps::AsyncUringCall(ref mut msg) => msg.Process(result),
             AsyncOps::None => {
                 //panic!("AsyncOps::None SEntry fail")
                 panic!("AsyncOps::None SEntry fail result {} id {}", result, id);
@@ -160,6 +168,7 @@ impl AsyncOps {
             AsyncOps::AsyncEpollCtl(_) => return 20,
             AsyncOps::AsyncSend(_) => return 21,
             AsyncOps::PollHostEpollWait(_) => return 22,
+            AsyncOps::AsyncUringCall(_) => return 23,
             AsyncOps::None => (),
         };
 
@@ -382,6 +391,31 @@ impl AsyncStatx {
     }
 }
 
+// AsyncUringCall completes a UringFuture instead of waking a fixed task, so
+// the submitter can poll the result or block on it later.
+pub struct AsyncUringCall {
+    pub msg: UringOp,
+    pub future: UringFuture,
+}
+
+impl AsyncUringCall {
+    pub fn New(msg: UringOp, future: &UringFuture) -> Self {
+        return Self {
+            msg: msg,
+            future: future.clone(),
+        };
+    }
+
+    pub fn SEntry(&self) -> squeue::Entry {
+        return self.msg.SEntry();
+    }
+
+    pub fn Process(&mut self, result: i32) -> bool {
+        self.future.Complete(result as i64);
+        return false;
+    }
+}
+
 pub struct AsyncTTYWrite {
     pub file: File,
     pub fd: i32,
@@ -449,6 +483,8 @@ pub struct AsyncBufWrite {
     pub buf: DataBuff,
     pub offset: i64,
     pub lockGuard: QAsyncLockGuard,
+    // keep the mount from being synced or frozen until the write completes
+    pub barrier: QAsyncReadLockGuard,
 }
 
 impl AsyncBufWrite {
@@ -469,12 +505,19 @@ impl AsyncBufWrite {
         return false;
     }
 
-    pub fn New(fd: i32, buf: DataBuff, offset: i64, lockGuard: QAsyncLockGuard) -> Self {
+    pub fn New(
+        fd: i32,
+        buf: DataBuff,
+        offset: i64,
+        lockGuard: QAsyncLockGuard,
+        barrier: QAsyncReadLockGuard,
+    ) -> Self {
         return Self {
             fd,
             buf,
             offset,
             lockGuard,
+            barrier,
         };
     }
 }
@@ -694,8 +737,15 @@ impl AsyncAccept {
         }
 
         NewSocket(result);
+        if self.acceptQueue.lock().Overflow() {
+            // only reachable with abortOnOverflow, the producer stops otherwise
+            HostSpace::ResetSocket(result);
+            self.len = 16;
+            return true;
+        }
+
         let sockBuf = Arc::new(SocketBuff::default());
-        let (trigger, hasSpace) = self
+        let (trigger, keepAccepting) = self
             .acceptQueue
             .lock()
             .EnqSocket(result, self.addr, self.len, sockBuf);
@@ -704,7 +754,7 @@ impl AsyncAccept {
         }
         self.len = 16;
 
-        return hasSpace;
+        return keepAccepting;
     }
 
     pub fn New(fd: i32, queue: Queue, acceptQueue: AcceptQueue) -> Self {
@@ -974,7 +1024,7 @@ impl AIOWrite {
         cbAddr: u64,
         eventfops: Option<EventOperations>,
     ) -> Result<Self> {
-        let vec = task.CopyInVec(cb.buf, cb.bytes as usize)?;
+        let vec = UserSlice::New(cb.buf, cb.bytes as usize).Read(task)?;
         let buf = DataBuff { buf: vec };
 
         return Ok(Self {
@@ -1361,9 +1411,22 @@ impl AsyncEpollCtl {
         //.flags(squeue::Flags::FIXED_FILE);
     }
 
-    pub fn Process(&mut self, _result: i32) -> bool {
+    pub fn Process(&mut self, result: i32) -> bool {
         //assert!(result >= 0, "AsyncEpollCtl process fail fd is {} {}, {:?}", self.fd, result, self);
 
+        // the host fd registration doesn't match the guest view, e.g. the fd
+        // was closed and reused. fix the op and rerun it.
+        if result == -SysErr::EEXIST && self.op == LibcConst::EPOLL_CTL_ADD as i32 {
+            self.op = LibcConst::EPOLL_CTL_MOD as i32;
+            return true;
+        }
+
+        if result == -SysErr::ENOENT && self.op == LibcConst::EPOLL_CTL_MOD as i32 {
+            self.op = LibcConst::EPOLL_CTL_ADD as i32;
+            return true;
+        }
+
+        GlobalIOMgr().EpollCtlDone(self.fd);
         return false;
     }
 }
@@ -0,0 +1,128 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::qlib::mutex::*;
+use alloc::sync::Arc;
+use core::ops::Deref;
+
+use super::super::super::common::*;
+use super::super::super::linux_def::*;
+use super::super::kernel::waiter::*;
+use super::super::task::*;
+
+#[derive(Default)]
+pub struct UringFutureInternal {
+    pub ret: i64,
+    pub done: bool,
+    pub queue: Queue,
+}
+
+// UringFuture is the completion handle of an asynchronous uring call. The
+// submitting task can keep running and poll it, or block on it and give up
+// its vcpu until the host completes the operation.
+#[derive(Default, Clone)]
+pub struct UringFuture(Arc<QMutex<UringFutureInternal>>);
+
+impl Deref for UringFuture {
+    type Target = Arc<QMutex<UringFutureInternal>>;
+
+    fn deref(&self) -> &Arc<QMutex<UringFutureInternal>> {
+        &self.0
+    }
+}
+
+impl UringFuture {
+    pub fn New() -> Self {
+        return Self::default();
+    }
+
+    // called from the uring completion path
+    pub fn Complete(&self, ret: i64) {
+        let queue = {
+            let mut f = self.lock();
+            f.ret = ret;
+            f.done = true;
+            f.queue.clone()
+        };
+
+        queue.Notify(READABLE_EVENT);
+    }
+
+    pub fn Poll(&self) -> Option<i64> {
+        let f = self.lock();
+        if f.done {
+            return Some(f.ret);
+        }
+
+        return None;
+    }
+
+    //if return == true, it is blocked, otherwise it can return
+    fn Register(&self, task: &Task, e: &WaitEntry) -> bool {
+        let f = self.lock();
+        if f.done {
+            return false;
+        }
+
+        e.Clear();
+        f.queue.EventRegister(task, e, READABLE_EVENT);
+        return true;
+    }
+
+    // Wait blocks the task until the operation completes. It can't be
+    // interrupted, so it is the one to use when the host may still access
+    // the caller's memory.
+    pub fn Wait(&self, task: &Task) -> i64 {
+        let blocker = task.blocker.clone();
+        if self.Register(task, &blocker.generalEntry) {
+            blocker.BlockGeneralOnly();
+            self.EventUnregister(task, &blocker.generalEntry);
+        }
+
+        return self.Poll().expect("UringFuture::Wait not done");
+    }
+
+    // WaitInterruptible returns ErrInterrupted on signal. The operation keeps
+    // running and its result is dropped unless the future is polled again.
+    pub fn WaitInterruptible(&self, task: &Task) -> Result<i64> {
+        let blocker = task.blocker.clone();
+        if self.Register(task, &blocker.generalEntry) {
+            let res = blocker.BlockGeneral();
+            self.EventUnregister(task, &blocker.generalEntry);
+            res?;
+        }
+
+        return Ok(self
+            .Poll()
+            .expect("UringFuture::WaitInterruptible not done"));
+    }
+}
+
+impl Waitable for UringFuture {
+    fn Readiness(&self, _task: &Task, mask: EventMask) -> EventMask {
+        if self.lock().done {
+            return mask & READABLE_EVENT;
+        }
+
+        return 0;
+    }
+
+    fn EventRegister(&self, task: &Task, e: &WaitEntry, mask: EventMask) {
+        self.lock().queue.EventRegister(task, e, mask);
+    }
+
+    fn EventUnregister(&self, task: &Task, e: &WaitEntry) {
+        self.lock().queue.EventUnregister(task, e);
+    }
+}
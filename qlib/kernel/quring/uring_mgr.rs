@@ -28,6 +28,7 @@ pub use super::super::super::uring::*;
 use super::super::fs::file::*;
 use super::super::task::*;
 use super::super::taskMgr::*;
+use super::super::TSC;
 
 use super::super::super::super::kernel_def::*;
 use super::super::super::linux_def::*;
@@ -42,6 +43,7 @@ use super::super::Kernel::HostSpace;
 use super::super::IOURING;
 use super::super::SHARESPACE;
 use super::uring_async::*;
+use super::uring_future::*;
 use super::uring_op::*;
 
 pub fn QUringTrigger() -> usize {
@@ -248,6 +250,30 @@ impl QUring {
         return self.UCall(task, msg);
     }
 
+    // Readv reads into the physical iovecs, the pages of them must stay pinned
+    // until it returns
+    pub fn Readv(&self, task: &Task, fd: i32, iovs: &[IoVec], offset: i64) -> i64 {
+        let msg = UringOp::Readv(ReadvOp {
+            fd: fd,
+            iovs: &iovs[0] as *const _ as u64,
+            iovcnt: iovs.len() as u32,
+            offset: offset,
+        });
+
+        return self.UCall(task, msg);
+    }
+
+    pub fn Writev(&self, task: &Task, fd: i32, iovs: &[IoVec], offset: i64) -> i64 {
+        let msg = UringOp::Writev(WritevOp {
+            fd: fd,
+            iovs: &iovs[0] as *const _ as u64,
+            iovcnt: iovs.len() as u32,
+            offset: offset,
+        });
+
+        return self.UCall(task, msg);
+    }
+
     pub fn Splice(
         &self,
         task: &Task,
@@ -311,6 +337,28 @@ impl QUring {
         return self.UCall(task, msg);
     }
 
+    pub fn Fallocate(&self, task: &Task, fd: i32, mode: i32, offset: i64, len: i64) -> i64 {
+        let msg = UringOp::Fallocate(FallocateOp {
+            fd: fd,
+            mode: mode,
+            offset: offset,
+            len: len,
+        });
+
+        return self.ACall(msg).Wait(task);
+    }
+
+    pub fn SyncFileRange(&self, task: &Task, fd: i32, offset: i64, nbytes: u32, flags: u32) -> i64 {
+        let msg = UringOp::SyncFileRange(SyncFileRangeOp {
+            fd: fd,
+            offset: offset,
+            nbytes: nbytes,
+            flags: flags,
+        });
+
+        return self.ACall(msg).Wait(task);
+    }
+
     pub fn Statx(
         &self,
         task: &Task,
@@ -426,9 +474,10 @@ impl QUring {
         buf: DataBuff,
         offset: i64,
         lockGuard: QAsyncLockGuard,
+        barrier: QAsyncReadLockGuard,
     ) -> i64 {
         let len = buf.Len() as i64;
-        let writeop = AsyncBufWrite::New(fd, buf, offset, lockGuard);
+        let writeop = AsyncBufWrite::New(fd, buf, offset, lockGuard, barrier);
 
         IOURING.AUCall(AsyncOps::AsyncBufWrite(writeop));
         return len;
@@ -468,21 +517,38 @@ impl QUring {
     }
 
     pub fn UCall(&self, task: &Task, msg: UringOp) -> i64 {
+        let ioprio = match &task.thread {
+            None => 0,
+            Some(t) => t.IOPrio().Host(),
+        };
+
         let call = UringCall {
             taskId: task.GetTaskId(),
             ret: 0,
             msg: msg,
+            ioprio: ioprio,
         };
 
+        let start = TSC.Rdtsc();
         {
             self.UringCall(&call);
         }
 
         Wait();
+        task.AccountIOWait(start);
 
         return call.ret as i64;
     }
 
+    // ACall submits msg and returns at once. The result is delivered through
+    // the returned future, which can be polled or waited on.
+    pub fn ACall(&self, msg: UringOp) -> UringFuture {
+        let future = UringFuture::New();
+        let ops = AsyncUringCall::New(msg, &future);
+        self.AUCall(AsyncOps::AsyncUringCall(ops));
+        return future;
+    }
+
     pub fn AUCallDirect(&self, ops: &AsyncOps, id: usize) {
         let entry = ops.SEntry().user_data(id as u64);
         self.AUringCall(entry)
@@ -24,6 +24,8 @@ pub struct UringCall {
     pub taskId: TaskId,
     pub ret: i32,
     pub msg: UringOp,
+    // ioprio is the host I/O priority of the file reads and writes
+    pub ioprio: u16,
 }
 
 impl Default for UringCall {
@@ -32,6 +34,7 @@ impl Default for UringCall {
             taskId: TaskId::default(),
             ret: 0,
             msg: DEFAULT_MSG,
+            ioprio: 0,
         };
     }
 }
@@ -47,18 +50,13 @@ impl UringCall {
     }
 
     pub fn SEntry(&self) -> squeue::Entry {
+        let entry = self.msg.SEntry();
         match self.msg {
-            UringOp::None => (),
-            UringOp::TimerRemove(ref msg) => return msg.SEntry(),
-            UringOp::Read(ref msg) => return msg.SEntry(),
-            UringOp::Write(ref msg) => return msg.SEntry(),
-            UringOp::Statx(ref msg) => return msg.SEntry(),
-            UringOp::Fsync(ref msg) => return msg.SEntry(),
-            UringOp::Splice(ref msg) => return msg.SEntry(),
-            UringOp::Accept(ref msg) => return msg.SEntry(),
-        };
-
-        panic!("UringCall SEntry UringOp::None")
+            UringOp::Read(_) | UringOp::Write(_) | UringOp::Readv(_) | UringOp::Writev(_) => {
+                return entry.ioprio(self.ioprio);
+            }
+            _ => return entry,
+        }
     }
 }
 
@@ -68,10 +66,14 @@ pub enum UringOp {
     TimerRemove(TimerRemoveOp),
     Read(ReadOp),
     Write(WriteOp),
+    Readv(ReadvOp),
+    Writev(WritevOp),
     Statx(StatxOp),
     Fsync(FsyncOp),
     Splice(SpliceOp),
     Accept(AcceptOp),
+    Fallocate(FallocateOp),
+    SyncFileRange(SyncFileRangeOp),
 }
 
 impl Default for UringOp {
@@ -80,6 +82,27 @@ impl Default for UringOp {
     }
 }
 
+impl UringOp {
+    pub fn SEntry(&self) -> squeue::Entry {
+        match self {
+            UringOp::None => (),
+            UringOp::TimerRemove(ref msg) => return msg.SEntry(),
+            UringOp::Read(ref msg) => return msg.SEntry(),
+            UringOp::Write(ref msg) => return msg.SEntry(),
+            UringOp::Readv(ref msg) => return msg.SEntry(),
+            UringOp::Writev(ref msg) => return msg.SEntry(),
+            UringOp::Statx(ref msg) => return msg.SEntry(),
+            UringOp::Fsync(ref msg) => return msg.SEntry(),
+            UringOp::Splice(ref msg) => return msg.SEntry(),
+            UringOp::Accept(ref msg) => return msg.SEntry(),
+            UringOp::Fallocate(ref msg) => return msg.SEntry(),
+            UringOp::SyncFileRange(ref msg) => return msg.SEntry(),
+        };
+
+        panic!("UringOp SEntry UringOp::None")
+    }
+}
+
 #[derive(Clone, Debug, Copy)]
 pub struct TimerRemoveOp {
     pub userData: u64,
@@ -126,6 +149,40 @@ impl WriteOp {
     }
 }
 
+#[derive(Clone, Debug, Copy)]
+pub struct ReadvOp {
+    pub fd: i32,
+    pub iovs: u64,
+    pub iovcnt: u32,
+    pub offset: i64,
+}
+
+impl ReadvOp {
+    pub fn SEntry(&self) -> squeue::Entry {
+        let op = Readv::new(types::Fd(self.fd), self.iovs as *const _, self.iovcnt)
+            .offset(self.offset);
+
+        return op.build().flags(squeue::Flags::FIXED_FILE);
+    }
+}
+
+#[derive(Clone, Debug, Copy)]
+pub struct WritevOp {
+    pub fd: i32,
+    pub iovs: u64,
+    pub iovcnt: u32,
+    pub offset: i64,
+}
+
+impl WritevOp {
+    pub fn SEntry(&self) -> squeue::Entry {
+        let op = Writev::new(types::Fd(self.fd), self.iovs as *const _, self.iovcnt)
+            .offset(self.offset);
+
+        return op.build().flags(squeue::Flags::FIXED_FILE);
+    }
+}
+
 #[derive(Clone, Debug, Copy)]
 pub struct StatxOp {
     pub dirfd: i32,
@@ -167,6 +224,42 @@ impl FsyncOp {
     }
 }
 
+#[derive(Clone, Debug, Copy)]
+pub struct FallocateOp {
+    pub fd: i32,
+    pub mode: i32,
+    pub offset: i64,
+    pub len: i64,
+}
+
+impl FallocateOp {
+    pub fn SEntry(&self) -> squeue::Entry {
+        let op = Fallocate::new(types::Fd(self.fd), self.len)
+            .offset(self.offset)
+            .mode(self.mode);
+
+        return op.build().flags(squeue::Flags::FIXED_FILE);
+    }
+}
+
+#[derive(Clone, Debug, Copy)]
+pub struct SyncFileRangeOp {
+    pub fd: i32,
+    pub offset: i64,
+    pub nbytes: u32,
+    pub flags: u32,
+}
+
+impl SyncFileRangeOp {
+    pub fn SEntry(&self) -> squeue::Entry {
+        let op = SyncFileRange::new(types::Fd(self.fd), self.nbytes)
+            .offset(self.offset)
+            .flags(self.flags);
+
+        return op.build().flags(squeue::Flags::FIXED_FILE);
+    }
+}
+
 #[derive(Clone, Debug, Copy)]
 pub struct SpliceOp {
     pub fdIn: i32,
@@ -1,3 +1,5 @@
+use alloc::sync::Arc;
+
 use super::super::super::super::common::*;
 use super::super::super::super::linux::socket::*;
 use super::super::super::super::linux::time::*;
@@ -5,6 +7,8 @@ use super::super::super::super::linux_def::*;
 use super::super::super::fs::file::*;
 use super::super::super::task::*;
 use super::super::super::tcpip::tcpip::*;
+use super::super::super::threadmgr::task_usermem::*;
+use super::super::socket::CopyInFilter;
 use super::super::unix::transport::unix::*;
 
 pub fn Ioctl(task: &Task, ep: &BoundEndpoint, _fd: i32, request: u64, val: u64) -> Result<()> {
@@ -12,6 +16,7 @@ pub fn Ioctl(task: &Task, ep: &BoundEndpoint, _fd: i32, request: u64, val: u64)
 
     match flags as u64 {
         LibcConst::SIOCGIFFLAGS
+        | LibcConst::SIOCGIFADDR
         | LibcConst::SIOCGIFBRDADDR
         | LibcConst::SIOCGIFDSTADDR
         | LibcConst::SIOCGIFHWADDR
@@ -45,7 +50,7 @@ pub fn Ioctl(task: &Task, ep: &BoundEndpoint, _fd: i32, request: u64, val: u64)
             let addr = val;
             if let SockOpt::ReceiveQueueSizeOption(res) = v {
                 //*task.GetTypeMut(addr)? = res;
-                task.CopyOutObj(&res, addr)?;
+                UserPtr::New(addr).Write(task, &res)?;
             }
 
             return Ok(());
@@ -60,7 +65,7 @@ pub fn Ioctl(task: &Task, ep: &BoundEndpoint, _fd: i32, request: u64, val: u64)
             let addr = val;
             if let SockOpt::SendQueueSizeOption(res) = v {
                 //*task.GetTypeMut(addr)? = res;
-                task.CopyOutObj(&res, addr)?;
+                UserPtr::New(addr).Write(task, &res)?;
             }
 
             return Ok(());
@@ -471,7 +476,7 @@ pub fn clampBufSize(newsz: usize, min: usize, max: usize, ignoreMax: bool) -> us
 
 // setSockOptSocket implements SetSockOpt when level is SOL_SOCKET.
 pub fn SetSockOptSocket(
-    _task: &Task,
+    task: &Task,
     s: &FileOperations,
     ep: &BoundEndpoint,
     name: i32,
@@ -609,6 +614,21 @@ pub fn SetSockOptSocket(
 
             return Ok(());
         }
+        SO_ATTACH_FILTER => {
+            let filter = CopyInFilter(task, optVal)?;
+            return ep.BaseEndpoint().SetFilter(Some(Arc::new(filter)));
+        }
+        SO_DETACH_FILTER => {
+            return ep.BaseEndpoint().SetFilter(None);
+        }
+        SO_LOCK_FILTER => {
+            if optVal.len() < SIZEOF_I32 {
+                return Err(Error::SysError(SysErr::EINVAL));
+            }
+
+            let val = unsafe { *(&optVal[0] as *const _ as u64 as *const i32) };
+            return ep.BaseEndpoint().LockFilter(val != 0);
+        }
         _ => {}
     }
 
@@ -26,10 +26,13 @@ use core::sync::atomic::Ordering;
 
 //use super::super::*;
 use super::super::super::super::common::*;
+use super::super::super::super::linux::bpf::SockFprog;
 use super::super::super::super::linux::netdevice::*;
-use super::super::super::super::linux::time::Timeval;
+use super::super::super::super::linux::socket::SO_ATTACH_REUSEPORT_CBPF;
+use super::super::super::super::linux::time::{Timeval, MILLISECOND, SECOND};
 use super::super::super::super::linux_def::*;
 use super::super::super::super::mem::block::*;
+use super::super::super::super::qmsg::qcall::RDMANotifyType;
 use super::super::super::super::socket_buf::*;
 use super::super::super::fd::*;
 use super::super::super::fs::attr::*;
@@ -42,16 +45,19 @@ use super::super::super::guestfdnotifier::*;
 use super::super::super::kernel::async_wait::*;
 use super::super::super::kernel::fd_table::*;
 use super::super::super::kernel::time::*;
+use super::super::super::kernel::timer::MonotonicNow;
 use super::super::super::kernel::waiter::*;
 use super::super::super::quring::QUring;
 use super::super::super::task::*;
 use super::super::super::tcpip::tcpip::*;
+use super::super::super::threadmgr::task_usermem::*;
 use super::super::super::Kernel;
 use super::super::super::Kernel::HostSpace;
 use super::super::super::IOURING;
 use super::super::super::SHARESPACE;
 use super::super::control::ControlMessageTCPInq;
 use super::super::control::*;
+use super::super::epsocket::epsocket::Linger;
 use super::super::socket::*;
 use super::super::unix::transport::unix::*;
 use super::rdma_socket::*;
@@ -169,6 +175,8 @@ pub struct SocketOperationsIntern {
     pub enableAsyncAccept: AtomicBool,
     pub hostops: HostInodeOp,
     passInq: AtomicBool,
+    // the address of a non-blocking connect which is still in progress
+    connecting: QMutex<Option<Vec<u8>>>,
 }
 
 #[derive(Clone)]
@@ -214,6 +222,7 @@ impl SocketOperations {
             enableAsyncAccept: AtomicBool::new(false),
             hostops: hostops,
             passInq: AtomicBool::new(false),
+            connecting: QMutex::new(None),
         };
 
         let ret = Self(Arc::new(ret));
@@ -289,6 +298,31 @@ impl SocketOperations {
         }
     }
 
+    // For a listener, Linux reports the accept queue depth in tcpi_unacked
+    // and the backlog in tcpi_sacked. The connections in the guest accept
+    // queue were already accepted on the host, so add them back.
+    pub fn ListenerTcpInfo(&self, info: &mut [u8]) {
+        const UNACKED_OFFSET: usize = 24;
+        const SACKED_OFFSET: usize = 28;
+
+        if info.len() < SACKED_OFFSET + 4 {
+            return;
+        }
+
+        let q = match self.AcceptQueue() {
+            None => return,
+            Some(q) => q,
+        };
+
+        let q = q.lock();
+        let mut unacked = [0u8; 4];
+        unacked.copy_from_slice(&info[UNACKED_OFFSET..UNACKED_OFFSET + 4]);
+        let unacked = u32::from_ne_bytes(unacked) + q.Len() as u32;
+        let sacked = q.queueLen.saturating_sub(1) as u32;
+        info[UNACKED_OFFSET..UNACKED_OFFSET + 4].copy_from_slice(&unacked.to_ne_bytes());
+        info[SACKED_OFFSET..SACKED_OFFSET + 4].copy_from_slice(&sacked.to_ne_bytes());
+    }
+
     pub fn PostConnect(&self, task: &Task) {
         let socketBuf = self.SocketBufType().Connect();
         *self.socketBuf.lock() = socketBuf.clone();
@@ -326,6 +360,261 @@ impl SocketOperations {
         QUring::BufSockInit(self.fd, self.queue.clone(), self.SocketBuf(), true).unwrap();*/
     }
 
+    // ConnectDone records a completed connect. The socket buffer can only be
+    // set up once, a repeated connect of a connected socket keeps it.
+    pub fn ConnectDone(&self, task: &Task, addr: Vec<u8>) -> Result<()> {
+        self.SetRemoteAddr(addr)?;
+        if self.stype != SockType::SOCK_STREAM {
+            return Ok(());
+        }
+
+        match self.SocketBufType() {
+            SocketBufType::TCPInit | SocketBufType::NoTCP => self.PostConnect(task),
+            _ => (),
+        }
+
+        return Ok(());
+    }
+
+    // FinishConnect completes the bookkeeping of a non-blocking connect once
+    // the host socket is connected. The SO_ERROR of the host socket is left
+    // for the application to read.
+    pub fn FinishConnect(&self, task: &Task) {
+        if self.connecting.lock().is_none() {
+            return;
+        }
+
+        let addr = [0u8; SIZEOF_SOCKADDR];
+        let len = addr.len() as i32;
+        let res = HostSpace::GetPeerName(
+            self.fd,
+            &addr[0] as *const _ as u64,
+            &len as *const _ as u64,
+        );
+        if res < 0 {
+            return;
+        }
+
+        let addr = match self.connecting.lock().take() {
+            None => return,
+            Some(addr) => addr,
+        };
+
+        self.ConnectDone(task, addr).ok();
+    }
+
+    fn TcpState(&self) -> u8 {
+        // tcpi_state is the first byte of struct tcp_info
+        let mut state: u8 = 0;
+        let len: i32 = 1;
+        let res = HostSpace::GetSockOpt(
+            self.fd,
+            LibcConst::SOL_TCP as i32,
+            LibcConst::TCP_INFO as i32,
+            &mut state as *mut _ as u64,
+            &len as *const _ as u64,
+        );
+        if res < 0 {
+            return 0;
+        }
+
+        return state;
+    }
+
+    // LingerClose runs the lingering close of SO_LINGER: as Linux, the last
+    // close waits until the queued data is sent and the FIN is acked or the
+    // linger time is over. The wait is done here instead of in the host
+    // close, which would hold the vcpu.
+    pub fn LingerClose(&self, task: &Task) {
+        const TCP_ESTABLISHED: u8 = 1;
+        const TCP_FIN_WAIT1: u8 = 4;
+        const TCP_CLOSE_WAIT: u8 = 8;
+        const TCP_LAST_ACK: u8 = 9;
+        const TCP_CLOSING: u8 = 11;
+        const POLL_INTERVAL: i64 = 10 * MILLISECOND;
+
+        if (self.family != AFType::AF_INET && self.family != AFType::AF_INET6)
+            || self.stype != SockType::SOCK_STREAM
+        {
+            return;
+        }
+
+        let mut linger = Linger::default();
+        let len = SocketSize::SIZEOF_LINGER as i32;
+        let res = HostSpace::GetSockOpt(
+            self.fd,
+            LibcConst::SOL_SOCKET as i32,
+            LibcConst::SO_LINGER as i32,
+            &mut linger as *mut _ as u64,
+            &len as *const _ as u64,
+        );
+
+        // a zero linger time aborts the connection in the host close
+        if res < 0 || linger.OnOff == 0 || linger.Linger == 0 {
+            return;
+        }
+
+        let state = self.TcpState();
+        if state == TCP_ESTABLISHED || state == TCP_CLOSE_WAIT {
+            let deadline = Time(MonotonicNow().saturating_add(linger.Linger as i64 * SECOND));
+            if self.DrainWriteBuf(task, Some(deadline)).is_ok() {
+                HostSpace::Shutdown(self.fd, LibcConst::SHUT_WR as i32);
+                loop {
+                    let state = self.TcpState();
+                    if state != TCP_FIN_WAIT1 && state != TCP_CLOSING && state != TCP_LAST_ACK {
+                        break;
+                    }
+
+                    let now = MonotonicNow();
+                    if now >= deadline.0 {
+                        break;
+                    }
+
+                    let next = Time(core::cmp::min(now + POLL_INTERVAL, deadline.0));
+                    match task.blocker.BlockWithMonoTimer(false, Some(next)) {
+                        Err(Error::SysError(SysErr::ETIMEDOUT)) => (),
+                        _ => break,
+                    }
+                }
+            }
+        }
+
+        // the rest of the close goes on in the background
+        let linger = Linger::default();
+        HostSpace::SetSockOpt(
+            self.fd,
+            LibcConst::SOL_SOCKET as i32,
+            LibcConst::SO_LINGER as i32,
+            &linger as *const _ as u64,
+            SocketSize::SIZEOF_LINGER as u32,
+        );
+    }
+
+    // DrainWriteBuf waits for the socket buffer to be written to the host
+    // socket, so the data sent by the host socket afterwards stays in order.
+    fn DrainWriteBuf(&self, task: &Task, deadline: Option<Time>) -> Result<()> {
+        if !self.SocketBufEnabled() || !self.SocketBuf().HasWriteData() {
+            return Ok(());
+        }
+
+        self.SocketBuf().SetPendingWriteShutdown();
+        let general = task.blocker.generalEntry.clone();
+        self.EventRegister(task, &general, EVENT_PENDING_SHUTDOWN);
+        defer!(self.EventUnregister(task, &general));
+
+        while self.SocketBuf().HasWriteData() {
+            match task.blocker.BlockWithMonoTimer(true, deadline) {
+                Err(Error::ErrInterrupted) => {
+                    return Err(Error::SysError(SysErr::ERESTARTSYS));
+                }
+                Err(Error::SysError(SysErr::ETIMEDOUT)) => {
+                    return Err(Error::SysError(SysErr::EAGAIN));
+                }
+                Err(e) => return Err(e),
+                Ok(()) => (),
+            }
+        }
+
+        return Ok(());
+    }
+
+    // SetTlsOpt checks the kTLS options before they are set on the host
+    // socket, the host kernel runs the record encryption.
+    fn SetTlsOpt(&self, task: &Task, level: u64, name: u64) -> Result<()> {
+        match (level, name) {
+            (LibcConst::SOL_TCP, LibcConst::TCP_ULP) => {
+                // the data of an RDMA socket doesn't go by the host socket,
+                // fail as a host without the tls module
+                if let SocketBufType::RDMA(_) = self.SocketBufType() {
+                    return Err(Error::SysError(SysErr::ENOENT));
+                }
+            }
+            (LibcConst::SOL_TLS, LibcConst::TLS_TX) => {
+                // the records written before, e.g. the handshake Finished,
+                // must not be encrypted again
+                self.DrainWriteBuf(task, None)?;
+            }
+            (LibcConst::SOL_TLS, LibcConst::TLS_RX) => {
+                // the socket buffer has read ahead the records after the
+                // handshake, so only the application can decrypt them
+                if self.SocketBufEnabled() {
+                    return Err(Error::SysError(SysErr::EOPNOTSUPP));
+                }
+            }
+            _ => (),
+        }
+
+        return Ok(());
+    }
+
+    // HostSendMsg sends the message by the host socket.
+    fn HostSendMsg(
+        &self,
+        task: &Task,
+        srcs: &[IoVec],
+        flags: i32,
+        msgHdr: &mut MsgHdr,
+        deadline: Option<Time>,
+    ) -> Result<i64> {
+        if flags
+            & !(MsgType::MSG_DONTWAIT
+                | MsgType::MSG_EOR
+                | MsgType::MSG_FASTOPEN
+                | MsgType::MSG_MORE
+                | MsgType::MSG_NOSIGNAL)
+            != 0
+        {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        let size = IoVec::NumBytes(srcs);
+        let mut buf = DataBuff::New(size);
+        let len = task.CopyDataInFromIovs(&mut buf.buf, srcs, true)?;
+        let iovs = buf.Iovs(len);
+
+        msgHdr.iov = &iovs[0] as *const _ as u64;
+        msgHdr.iovLen = iovs.len();
+        msgHdr.msgFlags = 0;
+
+        let mut res = Kernel::HostSpace::IOSendMsg(
+            self.fd,
+            msgHdr as *const _ as u64,
+            flags | MsgType::MSG_DONTWAIT,
+            false,
+        ) as i32;
+        while res == -SysErr::EWOULDBLOCK && flags & MsgType::MSG_DONTWAIT == 0 {
+            let general = task.blocker.generalEntry.clone();
+
+            // the host socket is watched here, a buffered socket doesn't
+            // watch it in EventRegister
+            self.queue.EventRegister(task, &general, EVENT_WRITE);
+            UpdateFD(self.fd).unwrap();
+            defer!({
+                self.queue.EventUnregister(task, &general);
+                UpdateFD(self.fd).unwrap();
+            });
+            match task.blocker.BlockWithMonoTimer(true, deadline) {
+                Err(e) => {
+                    return Err(e);
+                }
+                _ => (),
+            }
+
+            res = Kernel::HostSpace::IOSendMsg(
+                self.fd,
+                msgHdr as *const _ as u64,
+                flags | MsgType::MSG_DONTWAIT,
+                false,
+            ) as i32;
+        }
+
+        if res < 0 {
+            return Err(Error::SysError(-res as i32));
+        }
+
+        return Ok(res as i64);
+    }
+
     pub fn Notify(&self, mask: EventMask) {
         self.queue.Notify(EventMaskFromLinux(mask as u32));
     }
@@ -412,10 +701,143 @@ impl SocketOperations {
             Some(ref v) => Some(v.ToVec().unwrap()),
         };
     }
+
+    // CheckBindToDevice checks the device binding of the socket. As Linux
+    // does, a socket not bound to a device yet can be bound without
+    // CAP_NET_RAW, a change of the binding needs it.
+    pub fn CheckBindToDevice(&self, task: &Task) -> Result<()> {
+        if task.Creds().HasCapability(Capability::CAP_NET_RAW) {
+            return Ok(());
+        }
+
+        let mut name = [0u8; LibcConst::IFNAMSIZ as usize];
+        let mut len: u32 = name.len() as u32;
+        let res = HostSpace::GetSockOpt(
+            self.fd,
+            LibcConst::SOL_SOCKET as i32,
+            LibcConst::SO_BINDTODEVICE as i32,
+            &mut name[0] as *mut _ as u64,
+            &mut len as *mut _ as u64,
+        );
+        if res < 0 {
+            return Err(Error::SysError(-res as i32));
+        }
+
+        if len > 0 && name[0] != 0 {
+            return Err(Error::SysError(SysErr::EPERM));
+        }
+
+        return Ok(());
+    }
+}
+
+// CheckTransparent checks IP_TRANSPARENT and IPV6_TRANSPARENT, enabling them
+// needs CAP_NET_RAW or CAP_NET_ADMIN.
+pub fn CheckTransparent(task: &Task, opt: &[u8]) -> Result<()> {
+    if opt.len() == 0 {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    // the value is an int, or a char if the option is shorter
+    let enable = opt.iter().take(4).any(|b| *b != 0);
+    let creds = task.Creds();
+    if enable
+        && !creds.HasCapability(Capability::CAP_NET_RAW)
+        && !creds.HasCapability(Capability::CAP_NET_ADMIN)
+    {
+        return Err(Error::SysError(SysErr::EPERM));
+    }
+
+    return Ok(());
 }
 
 pub const SIZEOF_SOCKADDR: usize = SocketSize::SIZEOF_SOCKADDR_INET6;
 
+// MMsgBuf is the host side copy of one recvmmsg/sendmmsg message.
+pub struct MMsgBuf {
+    pub iovs: Vec<IoVec>,
+    pub data: DataBuff,
+    pub hostIov: [IoVec; 1],
+    pub name: Vec<u8>,
+    pub control: Vec<u8>,
+}
+
+impl MMsgBuf {
+    pub fn New(task: &Task, hdr: &MsgHdr, send: bool) -> Result<Self> {
+        let iovs = task.IovsFromAddr(hdr.iov, hdr.iovLen)?;
+        let size = IoVec::NumBytes(&iovs);
+        let mut data = DataBuff::New(size);
+        let (len, name, control) = if send {
+            let len = task.CopyDataInFromIovs(&mut data.buf, &iovs, true)?;
+            let name = UserSlice::<u8>::New(hdr.msgName, hdr.nameLen as usize).Read(task)?;
+            let control = UserSlice::<u8>::New(hdr.msgControl, hdr.msgControlLen).Read(task)?;
+            (len, name, control)
+        } else {
+            let nameLen = if hdr.msgName == 0 {
+                0
+            } else {
+                core::cmp::min(hdr.nameLen as usize, SIZEOF_SOCKADDR)
+            };
+            let controlLen = if hdr.msgControl == 0 {
+                0
+            } else {
+                hdr.msgControlLen
+            };
+            (size, vec![0; nameLen], vec![0; controlLen])
+        };
+
+        let hostIov = data.Iovs(len);
+        return Ok(Self {
+            iovs,
+            data,
+            hostIov,
+            name,
+            control,
+        });
+    }
+
+    pub fn HostMsg(&self) -> MMsgHdr {
+        let mut msg = MMsgHdr::default();
+        msg.msgHdr.iov = &self.hostIov[0] as *const _ as u64;
+        msg.msgHdr.iovLen = self.hostIov.len();
+        if self.name.len() > 0 {
+            msg.msgHdr.msgName = &self.name[0] as *const _ as u64;
+            msg.msgHdr.nameLen = self.name.len() as u32;
+        }
+        if self.control.len() > 0 {
+            msg.msgHdr.msgControl = &self.control[0] as *const _ as u64;
+            msg.msgHdr.msgControlLen = self.control.len();
+        }
+        return msg;
+    }
+
+    // CopyOut copies a received message back to the guest message.
+    pub fn CopyOut(&self, task: &Task, host: &MMsgHdr, msg: &mut MMsgHdr) -> Result<()> {
+        // with MSG_TRUNC the length is the full datagram
+        let len = core::cmp::min(host.msgLen as usize, self.data.buf.len());
+        task.CopyDataOutToIovs(&self.data.buf[..len], &self.iovs, false)?;
+
+        if msg.msgHdr.msgName != 0 {
+            // as Linux, the name is truncated to the guest buffer
+            let len = core::cmp::min(host.msgHdr.nameLen as usize, self.name.len());
+            UserSlice::New(msg.msgHdr.msgName, len).Write(task, &self.name[..len])?;
+            msg.msgHdr.nameLen = host.msgHdr.nameLen;
+        } else {
+            msg.msgHdr.nameLen = 0;
+        }
+
+        let controlLen = host.msgHdr.msgControlLen;
+        if msg.msgHdr.msgControl != 0 && controlLen != 0 {
+            UserSlice::New(msg.msgHdr.msgControl, controlLen)
+                .Write(task, &self.control[..controlLen])?;
+        }
+        msg.msgHdr.msgControlLen = controlLen;
+        msg.msgHdr.msgFlags = host.msgHdr.msgFlags;
+        msg.msgLen = host.msgLen;
+        return Ok(());
+    }
+}
+
 impl Waitable for SocketOperations {
     fn AsyncReadiness(&self, _task: &Task, mask: EventMask, wait: &MultiWait) -> Future<EventMask> {
         if self.SocketBufEnabled() {
@@ -484,18 +906,18 @@ impl Waitable for SocketOperations {
 
 // pass the ioctl to the shadow hostfd
 pub fn HostIoctlIFReq(task: &Task, hostfd: i32, request: u64, addr: u64) -> Result<()> {
-    let mut ifr: IFReq = task.CopyInObj(addr)?;
+    let mut ifr: IFReq = UserPtr::New(addr).Read(task)?;
     let res = HostSpace::IoCtl(hostfd, request, &mut ifr as *const _ as u64);
     if res < 0 {
         return Err(Error::SysError(-res as i32));
     }
 
-    task.CopyOutObj(&ifr, addr)?;
+    UserPtr::New(addr).Write(task, &ifr)?;
     return Ok(());
 }
 
 pub fn HostIoctlIFConf(task: &Task, hostfd: i32, request: u64, addr: u64) -> Result<()> {
-    let mut ifc: IFConf = task.CopyInObj(addr)?;
+    let mut ifc: IFConf = UserPtr::New(addr).Read(task)?;
 
     const MAX_LEN: usize = 64 * 0x1000; // 256 KB
 
@@ -527,7 +949,7 @@ pub fn HostIoctlIFConf(task: &Task, hostfd: i32, request: u64, addr: u64) -> Res
 
     ifc.Len = ifr.Len;
 
-    task.CopyOutObj(&ifc, addr)?;
+    UserPtr::New(addr).Write(task, &ifc)?;
     return Ok(());
 }
 
@@ -575,6 +997,7 @@ impl FileOperations for SocketOperations {
         _offset: i64,
         _blocking: bool,
     ) -> Result<i64> {
+        self.FinishConnect(task);
         let sockBufType = self.socketBuf.lock().clone();
         match sockBufType {
             SocketBufType::Uring(socketBuf) => {
@@ -610,6 +1033,7 @@ impl FileOperations for SocketOperations {
         _offset: i64,
         _blocking: bool,
     ) -> Result<i64> {
+        self.FinishConnect(task);
         let sockBufType = self.socketBuf.lock().clone();
         match sockBufType {
             SocketBufType::Uring(socketBuf) => {
@@ -649,7 +1073,12 @@ impl FileOperations for SocketOperations {
         return Err(Error::SysError(SysErr::EINVAL));
     }
 
-    fn Flush(&self, _task: &Task, _f: &File) -> Result<()> {
+    fn Flush(&self, task: &Task, f: &File) -> Result<()> {
+        // SO_LINGER applies to the close of the last reference
+        if Arc::strong_count(&f.0) == 1 {
+            self.LingerClose(task);
+        }
+
         return Ok(());
     }
 
@@ -661,9 +1090,11 @@ impl FileOperations for SocketOperations {
     fn Ioctl(&self, task: &Task, _f: &File, _fd: i32, request: u64, val: u64) -> Result<()> {
         let flags = request as i32;
 
+        self.FinishConnect(task);
         let hostfd = self.fd;
         match flags as u64 {
             LibcConst::SIOCGIFFLAGS
+            | LibcConst::SIOCGIFADDR
             | LibcConst::SIOCGIFBRDADDR
             | LibcConst::SIOCGIFDSTADDR
             | LibcConst::SIOCGIFHWADDR
@@ -688,7 +1119,7 @@ impl FileOperations for SocketOperations {
             LibcConst::TIOCINQ => {
                 if self.SocketBufEnabled() {
                     let v = self.SocketBuf().readBuf.lock().AvailableDataSize() as i32;
-                    task.CopyOutObj(&v, val)?;
+                    UserPtr::New(val).Write(task, &v)?;
                     return Ok(());
                 } else {
                     let tmp: i32 = 0;
@@ -696,19 +1127,40 @@ impl FileOperations for SocketOperations {
                     if res < 0 {
                         return Err(Error::SysError(-res as i32));
                     }
-                    task.CopyOutObj(&tmp, val)?;
+                    UserPtr::New(val).Write(task, &tmp)?;
                     return Ok(());
                 }
             }
-            _ => {
+            LibcConst::TIOCOUTQ => {
                 let tmp: i32 = 0;
                 let res = Kernel::HostSpace::IoCtl(self.fd, request, &tmp as *const _ as u64);
                 if res < 0 {
                     return Err(Error::SysError(-res as i32));
                 }
-                task.CopyOutObj(&tmp, val)?;
+
+                // the data not written to the host socket yet is queued too
+                let mut v = tmp;
+                if self.SocketBufEnabled() {
+                    let pending = self.SocketBuf().writeBuf.lock().AvailableDataSize();
+                    v = v.saturating_add(pending as i32);
+                }
+                UserPtr::New(val).Write(task, &v)?;
                 return Ok(());
             }
+            LibcConst::SIOCOUTQNSD | LibcConst::SIOCATMARK => {
+                let tmp: i32 = 0;
+                let res = Kernel::HostSpace::IoCtl(self.fd, request, &tmp as *const _ as u64);
+                if res < 0 {
+                    return Err(Error::SysError(-res as i32));
+                }
+                UserPtr::New(val).Write(task, &tmp)?;
+                return Ok(());
+            }
+            _ => {
+                // the argument of the other ioctls is not known to be an int,
+                // they can't be passed to the host safely
+                return Err(Error::SysError(SysErr::ENOTTY));
+            }
         }
     }
 
@@ -747,51 +1199,57 @@ impl SockOperations for SocketOperations {
             socketaddr.len() as u32,
         ) as i32;
         if res == 0 {
-            self.SetRemoteAddr(socketaddr.to_vec())?;
-            if self.stype == SockType::SOCK_STREAM {
-                self.PostConnect(task);
-            }
-
+            self.connecting.lock().take();
+            self.ConnectDone(task, socketaddr.to_vec())?;
             return Ok(0);
         }
 
-        let blocking = if blocking {
-            true
-        } else {
-            // in order to enable uring buff, have to do block accept
-            if SHARESPACE.config.read().UringIO
-                && (self.family == AFType::AF_INET || self.family == AFType::AF_INET6)
-                && self.stype == SockType::SOCK_STREAM
-            {
-                true
-            } else {
-                false
+        match -res {
+            SysErr::EINPROGRESS => {
+                *self.connecting.lock() = Some(socketaddr.to_vec());
             }
-
-            //false
-        };
-
-        if res != 0 {
-            if -res != SysErr::EINPROGRESS || !blocking {
+            SysErr::EALREADY => (),
+            SysErr::EISCONN => {
+                self.FinishConnect(task);
+                return Err(Error::SysError(-res));
+            }
+            _ => {
+                self.connecting.lock().take();
                 return Err(Error::SysError(-res));
             }
+        }
 
-            //todo: which one is more efficent?
-            let general = task.blocker.generalEntry.clone();
-            self.EventRegister(task, &general, EVENT_OUT);
-            defer!(self.EventUnregister(task, &general));
+        // a non-blocking connect completes in the background, the socket
+        // buffer is set up when the application uses the connected socket
+        if !blocking {
+            return Err(Error::SysError(-res));
+        }
 
-            if self.Readiness(task, WRITEABLE_EVENT) == 0 {
-                match task.blocker.BlockWithMonoTimer(true, None) {
-                    Err(Error::ErrInterrupted) => {
-                        return Err(Error::SysError(SysErr::ERESTARTSYS));
-                    }
-                    Err(e) => {
-                        error!("connect error {:?}", &e);
-                        return Err(e);
-                    }
-                    _ => (),
+        let mut deadline = None;
+        let dl = self.SendTimeout();
+        if dl > 0 {
+            deadline = Some(Time(MonotonicNow() + dl));
+        }
+
+        //todo: which one is more efficent?
+        let general = task.blocker.generalEntry.clone();
+        self.EventRegister(task, &general, EVENT_OUT);
+        defer!(self.EventUnregister(task, &general));
+
+        while self.Readiness(task, WRITEABLE_EVENT | EVENT_ERR | EVENT_HUP) == 0 {
+            match task.blocker.BlockWithMonoTimer(true, deadline) {
+                Err(Error::ErrInterrupted) => {
+                    return Err(Error::SysError(SysErr::ERESTARTSYS));
+                }
+                // as Linux, the connect goes on after the timeout
+                Err(Error::SysError(SysErr::ETIMEDOUT)) => {
+                    return Err(Error::SysError(SysErr::EINPROGRESS));
+                }
+                Err(e) => {
+                    error!("connect error {:?}", &e);
+                    return Err(e);
                 }
+                _ => (),
             }
         }
 
@@ -809,15 +1267,16 @@ impl SockOperations for SocketOperations {
             return Err(Error::SysError(-res));
         }
 
+        let addr = match self.connecting.lock().take() {
+            None => socketaddr.to_vec(),
+            Some(addr) => addr,
+        };
+
         if val != 0 {
             return Err(Error::SysError(val as i32));
         }
 
-        self.SetRemoteAddr(socketaddr.to_vec())?;
-        if self.stype == SockType::SOCK_STREAM {
-            self.PostConnect(task);
-        }
-
+        self.ConnectDone(task, addr)?;
         return Ok(0);
     }
 
@@ -945,23 +1404,36 @@ impl SockOperations for SocketOperations {
             && (self.family == AFType::AF_INET || self.family == AFType::AF_INET6)
             && self.stype == SockType::SOCK_STREAM;
 
-        let len = if backlog <= 0 { 5 } else { backlog };
+        // as Linux, the backlog is capped by net.core.somaxconn and the
+        // accept queue is full once it holds more than backlog connections
+        let somaxconn = NET_SOMAXCONN.load(Ordering::Relaxed);
+        let backlog = if backlog < 0 || backlog > somaxconn {
+            somaxconn
+        } else {
+            backlog
+        };
+        let len = backlog as usize + 1;
+        let abortOnOverflow = NET_TCP_ABORT_ON_OVERFLOW.load(Ordering::Relaxed);
 
         let socketBuf = self.socketBuf.lock().clone();
         let acceptQueue = match socketBuf {
             SocketBufType::TCPUringlServer(q) => {
-                q.lock().SetQueueLen(len as usize);
+                if q.lock().SetQueueLen(len, abortOnOverflow) {
+                    IOURING.AcceptInit(self.fd, &self.queue, &q)?;
+                }
                 return Ok(0);
             }
             SocketBufType::TCPRDMAServer(q) => {
-                q.lock().SetQueueLen(len as usize);
+                if q.lock().SetQueueLen(len, abortOnOverflow) {
+                    HostSpace::RDMANotify(self.fd, RDMANotifyType::Accept);
+                }
                 return Ok(0);
             }
             SocketBufType::TCPInit => AcceptQueue::default(),
             _ => AcceptQueue::default(), // panic?
         };
 
-        acceptQueue.lock().SetQueueLen(len as usize);
+        acceptQueue.lock().SetQueueLen(len, abortOnOverflow);
 
         let res = if enableRDMA {
             Kernel::HostSpace::RDMAListen(self.fd, backlog, asyncAccept, acceptQueue.clone())
@@ -992,7 +1464,9 @@ impl SockOperations for SocketOperations {
     fn Shutdown(&self, task: &Task, how: i32) -> Result<i64> {
         let how = how as u64;
 
-        if self.stype == SockType::SOCK_STREAM &&
+        self.FinishConnect(task);
+        let socketBufEnabled = self.SocketBufEnabled();
+        if self.stype == SockType::SOCK_STREAM && socketBufEnabled &&
             (how == LibcConst::SHUT_WR || how == LibcConst::SHUT_RDWR) {
             if self.SocketBuf().HasWriteData() {
                 self.SocketBuf().SetPendingWriteShutdown();
@@ -1012,11 +1486,11 @@ impl SockOperations for SocketOperations {
                 return Err(Error::SysError(-res as i32));
             }
 
-            if self.stype == SockType::SOCK_STREAM && (how == LibcConst::SHUT_RD || how == LibcConst::SHUT_RDWR) {
+            if self.stype == SockType::SOCK_STREAM && socketBufEnabled && (how == LibcConst::SHUT_RD || how == LibcConst::SHUT_RDWR) {
                 self.SocketBuf().SetRClosed();
             }
 
-            if self.stype == SockType::SOCK_STREAM && (how == LibcConst::SHUT_WR || how == LibcConst::SHUT_RDWR) {
+            if self.stype == SockType::SOCK_STREAM && socketBufEnabled && (how == LibcConst::SHUT_WR || how == LibcConst::SHUT_RDWR) {
                 self.SocketBuf().SetWClosed();
             }
 
@@ -1123,6 +1597,10 @@ impl SockOperations for SocketOperations {
             return Err(Error::SysError(-res as i32));
         }
 
+        if level as u64 == LibcConst::SOL_TCP && name as u64 == LibcConst::TCP_INFO {
+            self.ListenerTcpInfo(&mut opt[..optLen]);
+        }
+
         return Ok(optLen as i64);
     }
 
@@ -1165,7 +1643,7 @@ impl SockOperations for SocketOperations {
 
         if (level as u64) == LibcConst::SOL_SOCKET && (name as u64) == LibcConst::SO_SNDTIMEO {
             if opt.len() >= SocketSize::SIZEOF_TIMEVAL {
-                let timeVal = task.CopyInObj::<Timeval>(&opt[0] as *const _ as u64)?;
+                let timeVal = unsafe { *(&opt[0] as *const _ as u64 as *const Timeval) };
                 self.SetSendTimeout(timeVal.ToDuration() as i64);
             } else {
                 //TODO: to be aligned with Linux, Linux allows shorter length for this flag.
@@ -1175,7 +1653,7 @@ impl SockOperations for SocketOperations {
 
         if (level as u64) == LibcConst::SOL_SOCKET && (name as u64) == LibcConst::SO_RCVTIMEO {
             if opt.len() >= SocketSize::SIZEOF_TIMEVAL {
-                let timeVal = task.CopyInObj::<Timeval>(&opt[0] as *const _ as u64)?;
+                let timeVal = unsafe { *(&opt[0] as *const _ as u64 as *const Timeval) };
                 self.SetRecvTimeout(timeVal.ToDuration() as i64);
             } else {
                 //TODO: to be aligned with Linux, Linux allows shorter length for this flag.
@@ -1183,6 +1661,49 @@ impl SockOperations for SocketOperations {
             }
         }
 
+        // the host socket is owned by the sandbox process, the guest
+        // capabilities of the routing controls are checked here
+        match (level as u64, name as u64) {
+            (LibcConst::SOL_SOCKET, LibcConst::SO_BINDTODEVICE)
+            | (LibcConst::SOL_SOCKET, LibcConst::SO_BINDTOIFINDEX) => {
+                self.CheckBindToDevice(task)?;
+            }
+            (LibcConst::SOL_IP, LibcConst::IP_TRANSPARENT)
+            | (LibcConst::SOL_IPV6, LibcConst::IPV6_TRANSPARENT) => {
+                CheckTransparent(task, opt)?;
+            }
+            (LibcConst::SOL_TCP, LibcConst::TCP_ULP) | (LibcConst::SOL_TLS, _) => {
+                self.SetTlsOpt(task, level as u64, name as u64)?;
+            }
+            _ => (),
+        }
+
+        // the host can't read the program at the guest address of the
+        // sock_fprog, it gets a checked copy
+        if (level as u64) == LibcConst::SOL_SOCKET
+            && ((name as u64) == LibcConst::SO_ATTACH_FILTER || name == SO_ATTACH_REUSEPORT_CBPF)
+        {
+            let filter = CopyInFilter(task, opt)?;
+            let fprog = SockFprog {
+                Len: filter.Len() as u16,
+                Filter: &filter.instructions[0] as *const _ as u64,
+                ..Default::default()
+            };
+
+            let res = Kernel::HostSpace::SetSockOpt(
+                self.fd,
+                level,
+                name,
+                &fprog as *const _ as u64,
+                core::mem::size_of::<SockFprog>() as u32,
+            );
+            if res < 0 {
+                return Err(Error::SysError(-res as i32));
+            }
+
+            return Ok(res);
+        }
+
         // TCP_INQ is bound to buffer implementation
         if (level as u64) == LibcConst::SOL_TCP && (name as u64) == LibcConst::TCP_INQ {
             let val = unsafe { *(&opt[0] as *const _ as u64 as *const i32) };
@@ -1264,7 +1785,8 @@ impl SockOperations for SocketOperations {
             | MsgType::MSG_PEEK
             | MsgType::MSG_TRUNC
             | MsgType::MSG_CTRUNC
-            | MsgType::MSG_WAITALL)
+            | MsgType::MSG_WAITALL
+            | MsgType::MSG_CMSG_CLOEXEC)
             != 0
             {
                 return Err(Error::SysError(SysErr::EINVAL));
@@ -1273,6 +1795,7 @@ impl SockOperations for SocketOperations {
         let waitall = (flags & MsgType::MSG_WAITALL) != 0;
         let dontwait = (flags & MsgType::MSG_DONTWAIT) != 0;
 
+        self.FinishConnect(task);
         if self.SocketBufEnabled() {
             let controlDataLen = 0;
 
@@ -1445,7 +1968,9 @@ impl SockOperations for SocketOperations {
             return Err(Error::SysError(-res as i32));
         }
 
-        let msgFlags = msgHdr.msgFlags & !MsgType::MSG_CTRUNC;
+        // the host control buffer has the guest size, so MSG_CTRUNC is kept:
+        // a truncated UDP_GRO message means the segment size is unknown
+        let msgFlags = msgHdr.msgFlags;
         let senderAddr = if senderRequested
             // for tcp connect, recvmsg get nameLen=0 msg
             && msgHdr.nameLen >= 4
@@ -1477,15 +2002,23 @@ impl SockOperations for SocketOperations {
         msgHdr: &mut MsgHdr,
         deadline: Option<Time>,
     ) -> Result<i64> {
+        self.FinishConnect(task);
         if self.SocketBufEnabled() {
             if self.SocketBuf().WClosed() {
                 return Err(Error::SysError(SysErr::ESPIPE))
             }
 
-            if msgHdr.msgName != 0 || msgHdr.msgControl != 0 {
+            if msgHdr.msgName != 0 {
                 panic!("Hostnet Socketbuf doesn't supprot MsgHdr");
             }
 
+            // the control messages, e.g. the kTLS record type, are sent by
+            // the host socket after the buffered data
+            if msgHdr.msgControl != 0 {
+                self.DrainWriteBuf(task, deadline)?;
+                return self.HostSendMsg(task, srcs, flags, msgHdr, deadline);
+            }
+
             let len = Iovs(srcs).Count();
             let mut count = 0;
             let mut srcs = srcs;
@@ -1552,6 +2085,113 @@ impl SockOperations for SocketOperations {
             }
         }
 
+        return self.HostSendMsg(task, srcs, flags, msgHdr, deadline);
+    }
+
+    fn RecvMMsg(
+        &self,
+        task: &Task,
+        msgs: &mut [MMsgHdr],
+        flags: i32,
+        deadline: Option<Time>,
+    ) -> Result<i64> {
+        // stream sockets and the error queue take the per message path
+        if self.stype == SockType::SOCK_STREAM
+            || self.SocketBufEnabled()
+            || flags & MsgType::MSG_ERRQUEUE != 0
+        {
+            return Err(Error::SysError(SysErr::ENOSYS));
+        }
+
+        if flags
+            & !(MsgType::MSG_DONTWAIT
+                | MsgType::MSG_PEEK
+                | MsgType::MSG_TRUNC
+                | MsgType::MSG_CTRUNC
+                | MsgType::MSG_WAITALL
+                | MsgType::MSG_WAITFORONE
+                | MsgType::MSG_CMSG_CLOEXEC)
+            != 0
+        {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        let mut bufs = Vec::with_capacity(msgs.len());
+        for msg in msgs.iter() {
+            bufs.push(MMsgBuf::New(task, &msg.msgHdr, false)?);
+        }
+        let mut hostMsgs: Vec<MMsgHdr> = bufs.iter().map(|b| b.HostMsg()).collect();
+
+        let general = task.blocker.generalEntry.clone();
+        self.EventRegister(task, &general, EVENT_READ);
+        defer!(self.EventUnregister(task, &general));
+
+        let hostFlags = (flags & !MsgType::MSG_WAITFORONE) | MsgType::MSG_DONTWAIT;
+        let mut count = 0;
+        loop {
+            let res = Kernel::HostSpace::IORecvMMsg(
+                self.fd,
+                &mut hostMsgs[count] as *mut _ as u64,
+                (hostMsgs.len() - count) as u32,
+                hostFlags,
+            );
+
+            if res > 0 {
+                count += res as usize;
+                if count == hostMsgs.len() {
+                    break;
+                }
+            } else if res < 0 && res != -SysErr::EWOULDBLOCK as i64 {
+                // as Linux, the error is reported by the next call
+                if count > 0 {
+                    break;
+                }
+                return Err(Error::SysError(-res as i32));
+            }
+
+            if flags & MsgType::MSG_DONTWAIT != 0
+                || (count > 0 && flags & MsgType::MSG_WAITFORONE != 0)
+            {
+                break;
+            }
+
+            match task.blocker.BlockWithMonoTimer(true, deadline) {
+                Err(_) if count > 0 => break,
+                Err(Error::ErrInterrupted) => {
+                    return Err(Error::SysError(SysErr::ERESTARTSYS));
+                }
+                Err(Error::SysError(SysErr::ETIMEDOUT)) => {
+                    return Err(Error::SysError(SysErr::EAGAIN));
+                }
+                Err(e) => {
+                    return Err(e);
+                }
+                _ => (),
+            }
+        }
+
+        if count == 0 {
+            return Err(Error::SysError(SysErr::EAGAIN));
+        }
+
+        for i in 0..count {
+            bufs[i].CopyOut(task, &hostMsgs[i], &mut msgs[i])?;
+        }
+
+        return Ok(count as i64);
+    }
+
+    fn SendMMsg(
+        &self,
+        task: &Task,
+        msgs: &mut [MMsgHdr],
+        flags: i32,
+        deadline: Option<Time>,
+    ) -> Result<i64> {
+        if self.stype == SockType::SOCK_STREAM || self.SocketBufEnabled() {
+            return Err(Error::SysError(SysErr::ENOSYS));
+        }
+
         if flags
             & !(MsgType::MSG_DONTWAIT
                 | MsgType::MSG_EOR
@@ -1563,46 +2203,62 @@ impl SockOperations for SocketOperations {
             return Err(Error::SysError(SysErr::EINVAL));
         }
 
-        let size = IoVec::NumBytes(srcs);
-        let mut buf = DataBuff::New(size);
-        let len = task.CopyDataInFromIovs(&mut buf.buf, srcs, true)?;
-        let iovs = buf.Iovs(len);
+        let mut bufs = Vec::with_capacity(msgs.len());
+        for msg in msgs.iter() {
+            bufs.push(MMsgBuf::New(task, &msg.msgHdr, true)?);
+        }
+        let mut hostMsgs: Vec<MMsgHdr> = bufs.iter().map(|b| b.HostMsg()).collect();
 
-        msgHdr.iov = &iovs[0] as *const _ as u64;
-        msgHdr.iovLen = iovs.len();
-        msgHdr.msgFlags = 0;
+        let general = task.blocker.generalEntry.clone();
+        self.EventRegister(task, &general, EVENT_WRITE);
+        defer!(self.EventUnregister(task, &general));
 
-        let mut res = Kernel::HostSpace::IOSendMsg(
-            self.fd,
-            msgHdr as *const _ as u64,
-            flags | MsgType::MSG_DONTWAIT,
-            false,
-        ) as i32;
-        while res == -SysErr::EWOULDBLOCK && flags & MsgType::MSG_DONTWAIT == 0 {
-            let general = task.blocker.generalEntry.clone();
+        let mut count = 0;
+        loop {
+            let res = Kernel::HostSpace::IOSendMMsg(
+                self.fd,
+                &mut hostMsgs[count] as *mut _ as u64,
+                (hostMsgs.len() - count) as u32,
+                flags | MsgType::MSG_DONTWAIT,
+            );
+
+            if res > 0 {
+                count += res as usize;
+                if count == hostMsgs.len() {
+                    break;
+                }
+            } else if res < 0 && res != -SysErr::EWOULDBLOCK as i64 {
+                if count > 0 {
+                    break;
+                }
+                return Err(Error::SysError(-res as i32));
+            }
+
+            if flags & MsgType::MSG_DONTWAIT != 0 {
+                break;
+            }
 
-            self.EventRegister(task, &general, EVENT_WRITE);
-            defer!(self.EventUnregister(task, &general));
             match task.blocker.BlockWithMonoTimer(true, deadline) {
+                Err(_) if count > 0 => break,
+                Err(Error::SysError(SysErr::ETIMEDOUT)) => {
+                    return Err(Error::SysError(SysErr::EAGAIN));
+                }
                 Err(e) => {
                     return Err(e);
                 }
                 _ => (),
             }
+        }
 
-            res = Kernel::HostSpace::IOSendMsg(
-                self.fd,
-                msgHdr as *const _ as u64,
-                flags | MsgType::MSG_DONTWAIT,
-                false,
-            ) as i32;
+        if count == 0 {
+            return Err(Error::SysError(SysErr::EAGAIN));
         }
 
-        if res < 0 {
-            return Err(Error::SysError(-res as i32));
+        for i in 0..count {
+            msgs[i].msgLen = hostMsgs[i].msgLen;
         }
 
-        return Ok(res as i64);
+        return Ok(count as i64);
     }
 
     fn SetRecvTimeout(&self, ns: i64) {
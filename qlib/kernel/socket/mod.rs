@@ -16,6 +16,7 @@ pub mod buffer;
 pub mod control;
 pub mod epsocket;
 pub mod hostinet;
+pub mod netdevice;
 pub mod socket;
 pub mod unix;
 
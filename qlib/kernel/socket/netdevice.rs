@@ -0,0 +1,288 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+use super::super::super::common::*;
+use super::super::super::linux::netdevice::*;
+use super::super::super::linux_def::*;
+use super::super::fd::*;
+use super::super::fs::host::util::*;
+use super::super::Kernel::HostSpace;
+
+const MAX_INTERFACES: usize = 64;
+
+// HOST_NET_DEV is the host file of the device counters, the sandbox process
+// is in the sandbox network namespace.
+pub const HOST_NET_DEV: &str = "/proc/net/dev";
+
+// NetDevStats is the counters of a network device, as the columns of
+// /proc/net/dev.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NetDevStats {
+    pub rxBytes: u64,
+    pub rxPackets: u64,
+    pub rxErrs: u64,
+    pub rxDrop: u64,
+    pub rxFifo: u64,
+    pub rxFrame: u64,
+    pub rxCompressed: u64,
+    pub rxMulticast: u64,
+    pub txBytes: u64,
+    pub txPackets: u64,
+    pub txErrs: u64,
+    pub txDrop: u64,
+    pub txFifo: u64,
+    pub txColls: u64,
+    pub txCarrier: u64,
+    pub txCompressed: u64,
+}
+
+impl NetDevStats {
+    pub const FIELD_COUNT: usize = 16;
+
+    pub fn FromFields(v: &[u64; Self::FIELD_COUNT]) -> Self {
+        return Self {
+            rxBytes: v[0],
+            rxPackets: v[1],
+            rxErrs: v[2],
+            rxDrop: v[3],
+            rxFifo: v[4],
+            rxFrame: v[5],
+            rxCompressed: v[6],
+            rxMulticast: v[7],
+            txBytes: v[8],
+            txPackets: v[9],
+            txErrs: v[10],
+            txDrop: v[11],
+            txFifo: v[12],
+            txColls: v[13],
+            txCarrier: v[14],
+            txCompressed: v[15],
+        };
+    }
+
+    pub fn Fields(&self) -> [u64; Self::FIELD_COUNT] {
+        return [
+            self.rxBytes,
+            self.rxPackets,
+            self.rxErrs,
+            self.rxDrop,
+            self.rxFifo,
+            self.rxFrame,
+            self.rxCompressed,
+            self.rxMulticast,
+            self.txBytes,
+            self.txPackets,
+            self.txErrs,
+            self.txDrop,
+            self.txFifo,
+            self.txColls,
+            self.txCarrier,
+            self.txCompressed,
+        ];
+    }
+}
+
+// NetDevice is the attributes and counters of a network device
+#[derive(Debug, Default, Clone)]
+pub struct NetDevice {
+    pub name: String,
+    pub index: i32,
+    pub flags: u16,
+    pub mtu: i32,
+    pub txQueueLen: i32,
+    pub hwType: u16,
+    pub address: [u8; 6],
+    pub stats: NetDevStats,
+}
+
+impl NetDevice {
+    pub fn OperState(&self) -> &'static str {
+        if self.flags as u64 & LibcConst::IFF_LOOPBACK != 0 {
+            return "unknown";
+        }
+
+        if self.flags as u64 & LibcConst::IFF_RUNNING != 0 {
+            return "up";
+        }
+
+        return "down";
+    }
+
+    pub fn Address(&self) -> String {
+        let bytes: Vec<String> = self.address.iter().map(|b| format!("{:02x}", b)).collect();
+        return bytes.join(":");
+    }
+}
+
+fn IfReqIoctl(fd: i32, request: u64, name: &str) -> Result<IFReq> {
+    let mut ifr = IFReq::default();
+    ifr.SetName(name);
+    let res = HostSpace::IoCtl(fd, request, &mut ifr as *mut _ as u64);
+    if res < 0 {
+        return Err(Error::SysError(-res as i32));
+    }
+
+    return Ok(ifr);
+}
+
+fn IfReqInt(ifr: &IFReq) -> i32 {
+    return i32::from_ne_bytes([ifr.Data[0], ifr.Data[1], ifr.Data[2], ifr.Data[3]]);
+}
+
+fn HostNetDevice(fd: i32, name: &str) -> Result<NetDevice> {
+    let mut dev = NetDevice {
+        name: name.to_string(),
+        ..Default::default()
+    };
+
+    dev.index = IfReqInt(&IfReqIoctl(fd, LibcConst::SIOCGIFINDEX, name)?);
+    let ifr = IfReqIoctl(fd, LibcConst::SIOCGIFFLAGS, name)?;
+    dev.flags = u16::from_ne_bytes([ifr.Data[0], ifr.Data[1]]);
+    dev.mtu = IfReqIoctl(fd, LibcConst::SIOCGIFMTU, name).map_or(0, |ifr| IfReqInt(&ifr));
+    dev.txQueueLen = IfReqIoctl(fd, LibcConst::SIOCGIFTXQLEN, name).map_or(0, |ifr| IfReqInt(&ifr));
+
+    // ifr_hwaddr is a sockaddr, the family is the arp hardware type
+    if let Ok(ifr) = IfReqIoctl(fd, LibcConst::SIOCGIFHWADDR, name) {
+        dev.hwType = u16::from_ne_bytes([ifr.Data[0], ifr.Data[1]]);
+        dev.address.copy_from_slice(&ifr.Data[2..8]);
+    }
+
+    return Ok(dev);
+}
+
+// HostIfConfNames returns the names of the devices with an ipv4 address, as
+// SIOCGIFCONF does.
+fn HostIfConfNames(fd: i32) -> Vec<String> {
+    let reqs = vec![IFReq::default(); MAX_INTERFACES];
+    let mut ifc = IFConf {
+        Len: (reqs.len() * SIZE_OF_IFREQ) as i32,
+        Ptr: &reqs[0] as *const _ as u64,
+        ..Default::default()
+    };
+
+    let mut names: Vec<String> = Vec::new();
+    let res = HostSpace::IoCtl(fd, LibcConst::SIOCGIFCONF, &mut ifc as *mut _ as u64);
+    if res >= 0 {
+        let cnt = ifc.Len as usize / SIZE_OF_IFREQ;
+        for ifr in &reqs[..cnt] {
+            let name = ifr.Name();
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+
+    return names;
+}
+
+fn ReadHostFile(path: &str) -> Result<Vec<u8>> {
+    let (fd, _, _) = TryOpenAt(ATType::AT_FDCWD, path)?;
+
+    let mut data = Vec::new();
+    let mut buf = Vec::with_capacity(4096);
+    buf.resize(4096, 0);
+    let res = loop {
+        let iov = IoVec::New(&buf);
+        match IORead(fd, &[iov]) {
+            Err(e) => break Err(e),
+            Ok(0) => break Ok(()),
+            Ok(cnt) => data.extend_from_slice(&buf[..cnt as usize]),
+        }
+    };
+
+    HostSpace::Close(fd);
+    res?;
+    return Ok(data);
+}
+
+// ParseNetDev parses the device lines of /proc/net/dev.
+pub fn ParseNetDev(data: &[u8]) -> Vec<(String, NetDevStats)> {
+    let data = String::from_utf8_lossy(data);
+    let mut ret = Vec::new();
+    for line in data.lines() {
+        let idx = match line.find(':') {
+            None => continue,
+            Some(idx) => idx,
+        };
+
+        let mut fields = [0; NetDevStats::FIELD_COUNT];
+        let mut cnt = 0;
+        for v in line[idx + 1..].split_whitespace() {
+            if cnt == fields.len() {
+                break;
+            }
+
+            fields[cnt] = v.parse::<u64>().unwrap_or(0);
+            cnt += 1;
+        }
+
+        // the header lines have no counters
+        if cnt < fields.len() {
+            continue;
+        }
+
+        ret.push((
+            line[..idx].trim().to_string(),
+            NetDevStats::FromFields(&fields),
+        ));
+    }
+
+    return ret;
+}
+
+// NetDevices lists the network devices of the sandbox network namespace,
+// mirrored from the host. The devices and their counters are read from the
+// host /proc/net/dev, the devices with an ipv4 address are listed without
+// counters if it can't be read.
+pub fn NetDevices() -> Vec<NetDevice> {
+    let fd = HostSpace::Socket(AFType::AF_INET, SocketType::SOCK_DGRAM, 0) as i32;
+    if fd < 0 {
+        return Vec::new();
+    }
+
+    let devs = match ReadHostFile(HOST_NET_DEV) {
+        Ok(data) => ParseNetDev(&data),
+        Err(e) => {
+            info!("NetDevices: read {} fail {:?}", HOST_NET_DEV, e);
+            Vec::new()
+        }
+    };
+
+    let devs = if devs.len() > 0 {
+        devs
+    } else {
+        HostIfConfNames(fd)
+            .into_iter()
+            .map(|name| (name, NetDevStats::default()))
+            .collect()
+    };
+
+    let mut ret = Vec::new();
+    for (name, stats) in devs {
+        match HostNetDevice(fd, &name) {
+            Ok(mut dev) => {
+                dev.stats = stats;
+                ret.push(dev);
+            }
+            Err(e) => info!("NetDevices: {} fail {:?}", name, e),
+        }
+    }
+
+    HostSpace::Close(fd);
+    return ret;
+}
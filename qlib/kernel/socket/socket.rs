@@ -4,14 +4,19 @@ use alloc::collections::btree_map::BTreeMap;
 use alloc::string::ToString;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::AtomicI32;
 use core::sync::atomic::AtomicI64;
 use core::sync::atomic::Ordering;
 
 //use super::unix::transport::unix::*;
 use super::super::super::common::*;
+use super::super::super::config::HostFileCache;
 use super::super::super::device::*;
+use super::super::super::linux::bpf::*;
 use super::super::super::linux_def::*;
 use super::super::super::singleton::*;
+use super::super::bpf::*;
 use super::super::fs::dirent::*;
 use super::super::fs::file::*;
 use super::super::fs::filesystems::*;
@@ -20,12 +25,19 @@ use super::super::fs::host::util::*;
 use super::super::fs::inode::*;
 use super::super::fs::mount::*;
 use super::super::task::*;
+use super::super::threadmgr::task_usermem::*;
 
 pub static FAMILIAES: Singleton<QRwLock<Families>> = Singleton::<QRwLock<Families>>::New();
 pub static SOCKET_DEVICE: Singleton<Arc<QMutex<Device>>> = Singleton::<Arc<QMutex<Device>>>::New();
 pub static UNIX_SOCKET_DEVICE: Singleton<Arc<QMutex<Device>>> =
     Singleton::<Arc<QMutex<Device>>>::New();
 
+// The emulated net.core.somaxconn and net.ipv4.tcp_abort_on_overflow
+// sysctls, they are applied when a socket starts to listen.
+pub const SOMAXCONN_DEFAULT: i32 = 4096;
+pub static NET_SOMAXCONN: AtomicI32 = AtomicI32::new(SOMAXCONN_DEFAULT);
+pub static NET_TCP_ABORT_ON_OVERFLOW: AtomicBool = AtomicBool::new(false);
+
 pub unsafe fn InitSingleton() {
     FAMILIAES.Init(QRwLock::new(Families::New()));
     SOCKET_DEVICE.Init(NewAnonDevice());
@@ -136,6 +148,7 @@ pub fn NewSocketDirent(task: &Task, _d: Arc<QMutex<Device>>, fd: i32) -> Result<
         &WhitelistFileSystem::New(),
         &MountSourceFlags::default(),
         false,
+        HostFileCache::default(),
     );
 
     let mut fstat = LibcStat::default();
@@ -149,6 +162,23 @@ pub fn NewSocketDirent(task: &Task, _d: Arc<QMutex<Device>>, fd: i32) -> Result<
     return Ok(Dirent::New(&inode, &name.to_string()));
 }
 
+// CopyInFilter copies in and checks the program of SO_ATTACH_FILTER, optVal
+// is the struct sock_fprog.
+pub fn CopyInFilter(task: &Task, optVal: &[u8]) -> Result<BpfProgram> {
+    if optVal.len() < core::mem::size_of::<SockFprog>() {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    let fprog = unsafe { *(&optVal[0] as *const _ as u64 as *const SockFprog) };
+    let len = fprog.Len as usize;
+    if fprog.Filter == 0 || len == 0 || len > BPF_MAXINSNS {
+        return Err(Error::SysError(SysErr::EINVAL));
+    }
+
+    let instructions = UserSlice::<BPFInstruction>::New(fprog.Filter, len).Read(task)?;
+    return BpfProgram::Compile(instructions);
+}
+
 #[derive(Default)]
 pub struct SendReceiveTimeout {
     pub send: AtomicI64,
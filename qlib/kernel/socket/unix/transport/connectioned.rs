@@ -23,6 +23,7 @@ use crate::qlib::mutex::*;
 use super::super::super::super::super::common::*;
 use super::super::super::super::super::linux::socket::*;
 use super::super::super::super::super::linux_def::*;
+use super::super::super::super::bpf::*;
 use super::super::super::super::kernel::waiter::bufchan::*;
 use super::super::super::super::kernel::waiter::*;
 use super::super::super::super::task::*;
@@ -386,6 +387,12 @@ impl Passcred for ConnectionedEndPoint {
     }
 }
 
+impl SockFilter for ConnectionedEndPoint {
+    fn SockFilter(&self) -> Option<Arc<BpfProgram>> {
+        return self.baseEndpoint.SockFilter();
+    }
+}
+
 impl PartialEndPoint for ConnectionedEndPoint {
     fn Type(&self) -> i32 {
         return self.stype;
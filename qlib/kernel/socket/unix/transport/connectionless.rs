@@ -22,6 +22,7 @@ use core::ops::Deref;
 use super::super::super::super::super::common::*;
 use super::super::super::super::super::linux::socket::*;
 use super::super::super::super::super::linux_def::*;
+use super::super::super::super::bpf::*;
 use super::super::super::super::kernel::waiter::*;
 use super::super::super::super::task::*;
 use super::super::super::super::tcpip::tcpip::*;
@@ -129,6 +130,12 @@ impl Passcred for ConnectionLessEndPoint {
     }
 }
 
+impl SockFilter for ConnectionLessEndPoint {
+    fn SockFilter(&self) -> Option<Arc<BpfProgram>> {
+        return self.0.SockFilter();
+    }
+}
+
 impl PartialEndPoint for ConnectionLessEndPoint {
     fn Type(&self) -> i32 {
         return SockType::SOCK_DGRAM;
@@ -22,6 +22,7 @@ use core::ops::Deref;
 use super::super::super::super::super::common::*;
 use super::super::super::super::super::linux_def::*;
 use super::super::super::super::super::mem::seq::*;
+use super::super::super::super::bpf::*;
 use super::super::super::super::kernel::waiter::*;
 use super::super::super::super::task::*;
 use super::super::super::super::tcpip::tcpip::*;
@@ -82,7 +83,13 @@ pub trait Passcred {
     fn Passcred(&self) -> bool;
 }
 
-pub trait PartialEndPoint: Passcred + Sync + Send {
+pub trait SockFilter {
+    // SockFilter returns the SO_ATTACH_FILTER program of this end, it is run
+    // on the datagrams received.
+    fn SockFilter(&self) -> Option<Arc<BpfProgram>>;
+}
+
+pub trait PartialEndPoint: Passcred + SockFilter + Sync + Send {
     // GetLocalAddress implements Endpoint.GetLocalAddress.
     fn GetLocalAddress(&self) -> Result<SockAddrUnix>;
 
@@ -401,6 +408,15 @@ impl Passcred for BoundEndpoint {
     }
 }
 
+impl SockFilter for BoundEndpoint {
+    fn SockFilter(&self) -> Option<Arc<BpfProgram>> {
+        match self {
+            BoundEndpoint::Connected(ref c) => return c.SockFilter(),
+            BoundEndpoint::ConnectLess(ref c) => return c.SockFilter(),
+        }
+    }
+}
+
 impl ConnectedPasscred for BoundEndpoint {
     fn ConnectedPasscred(&self) -> bool {
         match self {
@@ -858,6 +874,12 @@ impl Passcred for UnixConnectedEndpoint {
     }
 }
 
+impl SockFilter for UnixConnectedEndpoint {
+    fn SockFilter(&self) -> Option<Arc<BpfProgram>> {
+        return self.endpoint.SockFilter();
+    }
+}
+
 impl PartialEndPoint for UnixConnectedEndpoint {
     // GetLocalAddress implements ConnectedEndpoint.GetLocalAddress.
     fn GetLocalAddress(&self) -> Result<SockAddrUnix> {
@@ -895,9 +917,26 @@ impl ConnectedEndpoint for UnixConnectedEndpoint {
             }
         }
 
-        let v = bs.ToVec();
+        let mut v = bs.ToVec();
+        if !truncate {
+            // as Linux, the filter of the receiver drops or trims the
+            // datagram without an error to the sender
+            if let Some(filter) = self.endpoint.SockFilter() {
+                let n = filter.Exec(&v) as usize;
+                if n == 0 {
+                    return Ok((l, false));
+                }
+
+                v.truncate(n);
+            }
+        }
+
         let message = Message::New(v, controlMessage.clone(), from.clone());
-        let (l, notify) = self.writeQueue.Enqueue(message, truncate)?;
+        let (n, notify) = self.writeQueue.Enqueue(message, truncate)?;
+        if truncate {
+            return Ok((n, notify));
+        }
+
         return Ok((l, notify));
     }
 
@@ -971,6 +1010,11 @@ pub struct BaseEndpointInternal {
 
     // an virutal host fd to handle IOCTL: SIOCGIFCONF call
     pub hostfd: i32,
+
+    // filter is the program of SO_ATTACH_FILTER, filterLocked is set by
+    // SO_LOCK_FILTER.
+    pub filter: Option<Arc<BpfProgram>>,
+    pub filterLocked: bool,
 }
 
 impl Default for BaseEndpointInternal {
@@ -983,6 +1027,8 @@ impl Default for BaseEndpointInternal {
             connected: None,
             path: String::default(),
             hostfd: 0,
+            filter: None,
+            filterLocked: false,
         };
     }
 }
@@ -1036,6 +1082,12 @@ impl Passcred for BaseEndpoint {
     }
 }
 
+impl SockFilter for BaseEndpoint {
+    fn SockFilter(&self) -> Option<Arc<BpfProgram>> {
+        return self.lock().filter.clone();
+    }
+}
+
 impl PartialEndPoint for BaseEndpoint {
     // GetLocalAddress implements Endpoint.GetLocalAddress.
     fn GetLocalAddress(&self) -> Result<SockAddrUnix> {
@@ -1095,6 +1147,32 @@ impl BaseEndpoint {
         return connected.is_some() && connected.as_ref().unwrap().Passcred();
     }
 
+    // SetFilter attaches the socket filter, or detaches it with None.
+    pub fn SetFilter(&self, filter: Option<Arc<BpfProgram>>) -> Result<()> {
+        let mut e = self.lock();
+        if e.filterLocked {
+            return Err(Error::SysError(SysErr::EPERM));
+        }
+
+        if filter.is_none() && e.filter.is_none() {
+            return Err(Error::SysError(SysErr::ENOENT));
+        }
+
+        e.filter = filter;
+        return Ok(());
+    }
+
+    // LockFilter sets SO_LOCK_FILTER, which can't be cleared once set.
+    pub fn LockFilter(&self, lock: bool) -> Result<()> {
+        let mut e = self.lock();
+        if e.filterLocked && !lock {
+            return Err(Error::SysError(SysErr::EPERM));
+        }
+
+        e.filterLocked = lock;
+        return Ok(());
+    }
+
     fn setPasscred(&self, pc: bool) {
         if pc {
             self.lock().passcred = 1;
@@ -1178,13 +1256,10 @@ impl BaseEndpoint {
         match *opt {
             SockOpt::ErrorOption => return Ok(()),
             SockOpt::SendQueueSizeOption(_) => {
-                let qs = {
-                    let e = self.lock();
-                    if !e.Connected() {
-                        return Err(Error::SysError(TcpipErr::ERR_NOT_CONNECTED.sysErr));
-                    }
-
-                    e.connected.as_ref().unwrap().SendQueuedSize() as i32
+                // nothing is queued by a socket not connected, as on Linux
+                let qs = match &self.lock().connected {
+                    None => 0,
+                    Some(connected) => connected.SendQueuedSize() as i32,
                 };
 
                 if qs < 0 {
@@ -1196,13 +1271,10 @@ impl BaseEndpoint {
                 return Ok(());
             }
             SockOpt::ReceiveQueueSizeOption(_) => {
-                let qs = {
-                    let e = self.lock();
-                    if !e.Connected() {
-                        return Err(Error::SysError(TcpipErr::ERR_NOT_CONNECTED.sysErr));
-                    }
-
-                    e.receiver.as_ref().unwrap().RecvQueuedSize() as i32
+                // a bound datagram socket receives without being connected
+                let qs = match &self.lock().receiver {
+                    None => 0,
+                    Some(receiver) => receiver.RecvQueuedSize() as i32,
                 };
 
                 if qs < 0 {
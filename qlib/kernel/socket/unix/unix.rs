@@ -48,6 +48,7 @@ use super::super::super::Kernel::HostSpace;
 use super::super::super::socket::control::*;
 use super::super::super::socket::epsocket::epsocket::*;
 use super::super::super::tcpip::tcpip::*;
+use super::super::super::threadmgr::task_usermem::*;
 use super::transport::connectioned::*;
 use super::transport::connectionless::*;
 use super::transport::unix::*;
@@ -928,14 +929,14 @@ impl SockOperations for UnixSocketOperations {
 
                 return Err(Error::SysError(SysErr::EOPNOTSUPP));
             } else {
-                task.CopyInVec(msgHdr.msgName, msgHdr.nameLen as usize)?
+                UserSlice::New(msgHdr.msgName, msgHdr.nameLen as usize).Read(task)?
             }
         } else {
             Vec::new()
         };
 
         let controlVec: Vec<u8> = if msgHdr.msgControl != 0 {
-            task.CopyInVec(msgHdr.msgControl, msgHdr.msgControlLen as usize)?
+            UserSlice::New(msgHdr.msgControl, msgHdr.msgControlLen as usize).Read(task)?
         } else {
             Vec::new()
         };
@@ -19,6 +19,7 @@ use core::mem;
 use super::super::auxv::*;
 use super::super::common::*;
 use super::task::*;
+use super::threadmgr::task_usermem::*;
 
 // StackLayout describes the location of the arguments and environment on the
 // stack.
@@ -49,7 +50,7 @@ impl Stack {
     pub fn PushType<T: Copy>(&mut self, task: &Task, data: &T) -> Result<u64> {
         let size = mem::size_of::<T>();
         self.sp -= size as u64;
-        task.CopyOutObj(data, self.sp).expect(&format!(
+        UserPtr::New(self.sp).Write(task, data).expect(&format!(
             "data {:x}, sp {:x}",
             data as *const _ as u64, self.sp
         ));
@@ -58,17 +59,21 @@ impl Stack {
 
     pub fn PushSlice(&mut self, task: &Task, data: &[u8]) -> Result<u64> {
         self.sp -= data.len() as u64;
-        task.CopyOutSlice(data, self.sp, data.len()).expect(&format!(
-            "data {:x}, sp {:x}",
-            &data[0] as *const _ as u64, self.sp
-        ));
+        UserSlice::New(self.sp, data.len())
+            .Write(task, data)
+            .expect(&format!(
+                "data {:x}, sp {:x}",
+                &data[0] as *const _ as u64, self.sp
+            ));
         return Ok(self.sp);
     }
 
     pub fn PopSlice(&mut self, task: &Task, data: &mut [u8]) -> Result<u64> {
-        let v : Vec<u8> = task.CopyInVec(self.sp, data.len()).expect(&format!(
-            "data {:x}, sp {:x}",
-            &data[0] as *const _ as u64, self.sp));
+        let v : Vec<u8> = UserSlice::New(self.sp, data.len())
+            .Read(task)
+            .expect(&format!(
+                "data {:x}, sp {:x}",
+                &data[0] as *const _ as u64, self.sp));
         for i in 0..data.len() {
             data[i] = v[i];
         }
@@ -79,7 +84,7 @@ impl Stack {
 
     pub fn PopType<T: Copy>(&mut self, task: &Task, data: &mut T) -> Result<u64> {
         let size = mem::size_of::<T>();
-        *data = task.CopyInObj(self.sp)?;
+        *data = UserPtr::New(self.sp).Read(task)?;
 
         self.sp += size as u64;
         return Ok(self.sp);
@@ -94,25 +99,25 @@ impl Stack {
 
     pub fn PushU64(&mut self, task: &Task, val: u64) -> Result<u64> {
         self.sp = self.sp - 8;
-        task.CopyOutObj(&val, self.sp)?;
+        UserPtr::New(self.sp).Write(task, &val)?;
         return Ok(self.sp);
     }
 
     pub fn PushU32(&mut self, task: &Task, val: u32) -> Result<u64> {
         self.sp = self.sp - 4;
-        task.CopyOutObj(&val, self.sp)?;
+        UserPtr::New(self.sp).Write(task, &val)?;
         return Ok(self.sp);
     }
 
     pub fn PushU16(&mut self, task: &Task, val: u16) -> Result<u64> {
         self.sp = self.sp - 2;
-        task.CopyOutObj(&val, self.sp)?;
+        UserPtr::New(self.sp).Write(task, &val)?;
         return Ok(self.sp);
     }
 
     pub fn PushU8(&mut self, task: &Task, val: u8) -> Result<u64> {
         self.sp = self.sp - 1;
-        task.CopyOutObj(&val, self.sp)?;
+        UserPtr::New(self.sp).Write(task, &val)?;
         return Ok(self.sp);
     }
 
@@ -200,6 +205,69 @@ impl Stack {
         self.PushU64(task, argAddrs.len() as u64)?;
         return Ok(l);
     }
+
+    // LoadEnv32 is LoadEnv for a 32 bit compat process, argc, the pointers and
+    // the aux vector entries are 4 bytes each.
+    pub fn LoadEnv32(
+        &mut self,
+        task: &Task,
+        envs: &[String],
+        args: &[String],
+        auxv: &[AuxEntry],
+    ) -> Result<StackLayout> {
+        let mut l = StackLayout::default();
+
+        self.Pad16(task)?;
+
+        l.EvvvEnd = self.sp;
+        let mut envAddrs = Vec::new();
+        for i in 0..envs.len() {
+            let idx = envs.len() - i - 1;
+            let addr = self.PushStr(task, envs[idx].as_str())?;
+            envAddrs.push(addr);
+        }
+        l.EnvvStart = self.sp;
+
+        l.ArgvEnd = self.sp;
+        let mut argAddrs: Vec<u64> = Vec::new();
+        for i in 0..args.len() {
+            let idx = args.len() - i - 1;
+            let addr = self.PushStr(task, args[idx].as_str())?;
+            argAddrs.push(addr);
+        }
+        l.ArgvStart = self.sp;
+
+        // the i386 abi needs the 16 bytes alignment at the argc
+        let argvSize = 4 * (args.len() + 1);
+        let envvSize = 4 * (envs.len() + 1);
+        let auxvSize = 4 * 2 * (auxv.len() + 1);
+        let total = argvSize + envvSize + auxvSize + 4;
+        let expectedBottom = self.sp - total as u64;
+        if expectedBottom % 16 != 0 {
+            self.sp -= expectedBottom % 16;
+        }
+
+        for i in 0..auxv.len() {
+            self.PushU32(task, auxv[i].Val as u32)?;
+            self.PushU32(task, auxv[i].Key as u32)?;
+        }
+        self.PushU32(task, 0)?;
+
+        /*env*/
+        for i in 0..envAddrs.len() {
+            self.PushU32(task, envAddrs[i] as u32)?;
+        }
+        self.PushU32(task, 0)?;
+
+        /*argv*/
+        for i in 0..argAddrs.len() {
+            self.PushU32(task, argAddrs[i] as u32)?;
+        }
+
+        /*argc*/
+        self.PushU32(task, argAddrs.len() as u32)?;
+        return Ok(l);
+    }
 }
 
 pub struct KernelStack {
@@ -0,0 +1,156 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::AtomicI32;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering;
+
+use super::super::common::*;
+use super::super::config::*;
+use super::super::control_msg::*;
+use super::super::linux_def::*;
+use super::super::SysCallID;
+use super::Kernel::HostSpace;
+use super::Scale;
+use super::SHARESPACE;
+
+pub static STRACE: Strace = Strace::New();
+
+// the max count of the thread groups in the pid filter
+pub const STRACE_MAX_PIDS: usize = 16;
+const STRACE_SYSCALL_WORDS: usize = (SysCallID::maxsupport as usize + 63) / 64;
+
+// Strace is the runtime switchable syscall tracer. It is configured through
+// the Strace control message and checked by the syscall handler for each syscall.
+// The filter is kept in atomics so that the check doesn't take a lock.
+pub struct Strace {
+    pub enable: AtomicBool,
+    // pidCnt is the count of the valid pids, 0 means all the thread groups
+    pub pidCnt: AtomicUsize,
+    pub pids: [AtomicI32; STRACE_MAX_PIDS],
+    // allSyscalls is set when the syscall filter is empty
+    pub allSyscalls: AtomicBool,
+    // syscalls is the bitmap of the traced syscall numbers
+    pub syscalls: [AtomicU64; STRACE_SYSCALL_WORDS],
+}
+
+impl Strace {
+    pub const fn New() -> Self {
+        const PID: AtomicI32 = AtomicI32::new(0);
+        const WORD: AtomicU64 = AtomicU64::new(0);
+        return Self {
+            enable: AtomicBool::new(false),
+            pidCnt: AtomicUsize::new(0),
+            pids: [PID; STRACE_MAX_PIDS],
+            allSyscalls: AtomicBool::new(true),
+            syscalls: [WORD; STRACE_SYSCALL_WORDS],
+        };
+    }
+
+    pub fn Configure(&self, args: &StraceArgs) -> Result<()> {
+        if args.Pids.len() > STRACE_MAX_PIDS {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        for nr in &args.Syscalls {
+            if *nr >= SysCallID::maxsupport as u64 {
+                return Err(Error::SysError(SysErr::EINVAL));
+            }
+        }
+
+        // the tracing is off while the filter is updated
+        self.enable.store(false, Ordering::SeqCst);
+
+        for (i, pid) in args.Pids.iter().enumerate() {
+            self.pids[i].store(*pid, Ordering::Relaxed);
+        }
+        self.pidCnt.store(args.Pids.len(), Ordering::Relaxed);
+
+        for word in &self.syscalls {
+            word.store(0, Ordering::Relaxed);
+        }
+        for nr in &args.Syscalls {
+            self.syscalls[*nr as usize / 64].fetch_or(1 << (*nr % 64), Ordering::Relaxed);
+        }
+        self.allSyscalls.store(args.Syscalls.len() == 0, Ordering::Relaxed);
+
+        self.enable.store(args.Enable, Ordering::SeqCst);
+        return Ok(());
+    }
+
+    #[inline]
+    pub fn Enabled(&self) -> bool {
+        return self.enable.load(Ordering::Relaxed);
+    }
+
+    // Match returns whether the syscall nr issued by thread group pid passes the filter.
+    pub fn Match(&self, pid: i32, nr: u64) -> bool {
+        let pidCnt = self.pidCnt.load(Ordering::Relaxed);
+        if pidCnt > 0 {
+            let mut found = false;
+            for i in 0..pidCnt {
+                if self.pids[i].load(Ordering::Relaxed) == pid {
+                    found = true;
+                    break;
+                }
+            }
+
+            if !found {
+                return false;
+            }
+        }
+
+        if !self.allSyscalls.load(Ordering::Relaxed) {
+            let word = self.syscalls[nr as usize / 64].load(Ordering::Relaxed);
+            if word & (1 << (nr % 64)) == 0 {
+                return false;
+            }
+        }
+
+        return true;
+    }
+
+    fn Output(str: &str) {
+        if SHARESPACE.config.read().SyncPrint() {
+            HostSpace::SyncPrint(DebugLevel::Error, &format!("[STRACE] {}", str));
+        } else {
+            HostSpace::Kprint(&format!("[STRACE] {}\n", str));
+        }
+    }
+
+    pub fn SyscallEnter(tid: i32, pid: i32, nr: u64, args: &[u64; 6]) {
+        let callId: SysCallID = unsafe { core::mem::transmute(nr) };
+        Self::Output(&format!(
+            "({}/{}) E {:?}({:x}, {:x}, {:x}, {:x}, {:x}, {:x})",
+            tid, pid, callId, args[0], args[1], args[2], args[3], args[4], args[5]
+        ));
+    }
+
+    // SyscallExit logs the syscall return value and the latency in microseconds.
+    // startTsc and endTsc are TSC counter values.
+    pub fn SyscallExit(tid: i32, pid: i32, nr: u64, ret: u64, startTsc: i64, endTsc: i64) {
+        let callId: SysCallID = unsafe { core::mem::transmute(nr) };
+        Self::Output(&format!(
+            "({}/{}) X {:?} = {} ({:x}) [{} us]",
+            tid,
+            pid,
+            callId,
+            ret as i64,
+            ret,
+            Scale(endTsc - startTsc)
+        ));
+    }
+}
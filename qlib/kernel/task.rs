@@ -20,6 +20,7 @@ use alloc::vec::Vec;
 use core::mem;
 use core::ops::Deref;
 use core::ptr;
+use core::sync::atomic::AtomicU64;
 use core::sync::atomic::Ordering;
 
 //use super::arch::x86_64::arch_x86::*;
@@ -27,6 +28,7 @@ use super::super::super::kernel_def::*;
 use super::super::auth::*;
 use super::super::common::*;
 use super::super::linux_def::*;
+use super::super::limits::*;
 use super::super::perf_tunning::*;
 use super::super::task_mgr::*;
 use super::super::usage::io::*;
@@ -136,27 +138,52 @@ pub struct TidInfo {
     pub robust_list_head: u64,
 }
 
+// STACK_CANARY is the canary of the kernel stacks. It is drawn from the boot
+// entropy of qvisor, the fixed MAGIC_GUILD is only used without it.
+pub static STACK_CANARY: AtomicU64 = AtomicU64::new(Guard::MAGIC_GUILD);
+
+// InitStackCanary must run before the first Task is created.
+pub fn InitStackCanary(seed: u64) {
+    if seed == 0 {
+        return;
+    }
+
+    // keep a zero byte like linux, a string overflow can't write the canary
+    STACK_CANARY.store(seed & !0xff, Ordering::Relaxed);
+}
+
 #[derive(Debug)]
 pub struct Guard(u64);
 
 impl Default for Guard {
     fn default() -> Self {
-        return Self(Self::MAGIC_GUILD);
+        return Self(STACK_CANARY.load(Ordering::Relaxed));
     }
 }
 
 impl Guard {
     const MAGIC_GUILD: u64 = 0x1234567890abcd;
 
+    // GUARD_ZONE_SIZE is the size of the canary filled zone between the Task
+    // and the bottom of its kernel stack. It catches the overflow of the
+    // stacks from the heap, the stacks in the stack area also have an
+    // unmapped guard page above it.
+    pub const GUARD_ZONE_SIZE: u64 = 1024;
+
     #[inline(always)]
     pub fn Check(&self) {
-        if self.0 != Self::MAGIC_GUILD {
+        Self::CheckVal(self.0);
+        //assert!(self.0==Self::MAGIC_GUILD)
+    }
+
+    #[inline(always)]
+    pub fn CheckVal(val: u64) {
+        if val != STACK_CANARY.load(Ordering::Relaxed) {
             let task = Task::Current();
-            raw!(0x240, task.taskId, self.0);
+            raw!(0x240, task.taskId, val);
             super::Kernel::HostSpace::VcpuDebug();
             loop {}
         }
-        //assert!(self.0==Self::MAGIC_GUILD)
     }
 }
 
@@ -211,6 +238,35 @@ impl Task {
     #[inline(always)]
     pub fn Check(&self) {
         self.guard.Check();
+        if self.taskId != 0 {
+            // the top of the guard zone is the first word an overflow hits
+            let top = self.GuardZone() + Guard::GUARD_ZONE_SIZE - 8;
+            Guard::CheckVal(unsafe { *(top as *const u64) });
+        }
+    }
+
+    // GuardZone returns the start of the guard zone above the Task.
+    #[inline(always)]
+    pub fn GuardZone(&self) -> u64 {
+        return (self.taskId + mem::size_of::<Task>() as u64 + 7) & !7;
+    }
+
+    // StackGuardOffset returns the offset of the unmapped guard page of the
+    // kernel stacks in the stack area, it is right above the guard zone.
+    pub fn StackGuardOffset() -> u64 {
+        let zoneEnd = ((mem::size_of::<Task>() as u64 + 7) & !7) + Guard::GUARD_ZONE_SIZE;
+        return (zoneEnd + MemoryDef::PAGE_SIZE - 1) & !(MemoryDef::PAGE_SIZE - 1);
+    }
+
+    // InitStackGuard fills the guard zone of a new kernel stack.
+    pub fn InitStackGuard(&self) {
+        let canary = STACK_CANARY.load(Ordering::Relaxed);
+        let start = self.GuardZone();
+        for i in 0..Guard::GUARD_ZONE_SIZE / 8 {
+            unsafe {
+                *((start + i * 8) as *mut u64) = canary;
+            }
+        }
     }
 
     //clean object on stack
@@ -234,11 +290,32 @@ impl Task {
     }
 
     pub fn SaveFp(&self) {
-        self.context.X86fpstate.SaveFp();
+        let fpstate = &self.context.X86fpstate;
+        fpstate.SaveFp();
+        fpstate.SetToken(CPULocal::Myself().NewFpToken());
+        // the kernel accesses the user memory with all the protection keys,
+        // RestoreFp loads the pkru of the task again
+        if SUPPORT_PKU.load(Ordering::Relaxed) {
+            wrpkru(0);
+        }
     }
 
     pub fn RestoreFp(&self) {
-        self.context.X86fpstate.RestoreFp();
+        let fpstate = &self.context.X86fpstate;
+        let cpuLocal = CPULocal::Myself();
+        let token = fpstate.Token();
+        // the kernel doesn't use the fp registers, they still hold the state
+        // if it is the last one saved or restored on this vcpu and it is not
+        // changed in memory since
+        if token != 0 && token == cpuLocal.FpToken() {
+            if SUPPORT_PKU.load(Ordering::Relaxed) {
+                wrpkru(fpstate.PKRU());
+            }
+            return;
+        }
+
+        fpstate.RestoreFp();
+        cpuLocal.SetFpToken(token);
     }
 
     pub fn QueueId(&self) -> usize {
@@ -327,10 +404,34 @@ impl Task {
             }
         }
 
+        if state == SchedState::Blocked {
+            if t.Yielding {
+                t.InvoluntaryCount += 1;
+            } else {
+                t.YieldCount += 1;
+            }
+        }
+
         t.Timestamp = now;
         t.State = state;
     }
 
+    pub fn AccountIOWait(&self, start: i64) {
+        let now = TSC.Rdtsc();
+        if start < now {
+            self.sched.lock().IODelayTicks += now - start;
+        }
+    }
+
+    pub fn AccountPageFault(&self, major: bool) {
+        let mut t = self.sched.lock();
+        if major {
+            t.MajFlt += 1;
+        } else {
+            t.MinFlt += 1;
+        }
+    }
+
     pub fn AccountTaskLeave(&self, state: SchedState) {
         //print!("AccountTaskLeave current task is {:x}, state is {:?}", self.taskId, state);
         if self.taskId == CPULocal::WaitTask() {
@@ -359,6 +460,11 @@ impl Task {
             t.UserTicks += now - t.Timestamp;
         }
 
+        if state == SchedState::Blocked {
+            t.AccountRunDelay(now);
+            t.Pcount += 1;
+        }
+
         t.Timestamp = now;
         t.State = SchedState::RunningSys;
     }
@@ -444,11 +550,32 @@ impl Task {
         return self.fdTbl.lock().SetFlags(fd, flags);
     }
 
+    // FileLimit returns the soft RLIMIT_NOFILE of the task as an exclusive fd
+    // upper bound.
+    pub fn FileLimit(&self) -> i32 {
+        let t = match &self.thread {
+            None => return core::i32::MAX,
+            Some(t) => t.clone(),
+        };
+
+        let limit = t.ThreadGroup().Limits().Get(LimitType::NumberOfFiles).Cur;
+        if limit > core::i32::MAX as u64 {
+            return core::i32::MAX;
+        }
+
+        return limit as i32;
+    }
+
     pub fn NewFDs(&mut self, fd: i32, file: &[File], flags: &FDFlags) -> Result<Vec<i32>> {
-        return self.fdTbl.lock().NewFDs(fd, file, flags);
+        let end = self.FileLimit();
+        return self.fdTbl.lock().NewFDs(fd, file, flags, end);
     }
 
     pub fn NewFDAt(&mut self, fd: i32, file: &File, flags: &FDFlags) -> Result<()> {
+        if fd >= self.FileLimit() {
+            return Err(Error::SysError(SysErr::EBADF));
+        }
+
         return self.fdTbl.lock().NewFDAt(fd, file, flags);
     }
 
@@ -481,7 +608,8 @@ impl Task {
     pub fn NewFDFrom(&self, fd: i32, file: &File, flags: &FDFlags) -> Result<i32> {
         //let fds = self.fdTbl.lock().NewFDs(fd, vec![file.clone()], flags)?;
         //return Ok(fds[0])
-        return self.fdTbl.lock().NewFDFrom(fd, file, flags);
+        let end = self.FileLimit();
+        return self.fdTbl.lock().NewFDFrom(fd, file, flags, end);
     }
 
     pub fn RemoveFile(&self, fd: i32) -> Result<File> {
@@ -492,7 +620,8 @@ impl Task {
     }
 
     pub fn Dup(&mut self, oldfd: u64) -> i64 {
-        match self.fdTbl.lock().Dup(oldfd as i32) {
+        let end = self.FileLimit();
+        match self.fdTbl.lock().Dup(oldfd as i32, end) {
             Ok(fd) => fd as i64,
             Err(Error::SysError(e)) => -e as i64,
             Err(e) => panic!("unsupport error {:?}", e),
@@ -500,23 +629,26 @@ impl Task {
     }
 
     pub fn Dup2(&mut self, oldfd: u64, newfd: u64) -> i64 {
-        match self.fdTbl.lock().Dup2(oldfd as i32, newfd as i32) {
-            Ok(fd) => fd as i64,
+        if newfd as i32 >= self.FileLimit() {
+            return -SysErr::EBADF as i64;
+        }
+
+        let res = self.fdTbl.lock().Dup2(oldfd as i32, newfd as i32);
+        match res {
+            Ok(_) => newfd as i64,
             Err(Error::SysError(e)) => -e as i64,
             Err(e) => panic!("unsupport error {:?}", e),
         }
     }
 
-    pub fn Dup3(&mut self, oldfd: u64, newfd: u64, flags: u64) -> i64 {
-        match self
-            .fdTbl
-            .lock()
-            .Dup3(oldfd as i32, newfd as i32, flags as i32)
-        {
-            Ok(fd) => fd as i64,
-            Err(Error::SysError(e)) => -e as i64,
-            Err(e) => panic!("unsupport error {:?}", e),
+    // Dup3 returns the file that newfd referred to before, which the caller
+    // should close.
+    pub fn Dup3(&self, oldfd: i32, newfd: i32, flags: &FDFlags) -> Result<Option<File>> {
+        if newfd >= self.FileLimit() {
+            return Err(Error::SysError(SysErr::EBADF));
         }
+
+        return self.fdTbl.lock().Dup3(oldfd, newfd, flags);
     }
 
     #[inline(always)]
@@ -614,7 +746,7 @@ impl Task {
 
     pub fn Create(runFnAddr: u64, para: *const u8, kernel: bool) -> &'static mut Self {
         //let s_ptr = pa.Alloc(DEFAULT_STACK_PAGES).unwrap() as *mut u8;
-        let s_ptr = KERNEL_STACK_ALLOCATOR.Allocate().unwrap() as *mut u8;
+        let s_ptr = CPULocal::AllocKernelStack() as *mut u8;
 
         let size = DEFAULT_STACK_SIZE;
 
@@ -672,6 +804,7 @@ impl Task {
             );
 
             let new = &mut *taskPtr;
+            new.InitStackGuard();
             new.PerfGoto(PerfType::Blocked);
             new.PerfGoto(PerfType::Kernel);
             return &mut (*taskPtr);
@@ -774,6 +907,7 @@ impl Task {
                 },
             );
 
+            (*taskPtr).InitStackGuard();
             return &mut (*taskPtr);
         }
     }
@@ -793,9 +927,22 @@ impl Task {
 
     #[inline]
     pub fn SetFS(&self) {
+        // a 32 bit task gets its fs and gs bases from the tls descriptors
+        if self.context.tls32.compat {
+            self.context.tls32.Load();
+            return;
+        }
+
         SetFs(self.context.fs);
     }
 
+    // SaveFS saves the fs and gs selectors of a 32 bit task switched out.
+    pub fn SaveFS(&mut self) {
+        if self.context.tls32.compat {
+            self.context.tls32.Save();
+        }
+    }
+
     #[inline]
     pub fn GetContext(&self) -> u64 {
         return (&self.context as *const Context) as u64;
@@ -837,7 +984,7 @@ impl Task {
                 ..Default::default()
             }
         } else {
-            alt.flags &= SignalStack::FLAG_DISABLE;
+            alt.flags &= SignalStack::FLAG_AUTODISARM;
             self.signalStack = alt;
         }
 
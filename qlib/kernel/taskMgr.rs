@@ -25,8 +25,8 @@ use super::threadmgr::task_sched::*;
 use super::Kernel::HostSpace;
 use super::Shutdown;
 use super::ASYNC_PROCESS;
-use super::KERNEL_STACK_ALLOCATOR;
 use super::SHARESPACE;
+use super::Tsc;
 use super::TSC;
 
 static ACTIVE_TASK: AtomicU32 = AtomicU32::new(0);
@@ -57,6 +57,10 @@ extern "C" {
 
 fn switch_to(to: TaskId) {
     to.GetTask().AccountTaskLeave(SchedState::Blocked);
+    to.Context().runStart.store(TSC.Rdtsc() as u64, Ordering::Relaxed);
+    CPULocal::Myself()
+        .coreCookie
+        .store(to.Context().CoreCookie(), Ordering::Relaxed);
 
     CPULocal::SetCurrentTask(to.Addr());
     let toCtx = to.GetTask();
@@ -121,7 +125,10 @@ pub fn WaitFn() {
                 // if there is memory needs free and freed, continue free them
                 // while super::ALLOCATOR.Free() {}
 
-                if SHARESPACE.scheduler.GlobalReadyTaskCnt() == 0 {
+                // a parked vcpu waits in the host even if there are ready tasks
+                if SHARESPACE.scheduler.GlobalReadyTaskCnt() == 0
+                    || !SHARESPACE.scheduler.IsOnline(CPULocal::CpuId())
+                {
                     //debug!("vcpu sleep");
                     let addr = HostSpace::VcpuWait();
                     //debug!("vcpu wakeup {:x}", addr);
@@ -142,7 +149,7 @@ pub fn WaitFn() {
                 let pendingFreeStack = CPULocal::PendingFreeStack();
                 if pendingFreeStack != 0 {
                     //(*PAGE_ALLOCATOR).Free(pendingFreeStack, DEFAULT_STACK_PAGES).unwrap();
-                    KERNEL_STACK_ALLOCATOR.Free(pendingFreeStack).unwrap();
+                    CPULocal::FreeKernelStack(pendingFreeStack);
                     CPULocal::SetPendingFreeStack(0);
                 }
 
@@ -235,6 +242,10 @@ impl Scheduler {
         }
 
         let vcpuId = CPULocal::CpuId() as usize;
+        if !self.IsOnline(vcpuId) {
+            return None;
+        }
+
         let vcpuCount = self.vcpuCnt;
 
         match self.GetNextForCpu(vcpuId, 0) {
@@ -283,59 +294,65 @@ impl Scheduler {
 
     #[inline]
     pub fn GetNextForCpu(&self, currentCpuId: usize, vcpuId: usize) -> Option<TaskId> {
-        // only stealing task from running VCPU
+        // only stealing task from running or parked VCPU
         if vcpuId != 0
             && currentCpuId != vcpuId
             && CPULocal::GetCPUState(vcpuId) != VcpuState::Running
+            && self.IsOnline(vcpuId)
         {
             return None;
         }
 
-        let count = self.queue[vcpuId].lock().len();
-        for _ in 0..count {
-            let task = {
-                let mut queue = self.queue[vcpuId].lock();
-                let task = queue.pop_front();
-                if task.is_none() {
-                    return None;
-                }
-
-                let _cnt = self.DecReadyTaskCount();
-                task
+        // the queue is ordered by vruntime, take the first task whose context
+        // has been setup, a task in the queue might still be switching out.
+        // There is at most one such task of each vcpu, so the search stops
+        // in a few steps.
+        let current = Task::Current().taskId;
+        let (taskId, count) = {
+            let mut queue = self.queue[vcpuId].lock();
+            let key = match queue
+                .iter()
+                .find(|(_, t)| t.Context().Ready() != 0 || t.data == current)
+            {
+                None => return None,
+                Some((key, _)) => *key,
             };
 
-            let taskId = task.unwrap();
-
-            assert!(
-                vcpuId == taskId.GetTask().QueueId(),
-                "vcpuId is {:x}, taskId.GetTask().QueueId() is {:x}, task {:x?}/{:x?}",
-                vcpuId,
-                taskId.GetTask().QueueId(),
-                taskId,
-                taskId.GetTask().guard
-            );
-            if taskId.GetTask().context.Ready() != 0 || taskId.data == Task::Current().taskId {
-                //the task is in the queue, but the context has not been setup
-                if currentCpuId != vcpuId {
-                    //stealing
-                    //error!("cpu currentCpuId {} stealing task {:x?} from cpu {}", currentCpuId, taskId, vcpuId);
-
-                    taskId.GetTask().SetQueueId(currentCpuId);
-                } else {
-                    if count > 1 {
-                        // current CPU has more task, try to wake other vcpu to handle
-                        self.WakeOne();
-                    }
-                }
+            let count = queue.len();
+            let taskId = queue.remove(&key).unwrap();
+            self.queue[vcpuId].UpdateMinVruntime(taskId.Context().Vruntime());
+            let _cnt = self.DecReadyTaskCount();
+            (taskId, count)
+        };
 
-                //error!("GetNextForCpu task is {:x?}", taskId);
-                return task;
+        assert!(
+            vcpuId == taskId.GetTask().QueueId(),
+            "vcpuId is {:x}, taskId.GetTask().QueueId() is {:x}, task {:x?}/{:x?}",
+            vcpuId,
+            taskId.GetTask().QueueId(),
+            taskId,
+            taskId.GetTask().guard
+        );
+
+        if currentCpuId != vcpuId {
+            //stealing
+            //error!("cpu currentCpuId {} stealing task {:x?} from cpu {}", currentCpuId, taskId, vcpuId);
+
+            taskId.GetTask().SetQueueId(currentCpuId);
+            // keep the vruntime relative to the queue it moves to
+            let ctx = taskId.Context();
+            let vruntime = ctx.Vruntime() as i64 - self.queue[vcpuId].MinVruntime() as i64
+                + self.queue[currentCpuId].MinVruntime() as i64;
+            ctx.vruntime.store(core::cmp::max(vruntime, 0) as u64, Ordering::Relaxed);
+        } else {
+            if count > 1 {
+                // current CPU has more task, try to wake other vcpu to handle
+                self.WakeOne();
             }
-
-            self.ScheduleQ(taskId, vcpuId as u64);
         }
 
-        return None;
+        //error!("GetNextForCpu task is {:x?}", taskId);
+        return Some(taskId);
     }
 
     pub fn Schedule(&self, taskId: TaskId) {
@@ -346,6 +363,7 @@ impl Scheduler {
 
     pub fn KScheduleQ(&self, task: TaskId, vcpuId: usize) {
         //debug!("KScheduleQ task {:x?}, vcpuId {}", task, vcpuId);
+        task.GetTask().sched.lock().ReadyTimestamp = TSC.Rdtsc();
         self.ScheduleQ(task, vcpuId as u64);
     }
 
@@ -355,9 +373,27 @@ impl Scheduler {
     }
 }
 
+// ChargeRuntime adds the cpu time since the task was switched in to its
+// vruntime.
+pub fn ChargeRuntime(ctx: &Context) {
+    let now = TSC.Rdtsc();
+    let start = ctx.runStart.swap(now as u64, Ordering::Relaxed) as i64;
+    if start != 0 && start < now {
+        ctx.Charge((Tsc::Scale(now - start) * 1000) as u64);
+    }
+}
+
 pub fn Yield() {
+    // the task is still runnable, so a switch here is involuntary
+    Task::Current().sched.lock().Yielding = true;
+    // charge before the task goes back to the queue ordered by vruntime
+    ChargeRuntime(&Task::Current().context);
     SHARESPACE.scheduler.Schedule(Task::TaskId());
     Wait();
+    // Wait might pick the task itself without a switch
+    let mut sched = Task::Current().sched.lock();
+    sched.Yielding = false;
+    sched.AccountRunDelay(TSC.Rdtsc());
 }
 
 pub fn NewTask(taskId: TaskId) {
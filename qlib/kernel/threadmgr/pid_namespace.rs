@@ -255,6 +255,23 @@ impl PIDNamespace {
         };
     }
 
+    // AllocateTIDHint returns tid if it is unused in ns, otherwise an unused
+    // ThreadID like AllocateTID.
+    pub fn AllocateTIDHint(&self, tid: ThreadID) -> Result<ThreadID> {
+        {
+            let me = self.lock();
+            if me.exiting {
+                return Err(Error::SysError(SysErr::ENOMEM));
+            }
+
+            if tid >= INIT_TID && tid <= TASKS_LIMIT && !me.tasks.contains_key(&tid) {
+                return Ok(tid);
+            }
+        }
+
+        return self.AllocateTID();
+    }
+
     // allocateTID returns an unused ThreadID from ns.
     pub fn AllocateTID(&self) -> Result<ThreadID> {
         let mut me = self.lock();
@@ -158,13 +158,14 @@ impl ProcessGroup {
             false
         };
 
+        // the group becomes an orphan when its last link to a parent in
+        // another group of the same session goes away
+        let mut orphaned = false;
         if ok {
-            // if this pg was parentPg. But after reparent become non-parentpg. the ancestor will be sub to negtive
-            // todo: this is bug. fix it.
-
             let ancestors = self.lock().ancestors;
             if ancestors > 0 {
                 self.lock().ancestors = ancestors - 1;
+                orphaned = ancestors == 1;
             }
         }
 
@@ -203,14 +204,15 @@ impl ProcessGroup {
             self.lock().session.DecRef();
         }
 
-        if alive {
+        if alive && orphaned {
             self.handleOrphan();
         };
     }
 
     // handleOrphan checks whether the process group is an orphan and has any
     // stopped jobs. If yes, then appropriate signals are delivered to each thread
-    // group within the process group.
+    // group within the process group. It is called when the group has just
+    // become an orphan, compare kernel/exit.c:kill_orphaned_pgrp().
     //
     // Precondition: callers must hold TaskSet.mu for writing.
     pub fn handleOrphan(&self) {
@@ -322,6 +324,23 @@ impl ProcessGroup {
         return self.lock().session.clone();
     }
 
+    // ThreadGroups returns the thread groups that are currently members of the
+    // process group.
+    pub fn ThreadGroups(&self) -> Vec<ThreadGroup> {
+        let ts = self.lock().originator.TaskSet();
+        let rootns = ts.Root();
+
+        let _r = ts.ReadLock();
+        let mut tgs = Vec::new();
+        for (tg, _) in &rootns.lock().tgids {
+            if tg.ProcessGroup() == Some(self.clone()) {
+                tgs.push(tg.clone());
+            }
+        }
+
+        return tgs;
+    }
+
     pub fn SendSignal(&self, info: &SignalInfo) -> Result<()> {
         let ts = self.lock().originator.TaskSet();
         let mut lastError: Result<()> = Ok(());
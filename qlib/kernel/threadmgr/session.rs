@@ -18,6 +18,7 @@ use alloc::sync::Arc;
 use core::cmp::*;
 use core::ops::Deref;
 
+use super::super::fs::tty::job_control::*;
 use super::super::uid::NewUID;
 use super::processgroup::*;
 use super::thread::*;
@@ -30,6 +31,11 @@ pub struct SessionInternal {
     pub leader: ThreadGroup,
     pub refs: AtomicRefCount,
     pub processGroups: BTreeSet<ProcessGroup>,
+
+    // tty is the job control of the session's controlling terminal, if any.
+    // The reference back to the session is dropped when the terminal is
+    // released or the session leader exits.
+    pub tty: Option<JobControl>,
 }
 
 #[derive(Clone, Default)]
@@ -75,6 +81,7 @@ impl Session {
             leader: leader,
             refs: Default::default(),
             processGroups: BTreeSet::new(),
+            tty: None,
         };
         return Self {
             uid: NewUID(),
@@ -212,7 +212,9 @@ impl IOUsage for ThreadGroup {
         let owner = self.TaskSet();
         let _r = owner.ReadLock();
 
-        let io = self.lock().ioUsage.clone();
+        // accumulate into a new IO, ioUsage holds the usage of dead threads
+        let io = IO::default();
+        io.Accumulate(&self.lock().ioUsage);
         for t in &self.lock().tasks {
             io.Accumulate(&t.IOUsage())
         }
@@ -12,6 +12,10 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use alloc::sync::Arc;
+use core::sync::atomic::AtomicI64;
+use core::sync::atomic::Ordering;
+
 use super::super::super::common::*;
 use super::super::super::linux::time::*;
 use super::super::super::linux_def::*;
@@ -25,11 +29,31 @@ use super::super::kernel::waiter::*;
 use super::super::task::*;
 use super::super::threadmgr::thread::*;
 
+// DEFAULT_TIMER_SLACK is the timer slack of the init task, 50us as Linux.
+pub const DEFAULT_TIMER_SLACK: i64 = 50_000;
+
 impl Thread {
     pub fn Interrupted(&self, clear: bool) -> bool {
         let blocker = self.lock().blocker.clone();
         return blocker.Interrupted(clear);
     }
+
+    pub fn TimerSlack(&self) -> i64 {
+        return self.lock().blocker.TimerSlack();
+    }
+
+    // SetTimerSlack sets the timer slack of the thread, 0 restores the default
+    // slack inherited from its creator.
+    pub fn SetTimerSlack(&self, slack: i64) {
+        let t = self.lock();
+        let slack = if slack == 0 {
+            t.defaultTimerSlack
+        } else {
+            slack
+        };
+
+        t.blocker.SetTimerSlack(slack);
+    }
 }
 
 impl ThreadInternal {
@@ -57,6 +81,10 @@ pub struct Blocker {
 
     pub interruptEntry: WaitEntry,
     pub generalEntry: WaitEntry,
+
+    // timerSlack is the slack of the timeouts of the blocking waits, see
+    // prctl(PR_SET_TIMERSLACK).
+    pub timerSlack: Arc<AtomicI64>,
 }
 
 impl Default for Blocker {
@@ -91,6 +119,7 @@ impl Default for Blocker {
             monoBlockTimer: monoTimer,
             interruptEntry: interruptEntry,
             generalEntry: generalEntry,
+            timerSlack: Arc::new(AtomicI64::new(DEFAULT_TIMER_SLACK)),
         };
     }
 }
@@ -117,6 +146,7 @@ impl Blocker {
             monoBlockTimer: monoTimer,
             interruptEntry: interruptEntry,
             generalEntry: generalEntry,
+            timerSlack: Arc::new(AtomicI64::new(DEFAULT_TIMER_SLACK)),
         };
     }
 
@@ -153,6 +183,7 @@ impl Blocker {
             monoBlockTimer: monoTimer,
             interruptEntry: interruptEntry,
             generalEntry: generalEntry,
+            timerSlack: Arc::new(AtomicI64::new(DEFAULT_TIMER_SLACK)),
         };
     }
 
@@ -232,6 +263,7 @@ impl Blocker {
         }
 
         let deadline = deadline.unwrap();
+        timer.SetSlack(self.TimerSlack());
         timer.Swap(&Setting {
             Enabled: true,
             Next: deadline,
@@ -250,6 +282,14 @@ impl Blocker {
         return Err(Error::SysError(SysErr::ERESTARTNOHAND));
     }
 
+    pub fn TimerSlack(&self) -> i64 {
+        return self.timerSlack.load(Ordering::Relaxed);
+    }
+
+    pub fn SetTimerSlack(&self, slack: i64) {
+        self.timerSlack.store(slack, Ordering::Relaxed);
+    }
+
     pub fn GetTimerWithClock(&self, clock: &Clock) -> Timer {
         return Timer::New(
             clock,
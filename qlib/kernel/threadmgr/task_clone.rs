@@ -19,11 +19,16 @@ use alloc::vec::Vec;
 use core::ptr;
 
 use super::super::super::super::kernel_def::*;
+use super::super::super::auth::*;
 use super::super::super::common::*;
+use super::super::super::limits::*;
 use super::super::super::linux_def::*;
 use super::super::super::task_mgr::*;
+use super::super::super::vcpu_mgr::*;
+use super::super::arch::x86_64::arch_x86::*;
 use super::super::arch::x86_64::context::*;
 use super::super::kernel::ipc_namespace::*;
+use super::super::kernel::kernel::UserCharge;
 use super::super::threadmgr::task_start::*;
 use super::super::threadmgr::thread::*;
 use super::super::SignalDef::*;
@@ -33,6 +38,7 @@ use super::super::perflog::*;
 use super::super::task::*;
 use super::task_block::*;
 use super::task_stop::*;
+use super::thread_group::*;
 
 pub fn IsValidSegmentBase(addr: u64) -> bool {
     return addr < MAX_ADDR64;
@@ -145,6 +151,9 @@ pub struct CloneOptions {
     // for it. If both Untraced and InheritTracer are true, no event will be
     // reported, but tracer inheritance will still occur.
     pub InheritTracer: bool,
+
+    // Tid is the tid of the new task if it is free, 0 to allocate one.
+    pub Tid: ThreadID,
 }
 
 impl CloneOptions {
@@ -186,6 +195,7 @@ impl CloneOptions {
             Vfork: flags & CloneOp::CLONE_VFORK != 0,
             Untraced: flags & CloneOp::CLONE_UNTRACED != 0,
             InheritTracer: flags & CloneOp::CLONE_PTRACE != 0,
+            Tid: 0,
         };
 
         if opts.sharingOption.NewUserNamespace {
@@ -247,10 +257,11 @@ impl Thread {
             userns = creds.NewChildUserNamespace()?;
         }
 
-        if opts.sharingOption.NewPIDNamespace
+        if (opts.sharingOption.NewPIDNamespace
             || opts.sharingOption.NewNetworkNamespace
             || opts.sharingOption.NewUTSNamespace
-                && !creds.HasCapabilityIn(Capability::CAP_SYS_ADMIN, &userns)
+            || opts.sharingOption.NewIPCNamespace)
+            && !creds.HasCapabilityIn(Capability::CAP_SYS_ADMIN, &userns)
         {
             return Err(Error::SysError(SysErr::EPERM));
         }
@@ -320,6 +331,7 @@ impl Thread {
             TaskId: stackAddr,
             Kernel: t.k.clone(),
             Parent: None,
+            Tid: opts.Tid,
             InheritParent: None,
             ThreadGroup: tg.clone(),
             SignalMask: t.signalMask.clone(),
@@ -350,11 +362,39 @@ impl Thread {
         let ts = pidns.lock().owner.clone();
 
         let name = t.name.to_string();
+        let ioprio = t.ioprio;
+        let timerSlack = t.blocker.TimerSlack();
+        let container = t.k.Container(&t.containerID);
         core::mem::drop(t);
+
+        let userCharge = self.ChargeUserTask(&tg)?;
+
+        // the new task is counted against the pids limit of the container
+        if let Some(c) = &container {
+            c.AddTask()?;
+        }
+
         let kernel = self.lock().k.clone();
-        let nt = ts.NewTask(&cfg, false, &kernel)?;
+        let nt = match ts.NewTask(&cfg, false, &kernel) {
+            Ok(nt) => nt,
+            Err(e) => {
+                if let Some(c) = &container {
+                    c.RemoveTask();
+                }
+                return Err(e);
+            }
+        };
 
         nt.lock().name = name;
+        // the I/O priority is inherited like the io_context in Linux
+        nt.lock().ioprio = ioprio;
+        // the timer slack of the creator is the slack and the default slack of
+        // the new task
+        nt.lock().defaultTimerSlack = timerSlack;
+        nt.SetTimerSlack(timerSlack);
+        nt.lock().userCharge = Some(userCharge);
+        // the new task is in the core scheduling group of its creator
+        nt.SetCoreCookie(self.CoreCookie());
 
         if userns != creds.lock().UserNamespace.clone() {
             nt.SetUserNamespace(&userns)
@@ -369,6 +409,30 @@ impl Thread {
         return Ok(nt);
     }
 
+    // ChargeUserTask counts a new task of the caller's real user. It fails with
+    // EAGAIN when the user already has RLIMIT_NPROC tasks, unless the caller is
+    // root or has CAP_SYS_RESOURCE or CAP_SYS_ADMIN.
+    pub fn ChargeUserTask(&self, tg: &ThreadGroup) -> Result<Arc<UserCharge>> {
+        let creds = self.Credentials();
+        let kernel = self.lock().k.clone();
+        let realKUID = creds.lock().RealKUID;
+        let count = kernel.UserTaskCount(realKUID);
+
+        let root = kernel.RootUserNamespace();
+        if realKUID == ROOT_KUID
+            || creds.HasCapabilityIn(Capability::CAP_SYS_RESOURCE, &root)
+            || creds.HasCapabilityIn(Capability::CAP_SYS_ADMIN, &root)
+        {
+            return Ok(UserCharge::New(&count));
+        }
+
+        let limit = tg.Limits().Get(LimitType::ProcessCount).Cur;
+        match UserCharge::TryNew(&count, limit) {
+            None => return Err(Error::SysError(SysErr::EAGAIN)),
+            Some(charge) => return Ok(charge),
+        }
+    }
+
     pub fn MaybeBeginVforkStop(&self, child: &Thread) {
         let tg = self.ThreadGroup();
         let _owner = tg.PIDNamespace().Owner();
@@ -377,12 +441,10 @@ impl Thread {
         let lock = tg.lock().signalLock.clone();
         let _s = lock.lock();
 
-        {
-            let mut threadLocked = self.lock();
-            if threadLocked.killedLocked() {
-                threadLocked.vforkParent = None;
-                return;
-            }
+        // The child mustn't wake up a parent that never stopped.
+        if self.lock().killedLocked() {
+            child.lock().vforkParent = None;
+            return;
         }
 
         let vforkParent = child.lock().vforkParent.clone();
@@ -452,7 +514,16 @@ impl Task {
         }
 
         if opts.SetTLS {
-            cTask.context.fs = tls;
+            if cTask.context.tls32.compat {
+                // the tls of a 32 bit task is a struct user_desc with a set entry
+                let mut info: UserDesc = self.CopyInObj(tls)?;
+                if info.entry_number == u32::MAX {
+                    return Err(Error::SysError(SysErr::EINVAL));
+                }
+                cTask.context.tls32.SetDesc(&mut info)?;
+            } else {
+                cTask.context.fs = tls;
+            }
         }
 
         taskMgr::NewTask(TaskId::New(cTask.taskId));
@@ -464,7 +535,7 @@ impl Task {
         //let pid = self.GetProcessId();
         let cPid;
 
-        let s_ptr = KERNEL_STACK_ALLOCATOR.Allocate().unwrap() as *mut u8;
+        let s_ptr = CPULocal::AllocKernelStack() as *mut u8;
         let taskPtr = s_ptr as *mut Self;
 
         let task = Task::Current();
@@ -537,6 +608,7 @@ impl Task {
         let curr = Self::Current();
         let new = unsafe { &mut *taskPtr };
 
+        new.InitStackGuard();
         new.PerfGoto(PerfType::Blocked);
         new.PerfGoto(PerfType::User);
         CreateCloneTask(curr, new, userSp);
@@ -658,6 +730,11 @@ pub fn CreateCloneTask(fromTask: &Task, toTask: &mut Task, userSp: u64) {
 
         toTask.context.SetReady(1);
         toTask.context.fs = fromTask.context.fs;
+        // fromTask is running, its selectors are the ones loaded in the vcpu
+        toTask.context.tls32 = fromTask.context.tls32;
+        if toTask.context.tls32.compat {
+            toTask.context.tls32.Save();
+        }
         toTask.context.rsp = toTask.GetPtRegs() as *const _ as u64 - 8;
         toTask.context.rdi = userSp;
         toTask.context.X86fpstate = Box::new(fromTask.context.X86fpstate.Fork());
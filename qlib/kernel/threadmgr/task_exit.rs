@@ -135,6 +135,7 @@ impl ThreadInternal {
                     ..Default::default()
                 }),
                 None,
+                None,
             )
             .expect("killLocked fail");
 
@@ -432,6 +433,23 @@ impl Thread {
             }
         }
 
+        // Move the process group's link from the old parent's group to the new
+        // one, the old group may become an orphan. (Compare
+        // kernel/exit.c:kill_orphaned_pgrp().)
+        let pg = tg.lock().processGroup.clone();
+        if let Some(pg) = pg {
+            let oldPG = match &oldParent {
+                None => None,
+                Some(p) => p.lock().tg.lock().processGroup.clone(),
+            };
+            let newPG = match parent {
+                None => None,
+                Some(p) => p.lock().tg.lock().processGroup.clone(),
+            };
+            pg.incRefWithParent(newPG);
+            pg.decRefWithParent(oldPG);
+        }
+
         tg.lock().terminationSignal = Signal(Signal::SIGCHLD);
         let exitParentNotified = self.lock().exitParentNotified;
         let exitParentAcked = self.lock().exitParentAcked;
@@ -561,14 +579,20 @@ impl Thread {
             return None;
         }
 
-        let pidns = targetTg.PIDNamespace();
-        let pid = pidns.IDOfTaskLocked(&target); // .lock().tids.get(&target).unwrap();
+        // the id is reported in the waiter's pid namespace
+        let pidns = self.PIDNamespace();
+        let pid = pidns.IDOfTaskLocked(&target);
 
         let creds = target.Credentials();
         let userns = self.UserNamespace();
         let uid = creds.lock().RealKUID.In(&userns).OrOverflow();
 
+        // Both consuming and WNOWAIT waits report the group exit code if one
+        // is available, see kernel/exit.c:wait_task_zombie().
         let mut status = target.lock().exitStatus.Status();
+        if targetTg.lock().exiting {
+            status = targetTg.lock().exitStatus.Status();
+        }
 
         if !opts.ConsumeEvent {
             return Some(WaitResult {
@@ -580,14 +604,6 @@ impl Thread {
             });
         }
 
-        // Surprisingly, the exit status reported by a non-consuming wait can
-        // differ from that reported by a consuming wait; the latter will return
-        // the group exit code if one is available.
-
-        if targetTg.lock().exiting {
-            status = targetTg.lock().exitStatus.Status();
-        }
-
         let targetParent = target.lock().parent.clone();
         let exitParentNotified = target.lock().exitParentNotified;
 
@@ -615,14 +631,12 @@ impl Thread {
                 tglock.childCPUStats.Accumulate(&targettglock.childCPUStats);
 
                 // Update t's child max resident set size. The size will be the maximum
-                // of this thread's size and all its childrens' sizes.
-                let maxRSS = tglock.maxRSS;
-                if maxRSS < targettglock.maxRSS {
+                // of the child's size and all its childrens' sizes.
+                if tglock.childMaxRSS < targettglock.maxRSS {
                     tglock.childMaxRSS = targettglock.maxRSS;
                 }
 
-                let childMaxRSS = tglock.childMaxRSS;
-                if childMaxRSS < targettglock.childMaxRSS {
+                if tglock.childMaxRSS < targettglock.childMaxRSS {
                     tglock.childMaxRSS = targettglock.childMaxRSS;
                 }
             }
@@ -663,7 +677,7 @@ impl Thread {
             return None;
         }
 
-        let pidns = targetTg.PIDNamespace();
+        let pidns = self.PIDNamespace();
         let pid = pidns.IDOfTaskLocked(target);
 
         let creds = target.Credentials();
@@ -698,7 +712,7 @@ impl Thread {
         }
 
         let pidns = self.PIDNamespace();
-        let pid = *pidns.lock().tids.get(target).unwrap();
+        let pid = pidns.IDOfTaskLocked(target);
 
         let creds = target.Credentials();
         let userns = self.UserNamespace();
@@ -745,6 +759,9 @@ impl Thread {
                 t.lock().exitParentAcked = true;
             } else if tg.lock().tasksCount == 1 {
                 t.lock().exitParentNotified = true;
+                let exitQueue = tg.lock().exitQueue.clone();
+                exitQueue.Notify(READABLE_EVENT);
+
                 let parent = t.lock().parent.clone();
                 if parent.is_none() {
                     t.lock().exitParentAcked = true;
@@ -840,7 +857,8 @@ impl Thread {
 
             let cpuStatus = t.lock().CPUStats();
             tg.lock().exitedCPUStats.Accumulate(&cpuStatus);
-            //tg.lock().ioUsage.Accumulate(t.lock().ioUsage);
+            let ioUsage = t.lock().ioUsage.clone();
+            tg.lock().ioUsage.Accumulate(&ioUsage);
             let tc = {
                 let lock = tg.lock().signalLock.clone();
                 let _s = lock.lock();
@@ -951,6 +969,21 @@ impl Thread {
         // If this is the last task to exit from the thread group, release the
         // thread group's resources.
         if lastExiter {
+            // "If the process is a controlling process, the SIGHUP signal is
+            // sent to each process in the foreground process group of the
+            // controlling terminal belonging to that process." - _exit(2)
+            if let Some(session) = tg.Session() {
+                let tty = if session.lock().leader == tg {
+                    session.lock().tty.clone()
+                } else {
+                    None
+                };
+
+                if let Some(tty) = tty {
+                    tty.SessionLeaderExit(&session);
+                }
+            }
+
             tg.release();
         }
 
@@ -1001,7 +1034,21 @@ impl Thread {
         }
 
         self.exitNotifyLocked();
+
+        let kernel = self.lock().k.clone();
+        let container = kernel.Container(&cid);
+        if let Some(c) = &container {
+            c.RemoveTask();
+        }
+        self.lock().userCharge = None;
+
         if isRootProcess && tg.lock().liveTasks == 0 {
+            // the container stops with its init process
+            if let Some(c) = &container {
+                if execId.is_none() {
+                    c.SetStopped();
+                }
+            }
             let execId = execId.unwrap_or_default();
             info!(
                 " sending exit notification for CID:{}, execID:{}",
@@ -1158,12 +1205,8 @@ impl WaitOptions {
     // Preconditions: The TaskSet mutex must be locked (for reading or writing).
     pub fn matchesTask(&self, t: &Thread, pidns: &PIDNamespace) -> bool {
         if self.SpecificTID != 0 {
-            // && self.SpecificTID != *pidns.lock().tids.get(t).unwrap() {
             let id = match pidns.lock().tids.get(t) {
-                None => {
-                    panic!("thread id {} doesn't exist", t.lock().id)
-                    //return false
-                }
+                None => return false,
                 Some(id) => *id,
             };
 
@@ -1174,11 +1217,18 @@ impl WaitOptions {
 
         let tg = t.lock().tg.clone();
         let pg = tg.lock().processGroup.clone();
-        if self.SpecificPGID != 0
-            && pg.is_some()
-            && self.SpecificPGID != *pidns.lock().pgids.get(&pg.unwrap()).unwrap()
-        {
-            return false;
+        if self.SpecificPGID != 0 {
+            let pgid = match pg {
+                None => 0,
+                Some(pg) => match pidns.lock().pgids.get(&pg) {
+                    None => 0,
+                    Some(id) => *id,
+                },
+            };
+
+            if pgid != self.SpecificPGID {
+                return false;
+            }
         }
 
         let leader = tg.lock().leader.Upgrade();
@@ -15,6 +15,7 @@
 use super::super::super::linux::futex::*;
 use super::super::kernel::futex::*;
 use super::super::task::*;
+use super::super::threadmgr::task_usermem::*;
 use super::super::threadmgr::thread::*;
 
 impl Thread {
@@ -30,7 +31,7 @@ impl Thread {
             return;
         }
 
-        let rl: RobustListHead = match task.CopyInObj(addr) {
+        let rl: RobustListHead = match UserPtr::New(addr).Read(task) {
             Err(_) => return,
             Ok(p) => p,
         };
@@ -52,7 +53,7 @@ impl Thread {
             // Try to decode the next element in the list before waking the
             // current futex. But don't check the error until after we've
             // woken the current futex. Linux does it in this order too
-            next = match task.CopyInObj(next) {
+            next = match UserPtr::New(next).Read(task) {
                 Err(_) => {
                     if thisLockAddr != pendingLockAddr {
                         self.WakeRobustListOne(task, thisLockAddr)
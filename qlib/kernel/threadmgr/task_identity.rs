@@ -20,6 +20,7 @@ use super::super::super::auth::userns::*;
 use super::super::super::auth::*;
 use super::super::super::common::*;
 use super::super::super::linux_def::*;
+use super::super::kernel::kernel::UserCharge;
 use super::super::task::*;
 use super::super::threadmgr::thread::*;
 
@@ -86,6 +87,11 @@ impl ThreadInternal {
         if oldE != newE {
             self.parentDeathSignal = Signal(0);
         }
+
+        // The task now counts against the RLIMIT_NPROC of its new real user.
+        if oldR != newR && self.userCharge.is_some() {
+            self.userCharge = Some(UserCharge::New(&self.k.UserTaskCount(newR)));
+        }
     }
 
     pub fn setKGIDsUncheckedLocked(&mut self, newR: KGID, newE: KGID, newS: KGID) {
@@ -111,9 +117,9 @@ impl ThreadInternal {
     // disables the features we don't support anyway, is always set. This
     // drastically simplifies this function.
     //
-    // - We don't implement AT_SECURE, because no_new_privs always being set means
-    // that the conditions that require AT_SECURE never arise. (Compare Linux's
-    // security/commoncap.c:cap_bprm_set_creds() and cap_bprm_secureexec().)
+    // - AT_SECURE is only set if the effective IDs differed from the real IDs,
+    // since no_new_privs being set means that no privileges are ever gained.
+    // (Compare Linux's security/commoncap.c:cap_bprm_creds_from_file().)
     //
     // - We don't check for CAP_SYS_ADMIN in prctl(PR_SET_SECCOMP), since
     // seccomp-bpf is also allowed if the task has no_new_privs set.
@@ -122,8 +128,10 @@ impl ThreadInternal {
     // since no_new_privs being set has the same effect as the presence of an
     // unprivileged tracer.
     //
+    // It returns the AT_SECURE value of the new program.
+    //
     // Preconditions: t.mu must be locked.
-    pub fn updateCredsForExecLocked(&mut self) {
+    pub fn updateCredsForExecLocked(&mut self) -> bool {
         // """
         // During an execve(2), the kernel calculates the new capabilities of
         // the process using the following algorithm:
@@ -229,7 +237,8 @@ impl ThreadInternal {
         // is a no-op. So we can just do C1 and C2 unconditionally.
         let EffectiveKGID = self.creds.lock().EffectiveKGID;
         let RealKGID = self.creds.lock().RealKGID;
-        if EffectiveKUID != RealKUID || EffectiveKGID != RealKGID {
+        let secure = EffectiveKUID != RealKUID || EffectiveKGID != RealKGID;
+        if secure {
             self.creds.lock().EffectiveKUID = RealKUID;
             self.creds.lock().EffectiveKGID = RealKGID;
             self.parentDeathSignal = Signal(0);
@@ -254,6 +263,7 @@ impl ThreadInternal {
 
         // "The bounding set is inherited at fork(2) from the thread's parent, and
         // is preserved across an execve(2)". So we're done.
+        return secure;
     }
 
     pub fn setUserNamespace(&mut self, ns: &UserNameSpace) -> Result<()> {
@@ -16,11 +16,16 @@ use crate::qlib::mutex::*;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use core::ops::Deref;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering;
 
+use super::super::super::cgroup::CPU_WEIGHT_DEFAULT;
 use super::super::super::common::*;
 use super::super::super::limits::*;
+use super::super::super::linux::ioprio::*;
 use super::super::super::linux::time::*;
 use super::super::super::linux_def::*;
+use super::super::super::task_mgr::*;
 use super::super::super::usage::cpu::*;
 use super::super::super::vcpu_mgr::*;
 use super::super::kernel::cpuset::*;
@@ -99,6 +104,29 @@ pub struct TaskSchedInfoInternal {
     // Task.InterruptibleSleepStart, Task.UninterruptibleSleepStart, or
     // Task.Yield(), voluntarily ceasing execution.
     pub YieldCount: u64,
+
+    // InvoluntaryCount is the number of times the task has been switched out
+    // while runnable, i.e. in taskMgr::Yield().
+    pub InvoluntaryCount: u64,
+
+    // Yielding is set while the task is in taskMgr::Yield().
+    pub Yielding: bool,
+
+    // MinFlt and MajFlt are the page fault counts of the task.
+    pub MinFlt: u64,
+    pub MajFlt: u64,
+
+    // ReadyTimestamp is the cpu cycle when the task was put in the ready
+    // queue, 0 if it is not there.
+    pub ReadyTimestamp: i64,
+
+    // RunDelayTicks is the time the task has spent in the ready queue and
+    // IODelayTicks is the time it has waited for host io, in cpu cycles.
+    pub RunDelayTicks: i64,
+    pub IODelayTicks: i64,
+
+    // Pcount is the number of times the task has been switched in.
+    pub Pcount: u64,
 }
 
 impl TaskSchedInfoInternal {
@@ -126,6 +154,15 @@ impl TaskSchedInfoInternal {
 
         return self.SysTicks;
     }
+
+    // AccountRunDelay adds the time since the task was put in the ready queue.
+    pub fn AccountRunDelay(&mut self, now: i64) {
+        if self.ReadyTimestamp != 0 && self.ReadyTimestamp < now {
+            self.RunDelayTicks += now - self.ReadyTimestamp;
+        }
+
+        self.ReadyTimestamp = 0;
+    }
 }
 
 #[derive(Clone, Default, Debug)]
@@ -154,6 +191,12 @@ impl ThreadInternal {
             UserTime: Tsc::Scale(userTime) * 1000,
             SysTime: Tsc::Scale(sysTime) * 1000,
             VoluntarySwitches: tsched.YieldCount,
+            InvoluntarySwitches: tsched.InvoluntaryCount,
+            MinorFaults: tsched.MinFlt,
+            MajorFaults: tsched.MajFlt,
+            RunDelay: Tsc::Scale(tsched.RunDelayTicks) * 1000,
+            IODelay: Tsc::Scale(tsched.IODelayTicks) * 1000,
+            Timeslices: tsched.Pcount,
         };
     }
 
@@ -263,7 +306,20 @@ impl Thread {
     // CPUMask returns a copy of t's allowed CPU mask.
     pub fn CPUMask(&self) -> CPUSet {
         let t = self.lock();
-        return t.allowedCPUMask.Copy();
+        let mut mask = t.allowedCPUMask.Copy();
+
+        // the cpus above the online ones are parked
+        let online = t.k.OnlineCores();
+        mask.ClearAbove(online);
+        if mask.NumCPUs() == 0 {
+            let mut full = CPUSet::New(t.allowedCPUMask.Size() * 8);
+            for i in 0..online {
+                full.Set(i);
+            }
+            return full;
+        }
+
+        return mask;
     }
 
     // SetCPUMask sets t's allowed CPU mask based on mask. It takes ownership of
@@ -273,7 +329,7 @@ impl Thread {
     // sched.CPUSetSize(t.Kernel().ApplicationCores()).
     pub fn SetCPUMask(&self, mask: CPUSet) -> Result<()> {
         let mut mask = mask.Copy();
-        let applicationCores = self.lock().k.staticInfo.lock().ApplicationCores as usize;
+        let applicationCores = self.lock().k.ApplicationCores() as usize;
         let want = CPUSetSize(applicationCores);
         if mask.Size() != want {
             panic!("Invalid CPUSet {:?} (expected {} bytes)", &mask, want);
@@ -322,6 +378,46 @@ impl Thread {
     // SetNiceness sets t's niceness to n.
     pub fn SetNiceness(&self, n: i32) {
         self.lock().niceness = n;
+        self.UpdateSchedWeight();
+    }
+
+    pub fn IOPrio(&self) -> IOPrio {
+        return self.lock().ioprio;
+    }
+
+    pub fn SetIOPrio(&self, ioprio: IOPrio) {
+        self.lock().ioprio = ioprio;
+    }
+
+    // CoreCookie returns the core scheduling cookie of t, 0 is none.
+    pub fn CoreCookie(&self) -> u64 {
+        let taskId = self.lock().taskId;
+        return TaskId::New(taskId).Context().CoreCookie();
+    }
+
+    pub fn SetCoreCookie(&self, cookie: u64) {
+        let taskId = self.lock().taskId;
+        TaskId::New(taskId)
+            .Context()
+            .coreCookie
+            .store(cookie, Ordering::Relaxed);
+    }
+
+    // UpdateSchedWeight sets the scheduling weight of t's task by its niceness
+    // scaled by the cpu.weight of its container.
+    pub fn UpdateSchedWeight(&self) {
+        let (taskId, niceness, cid, kernel) = {
+            let t = self.lock();
+            (t.taskId, t.niceness, t.containerID.clone(), t.k.clone())
+        };
+
+        let cpuWeight = match kernel.Container(&cid) {
+            Some(c) => c.CpuWeight(),
+            None => CPU_WEIGHT_DEFAULT,
+        };
+
+        let weight = NiceToWeight(niceness) * cpuWeight / CPU_WEIGHT_DEFAULT;
+        TaskId::New(taskId).Context().SetWeight(weight);
     }
 
     // NumaPolicy returns t's current numa policy.
@@ -338,6 +434,13 @@ impl Thread {
     }
 }
 
+static CORE_COOKIE: AtomicU64 = AtomicU64::new(1);
+
+// NewCoreCookie returns a cookie for prctl(PR_SCHED_CORE_CREATE).
+pub fn NewCoreCookie() -> u64 {
+    return CORE_COOKIE.fetch_add(1, Ordering::Relaxed);
+}
+
 // assignCPU returns the virtualized CPU number for the task with global TID
 // tid and allowedCPUMask allowed.
 fn assignCPU(allowed: &CPUSet, tid: ThreadID) -> i32 {
@@ -17,11 +17,15 @@ use alloc::sync::Arc;
 
 use super::super::super::common::*;
 use super::super::super::cpuid::*;
+use super::super::super::limits::*;
 use super::super::super::linux::time::*;
 use super::super::super::linux_def::*;
 use super::super::arch::x86_64::arch_x86::*;
+use super::super::asm::*;
+use super::super::kernel::kernel::*;
 use super::super::kernel::posixtimer::*;
 use super::super::kernel::waiter::*;
+use super::super::loader::vdso::*;
 use super::super::stack::*;
 use super::super::task::*;
 use super::super::threadmgr::thread::*;
@@ -474,21 +478,41 @@ impl Thread {
             return Ok(());
         }
 
+        // Queued signals are charged to the receiver's real user. Linux
+        // overrides the limit for standard signals; a realtime signal over
+        // the limit fails with EAGAIN unless it comes from kill(2), in which
+        // case it is still marked pending but without a queue entry of its own.
+        let realKUID = self.Credentials().lock().RealKUID;
+        let limit = tg.Limits().Get(LimitType::SignalsPending).Cur;
+        let count = GetKernel().SigPendingCount(realKUID);
+        let charge = UserCharge::TryNew(&count, limit);
+        if charge.is_none() && sig.IsRealtime() && timer.is_none() {
+            if info.Code != SignalInfo::SIGNAL_INFO_USER {
+                return Err(Error::SysError(SysErr::EAGAIN));
+            }
+
+            let pending = if !group {
+                self.lock().pendingSignals.IsPending(sig)
+            } else {
+                tg.lock().pendingSignals.IsPending(sig)
+            };
+
+            if pending {
+                return Ok(());
+            }
+        }
+
         let res = if !group {
             self.lock()
                 .pendingSignals
-                .Enque(Box::new(*info), timer.clone())?
+                .Enque(Box::new(*info), timer.clone(), charge)?
         } else {
             tg.lock()
                 .pendingSignals
-                .Enque(Box::new(*info), timer.clone())?
+                .Enque(Box::new(*info), timer.clone(), charge)?
         };
 
         if !res {
-            if sig.IsRealtime() {
-                return Err(Error::SysError(SysErr::EAGAIN));
-            }
-
             if timer.is_some() {
                 timer.clone().unwrap().lock().signalRejectedLocked();
             }
@@ -533,6 +557,21 @@ impl Thread {
         return SignalSet(pendingset | tg.lock().pendingSignals.pendingSet.0);
     }
 
+    // PendingSignalSets returns the signals pending on the task only and the
+    // ones pending on its thread group.
+    pub fn PendingSignalSets(&self) -> (SignalSet, SignalSet) {
+        let tg = self.lock().tg.clone();
+        let pidns = tg.PIDNamespace();
+        let owner = pidns.lock().owner.clone();
+        let _r = owner.read();
+
+        let lock = tg.lock().signalLock.clone();
+        let _s = lock.lock();
+
+        let pendingset = self.lock().pendingSignals.pendingSet;
+        return (pendingset, tg.lock().pendingSignals.pendingSet);
+    }
+
     // PendingSignals returns the set of pending signals without lock. Just for signalfd readiness check.
     pub fn PendingSignalsNolock(&self) -> SignalSet {
         let tg = self.lock().tg.clone();
@@ -1102,15 +1141,72 @@ impl Task {
     // xsave features that are always enabled in signal frame fpstate.
     pub const XFEATURE_MASK_FPSSE : u64 = 0x3;
 
+    // SignalFrameSize returns the worst case user stack usage of
+    // deliverSignalToHandler, which is reported as AT_MINSIGSTKSZ.
+    pub fn SignalFrameSize() -> u64 {
+        let (fpSize, fpAlign) = HostFeatureSet().ExtendedStateSize();
+        let size = fpSize as u64
+            + Self::FP_XSTATE_MAGIC2_SIZE as u64
+            + fpAlign as u64
+            + core::mem::size_of::<SignalInfo>() as u64
+            + core::mem::size_of::<UContext>() as u64
+            + 8 // restorer
+            + 128; // red zone
+        return (size + 15) & !15;
+    }
+
+    // handlerSignalMask blocks the signals of the handler and returns the mask
+    // to restore on sigreturn.
+    fn handlerSignalMask(&self, info: &SignalInfo, sigAct: &SigAct) -> SignalSet {
+        let t = self.Thread();
+        let mut mask = t.lock().signalMask;
+        let haveSavedSignalMask = t.lock().haveSavedSignalMask;
+        if haveSavedSignalMask {
+            mask = t.lock().savedSignalMask;
+            t.lock().haveSavedSignalMask = false;
+        }
+
+        let mut newMask = t.lock().signalMask;
+        newMask.0 |= sigAct.mask;
+        if !sigAct.flags.IsNoDefer() {
+            newMask.0 |= SignalSet::New(Signal(info.Signo)).0;
+        }
+        t.SetSignalMask(newMask);
+
+        return mask;
+    }
+
+    fn faultAddr(info: &SignalInfo) -> u64 {
+        if info.Signo == Signal::SIGBUS || info.Signo == Signal::SIGSEGV {
+            return info.SigFault().addr;
+        }
+
+        return 0;
+    }
+
     pub fn deliverSignalToHandler(&mut self, info: &SignalInfo, sigAct: &SigAct) -> Result<()> {
+        if self.mm.IsCompat() {
+            return self.deliverSignalToHandler32(info, sigAct);
+        }
+
         let pt = self.GetPtRegs();
         let mut userStack = Stack::New(pt.rsp - 128); // red zone
 
+        // uc_stack describes the alternate stack as it was before delivery
+        let mut altStack = self.signalStack;
+        if altStack.IsEnable() && altStack.Contains(pt.rsp) {
+            altStack.SetOnStack();
+        }
+
         if sigAct.flags.IsOnStack() && self.signalStack.IsEnable() {
-            self.signalStack.SetOnStack();
             if !self.signalStack.Contains(pt.rsp) {
                 userStack = Stack::New(self.signalStack.Top());
             }
+
+            // sigreturn restores it from uc_stack
+            if self.signalStack.AutoDisarm() {
+                self.signalStack = SignalStack::default();
+            }
         }
 
         let (mut fpSize, fpAlign) = HostFeatureSet().ExtendedStateSize();
@@ -1137,28 +1233,10 @@ impl Task {
 
         self.context.X86fpstate = Box::new(X86fpstate::default());
 
-        let t = self.Thread();
-        let mut mask = t.lock().signalMask;
-        let haveSavedSignalMask = t.lock().haveSavedSignalMask;
-        if haveSavedSignalMask {
-            mask = t.lock().savedSignalMask;
-            t.lock().haveSavedSignalMask = false;
-        }
-
-        let mut newMask = t.lock().signalMask;
-        newMask.0 |= sigAct.mask;
-        if !sigAct.flags.IsNoDefer() {
-            newMask.0 |= SignalSet::New(Signal(info.Signo)).0;
-        }
-        t.SetSignalMask(newMask);
-
-        let mut cr2 = 0;
-        if info.Signo == Signal::SIGBUS || info.Signo == Signal::SIGSEGV {
-            let fault = info.SigFault();
-            cr2 = fault.addr;
-        }
+        let mask = self.handlerSignalMask(info, sigAct);
+        let cr2 = Self::faultAddr(info);
 
-        let mut ctx = UContext::New(pt, mask.0, cr2, fpstateAddr, &self.signalStack);
+        let mut ctx = UContext::New(pt, mask.0, cr2, fpstateAddr, &altStack);
         ctx.Flags |= Self::UC_FP_XSTATE;
 
         let sigInfoAddr = userStack.PushType::<SignalInfo>(self, info)?;
@@ -1230,4 +1308,170 @@ impl Task {
 
         return Err(Error::SysCallRetCtrl(TaskRunState::RunSyscallRet));
     }
+
+    // deliverSignalToHandler32 sets up the frame of a 32 bit handler, as
+    // linux's ia32_setup_rt_frame and ia32_setup_frame. The fp state is the
+    // xsave image of the task, not the fsave layout of linux, it is only
+    // read back by sigreturn.
+    fn deliverSignalToHandler32(&mut self, info: &SignalInfo, sigAct: &SigAct) -> Result<()> {
+        let pt = self.GetPtRegs();
+        let mut sp = pt.rsp & 0xffff_ffff;
+
+        let mut altStack = self.signalStack;
+        if altStack.IsEnable() && altStack.Contains(sp) {
+            altStack.SetOnStack();
+        }
+
+        if sigAct.flags.IsOnStack() && self.signalStack.IsEnable() {
+            if !self.signalStack.Contains(sp) {
+                sp = self.signalStack.Top();
+            }
+
+            if self.signalStack.AutoDisarm() {
+                self.signalStack = SignalStack::default();
+            }
+        }
+
+        let fpstate = self.context.X86fpstate.Slice();
+        let fpstateAddr = (sp - fpstate.len() as u64) & !63;
+        self.CopyOutSlice(fpstate, fpstateAddr, fpstate.len())?;
+        self.context.X86fpstate = Box::new(X86fpstate::default());
+
+        let mask = self.handlerSignalMask(info, sigAct);
+        let cr2 = Self::faultAddr(info);
+        let sc = SigContext32::New(pt, GetFsSel(), GetGsSel(), mask.0, cr2, fpstateAddr);
+
+        let rt = sigAct.flags.IsSigInfo();
+        let restorer = if sigAct.flags.HasRestorer() {
+            sigAct.restorer
+        } else if rt {
+            self.mm.VDSO() + VDSO.read().compatRtSigreturn
+        } else {
+            self.mm.VDSO() + VDSO.read().compatSigreturn
+        };
+
+        let signo = info.Signo;
+        let frameSize = if rt {
+            core::mem::size_of::<RtSigFrame32>()
+        } else {
+            core::mem::size_of::<SigFrame32>()
+        } as u64;
+
+        // the i386 abi wants esp + 4 16 bytes aligned at the handler entry
+        let frameAddr = ((fpstateAddr - frameSize + 4) & !15) - 4;
+        let (pinfo, puc) = if rt {
+            let pinfo = frameAddr + 16;
+            let puc = pinfo + core::mem::size_of::<SignalInfo32>() as u64;
+            let frame = RtSigFrame32 {
+                pretcode: restorer as u32,
+                sig: signo,
+                pinfo: pinfo as u32,
+                puc: puc as u32,
+                info: SignalInfo32::New(info),
+                uc: UContext32 {
+                    Flags: 0,
+                    Link: 0,
+                    Stack: SignalStack32::New(&altStack),
+                    MContext: sc,
+                    Sigset: [mask.0 as u32, (mask.0 >> 32) as u32],
+                },
+                retcode: RtSigFrame32::RETCODE,
+            };
+            self.CopyOutObj(&frame, frameAddr)?;
+            (pinfo, puc)
+        } else {
+            let frame = SigFrame32 {
+                pretcode: restorer as u32,
+                sig: signo,
+                sc: sc,
+                fpstateUnused: [0; 156],
+                extramask: [(mask.0 >> 32) as u32],
+                retcode: SigFrame32::RETCODE,
+            };
+            self.CopyOutObj(&frame, frameAddr)?;
+            (0, 0)
+        };
+
+        info!(
+            "=========start enter 32 bit user, the address is {:?}, esp is {:x}, signo is {}",
+            sigAct, frameAddr, signo
+        );
+
+        let eflags =
+            pt.eflags & !(EflagsDef::EFLAGS_DF | EflagsDef::EFLAGS_RF | EflagsDef::EFLAGS_TF);
+        *pt = PtRegs::default();
+        pt.rsp = frameAddr;
+        pt.rip = sigAct.handler;
+        // the regparm(3) handlers take the args in eax, edx and ecx
+        pt.rax = signo as u64;
+        pt.rdx = pinfo;
+        pt.rcx = puc;
+        pt.eflags = eflags;
+        pt.cs = UCODE32 as u64;
+        pt.ss = UDATA as u64;
+
+        return Ok(());
+    }
+
+    // SignalReturn32 implements the ia32 sigreturn and rt_sigreturn. The
+    // return address and, for sigreturn, the signal number are popped.
+    pub fn SignalReturn32(&mut self, rt: bool) -> Result<i64> {
+        let pt = self.GetPtRegs();
+        let sp = pt.rsp & 0xffff_ffff;
+
+        let (sc, mask) = if rt {
+            let frame: RtSigFrame32 = self.CopyInObj(sp - 4)?;
+            self.SetSignalStack(frame.uc.Stack.SignalStack());
+            let uc = frame.uc;
+            (
+                uc.MContext,
+                uc.Sigset[0] as u64 | (uc.Sigset[1] as u64) << 32,
+            )
+        } else {
+            let frame: SigFrame32 = self.CopyInObj(sp - 8)?;
+            (
+                frame.sc,
+                frame.sc.oldmask as u64 | (frame.extramask[0] as u64) << 32,
+            )
+        };
+
+        self.context.X86fpstate = Box::new(X86fpstate::default());
+        if sc.fpstate != 0 {
+            let slice = self.context.X86fpstate.Slice();
+            let mut userStack = Stack::New(sc.fpstate as u64);
+            userStack.PopSlice(self, slice)?;
+            self.context.X86fpstate.SanitizeUser();
+        }
+
+        let cEflags = pt.eflags;
+        let nEflags = sc.eflags as u64;
+
+        pt.rdi = sc.edi as u64;
+        pt.rsi = sc.esi as u64;
+        pt.rbp = sc.ebp as u64;
+        pt.rsp = sc.esp as u64;
+        pt.rbx = sc.ebx as u64;
+        pt.rdx = sc.edx as u64;
+        pt.rcx = sc.ecx as u64;
+        pt.rax = sc.eax as u64;
+        pt.rip = sc.eip as u64;
+        // there is only one 32 bit code segment
+        pt.cs = UCODE32 as u64;
+        pt.ss = UDATA as u64;
+        pt.eflags =
+            (cEflags & !EflagsDef::EFLAGS_RESTOREABLE) | (nEflags & EflagsDef::EFLAGS_RESTOREABLE);
+        pt.orig_rax = core::u64::MAX;
+
+        self.context.tls32.fs = sc.fs as u16;
+        self.context.tls32.gs = sc.gs as u16;
+        self.context.tls32.Load();
+
+        let t = self.Thread();
+        t.SetSignalMask(SignalSet(mask & !(UNBLOCKED_SIGNALS.0)));
+        if t.lock().HasSignal() {
+            t.lock().interruptSelf();
+        }
+
+        return Err(Error::SysCallRetCtrl(TaskRunState::RunSyscallRet));
+    }
 }
@@ -37,6 +37,9 @@ pub struct TaskConfig {
     // Parent is the new task's parent. Parent may be nil.
     pub Parent: Option<Thread>,
 
+    // Tid is the tid of the new task if it is free, 0 to allocate one.
+    pub Tid: ThreadID,
+
     // If InheritParent is not nil, use InheritParent's parent as the new
     // task's parent.
     pub InheritParent: Option<Thread>,
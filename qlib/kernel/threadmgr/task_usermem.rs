@@ -12,12 +12,13 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use alloc::slice;
 use alloc::str;
 use alloc::string::String;
 use alloc::string::ToString;
 use alloc::vec::Vec;
+use core::marker::PhantomData;
 use core::mem::*;
+use core::ptr;
 use core::sync::atomic::{AtomicU32, Ordering};
 
 use super::super::super::common::*;
@@ -38,14 +39,19 @@ impl MemoryManager {
     pub fn CopyDataInLocked(&self, task: &Task, vaddr: u64, to: u64, len: usize, allowPartial: bool) -> Result<()> {
         self.V2PLocked(task, vaddr, len as u64, &mut task.GetMut().iovs, false, allowPartial)?;
         defer!(task.GetMut().iovs.clear());
-        let target: *mut u8 = to as *mut u8;
-        let dst = unsafe { slice::from_raw_parts_mut(target, len) };
 
+        // the target might be uninitialized, e.g. a MaybeUninit, so copy
+        // through raw pointers instead of making a slice of it
         let mut offset = 0;
         for iov in &task.GetMut().iovs {
-            let src = iov.start as *const u8;
-            let src = unsafe { slice::from_raw_parts(src, iov.len) };
-            dst[offset..offset + iov.len].clone_from_slice(src);
+            assert!(offset + iov.len <= len);
+            unsafe {
+                ptr::copy_nonoverlapping(
+                    iov.start as *const u8,
+                    (to as *mut u8).add(offset),
+                    iov.len,
+                );
+            }
 
             offset += iov.len;
         }
@@ -56,15 +62,18 @@ impl MemoryManager {
     pub fn CopyDataOutLocked(&self, task: &Task, from: u64, vaddr: u64, len: usize, allowPartial: bool) -> Result<()> {
         self.V2PLocked(task, vaddr, len as u64, &mut task.GetMut().iovs, true, allowPartial)?;
         defer!(task.GetMut().iovs.clear());
-        let from: *const u8 = from as *const u8;
-        let src = unsafe { slice::from_raw_parts(from, len) };
 
         let mut offset = 0;
         for iov in &task.GetMut().iovs {
-            let dst = iov.start as *mut u8;
-            let dst = unsafe { slice::from_raw_parts_mut(dst, iov.len) };
-            
-            dst[0..iov.len].clone_from_slice(&src[offset..offset + iov.len]);
+            assert!(offset + iov.len <= len);
+            unsafe {
+                ptr::copy_nonoverlapping(
+                    (from as *const u8).add(offset),
+                    iov.start as *mut u8,
+                    iov.len,
+                );
+            }
+
             offset += iov.len;
         }
 
@@ -219,18 +228,20 @@ impl MemoryManager {
         return Ok(count);
     }
 
+    // the object is only assumed initialized after all of its bytes are copied
+    // in, T must be a plain data type which is valid for any bit pattern
     pub fn CopyInObjLocked<T: Sized + Copy>(&self, task: &Task, src: u64) -> Result<T> {
-        let data: T = unsafe { MaybeUninit::uninit().assume_init() };
+        let mut data = MaybeUninit::<T>::uninit();
         let size = size_of::<T>();
-        self.CopyDataInLocked(task, src, &data as *const _ as u64, size, false)?;
-        return Ok(data);
+        self.CopyDataInLocked(task, src, data.as_mut_ptr() as u64, size, false)?;
+        return Ok(unsafe { data.assume_init() });
     }
 
     pub fn CopyInObj<T: Sized + Copy>(&self, task: &Task, src: u64) -> Result<T> {
-        let data: T = unsafe { MaybeUninit::uninit().assume_init() };
+        let mut data = MaybeUninit::<T>::uninit();
         let size = size_of::<T>();
-        self.CopyDataIn(task, src, &data as *const _ as u64, size, false)?;
-        return Ok(data);
+        self.CopyDataIn(task, src, data.as_mut_ptr() as u64, size, false)?;
+        return Ok(unsafe { data.assume_init() });
     }
 
     pub fn CopyOutObjLocked<T: Sized + Copy>(&self, task: &Task, data: &T, dst: u64) -> Result<()> {
@@ -260,10 +271,14 @@ impl MemoryManager {
 
         let recordLen = core::mem::size_of::<T>();
         let mut vec: Vec<T> = Vec::with_capacity(count);
+        // a partial copy leaves the tail zeroed instead of uninitialized
+        unsafe {
+            ptr::write_bytes(vec.as_mut_ptr(), 0, count);
+        }
+        self.CopyDataInLocked(task, src, vec.as_mut_ptr() as u64, recordLen * count, allowPartial)?;
         unsafe {
             vec.set_len(count);
         }
-        self.CopyDataInLocked(task, src, vec.as_ptr() as u64, recordLen * count, allowPartial)?;
         return Ok(vec);
     }
 
@@ -279,10 +294,10 @@ impl MemoryManager {
 
         let recordLen = core::mem::size_of::<T>();
         let mut vec: Vec<T> = Vec::with_capacity(count);
+        self.CopyDataIn(task, src, vec.as_mut_ptr() as u64, recordLen * count, false)?;
         unsafe {
             vec.set_len(count);
         }
-        self.CopyDataIn(task, src, vec.as_ptr() as u64, recordLen * count, false)?;
         return Ok(vec);
     }
 
@@ -311,7 +326,7 @@ impl MemoryManager {
 
         assert!(vaddr % 4 == 0);
 
-        self.V2PLocked(task, vaddr, 4, &mut task.GetMut().iovs, false, false)?;
+        self.V2PLocked(task, vaddr, 4, &mut task.GetMut().iovs, true, false)?;
         defer!(task.GetMut().iovs.clear());
 
         assert!(task.GetMut().iovs.len() == 1);
@@ -327,7 +342,7 @@ impl MemoryManager {
 
         assert!(vaddr % 4 == 0);
 
-        self.V2PLocked(task, vaddr, 4, &mut task.GetMut().iovs, false, false)?;
+        self.V2PLocked(task, vaddr, 4, &mut task.GetMut().iovs, true, false)?;
         defer!(task.GetMut().iovs.clear());
 
         assert!(task.GetMut().iovs.len() == 1);
@@ -366,7 +381,13 @@ impl MemoryManager {
         let mut maxTotalSize = maxTotalSize;
 
         let maxlen = self.FixPermission(task, addr, maxElemSize as u64 * 8, false, true)? as usize;
-        let addresses: Vec<u64> = self.CopyInVec(task, addr, maxlen / 8)?;
+        // a 32 bit compat process passes an array of 4 bytes pointers
+        let addresses: Vec<u64> = if self.IsCompat() {
+            let addresses: Vec<u32> = self.CopyInVec(task, addr, maxlen / 4)?;
+            addresses.iter().map(|a| *a as u64).collect()
+        } else {
+            self.CopyInVec(task, addr, maxlen / 8)?
+        };
 
         let mut v = Vec::new();
         for i in 0..addresses.len() {
@@ -601,6 +622,13 @@ impl Task {
         if iovsnum > UIO_MAXIOV {
             return Err(Error::SysError(SysErr::EINVAL))
         }
+
+        // struct iovec of a 32 bit compat process is 2 u32
+        if self.mm.IsCompat() {
+            let iovs: Vec<IoVec32> = self.mm.CopyInVec(self, iovs, iovsnum)?;
+            return Ok(iovs.iter().map(|iov| iov.IoVec()).collect());
+        }
+
         return self.mm.CopyInVec(self, iovs, iovsnum);
     }
 
@@ -612,6 +640,10 @@ impl Task {
         return self.V2P(iov.start, iov.len as u64, output, writable, allowPartial);
     }
 
+    pub fn PinIovs(&self, iovs: &[IoVec], writable: bool, output: &mut Vec<IoVec>) -> Result<PinnedPages> {
+        return self.mm.PinIovs(self, iovs, writable, output);
+    }
+
     pub fn V2PIovs(&self, iovs: &[IoVec], writable: bool, output: &mut Vec<IoVec>, allowPartial: bool) -> Result<()> {
         for iov in iovs {
             self.V2PIov(iov, output, writable, allowPartial)?;
@@ -620,3 +652,145 @@ impl Task {
         return Ok(());
     }
 }
+
+// UserPtr is a typed address in the user memory of a task. The user memory is
+// only accessed through Read/Write, which check the mapping and the permission
+// and copy the data under the mapping lock, so no kernel reference into the
+// user memory outlives the lock, e.g. when another thread unmaps it.
+#[derive(Debug)]
+pub struct UserPtr<T: Sized + Copy> {
+    pub addr: u64,
+    phantom: PhantomData<T>,
+}
+
+impl<T: Sized + Copy> Clone for UserPtr<T> {
+    fn clone(&self) -> Self {
+        return Self::New(self.addr);
+    }
+}
+
+impl<T: Sized + Copy> Copy for UserPtr<T> {}
+
+impl<T: Sized + Copy> UserPtr<T> {
+    pub fn New(addr: u64) -> Self {
+        return Self {
+            addr: addr,
+            phantom: PhantomData,
+        };
+    }
+
+    pub fn Addr(&self) -> u64 {
+        return self.addr;
+    }
+
+    pub fn IsNull(&self) -> bool {
+        return self.addr == 0;
+    }
+
+    // Offset returns the pointer to the idx-th object from this one
+    pub fn Offset(&self, idx: usize) -> Result<Self> {
+        let addr = match self.addr.checked_add((idx * size_of::<T>()) as u64) {
+            None => return Err(Error::SysError(SysErr::EFAULT)),
+            Some(addr) => addr,
+        };
+
+        return Ok(Self::New(addr));
+    }
+
+    pub fn Read(&self, task: &Task) -> Result<T> {
+        if self.IsNull() {
+            return Err(Error::SysError(SysErr::EFAULT));
+        }
+
+        return task.CopyInObj(self.addr);
+    }
+
+    pub fn Write(&self, task: &Task, data: &T) -> Result<()> {
+        if self.IsNull() {
+            return Err(Error::SysError(SysErr::EFAULT));
+        }
+
+        return task.CopyOutObj(data, self.addr);
+    }
+
+    // WriteIfNotNull writes the data when the user passed a pointer, for the
+    // optional output arguments of the syscalls
+    pub fn WriteIfNotNull(&self, task: &Task, data: &T) -> Result<()> {
+        if self.IsNull() {
+            return Ok(());
+        }
+
+        return task.CopyOutObj(data, self.addr);
+    }
+}
+
+// UserSlice is an array of count objects in the user memory of a task
+#[derive(Debug)]
+pub struct UserSlice<T: Sized + Copy> {
+    pub addr: u64,
+    pub count: usize,
+    phantom: PhantomData<T>,
+}
+
+impl<T: Sized + Copy> Clone for UserSlice<T> {
+    fn clone(&self) -> Self {
+        return Self::New(self.addr, self.count);
+    }
+}
+
+impl<T: Sized + Copy> Copy for UserSlice<T> {}
+
+impl<T: Sized + Copy> UserSlice<T> {
+    pub fn New(addr: u64, count: usize) -> Self {
+        return Self {
+            addr: addr,
+            count: count,
+            phantom: PhantomData,
+        };
+    }
+
+    pub fn Len(&self) -> usize {
+        return self.count;
+    }
+
+    // Size returns the size in bytes, EFAULT when it overflows
+    pub fn Size(&self) -> Result<usize> {
+        let size = match self.count.checked_mul(size_of::<T>()) {
+            None => return Err(Error::SysError(SysErr::EFAULT)),
+            Some(size) => size,
+        };
+
+        if self.addr.checked_add(size as u64).is_none() {
+            return Err(Error::SysError(SysErr::EFAULT));
+        }
+
+        return Ok(size);
+    }
+
+    pub fn Get(&self, idx: usize) -> Result<UserPtr<T>> {
+        if idx >= self.count {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
+        return UserPtr::New(self.addr).Offset(idx);
+    }
+
+    pub fn Read(&self, task: &Task) -> Result<Vec<T>> {
+        if self.count == 0 {
+            return Ok(Vec::new());
+        }
+
+        self.Size()?;
+        return task.CopyInVec(self.addr, self.count);
+    }
+
+    // Write writes src to the head of the slice, src can't be longer than it
+    pub fn Write(&self, task: &Task, src: &[T]) -> Result<()> {
+        if src.len() == 0 {
+            return Ok(());
+        }
+
+        self.Size()?;
+        return task.CopyOutSlice(src, self.addr, self.count);
+    }
+}
@@ -22,6 +22,7 @@ use core::cmp::*;
 use core::ops::Deref;
 
 use super::super::super::auth::*;
+use super::super::super::linux::ioprio::*;
 use super::super::super::linux_def::*;
 use super::super::super::usage::io::*;
 use super::super::kernel::cpuset::*;
@@ -228,6 +229,10 @@ pub struct ThreadInternal {
     // niceness is protected by mu.
     pub niceness: i32,
 
+    // ioprio is the I/O priority set by ioprio_set(2). It is passed to the
+    // host with the file I/O of the task.
+    pub ioprio: IOPrio,
+
     // This is used to track the numa policy for the current thread. This can be
     // modified through a set_mempolicy(2) syscall. Since we always report a
     // single numa node, all policies are no-ops. We only track this information
@@ -253,6 +258,10 @@ pub struct ThreadInternal {
     // parentDeathSignal is protected by mu.
     pub parentDeathSignal: Signal,
 
+    // defaultTimerSlack is the timer slack restored by PR_SET_TIMERSLACK with
+    // 0, the slack of the creator when the task is cloned.
+    pub defaultTimerSlack: i64,
+
     // If stop is not nil, it is the internally-initiated condition that
     // currently prevents the task goroutine from running.
     //
@@ -322,18 +331,15 @@ pub struct ThreadInternal {
     pub ioUsage: IO,
 
     pub robust_list_head: u64,
+
+    // userCharge counts the task against the RLIMIT_NPROC of its real user
+    // until it exits.
+    pub userCharge: Option<Arc<UserCharge>>,
 }
 
 impl ThreadInternal {
     pub fn IsChrooted(&self) -> bool {
-        let realRoot = self
-            .k
-            .mounts
-            .read()
-            .get(&self.containerID)
-            .unwrap()
-            .root
-            .clone();
+        let realRoot = self.k.Container(&self.containerID).unwrap().Root();
         let root = self.fsc.RootDirectory();
         return realRoot == root;
     }
@@ -426,6 +432,12 @@ impl Thread {
         return self.lock().utsns.clone();
     }
 
+    // IPCNamespace returns the ipc namespace which scopes the ipc key lookups
+    // of the thread.
+    pub fn IPCNamespace(&self) -> IPCNamespace {
+        return self.lock().ipcns.clone();
+    }
+
     pub fn MemoryManager(&self) -> MemoryManager {
         return self.lock().memoryMgr.clone();
     }
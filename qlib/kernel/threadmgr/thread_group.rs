@@ -51,6 +51,10 @@ pub struct ThreadGroupInternal {
 
     pub eventQueue: Queue,
 
+    // exitQueue is notified when the whole thread group has exited, see
+    // Exited. It backs the readiness of pidfds.
+    pub exitQueue: Queue,
+
     // leader is the thread group's leader, which is the oldest task in the
     // thread group; usually the last task in the thread group to call
     // execve(), or if no such task exists then the first task in the thread
@@ -375,6 +379,12 @@ impl ThreadGroup {
         return self.lock().leader.Upgrade();
     }
 
+    // Exited returns true once every task in the thread group has exited.
+    // Compare kernel/pid.c:thread_group_exited().
+    pub fn Exited(&self) -> bool {
+        return self.lock().liveTasks == 0;
+    }
+
     pub fn Count(&self) -> usize {
         let ts = self.TaskSet();
         let _ts = ts.ReadLock();
@@ -426,6 +436,9 @@ impl ThreadGroup {
     }
 
     pub fn forEachChildThreadGroupLocked(&self, f: impl Fn(ThreadGroup)) {
+        // f may signal an orphaned process group, which locks every thread
+        // group, so collect the children before calling it
+        let mut children = Vec::new();
         for t in &self.lock().tasks {
             for child in &t.lock().children {
                 let tg = child.lock().tg.clone();
@@ -435,11 +448,14 @@ impl ThreadGroup {
                 };
 
                 if child.clone() == leader {
-                    let tg = child.lock().tg.clone();
-                    f(tg)
+                    children.push(tg);
                 }
             }
         }
+
+        for tg in children {
+            f(tg)
+        }
     }
 
     pub fn CreateProcessGroup(&self) -> Result<()> {
@@ -449,7 +465,8 @@ impl ThreadGroup {
 
         let pidns = self.PIDNamespace();
         let id = match pidns.lock().tgids.get(self) {
-            None => 0,
+            // the process has already exited
+            None => return Err(Error::SysError(SysErr::ESRCH)),
             Some(tid) => *tid,
         };
 
@@ -494,17 +511,20 @@ impl ThreadGroup {
             }
         }
 
+        // The children's parent group changes from our old group to the new one.
         let oldParentPG = self.parentPG();
+        let oldPg = self.lock().processGroup.clone().unwrap();
         self.forEachChildThreadGroupLocked(|childTG: ThreadGroup| {
             let currentPg = childTG.lock().processGroup.clone().unwrap();
             currentPg.incRefWithParent(Some(pg.clone()));
-            currentPg.decRefWithParent(oldParentPG.clone());
+            currentPg.decRefWithParent(Some(oldPg.clone()));
         });
 
-        let oldPg = self.lock().processGroup.clone().unwrap();
-        oldPg.decRefWithParent(oldParentPG);
-        oldPg.lock().session.IncRef();
+        // Switch to the new group before leaving the old one, so that we are
+        // not signalled if the old group becomes an orphan.
         self.lock().processGroup = Some(pg.clone());
+        oldPg.lock().session.IncRef();
+        oldPg.decRefWithParent(oldParentPG);
 
         let sessionTmp = pg.lock().session.clone();
         sessionTmp.lock().processGroups.insert(pg.clone());
@@ -533,7 +553,7 @@ impl ThreadGroup {
         checkExec: bool,
     ) -> Result<()> {
         let owner = pidns.lock().owner.clone();
-        let _r = owner.ReadLock();
+        let _w = owner.WriteLock();
 
         let pg = match pidns.lock().processGroups.get(&pgid) {
             None => return Err(Error::SysError(SysErr::EPERM)),
@@ -550,19 +570,27 @@ impl ThreadGroup {
             return Err(Error::SysError(SysErr::EPERM));
         }
 
+        // A session leader can't leave its process group.
+        if session.lock().leader == self.clone() {
+            return Err(Error::SysError(SysErr::EPERM));
+        }
+
+        if pg == currentPg {
+            return Ok(());
+        }
+
         let parentPG = self.parentPG();
         pg.incRefWithParent(parentPG.clone());
 
-        let pgCurr = self.lock().processGroup.clone().unwrap();
-        pg.incRefWithParent(Some(pgCurr.clone()));
+        let pgCurr = currentPg;
         self.forEachChildThreadGroupLocked(|childTG: ThreadGroup| {
             let pgTmp = childTG.lock().processGroup.clone().unwrap();
             pgTmp.incRefWithParent(Some(pg.clone()));
             pgTmp.decRefWithParent(Some(pgCurr.clone()));
         });
 
-        pgCurr.decRefWithParent(parentPG);
         self.lock().processGroup = Some(pg);
+        pgCurr.decRefWithParent(parentPG);
         return Ok(());
     }
 
@@ -656,15 +684,15 @@ impl ThreadGroup {
 
         if self.lock().processGroup.clone().is_some() {
             let oldParentPG = self.parentPG();
+            let oldPg = self.lock().processGroup.clone().unwrap();
             self.forEachChildThreadGroupLocked(|childTG: ThreadGroup| {
                 let pgTmp = childTG.lock().processGroup.clone().unwrap();
                 pgTmp.incRefWithParent(Some(pg.clone()));
-                pgTmp.decRefWithParent(oldParentPG.clone());
+                pgTmp.decRefWithParent(Some(oldPg.clone()));
             });
 
-            let oldPg = self.lock().processGroup.clone().unwrap();
-            oldPg.decRefWithParent(oldParentPG);
             self.lock().processGroup = Some(pg.clone());
+            oldPg.decRefWithParent(oldParentPG);
         } else {
             self.lock().processGroup = Some(pg.clone());
             pg.lock().ancestors += 1;
@@ -22,6 +22,7 @@ use spin::*;
 
 use super::super::super::auth::userns::*;
 use super::super::super::common::*;
+use super::super::super::linux::ioprio::*;
 use super::super::super::linux_def::*;
 use super::super::super::usage::io::*;
 use super::super::kernel::kernel::*;
@@ -33,6 +34,7 @@ use super::super::uid::NewUID;
 use super::super::SignalDef::*;
 use super::pid_namespace::*;
 use super::session::*;
+use super::task_block::*;
 use super::task_exit::*;
 use super::task_sched::*;
 use super::thread::*;
@@ -47,7 +49,9 @@ pub struct TaskSetInternal {
 }
 
 impl TaskSetInternal {
-    pub fn AssignTids(&mut self, t: &Thread) -> Result<()> {
+    // AssignTids assigns the tids of t in its pid namespace and the ancestors.
+    // If hint is not 0, it is the tid in the pid namespace of t if it is free.
+    pub fn AssignTids(&mut self, t: &Thread, hint: ThreadID) -> Result<()> {
         struct AllocatedTID {
             ns: PIDNamespace,
             tid: ThreadID,
@@ -59,7 +63,13 @@ impl TaskSetInternal {
         let mut allocatedTIDs: Vec<AllocatedTID> = Vec::new();
 
         loop {
-            let tid = match pidns.AllocateTID() {
+            let res = if hint != 0 && allocatedTIDs.len() == 0 {
+                pidns.AllocateTIDHint(hint)
+            } else {
+                pidns.AllocateTID()
+            };
+
+            let tid = match res {
                 Err(e) => {
                     for a in allocatedTIDs {
                         let tns = a.ns.clone();
@@ -204,11 +214,13 @@ impl TaskSet {
             trapNotifyPending: false,
             allowedCPUMask: cfg.AllowedCPUMask.Copy(),
             cpu: 0,
-            niceness: 0,
+            niceness: cfg.Niceness,
+            ioprio: IOPrio::default(),
             numaPolicy: 0,
             numaNodeMask: 0,
             netns: false,
             parentDeathSignal: Signal::default(),
+            defaultTimerSlack: DEFAULT_TIMER_SLACK,
             stop: None,
             stopCount: WaitGroup::default(),
             exitStatus: ExitStatus::default(),
@@ -221,12 +233,14 @@ impl TaskSet {
             containerID: cfg.ContainerID.to_string(),
             ioUsage: IO::default(),
             robust_list_head: 0,
+            userCharge: None,
         };
 
         let t = Thread {
             uid: NewUID(),
             data: Arc::new(QMutex::new(internal)),
         };
+        t.UpdateSchedWeight();
 
         if fromContext {
             let task = Task::Current();
@@ -252,7 +266,7 @@ impl TaskSet {
                 }
             }
 
-            tslock.AssignTids(&t)?;
+            tslock.AssignTids(&t, cfg.Tid)?;
             tslock.IncrTaskCount();
         }
 
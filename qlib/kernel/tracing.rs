@@ -0,0 +1,177 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::Ordering;
+
+use super::super::mutex::*;
+use super::super::perf_tunning::*;
+use super::super::vcpu_mgr::*;
+use super::Scale;
+use super::TSC;
+
+pub static TRACER: Tracer = Tracer::New();
+
+// the default event count of each vcpu ring buffer
+pub const DEFAULT_TRACE_RING_SIZE: usize = 4096;
+
+// TraceEvent is a begin or end point of a span. The spans are the PerfType
+// regions marked by Task::PerfGoto/PerfGofrom.
+#[derive(Debug, Clone, Copy)]
+pub struct TraceEvent {
+    pub typ: PerfType,
+    pub begin: bool,
+    pub taskId: u64,
+    pub tsc: i64,
+}
+
+pub struct TraceRing {
+    pub events: Vec<TraceEvent>,
+    pub size: usize,
+    // next is the slot which will be written by the next push
+    pub next: usize,
+    pub dropped: u64,
+}
+
+impl TraceRing {
+    pub fn New(size: usize) -> Self {
+        return Self {
+            events: Vec::with_capacity(size),
+            size: size,
+            next: 0,
+            dropped: 0,
+        };
+    }
+
+    pub fn Push(&mut self, event: TraceEvent) {
+        if self.events.len() < self.size {
+            self.events.push(event);
+        } else {
+            self.events[self.next] = event;
+            self.dropped += 1;
+        }
+
+        self.next = (self.next + 1) % self.size;
+    }
+
+    // Events returns the events from the oldest to the newest
+    pub fn Events(&self) -> Vec<TraceEvent> {
+        let mut ret = Vec::with_capacity(self.events.len());
+        if self.events.len() < self.size {
+            ret.extend_from_slice(&self.events);
+        } else {
+            ret.extend_from_slice(&self.events[self.next..]);
+            ret.extend_from_slice(&self.events[..self.next]);
+        }
+
+        return ret;
+    }
+}
+
+pub struct Tracer {
+    pub enable: AtomicBool,
+    pub rings: QRwLock<Vec<QMutex<TraceRing>>>,
+}
+
+impl Tracer {
+    pub const fn New() -> Self {
+        return Self {
+            enable: AtomicBool::new(false),
+            rings: QRwLock::new(Vec::new()),
+        };
+    }
+
+    #[inline]
+    pub fn Enabled(&self) -> bool {
+        return self.enable.load(Ordering::Relaxed);
+    }
+
+    // Start drops the previous events and starts recording with one ring buffer
+    // of ringSize events for each vcpu.
+    pub fn Start(&self, vcpuCount: usize, ringSize: usize) {
+        let ringSize = if ringSize == 0 {
+            DEFAULT_TRACE_RING_SIZE
+        } else {
+            ringSize
+        };
+
+        let mut rings = Vec::with_capacity(vcpuCount);
+        for _i in 0..vcpuCount {
+            rings.push(QMutex::new(TraceRing::New(ringSize)));
+        }
+
+        *self.rings.write() = rings;
+        self.enable.store(true, Ordering::SeqCst);
+    }
+
+    pub fn Stop(&self) {
+        self.enable.store(false, Ordering::SeqCst);
+    }
+
+    #[inline]
+    pub fn Record(&self, typ: PerfType, begin: bool, taskId: u64) {
+        if !self.Enabled() {
+            return;
+        }
+
+        let event = TraceEvent {
+            typ: typ,
+            begin: begin,
+            taskId: taskId,
+            tsc: TSC.Rdtsc(),
+        };
+
+        let rings = self.rings.read();
+        if let Some(ring) = rings.get(CPULocal::CpuId()) {
+            ring.lock().Push(event);
+        }
+    }
+
+    // Dump returns the recorded events in Chrome trace event format, which can be
+    // loaded by chrome://tracing or https://ui.perfetto.dev. Each task is shown as a
+    // thread and the args of each event record the vcpu where it happens.
+    pub fn Dump(&self) -> String {
+        let rings = self.rings.read();
+        let mut dropped = 0;
+        let mut output = String::from("{\"traceEvents\":[");
+        let mut first = true;
+        for vcpu in 0..rings.len() {
+            let ring = rings[vcpu].lock();
+            dropped += ring.dropped;
+            for event in ring.Events() {
+                if !first {
+                    output += ",";
+                }
+                first = false;
+
+                output += &format!(
+                    "{{\"name\":\"{:?}\",\"cat\":\"quark\",\"ph\":\"{}\",\"ts\":{},\"pid\":0,\"tid\":{},\"args\":{{\"vcpu\":{}}}}}",
+                    event.typ,
+                    if event.begin { "B" } else { "E" },
+                    Scale(event.tsc),
+                    event.taskId,
+                    vcpu
+                );
+            }
+        }
+
+        output += &format!(
+            "],\"displayTimeUnit\":\"ns\",\"otherData\":{{\"dropped\":{}}}}}",
+            dropped
+        );
+        return output;
+    }
+}
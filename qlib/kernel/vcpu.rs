@@ -19,6 +19,7 @@ use super::asm::*;
 //use super::IOURING;
 use super::super::singleton::*;
 use super::super::vcpu_mgr::*;
+use super::KERNEL_STACK_ALLOCATOR;
 use super::SHARESPACE;
 
 pub static VCPU_COUNT: Singleton<AtomicUsize> = Singleton::<AtomicUsize>::New();
@@ -59,8 +60,17 @@ pub enum PrCtlEnum {
     ARCH_SET_FS = 0x1002,
     ARCH_GET_FS = 0x1003,
     ARCH_GET_GS = 0x1004,
+    ARCH_SHSTK_ENABLE = 0x5001,
+    ARCH_SHSTK_DISABLE = 0x5002,
+    ARCH_SHSTK_LOCK = 0x5003,
+    ARCH_SHSTK_UNLOCK = 0x5004,
+    ARCH_SHSTK_STATUS = 0x5005,
 }
 
+// the features of ARCH_SHSTK_*
+pub const ARCH_SHSTK_SHSTK: u64 = 1 << 0;
+pub const ARCH_SHSTK_WRSS: u64 = 1 << 1;
+
 pub fn RegisterSysCall(addr: u64) {
     //WriteMsr(MSR::MSR_STAR as u32, 0x00200008<<32);
     WriteMsr(MSR::MSR_STAR as u32, 0x00100008 << 32);
@@ -151,6 +161,23 @@ impl CPULocal {
         return Self::Myself().pendingFreeStack.load(Ordering::SeqCst);
     }
 
+    // AllocKernelStack takes a kernel stack from the pool of the vcpu before
+    // going to the global allocator
+    pub fn AllocKernelStack() -> u64 {
+        let stack = Self::Myself().stackPool.lock().Pop();
+        match stack {
+            Some(stack) => return stack,
+            None => return KERNEL_STACK_ALLOCATOR.Allocate().unwrap(),
+        }
+    }
+
+    pub fn FreeKernelStack(stack: u64) {
+        let pooled = Self::Myself().stackPool.lock().Push(stack);
+        if !pooled {
+            KERNEL_STACK_ALLOCATOR.Free(stack).unwrap();
+        }
+    }
+
     pub fn CPUState() -> VcpuState {
         return Self::Myself().State();
     }
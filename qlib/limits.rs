@@ -190,20 +190,18 @@ impl LimitSet {
     }
 
     pub fn Set(&self, t: LimitType, v: Limit, privileged: bool) -> Result<Limit> {
+        if v.Cur > v.Max {
+            return Err(Error::SysError(SysErr::EINVAL));
+        }
+
         let mut internal = self.lock();
 
         match internal.data.get_mut(&t) {
             Some(l) => {
-                info!("current is {:?}, the neww is {:?}", l, &v);
                 if l.Max < v.Max && !privileged {
                     return Err(Error::SysError(SysErr::EPERM));
                 }
 
-                info!("Set ....");
-                if l.Cur > v.Max {
-                    return Err(Error::SysError(SysErr::EINVAL));
-                }
-
                 let old = *l;
                 *l = v;
                 return Ok(old);
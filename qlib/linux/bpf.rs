@@ -0,0 +1,122 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Classic BPF instruction encoding, from include/uapi/linux/bpf_common.h and
+// include/uapi/linux/filter.h.
+
+// instruction classes
+pub const BPF_LD: u16 = 0x00;
+pub const BPF_LDX: u16 = 0x01;
+pub const BPF_ST: u16 = 0x02;
+pub const BPF_STX: u16 = 0x03;
+pub const BPF_ALU: u16 = 0x04;
+pub const BPF_JMP: u16 = 0x05;
+pub const BPF_RET: u16 = 0x06;
+pub const BPF_MISC: u16 = 0x07;
+pub const BPF_CLASS_MASK: u16 = 0x07;
+
+// load sizes
+pub const BPF_W: u16 = 0x00;
+pub const BPF_H: u16 = 0x08;
+pub const BPF_B: u16 = 0x10;
+pub const BPF_SIZE_MASK: u16 = 0x18;
+
+// load modes
+pub const BPF_IMM: u16 = 0x00;
+pub const BPF_ABS: u16 = 0x20;
+pub const BPF_IND: u16 = 0x40;
+pub const BPF_MEM: u16 = 0x60;
+pub const BPF_LEN: u16 = 0x80;
+pub const BPF_MSH: u16 = 0xa0;
+pub const BPF_MODE_MASK: u16 = 0xe0;
+
+// alu operations
+pub const BPF_ADD: u16 = 0x00;
+pub const BPF_SUB: u16 = 0x10;
+pub const BPF_MUL: u16 = 0x20;
+pub const BPF_DIV: u16 = 0x30;
+pub const BPF_OR: u16 = 0x40;
+pub const BPF_AND: u16 = 0x50;
+pub const BPF_LSH: u16 = 0x60;
+pub const BPF_RSH: u16 = 0x70;
+pub const BPF_NEG: u16 = 0x80;
+pub const BPF_MOD: u16 = 0x90;
+pub const BPF_XOR: u16 = 0xa0;
+pub const BPF_OP_MASK: u16 = 0xf0;
+
+// jump operations
+pub const BPF_JA: u16 = 0x00;
+pub const BPF_JEQ: u16 = 0x10;
+pub const BPF_JGT: u16 = 0x20;
+pub const BPF_JGE: u16 = 0x30;
+pub const BPF_JSET: u16 = 0x40;
+
+// operand sources
+pub const BPF_K: u16 = 0x00;
+pub const BPF_X: u16 = 0x08;
+pub const BPF_SRC_MASK: u16 = 0x08;
+
+// return value sources, BPF_K and BPF_X as above
+pub const BPF_A: u16 = 0x10;
+pub const BPF_RVAL_MASK: u16 = 0x18;
+
+// misc operations
+pub const BPF_TAX: u16 = 0x00;
+pub const BPF_TXA: u16 = 0x80;
+pub const BPF_MISCOP_MASK: u16 = 0xf8;
+
+// BPF_MEMWORDS is the number of the scratch memory words.
+pub const BPF_MEMWORDS: usize = 16;
+
+// BPF_MAXINSNS is the max instructions of a program.
+pub const BPF_MAXINSNS: usize = 4096;
+
+// BPFInstruction is struct sock_filter.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BPFInstruction {
+    pub OpCode: u16,
+    pub JumpIfTrue: u8,
+    pub JumpIfFalse: u8,
+    pub K: u32,
+}
+
+impl BPFInstruction {
+    pub fn Stmt(code: u16, k: u32) -> Self {
+        return Self {
+            OpCode: code,
+            JumpIfTrue: 0,
+            JumpIfFalse: 0,
+            K: k,
+        };
+    }
+
+    pub fn Jump(code: u16, k: u32, jt: u8, jf: u8) -> Self {
+        return Self {
+            OpCode: code,
+            JumpIfTrue: jt,
+            JumpIfFalse: jf,
+            K: k,
+        };
+    }
+}
+
+// SockFprog is struct sock_fprog, the value of SO_ATTACH_FILTER.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SockFprog {
+    pub Len: u16,
+    pub pad: [u8; 6],
+    pub Filter: u64,
+}
@@ -0,0 +1,47 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// struct file_handle of name_to_handle_at(2) and open_by_handle_at(2), from
+// include/linux/fs.h and include/uapi/linux/fcntl.h.
+pub const MAX_HANDLE_SZ: u32 = 128;
+
+// FileHandleHeader is the head of struct file_handle, the opaque handle of
+// HandleBytes follows it.
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct FileHandleHeader {
+    pub HandleBytes: u32,
+    pub HandleType: i32,
+}
+
+// FILEID_QUARK_HOST is the handle type of the files of the host mounts, the
+// handle is HostFileHandle.
+pub const FILEID_QUARK_HOST: i32 = 0x51;
+
+// HostFileHandle is struct file_handle with the opaque handle of a host file.
+// Nonce is the random handle nonce of the mount source, a handle can't be
+// made up without it.
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct HostFileHandle {
+    pub HandleBytes: u32,
+    pub HandleType: i32,
+    pub MountId: u64,
+    pub InodeId: u64,
+    pub Nonce: u64,
+}
+
+impl HostFileHandle {
+    pub const HANDLE_BYTES: u32 = 24;
+}
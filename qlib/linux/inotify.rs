@@ -105,3 +105,18 @@ pub const ALL_INOTIFY_BITS: u32 = IN_ACCESS
     | IN_MASK_ADD
     | IN_ISDIR
     | IN_ONESHOT;
+
+// InotifyEvent is the header of an event read from an inotify fd, Len bytes
+// of the null padded name follow it.
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct InotifyEvent {
+    pub Wd: i32,
+    pub Mask: u32,
+    pub Cookie: u32,
+    pub Len: u32,
+}
+
+impl InotifyEvent {
+    pub const SIZE: usize = 16;
+}
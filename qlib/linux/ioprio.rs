@@ -0,0 +1,82 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// I/O priority classes and targets of ioprio_set(2), from
+// include/uapi/linux/ioprio.h.
+pub const IOPRIO_CLASS_SHIFT: i32 = 13;
+pub const IOPRIO_PRIO_MASK: i32 = (1 << IOPRIO_CLASS_SHIFT) - 1;
+
+pub const IOPRIO_CLASS_NONE: i32 = 0;
+pub const IOPRIO_CLASS_RT: i32 = 1;
+pub const IOPRIO_CLASS_BE: i32 = 2;
+pub const IOPRIO_CLASS_IDLE: i32 = 3;
+
+// IOPRIO_NR_LEVELS is the number of levels of the RT and BE classes.
+pub const IOPRIO_NR_LEVELS: i32 = 8;
+
+// IOPRIO_NORM is the level of a task without an I/O priority of its own.
+pub const IOPRIO_NORM: i32 = 4;
+
+pub const IOPRIO_WHO_PROCESS: i32 = 1;
+pub const IOPRIO_WHO_PGRP: i32 = 2;
+pub const IOPRIO_WHO_USER: i32 = 3;
+
+// IOPrio is an I/O priority value, a class and a level within the class.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct IOPrio(pub i32);
+
+impl IOPrio {
+    pub fn New(class: i32, data: i32) -> Self {
+        return Self((class << IOPRIO_CLASS_SHIFT) | data);
+    }
+
+    pub fn Class(&self) -> i32 {
+        return self.0 >> IOPRIO_CLASS_SHIFT;
+    }
+
+    pub fn Data(&self) -> i32 {
+        return self.0 & IOPRIO_PRIO_MASK;
+    }
+
+    // Effective returns the priority the I/O is scheduled with, i.e. BE at the
+    // normal level when no class is set.
+    pub fn Effective(&self) -> Self {
+        if self.Class() == IOPRIO_CLASS_NONE {
+            return Self::New(IOPRIO_CLASS_BE, IOPRIO_NORM);
+        }
+
+        return *self;
+    }
+
+    // Best returns the higher of two priorities, like ioprio_best in Linux.
+    // Lower values of the effective priority are scheduled first.
+    pub fn Best(&self, other: &Self) -> Self {
+        if self.Effective().0 <= other.Effective().0 {
+            return self.Effective();
+        }
+
+        return other.Effective();
+    }
+
+    // Host returns the priority to set on the host I/O of the task. The RT
+    // class needs CAP_SYS_ADMIN on the host, so it is scheduled as the highest
+    // BE level there instead of failing the I/O.
+    pub fn Host(&self) -> u16 {
+        if self.Class() == IOPRIO_CLASS_RT {
+            return Self::New(IOPRIO_CLASS_BE, 0).0 as u16;
+        }
+
+        return self.0 as u16;
+    }
+}
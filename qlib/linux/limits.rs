@@ -98,7 +98,13 @@ pub unsafe fn InitSingleton() {
                 Max: RLIM_INFINITY,
             },
         ),
-        (RLIMIT_SIGPENDING, RLimit { Cur: 0, Max: 0 }),
+        (
+            RLIMIT_SIGPENDING,
+            RLimit {
+                Cur: DEFAULT_SIGPENDING_LIMIT,
+                Max: DEFAULT_SIGPENDING_LIMIT,
+            },
+        ),
         (
             RLIMIT_MSGQUEUE,
             RLimit {
@@ -177,12 +183,19 @@ pub const DEFAULT_STACK_SOFT_LIMIT: u64 = 8 * 1024 * 1024;
 // called MAX_THREADS / 2 in Linux.
 pub const DEFAULT_NPROC_LIMIT: u64 = FUTEX_TID_MASK as u64 / 2;
 
+// DefaultSigpendingLimit is set to the same MAX_THREADS / 2 in
+// kernel/fork.c:set_max_threads.
+pub const DEFAULT_SIGPENDING_LIMIT: u64 = DEFAULT_NPROC_LIMIT;
+
 // DefaultNofileSoftLimit is called INR_OPEN_CUR in Linux.
 pub const DEFAULT_NOFILE_SOFT_LIMIT: u64 = 1024;
 
 // DefaultNofileHardLimit is called INR_OPEN_MAX in Linux.
 pub const DEFAULT_NOFILE_HARD_LIMIT: u64 = 4096;
 
+// NrOpen is the default of sysctl fs.nr_open, the ceiling for RLIMIT_NOFILE.
+pub const NR_OPEN: u64 = 1024 * 1024;
+
 // DefaultMemlockLimit is called MLOCK_LIMIT in Linux.
 pub const DEFAULT_MEMLOCK_LIMIT: u64 = 64 * 1024;
 
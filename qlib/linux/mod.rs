@@ -12,12 +12,18 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod bpf;
 pub mod fcntl;
+pub mod fhandle;
 pub mod futex;
 pub mod inotify;
+pub mod ioprio;
 pub mod ipc;
 pub mod limits;
 pub mod membarrier;
+pub mod mount;
+pub mod mqueue;
+pub mod msgqueue;
 pub mod netdevice;
 pub mod rusage;
 pub mod sem;
@@ -0,0 +1,54 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Flags and commands of the new mount API, fsopen(2), fsconfig(2),
+// fsmount(2), move_mount(2) and open_tree(2), from
+// include/uapi/linux/mount.h.
+pub const FSOPEN_CLOEXEC: u32 = 0x1;
+
+pub const FSCONFIG_SET_FLAG: u32 = 0;
+pub const FSCONFIG_SET_STRING: u32 = 1;
+pub const FSCONFIG_SET_BINARY: u32 = 2;
+pub const FSCONFIG_SET_PATH: u32 = 3;
+pub const FSCONFIG_SET_PATH_EMPTY: u32 = 4;
+pub const FSCONFIG_SET_FD: u32 = 5;
+pub const FSCONFIG_CMD_CREATE: u32 = 6;
+pub const FSCONFIG_CMD_RECONFIGURE: u32 = 7;
+pub const FSCONFIG_CMD_CREATE_EXCL: u32 = 8;
+
+pub const FSMOUNT_CLOEXEC: u32 = 0x1;
+
+pub const MOUNT_ATTR_RDONLY: u32 = 0x1;
+pub const MOUNT_ATTR_NOSUID: u32 = 0x2;
+pub const MOUNT_ATTR_NODEV: u32 = 0x4;
+pub const MOUNT_ATTR_NOEXEC: u32 = 0x8;
+pub const MOUNT_ATTR__ATIME: u32 = 0x70;
+pub const MOUNT_ATTR_RELATIME: u32 = 0x0;
+pub const MOUNT_ATTR_NOATIME: u32 = 0x10;
+pub const MOUNT_ATTR_STRICTATIME: u32 = 0x20;
+pub const MOUNT_ATTR_NODIRATIME: u32 = 0x80;
+
+pub const MOVE_MOUNT_F_SYMLINKS: u32 = 0x1;
+pub const MOVE_MOUNT_F_AUTOMOUNTS: u32 = 0x2;
+pub const MOVE_MOUNT_F_EMPTY_PATH: u32 = 0x4;
+pub const MOVE_MOUNT_T_SYMLINKS: u32 = 0x10;
+pub const MOVE_MOUNT_T_AUTOMOUNTS: u32 = 0x20;
+pub const MOVE_MOUNT_T_EMPTY_PATH: u32 = 0x40;
+pub const MOVE_MOUNT__MASK: u32 = 0x77;
+
+pub const OPEN_TREE_CLONE: u32 = 0x1;
+pub const OPEN_TREE_CLOEXEC: u32 = 0o2000000;
+
+pub const AT_NO_AUTOMOUNT: u32 = 0x800;
+pub const AT_RECURSIVE: u32 = 0x8000;
@@ -0,0 +1,73 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::ipc::*;
+use super::time::*;
+
+// msgrcv(2) flags. Source: include/uapi/linux/msg.h
+pub const MSG_NOERROR: i32 = 0o10000; // No error if message is too big.
+pub const MSG_EXCEPT: i32 = 0o20000; // Receive any message except of specified type.
+pub const MSG_COPY: i32 = 0o40000; // Copy (not remove) all queue messages.
+
+// msgctl(2) commands. Source: include/uapi/linux/msg.h
+pub const MSG_STAT: i32 = 11;
+pub const MSG_INFO: i32 = 12;
+pub const MSG_STAT_ANY: i32 = 13;
+
+// System-wide limits for message queues. Source: include/uapi/linux/msg.h
+pub const MSGMNI: u64 = 32000; // Max number of message queue identifiers.
+pub const MSGMAX: u64 = 8192; // Max size of message (bytes).
+pub const MSGMNB: u64 = 16384; // Default max size of a message queue.
+
+// System-wide limits. Unused. Source: include/uapi/linux/msg.h
+pub const MSGPOOL: u64 = MSGMNI * MSGMNB / 1024;
+pub const MSGTQL: u64 = MSGMNB;
+pub const MSGMAP: u64 = MSGMNB;
+pub const MSGSSZ: u64 = 16;
+
+// MSGSEG is (MSGPOOL * 1024) / MSGSSZ capped to 0xffff, which is 0xffff with
+// the limits above.
+pub const MSGSEG: u64 = 0xffff;
+
+// MsqidDS is equivalent to struct msqid64_ds. Source:
+// include/uapi/asm-generic/msgbuf.h
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MsqidDS {
+    pub MsgPerm: IPCPerm, // IPC permissions.
+    pub MsgStime: TimeT,  // Last msgsnd time.
+    pub MsgRtime: TimeT,  // Last msgrcv time.
+    pub MsgCtime: TimeT,  // Last change time.
+    pub MsgCbytes: u64,   // Current number of bytes on the queue.
+    pub MsgQnum: u64,     // Number of messages in the queue.
+    pub MsgQbytes: u64,   // Max number of bytes in the queue.
+    pub MsgLspid: i32,    // PID of last msgsnd.
+    pub MsgLrpid: i32,    // PID of last msgrcv.
+    pub unused4: u64,
+    pub unused5: u64,
+}
+
+// MsgInfo is equivalent to struct msginfo. Source: include/uapi/linux/msg.h
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MsgInfo {
+    pub MsgPool: i32,
+    pub MsgMap: i32,
+    pub MsgMax: i32,
+    pub MsgMnb: i32,
+    pub MsgMni: i32,
+    pub MsgSsz: i32,
+    pub MsgTql: i32,
+    pub MsgSeg: u16,
+}
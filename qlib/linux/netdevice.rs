@@ -48,6 +48,7 @@ impl IFReq {
         for i in 0..len {
             if self.IFName[i] == 0 {
                 idx = i;
+                break;
             }
         }
 
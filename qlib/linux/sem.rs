@@ -32,16 +32,22 @@ pub const SEM_STAT_ANY: i32 = 20;
 pub const SEM_UNDO: i32 = 0x1000;
 
 // SemidDS is equivalent to struct semid64_ds.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
 pub struct SemidDS {
     pub SemPerm: IPCPerm,
     pub SemOTime: TimeT,
+    pub unused1: u64,
     pub SemCTime: TimeT,
+    pub unused2: u64,
     pub SemNSems: u64,
     pub unused3: u64,
     pub unused4: u64,
 }
 
 // Sembuf is equivalent to struct sembuf.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
 pub struct Sembuf {
     pub SemNum: u16,
     pub SemOp: i16,
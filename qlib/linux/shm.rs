@@ -38,14 +38,14 @@ pub const SHM_INFO: u16 = 14;
 // SHM defaults as specified by linux. Source: include/uapi/linux/shm.h
 pub const SHMMIN: u64 = 1;
 pub const SHMMNI: u64 = 4096;
-pub const SHMMAX: u64 = u64::MAX - 1 << 24;
-pub const SHMALL: u64 = u64::MAX - 1 << 24;
+pub const SHMMAX: u64 = u64::MAX - (1 << 24);
+pub const SHMALL: u64 = u64::MAX - (1 << 24);
 pub const SHMSEG: u64 = 4096;
 
 // ShmidDS is equivalent to struct shmid64_ds. Source:
 // include/uapi/asm-generic/shmbuf.h
 #[repr(C)]
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default)]
 pub struct ShmidDS {
     pub ShmPerm: IPCPerm,
     pub ShmSegsz: u64,
@@ -62,18 +62,23 @@ pub struct ShmidDS {
 
 // ShmParams is equivalent to struct shminfo. Source: include/uapi/linux/shm.h
 #[repr(C)]
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default)]
 pub struct ShmParams {
     pub ShmMax: u64,
     pub ShmMin: u64,
     pub ShmMni: u64,
     pub ShmSeg: u64,
     pub ShmAll: u64,
+
+    pub Unused1: u64,
+    pub Unused2: u64,
+    pub Unused3: u64,
+    pub Unused4: u64,
 }
 
 // ShmInfo is equivalent to struct shm_info. Source: include/uapi/linux/shm.h
 #[repr(C)]
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default)]
 pub struct ShmInfo {
     pub UsedIDs: i32,
     // Number of currently existing segments.
@@ -61,6 +61,7 @@ pub const CLOCK_MONOTONIC_COARSE: i32 = 6;
 pub const CLOCK_BOOTTIME: i32 = 7;
 pub const CLOCK_REALTIME_ALARM: i32 = 8;
 pub const CLOCK_BOOTTIME_ALARM: i32 = 9;
+pub const CLOCK_TAI: i32 = 11;
 
 // Flags for clock_nanosleep(2).
 pub const TIMER_ABSTIME: i32 = 1;
@@ -376,6 +376,16 @@ pub struct LibcStatfs {
     pub Spare: [u64; 4],
 }
 
+impl LibcStatfs {
+    // Flags of statfs(2), see include/linux/statfs.h.
+    pub const ST_RDONLY: u64 = 0x0001;
+    pub const ST_NOSUID: u64 = 0x0002;
+    pub const ST_NODEV: u64 = 0x0004;
+    pub const ST_NOEXEC: u64 = 0x0008;
+    pub const ST_VALID: u64 = 0x0020;
+    pub const ST_NOATIME: u64 = 0x0400;
+}
+
 // Filesystem types used in statfs(2).
 // See linux/magic.h.
 pub struct FSMagic {}
@@ -383,11 +393,12 @@ pub struct FSMagic {}
 impl FSMagic {
     pub const ANON_INODE_FS_MAGIC: u64 = 0x09041934;
     pub const DEVPTS_SUPER_MAGIC: u64 = 0x00001cd1;
+    pub const EROFS_SUPER_MAGIC_V1: u64 = 0xe0f5e1e2;
     pub const EXT_SUPER_MAGIC: u64 = 0xef53;
     pub const OVERLAYFS_SUPER_MAGIC: u64 = 0x794c7630;
     pub const PIPEFS_MAGIC: u64 = 0x50495045;
     pub const PROC_SUPER_MAGIC: u64 = 0x9fa0;
-    pub const RAMFS_MAGIC: u64 = 0x09041934;
+    pub const RAMFS_MAGIC: u64 = 0x858458f6;
     pub const SOCKFS_MAGIC: u64 = 0x534F434B;
     pub const SYSFS_MAGIC: u64 = 0x62656572;
     pub const TMPFS_MAGIC: u64 = 0x01021994;
@@ -401,6 +412,16 @@ impl MfdType {
     pub const MFD_ALLOW_SEALING: u32 = 0x0002;
 }
 
+pub struct SealFlags {}
+
+impl SealFlags {
+    pub const F_SEAL_SEAL: u32 = 0x0001;
+    pub const F_SEAL_SHRINK: u32 = 0x0002;
+    pub const F_SEAL_GROW: u32 = 0x0004;
+    pub const F_SEAL_WRITE: u32 = 0x0008;
+    pub const F_SEAL_FUTURE_WRITE: u32 = 0x0010;
+}
+
 #[repr(C)]
 #[derive(Debug, Default, Copy, Clone)]
 pub struct FlockStruct {
@@ -521,6 +542,15 @@ impl ATType {
     pub const AT_SYMLINK_NOFOLLOW: i32 = 0x100;
 }
 
+// Flags for renameat2(2).
+pub struct RenameFlags {}
+
+impl RenameFlags {
+    pub const RENAME_NOREPLACE: u32 = 1 << 0;
+    pub const RENAME_EXCHANGE: u32 = 1 << 1;
+    pub const RENAME_WHITEOUT: u32 = 1 << 2;
+}
+
 // Values for linux_dirent64.d_type.
 pub struct DType {}
 
@@ -1151,6 +1181,7 @@ impl LibcConst {
     pub const IPV6_CHECKSUM: u64 = 0x7;
     pub const IPV6_DROP_MEMBERSHIP: u64 = 0x15;
     pub const IPV6_DSTOPTS: u64 = 0x3b;
+    pub const IPV6_FREEBIND: u64 = 0x4e;
     pub const IPV6_HOPLIMIT: u64 = 0x34;
     pub const IPV6_HOPOPTS: u64 = 0x36;
     pub const IPV6_IPSEC_POLICY: u64 = 0x22;
@@ -1185,6 +1216,7 @@ impl LibcConst {
     pub const IPV6_RXDSTOPTS: u64 = 0x3b;
     pub const IPV6_RXHOPOPTS: u64 = 0x36;
     pub const IPV6_TCLASS: u64 = 0x43;
+    pub const IPV6_TRANSPARENT: u64 = 0x4b;
     pub const IPV6_UNICAST_HOPS: u64 = 0x10;
     pub const IPV6_V6ONLY: u64 = 0x1a;
     pub const IPV6_XFRM_POLICY: u64 = 0x23;
@@ -1719,6 +1751,7 @@ impl LibcConst {
     pub const SIOCGRARP: u64 = 0x8961;
     pub const SIOCGSTAMP: u64 = 0x8906;
     pub const SIOCGSTAMPNS: u64 = 0x8907;
+    pub const SIOCOUTQNSD: u64 = 0x894b;
     pub const SIOCPROTOPRIVATE: u64 = 0x89e0;
     pub const SIOCRTMSG: u64 = 0x890d;
     pub const SIOCSARP: u64 = 0x8955;
@@ -1764,12 +1797,15 @@ impl LibcConst {
     pub const SOL_RAW: u64 = 0xff;
     pub const SOL_SOCKET: u64 = 0x1;
     pub const SOL_TCP: u64 = 0x6;
+    pub const SOL_TLS: u64 = 0x11a;
+    pub const SOL_UDP: u64 = 0x11;
     pub const SOL_X25: u64 = 0x106;
 
     pub const SOMAXCONN: u64 = 0x80;
     pub const SO_ACCEPTCONN: u64 = 0x1e;
     pub const SO_ATTACH_FILTER: u64 = 0x1a;
     pub const SO_BINDTODEVICE: u64 = 0x19;
+    pub const SO_BINDTOIFINDEX: u64 = 0x3e;
     pub const SO_BROADCAST: u64 = 0x6;
     pub const SO_BSDCOMPAT: u64 = 0xe;
     pub const SO_REUSEPORT: u64 = 0xf;
@@ -1856,6 +1892,7 @@ impl LibcConst {
     pub const TCP_SYNCNT: u64 = 0x7;
     pub const TCP_WINDOW_CLAMP: u64 = 0xa;
     pub const TCP_INQ: u64 = 0x24;
+    pub const TCP_ULP: u64 = 0x1f;
     pub const TIOCCBRK: u64 = 0x5428;
     pub const TIOCCONS: u64 = 0x541d;
     pub const TIOCEXCL: u64 = 0x540c;
@@ -1920,6 +1957,8 @@ impl LibcConst {
     pub const TIOCSSOFTCAR: u64 = 0x541a;
     pub const TIOCSTI: u64 = 0x5412;
     pub const TIOCSWINSZ: u64 = 0x5414;
+    pub const TLS_RX: u64 = 0x2;
+    pub const TLS_TX: u64 = 0x1;
     pub const TUNATTACHFILTER: u64 = 0x401054d5;
     pub const TUNDETACHFILTER: u64 = 0x401054d6;
     pub const TUNGETFEATURES: u64 = 0x800454cf;
@@ -1937,6 +1976,9 @@ impl LibcConst {
     pub const TUNSETSNDBUF: u64 = 0x400454d4;
     pub const TUNSETTXFILTER: u64 = 0x400454d1;
     pub const TUNSETVNETHDRSZ: u64 = 0x400454d8;
+    pub const UDP_CORK: u64 = 0x1;
+    pub const UDP_SEGMENT: u64 = 0x67;
+    pub const UDP_GRO: u64 = 0x68;
     pub const WALL: u64 = 0x40000000;
     pub const WCLONE: u64 = 0x80000000;
     pub const WCONTINUED: u64 = 0x8;
@@ -1962,6 +2004,8 @@ impl Cmd {
     pub const F_SETLKW: i32 = 7;
     pub const F_SETOWN: i32 = 8;
     pub const F_GETOWN: i32 = 9;
+    pub const F_SETSIG: i32 = 10;
+    pub const F_GETSIG: i32 = 11;
     pub const F_SETOWN_EX: i32 = 15;
     pub const F_GETOWN_EX: i32 = 16;
     pub const F_DUPFD_CLOEXEC: i32 = 1024 + 6;
@@ -2272,6 +2316,23 @@ pub struct IoVec {
     pub len: usize,
 }
 
+// IoVec32 is the struct iovec of a 32 bit compat process.
+#[repr(C)]
+#[derive(Clone, Copy, Default, Debug)]
+pub struct IoVec32 {
+    pub start: u32,
+    pub len: u32,
+}
+
+impl IoVec32 {
+    pub fn IoVec(&self) -> IoVec {
+        return IoVec {
+            start: self.start as u64,
+            len: self.len as usize,
+        };
+    }
+}
+
 pub struct DataBuff {
     pub buf: Vec<u8>,
 }
@@ -2509,6 +2570,13 @@ impl IoCtlCmd {
     pub const SIOCSPGRP: u64 = 0x00008902;
     pub const FIOGETOWN: u64 = 0x00008903;
     pub const SIOCGPGRP: u64 = 0x00008904;
+    pub const RNDGETENTCNT: u64 = 0x80045200;
+    pub const RNDADDTOENTCNT: u64 = 0x40045201;
+    pub const RNDGETPOOL: u64 = 0x80085202;
+    pub const RNDADDENTROPY: u64 = 0x40085203;
+    pub const RNDZAPENTCNT: u64 = 0x00005204;
+    pub const RNDCLEARPOOL: u64 = 0x00005206;
+    pub const RNDRESEEDCRNG: u64 = 0x00005207;
 }
 
 #[derive(Clone, PartialEq, Copy, Debug)]
@@ -2637,6 +2705,7 @@ impl IDType {
     pub const P_ALL: i32 = 0x0;
     pub const P_PID: i32 = 0x1;
     pub const P_PGID: i32 = 0x2;
+    pub const P_PIDFD: i32 = 0x3;
 }
 
 pub struct MAdviseOp {}
@@ -2787,6 +2856,8 @@ impl MemoryDef {
     pub const DEFAULT_STACK_PAGES: u64 = 32;
 
     pub const DEFAULT_STACK_SIZE: u64 = Self::DEFAULT_STACK_PAGES * Self::PAGE_SIZE; //64 KB
+    // the kernel stacks with the unmapped guard pages, 4096 stacks
+    pub const KERNEL_STACK_AREA_SIZE: u64 = 256 * Self::ONE_MB;
     pub const PAGE_SIZE: u64 = 1 << Self::PAGE_SHIFT; //0x1000;
     pub const HUGE_PAGE_SIZE: u64 = 1 << Self::HUGE_PAGE_SHIFT;
     pub const HUGE_PAGE_SIZE_1G: u64 = 1 << Self::HUGE_1GPAGE_SHIFT;
@@ -2809,9 +2880,13 @@ impl MemoryDef {
     // RDMA global share memory
     pub const RDMA_GLOBAL_SHARE_OFFSET: u64 = Self::RDMA_LOCAL_SHARE_OFFSET + Self::RDMA_LOCAL_SHARE_SIZE;
     pub const RDMA_GLOBAL_SHARE_SIZE: u64 = 2 * Self::ONE_MB;
-    // heap
-    pub const HEAP_OFFSET: u64 = Self::RDMA_GLOBAL_SHARE_OFFSET + Self::RDMA_GLOBAL_SHARE_SIZE;
+    // heap, aligned to 1GB so that it can be backed by the 1GB host hugepages
+    pub const HEAP_OFFSET: u64 = (Self::RDMA_GLOBAL_SHARE_OFFSET + Self::RDMA_GLOBAL_SHARE_SIZE
+        + Self::ONE_GB - 1) & !(Self::ONE_GB - 1);
     pub const HEAP_SIZE: u64 = 8 * Self::ONE_GB;
+    // the heap part for the allocations of qvisor before the config is loaded,
+    // the hugepage backing applies to the heap above it
+    pub const HEAP_BOOT_SIZE: u64 = 1 * Self::ONE_GB;
 
     // file map area
     pub const FILE_MAP_OFFSET: u64 = Self::HEAP_OFFSET + Self::HEAP_SIZE;
@@ -2843,6 +2918,15 @@ impl MmapProt {
     pub const PROT_GROWSUP: u64 = 1 << 25;
 }
 
+//pkey_alloc access rights
+pub struct PkeyRights {}
+
+impl PkeyRights {
+    pub const PKEY_DISABLE_ACCESS: u64 = 0x1;
+    pub const PKEY_DISABLE_WRITE: u64 = 0x2;
+    pub const PKEY_ACCESS_MASK: u64 = Self::PKEY_DISABLE_ACCESS | Self::PKEY_DISABLE_WRITE;
+}
+
 //mmap flags
 pub struct MmapFlags {}
 
@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use alloc::collections::btree_map::BTreeMap;
 use alloc::string::String;
 use alloc::vec::Vec;
 
@@ -29,6 +30,8 @@ pub struct Process {
     pub Args: Vec<String>,
     pub Envs: Vec<String>,
     pub Cwd: String,
+    // Umask is the umask of the spec user, 0o22 if it is not set
+    pub Umask: Option<u32>,
 
     //caps
     pub Caps: TaskCaps,
@@ -38,6 +41,12 @@ pub struct Process {
     //host
     pub NumCpu: u32,
     pub HostName: String,
+    // Sysctls are the sysctls of the spec supported by qkernel
+    pub Sysctls: BTreeMap<String, String>,
+    // MaskedPaths and ReadonlyPaths are the masked and readonly paths of
+    // the spec, e.g. /proc/kcore and /proc/sys
+    pub MaskedPaths: Vec<String>,
+    pub ReadonlyPaths: Vec<String>,
 
     //Container
     pub limitSet: LimitSetInternal,
@@ -46,4 +55,31 @@ pub struct Process {
     pub Root: String,
     pub Stdiofds: [i32; 3],
     pub ExecId: Option<String>,
+    // Devices are the host devices of the spec exposed in the guest /dev
+    pub Devices: Vec<HostDevice>,
+    // Volumes are the host block volumes exposed in the guest /dev
+    pub Volumes: Vec<BlockVolume>,
+    // WXAllowList are the executables allowed to map writable and
+    // executable pages when the W^X enforcement is on, e.g. the JITs
+    pub WXAllowList: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Eq, PartialEq, Clone)]
+pub struct BlockVolume {
+    // Name is the node name in /dev, e.g. vdb
+    pub Name: String,
+    // HostFd is the image file or block device opened by qvisor
+    pub HostFd: i32,
+    pub Size: u64,
+    pub ReadOnly: bool,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Eq, PartialEq, Clone)]
+pub struct HostDevice {
+    pub Path: String,
+    pub Major: u32,
+    pub Minor: u32,
+    pub Mode: u32,
+    pub UID: u32,
+    pub GID: u32,
 }
@@ -172,7 +172,9 @@ impl<T: Clone> LruCache<T> {
         return self.map.contains_key(&key);
     }
 
-    pub fn Add(&mut self, key: u64, d: T) {
+    // Add inserts d or refreshes the entry of key. It returns the least
+    // recently used entry evicted to make room.
+    pub fn Add(&mut self, key: u64, d: T) -> Option<T> {
         assert!(
             self.currentSize == self.list.count,
             "LruCache add mismatch, self.currentSize is {}, self.list.count is {}, map",
@@ -180,6 +182,7 @@ impl<T: Clone> LruCache<T> {
             self.list.count
         );
 
+        let mut evicted = None;
         let exist = if !self.map.contains_key(&key) {
             if self.currentSize >= self.maxSize {
                 //remove the last one
@@ -191,6 +194,7 @@ impl<T: Clone> LruCache<T> {
                     Some(r) => r,
                 };
                 let removeKey = (*remove).lock().GetKey();
+                evicted = (*remove).lock().val.take();
                 self.map.remove(&removeKey);
                 self.currentSize -= 1;
             }
@@ -215,11 +219,30 @@ impl<T: Clone> LruCache<T> {
                 }
             }
             self.list.PushFront(entry);
-            return;
+            return evicted;
         }
 
         self.list.PushFront(entry);
         self.currentSize += 1;
+        return evicted;
+    }
+
+    // Touch makes the entry of key the most recently used one, it returns
+    // false if there is no such entry.
+    pub fn Touch(&mut self, key: u64) -> bool {
+        let entry = match self.map.get(&key) {
+            None => return false,
+            Some(e) => e.clone(),
+        };
+
+        match entry.lock().Remove() {
+            Err(e) => panic!("Touch fail, {:?}", e),
+            Ok(_) => {
+                self.list.Decrease();
+            }
+        }
+        self.list.PushFront(&entry);
+        return true;
     }
 
     //ret: true- exit the item, false-not exist
@@ -309,4 +332,17 @@ mod tests {
         assert!(cache.Get(3) == None);
         assert!(cache.Get(4) == Some(4));
     }
+
+    #[test]
+    fn test_cache_evict() {
+        let mut cache = LruCache::New(2);
+        assert!(cache.Add(1, 1) == None);
+        assert!(cache.Add(2, 2) == None);
+
+        assert!(cache.Touch(1));
+        assert!(cache.Add(3, 3) == Some(2));
+        assert!(!cache.Touch(2));
+        assert!(cache.Add(1, 1) == None);
+        assert!(cache.Add(4, 4) == Some(3));
+    }
 }
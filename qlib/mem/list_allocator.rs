@@ -136,6 +136,39 @@ impl StackAllocator {
     }
 }
 
+// KernelStackPool is the per vcpu cache of the free kernel stacks. The stacks
+// are reused in LIFO order so that a new task gets the stack freed last, which
+// is still hot in the cache of the vcpu.
+#[derive(Debug, Default)]
+pub struct KernelStackPool {
+    pub stacks: [u64; Self::STACK_CACHE_COUNT],
+    pub count: usize,
+}
+
+impl KernelStackPool {
+    pub const STACK_CACHE_COUNT: usize = 8;
+
+    pub fn Pop(&mut self) -> Option<u64> {
+        if self.count == 0 {
+            return None;
+        }
+
+        self.count -= 1;
+        return Some(self.stacks[self.count]);
+    }
+
+    // Push returns false if the pool is full, the caller frees the stack
+    pub fn Push(&mut self, stack: u64) -> bool {
+        if self.count == self.stacks.len() {
+            return false;
+        }
+
+        self.stacks[self.count] = stack;
+        self.count += 1;
+        return true;
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct PageAllocator {
     pub pages: VecDeque<(u64, i64)>,
@@ -193,58 +226,70 @@ impl PageAllocator {
     }
 }
 
+// VCPU_CLASS_CNT is the count of the size classes cached per vcpu, from 8 bytes
+// to 4KB. The hot kernel objects (Task, File, Dirent, socket buffer nodes...)
+// fall in these classes.
+pub const VCPU_CLASS_CNT: usize = 10;
+// VCPU_BATCH is the count of blocks moved between a vcpu magazine and the
+// ListAllocator at a time.
+pub const VCPU_BATCH: usize = 8;
+
+// VcpuAllocator is the per vcpu magazine layer over the ListAllocator. Each
+// size class has a small stack of free blocks which is refilled from and drained
+// to the ListAllocator in batches, so that most of the alloc/dealloc on a vcpu
+// don't touch the shared size class locks. The magazine lock is only contended
+// when an interrupt handler allocates on the same vcpu, which falls back to the
+// ListAllocator.
 #[derive(Debug, Default)]
 pub struct VcpuAllocator {
-    pub bufs: [StackAllocator; 12],
+    pub bufs: [QMutex<StackAllocator>; VCPU_CLASS_CNT],
 }
-/*
+
 impl VcpuAllocator {
-    #[inline(never)]
-    pub fn alloc(&mut self, layout: Layout) -> *mut u8 {
-        let size = max(
-            layout.size().next_power_of_two(),
-            max(layout.align(), size_of::<usize>()),
-        );
-        let class = size.trailing_zeros() as usize;
-        assert!(class >= 3);
-        let idx = class - 3;
+    pub fn alloc(&self, heap: &ListAllocator, layout: Layout) -> *mut u8 {
+        let class = ListAllocator::Class(layout);
+        if class < 3 || class - 3 >= self.bufs.len() {
+            return unsafe { heap.alloc(layout) };
+        }
 
-        let ret;
-        if idx < self.bufs.len() && !self.bufs[idx].IsEmpty() {
-             ret = self.bufs[idx].Pop();
-        } else {
-            unsafe {
-                ret = GLOBAL_ALLOCATOR
-                    .alloc(layout) as u64
-            };
+        let mut buf = match self.bufs[class - 3].try_lock() {
+            None => return unsafe { heap.alloc(layout) },
+            Some(b) => b,
+        };
+
+        if buf.IsEmpty() {
+            heap.AllocBatch(class, &mut buf, VCPU_BATCH);
         }
 
-        return ret as *mut u8;
+        return buf.Pop() as *mut u8;
     }
 
-    pub fn dealloc(&mut self, ptr: *mut u8, layout: Layout) {
-        let size = max(
-            layout.size().next_power_of_two(),
-            max(layout.align(), size_of::<usize>()),
-        );
-        let class = size.trailing_zeros() as usize;
-        assert!(class >= 3);
-        let idx = class - 3;
+    pub fn dealloc(&self, heap: &ListAllocator, ptr: *mut u8, layout: Layout) {
+        let class = ListAllocator::Class(layout);
+        if class < 3 || class - 3 >= self.bufs.len() {
+            return unsafe { heap.dealloc(ptr, layout) };
+        }
 
-        if idx < self.bufs.len() && !self.bufs[idx].IsFull() {
-            self.bufs[idx].Push(ptr as u64);
-        } else {
-            unsafe {
-                GLOBAL_ALLOCATOR.dealloc(ptr, layout)
-            }
+        let mut buf = match self.bufs[class - 3].try_lock() {
+            None => return unsafe { heap.dealloc(ptr, layout) },
+            Some(b) => b,
+        };
+
+        if buf.IsFull() {
+            heap.FreeBatch(class, &mut buf, VCPU_BATCH);
         }
+
+        buf.Push(ptr as u64);
     }
-}*/
+}
 
 #[derive(Debug, Default)]
 pub struct HostAllocator {
     pub listHeapAddr: AtomicU64,
     pub initialized: AtomicBool,
+    // vcpuCache is set when the per vcpu magazines can be used, i.e. the
+    // CPULocal of the vcpus is ready
+    pub vcpuCache: AtomicBool,
 }
 
 impl HostAllocator {
@@ -391,6 +436,111 @@ impl ListAllocator {
 
         return count > 0;
     }
+
+    // FreePages returns the count of the free pages of the heap, the blocks
+    // buffered in the size classes included.
+    pub fn FreePages(&self) -> u64 {
+        return self.free.load(Ordering::Acquire) as u64 / MemoryDef::PAGE_SIZE;
+    }
+
+    // AllocHugePage takes a free huge page from the buddy heap. Unlike alloc, it
+    // returns None instead of reporting OOM when there is no free huge page.
+    pub fn AllocHugePage(&self) -> Option<u64> {
+        let size = MemoryDef::HUGE_PAGE_SIZE as usize;
+        let layout = Layout::from_size_align(size, size).unwrap();
+        let addr = match self.heap.lock().alloc(layout) {
+            Ok(ptr) => ptr.as_ptr() as u64,
+            Err(_) => return None,
+        };
+
+        self.free.fetch_sub(size, Ordering::Release);
+        return Some(addr);
+    }
+
+    pub fn FreeHugePage(&self, addr: u64) {
+        let size = MemoryDef::HUGE_PAGE_SIZE as usize;
+        let layout = Layout::from_size_align(size, size).unwrap();
+        unsafe {
+            self.heap
+                .lock()
+                .dealloc(NonNull::new_unchecked(addr as *mut u8), layout);
+        }
+
+        self.free.fetch_add(size, Ordering::Release);
+    }
+
+    // Class returns the size class of the layout
+    pub fn Class(layout: Layout) -> usize {
+        let size = max(
+            layout.size().next_power_of_two(),
+            max(layout.align(), size_of::<usize>()),
+        );
+
+        return size.trailing_zeros() as usize;
+    }
+
+    // AllocBatch moves up to cnt free blocks of the size class to the vcpu
+    // magazine. It takes the size class lock and the heap lock once for the batch.
+    pub fn AllocBatch(&self, class: usize, buf: &mut StackAllocator, cnt: usize) {
+        self.Check();
+
+        let size = 1 << class;
+        let mut count = 0;
+        {
+            let mut mgr = self.bufs[class].lock();
+            while count < cnt && !buf.IsFull() {
+                match mgr.Alloc() {
+                    None => break,
+                    Some(addr) => buf.Push(addr as u64),
+                }
+                count += 1;
+            }
+        }
+        self.bufSize.fetch_sub(count * size, Ordering::Release);
+
+        if count == cnt || buf.IsFull() {
+            return;
+        }
+
+        let layout = Layout::from_size_align(size, size).unwrap();
+        let mut heapCount = 0;
+        {
+            let mut heap = self.heap.lock();
+            while count < cnt && !buf.IsFull() {
+                match heap.alloc(layout) {
+                    Err(_) => break,
+                    Ok(ptr) => buf.Push(ptr.as_ptr() as u64),
+                }
+                count += 1;
+                heapCount += 1;
+            }
+        }
+        self.free.fetch_sub(heapCount * size, Ordering::Release);
+
+        if buf.IsEmpty() {
+            self.handleError(size as u64, size as u64);
+            loop {}
+        }
+    }
+
+    // FreeBatch moves up to cnt blocks from the vcpu magazine back to the size
+    // class.
+    pub fn FreeBatch(&self, class: usize, buf: &mut StackAllocator, cnt: usize) {
+        self.Check();
+
+        let size = 1 << class;
+        let mut count = 0;
+        {
+            let mut mgr = self.bufs[class].lock();
+            while count < cnt && !buf.IsEmpty() {
+                mgr.Dealloc(buf.Pop() as *mut u8, &self.heap);
+                count += 1;
+            }
+        }
+
+        self.free.fetch_add(count * size, Ordering::Release);
+        self.bufSize.fetch_add(count * size, Ordering::Release);
+    }
 }
 
 unsafe impl GlobalAlloc for ListAllocator {
@@ -17,6 +17,7 @@ use alloc::collections::btree_map::BTreeMap;
 use alloc::string::String;
 use alloc::string::ToString;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 use core::sync::atomic::AtomicU64;
 use core::sync::atomic::Ordering;
 
@@ -34,8 +35,21 @@ pub fn NewU64Metric(name: &str, sync: bool, description: &str) -> Arc<U64Metric>
         .RegisterU64Metric(name.to_string(), sync, description.to_string());
 }
 
+pub fn NewHistogramMetric(name: &str, sync: bool, description: &str) -> Arc<HistogramMetric> {
+    return ALL_METRICS.lock().RegisterHistogramMetric(
+        name.to_string(),
+        sync,
+        description.to_string(),
+    );
+}
+
 pub trait Metric: Send + Sync {
     fn Value(&self) -> u64;
+
+    // Buckets is the distribution of a histogram metric, empty for the others
+    fn Buckets(&self) -> Vec<u64> {
+        return Vec::new();
+    }
 }
 
 pub struct U64Metric {
@@ -64,6 +78,62 @@ impl U64Metric {
     }
 }
 
+// HISTOGRAM_BUCKETS is the bucket count of a HistogramMetric. The bucket i
+// counts the values in [2^(i-1), 2^i), the bucket 0 counts the zeros and the
+// last bucket also counts all the larger values.
+pub const HISTOGRAM_BUCKETS: usize = 32;
+
+pub struct HistogramMetric {
+    count: AtomicU64,
+    sum: AtomicU64,
+    buckets: [AtomicU64; HISTOGRAM_BUCKETS],
+}
+
+impl Metric for HistogramMetric {
+    fn Value(&self) -> u64 {
+        return self.count.load(Ordering::Relaxed);
+    }
+
+    fn Buckets(&self) -> Vec<u64> {
+        return self
+            .buckets
+            .iter()
+            .map(|b| b.load(Ordering::Relaxed))
+            .collect();
+    }
+}
+
+impl HistogramMetric {
+    pub fn New() -> Self {
+        return Self {
+            count: AtomicU64::new(0),
+            sum: AtomicU64::new(0),
+            buckets: Default::default(),
+        };
+    }
+
+    pub fn Record(&self, v: u64) {
+        let idx = (64 - v.leading_zeros() as usize).min(HISTOGRAM_BUCKETS - 1);
+        self.buckets[idx].fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(v, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn Sum(&self) -> u64 {
+        return self.sum.load(Ordering::Relaxed);
+    }
+}
+
+// MetricValue is the exported value of a metric
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MetricValue {
+    pub Name: String,
+    pub Description: String,
+    pub Value: u64,
+    // Buckets is the distribution of a histogram, see HISTOGRAM_BUCKETS
+    pub Buckets: Vec<u64>,
+}
+
 pub struct MetricData {
     pub description: String,
     pub sync: bool,
@@ -99,4 +169,39 @@ impl MetricSet {
         self.m.insert(name, data);
         return metric;
     }
+
+    pub fn RegisterHistogramMetric(
+        &mut self,
+        name: String,
+        sync: bool,
+        description: String,
+    ) -> Arc<HistogramMetric> {
+        if self.m.contains_key(&name) {
+            panic!("Unable to create metric: {}", name);
+        }
+
+        let metric = Arc::new(HistogramMetric::New());
+        let data = MetricData {
+            description: description,
+            sync: sync,
+            metric: metric.clone(),
+        };
+
+        self.m.insert(name, data);
+        return metric;
+    }
+
+    // Export returns the values of the metrics sorted by name
+    pub fn Export(&self) -> Vec<MetricValue> {
+        return self
+            .m
+            .iter()
+            .map(|(name, data)| MetricValue {
+                Name: name.clone(),
+                Description: data.description.clone(),
+                Value: data.metric.Value(),
+                Buckets: data.metric.Buckets(),
+            })
+            .collect();
+    }
 }
@@ -27,6 +27,7 @@ pub mod range;
 //pub mod Process;
 pub mod auth;
 pub mod bytestream;
+pub mod cgroup;
 pub mod config;
 pub mod control_msg;
 pub mod cpuid;
@@ -41,6 +42,8 @@ pub mod lrc_cache;
 pub mod mem;
 pub mod metric;
 pub mod mutex;
+pub mod numa;
+pub mod nvproxy;
 pub mod object_ref;
 pub mod path;
 pub mod perf_tunning;
@@ -71,6 +74,7 @@ use core::sync::atomic::AtomicU64;
 use core::sync::atomic::Ordering;
 
 use self::bytestream::*;
+use self::cgroup::*;
 use self::config::*;
 use self::control_msg::SignalArgs;
 use self::kernel::kernel::futex::*;
@@ -80,6 +84,7 @@ use self::kernel::kernel::timer::timer_store::*;
 use self::kernel::memmgr::pma::*;
 use self::kernel::quring::uring_mgr::QUring;
 use self::linux_def::*;
+use self::numa::*;
 use self::object_ref::ObjectRef;
 use self::qmsg::*;
 use self::ringbuf::*;
@@ -681,7 +686,10 @@ pub struct ShareSpace {
     pub values: Vec<[AtomicU64; 2]>,
     pub tlbShootdownLock: QMutex<()>,
     pub tlbShootdownMask: AtomicU64,
-
+    pub numa: QMutex<NumaTopology>,
+    pub cgroup: QMutex<CgroupLimits>,
+    // kernelSeed is the boot entropy given by qvisor, 0 if there is none
+    pub kernelSeed: AtomicU64,
 }
 
 impl ShareSpace {
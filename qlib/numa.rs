@@ -0,0 +1,122 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+// the node distances reported to the guest, they are the linux defaults
+pub const LOCAL_DISTANCE: usize = 10;
+pub const REMOTE_DISTANCE: usize = 20;
+
+// NumaTopology is the virtual numa topology of the guest. The vcpus running on
+// the same host node are in the same guest node, the guest nodes are numbered
+// from 0 in the order of the host nodes.
+#[derive(Debug, Default, Clone)]
+pub struct NumaTopology {
+    // vcpuNode[i] is the guest node of the vcpu i. It is empty when the numa
+    // placement is disabled, all the vcpus are in node 0 then.
+    pub vcpuNode: Vec<usize>,
+}
+
+impl NumaTopology {
+    pub fn NodeCnt(&self) -> usize {
+        return match self.vcpuNode.iter().max() {
+            None => 1,
+            Some(max) => max + 1,
+        };
+    }
+
+    pub fn NodeOf(&self, vcpuId: usize) -> usize {
+        return match self.vcpuNode.get(vcpuId) {
+            None => 0,
+            Some(node) => *node,
+        };
+    }
+
+    // Cpus returns the cpus below cpuCnt in the node
+    pub fn Cpus(&self, node: usize, cpuCnt: usize) -> Vec<usize> {
+        return (0..cpuCnt)
+            .filter(|cpu| self.NodeOf(*cpu) == node)
+            .collect();
+    }
+}
+
+// CpuList formats the sorted cpus in the linux cpulist format, e.g. "0-3,8"
+pub fn CpuList(cpus: &[usize]) -> String {
+    let mut ret = String::new();
+    let mut i = 0;
+    while i < cpus.len() {
+        let start = cpus[i];
+        while i + 1 < cpus.len() && cpus[i + 1] == cpus[i] + 1 {
+            i += 1;
+        }
+
+        if ret.len() > 0 {
+            ret.push(',');
+        }
+
+        if start == cpus[i] {
+            ret += &format!("{}", start);
+        } else {
+            ret += &format!("{}-{}", start, cpus[i]);
+        }
+        i += 1;
+    }
+
+    return ret;
+}
+
+// ParseCpuList parses the linux cpulist format, the invalid parts are skipped
+pub fn ParseCpuList(s: &str) -> Vec<usize> {
+    let mut ret = Vec::new();
+    for part in s.trim().split(',') {
+        let mut range = part.splitn(2, '-');
+        let start = match range.next().map(|v| v.trim().parse::<usize>()) {
+            Some(Ok(v)) => v,
+            _ => continue,
+        };
+        let end = match range.next() {
+            None => start,
+            Some(v) => match v.trim().parse::<usize>() {
+                Ok(v) => v,
+                Err(_) => continue,
+            },
+        };
+
+        for cpu in start..end + 1 {
+            ret.push(cpu);
+        }
+    }
+
+    return ret;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_CpuList() {
+        assert_eq!(CpuList(&[0, 1, 2, 3, 8]), "0-3,8");
+        assert_eq!(CpuList(&[1, 3, 5]), "1,3,5");
+        assert_eq!(CpuList(&[]), "");
+    }
+
+    #[test]
+    fn test_ParseCpuList() {
+        assert_eq!(ParseCpuList("0-3,8\n"), vec![0, 1, 2, 3, 8]);
+        assert_eq!(ParseCpuList("5"), vec![5]);
+        assert_eq!(ParseCpuList(""), Vec::<usize>::new());
+    }
+}
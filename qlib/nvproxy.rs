@@ -0,0 +1,225 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// The nvidia driver ioctls the guest can pass through to the host gpu. Both
+// qkernel and the qcall handler in qvisor check a request against this list,
+// an ioctl which is not here is rejected with EINVAL.
+//
+// The device memory of /dev/nvidiactl and /dev/nvidiaN is mapped the way the
+// driver does: RM_MAP_MEMORY sets up the file range of the next mmap of the
+// fd it names and the guest mmap must map exactly that range. The mmap of
+// /dev/nvidia-uvm still fails with ENODEV, it maps the guest anonymous memory
+// into the gpu address space, which can't be done by a host fd mapping.
+
+pub const NV_IOCTL_MAGIC: u8 = b'F';
+
+// frontend ioctls of /dev/nvidiactl and /dev/nvidiaN, the nr of the request
+pub const NV_ESC_RM_FREE: u32 = 0x29;
+pub const NV_ESC_RM_CONTROL: u32 = 0x2a;
+pub const NV_ESC_RM_ALLOC: u32 = 0x2b;
+pub const NV_ESC_RM_DUP_OBJECT: u32 = 0x34;
+pub const NV_ESC_RM_SHARE: u32 = 0x35;
+pub const NV_ESC_RM_MAP_MEMORY: u32 = 0x4e;
+pub const NV_ESC_RM_UNMAP_MEMORY: u32 = 0x4f;
+pub const NV_ESC_RM_UPDATE_DEVICE_MAPPING_INFO: u32 = 0x5e;
+pub const NV_ESC_CARD_INFO: u32 = 200;
+pub const NV_ESC_REGISTER_FD: u32 = 201;
+pub const NV_ESC_ALLOC_OS_EVENT: u32 = 206;
+pub const NV_ESC_FREE_OS_EVENT: u32 = 207;
+pub const NV_ESC_CHECK_VERSION_STR: u32 = 210;
+pub const NV_ESC_ATTACH_GPUS_TO_FD: u32 = 212;
+pub const NV_ESC_SYS_PARAMS: u32 = 214;
+pub const NV_ESC_NUMA_INFO: u32 = 215;
+
+pub const NV_FRONTEND_IOCTLS: &[u32] = &[
+    NV_ESC_RM_FREE,
+    NV_ESC_RM_CONTROL,
+    NV_ESC_RM_ALLOC,
+    NV_ESC_RM_DUP_OBJECT,
+    NV_ESC_RM_SHARE,
+    NV_ESC_RM_MAP_MEMORY,
+    NV_ESC_RM_UNMAP_MEMORY,
+    NV_ESC_RM_UPDATE_DEVICE_MAPPING_INFO,
+    NV_ESC_CARD_INFO,
+    NV_ESC_REGISTER_FD,
+    NV_ESC_ALLOC_OS_EVENT,
+    NV_ESC_FREE_OS_EVENT,
+    NV_ESC_CHECK_VERSION_STR,
+    NV_ESC_ATTACH_GPUS_TO_FD,
+    NV_ESC_SYS_PARAMS,
+    NV_ESC_NUMA_INFO,
+];
+
+// ioctls of /dev/nvidia-uvm, the request is the plain number
+pub const UVM_INITIALIZE: u64 = 0x30000001;
+pub const UVM_DEINITIALIZE: u64 = 0x30000002;
+pub const UVM_CREATE_RANGE_GROUP: u64 = 23;
+pub const UVM_DESTROY_RANGE_GROUP: u64 = 24;
+pub const UVM_REGISTER_GPU_VASPACE: u64 = 25;
+pub const UVM_UNREGISTER_GPU_VASPACE: u64 = 26;
+pub const UVM_FREE: u64 = 34;
+pub const UVM_REGISTER_GPU: u64 = 37;
+pub const UVM_UNREGISTER_GPU: u64 = 38;
+pub const UVM_PAGEABLE_MEM_ACCESS: u64 = 39;
+pub const UVM_CREATE_EXTERNAL_RANGE: u64 = 73;
+pub const UVM_MM_INITIALIZE: u64 = 75;
+
+// (request, size of the argument)
+pub const UVM_IOCTLS: &[(u64, usize)] = &[
+    (UVM_INITIALIZE, 16),
+    (UVM_DEINITIALIZE, 0),
+    (UVM_CREATE_RANGE_GROUP, 16),
+    (UVM_DESTROY_RANGE_GROUP, 16),
+    (UVM_REGISTER_GPU_VASPACE, 32),
+    (UVM_UNREGISTER_GPU_VASPACE, 20),
+    (UVM_FREE, 24),
+    (UVM_REGISTER_GPU, 40),
+    (UVM_UNREGISTER_GPU, 20),
+    (UVM_PAGEABLE_MEM_ACCESS, 8),
+    (UVM_CREATE_EXTERNAL_RANGE, 24),
+    (UVM_MM_INITIALIZE, 8),
+];
+
+// the nested parameters of a control or an alloc are copied with the size the
+// caller gives, this is the upper bound of it
+pub const NV_MAX_PARAMS_SIZE: usize = 1 << 20;
+
+const IOC_NRMASK: u64 = 0xff;
+const IOC_TYPESHIFT: u64 = 8;
+const IOC_SIZESHIFT: u64 = 16;
+const IOC_SIZEMASK: u64 = 0x3fff;
+
+// IsFrontendDevice returns whether the path is /dev/nvidiactl or a gpu, the
+// devices whose memory is mapped by RM_MAP_MEMORY
+pub fn IsFrontendDevice(path: &str) -> bool {
+    if path == "/dev/nvidiactl" {
+        return true;
+    }
+
+    match path.strip_prefix("/dev/nvidia") {
+        Some(n) => return n.len() > 0 && n.bytes().all(|c| c.is_ascii_digit()),
+        None => return false,
+    }
+}
+
+pub fn IsFrontendIoctl(request: u64) -> bool {
+    return ((request >> IOC_TYPESHIFT) & 0xff) as u8 == NV_IOCTL_MAGIC;
+}
+
+pub fn FrontendNr(request: u64) -> u32 {
+    return (request & IOC_NRMASK) as u32;
+}
+
+// IoctlArgSize returns the size of the argument of an allowed ioctl
+pub fn IoctlArgSize(request: u64) -> Option<usize> {
+    if IsFrontendIoctl(request) {
+        if !NV_FRONTEND_IOCTLS.contains(&FrontendNr(request)) {
+            return None;
+        }
+
+        return Some(((request >> IOC_SIZESHIFT) & IOC_SIZEMASK) as usize);
+    }
+
+    for (cmd, size) in UVM_IOCTLS {
+        if *cmd == request {
+            return Some(*size);
+        }
+    }
+
+    return None;
+}
+
+// IoctlFdOffset returns the offset of the fd field in the argument of the
+// ioctls which take a nvidia fd. The guest fd is replaced with the host fd
+// by qkernel and the qcall handler checks it is a fd of the sandbox.
+pub fn IoctlFdOffset(request: u64) -> Option<usize> {
+    if IsFrontendIoctl(request) {
+        match FrontendNr(request) {
+            NV_ESC_REGISTER_FD => return Some(0),
+            NV_ESC_ALLOC_OS_EVENT | NV_ESC_FREE_OS_EVENT => return Some(8),
+            NV_ESC_RM_MAP_MEMORY => return Some(48),
+            _ => return None,
+        }
+    }
+
+    match request {
+        UVM_REGISTER_GPU => return Some(24),
+        UVM_REGISTER_GPU_VASPACE => return Some(16),
+        UVM_MM_INITIALIZE => return Some(0),
+        _ => return None,
+    }
+}
+
+// NVOS54_PARAMETERS, the argument of NV_ESC_RM_CONTROL
+#[repr(C)]
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Nvos54Parameters {
+    pub HClient: u32,
+    pub HObject: u32,
+    pub Cmd: u32,
+    pub Flags: u32,
+    pub Params: u64,
+    pub ParamsSize: u32,
+    pub Status: u32,
+}
+
+// NVOS21_PARAMETERS, the argument of NV_ESC_RM_ALLOC
+#[repr(C)]
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Nvos21Parameters {
+    pub HRoot: u32,
+    pub HObjectParent: u32,
+    pub HObjectNew: u32,
+    pub HClass: u32,
+    pub PAllocParms: u64,
+    pub ParamsSize: u32,
+    pub Status: u32,
+}
+
+// NVOS64_PARAMETERS, the argument of NV_ESC_RM_ALLOC with access rights
+#[repr(C)]
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Nvos64Parameters {
+    pub HRoot: u32,
+    pub HObjectParent: u32,
+    pub HObjectNew: u32,
+    pub HClass: u32,
+    pub PAllocParms: u64,
+    pub PRightsRequested: u64,
+    pub ParamsSize: u32,
+    pub Flags: u32,
+    pub Status: u32,
+    pub Pad0: u32,
+}
+
+// NVOS33_PARAMETERS_WITH_FD, the argument of NV_ESC_RM_MAP_MEMORY. The
+// driver sets up the mmap of Fd at PLinearAddress for Length bytes.
+#[repr(C)]
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Nvos33ParametersWithFd {
+    pub HClient: u32,
+    pub HDevice: u32,
+    pub HMemory: u32,
+    pub Pad0: u32,
+    pub Offset: u64,
+    pub Length: u64,
+    pub PLinearAddress: u64,
+    pub Status: u32,
+    pub Flags: u32,
+    pub Fd: i32,
+    pub Pad1: u32,
+}
+
+// RS_ACCESS_MASK, pointed by PRightsRequested
+pub const RS_ACCESS_MASK_SIZE: usize = 4;
@@ -29,6 +29,7 @@ use super::addr::*;
 use super::common::{Allocator, Error, Result};
 use super::linux_def::*;
 use super::mem::stackvec::*;
+use super::mutex::*;
 
 #[derive(Default)]
 pub struct PageTables {
@@ -134,31 +135,29 @@ impl PageTables {
 
     // Copy the range and make the range readonly for from and to pagetable. It is used for VirtualArea private area.
     // The Copy On Write will be done when write to the page
-    pub fn ForkRange(&self, to: &Self, start: u64, len: u64, pagePool: &Allocator) -> Result<()> {
+    pub fn ForkRange(
+        &self,
+        to: &Self,
+        start: u64,
+        len: u64,
+        pkey: i32,
+        pagePool: &Allocator,
+    ) -> Result<()> {
         if start & MemoryDef::PAGE_MASK != 0 || len & MemoryDef::PAGE_MASK != 0 {
             return Err(Error::UnallignedAddress);
         }
 
         //change to read only
         //todo: there is chance the orignal range is changed to readonly by mprotected before. Need to handle.
-        let _ = self.MProtect(
-            Addr(start),
-            Addr(start + len),
-            PageOpts::UserReadOnly().Val(),
-            false,
-        ); //there won't be any failure
+        let flags = PageOpts::UserReadOnly().SetPkey(pkey).Val();
+        let _ = self.MProtect(Addr(start), Addr(start + len), flags, false); //there won't be any failure
 
         let mut vAddr = start;
         while vAddr < start + len {
             match self.VirtualToEntry(vAddr) {
                 Ok(entry) => {
                     let phyAddr = entry.addr().as_u64();
-                    to.MapPage(
-                        Addr(vAddr),
-                        Addr(phyAddr),
-                        PageOpts::UserReadOnly().Val(),
-                        pagePool,
-                    )?;
+                    to.MapPage(Addr(vAddr), Addr(phyAddr), flags, pagePool)?;
                 }
                 Err(_) => (),
             }
@@ -925,6 +924,50 @@ impl PageTables {
         );
     }
 
+    // CollectDirty visits the mapped pages in [start, end) and clears their dirty bits,
+    // f is called with the virtual address, the physical address and whether the
+    // page has been written since the last CollectDirty. The caller must shoot down
+    // the tlb of the other vcpus before it reads the pages.
+    pub fn CollectDirty(
+        &self,
+        start: Addr,
+        end: Addr,
+        mut f: impl FnMut(u64, u64, bool),
+    ) -> Result<()> {
+        self.EnableTlbShootdown();
+        return self.Traverse(
+            start,
+            end,
+            |entry, virtualAddr| {
+                let flags = entry.flags();
+                let dirty = flags.contains(PageTableFlags::DIRTY);
+                if dirty {
+                    entry.set_flags(flags - PageTableFlags::DIRTY);
+                    Invlpg(virtualAddr);
+                }
+                f(virtualAddr, entry.addr().as_u64(), dirty);
+            },
+            false,
+        );
+    }
+
+    // MarkDirty sets the dirty bits of the mapped pages in [start, end). The kernel
+    // and the host write the user pages through their physical addresses, which
+    // doesn't set the dirty bits of the user mapping.
+    pub fn MarkDirty(&self, start: Addr, end: Addr) -> Result<()> {
+        return self.Traverse(
+            start,
+            end,
+            |entry, _virtualAddr| {
+                let flags = entry.flags();
+                if !flags.contains(PageTableFlags::DIRTY) {
+                    entry.set_flags(flags | PageTableFlags::DIRTY);
+                }
+            },
+            false,
+        );
+    }
+
     //get the list for page phyaddress for a virtual address range
     pub fn GetAddresses(&self, start: Addr, end: Addr, vec: &mut StackVec<u64>) -> Result<()> {
         self.Traverse(
@@ -1072,6 +1115,91 @@ impl PageTables {
 
         return Ok(res);
     }
+
+    // MapKernelStacks maps the kernel stack area [start, end) with the 4KB
+    // pages in place of the huge kernel pages, the page at guardOffset of each
+    // stack is left unmapped as its guard page. It has to run before any page
+    // table copies the kernel entries.
+    pub fn MapKernelStacks(
+        &self,
+        start: u64,
+        end: u64,
+        stackSize: u64,
+        guardOffset: u64,
+        pagePool: &Allocator,
+    ) -> Result<()> {
+        assert!(start & (MemoryDef::HUGE_PAGE_SIZE - 1) == 0);
+        assert!(end & (MemoryDef::HUGE_PAGE_SIZE - 1) == 0);
+
+        let pt: *mut PageTable = self.GetRoot() as *mut PageTable;
+        let mut addr = start;
+        unsafe {
+            while addr < end {
+                let vaddr = VirtAddr::new(addr);
+                let pgdEntry = &(*pt)[vaddr.p4_index()];
+                if pgdEntry.is_unused() {
+                    return Err(Error::AddressNotMap(addr));
+                }
+
+                let pudTbl = pgdEntry.addr().as_u64() as *mut PageTable;
+                let pudEntry = &mut (*pudTbl)[vaddr.p3_index()];
+                if pudEntry.is_unused() {
+                    return Err(Error::AddressNotMap(addr));
+                }
+
+                if pudEntry.flags().contains(PageTableFlags::HUGE_PAGE) {
+                    Self::SplitHugePage(pudEntry, MemoryDef::HUGE_PAGE_SIZE, pagePool)?;
+                }
+
+                let pmdTbl = pudEntry.addr().as_u64() as *mut PageTable;
+                let pmdEntry = &mut (*pmdTbl)[vaddr.p2_index()];
+                if pmdEntry.flags().contains(PageTableFlags::HUGE_PAGE) {
+                    Self::SplitHugePage(pmdEntry, MemoryDef::PAGE_SIZE, pagePool)?;
+                }
+
+                let pteTbl = pmdEntry.addr().as_u64() as *mut PageTable;
+                let mut pageAddr = addr;
+                while pageAddr < addr + MemoryDef::HUGE_PAGE_SIZE {
+                    if (pageAddr - start) % stackSize == guardOffset {
+                        (*pteTbl)[VirtAddr::new(pageAddr).p1_index()].set_unused();
+                        Invlpg(pageAddr);
+                    }
+
+                    pageAddr += MemoryDef::PAGE_SIZE;
+                }
+
+                addr += MemoryDef::HUGE_PAGE_SIZE;
+            }
+        }
+
+        return Ok(());
+    }
+
+    // SplitHugePage replaces the huge page entry with a table of the pages of
+    // pageSize which map the same memory.
+    unsafe fn SplitHugePage(
+        entry: &mut PageTableEntry,
+        pageSize: u64,
+        pagePool: &Allocator,
+    ) -> Result<()> {
+        let phyAddr = entry.addr().as_u64();
+        let mut flags = entry.flags();
+        if pageSize == MemoryDef::PAGE_SIZE {
+            // the bit 7 of a 4KB page entry is PAT
+            flags.remove(PageTableFlags::HUGE_PAGE);
+        }
+
+        let tbl = pagePool.AllocPage(true)? as *mut PageTable;
+        for i in 0..MemoryDef::ENTRY_COUNT as usize {
+            (*tbl)[i].set_addr(PhysAddr::new(phyAddr + i as u64 * pageSize), flags);
+        }
+
+        entry.set_addr(
+            PhysAddr::new(tbl as u64),
+            PageTableFlags::PRESENT | PageTableFlags::WRITABLE,
+        );
+        return Ok(());
+    }
 }
 
 pub struct PageBufAllocator {
@@ -1143,6 +1271,69 @@ impl AlignedAllocator {
     }
 }
 
+#[derive(Default)]
+pub struct KernelStackArea {
+    pub start: u64,
+    pub end: u64,
+    pub next: u64,
+    pub free: Vec<u64>,
+}
+
+// KernelStackAllocator allocates the kernel stacks. The stacks in the stack
+// area have an unmapped guard page above the Task at their bottom, the stacks
+// from the heap are used once the area is used up.
+pub struct KernelStackAllocator {
+    pub heap: AlignedAllocator,
+    pub area: QMutex<KernelStackArea>,
+}
+
+impl KernelStackAllocator {
+    pub fn New(size: usize) -> Self {
+        return Self {
+            heap: AlignedAllocator::New(size, size),
+            area: QMutex::new(KernelStackArea::default()),
+        };
+    }
+
+    // InitArea sets the stack area [start, end) whose guard pages are
+    // unmapped by PageTables::MapKernelStacks.
+    pub fn InitArea(&self, start: u64, end: u64) {
+        let mut area = self.area.lock();
+        area.start = start;
+        area.end = end;
+        area.next = start;
+    }
+
+    pub fn Allocate(&self) -> Result<u64> {
+        {
+            let mut area = self.area.lock();
+            if let Some(stack) = area.free.pop() {
+                return Ok(stack);
+            }
+
+            if area.next < area.end {
+                let stack = area.next;
+                area.next += self.heap.size as u64;
+                return Ok(stack);
+            }
+        }
+
+        return self.heap.Allocate();
+    }
+
+    pub fn Free(&self, addr: u64) -> Result<()> {
+        {
+            let mut area = self.area.lock();
+            if area.start <= addr && addr < area.end {
+                area.free.push(addr);
+                return Ok(());
+            }
+        }
+
+        return self.heap.Free(addr);
+    }
+}
+
 #[cfg(test1)]
 mod tests {
     use super::super::buddyallocator::*;
@@ -1257,7 +1448,7 @@ mod tests {
 
         let mut nPt = PageTables::New(&Allocator).unwrap();
 
-        pt.ForkRange(&mut nPt, 4096 * 500, 4096 * 100, &Allocator)
+        pt.ForkRange(&mut nPt, 4096 * 500, 4096 * 100, 0, &Allocator)
             .unwrap();
         //pt.MProtect(Addr(4096 * 500), Addr(4096 * 600), PageOpts::UserReadOnly().Val(), true).unwrap();
         for i in 500..600 {
@@ -39,6 +39,7 @@ pub enum Msg {
     ReadLinkAt(ReadLinkAt),
     GetTimeOfDay(GetTimeOfDay),
     IoCtl(IoCtl),
+    NvProxyIoctl(NvProxyIoctl),
     Fcntl(Fcntl),
     Close(Close),
 
@@ -55,6 +56,10 @@ pub enum Msg {
     Mkdirat(Mkdirat),
     SysSync(SysSync),
     SyncFs(SyncFs),
+    InotifyInit(InotifyInit),
+    InotifyAddWatch(InotifyAddWatch),
+    InotifyRmWatch(InotifyRmWatch),
+    ChannelOpen(ChannelOpen),
     SyncFileRange(SyncFileRange),
     FSync(FSync),
     MSync(MSync),
@@ -76,6 +81,7 @@ pub enum Msg {
 
     SchedGetAffinity(SchedGetAffinity),
     GetRandom(GetRandom),
+    HostEntropy(HostEntropy),
     Fchdir(Fchdir),
     Fadvise(Fadvise),
     Mlock2(Mlock2),
@@ -97,6 +103,8 @@ pub enum Msg {
     IOConnect(IOConnect),
     IORecvMsg(IORecvMsg),
     IOSendMsg(IOSendMsg),
+    IORecvMMsg(IORecvMMsg),
+    IOSendMMsg(IOSendMMsg),
     MMapFile(MMapFile),
     MUnmap(MUnmap),
     NonBlockingPoll(NonBlockingPoll),
@@ -114,6 +122,8 @@ pub enum Msg {
     TlbShootdown(TlbShootdown),
     Sysinfo(Sysinfo),
     ReadDir(ReadDir),
+    AuditLog(AuditLog),
+    BalloonRelease(BalloonRelease),
 }
 
 #[derive(Clone, Default, Debug)]
@@ -121,6 +131,19 @@ pub struct TlbShootdown {
     pub vcpuMask: u64,
 }
 
+#[derive(Clone, Default, Debug)]
+pub struct BalloonRelease {
+    // addr is the address of the array of the huge pages taken by the balloon
+    pub addr: u64,
+    pub count: usize,
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct AuditLog {
+    pub addr: u64,
+    pub len: usize,
+}
+
 #[derive(Clone, Default, Debug)]
 pub struct Sysinfo {
     pub addr: u64,
@@ -215,6 +238,7 @@ pub struct RenameAt {
     pub oldpath: u64,
     pub newdirfd: i32,
     pub newpath: u64,
+    pub flags: u32,
 }
 
 #[derive(Clone, Default, Debug)]
@@ -237,6 +261,13 @@ pub struct IoCtl {
     pub argp: u64,
 }
 
+#[derive(Clone, Default, Debug)]
+pub struct NvProxyIoctl {
+    pub fd: i32,
+    pub cmd: u64,
+    pub argp: u64,
+}
+
 #[derive(Clone, Default, Debug)]
 pub struct GetTimeOfDay {
     pub tv: u64,
@@ -350,6 +381,42 @@ pub struct SyncFs {
     pub fd: i32,
 }
 
+#[derive(Clone, Default, Debug)]
+pub struct InotifyInit {}
+
+#[derive(Clone, Default, Debug)]
+pub struct InotifyAddWatch {
+    pub fd: i32,
+    pub dirfd: i32,
+    pub mask: u32,
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct InotifyRmWatch {
+    pub fd: i32,
+    pub wd: i32,
+}
+
+// SHM_CHANNEL_SIZE is the size of the share memory file of a channel
+pub const SHM_CHANNEL_SIZE: u64 = MemoryDef::PMD_SIZE;
+
+// ChannelOpen opens the share memory file and the doorbell fifos of the two
+// sides of the channel name, fds is an array of 3 host fds
+#[derive(Clone, Default, Debug)]
+pub struct ChannelOpen {
+    pub name: u64,
+    pub fds: u64,
+}
+
+// the channel name is a file name on the host, it is checked on both sides
+pub fn ValidChannelName(name: &str) -> bool {
+    return name.len() > 0
+        && name.len() < 32
+        && name
+            .bytes()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == b'-' || c == b'_');
+}
+
 #[derive(Clone, Default, Debug)]
 pub struct SyncFileRange {
     pub fd: i32,
@@ -531,6 +598,12 @@ pub struct GetRandom {
     pub flags: u32,
 }
 
+#[derive(Clone, Default, Debug)]
+pub struct HostEntropy {
+    // bits is the entropy the guest pool asks for
+    pub bits: i32,
+}
+
 #[derive(Clone, Default, Debug)]
 pub struct Fchdir {
     pub fd: i32,
@@ -711,6 +784,24 @@ pub struct IOSendMsg {
     pub blocking: bool,
 }
 
+#[derive(Clone, Default, Debug)]
+pub struct IORecvMMsg {
+    pub fd: i32,
+    pub msgvec: u64,
+    //address of [MMsgHdr]
+    pub vlen: u32,
+    pub flags: i32,
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct IOSendMMsg {
+    pub fd: i32,
+    pub msgvec: u64,
+    //address of [MMsgHdr]
+    pub vlen: u32,
+    pub flags: i32,
+}
+
 #[derive(Clone, Default, Debug)]
 pub struct NewSocket {
     pub fd: i32,
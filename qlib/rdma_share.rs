@@ -142,6 +142,7 @@ pub enum RDMARespMsg {
     RDMAAccept(RDMAAcceptResp),
     RDMANotify(RDMANotifyResp),
     RDMAFinNotify(RDMAFinNotifyResp),
+    RDMAConnectError(RDMAConnectErrorResp),
 }
 
 impl Default for RDMARespMsg {
@@ -157,6 +158,14 @@ pub struct RDMAConnectResp {
     pub channelId: u32,
 }
 
+// RDMAConnectErrorResp tells the client that the connect of sockfd failed,
+// errno is ECONNREFUSED or ETIMEDOUT.
+#[derive(Default, Clone, Copy, Debug)]
+pub struct RDMAConnectErrorResp {
+    pub sockfd: u32,
+    pub errno: i32,
+}
+
 #[derive(Default, Clone, Copy, Debug)]
 pub struct RDMAAcceptResp {
     pub sockfd: u32,
@@ -243,6 +243,14 @@ pub struct AcceptQueueIntern {
     pub queueLen: usize,
     pub error: i32,
     pub total: u64,
+    // connections accepted on the host which are not ready to be queued yet
+    pub pending: usize,
+    // the producer stopped accepting because the queue was full
+    pub stopped: bool,
+    // reset connections which overflow the queue instead of leaving them in
+    // the host backlog, as net.ipv4.tcp_abort_on_overflow
+    pub abortOnOverflow: bool,
+    pub overflows: u64,
 }
 
 impl AcceptQueueIntern {
@@ -254,15 +262,78 @@ impl AcceptQueueIntern {
         return self.error;
     }
 
-    pub fn SetQueueLen(&mut self, len: usize) {
+    //return: whether the stopped producer needs to be restarted
+    pub fn SetQueueLen(&mut self, len: usize, abortOnOverflow: bool) -> bool {
         self.queueLen = len;
+        self.abortOnOverflow = abortOnOverflow;
+        return self.Restart();
+    }
+
+    fn Restart(&mut self) -> bool {
+        if self.stopped && (self.HasSpace() || self.abortOnOverflow) {
+            self.stopped = false;
+            return true;
+        }
+
+        return false;
     }
 
     pub fn HasSpace(&self) -> bool {
-        return self.queue.len() < self.queueLen;
+        return self.Len() < self.queueLen;
+    }
+
+    // Len is the accept queue depth including the pending connections.
+    pub fn Len(&self) -> usize {
+        return self.queue.len() + self.pending;
+    }
+
+    // Overflow is called for a new connection before it is queued.
+    // return: whether the connection has to be reset
+    pub fn Overflow(&mut self) -> bool {
+        if self.HasSpace() {
+            return false;
+        }
+
+        self.overflows += 1;
+        return true;
+    }
+
+    // Reserve counts a connection which will be queued by EnqPending later.
+    //return: whether the producer can keep accepting
+    pub fn Reserve(&mut self) -> bool {
+        self.pending += 1;
+        return self.KeepAccepting();
+    }
+
+    fn KeepAccepting(&mut self) -> bool {
+        let keep = self.HasSpace() || self.abortOnOverflow;
+        self.stopped = !keep;
+        return keep;
+    }
+
+    //return: trigger
+    pub fn EnqPending(
+        &mut self,
+        fd: i32,
+        addr: TcpSockAddr,
+        len: u32,
+        sockBuf: Arc<SocketBuff>,
+    ) -> bool {
+        if self.pending > 0 {
+            self.pending -= 1;
+        }
+
+        self.queue.push_back(AcceptItem {
+            fd: fd,
+            addr: addr,
+            len: len,
+            sockBuf: sockBuf,
+        });
+        self.total += 1;
+        return self.queue.len() == 1;
     }
 
-    //return: (trigger, hasSpace)
+    //return: (trigger, keepAccepting)
     pub fn EnqSocket(
         &mut self,
         fd: i32,
@@ -280,13 +351,15 @@ impl AcceptQueueIntern {
         self.queue.push_back(item);
         self.total += 1;
         let trigger = self.queue.len() == 1;
-        return (trigger, self.queue.len() < self.queueLen);
+        return (trigger, self.KeepAccepting());
     }
 
+    //return: (restart producer, item)
     pub fn DeqSocket(&mut self) -> (bool, Result<AcceptItem>) {
-        let trigger = self.queue.len() == self.queueLen;
+        let item = self.queue.pop_front();
+        let trigger = self.Restart();
 
-        match self.queue.pop_front() {
+        match item {
             None => {
                 if self.error != 0 {
                     return (false, Err(Error::SysError(self.error)));
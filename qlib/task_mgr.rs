@@ -14,7 +14,7 @@
 
 use super::mutex::*;
 use alloc::boxed::Box;
-use alloc::collections::vec_deque::VecDeque;
+use alloc::collections::btree_map::BTreeMap;
 use alloc::string::String;
 use alloc::vec::Vec;
 use cache_padded::CachePadded;
@@ -54,6 +54,26 @@ impl TaskId {
     }
 }
 
+// NICE_0_WEIGHT is the scheduling weight of a nice 0 task
+pub const NICE_0_WEIGHT: u64 = 1024;
+
+// SCHED_PRIO_TO_WEIGHT maps nice -20..19 to the scheduling weight, each nice
+// level is about 10% cpu time, the same table as linux sched_prio_to_weight.
+pub const SCHED_PRIO_TO_WEIGHT: [u64; 40] = [
+    88761, 71755, 56483, 46273, 36291, 29154, 23254, 18705, 14949, 11916, 9548, 7620, 6100, 4904,
+    3906, 3121, 2501, 1991, 1586, 1277, 1024, 820, 655, 526, 423, 335, 272, 215, 172, 137, 110,
+    87, 70, 56, 45, 36, 29, 23, 18, 15,
+];
+
+// SCHED_WAKEUP_LAG is the max vruntime in ns a woken task can be behind the
+// queue, so that a long sleeper can't starve the others after waking up.
+pub const SCHED_WAKEUP_LAG: u64 = 6_000_000;
+
+pub fn NiceToWeight(nice: i32) -> u64 {
+    let idx = core::cmp::min(core::cmp::max(nice + 20, 0), 39);
+    return SCHED_PRIO_TO_WEIGHT[idx as usize];
+}
+
 #[derive(Debug, Default)]
 pub struct Links {
     pub prev: AtomicU64,
@@ -78,6 +98,18 @@ pub struct Context {
     // job queue id
     pub queueId: AtomicUsize,
     pub links: Links,
+
+    // weight is the scheduling weight, vruntime is the cpu time in ns scaled
+    // by NICE_0_WEIGHT/weight, the queue runs the task with the least vruntime
+    pub weight: AtomicU64,
+    pub vruntime: AtomicU64,
+    // runStart is the tsc when the task was switched in
+    pub runStart: AtomicU64,
+    // coreCookie groups the related tasks set by prctl(PR_SCHED_CORE), 0 is
+    // none. The vcpu running the task shows it to qvisor.
+    pub coreCookie: AtomicU64,
+    // tls32 is the set_thread_area tls of a 32 bit task
+    pub tls32: Tls32,
 }
 
 impl Context {
@@ -98,6 +130,12 @@ impl Context {
             X86fpstate: Default::default(),
             queueId: AtomicUsize::new(0),
             links: Links::default(),
+
+            weight: AtomicU64::new(NICE_0_WEIGHT),
+            vruntime: AtomicU64::new(0),
+            runStart: AtomicU64::new(0),
+            coreCookie: AtomicU64::new(0),
+            tls32: Tls32::default(),
         };
     }
 
@@ -108,6 +146,26 @@ impl Context {
     pub fn SetReady(&self, val: u64) {
         return self.ready.store(val, Ordering::SeqCst);
     }
+
+    pub fn Vruntime(&self) -> u64 {
+        return self.vruntime.load(Ordering::Relaxed);
+    }
+
+    pub fn SetWeight(&self, weight: u64) {
+        let weight = if weight == 0 { 1 } else { weight };
+        self.weight.store(weight, Ordering::Relaxed);
+    }
+
+    pub fn CoreCookie(&self) -> u64 {
+        return self.coreCookie.load(Ordering::Relaxed);
+    }
+
+    // Charge adds ns of cpu time to the vruntime.
+    pub fn Charge(&self, ns: u64) {
+        let weight = self.weight.load(Ordering::Relaxed);
+        let delta = ns.saturating_mul(NICE_0_WEIGHT) / weight;
+        self.vruntime.fetch_add(delta, Ordering::Relaxed);
+    }
 }
 
 #[derive(Default)]
@@ -119,6 +177,9 @@ pub struct Scheduler {
     pub taskCnt: AtomicUsize,
     pub readyTaskCnt: AtomicUsize,
     pub haltVcpuCnt: AtomicUsize,
+    // onlineVcpuCnt is the count of the vcpus taking tasks, the vcpus
+    // [onlineVcpuCnt, vcpuCnt) are parked by the vcpu autoscaling of qvisor
+    pub onlineVcpuCnt: AtomicUsize,
 
     pub vcpuWaitMask: AtomicU64,
     pub VcpuArr: Vec<CPULocal>,
@@ -137,10 +198,41 @@ impl Scheduler {
             VcpuArr: vcpuArr,
             queue: queue,
             vcpuCnt: vcpuCount,
+            onlineVcpuCnt: AtomicUsize::new(vcpuCount),
             ..Default::default()
         };
     }
 
+    #[inline(always)]
+    pub fn OnlineVcpuCnt(&self) -> usize {
+        return self.onlineVcpuCnt.load(Ordering::Acquire);
+    }
+
+    #[inline(always)]
+    pub fn IsOnline(&self, vcpuId: usize) -> bool {
+        return vcpuId < self.OnlineVcpuCnt();
+    }
+
+    // SetOnlineVcpuCnt parks the vcpus above cnt or brings the parked ones back
+    // online. A parked vcpu finishes its running task and then waits in the
+    // host, the tasks left in its queue are stolen by the online vcpus.
+    pub fn SetOnlineVcpuCnt(&self, cnt: usize) {
+        assert!(cnt >= 1 && cnt <= self.vcpuCnt);
+        let prev = self.onlineVcpuCnt.swap(cnt, Ordering::SeqCst);
+        for i in prev..cnt {
+            self.VcpuArr[i].Wakeup();
+        }
+    }
+
+    pub fn OnlineMask(&self) -> u64 {
+        let cnt = self.OnlineVcpuCnt();
+        if cnt >= 64 {
+            return !0;
+        }
+
+        return (1 << cnt) - 1;
+    }
+
     pub fn DecreaseHaltVcpuCnt(&self) {
         self.haltVcpuCnt.fetch_sub(1, Ordering::SeqCst);
     }
@@ -180,9 +272,16 @@ impl Scheduler {
     }
 
     pub fn ScheduleQ(&self, task: TaskId, vcpuId: u64) {
+        let vcpuId = if self.IsOnline(vcpuId as usize) {
+            vcpuId
+        } else {
+            // the vcpu is parked, move the task to vcpu 0
+            task.Context().queueId.store(0, Ordering::Release);
+            0
+        };
+
         let _cnt = {
-            let mut queue = self.queue[vcpuId as usize].lock();
-            queue.push_back(task);
+            self.queue[vcpuId as usize].Enqueue(task);
             self.IncReadyTaskCount()
         };
 
@@ -204,7 +303,7 @@ impl Scheduler {
     pub fn AllTasks(&self) -> Vec<TaskId> {
         let mut ret = Vec::new();
         for i in 0..8 {
-            for t in self.queue[i].lock().iter() {
+            for t in self.queue[i].lock().values() {
                 ret.push(*t)
             }
         }
@@ -214,7 +313,7 @@ impl Scheduler {
 
     pub fn WakeOne(&self) -> i64 {
         loop {
-            let mask = self.vcpuWaitMask.load(Ordering::Acquire);
+            let mask = self.vcpuWaitMask.load(Ordering::Acquire) & self.OnlineMask();
 
             let vcpuId = mask.trailing_zeros() as usize;
             if vcpuId >= 64 {
@@ -254,13 +353,25 @@ impl Scheduler {
     }
 }
 
-pub struct TaskQueue(pub QMutex<VecDeque<TaskId>>);
+// TaskQueueKey orders the ready tasks by the vruntime at the enqueue, the
+// sequence keeps the tasks with the same vruntime in the fifo order.
+pub type TaskQueueKey = (u64, u64);
+
+// TaskQueue is the ready queue of a vcpu ordered by the task vruntime, it is
+// a weighted fair queue so a task gets cpu time by its weight. The enqueue
+// and the dequeue are O(log n) under the lock.
+pub struct TaskQueue {
+    pub queue: QMutex<BTreeMap<TaskQueueKey, TaskId>>,
+    // minVruntime is the vruntime of the last task taken from the queue
+    pub minVruntime: AtomicU64,
+    pub seq: AtomicU64,
+}
 
 impl Deref for TaskQueue {
-    type Target = QMutex<VecDeque<TaskId>>;
+    type Target = QMutex<BTreeMap<TaskQueueKey, TaskId>>;
 
-    fn deref(&self) -> &QMutex<VecDeque<TaskId>> {
-        &self.0
+    fn deref(&self) -> &QMutex<BTreeMap<TaskQueueKey, TaskId>> {
+        &self.queue
     }
 }
 
@@ -272,15 +383,48 @@ impl Default for TaskQueue {
 
 impl TaskQueue {
     pub fn New() -> Self {
-        return TaskQueue(QMutex::new(VecDeque::with_capacity(128)));
+        return TaskQueue {
+            queue: QMutex::new(BTreeMap::new()),
+            minVruntime: AtomicU64::new(0),
+            seq: AtomicU64::new(0),
+        };
+    }
+
+    pub fn MinVruntime(&self) -> u64 {
+        return self.minVruntime.load(Ordering::Relaxed);
+    }
+
+    pub fn UpdateMinVruntime(&self, vruntime: u64) {
+        self.minVruntime.fetch_max(vruntime, Ordering::Relaxed);
     }
 
     pub fn Dequeue(&self) -> Option<TaskId> {
-        return self.lock().pop_front();
+        let task = {
+            let mut queue = self.lock();
+            let key = match queue.keys().next() {
+                None => return None,
+                Some(key) => *key,
+            };
+            queue.remove(&key)
+        };
+
+        if let Some(t) = task {
+            self.UpdateMinVruntime(t.Context().Vruntime());
+        }
+
+        return task;
     }
 
+    // Enqueue inserts the task after the tasks with less or equal vruntime.
     pub fn Enqueue(&self, task: TaskId) {
-        self.lock().push_back(task);
+        let ctx = task.Context();
+        let min = self.MinVruntime().saturating_sub(SCHED_WAKEUP_LAG);
+        if ctx.Vruntime() < min {
+            ctx.vruntime.store(min, Ordering::Relaxed);
+        }
+
+        let seq = self.seq.fetch_add(1, Ordering::Relaxed);
+        self.lock().insert((ctx.Vruntime(), seq), task);
     }
 
     pub fn ToString(&self) -> String {
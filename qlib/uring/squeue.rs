@@ -192,4 +192,10 @@ impl Entry {
         self.0.user_data = user_data;
         self
     }
+
+    /// The I/O priority of a read or write request, see `ioprio_set(2)`.
+    pub fn ioprio(mut self, ioprio: u16) -> Entry {
+        self.0.ioprio = ioprio;
+        self
+    }
 }
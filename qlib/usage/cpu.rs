@@ -22,6 +22,26 @@ pub struct CPUStats {
     // VoluntarySwitches is the number of times control has been voluntarily
     // ceded due to blocking, etc.
     pub VoluntarySwitches: u64,
+
+    // InvoluntarySwitches is the number of times control has been taken away
+    // while the task is still runnable, e.g. on time slice end.
+    pub InvoluntarySwitches: u64,
+
+    // MinorFaults is the number of page faults served without host io.
+    pub MinorFaults: u64,
+
+    // MajorFaults is the number of page faults which needed the host to map
+    // the file.
+    pub MajorFaults: u64,
+
+    // RunDelay is the time in ns spent runnable in the ready queue.
+    pub RunDelay: i64,
+
+    // IODelay is the time in ns spent waiting for host io.
+    pub IODelay: i64,
+
+    // Timeslices is the number of times the task has been switched in.
+    pub Timeslices: u64,
 }
 
 impl CPUStats {
@@ -29,5 +49,11 @@ impl CPUStats {
         self.UserTime += s2.UserTime;
         self.SysTime += s2.SysTime;
         self.VoluntarySwitches += s2.VoluntarySwitches;
+        self.InvoluntarySwitches += s2.InvoluntarySwitches;
+        self.MinorFaults += s2.MinorFaults;
+        self.MajorFaults += s2.MajorFaults;
+        self.RunDelay += s2.RunDelay;
+        self.IODelay += s2.IODelay;
+        self.Timeslices += s2.Timeslices;
     }
 }
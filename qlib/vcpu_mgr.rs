@@ -47,10 +47,20 @@ pub struct CPULocal {
     pub epollfd: i32,
     pub allocator: VcpuAllocator,
     pub pageAllocator: Mutex<PageAllocator>,
+    pub stackPool: Mutex<KernelStackPool>,
 
     // it is the time to enter guest ring3. If it is in ring0, the vale will be zero
     pub enterAppTimestamp: AtomicI64,
     pub interruptMask: AtomicU64,
+
+    // coreCookie is the core cookie of the running task, qvisor places the
+    // vcpus running the tasks of a cookie close to each other
+    pub coreCookie: AtomicU64,
+
+    // fpToken is the token of the fp state held in the fp registers, 0 if
+    // the registers don't match a saved state
+    pub fpToken: AtomicU64,
+    pub fpSaveCount: AtomicU64,
 }
 
 impl CPULocal {
@@ -98,6 +108,23 @@ impl CPULocal {
         return searchingCnt;
     }
 
+    // NewFpToken returns a token for the fp state just saved from the
+    // registers, the vcpu id keeps it unique among the vcpus
+    pub fn NewFpToken(&self) -> u64 {
+        let count = self.fpSaveCount.fetch_add(1, Ordering::Relaxed) + 1;
+        let token = ((self.vcpuId as u64 + 1) << 48) | (count & ((1 << 48) - 1));
+        self.fpToken.store(token, Ordering::Relaxed);
+        return token;
+    }
+
+    pub fn FpToken(&self) -> u64 {
+        return self.fpToken.load(Ordering::Relaxed);
+    }
+
+    pub fn SetFpToken(&self, token: u64) {
+        self.fpToken.store(token, Ordering::Relaxed);
+    }
+
     pub fn IncrUringMsgCnt(&self, cnt: u64) -> u64 {
         return self.uringMsgCount.fetch_add(cnt, Ordering::Relaxed);
     }
@@ -13,9 +13,11 @@
 // limitations under the License.
 
 use std::os::unix::io::AsRawFd;
+use std::os::unix::net::UnixListener;
 
 use super::super::qlib::common::*;
 use super::pty::*;
+use super::stream::*;
 use super::unix_socket::*;
 
 pub fn NewWithSocket(socketPath: &str) -> Result<Master> {
@@ -26,3 +28,22 @@ pub fn NewWithSocket(socketPath: &str) -> Result<Master> {
 
     return Ok(master);
 }
+
+// ServeStreams serves the multiplexed stream protocol on socketPath. Clients
+// can attach to and resize the console, when there is one, and forward tcp
+// ports into the network namespace of the sandbox process sandboxPid.
+pub fn ServeStreams(socketPath: &str, console: Option<Master>, sandboxPid: i32) -> Result<()> {
+    std::fs::remove_file(socketPath).ok();
+    let listener = UnixListener::bind(socketPath)
+        .map_err(|e| Error::IOError(format!("ServeStreams bind {} fail {:?}", socketPath, e)))?;
+
+    let consoleFd = console.as_ref().map(|m| m.as_raw_fd());
+    for conn in listener.incoming() {
+        match conn {
+            Err(e) => info!("ServeStreams accept fail {:?}", e),
+            Ok(conn) => ServeConnection(conn, consoleFd, sandboxPid),
+        }
+    }
+
+    return Ok(());
+}
@@ -14,4 +14,5 @@
 
 pub mod console;
 pub mod pty;
+pub mod stream;
 pub mod unix_socket;
@@ -0,0 +1,395 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::collections::btree_map::BTreeMap;
+use alloc::sync::Arc;
+use nix::ioctl_write_ptr_bad;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::sync::Mutex;
+use std::thread;
+
+use super::super::qlib::common::*;
+
+ioctl_write_ptr_bad!(ioctl_set_winsz, libc::TIOCSWINSZ, libc::winsize);
+
+// The stream protocol multiplexes several byte streams over one unix socket
+// connection. Each frame is a FrameHeader followed by len bytes of payload.
+// The client picks the stream ids; stream 0 is conventionally the console.
+pub struct FrameKind {}
+
+impl FrameKind {
+    // client: attach the stream to the console. Console output is sent as
+    // DATA frames and DATA frames from the client are written to the console.
+    pub const ATTACH: u32 = 1;
+    // both: payload bytes of the stream.
+    pub const DATA: u32 = 2;
+    // client: resize the console, the payload is a ResizePayload.
+    pub const RESIZE: u32 = 3;
+    // client: open the stream as a tcp connection to the port in the payload
+    // (u16, little endian) on the loopback of the sandbox network namespace.
+    pub const PORT_FORWARD: u32 = 4;
+    // both: the stream is closed.
+    pub const CLOSE: u32 = 5;
+    // server: the request on the stream failed, the payload is the error text.
+    pub const ERROR: u32 = 6;
+}
+
+pub const FRAME_HEADER_SIZE: usize = 12;
+pub const MAX_FRAME_PAYLOAD: usize = 64 * 1024;
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FrameHeader {
+    pub kind: u32,
+    pub streamId: u32,
+    pub len: u32,
+}
+
+impl FrameHeader {
+    pub fn Encode(&self) -> [u8; FRAME_HEADER_SIZE] {
+        let mut buf = [0; FRAME_HEADER_SIZE];
+        buf[0..4].copy_from_slice(&self.kind.to_le_bytes());
+        buf[4..8].copy_from_slice(&self.streamId.to_le_bytes());
+        buf[8..12].copy_from_slice(&self.len.to_le_bytes());
+        return buf;
+    }
+
+    pub fn Decode(buf: &[u8; FRAME_HEADER_SIZE]) -> Self {
+        let word = |i: usize| u32::from_le_bytes([buf[i], buf[i + 1], buf[i + 2], buf[i + 3]]);
+        return Self {
+            kind: word(0),
+            streamId: word(4),
+            len: word(8),
+        };
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ResizePayload {
+    pub rows: u16,
+    pub cols: u16,
+}
+
+impl ResizePayload {
+    pub fn Decode(buf: &[u8]) -> Result<Self> {
+        if buf.len() < 4 {
+            return Err(Error::Common(format!(
+                "stream: resize payload too short ({} bytes)",
+                buf.len()
+            )));
+        }
+
+        return Ok(Self {
+            rows: u16::from_le_bytes([buf[0], buf[1]]),
+            cols: u16::from_le_bytes([buf[2], buf[3]]),
+        });
+    }
+}
+
+// FrameWriter serializes the frames sent by the pump threads.
+#[derive(Clone)]
+pub struct FrameWriter(Arc<Mutex<UnixStream>>);
+
+impl FrameWriter {
+    pub fn New(conn: UnixStream) -> Self {
+        return Self(Arc::new(Mutex::new(conn)));
+    }
+
+    pub fn Send(&self, kind: u32, streamId: u32, payload: &[u8]) -> Result<()> {
+        let header = FrameHeader {
+            kind: kind,
+            streamId: streamId,
+            len: payload.len() as u32,
+        };
+
+        let mut conn = self.0.lock().unwrap();
+        conn.write_all(&header.Encode())
+            .and_then(|_| conn.write_all(payload))
+            .map_err(|e| Error::IOError(format!("stream: send frame fail {:?}", e)))?;
+        return Ok(());
+    }
+
+    pub fn SendError(&self, streamId: u32, e: &Error) {
+        let msg = format!("{:?}", e);
+        self.Send(FrameKind::ERROR, streamId, msg.as_bytes()).ok();
+    }
+}
+
+pub fn ReadFrame(conn: &mut UnixStream) -> Result<Option<(FrameHeader, Vec<u8>)>> {
+    let mut buf = [0; FRAME_HEADER_SIZE];
+    match conn.read_exact(&mut buf) {
+        Ok(()) => (),
+        Err(ref e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(Error::IOError(format!("stream: read frame fail {:?}", e))),
+    }
+
+    let header = FrameHeader::Decode(&buf);
+    if header.len as usize > MAX_FRAME_PAYLOAD {
+        return Err(Error::Common(format!(
+            "stream: frame payload {} is larger than {}",
+            header.len, MAX_FRAME_PAYLOAD
+        )));
+    }
+
+    let mut payload = vec![0; header.len as usize];
+    conn.read_exact(&mut payload)
+        .map_err(|e| Error::IOError(format!("stream: read frame fail {:?}", e)))?;
+    return Ok(Some((header, payload)));
+}
+
+pub enum StreamTarget {
+    Console(File),
+    Tcp(TcpStream),
+}
+
+impl StreamTarget {
+    pub fn Write(&mut self, buf: &[u8]) -> Result<()> {
+        let res = match self {
+            StreamTarget::Console(f) => f.write_all(buf),
+            StreamTarget::Tcp(s) => s.write_all(buf),
+        };
+
+        return res.map_err(|e| Error::IOError(format!("stream: write fail {:?}", e)));
+    }
+
+    pub fn Close(&mut self) {
+        match self {
+            StreamTarget::Console(_) => (),
+            StreamTarget::Tcp(s) => {
+                s.shutdown(Shutdown::Both).ok();
+            }
+        }
+    }
+}
+
+// StreamServer serves one client connection.
+pub struct StreamServer {
+    pub conn: UnixStream,
+    pub writer: FrameWriter,
+    pub console: Option<RawFd>,
+    // sandboxPid is the pid of the sandbox process whose network namespace
+    // the forwarded ports are connected in.
+    pub sandboxPid: i32,
+    pub streams: BTreeMap<u32, StreamTarget>,
+}
+
+impl StreamServer {
+    pub fn New(conn: UnixStream, console: Option<RawFd>, sandboxPid: i32) -> Result<Self> {
+        let writerConn = conn
+            .try_clone()
+            .map_err(|e| Error::IOError(format!("stream: clone connection fail {:?}", e)))?;
+
+        return Ok(Self {
+            conn: conn,
+            writer: FrameWriter::New(writerConn),
+            console: console,
+            sandboxPid: sandboxPid,
+            streams: BTreeMap::new(),
+        });
+    }
+
+    pub fn Serve(&mut self) -> Result<()> {
+        loop {
+            let (header, payload) = match ReadFrame(&mut self.conn)? {
+                None => break,
+                Some(frame) => frame,
+            };
+
+            let streamId = header.streamId;
+            let res = match header.kind {
+                FrameKind::ATTACH => self.Attach(streamId),
+                FrameKind::DATA => self.Data(streamId, &payload),
+                FrameKind::RESIZE => self.Resize(&payload),
+                FrameKind::PORT_FORWARD => self.PortForward(streamId, &payload),
+                FrameKind::CLOSE => {
+                    if let Some(mut target) = self.streams.remove(&streamId) {
+                        target.Close();
+                    }
+                    Ok(())
+                }
+                kind => Err(Error::Common(format!(
+                    "stream: unknown frame kind {}",
+                    kind
+                ))),
+            };
+
+            if let Err(e) = res {
+                info!("stream {}: {:?}", streamId, e);
+                self.writer.SendError(streamId, &e);
+            }
+        }
+
+        for (_, target) in self.streams.iter_mut() {
+            target.Close();
+        }
+
+        return Ok(());
+    }
+
+    fn OpenStream(&mut self, streamId: u32) -> Result<()> {
+        if self.streams.contains_key(&streamId) {
+            return Err(Error::Common(format!(
+                "stream: stream {} is already open",
+                streamId
+            )));
+        }
+
+        return Ok(());
+    }
+
+    pub fn Attach(&mut self, streamId: u32) -> Result<()> {
+        self.OpenStream(streamId)?;
+        let console = match self.console {
+            None => return Err(Error::Common("stream: no console to attach".to_string())),
+            Some(fd) => fd,
+        };
+
+        let reader = Self::Dup(console)?;
+        let writer = Self::Dup(console)?;
+        self.streams.insert(streamId, StreamTarget::Console(writer));
+        Pump(reader, streamId, self.writer.clone());
+        return Ok(());
+    }
+
+    pub fn Data(&mut self, streamId: u32, payload: &[u8]) -> Result<()> {
+        match self.streams.get_mut(&streamId) {
+            None => {
+                return Err(Error::Common(format!(
+                    "stream: stream {} is not open",
+                    streamId
+                )))
+            }
+            Some(target) => return target.Write(payload),
+        }
+    }
+
+    pub fn Resize(&mut self, payload: &[u8]) -> Result<()> {
+        let console = match self.console {
+            None => return Err(Error::Common("stream: no console to resize".to_string())),
+            Some(fd) => fd,
+        };
+
+        let size = ResizePayload::Decode(payload)?;
+        let winsize = libc::winsize {
+            ws_row: size.rows,
+            ws_col: size.cols,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+
+        unsafe {
+            ioctl_set_winsz(console, &winsize)
+                .map_err(|e| Error::Common(format!("stream: resize console fail {:?}", e)))?;
+        }
+
+        return Ok(());
+    }
+
+    pub fn PortForward(&mut self, streamId: u32, payload: &[u8]) -> Result<()> {
+        self.OpenStream(streamId)?;
+        if payload.len() < 2 {
+            return Err(Error::Common(
+                "stream: port forward needs a port".to_string(),
+            ));
+        }
+
+        let port = u16::from_le_bytes([payload[0], payload[1]]);
+        let conn = ConnectInNetns(self.sandboxPid, port)?;
+        let reader = conn
+            .try_clone()
+            .map_err(|e| Error::IOError(format!("stream: clone tcp stream fail {:?}", e)))?;
+
+        self.streams.insert(streamId, StreamTarget::Tcp(conn));
+        Pump(reader, streamId, self.writer.clone());
+        return Ok(());
+    }
+
+    fn Dup(fd: RawFd) -> Result<File> {
+        let newfd = unsafe { libc::dup(fd) };
+        if newfd < 0 {
+            return Err(Error::SysError(errno::errno().0));
+        }
+
+        return Ok(unsafe { File::from_raw_fd(newfd) });
+    }
+}
+
+// Pump copies the data read from reader to the client as DATA frames of
+// streamId until EOF, then closes the stream.
+pub fn Pump<R: Read + Send + 'static>(mut reader: R, streamId: u32, writer: FrameWriter) {
+    thread::spawn(move || {
+        let mut buf = vec![0; MAX_FRAME_PAYLOAD];
+        loop {
+            let cnt = match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(cnt) => cnt,
+                Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(_) => break,
+            };
+
+            if writer.Send(FrameKind::DATA, streamId, &buf[..cnt]).is_err() {
+                return;
+            }
+        }
+
+        writer.Send(FrameKind::CLOSE, streamId, &[]).ok();
+    });
+}
+
+// ConnectInNetns connects to the port on the loopback of the network namespace
+// of pid. setns only switches the calling thread, so it is done in a helper
+// thread and the connected socket stays in the sandbox namespace.
+pub fn ConnectInNetns(pid: i32, port: u16) -> Result<TcpStream> {
+    let path = format!("/proc/{}/ns/net", pid);
+    let handle = thread::spawn(move || -> Result<TcpStream> {
+        let ns = File::open(&path)
+            .map_err(|e| Error::IOError(format!("stream: open {} fail {:?}", path, e)))?;
+        let ret = unsafe { libc::setns(ns.as_raw_fd(), libc::CLONE_NEWNET) };
+        if ret < 0 {
+            return Err(Error::SysError(errno::errno().0));
+        }
+
+        return TcpStream::connect(("127.0.0.1", port))
+            .map_err(|e| Error::IOError(format!("stream: connect to port {} fail {:?}", port, e)));
+    });
+
+    match handle.join() {
+        Err(_) => {
+            return Err(Error::Common(
+                "stream: port forward thread panic".to_string(),
+            ))
+        }
+        Ok(res) => return res,
+    }
+}
+
+// ServeConnection serves conn in its own thread.
+pub fn ServeConnection(conn: UnixStream, console: Option<RawFd>, sandboxPid: i32) {
+    thread::spawn(move || {
+        let mut server = match StreamServer::New(conn, console, sandboxPid) {
+            Err(e) => {
+                error!("stream: start server fail {:?}", e);
+                return;
+            }
+            Ok(s) => s,
+        };
+
+        if let Err(e) = server.Serve() {
+            info!("stream: connection closed with {:?}", e);
+        }
+    });
+}
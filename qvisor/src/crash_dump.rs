@@ -0,0 +1,407 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::AtomicI32;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::Ordering;
+use lazy_static::lazy_static;
+use spin::Mutex;
+use std::ffi::CString;
+use std::fs;
+use std::os::unix::io::IntoRawFd;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use super::kvm_vcpu::KVMVcpu;
+use super::qlib::common::*;
+use super::qlib::config::*;
+use super::qlib::kernel::crash::*;
+use super::qlib::linux_def::*;
+use super::qlib::qmsg::*;
+use super::qlib::MAX_VCPU_COUNT;
+use super::VMS;
+
+// The crash dump is written when qkernel panics. It is a json file with the
+// registers of the vcpus, the crash info collected by the panicking vcpu, i.e.
+// its kernel stack, the vmas of the current mm and the kernel log ring, and the
+// latest qcalls of each vcpu. The dump directory is "CrashDumpDir" of the
+// config file, the crash dump is disabled if it is not set or empty.
+
+pub const CRASH_DUMP_DIR_DEFAULT: &str = "";
+
+// the qcall count kept for the crash dump per vcpu
+pub const QCALL_HISTORY_SIZE: usize = 256;
+
+// the time waiting for the other vcpus to leave the guest
+const CRASH_STOP_TIMEOUT: Duration = Duration::from_secs(1);
+
+lazy_static! {
+    pub static ref CRASH_DUMP: CrashDump = CrashDump::New();
+}
+
+// CrashDumpConfig is the part of the config file read by qvisor only, as the
+// shared Config has to be Copy.
+#[derive(Debug, Default, Deserialize)]
+pub struct CrashDumpConfig {
+    pub CrashDumpDir: Option<String>,
+}
+
+impl CrashDumpConfig {
+    pub fn Load() -> Self {
+        let contents = match fs::read_to_string(Config::CONFIG_FILE) {
+            Ok(c) => c,
+            _ => return Self::default(),
+        };
+
+        return serde_json::from_str(&contents).unwrap_or_default();
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QcallRecord {
+    pub Vcpu: usize,
+    pub TaskId: u64,
+    // Kind is the index of the Msg variant
+    pub Kind: u64,
+    pub Ret: u64,
+}
+
+// QcallEntry is the raw record of a qcall, it is only formatted when the dump
+// is written.
+#[derive(Default)]
+pub struct QcallEntry {
+    pub taskId: AtomicU64,
+    pub kind: AtomicU64,
+    pub ret: AtomicU64,
+}
+
+// QcallRing is the qcall history of a vcpu. Only the vcpu thread writes it, so
+// the record is lock free, the dump reads it after the vcpus are stopped.
+pub struct QcallRing {
+    // count is the number of the recorded qcalls
+    pub count: AtomicU64,
+    pub entries: Vec<QcallEntry>,
+}
+
+impl QcallRing {
+    pub fn New() -> Self {
+        let mut entries = Vec::with_capacity(QCALL_HISTORY_SIZE);
+        entries.resize_with(QCALL_HISTORY_SIZE, QcallEntry::default);
+        return Self {
+            count: AtomicU64::new(0),
+            entries: entries,
+        };
+    }
+
+    #[inline]
+    pub fn Push(&self, taskId: u64, kind: u64, ret: u64) {
+        let count = self.count.load(Ordering::Relaxed);
+        let entry = &self.entries[count as usize % QCALL_HISTORY_SIZE];
+        entry.taskId.store(taskId, Ordering::Relaxed);
+        entry.kind.store(kind, Ordering::Relaxed);
+        entry.ret.store(ret, Ordering::Relaxed);
+        self.count.store(count + 1, Ordering::Release);
+    }
+
+    pub fn Records(&self, vcpuId: usize, records: &mut Vec<QcallRecord>) {
+        let count = self.count.load(Ordering::Acquire);
+        let start = count.saturating_sub(QCALL_HISTORY_SIZE as u64);
+        for i in start..count {
+            let entry = &self.entries[i as usize % QCALL_HISTORY_SIZE];
+            records.push(QcallRecord {
+                Vcpu: vcpuId,
+                TaskId: entry.taskId.load(Ordering::Relaxed),
+                Kind: entry.kind.load(Ordering::Relaxed),
+                Ret: entry.ret.load(Ordering::Relaxed),
+            });
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct VcpuRegs {
+    pub rax: u64,
+    pub rbx: u64,
+    pub rcx: u64,
+    pub rdx: u64,
+    pub rsi: u64,
+    pub rdi: u64,
+    pub rsp: u64,
+    pub rbp: u64,
+    pub r8: u64,
+    pub r9: u64,
+    pub r10: u64,
+    pub r11: u64,
+    pub r12: u64,
+    pub r13: u64,
+    pub r14: u64,
+    pub r15: u64,
+    pub rip: u64,
+    pub rflags: u64,
+    pub cr0: u64,
+    pub cr2: u64,
+    pub cr3: u64,
+    pub cr4: u64,
+    pub efer: u64,
+    pub fs_base: u64,
+    pub gs_base: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VcpuDump {
+    pub Id: usize,
+    // InGuest is true if the vcpu doesn't leave the guest before the timeout,
+    // its registers can't be read then
+    pub InGuest: bool,
+    pub Regs: Option<VcpuRegs>,
+}
+
+#[derive(Serialize)]
+pub struct CrashReport<'a> {
+    pub SandboxId: String,
+    // Time is the unix time in seconds
+    pub Time: u64,
+    pub Vcpu: usize,
+    pub Message: String,
+    pub Vcpus: Vec<VcpuDump>,
+    pub Kernel: Option<&'a CrashInfo>,
+    pub Qcalls: Vec<QcallRecord>,
+}
+
+pub struct CrashDump {
+    pub dirfd: AtomicI32,
+    pub stopping: AtomicBool,
+    // dir and id are the dump directory and the sandbox id
+    pub dir: Mutex<String>,
+    pub id: Mutex<String>,
+    // history is the qcall ring of each vcpu
+    pub history: Vec<QcallRing>,
+}
+
+impl CrashDump {
+    pub fn New() -> Self {
+        return Self {
+            dirfd: AtomicI32::new(-1),
+            stopping: AtomicBool::new(false),
+            dir: Mutex::new(String::new()),
+            id: Mutex::new(String::new()),
+            history: (0..MAX_VCPU_COUNT).map(|_| QcallRing::New()).collect(),
+        };
+    }
+
+    // Init opens the dump directory. It must be called before the sandbox
+    // pivots root.
+    pub fn Init(&self, id: &str) -> Result<()> {
+        let dir = match CrashDumpConfig::Load().CrashDumpDir {
+            None => CRASH_DUMP_DIR_DEFAULT.to_string(),
+            Some(dir) => dir,
+        };
+
+        if dir.len() == 0 {
+            return Ok(());
+        }
+
+        fs::create_dir_all(&dir)
+            .map_err(|e| Error::IOError(format!("crash dump create dir io::error is {:?}", e)))?;
+        let file = fs::File::open(&dir)
+            .map_err(|e| Error::IOError(format!("crash dump open dir io::error is {:?}", e)))?;
+
+        *self.dir.lock() = dir;
+        *self.id.lock() = id.to_string();
+        self.dirfd.store(file.into_raw_fd(), Ordering::SeqCst);
+        return Ok(());
+    }
+
+    #[inline]
+    pub fn Enabled(&self) -> bool {
+        return self.dirfd.load(Ordering::Relaxed) >= 0;
+    }
+
+    #[inline]
+    pub fn Stopping(&self) -> bool {
+        return self.stopping.load(Ordering::SeqCst);
+    }
+
+    #[inline]
+    pub fn RecordQcall(&self, vcpuId: usize, taskId: u64, msg: &Msg, ret: u64) {
+        if !self.Enabled() || vcpuId >= self.history.len() {
+            return;
+        }
+
+        let kind = core::intrinsics::discriminant_value(msg) as u64;
+        self.history[vcpuId].Push(taskId, kind, ret);
+    }
+
+    // VcpuPark is called by a vcpu thread which sees the crash, it waits for the
+    // panicking vcpu to exit the process.
+    pub fn VcpuPark(&self) -> ! {
+        loop {
+            thread::sleep(Duration::from_secs(1));
+        }
+    }
+
+    // StopVcpus keeps the vcpus out of the guest so that their registers can be
+    // read. It returns the vcpus still in the guest after the timeout.
+    fn StopVcpus(&self, vcpus: &[Arc<KVMVcpu>], me: usize) -> Vec<bool> {
+        self.stopping.store(true, Ordering::SeqCst);
+        let deadline = Instant::now() + CRASH_STOP_TIMEOUT;
+        loop {
+            let mut inGuest = Vec::with_capacity(vcpus.len());
+            for vcpu in vcpus {
+                let running = vcpu.id != me && vcpu.state.load(Ordering::SeqCst) == 1;
+                if running {
+                    vcpu.Signal(Signal::SIGCHLD);
+                }
+                inGuest.push(running);
+            }
+
+            if !inGuest.contains(&true) || Instant::now() > deadline {
+                return inGuest;
+            }
+
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn Regs(vcpu: &KVMVcpu) -> Option<VcpuRegs> {
+        let regs = vcpu.vcpu.get_regs().ok()?;
+        let sregs = vcpu.vcpu.get_sregs().ok()?;
+        return Some(VcpuRegs {
+            rax: regs.rax,
+            rbx: regs.rbx,
+            rcx: regs.rcx,
+            rdx: regs.rdx,
+            rsi: regs.rsi,
+            rdi: regs.rdi,
+            rsp: regs.rsp,
+            rbp: regs.rbp,
+            r8: regs.r8,
+            r9: regs.r9,
+            r10: regs.r10,
+            r11: regs.r11,
+            r12: regs.r12,
+            r13: regs.r13,
+            r14: regs.r14,
+            r15: regs.r15,
+            rip: regs.rip,
+            rflags: regs.rflags,
+            cr0: sregs.cr0,
+            cr2: sregs.cr2,
+            cr3: sregs.cr3,
+            cr4: sregs.cr4,
+            efer: sregs.efer,
+            fs_base: sregs.fs.base,
+            gs_base: sregs.gs.base,
+        });
+    }
+
+    fn Qcalls(&self, vcpuCount: usize) -> Vec<QcallRecord> {
+        let mut records = Vec::new();
+        for i in 0..core::cmp::min(vcpuCount, self.history.len()) {
+            self.history[i].Records(i, &mut records);
+        }
+
+        return records;
+    }
+
+    // Write writes the crash dump of the panic on the vcpu and returns its path.
+    pub fn Write(&self, vcpuId: usize, msg: &str, info: Option<&CrashInfo>) -> Result<String> {
+        let dirfd = self.dirfd.load(Ordering::SeqCst);
+        if dirfd < 0 {
+            return Err(Error::Common("crash dump is disabled".to_string()));
+        }
+
+        let vcpus = VMS.lock().vcpus.clone();
+        let inGuest = self.StopVcpus(&vcpus, vcpuId);
+        let mut vcpuDumps = Vec::with_capacity(vcpus.len());
+        for i in 0..vcpus.len() {
+            vcpuDumps.push(VcpuDump {
+                Id: vcpus[i].id,
+                InGuest: inGuest[i],
+                Regs: if inGuest[i] {
+                    None
+                } else {
+                    Self::Regs(&vcpus[i])
+                },
+            });
+        }
+
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let id = self.id.lock().clone();
+        let report = CrashReport {
+            SandboxId: id.clone(),
+            Time: time,
+            Vcpu: vcpuId,
+            Message: msg.to_string(),
+            Vcpus: vcpuDumps,
+            Kernel: info,
+            Qcalls: self.Qcalls(vcpus.len()),
+        };
+
+        let data = serde_json::to_vec(&report)
+            .map_err(|e| Error::Common(format!("crash dump serialize error is {:?}", e)))?;
+
+        let name = format!(
+            "{}.crash.{}.json",
+            &id[0..core::cmp::min(12, id.len())],
+            time
+        );
+        let cname = CString::new(name.clone()).unwrap();
+        let fd = unsafe {
+            libc::openat(
+                dirfd,
+                cname.as_ptr(),
+                libc::O_CREAT | libc::O_WRONLY | libc::O_TRUNC | libc::O_CLOEXEC,
+                0o644,
+            )
+        };
+        if fd < 0 {
+            return Err(Error::SysError(errno::errno().0));
+        }
+
+        let mut offset = 0;
+        while offset < data.len() {
+            let ret = unsafe {
+                libc::write(
+                    fd,
+                    data[offset..].as_ptr() as *const libc::c_void,
+                    data.len() - offset,
+                )
+            };
+            if ret < 0 {
+                let errno = errno::errno().0;
+                if errno == SysErr::EINTR {
+                    continue;
+                }
+                unsafe { libc::close(fd) };
+                return Err(Error::SysError(errno));
+            }
+            offset += ret as usize;
+        }
+
+        unsafe {
+            libc::fsync(fd);
+            libc::close(fd);
+        }
+
+        return Ok(format!("{}/{}", self.dir.lock(), name));
+    }
+}
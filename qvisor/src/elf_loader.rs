@@ -16,8 +16,8 @@ use std::fs::File;
 use std::slice;
 use xmas_elf::program::ProgramHeader::Ph64;
 use xmas_elf::program::Type;
+use xmas_elf::sections::{SectionData, ShType};
 //use xmas_elf::program::{ProgramIter, SegmentData, Type};
-//use xmas_elf::sections::SectionData;
 use memmap::Mmap;
 use std::os::unix::io::AsRawFd;
 use xmas_elf::*;
@@ -34,6 +34,8 @@ use super::addr::Addr;
 //use xmas_elf::header;
 use super::qlib::common::Error;
 use super::qlib::common::Result;
+use super::qlib::linux_def::MemoryDef;
+use super::util::HostRandU64;
 
 use super::memmgr::{MapOption, MappedRegion};
 
@@ -67,7 +69,31 @@ impl KernelELF {
         return self.endAddr;
     }
 
-    pub fn LoadKernel(&mut self, fileName: &str) -> Result<u64> {
+    // VDSO_SIZE is the size of the vdso pages which are loaded after qkernel
+    pub const VDSO_SIZE: u64 = 4 * 4096;
+    // KERNEL_SLIDE_ALIGN is the step of the random load address of qkernel,
+    // the image keeps its 2MB alignment
+    pub const KERNEL_SLIDE_ALIGN: u64 = 2 * MemoryDef::ONE_MB;
+    // the relocation types of a static pie qkernel
+    pub const R_X86_64_NONE: u32 = 0;
+    pub const R_X86_64_RELATIVE: u32 = 8;
+
+    // KernelSlide returns a random offset for the qkernel image which is
+    // linked at [start, end). The image and the vdso pages after it have to
+    // stay in the qkernel image area.
+    fn KernelSlide(start: u64, end: u64) -> Result<u64> {
+        let areaEnd = MemoryDef::PHY_LOWER_ADDR + MemoryDef::QKERNEL_IMAGE_SIZE;
+        if start < MemoryDef::PHY_LOWER_ADDR || end + Self::VDSO_SIZE > areaEnd {
+            return Err(Error::ELFLoadError("qkernel is out of the image area"));
+        }
+
+        let slots = (areaEnd - Self::VDSO_SIZE - end) / Self::KERNEL_SLIDE_ALIGN;
+        return Ok(HostRandU64() % (slots + 1) * Self::KERNEL_SLIDE_ALIGN);
+    }
+
+    // LoadKernel loads qkernel. With randomize, a qkernel linked as a static
+    // pie is moved by a random slide and its relocations are applied.
+    pub fn LoadKernel(&mut self, fileName: &str, randomize: bool) -> Result<u64> {
         let f =
             File::open(fileName).map_err(|e| Error::IOError(format!("io::error is {:?}", e)))?;
         let mmap =
@@ -84,25 +110,51 @@ impl KernelELF {
             _ => return Err(Error::WrongELFFormat),
         };
 
+        // the static pie keeps its link address, it is told by the dynamic
+        // relocations
+        let pie = elfFile.find_section_by_name(".rela.dyn").is_some();
+
+        let slide = if randomize && pie {
+            let mut imageStart = u64::MAX;
+            let mut imageEnd = 0;
+            for p in elfFile.program_iter() {
+                if let Ph64(header) = p {
+                    if header.get_type().map_err(Error::ELFLoadError)? == Type::Load {
+                        imageStart = core::cmp::min(imageStart, header.virtual_addr);
+                        imageEnd = core::cmp::max(
+                            imageEnd,
+                            Addr(header.virtual_addr + header.mem_size).RoundUp()?.0,
+                        );
+                    }
+                }
+            }
+
+            Self::KernelSlide(imageStart, imageEnd)?
+        } else {
+            if randomize {
+                error!(
+                    "LoadKernel: {} is not a pie, it is loaded at the link address",
+                    fileName
+                );
+            }
+            0
+        };
+
         for p in elfFile.program_iter() {
             //todo : add more check
             if let Ph64(header) = p {
                 if header.get_type().map_err(Error::ELFLoadError)? == Type::Load {
-                    let startMem = Addr(header.virtual_addr).RoundDown()?;
-                    let endMem = Addr(header.virtual_addr)
-                        .AddLen(header.file_size)?
-                        .RoundUp()?;
-                    let pageOffset =
-                        Addr(header.virtual_addr).0 - Addr(header.virtual_addr).RoundDown()?.0;
+                    let vaddr = header.virtual_addr + slide;
+                    let startMem = Addr(vaddr).RoundDown()?;
+                    let endMem = Addr(vaddr).AddLen(header.file_size)?.RoundUp()?;
+                    let pageOffset = Addr(vaddr).0 - Addr(vaddr).RoundDown()?.0;
                     let len = Addr(header.file_size).RoundUp()?.0;
 
                     if startMem.0 < startAddr.0 {
                         startAddr = startMem;
                     }
 
-                    let end = Addr(header.virtual_addr)
-                        .AddLen(header.mem_size)?
-                        .RoundUp()?;
+                    let end = Addr(vaddr).AddLen(header.mem_size)?.RoundUp()?;
                     if endAddr.0 < endMem.0 {
                         endAddr = end;
                     }
@@ -123,7 +175,7 @@ impl KernelELF {
                     assert!(mr.ptr == startMem.0 + pageOffset);
                     self.mrs.push(mr);
 
-                    let adjust = header.virtual_addr - startMem.0;
+                    let adjust = vaddr - startMem.0;
 
                     if adjust + header.file_size < endMem.0 - startMem.0 {
                         let cnt = (endMem.0 - startMem.0 - (adjust + header.file_size)) as usize;
@@ -140,7 +192,7 @@ impl KernelELF {
                     }
 
                     if header.mem_size > header.file_size {
-                        let bssEnd = Addr(header.virtual_addr + header.mem_size).RoundUp()?;
+                        let bssEnd = Addr(vaddr + header.mem_size).RoundUp()?;
                         if bssEnd.0 != endMem.0 {
                             let mut option = &mut MapOption::New();
                             option = option
@@ -160,13 +212,52 @@ impl KernelELF {
             }
         }
 
+        if pie {
+            Self::Relocate(&elfFile, slide)?;
+        }
+
+        // the kernel addresses in the backtraces are resolved after the
+        // slide is subtracted
+        info!("LoadKernel: qkernel is loaded with the slide {:x}", slide);
+
         self.startAddr = startAddr;
         self.endAddr = endAddr;
 
-        return Ok(entry);
+        return Ok(entry + slide);
     }
 
-    pub fn LoadVDSO(&mut self, fileName: &String) -> Result<()> {
+    // Relocate applies the relocations of a pie qkernel which is loaded at
+    // the link address plus slide.
+    fn Relocate(elfFile: &ElfFile, slide: u64) -> Result<()> {
+        for section in elfFile.section_iter() {
+            if section.get_type().map_err(Error::ELFLoadError)? != ShType::Rela {
+                continue;
+            }
+
+            let relas = match section.get_data(elfFile).map_err(Error::ELFLoadError)? {
+                SectionData::Rela64(relas) => relas,
+                _ => return Err(Error::WrongELFFormat),
+            };
+
+            for rela in relas {
+                match rela.get_type() {
+                    Self::R_X86_64_NONE => (),
+                    Self::R_X86_64_RELATIVE => unsafe {
+                        *((rela.get_offset() + slide) as *mut u64) =
+                            (rela.get_addend() as u64).wrapping_add(slide);
+                    },
+                    _ => return Err(Error::ELFLoadError("unsupported qkernel relocation")),
+                }
+            }
+        }
+
+        return Ok(());
+    }
+
+    // LoadVDSO loads the vdso after the kernel image, the layout is the
+    // param page, the 2 pages of the 64 bit vdso and the page of the 32 bit
+    // compat vdso.
+    pub fn LoadVDSO(&mut self, fileName: &String, compatFileName: &String) -> Result<()> {
         let f =
             File::open(fileName).map_err(|e| Error::IOError(format!("io::error is {:?}", e)))?;
         let mmap =
@@ -175,10 +266,19 @@ impl KernelELF {
 
         assert!(Addr(len as u64).RoundUp()?.0 == 2 * 4096);
 
+        let compatFile = File::open(compatFileName)
+            .map_err(|e| Error::IOError(format!("io::error is {:?}", e)))?;
+        let compatMmap = unsafe {
+            Mmap::map(&compatFile).map_err(|e| Error::IOError(format!("io::error is {:?}", e)))?
+        };
+        let compatLen = compatMmap.len();
+
+        assert!(Addr(compatLen as u64).RoundUp()?.0 == 4096);
+
         let mut option = &mut MapOption::New();
         option = option
             .Addr(self.EndAddr().0)
-            .Len(3 * 4096)
+            .Len(Self::VDSO_SIZE)
             .MapAnan()
             .MapPrivate()
             .ProtoRead()
@@ -195,8 +295,13 @@ impl KernelELF {
         let source = &mmap[..];
         target.clone_from_slice(source);
 
+        let target =
+            unsafe { slice::from_raw_parts_mut((hostAddr + 3 * 4096) as *mut u8, compatLen) };
+        let source = &compatMmap[..];
+        target.clone_from_slice(source);
+
         self.vdsoStart = hostAddr;
-        self.vdsoLen = 3 * 4096;
+        self.vdsoLen = Self::VDSO_SIZE;
         self.vdsomr = Some(mr);
 
         return Ok(());
@@ -0,0 +1,771 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::collections::btree_map::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::Ordering;
+use kvm_bindings::*;
+use lazy_static::lazy_static;
+use std::fs;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use super::kvm_vcpu::KVMVcpu;
+use super::qlib::common::*;
+use super::qlib::linux_def::*;
+use super::qlib::pagetable::PageTables;
+use super::{QUARK_CONFIG, VMS};
+
+// The gdb stub serves the gdb remote serial protocol for the vcpus of the
+// sandbox. It works in all-stop mode: when a vcpu hits a breakpoint or the
+// debugger interrupts, all the vcpus are stopped before the debugger is told.
+// Each vcpu is shown as a thread whose id is the vcpu id + 1.
+//
+//   gdb qkernel/target/... -ex "target remote 127.0.0.1:<GdbPort>"
+//   gdb qkernel/target/... -ex "target remote /var/run/quark/gdb-<id>.sock"
+
+pub const GDB_SOCKET_DIR: &str = "/var/run/quark";
+
+// the gdb signal numbers of the stop replies
+const GDB_SIGINT: u8 = 2;
+const GDB_SIGTRAP: u8 = 5;
+
+const INT3: u8 = 0xcc;
+
+lazy_static! {
+    pub static ref GDB_STUB: GdbStub = GdbStub::New();
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StopReason {
+    Interrupt,
+    Breakpoint,
+    Step,
+}
+
+#[derive(Default)]
+pub struct GdbState {
+    // parked is the count of the vcpus waiting in VcpuStop
+    pub parked: usize,
+    // event is the first stop event since the last resume
+    pub event: Option<(usize, StopReason)>,
+    // resumeGen is increased when the debugger resumes the vcpus
+    pub resumeGen: u64,
+    // breakpoints are the original bytes at the software breakpoint addresses,
+    // keyed by the host address of the int3
+    pub breakpoints: BTreeMap<u64, u8>,
+}
+
+pub enum GdbListener {
+    Tcp(TcpListener),
+    Unix(UnixListener),
+}
+
+pub struct GdbStub {
+    pub attached: AtomicBool,
+    pub stopping: AtomicBool,
+    pub state: Mutex<GdbState>,
+    pub cond: Condvar,
+    // listeners are bound by Listen and served by Start
+    pub listeners: Mutex<Vec<GdbListener>>,
+}
+
+impl GdbStub {
+    pub fn New() -> Self {
+        return Self {
+            attached: AtomicBool::new(false),
+            stopping: AtomicBool::new(false),
+            state: Mutex::new(GdbState::default()),
+            cond: Condvar::new(),
+            listeners: Mutex::new(Vec::new()),
+        };
+    }
+
+    #[inline]
+    pub fn Stopping(&self) -> bool {
+        return self.stopping.load(Ordering::SeqCst);
+    }
+
+    // VcpuStop is called by the vcpu thread when it is requested to stop or it
+    // gets a debug exit. It blocks until the debugger resumes the vcpus.
+    pub fn VcpuStop(&self, vcpuId: usize, reason: Option<StopReason>) {
+        let mut state = self.state.lock().unwrap();
+        if !self.attached.load(Ordering::SeqCst) {
+            return;
+        }
+
+        // the vcpus have been resumed after the vcpu sees the stop request
+        if reason.is_none() && !self.Stopping() {
+            return;
+        }
+
+        if let Some(reason) = reason {
+            if state.event.is_none() {
+                state.event = Some((vcpuId, reason));
+            }
+            self.stopping.store(true, Ordering::SeqCst);
+        }
+
+        state.parked += 1;
+        self.cond.notify_all();
+
+        let gen = state.resumeGen;
+        while state.resumeGen == gen {
+            state = self.cond.wait(state).unwrap();
+        }
+        state.parked -= 1;
+    }
+
+    fn Vcpus() -> Vec<Arc<KVMVcpu>> {
+        return VMS.lock().vcpus.clone();
+    }
+
+    // StopAll stops all the vcpus and returns the vcpu and the reason to report.
+    // A vcpu waiting in the host is taken as stopped, it parks in VcpuStop
+    // before it enters the guest again.
+    fn StopAll(&self) -> (usize, StopReason) {
+        self.stopping.store(true, Ordering::SeqCst);
+        let vcpus = Self::Vcpus();
+        loop {
+            let mut running = false;
+            for vcpu in &vcpus {
+                // the vcpu thread may miss the signal just before it enters the
+                // guest, so it is kicked until it leaves the guest
+                if vcpu.state.load(Ordering::SeqCst) == 1 {
+                    vcpu.Signal(Signal::SIGCHLD);
+                    running = true;
+                }
+            }
+
+            if !running {
+                break;
+            }
+
+            let state = self.state.lock().unwrap();
+            let _ = self
+                .cond
+                .wait_timeout(state, Duration::from_millis(10))
+                .unwrap();
+        }
+
+        let state = self.state.lock().unwrap();
+        return match state.event {
+            Some(event) => event,
+            None => (0, StopReason::Interrupt),
+        };
+    }
+
+    // Resume lets the stopped vcpus continue, the vcpu step is single stepped.
+    fn Resume(&self, step: Option<usize>) -> Result<()> {
+        let attached = self.attached.load(Ordering::SeqCst);
+        for vcpu in Self::Vcpus() {
+            Self::SetGuestDebug(&vcpu, attached, step == Some(vcpu.id))?;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        state.event = None;
+        self.stopping.store(false, Ordering::SeqCst);
+        state.resumeGen += 1;
+        self.cond.notify_all();
+        return Ok(());
+    }
+
+    fn SetGuestDebug(vcpu: &KVMVcpu, enable: bool, step: bool) -> Result<()> {
+        let mut debug = kvm_guest_debug::default();
+        if enable {
+            debug.control = KVM_GUESTDBG_ENABLE | KVM_GUESTDBG_USE_SW_BP;
+            if step {
+                debug.control |= KVM_GUESTDBG_SINGLESTEP;
+            }
+        }
+
+        vcpu.vcpu
+            .set_guest_debug(&debug)
+            .map_err(|e| Error::IOError(format!("set_guest_debug io::error is {:?}", e)))?;
+        return Ok(());
+    }
+
+    // Translate returns the host address of the guest virtual address addr for
+    // the current page table of the vcpu. The guest physical memory is identity
+    // mapped in the kernel and in qvisor.
+    fn Translate(vcpu: &KVMVcpu, addr: u64) -> Option<u64> {
+        let memEnd =
+            MemoryDef::PHY_LOWER_ADDR + QUARK_CONFIG.lock().KernelMemSize * MemoryDef::ONE_GB;
+        if MemoryDef::PHY_LOWER_ADDR <= addr && addr < memEnd {
+            return Some(addr);
+        }
+
+        let sregs = vcpu.vcpu.get_sregs().ok()?;
+        let pt = PageTables::Init(sregs.cr3 & !(MemoryDef::PAGE_SIZE - 1));
+        let (phyAddr, _) = pt.VirtualToPhy(addr).ok()?;
+        if phyAddr < MemoryDef::PHY_LOWER_ADDR || phyAddr >= memEnd {
+            return None;
+        }
+
+        return Some(phyAddr);
+    }
+
+    fn ReadMem(vcpu: &KVMVcpu, addr: u64, len: usize) -> Option<Vec<u8>> {
+        let mut data = Vec::with_capacity(len);
+        for i in 0..len as u64 {
+            let hostAddr = Self::Translate(vcpu, addr + i)?;
+            data.push(unsafe { *(hostAddr as *const u8) });
+        }
+
+        // show the original bytes instead of the int3 of the breakpoints
+        let state = GDB_STUB.state.lock().unwrap();
+        for i in 0..len {
+            if let Some(hostAddr) = Self::Translate(vcpu, addr + i as u64) {
+                if let Some(orig) = state.breakpoints.get(&hostAddr) {
+                    data[i] = *orig;
+                }
+            }
+        }
+
+        return Some(data);
+    }
+
+    fn WriteMem(vcpu: &KVMVcpu, addr: u64, data: &[u8]) -> bool {
+        for i in 0..data.len() {
+            match Self::Translate(vcpu, addr + i as u64) {
+                None => return false,
+                Some(hostAddr) => unsafe { *(hostAddr as *mut u8) = data[i] },
+            }
+        }
+
+        return true;
+    }
+
+    fn InsertBreakpoint(&self, vcpu: &KVMVcpu, addr: u64) -> bool {
+        let hostAddr = match Self::Translate(vcpu, addr) {
+            None => return false,
+            Some(a) => a,
+        };
+
+        let mut state = self.state.lock().unwrap();
+        if !state.breakpoints.contains_key(&hostAddr) {
+            let orig = unsafe { *(hostAddr as *const u8) };
+            state.breakpoints.insert(hostAddr, orig);
+            unsafe { *(hostAddr as *mut u8) = INT3 };
+        }
+
+        return true;
+    }
+
+    fn RemoveBreakpoint(&self, vcpu: &KVMVcpu, addr: u64) -> bool {
+        let hostAddr = match Self::Translate(vcpu, addr) {
+            None => return false,
+            Some(a) => a,
+        };
+
+        let mut state = self.state.lock().unwrap();
+        if let Some(orig) = state.breakpoints.remove(&hostAddr) {
+            unsafe { *(hostAddr as *mut u8) = orig };
+        }
+
+        return true;
+    }
+
+    fn RemoveAllBreakpoints(&self) {
+        let mut state = self.state.lock().unwrap();
+        for (hostAddr, orig) in state.breakpoints.iter() {
+            unsafe { *(*hostAddr as *mut u8) = *orig };
+        }
+        state.breakpoints.clear();
+    }
+
+    // Listen binds the gdb stub addresses enabled in the config. It must be
+    // called before the sandbox pivots root.
+    pub fn Listen(id: &str) -> Result<()> {
+        let (port, socket) = {
+            let config = QUARK_CONFIG.lock();
+            (config.GdbPort, config.GdbSocket)
+        };
+
+        if port != 0 {
+            let listener = TcpListener::bind(("127.0.0.1", port))
+                .map_err(|e| Error::IOError(format!("gdb stub bind io::error is {:?}", e)))?;
+            info!("gdb stub listens on 127.0.0.1:{}", port);
+            GDB_STUB
+                .listeners
+                .lock()
+                .unwrap()
+                .push(GdbListener::Tcp(listener));
+        }
+
+        if socket {
+            fs::create_dir_all(GDB_SOCKET_DIR)
+                .map_err(|e| Error::IOError(format!("gdb stub create dir io::error is {:?}", e)))?;
+            let path = format!("{}/gdb-{}.sock", GDB_SOCKET_DIR, id);
+            let _ = fs::remove_file(&path);
+            let listener = UnixListener::bind(&path)
+                .map_err(|e| Error::IOError(format!("gdb stub bind io::error is {:?}", e)))?;
+            info!("gdb stub listens on {}", path);
+            GDB_STUB
+                .listeners
+                .lock()
+                .unwrap()
+                .push(GdbListener::Unix(listener));
+        }
+
+        return Ok(());
+    }
+
+    // Start serves the debugger connections of the listeners, one thread
+    // each. It is called after the sandbox process is confined, as the
+    // capabilities dropped later are kept by the threads created before.
+    pub fn Start() {
+        let listeners: Vec<GdbListener> = GDB_STUB.listeners.lock().unwrap().drain(..).collect();
+        for listener in listeners {
+            match listener {
+                GdbListener::Tcp(listener) => {
+                    thread::Builder::new()
+                        .name("gdbstub-tcp".to_string())
+                        .spawn(move || {
+                            for stream in listener.incoming() {
+                                match stream {
+                                    Ok(s) => GDB_STUB.Serve(GdbConn::Tcp(s)),
+                                    Err(e) => error!("gdb stub accept fail {:?}", e),
+                                }
+                            }
+                        })
+                        .unwrap();
+                }
+                GdbListener::Unix(listener) => {
+                    thread::Builder::new()
+                        .name("gdbstub-unix".to_string())
+                        .spawn(move || {
+                            for stream in listener.incoming() {
+                                match stream {
+                                    Ok(s) => GDB_STUB.Serve(GdbConn::Unix(s)),
+                                    Err(e) => error!("gdb stub accept fail {:?}", e),
+                                }
+                            }
+                        })
+                        .unwrap();
+                }
+            }
+        }
+    }
+
+    pub fn Serve(&self, conn: GdbConn) {
+        if self.attached.swap(true, Ordering::SeqCst) {
+            error!("gdb stub: there is already a debugger attached");
+            return;
+        }
+
+        info!("gdb stub: debugger attached");
+        let mut session = GdbSession {
+            conn: conn,
+            curVcpu: 0,
+        };
+
+        // the vcpus are stopped when the debugger attaches
+        self.StopAll();
+        if let Err(e) = session.Run(self) {
+            info!("gdb stub: session end with {:?}", e);
+        }
+
+        self.RemoveAllBreakpoints();
+        self.attached.store(false, Ordering::SeqCst);
+        if let Err(e) = self.Resume(None) {
+            error!("gdb stub: resume fail {:?}", e);
+        }
+        info!("gdb stub: debugger detached");
+    }
+}
+
+pub enum GdbConn {
+    Tcp(TcpStream),
+    Unix(UnixStream),
+}
+
+impl GdbConn {
+    fn SetReadTimeout(&self, timeout: Option<Duration>) {
+        let _ = match self {
+            GdbConn::Tcp(s) => s.set_read_timeout(timeout),
+            GdbConn::Unix(s) => s.set_read_timeout(timeout),
+        };
+    }
+
+    // ReadByte returns None when the read times out
+    fn ReadByte(&mut self) -> Result<Option<u8>> {
+        let mut buf = [0u8; 1];
+        let ret = match self {
+            GdbConn::Tcp(s) => s.read(&mut buf),
+            GdbConn::Unix(s) => s.read(&mut buf),
+        };
+
+        match ret {
+            Ok(0) => return Err(Error::Common("gdb connection closed".to_string())),
+            Ok(_) => return Ok(Some(buf[0])),
+            Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {
+                return Ok(None)
+            }
+            Err(e) => return Err(Error::IOError(format!("gdb read io::error is {:?}", e))),
+        }
+    }
+
+    fn WriteAll(&mut self, data: &[u8]) -> Result<()> {
+        let ret = match self {
+            GdbConn::Tcp(s) => s.write_all(data),
+            GdbConn::Unix(s) => s.write_all(data),
+        };
+
+        return ret.map_err(|e| Error::IOError(format!("gdb write io::error is {:?}", e)));
+    }
+}
+
+pub enum GdbInput {
+    Packet(Vec<u8>),
+    // Interrupt is the ctrl-c (0x03) sent by the debugger
+    Interrupt,
+}
+
+fn ToHex(data: &[u8]) -> String {
+    let mut s = String::with_capacity(data.len() * 2);
+    for b in data {
+        s += &format!("{:02x}", b);
+    }
+    return s;
+}
+
+fn FromHex(s: &[u8]) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return None;
+    }
+
+    let s = core::str::from_utf8(s).ok()?;
+    let mut data = Vec::with_capacity(s.len() / 2);
+    for i in (0..s.len()).step_by(2) {
+        data.push(u8::from_str_radix(&s[i..i + 2], 16).ok()?);
+    }
+    return Some(data);
+}
+
+fn ParseHex(s: &[u8]) -> Option<u64> {
+    let s = core::str::from_utf8(s).ok()?;
+    return u64::from_str_radix(s, 16).ok();
+}
+
+// ParseAddrLen parses "addr,len"
+fn ParseAddrLen(s: &[u8]) -> Option<(u64, usize)> {
+    let pos = s.iter().position(|c| *c == b',')?;
+    let addr = ParseHex(&s[..pos])?;
+    let len = ParseHex(&s[pos + 1..])? as usize;
+    return Some((addr, len));
+}
+
+// the amd64 register layout of the g/G packets without the float registers:
+// rax rbx rcx rdx rsi rdi rbp rsp r8-r15 rip as 8 bytes, eflags cs ss ds es fs
+// gs as 4 bytes
+const GDB_REGS_LEN: usize = 17 * 8 + 7 * 4;
+
+fn RegsToGdb(regs: &kvm_regs, sregs: &kvm_sregs) -> Vec<u8> {
+    let mut data = Vec::with_capacity(GDB_REGS_LEN);
+    for r in &[
+        regs.rax, regs.rbx, regs.rcx, regs.rdx, regs.rsi, regs.rdi, regs.rbp, regs.rsp, regs.r8,
+        regs.r9, regs.r10, regs.r11, regs.r12, regs.r13, regs.r14, regs.r15, regs.rip,
+    ] {
+        data.extend_from_slice(&r.to_le_bytes());
+    }
+
+    for r in &[
+        regs.rflags as u32,
+        sregs.cs.selector as u32,
+        sregs.ss.selector as u32,
+        sregs.ds.selector as u32,
+        sregs.es.selector as u32,
+        sregs.fs.selector as u32,
+        sregs.gs.selector as u32,
+    ] {
+        data.extend_from_slice(&r.to_le_bytes());
+    }
+
+    return data;
+}
+
+// GdbToRegs updates the general registers, the segment selectors are not
+// writable.
+fn GdbToRegs(data: &[u8], regs: &mut kvm_regs) -> bool {
+    if data.len() < 17 * 8 + 4 {
+        return false;
+    }
+
+    let mut words = [0u64; 17];
+    for i in 0..17 {
+        let mut b = [0u8; 8];
+        b.copy_from_slice(&data[i * 8..i * 8 + 8]);
+        words[i] = u64::from_le_bytes(b);
+    }
+
+    let mut b = [0u8; 4];
+    b.copy_from_slice(&data[17 * 8..17 * 8 + 4]);
+
+    regs.rax = words[0];
+    regs.rbx = words[1];
+    regs.rcx = words[2];
+    regs.rdx = words[3];
+    regs.rsi = words[4];
+    regs.rdi = words[5];
+    regs.rbp = words[6];
+    regs.rsp = words[7];
+    regs.r8 = words[8];
+    regs.r9 = words[9];
+    regs.r10 = words[10];
+    regs.r11 = words[11];
+    regs.r12 = words[12];
+    regs.r13 = words[13];
+    regs.r14 = words[14];
+    regs.r15 = words[15];
+    regs.rip = words[16];
+    regs.rflags = u32::from_le_bytes(b) as u64;
+    return true;
+}
+
+pub struct GdbSession {
+    pub conn: GdbConn,
+    // curVcpu is the vcpu selected by the Hg packet
+    pub curVcpu: usize,
+}
+
+impl GdbSession {
+    fn ReadInput(&mut self) -> Result<Option<GdbInput>> {
+        loop {
+            let c = match self.conn.ReadByte()? {
+                None => return Ok(None),
+                Some(c) => c,
+            };
+
+            match c {
+                0x03 => return Ok(Some(GdbInput::Interrupt)),
+                b'$' => break,
+                // acks
+                _ => continue,
+            }
+        }
+
+        self.conn.SetReadTimeout(None);
+        let mut packet = Vec::new();
+        loop {
+            match self.conn.ReadByte()? {
+                Some(b'#') => break,
+                Some(c) => packet.push(c),
+                None => (),
+            }
+        }
+
+        // the checksum is not verified, the transport is reliable
+        let _ = self.conn.ReadByte()?;
+        let _ = self.conn.ReadByte()?;
+        self.conn.WriteAll(b"+")?;
+        return Ok(Some(GdbInput::Packet(packet)));
+    }
+
+    fn Send(&mut self, data: &str) -> Result<()> {
+        let sum = data.bytes().fold(0u8, |s, c| s.wrapping_add(c));
+        let packet = format!("${}#{:02x}", data, sum);
+        return self.conn.WriteAll(packet.as_bytes());
+    }
+
+    fn Vcpu(&self) -> Option<Arc<KVMVcpu>> {
+        return GdbStub::Vcpus().get(self.curVcpu).cloned();
+    }
+
+    fn StopReply(&mut self, vcpuId: usize, reason: StopReason) -> Result<()> {
+        self.curVcpu = vcpuId;
+        let reply = match reason {
+            StopReason::Interrupt => format!("T{:02x}thread:{:x};", GDB_SIGINT, vcpuId + 1),
+            StopReason::Breakpoint => {
+                format!("T{:02x}thread:{:x};swbreak:;", GDB_SIGTRAP, vcpuId + 1)
+            }
+            StopReason::Step => format!("T{:02x}thread:{:x};", GDB_SIGTRAP, vcpuId + 1),
+        };
+        return self.Send(&reply);
+    }
+
+    // Continue resumes the vcpus and waits until they stop again
+    fn Continue(&mut self, stub: &GdbStub, step: bool) -> Result<()> {
+        stub.Resume(if step { Some(self.curVcpu) } else { None })?;
+        loop {
+            if stub.Stopping() {
+                break;
+            }
+
+            self.conn.SetReadTimeout(Some(Duration::from_millis(50)));
+            if let Some(GdbInput::Interrupt) = self.ReadInput()? {
+                break;
+            }
+        }
+
+        self.conn.SetReadTimeout(None);
+        let (vcpuId, reason) = stub.StopAll();
+        return self.StopReply(vcpuId, reason);
+    }
+
+    pub fn Run(&mut self, stub: &GdbStub) -> Result<()> {
+        loop {
+            let packet = match self.ReadInput()? {
+                None | Some(GdbInput::Interrupt) => continue,
+                Some(GdbInput::Packet(p)) => p,
+            };
+
+            if packet.is_empty() {
+                self.Send("")?;
+                continue;
+            }
+
+            let args = &packet[1..];
+            match packet[0] {
+                b'?' => {
+                    let (vcpuId, reason) = stub.StopAll();
+                    self.StopReply(vcpuId, reason)?;
+                }
+                b'g' => {
+                    let reply = match self.Vcpu() {
+                        None => "E01".to_string(),
+                        Some(vcpu) => match (vcpu.vcpu.get_regs(), vcpu.vcpu.get_sregs()) {
+                            (Ok(regs), Ok(sregs)) => ToHex(&RegsToGdb(&regs, &sregs)),
+                            _ => "E01".to_string(),
+                        },
+                    };
+                    self.Send(&reply)?;
+                }
+                b'G' => {
+                    let ok = match (self.Vcpu(), FromHex(args)) {
+                        (Some(vcpu), Some(data)) => match vcpu.vcpu.get_regs() {
+                            Ok(mut regs) => {
+                                GdbToRegs(&data, &mut regs) && vcpu.vcpu.set_regs(&regs).is_ok()
+                            }
+                            Err(_) => false,
+                        },
+                        _ => false,
+                    };
+                    self.Send(if ok { "OK" } else { "E01" })?;
+                }
+                b'm' => {
+                    let reply = match (self.Vcpu(), ParseAddrLen(args)) {
+                        (Some(vcpu), Some((addr, len))) => {
+                            match GdbStub::ReadMem(&vcpu, addr, len) {
+                                Some(data) => ToHex(&data),
+                                None => "E14".to_string(),
+                            }
+                        }
+                        _ => "E01".to_string(),
+                    };
+                    self.Send(&reply)?;
+                }
+                b'M' => {
+                    let reply = match args.iter().position(|c| *c == b':') {
+                        None => "E01",
+                        Some(pos) => match (
+                            self.Vcpu(),
+                            ParseAddrLen(&args[..pos]),
+                            FromHex(&args[pos + 1..]),
+                        ) {
+                            (Some(vcpu), Some((addr, len)), Some(data)) if len == data.len() => {
+                                if GdbStub::WriteMem(&vcpu, addr, &data) {
+                                    "OK"
+                                } else {
+                                    "E14"
+                                }
+                            }
+                            _ => "E01",
+                        },
+                    };
+                    self.Send(reply)?;
+                }
+                b'Z' | b'z' => {
+                    // only the software breakpoints "Z0,addr,kind" are supported
+                    if args.len() < 2 || args[0] != b'0' {
+                        self.Send("")?;
+                        continue;
+                    }
+
+                    let reply = match (self.Vcpu(), ParseAddrLen(&args[2..])) {
+                        (Some(vcpu), Some((addr, _kind))) => {
+                            let ok = if packet[0] == b'Z' {
+                                stub.InsertBreakpoint(&vcpu, addr)
+                            } else {
+                                stub.RemoveBreakpoint(&vcpu, addr)
+                            };
+                            if ok {
+                                "OK"
+                            } else {
+                                "E14"
+                            }
+                        }
+                        _ => "E01",
+                    };
+                    self.Send(reply)?;
+                }
+                b'H' => {
+                    // Hg<thread> selects the vcpu of the register and memory access,
+                    // the resume packets always resume all the vcpus
+                    if args.len() > 1 && args[0] == b'g' {
+                        if let Some(tid) = ParseHex(&args[1..]) {
+                            if tid > 0 {
+                                self.curVcpu = tid as usize - 1;
+                            }
+                        }
+                    }
+                    self.Send("OK")?;
+                }
+                b'T' => {
+                    let alive = match ParseHex(args) {
+                        Some(tid) => tid > 0 && (tid as usize) <= GdbStub::Vcpus().len(),
+                        None => false,
+                    };
+                    self.Send(if alive { "OK" } else { "E01" })?;
+                }
+                b'c' => self.Continue(stub, false)?,
+                b's' => self.Continue(stub, true)?,
+                b'D' => {
+                    self.Send("OK")?;
+                    return Ok(());
+                }
+                b'k' => return Ok(()),
+                b'q' => {
+                    let query = String::from_utf8_lossy(args).into_owned();
+                    if query.starts_with("Supported") {
+                        self.Send("PacketSize=4000;swbreak+")?;
+                    } else if query == "Attached" {
+                        self.Send("1")?;
+                    } else if query == "C" {
+                        let reply = format!("QC{:x}", self.curVcpu + 1);
+                        self.Send(&reply)?;
+                    } else if query == "fThreadInfo" {
+                        let tids: Vec<String> = (0..GdbStub::Vcpus().len())
+                            .map(|i| format!("{:x}", i + 1))
+                            .collect();
+                        let reply = format!("m{}", tids.join(","));
+                        self.Send(&reply)?;
+                    } else if query == "sThreadInfo" {
+                        self.Send("l")?;
+                    } else {
+                        self.Send("")?;
+                    }
+                }
+                _ => self.Send("")?,
+            }
+        }
+    }
+}
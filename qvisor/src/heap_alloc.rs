@@ -4,10 +4,17 @@ use core::sync::atomic::AtomicU64;
 use core::sync::atomic::Ordering;
 use libc;
 
+use super::qlib::config::HugePageBacking;
 use super::qlib::linux_def::MemoryDef;
 use super::qlib::mem::list_allocator::*;
+use super::util::HostRandU64;
 
 pub const KERNEL_HEAP_ORD: usize = 33; // 8GB
+// HEAP_RAND_MAX is the range of the random heap base, kept in 2MB steps so
+// the heap stays hugepage aligned
+pub const HEAP_RAND_MAX: u64 = 256 * MemoryDef::ONE_MB;
+// BOOT_HEAP_RAND_MAX is the range of the random boot heap base, in 4KB steps
+pub const BOOT_HEAP_RAND_MAX: u64 = 64 * MemoryDef::ONE_MB;
 pub const ENABLE_HUGEPAGE: bool = false;
 
 impl HostAllocator {
@@ -15,16 +22,14 @@ impl HostAllocator {
         return Self {
             listHeapAddr: AtomicU64::new(MemoryDef::HEAP_OFFSET),
             initialized: AtomicBool::new(false),
+            vcpuCache: AtomicBool::new(false),
         };
     }
 
     pub fn Init(&self) {
         let heapSize = MemoryDef::HEAP_SIZE as usize;
         let addr = unsafe {
-            let mut flags = libc::MAP_PRIVATE | libc::MAP_ANON | libc::MAP_FIXED;
-            if ENABLE_HUGEPAGE {
-                flags |= libc::MAP_HUGE_2MB;
-            }
+            let flags = libc::MAP_PRIVATE | libc::MAP_ANON | libc::MAP_FIXED;
             libc::mmap(
                 self.listHeapAddr.load(Ordering::Relaxed) as _,
                 heapSize,
@@ -48,12 +53,118 @@ impl HostAllocator {
 
         *self.Allocator() = ListAllocator::Empty();
 
-        // reserve first 4KB gor the listAllocator
+        // reserve first 4KB gor the listAllocator, the heap above the boot
+        // part is added by InitHeap
         let size = core::mem::size_of::<ListAllocator>();
-        self.Allocator().Add(addr as usize + size, heapSize - size);
+        let bootSize = MemoryDef::HEAP_BOOT_SIZE as usize;
+        let skip = Self::BootHeapSkip() as usize;
+        self.Allocator()
+            .Add(addr as usize + size + skip, bootSize - size - skip);
         self.initialized.store(true, Ordering::Relaxed);
     }
 
+    // BootHeapSkip returns the random part at the start of the boot heap which
+    // is left out. It is done whatever the KernelASLR config is, the config is
+    // loaded after the first allocation. The host getrandom is called directly
+    // as nothing can log before the allocator is ready.
+    fn BootHeapSkip() -> u64 {
+        let mut val: u64 = 0;
+        let ret = unsafe { libc::getrandom(&mut val as *mut _ as *mut libc::c_void, 8, 0) };
+        if ret != 8 {
+            return 0;
+        }
+
+        return val % (BOOT_HEAP_RAND_MAX / MemoryDef::PAGE_SIZE) * MemoryDef::PAGE_SIZE;
+    }
+
+    // InitHeap adds the heap above the boot part to the allocator. It is called
+    // once the config is loaded, and the part is still untouched so that it can
+    // be mapped again on the hugepages. With randomize, a random part at the
+    // start is left out so the guest kernel objects aren't at fixed addresses.
+    pub fn InitHeap(&self, backing: HugePageBacking, randomize: bool) {
+        let start = MemoryDef::HEAP_OFFSET + MemoryDef::HEAP_BOOT_SIZE;
+        let len = MemoryDef::HEAP_SIZE - MemoryDef::HEAP_BOOT_SIZE;
+
+        let hugeTlbFlag = match backing {
+            HugePageBacking::HugeTlb2M => libc::MAP_HUGE_2MB,
+            HugePageBacking::HugeTlb1G => libc::MAP_HUGE_1GB,
+            _ => 0,
+        };
+
+        let mut thp = backing != HugePageBacking::None;
+        if hugeTlbFlag != 0 {
+            let flags = libc::MAP_PRIVATE
+                | libc::MAP_ANON
+                | libc::MAP_FIXED
+                | libc::MAP_HUGETLB
+                | hugeTlbFlag;
+            let addr = unsafe {
+                libc::mmap(
+                    start as _,
+                    len as usize,
+                    libc::PROT_READ | libc::PROT_WRITE,
+                    flags,
+                    -1,
+                    0,
+                ) as u64
+            };
+
+            // without MAP_NORESERVE the hugetlb pages are reserved by the mmap,
+            // so a short pool fails here instead of with a SIGBUS at the first
+            // touch. The failed mmap has already unmapped the range, it is
+            // mapped again with the 4KB pages.
+            if addr == libc::MAP_FAILED as u64 {
+                error!(
+                    "InitHeap {:?} fail with errno {}, fall back to Thp",
+                    backing,
+                    errno::errno().0
+                );
+
+                let addr = unsafe {
+                    libc::mmap(
+                        start as _,
+                        len as usize,
+                        libc::PROT_READ | libc::PROT_WRITE,
+                        libc::MAP_PRIVATE | libc::MAP_ANON | libc::MAP_FIXED,
+                        -1,
+                        0,
+                    ) as u64
+                };
+
+                if addr == libc::MAP_FAILED as u64 {
+                    panic!("InitHeap: failed to map the heap again after the hugetlb failure");
+                }
+            } else {
+                thp = false;
+            }
+        }
+
+        if thp {
+            let ret = unsafe {
+                libc::madvise(
+                    MemoryDef::HEAP_OFFSET as _,
+                    MemoryDef::HEAP_SIZE as usize,
+                    libc::MADV_HUGEPAGE,
+                )
+            };
+            if ret < 0 {
+                error!(
+                    "InitHeap madvise MADV_HUGEPAGE fail with errno {}",
+                    errno::errno().0
+                );
+            }
+        }
+
+        let skip = if randomize {
+            HostRandU64() % (HEAP_RAND_MAX / MemoryDef::HUGE_PAGE_SIZE) * MemoryDef::HUGE_PAGE_SIZE
+        } else {
+            0
+        };
+
+        self.Allocator()
+            .Add((start + skip) as usize, (len - skip) as usize);
+    }
+
     pub fn Clear(&self) -> bool {
         return self.Allocator().Free();
     }
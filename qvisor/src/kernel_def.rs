@@ -304,6 +304,8 @@ impl HostSpace {
 #[inline]
 pub fn child_clone(_userSp: u64) {}
 
+pub fn StartRestoredTask() {}
+
 pub fn InitX86FPState(_data: u64, _useXsave: bool) {}
 
 #[inline]
@@ -35,13 +35,18 @@ use super::qlib::task_mgr::*;
 use super::qlib::GetTimeCall;
 //use super::qlib::kernel::TSC;
 use super::amd64_def::*;
+use super::crash_dump::*;
+use super::gdbstub::*;
+use super::numa::HostNuma;
 use super::qlib::buddyallocator::ZeroPage;
+use super::qlib::kernel::crash::*;
 use super::qlib::kernel::IOURING;
 use super::qlib::vcpu_mgr::*;
 use super::qlib::*;
 use super::runc::runtime::vm::*;
 use super::URING_MGR;
-use crate::qlib::cpuid::XSAVEFeature::{XSAVEFeatureBNDCSR, XSAVEFeatureBNDREGS};
+use crate::qlib::cpuid::HostID;
+use crate::qlib::cpuid::XSAVEFeature::{XSAVEFeatureBNDCSR, XSAVEFeatureBNDREGS, XSAVEFeaturePKRU};
 use crate::qlib::kernel::asm::xgetbv;
 
 #[repr(C)]
@@ -220,7 +225,7 @@ impl KVMVcpu {
 
         let KernelCodeSegment = SegmentDescriptor::default().SetCode64(0, 0, 0);
         let KernelDataSegment = SegmentDescriptor::default().SetData(0, 0xffffffff, 0);
-        let _UserCodeSegment32 = SegmentDescriptor::default().SetCode64(0, 0, 3);
+        let UserCodeSegment32 = SegmentDescriptor::default().SetCode32(0, 0xffffffff, 3);
         let UserDataSegment = SegmentDescriptor::default().SetData(0, 0xffffffff, 3);
         let UserCodeSegment64 = SegmentDescriptor::default().SetCode64(0, 0, 3);
 
@@ -242,6 +247,7 @@ impl KVMVcpu {
         gdtTbl[2] = KernelDataSegment.AsU64();
         gdtTbl[3] = UserDataSegment.AsU64();
         gdtTbl[4] = UserCodeSegment64.AsU64();
+        gdtTbl[SEG_UCODE32 as usize] = UserCodeSegment32.AsU64();
 
         let stack_end = x86_64::VirtAddr::from_ptr(
             (self.tssIntStackStart + MemoryDef::INTERRUPT_STACK_PAGES * MemoryDef::PAGE_SIZE)
@@ -304,6 +310,13 @@ impl KVMVcpu {
         vcpu_sregs.cr4 =
             CR4_PAE | CR4_PGE | CR4_OSFXSR | CR4_OSXMMEXCPT | CR4_FSGSBASE | CR4_OSXSAVE; // | CR4_UMIP ;// CR4_PSE | | CR4_SMEP | CR4_SMAP;
 
+        // enable the memory protection keys when the host kernel uses them and
+        // the pkru is in the xsave area
+        let (_, _, ecx, _) = HostID(7, 0);
+        if ecx & (1 << 4) != 0 && xgetbv() & XSAVEFeaturePKRU as u64 != 0 {
+            vcpu_sregs.cr4 |= CR4_PKE;
+        }
+
         vcpu_sregs.efer = EFER_LME | EFER_LMA | EFER_SCE | EFER_NX;
 
         vcpu_sregs.idt = kvm_bindings::kvm_dtable {
@@ -451,6 +464,10 @@ impl KVMVcpu {
 
         let coreid = core_affinity::CoreId { id: self.cordId };
         core_affinity::set_for_current(coreid);
+        let hostNode = VMS.lock().HostNumaNode(self.cordId);
+        if let Some(node) = hostNode {
+            HostNuma::BindThread(node);
+        }
 
         info!(
             "start enter guest[{}]: entry is {:x}, stack is {:x}",
@@ -462,6 +479,19 @@ impl KVMVcpu {
             }
 
             self.state.store(1, Ordering::SeqCst);
+            // the state must be set before the check, so that the gdb stub either
+            // sees the vcpu in the guest or the vcpu sees the stop request
+            if GDB_STUB.Stopping() {
+                self.state.store(2, Ordering::SeqCst);
+                GDB_STUB.VcpuStop(self.id, None);
+                continue;
+            }
+
+            if CRASH_DUMP.Stopping() {
+                self.state.store(2, Ordering::SeqCst);
+                CRASH_DUMP.VcpuPark();
+            }
+
             let kvmRet = match self.vcpu.run() {
                 Ok(ret) => ret,
                 Err(e) => {
@@ -582,8 +612,19 @@ impl KVMVcpu {
                             let vcpu_regs = self.vcpu.get_regs().unwrap();
                             let addr = vcpu_regs.rbx;
                             let msg = unsafe { &*(addr as *const Print) };
+                            let info = if vcpu_regs.rcx == 0 {
+                                None
+                            } else {
+                                Some(unsafe { &*(vcpu_regs.rcx as *const CrashInfo) })
+                            };
 
                             eprintln!("Application error: {}", msg.str);
+                            if CRASH_DUMP.Enabled() {
+                                match CRASH_DUMP.Write(self.id, msg.str, info) {
+                                    Ok(path) => eprintln!("crash dump is written to {}", path),
+                                    Err(e) => eprintln!("crash dump write fail {:?}", e),
+                                }
+                            }
                             ::std::process::exit(1);
                         }
 
@@ -749,6 +790,13 @@ impl KVMVcpu {
                                 qmsg.ret = Self::qCall(qmsg.msg);
                             }
 
+                            CRASH_DUMP.RecordQcall(
+                                self.id,
+                                qmsg.taskId.Addr(),
+                                qmsg.msg,
+                                qmsg.ret,
+                            );
+
                             SHARE_SPACE.IncrHostProcessor();
 
                             Self::GuestMsgProcess(&SHARE_SPACE);
@@ -799,6 +847,15 @@ impl KVMVcpu {
                     info!("get fail entry***********************************");
                     break;
                 }
+                VcpuExit::Debug(arch) => {
+                    let reason = if arch.exception == 1 {
+                        // #DB of the single step
+                        StopReason::Step
+                    } else {
+                        StopReason::Breakpoint
+                    };
+                    GDB_STUB.VcpuStop(self.id, Some(reason));
+                }
                 VcpuExit::Exception => {
                     info!("get exception");
                 }
@@ -65,20 +65,26 @@ pub mod print;
 
 pub mod amd64_def;
 pub mod console;
+mod crash_dump;
 pub mod elf_loader;
+mod gdbstub;
 pub mod heap_alloc;
 pub mod kernel_def;
 mod kvm_vcpu;
 mod memmgr;
 pub mod namespace;
+mod numa;
 mod qcall;
 pub mod qlib;
 pub mod rdma_def;
 pub mod runc;
+mod seccomp;
 mod syncmgr;
 pub mod ucall;
 pub mod unix_socket_def;
 pub mod util;
+mod vcpu_gang;
+mod vcpu_scaler;
 mod vmspace;
 
 use alloc::sync::Arc;
@@ -151,6 +157,25 @@ pub fn InitSingleton() {
     self::qlib::InitSingleton();
 }
 
+// containerd starts the shim v2 binary by the runtime name, e.g.
+// "io.containerd.quark.v2" runs "containerd-shim-quark-v2". The binary is the
+// quark binary installed under that name.
+pub const SHIM_V2_BINARY_PREFIX: &str = "containerd-shim-";
+pub const SHIM_V2_BINARY_VERSION: &str = "v2";
+
+// ShimV2RuntimeId returns the runtime id containerd started the binary for,
+// or None if the binary isn't named as a shim v2
+fn ShimV2RuntimeId(arg0: &str) -> Option<String> {
+    let name = std::path::Path::new(arg0).file_name()?.to_string_lossy();
+    let name = name.strip_prefix(SHIM_V2_BINARY_PREFIX)?;
+    let (runtime, version) = name.rsplit_once('-')?;
+    if version != SHIM_V2_BINARY_VERSION || runtime.len() == 0 || runtime.contains('.') {
+        return None;
+    }
+
+    return Some(format!("io.containerd.{}.{}", runtime, version));
+}
+
 #[global_allocator]
 static ALLOCATOR: HostAllocator = HostAllocator::New();
 
@@ -158,11 +183,13 @@ fn main() {
     InitSingleton();
 
     let cmd;
+    let shimV2;
 
     {
         let mut str = "".to_string();
         let args: Vec<String> = env::args().collect();
         cmd = args[1].clone();
+        shimV2 = ShimV2RuntimeId(&args[0]);
         for s in &args {
             str.push_str(s);
             str.push_str(" ");
@@ -170,8 +197,21 @@ fn main() {
         info!("commandline args is {}", str);
     }
 
+    // only the sandbox process backs the guest heap with the hugepages and
+    // randomizes its base
+    let (hugePage, randomize) = if &cmd == "boot" {
+        let config = QUARK_CONFIG.lock();
+        (config.HugePage, config.KernelASLR)
+    } else {
+        (HugePageBacking::None, false)
+    };
+    ALLOCATOR.InitHeap(hugePage, randomize);
+
     let shimMode = QUARK_CONFIG.lock().ShimMode;
-    if shimMode == true && &cmd != "boot" {
+    if shimV2.is_some() && &cmd != "boot" {
+        error!("*********shim v2 mode***************");
+        containerd_shim::run::<Service>(&shimV2.unwrap(), None)
+    } else if shimMode == true && &cmd != "boot" {
         error!("*********shim mode***************");
         containerd_shim::run::<Service>("io.containerd.empty.v1", None)
     } else {
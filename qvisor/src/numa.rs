@@ -0,0 +1,167 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fs;
+
+use super::qlib::linux_def::*;
+use super::qlib::numa::*;
+
+// With NumaAware, the vcpus are placed on the host cores ordered by the numa
+// nodes so that the vcpus with the adjacent ids share a node. A vcpu thread
+// prefers the memory of its node, the guest heap is shared by all the vcpus
+// and is interleaved on their nodes. The guest gets a numa node for each host
+// node with vcpus.
+
+pub const NODE_SYSFS_DIR: &str = "/sys/devices/system/node";
+
+// the mempolicy modes and flags of linux
+pub const MPOL_PREFERRED: i32 = 1;
+pub const MPOL_INTERLEAVE: i32 = 3;
+pub const MPOL_MF_MOVE: u32 = 1 << 1;
+
+#[derive(Debug)]
+pub struct HostNode {
+    pub id: usize,
+    pub cpus: Vec<usize>,
+}
+
+#[derive(Debug)]
+pub struct HostNuma {
+    pub nodes: Vec<HostNode>,
+    // cores are the host cores ordered by the nodes
+    pub cores: Vec<usize>,
+}
+
+impl HostNuma {
+    // Load reads the host nodes from the sysfs. A host without numa has one
+    // node with all the cores.
+    pub fn Load() -> Self {
+        let mut nodes = Vec::new();
+        if let Ok(entries) = fs::read_dir(NODE_SYSFS_DIR) {
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                let id = match name.strip_prefix("node").map(|v| v.parse::<usize>()) {
+                    Some(Ok(id)) => id,
+                    _ => continue,
+                };
+
+                let cpus = match fs::read_to_string(entry.path().join("cpulist")) {
+                    Ok(s) => ParseCpuList(&s),
+                    Err(_) => continue,
+                };
+
+                // skip the memory only nodes
+                if cpus.len() == 0 {
+                    continue;
+                }
+
+                nodes.push(HostNode { id: id, cpus: cpus });
+            }
+        }
+
+        if nodes.len() == 0 {
+            nodes.push(HostNode {
+                id: 0,
+                cpus: (0..num_cpus::get()).collect(),
+            });
+        }
+
+        nodes.sort_by_key(|n| n.id);
+        let cores = nodes.iter().flat_map(|n| n.cpus.iter().cloned()).collect();
+        return Self {
+            nodes: nodes,
+            cores: cores,
+        };
+    }
+
+    pub fn NodeOf(&self, core: usize) -> usize {
+        for node in &self.nodes {
+            if node.cpus.contains(&core) {
+                return node.id;
+            }
+        }
+
+        return 0;
+    }
+
+    // GuestTopology returns the guest topology of the vcpus on the host nodes,
+    // the host node ids are numbered again from 0
+    pub fn GuestTopology(vcpuHostNode: &[usize]) -> NumaTopology {
+        let mut hostNodes = vcpuHostNode.to_vec();
+        hostNodes.sort();
+        hostNodes.dedup();
+
+        let vcpuNode = vcpuHostNode
+            .iter()
+            .map(|n| hostNodes.iter().position(|h| h == n).unwrap())
+            .collect();
+        return NumaTopology { vcpuNode: vcpuNode };
+    }
+
+    fn NodeMask(nodes: &[usize]) -> Vec<u64> {
+        let max = nodes.iter().max().cloned().unwrap_or(0);
+        let mut mask = vec![0u64; max / 64 + 1];
+        for n in nodes {
+            mask[n / 64] |= 1 << (n % 64);
+        }
+
+        return mask;
+    }
+
+    // BindHeap interleaves the guest heap on the nodes, the pages already
+    // touched are moved.
+    pub fn BindHeap(nodes: &[usize]) {
+        let mask = Self::NodeMask(nodes);
+        let ret = unsafe {
+            libc::syscall(
+                libc::SYS_mbind,
+                MemoryDef::HEAP_OFFSET,
+                MemoryDef::HEAP_SIZE,
+                MPOL_INTERLEAVE,
+                mask.as_ptr(),
+                mask.len() * 64 + 1,
+                MPOL_MF_MOVE,
+            )
+        };
+
+        if ret < 0 {
+            error!(
+                "HostNuma::BindHeap {:?} fail with errno {}",
+                nodes,
+                errno::errno().0
+            );
+        }
+    }
+
+    // BindThread makes the current thread prefer the memory of the node
+    pub fn BindThread(node: usize) {
+        let mask = Self::NodeMask(&[node]);
+        let ret = unsafe {
+            libc::syscall(
+                libc::SYS_set_mempolicy,
+                MPOL_PREFERRED,
+                mask.as_ptr(),
+                mask.len() * 64 + 1,
+            )
+        };
+
+        if ret < 0 {
+            error!(
+                "HostNuma::BindThread {} fail with errno {}",
+                node,
+                errno::errno().0
+            );
+        }
+    }
+}
@@ -16,18 +16,31 @@ use alloc::string::String;
 use chrono::prelude::*;
 use lazy_static::lazy_static;
 use spin::Mutex;
+use std::fs;
 use std::fs::File;
 use std::fs::OpenOptions;
 use std::io::Write;
 use std::os::unix::io::AsRawFd;
+use std::os::unix::io::FromRawFd;
 
+use super::qlib::common::*;
 use super::qlib::kernel::Timestamp;
 use super::qlib::kernel::IOURING;
+use super::qlib::linux_def::SysErr;
+use super::qlib::path::*;
 use super::qlib::ShareSpace;
+use super::runc::runtime::rootless::LogPath;
 use super::ThreadId;
 
 lazy_static! {
-    pub static ref LOG: Mutex<Log> = Mutex::new(Log::New());
+    pub static ref LOG: Mutex<Log> = Mutex::new(match Log::New() {
+        Ok(log) => log,
+        Err(e) => {
+            eprintln!("qvisor log open fail {:?}, logging to stderr", e);
+            Log::Stderr()
+        }
+    });
+    pub static ref AUDIT_LOG: Mutex<AuditLog> = Mutex::new(AuditLog::New());
 }
 
 pub struct Log {
@@ -44,16 +57,42 @@ pub fn SetSharespace(sharespace: &'static ShareSpace) {
     LOG.lock().shareSpace = sharespace;
 }
 
-pub const LOG_FILE_DEFAULT: &str = "/var/log/quark/quark.log";
-pub const LOG_FILE_FORMAT: &str = "/var/log/quark/{}.log";
+pub const LOG_FILE_DEFAULT: &str = "quark.log";
 pub const TIME_FORMAT: &str = "%H:%M:%S%.3f";
+
+// OpenLogFile opens the log file of the name in the log directory, which is
+// created if it doesn't exist
+fn OpenLogFile(name: &str) -> Result<File> {
+    let dir = LogPath();
+    fs::create_dir_all(&dir)
+        .map_err(|e| Error::IOError(format!("log create dir {} io::error is {:?}", &dir, e)))?;
+    let filename = Join(&dir, name);
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&filename)
+        .map_err(|e| Error::IOError(format!("log open {} io::error is {:?}", &filename, e)))?;
+    return Ok(file);
+}
+
 impl Log {
-    pub fn New() -> Self {
-        let file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(LOG_FILE_DEFAULT)
-            .expect("Log Open fail");
+    pub fn New() -> Result<Self> {
+        let file = OpenLogFile(LOG_FILE_DEFAULT)?;
+        return Ok(Self::NewWithFile(file));
+    }
+
+    // Stderr is the log of a qvisor which can't open its log file
+    pub fn Stderr() -> Self {
+        let fd = unsafe { libc::dup(2) };
+        let file = if fd >= 0 {
+            unsafe { File::from_raw_fd(fd) }
+        } else {
+            File::create("/dev/null").expect("Log open /dev/null fail")
+        };
+        return Self::NewWithFile(file);
+    }
+
+    fn NewWithFile(file: File) -> Self {
         return Self {
             file: file,
             syncPrint: true,
@@ -61,14 +100,9 @@ impl Log {
         };
     }
 
-    pub fn Reset(&mut self, name: &str) {
-        let filename = format!("/var/log/quark/{}.log", name);
-        let file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(filename)
-            .expect("Log Open fail");
-        self.file = file;
+    pub fn Reset(&mut self, name: &str) -> Result<()> {
+        self.file = OpenLogFile(&format!("{}.log", name))?;
+        return Ok(());
     }
 
     pub fn SetSharespace(&mut self, sharespace: &'static ShareSpace) {
@@ -130,6 +164,59 @@ impl Log {
     }
 }
 
+pub const AUDIT_FILE_DEFAULT: &str = "audit.log";
+
+// AuditLog stores the audit records from qkernel, one json record per line.
+// The file has to be opened before the sandbox pivots into the container rootfs.
+pub struct AuditLog {
+    pub filename: String,
+    pub file: Option<File>,
+}
+
+impl AuditLog {
+    pub fn New() -> Self {
+        return Self {
+            filename: Join(&LogPath(), AUDIT_FILE_DEFAULT),
+            file: None,
+        };
+    }
+
+    pub fn Reset(&mut self, name: &str) {
+        self.filename = Join(&LogPath(), &format!("{}.audit.log", name));
+        self.file = None;
+    }
+
+    pub fn Open(&mut self) -> Result<()> {
+        fs::create_dir_all(LogPath())
+            .map_err(|e| Error::Common(format!("AuditLog create dir fail {:?}", e)))?;
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.filename)
+            .map_err(|e| Error::Common(format!("AuditLog open {} fail {:?}", &self.filename, e)))?;
+        self.file = Some(file);
+        return Ok(());
+    }
+
+    // Write appends one record and returns the errno of the failure, e.g. when
+    // the disk is full. The record and its newline are written with one call so
+    // that a failed write doesn't leave a partial line.
+    pub fn Write(&mut self, record: &[u8]) -> Result<()> {
+        let file = match self.file.as_mut() {
+            None => return Err(Error::SysError(SysErr::EBADF)),
+            Some(f) => f,
+        };
+
+        let mut line = Vec::with_capacity(record.len() + 1);
+        line.extend_from_slice(record);
+        line.push(b'\n');
+        match file.write_all(&line) {
+            Ok(()) => return Ok(()),
+            Err(e) => return Err(Error::SysError(e.raw_os_error().unwrap_or(SysErr::EIO))),
+        }
+    }
+}
+
 #[macro_export]
 macro_rules! raw {
     // macth like arm for macro
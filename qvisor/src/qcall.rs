@@ -85,8 +85,13 @@ impl KVMVcpu {
                 ret = super::VMSpace::Fallocate(msg.fd, msg.mode, msg.offset, msg.len) as u64;
             }
             Msg::RenameAt(msg) => {
-                ret = super::VMSpace::RenameAt(msg.olddirfd, msg.oldpath, msg.newdirfd, msg.newpath)
-                    as u64;
+                ret = super::VMSpace::RenameAt(
+                    msg.olddirfd,
+                    msg.oldpath,
+                    msg.newdirfd,
+                    msg.newpath,
+                    msg.flags,
+                ) as u64;
             }
             Msg::Ftruncate(msg) => {
                 ret = super::VMSpace::Ftruncate(msg.fd, msg.len) as u64;
@@ -103,6 +108,9 @@ impl KVMVcpu {
             Msg::IoCtl(msg) => {
                 ret = super::VMSpace::IoCtl(msg.fd, msg.cmd, msg.argp) as u64;
             }
+            Msg::NvProxyIoctl(msg) => {
+                ret = super::VMSpace::NvProxyIoctl(msg.fd, msg.cmd, msg.argp) as u64;
+            }
             Msg::Fcntl(msg) => {
                 ret = super::VMSpace::Fcntl(msg.fd, msg.cmd, msg.arg) as u64;
             }
@@ -154,6 +162,18 @@ impl KVMVcpu {
             Msg::SyncFs(msg) => {
                 ret = super::VMSpace::SyncFs(msg.fd) as u64;
             }
+            Msg::InotifyInit(_msg) => {
+                ret = super::VMSpace::InotifyInit() as u64;
+            }
+            Msg::InotifyAddWatch(msg) => {
+                ret = super::VMSpace::InotifyAddWatch(msg.fd, msg.dirfd, msg.mask) as u64;
+            }
+            Msg::InotifyRmWatch(msg) => {
+                ret = super::VMSpace::InotifyRmWatch(msg.fd, msg.wd) as u64;
+            }
+            Msg::ChannelOpen(msg) => {
+                ret = super::VMSpace::ChannelOpen(msg.name, msg.fds) as u64;
+            }
             Msg::SyncFileRange(msg) => {
                 ret =
                     super::VMSpace::SyncFileRange(msg.fd, msg.offset, msg.nbytes, msg.flags) as u64;
@@ -222,6 +242,9 @@ impl KVMVcpu {
             Msg::GetRandom(msg) => {
                 ret = super::VMS.lock().GetRandom(msg.buf, msg.len, msg.flags) as u64;
             }
+            Msg::HostEntropy(msg) => {
+                ret = super::VMSpace::HostEntropy(msg.bits) as u64;
+            }
             Msg::Fchdir(msg) => {
                 ret = super::VMSpace::Fchdir(msg.fd) as u64;
             }
@@ -284,6 +307,12 @@ impl KVMVcpu {
             Msg::IOSendMsg(msg) => {
                 ret = super::VMSpace::IOSendMsg(msg.fd, msg.msghdr, msg.flags) as u64;
             }
+            Msg::IORecvMMsg(msg) => {
+                ret = super::VMSpace::IORecvMMsg(msg.fd, msg.msgvec, msg.vlen, msg.flags) as u64;
+            }
+            Msg::IOSendMMsg(msg) => {
+                ret = super::VMSpace::IOSendMMsg(msg.fd, msg.msgvec, msg.vlen, msg.flags) as u64;
+            }
             Msg::MMapFile(msg) => {
                 ret = match super::PMA_KEEPER.MapFile(msg.len, msg.prot, msg.fd, msg.offset) {
                     Err(Error::SysError(e)) => -e as u64,
@@ -337,6 +366,12 @@ impl KVMVcpu {
             Msg::Sysinfo(msg) => {
                 ret = super::VMSpace::Sysinfo(msg.addr) as u64;
             }
+            Msg::AuditLog(msg) => {
+                ret = super::VMSpace::AuditLog(msg.addr, msg.len) as u64;
+            }
+            Msg::BalloonRelease(msg) => {
+                ret = super::VMSpace::BalloonRelease(msg.addr, msg.count) as u64;
+            }
             Msg::ReadDir(msg) => {
                 ret = super::VMSpace::ReadDir(msg.dirfd, msg.data) as u64;
             }
@@ -23,10 +23,12 @@ use std::io::{BufRead, BufReader};
 use std::path::Path;
 use std::{thread, time};
 
+use super::super::qlib::cgroup::*;
 use super::super::qlib::common::*;
 use super::super::qlib::linux_def::*;
 use super::super::qlib::path::*;
 use super::oci::*;
+use super::runtime::rootless::IsRootless;
 use super::specutils::specutils::MkdirAll;
 
 pub const CONTROLLERS: [(&str, fn(spec: &LinuxResources, path: &str) -> Result<()>); 11] = [
@@ -47,6 +49,178 @@ pub const CONTROLLERS: [(&str, fn(spec: &LinuxResources, path: &str) -> Result<(
 
 pub const CGROUP_ROOT: &str = "/sys/fs/cgroup";
 
+// IsCgroupV2 returns whether the host uses the cgroup v2 unified hierarchy
+pub fn IsCgroupV2() -> bool {
+    return Path::new(&Join(CGROUP_ROOT, "cgroup.controllers")).exists();
+}
+
+// LimitsFromSpec converts the OCI resources into the cgroup v2 limits, which
+// are set on the host cgroup v2 and shown in the guest cgroupfs.
+pub fn LimitsFromSpec(res: &LinuxResources) -> CgroupLimits {
+    let mut limits = CgroupLimits::default();
+    if let Some(ref c) = res.cpu {
+        limits.CpuQuota = match c.quota {
+            Some(q) if q > 0 => q as u64,
+            _ => 0,
+        };
+        limits.CpuPeriod = c.period.unwrap_or(0);
+        limits.CpuWeight = CgroupLimits::SharesToWeight(c.shares.unwrap_or(0));
+        limits.Cpus = c.cpus.to_string();
+        limits.Mems = c.mems.to_string();
+    }
+
+    if let Some(ref m) = res.memory {
+        limits.MemoryMax = match m.limit {
+            Some(l) if l > 0 => l as u64,
+            _ => 0,
+        };
+        limits.MemoryLow = match m.reservation {
+            Some(r) if r > 0 => r as u64,
+            _ => 0,
+        };
+        // the spec swap limit is of the memory and the swap, -1 is unlimited
+        limits.SwapMax = match m.swap {
+            Some(s) if s > 0 && limits.MemoryMax > 0 => {
+                Some((s as u64).saturating_sub(limits.MemoryMax))
+            }
+            _ => None,
+        };
+    }
+
+    if let Some(ref b) = res.block_io {
+        AddIoMax(&mut limits.IoMax, &b.throttle_read_bps_device, |io, rate| {
+            io.rbps = rate
+        });
+        AddIoMax(&mut limits.IoMax, &b.throttle_write_bps_device, |io, rate| {
+            io.wbps = rate
+        });
+        AddIoMax(&mut limits.IoMax, &b.throttle_read_iops_device, |io, rate| {
+            io.riops = rate
+        });
+        AddIoMax(&mut limits.IoMax, &b.throttle_write_iops_device, |io, rate| {
+            io.wiops = rate
+        });
+    }
+
+    if let Some(ref p) = res.pids {
+        if p.limit > 0 {
+            limits.PidsMax = p.limit as u64;
+        }
+    }
+
+    return limits;
+}
+
+// AddIoMax merges the throttles of the devices into their io.max limits
+fn AddIoMax(ioMax: &mut Vec<IoMax>, devs: &[LinuxThrottleDevice], set: fn(&mut IoMax, u64)) {
+    for dev in devs {
+        let idx = match ioMax
+            .iter()
+            .position(|io| io.major == dev.major && io.minor == dev.minor)
+        {
+            Some(idx) => idx,
+            None => {
+                ioMax.push(IoMax {
+                    major: dev.major,
+                    minor: dev.minor,
+                    ..Default::default()
+                });
+                ioMax.len() - 1
+            }
+        };
+
+        set(&mut ioMax[idx], dev.rate);
+    }
+}
+
+// EnableControllers creates the ancestors of the cgroup v2 path and enables the
+// sandbox controllers in their cgroup.subtree_control, as a controller can only
+// be set in a cgroup if its parent enables it. A controller which the host
+// doesn't have is skipped.
+pub fn EnableControllers(path: &str) -> Result<()> {
+    let rel = path.trim_start_matches(CGROUP_ROOT);
+    let parts: Vec<&str> = rel.split('/').filter(|p| p.len() > 0).collect();
+
+    let mut dir = CGROUP_ROOT.to_string();
+    for part in parts {
+        MkdirAll(&dir)?;
+        for c in CGROUP_CONTROLLERS.split(' ') {
+            match SetValue(&dir, "cgroup.subtree_control", &format!("+{}", c)) {
+                Ok(()) => (),
+                Err(e) => info!("can't enable cgroup controller {} in {}: {:?}", c, &dir, e),
+            }
+        }
+        dir = Join(&dir, part);
+    }
+
+    return MkdirAll(&dir);
+}
+
+pub fn SetLimitsV2(limits: &CgroupLimits, path: &str) -> Result<()> {
+    if limits.CpuQuota > 0 || limits.CpuPeriod > 0 {
+        SetValue(path, "cpu.max", &limits.CpuMax())?;
+    }
+
+    if limits.CpuWeight > 0 {
+        SetValue(path, "cpu.weight", &limits.CpuWeight())?;
+    }
+
+    if limits.Cpus.len() > 0 {
+        SetValue(path, "cpuset.cpus", &limits.Cpus)?;
+    }
+
+    if limits.Mems.len() > 0 {
+        SetValue(path, "cpuset.mems", &limits.Mems)?;
+    }
+
+    if limits.MemoryLow > 0 {
+        SetValue(path, "memory.low", &limits.MemoryLow())?;
+    }
+
+    if limits.MemoryMax > 0 {
+        SetValue(path, "memory.max", &limits.MemoryMax())?;
+    }
+
+    if limits.SwapMax.is_some() {
+        SetValue(path, "memory.swap.max", &limits.SwapMax())?;
+    }
+
+    for io in &limits.IoMax {
+        SetValue(path, "io.max", &io.String())?;
+    }
+
+    if limits.PidsMax > 0 {
+        SetValue(path, "pids.max", &limits.PidsMax())?;
+    }
+
+    return Ok(());
+}
+
+// RemoveCgroupDir removes the cgroup directory. If we try to remove the
+// cgroup too soon after killing the sandbox we might get EBUSY, so we retry
+// for a few seconds until it succeeds.
+pub fn RemoveCgroupDir(path: &str) {
+    for i in 0..7 {
+        match fs::remove_dir(path) {
+            Ok(()) => break,
+            Err(e) => {
+                if let Some(errno) = e.raw_os_error() {
+                    if errno == SysErr::ENOENT {
+                        continue;
+                    }
+
+                    error!("can't uninstall ({:?}) failed: {:?}", path, e);
+                    break;
+                }
+            }
+        }
+
+        //sleep 2^i * 100 ms
+        let millies = time::Duration::from_millis(100 << i);
+        thread::sleep(millies);
+    }
+}
+
 pub fn SetOptionalValueInt(path: &str, name: &str, val: Option<i64>) -> Result<()> {
     let val = match val {
         None => return Ok(()),
@@ -256,12 +430,15 @@ impl<'a> Drop for CgroupCleanup<'a> {
 }
 
 // Cgroup represents a group inside all controllers. For example: Name='/foo/bar'
-// maps to /sys/fs/cgroup/<controller>/foo/bar on all controllers.
+// maps to /sys/fs/cgroup/<controller>/foo/bar on all controllers, or to
+// /sys/fs/cgroup/foo/bar with the cgroup v2 unified hierarchy.
 #[derive(Serialize, Deserialize, Debug, Default)]
 pub struct Cgroup {
     pub Name: String,
     pub Parents: BTreeMap<String, String>,
     pub Own: bool,
+    #[serde(default)]
+    pub V2: bool,
 }
 
 impl Cgroup {
@@ -270,6 +447,11 @@ impl Cgroup {
             return Ok(None);
         }
 
+        if IsRootless() {
+            info!("the cgroup is not created in rootless mode");
+            return Ok(None);
+        }
+
         let cgroupsPath = spec.linux.as_ref().unwrap().cgroups_path.to_string();
 
         let parents = if !IsAbs(&cgroupsPath) {
@@ -282,6 +464,7 @@ impl Cgroup {
             Name: cgroupsPath,
             Parents: parents,
             Own: false,
+            V2: IsCgroupV2(),
         }));
     }
 
@@ -289,6 +472,10 @@ impl Cgroup {
     // already exists, it means that the caller has already provided a
     // pre-configured cgroups, and 'res' is ignored.
     pub fn Install(&mut self, res: &Option<LinuxResources>) -> Result<()> {
+        if self.V2 {
+            return self.InstallV2(res);
+        }
+
         if Path::new(&self.MakePath("memory")).exists() {
             info!("Using pre-created cgroup {}", &self.Name);
             return Ok(());
@@ -320,38 +507,51 @@ impl Cgroup {
         return Ok(());
     }
 
+    // InstallV2 creates the cgroup v2 of the sandbox. The vcpu and io threads
+    // of the sandbox are in it as the sandbox process joins it before they
+    // are created.
+    fn InstallV2(&mut self, res: &Option<LinuxResources>) -> Result<()> {
+        let path = self.MakePath("");
+        if Path::new(&path).exists() {
+            info!("Using pre-created cgroup {}", &self.Name);
+            return Ok(());
+        }
+
+        info!("Creating cgroup v2 {}", &self.Name);
+        self.Own = true;
+
+        let mut cgroupCleanup = CgroupCleanup {
+            cgroup: self,
+            enable: true,
+        };
+
+        EnableControllers(&path)?;
+        match res {
+            None => (),
+            Some(ref res) => {
+                SetLimitsV2(&LimitsFromSpec(res), &path)?;
+            }
+        }
+
+        cgroupCleanup.enable = false;
+        return Ok(());
+    }
+
     pub fn Uninstall(&self) {
         if !self.Own {
             return;
         }
 
         info!("Deleting cgroup {}", &self.Name);
+        if self.V2 {
+            RemoveCgroupDir(&self.MakePath(""));
+            return;
+        }
+
         for c in &CONTROLLERS {
             let path = self.MakePath(c.0);
             info!("Removing cgroup controller for key={} path={}", &c.0, &path);
-
-            // If we try to remove the cgroup too soon after killing the
-            // sandbox we might get EBUSY, so we retry for a few seconds
-            // until it succeeds.
-            for i in 0..7 {
-                match fs::remove_dir(&path) {
-                    Ok(()) => break,
-                    Err(e) => {
-                        if let Some(errno) = e.raw_os_error() {
-                            if errno == SysErr::ENOENT {
-                                continue;
-                            }
-
-                            error!("can't uninstall ({:?}) failed: {:?}", path, e);
-                            break;
-                        }
-                    }
-                }
-
-                //sleep 2^i * 100 ms
-                let millies = time::Duration::from_millis(100 << i);
-                thread::sleep(millies);
-            }
+            RemoveCgroupDir(&path);
         }
     }
 
@@ -364,16 +564,29 @@ impl Cgroup {
         };
 
         let mut undoPaths = Vec::new();
-        //'outer:
-        for (ctrlr, path) in &paths {
-            for c in &CONTROLLERS {
-                if ctrlr == c.0 {
-                    let fullpath = Join(&Join(CGROUP_ROOT, ctrlr), path);
-                    undoPaths.push(fullpath);
-
-                    //break 'outer;
+        let mut joinPaths = Vec::new();
+        if self.V2 {
+            // the unified hierarchy has one cgroup for all the controllers
+            if let Some(path) = paths.get("") {
+                undoPaths.push(Join(CGROUP_ROOT, path));
+            }
+            joinPaths.push(self.MakePath(""));
+        } else {
+            //'outer:
+            for (ctrlr, path) in &paths {
+                for c in &CONTROLLERS {
+                    if ctrlr == c.0 {
+                        let fullpath = Join(&Join(CGROUP_ROOT, ctrlr), path);
+                        undoPaths.push(fullpath);
+
+                        //break 'outer;
+                    }
                 }
             }
+
+            for c in &CONTROLLERS {
+                joinPaths.push(self.MakePath(&c.0));
+            }
         }
 
         // Replace empty undo with the real thing before changes are made to cgroups.
@@ -388,8 +601,7 @@ impl Cgroup {
         };
 
         // Now join the cgroups.
-        for c in &CONTROLLERS {
-            let path = self.MakePath(&c.0);
+        for path in &joinPaths {
             info!("Joining cgroup {}", &path);
 
             match SetValue(&path, "cgroup.procs", "0") {
@@ -406,6 +618,11 @@ impl Cgroup {
 
     // NumCPU returns the number of CPUs configured in 'cpuset/cpuset.cpus'.
     pub fn NumCPU(&self) -> Result<usize> {
+        if self.V2 {
+            let cpuset = GetValue(&self.MakePath(""), "cpuset.cpus.effective")?;
+            return CountCpuset(cpuset.trim());
+        }
+
         let path = self.MakePath("cpuset");
         let cpuset = GetValue(&path, "cpuset.cpus")?;
         return CountCpuset(&cpuset);
@@ -413,6 +630,18 @@ impl Cgroup {
 
     // MemoryLimit returns the memory limit.
     pub fn MemoryLimit(&self) -> Result<u64> {
+        if self.V2 {
+            let limStr = GetValue(&self.MakePath(""), "memory.max")?;
+            let limStr = limStr.trim();
+            if limStr == "max" {
+                return Ok(u64::MAX);
+            }
+
+            return limStr
+                .parse::<u64>()
+                .map_err(|_| Error::Common(format!("MemoryLimit: can't parse {}", limStr)));
+        }
+
         let path = self.MakePath("memory");
         let limStr = GetValue(&path, "memory.limit_in_bytes")?;
         let limStr = limStr.trim();
@@ -422,7 +651,10 @@ impl Cgroup {
         )));
     }
 
+    // MakePath ignores the controllerName with cgroup v2, where the parent is
+    // the "" entry of /proc/self/cgroup.
     pub fn MakePath(&self, controllerName: &str) -> String {
+        let controllerName = if self.V2 { "" } else { controllerName };
         let mut path = self.Name.to_string();
         match self.Parents.get(controllerName) {
             None => (),
@@ -431,6 +663,10 @@ impl Cgroup {
             }
         }
 
+        if self.V2 {
+            return Join(CGROUP_ROOT, &path);
+        }
+
         return Join(CGROUP_ROOT, &Join(controllerName, &path));
     }
 }
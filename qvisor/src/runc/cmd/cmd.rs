@@ -22,6 +22,7 @@ use super::super::super::qlib::common::*;
 use super::super::super::qlib::config::*;
 use super::super::cmd::config::*;
 use super::super::runtime::loader::*;
+use super::super::runtime::rootless::*;
 use super::super::runtime::vm::*;
 use super::command::*;
 
@@ -53,6 +54,7 @@ impl CmdCmd {
     }
 
     pub fn Run(&self, _gCfg: &GlobalConfig) -> Result<()> {
+        CheckKvmAccess()?;
         let kvmfd = Kvm::open_with_cloexec(false).expect("can't open kvm");
 
         let mut args = Args::default();
@@ -16,21 +16,32 @@ use clap::{App, AppSettings, Arg};
 use std::env;
 
 use super::super::super::qlib::common::*;
+use super::super::runtime::rootless::*;
+use super::balloon::*;
 use super::boot::*;
+use super::checkpoint::*;
 use super::cmd::*;
 use super::config;
 use super::config::*;
 use super::create::*;
 use super::delete::*;
 use super::exec::*;
+use super::fsfreeze::*;
 use super::kill::*;
 use super::list::*;
+use super::metrics::*;
+use super::migrate::*;
 use super::pause::*;
 use super::ps::*;
+use super::restore::*;
 use super::resume::*;
 use super::run::*;
 use super::start::*;
 use super::state::*;
+use super::stream::*;
+use super::strace::*;
+use super::trace::*;
+use super::update_config::*;
 use super::wait::*;
 
 fn id_validator(val: String) -> core::result::Result<(), String> {
@@ -175,6 +186,16 @@ pub fn Parse() -> Result<Arguments> {
         .subcommand(KillCmd::SubCommand(&common))
         .subcommand(DeleteCmd::SubCommand(&common))
         .subcommand(StateCmd::SubCommand(&common))
+        .subcommand(StraceCmd::SubCommand(&common))
+        .subcommand(TraceCmd::SubCommand(&common))
+        .subcommand(UpdateConfigCmd::SubCommand(&common))
+        .subcommand(CheckpointCmd::SubCommand(&common))
+        .subcommand(RestoreCmd::SubCommand(&common))
+        .subcommand(MigrateCmd::SubCommand(&common))
+        .subcommand(BalloonCmd::SubCommand(&common))
+        .subcommand(FsFreezeCmd::SubCommand(&common))
+        .subcommand(MetricsCmd::SubCommand(&common))
+        .subcommand(StreamCmd::SubCommand(&common))
         .get_matches_from(get_args());
 
     let level = match matches.occurrences_of("v") {
@@ -189,7 +210,11 @@ pub fn Parse() -> Result<Arguments> {
     // create empty log file to avoid warning
     let logFile = matches.value_of("log").unwrap_or_default();
 
-    let rootDir = matches.value_of("r").unwrap().to_string();
+    let rootDir = if matches.occurrences_of("r") == 0 {
+        StateRootPath()
+    } else {
+        matches.value_of("r").unwrap().to_string()
+    };
     /*debug!("ensuring railcar state dir {}", &state_dir);
     let chain = || format!("ensuring railcar state dir {} failed", &state_dir);
     create_dir_all(&state_dir).chain_err(chain)?;*/
@@ -259,6 +284,46 @@ pub fn Parse() -> Result<Arguments> {
             config: gConfig,
             cmd: Command::StateCmd(StateCmd::Init(&cmd_matches)?),
         },
+        ("strace", Some(cmd_matches)) => Arguments {
+            config: gConfig,
+            cmd: Command::StraceCmd(StraceCmd::Init(&cmd_matches)?),
+        },
+        ("trace", Some(cmd_matches)) => Arguments {
+            config: gConfig,
+            cmd: Command::TraceCmd(TraceCmd::Init(&cmd_matches)?),
+        },
+        ("config", Some(cmd_matches)) => Arguments {
+            config: gConfig,
+            cmd: Command::UpdateConfigCmd(UpdateConfigCmd::Init(&cmd_matches)?),
+        },
+        ("checkpoint", Some(cmd_matches)) => Arguments {
+            config: gConfig,
+            cmd: Command::CheckpointCmd(CheckpointCmd::Init(&cmd_matches)?),
+        },
+        ("restore", Some(cmd_matches)) => Arguments {
+            config: gConfig,
+            cmd: Command::RestoreCmd(RestoreCmd::Init(&cmd_matches)?),
+        },
+        ("migrate", Some(cmd_matches)) => Arguments {
+            config: gConfig,
+            cmd: Command::MigrateCmd(MigrateCmd::Init(&cmd_matches)?),
+        },
+        ("balloon", Some(cmd_matches)) => Arguments {
+            config: gConfig,
+            cmd: Command::BalloonCmd(BalloonCmd::Init(&cmd_matches)?),
+        },
+        ("fsfreeze", Some(cmd_matches)) => Arguments {
+            config: gConfig,
+            cmd: Command::FsFreezeCmd(FsFreezeCmd::Init(&cmd_matches)?),
+        },
+        ("metrics", Some(cmd_matches)) => Arguments {
+            config: gConfig,
+            cmd: Command::MetricsCmd(MetricsCmd::Init(&cmd_matches)?),
+        },
+        ("stream", Some(cmd_matches)) => Arguments {
+            config: gConfig,
+            cmd: Command::StreamCmd(StreamCmd::Init(&cmd_matches)?),
+        },
         // We should never reach here because clap already enforces this
         _ => panic!("command not recognized"),
     };
@@ -288,6 +353,16 @@ pub enum Command {
     KillCmd(KillCmd),
     DeleteCmd(DeleteCmd),
     StateCmd(StateCmd),
+    StraceCmd(StraceCmd),
+    TraceCmd(TraceCmd),
+    UpdateConfigCmd(UpdateConfigCmd),
+    CheckpointCmd(CheckpointCmd),
+    RestoreCmd(RestoreCmd),
+    MigrateCmd(MigrateCmd),
+    BalloonCmd(BalloonCmd),
+    FsFreezeCmd(FsFreezeCmd),
+    MetricsCmd(MetricsCmd),
+    StreamCmd(StreamCmd),
 }
 
 pub fn Run(args: &mut Arguments) -> Result<()> {
@@ -306,5 +381,15 @@ pub fn Run(args: &mut Arguments) -> Result<()> {
         Command::KillCmd(cmd) => return cmd.Run(&mut args.config),
         Command::DeleteCmd(cmd) => return cmd.Run(&mut args.config),
         Command::StateCmd(cmd) => return cmd.Run(&mut args.config),
+        Command::StraceCmd(cmd) => return cmd.Run(&mut args.config),
+        Command::TraceCmd(cmd) => return cmd.Run(&mut args.config),
+        Command::UpdateConfigCmd(cmd) => return cmd.Run(&mut args.config),
+        Command::CheckpointCmd(cmd) => return cmd.Run(&mut args.config),
+        Command::RestoreCmd(cmd) => return cmd.Run(&mut args.config),
+        Command::MigrateCmd(cmd) => return cmd.Run(&mut args.config),
+        Command::BalloonCmd(cmd) => return cmd.Run(&mut args.config),
+        Command::FsFreezeCmd(cmd) => return cmd.Run(&mut args.config),
+        Command::MetricsCmd(cmd) => return cmd.Run(&mut args.config),
+        Command::StreamCmd(cmd) => return cmd.Run(&mut args.config),
     }
 }
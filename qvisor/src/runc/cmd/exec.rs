@@ -31,6 +31,7 @@ use super::super::super::qlib::auth::cap_set::*;
 use super::super::super::qlib::auth::id::*;
 use super::super::super::qlib::common::*;
 use super::super::super::qlib::linux::time::*;
+use super::super::super::qlib::linux_def::WaitStatus;
 use super::super::cmd::config::*;
 use super::super::container::container::*;
 use super::super::oci::serialize::*;
@@ -190,19 +191,40 @@ impl ExecCmd {
             .about("Run a container");
     }
 
-    pub fn ArgsFromCLI(&mut self) -> Result<ExecArgs> {
-        let mut extraKGIDs = Vec::new();
-        for g in &self.extraKGIDs {
-            let kgid = match g.parse::<u32>() {
-                Err(e) => panic!("parsing gid: {} fail, err is {:?}", g, e),
-                Ok(id) => id,
+    // ExecCaps returns the caps of the container process with the caps given
+    // by --cap added
+    pub fn ExecCaps(&self, spec: &Spec) -> Result<TaskCaps> {
+        let mut caps = Capabilities(false, &spec.process.capabilities);
+        for name in &self.caps {
+            let name = name.to_uppercase();
+            let name = if name.starts_with("CAP_") {
+                name
+            } else {
+                format!("CAP_{}", name)
             };
 
+            let cap: LinuxCapabilityType = serde_json::from_str(&format!("\"{}\"", name))
+                .map_err(|_e| Error::Common(format!("unknown capability {}", name)))?;
+            let set = CapSet::New(cap as u64);
+            caps.BoundingCaps.0 |= set.0;
+            caps.EffectiveCaps.0 |= set.0;
+            caps.InheritableCaps.0 |= set.0;
+            caps.PermittedCaps.0 |= set.0;
+        }
+
+        return Ok(caps);
+    }
+
+    pub fn ArgsFromCLI(&mut self, spec: &Spec) -> Result<ExecArgs> {
+        let mut extraKGIDs = Vec::new();
+        for g in &self.extraKGIDs {
+            let kgid = g
+                .parse::<u32>()
+                .map_err(|e| Error::Common(format!("parsing gid: {} fail, err is {:?}", g, e)))?;
             extraKGIDs.push(KGID(kgid));
         }
 
-        //todo: handle capacities
-        let caps = TaskCaps::default();
+        let caps = self.ExecCaps(spec)?;
 
         let mut argv = Vec::new();
         argv.append(&mut self.argv);
@@ -210,21 +232,37 @@ impl ExecCmd {
         let mut envv = Vec::new();
         envv.append(&mut self.envv);
 
-        let ids: Vec<&str> = self.user.split(':').collect();
-        let uid = match ids[0].parse::<u32>() {
-            Err(e) => panic!("parsing uid: {} fail, err is {:?}", ids[1], e),
-            Ok(id) => id,
-        };
-
-        let gid = if ids.len() > 2 {
-            panic!("user's format should be <uid>[:<gid>]");
-        } else if ids.len() == 2 {
-            match ids[1].parse::<u32>() {
-                Err(e) => panic!("parsing gid: {} fail, err is {:?}", ids[1], e),
-                Ok(id) => id,
+        // the process runs as the user of the container without --user
+        let (uid, gid) = if self.user.len() == 0 {
+            if extraKGIDs.len() == 0 {
+                extraKGIDs = spec
+                    .process
+                    .user
+                    .additional_gids
+                    .iter()
+                    .map(|id| KGID(*id))
+                    .collect();
             }
+            (spec.process.user.uid, spec.process.user.gid)
         } else {
-            0
+            let ids: Vec<&str> = self.user.split(':').collect();
+            if ids.len() > 2 {
+                return Err(Error::Common(
+                    "user's format should be <uid>[:<gid>]".to_string(),
+                ));
+            }
+
+            let uid = ids[0].parse::<u32>().map_err(|e| {
+                Error::Common(format!("parsing uid: {} fail, err is {:?}", ids[0], e))
+            })?;
+            let gid = if ids.len() == 2 {
+                ids[1].parse::<u32>().map_err(|e| {
+                    Error::Common(format!("parsing gid: {} fail, err is {:?}", ids[1], e))
+                })?
+            } else {
+                0
+            };
+            (uid, gid)
         };
 
         if self.detach && self.terminal && self.consoleSocket.len() == 0 {
@@ -262,8 +300,7 @@ impl ExecCmd {
         let mut process: Process = deserialize(&self.processPath)
             .map_err(|e| Error::Common(format!("deserialize process with error {:?}", e)))?;
 
-        //todo: handle caps
-        let caps = TaskCaps::default();
+        let caps = Capabilities(false, &process.capabilities);
 
         let mut extraKGIDs: Vec<KGID> = Vec::with_capacity(process.user.additional_gids.len());
         extraKGIDs.append(
@@ -299,9 +336,9 @@ impl ExecCmd {
         });
     }
 
-    pub fn ParseArgs(&mut self) -> Result<ExecArgs> {
+    pub fn ParseArgs(&mut self, spec: &Spec) -> Result<ExecArgs> {
         if self.processPath.len() == 0 {
-            return self.ArgsFromCLI();
+            return self.ArgsFromCLI(spec);
         } else {
             return self.ArgsFromProcess();
         }
@@ -326,10 +363,10 @@ impl ExecCmd {
             }
         }
 
-        let mut execArgs = self.ParseArgs()?;
-
         let mut container = Container::Load(&gCfg.RootDir, &self.id)?;
 
+        let mut execArgs = self.ParseArgs(&container.Spec)?;
+
         if execArgs.WorkDir.len() == 0 {
             execArgs.WorkDir = container.Spec.process.cwd.to_string();
         }
@@ -338,11 +375,17 @@ impl ExecCmd {
             execArgs.Envv = ResolveEnvs(&[&container.Spec.process.env, &self.envv])?;
         }
 
-        //todo: handle caps
+        let status = WaitStatus(container.Execute(execArgs, self)?);
 
-        let _pid = container.Execute(execArgs, self)?;
-
-        return Ok(());
+        // exit with the status of the process, a signaled process exits with
+        // 128 + signo as in the shell
+        let code = if status.Signaled() {
+            128 + status.Signal()
+        } else {
+            status.ExitStatus()
+        };
+        info!("Container:: Exec exits with {}", code);
+        ::std::process::exit(code);
     }
 
     pub fn ExecAndWait(&self, gCfg: &GlobalConfig) -> Result<()> {
@@ -0,0 +1,60 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::string::String;
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+
+use super::super::super::qlib::common::*;
+use super::super::cmd::config::*;
+use super::super::container::container::*;
+use super::command::*;
+
+#[derive(Debug)]
+pub struct FsFreezeCmd {
+    pub id: String,
+    pub unfreeze: bool,
+}
+
+impl FsFreezeCmd {
+    pub fn Init(cmd_matches: &ArgMatches) -> Result<Self> {
+        return Ok(Self {
+            id: cmd_matches.value_of("id").unwrap().to_string(),
+            unfreeze: cmd_matches.is_present("unfreeze"),
+        });
+    }
+
+    pub fn SubCommand<'a, 'b>(common: &CommonArgs<'a, 'b>) -> App<'a, 'b> {
+        return SubCommand::with_name("fsfreeze")
+            .setting(AppSettings::ColoredHelp)
+            .arg(&common.id_arg)
+            .arg(
+                Arg::with_name("unfreeze")
+                    .long("unfreeze")
+                    .help("thaw the frozen filesystems so that the writes go on"),
+            )
+            .about("fsfreeze syncs the filesystems of a container and blocks the writes to them, e.g. before snapshotting its volumes");
+    }
+
+    pub fn Run(&self, gCfg: &GlobalConfig) -> Result<()> {
+        let container = Container::Load(&gCfg.RootDir, &self.id)?;
+        let count = container.FsFreeze(!self.unfreeze)?;
+        if self.unfreeze {
+            println!("thawed {} mounts", count);
+        } else {
+            println!("froze {} mounts", count);
+        }
+
+        return Ok(());
+    }
+}
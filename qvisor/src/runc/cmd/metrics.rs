@@ -0,0 +1,98 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+
+use super::super::super::qlib::common::*;
+use super::super::super::qlib::metric::*;
+use super::super::cmd::config::*;
+use super::super::container::container::*;
+use super::command::*;
+
+#[derive(Debug)]
+pub struct MetricsCmd {
+    pub id: String,
+    pub all: bool,
+    pub json: bool,
+}
+
+impl MetricsCmd {
+    pub fn Init(cmd_matches: &ArgMatches) -> Result<Self> {
+        return Ok(Self {
+            id: cmd_matches.value_of("id").unwrap().to_string(),
+            all: cmd_matches.is_present("all"),
+            json: cmd_matches.is_present("json"),
+        });
+    }
+
+    pub fn SubCommand<'a, 'b>(common: &CommonArgs<'a, 'b>) -> App<'a, 'b> {
+        return SubCommand::with_name("metrics")
+            .setting(AppSettings::ColoredHelp)
+            .arg(&common.id_arg)
+            .arg(
+                Arg::with_name("all")
+                    .long("all")
+                    .short("a")
+                    .help("show the metrics which are still 0"),
+            )
+            .arg(
+                Arg::with_name("json")
+                    .long("json")
+                    .help("print the metrics in json"),
+            )
+            .about("metrics shows the metrics of the sandbox kernel, e.g. the count and the latency histogram of each syscall");
+    }
+
+    pub fn Run(&self, gCfg: &GlobalConfig) -> Result<()> {
+        let container = Container::Load(&gCfg.RootDir, &self.id)?;
+        let metrics: Vec<MetricValue> = container
+            .Metrics()?
+            .into_iter()
+            .filter(|m| self.all || m.Value != 0)
+            .collect();
+
+        if self.json {
+            let output = serde_json::to_string_pretty(&metrics)
+                .map_err(|e| Error::Common(format!("metrics to json fail with {:?}", e)))?;
+            println!("{}", output);
+            return Ok(());
+        }
+
+        for m in &metrics {
+            if m.Buckets.len() == 0 {
+                println!("{} {}", m.Name, m.Value);
+                continue;
+            }
+
+            // the bucket i counts the values below 2^i
+            let mut buckets = Vec::new();
+            for (i, count) in m.Buckets.iter().enumerate() {
+                if *count == 0 {
+                    continue;
+                }
+
+                if i == HISTOGRAM_BUCKETS - 1 {
+                    buckets.push(format!("+inf:{}", count));
+                } else {
+                    buckets.push(format!("<{}:{}", 1u64 << i, count));
+                }
+            }
+            println!("{} {} [{}]", m.Name, m.Value, buckets.join(" "));
+        }
+
+        return Ok(());
+    }
+}
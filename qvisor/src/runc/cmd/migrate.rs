@@ -0,0 +1,106 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::string::String;
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+
+use super::super::super::qlib::common::*;
+use super::super::cmd::config::*;
+use super::super::container::container::*;
+use super::command::*;
+
+// the default max pre-copy rounds before the sandbox is paused
+pub const DEFAULT_MIGRATE_ROUNDS: usize = 5;
+// the default dirty page count under which the pre-copy stops
+pub const DEFAULT_DIRTY_THRESHOLD: u64 = 1024;
+
+#[derive(Debug)]
+pub struct MigrateCmd {
+    pub id: String,
+    pub dest: String,
+    pub tokenFile: String,
+    pub maxRounds: usize,
+    pub dirtyThreshold: u64,
+}
+
+impl MigrateCmd {
+    pub fn Init(cmd_matches: &ArgMatches) -> Result<Self> {
+        let maxRounds = match cmd_matches.value_of("max-rounds") {
+            None => DEFAULT_MIGRATE_ROUNDS,
+            Some(v) => v
+                .parse::<usize>()
+                .map_err(|e| Error::Common(format!("invalid max-rounds {}: {:?}", v, e)))?,
+        };
+
+        let dirtyThreshold = match cmd_matches.value_of("dirty-threshold") {
+            None => DEFAULT_DIRTY_THRESHOLD,
+            Some(v) => v
+                .parse::<u64>()
+                .map_err(|e| Error::Common(format!("invalid dirty-threshold {}: {:?}", v, e)))?,
+        };
+
+        return Ok(Self {
+            id: cmd_matches.value_of("id").unwrap().to_string(),
+            dest: cmd_matches.value_of("to").unwrap().to_string(),
+            tokenFile: cmd_matches.value_of("token-file").unwrap().to_string(),
+            maxRounds: maxRounds,
+            dirtyThreshold: dirtyThreshold,
+        });
+    }
+
+    pub fn SubCommand<'a, 'b>(common: &CommonArgs<'a, 'b>) -> App<'a, 'b> {
+        return SubCommand::with_name("migrate")
+            .setting(AppSettings::ColoredHelp)
+            .arg(&common.id_arg)
+            .arg(
+                Arg::with_name("to")
+                    .long("to")
+                    .takes_value(true)
+                    .required(true)
+                    .help("address of the destination, e.g. 10.0.0.2:7000"),
+            )
+            .arg(
+                Arg::with_name("token-file")
+                    .long("token-file")
+                    .takes_value(true)
+                    .required(true)
+                    .help("file of the token shared with the destination, which authenticates and encrypts the stream"),
+            )
+            .arg(
+                Arg::with_name("max-rounds")
+                    .long("max-rounds")
+                    .takes_value(true)
+                    .help("max pre-copy rounds before the container is paused, default 5"),
+            )
+            .arg(
+                Arg::with_name("dirty-threshold")
+                    .long("dirty-threshold")
+                    .takes_value(true)
+                    .help("stop the pre-copy when fewer pages are dirtied in a round, default 1024"),
+            )
+            .about("migrate streams the image of a running container to the destination with an iterative pre-copy, the container is left paused. The destination receives it with restore --listen");
+    }
+
+    pub fn Run(&self, gCfg: &GlobalConfig) -> Result<()> {
+        let mut container = Container::Load(&gCfg.RootDir, &self.id)?;
+        container.Migrate(
+            &self.dest,
+            &self.tokenFile,
+            self.maxRounds,
+            self.dirtyThreshold,
+        )?;
+
+        return Ok(());
+    }
+}
@@ -12,19 +12,29 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod balloon;
 pub mod boot;
+pub mod checkpoint;
 pub mod cmd;
 pub mod command;
 pub mod config;
 pub mod create;
 pub mod delete;
 pub mod exec;
+pub mod fsfreeze;
 pub mod kill;
 pub mod list;
+pub mod metrics;
+pub mod migrate;
 pub mod pause;
 pub mod ps;
+pub mod restore;
 pub mod resume;
 pub mod run;
 pub mod start;
 pub mod state;
+pub mod stream;
+pub mod strace;
+pub mod trace;
+pub mod update_config;
 pub mod wait;
@@ -0,0 +1,79 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use alloc::string::String;
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+
+use super::super::super::qlib::common::*;
+use super::super::cmd::config::*;
+use super::super::container::container::*;
+use super::command::*;
+
+#[derive(Debug)]
+pub struct RestoreCmd {
+    pub id: String,
+    pub imagePath: String,
+    pub listen: Option<String>,
+    pub tokenFile: String,
+}
+
+impl RestoreCmd {
+    pub fn Init(cmd_matches: &ArgMatches) -> Result<Self> {
+        return Ok(Self {
+            id: cmd_matches.value_of("id").unwrap().to_string(),
+            imagePath: cmd_matches.value_of("image-path").unwrap_or("").to_string(),
+            listen: cmd_matches.value_of("listen").map(|s| s.to_string()),
+            tokenFile: cmd_matches.value_of("token-file").unwrap_or("").to_string(),
+        });
+    }
+
+    pub fn SubCommand<'a, 'b>(common: &CommonArgs<'a, 'b>) -> App<'a, 'b> {
+        return SubCommand::with_name("restore")
+            .setting(AppSettings::ColoredHelp)
+            .arg(&common.id_arg)
+            .arg(
+                Arg::with_name("image-path")
+                    .long("image-path")
+                    .takes_value(true)
+                    .required_unless("listen")
+                    .help("directory of the checkpoint image"),
+            )
+            .arg(
+                Arg::with_name("listen")
+                    .long("listen")
+                    .takes_value(true)
+                    .requires("token-file")
+                    .help("receive the image from a migrate on the address, e.g. 0.0.0.0:7000"),
+            )
+            .arg(
+                Arg::with_name("token-file")
+                    .long("token-file")
+                    .takes_value(true)
+                    .help("file of the token shared with the migrate source, which authenticates and encrypts the stream"),
+            )
+            .about("restore recreates the processes of a checkpoint image in a running container");
+    }
+
+    pub fn Run(&self, gCfg: &GlobalConfig) -> Result<()> {
+        let mut container = Container::Load(&gCfg.RootDir, &self.id)?;
+        let pids = match &self.listen {
+            None => container.Restore(&self.imagePath)?,
+            Some(addr) => container.MigrateReceive(addr, &self.tokenFile)?,
+        };
+        for pid in pids {
+            println!("{}", pid);
+        }
+
+        return Ok(());
+    }
+}
@@ -0,0 +1,126 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+
+use super::super::super::qlib::common::*;
+use super::super::super::qlib::control_msg::*;
+use super::super::super::qlib::SysCallID;
+use super::super::cmd::config::*;
+use super::super::container::container::*;
+use super::command::*;
+
+// SyscallNr looks up the syscall number by name, e.g. "read" or "sys_read"
+pub fn SyscallNr(name: &str) -> Result<u64> {
+    if let Ok(nr) = name.parse::<u64>() {
+        if nr < SysCallID::maxsupport as u64 {
+            return Ok(nr);
+        }
+    }
+
+    let name = if name.starts_with("sys_") {
+        name.to_string()
+    } else {
+        format!("sys_{}", name)
+    };
+
+    for nr in 0..SysCallID::maxsupport as u64 {
+        let callId: SysCallID = unsafe { core::mem::transmute(nr) };
+        if format!("{:?}", callId) == name {
+            return Ok(nr);
+        }
+    }
+
+    return Err(Error::Common(format!("unknown syscall {}", name)));
+}
+
+#[derive(Debug)]
+pub struct StraceCmd {
+    pub id: String,
+    pub args: StraceArgs,
+}
+
+impl StraceCmd {
+    pub fn Init(cmd_matches: &ArgMatches) -> Result<Self> {
+        let mut pids = Vec::new();
+        if let Some(iter) = cmd_matches.values_of("pid") {
+            for pidStr in iter {
+                match pidStr.parse::<i32>() {
+                    Err(_e) => {
+                        return Err(Error::Common(format!(
+                            "pid {} cant not be parsed as int type",
+                            pidStr
+                        )))
+                    }
+                    Ok(v) => pids.push(v),
+                }
+            }
+        }
+
+        let mut syscalls = Vec::new();
+        if let Some(iter) = cmd_matches.values_of("syscall") {
+            for name in iter {
+                syscalls.push(SyscallNr(name)?);
+            }
+        }
+
+        return Ok(Self {
+            id: cmd_matches.value_of("id").unwrap().to_string(),
+            args: StraceArgs {
+                Enable: !cmd_matches.is_present("disable"),
+                Pids: pids,
+                Syscalls: syscalls,
+            },
+        });
+    }
+
+    pub fn SubCommand<'a, 'b>(common: &CommonArgs<'a, 'b>) -> App<'a, 'b> {
+        return SubCommand::with_name("strace")
+            .setting(AppSettings::ColoredHelp)
+            .arg(&common.id_arg)
+            .arg(
+                Arg::with_name("disable")
+                    .long("disable")
+                    .help("turn off the syscall tracing"),
+            )
+            .arg(
+                Arg::with_name("pid")
+                    .long("pid")
+                    .short("p")
+                    .takes_value(true)
+                    .multiple(true)
+                    .use_delimiter(true)
+                    .help("only trace the processes with the pids"),
+            )
+            .arg(
+                Arg::with_name("syscall")
+                    .long("syscall")
+                    .short("s")
+                    .takes_value(true)
+                    .multiple(true)
+                    .use_delimiter(true)
+                    .help("only trace the syscalls, e.g. read,write,openat"),
+            )
+            .about("strace turns on/off the syscall tracing of a running container");
+    }
+
+    pub fn Run(&self, gCfg: &GlobalConfig) -> Result<()> {
+        let container = Container::Load(&gCfg.RootDir, &self.id)?;
+        container.Strace(&self.args)?;
+
+        return Ok(());
+    }
+}
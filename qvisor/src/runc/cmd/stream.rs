@@ -0,0 +1,66 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::string::String;
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+
+use super::super::super::console::console::*;
+use super::super::super::qlib::common::*;
+use super::super::cmd::config::*;
+use super::super::container::container::*;
+use super::command::*;
+
+#[derive(Debug)]
+pub struct StreamCmd {
+    pub id: String,
+    pub socket: String,
+}
+
+impl StreamCmd {
+    pub fn Init(cmd_matches: &ArgMatches) -> Result<Self> {
+        return Ok(Self {
+            id: cmd_matches.value_of("id").unwrap().to_string(),
+            socket: cmd_matches.value_of("socket").unwrap().to_string(),
+        });
+    }
+
+    pub fn SubCommand<'a, 'b>(common: &CommonArgs<'a, 'b>) -> App<'a, 'b> {
+        return SubCommand::with_name("stream")
+            .setting(AppSettings::ColoredHelp)
+            .arg(&common.id_arg)
+            .arg(
+                Arg::with_name("socket")
+                    .help("path of the unix socket to serve the stream protocol on")
+                    .required(true)
+                    .takes_value(true)
+                    .long("socket"),
+            )
+            .about("stream forwards tcp ports from the host into a container");
+    }
+
+    pub fn Run(&mut self, gCfg: &GlobalConfig) -> Result<()> {
+        info!("Container:: Stream ....");
+        let container = Container::Load(&gCfg.RootDir, &self.id)?;
+
+        let pid = container.SandboxPid();
+        if pid < 0 {
+            return Err(Error::Common(format!(
+                "container {} is not running",
+                self.id
+            )));
+        }
+
+        return ServeStreams(&self.socket, None, pid);
+    }
+}
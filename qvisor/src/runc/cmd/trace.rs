@@ -0,0 +1,121 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::string::String;
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use libc::STDOUT_FILENO;
+use std::fs::OpenOptions;
+use std::os::unix::io::AsRawFd;
+
+use super::super::super::qlib::common::*;
+use super::super::super::qlib::control_msg::*;
+use super::super::cmd::config::*;
+use super::super::container::container::*;
+use super::command::*;
+
+#[derive(Debug)]
+pub struct TraceCmd {
+    pub id: String,
+    pub args: TraceArgs,
+    pub output: String,
+}
+
+impl TraceCmd {
+    pub fn Init(cmd_matches: &ArgMatches) -> Result<Self> {
+        let op = if cmd_matches.is_present("start") {
+            TraceOp::Start
+        } else if cmd_matches.is_present("stop") {
+            TraceOp::Stop
+        } else {
+            TraceOp::Dump
+        };
+
+        let ringSize = match cmd_matches.value_of("size") {
+            None => 0,
+            Some(sizeStr) => match sizeStr.parse::<usize>() {
+                Err(_e) => {
+                    return Err(Error::Common(format!(
+                        "size {} cant not be parsed as int type",
+                        sizeStr
+                    )))
+                }
+                Ok(v) => v,
+            },
+        };
+
+        return Ok(Self {
+            id: cmd_matches.value_of("id").unwrap().to_string(),
+            args: TraceArgs {
+                Op: op,
+                RingSize: ringSize,
+                fds: Vec::new(),
+            },
+            output: cmd_matches.value_of("output").unwrap_or("").to_string(),
+        });
+    }
+
+    pub fn SubCommand<'a, 'b>(common: &CommonArgs<'a, 'b>) -> App<'a, 'b> {
+        return SubCommand::with_name("trace")
+            .setting(AppSettings::ColoredHelp)
+            .arg(&common.id_arg)
+            .arg(
+                Arg::with_name("start")
+                    .long("start")
+                    .conflicts_with("stop")
+                    .help("drop the recorded spans and start recording"),
+            )
+            .arg(
+                Arg::with_name("stop")
+                    .long("stop")
+                    .help("stop recording, the recorded spans are kept for dump"),
+            )
+            .arg(
+                Arg::with_name("size")
+                    .long("size")
+                    .takes_value(true)
+                    .requires("start")
+                    .help("span event count kept for each vcpu"),
+            )
+            .arg(
+                Arg::with_name("output")
+                    .long("output")
+                    .short("o")
+                    .takes_value(true)
+                    .help("file to write the Chrome trace json, stdout by default"),
+            )
+            .about("trace records the kernel spans of a running container and dumps them in Chrome trace format");
+    }
+
+    pub fn Run(&self, gCfg: &GlobalConfig) -> Result<()> {
+        let container = Container::Load(&gCfg.RootDir, &self.id)?;
+
+        if self.args.Op != TraceOp::Dump || self.output.len() == 0 {
+            return container.Trace(&self.args, STDOUT_FILENO);
+        }
+
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&self.output)
+            .map_err(|e| {
+                Error::Common(format!(
+                    "open trace output {} fail with {:?}",
+                    self.output, e
+                ))
+            })?;
+
+        return container.Trace(&self.args, file.as_raw_fd());
+    }
+}
@@ -0,0 +1,162 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+
+use super::super::super::qlib::common::*;
+use super::super::super::qlib::config::{ConfigUpdate, DebugLevel, LogLevel, RUNTIME_CONFIG_FLAGS};
+use super::super::cmd::config::GlobalConfig;
+use super::super::container::container::*;
+use super::command::*;
+
+fn ParseDebugLevel(level: &str) -> Result<DebugLevel> {
+    let level = match level {
+        "Off" => DebugLevel::Off,
+        "Error" => DebugLevel::Error,
+        "Warn" => DebugLevel::Warn,
+        "Info" => DebugLevel::Info,
+        "Debug" => DebugLevel::Debug,
+        "Trace" => DebugLevel::Trace,
+        _ => return Err(Error::Common(format!("invalid debug level {}", level))),
+    };
+
+    return Ok(level);
+}
+
+fn ParseLogLevel(level: &str) -> Result<LogLevel> {
+    let level = match level {
+        "None" => LogLevel::None,
+        "Simple" => LogLevel::Simple,
+        "Complex" => LogLevel::Complex,
+        _ => return Err(Error::Common(format!("invalid log level {}", level))),
+    };
+
+    return Ok(level);
+}
+
+#[derive(Debug)]
+pub struct UpdateConfigCmd {
+    pub id: String,
+    pub update: ConfigUpdate,
+}
+
+impl UpdateConfigCmd {
+    pub fn Init(cmd_matches: &ArgMatches) -> Result<Self> {
+        let mut update = ConfigUpdate::default();
+
+        if let Some(level) = cmd_matches.value_of("debug-level") {
+            update.DebugLevel = Some(ParseDebugLevel(level)?);
+        }
+
+        if let Some(level) = cmd_matches.value_of("log-level") {
+            update.LogLevel = Some(ParseLogLevel(level)?);
+        }
+
+        let mut flags = Vec::new();
+        if let Some(iter) = cmd_matches.values_of("set") {
+            for kv in iter {
+                let parts: Vec<&str> = kv.splitn(2, '=').collect();
+                if parts.len() != 2 {
+                    return Err(Error::Common(format!(
+                        "invalid config {}, expect NAME=true|false",
+                        kv
+                    )));
+                }
+
+                let val = match parts[1].parse::<bool>() {
+                    Err(_e) => {
+                        return Err(Error::Common(format!(
+                            "config {} value {} cant not be parsed as bool",
+                            parts[0], parts[1]
+                        )))
+                    }
+                    Ok(v) => v,
+                };
+
+                flags.push((parts[0].to_string(), val));
+            }
+        }
+        update.Flags = flags;
+
+        if cmd_matches.is_present("trace-on") {
+            update.Trace = Some(true);
+        } else if cmd_matches.is_present("trace-off") {
+            update.Trace = Some(false);
+        }
+
+        return Ok(Self {
+            id: cmd_matches.value_of("id").unwrap().to_string(),
+            update: update,
+        });
+    }
+
+    pub fn SubCommand<'a, 'b>(common: &CommonArgs<'a, 'b>) -> App<'a, 'b> {
+        return SubCommand::with_name("config")
+            .setting(AppSettings::ColoredHelp)
+            .arg(&common.id_arg)
+            .arg(
+                Arg::with_name("debug-level")
+                    .long("debug-level")
+                    .takes_value(true)
+                    .possible_values(&["Off", "Error", "Warn", "Info", "Debug", "Trace"])
+                    .help("qkernel debug log level"),
+            )
+            .arg(
+                Arg::with_name("log-level")
+                    .long("log-level")
+                    .takes_value(true)
+                    .possible_values(&["None", "Simple", "Complex"])
+                    .help("qkernel syscall log level"),
+            )
+            .arg(
+                Arg::with_name("set")
+                    .long("set")
+                    .takes_value(true)
+                    .multiple(true)
+                    .use_delimiter(true)
+                    .help("set bool config, e.g. UringEpollCtl=true"),
+            )
+            .arg(
+                Arg::with_name("trace-on")
+                    .long("trace-on")
+                    .conflicts_with("trace-off")
+                    .help("start the span tracing"),
+            )
+            .arg(
+                Arg::with_name("trace-off")
+                    .long("trace-off")
+                    .help("stop the span tracing"),
+            )
+            .about("config changes the config of a running container and prints the result");
+    }
+
+    pub fn Run(&self, gCfg: &GlobalConfig) -> Result<()> {
+        for (name, _) in &self.update.Flags {
+            if !RUNTIME_CONFIG_FLAGS.contains(&name.as_str()) {
+                return Err(Error::Common(format!(
+                    "config {} can't be changed at runtime, the supported ones are {:?}",
+                    name, RUNTIME_CONFIG_FLAGS
+                )));
+            }
+        }
+
+        let container = Container::Load(&gCfg.RootDir, &self.id)?;
+        let config = container.UpdateConfig(&self.update)?;
+        println!("{:#?}", config);
+
+        return Ok(());
+    }
+}
@@ -21,14 +21,19 @@ use std::fs;
 use std::fs::File;
 use std::fs::OpenOptions;
 use std::io::Write;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::UnixStream;
 use std::path::Path;
+use std::thread;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use super::super::super::qlib::auth::cap_set::*;
 use super::super::super::qlib::auth::id::*;
 use super::super::super::qlib::common::*;
+use super::super::super::qlib::config::{Config, ConfigUpdate};
 use super::super::super::qlib::control_msg::*;
 use super::super::super::qlib::linux_def::*;
+use super::super::super::qlib::metric::MetricValue;
 use super::super::super::qlib::path::*;
 use super::super::super::ucall::ucall::*;
 //use super::super::super::qlib::util::*;
@@ -40,10 +45,14 @@ use super::super::oci::*;
 use super::super::sandbox::sandbox::*;
 use super::super::specutils::specutils::*;
 use super::hook::*;
+use super::migrate_channel::*;
 use super::status::*;
 
 use super::super::shim::container_io::*;
 
+// CHECKPOINT_IMAGE_FILE is the image file name in the checkpoint image path
+pub const CHECKPOINT_IMAGE_FILE: &str = "checkpoint.img";
+
 // metadataFilename is the name of the metadata file relative to the
 // container root directory that holds sandbox metadata.
 const METADATA_FILENAME: &str = "meta.json";
@@ -853,6 +862,183 @@ impl Container {
         return self.Save();
     }
 
+    // Checkpoint saves the image of the sandbox to imagePath. The container is
+    // left paused unless leaveRunning is set.
+    pub fn Checkpoint(&mut self, imagePath: &str, leaveRunning: bool) -> Result<()> {
+        info!("Checkpoint container {} to {}", self.ID, imagePath);
+
+        let _unlock = self.Lock()?;
+
+        self.RequireStatus("Checkpoint", &[Status::Running])?;
+
+        fs::create_dir_all(imagePath)
+            .map_err(|e| Error::IOError(format!("Checkpoint create dir io::error is {:?}", e)))?;
+
+        let imageFile = Path::new(imagePath).join(CHECKPOINT_IMAGE_FILE);
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&imageFile)
+            .map_err(|e| Error::IOError(format!("Checkpoint open image io::error is {:?}", e)))?;
+
+        self.Sandbox
+            .as_ref()
+            .unwrap()
+            .Checkpoint(file.as_raw_fd(), leaveRunning, false, false)?;
+
+        if !leaveRunning {
+            self.changeStatus(Status::Paused);
+        }
+        return self.Save();
+    }
+
+    // Restore recreates the processes of the checkpoint image in imagePath in
+    // the running container and returns their pids.
+    pub fn Restore(&mut self, imagePath: &str) -> Result<Vec<i32>> {
+        info!("Restore container {} from {}", self.ID, imagePath);
+
+        let _unlock = self.Lock()?;
+
+        self.RequireStatus("Restore", &[Status::Running])?;
+
+        let imageFile = Path::new(imagePath).join(CHECKPOINT_IMAGE_FILE);
+        let file = OpenOptions::new()
+            .read(true)
+            .open(&imageFile)
+            .map_err(|e| Error::IOError(format!("Restore open image io::error is {:?}", e)))?;
+
+        return self.Sandbox.as_ref().unwrap().Restore(
+            &self.ID,
+            &self.Spec,
+            file.as_raw_fd(),
+            false,
+        );
+    }
+
+    // Migrate streams the image of the sandbox to the destination address with
+    // an iterative pre-copy. The memory is sent while the container keeps running,
+    // then the pages written during the previous round are sent again until they
+    // are fewer than dirtyThreshold or maxRounds is reached. At last the container
+    // is paused and the remaining dirty pages and the thread state are sent, so
+    // the pause time is bounded by the last round. The container is left paused.
+    // The stream is encrypted and authenticated with the token in tokenFile.
+    pub fn Migrate(
+        &mut self,
+        dest: &str,
+        tokenFile: &str,
+        maxRounds: usize,
+        dirtyThreshold: u64,
+    ) -> Result<()> {
+        info!("Migrate container {} to {}", self.ID, dest);
+
+        let _unlock = self.Lock()?;
+
+        self.RequireStatus("Migrate", &[Status::Running])?;
+
+        let token = ReadToken(tokenFile)?;
+        let mut channel = MigrateChannel::Connect(dest, &token)?;
+        let abort = channel.AbortHandle()?;
+
+        // the sandbox writes the images to one end of the socket pair, the
+        // relay seals them to the channel
+        let (mut local, remote) = UnixStream::pair()
+            .map_err(|e| Error::IOError(format!("Migrate socketpair io::error is {:?}", e)))?;
+        let relay = thread::spawn(move || channel.SendFrom(&mut local));
+
+        let res = self.MigrateRounds(remote.as_raw_fd(), maxRounds, dirtyThreshold);
+        if res.is_err() {
+            // the destination must not see the end of the image
+            MigrateChannel::Abort(&abort);
+        }
+
+        drop(remote);
+        let sent = match relay.join() {
+            Err(_) => Err(Error::Common("Migrate relay panic".to_string())),
+            Ok(sent) => sent,
+        };
+
+        res?;
+        let sent = sent?;
+        info!("Migrate container {} sent {} bytes", self.ID, sent);
+
+        self.changeStatus(Status::Paused);
+        return self.Save();
+    }
+
+    fn MigrateRounds(&self, fd: i32, maxRounds: usize, dirtyThreshold: u64) -> Result<()> {
+        let sandbox = self.Sandbox.as_ref().unwrap();
+        let mut pages = sandbox.Checkpoint(fd, false, true, false)?;
+        info!(
+            "Migrate container {} pre-copy round 0 sent {} pages",
+            self.ID, pages
+        );
+
+        let mut round = 1;
+        while round < maxRounds && pages > dirtyThreshold {
+            pages = sandbox.Checkpoint(fd, false, true, true)?;
+            info!(
+                "Migrate container {} pre-copy round {} sent {} pages",
+                self.ID, round, pages
+            );
+            round += 1;
+        }
+
+        let start = SystemTime::now();
+        pages = sandbox.Checkpoint(fd, false, false, true)?;
+        info!(
+            "Migrate container {} final round sent {} pages in {:?}",
+            self.ID,
+            pages,
+            start.elapsed().unwrap_or_default()
+        );
+
+        return Ok(());
+    }
+
+    // MigrateReceive accepts the image stream of a Migrate on addr and restores
+    // it in the running container. The source is authenticated with the token
+    // in tokenFile. The pages of the pre-copy rounds are staged in the guest
+    // memory, the stream is only complete after the MAC of the image is
+    // verified. It returns the pids of the restored processes.
+    pub fn MigrateReceive(&mut self, addr: &str, tokenFile: &str) -> Result<Vec<i32>> {
+        info!("MigrateReceive container {} on {}", self.ID, addr);
+
+        let _unlock = self.Lock()?;
+
+        self.RequireStatus("MigrateReceive", &[Status::Running])?;
+
+        let token = ReadToken(tokenFile)?;
+        let mut channel = MigrateChannel::Accept(addr, &token)?;
+
+        let (mut local, remote) = UnixStream::pair().map_err(|e| {
+            Error::IOError(format!("MigrateReceive socketpair io::error is {:?}", e))
+        })?;
+        let relay = thread::spawn(move || channel.ReceiveTo(&mut local));
+
+        let res =
+            self.Sandbox
+                .as_ref()
+                .unwrap()
+                .Restore(&self.ID, &self.Spec, remote.as_raw_fd(), true);
+
+        // the relay gets EPIPE if the sandbox stopped reading
+        drop(remote);
+        let received = match relay.join() {
+            Err(_) => Err(Error::Common("MigrateReceive relay panic".to_string())),
+            Ok(received) => received,
+        };
+
+        // the channel error explains the truncated stream of the restore
+        let received = received?;
+        let pids = res?;
+        info!(
+            "MigrateReceive container {} received {} bytes",
+            self.ID, received
+        );
+        return Ok(pids);
+    }
+
     pub fn Resume(&mut self) -> Result<()> {
         info!("Resume container {}", self.ID);
 
@@ -870,6 +1056,47 @@ impl Container {
         return self.Sandbox.as_ref().unwrap().Processes(&self.ID);
     }
 
+    // Strace changes the syscall tracing filter of the sandbox.
+    pub fn Strace(&self, args: &StraceArgs) -> Result<()> {
+        self.RequireStatus("strace", &[Status::Running, Status::Paused])?;
+        return self.Sandbox.as_ref().unwrap().Strace(args);
+    }
+
+    // Trace starts/stops the span recording of the sandbox or dumps the recorded
+    // spans to the outputFd.
+    pub fn Trace(&self, args: &TraceArgs, outputFd: i32) -> Result<()> {
+        self.RequireStatus("trace", &[Status::Running, Status::Paused])?;
+        return self.Sandbox.as_ref().unwrap().Trace(args, outputFd);
+    }
+
+    // UpdateConfig changes the runtime config of the sandbox and returns the
+    // updated config.
+    pub fn UpdateConfig(&self, update: &ConfigUpdate) -> Result<Config> {
+        self.RequireStatus("update config of", &[Status::Running, Status::Paused])?;
+        return self.Sandbox.as_ref().unwrap().UpdateConfig(update);
+    }
+
+    // Balloon inflates or deflates the memory balloon of the sandbox to
+    // targetSize bytes.
+    pub fn Balloon(&self, targetSize: u64) -> Result<BalloonInfo> {
+        self.RequireStatus("balloon", &[Status::Running, Status::Paused])?;
+        return self.Sandbox.as_ref().unwrap().Balloon(targetSize);
+    }
+
+    // FsFreeze quiesces the filesystems of the sandbox, e.g. before a snapshot
+    // of its volumes, or thaws them.
+    pub fn FsFreeze(&self, freeze: bool) -> Result<u64> {
+        self.RequireStatus("freeze filesystems of", &[Status::Running, Status::Paused])?;
+        return self.Sandbox.as_ref().unwrap().FsFreeze(freeze);
+    }
+
+    // Metrics returns the metrics of the sandbox kernel, e.g. the syscall
+    // counts and latencies.
+    pub fn Metrics(&self) -> Result<Vec<MetricValue>> {
+        self.RequireStatus("get metrics of", &[Status::Running, Status::Paused])?;
+        return self.Sandbox.as_ref().unwrap().Metrics();
+    }
+
     // Start starts running the containerized process inside the sandbox.
     pub fn Start(&mut self) -> Result<()> {
         info!("Start container {}", &self.ID);
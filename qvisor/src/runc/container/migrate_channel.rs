@@ -0,0 +1,386 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use alloc::vec::Vec;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::fs;
+use std::fs::File;
+use std::io::Read;
+use std::io::Write;
+use std::net::Shutdown;
+use std::net::TcpListener;
+use std::net::TcpStream;
+use std::os::unix::fs::PermissionsExt;
+use std::time::Duration;
+
+use super::super::super::qlib::common::*;
+
+// The migration stream is sent over an authenticated and encrypted channel.
+// Both sides share a token out of band, e.g. a file provisioned by the
+// orchestrator. The handshake proves the knowledge of the token on both sides:
+//
+//   client -> server: MIGRATE_MAGIC, client nonce
+//   server -> client: server nonce, HMAC(token, "server" | cn | sn)
+//   client -> server: HMAC(token, "client" | cn | sn)
+//
+// The session keys are derived from the token and both nonces, so a recorded
+// session can't be replayed. The image is sent in records of
+// [type u8][len u32][ChaCha20-Poly1305 ciphertext], the nonce is the record
+// sequence and the header is the associated data. The end record has the
+// HMAC of the whole image, the receiver only completes the image after it is
+// verified.
+pub const MIGRATE_MAGIC: &[u8; 8] = b"QMIGRATE";
+// the max plain bytes of a record
+pub const MIGRATE_RECORD_SIZE: usize = 64 * 1024;
+
+// the token has to be hard to guess
+const TOKEN_MIN_LEN: usize = 16;
+const NONCE_LEN: usize = 32;
+const TAG_LEN: usize = 16;
+const MAC_LEN: usize = 32;
+const RECORD_HEADER_LEN: usize = 5;
+
+const RECORD_DATA: u8 = 1;
+const RECORD_END: u8 = 2;
+
+// the peer has to finish the handshake in time
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(30);
+
+type HmacSha256 = Hmac<Sha256>;
+
+fn IOError(op: &str, e: std::io::Error) -> Error {
+    return Error::IOError(format!("Migrate {} io::error is {:?}", op, e));
+}
+
+fn NewMac(key: &[u8]) -> HmacSha256 {
+    // hmac accepts keys of any length
+    return <HmacSha256 as Mac>::new_from_slice(key).unwrap();
+}
+
+fn Derive(key: &[u8], label: &[u8], cn: &[u8], sn: &[u8]) -> [u8; MAC_LEN] {
+    let mut mac = NewMac(key);
+    mac.update(label);
+    mac.update(cn);
+    mac.update(sn);
+
+    let mut out = [0; MAC_LEN];
+    out.copy_from_slice(&mac.finalize().into_bytes());
+    return out;
+}
+
+fn Verify(key: &[u8], label: &[u8], cn: &[u8], sn: &[u8], tag: &[u8]) -> Result<()> {
+    let mut mac = NewMac(key);
+    mac.update(label);
+    mac.update(cn);
+    mac.update(sn);
+    return mac
+        .verify_slice(tag)
+        .map_err(|_| Error::Common("Migrate peer failed the token authentication".to_string()));
+}
+
+fn RandomNonce() -> Result<[u8; NONCE_LEN]> {
+    let mut nonce = [0; NONCE_LEN];
+    File::open("/dev/urandom")
+        .and_then(|mut f| f.read_exact(&mut nonce))
+        .map_err(|e| IOError("random", e))?;
+    return Ok(nonce);
+}
+
+// ReadToken reads the pre-shared token of the migration in path. The file
+// must not be accessible by the other users.
+pub fn ReadToken(path: &str) -> Result<Vec<u8>> {
+    let meta = fs::metadata(path).map_err(|e| IOError("token stat", e))?;
+    if meta.permissions().mode() & 0o077 != 0 {
+        return Err(Error::Common(format!(
+            "Migrate token file {} is accessible by other users",
+            path
+        )));
+    }
+
+    let data = fs::read(path).map_err(|e| IOError("token read", e))?;
+    // the token file usually ends with a newline
+    let mut len = data.len();
+    while len > 0 && data[len - 1].is_ascii_whitespace() {
+        len -= 1;
+    }
+
+    let token = &data[..len];
+    if token.len() < TOKEN_MIN_LEN {
+        return Err(Error::Common(format!(
+            "Migrate token in {} is shorter than {} bytes",
+            path, TOKEN_MIN_LEN
+        )));
+    }
+
+    return Ok(token.to_vec());
+}
+
+pub struct MigrateChannel {
+    stream: TcpStream,
+    cipher: ChaCha20Poly1305,
+    // seq is the nonce of the next record, the records of both directions
+    // use the same key but only the client sends records
+    seq: u64,
+    // imageMac is the HMAC of the plain image sent or received so far
+    imageMac: HmacSha256,
+}
+
+impl MigrateChannel {
+    fn New(stream: TcpStream, token: &[u8], cn: &[u8], sn: &[u8]) -> Self {
+        let dataKey = Derive(token, b"data", cn, sn);
+        let imageKey = Derive(token, b"image", cn, sn);
+        return Self {
+            stream: stream,
+            cipher: ChaCha20Poly1305::new(Key::from_slice(&dataKey)),
+            seq: 0,
+            imageMac: NewMac(&imageKey),
+        };
+    }
+
+    // Connect connects the destination and authenticates the both sides with
+    // token. It is the sending side of the image.
+    pub fn Connect(dest: &str, token: &[u8]) -> Result<Self> {
+        let mut stream = TcpStream::connect(dest).map_err(|e| IOError("connect", e))?;
+        stream
+            .set_read_timeout(Some(HANDSHAKE_TIMEOUT))
+            .map_err(|e| IOError("set timeout", e))?;
+
+        let cn = RandomNonce()?;
+        stream
+            .write_all(MIGRATE_MAGIC)
+            .and_then(|_| stream.write_all(&cn))
+            .map_err(|e| IOError("handshake write", e))?;
+
+        let mut sn = [0; NONCE_LEN];
+        let mut serverMac = [0; MAC_LEN];
+        stream
+            .read_exact(&mut sn)
+            .and_then(|_| stream.read_exact(&mut serverMac))
+            .map_err(|e| IOError("handshake read", e))?;
+        Verify(token, b"server", &cn, &sn, &serverMac)?;
+
+        let clientMac = Derive(token, b"client", &cn, &sn);
+        stream
+            .write_all(&clientMac)
+            .map_err(|e| IOError("handshake write", e))?;
+
+        stream
+            .set_read_timeout(None)
+            .map_err(|e| IOError("set timeout", e))?;
+        return Ok(Self::New(stream, token, &cn, &sn));
+    }
+
+    // Accept accepts a source on addr and authenticates the both sides with
+    // token. It is the receiving side of the image, the peers which fail the
+    // authentication are rejected.
+    pub fn Accept(addr: &str, token: &[u8]) -> Result<Self> {
+        let listener = TcpListener::bind(addr).map_err(|e| IOError("bind", e))?;
+        loop {
+            let (stream, peer) = listener.accept().map_err(|e| IOError("accept", e))?;
+            match Self::Handshake(stream, token) {
+                Ok(channel) => {
+                    info!("Migrate channel from {} is authenticated", peer);
+                    return Ok(channel);
+                }
+                Err(e) => {
+                    error!("Migrate channel from {} is rejected: {:?}", peer, e);
+                }
+            }
+        }
+    }
+
+    fn Handshake(mut stream: TcpStream, token: &[u8]) -> Result<Self> {
+        stream
+            .set_read_timeout(Some(HANDSHAKE_TIMEOUT))
+            .map_err(|e| IOError("set timeout", e))?;
+
+        let mut magic = [0; 8];
+        let mut cn = [0; NONCE_LEN];
+        stream
+            .read_exact(&mut magic)
+            .and_then(|_| stream.read_exact(&mut cn))
+            .map_err(|e| IOError("handshake read", e))?;
+        if &magic != MIGRATE_MAGIC {
+            return Err(Error::Common("Migrate handshake bad magic".to_string()));
+        }
+
+        let sn = RandomNonce()?;
+        let serverMac = Derive(token, b"server", &cn, &sn);
+        stream
+            .write_all(&sn)
+            .and_then(|_| stream.write_all(&serverMac))
+            .map_err(|e| IOError("handshake write", e))?;
+
+        let mut clientMac = [0; MAC_LEN];
+        stream
+            .read_exact(&mut clientMac)
+            .map_err(|e| IOError("handshake read", e))?;
+        Verify(token, b"client", &cn, &sn, &clientMac)?;
+
+        stream
+            .set_read_timeout(None)
+            .map_err(|e| IOError("set timeout", e))?;
+        return Ok(Self::New(stream, token, &cn, &sn));
+    }
+
+    // AbortHandle returns a handle to abort the channel from another thread
+    // with Abort, the receiver sees a truncated image without the end record.
+    pub fn AbortHandle(&self) -> Result<TcpStream> {
+        return self.stream.try_clone().map_err(|e| IOError("clone", e));
+    }
+
+    pub fn Abort(handle: &TcpStream) {
+        handle.shutdown(Shutdown::Both).ok();
+    }
+
+    fn Nonce(&mut self) -> Result<[u8; 12]> {
+        if self.seq == core::u64::MAX {
+            return Err(Error::Common(
+                "Migrate record sequence overflow".to_string(),
+            ));
+        }
+
+        let mut nonce = [0; 12];
+        nonce[4..].copy_from_slice(&self.seq.to_le_bytes());
+        self.seq += 1;
+        return Ok(nonce);
+    }
+
+    fn WriteRecord(&mut self, typ: u8, plain: &[u8]) -> Result<()> {
+        let mut header = [0; RECORD_HEADER_LEN];
+        header[0] = typ;
+        header[1..].copy_from_slice(&((plain.len() + TAG_LEN) as u32).to_le_bytes());
+
+        let nonce = self.Nonce()?;
+        let data = self
+            .cipher
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: plain,
+                    aad: &header,
+                },
+            )
+            .map_err(|_| Error::Common("Migrate encrypt record fail".to_string()))?;
+
+        self.stream
+            .write_all(&header)
+            .and_then(|_| self.stream.write_all(&data))
+            .map_err(|e| IOError("write", e))?;
+        return Ok(());
+    }
+
+    fn ReadRecord(&mut self) -> Result<(u8, Vec<u8>)> {
+        let mut header = [0; RECORD_HEADER_LEN];
+        self.stream
+            .read_exact(&mut header)
+            .map_err(|e| IOError("read", e))?;
+
+        let mut lenBytes = [0; 4];
+        lenBytes.copy_from_slice(&header[1..]);
+        let len = u32::from_le_bytes(lenBytes) as usize;
+        if len < TAG_LEN || len > MIGRATE_RECORD_SIZE + TAG_LEN {
+            return Err(Error::Common(format!("Migrate record len {} is bad", len)));
+        }
+
+        let mut data = vec![0; len];
+        self.stream
+            .read_exact(&mut data)
+            .map_err(|e| IOError("read", e))?;
+
+        let nonce = self.Nonce()?;
+        let plain = self
+            .cipher
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &data,
+                    aad: &header,
+                },
+            )
+            .map_err(|_| Error::Common("Migrate record failed the authentication".to_string()))?;
+        return Ok((header[0], plain));
+    }
+
+    // SendFrom sends the image read from input until its end, then the end
+    // record with the HMAC of the image.
+    pub fn SendFrom(&mut self, input: &mut impl Read) -> Result<u64> {
+        let mut buf = vec![0; MIGRATE_RECORD_SIZE];
+        let mut total = 0;
+        loop {
+            let cnt = match input.read(&mut buf) {
+                Ok(0) => break,
+                Ok(cnt) => cnt,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(IOError("read image", e)),
+            };
+
+            self.imageMac.update(&buf[..cnt]);
+            self.WriteRecord(RECORD_DATA, &buf[..cnt])?;
+            total += cnt as u64;
+        }
+
+        let mac = self.imageMac.clone().finalize().into_bytes();
+        self.WriteRecord(RECORD_END, &mac)?;
+        return Ok(total);
+    }
+
+    // ReceiveTo writes the image to output. A data record is held back until
+    // the next record is verified, so the image is only complete in output
+    // after the HMAC of the whole image is verified. On any error the image
+    // in output is left truncated.
+    pub fn ReceiveTo(&mut self, output: &mut impl Write) -> Result<u64> {
+        let mut pending: Option<Vec<u8>> = None;
+        let mut total = 0;
+        loop {
+            let (typ, plain) = self.ReadRecord()?;
+            match typ {
+                RECORD_DATA => {
+                    if let Some(data) = pending.take() {
+                        output
+                            .write_all(&data)
+                            .map_err(|e| IOError("write image", e))?;
+                        total += data.len() as u64;
+                    }
+
+                    self.imageMac.update(&plain);
+                    pending = Some(plain);
+                }
+                RECORD_END => {
+                    self.imageMac.clone().verify_slice(&plain).map_err(|_| {
+                        Error::Common("Migrate image failed the MAC verification".to_string())
+                    })?;
+
+                    if let Some(data) = pending.take() {
+                        output
+                            .write_all(&data)
+                            .map_err(|e| IOError("write image", e))?;
+                        total += data.len() as u64;
+                    }
+
+                    return Ok(total);
+                }
+                _ => {
+                    return Err(Error::Common(format!(
+                        "Migrate unknown record type {}",
+                        typ
+                    )));
+                }
+            }
+        }
+    }
+}
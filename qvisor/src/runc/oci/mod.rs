@@ -66,6 +66,8 @@ pub struct User {
         rename = "additionalGids"
     )]
     pub additional_gids: Vec<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub umask: Option<u32>,
     #[serde(default, skip_serializing_if = "String::is_empty")]
     pub username: String,
 }
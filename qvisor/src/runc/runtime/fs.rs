@@ -2,10 +2,9 @@ use super::super::super::namespace::Util;
 use super::super::super::qlib::common::Result;
 use super::super::super::qlib::path::{IsAbs, Join};
 use super::super::oci::Spec;
+use super::rootless::SandboxRootPath;
 use std::fs::create_dir_all;
 
-const DEFAULT_QUARK_SANDBOX_ROOT_PATH: &str = "/var/lib/quark/";
-
 pub struct FsImageMounter {
     pub rootPath: String,
     pub sandboxId: String,
@@ -21,7 +20,7 @@ impl FsImageMounter {
 
     pub fn New(sandboxId: &str) -> Self {
         return FsImageMounter {
-            rootPath: DEFAULT_QUARK_SANDBOX_ROOT_PATH.to_string(),
+            rootPath: SandboxRootPath(),
             sandboxId: sandboxId.to_string(),
         };
     }
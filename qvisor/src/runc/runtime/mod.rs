@@ -15,6 +15,7 @@
 pub mod console;
 pub mod fs;
 pub mod loader;
+pub mod rootless;
 pub mod sandbox_process;
 pub mod signal_handle;
 pub mod util;
@@ -0,0 +1,149 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use std::env;
+use std::ffi::CString;
+
+use super::super::super::qlib::common::*;
+use super::super::super::qlib::linux_def::*;
+use super::super::super::qlib::path::*;
+use super::super::oci::*;
+
+// Rootless mode runs the sandbox as a normal user which can open /dev/kvm,
+// e.g. a member of the kvm group. It is used when qvisor doesn't run as root.
+//  - the sandbox is in a new user namespace mapping the user to root, which
+//    owns the mount namespace of the sandbox and its bind mounts.
+//  - the sandbox root and the state directories are under $XDG_RUNTIME_DIR.
+//  - the sandbox keeps the network namespace of the user. The guest sockets
+//    are host sockets of qvisor, so the guest gets the user mode network of
+//    the user as slirp4netns gives it to runc, without a veth setup.
+//  - the host cgroup, which needs root, is not created.
+//  - proc and sysfs, which qkernel emulates, are not mounted on the host and
+//    the devices are bind mounted rather than created.
+
+pub const KVM_DEVICE: &str = "/dev/kvm";
+const QUARK_SANDBOX_ROOT_PATH: &str = "/var/lib/quark/";
+const QUARK_STATE_ROOT_PATH: &str = "/run/qvisor";
+const QUARK_LOG_PATH: &str = "/var/log/quark";
+
+pub fn IsRootless() -> bool {
+    return unsafe { libc::geteuid() } != 0;
+}
+
+// CheckKvmAccess returns an error telling how to fix it if the user can't
+// open /dev/kvm.
+pub fn CheckKvmAccess() -> Result<()> {
+    let path = CString::new(KVM_DEVICE).unwrap();
+    let ret = unsafe { libc::access(path.as_ptr(), libc::R_OK | libc::W_OK) };
+    if ret == 0 {
+        return Ok(());
+    }
+
+    let errno = errno::errno().0;
+    let msg = match errno {
+        SysErr::ENOENT => format!(
+            "{} doesn't exist, check that the virtualization is enabled in the BIOS and the kvm module is loaded",
+            KVM_DEVICE
+        ),
+        SysErr::EACCES | SysErr::EPERM => format!(
+            "{} is not accessible by uid {}, add the user to the group of {} (usually kvm) or run quark as root",
+            KVM_DEVICE,
+            unsafe { libc::geteuid() },
+            KVM_DEVICE
+        ),
+        _ => format!("can't access {}, errno is {}", KVM_DEVICE, errno),
+    };
+
+    return Err(Error::Common(msg));
+}
+
+// UserRuntimeDir is the quark directory of the user in rootless mode
+pub fn UserRuntimeDir() -> String {
+    match env::var("XDG_RUNTIME_DIR") {
+        Ok(dir) if dir.len() > 0 => return Join(&dir, "quark"),
+        _ => return format!("/tmp/quark-{}", unsafe { libc::geteuid() }),
+    }
+}
+
+// SandboxRootPath is the directory of the sandbox root directories
+pub fn SandboxRootPath() -> String {
+    if !IsRootless() {
+        return QUARK_SANDBOX_ROOT_PATH.to_string();
+    }
+
+    return Join(&UserRuntimeDir(), "sandbox");
+}
+
+// StateRootPath is the default of the "--root" state directory
+pub fn StateRootPath() -> String {
+    if !IsRootless() {
+        return QUARK_STATE_ROOT_PATH.to_string();
+    }
+
+    return Join(&UserRuntimeDir(), "state");
+}
+
+// LogPath is the directory of the qvisor and audit logs. A normal user can't
+// write /var/log/quark, its logs are under its runtime directory.
+pub fn LogPath() -> String {
+    if !IsRootless() {
+        return QUARK_LOG_PATH.to_string();
+    }
+
+    return Join(&UserRuntimeDir(), "log");
+}
+
+// RootlessSpec changes the sandbox spec for rootless mode: a user namespace
+// mapping the user and its group to root, the network namespace of the user
+// and no cgroup.
+pub fn RootlessSpec(spec: &mut Spec) {
+    if spec.linux.is_none() {
+        spec.linux = Some(Linux::default());
+    }
+
+    let linux = spec.linux.as_mut().unwrap();
+    linux
+        .namespaces
+        .retain(|ns| ns.typ != LinuxNamespaceType::network);
+    if !linux
+        .namespaces
+        .iter()
+        .any(|ns| ns.typ == LinuxNamespaceType::user)
+    {
+        linux.namespaces.push(LinuxNamespace {
+            typ: LinuxNamespaceType::user,
+            path: "".to_string(),
+        });
+    }
+
+    // an unprivileged user can only map itself
+    linux.uid_mappings = vec![LinuxIDMapping {
+        host_id: unsafe { libc::geteuid() },
+        container_id: 0,
+        size: 1,
+    }];
+    linux.gid_mappings = vec![LinuxIDMapping {
+        host_id: unsafe { libc::getegid() },
+        container_id: 0,
+        size: 1,
+    }];
+
+    linux.cgroups_path = "".to_string();
+}
+
+// HostMountNeeded returns whether the spec mount is mounted on the host in
+// rootless mode. proc and sysfs can't be mounted without the pid and network
+// namespaces of the sandbox, qkernel emulates them anyway.
+pub fn HostMountNeeded(m: &Mount) -> bool {
+    return m.typ != "proc" && m.typ != "sysfs";
+}
@@ -51,12 +51,11 @@ use super::super::shim::container_io::*;
 use super::super::specutils::specutils::*;
 use super::console::*;
 use super::loader::*;
+use super::rootless::*;
 use super::signal_handle::*;
 use super::util::*;
 use super::vm::*;
 
-const QUARK_SANDBOX_ROOT_PATH: &str = "/var/lib/quark/";
-
 pub struct NSRestore {
     pub fd: i32,
     pub flag: i32,
@@ -121,6 +120,7 @@ impl SandboxProcess {
         pivot: bool,
     ) -> Result<Self> {
         let specfile = Join(bundleDir, "config.json");
+        CheckKvmAccess()?;
 
         let mut process = SandboxProcess {
             eventfd: 0,
@@ -141,9 +141,14 @@ impl SandboxProcess {
             CCond: Cond::New()?,
             PCond: Cond::New()?,
             Rootfs: "".to_string(),
-            SandboxRootDir: Join(QUARK_SANDBOX_ROOT_PATH, id),
+            SandboxRootDir: Join(&SandboxRootPath(), id),
         };
 
+        if IsRootless() {
+            info!("qvisor is not root, run the sandbox {} in rootless mode", id);
+            RootlessSpec(&mut process.spec);
+        }
+
         let spec = &process.spec;
 
         if !IsAbs(&spec.root.path) {
@@ -178,7 +183,7 @@ impl SandboxProcess {
         args.AutoStart = self.action == RunAction::Run;
         args.BundleDir = self.bundleDir.to_string();
         args.Pivot = self.pivot;
-        args.Rootfs = Join(QUARK_SANDBOX_ROOT_PATH, id.as_str());
+        args.Rootfs = Join(&SandboxRootPath(), id.as_str());
         args.ControlSock = controlSock;
         args.RDMASvcCliSock = rdmaSvcCliSock;
 
@@ -206,6 +211,13 @@ impl SandboxProcess {
             Ok(()) => (),
             Err(_e) => return Err(Error::Common(String::from("failed creating directory"))),
         }
+
+        // the user can't mount in the host mount namespace, InitRootfs binds
+        // the rootfs in the sandbox mount namespace
+        if IsRootless() {
+            return Ok(());
+        }
+
         let rbindFlags = libc::MS_REC | libc::MS_BIND;
 
         // convert sandbox Root Dir to a mount point
@@ -357,6 +369,10 @@ impl SandboxProcess {
                 let msg = format!("invalid mount destination: {}", m.destination);
                 return Err(Error::Common(msg));
             }
+            if IsRootless() && !HostMountNeeded(m) {
+                continue;
+            }
+
             let (flags, data) = parse_mount(m);
             if m.typ == "cgroup" {
                 //mount_cgroups(m, rootfs, flags, &data, &linux.mount_label, cpath)?;
@@ -384,7 +400,8 @@ impl SandboxProcess {
         }
 
         //default_symlinks()?;
-        create_devices(&linux.devices, false)?;
+        // an unprivileged user can't mknod the devices
+        create_devices(&linux.devices, IsRootless())?;
         //ensure_ptmx()?;
 
         if Util::Chdir(olddir.as_path().to_str().unwrap()) == -1 {
@@ -600,6 +617,11 @@ impl SandboxProcess {
         if self.UserNS {
             // write uid/gid map
             WriteIDMapping(&format!("/proc/{}/uid_map", child), &linux.uid_mappings)?;
+            // an unprivileged user has to disable setgroups before the gid map
+            if IsRootless() {
+                std::fs::write(format!("/proc/{}/setgroups", child), "deny")
+                    .map_err(|e| Error::IOError(format!("io error is {:?}", e)))?;
+            }
             WriteIDMapping(&format!("/proc/{}/gid_map", child), &linux.gid_mappings)?;
         }
 
@@ -24,8 +24,11 @@ use std::os::unix::io::FromRawFd;
 use std::thread;
 
 use super::super::super::elf_loader::*;
+use super::super::super::crash_dump::*;
+use super::super::super::gdbstub::*;
 use super::super::super::kvm_vcpu::*;
-use super::super::super::print::LOG;
+use super::super::super::numa::*;
+use super::super::super::print::{AUDIT_LOG, LOG};
 use super::super::super::qlib::addr;
 use super::super::super::qlib::common::*;
 use super::super::super::qlib::kernel::kernel::futex;
@@ -38,15 +41,20 @@ use super::super::super::qlib::kernel::KERNEL_STACK_ALLOCATOR;
 use super::super::super::qlib::kernel::PAGE_MGR;
 use super::super::super::qlib::kernel::SHARESPACE;
 use super::super::super::qlib::linux_def::*;
-use super::super::super::qlib::pagetable::AlignedAllocator;
+use super::super::super::qlib::pagetable::KernelStackAllocator;
 use super::super::super::qlib::pagetable::PageTables;
 use super::super::super::qlib::perf_tunning::*;
 use super::super::super::qlib::task_mgr::*;
 use super::super::super::qlib::ShareSpace;
+use super::super::super::runc::cgroup::*;
+use super::super::super::seccomp::*;
 use super::super::super::runc::runtime::loader::*;
 use super::super::super::syncmgr;
+use super::super::super::vcpu_gang::*;
+use super::super::super::vcpu_scaler::*;
 use super::super::super::vmspace::*;
 use super::super::super::SHARE_SPACE;
+use super::super::super::util::HostRandU64;
 use super::super::super::SHARE_SPACE_STRUCT;
 use super::super::super::{
     ThreadId, KERNEL_IO_THREAD, PMA_KEEPER, QUARK_CONFIG, ROOT_CONTAINER_ID, THREAD_ID, URING_MGR,
@@ -153,6 +161,51 @@ impl VirtualMachine {
     #[cfg(not(debug_assertions))]
     pub const KERNEL_IMAGE: &'static str = "/usr/local/bin/qkernel.bin";
 
+    // InitNuma places the vcpus by the host numa nodes, interleaves the guest
+    // heap on the nodes of the vcpus and gives the guest the matching topology.
+    // It must be called before the vcpus are created.
+    pub fn InitNuma(cpuCount: usize) {
+        if !QUARK_CONFIG.lock().NumaAware {
+            return;
+        }
+
+        let numa = HostNuma::Load();
+        info!("VM::InitNuma host nodes {:?}", &numa.nodes);
+
+        let mut vms = VMS.lock();
+        vms.numa = Some(numa);
+        let mut vcpuHostNode = Vec::with_capacity(cpuCount);
+        for i in 0..cpuCount {
+            let coreId = vms.ComputeVcpuCoreId(i);
+            vcpuHostNode.push(vms.HostNumaNode(coreId).unwrap());
+        }
+
+        HostNuma::BindHeap(&vcpuHostNode);
+        let topology = HostNuma::GuestTopology(&vcpuHostNode);
+        info!("VM::InitNuma guest vcpu nodes {:?}", &topology.vcpuNode);
+        *SHARE_SPACE.numa.lock() = topology;
+    }
+
+    // InitCgroupLimits gives the guest the resource limits of the spec, which
+    // the guest sees in its /sys/fs/cgroup.
+    pub fn InitCgroupLimits() {
+        let vms = VMS.lock();
+        let resources = vms
+            .args
+            .as_ref()
+            .unwrap()
+            .Spec
+            .linux
+            .as_ref()
+            .and_then(|l| l.resources.as_ref());
+
+        if let Some(res) = resources {
+            let limits = LimitsFromSpec(res);
+            info!("VM::InitCgroupLimits limits {:?}", &limits);
+            *SHARE_SPACE.cgroup.lock() = limits;
+        }
+    }
+
     pub fn InitShareSpace(vmfd: &VmFd, cpuCount: usize, controlSock: i32, rdmaSvcCliSock: i32) {
         SHARE_SPACE_STRUCT
             .lock()
@@ -199,8 +252,7 @@ impl VirtualMachine {
             KERNEL_PAGETABLE.SetRoot(VMS.lock().pageTables.GetRoot());
             PAGE_MGR.SetValue(sharespace.GetPageMgrAddr());
 
-            KERNEL_STACK_ALLOCATOR.Init(AlignedAllocator::New(
-                MemoryDef::DEFAULT_STACK_SIZE as usize,
+            KERNEL_STACK_ALLOCATOR.Init(KernelStackAllocator::New(
                 MemoryDef::DEFAULT_STACK_SIZE as usize,
             ));
 
@@ -220,7 +272,24 @@ impl VirtualMachine {
 
         *ROOT_CONTAINER_ID.lock() = args.ID.clone();
         if QUARK_CONFIG.lock().PerSandboxLog {
-            LOG.lock().Reset(&args.ID[0..12]);
+            if let Err(e) = LOG.lock().Reset(&args.ID[0..12]) {
+                error!("per sandbox log is disabled as its open fails: {:?}", e);
+            }
+            AUDIT_LOG.lock().Reset(&args.ID[0..12]);
+        }
+
+        if QUARK_CONFIG.lock().Audit {
+            // the audit is a security control, the sandbox doesn't start
+            // without it when it is configured
+            AUDIT_LOG.lock().Open()?;
+        }
+
+        // the gdb stub sockets and the crash dump directory are opened before
+        // the sandbox pivots root
+        GdbStub::Listen(&args.ID)?;
+        // the crash dump is a debug aid, the sandbox starts without it
+        if let Err(e) = CRASH_DUMP.Init(&args.ID) {
+            error!("crash dump is disabled as its init fails: {:?}", e);
         }
 
         let kvmfd = args.KvmFd;
@@ -325,12 +394,23 @@ impl VirtualMachine {
         }
 
         Self::InitShareSpace(&vm_fd, cpuCount, controlSock, rdmaSvcCliSock);
+        let kernelASLR = QUARK_CONFIG.lock().KernelASLR;
+        if kernelASLR {
+            SHARE_SPACE
+                .kernelSeed
+                .store(HostRandU64(), Ordering::Relaxed);
+        }
+        Self::InitNuma(cpuCount);
+        Self::InitCgroupLimits();
 
         info!("before loadKernel");
 
-        let entry = elf.LoadKernel(Self::KERNEL_IMAGE)?;
+        let entry = elf.LoadKernel(Self::KERNEL_IMAGE, kernelASLR)?;
         //let vdsoMap = VDSOMemMap::Init(&"/home/brad/rust/quark/vdso/vdso.so".to_string()).unwrap();
-        elf.LoadVDSO(&"/usr/local/bin/vdso.so".to_string())?;
+        elf.LoadVDSO(
+            &"/usr/local/bin/vdso.so".to_string(),
+            &"/usr/local/bin/vdso32.so".to_string(),
+        )?;
         VMS.lock().vdsoAddr = elf.vdsoStart;
 
         let p = entry as *const u8;
@@ -377,6 +457,12 @@ impl VirtualMachine {
     }
 
     pub fn run(&mut self) -> Result<i32> {
+        // the vm and its fds are ready, confine the sandbox process before the
+        // guest starts
+        let config = *QUARK_CONFIG.lock();
+        Confine(&config)?;
+        GdbStub::Start();
+
         let cpu = self.vcpus[0].clone();
 
         let mut threads = Vec::new();
@@ -415,6 +501,9 @@ impl VirtualMachine {
             );
         }
 
+        VcpuScaler::Start();
+        VcpuGang::Start();
+
         for t in threads {
             t.join().expect("the working threads has panicked");
         }
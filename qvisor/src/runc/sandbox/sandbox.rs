@@ -19,20 +19,25 @@ use lazy_static::lazy_static;
 use libc::*;
 use nix::sys::signal;
 use spin::Mutex;
+use std::io::{Read, Seek, SeekFrom};
 use std::os::unix::io::AsRawFd;
 use std::{thread, time};
+use tempfile::tempfile;
 
 use super::super::super::qlib::auth::cap_set::*;
 use super::super::super::qlib::auth::id::*;
 use super::super::super::qlib::common::*;
+use super::super::super::qlib::config::{Config, ConfigUpdate};
 use super::super::super::qlib::control_msg::*;
 use super::super::super::qlib::linux_def::*;
 use super::super::super::qlib::loader;
+use super::super::super::qlib::metric::MetricValue;
 use super::super::super::qlib::*;
 use super::super::super::ucall::ucall::*;
 use super::super::super::ucall::ucall_client::*;
 use super::super::super::vmspace::limits::CreateLimitSet;
 use super::super::super::vmspace::syscall::*;
+use super::super::super::QUARK_CONFIG;
 use super::super::cgroup::*;
 use super::super::cmd::config::*;
 use super::super::container::container::*;
@@ -277,6 +282,133 @@ impl Sandbox {
         return Ok(());
     }
 
+    // Checkpoint writes the sandbox image to the file imageFd and returns the
+    // count of the saved pages. The fd is sent to the sandbox with the request.
+    pub fn Checkpoint(
+        &self,
+        imageFd: i32,
+        leaveRunning: bool,
+        preDump: bool,
+        incremental: bool,
+    ) -> Result<u64> {
+        info!(
+            "Checkpoint sandbox {} preDump {} incremental {}",
+            self.ID, preDump, incremental
+        );
+
+        let client = self.SandboxConnect()?;
+
+        let req = UCallReq::Checkpoint(CheckpointArgs {
+            LeaveRunning: leaveRunning,
+            PreDump: preDump,
+            Incremental: incremental,
+            fds: vec![imageFd],
+        });
+
+        let resp = client.Call(&req)?;
+        match resp {
+            UCallResp::CheckpointResp(pages) => Ok(pages),
+            resp => {
+                panic!("Checkpoint get unknow resp {:?}", resp);
+            }
+        }
+    }
+
+    // Restore recreates the processes of the checkpoint image in imageFd in the
+    // container cid and returns their pids. The stdio of the restored processes
+    // is the stdio of the caller. If stream is set imageFd is a migration stream.
+    pub fn Restore(&self, cid: &str, spec: &Spec, imageFd: i32, stream: bool) -> Result<Vec<i32>> {
+        info!("Restore container {} in sandbox {}", cid, self.ID);
+
+        let client = self.SandboxConnect()?;
+
+        let req = UCallReq::Restore(RestoreArgs {
+            cid: cid.to_string(),
+            Uid: spec.process.user.uid,
+            Gid: spec.process.user.gid,
+            AdditionalGids: spec.process.user.additional_gids.clone(),
+            Caps: specutils::Capabilities(false, &spec.process.capabilities),
+            Stream: stream,
+            fds: vec![imageFd, 0, 1, 2],
+        });
+
+        let resp = client.Call(&req)?;
+        match resp {
+            UCallResp::RestoreResp(pids) => Ok(pids),
+            resp => {
+                panic!("Restore get unknow resp {:?}", resp);
+            }
+        }
+    }
+
+    // Metrics returns the values of the metrics of the sandbox kernel. They are
+    // written to a temporary file, which is sent to the sandbox with the request.
+    pub fn Metrics(&self) -> Result<Vec<MetricValue>> {
+        info!("Metrics of sandbox {}", self.ID);
+
+        let client = self.SandboxConnect()?;
+
+        let mut file = tempfile()
+            .map_err(|e| Error::Common(format!("Metrics create output fail with {:?}", e)))?;
+        let req = UCallReq::Metrics(MetricsArgs {
+            fds: vec![file.as_raw_fd()],
+        });
+
+        let resp = client.Call(&req)?;
+        match resp {
+            UCallResp::MetricsResp => (),
+            resp => {
+                panic!("Metrics get unknow resp {:?}", resp);
+            }
+        }
+
+        let mut output = Vec::new();
+        file.seek(SeekFrom::Start(0))
+            .and_then(|_| file.read_to_end(&mut output))
+            .map_err(|e| Error::Common(format!("Metrics read output fail with {:?}", e)))?;
+        let metrics: Vec<MetricValue> = serde_json::from_slice(&output)
+            .map_err(|e| Error::Common(format!("Metrics deser error is {:?}", e)))?;
+        return Ok(metrics);
+    }
+
+    // Balloon sets the bytes of the guest memory given back to the host.
+    pub fn Balloon(&self, targetSize: u64) -> Result<BalloonInfo> {
+        info!("Balloon sandbox {} to {:x}", self.ID, targetSize);
+
+        let client = self.SandboxConnect()?;
+
+        let req = UCallReq::Balloon(BalloonArgs {
+            TargetSize: targetSize,
+        });
+
+        let resp = client.Call(&req)?;
+        match resp {
+            UCallResp::BalloonResp(info) => Ok(info),
+            resp => {
+                panic!("Balloon get unknow resp {:?}", resp);
+            }
+        }
+    }
+
+    // FsFreeze syncs the filesystems of the sandbox and blocks the writes to
+    // them, or thaws them if freeze is false. It returns the count of the
+    // frozen or thawed mounts.
+    pub fn FsFreeze(&self, freeze: bool) -> Result<u64> {
+        info!("FsFreeze sandbox {} freeze {}", self.ID, freeze);
+
+        let client = self.SandboxConnect()?;
+
+        let req = UCallReq::FsFreeze(FsFreezeArgs { Freeze: freeze });
+
+        let resp = client.Call(&req)?;
+        match resp {
+            UCallResp::FsFreezeResp(count) => Ok(count),
+            resp => {
+                panic!("FsFreeze get unknow resp {:?}", resp);
+            }
+        }
+    }
+
     pub fn Unpause(&self, cid: &str) -> Result<()> {
         info!("Unpause sandbox {}", cid);
 
@@ -307,6 +439,57 @@ impl Sandbox {
         }
     }
 
+    pub fn Strace(&self, args: &StraceArgs) -> Result<()> {
+        info!("Strace sandbox {} with {:?}", self.ID, args);
+
+        let client = self.SandboxConnect()?;
+
+        let req = UCallReq::Strace(args.clone());
+
+        let _resp = client.Call(&req)?;
+
+        return Ok(());
+    }
+
+    // Trace sends the trace op to the sandbox, the dump is written to the
+    // outputFd, which is sent to the sandbox with the request.
+    pub fn Trace(&self, args: &TraceArgs, outputFd: i32) -> Result<()> {
+        info!("Trace sandbox {} with {:?}", self.ID, args);
+
+        let client = self.SandboxConnect()?;
+
+        let mut args = args.clone();
+        if args.Op == TraceOp::Dump {
+            args.fds = vec![outputFd];
+        }
+
+        let req = UCallReq::Trace(args);
+
+        let resp = client.Call(&req)?;
+        match resp {
+            UCallResp::TraceResp => Ok(()),
+            resp => {
+                panic!("Trace get unknow resp {:?}", resp);
+            }
+        }
+    }
+
+    pub fn UpdateConfig(&self, update: &ConfigUpdate) -> Result<Config> {
+        info!("UpdateConfig sandbox {} with {:?}", self.ID, update);
+
+        let client = self.SandboxConnect()?;
+
+        let req = UCallReq::UpdateConfig(update.clone());
+
+        let resp = client.Call(&req)?;
+        match resp {
+            UCallResp::UpdateConfigResp(config) => Ok(config),
+            resp => {
+                panic!("UpdateConfig get unknow resp {:?}", resp);
+            }
+        }
+    }
+
     pub fn StartRootContainer(&self) -> Result<()> {
         let client = self.SandboxConnect()?;
 
@@ -374,16 +557,43 @@ impl Sandbox {
             Args: spec.process.args.clone(),
             Envs: spec.process.env.clone(),
             Cwd: spec.process.cwd.clone(),
+            Umask: spec.process.user.umask,
             limitSet: CreateLimitSet(&spec)
                 .expect("load limitSet fail")
                 .GetInternalCopy(),
             ID: id.to_string(),
             Caps: specutils::Capabilities(false, &spec.process.capabilities),
+            NoNewPrivileges: spec.process.no_new_privileges,
             Root: format!("{}{}", "/", id),
+            HostName: spec.hostname.clone(),
+            Sysctls: specutils::Sysctls(&spec),
+            MaskedPaths: specutils::MaskedPaths(&spec),
+            ReadonlyPaths: specutils::ReadonlyPaths(&spec),
+            Devices: specutils::HostDevices(&spec, &QUARK_CONFIG.lock().HostDevices),
+            WXAllowList: specutils::WXAllowList(&spec),
             ..Default::default()
         };
 
-        let startArgs = StartArgs { process: process };
+        let pidsLimit = spec
+            .linux
+            .as_ref()
+            .and_then(|l| l.resources.as_ref())
+            .and_then(|r| r.pids.as_ref())
+            .map(|p| if p.limit > 0 { p.limit as u64 } else { 0 })
+            .unwrap_or(0);
+
+        let cpuWeight = spec
+            .linux
+            .as_ref()
+            .and_then(|l| l.resources.as_ref())
+            .map(|r| LimitsFromSpec(r).CpuWeight)
+            .unwrap_or(0);
+
+        let startArgs = StartArgs {
+            process: process,
+            pidsLimit: pidsLimit,
+            cpuWeight: cpuWeight,
+        };
         debug!(
             "starting subcontainer with the following args: {:?}",
             &startArgs
@@ -406,7 +616,7 @@ impl Sandbox {
         process: &oci::Process,
         stdios: &[i32],
     ) -> Result<i32> {
-        let caps = TaskCaps::default();
+        let caps = specutils::Capabilities(false, &process.capabilities);
 
         let mut extraKGIDs: Vec<KGID> = Vec::with_capacity(process.user.additional_gids.len());
         for gid in &process.user.additional_gids {
@@ -689,6 +899,50 @@ impl Sandbox {
         }
     }
 
+    pub fn ContainerState(&self, cid: &str) -> Result<ContainerInfo> {
+        let client = self.SandboxConnect()?;
+        let req = UCallReq::ContainerState(cid.to_string());
+        let resp = client.Call(&req)?;
+        match resp {
+            UCallResp::ContainerStateResp(info) => return Ok(info),
+            resp => {
+                panic!("ContainerState get unknow resp {:?}", resp);
+            }
+        }
+    }
+
+    // WaitContainer waits for the init process of the container in the sandbox
+    // and returns its wait status, the sandbox keeps running.
+    pub fn WaitContainer(&self, cid: &str) -> Result<u32> {
+        let client = self.SandboxConnect()?;
+        let req = UCallReq::WaitContainer(cid.to_string());
+        let resp = client.Call(&req)?;
+        match resp {
+            UCallResp::WaitContainerResp(status) => return Ok(status),
+            resp => {
+                panic!("WaitContainer get unknow resp {:?}", resp);
+            }
+        }
+    }
+
+    // StopContainer sends SIGTERM to the processes of the container and
+    // SIGKILL if the container doesn't stop before the timeout.
+    pub fn StopContainer(&self, cid: &str, timeout: time::Duration) -> Result<()> {
+        info!("Stop container {} inside sandbox {}", cid, &self.ID);
+
+        self.SignalContainer(cid, Signal::SIGTERM, true)?;
+        let deadline = time::Instant::now() + timeout;
+        while time::Instant::now() < deadline {
+            if self.ContainerState(cid)?.Status == "stopped" {
+                return Ok(());
+            }
+            thread::sleep(time::Duration::from_millis(10));
+        }
+
+        info!("container {} doesn't stop in {:?}, kill it", cid, timeout);
+        return self.SignalContainer(cid, Signal::SIGKILL, true);
+    }
+
     pub fn IsRootContainer(&self, cid: &str) -> bool {
         return self.ID.as_str() == cid;
     }
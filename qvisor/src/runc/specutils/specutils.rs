@@ -1,4 +1,6 @@
+use alloc::collections::btree_map::BTreeMap;
 use alloc::collections::btree_set::BTreeSet;
+use alloc::string::String;
 use alloc::string::ToString;
 use alloc::vec::Vec;
 use libc::*;
@@ -7,13 +9,18 @@ use std::fs::File;
 
 use super::super::super::qlib::auth::cap_set::*;
 use super::super::super::qlib::common::*;
+use super::super::super::qlib::config::HostDeviceSet;
 use super::super::super::qlib::linux_def::*;
+use super::super::super::qlib::loader::HostDevice;
 use super::super::super::qlib::path::*;
 use super::super::oci::*;
 use super::fs::*;
 
 pub const EXE_PATH: &str = "/proc/self/exe";
 
+// the sysctls applied by qkernel, the others of the spec are ignored
+pub const SUPPORTED_SYSCTLS: &[&str] = &["kernel.domainname"];
+
 pub fn ReadLink(path: &str) -> Result<String> {
     let p = match fs::read_link(path) {
         Err(e) => return Err(Error::SysError(e.raw_os_error().unwrap())),
@@ -160,11 +167,144 @@ pub fn Capabilities(enableRaw: bool, specCaps: &Option<LinuxCapabilities>) -> Ta
         caps.EffectiveCaps = CapsFromSpec(&specCaps.effective[..], &skipSet);
         caps.InheritableCaps = CapsFromSpec(&specCaps.inheritable[..], &skipSet);
         caps.PermittedCaps = CapsFromSpec(&specCaps.permitted[..], &skipSet);
+        caps.AmbientCaps = CapsFromSpec(&specCaps.ambient[..], &skipSet);
     }
 
     return caps;
 }
 
+// Sysctls returns the sysctls of the spec supported by qkernel. The others
+// are logged and ignored, as the sandbox has no host sysctl to set.
+pub fn Sysctls(spec: &Spec) -> BTreeMap<String, String> {
+    let mut sysctls = BTreeMap::new();
+    let linux = match &spec.linux {
+        None => return sysctls,
+        Some(linux) => linux,
+    };
+
+    for (key, value) in &linux.sysctl {
+        if ContainsStr(SUPPORTED_SYSCTLS, key) {
+            sysctls.insert(key.to_string(), value.to_string());
+        } else {
+            info!("sysctl {}={} is being ignored", key, value);
+        }
+    }
+
+    return sysctls;
+}
+
+// MaskedPaths returns the paths of the spec hidden from the container.
+pub fn MaskedPaths(spec: &Spec) -> Vec<String> {
+    return match &spec.linux {
+        None => Vec::new(),
+        Some(linux) => linux.masked_paths.clone(),
+    };
+}
+
+// ReadonlyPaths returns the paths of the spec the container can't write.
+pub fn ReadonlyPaths(spec: &Spec) -> Vec<String> {
+    return match &spec.linux {
+        None => Vec::new(),
+        Some(linux) => linux.readonly_paths.clone(),
+    };
+}
+
+// HostDevices returns the character devices of the spec which are in the
+// host device allowlist of the config, the others are logged and ignored.
+pub fn HostDevices(spec: &Spec, allowed: &HostDeviceSet) -> Vec<HostDevice> {
+    let mut devices = Vec::new();
+    let linux = match &spec.linux {
+        None => return devices,
+        Some(linux) => linux,
+    };
+
+    for d in &linux.devices {
+        let path = Clean(&d.path);
+        let isChar = match d.typ {
+            LinuxDeviceType::c => true,
+            _ => false,
+        };
+        if !isChar || allowed.Class(&path).is_none() {
+            info!("device {} is not in the host device allowlist", &d.path);
+            continue;
+        }
+
+        devices.push(HostDevice {
+            Path: path,
+            Major: d.major as u32,
+            Minor: d.minor as u32,
+            Mode: d.file_mode.unwrap_or(0o666) & 0o7777,
+            UID: d.uid.unwrap_or(0),
+            GID: d.gid.unwrap_or(0),
+        });
+    }
+
+    return devices;
+}
+
+// The executables which need writable and executable mappings, e.g. the
+// JITs, are listed in the annotation
+//   "dev.quark.wx-allow": "<path>[,<path>...]"
+// with the paths seen in the container.
+pub const WX_ALLOW_ANNOTATION: &str = "dev.quark.wx-allow";
+
+// WXAllowList returns the W^X exempted executables of the spec.
+pub fn WXAllowList(spec: &Spec) -> Vec<String> {
+    let mut paths = Vec::new();
+    let value = match spec.annotations.get(WX_ALLOW_ANNOTATION) {
+        None => return paths,
+        Some(v) => v,
+    };
+
+    for path in value.split(',') {
+        let path = path.trim();
+        if path.len() == 0 {
+            continue;
+        }
+
+        if !IsAbs(path) {
+            info!("wx allow list has a relative path {}", path);
+            continue;
+        }
+
+        paths.push(Clean(path));
+    }
+
+    return paths;
+}
+
+// A block volume is asked for with the annotation
+//   "dev.quark.blockvolume.<name>": "<host path>[:ro]"
+// and shows up in the guest as /dev/<name>.
+pub const BLOCK_VOLUME_ANNOTATION_PREFIX: &str = "dev.quark.blockvolume.";
+
+// BlockVolumeSpecs returns the name, the host path and the read only flag
+// of the block volumes of the spec.
+pub fn BlockVolumeSpecs(spec: &Spec) -> Vec<(String, String, bool)> {
+    let mut volumes = Vec::new();
+    for (key, value) in &spec.annotations {
+        let name = match key.strip_prefix(BLOCK_VOLUME_ANNOTATION_PREFIX) {
+            None => continue,
+            Some(name) => name,
+        };
+
+        let (path, readOnly) = match value.strip_suffix(":ro") {
+            None => (value.as_str(), false),
+            Some(path) => (path, true),
+        };
+
+        if !IsAbs(path) {
+            info!("block volume {} has a relative path {}", name, path);
+            continue;
+        }
+
+        volumes.push((name.to_string(), Clean(path), readOnly));
+    }
+
+    volumes.sort();
+    return volumes;
+}
+
 // Capabilities takes in spec and returns a TaskCapabilities corresponding to
 // the spec.
 pub fn CapsFromSpec(caps: &[LinuxCapabilityType], skipSet: &BTreeSet<u64>) -> CapSet {
@@ -0,0 +1,531 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use alloc::vec::Vec;
+use caps::*;
+
+use super::qlib::common::*;
+use super::qlib::config::*;
+use super::qlib::nvproxy::UVM_IOCTLS;
+
+// The sandbox process confines itself after the vm is created: a seccomp
+// allowlist of the syscalls it makes to serve the guest, and the capabilities
+// it needs for the guest file and socket operations. They shrink what a
+// compromised qvisor can do on the host.
+
+const SECCOMP_SET_MODE_FILTER: libc::c_uint = 1;
+const SECCOMP_FILTER_FLAG_TSYNC: libc::c_uint = 1;
+
+const SECCOMP_RET_KILL_PROCESS: u32 = 0x8000_0000;
+const SECCOMP_RET_ERRNO: u32 = 0x0005_0000;
+const SECCOMP_RET_LOG: u32 = 0x7ffc_0000;
+const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+
+const AUDIT_ARCH_X86_64: u32 = 0xc000_003e;
+
+const BPF_LD_W_ABS: u16 = 0x20;
+const BPF_ALU_AND_K: u16 = 0x54;
+const BPF_JMP_JEQ_K: u16 = 0x15;
+const BPF_RET_K: u16 = 0x06;
+
+// the offsets of nr, arch and the low word of the args in struct seccomp_data
+const SECCOMP_DATA_NR: u32 = 0;
+const SECCOMP_DATA_ARCH: u32 = 4;
+const SECCOMP_DATA_ARGS: u32 = 16;
+
+const KVMIO: u8 = 0xae;
+
+// SYSCALL_ALLOWLIST are the syscalls of qvisor after the vm is created: kvm,
+// the qcalls, io_uring, the host sockets of the guest and the rust runtime.
+pub const SYSCALL_ALLOWLIST: &[libc::c_long] = &[
+    libc::SYS_accept,
+    libc::SYS_accept4,
+    libc::SYS_access,
+    libc::SYS_arch_prctl,
+    libc::SYS_bind,
+    libc::SYS_brk,
+    libc::SYS_chdir,
+    libc::SYS_chmod,
+    libc::SYS_chown,
+    libc::SYS_clock_getres,
+    libc::SYS_clock_gettime,
+    libc::SYS_clock_nanosleep,
+    libc::SYS_close,
+    libc::SYS_close_range,
+    libc::SYS_connect,
+    libc::SYS_copy_file_range,
+    libc::SYS_dup,
+    libc::SYS_dup2,
+    libc::SYS_dup3,
+    libc::SYS_epoll_create,
+    libc::SYS_epoll_create1,
+    libc::SYS_epoll_ctl,
+    libc::SYS_epoll_pwait,
+    libc::SYS_epoll_wait,
+    libc::SYS_eventfd,
+    libc::SYS_eventfd2,
+    libc::SYS_exit,
+    libc::SYS_exit_group,
+    libc::SYS_faccessat,
+    libc::SYS_faccessat2,
+    libc::SYS_fadvise64,
+    libc::SYS_fallocate,
+    libc::SYS_fchdir,
+    libc::SYS_fchmod,
+    libc::SYS_fchmodat,
+    libc::SYS_fchown,
+    libc::SYS_fchownat,
+    libc::SYS_fcntl,
+    libc::SYS_fdatasync,
+    libc::SYS_fgetxattr,
+    libc::SYS_flistxattr,
+    libc::SYS_flock,
+    libc::SYS_fremovexattr,
+    libc::SYS_fsetxattr,
+    libc::SYS_fstat,
+    libc::SYS_fstatfs,
+    libc::SYS_fsync,
+    libc::SYS_ftruncate,
+    libc::SYS_futex,
+    libc::SYS_get_mempolicy,
+    libc::SYS_get_robust_list,
+    libc::SYS_getcpu,
+    libc::SYS_getcwd,
+    libc::SYS_getdents,
+    libc::SYS_getdents64,
+    libc::SYS_getegid,
+    libc::SYS_geteuid,
+    libc::SYS_getgid,
+    libc::SYS_getgroups,
+    libc::SYS_getitimer,
+    libc::SYS_getpeername,
+    libc::SYS_getpid,
+    libc::SYS_getppid,
+    libc::SYS_getpriority,
+    libc::SYS_getrandom,
+    libc::SYS_getresgid,
+    libc::SYS_getresuid,
+    libc::SYS_getrlimit,
+    libc::SYS_getrusage,
+    libc::SYS_getsockname,
+    libc::SYS_getsockopt,
+    libc::SYS_gettid,
+    libc::SYS_gettimeofday,
+    libc::SYS_getuid,
+    libc::SYS_getxattr,
+    libc::SYS_inotify_add_watch,
+    libc::SYS_inotify_init1,
+    libc::SYS_inotify_rm_watch,
+    libc::SYS_io_uring_enter,
+    libc::SYS_io_uring_register,
+    libc::SYS_io_uring_setup,
+    libc::SYS_lgetxattr,
+    libc::SYS_link,
+    libc::SYS_linkat,
+    libc::SYS_listen,
+    libc::SYS_listxattr,
+    libc::SYS_lseek,
+    libc::SYS_lstat,
+    libc::SYS_madvise,
+    libc::SYS_mbind,
+    libc::SYS_membarrier,
+    libc::SYS_memfd_create,
+    libc::SYS_mincore,
+    libc::SYS_mkdir,
+    libc::SYS_mkdirat,
+    libc::SYS_mknod,
+    libc::SYS_mknodat,
+    libc::SYS_mlock,
+    libc::SYS_mmap,
+    libc::SYS_mprotect,
+    libc::SYS_mremap,
+    libc::SYS_msync,
+    libc::SYS_munlock,
+    libc::SYS_munmap,
+    libc::SYS_nanosleep,
+    libc::SYS_newfstatat,
+    libc::SYS_open,
+    libc::SYS_openat,
+    libc::SYS_pipe,
+    libc::SYS_pipe2,
+    libc::SYS_poll,
+    libc::SYS_ppoll,
+    libc::SYS_pread64,
+    libc::SYS_preadv,
+    libc::SYS_preadv2,
+    libc::SYS_prlimit64,
+    libc::SYS_pselect6,
+    libc::SYS_pwrite64,
+    libc::SYS_pwritev,
+    libc::SYS_pwritev2,
+    libc::SYS_read,
+    libc::SYS_readlink,
+    libc::SYS_readlinkat,
+    libc::SYS_readv,
+    libc::SYS_recvfrom,
+    libc::SYS_recvmmsg,
+    libc::SYS_recvmsg,
+    libc::SYS_removexattr,
+    libc::SYS_rename,
+    libc::SYS_renameat,
+    libc::SYS_renameat2,
+    libc::SYS_restart_syscall,
+    libc::SYS_rmdir,
+    libc::SYS_rseq,
+    libc::SYS_rt_sigaction,
+    libc::SYS_rt_sigprocmask,
+    libc::SYS_rt_sigreturn,
+    libc::SYS_rt_sigtimedwait,
+    libc::SYS_sched_getaffinity,
+    libc::SYS_sched_getparam,
+    libc::SYS_sched_getscheduler,
+    libc::SYS_sched_setaffinity,
+    libc::SYS_sched_yield,
+    libc::SYS_select,
+    libc::SYS_sendfile,
+    libc::SYS_sendmmsg,
+    libc::SYS_sendmsg,
+    libc::SYS_sendto,
+    libc::SYS_set_mempolicy,
+    libc::SYS_set_robust_list,
+    libc::SYS_setitimer,
+    libc::SYS_setpriority,
+    libc::SYS_setsockopt,
+    libc::SYS_setxattr,
+    libc::SYS_shutdown,
+    libc::SYS_sigaltstack,
+    libc::SYS_signalfd4,
+    libc::SYS_socketpair,
+    libc::SYS_splice,
+    libc::SYS_stat,
+    libc::SYS_statfs,
+    libc::SYS_statx,
+    libc::SYS_symlink,
+    libc::SYS_symlinkat,
+    libc::SYS_sync,
+    libc::SYS_sync_file_range,
+    libc::SYS_syncfs,
+    libc::SYS_sysinfo,
+    libc::SYS_tee,
+    libc::SYS_tgkill,
+    libc::SYS_timerfd_create,
+    libc::SYS_timerfd_gettime,
+    libc::SYS_timerfd_settime,
+    libc::SYS_times,
+    libc::SYS_tkill,
+    libc::SYS_truncate,
+    libc::SYS_umask,
+    libc::SYS_uname,
+    libc::SYS_unlink,
+    libc::SYS_unlinkat,
+    libc::SYS_utimensat,
+    libc::SYS_wait4,
+    libc::SYS_write,
+    libc::SYS_writev,
+];
+
+// SYSCALL_ENOSYS are the syscalls failed with ENOSYS. The clone3 args are in
+// memory, which seccomp can't check, glibc falls back to clone on ENOSYS.
+pub const SYSCALL_ENOSYS: &[libc::c_long] = &[libc::SYS_clone3];
+
+// ArgCheck passes when the low 32 bits of the arg, masked, are one of the
+// values
+pub struct ArgCheck {
+    pub Arg: u32,
+    pub Mask: u32,
+    pub Values: Vec<u32>,
+}
+
+// ArgRule allows the syscall when one of its checks passes
+pub struct ArgRule {
+    pub Nr: libc::c_long,
+    pub Checks: Vec<ArgCheck>,
+}
+
+// ArgRules are the syscalls of qvisor which are allowed with some args only
+pub fn ArgRules() -> Vec<ArgRule> {
+    // the ioctl types of kvm, the host tty and sockets, the block volumes and
+    // the host devices, and the nvidia uvm requests which have no type
+    let mut types = vec![KVMIO, b'T', 0x89, 0x12];
+    for class in &[
+        HostDeviceClass::Fuse,
+        HostDeviceClass::Uinput,
+        HostDeviceClass::Nvidia,
+        HostDeviceClass::Tun,
+    ] {
+        types.extend_from_slice(class.IoctlTypes());
+    }
+    types.sort();
+    types.dedup();
+
+    return vec![
+        ArgRule {
+            Nr: libc::SYS_ioctl,
+            Checks: vec![
+                ArgCheck {
+                    Arg: 1,
+                    Mask: 0xff00,
+                    Values: types.iter().map(|t| (*t as u32) << 8).collect(),
+                },
+                ArgCheck {
+                    Arg: 1,
+                    Mask: 0xffff_ffff,
+                    Values: UVM_IOCTLS.iter().map(|(r, _)| *r as u32).collect(),
+                },
+            ],
+        },
+        // the guest sockets and the unix sockets of qvisor
+        ArgRule {
+            Nr: libc::SYS_socket,
+            Checks: vec![ArgCheck {
+                Arg: 0,
+                Mask: 0xffff_ffff,
+                Values: vec![
+                    libc::AF_UNIX as u32,
+                    libc::AF_INET as u32,
+                    libc::AF_INET6 as u32,
+                    libc::AF_NETLINK as u32,
+                ],
+            }],
+        },
+        // threads only, qvisor doesn't fork once the guest runs
+        ArgRule {
+            Nr: libc::SYS_clone,
+            Checks: vec![ArgCheck {
+                Arg: 0,
+                Mask: libc::CLONE_THREAD as u32,
+                Values: vec![libc::CLONE_THREAD as u32],
+            }],
+        },
+        // the SIGKILL of the process group of the sandbox
+        ArgRule {
+            Nr: libc::SYS_kill,
+            Checks: vec![ArgCheck {
+                Arg: 0,
+                Mask: 0xffff_ffff,
+                Values: vec![0],
+            }],
+        },
+        // the thread names
+        ArgRule {
+            Nr: libc::SYS_prctl,
+            Checks: vec![ArgCheck {
+                Arg: 0,
+                Mask: 0xffff_ffff,
+                Values: vec![libc::PR_SET_NAME as u32, libc::PR_GET_NAME as u32],
+            }],
+        },
+    ];
+}
+
+// KEEP_CAPS are the capabilities for the guest file and socket operations,
+// the others are dropped from all the sets including the bounding set.
+pub const KEEP_CAPS: &[Capability] = &[
+    Capability::CAP_AUDIT_WRITE,
+    Capability::CAP_CHOWN,
+    Capability::CAP_DAC_OVERRIDE,
+    Capability::CAP_DAC_READ_SEARCH,
+    Capability::CAP_FOWNER,
+    Capability::CAP_FSETID,
+    Capability::CAP_IPC_LOCK,
+    Capability::CAP_KILL,
+    Capability::CAP_MKNOD,
+    Capability::CAP_NET_BIND_SERVICE,
+    Capability::CAP_NET_RAW,
+    Capability::CAP_SETGID,
+    Capability::CAP_SETUID,
+    Capability::CAP_SYS_NICE,
+    Capability::CAP_SYS_RESOURCE,
+];
+
+fn Stmt(code: u16, k: u32) -> libc::sock_filter {
+    return libc::sock_filter {
+        code: code,
+        jt: 0,
+        jf: 0,
+        k: k,
+    };
+}
+
+fn Jump(code: u16, k: u32, jt: u8, jf: u8) -> libc::sock_filter {
+    return libc::sock_filter {
+        code: code,
+        jt: jt,
+        jf: jf,
+        k: k,
+    };
+}
+
+// SeccompProgram returns the bpf program allowing the syscalls and the
+// syscalls of the rules with their args, failing the enosys syscalls with
+// ENOSYS. The others get the action.
+pub fn SeccompProgram(
+    syscalls: &[libc::c_long],
+    rules: &[ArgRule],
+    enosys: &[libc::c_long],
+    action: u32,
+) -> Result<Vec<libc::sock_filter>> {
+    let mut prog = Vec::with_capacity(syscalls.len() * 2 + 5);
+
+    // kill the syscalls of the other archs, whose numbers are different
+    prog.push(Stmt(BPF_LD_W_ABS, SECCOMP_DATA_ARCH));
+    prog.push(Jump(BPF_JMP_JEQ_K, AUDIT_ARCH_X86_64, 1, 0));
+    prog.push(Stmt(BPF_RET_K, SECCOMP_RET_KILL_PROCESS));
+
+    prog.push(Stmt(BPF_LD_W_ABS, SECCOMP_DATA_NR));
+    for nr in syscalls {
+        prog.push(Jump(BPF_JMP_JEQ_K, *nr as u32, 0, 1));
+        prog.push(Stmt(BPF_RET_K, SECCOMP_RET_ALLOW));
+    }
+
+    for nr in enosys {
+        prog.push(Jump(BPF_JMP_JEQ_K, *nr as u32, 0, 1));
+        prog.push(Stmt(BPF_RET_K, SECCOMP_RET_ERRNO | libc::ENOSYS as u32));
+    }
+
+    // a rule is a block ending with the action and the allow, the checks
+    // jump to the allow. The nr is not loaded again after the block as the
+    // block always returns.
+    for rule in rules {
+        let mut block = Vec::new();
+        for check in &rule.Checks {
+            block.push(Stmt(BPF_LD_W_ABS, SECCOMP_DATA_ARGS + check.Arg * 8));
+            if check.Mask != 0xffff_ffff {
+                block.push(Stmt(BPF_ALU_AND_K, check.Mask));
+            }
+            for value in &check.Values {
+                // the jump offset to the allow is patched below
+                block.push(Jump(BPF_JMP_JEQ_K, *value, 0, 0));
+            }
+        }
+        block.push(Stmt(BPF_RET_K, action));
+        block.push(Stmt(BPF_RET_K, SECCOMP_RET_ALLOW));
+
+        if block.len() > u8::MAX as usize {
+            return Err(Error::Common(format!(
+                "seccomp rule of syscall {} is too long",
+                rule.Nr
+            )));
+        }
+
+        let allow = block.len() - 1;
+        for i in 0..allow - 1 {
+            if block[i].code == BPF_JMP_JEQ_K {
+                block[i].jt = (allow - i - 1) as u8;
+            }
+        }
+
+        prog.push(Jump(BPF_JMP_JEQ_K, rule.Nr as u32, 0, block.len() as u8));
+        prog.append(&mut block);
+    }
+
+    prog.push(Stmt(BPF_RET_K, action));
+    return Ok(prog);
+}
+
+// InstallSeccomp installs the allowlist on all the threads of qvisor with
+// TSYNC, including the threads created before. The threads created later
+// inherit it.
+pub fn InstallSeccomp(mode: HostSeccompMode) -> Result<()> {
+    let action = match mode {
+        HostSeccompMode::Disabled => return Ok(()),
+        HostSeccompMode::Log => SECCOMP_RET_LOG,
+        HostSeccompMode::Enforce => SECCOMP_RET_KILL_PROCESS,
+    };
+
+    let mut filters = SeccompProgram(SYSCALL_ALLOWLIST, &ArgRules(), SYSCALL_ENOSYS, action)?;
+    let prog = libc::sock_fprog {
+        len: filters.len() as u16,
+        filter: filters.as_mut_ptr(),
+    };
+
+    let ret = unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) };
+    if ret < 0 {
+        return Err(Error::SysError(errno::errno().0));
+    }
+
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_seccomp,
+            SECCOMP_SET_MODE_FILTER,
+            SECCOMP_FILTER_FLAG_TSYNC,
+            &prog as *const libc::sock_fprog,
+        )
+    };
+    if ret < 0 {
+        return Err(Error::SysError(errno::errno().0));
+    }
+
+    // TSYNC returns the thread which can't be synchronized
+    if ret > 0 {
+        return Err(Error::Common(format!(
+            "seccomp can't be installed on thread {}",
+            ret
+        )));
+    }
+
+    info!(
+        "host seccomp {:?} is installed with {} syscalls",
+        mode,
+        SYSCALL_ALLOWLIST.len()
+    );
+    return Ok(());
+}
+
+// DropCaps drops the capabilities out of KEEP_CAPS. The capabilities are per
+// thread, the threads created later get the same sets but the threads
+// created before keep theirs, so it must run before qvisor creates any
+// thread.
+pub fn DropCaps() -> Result<()> {
+    let keep: CapsHashSet = KEEP_CAPS.iter().cloned().collect();
+
+    for cap in caps::all() {
+        if keep.contains(&cap) {
+            continue;
+        }
+
+        // the caps unknown to the host kernel are not in the bounding set
+        match has_cap(None, CapSet::Bounding, cap) {
+            Ok(true) => (),
+            _ => continue,
+        }
+
+        caps::drop(None, CapSet::Bounding, cap)
+            .map_err(|e| Error::Common(format!("DropCaps bounding {:?} fail {:?}", cap, e)))?;
+    }
+
+    let permitted = read(None, CapSet::Permitted)
+        .map_err(|e| Error::Common(format!("DropCaps read fail {:?}", e)))?;
+    let kept: CapsHashSet = permitted.intersection(&keep).cloned().collect();
+
+    // the ambient caps are cleared with the inheritable caps
+    set(None, CapSet::Inheritable, CapsHashSet::new())
+        .map_err(|e| Error::Common(format!("DropCaps inheritable fail {:?}", e)))?;
+    set(None, CapSet::Effective, kept.clone())
+        .map_err(|e| Error::Common(format!("DropCaps effective fail {:?}", e)))?;
+    set(None, CapSet::Permitted, kept.clone())
+        .map_err(|e| Error::Common(format!("DropCaps permitted fail {:?}", e)))?;
+
+    info!("host caps are dropped to {:?}", &kept);
+    return Ok(());
+}
+
+// Confine is called by the sandbox process when the vm is created and before
+// any thread starts, the gdb stub and the vcpu threads start after it.
+pub fn Confine(config: &Config) -> Result<()> {
+    if config.HostDropCaps {
+        DropCaps()?;
+    }
+
+    return InstallSeccomp(config.HostSeccomp);
+}
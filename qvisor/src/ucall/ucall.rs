@@ -14,6 +14,7 @@
 
 use alloc::string::String;
 
+use super::super::qlib::config::*;
 use super::super::qlib::control_msg::*;
 use super::super::runc::container::container::*;
 
@@ -39,6 +40,15 @@ pub enum UCallReq {
     CreateSubContainer(CreateArgs),
     StartSubContainer(StartArgs),
     WaitAll,
+    Strace(StraceArgs),
+    Trace(TraceArgs),
+    UpdateConfig(ConfigUpdate),
+    Checkpoint(CheckpointArgs),
+    Restore(RestoreArgs),
+    Balloon(BalloonArgs),
+    ContainerState(Cid),
+    FsFreeze(FsFreezeArgs),
+    Metrics(MetricsArgs),
 }
 
 impl FileDescriptors for UCallReq {
@@ -53,6 +63,16 @@ impl FileDescriptors for UCallReq {
                     return Some(&args.fds);
                 }
             }
+            UCallReq::Trace(args) => {
+                if args.fds.len() == 0 {
+                    return None;
+                } else {
+                    return Some(&args.fds);
+                }
+            }
+            UCallReq::Metrics(args) => return Some(&args.fds),
+            UCallReq::Checkpoint(args) => return Some(&args.fds),
+            UCallReq::Restore(args) => return Some(&args.fds),
             _ => return None,
         }
     }
@@ -41,9 +41,14 @@ impl UCallClient {
         self.sock.WriteAll(&reqArr)?;
 
         let (len, _fds) = self.sock.ReadLen()?;
-        let mut buf: [u8; UCALL_BUF_LEN] = [0; UCALL_BUF_LEN];
+        if len >= UCALL_BUF_LEN {
+            return Err(Error::Common(format!(
+                "UCallClient resp len {} is too long",
+                len
+            )));
+        }
 
-        assert!(len < UCALL_BUF_LEN, "UCallClient::Call resp is too long");
+        let mut buf: [u8; UCALL_BUF_LEN] = [0; UCALL_BUF_LEN];
         self.sock.ReadAll(&mut buf[0..len])?;
         let resp: UCallResp = serde_json::from_slice(&buf[0..len])
             .map_err(|e| Error::Common(format!("UCallClient deser error is {:?}", e)))?;
@@ -69,9 +74,14 @@ impl UCallClient {
 
     pub fn StreamGetRet(&self) -> Result<UCallResp> {
         let (len, _fds) = self.sock.ReadLen()?;
-        let mut buf: [u8; UCALL_BUF_LEN] = [0; UCALL_BUF_LEN];
+        if len >= UCALL_BUF_LEN {
+            return Err(Error::Common(format!(
+                "UCallClient resp len {} is too long",
+                len
+            )));
+        }
 
-        assert!(len < UCALL_BUF_LEN, "UCallClient::Call resp is too long");
+        let mut buf: [u8; UCALL_BUF_LEN] = [0; UCALL_BUF_LEN];
         self.sock.ReadAll(&mut buf[0..len])?;
         let resp: UCallResp = serde_json::from_slice(&buf[0..len])
             .map_err(|e| Error::Common(format!("UCallClient deser error is {:?}", e)))?;
@@ -15,6 +15,7 @@
 use crate::qlib::kernel::GlobalIOMgr;
 
 use super::super::qlib::common::*;
+use super::super::qlib::config::*;
 use super::super::qlib::control_msg::*;
 use super::super::qlib::linux_def::*;
 use super::super::qlib::loader;
@@ -63,6 +64,7 @@ pub fn ExecProcessHandler(execArgs: &mut ExecArgs, fds: &[i32]) -> Result<Contro
         .AdditionalGids
         .append(&mut execArgs.ExtraKGIDs.iter().map(|gid| gid.0).collect());
     process.Terminal = execArgs.Terminal;
+    process.Caps = execArgs.Capabilities;
     process.ExecId = Some(execArgs.ExecId.clone());
 
     for i in 0..execArgs.Fds.len() {
@@ -156,6 +158,102 @@ pub fn StartSubContainerHandler(args: &mut StartArgs) -> Result<ControlMsg> {
     return Ok(msg);
 }
 
+pub fn StraceHandler(args: &StraceArgs) -> Result<ControlMsg> {
+    let msg = ControlMsg::New(Payload::Strace(args.clone()));
+    return Ok(msg);
+}
+
+pub fn TraceHandler(args: &TraceArgs, fds: &[i32]) -> Result<ControlMsg> {
+    let mut args = args.clone();
+    if args.Op == TraceOp::Dump {
+        if fds.len() != 1 {
+            return Err(Error::Common(format!(
+                "Trace dump expect 1 output fd, get {}",
+                fds.len()
+            )));
+        }
+
+        let hostfd = GlobalIOMgr().AddFile(fds[0]);
+        URING_MGR.lock().Addfd(fds[0]).unwrap();
+        args.fds = vec![hostfd];
+    }
+
+    let msg = ControlMsg::New(Payload::Trace(args));
+    return Ok(msg);
+}
+
+pub fn UpdateConfigHandler(update: &ConfigUpdate) -> Result<ControlMsg> {
+    let msg = ControlMsg::New(Payload::UpdateConfig(update.clone()));
+    return Ok(msg);
+}
+
+pub fn CheckpointHandler(args: &CheckpointArgs, fds: &[i32]) -> Result<ControlMsg> {
+    if fds.len() != 1 {
+        return Err(Error::Common(format!(
+            "Checkpoint expect 1 image fd, get {}",
+            fds.len()
+        )));
+    }
+
+    let mut args = args.clone();
+    let hostfd = GlobalIOMgr().AddFile(fds[0]);
+    URING_MGR.lock().Addfd(fds[0]).unwrap();
+    args.fds = vec![hostfd];
+    let msg = ControlMsg::New(Payload::Checkpoint(args));
+    return Ok(msg);
+}
+
+pub fn RestoreHandler(args: &RestoreArgs, fds: &[i32]) -> Result<ControlMsg> {
+    if fds.len() != 4 {
+        return Err(Error::Common(format!(
+            "Restore expect image and stdio fds, get {}",
+            fds.len()
+        )));
+    }
+
+    let mut args = args.clone();
+    args.fds = Vec::with_capacity(fds.len());
+    for fd in fds {
+        let hostfd = GlobalIOMgr().AddFile(*fd);
+        URING_MGR.lock().Addfd(*fd).unwrap();
+        args.fds.push(hostfd);
+    }
+    let msg = ControlMsg::New(Payload::Restore(args));
+    return Ok(msg);
+}
+
+pub fn BalloonHandler(args: &BalloonArgs) -> Result<ControlMsg> {
+    let msg = ControlMsg::New(Payload::Balloon(args.clone()));
+    return Ok(msg);
+}
+
+pub fn FsFreezeHandler(args: &FsFreezeArgs) -> Result<ControlMsg> {
+    let msg = ControlMsg::New(Payload::FsFreeze(args.clone()));
+    return Ok(msg);
+}
+
+pub fn MetricsHandler(args: &MetricsArgs, fds: &[i32]) -> Result<ControlMsg> {
+    if fds.len() != 1 {
+        return Err(Error::Common(format!(
+            "Metrics expect 1 output fd, get {}",
+            fds.len()
+        )));
+    }
+
+    let mut args = args.clone();
+    let hostfd = GlobalIOMgr().AddFile(fds[0]);
+    URING_MGR.lock().Addfd(fds[0]).unwrap();
+    args.fds = vec![hostfd];
+
+    let msg = ControlMsg::New(Payload::Metrics(args));
+    return Ok(msg);
+}
+
+pub fn ContainerStateHandler(cid: &str) -> Result<ControlMsg> {
+    let msg = ControlMsg::New(Payload::ContainerState(cid.to_string()));
+    return Ok(msg);
+}
+
 pub fn ProcessReqHandler(req: &mut UCallReq, fds: &[i32]) -> Result<ControlMsg> {
     let msg = match req {
         UCallReq::RootContainerStart(start) => RootContainerStartHandler(start)?,
@@ -170,6 +268,15 @@ pub fn ProcessReqHandler(req: &mut UCallReq, fds: &[i32]) -> Result<ControlMsg>
         UCallReq::CreateSubContainer(args) => CreateSubContainerHandler(args, fds)?,
         UCallReq::StartSubContainer(args) => StartSubContainerHandler(args)?,
         UCallReq::WaitAll => WaitAll()?,
+        UCallReq::Strace(args) => StraceHandler(args)?,
+        UCallReq::Trace(args) => TraceHandler(args, fds)?,
+        UCallReq::UpdateConfig(update) => UpdateConfigHandler(update)?,
+        UCallReq::Checkpoint(args) => CheckpointHandler(args, fds)?,
+        UCallReq::Restore(args) => RestoreHandler(args, fds)?,
+        UCallReq::Balloon(args) => BalloonHandler(args)?,
+        UCallReq::ContainerState(cid) => ContainerStateHandler(cid)?,
+        UCallReq::FsFreeze(args) => FsFreezeHandler(args)?,
+        UCallReq::Metrics(args) => MetricsHandler(args, fds)?,
     };
 
     return Ok(msg);
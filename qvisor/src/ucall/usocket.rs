@@ -252,9 +252,14 @@ impl USocket {
 
     pub fn GetReq(&self) -> Result<(UCallReq, Vec<i32>)> {
         let (len, fds) = self.ReadLen()?;
-        let mut buf: [u8; UCALL_BUF_LEN] = [0; UCALL_BUF_LEN];
+        if len >= UCALL_BUF_LEN {
+            return Err(Error::Common(format!(
+                "UCallSrv req len {} is too long",
+                len
+            )));
+        }
 
-        assert!(len < UCALL_BUF_LEN, "UCallClient::Call req is too long");
+        let mut buf: [u8; UCALL_BUF_LEN] = [0; UCALL_BUF_LEN];
         self.ReadAll(&mut buf[0..len])?;
         let req: UCallReq = serde_json::from_slice(&buf[0..len])
             .map_err(|e| Error::Common(format!("UCallSrv deser error is {:?}", e)))?;
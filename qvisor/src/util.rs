@@ -31,6 +31,18 @@ pub fn GetRet(ret: i32) -> Result<i32> {
     return Ok(ret);
 }
 
+// HostRandU64 returns 8 bytes from the host getrandom, 0 on failure.
+pub fn HostRandU64() -> u64 {
+    let mut val: u64 = 0;
+    let ret = unsafe { getrandom(&mut val as *mut _ as *mut c_void, 8, 0) };
+    if ret != 8 {
+        error!("HostRandU64 getrandom fail with errno {}", errno::errno().0);
+        return 0;
+    }
+
+    return val;
+}
+
 pub fn SysRet(ret: i64) -> i64 {
     if ret == -1 {
         return -errno::errno().0 as i64;
@@ -0,0 +1,155 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::mem;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
+
+use super::qlib::numa::ParseCpuList;
+use super::runc::runtime::vm::IsRunning;
+use super::QUARK_CONFIG;
+use super::SHARE_SPACE;
+use super::VMS;
+
+// The vcpu gang placement moves the host threads of the vcpus running the
+// tasks of a core scheduling cookie, set by prctl(PR_SCHED_CORE) in the guest,
+// onto the cores sharing the last level cache with the first vcpu of the
+// group. The other vcpus stay on their own core.
+
+// the interval between two samples of the vcpu cookies
+pub const VCPU_GANG_INTERVAL: Duration = Duration::from_millis(10);
+// the samples a vcpu has to keep its cookie before it is moved, i.e. 50ms
+pub const VCPU_GANG_STABLE_SAMPLES: usize = 5;
+
+pub struct VcpuGang {
+    // home is the host core of each vcpu
+    pub home: Vec<usize>,
+    // cookie and samples are the last cookie of each vcpu and the
+    // consecutive samples with it
+    pub cookie: Vec<u64>,
+    pub samples: Vec<usize>,
+    // placed is the host cores each vcpu thread is bound to now
+    pub placed: Vec<Vec<usize>>,
+}
+
+impl VcpuGang {
+    // Start starts the placement thread if the gang scheduling is enabled.
+    pub fn Start() {
+        if !QUARK_CONFIG.lock().VcpuGangSchedule {
+            return;
+        }
+
+        let home: Vec<usize> = VMS.lock().vcpus.iter().map(|v| v.cordId).collect();
+        let cnt = home.len();
+        let mut gang = Self {
+            placed: home.iter().map(|c| vec![*c]).collect(),
+            home: home,
+            cookie: vec![0; cnt],
+            samples: vec![0; cnt],
+        };
+
+        info!("VcpuGang start with vcpu cores {:?}", &gang.home);
+        thread::Builder::new()
+            .name("vcpu_gang".to_string())
+            .spawn(move || {
+                while IsRunning() {
+                    thread::sleep(VCPU_GANG_INTERVAL);
+                    gang.Sample();
+                }
+            })
+            .unwrap();
+    }
+
+    pub fn Sample(&mut self) {
+        let scheduler = &SHARE_SPACE.scheduler;
+        let mut groups: BTreeMap<u64, Vec<usize>> = BTreeMap::new();
+        for i in 0..self.home.len() {
+            let cookie = scheduler.VcpuArr[i].coreCookie.load(Ordering::Relaxed);
+            if cookie == self.cookie[i] {
+                self.samples[i] += 1;
+            } else {
+                self.cookie[i] = cookie;
+                self.samples[i] = 0;
+            }
+
+            if cookie != 0 && self.samples[i] >= VCPU_GANG_STABLE_SAMPLES {
+                groups.entry(cookie).or_insert_with(Vec::new).push(i);
+            }
+        }
+
+        let mut want: Vec<Vec<usize>> = self.home.iter().map(|c| vec![*c]).collect();
+        for (_, vcpus) in &groups {
+            if vcpus.len() < 2 {
+                continue;
+            }
+
+            let cores = Self::CacheSiblings(self.home[vcpus[0]]);
+            for v in vcpus {
+                want[*v] = cores.clone();
+            }
+        }
+
+        for i in 0..want.len() {
+            if want[i] != self.placed[i] {
+                Self::Place(i, &want[i]);
+                self.placed[i] = want[i].clone();
+            }
+        }
+    }
+
+    // CacheSiblings returns the host cores sharing the last level cache with
+    // the core, only the core if the sysfs doesn't tell.
+    pub fn CacheSiblings(core: usize) -> Vec<usize> {
+        for index in ["index3", "index2"].iter() {
+            let path = format!(
+                "/sys/devices/system/cpu/cpu{}/cache/{}/shared_cpu_list",
+                core, index
+            );
+            if let Ok(s) = fs::read_to_string(&path) {
+                let cores = ParseCpuList(&s);
+                if cores.len() > 0 {
+                    return cores;
+                }
+            }
+        }
+
+        return vec![core];
+    }
+
+    // Place binds the host thread of the vcpu to the cores.
+    pub fn Place(vcpuId: usize, cores: &[usize]) {
+        let tid = VMS.lock().vcpus[vcpuId].threadid.load(Ordering::Relaxed) as i32;
+        if tid == 0 {
+            return;
+        }
+
+        let mut set: libc::cpu_set_t = unsafe { mem::zeroed() };
+        for c in cores {
+            unsafe { libc::CPU_SET(*c, &mut set) };
+        }
+
+        let ret = unsafe { libc::sched_setaffinity(tid, mem::size_of::<libc::cpu_set_t>(), &set) };
+        if ret < 0 {
+            error!(
+                "VcpuGang::Place vcpu {} to {:?} fail with errno {}",
+                vcpuId,
+                cores,
+                errno::errno().0
+            );
+        }
+    }
+}
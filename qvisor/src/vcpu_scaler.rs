@@ -0,0 +1,105 @@
+// Copyright (c) 2021 Quark Container Authors / 2018 The gVisor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::thread;
+use std::time::Duration;
+
+use super::qlib::task_mgr::*;
+use super::runc::runtime::vm::IsRunning;
+use super::QUARK_CONFIG;
+use super::SHARE_SPACE;
+
+// The vcpu scaler parks the idle vcpus and brings them back when the tasks
+// wait for a vcpu, so that a sandbox takes the host cpus with its load. All
+// the vcpus are created at boot, a parked vcpu waits in the host and doesn't
+// take tasks.
+
+// the interval between two samples of the scheduler
+pub const VCPU_SCALE_INTERVAL: Duration = Duration::from_millis(10);
+// the samples with waiting tasks before a vcpu is brought online, i.e. 30ms
+pub const VCPU_SCALE_UP_SAMPLES: usize = 3;
+// the samples with 2 or more idle vcpus before a vcpu is parked, i.e. 2s
+pub const VCPU_SCALE_DOWN_SAMPLES: usize = 200;
+
+pub struct VcpuScaler {
+    pub minVcpuCnt: usize,
+    // busy and idle are the consecutive samples with waiting tasks and with
+    // idle vcpus
+    pub busy: usize,
+    pub idle: usize,
+}
+
+impl VcpuScaler {
+    // Start starts the scaler thread if the vcpu autoscaling is enabled.
+    pub fn Start() {
+        let (enabled, minVcpuCnt) = {
+            let config = QUARK_CONFIG.lock();
+            (config.VcpuAutoScale, config.MinVcpuCount)
+        };
+
+        if !enabled {
+            return;
+        }
+
+        let vcpuCnt = SHARE_SPACE.scheduler.vcpuCnt;
+        let mut scaler = Self {
+            minVcpuCnt: core::cmp::min(core::cmp::max(minVcpuCnt, 1), vcpuCnt),
+            busy: 0,
+            idle: 0,
+        };
+
+        info!(
+            "VcpuScaler start with {} to {} vcpus",
+            scaler.minVcpuCnt, vcpuCnt
+        );
+        thread::Builder::new()
+            .name("vcpu_scaler".to_string())
+            .spawn(move || {
+                while IsRunning() {
+                    thread::sleep(VCPU_SCALE_INTERVAL);
+                    scaler.Sample(&SHARE_SPACE.scheduler);
+                }
+            })
+            .unwrap();
+    }
+
+    pub fn Sample(&mut self, scheduler: &Scheduler) {
+        let online = scheduler.OnlineVcpuCnt();
+        let parked = scheduler.vcpuCnt - online;
+        // the parked vcpus are halted too
+        let idle = scheduler.HaltVcpuCnt().saturating_sub(parked);
+        let ready = scheduler.GlobalReadyTaskCnt();
+
+        if ready > idle {
+            self.busy += 1;
+            self.idle = 0;
+        } else if idle >= 2 {
+            self.idle += 1;
+            self.busy = 0;
+        } else {
+            self.busy = 0;
+            self.idle = 0;
+        }
+
+        if self.busy >= VCPU_SCALE_UP_SAMPLES && online < scheduler.vcpuCnt {
+            info!("VcpuScaler bring vcpu {} online", online);
+            scheduler.SetOnlineVcpuCnt(online + 1);
+            self.busy = 0;
+        } else if self.idle >= VCPU_SCALE_DOWN_SAMPLES && online > self.minVcpuCnt {
+            info!("VcpuScaler park vcpu {}", online - 1);
+            scheduler.SetOnlineVcpuCnt(online - 1);
+            self.idle = 0;
+        }
+    }
+}
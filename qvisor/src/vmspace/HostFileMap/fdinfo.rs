@@ -48,6 +48,9 @@ impl FdInfo {
         return SysRet(ret as i64);
     }
 
+    // ReadDir lists the whole directory in one call. The d_type and d_ino of
+    // getdents are used as they are, the entries are only stat'ed when the
+    // host filesystem doesn't report their type.
     pub fn ReadDir(dirfd: i32, data: u64) -> i64 {
         let data = unsafe { &mut *(data as *mut FileTypes) };
 
@@ -63,6 +66,13 @@ impl FdInfo {
             return res;
         }
 
+        let mut dirStat: LibcStat = Default::default();
+        let ret =
+            unsafe { SysRet(fstat(dirfd, &mut dirStat as *mut _ as u64 as *mut stat) as i64) };
+        if ret < 0 {
+            return ret;
+        }
+
         loop {
             let addr = &buf[0] as *const _ as u64;
             let cnt = Self::GetDents64(dirfd, addr, buf.len() as u32);
@@ -79,8 +89,8 @@ impl FdInfo {
             let mut pos: u64 = 0;
             while pos < cnt {
                 let name;
-                let dType;
-                let inode;
+                let mut dType;
+                let mut inode;
                 unsafe {
                     let d: *const Dirent64 = (addr + pos) as *const Dirent64;
                     name = (*d).name;
@@ -89,7 +99,12 @@ impl FdInfo {
                     pos += (*d).reclen as u64;
                 }
 
-                if true {
+                if &name[..2] == b".\0" || &name[..3] == b"..\0" {
+                    continue;
+                }
+
+                let mut device = dirStat.st_dev;
+                if dType == DType::DT_UNKNOWN {
                     let mut stat: LibcStat = Default::default();
 
                     let ret = unsafe {
@@ -101,27 +116,24 @@ impl FdInfo {
                         ) as i64)
                     };
 
-                    if ret >= 0 {
-                        let ft = FileType {
-                            pathname: CString::FromAddr(&name[0] as *const _ as u64),
-                            device: stat.st_dev,
-                            inode: stat.st_ino,
-                            dType: dType,
-                        };
-
-                        data.fileTypes.push(ft);
+                    // the entry is removed after it is listed
+                    if ret < 0 {
+                        continue;
                     }
-                } else {
-                    // experiment for removing fstatat
-                    let ft = FileType {
-                        pathname: CString::FromAddr(&name[0] as *const _ as u64),
-                        device: 0, //stat.st_dev,
-                        inode: inode,
-                        dType: dType,
-                    };
 
-                    data.fileTypes.push(ft);
+                    dType = ((stat.st_mode & S_IFMT) >> 12) as u8;
+                    device = stat.st_dev;
+                    inode = stat.st_ino;
                 }
+
+                let ft = FileType {
+                    pathname: CString::FromAddr(&name[0] as *const _ as u64),
+                    device: device,
+                    inode: inode,
+                    dType: dType,
+                };
+
+                data.fileTypes.push(ft);
             }
         }
 
@@ -283,6 +295,33 @@ impl FdInfo {
         return SysRet(ret as i64);
     }
 
+    pub fn RecvMMsg(sockfd: i32, msgvec: u64, vlen: u32, flags: i32) -> i64 {
+        let ret = unsafe {
+            recvmmsg(
+                sockfd,
+                msgvec as *mut mmsghdr,
+                vlen as c_uint,
+                flags as c_int,
+                core::ptr::null_mut::<timespec>(),
+            )
+        };
+
+        return SysRet(ret as i64);
+    }
+
+    pub fn SendMMsg(sockfd: i32, msgvec: u64, vlen: u32, flags: i32) -> i64 {
+        let ret = unsafe {
+            sendmmsg(
+                sockfd,
+                msgvec as *mut mmsghdr,
+                vlen as c_uint,
+                flags as c_int,
+            )
+        };
+
+        return SysRet(ret as i64);
+    }
+
     pub fn GetSockName(sockfd: i32, addr: u64, addrlen: u64) -> i64 {
         let ret = unsafe { getsockname(sockfd, addr as *mut sockaddr, addrlen as *mut socklen_t) };
 
@@ -407,9 +446,19 @@ impl FdInfo {
         return Self::WriteAt(fd, iovs, iovcnt, offset);
     }
 
-    pub fn IOFcntl(&self, cmd: i32, _arg: u64) -> i64 {
-        assert!(cmd == Cmd::F_GETFL, "we only support Cmd::F_GETFL in Fcntl");
-        return self.lock().GetFlags() as i64;
+    pub fn IOFcntl(&self, cmd: i32, arg: u64) -> i64 {
+        match cmd {
+            Cmd::F_GETFL => return self.lock().GetFlags() as i64,
+            Cmd::F_GET_SEALS | Cmd::F_ADD_SEALS => {
+                let fd = self.lock().fd;
+                let ret = unsafe { fcntl(fd, cmd, arg as i32) };
+                return SysRet(ret as i64);
+            }
+            _ => panic!(
+                "we only support F_GETFL and the seals in Fcntl, cmd is {}",
+                cmd
+            ),
+        }
     }
 
     pub fn IOIoCtl(&self, cmd: u64, argp: u64) -> i64 {
@@ -448,6 +497,16 @@ impl FdInfo {
         return Self::SendMsg(fd, msghdr, flags);
     }
 
+    pub fn IORecvMMsg(&self, msgvec: u64, vlen: u32, flags: i32) -> i64 {
+        let fd = self.lock().fd;
+        return Self::RecvMMsg(fd, msgvec, vlen, flags);
+    }
+
+    pub fn IOSendMMsg(&self, msgvec: u64, vlen: u32, flags: i32) -> i64 {
+        let fd = self.lock().fd;
+        return Self::SendMMsg(fd, msgvec, vlen, flags);
+    }
+
     pub fn IOGetSockName(&self, addr: u64, addrlen: u64) -> i64 {
         let sockfd = self.lock().fd;
         return Self::GetSockName(sockfd, addr, addrlen);
@@ -54,7 +54,10 @@ impl RDMAServerSock {
             return;
         }
 
-        let mut hasSpace = acceptQueue.lock().HasSpace();
+        let mut hasSpace = {
+            let q = acceptQueue.lock();
+            q.HasSpace() || q.abortOnOverflow
+        };
 
         while hasSpace {
             let tcpAddr = TcpSockAddr::default();
@@ -81,6 +84,25 @@ impl RDMAServerSock {
 
             let fd = ret;
 
+            if acceptQueue.lock().Overflow() {
+                // only reachable with abortOnOverflow, the loop stops otherwise
+                let l = linger {
+                    l_onoff: 1,
+                    l_linger: 0,
+                };
+                unsafe {
+                    setsockopt(
+                        fd,
+                        SOL_SOCKET,
+                        SO_LINGER,
+                        &l as *const _ as *const c_void,
+                        mem::size_of::<linger>() as socklen_t,
+                    );
+                    close(fd);
+                }
+                continue;
+            }
+
             IO_MGR().AddSocket(fd);
             let socketBuf = Arc::new(SocketBuff::default());
 
@@ -113,8 +135,8 @@ impl RDMAServerSock {
                     waitinfo.Notify(EVENT_IN);
                 }
             } else {
-                // todo: how to handle the accept queue len?
-                hasSpace = true;
+                // the connection is queued by SetReady after the RDMA setup
+                hasSpace = acceptQueue.lock().Reserve();
             }
         }
     }
@@ -522,7 +544,7 @@ impl RDMADataSock {
             }
             RDMAType::Server(ref serverSock) => {
                 let acceptQueue = serverSock.sock.acceptQueue.clone();
-                let (trigger, _tmp) = acceptQueue.lock().EnqSocket(
+                let trigger = acceptQueue.lock().EnqPending(
                     serverSock.fd,
                     serverSock.addr,
                     serverSock.len,
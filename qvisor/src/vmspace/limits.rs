@@ -17,8 +17,8 @@ use lazy_static::lazy_static;
 
 use super::super::qlib::common::*;
 use super::super::qlib::limits::*;
+use super::super::qlib::linux::limits::DEFAULT_SIGPENDING_LIMIT;
 use super::super::runc::oci::*;
-//use super::super::qlib::linux::limits::*;
 
 lazy_static! {
     pub static ref FROM_LINUX_SOURCE: BTreeMap<&'static str, LimitType> = [
@@ -146,7 +146,13 @@ pub fn DefaultLimits() -> LimitSet {
             Max: INFINITY,
         },
     );
-    ls.SetUnchecked(LimitType::SignalsPending, Limit { Cur: 0, Max: 0 });
+    ls.SetUnchecked(
+        LimitType::SignalsPending,
+        Limit {
+            Cur: DEFAULT_SIGPENDING_LIMIT,
+            Max: DEFAULT_SIGPENDING_LIMIT,
+        },
+    );
     ls.SetUnchecked(
         LimitType::Stack,
         Limit {
@@ -157,7 +163,13 @@ pub fn DefaultLimits() -> LimitSet {
 
     // Read host limits that directly affect the sandbox and adjust the defaults
     // based on them.
-    for res in [libc::RLIMIT_FSIZE, libc::RLIMIT_NOFILE].iter() {
+    for res in [
+        libc::RLIMIT_FSIZE,
+        libc::RLIMIT_NOFILE,
+        libc::RLIMIT_SIGPENDING,
+    ]
+    .iter()
+    {
         let mut hl = libc::rlimit {
             rlim_cur: 0,
             rlim_max: 0,
@@ -60,6 +60,7 @@ use self::syscall::*;
 use super::kvm_vcpu::HostPageAllocator;
 use super::kvm_vcpu::KVMVcpu;
 use super::namespace::MountNs;
+use super::numa::HostNuma;
 use super::qlib::kernel::SignalProcess;
 use super::qlib::perf_tunning::*;
 use super::runc::runtime::signal_handle::*;
@@ -71,6 +72,9 @@ const ARCH_SET_FS: u64 = 0x1002;
 const ARCH_GET_FS: u64 = 0x1003;
 const ARCH_GET_GS: u64 = 0x1004;
 
+// the sandboxes of the host meet in this directory by the channel name
+const SHM_CHANNEL_DIR: &str = "/dev/shm/quark-channel";
+
 lazy_static! {
     static ref UID: AtomicU64 = AtomicU64::new(1);
 }
@@ -106,6 +110,8 @@ pub struct VMSpace {
     pub vdsoAddr: u64,
     pub vcpuCount: usize,
     pub vcpuMappingDelta: usize,
+    // numa is the host numa topology when the vcpus are placed by the nodes
+    pub numa: Option<HostNuma>,
 
     pub rng: RandGen,
     pub args: Option<Args>,
@@ -213,6 +219,7 @@ impl VMSpace {
             cwd = "/".to_string();
         }
         process.Cwd = cwd;
+        process.Umask = spec.process.user.umask;
 
         SetConole(spec.process.terminal);
         process.Terminal = spec.process.terminal;
@@ -230,8 +237,20 @@ impl VMSpace {
             .expect("load limitSet fail")
             .GetInternalCopy();
         process.Caps = Capabilities(false, &spec.process.capabilities);
+        process.NoNewPrivileges = spec.process.no_new_privileges;
 
         process.HostName = spec.hostname.to_string();
+        process.Sysctls = Sysctls(&spec);
+        process.MaskedPaths = MaskedPaths(&spec);
+        process.ReadonlyPaths = ReadonlyPaths(&spec);
+        process.Devices = HostDevices(&spec, &QUARK_CONFIG.lock().HostDevices);
+        process.WXAllowList = WXAllowList(&spec);
+        // the volumes are opened before the pivot root hides the host paths
+        process.Volumes = match Self::OpenBlockVolumes(&BlockVolumeSpecs(&spec)) {
+            Err(Error::SysError(errno)) => return -errno as i64,
+            Err(e) => panic!("LoadProcessKernel: open block volumes fail {:?}", e),
+            Ok(volumes) => volumes,
+        };
 
         process.NumCpu = self.vcpuCount as u32;
         process.ExecId = Some("".to_string());
@@ -281,6 +300,63 @@ impl VMSpace {
         return vec.len() as i64;
     }
 
+    // OpenBlockVolumes opens the host files of the block volumes of the spec,
+    // the kernel serves them as raw block devices through the host fds.
+    pub fn OpenBlockVolumes(specs: &[(String, String, bool)]) -> Result<Vec<loader::BlockVolume>> {
+        const BLKGETSIZE64: u64 = 0x80081272;
+
+        let mut volumes = Vec::new();
+        for (name, path, readOnly) in specs {
+            let flags = if *readOnly { O_RDONLY } else { O_RDWR };
+            let cstr = CString::New(path);
+            let osfd = unsafe { open(cstr.Ptr() as *const c_char, flags | O_CLOEXEC) };
+            if osfd < 0 {
+                let errno = errno::errno().0;
+                error!("OpenBlockVolumes: open {} for volume {} fail {}", path, name, errno);
+                return Err(Error::SysError(errno));
+            }
+
+            let mut st: stat = unsafe { core::mem::zeroed() };
+            let ret = unsafe { fstat(osfd, &mut st) };
+            if ret < 0 {
+                let errno = errno::errno().0;
+                unsafe { close(osfd) };
+                return Err(Error::SysError(errno));
+            }
+
+            let size = match st.st_mode & S_IFMT {
+                S_IFREG => st.st_size as u64,
+                S_IFBLK => {
+                    let mut size: u64 = 0;
+                    let ret = unsafe { ioctl(osfd, BLKGETSIZE64, &mut size as *mut u64) };
+                    if ret < 0 {
+                        let errno = errno::errno().0;
+                        unsafe { close(osfd) };
+                        return Err(Error::SysError(errno));
+                    }
+                    size
+                }
+                _ => {
+                    error!("OpenBlockVolumes: {} is not a file or a block device", path);
+                    unsafe { close(osfd) };
+                    return Err(Error::SysError(SysErr::EINVAL));
+                }
+            };
+
+            URING_MGR.lock().Addfd(osfd).unwrap();
+            let hostfd = GlobalIOMgr().AddFile(osfd);
+
+            volumes.push(loader::BlockVolume {
+                Name: name.to_string(),
+                HostFd: hostfd,
+                Size: size,
+                ReadOnly: *readOnly,
+            });
+        }
+
+        return Ok(volumes);
+    }
+
     pub fn TgKill(tgid: i32, tid: i32, signal: i32) -> i64 {
         let nr = SysCallID::sys_tgkill as usize;
         let ret = unsafe { syscall3(nr, tgid as usize, tid as usize, signal as usize) as i32 };
@@ -323,7 +399,7 @@ impl VMSpace {
         return Self::GetRet(ret as i64);
     }
 
-    pub fn RenameAt(olddirfd: i32, oldpath: u64, newdirfd: i32, newpath: u64) -> i64 {
+    pub fn RenameAt(olddirfd: i32, oldpath: u64, newdirfd: i32, newpath: u64, flags: u32) -> i64 {
         let olddirfd = {
             if olddirfd > 0 {
                 match Self::GetOsfd(olddirfd) {
@@ -346,13 +422,27 @@ impl VMSpace {
             }
         };
 
-        let ret = unsafe {
-            renameat(
-                olddirfd,
-                oldpath as *const c_char,
-                newdirfd,
-                newpath as *const c_char,
-            )
+        let ret = if flags == 0 {
+            unsafe {
+                renameat(
+                    olddirfd,
+                    oldpath as *const c_char,
+                    newdirfd,
+                    newpath as *const c_char,
+                )
+            }
+        } else {
+            // glibc may lack a renameat2 wrapper, so issue the syscall directly.
+            unsafe {
+                libc::syscall(
+                    libc::SYS_renameat2,
+                    olddirfd,
+                    oldpath as *const c_char,
+                    newdirfd,
+                    newpath as *const c_char,
+                    flags,
+                ) as i32
+            }
         };
 
         return Self::GetRet(ret as i64);
@@ -705,6 +795,24 @@ impl VMSpace {
         return fdInfo.IOSendMsg(msghdr, flags);
     }
 
+    pub fn IORecvMMsg(fd: i32, msgvec: u64, vlen: u32, flags: i32) -> i64 {
+        let fdInfo = match Self::GetFdInfo(fd) {
+            Some(info) => info,
+            None => return -SysErr::EBADF as i64,
+        };
+
+        return fdInfo.IORecvMMsg(msgvec, vlen, flags);
+    }
+
+    pub fn IOSendMMsg(fd: i32, msgvec: u64, vlen: u32, flags: i32) -> i64 {
+        let fdInfo = match Self::GetFdInfo(fd) {
+            Some(info) => info,
+            None => return -SysErr::EBADF as i64,
+        };
+
+        return fdInfo.IOSendMMsg(msgvec, vlen, flags);
+    }
+
     pub fn Fcntl(fd: i32, cmd: i32, arg: u64) -> i64 {
         let fdInfo = match Self::GetFdInfo(fd) {
             Some(info) => info,
@@ -723,6 +831,37 @@ impl VMSpace {
         return fdInfo.IOIoCtl(cmd, argp);
     }
 
+    // NvProxyIoctl runs a nvidia ioctl of the guest. The request has to be in
+    // the nvproxy allowlist and the fd carried in its argument, if any, has to
+    // be a fd of the sandbox.
+    pub fn NvProxyIoctl(fd: i32, cmd: u64, argp: u64) -> i64 {
+        let fdInfo = match Self::GetFdInfo(fd) {
+            Some(fdInfo) => fdInfo,
+            None => return -SysErr::EBADF as i64,
+        };
+
+        let size = match nvproxy::IoctlArgSize(cmd) {
+            None => {
+                error!("NvProxyIoctl: ioctl {:x} is not allowed", cmd);
+                return -SysErr::EINVAL as i64;
+            }
+            Some(size) => size,
+        };
+
+        if let Some(offset) = nvproxy::IoctlFdOffset(cmd) {
+            if offset + 4 > size {
+                return -SysErr::EINVAL as i64;
+            }
+
+            let argFd = unsafe { *((argp + offset as u64) as *const i32) };
+            if Self::GetFdInfo(argFd).is_none() {
+                return -SysErr::EBADF as i64;
+            }
+        }
+
+        return fdInfo.IOIoCtl(cmd, argp);
+    }
+
     pub fn SysSync() -> i64 {
         // as quark running inside container, assume sys_sync only works for the current fs namespace
         // todo: confirm this
@@ -742,6 +881,128 @@ impl VMSpace {
         return Self::GetRet(ret);
     }
 
+    pub fn InotifyInit() -> i64 {
+        let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Self::GetRet(fd as i64);
+        }
+
+        let hostfd = GlobalIOMgr().AddFile(fd);
+        return hostfd as i64;
+    }
+
+    // InotifyAddWatch watches the directory of dirfd. inotify_add_watch only
+    // takes a path, the directory is reached by its /proc/self/fd link.
+    pub fn InotifyAddWatch(fd: i32, dirfd: i32, mask: u32) -> i64 {
+        let osfd = match Self::GetOsfd(fd) {
+            Some(fd) => fd,
+            None => return -SysErr::EBADF as i64,
+        };
+
+        let dirOsfd = match Self::GetOsfd(dirfd) {
+            Some(fd) => fd,
+            None => return -SysErr::EBADF as i64,
+        };
+
+        let path = format!("/proc/self/fd/{}", dirOsfd);
+        let cstr = CString::New(&path);
+        let ret = unsafe { libc::inotify_add_watch(osfd, cstr.Ptr() as *const c_char, mask) };
+
+        return Self::GetRet(ret as i64);
+    }
+
+    pub fn InotifyRmWatch(fd: i32, wd: i32) -> i64 {
+        let osfd = match Self::GetOsfd(fd) {
+            Some(fd) => fd,
+            None => return -SysErr::EBADF as i64,
+        };
+
+        let ret = unsafe { libc::inotify_rm_watch(osfd, wd) };
+
+        return Self::GetRet(ret as i64);
+    }
+
+    // ChannelOpen opens the share memory file of a channel and the doorbell
+    // fifos of its two sides. A new file is zero filled, which is an empty
+    // channel.
+    pub fn ChannelOpen(name: u64, fds: u64) -> i64 {
+        if !QUARK_CONFIG.lock().ShmChannel {
+            return -SysErr::ENODEV as i64;
+        }
+
+        let name = Self::GetStr(name);
+        if !ValidChannelName(name) {
+            return -SysErr::EINVAL as i64;
+        }
+
+        let dir = CString::New(SHM_CHANNEL_DIR);
+        let ret = unsafe { libc::mkdir(dir.Ptr() as *const c_char, 0o700) };
+        if ret < 0 && errno::errno().0 != SysErr::EEXIST {
+            return Self::GetRet(ret as i64);
+        }
+
+        let mut osfds: [i32; 3] = [-1; 3];
+        let closeAll = |osfds: &[i32; 3]| {
+            for fd in osfds.iter() {
+                if *fd >= 0 {
+                    unsafe { libc::close(*fd) };
+                }
+            }
+        };
+
+        let path = CString::New(&format!("{}/{}", SHM_CHANNEL_DIR, name));
+        let fd = unsafe {
+            libc::open(
+                path.Ptr() as *const c_char,
+                libc::O_RDWR | libc::O_CREAT | libc::O_CLOEXEC,
+                0o600,
+            )
+        };
+        if fd < 0 {
+            return Self::GetRet(fd as i64);
+        }
+        osfds[0] = fd;
+
+        let ret = unsafe { libc::ftruncate(fd, SHM_CHANNEL_SIZE as i64) };
+        if ret < 0 {
+            let ret = Self::GetRet(ret as i64);
+            closeAll(&osfds);
+            return ret;
+        }
+
+        for side in 0..2 {
+            let path = CString::New(&format!("{}/{}.{}", SHM_CHANNEL_DIR, name, side));
+            let ret = unsafe { libc::mkfifo(path.Ptr() as *const c_char, 0o600) };
+            if ret < 0 && errno::errno().0 != SysErr::EEXIST {
+                let ret = Self::GetRet(ret as i64);
+                closeAll(&osfds);
+                return ret;
+            }
+
+            // opened for read and write, the open doesn't wait for the peer
+            // and the fifo doesn't see an eof when the peer is gone
+            let fd = unsafe {
+                libc::open(
+                    path.Ptr() as *const c_char,
+                    libc::O_RDWR | libc::O_NONBLOCK | libc::O_CLOEXEC,
+                )
+            };
+            if fd < 0 {
+                let ret = Self::GetRet(fd as i64);
+                closeAll(&osfds);
+                return ret;
+            }
+            osfds[side + 1] = fd;
+        }
+
+        let fds = unsafe { slice::from_raw_parts_mut(fds as *mut i32, 3) };
+        for i in 0..3 {
+            fds[i] = GlobalIOMgr().AddFile(osfds[i]);
+        }
+
+        return 0;
+    }
+
     pub fn SyncFileRange(fd: i32, offset: i64, nbytes: i64, flags: u32) -> i64 {
         let osfd = match Self::GetOsfd(fd) {
             Some(fd) => fd,
@@ -1174,9 +1435,19 @@ impl VMSpace {
         let DedicateUring = QUARK_CONFIG.lock().DedicateUring;
         let id = (threadId + self.vcpuMappingDelta + DedicateUring) % Self::VCPUCount();
 
+        if let Some(numa) = &self.numa {
+            return numa.cores[id % numa.cores.len()];
+        }
+
         return id;
     }
 
+    // HostNumaNode returns the host node of the core if the vcpus are placed by
+    // the numa nodes
+    pub fn HostNumaNode(&self, coreId: usize) -> Option<usize> {
+        return self.numa.as_ref().map(|numa| numa.NodeOf(coreId));
+    }
+
     pub fn Fchdir(fd: i32) -> i64 {
         let fd = match Self::GetOsfd(fd) {
             Some(fd) => fd,
@@ -1188,6 +1459,51 @@ impl VMSpace {
         return Self::GetRet(ret as i64);
     }
 
+    pub fn AuditLog(addr: u64, len: usize) -> i64 {
+        let record = unsafe { slice::from_raw_parts(addr as *const u8, len) };
+        match super::print::AUDIT_LOG.lock().Write(record) {
+            Ok(()) => return 0,
+            Err(Error::SysError(errno)) => return -errno as i64,
+            Err(_) => return -SysErr::EIO as i64,
+        }
+    }
+
+    // BalloonRelease drops the host pages of the huge pages taken by the guest
+    // balloon, the guest gets zero pages when it touches them again.
+    pub fn BalloonRelease(addr: u64, count: usize) -> i64 {
+        let pages = unsafe { slice::from_raw_parts(addr as *const u64, count) };
+        for &page in pages {
+            let ret = unsafe {
+                madvise(
+                    page as *mut c_void,
+                    MemoryDef::HUGE_PAGE_SIZE as usize,
+                    MAdviseOp::MADV_DONTNEED,
+                )
+            };
+            if ret < 0 {
+                return Self::GetRet(ret as i64);
+            }
+        }
+
+        return 0;
+    }
+
+    // HostEntropy credits the guest pool with up to bits of the entropy of the
+    // host pool. The guest reads its random bytes from the host, so the host
+    // entropy backs them.
+    pub fn HostEntropy(bits: i32) -> i64 {
+        let avail = match fs::read_to_string("/proc/sys/kernel/random/entropy_avail") {
+            Err(_) => return 0,
+            Ok(s) => s.trim().parse::<i64>().unwrap_or(0),
+        };
+
+        if avail < bits as i64 {
+            return avail;
+        }
+
+        return bits as i64;
+    }
+
     pub fn Sysinfo(info: u64) -> i64 {
         unsafe {
             return Self::GetRet(sysinfo(info as *mut sysinfo) as i64);
@@ -1421,6 +1737,37 @@ impl VMSpace {
         };
 
         let ret = unsafe { futimens(fd, times as *const timespec) };
+        let ret = Self::GetRet(ret as i64);
+        if ret != -SysErr::EBADF as i64 {
+            return ret;
+        }
+
+        // the symlinks and the files that can't be read or written are opened
+        // with O_PATH, futimens doesn't take such fds
+        let empty = CString::New("");
+        let ret = unsafe {
+            utimensat(
+                fd,
+                empty.Ptr() as *const c_char,
+                times as *const timespec,
+                libc::AT_EMPTY_PATH,
+            )
+        };
+        let ret = Self::GetRet(ret as i64);
+        if ret != -SysErr::EINVAL as i64 {
+            return ret;
+        }
+
+        // AT_EMPTY_PATH is not supported by utimensat before linux 5.8
+        let path = CString::New(&format!("/proc/self/fd/{}", fd));
+        let ret = unsafe {
+            utimensat(
+                libc::AT_FDCWD,
+                path.Ptr() as *const c_char,
+                times as *const timespec,
+                0,
+            )
+        };
 
         return Self::GetRet(ret as i64);
     }
@@ -1554,6 +1901,7 @@ impl VMSpace {
             vdsoAddr: 0,
             vcpuCount: 0,
             vcpuMappingDelta: 0,
+            numa: None,
             rng: RandGen::Init(),
             args: None,
             pivot: false,
@@ -386,6 +386,20 @@ impl GatewayClient {
         }
     }
 
+    // ConnectFailed drops the data socket whose connect failed in the svc and
+    // closes its tcp connection. It returns the closed fd.
+    pub fn ConnectFailed(
+        &self,
+        sockfd: u32,
+        sockFdMappings: &mut HashMap<u32, i32>,
+    ) -> Option<i32> {
+        self.dataSockFdInfos.lock().remove(&sockfd);
+        self.sockIdMgr.lock().Remove(sockfd);
+        let fd = sockFdMappings.remove(&sockfd)?;
+        unsafe { libc::close(fd) };
+        return Some(fd);
+    }
+
     pub fn accept(&self, sockfd: u32, ipAddr: &mut u32, port: &mut u16) -> Result<i32> {
         let serverSockFdInfos = self.serverSockFdInfos.lock();
         match serverSockFdInfos.get(&sockfd) {
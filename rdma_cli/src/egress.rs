@@ -225,6 +225,16 @@ fn wait(epoll_fd: i32, gatewayCli: &GatewayClient, fds: &mut HashMap<i32, FdType
 
                                     gatewayCli.ReadFromSocket(sockInfo, &sockFdMappings);
                                 }
+                                RDMARespMsg::RDMAConnectError(response) => {
+                                    match gatewayCli
+                                        .ConnectFailed(response.sockfd, &mut sockFdMappings)
+                                    {
+                                        Some(fd) => {
+                                            fds.remove(&fd);
+                                        }
+                                        None => (),
+                                    }
+                                }
                                 RDMARespMsg::RDMAAccept(response) => {
                                     let mut sockFdInfos = gatewayCli.serverSockFdInfos.lock();
                                     let sockInfo = sockFdInfos.get_mut(&response.sockfd).unwrap();
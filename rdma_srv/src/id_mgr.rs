@@ -1,10 +1,16 @@
 use super::qlib::common::{Error, Result};
 use std::collections::HashSet;
+use std::collections::VecDeque;
 
+// IdMgr hands out the ids in [start, start + len). The removed ids are
+// reused first, so an alloc doesn't scan the ids in use.
 pub struct IdMgr {
     pub set: HashSet<u32>,
     pub len: u32,
     pub start: u32,
+    // the first id which has never been allocated
+    pub next: u32,
+    pub free: VecDeque<u32>,
 }
 
 impl IdMgr {
@@ -13,24 +19,31 @@ impl IdMgr {
             set: HashSet::new(),
             len: len,
             start: start,
+            next: start,
+            free: VecDeque::new(),
         };
     }
 
     pub fn AllocId(&mut self) -> Result<u32> {
-        if self.set.len() == self.len as usize {
-            return Err(Error::NoEnoughSpace);
-        }
-        for i in self.start..(self.len + self.start) {
-            if !self.set.contains(&i) {
-                self.set.insert(i);
-                return Ok(i);
+        let id = match self.free.pop_front() {
+            Some(id) => id,
+            None => {
+                if self.next == self.start + self.len {
+                    return Err(Error::NoEnoughSpace);
+                }
+                self.next += 1;
+                self.next - 1
             }
-        }
-        return Err(Error::NoData);
+        };
+
+        self.set.insert(id);
+        return Ok(id);
     }
 
     pub fn Remove(&mut self, i: u32) {
-        self.set.remove(&i);
+        if self.set.remove(&i) {
+            self.free.push_back(i);
+        }
     }
 
     pub fn AddCapacity(&mut self, i: u32) {
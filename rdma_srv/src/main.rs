@@ -147,6 +147,7 @@ pub enum FdType {
     TCPSocketConnect(u32),
     RDMACompletionChannel,
     SrvEventFd(i32),
+    NotifyTimer,
 }
 
 // fn main() {
@@ -728,6 +729,10 @@ fn main() -> io::Result<()> {
     unblock_fd(srvEventFd);
     fds.insert(srvEventFd, FdType::SrvEventFd(srvEventFd));
 
+    let notifyTimerfd = RDMA_SRV.notifyTimerfd;
+    epoll_add(epoll_fd, notifyTimerfd, read_event(notifyTimerfd as u64))?;
+    fds.insert(notifyTimerfd, FdType::NotifyTimer);
+
     loop {
         events.clear();
         // println!("in loop");
@@ -904,6 +909,9 @@ fn main() -> io::Result<()> {
                     // println!("eventdata: {}", eventdata);
                     RDMA_SRV.HandleClientRequest();
                 }
+                Some(FdType::NotifyTimer) => {
+                    RDMA_SRV.HandleNotifyTimer();
+                }
                 None => {
                     // panic!("unexpected fd {} found", ev.U64);
                 }
@@ -200,7 +200,9 @@ impl IBContext {
     }
 
     pub fn CreateCompleteQueue(&self, cc: &CompleteChannel) -> CompleteQueue {
-        let cq = unsafe { rdmaffi::ibv_create_cq(self.0, 2000, ptr::null_mut(), cc.0, 0) };
+        let cq = unsafe {
+            rdmaffi::ibv_create_cq(self.0, COMPLETE_QUEUE_SIZE as i32, ptr::null_mut(), cc.0, 0)
+        };
 
         if cq.is_null() {
             // TODO: cleanup
@@ -215,6 +217,25 @@ impl IBContext {
         return CompleteQueue(cq);
     }
 
+    pub fn CreateSharedReceiveQueue(&self, pd: &ProtectionDomain) -> SharedReceiveQueue {
+        let mut attr = rdmaffi::ibv_srq_init_attr {
+            srq_context: ptr::null_mut(),
+            attr: rdmaffi::ibv_srq_attr {
+                max_wr: SHARED_RECV_QUEUE_SIZE,
+                max_sge: MAX_RECV_SGE,
+                srq_limit: 0,
+            },
+        };
+
+        let srq = unsafe { rdmaffi::ibv_create_srq(pd.0, &mut attr) };
+        if srq.is_null() {
+            // TODO: cleanup
+            panic!("ibv_create_srq failed: {}\n", errno::errno().0);
+        }
+
+        return SharedReceiveQueue(srq);
+    }
+
     pub fn QueryGid(&self, ibPort: u8) -> Gid {
         let mut gid = Gid::default();
         let ok = unsafe { rdmaffi::ibv_query_gid(self.0, ibPort, 0, gid.as_mut()) };
@@ -301,16 +322,30 @@ impl Default for CompleteQueue {
     }
 }
 
+// SharedReceiveQueue holds the receive requests of all the queue pairs, so
+// the receive requests don't grow with the rdma connections
+pub struct SharedReceiveQueue(pub *mut rdmaffi::ibv_srq);
+impl Drop for SharedReceiveQueue {
+    fn drop(&mut self) {}
+}
+
+impl Default for SharedReceiveQueue {
+    fn default() -> Self {
+        return Self(0 as _);
+    }
+}
+
 #[derive(Default)]
 pub struct RDMAContextIntern {
     //device_attr: rdmaffi::ibv_device_attr,
     /* Device attributes */
-    portAttr: PortAttr,               /* IB port attributes */
-    ibContext: IBContext,             /* device handle */
-    protectDomain: ProtectionDomain,  /* PD handle */
-    completeChannel: CompleteChannel, /* io completion channel */
-    completeQueue: CompleteQueue,     /* CQ handle */
-    ccfd: i32,                        // complete channel fd
+    portAttr: PortAttr,                  /* IB port attributes */
+    ibContext: IBContext,                /* device handle */
+    protectDomain: ProtectionDomain,     /* PD handle */
+    completeChannel: CompleteChannel,    /* io completion channel */
+    completeQueue: CompleteQueue,        /* CQ handle */
+    sharedRecvQueue: SharedReceiveQueue, /* SRQ handle */
+    ccfd: i32,                           // complete channel fd
     ibPort: u8,
     gid: Gid,
 }
@@ -329,6 +364,7 @@ impl RDMAContextIntern {
         // println!("RDMA ccfd: {}", ccfd);
 
         let completeQueue = ibContext.CreateCompleteQueue(&completeChannel);
+        let sharedRecvQueue = ibContext.CreateSharedReceiveQueue(&protectDomain);
         let gid = ibContext.QueryGid(ibPort);
 
         // unblock complete channel fd
@@ -349,6 +385,7 @@ impl RDMAContextIntern {
             completeChannel: completeChannel,
             ccfd: ccfd,
             completeQueue: completeQueue,
+            sharedRecvQueue: sharedRecvQueue,
             ibPort: ibPort,
             gid: gid,
         };
@@ -374,6 +411,11 @@ pub const MAX_RECV_WR: u32 = 8192;
 pub const MAX_SEND_SGE: u32 = 1;
 pub const MAX_RECV_SGE: u32 = 1;
 
+// the receive requests of all the connections are in the shared receive
+// queue, each connection posts RECV_REQUEST_COUNT of them
+pub const SHARED_RECV_QUEUE_SIZE: u32 = 16 * 1024;
+pub const COMPLETE_QUEUE_SIZE: u32 = 32 * 1024;
+
 impl RDMAContext {
     pub fn Init(&self, deviceName: &str, ibPort: u8) {
         *self.0.lock() = RDMAContextIntern::New(deviceName, ibPort);
@@ -398,10 +440,11 @@ impl RDMAContext {
             qp_context: 0 as *mut _,
             send_cq: context.completeQueue.0 as *const _ as *mut _,
             recv_cq: context.completeQueue.0 as *const _ as *mut _,
-            srq: ptr::null::<rdmaffi::ibv_srq>() as *mut _,
+            srq: context.sharedRecvQueue.0,
             cap: rdmaffi::ibv_qp_cap {
                 max_send_wr: 8192, //MAX_SEND_WR,
-                max_recv_wr: 8192, //MAX_RECV_WR,
+                // the receive requests are posted to the shared receive queue
+                max_recv_wr: 0,
                 max_send_sge: MAX_SEND_SGE,
                 max_recv_sge: MAX_RECV_SGE,
                 max_inline_data: 0,
@@ -457,6 +500,28 @@ impl RDMAContext {
         return fd
     }
 
+    pub fn PostSrqRecv(&self, wrId: u64, addr: u64, lkey: u32) -> Result<()> {
+        let srq = self.lock().sharedRecvQueue.0;
+        let mut sge = rdmaffi::ibv_sge {
+            addr: addr,
+            length: 0,
+            lkey: lkey,
+        };
+        let mut rw = rdmaffi::ibv_recv_wr {
+            wr_id: wrId,
+            next: ptr::null_mut(),
+            sg_list: &mut sge,
+            num_sge: 1,
+        };
+        let mut bad_wr: *mut rdmaffi::ibv_recv_wr = ptr::null_mut();
+        let rc = unsafe { rdmaffi::ibv_post_srq_recv(srq, &mut rw, &mut bad_wr) };
+        if rc != 0 {
+            return Err(Error::SysError(errno::errno().0));
+        }
+
+        return Ok(());
+    }
+
     pub fn PollCompletionQueueAndProcess(&self) -> usize {
         // println!("PollCompletionQueueAndProcess");
         let mut wc = rdmaffi::ibv_wc {
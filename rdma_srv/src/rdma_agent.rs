@@ -20,10 +20,12 @@ use std::collections::HashSet;
 use std::ffi::CString;
 use std::ops::{Deref, DerefMut};
 use std::os::unix::io::{AsRawFd, RawFd};
+use std::time::Instant;
 use std::{env, mem, ptr, thread, time};
 
 use super::id_mgr::IdMgr;
 use super::qlib::common::*;
+use super::qlib::config::Config;
 use super::qlib::linux_def::*;
 use super::qlib::rdma_share::*;
 use super::qlib::socket_buf::SocketBuff;
@@ -34,12 +36,69 @@ use super::rdma_conn::*;
 use super::rdma_ctrlconn::*;
 use super::rdma_srv::*;
 
+// the io buffers registered by one memory region
+pub const IO_BUF_CHUNK: usize = 16;
+
 pub struct SockInfo {
     pub sockfd: u32,
     pub status: SockStatus,
     // pub acceptQueue: AcceptQueue,
 }
 
+// NotifyModeration coalesces the RDMANotify responses to a client, so a
+// busy stream doesn't wake the client with the eventfd for each message.
+#[derive(Default)]
+pub struct NotifyModeration {
+    // the notifications in the cq which haven't woken the client
+    pub pending: u32,
+    pub lastNotify: Option<Instant>,
+    // moving average of the nanoseconds between 2 notifications
+    pub avgInterval: u64,
+}
+
+impl NotifyModeration {
+    // Add counts a notification and returns whether to wake the client now
+    pub fn Add(&mut self, config: &Config) -> bool {
+        let now = Instant::now();
+        if let Some(last) = self.lastNotify {
+            let interval = now.duration_since(last).as_nanos() as u64;
+            self.avgInterval = (self.avgInterval * 7 + interval) / 8;
+        }
+        self.lastNotify = Some(now);
+
+        self.pending += 1;
+        return self.pending >= self.Batch(config);
+    }
+
+    pub fn Batch(&self, config: &Config) -> u32 {
+        let max = config.RDMANotifyBatch;
+        if max <= 1 || config.RDMANotifyDelay == 0 {
+            return 1;
+        }
+
+        if !config.RDMANotifyAdaptive {
+            return max;
+        }
+
+        // the notifications expected in the delay at the observed rate, a
+        // slow flow is woken at once
+        if self.avgInterval == 0 {
+            return 1;
+        }
+
+        let expected = config.RDMANotifyDelay * 1000 / self.avgInterval;
+        if expected < 1 {
+            return 1;
+        }
+
+        if expected > max as u64 {
+            return max;
+        }
+
+        return expected as u32;
+    }
+}
+
 pub struct RDMAAgentIntern {
     pub id: u32,
 
@@ -62,9 +121,10 @@ pub struct RDMAAgentIntern {
 
     pub ioBufIdMgr: Mutex<IdMgr>,
 
-    pub keys: Vec<[u32; 2]>,
-    // TODO: indexes allocated for io buffer.
+    pub notifyModeration: Mutex<NotifyModeration>,
 
+    // io buffer chunk -> [lkey, rkey] of its memory region
+    pub keys: Mutex<HashMap<usize, [u32; 2]>>,
     //sockfd -> sockInfo
     // pub sockInfos: Mutex<HashMap<u32, SockInfo>>,
 }
@@ -97,10 +157,6 @@ impl RDMAAgent {
             )
         };
 
-        //start from 2M registration.
-        let mr = RDMA
-            .CreateMemoryRegion(addr as u64, 2 * 1024 * 1024)
-            .unwrap();
         let shareRegion = unsafe {
             let addr = addr as *mut ClientShareRegion;
             &mut (*addr)
@@ -117,8 +173,9 @@ impl RDMAAgent {
             client_eventfd: clientEventfd,
             shareMemRegion: MemRegion { addr: 0, len: 0 },
             shareRegion: Mutex::new(shareRegion),
-            ioBufIdMgr: Mutex::new(IdMgr::Init(0, 20)),
-            keys: vec![[mr.LKey(), mr.RKey()]],
+            ioBufIdMgr: Mutex::new(IdMgr::Init(0, IO_BUF_COUNT as u32)),
+            keys: Mutex::new(HashMap::new()),
+            notifyModeration: Mutex::new(NotifyModeration::default()),
         }))
     }
 
@@ -135,18 +192,56 @@ impl RDMAAgent {
                 Mutex::new(&mut (*addr))
             },
             ioBufIdMgr: Mutex::new(IdMgr::Init(0, 0)),
-            keys: vec![[0, 0]],
+            keys: Mutex::new(HashMap::new()),
+            notifyModeration: Mutex::new(NotifyModeration::default()),
         }))
     }
 
-    pub fn CreateServerRDMAChannel(
+    // IOBufKeys returns the memory region keys of the io buffer. The io
+    // buffers are registered IO_BUF_CHUNK at a time when first used, the
+    // share region is too big to be registered at once.
+    pub fn IOBufKeys(
         &self,
-        connectRequest: &ConnectRequest,
-        rdmaConn: RDMAConn,
-    ) -> RDMAChannel {
-        let channelId = RDMA_SRV.channelIdMgr.lock().AllocId().unwrap();
-        let ioBufIndex = self.ioBufIdMgr.lock().AllocId().unwrap() as usize;
-        let shareRegion = self.shareRegion.lock();
+        ioBufIndex: usize,
+        shareRegion: &ClientShareRegion,
+    ) -> Result<[u32; 2]> {
+        let chunk = ioBufIndex / IO_BUF_CHUNK;
+        let mut keys = self.keys.lock();
+        if let Some(k) = keys.get(&chunk) {
+            return Ok(*k);
+        }
+
+        let addr = &shareRegion.iobufs[chunk * IO_BUF_CHUNK] as *const _ as u64;
+        let mr = RDMA.CreateMemoryRegion(addr, IO_BUF_CHUNK * mem::size_of::<IOBuf>())?;
+        let k = [mr.LKey(), mr.RKey()];
+        keys.insert(chunk, k);
+        return Ok(k);
+    }
+
+    // AllocChannelBuf takes a channel id and an io buffer for a new channel.
+    // It fails instead of panic when the channels or the io buffers run out.
+    fn AllocChannelBuf(
+        &self,
+        shareRegion: &ClientShareRegion,
+    ) -> Result<(u32, usize, [u32; 2], Arc<SocketBuff>)> {
+        let channelId = RDMA_SRV.channelIdMgr.lock().AllocId()?;
+        let ioBufIndex = match self.ioBufIdMgr.lock().AllocId() {
+            Ok(i) => i as usize,
+            Err(e) => {
+                RDMA_SRV.channelIdMgr.lock().Remove(channelId);
+                return Err(e);
+            }
+        };
+
+        let keys = match self.IOBufKeys(ioBufIndex, shareRegion) {
+            Ok(k) => k,
+            Err(e) => {
+                self.ioBufIdMgr.lock().Remove(ioBufIndex as u32);
+                RDMA_SRV.channelIdMgr.lock().Remove(channelId);
+                return Err(e);
+            }
+        };
+
         let sockBuf = Arc::new(SocketBuff::InitWithShareMemory(
             MemoryDef::DEFAULT_BUF_PAGE_COUNT,
             &shareRegion.ioMetas[ioBufIndex].readBufAtoms as *const _ as u64,
@@ -155,17 +250,28 @@ impl RDMAAgent {
             &shareRegion.iobufs[ioBufIndex].read as *const _ as u64,
             &shareRegion.iobufs[ioBufIndex].write as *const _ as u64,
         ));
+
+        return Ok((channelId, ioBufIndex, keys, sockBuf));
+    }
+
+    pub fn CreateServerRDMAChannel(
+        &self,
+        connectRequest: &ConnectRequest,
+        rdmaConn: RDMAConn,
+    ) -> Result<RDMAChannel> {
+        let shareRegion = self.shareRegion.lock();
+        let (channelId, ioBufIndex, keys, sockBuf) = self.AllocChannelBuf(&shareRegion)?;
         let rdmaChannel = RDMAChannel::CreateRDMAChannel(
             channelId,
-            self.keys[ioBufIndex / 16][0],
-            self.keys[ioBufIndex / 16][1],
+            keys[0],
+            keys[1],
             sockBuf,
             rdmaConn,
             connectRequest,
             ioBufIndex as u32,
             self,
         );
-        rdmaChannel
+        Ok(rdmaChannel)
     }
 
     pub fn CreateClientRDMAChannel(
@@ -173,36 +279,28 @@ impl RDMAAgent {
         connectReq: &RDMAConnectReq,
         rdmaConn: RDMAConn,
         shareRegion: &ClientShareRegion,
-    ) -> RDMAChannel {
-        let channelId = RDMA_SRV.channelIdMgr.lock().AllocId().unwrap();
-        let ioBufIndex = self.ioBufIdMgr.lock().AllocId().unwrap() as usize;
-        let sockBuf = Arc::new(SocketBuff::InitWithShareMemory(
-            MemoryDef::DEFAULT_BUF_PAGE_COUNT,
-            &shareRegion.ioMetas[ioBufIndex].readBufAtoms as *const _ as u64,
-            &shareRegion.ioMetas[ioBufIndex].writeBufAtoms as *const _ as u64,
-            &shareRegion.ioMetas[ioBufIndex].consumeReadData as *const _ as u64,
-            &shareRegion.iobufs[ioBufIndex].read as *const _ as u64,
-            &shareRegion.iobufs[ioBufIndex].write as *const _ as u64,
-        ));
-
+    ) -> Result<RDMAChannel> {
+        let (channelId, ioBufIndex, keys, sockBuf) = self.AllocChannelBuf(shareRegion)?;
         let rdmaChannel = RDMAChannel::CreateClientChannel(
             channelId,
-            self.keys[ioBufIndex / 16][0],
-            self.keys[ioBufIndex / 16][1],
+            keys[0],
+            keys[1],
             sockBuf,
             rdmaConn,
             &connectReq,
             ioBufIndex as u32,
             self,
         );
-        rdmaChannel
+        Ok(rdmaChannel)
     }
 
     pub fn HandleClientRequest(&self) {
         loop {
-            let shareRegion = self.shareRegion.lock();
+            let mut shareRegion = self.shareRegion.lock();
             match shareRegion.sq.Pop() {
-                Some(rdmaRequest) => self.HandleClientRequestInternal(rdmaRequest, &shareRegion),
+                Some(rdmaRequest) => {
+                    self.HandleClientRequestInternal(rdmaRequest, &mut shareRegion)
+                }
                 None => {
                     // println!("No more request for agent: {}", self.id);
                     break;
@@ -213,7 +311,36 @@ impl RDMAAgent {
 
     pub fn SendResponse(&self, response: RDMAResp) {
         let mut shareRegion = self.shareRegion.lock();
+        let coalesce = matches!(response.msg, RDMARespMsg::RDMANotify(_));
         shareRegion.cq.Push(response);
+
+        {
+            let mut moderation = self.notifyModeration.lock();
+            if coalesce && !moderation.Add(&RDMA_CONFIG) {
+                RDMA_SRV.ArmNotifyTimer();
+                return;
+            }
+            moderation.pending = 0;
+        }
+
+        self.WakeupClient(&shareRegion);
+    }
+
+    // FlushNotify wakes the client for the coalesced notifications
+    pub fn FlushNotify(&self) {
+        let shareRegion = self.shareRegion.lock();
+        {
+            let mut moderation = self.notifyModeration.lock();
+            if moderation.pending == 0 {
+                return;
+            }
+            moderation.pending = 0;
+        }
+
+        self.WakeupClient(&shareRegion);
+    }
+
+    fn WakeupClient(&self, shareRegion: &ClientShareRegion) {
         if shareRegion.clientBitmap.load(Ordering::SeqCst) == 1 {
             let data = 16u64;
             let ret = unsafe {
@@ -229,7 +356,18 @@ impl RDMAAgent {
         }
     }
 
-    fn HandleClientRequestInternal(&self, rdmaReq: RDMAReq, shareRegion: &ClientShareRegion) {
+    // SendConnectError fails the connect of sockfd. It is called for a client
+    // request, which holds the share region lock already.
+    fn SendConnectError(&self, shareRegion: &mut ClientShareRegion, sockfd: u32, errno: i32) {
+        shareRegion.cq.Push(RDMAResp {
+            user_data: 0,
+            msg: RDMARespMsg::RDMAConnectError(RDMAConnectErrorResp { sockfd, errno }),
+        });
+        self.notifyModeration.lock().pending = 0;
+        self.WakeupClient(shareRegion);
+    }
+
+    fn HandleClientRequestInternal(&self, rdmaReq: RDMAReq, shareRegion: &mut ClientShareRegion) {
         match rdmaReq.msg {
             RDMAReqMsg::RDMAListen(msg) => {
                 RDMA_SRV.srvEndPoints.lock().insert(
@@ -255,7 +393,18 @@ impl RDMAAgent {
                         let conns = RDMA_SRV.conns.lock();
                         let rdmaConn = conns.get(nodeIpAddr).unwrap();
                         let rdmaChannel =
-                            self.CreateClientRDMAChannel(&msg, rdmaConn.clone(), shareRegion);
+                            match self.CreateClientRDMAChannel(&msg, rdmaConn.clone(), shareRegion)
+                            {
+                                Ok(c) => c,
+                                Err(_) => {
+                                    self.SendConnectError(
+                                        shareRegion,
+                                        msg.sockfd,
+                                        SysErr::ETIMEDOUT,
+                                    );
+                                    return;
+                                }
+                            };
                         RDMA_SRV
                             .channels
                             .lock()
@@ -269,7 +418,7 @@ impl RDMAAgent {
                             // .expect("fail to send msg");
                     }
                     None => {
-                        println!("TODO: return error as no ip mapping is found");
+                        self.SendConnectError(shareRegion, msg.sockfd, SysErr::ECONNREFUSED);
                     }
                 }
             }
@@ -162,8 +162,7 @@ impl RDMAConn {
             .Setup(&RDMA, remoteInfo.qp_num, remoteInfo.lid, remoteInfo.gid)
             .expect("SetupRDMA fail...");
         for _i in 0..RECV_REQUEST_COUNT {
-            self.qps[0]
-                .PostRecv(0, self.localRDMAInfo.raddr, self.localRDMAInfo.rkey)
+            RDMA.PostSrqRecv(0, self.localRDMAInfo.raddr, self.localRDMAInfo.rkey)
                 .expect("SetupRDMA PostRecv fail");
         }
     }
@@ -512,8 +511,9 @@ impl RDMAConn {
     }
 
     pub fn PostRecv(&self, _qpNum: u32, wrId: u64, addr: u64, lkey: u32) -> Result<()> {
-        //TODO: get right qp when multiple QP are used between two physical machines.
-        self.qps[0].PostRecv(wrId, addr, lkey)?;
+        // the receive request goes to the shared receive queue, it is still
+        // counted for the connection which consumed one
+        RDMA.PostSrqRecv(wrId, addr, lkey)?;
         let current = self
             .localInsertedRecvRequestCount
             .fetch_add(1, Ordering::SeqCst)
@@ -651,6 +651,14 @@ impl RDMAControlChannel {
                         .IncreaseRemoteRequestCount(msg.recvRequestCount);
                     self.HandleConnectResponse(msg);
                 }
+                ControlMsgBody::ConnectError(msg) => {
+                    self.chan
+                        .upgrade()
+                        .unwrap()
+                        .conn
+                        .IncreaseRemoteRequestCount(msg.recvRequestCount);
+                    self.HandleConnectError(msg);
+                }
                 ControlMsgBody::ConsumedData(msg) => {
                     // println!("ControlChannel::ConsumedData: {}", msg.consumedData);
                     self.chan
@@ -755,8 +763,17 @@ impl RDMAControlChannel {
             // println!("HandleConnectRequest 2");
             let agents = RDMA_SRV.agents.lock();
             let agent = agents.get(&agentId).unwrap();
-            let rdmaChannel = agent
-                .CreateServerRDMAChannel(connectRequest, self.chan.upgrade().unwrap().conn.clone());
+            let rdmaChannel = match agent
+                .CreateServerRDMAChannel(connectRequest, self.chan.upgrade().unwrap().conn.clone())
+            {
+                Ok(c) => c,
+                Err(_) => {
+                    // out of channel ids or io buffers, drop the connect as a
+                    // full backlog does
+                    self.SendConnectError(connectRequest, SysErr::ETIMEDOUT);
+                    return;
+                }
+            };
 
             RDMA_SRV
                 .channels
@@ -792,10 +809,52 @@ impl RDMAControlChannel {
                 }),
             });
         } else {
-            println!("TODO: no server listening, need ack error back!");
+            self.SendConnectError(connectRequest, SysErr::ECONNREFUSED);
         }
     }
 
+    fn SendConnectError(&self, connectRequest: &ConnectRequest, errno: i32) {
+        self.SendControlMsg(ControlMsgBody::ConnectError(ConnectError {
+            remoteChannelId: connectRequest.remoteChannelId,
+            errno: errno,
+            recvRequestCount: self
+                .chan
+                .upgrade()
+                .unwrap()
+                .conn
+                .localInsertedRecvRequestCount
+                .swap(0, Ordering::SeqCst),
+            remoteSockFd: connectRequest.sockFd,
+        }));
+    }
+
+    // HandleConnectError releases the client channel whose connect is refused
+    // by the peer and passes the error to the client.
+    pub fn HandleConnectError(&self, connectError: &ConnectError) {
+        let rdmaChannel = match RDMA_SRV
+            .channels
+            .lock()
+            .remove(&connectError.remoteChannelId)
+        {
+            Some(c) => c,
+            None => return,
+        };
+
+        RDMA_SRV.channelIdMgr.lock().Remove(rdmaChannel.localId);
+        rdmaChannel
+            .agent
+            .ioBufIdMgr
+            .lock()
+            .Remove(rdmaChannel.ioBufIndex);
+        rdmaChannel.agent.SendResponse(RDMAResp {
+            user_data: 0,
+            msg: RDMARespMsg::RDMAConnectError(RDMAConnectErrorResp {
+                sockfd: connectError.remoteSockFd,
+                errno: connectError.errno,
+            }),
+        });
+    }
+
     //Send msg using control channel
     //Where to be called:
     //1. Connect request: Trigger by SQ.
@@ -894,6 +953,7 @@ impl RDMAControlChannel {
 pub enum ControlMsgBody {
     ConnectRequest(ConnectRequest),
     ConnectResponse(ConnectResponse),
+    ConnectError(ConnectError),
     // ConnectConfirm(ConnectConfirm),
     ConsumedData(ConsumedData),
     RecvRequestCount(RecvRequestCount),
@@ -937,6 +997,14 @@ pub struct ConnectResponse {
     pub remoteSockFd: u32,
 }
 
+#[derive(Clone, Debug)]
+pub struct ConnectError {
+    pub remoteChannelId: u32,
+    pub errno: i32,
+    pub recvRequestCount: u32,
+    pub remoteSockFd: u32,
+}
+
 #[repr(u32)]
 pub enum ControlMsgType {
     ConnectionRequest,
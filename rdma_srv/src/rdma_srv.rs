@@ -12,10 +12,14 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::Ordering;
 use spin::Mutex;
 use std::collections::HashMap;
+use std::fs;
 
 use super::id_mgr::IdMgr;
+use super::qlib::config::Config;
 use super::qlib::rdma_share::*;
 use super::rdma::*;
 use super::rdma_agent::*;
@@ -31,9 +35,25 @@ use std::{env, mem, ptr, thread, time};
 lazy_static! {
     pub static ref RDMA_SRV: RDMASrv = RDMASrv::New();
     pub static ref RDMA_CTLINFO: CtrlInfo = CtrlInfo::default();
+    pub static ref RDMA_CONFIG: Config = LoadConfig();
     //pub static ref RDMA_SRV_SHARED_REGION: ShareRegion = ShareRegion::default();
 }
 
+const QUARK_CONFIG_FILE: &str = "/etc/quark/config.json";
+
+// max RDMA channels across all the agents of the node
+pub const MAX_CHANNEL_COUNT: u32 = 64 * 1024;
+
+// the rdma service shares the quark config file with the sandboxes
+fn LoadConfig() -> Config {
+    let contents = match fs::read_to_string(QUARK_CONFIG_FILE) {
+        Ok(c) => c,
+        _ => return Config::default(),
+    };
+
+    return serde_json::from_str(&contents).expect("configuration wrong format");
+}
+
 #[derive(Clone)]
 pub enum SrvEndPointStatus {
     Binded,
@@ -109,6 +129,10 @@ pub struct RDMASrv {
     pub controlChannelRegionAddress: MemRegion,
     pub controlBufIdMgr: Mutex<IdMgr>,
     pub keys: Vec<[u32; 2]>,
+
+    // timerfd which flushes the coalesced client notifications
+    pub notifyTimerfd: i32,
+    pub notifyTimerArmed: AtomicBool,
 }
 
 impl Drop for RDMASrv {
@@ -212,7 +236,7 @@ impl RDMASrv {
             },
             srvEndPoints: Mutex::new(HashMap::new()),
             currNode: Node::default(),
-            channelIdMgr: Mutex::new(IdMgr::Init(1, 1000)),
+            channelIdMgr: Mutex::new(IdMgr::Init(1, MAX_CHANNEL_COUNT)),
             agentIdMgr: Mutex::new(IdMgr::Init(0, 1000)),
             controlRegion: unsafe {
                 let addr = contrlAddr as *mut RDMAControlChannelRegion;
@@ -226,6 +250,13 @@ impl RDMASrv {
             keys: vec![[mr.LKey(), mr.RKey()]],
             controlChannels: Mutex::new(HashMap::new()),
             controlChannels2: Mutex::new(HashMap::new()),
+            notifyTimerfd: unsafe {
+                libc::timerfd_create(
+                    libc::CLOCK_MONOTONIC,
+                    libc::TFD_NONBLOCK | libc::TFD_CLOEXEC,
+                )
+            },
+            notifyTimerArmed: AtomicBool::new(false),
         };
     }
 
@@ -326,6 +357,49 @@ impl RDMASrv {
         }
     }
 
+    // ArmNotifyTimer makes sure a coalesced notification waits no more than
+    // RDMANotifyDelay microseconds
+    pub fn ArmNotifyTimer(&self) {
+        if self.notifyTimerArmed.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let delay = RDMA_CONFIG.RDMANotifyDelay;
+        let spec = libc::itimerspec {
+            it_interval: libc::timespec {
+                tv_sec: 0,
+                tv_nsec: 0,
+            },
+            it_value: libc::timespec {
+                tv_sec: (delay / 1_000_000) as i64,
+                tv_nsec: (delay % 1_000_000 * 1000) as i64,
+            },
+        };
+
+        let ret = unsafe { libc::timerfd_settime(self.notifyTimerfd, 0, &spec, ptr::null_mut()) };
+        if ret < 0 {
+            println!("ArmNotifyTimer fail: {}", std::io::Error::last_os_error());
+            self.notifyTimerArmed.store(false, Ordering::SeqCst);
+        }
+    }
+
+    pub fn HandleNotifyTimer(&self) {
+        let mut expirations: u64 = 0;
+        unsafe {
+            libc::read(
+                self.notifyTimerfd,
+                &mut expirations as *mut _ as *mut libc::c_void,
+                8,
+            )
+        };
+
+        self.notifyTimerArmed.store(false, Ordering::SeqCst);
+        let rdmaAgents: Vec<RDMAAgent> = self.agents.lock().values().cloned().collect();
+        for rdmaAgent in rdmaAgents.iter() {
+            rdmaAgent.FlushNotify();
+        }
+    }
+
     // pub fn CreateRDMAChannel(&self, agentId: u32) {
     //     let channelId = self.channelIdMgr.lock().AllocId();
 
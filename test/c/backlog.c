@@ -0,0 +1,113 @@
+// backlog test: the accept queue is bounded by the listen() backlog, its
+// depth is reported in TCP_INFO, and net.core.somaxconn is readable.
+
+#define _GNU_SOURCE
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <netinet/in.h>
+#include <netinet/tcp.h>
+#include <arpa/inet.h>
+#include <sys/socket.h>
+
+static int failed;
+
+#define CHECK(cond, ...)                                                       \
+  do {                                                                         \
+    if (!(cond)) {                                                             \
+      fprintf(stderr, __VA_ARGS__);                                            \
+      fprintf(stderr, " (line %d, errno %d)\n", __LINE__, errno);              \
+      failed = 1;                                                              \
+    }                                                                          \
+  } while (0)
+
+#define BACKLOG 2
+#define CLIENTS 6
+
+static int readint(const char *path) {
+  char buf[32] = {0};
+  int fd = open(path, O_RDONLY);
+  if (fd < 0)
+    return -1;
+  int n = read(fd, buf, sizeof(buf) - 1);
+  close(fd);
+  if (n <= 0)
+    return -1;
+  return atoi(buf);
+}
+
+// queued waits up to ms until the listener reports n queued connections
+// and returns the last tcpi_unacked.
+static int queued(int fd, int n, int ms, struct tcp_info *info) {
+  for (int i = 0;; i++) {
+    socklen_t len = sizeof(*info);
+    memset(info, 0, sizeof(*info));
+    if (getsockopt(fd, SOL_TCP, TCP_INFO, info, &len) != 0)
+      return -1;
+    if ((int)info->tcpi_unacked >= n || i >= ms / 5)
+      break;
+    usleep(5000);
+  }
+  return info->tcpi_unacked;
+}
+
+int main() {
+  int somaxconn = readint("/proc/sys/net/core/somaxconn");
+  CHECK(somaxconn > 0, "somaxconn %d", somaxconn);
+  int abort = readint("/proc/sys/net/ipv4/tcp_abort_on_overflow");
+  CHECK(abort == 0 || abort == 1, "tcp_abort_on_overflow %d", abort);
+
+  int l = socket(AF_INET, SOCK_STREAM, 0);
+  struct sockaddr_in addr = {0};
+  addr.sin_family = AF_INET;
+  addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+  socklen_t alen = sizeof(addr);
+  CHECK(bind(l, (struct sockaddr *)&addr, alen) == 0, "bind");
+  CHECK(getsockname(l, (struct sockaddr *)&addr, &alen) == 0, "getsockname");
+  CHECK(listen(l, BACKLOG) == 0, "listen");
+
+  int c[CLIENTS];
+  for (int i = 0; i < CLIENTS; i++) {
+    c[i] = socket(AF_INET, SOCK_STREAM | SOCK_NONBLOCK, 0);
+    int r = connect(c[i], (struct sockaddr *)&addr, sizeof(addr));
+    CHECK(r == 0 || errno == EINPROGRESS, "connect %d", i);
+  }
+
+  // as Linux, the queue is full once it holds more than backlog connections
+  struct tcp_info info;
+  int n = queued(l, BACKLOG + 1, 1000, &info);
+  usleep(100000);
+  n = queued(l, BACKLOG + 1, 0, &info);
+  CHECK(n == BACKLOG + 1, "queued %d", n);
+  CHECK(info.tcpi_sacked == BACKLOG, "backlog %d", info.tcpi_sacked);
+
+  // accepting one makes room for another
+  int a = accept(l, NULL, NULL);
+  CHECK(a >= 0, "accept");
+  close(a);
+  n = queued(l, BACKLOG + 1, 1000, &info);
+  CHECK(n == BACKLOG + 1, "queued after accept %d", n);
+
+  // raising the backlog with a second listen lets the others in, the
+  // dropped handshakes are retried by the clients
+  CHECK(listen(l, CLIENTS) == 0, "relisten");
+  n = queued(l, CLIENTS - 1, 10000, &info);
+  CHECK(n == CLIENTS - 1, "queued after relisten %d", n);
+  CHECK(info.tcpi_sacked == CLIENTS, "backlog after relisten %d",
+        info.tcpi_sacked);
+
+  for (int i = 0; i < CLIENTS; i++)
+    close(c[i]);
+  close(l);
+
+  if (failed) {
+    printf("backlog test fail\n");
+    return 1;
+  }
+
+  printf("backlog test pass\n");
+  return 0;
+}
@@ -0,0 +1,119 @@
+// bindtodevice test: SO_BINDTODEVICE binds a socket to an interface and
+// reports it back, IP_FREEBIND allows binding a non-local address, and
+// without CAP_NET_RAW a bound socket can't be moved or made transparent.
+// It runs as root.
+
+#define _GNU_SOURCE
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include <errno.h>
+#include <net/if.h>
+#include <netinet/in.h>
+#include <arpa/inet.h>
+#include <sys/socket.h>
+#include <sys/wait.h>
+
+#ifndef IP_FREEBIND
+#define IP_FREEBIND 15
+#endif
+#ifndef IP_TRANSPARENT
+#define IP_TRANSPARENT 19
+#endif
+
+static int failed;
+
+#define CHECK(cond, ...)                                                       \
+  do {                                                                         \
+    if (!(cond)) {                                                             \
+      fprintf(stderr, __VA_ARGS__);                                            \
+      fprintf(stderr, " (line %d, errno %d)\n", __LINE__, errno);              \
+      failed = 1;                                                              \
+    }                                                                          \
+  } while (0)
+
+int main(int argc, char **argv) {
+  int one = 1;
+  char name[IFNAMSIZ];
+  socklen_t len;
+
+  int s = socket(AF_INET, SOCK_DGRAM, 0);
+  if (s < 0) {
+    perror("socket");
+    return 1;
+  }
+
+  // Bind to lo and read it back, an empty name unbinds.
+  CHECK(setsockopt(s, SOL_SOCKET, SO_BINDTODEVICE, "lo", 3) == 0,
+        "SO_BINDTODEVICE lo");
+  len = sizeof(name);
+  memset(name, 0, sizeof(name));
+  CHECK(getsockopt(s, SOL_SOCKET, SO_BINDTODEVICE, name, &len) == 0 &&
+            strcmp(name, "lo") == 0,
+        "getsockopt SO_BINDTODEVICE %s", name);
+  CHECK(setsockopt(s, SOL_SOCKET, SO_BINDTODEVICE, "nosuchdev0", 11) == -1 &&
+            errno == ENODEV,
+        "SO_BINDTODEVICE unknown device");
+  CHECK(setsockopt(s, SOL_SOCKET, SO_BINDTODEVICE, "", 0) == 0,
+        "SO_BINDTODEVICE unbind");
+  len = sizeof(name);
+  CHECK(getsockopt(s, SOL_SOCKET, SO_BINDTODEVICE, name, &len) == 0 &&
+            len == 0,
+        "unbound len %d", len);
+
+  // IP_FREEBIND allows binding an address no interface has.
+  struct sockaddr_in addr = {0};
+  addr.sin_family = AF_INET;
+  addr.sin_addr.s_addr = inet_addr("192.0.2.123");
+  int f = socket(AF_INET, SOCK_DGRAM, 0);
+  CHECK(bind(f, (struct sockaddr *)&addr, sizeof(addr)) == -1 &&
+            errno == EADDRNOTAVAIL,
+        "bind non-local address");
+  CHECK(setsockopt(f, SOL_IP, IP_FREEBIND, &one, sizeof(one)) == 0,
+        "IP_FREEBIND");
+  CHECK(bind(f, (struct sockaddr *)&addr, sizeof(addr)) == 0,
+        "bind with IP_FREEBIND");
+  int v = 0;
+  len = sizeof(v);
+  CHECK(getsockopt(f, SOL_IP, IP_FREEBIND, &v, &len) == 0 && v == 1,
+        "getsockopt IP_FREEBIND");
+  close(f);
+
+  CHECK(setsockopt(s, SOL_IP, IP_TRANSPARENT, &one, sizeof(one)) == 0,
+        "IP_TRANSPARENT as root");
+
+  // Without capabilities: binding an unbound socket is allowed, moving a
+  // bound one and enabling IP_TRANSPARENT are not.
+  int b = socket(AF_INET, SOCK_DGRAM, 0);
+  CHECK(setsockopt(b, SOL_SOCKET, SO_BINDTODEVICE, "lo", 3) == 0,
+        "SO_BINDTODEVICE lo");
+  pid_t child = fork();
+  if (child == 0) {
+    if (setuid(65534) != 0)
+      _exit(2);
+    int u = socket(AF_INET, SOCK_DGRAM, 0);
+    if (setsockopt(u, SOL_SOCKET, SO_BINDTODEVICE, "lo", 3) != 0)
+      _exit(3);
+    if (setsockopt(b, SOL_SOCKET, SO_BINDTODEVICE, "", 0) != -1 ||
+        errno != EPERM)
+      _exit(4);
+    if (setsockopt(u, SOL_IP, IP_TRANSPARENT, &one, sizeof(one)) != -1 ||
+        errno != EPERM)
+      _exit(5);
+    int zero = 0;
+    if (setsockopt(u, SOL_IP, IP_TRANSPARENT, &zero, sizeof(zero)) != 0)
+      _exit(6);
+    _exit(0);
+  }
+  int status;
+  waitpid(child, &status, 0);
+  CHECK(WIFEXITED(status) && WEXITSTATUS(status) == 0,
+        "unprivileged checks, status %x", status);
+
+  if (failed)
+    return 1;
+
+  printf("bindtodevice ok\n");
+  return 0;
+}
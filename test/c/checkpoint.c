@@ -0,0 +1,193 @@
+// checkpoint test: the state of a process survives a checkpoint followed by a
+// restore. Run it in a container, then on the host
+//   quark checkpoint --image-path /tmp/ckpt <id>
+//   quark restore --image-path /tmp/ckpt <id>
+// The restored process keeps counting from the round of the checkpoint and
+// checks its heap, anonymous and file mappings, thread local storage, sse
+// registers, signal mask, signal action, pending signal, timers, child process,
+// listening socket and file offset in every round.
+
+#define _GNU_SOURCE
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <signal.h>
+#include <pthread.h>
+#include <time.h>
+#include <netinet/in.h>
+#include <arpa/inet.h>
+#include <sys/mman.h>
+#include <sys/socket.h>
+#include <sys/time.h>
+#include <sys/wait.h>
+
+#define HEAP_SIZE (16 << 20)
+#define ROUNDS 600
+
+static int failed;
+
+#define CHECK(cond, ...)                                                       \
+  do {                                                                         \
+    if (!(cond)) {                                                             \
+      fprintf(stderr, __VA_ARGS__);                                            \
+      fprintf(stderr, " (line %d, errno %d)\n", __LINE__, errno);              \
+      failed = 1;                                                              \
+    }                                                                          \
+  } while (0)
+
+static __thread unsigned long tls_rounds;
+static volatile unsigned long thread_rounds;
+
+// the thread keeps its round count in the tls and xmm8, both are restored
+static void *count_thread(void *arg) {
+  unsigned long v;
+  __asm__ volatile("movq %0, %%xmm8" ::"r"(0UL) : "xmm8");
+  for (;;) {
+    __asm__ volatile("movq %%xmm8, %0" : "=r"(v));
+    if (v != tls_rounds) {
+      fprintf(stderr, "thread xmm8 %lx tls %lx\n", v, tls_rounds);
+      exit(1);
+    }
+    tls_rounds++;
+    __asm__ volatile("movq %0, %%xmm8" ::"r"(tls_rounds) : "xmm8");
+    thread_rounds = tls_rounds;
+    usleep(1000);
+  }
+  return NULL;
+}
+
+static unsigned char pattern(unsigned long i) { return (i * 7 + 3) & 0xff; }
+
+static void usr2_handler(int sig) { (void)sig; }
+
+int main() {
+  unsigned char *heap = malloc(HEAP_SIZE);
+  CHECK(heap != NULL, "malloc");
+  for (unsigned long i = 0; i < HEAP_SIZE; i++)
+    heap[i] = pattern(i);
+
+  unsigned long *anon = mmap(NULL, 4096, PROT_READ | PROT_WRITE,
+                             MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+  CHECK(anon != MAP_FAILED, "mmap anon");
+
+  // a shared anonymous mapping is seen by the restored process as well
+  unsigned long *shared = mmap(NULL, 4096, PROT_READ | PROT_WRITE,
+                               MAP_SHARED | MAP_ANONYMOUS, -1, 0);
+  CHECK(shared != MAP_FAILED, "mmap shared");
+
+  int fd = open("/tmp/checkpoint_test", O_RDWR | O_CREAT | O_TRUNC, 0644);
+  CHECK(fd >= 0, "open");
+  char line[16];
+  memset(line, 'x', sizeof(line));
+  for (int i = 0; i < 2; i++)
+    CHECK(write(fd, line, sizeof(line)) == sizeof(line), "write");
+
+  // the private file mapping has a written page and a clean one
+  char *fmap = mmap(NULL, 4096, PROT_READ | PROT_WRITE, MAP_PRIVATE, fd, 0);
+  CHECK(fmap != MAP_FAILED, "mmap file");
+  fmap[0] = 'p';
+
+  sigset_t mask, old, pending;
+  sigemptyset(&mask);
+  sigaddset(&mask, SIGUSR1);
+  CHECK(sigprocmask(SIG_BLOCK, &mask, NULL) == 0, "sigprocmask");
+  // the blocked SIGUSR1 stays pending
+  CHECK(kill(getpid(), SIGUSR1) == 0, "kill");
+
+  struct sigaction act, oldact;
+  memset(&act, 0, sizeof(act));
+  act.sa_handler = usr2_handler;
+  act.sa_flags = SA_RESTART;
+  CHECK(sigaction(SIGUSR2, &act, NULL) == 0, "sigaction");
+
+  // the timers expire after the test
+  struct itimerval itv = {{0, 0}, {3600, 0}}, olditv;
+  CHECK(setitimer(ITIMER_REAL, &itv, NULL) == 0, "setitimer");
+  struct sigevent sev;
+  memset(&sev, 0, sizeof(sev));
+  sev.sigev_notify = SIGEV_SIGNAL;
+  sev.sigev_signo = SIGUSR2;
+  timer_t timer;
+  CHECK(timer_create(CLOCK_MONOTONIC, &sev, &timer) == 0, "timer_create");
+  struct itimerspec its = {{0, 0}, {3600, 0}}, oldits;
+  CHECK(timer_settime(timer, 0, &its, NULL) == 0, "timer_settime");
+
+  // the child stays the child of the restored process
+  pid_t child = fork();
+  if (child == 0) {
+    for (;;)
+      pause();
+  }
+  CHECK(child > 0, "fork");
+
+  int lfd = socket(AF_INET, SOCK_STREAM, 0);
+  CHECK(lfd >= 0, "socket");
+  struct sockaddr_in addr = {.sin_family = AF_INET};
+  addr.sin_addr.s_addr = inet_addr("127.0.0.1");
+  socklen_t addrlen = sizeof(addr);
+  CHECK(bind(lfd, (struct sockaddr *)&addr, sizeof(addr)) == 0, "bind");
+  CHECK(listen(lfd, 16) == 0, "listen");
+  CHECK(getsockname(lfd, (struct sockaddr *)&addr, &addrlen) == 0,
+        "getsockname");
+  unsigned short port = addr.sin_port;
+
+  pthread_t thread;
+  CHECK(pthread_create(&thread, NULL, count_thread, NULL) == 0,
+        "pthread_create");
+
+  if (failed)
+    return 1;
+
+  for (unsigned long round = 0; round < ROUNDS; round++) {
+    *anon = round;
+    *shared = round;
+    CHECK(lseek(fd, 0, SEEK_CUR) == 2 * sizeof(line), "file offset");
+
+    sleep(1);
+
+    CHECK(*anon == round, "anon %lu round %lu", *anon, round);
+    CHECK(*shared == round, "shared %lu round %lu", *shared, round);
+    CHECK(fmap[0] == 'p' && fmap[1] == 'x', "file mapping");
+    for (unsigned long i = 0; i < HEAP_SIZE; i += 4093)
+      CHECK(heap[i] == pattern(i), "heap at %lu", i);
+    CHECK(sigprocmask(SIG_BLOCK, NULL, &old) == 0 &&
+              sigismember(&old, SIGUSR1),
+          "signal mask");
+    CHECK(sigpending(&pending) == 0 && sigismember(&pending, SIGUSR1),
+          "pending signal");
+    CHECK(sigaction(SIGUSR2, NULL, &oldact) == 0 &&
+              oldact.sa_handler == usr2_handler &&
+              (oldact.sa_flags & SA_RESTART),
+          "signal action");
+    CHECK(getitimer(ITIMER_REAL, &olditv) == 0 && olditv.it_value.tv_sec > 0,
+          "itimer");
+    CHECK(timer_gettime(timer, &oldits) == 0 && oldits.it_value.tv_sec > 0,
+          "posix timer");
+    CHECK(waitpid(child, NULL, WNOHANG) == 0, "child");
+    addrlen = sizeof(addr);
+    int accepting = 0;
+    socklen_t optlen = sizeof(accepting);
+    CHECK(getsockname(lfd, (struct sockaddr *)&addr, &addrlen) == 0 &&
+              addr.sin_port == port,
+          "socket address");
+    CHECK(getsockopt(lfd, SOL_SOCKET, SO_ACCEPTCONN, &accepting, &optlen) ==
+                  0 &&
+              accepting,
+          "listening socket");
+    if (failed) {
+      printf("checkpoint test fail in round %lu\n", round);
+      return 1;
+    }
+
+    printf("round %lu ok, thread rounds %lu\n", round, thread_rounds);
+    fflush(stdout);
+  }
+
+  kill(child, SIGKILL);
+  waitpid(child, NULL, 0);
+  printf("checkpoint test pass\n");
+  return 0;
+}
@@ -0,0 +1,112 @@
+// clocks test: CLOCK_BOOTTIME and CLOCK_TAI are readable and settle next to
+// the monotonic and realtime clocks, clock_nanosleep sleeps on them with both
+// relative and absolute deadlines, and the clocks Linux can't sleep on fail.
+
+#define _GNU_SOURCE
+#include <stdio.h>
+#include <string.h>
+#include <unistd.h>
+#include <errno.h>
+#include <signal.h>
+#include <time.h>
+
+#ifndef CLOCK_TAI
+#define CLOCK_TAI 11
+#endif
+
+static int failed;
+
+#define CHECK(cond, ...)                                                       \
+  do {                                                                         \
+    if (!(cond)) {                                                             \
+      fprintf(stderr, __VA_ARGS__);                                            \
+      fprintf(stderr, " (line %d, errno %d)\n", __LINE__, errno);              \
+      failed = 1;                                                              \
+    }                                                                          \
+  } while (0)
+
+static long long now_ns(clockid_t clock) {
+  struct timespec ts;
+  if (clock_gettime(clock, &ts) != 0)
+    return -1;
+  return ts.tv_sec * 1000000000LL + ts.tv_nsec;
+}
+
+static void sleep_on(clockid_t clock, const char *name) {
+  // relative sleep
+  long long start = now_ns(clock);
+  struct timespec req = {0, 20 * 1000 * 1000};
+  CHECK(clock_nanosleep(clock, 0, &req, NULL) == 0, "%s relative", name);
+  CHECK(now_ns(clock) - start >= 20 * 1000 * 1000, "%s relative early", name);
+
+  // absolute sleep
+  long long end = now_ns(clock) + 20 * 1000 * 1000;
+  struct timespec abs = {end / 1000000000LL, end % 1000000000LL};
+  CHECK(clock_nanosleep(clock, TIMER_ABSTIME, &abs, NULL) == 0, "%s absolute",
+        name);
+  CHECK(now_ns(clock) >= end, "%s absolute early", name);
+
+  // a deadline in the past returns at once
+  abs.tv_sec -= 1;
+  CHECK(clock_nanosleep(clock, TIMER_ABSTIME, &abs, NULL) == 0, "%s past",
+        name);
+}
+
+static void on_alarm(int sig) { (void)sig; }
+
+int main() {
+  long long mono = now_ns(CLOCK_MONOTONIC);
+  long long boot = now_ns(CLOCK_BOOTTIME);
+  CHECK(boot >= mono, "boottime %lld before monotonic %lld", boot, mono);
+
+  long long real = now_ns(CLOCK_REALTIME);
+  long long tai = now_ns(CLOCK_TAI);
+  CHECK(tai >= real, "tai %lld before realtime %lld", tai, real);
+  CHECK((tai - real) % 1000000000LL < 100 * 1000 * 1000,
+        "tai offset %lld is not whole seconds", tai - real);
+
+  struct timespec res;
+  CHECK(clock_getres(CLOCK_BOOTTIME, &res) == 0, "getres boottime");
+  CHECK(clock_getres(CLOCK_TAI, &res) == 0, "getres tai");
+
+  sleep_on(CLOCK_REALTIME, "realtime");
+  sleep_on(CLOCK_MONOTONIC, "monotonic");
+  sleep_on(CLOCK_BOOTTIME, "boottime");
+  sleep_on(CLOCK_TAI, "tai");
+
+  struct timespec req = {0, 1000};
+  CHECK(clock_nanosleep(CLOCK_THREAD_CPUTIME_ID, 0, &req, NULL) == EINVAL,
+        "thread cputime");
+  CHECK(clock_nanosleep(CLOCK_MONOTONIC_RAW, 0, &req, NULL) == EOPNOTSUPP,
+        "monotonic raw");
+
+  // an interrupted absolute sleep reports EINTR and leaves rem alone
+  struct sigaction sa;
+  memset(&sa, 0, sizeof(sa));
+  sa.sa_handler = on_alarm;
+  sigaction(SIGALRM, &sa, NULL);
+  ualarm(50 * 1000, 0);
+
+  long long end = now_ns(CLOCK_BOOTTIME) + 2000000000LL;
+  struct timespec abs = {end / 1000000000LL, end % 1000000000LL};
+  struct timespec rem = {-1, -1};
+  CHECK(clock_nanosleep(CLOCK_BOOTTIME, TIMER_ABSTIME, &abs, &rem) == EINTR,
+        "interrupted absolute");
+  CHECK(rem.tv_sec == -1 && rem.tv_nsec == -1, "absolute rem written");
+
+  // an interrupted relative sleep reports the remaining time
+  ualarm(50 * 1000, 0);
+  req.tv_sec = 2;
+  req.tv_nsec = 0;
+  CHECK(clock_nanosleep(CLOCK_BOOTTIME, 0, &req, &rem) == EINTR,
+        "interrupted relative");
+  CHECK(rem.tv_sec >= 1 && rem.tv_sec <= 2, "relative rem %ld", rem.tv_sec);
+
+  if (failed) {
+    printf("clocks test fail\n");
+    return 1;
+  }
+
+  printf("clocks test pass\n");
+  return 0;
+}
@@ -0,0 +1,153 @@
+// compat32 test: a dynamic 32 bit program runs on the ia32 syscalls. It is
+// built with gcc -m32, ld.so finds the compat vdso through AT_SYSINFO_EHDR,
+// libc sets up its tls with set_thread_area, the signal handlers get the 32
+// bit frames and the sockets go through socketcall.
+
+#define _GNU_SOURCE
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include <errno.h>
+#include <signal.h>
+#include <ucontext.h>
+#include <sys/auxv.h>
+#include <sys/socket.h>
+#include <sys/syscall.h>
+#include <linux/net.h>
+#include <asm/ldt.h>
+
+#if !defined(__i386__)
+#error "compat32 must be built with -m32"
+#endif
+
+static int failed;
+
+#define CHECK(cond, ...)                                                       \
+  do {                                                                         \
+    if (!(cond)) {                                                             \
+      fprintf(stderr, __VA_ARGS__);                                            \
+      fprintf(stderr, " (line %d, errno %d)\n", __LINE__, errno);              \
+      failed = 1;                                                              \
+    }                                                                          \
+  } while (0)
+
+static __thread int tls_var = 42;
+
+static volatile int info_signo;
+static volatile int info_pid;
+static volatile int info_uc;
+static volatile int plain_signo;
+static volatile int on_altstack;
+static char altstack[65536];
+
+static void info_handler(int sig, siginfo_t *info, void *ucv) {
+  ucontext_t *uc = ucv;
+  info_signo = info->si_signo;
+  info_pid = info->si_pid;
+  info_uc = uc != NULL && uc->uc_mcontext.gregs[REG_EIP] != 0;
+
+  // the handler runs on the tls of the thread
+  tls_var++;
+}
+
+static void plain_handler(int sig) {
+  char c;
+  plain_signo = sig;
+  on_altstack = &c >= altstack && &c < altstack + sizeof(altstack);
+}
+
+static int gs_sel(void) {
+  int sel;
+  __asm__ volatile("movl %%gs, %0" : "=r"(sel));
+  return sel;
+}
+
+int main() {
+  // the compat vdso
+  CHECK(getauxval(AT_SYSINFO_EHDR) != 0, "no AT_SYSINFO_EHDR");
+  CHECK(getauxval(AT_SYSINFO) != 0, "no AT_SYSINFO");
+
+  // the tls of libc: errno and __thread are gs relative
+  CHECK(tls_var == 42, "tls_var %d", tls_var);
+  errno = 0;
+  CHECK(close(-1) == -1 && errno == EBADF, "errno");
+
+  struct user_desc desc;
+  memset(&desc, 0, sizeof(desc));
+  desc.entry_number = gs_sel() >> 3;
+  CHECK(syscall(SYS_get_thread_area, &desc) == 0, "get_thread_area");
+  CHECK(desc.seg_32bit && desc.base_addr != 0, "tls desc base %x",
+        desc.base_addr);
+
+  // a new entry is allocated with entry_number -1
+  memset(&desc, 0, sizeof(desc));
+  desc.entry_number = -1;
+  desc.base_addr = (unsigned long)&tls_var;
+  desc.limit = 0xfffff;
+  desc.seg_32bit = 1;
+  desc.limit_in_pages = 1;
+  desc.useable = 1;
+  CHECK(syscall(SYS_set_thread_area, &desc) == 0, "set_thread_area");
+  CHECK(desc.entry_number >= 12 && desc.entry_number <= 14, "entry %d",
+        desc.entry_number);
+  int sel = desc.entry_number * 8 + 3, v;
+  __asm__ volatile("movl %%fs, %%ecx\n"
+                   "movl %1, %%fs\n"
+                   "movl %%fs:0, %0\n"
+                   "movl %%ecx, %%fs\n"
+                   : "=r"(v)
+                   : "r"(sel)
+                   : "ecx", "memory");
+  CHECK(v == tls_var, "fs:0 is %d", v);
+
+  // the rt frame of a SA_SIGINFO handler
+  struct sigaction sa;
+  memset(&sa, 0, sizeof(sa));
+  sa.sa_sigaction = info_handler;
+  sa.sa_flags = SA_SIGINFO;
+  CHECK(sigaction(SIGUSR1, &sa, NULL) == 0, "sigaction SIGUSR1");
+  CHECK(kill(getpid(), SIGUSR1) == 0, "kill SIGUSR1");
+  CHECK(info_signo == SIGUSR1, "si_signo %d", info_signo);
+  CHECK(info_pid == getpid(), "si_pid %d", info_pid);
+  CHECK(info_uc, "ucontext");
+  CHECK(tls_var == 43, "tls_var %d after the handler", tls_var);
+
+  struct sigaction old;
+  CHECK(sigaction(SIGUSR1, NULL, &old) == 0 &&
+            old.sa_sigaction == info_handler && (old.sa_flags & SA_SIGINFO),
+        "old sigaction");
+
+  // the frame of a plain handler on the alternate stack
+  stack_t ss = {.ss_sp = altstack, .ss_size = sizeof(altstack)};
+  CHECK(sigaltstack(&ss, NULL) == 0, "sigaltstack");
+  memset(&sa, 0, sizeof(sa));
+  sa.sa_handler = plain_handler;
+  sa.sa_flags = SA_ONSTACK;
+  CHECK(sigaction(SIGUSR2, &sa, NULL) == 0, "sigaction SIGUSR2");
+  CHECK(raise(SIGUSR2) == 0, "raise SIGUSR2");
+  CHECK(plain_signo == SIGUSR2, "plain handler %d", plain_signo);
+  CHECK(on_altstack, "not on the alternate stack");
+
+  // socketcall
+  int fds[2];
+  unsigned long args[4] = {AF_UNIX, SOCK_STREAM, 0, (unsigned long)fds};
+  CHECK(syscall(SYS_socketcall, SYS_SOCKETPAIR, args) == 0, "socketpair");
+  unsigned long sargs[4] = {fds[0], (unsigned long)"ping", 4, 0};
+  CHECK(syscall(SYS_socketcall, SYS_SEND, sargs) == 4, "send");
+  char buf[8] = {0};
+  unsigned long rargs[4] = {fds[1], (unsigned long)buf, sizeof(buf), 0};
+  CHECK(syscall(SYS_socketcall, SYS_RECV, rargs) == 4 &&
+            memcmp(buf, "ping", 4) == 0,
+        "recv");
+  close(fds[0]);
+  close(fds[1]);
+
+  if (failed) {
+    printf("compat32 test fail\n");
+    return 1;
+  }
+
+  printf("compat32 test pass\n");
+  return 0;
+}
@@ -0,0 +1,95 @@
+// dcache test: a name looked up while missing is found once it is created by
+// open, mkdir, symlink, link, mknod or rename, and is missing again after it
+// is removed.
+
+#define _GNU_SOURCE
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <sys/stat.h>
+
+static int failed;
+
+static void expect(const char *step, const char *path, int exist) {
+  struct stat st;
+
+  // walk the name twice, the second time is served by the dentry cache
+  for (int i = 0; i < 2; i++) {
+    int ret = stat(path, &st);
+    if (exist && ret < 0) {
+      fprintf(stderr, "%s: stat %s: %s\n", step, path, strerror(errno));
+      failed = 1;
+    } else if (!exist && (ret == 0 || errno != ENOENT)) {
+      fprintf(stderr, "%s: %s exists\n", step, path);
+      failed = 1;
+    }
+  }
+}
+
+int main(int argc, char **argv) {
+  char dir[] = "dcacheXXXXXX";
+  char name[64], other[64];
+
+  if (mkdtemp(dir) == NULL) {
+    perror("mkdtemp");
+    return 1;
+  }
+  snprintf(name, sizeof(name), "%s/f", dir);
+  snprintf(other, sizeof(other), "%s/g", dir);
+
+  expect("initial", name, 0);
+  int fd = open(name, O_CREAT | O_WRONLY, 0644);
+  if (fd < 0) {
+    perror("open");
+    return 1;
+  }
+  close(fd);
+  expect("open", name, 1);
+
+  unlink(name);
+  expect("unlink", name, 0);
+  mkdir(name, 0755);
+  expect("mkdir", name, 1);
+
+  rmdir(name);
+  expect("rmdir", name, 0);
+  symlink("g", name);
+  expect("symlink", other, 0);
+  struct stat st;
+  if (lstat(name, &st) < 0) {
+    perror("lstat");
+    failed = 1;
+  }
+
+  fd = open(other, O_CREAT | O_WRONLY, 0644);
+  close(fd);
+  expect("symlink target", name, 1);
+
+  unlink(name);
+  expect("unlink symlink", name, 0);
+  link(other, name);
+  expect("link", name, 1);
+
+  unlink(name);
+  expect("unlink link", name, 0);
+  mknod(name, S_IFIFO | 0644, 0);
+  expect("mknod", name, 1);
+
+  unlink(name);
+  expect("unlink fifo", name, 0);
+  rename(other, name);
+  expect("rename", name, 1);
+  expect("rename source", other, 0);
+
+  unlink(name);
+  rmdir(dir);
+
+  if (failed)
+    return 1;
+
+  printf("dcache ok\n");
+  return 0;
+}
@@ -0,0 +1,114 @@
+// dup test: dup/dup2/dup3/F_DUPFD_CLOEXEC fd flag and offset sharing rules,
+// dup3 argument checks, and that a descriptor installed with O_CLOEXEC by one
+// thread is never visible without the flag to another thread or across exec.
+
+#define _GNU_SOURCE
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <pthread.h>
+#include <sys/wait.h>
+
+#define TARGET 100
+
+static int failed;
+static volatile int stop;
+static int fda, fdb;
+
+#define CHECK(cond, ...)                                                       \
+  do {                                                                         \
+    if (!(cond)) {                                                             \
+      fprintf(stderr, __VA_ARGS__);                                            \
+      fprintf(stderr, " (line %d, errno %d)\n", __LINE__, errno);              \
+      failed = 1;                                                              \
+    }                                                                          \
+  } while (0)
+
+static void *duploop(void *arg) {
+  while (!stop) {
+    dup3(fda, TARGET, O_CLOEXEC);
+    dup3(fdb, TARGET, O_CLOEXEC);
+  }
+  return NULL;
+}
+
+int main(int argc, char **argv) {
+  if (argc > 1 && strcmp(argv[1], "exec") == 0) {
+    // Anything installed with O_CLOEXEC must be gone after exec.
+    return fcntl(TARGET, F_GETFD) == -1 && errno == EBADF ? 0 : 1;
+  }
+
+  char file[] = "/tmp/dupXXXXXX";
+  int fd = mkstemp(file);
+  if (fd < 0) {
+    perror("mkstemp");
+    return 1;
+  }
+  unlink(file);
+  write(fd, "0123456789", 10);
+
+  // dup and dup2 clear FD_CLOEXEC; F_DUPFD_CLOEXEC and dup3 set it.
+  CHECK(fcntl(fd, F_SETFD, FD_CLOEXEC) == 0, "F_SETFD");
+  int d = dup(fd);
+  CHECK(fcntl(d, F_GETFD) == 0, "dup kept FD_CLOEXEC");
+  CHECK(dup2(fd, 50) == 50 && fcntl(50, F_GETFD) == 0,
+        "dup2 kept FD_CLOEXEC");
+  CHECK(dup3(d, 50, O_CLOEXEC) == 50 && fcntl(50, F_GETFD) == FD_CLOEXEC,
+        "dup3 O_CLOEXEC");
+  int c = fcntl(d, F_DUPFD_CLOEXEC, 60);
+  CHECK(c >= 60 && fcntl(c, F_GETFD) == FD_CLOEXEC, "F_DUPFD_CLOEXEC");
+  CHECK(fcntl(fd, F_GETFD) == FD_CLOEXEC, "flags are per descriptor");
+
+  // The offset belongs to the open file and is shared by every duplicate.
+  CHECK(lseek(fd, 3, SEEK_SET) == 3, "lseek");
+  CHECK(lseek(50, 0, SEEK_CUR) == 3 && lseek(c, 0, SEEK_CUR) == 3,
+        "offset not shared");
+
+  // dup3 argument checks, and a bad oldfd leaves newfd alone.
+  CHECK(dup3(d, d, 0) == -1 && errno == EINVAL, "dup3 same fd");
+  CHECK(dup3(d, 51, O_NONBLOCK) == -1 && errno == EINVAL, "dup3 bad flags");
+  CHECK(dup2(d, d) == d, "dup2 same fd");
+  CHECK(dup2(999, 50) == -1 && errno == EBADF, "dup2 bad oldfd");
+  CHECK(fcntl(50, F_GETFD) == FD_CLOEXEC, "bad dup2 closed newfd");
+  CHECK(dup2(d, -1) == -1 && errno == EBADF, "dup2 negative newfd");
+
+  // Concurrent dup3 never exposes the target closed or without CLOEXEC.
+  fda = open("/dev/null", O_RDONLY);
+  fdb = open("/dev/zero", O_RDONLY);
+  dup3(fda, TARGET, O_CLOEXEC);
+  pthread_t th;
+  pthread_create(&th, NULL, duploop, NULL);
+  for (int i = 0; i < 100000; i++) {
+    int flags = fcntl(TARGET, F_GETFD);
+    if (flags != FD_CLOEXEC) {
+      CHECK(0, "target fd flags %d", flags);
+      break;
+    }
+  }
+
+  // exec while another thread keeps installing the descriptor.
+  pid_t child = fork();
+  if (child == 0) {
+    pthread_t t2;
+    pthread_create(&t2, NULL, duploop, NULL);
+    usleep(1000);
+    execl("/proc/self/exe", argv[0], "exec", NULL);
+    _exit(2);
+  }
+  int status;
+  waitpid(child, &status, 0);
+  CHECK(WIFEXITED(status) && WEXITSTATUS(status) == 0,
+        "CLOEXEC fd survived exec, status %x", status);
+
+  stop = 1;
+  pthread_join(th, NULL);
+
+  if (failed)
+    return 1;
+
+  printf("dup ok\n");
+  return 0;
+}
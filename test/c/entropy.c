@@ -0,0 +1,107 @@
+// entropy test: /proc/sys/kernel/random reports the entropy pool, the random
+// devices answer the entropy ioctls and the privileged ones need
+// CAP_SYS_ADMIN.
+
+#define _GNU_SOURCE
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <sys/ioctl.h>
+#include <linux/random.h>
+
+static int failed;
+
+#define CHECK(cond, ...)                                                       \
+  do {                                                                         \
+    if (!(cond)) {                                                             \
+      fprintf(stderr, __VA_ARGS__);                                            \
+      fprintf(stderr, " (line %d, errno %d)\n", __LINE__, errno);              \
+      failed = 1;                                                              \
+    }                                                                          \
+  } while (0)
+
+static int read_file(const char *path, char *buf, int len) {
+  int fd = open(path, O_RDONLY);
+  if (fd < 0)
+    return -1;
+  int n = read(fd, buf, len - 1);
+  close(fd);
+  if (n < 0)
+    return -1;
+  buf[n] = 0;
+  return n;
+}
+
+static long read_long(const char *path) {
+  char buf[64];
+  if (read_file(path, buf, sizeof(buf)) < 0)
+    return -1;
+  return atol(buf);
+}
+
+int main() {
+  long poolsize = read_long("/proc/sys/kernel/random/poolsize");
+  CHECK(poolsize > 0, "poolsize %ld", poolsize);
+
+  long avail = read_long("/proc/sys/kernel/random/entropy_avail");
+  CHECK(avail >= 0 && avail <= poolsize, "entropy_avail %ld", avail);
+  CHECK(read_long("/proc/sys/kernel/random/write_wakeup_threshold") >= 0,
+        "write_wakeup_threshold");
+  CHECK(read_long("/proc/sys/kernel/random/urandom_min_reseed_secs") >= 0,
+        "urandom_min_reseed_secs");
+
+  // the boot id stays, each read of uuid is a new one
+  char b1[64], b2[64], u1[64], u2[64];
+  CHECK(read_file("/proc/sys/kernel/random/boot_id", b1, sizeof(b1)) == 37,
+        "boot_id");
+  CHECK(read_file("/proc/sys/kernel/random/boot_id", b2, sizeof(b2)) == 37 &&
+            strcmp(b1, b2) == 0,
+        "boot_id changed");
+  CHECK(read_file("/proc/sys/kernel/random/uuid", u1, sizeof(u1)) == 37 &&
+            u1[14] == '4',
+        "uuid %s", u1);
+  CHECK(read_file("/proc/sys/kernel/random/uuid", u2, sizeof(u2)) == 37 &&
+            strcmp(u1, u2) != 0,
+        "uuid repeated");
+
+  int fd = open("/dev/urandom", O_RDWR);
+  CHECK(fd >= 0, "open /dev/urandom");
+
+  int count = -1;
+  CHECK(ioctl(fd, RNDGETENTCNT, &count) == 0, "RNDGETENTCNT");
+  CHECK(count >= 0 && count <= poolsize, "entropy count %d", count);
+
+  struct {
+    struct rand_pool_info info;
+    unsigned char buf[16];
+  } add;
+  add.info.entropy_count = 128;
+  add.info.buf_size = sizeof(add.buf);
+  memset(add.buf, 0x5a, sizeof(add.buf));
+
+  int ret = ioctl(fd, RNDADDENTROPY, &add);
+  if (geteuid() == 0) {
+    CHECK(ret == 0, "RNDADDENTROPY");
+    CHECK(ioctl(fd, RNDADDTOENTCNT, &count) == 0, "RNDADDTOENTCNT");
+    int bad = -1;
+    CHECK(ioctl(fd, RNDADDTOENTCNT, &bad) < 0 && errno == EINVAL,
+          "negative RNDADDTOENTCNT");
+  } else {
+    CHECK(ret < 0 && errno == EPERM, "unprivileged RNDADDENTROPY");
+  }
+
+  unsigned char data[32];
+  CHECK(read(fd, data, sizeof(data)) == sizeof(data), "read /dev/urandom");
+  close(fd);
+
+  if (failed) {
+    printf("entropy test fail\n");
+    return 1;
+  }
+
+  printf("entropy test pass\n");
+  return 0;
+}
@@ -0,0 +1,187 @@
+// epoll stress test: several threads wait on one epoll fd watching the
+// receiving ends of a set of loopback tcp connections while a producer
+// writes to them. Every byte must be delivered, a consumer that times out
+// with data still in flight means a wakeup was lost.
+//
+// usage: epollst [et|lt]
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <pthread.h>
+#include <arpa/inet.h>
+#include <netinet/in.h>
+#include <sys/epoll.h>
+#include <sys/socket.h>
+
+#define NUM_CONNS 16
+#define NUM_CONSUMERS 4
+#define ROUNDS 100000
+#define MSG_SIZE 64
+#define WAIT_MS 5000
+
+static int epfd;
+static int rfds[NUM_CONNS];
+static int wfds[NUM_CONNS];
+static int edge = 1;
+static long expected = (long)ROUNDS * MSG_SIZE;
+static long received;
+static long spurious;
+static long wakeups;
+static int failed;
+
+static void die(const char *msg) {
+  perror(msg);
+  exit(1);
+}
+
+static void *consumer(void *arg) {
+  struct epoll_event events[NUM_CONNS];
+  char buf[4096];
+
+  while (__atomic_load_n(&received, __ATOMIC_SEQ_CST) < expected && !failed) {
+    int n = epoll_wait(epfd, events, NUM_CONNS, WAIT_MS);
+    if (n < 0) {
+      if (errno == EINTR)
+        continue;
+      die("epoll_wait");
+    }
+
+    if (n == 0) {
+      long got = __atomic_load_n(&received, __ATOMIC_SEQ_CST);
+      if (got < expected) {
+        fprintf(stderr, "lost wakeup: received %ld of %ld bytes\n", got, expected);
+        failed = 1;
+      }
+      break;
+    }
+
+    __atomic_add_fetch(&wakeups, n, __ATOMIC_SEQ_CST);
+    for (int i = 0; i < n; i++) {
+      int fd = events[i].data.fd;
+      int first = 1;
+      for (;;) {
+        ssize_t r = read(fd, buf, sizeof(buf));
+        if (r > 0) {
+          __atomic_add_fetch(&received, r, __ATOMIC_SEQ_CST);
+          first = 0;
+          // level triggered consumers read once per event
+          if (!edge)
+            break;
+          continue;
+        }
+
+        if (r < 0 && errno == EINTR)
+          continue;
+        if (r < 0 && errno != EAGAIN)
+          die("read");
+        if (r == 0) {
+          fprintf(stderr, "unexpected eof on fd %d\n", fd);
+          failed = 1;
+        }
+        break;
+      }
+
+      if (first)
+        __atomic_add_fetch(&spurious, 1, __ATOMIC_SEQ_CST);
+    }
+  }
+
+  return NULL;
+}
+
+static void *producer(void *arg) {
+  char msg[MSG_SIZE];
+  memset(msg, 'x', sizeof(msg));
+
+  for (int i = 0; i < ROUNDS; i++) {
+    int fd = wfds[i % NUM_CONNS];
+    size_t off = 0;
+    while (off < sizeof(msg)) {
+      ssize_t w = write(fd, msg + off, sizeof(msg) - off);
+      if (w < 0) {
+        if (errno == EINTR)
+          continue;
+        die("write");
+      }
+      off += w;
+    }
+  }
+
+  return NULL;
+}
+
+static void connect_pairs(void) {
+  struct sockaddr_in addr;
+  socklen_t len = sizeof(addr);
+  int lfd = socket(AF_INET, SOCK_STREAM, 0);
+  if (lfd < 0)
+    die("socket");
+
+  memset(&addr, 0, sizeof(addr));
+  addr.sin_family = AF_INET;
+  addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+  addr.sin_port = 0;
+  if (bind(lfd, (struct sockaddr *)&addr, sizeof(addr)) < 0)
+    die("bind");
+  if (listen(lfd, NUM_CONNS) < 0)
+    die("listen");
+  if (getsockname(lfd, (struct sockaddr *)&addr, &len) < 0)
+    die("getsockname");
+
+  for (int i = 0; i < NUM_CONNS; i++) {
+    wfds[i] = socket(AF_INET, SOCK_STREAM, 0);
+    if (wfds[i] < 0)
+      die("socket");
+    if (connect(wfds[i], (struct sockaddr *)&addr, sizeof(addr)) < 0)
+      die("connect");
+    rfds[i] = accept(lfd, NULL, NULL);
+    if (rfds[i] < 0)
+      die("accept");
+    if (fcntl(rfds[i], F_SETFL, fcntl(rfds[i], F_GETFL) | O_NONBLOCK) < 0)
+      die("fcntl");
+  }
+
+  close(lfd);
+}
+
+int main(int argc, char **argv) {
+  pthread_t consumers[NUM_CONSUMERS];
+  pthread_t prod;
+
+  if (argc > 1 && strcmp(argv[1], "lt") == 0)
+    edge = 0;
+
+  connect_pairs();
+
+  epfd = epoll_create1(0);
+  if (epfd < 0)
+    die("epoll_create1");
+
+  for (int i = 0; i < NUM_CONNS; i++) {
+    struct epoll_event ev;
+    ev.events = EPOLLIN | (edge ? EPOLLET : 0);
+    ev.data.fd = rfds[i];
+    if (epoll_ctl(epfd, EPOLL_CTL_ADD, rfds[i], &ev) < 0)
+      die("epoll_ctl");
+  }
+
+  for (int i = 0; i < NUM_CONSUMERS; i++) {
+    if (pthread_create(&consumers[i], NULL, consumer, NULL))
+      die("pthread_create");
+  }
+  if (pthread_create(&prod, NULL, producer, NULL))
+    die("pthread_create");
+
+  pthread_join(prod, NULL);
+  for (int i = 0; i < NUM_CONSUMERS; i++)
+    pthread_join(consumers[i], NULL);
+
+  printf("%s: received %ld of %ld bytes, %ld events, %ld without data\n",
+         edge ? "et" : "lt", received, expected, wakeups, spurious);
+
+  return failed || received != expected;
+}
@@ -0,0 +1,119 @@
+// execve test: a bad binary fails with ENOEXEC and leaves the caller
+// intact, and an exec from a process with several busy threads closes the
+// close-on-exec descriptors only, and passes the real ids in the auxv.
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <pthread.h>
+#include <sys/auxv.h>
+#include <sys/stat.h>
+#include <sys/types.h>
+#include <sys/wait.h>
+
+#define NUM_THREADS 8
+
+static int failed;
+
+static void die(const char *msg) {
+  perror(msg);
+  exit(1);
+}
+
+static void *spin(void *arg) {
+  volatile long n = 0;
+  for (;;) {
+    n++;
+    if (n % 100000 == 0)
+      usleep(100);
+  }
+  return NULL;
+}
+
+// runs in the new image: argv[2] must be open, argv[3] must be closed
+static int check(char **argv) {
+  int keep = atoi(argv[2]);
+  int gone = atoi(argv[3]);
+
+  if (fcntl(keep, F_GETFD) < 0) {
+    fprintf(stderr, "fd %d was closed by exec\n", keep);
+    return 1;
+  }
+  if (fcntl(gone, F_GETFD) != -1 || errno != EBADF) {
+    fprintf(stderr, "close-on-exec fd %d survived exec\n", gone);
+    return 1;
+  }
+  if (getauxval(AT_SECURE) != 0 || getauxval(AT_UID) != getuid() ||
+      getauxval(AT_EUID) != geteuid() || getauxval(AT_GID) != getgid() ||
+      getauxval(AT_EGID) != getegid()) {
+    fprintf(stderr, "auxv ids: secure %lu uid %lu euid %lu gid %lu egid %lu\n",
+            getauxval(AT_SECURE), getauxval(AT_UID), getauxval(AT_EUID),
+            getauxval(AT_GID), getauxval(AT_EGID));
+    return 1;
+  }
+
+  return 0;
+}
+
+static void bad_binary(void) {
+  char path[] = "/tmp/execXXXXXX";
+  int fd = mkstemp(path);
+  if (fd < 0)
+    die("mkstemp");
+  write(fd, "garbage\n", 8);
+  fchmod(fd, 0755);
+  close(fd);
+
+  char *argv[] = {path, NULL};
+  char *envp[] = {NULL};
+  if (execve(path, argv, envp) != -1 || errno != ENOEXEC) {
+    fprintf(stderr, "exec of a bad binary: errno %d, expect ENOEXEC\n", errno);
+    failed = 1;
+  }
+  unlink(path);
+}
+
+static void busy_exec(char *self) {
+  pid_t pid = fork();
+  if (pid < 0)
+    die("fork");
+  if (pid == 0) {
+    pthread_t threads[NUM_THREADS];
+    for (int i = 0; i < NUM_THREADS; i++) {
+      if (pthread_create(&threads[i], NULL, spin, NULL))
+        die("pthread_create");
+    }
+
+    int keep = open("/dev/null", O_RDONLY);
+    int gone = open("/dev/null", O_RDONLY | O_CLOEXEC);
+    if (keep < 0 || gone < 0)
+      die("open");
+
+    char keepArg[16], goneArg[16];
+    snprintf(keepArg, sizeof(keepArg), "%d", keep);
+    snprintf(goneArg, sizeof(goneArg), "%d", gone);
+    char *argv[] = {self, "check", keepArg, goneArg, NULL};
+    execv(self, argv);
+    die("execv");
+  }
+
+  int status;
+  if (waitpid(pid, &status, 0) != pid || !WIFEXITED(status) || WEXITSTATUS(status) != 0) {
+    fprintf(stderr, "exec from a multithreaded process: status %x\n", status);
+    failed = 1;
+  }
+}
+
+int main(int argc, char **argv) {
+  if (argc == 4 && strcmp(argv[1], "check") == 0)
+    return check(argv);
+
+  bad_binary();
+  busy_exec("/proc/self/exe");
+
+  printf("%s\n", failed ? "fail" : "pass");
+  return failed;
+}
@@ -0,0 +1,88 @@
+// fhandle test: name_to_handle_at gives a handle that open_by_handle_at opens
+// again on the same mount, a too small buffer gets EOVERFLOW with the needed
+// size, and a changed handle is refused.
+
+#define _GNU_SOURCE
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include <errno.h>
+#include <fcntl.h>
+
+static int failed;
+
+static void die(const char *msg) {
+  perror(msg);
+  exit(1);
+}
+
+static void expect(const char *what, long ret, long want, int err) {
+  if (ret != want || (ret < 0 && errno != err)) {
+    fprintf(stderr, "%s: got %ld errno %d, expect %ld errno %d\n", what, ret,
+            ret < 0 ? errno : 0, want, err);
+    failed = 1;
+  }
+}
+
+int main(int argc, char **argv) {
+  const char *dir = argc > 1 ? argv[1] : "/tmp";
+  char path[256], buf[64];
+  int mountId;
+
+  snprintf(path, sizeof(path), "%s/fhandleXXXXXX", dir);
+  int fd = mkstemp(path);
+  if (fd < 0)
+    die("mkstemp");
+  if (write(fd, "fhandle", 8) != 8)
+    die("write");
+  close(fd);
+
+  struct file_handle *fh = malloc(sizeof(*fh) + MAX_HANDLE_SZ);
+  fh->handle_bytes = 0;
+  expect("small handle", name_to_handle_at(AT_FDCWD, path, fh, &mountId, 0), -1,
+         EOVERFLOW);
+  if (fh->handle_bytes == 0 || fh->handle_bytes > MAX_HANDLE_SZ) {
+    fprintf(stderr, "needed handle size %u\n", fh->handle_bytes);
+    return 1;
+  }
+
+  if (name_to_handle_at(AT_FDCWD, path, fh, &mountId, 0) < 0)
+    die("name_to_handle_at");
+
+  int mountFd = open(dir, O_RDONLY | O_DIRECTORY);
+  if (mountFd < 0)
+    die("open dir");
+
+  fd = open_by_handle_at(mountFd, fh, O_RDONLY);
+  if (fd < 0) {
+    perror("open_by_handle_at");
+    failed = 1;
+  } else {
+    memset(buf, 0, sizeof(buf));
+    if (read(fd, buf, sizeof(buf)) != 8 || strcmp(buf, "fhandle") != 0) {
+      fprintf(stderr, "read by handle '%s'\n", buf);
+      failed = 1;
+    }
+    close(fd);
+  }
+
+  // a handle that wasn't given out must not open anything
+  for (unsigned i = 0; i < fh->handle_bytes; i++)
+    fh->f_handle[i] ^= 0x5a;
+  fd = open_by_handle_at(mountFd, fh, O_RDONLY);
+  if (fd >= 0) {
+    fprintf(stderr, "changed handle opens a file\n");
+    failed = 1;
+    close(fd);
+  }
+
+  close(mountFd);
+  unlink(path);
+
+  if (failed)
+    return 1;
+
+  printf("fhandle test pass\n");
+  return 0;
+}
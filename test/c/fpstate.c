@@ -0,0 +1,109 @@
+// fpstate test: the sse registers of a thread survive the syscalls, the
+// switches to the other threads, the signal handlers and fork, whether the
+// kernel reloads them on the return to the user or not.
+
+#define _GNU_SOURCE
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include <errno.h>
+#include <signal.h>
+#include <pthread.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+
+#define THREADS 8
+#define LOOPS 20000
+
+static int failed;
+
+#define CHECK(cond, ...)                                                       \
+  do {                                                                         \
+    if (!(cond)) {                                                             \
+      fprintf(stderr, __VA_ARGS__);                                            \
+      fprintf(stderr, " (line %d, errno %d)\n", __LINE__, errno);              \
+      failed = 1;                                                              \
+    }                                                                          \
+  } while (0)
+
+// syscall_keeps_xmm8 loads xmm8, makes the syscall and returns xmm8, the
+// compiler can't spill it around the syscall
+static unsigned long syscall_keeps_xmm8(unsigned long pattern, long nr,
+                                        long arg0, long arg1) {
+  unsigned long out;
+  __asm__ volatile("movq %1, %%xmm8\n"
+                   "syscall\n"
+                   "movq %%xmm8, %0\n"
+                   : "=r"(out)
+                   : "r"(pattern), "a"(nr), "D"(arg0), "S"(arg1)
+                   : "rcx", "r11", "xmm8", "memory");
+  return out;
+}
+
+// fork_keeps_xmm8 forks with xmm8 loaded, out is xmm8 after the fork in both
+// the parent and the child
+static long fork_keeps_xmm8(unsigned long pattern, unsigned long *out) {
+  long ret = SYS_fork;
+  unsigned long v;
+  __asm__ volatile("movq %2, %%xmm8\n"
+                   "syscall\n"
+                   "movq %%xmm8, %1\n"
+                   : "+a"(ret), "=r"(v)
+                   : "r"(pattern)
+                   : "rcx", "r11", "xmm8", "memory");
+  *out = v;
+  return ret;
+}
+
+static void *yield_thread(void *arg) {
+  unsigned long pattern = 0x5a5a000000000000UL | (unsigned long)arg << 32;
+  for (int i = 0; i < LOOPS; i++) {
+    unsigned long v = pattern | i;
+    if (syscall_keeps_xmm8(v, SYS_sched_yield, 0, 0) != v)
+      return (void *)1;
+  }
+  return NULL;
+}
+
+static void handler(int sig) {
+  // the handler runs with its own fp state
+  __asm__ volatile("pcmpeqd %%xmm8, %%xmm8" ::: "xmm8");
+}
+
+int main() {
+  unsigned long v = 0x0123456789abcdefUL;
+  CHECK(syscall_keeps_xmm8(v, SYS_getpid, 0, 0) == v, "getpid");
+
+  pthread_t threads[THREADS];
+  for (long i = 0; i < THREADS; i++)
+    CHECK(pthread_create(&threads[i], NULL, yield_thread, (void *)i) == 0,
+          "pthread_create");
+  for (int i = 0; i < THREADS; i++) {
+    void *ret;
+    pthread_join(threads[i], &ret);
+    CHECK(ret == NULL, "thread %d lost its xmm8", i);
+  }
+
+  signal(SIGUSR1, handler);
+  CHECK(syscall_keeps_xmm8(v, SYS_kill, getpid(), SIGUSR1) == v,
+        "signal handler");
+
+  unsigned long out;
+  pid_t pid = fork_keeps_xmm8(v, &out);
+  if (pid == 0)
+    _exit(out == v ? 0 : 1);
+  CHECK(pid > 0 && out == v, "fork parent");
+  int status;
+  CHECK(waitpid(pid, &status, 0) == pid && WIFEXITED(status) &&
+            WEXITSTATUS(status) == 0,
+        "fork child");
+
+  if (failed) {
+    printf("fpstate test fail\n");
+    return 1;
+  }
+
+  printf("fpstate test pass\n");
+  return 0;
+}
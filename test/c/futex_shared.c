@@ -0,0 +1,116 @@
+// futex_shared test: processes sharing memory through fork or a shm object
+// wait and wake on the same futex, also when they map it at different
+// addresses, and a process-shared pthread mutex serializes them.
+
+#define _GNU_SOURCE
+#include <stdio.h>
+#include <stdint.h>
+#include <string.h>
+#include <unistd.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <pthread.h>
+#include <linux/futex.h>
+#include <sys/mman.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+
+static int failed;
+
+#define CHECK(cond, ...)                                                       \
+  do {                                                                         \
+    if (!(cond)) {                                                             \
+      fprintf(stderr, __VA_ARGS__);                                            \
+      fprintf(stderr, " (line %d, errno %d)\n", __LINE__, errno);              \
+      failed = 1;                                                              \
+    }                                                                          \
+  } while (0)
+
+#define SHM_NAME "/quark_futex_shared"
+#define LOOPS 10000
+
+static long futex(uint32_t *uaddr, int op, uint32_t val) {
+  return syscall(SYS_futex, uaddr, op, val, NULL, NULL, 0);
+}
+
+// the parent waits on *f, the child sets it and wakes the parent
+static void wait_wake(uint32_t *parent, uint32_t *child, const char *name) {
+  *parent = 0;
+  pid_t pid = fork();
+  if (pid == 0) {
+    usleep(100 * 1000);
+    __atomic_store_n(child, 1, __ATOMIC_SEQ_CST);
+    _exit(futex(child, FUTEX_WAKE, 1) == 1 ? 0 : 1);
+  }
+
+  while (__atomic_load_n(parent, __ATOMIC_SEQ_CST) == 0) {
+    if (futex(parent, FUTEX_WAIT, 0) < 0 && errno != EAGAIN) {
+      CHECK(0, "%s wait", name);
+      break;
+    }
+  }
+
+  int status;
+  CHECK(waitpid(pid, &status, 0) == pid && WEXITSTATUS(status) == 0,
+        "%s wake", name);
+}
+
+struct shared {
+  pthread_mutex_t mu;
+  long counter;
+};
+
+int main() {
+  // shared anonymous memory inherited by fork
+  uint32_t *anon = mmap(NULL, 4096, PROT_READ | PROT_WRITE,
+                        MAP_SHARED | MAP_ANONYMOUS, -1, 0);
+  CHECK(anon != MAP_FAILED, "mmap anon");
+  wait_wake(anon, anon, "anon");
+
+  // the same shm object mapped twice, the futex is at different addresses
+  int fd = shm_open(SHM_NAME, O_CREAT | O_RDWR, 0600);
+  CHECK(fd >= 0, "shm_open");
+  CHECK(ftruncate(fd, 8192) == 0, "ftruncate");
+  char *m1 = mmap(NULL, 8192, PROT_READ | PROT_WRITE, MAP_SHARED, fd, 0);
+  char *m2 = mmap(NULL, 4096, PROT_READ | PROT_WRITE, MAP_SHARED, fd, 4096);
+  CHECK(m1 != MAP_FAILED && m2 != MAP_FAILED, "mmap shm");
+  wait_wake((uint32_t *)(m1 + 4096 + 64), (uint32_t *)(m2 + 64), "shm");
+
+  // a process-shared mutex in the shm object
+  struct shared *s = (struct shared *)m1;
+  pthread_mutexattr_t attr;
+  pthread_mutexattr_init(&attr);
+  pthread_mutexattr_setpshared(&attr, PTHREAD_PROCESS_SHARED);
+  pthread_mutex_init(&s->mu, &attr);
+  s->counter = 0;
+
+  pid_t pid = fork();
+  for (int i = 0; i < LOOPS; i++) {
+    pthread_mutex_lock(&s->mu);
+    long c = s->counter;
+    if (i % 100 == 0)
+      sched_yield();
+    s->counter = c + 1;
+    pthread_mutex_unlock(&s->mu);
+  }
+
+  if (pid == 0)
+    _exit(0);
+
+  int status;
+  CHECK(waitpid(pid, &status, 0) == pid && WEXITSTATUS(status) == 0, "child");
+  CHECK(s->counter == 2 * LOOPS, "counter %ld", s->counter);
+
+  munmap(m1, 8192);
+  munmap(m2, 4096);
+  close(fd);
+  shm_unlink(SHM_NAME);
+
+  if (failed) {
+    printf("futex_shared test fail\n");
+    return 1;
+  }
+
+  printf("futex_shared test pass\n");
+  return 0;
+}
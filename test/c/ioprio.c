@@ -0,0 +1,98 @@
+// ioprio test: the I/O priority set by ioprio_set is reported by ioprio_get,
+// inherited by a child and shown for the process group, invalid values are
+// rejected, and file I/O still works under the idle class.
+
+#define _GNU_SOURCE
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <sys/syscall.h>
+#include <sys/wait.h>
+
+#define IOPRIO_CLASS_SHIFT 13
+#define IOPRIO_PRIO_VALUE(class, data) (((class) << IOPRIO_CLASS_SHIFT) | (data))
+#define IOPRIO_CLASS_BE 2
+#define IOPRIO_CLASS_IDLE 3
+#define IOPRIO_WHO_PROCESS 1
+#define IOPRIO_WHO_PGRP 2
+
+static int failed;
+
+static void die(const char *msg) {
+  perror(msg);
+  exit(1);
+}
+
+static long ioprioSet(int which, int who, int ioprio) {
+  return syscall(SYS_ioprio_set, which, who, ioprio);
+}
+
+static long ioprioGet(int which, int who) {
+  return syscall(SYS_ioprio_get, which, who);
+}
+
+static void expect(const char *what, long ret, long want, int err) {
+  if (ret != want || (ret < 0 && errno != err)) {
+    fprintf(stderr, "%s: got %ld errno %d, expect %ld errno %d\n", what, ret,
+            ret < 0 ? errno : 0, want, err);
+    failed = 1;
+  }
+}
+
+int main(void) {
+  int be7 = IOPRIO_PRIO_VALUE(IOPRIO_CLASS_BE, 7);
+  int idle = IOPRIO_PRIO_VALUE(IOPRIO_CLASS_IDLE, 0);
+
+  // the process group only has this process
+  if (setpgid(0, 0) < 0)
+    die("setpgid");
+
+  expect("set be7", ioprioSet(IOPRIO_WHO_PROCESS, 0, be7), 0, 0);
+  expect("get be7", ioprioGet(IOPRIO_WHO_PROCESS, 0), be7, 0);
+  expect("get pgrp", ioprioGet(IOPRIO_WHO_PGRP, 0), be7, 0);
+
+  expect("bad class", ioprioSet(IOPRIO_WHO_PROCESS, 0, IOPRIO_PRIO_VALUE(5, 0)), -1, EINVAL);
+  expect("bad which", ioprioGet(7, 0), -1, EINVAL);
+
+  pid_t pid = fork();
+  if (pid < 0)
+    die("fork");
+  if (pid == 0)
+    _exit(ioprioGet(IOPRIO_WHO_PROCESS, 0) == be7 ? 0 : 1);
+
+  int status;
+  if (waitpid(pid, &status, 0) != pid)
+    die("waitpid");
+  if (!WIFEXITED(status) || WEXITSTATUS(status) != 0) {
+    fprintf(stderr, "child didn't inherit the I/O priority\n");
+    failed = 1;
+  }
+
+  expect("set idle", ioprioSet(IOPRIO_WHO_PROCESS, getpid(), idle), 0, 0);
+  expect("get idle", ioprioGet(IOPRIO_WHO_PROCESS, getpid()), idle, 0);
+
+  char path[] = "/tmp/ioprioXXXXXX";
+  char buf[4096], rbuf[4096];
+  int fd = mkstemp(path);
+  if (fd < 0)
+    die("mkstemp");
+  unlink(path);
+
+  memset(buf, 'x', sizeof(buf));
+  expect("idle write", pwrite(fd, buf, sizeof(buf), 0), sizeof(buf), 0);
+  expect("idle read", pread(fd, rbuf, sizeof(rbuf), 0), sizeof(rbuf), 0);
+  if (memcmp(buf, rbuf, sizeof(buf)) != 0) {
+    fprintf(stderr, "read back different data\n");
+    failed = 1;
+  }
+  close(fd);
+
+  if (failed)
+    return 1;
+
+  printf("ioprio test pass\n");
+  return 0;
+}
@@ -0,0 +1,146 @@
+// linger test: the keepalive and SYN retry options are kept per socket and
+// SO_LINGER makes close wait for the queued data or abort the connection.
+
+#define _GNU_SOURCE
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <time.h>
+#include <netinet/in.h>
+#include <netinet/tcp.h>
+#include <arpa/inet.h>
+#include <sys/socket.h>
+
+static int failed;
+
+#define CHECK(cond, ...)                                                       \
+  do {                                                                         \
+    if (!(cond)) {                                                             \
+      fprintf(stderr, __VA_ARGS__);                                            \
+      fprintf(stderr, " (line %d, errno %d)\n", __LINE__, errno);              \
+      failed = 1;                                                              \
+    }                                                                          \
+  } while (0)
+
+static int getint(int fd, int level, int name) {
+  int v = -1;
+  socklen_t len = sizeof(v);
+  if (getsockopt(fd, level, name, &v, &len) < 0)
+    return -1;
+  return v;
+}
+
+static int setint(int fd, int level, int name, int v) {
+  return setsockopt(fd, level, name, &v, sizeof(v));
+}
+
+static double now() {
+  struct timespec ts;
+  clock_gettime(CLOCK_MONOTONIC, &ts);
+  return ts.tv_sec + ts.tv_nsec / 1e9;
+}
+
+static void pair(int l, struct sockaddr_in *addr, int *c, int *a) {
+  *c = socket(AF_INET, SOCK_STREAM, 0);
+  CHECK(connect(*c, (struct sockaddr *)addr, sizeof(*addr)) == 0, "connect");
+  *a = accept(l, NULL, NULL);
+  CHECK(*a >= 0, "accept");
+}
+
+static double lingerclose(int fd, int onoff, int secs) {
+  struct linger lg = {onoff, secs};
+  CHECK(setsockopt(fd, SOL_SOCKET, SO_LINGER, &lg, sizeof(lg)) == 0,
+        "SO_LINGER");
+  double start = now();
+  close(fd);
+  return now() - start;
+}
+
+int main() {
+  struct sockaddr_in addr;
+  socklen_t len = sizeof(addr);
+  memset(&addr, 0, sizeof(addr));
+  addr.sin_family = AF_INET;
+  addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+
+  int l = socket(AF_INET, SOCK_STREAM, 0);
+  CHECK(bind(l, (struct sockaddr *)&addr, sizeof(addr)) == 0, "bind");
+  CHECK(listen(l, 16) == 0, "listen");
+  CHECK(getsockname(l, (struct sockaddr *)&addr, &len) == 0, "getsockname");
+
+  // the keepalive timing and the SYN retries are per socket
+  int s = socket(AF_INET, SOCK_STREAM, 0);
+  CHECK(setint(s, SOL_SOCKET, SO_KEEPALIVE, 1) == 0, "SO_KEEPALIVE");
+  CHECK(setint(s, SOL_TCP, TCP_KEEPIDLE, 30) == 0, "TCP_KEEPIDLE");
+  CHECK(setint(s, SOL_TCP, TCP_KEEPINTVL, 5) == 0, "TCP_KEEPINTVL");
+  CHECK(setint(s, SOL_TCP, TCP_KEEPCNT, 3) == 0, "TCP_KEEPCNT");
+  CHECK(setint(s, SOL_TCP, TCP_SYNCNT, 2) == 0, "TCP_SYNCNT");
+  CHECK(getint(s, SOL_SOCKET, SO_KEEPALIVE) == 1, "get SO_KEEPALIVE");
+  CHECK(getint(s, SOL_TCP, TCP_KEEPIDLE) == 30, "get TCP_KEEPIDLE");
+  CHECK(getint(s, SOL_TCP, TCP_KEEPINTVL) == 5, "get TCP_KEEPINTVL");
+  CHECK(getint(s, SOL_TCP, TCP_KEEPCNT) == 3, "get TCP_KEEPCNT");
+  CHECK(getint(s, SOL_TCP, TCP_SYNCNT) == 2, "get TCP_SYNCNT");
+  CHECK(setint(s, SOL_TCP, TCP_KEEPIDLE, 0) < 0 && errno == EINVAL,
+        "TCP_KEEPIDLE 0");
+  CHECK(setint(s, SOL_TCP, TCP_KEEPCNT, 0) < 0 && errno == EINVAL,
+        "TCP_KEEPCNT 0");
+  CHECK(setint(s, SOL_TCP, TCP_SYNCNT, 200) < 0 && errno == EINVAL,
+        "TCP_SYNCNT 200");
+
+  // as Linux, l_onoff is reported as a flag and a short option fails
+  struct linger lg = {5, 7};
+  CHECK(setsockopt(s, SOL_SOCKET, SO_LINGER, &lg, sizeof(lg)) == 0,
+        "SO_LINGER");
+  memset(&lg, 0, sizeof(lg));
+  len = sizeof(lg);
+  CHECK(getsockopt(s, SOL_SOCKET, SO_LINGER, &lg, &len) == 0 &&
+            lg.l_onoff == 1 && lg.l_linger == 7,
+        "get SO_LINGER %d %d", lg.l_onoff, lg.l_linger);
+  CHECK(setsockopt(s, SOL_SOCKET, SO_LINGER, &lg, sizeof(int)) < 0 &&
+            errno == EINVAL,
+        "short SO_LINGER");
+  close(s);
+
+  // the queued data is acked at once, a lingering close doesn't wait
+  int c, a;
+  pair(l, &addr, &c, &a);
+  CHECK(write(c, "data", 4) == 4, "write");
+  double t = lingerclose(c, 1, 5);
+  CHECK(t < 1, "close took %f", t);
+  char buf[8];
+  CHECK(read(a, buf, sizeof(buf)) == 4, "read");
+  CHECK(read(a, buf, sizeof(buf)) == 0, "eof");
+  close(a);
+
+  // a zero linger time resets the connection
+  pair(l, &addr, &c, &a);
+  t = lingerclose(c, 1, 0);
+  CHECK(t < 1, "abort took %f", t);
+  CHECK(read(a, buf, sizeof(buf)) < 0 && errno == ECONNRESET, "reset");
+  close(a);
+
+  // the peer doesn't read, the close waits out the linger time
+  pair(l, &addr, &c, &a);
+  setint(a, SOL_SOCKET, SO_RCVBUF, 4096);
+  fcntl(c, F_SETFL, O_NONBLOCK);
+  static char big[65536];
+  while (write(c, big, sizeof(big)) > 0)
+    ;
+  CHECK(errno == EAGAIN, "fill");
+  t = lingerclose(c, 1, 1);
+  CHECK(t > 0.8 && t < 3, "linger took %f", t);
+  close(a);
+
+  close(l);
+
+  if (failed) {
+    printf("linger test fail\n");
+    return 1;
+  }
+
+  printf("linger test pass\n");
+  return 0;
+}
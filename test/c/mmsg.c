@@ -0,0 +1,127 @@
+// mmsg test: sendmmsg and recvmmsg on udp sockets, with sender addresses,
+// MSG_WAITFORONE, MSG_DONTWAIT partial batches and SO_RCVTIMEO.
+
+#define _GNU_SOURCE
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include <errno.h>
+#include <sys/time.h>
+#include <netinet/in.h>
+#include <arpa/inet.h>
+#include <sys/socket.h>
+
+static int failed;
+
+#define CHECK(cond, ...)                                                       \
+  do {                                                                         \
+    if (!(cond)) {                                                             \
+      fprintf(stderr, __VA_ARGS__);                                            \
+      fprintf(stderr, " (line %d, errno %d)\n", __LINE__, errno);              \
+      failed = 1;                                                              \
+    }                                                                          \
+  } while (0)
+
+#define N 8
+#define SIZE 64
+
+static char sendbufs[N][SIZE];
+static char recvbufs[N][SIZE];
+static struct iovec iovs[N];
+static struct sockaddr_in names[N];
+static struct mmsghdr msgs[N];
+
+static void setup(int send) {
+  memset(msgs, 0, sizeof(msgs));
+  memset(names, 0, sizeof(names));
+  for (int i = 0; i < N; i++) {
+    iovs[i].iov_base = send ? sendbufs[i] : recvbufs[i];
+    iovs[i].iov_len = SIZE;
+    msgs[i].msg_hdr.msg_iov = &iovs[i];
+    msgs[i].msg_hdr.msg_iovlen = 1;
+    if (!send) {
+      msgs[i].msg_hdr.msg_name = &names[i];
+      msgs[i].msg_hdr.msg_namelen = sizeof(names[i]);
+    }
+  }
+}
+
+static int sendn(int s, int n) {
+  setup(1);
+  return sendmmsg(s, msgs, n, 0);
+}
+
+int main() {
+  for (int i = 0; i < N; i++)
+    memset(sendbufs[i], 'a' + i, SIZE);
+
+  int r = socket(AF_INET, SOCK_DGRAM, 0);
+  int s = socket(AF_INET, SOCK_DGRAM, 0);
+  struct sockaddr_in addr = {0}, saddr = {0};
+  addr.sin_family = AF_INET;
+  addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+  socklen_t alen = sizeof(addr);
+  CHECK(bind(r, (struct sockaddr *)&addr, alen) == 0, "bind");
+  CHECK(getsockname(r, (struct sockaddr *)&addr, &alen) == 0, "getsockname");
+  CHECK(connect(s, (struct sockaddr *)&addr, alen) == 0, "connect");
+  alen = sizeof(saddr);
+  CHECK(getsockname(s, (struct sockaddr *)&saddr, &alen) == 0, "sender name");
+
+  CHECK(sendmmsg(s, msgs, 0, 0) == 0, "sendmmsg vlen 0");
+  CHECK(recvmmsg(r, msgs, 0, 0, NULL) == 0, "recvmmsg vlen 0");
+
+  // a full batch with the lengths and sender addresses
+  CHECK(sendn(s, N) == N, "sendmmsg");
+  for (int i = 0; i < N; i++)
+    CHECK(msgs[i].msg_len == SIZE, "sent len %d: %u", i, msgs[i].msg_len);
+
+  setup(0);
+  int n = recvmmsg(r, msgs, N, 0, NULL);
+  CHECK(n == N, "recvmmsg %d", n);
+  for (int i = 0; i < n; i++) {
+    CHECK(msgs[i].msg_len == SIZE, "recv len %d: %u", i, msgs[i].msg_len);
+    CHECK(memcmp(recvbufs[i], sendbufs[i], SIZE) == 0, "data %d", i);
+    CHECK(msgs[i].msg_hdr.msg_namelen == sizeof(struct sockaddr_in) &&
+              names[i].sin_port == saddr.sin_port,
+          "sender %d", i);
+  }
+
+  // a partial batch without blocking
+  CHECK(sendn(s, 3) == 3, "sendmmsg 3");
+  usleep(10000);
+  setup(0);
+  n = recvmmsg(r, msgs, N, MSG_DONTWAIT, NULL);
+  CHECK(n == 3, "recvmmsg dontwait %d", n);
+  errno = 0;
+  n = recvmmsg(r, msgs, N, MSG_DONTWAIT, NULL);
+  CHECK(n == -1 && errno == EAGAIN, "recvmmsg empty %d", n);
+
+  // MSG_WAITFORONE blocks only for the first message
+  CHECK(sendn(s, 2) == 2, "sendmmsg 2");
+  usleep(10000);
+  setup(0);
+  n = recvmmsg(r, msgs, N, MSG_WAITFORONE, NULL);
+  CHECK(n == 2, "recvmmsg waitforone %d", n);
+
+  // the receive timeout returns what was received
+  struct timeval tv = {0, 100 * 1000};
+  CHECK(setsockopt(r, SOL_SOCKET, SO_RCVTIMEO, &tv, sizeof(tv)) == 0,
+        "SO_RCVTIMEO");
+  CHECK(sendn(s, 1) == 1, "sendmmsg 1");
+  usleep(10000);
+  setup(0);
+  n = recvmmsg(r, msgs, N, 0, NULL);
+  CHECK(n == 1, "recvmmsg timeout %d", n);
+
+  close(s);
+  close(r);
+
+  if (failed) {
+    printf("mmsg test fail\n");
+    return 1;
+  }
+
+  printf("mmsg test pass\n");
+  return 0;
+}
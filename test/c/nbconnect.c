@@ -0,0 +1,134 @@
+// nbconnect test: parallel non-blocking connects report EPOLLOUT on
+// completion, the result is in SO_ERROR and getpeername only succeeds once
+// the connection is established.
+
+#define _GNU_SOURCE
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include <errno.h>
+#include <netinet/in.h>
+#include <arpa/inet.h>
+#include <sys/epoll.h>
+#include <sys/socket.h>
+
+static int failed;
+
+#define CHECK(cond, ...)                                                       \
+  do {                                                                         \
+    if (!(cond)) {                                                             \
+      fprintf(stderr, __VA_ARGS__);                                            \
+      fprintf(stderr, " (line %d, errno %d)\n", __LINE__, errno);              \
+      failed = 1;                                                              \
+    }                                                                          \
+  } while (0)
+
+static int sockerror(int fd) {
+  int err = -1;
+  socklen_t len = sizeof(err);
+  if (getsockopt(fd, SOL_SOCKET, SO_ERROR, &err, &len) < 0)
+    return -1;
+  return err;
+}
+
+static int nbconnect(struct sockaddr_in *addr) {
+  int fd = socket(AF_INET, SOCK_STREAM | SOCK_NONBLOCK, 0);
+  int r = connect(fd, (struct sockaddr *)addr, sizeof(*addr));
+  CHECK(r == 0 || errno == EINPROGRESS, "connect");
+  return fd;
+}
+
+int main() {
+  struct sockaddr_in addr;
+  socklen_t len = sizeof(addr);
+  memset(&addr, 0, sizeof(addr));
+  addr.sin_family = AF_INET;
+  addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+
+  int l = socket(AF_INET, SOCK_STREAM, 0);
+  CHECK(bind(l, (struct sockaddr *)&addr, sizeof(addr)) == 0, "bind");
+  CHECK(listen(l, 16) == 0, "listen");
+  CHECK(getsockname(l, (struct sockaddr *)&addr, &len) == 0, "getsockname");
+
+  // a closed port on the loopback refuses the connection
+  struct sockaddr_in closed = addr;
+  int t = socket(AF_INET, SOCK_STREAM, 0);
+  closed.sin_port = 0;
+  CHECK(bind(t, (struct sockaddr *)&closed, sizeof(closed)) == 0, "bind");
+  len = sizeof(closed);
+  CHECK(getsockname(t, (struct sockaddr *)&closed, &len) == 0, "getsockname");
+  close(t);
+
+  int good = nbconnect(&addr);
+  int bad = nbconnect(&closed);
+
+  int ep = epoll_create1(0);
+  struct epoll_event ev;
+  ev.events = EPOLLOUT;
+  ev.data.fd = good;
+  CHECK(epoll_ctl(ep, EPOLL_CTL_ADD, good, &ev) == 0, "epoll_ctl");
+  ev.data.fd = bad;
+  CHECK(epoll_ctl(ep, EPOLL_CTL_ADD, bad, &ev) == 0, "epoll_ctl");
+
+  int goodDone = 0, badDone = 0;
+  for (int i = 0; i < 10 && !(goodDone && badDone); i++) {
+    struct epoll_event evs[2];
+    int n = epoll_wait(ep, evs, 2, 1000);
+    for (int j = 0; j < n; j++) {
+      if (evs[j].data.fd == good && !goodDone) {
+        goodDone = 1;
+        CHECK(!(evs[j].events & EPOLLERR), "good events %x", evs[j].events);
+        CHECK(sockerror(good) == 0, "good SO_ERROR");
+      } else if (evs[j].data.fd == bad && !badDone) {
+        badDone = 1;
+        CHECK(evs[j].events & EPOLLERR, "bad events %x", evs[j].events);
+        CHECK(sockerror(bad) == ECONNREFUSED, "bad SO_ERROR");
+        // the error is reported once
+        CHECK(sockerror(bad) == 0, "bad SO_ERROR again");
+      }
+    }
+  }
+  CHECK(goodDone && badDone, "epoll good %d bad %d", goodDone, badDone);
+
+  struct sockaddr_in peer;
+  len = sizeof(peer);
+  CHECK(getpeername(good, (struct sockaddr *)&peer, &len) == 0 &&
+            peer.sin_port == addr.sin_port,
+        "getpeername good");
+  len = sizeof(peer);
+  CHECK(getpeername(bad, (struct sockaddr *)&peer, &len) < 0 &&
+            errno == ENOTCONN,
+        "getpeername bad");
+
+  // as Linux, the first connect after the completion returns 0
+  int r = connect(good, (struct sockaddr *)&addr, sizeof(addr));
+  CHECK(r == 0 || errno == EISCONN, "connect again");
+  r = connect(good, (struct sockaddr *)&addr, sizeof(addr));
+  CHECK(r < 0 && errno == EISCONN, "connect connected");
+  CHECK(write(good, "ping", 4) == 4, "write");
+  int a = accept(l, NULL, NULL);
+  CHECK(a >= 0, "accept");
+  char buf[8];
+  CHECK(read(a, buf, sizeof(buf)) == 4, "read");
+  CHECK(write(a, "pong", 4) == 4, "write back");
+  ev.events = EPOLLIN;
+  ev.data.fd = good;
+  CHECK(epoll_ctl(ep, EPOLL_CTL_MOD, good, &ev) == 0, "epoll_ctl mod");
+  CHECK(epoll_wait(ep, &ev, 1, 1000) == 1 && ev.data.fd == good, "epollin");
+  CHECK(read(good, buf, sizeof(buf)) == 4, "read back");
+
+  close(a);
+  close(good);
+  close(bad);
+  close(ep);
+  close(l);
+
+  if (failed) {
+    printf("nbconnect test fail\n");
+    return 1;
+  }
+
+  printf("nbconnect test pass\n");
+  return 0;
+}
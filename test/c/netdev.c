@@ -0,0 +1,113 @@
+// netdev test: the network devices are visible through /proc/net/dev with
+// live counters, getifaddrs, /sys/class/net and the SIOCGIF* ioctls, and all
+// of them agree on the loopback device.
+
+#define _GNU_SOURCE
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include <errno.h>
+#include <ifaddrs.h>
+#include <net/if.h>
+#include <netinet/in.h>
+#include <arpa/inet.h>
+#include <sys/ioctl.h>
+#include <sys/socket.h>
+#include <linux/if_link.h>
+
+static int failed;
+
+#define CHECK(cond, ...)                                                       \
+  do {                                                                         \
+    if (!(cond)) {                                                             \
+      fprintf(stderr, __VA_ARGS__);                                            \
+      fprintf(stderr, " (line %d, errno %d)\n", __LINE__, errno);              \
+      failed = 1;                                                              \
+    }                                                                          \
+  } while (0)
+
+// lopackets returns the received packets of lo in /proc/net/dev, -1 if it
+// isn't listed.
+static long long lopackets(void) {
+  FILE *f = fopen("/proc/net/dev", "r");
+  if (f == NULL)
+    return -1;
+
+  char line[512];
+  long long ret = -1;
+  int n = 0;
+  while (fgets(line, sizeof(line), f)) {
+    n++;
+    if (n == 1 && strncmp(line, "Inter-|", 7) != 0)
+      break;
+    char *colon = strchr(line, ':');
+    if (colon == NULL)
+      continue;
+    *colon = 0;
+    char *name = line;
+    while (*name == ' ')
+      name++;
+    if (strcmp(name, "lo") == 0) {
+      long long bytes, packets;
+      if (sscanf(colon + 1, "%lld %lld", &bytes, &packets) == 2)
+        ret = packets;
+    }
+  }
+  fclose(f);
+  return ret;
+}
+
+int main(int argc, char **argv) {
+  long long before = lopackets();
+  CHECK(before >= 0, "lo not in /proc/net/dev");
+
+  // The counters are live.
+  int s = socket(AF_INET, SOCK_DGRAM, 0);
+  struct sockaddr_in addr = {0};
+  addr.sin_family = AF_INET;
+  addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+  addr.sin_port = htons(9);
+  for (int i = 0; i < 10; i++)
+    sendto(s, "x", 1, 0, (struct sockaddr *)&addr, sizeof(addr));
+  long long after = lopackets();
+  CHECK(after >= before + 10, "lo packets %lld -> %lld", before, after);
+
+  // getifaddrs sees lo with its link stats.
+  struct ifaddrs *ifa, *p;
+  int found = 0;
+  CHECK(getifaddrs(&ifa) == 0, "getifaddrs");
+  for (p = ifa; p != NULL; p = p->ifa_next) {
+    if (strcmp(p->ifa_name, "lo") == 0 && p->ifa_addr &&
+        p->ifa_addr->sa_family == AF_PACKET && p->ifa_data) {
+      struct rtnl_link_stats *st = p->ifa_data;
+      found = st->rx_packets > 0;
+    }
+  }
+  freeifaddrs(ifa);
+  CHECK(found, "getifaddrs lo stats");
+
+  // The ioctls and sysfs agree on the index and the mtu.
+  struct ifreq ifr = {0};
+  strcpy(ifr.ifr_name, "lo");
+  CHECK(ioctl(s, SIOCGIFINDEX, &ifr) == 0, "SIOCGIFINDEX");
+  int index = ifr.ifr_ifindex;
+  CHECK(index == (int)if_nametoindex("lo"), "if_nametoindex");
+  CHECK(ioctl(s, SIOCGIFMTU, &ifr) == 0 && ifr.ifr_mtu > 0, "SIOCGIFMTU");
+  CHECK(ioctl(s, SIOCGIFFLAGS, &ifr) == 0 && (ifr.ifr_flags & IFF_LOOPBACK),
+        "SIOCGIFFLAGS");
+
+  FILE *f = fopen("/sys/class/net/lo/ifindex", "r");
+  int sysindex = -1;
+  if (f) {
+    fscanf(f, "%d", &sysindex);
+    fclose(f);
+  }
+  CHECK(sysindex == index, "sysfs ifindex %d, ioctl %d", sysindex, index);
+
+  if (failed)
+    return 1;
+
+  printf("netdev ok\n");
+  return 0;
+}
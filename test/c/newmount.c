@@ -0,0 +1,104 @@
+// newmount test: fsopen, fsconfig and fsmount make a detached tmpfs mount,
+// move_mount attaches it on a directory, and a file written through the
+// mount point is read back. A detached mount is attached only once.
+
+#define _GNU_SOURCE
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <sys/mount.h>
+#include <sys/stat.h>
+#include <sys/syscall.h>
+
+static int failed;
+
+static void die(const char *msg) {
+  perror(msg);
+  exit(1);
+}
+
+static void expect(const char *what, long ret, long want, int err) {
+  if (ret != want || (ret < 0 && errno != err)) {
+    fprintf(stderr, "%s: got %ld errno %d, expect %ld errno %d\n", what, ret,
+            ret < 0 ? errno : 0, want, err);
+    failed = 1;
+  }
+}
+
+int main(int argc, char **argv) {
+  const char *dir = argc > 1 ? argv[1] : "/tmp";
+  char target[256], path[300], buf[16];
+
+  snprintf(target, sizeof(target), "%s/newmountXXXXXX", dir);
+  if (mkdtemp(target) == NULL)
+    die("mkdtemp");
+
+  int fsfd = syscall(SYS_fsopen, "tmpfs", 0);
+  if (fsfd < 0)
+    die("fsopen");
+
+  expect("fsmount before create", syscall(SYS_fsmount, fsfd, 0, 0), -1,
+         EINVAL);
+
+  if (syscall(SYS_fsconfig, fsfd, FSCONFIG_SET_STRING, "source", "newmount",
+              0) < 0)
+    die("fsconfig source");
+  if (syscall(SYS_fsconfig, fsfd, FSCONFIG_CMD_CREATE, NULL, NULL, 0) < 0)
+    die("fsconfig create");
+
+  int mfd = syscall(SYS_fsmount, fsfd, 0, 0);
+  if (mfd < 0)
+    die("fsmount");
+  expect("fsmount twice", syscall(SYS_fsmount, fsfd, 0, 0), -1, EBUSY);
+
+  if (syscall(SYS_move_mount, mfd, "", AT_FDCWD, target,
+              MOVE_MOUNT_F_EMPTY_PATH) < 0)
+    die("move_mount");
+  // the mount isn't detached anymore, the errno differs as moving an
+  // attached mount isn't supported in the sandbox
+  if (syscall(SYS_move_mount, mfd, "", AT_FDCWD, target,
+              MOVE_MOUNT_F_EMPTY_PATH) == 0) {
+    fprintf(stderr, "move_mount twice succeeded\n");
+    failed = 1;
+  }
+
+  snprintf(path, sizeof(path), "%s/file", target);
+  int fd = open(path, O_CREAT | O_RDWR, 0644);
+  if (fd < 0)
+    die("open");
+  if (write(fd, "newmount", 8) != 8)
+    die("write");
+  close(fd);
+
+  // the file is in the new mount, not in the directory under it
+  fd = openat(mfd, "file", O_RDONLY);
+  if (fd < 0) {
+    perror("openat mount fd");
+    failed = 1;
+  } else {
+    memset(buf, 0, sizeof(buf));
+    if (read(fd, buf, sizeof(buf)) != 8 || strcmp(buf, "newmount") != 0) {
+      fprintf(stderr, "read back \"%s\"\n", buf);
+      failed = 1;
+    }
+    close(fd);
+  }
+
+  close(mfd);
+  close(fsfd);
+  if (umount(target) < 0) {
+    perror("umount");
+    failed = 1;
+  }
+  expect("file after umount", access(path, F_OK), -1, ENOENT);
+  rmdir(target);
+
+  if (failed)
+    return 1;
+
+  printf("newmount ok\n");
+  return 0;
+}
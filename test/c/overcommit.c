@@ -0,0 +1,142 @@
+// overcommit test: the heuristic mode refuses only the obvious overcommits,
+// the strict mode refuses the private writable mappings past the commit limit
+// and the always mode refuses nothing. /proc/meminfo reports the commit
+// limit and the committed memory.
+
+#define _GNU_SOURCE
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <sys/mman.h>
+
+#define HUGE_LEN (1UL << 44)
+
+static int failed;
+
+#define CHECK(cond, ...)                                                       \
+  do {                                                                         \
+    if (!(cond)) {                                                             \
+      fprintf(stderr, __VA_ARGS__);                                            \
+      fprintf(stderr, " (line %d, errno %d)\n", __LINE__, errno);              \
+      failed = 1;                                                              \
+    }                                                                          \
+  } while (0)
+
+static long read_long(const char *path) {
+  char buf[64];
+  int fd = open(path, O_RDONLY);
+  if (fd < 0)
+    return -1;
+  int n = read(fd, buf, sizeof(buf) - 1);
+  close(fd);
+  if (n < 0)
+    return -1;
+  buf[n] = 0;
+  return atol(buf);
+}
+
+static int write_long(const char *path, long v) {
+  char buf[64];
+  int fd = open(path, O_WRONLY);
+  if (fd < 0)
+    return -1;
+  int len = snprintf(buf, sizeof(buf), "%ld\n", v);
+  int n = write(fd, buf, len);
+  close(fd);
+  return n == len ? 0 : -1;
+}
+
+static long meminfo(const char *key) {
+  char line[256];
+  long v = -1;
+  FILE *f = fopen("/proc/meminfo", "r");
+  if (f == NULL)
+    return -1;
+  while (fgets(line, sizeof(line), f)) {
+    if (strncmp(line, key, strlen(key)) == 0 && line[strlen(key)] == ':') {
+      v = atol(line + strlen(key) + 1);
+      break;
+    }
+  }
+  fclose(f);
+  return v;
+}
+
+static int try_map(size_t len, int prot, int flags) {
+  void *p = mmap(NULL, len, prot, MAP_PRIVATE | MAP_ANONYMOUS | flags, -1, 0);
+  if (p == MAP_FAILED)
+    return -1;
+  munmap(p, len);
+  return 0;
+}
+
+int main() {
+  CHECK(meminfo("CommitLimit") >= 0, "CommitLimit");
+  CHECK(meminfo("Committed_AS") >= 0, "Committed_AS");
+
+  long mode = read_long("/proc/sys/vm/overcommit_memory");
+  long ratio = read_long("/proc/sys/vm/overcommit_ratio");
+  long kbytes = read_long("/proc/sys/vm/overcommit_kbytes");
+  CHECK(mode >= 0 && mode <= 2, "overcommit_memory %ld", mode);
+  CHECK(ratio >= 0 && kbytes >= 0, "ratio %ld kbytes %ld", ratio, kbytes);
+
+  if (mode == 0) {
+    CHECK(try_map(HUGE_LEN, PROT_READ | PROT_WRITE, 0) < 0 && errno == ENOMEM,
+          "heuristic huge mapping");
+    CHECK(try_map(HUGE_LEN, PROT_READ | PROT_WRITE, MAP_NORESERVE) == 0,
+          "heuristic noreserve mapping");
+    CHECK(try_map(HUGE_LEN, PROT_NONE, 0) == 0, "heuristic reservation");
+  }
+
+  if (geteuid() == 0) {
+    CHECK(write_long("/proc/sys/vm/overcommit_memory", 3) < 0 &&
+              errno == EINVAL,
+          "invalid mode");
+
+    // the strict mode with a commit limit 64M above the committed memory
+    long committed = meminfo("Committed_AS");
+    CHECK(write_long("/proc/sys/vm/overcommit_kbytes", committed + 65536) == 0,
+          "set kbytes");
+    CHECK(read_long("/proc/sys/vm/overcommit_ratio") == 0, "ratio not reset");
+    CHECK(write_long("/proc/sys/vm/overcommit_memory", 2) == 0, "set strict");
+    CHECK(try_map(16 << 20, PROT_READ | PROT_WRITE, 0) == 0, "strict mapping");
+    CHECK(try_map(256 << 20, PROT_READ | PROT_WRITE, 0) < 0 && errno == ENOMEM,
+          "strict mapping over the limit");
+    CHECK(try_map(256 << 20, PROT_READ | PROT_WRITE, MAP_NORESERVE) < 0 &&
+              errno == ENOMEM,
+          "strict noreserve mapping over the limit");
+    CHECK(try_map(256 << 20, PROT_READ, 0) == 0,
+          "strict read only mapping over the limit");
+    void *p = mmap(NULL, 256 << 20, PROT_READ, MAP_PRIVATE | MAP_ANONYMOUS, -1,
+                   0);
+    CHECK(p != MAP_FAILED, "strict read only mapping");
+    if (p != MAP_FAILED) {
+      CHECK(mprotect(p, 256 << 20, PROT_READ | PROT_WRITE) < 0 &&
+                errno == ENOMEM,
+            "strict mprotect over the limit");
+      munmap(p, 256 << 20);
+    }
+    CHECK(sbrk(256 << 20) == (void *)-1 && errno == ENOMEM,
+          "strict brk over the limit");
+
+    CHECK(write_long("/proc/sys/vm/overcommit_memory", 1) == 0, "set always");
+    CHECK(try_map(HUGE_LEN, PROT_READ | PROT_WRITE, 0) == 0, "always mapping");
+
+    if (kbytes > 0)
+      write_long("/proc/sys/vm/overcommit_kbytes", kbytes);
+    else
+      write_long("/proc/sys/vm/overcommit_ratio", ratio);
+    write_long("/proc/sys/vm/overcommit_memory", mode);
+  }
+
+  if (failed) {
+    printf("overcommit test fail\n");
+    return 1;
+  }
+
+  printf("overcommit test pass\n");
+  return 0;
+}
@@ -0,0 +1,118 @@
+// process group test: checks the setpgid/setsid error cases and that a
+// process group with a stopped member gets SIGHUP and SIGCONT when it
+// becomes orphaned by its parent's exit.
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include <errno.h>
+#include <signal.h>
+#include <sys/types.h>
+#include <sys/wait.h>
+
+static int fds[2];
+static int failed;
+
+static void die(const char *msg) {
+  perror(msg);
+  exit(1);
+}
+
+static void expect(const char *what, int ret, int err) {
+  if (ret != -1 || errno != err) {
+    fprintf(stderr, "%s: got %d errno %d, expect errno %d\n", what, ret, errno, err);
+    failed = 1;
+  }
+}
+
+static void onsig(int sig) {
+  char c = sig == SIGHUP ? 'h' : 'c';
+  write(fds[1], &c, 1);
+}
+
+static void errors(void) {
+  // a process group leader can't create a new session
+  if (setpgid(0, 0) < 0)
+    die("setpgid");
+  expect("setsid as group leader", setsid(), EPERM);
+
+  // setpgid on a process that is not our child
+  expect("setpgid non child", setpgid(getppid(), 0), ESRCH);
+
+  pid_t pid = fork();
+  if (pid < 0)
+    die("fork");
+  if (pid == 0) {
+    if (setsid() < 0)
+      die("setsid");
+    // the session leader can't change its group
+    expect("setpgid session leader", setpgid(0, 0), EPERM);
+    // there is no group with our parent's id in the new session
+    expect("setpgid other session", setpgid(0, getppid()), EPERM);
+    exit(failed);
+  }
+
+  int status;
+  waitpid(pid, &status, 0);
+  if (!WIFEXITED(status) || WEXITSTATUS(status) != 0)
+    failed = 1;
+}
+
+static void orphan(void) {
+  if (pipe(fds) < 0)
+    die("pipe");
+
+  pid_t pid = fork();
+  if (pid < 0)
+    die("fork");
+  if (pid == 0) {
+    // middle process: its child runs in a separate group of the same
+    // session, so the middle process is that group's only link
+    pid_t gc = fork();
+    if (gc < 0)
+      die("fork");
+    if (gc == 0) {
+      setpgid(0, 0);
+      signal(SIGHUP, onsig);
+      signal(SIGCONT, onsig);
+      raise(SIGSTOP);
+      // continued by the orphaning SIGCONT
+      sleep(1);
+      exit(0);
+    }
+
+    setpgid(gc, gc);
+    int status;
+    if (waitpid(gc, &status, WUNTRACED) < 0 || !WIFSTOPPED(status))
+      die("waitpid");
+    exit(0);
+  }
+
+  waitpid(pid, NULL, 0);
+  close(fds[1]);
+
+  char buf[8];
+  int got_hup = 0, got_cont = 0;
+  ssize_t n;
+  while ((n = read(fds[0], buf, sizeof(buf))) > 0) {
+    for (int i = 0; i < n; i++) {
+      if (buf[i] == 'h')
+        got_hup = 1;
+      if (buf[i] == 'c')
+        got_cont = 1;
+    }
+  }
+
+  if (!got_hup || !got_cont) {
+    fprintf(stderr, "orphaned group: SIGHUP %d SIGCONT %d\n", got_hup, got_cont);
+    failed = 1;
+  }
+}
+
+int main(void) {
+  errors();
+  orphan();
+  printf("%s\n", failed ? "fail" : "pass");
+  return failed;
+}
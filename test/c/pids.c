@@ -0,0 +1,123 @@
+// pids test: the cgroup of the container counts its tasks in pids.current,
+// and when pids.max is set a fork bomb gets EAGAIN and is counted in
+// pids.events instead of exhausting the sandbox.
+
+#define _GNU_SOURCE
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <signal.h>
+#include <sys/wait.h>
+
+#define MAX_CHILDREN 4096
+
+static int failed;
+
+#define CHECK(cond, ...)                                                       \
+  do {                                                                         \
+    if (!(cond)) {                                                             \
+      fprintf(stderr, __VA_ARGS__);                                            \
+      fprintf(stderr, " (line %d, errno %d)\n", __LINE__, errno);              \
+      failed = 1;                                                              \
+    }                                                                          \
+  } while (0)
+
+static int read_file(const char *path, char *buf, int len) {
+  int fd = open(path, O_RDONLY);
+  if (fd < 0)
+    return -1;
+  int n = read(fd, buf, len - 1);
+  close(fd);
+  if (n < 0)
+    return -1;
+  buf[n] = 0;
+  return n;
+}
+
+static long read_long(const char *path) {
+  char buf[64];
+  if (read_file(path, buf, sizeof(buf)) < 0)
+    return -1;
+  return atol(buf);
+}
+
+static long events_max() {
+  char buf[64];
+  if (read_file("/sys/fs/cgroup/pids.events", buf, sizeof(buf)) < 0)
+    return -1;
+  long n = -1;
+  sscanf(buf, "max %ld", &n);
+  return n;
+}
+
+static pid_t children[MAX_CHILDREN];
+
+int main() {
+  long current = read_long("/sys/fs/cgroup/pids.current");
+  CHECK(current >= 1, "pids.current %ld", current);
+
+  char max[64];
+  CHECK(read_file("/sys/fs/cgroup/pids.max", max, sizeof(max)) > 0,
+        "pids.max");
+  long events = events_max();
+  CHECK(events >= 0, "pids.events %ld", events);
+
+  pid_t pid = fork();
+  if (pid == 0) {
+    pause();
+    _exit(0);
+  }
+  CHECK(pid > 0, "fork");
+  CHECK(read_long("/sys/fs/cgroup/pids.current") == current + 1,
+        "pids.current doesn't count the child");
+  kill(pid, SIGKILL);
+  waitpid(pid, NULL, 0);
+  CHECK(read_long("/sys/fs/cgroup/pids.current") == current,
+        "pids.current doesn't drop the child");
+
+  // the fork bomb only runs against a limit
+  if (strncmp(max, "max", 3) != 0) {
+    long limit = atol(max);
+    int n = 0;
+    int err = 0;
+    while (n < MAX_CHILDREN) {
+      pid = fork();
+      if (pid == 0) {
+        pause();
+        _exit(0);
+      }
+      if (pid < 0) {
+        err = errno;
+        break;
+      }
+      children[n++] = pid;
+    }
+
+    CHECK(err == EAGAIN, "fork bomb error %d", err);
+    CHECK(current + n <= limit, "%d children over pids.max %ld", n, limit);
+    CHECK(events_max() > events, "pids.events not counted");
+
+    for (int i = 0; i < n; i++) {
+      kill(children[i], SIGKILL);
+      waitpid(children[i], NULL, 0);
+    }
+
+    // the tasks are released
+    pid = fork();
+    if (pid == 0)
+      _exit(0);
+    CHECK(pid > 0, "fork after the fork bomb");
+    waitpid(pid, NULL, 0);
+  }
+
+  if (failed) {
+    printf("pids test fail\n");
+    return 1;
+  }
+
+  printf("pids test pass\n");
+  return 0;
+}
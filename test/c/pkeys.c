@@ -0,0 +1,123 @@
+// pkeys test: pkey_alloc hands out the protection keys, pkey_mprotect tags
+// the pages with a key and the pkru of the thread denies the access to the
+// pages of the key with a SIGSEGV of code SEGV_PKUERR.
+
+#define _GNU_SOURCE
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include <errno.h>
+#include <setjmp.h>
+#include <signal.h>
+#include <sys/mman.h>
+#include <sys/wait.h>
+
+#ifndef SEGV_PKUERR
+#define SEGV_PKUERR 4
+#endif
+
+static int failed;
+
+#define CHECK(cond, ...)                                                       \
+  do {                                                                         \
+    if (!(cond)) {                                                             \
+      fprintf(stderr, __VA_ARGS__);                                            \
+      fprintf(stderr, " (line %d, errno %d)\n", __LINE__, errno);              \
+      failed = 1;                                                              \
+    }                                                                          \
+  } while (0)
+
+static sigjmp_buf env;
+static volatile int fault_code;
+static volatile int fault_pkey;
+
+static void segv_handler(int sig, siginfo_t *si, void *ctx) {
+  fault_code = si->si_code;
+  // si_pkey follows the 8 bytes of si_addr_lsb and its padding
+  fault_pkey = *(int *)((char *)&si->si_addr + 16);
+  siglongjmp(env, 1);
+}
+
+// access returns 0 if the access succeeds, otherwise the si_code of the fault
+static int access_page(volatile char *p, int write) {
+  fault_code = 0;
+  fault_pkey = -1;
+  if (sigsetjmp(env, 1) == 0) {
+    if (write)
+      *p = 1;
+    else
+      (void)*p;
+  }
+  return fault_code;
+}
+
+int main() {
+  CHECK(pkey_alloc(1, 0) < 0 && errno == EINVAL, "invalid flags");
+  CHECK(pkey_alloc(0, 4) < 0 && errno == EINVAL, "invalid rights");
+
+  int pkey = pkey_alloc(0, 0);
+  if (pkey < 0 && (errno == ENOSPC || errno == EINVAL || errno == ENOSYS)) {
+    // the cpu doesn't support the protection keys
+    printf("pkeys test pass\n");
+    return 0;
+  }
+  CHECK(pkey > 0, "pkey_alloc");
+
+  struct sigaction sa;
+  memset(&sa, 0, sizeof(sa));
+  sa.sa_sigaction = segv_handler;
+  sa.sa_flags = SA_SIGINFO;
+  sigaction(SIGSEGV, &sa, NULL);
+
+  char *p = mmap(NULL, 4096, PROT_READ | PROT_WRITE,
+                 MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+  CHECK(p != MAP_FAILED, "mmap");
+  p[0] = 1;
+
+  CHECK(pkey_mprotect(p, 4096, PROT_READ | PROT_WRITE, pkey + 1) < 0 &&
+            errno == EINVAL,
+        "unallocated pkey");
+  CHECK(pkey_mprotect(p, 4096, PROT_READ | PROT_WRITE, pkey) == 0,
+        "pkey_mprotect");
+  CHECK(access_page(p, 1) == 0, "write with the key allowed");
+
+  CHECK(pkey_set(pkey, PKEY_DISABLE_WRITE) == 0, "pkey_set");
+  CHECK(access_page(p, 0) == 0, "read with the write disabled");
+  CHECK(access_page(p, 1) == SEGV_PKUERR && fault_pkey == pkey,
+        "write with the write disabled, code %d pkey %d", fault_code,
+        fault_pkey);
+
+  // the child inherits the pkru of the thread
+  pid_t pid = fork();
+  if (pid == 0)
+    _exit(access_page(p, 1) == SEGV_PKUERR ? 0 : 1);
+  int status;
+  CHECK(waitpid(pid, &status, 0) == pid && WIFEXITED(status) &&
+            WEXITSTATUS(status) == 0,
+        "pkru of the child");
+
+  CHECK(pkey_set(pkey, PKEY_DISABLE_ACCESS) == 0, "pkey_set");
+  CHECK(access_page(p, 0) == SEGV_PKUERR, "read with the access disabled");
+
+  // mprotect keeps the key of the pages
+  CHECK(mprotect(p, 4096, PROT_READ | PROT_WRITE) == 0, "mprotect");
+  CHECK(access_page(p, 0) == SEGV_PKUERR, "read after mprotect");
+
+  CHECK(pkey_set(pkey, 0) == 0, "pkey_set");
+  CHECK(access_page(p, 1) == 0 && p[0] == 1, "write with the key allowed");
+
+  CHECK(pkey_mprotect(p, 4096, PROT_READ | PROT_WRITE, 0) == 0,
+        "pkey_mprotect to the default key");
+  CHECK(pkey_free(pkey) == 0, "pkey_free");
+  CHECK(pkey_free(pkey) < 0 && errno == EINVAL, "double pkey_free");
+  munmap(p, 4096);
+
+  if (failed) {
+    printf("pkeys test fail\n");
+    return 1;
+  }
+
+  printf("pkeys test pass\n");
+  return 0;
+}
@@ -0,0 +1,120 @@
+// readdir test: a large directory is listed completely with the right d_type,
+// seekdir returns to a position given by telldir and rewinddir sees a new
+// entry.
+
+#define _GNU_SOURCE
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <dirent.h>
+#include <sys/stat.h>
+
+#define FILES 2000
+
+static int failed;
+
+static int count(DIR *d, int *dirs) {
+  struct dirent *e;
+  int n = 0;
+
+  *dirs = 0;
+  while ((e = readdir(d)) != NULL) {
+    if (strcmp(e->d_name, ".") == 0 || strcmp(e->d_name, "..") == 0)
+      continue;
+    if (e->d_name[0] == 'd') {
+      if (e->d_type != DT_DIR) {
+        fprintf(stderr, "%s: d_type %d\n", e->d_name, e->d_type);
+        failed = 1;
+      }
+      (*dirs)++;
+    } else if (e->d_type != DT_REG) {
+      fprintf(stderr, "%s: d_type %d\n", e->d_name, e->d_type);
+      failed = 1;
+    }
+    n++;
+  }
+
+  return n;
+}
+
+int main(int argc, char **argv) {
+  char dir[] = "readdirXXXXXX";
+  char path[64];
+  int dirs;
+
+  if (mkdtemp(dir) == NULL) {
+    perror("mkdtemp");
+    return 1;
+  }
+
+  for (int i = 0; i < FILES; i++) {
+    snprintf(path, sizeof(path), "%s/f%d", dir, i);
+    int fd = open(path, O_CREAT | O_WRONLY, 0644);
+    if (fd < 0) {
+      perror("open");
+      return 1;
+    }
+    close(fd);
+  }
+  snprintf(path, sizeof(path), "%s/d0", dir);
+  mkdir(path, 0755);
+
+  DIR *d = opendir(dir);
+  if (d == NULL) {
+    perror("opendir");
+    return 1;
+  }
+
+  int n = count(d, &dirs);
+  if (n != FILES + 1 || dirs != 1) {
+    fprintf(stderr, "listed %d entries %d dirs\n", n, dirs);
+    failed = 1;
+  }
+
+  // telldir in the middle of the listing, then seekdir back to it
+  rewinddir(d);
+  struct dirent *e;
+  for (int i = 0; i < FILES / 2; i++)
+    readdir(d);
+  long pos = telldir(d);
+  e = readdir(d);
+  char name[256];
+  strcpy(name, e ? e->d_name : "");
+  for (int i = 0; i < 10; i++)
+    readdir(d);
+  seekdir(d, pos);
+  e = readdir(d);
+  if (e == NULL || strcmp(e->d_name, name) != 0) {
+    fprintf(stderr, "seekdir: %s, expect %s\n", e ? e->d_name : "end", name);
+    failed = 1;
+  }
+
+  snprintf(path, sizeof(path), "%s/new", dir);
+  int fd = open(path, O_CREAT | O_WRONLY, 0644);
+  close(fd);
+  rewinddir(d);
+  n = count(d, &dirs);
+  if (n != FILES + 2) {
+    fprintf(stderr, "listed %d entries after rewinddir\n", n);
+    failed = 1;
+  }
+  closedir(d);
+
+  unlink(path);
+  for (int i = 0; i < FILES; i++) {
+    snprintf(path, sizeof(path), "%s/f%d", dir, i);
+    unlink(path);
+  }
+  snprintf(path, sizeof(path), "%s/d0", dir);
+  rmdir(path);
+  rmdir(dir);
+
+  if (failed)
+    return 1;
+
+  printf("readdir ok\n");
+  return 0;
+}
@@ -0,0 +1,120 @@
+// renameat2 test: RENAME_NOREPLACE and RENAME_EXCHANGE for files and
+// directories, in one directory and across directories, plus flag validation.
+
+#define _GNU_SOURCE
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <sys/stat.h>
+#include <sys/syscall.h>
+
+#ifndef RENAME_NOREPLACE
+#define RENAME_NOREPLACE (1 << 0)
+#endif
+#ifndef RENAME_EXCHANGE
+#define RENAME_EXCHANGE (1 << 1)
+#endif
+#ifndef RENAME_WHITEOUT
+#define RENAME_WHITEOUT (1 << 2)
+#endif
+
+static int failed;
+
+#define CHECK(cond, ...)                                                       \
+  do {                                                                         \
+    if (!(cond)) {                                                             \
+      fprintf(stderr, __VA_ARGS__);                                            \
+      fprintf(stderr, " (line %d, errno %d)\n", __LINE__, errno);              \
+      failed = 1;                                                              \
+    }                                                                          \
+  } while (0)
+
+static int rename2(const char *oldpath, const char *newpath, unsigned flags) {
+  return syscall(SYS_renameat2, AT_FDCWD, oldpath, AT_FDCWD, newpath, flags);
+}
+
+static void writefile(const char *path, const char *data) {
+  int fd = open(path, O_CREAT | O_TRUNC | O_WRONLY, 0644);
+  write(fd, data, strlen(data));
+  close(fd);
+}
+
+static int contains(const char *path, const char *data) {
+  char buf[64] = {0};
+  int fd = open(path, O_RDONLY);
+  if (fd < 0)
+    return 0;
+  read(fd, buf, sizeof(buf) - 1);
+  close(fd);
+  return strcmp(buf, data) == 0;
+}
+
+int main(int argc, char **argv) {
+  char dir[] = "/tmp/renameat2XXXXXX";
+  char a[64], b[64], c[64], sub[64], subfile[64], subdir[64];
+  struct stat st;
+
+  if (mkdtemp(dir) == NULL) {
+    perror("mkdtemp");
+    return 1;
+  }
+  snprintf(a, sizeof(a), "%s/a", dir);
+  snprintf(b, sizeof(b), "%s/b", dir);
+  snprintf(c, sizeof(c), "%s/c", dir);
+  snprintf(sub, sizeof(sub), "%s/sub", dir);
+  snprintf(subfile, sizeof(subfile), "%s/sub/f", dir);
+  snprintf(subdir, sizeof(subdir), "%s/sub/d", dir);
+
+  writefile(a, "aaa");
+  writefile(b, "bbb");
+
+  // Invalid flag combinations.
+  CHECK(rename2(a, c, RENAME_NOREPLACE | RENAME_EXCHANGE) == -1 &&
+            errno == EINVAL,
+        "noreplace|exchange");
+  CHECK(rename2(a, c, 0x80) == -1 && errno == EINVAL, "unknown flag");
+
+  // RENAME_NOREPLACE refuses to clobber, but renames to a free name.
+  CHECK(rename2(a, b, RENAME_NOREPLACE) == -1 && errno == EEXIST,
+        "noreplace existing");
+  CHECK(contains(a, "aaa") && contains(b, "bbb"), "noreplace changed files");
+  CHECK(rename2(a, a, RENAME_NOREPLACE) == -1 && errno == EEXIST,
+        "noreplace same name");
+  CHECK(rename2(a, c, RENAME_NOREPLACE) == 0, "noreplace free name");
+  CHECK(access(a, F_OK) == -1 && contains(c, "aaa"), "noreplace result");
+  CHECK(rename2(c, a, 0) == 0, "rename back");
+
+  // RENAME_EXCHANGE swaps two files and needs both to exist.
+  CHECK(rename2(a, b, RENAME_EXCHANGE) == 0, "exchange files");
+  CHECK(contains(a, "bbb") && contains(b, "aaa"), "exchange result");
+  CHECK(rename2(a, c, RENAME_EXCHANGE) == -1 && errno == ENOENT,
+        "exchange missing target");
+
+  // Exchange a file with a directory across directories.
+  CHECK(mkdir(sub, 0755) == 0, "mkdir sub");
+  CHECK(mkdir(subdir, 0755) == 0, "mkdir sub/d");
+  writefile(subfile, "fff");
+  CHECK(rename2(a, subdir, RENAME_EXCHANGE) == 0, "exchange file and dir");
+  CHECK(stat(a, &st) == 0 && S_ISDIR(st.st_mode), "a is now a dir");
+  CHECK(contains(subdir, "bbb"), "sub/d is now the file");
+
+  // A directory can't be exchanged with its own ancestor.
+  CHECK(rename2(sub, subfile, RENAME_EXCHANGE) == -1 && errno == EINVAL,
+        "exchange with descendant");
+
+  unlink(subfile);
+  unlink(subdir);
+  rmdir(sub);
+  rmdir(a);
+  unlink(b);
+  rmdir(dir);
+
+  if (failed)
+    return 1;
+
+  printf("renameat2 ok\n");
+  return 0;
+}
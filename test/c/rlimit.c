@@ -0,0 +1,156 @@
+// rlimit test: RLIMIT_NOFILE bounds fd allocation, dup2 and F_DUPFD, and
+// RLIMIT_FSIZE shortens writes, then fails them with SIGXFSZ and EFBIG, and
+// bounds ftruncate. prlimit changes the limits of a child, which shows them
+// in /proc/<pid>/limits.
+
+#define _GNU_SOURCE
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <signal.h>
+#include <sys/resource.h>
+#include <sys/wait.h>
+
+static int failed;
+static volatile sig_atomic_t xfsz;
+
+static void die(const char *msg) {
+  perror(msg);
+  exit(1);
+}
+
+static void expect(const char *what, long ret, long want, int err) {
+  if (ret != want || (ret < 0 && errno != err)) {
+    fprintf(stderr, "%s: got %ld errno %d, expect %ld errno %d\n", what, ret,
+            ret < 0 ? errno : 0, want, err);
+    failed = 1;
+  }
+}
+
+static void onXfsz(int sig) {
+  (void)sig;
+  xfsz = 1;
+}
+
+static void testNofile(void) {
+  struct rlimit rl = {16, 16};
+  if (setrlimit(RLIMIT_NOFILE, &rl) < 0)
+    die("setrlimit NOFILE");
+
+  int fd = -1;
+  for (int i = 0; i < 16; i++) {
+    fd = open("/dev/null", O_RDONLY);
+    if (fd < 0)
+      break;
+  }
+
+  expect("open over limit", fd, -1, EMFILE);
+  expect("dup over limit", dup(0), -1, EMFILE);
+  expect("dup2 to limit", dup2(0, 16), -1, EBADF);
+  expect("F_DUPFD from limit", fcntl(0, F_DUPFD, 16), -1, EINVAL);
+
+  struct rlimit bad = {32, 16};
+  expect("setrlimit cur > max", setrlimit(RLIMIT_NOFILE, &bad), -1, EINVAL);
+
+  for (int i = 3; i < 16; i++)
+    close(i);
+}
+
+static void testFsize(void) {
+  char path[] = "/tmp/rlimitXXXXXX";
+  char buf[100];
+
+  int fd = mkstemp(path);
+  if (fd < 0)
+    die("mkstemp");
+  unlink(path);
+
+  signal(SIGXFSZ, onXfsz);
+
+  struct rlimit rl = {64, RLIM_INFINITY};
+  if (setrlimit(RLIMIT_FSIZE, &rl) < 0)
+    die("setrlimit FSIZE");
+
+  memset(buf, 'a', sizeof(buf));
+  expect("short write", write(fd, buf, sizeof(buf)), 64, 0);
+  if (xfsz) {
+    fprintf(stderr, "short write raised SIGXFSZ\n");
+    failed = 1;
+  }
+
+  expect("write at limit", write(fd, buf, sizeof(buf)), -1, EFBIG);
+  if (!xfsz) {
+    fprintf(stderr, "write at limit didn't raise SIGXFSZ\n");
+    failed = 1;
+  }
+
+  expect("ftruncate over limit", ftruncate(fd, 128), -1, EFBIG);
+  expect("ftruncate at limit", ftruncate(fd, 64), 0, 0);
+
+  close(fd);
+}
+
+static void testPrlimit(void) {
+  int pipefd[2];
+  char path[64], line[256];
+
+  if (pipe(pipefd) < 0)
+    die("pipe");
+
+  pid_t pid = fork();
+  if (pid < 0)
+    die("fork");
+  if (pid == 0) {
+    char c;
+    close(pipefd[1]);
+    read(pipefd[0], &c, 1);
+    _exit(0);
+  }
+  close(pipefd[0]);
+
+  struct rlimit newl = {8, 12}, oldl;
+  expect("prlimit child", prlimit(pid, RLIMIT_NOFILE, &newl, &oldl), 0, 0);
+
+  struct rlimit got;
+  expect("prlimit get child", prlimit(pid, RLIMIT_NOFILE, NULL, &got), 0, 0);
+  if (got.rlim_cur != 8 || got.rlim_max != 12) {
+    fprintf(stderr, "child NOFILE is %ld/%ld\n", (long)got.rlim_cur, (long)got.rlim_max);
+    failed = 1;
+  }
+
+  snprintf(path, sizeof(path), "/proc/%d/limits", pid);
+  FILE *f = fopen(path, "r");
+  if (f == NULL)
+    die("fopen limits");
+
+  int found = 0;
+  while (fgets(line, sizeof(line), f) != NULL) {
+    long soft, hard;
+    if (sscanf(line, "Max open files %ld %ld files", &soft, &hard) == 2)
+      found = soft == 8 && hard == 12;
+  }
+  fclose(f);
+
+  if (!found) {
+    fprintf(stderr, "/proc/%d/limits doesn't show the new NOFILE\n", pid);
+    failed = 1;
+  }
+
+  close(pipefd[1]);
+  waitpid(pid, NULL, 0);
+}
+
+int main(void) {
+  testNofile();
+  testFsize();
+  testPrlimit();
+
+  if (failed)
+    return 1;
+
+  printf("rlimit test pass\n");
+  return 0;
+}
@@ -0,0 +1,77 @@
+// realtime signal queueing test: with the signals blocked, queue several
+// instances of two realtime signals and one standard signal. Every realtime
+// instance must be delivered in FIFO order with its sigqueue value, lower
+// numbered signals first, and the standard signal exactly once.
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include <signal.h>
+
+#define COUNT 200
+
+static void die(const char *msg) {
+  perror(msg);
+  exit(1);
+}
+
+int main(void) {
+  sigset_t set;
+  siginfo_t info;
+  union sigval val;
+  int sigs[2] = {SIGRTMIN + 1, SIGRTMIN};
+  int next[2] = {0, 0};
+  int usr1 = 0;
+  int failed = 0;
+
+  sigemptyset(&set);
+  sigaddset(&set, sigs[0]);
+  sigaddset(&set, sigs[1]);
+  sigaddset(&set, SIGUSR1);
+  if (sigprocmask(SIG_BLOCK, &set, NULL) < 0)
+    die("sigprocmask");
+
+  for (int i = 0; i < COUNT; i++) {
+    for (int j = 0; j < 2; j++) {
+      val.sival_int = i;
+      if (sigqueue(getpid(), sigs[j], val) < 0)
+        die("sigqueue");
+    }
+    kill(getpid(), SIGUSR1);
+  }
+
+  struct timespec ts = {0, 0};
+  for (;;) {
+    int sig = sigtimedwait(&set, &info, &ts);
+    if (sig < 0)
+      break;
+
+    if (sig == SIGUSR1) {
+      usr1++;
+      continue;
+    }
+
+    // SIGRTMIN must be drained before SIGRTMIN + 1
+    int j = sig == sigs[1] ? 1 : 0;
+    if (j == 0 && next[1] != COUNT) {
+      fprintf(stderr, "SIGRTMIN+1 delivered before SIGRTMIN was drained\n");
+      failed = 1;
+    }
+
+    if (info.si_code != SI_QUEUE || info.si_pid != getpid() || info.si_value.sival_int != next[j]) {
+      fprintf(stderr, "signal %d: got code %d pid %d value %d, expect value %d\n", sig,
+              info.si_code, info.si_pid, info.si_value.sival_int, next[j]);
+      failed = 1;
+    }
+    next[j]++;
+  }
+
+  if (next[0] != COUNT || next[1] != COUNT || usr1 != 1) {
+    fprintf(stderr, "received %d/%d realtime and %d SIGUSR1\n", next[1], next[0], usr1);
+    failed = 1;
+  }
+
+  printf("%s\n", failed ? "fail" : "pass");
+  return failed;
+}
@@ -0,0 +1,93 @@
+// shm test: shm_open creates the objects in the /dev/shm tmpfs, they are
+// shared through mmap and unlinked by shm_unlink, memfd files take seals.
+
+#define _GNU_SOURCE
+#include <stdio.h>
+#include <string.h>
+#include <unistd.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <sys/mman.h>
+#include <sys/stat.h>
+#include <sys/vfs.h>
+#include <sys/wait.h>
+
+static int failed;
+
+#define CHECK(cond, ...)                                                       \
+  do {                                                                         \
+    if (!(cond)) {                                                             \
+      fprintf(stderr, __VA_ARGS__);                                            \
+      fprintf(stderr, " (line %d, errno %d)\n", __LINE__, errno);              \
+      failed = 1;                                                              \
+    }                                                                          \
+  } while (0)
+
+#define TMPFS_MAGIC 0x01021994
+#define SHM_NAME "/quark_shm_test"
+#define SHM_SIZE (1 << 20)
+
+int main() {
+  struct statfs fs;
+  CHECK(statfs("/dev/shm", &fs) == 0, "statfs /dev/shm");
+  CHECK(fs.f_type == TMPFS_MAGIC, "/dev/shm type %lx", (long)fs.f_type);
+
+  struct stat st;
+  CHECK(stat("/dev/shm", &st) == 0, "stat /dev/shm");
+  CHECK((st.st_mode & 01777) == 01777, "/dev/shm mode %o", st.st_mode);
+
+  int fd = shm_open(SHM_NAME, O_CREAT | O_EXCL | O_RDWR, 0600);
+  CHECK(fd >= 0, "shm_open");
+  CHECK(ftruncate(fd, SHM_SIZE) == 0, "ftruncate");
+
+  char *p = mmap(NULL, SHM_SIZE, PROT_READ | PROT_WRITE, MAP_SHARED, fd, 0);
+  CHECK(p != MAP_FAILED, "mmap");
+
+  // the child writes through its own mapping of the object
+  pid_t pid = fork();
+  if (pid == 0) {
+    int cfd = shm_open(SHM_NAME, O_RDWR, 0);
+    char *c = mmap(NULL, SHM_SIZE, PROT_READ | PROT_WRITE, MAP_SHARED, cfd, 0);
+    if (cfd < 0 || c == MAP_FAILED)
+      _exit(1);
+    strcpy(c + SHM_SIZE / 2, "hello");
+    _exit(0);
+  }
+
+  int status;
+  CHECK(waitpid(pid, &status, 0) == pid && WEXITSTATUS(status) == 0, "child");
+  CHECK(strcmp(p + SHM_SIZE / 2, "hello") == 0, "shared write");
+
+  // the shm objects are not memfds, they can't be sealed
+  CHECK(fcntl(fd, F_GET_SEALS) == F_SEAL_SEAL, "shm get seals");
+  CHECK(fcntl(fd, F_ADD_SEALS, F_SEAL_WRITE) < 0 && errno == EPERM,
+        "shm add seals");
+
+  munmap(p, SHM_SIZE);
+  close(fd);
+  CHECK(shm_unlink(SHM_NAME) == 0, "shm_unlink");
+  CHECK(shm_open(SHM_NAME, O_RDWR, 0) < 0 && errno == ENOENT, "unlinked");
+
+  int mfd = memfd_create("shm_test", MFD_ALLOW_SEALING);
+  CHECK(mfd >= 0, "memfd_create");
+  CHECK(ftruncate(mfd, 4096) == 0, "memfd ftruncate");
+  CHECK(fcntl(mfd, F_ADD_SEALS, F_SEAL_SHRINK | F_SEAL_GROW) == 0,
+        "memfd add seals");
+  CHECK(fcntl(mfd, F_GET_SEALS) == (F_SEAL_SHRINK | F_SEAL_GROW),
+        "memfd get seals");
+  CHECK(ftruncate(mfd, 8192) < 0 && errno == EPERM, "sealed ftruncate");
+  close(mfd);
+
+  mfd = memfd_create("shm_test", 0);
+  CHECK(fcntl(mfd, F_ADD_SEALS, F_SEAL_WRITE) < 0 && errno == EPERM,
+        "memfd without sealing");
+  close(mfd);
+
+  if (failed) {
+    printf("shm test fail\n");
+    return 1;
+  }
+
+  printf("shm test pass\n");
+  return 0;
+}
@@ -0,0 +1,124 @@
+// fcntl async I/O test: O_ASYNC with F_SETOWN delivers SIGIO to the owner,
+// F_SETSIG switches to a queued signal carrying si_fd and si_code, process
+// group owners receive the signal, and F_SETFL toggles O_APPEND/O_NONBLOCK.
+
+#define _GNU_SOURCE
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <poll.h>
+#include <signal.h>
+#include <time.h>
+#include <sys/wait.h>
+
+static int failed;
+
+#define CHECK(cond, ...)                                                       \
+  do {                                                                         \
+    if (!(cond)) {                                                             \
+      fprintf(stderr, __VA_ARGS__);                                            \
+      fprintf(stderr, " (line %d, errno %d)\n", __LINE__, errno);              \
+      failed = 1;                                                              \
+    }                                                                          \
+  } while (0)
+
+static int waitsig(sigset_t *set, siginfo_t *info) {
+  struct timespec ts = {2, 0};
+  return sigtimedwait(set, info, &ts);
+}
+
+int main(int argc, char **argv) {
+  int p[2];
+  siginfo_t info;
+  sigset_t set;
+  int rtsig = SIGRTMIN + 1;
+
+  sigemptyset(&set);
+  sigaddset(&set, SIGIO);
+  sigaddset(&set, rtsig);
+  sigprocmask(SIG_BLOCK, &set, NULL);
+
+  if (pipe(p) != 0) {
+    perror("pipe");
+    return 1;
+  }
+
+  // Default SIGIO to the owning process.
+  CHECK(fcntl(p[0], F_SETOWN, getpid()) == 0, "F_SETOWN");
+  CHECK(fcntl(p[0], F_GETOWN) == getpid(), "F_GETOWN");
+  CHECK(fcntl(p[0], F_SETFL, O_ASYNC) == 0, "F_SETFL O_ASYNC");
+  CHECK(fcntl(p[0], F_GETFL) & O_ASYNC, "F_GETFL O_ASYNC");
+  CHECK(write(p[1], "x", 1) == 1, "write");
+  CHECK(waitsig(&set, &info) == SIGIO, "SIGIO not delivered");
+
+  char c;
+  read(p[0], &c, 1);
+
+  // F_SETSIG reports the descriptor and the event in siginfo.
+  CHECK(fcntl(p[0], F_GETSIG) == 0, "F_GETSIG default");
+  CHECK(fcntl(p[0], F_SETSIG, 65) == -1 && errno == EINVAL, "F_SETSIG 65");
+  CHECK(fcntl(p[0], F_SETSIG, rtsig) == 0, "F_SETSIG");
+  CHECK(fcntl(p[0], F_GETSIG) == rtsig, "F_GETSIG");
+  CHECK(write(p[1], "x", 1) == 1, "write");
+  CHECK(waitsig(&set, &info) == rtsig, "F_SETSIG signal not delivered");
+  CHECK(info.si_fd == p[0], "si_fd %d", info.si_fd);
+  CHECK(info.si_code == POLL_IN, "si_code %d", info.si_code);
+  CHECK(info.si_band & POLLIN, "si_band %ld", info.si_band);
+  read(p[0], &c, 1);
+
+  // A process group owner: every member receives the signal.
+  CHECK(fcntl(p[0], F_SETSIG, 0) == 0, "F_SETSIG 0");
+  setpgid(0, 0);
+  CHECK(fcntl(p[0], F_SETOWN, -getpgrp()) == 0, "F_SETOWN pgrp");
+  CHECK(fcntl(p[0], F_GETOWN) == -getpgrp(), "F_GETOWN pgrp");
+
+  int ready[2];
+  pipe(ready);
+  pid_t child = fork();
+  if (child == 0) {
+    write(ready[1], "r", 1);
+    exit(waitsig(&set, &info) == SIGIO ? 0 : 1);
+  }
+  read(ready[0], &c, 1);
+  CHECK(write(p[1], "x", 1) == 1, "write");
+  CHECK(waitsig(&set, &info) == SIGIO, "parent missed group SIGIO");
+  int status;
+  waitpid(child, &status, 0);
+  CHECK(WIFEXITED(status) && WEXITSTATUS(status) == 0,
+        "child missed group SIGIO");
+  read(p[0], &c, 1);
+
+  // Clearing O_ASYNC stops the signals.
+  CHECK(fcntl(p[0], F_SETFL, 0) == 0, "F_SETFL 0");
+  CHECK(write(p[1], "x", 1) == 1, "write");
+  struct timespec ts = {0, 100 * 1000 * 1000};
+  CHECK(sigtimedwait(&set, &info, &ts) == -1 && errno == EAGAIN,
+        "signal after clearing O_ASYNC");
+
+  // O_NONBLOCK and O_APPEND are per open file.
+  CHECK(fcntl(p[0], F_SETFL, O_NONBLOCK) == 0, "F_SETFL O_NONBLOCK");
+  read(p[0], &c, 1);
+  CHECK(read(p[0], &c, 1) == -1 && errno == EAGAIN, "nonblocking read");
+
+  char file[] = "/tmp/sigioXXXXXX";
+  int fd = mkstemp(file);
+  int fd2 = open(file, O_WRONLY);
+  write(fd, "abc", 3);
+  CHECK(fcntl(fd2, F_SETFL, O_APPEND) == 0, "F_SETFL O_APPEND");
+  CHECK(fcntl(fd2, F_GETFL) & O_APPEND, "F_GETFL O_APPEND");
+  CHECK(!(fcntl(fd, F_GETFL) & O_APPEND), "O_APPEND leaked to other file");
+  write(fd2, "d", 1);
+  CHECK(lseek(fd, 0, SEEK_END) == 4, "append offset");
+  close(fd2);
+  close(fd);
+  unlink(file);
+
+  if (failed)
+    return 1;
+
+  printf("sigio ok\n");
+  return 0;
+}
@@ -0,0 +1,110 @@
+// sockfilter test: SO_ATTACH_FILTER drops and trims the datagrams of unix
+// and UDP sockets, bad programs are refused and SO_LOCK_FILTER holds.
+
+#define _GNU_SOURCE
+#include <stdio.h>
+#include <string.h>
+#include <unistd.h>
+#include <errno.h>
+#include <netinet/in.h>
+#include <arpa/inet.h>
+#include <sys/socket.h>
+#include <linux/filter.h>
+
+static int failed;
+
+#define CHECK(cond, ...)                                                       \
+  do {                                                                         \
+    if (!(cond)) {                                                             \
+      fprintf(stderr, __VA_ARGS__);                                            \
+      fprintf(stderr, " (line %d, errno %d)\n", __LINE__, errno);              \
+      failed = 1;                                                              \
+    }                                                                          \
+  } while (0)
+
+// accept the datagrams starting with 'A', trimmed to 4 bytes. The UDP
+// filter sees the UDP header before the payload.
+#define ACCEPT_A(off)                                                          \
+  {                                                                            \
+    BPF_STMT(BPF_LD | BPF_B | BPF_ABS, off),                                   \
+        BPF_JUMP(BPF_JMP | BPF_JEQ | BPF_K, 'A', 0, 1),                        \
+        BPF_STMT(BPF_RET | BPF_K, (off) + 4), BPF_STMT(BPF_RET | BPF_K, 0),    \
+  }
+
+static struct sock_filter accept_a[] = ACCEPT_A(0);
+static struct sock_filter accept_a_udp[] = ACCEPT_A(8);
+
+static int attach(int fd, struct sock_filter *insns, int len) {
+  struct sock_fprog prog = {len, insns};
+  return setsockopt(fd, SOL_SOCKET, SO_ATTACH_FILTER, &prog, sizeof(prog));
+}
+
+// check sends "Bxx" and "Axxxxxx" to fd by tx, only "Axxx" is received
+static void check(int tx, int rx, struct sockaddr *to, socklen_t tolen,
+                  const char *name) {
+  char buf[64];
+  CHECK(sendto(tx, "Bxx", 3, 0, to, tolen) == 3, "%s: send B", name);
+  CHECK(sendto(tx, "Axxxxxx", 7, 0, to, tolen) == 7, "%s: send A", name);
+  int n = recv(rx, buf, sizeof(buf), 0);
+  CHECK(n == 4 && memcmp(buf, "Axxx", 4) == 0, "%s: recv %d", name, n);
+  CHECK(recv(rx, buf, sizeof(buf), MSG_DONTWAIT) < 0 && errno == EAGAIN,
+        "%s: dropped", name);
+}
+
+int main() {
+  int sv[2];
+  CHECK(socketpair(AF_UNIX, SOCK_DGRAM, 0, sv) == 0, "socketpair");
+  CHECK(attach(sv[1], accept_a, 4) == 0, "unix attach");
+  check(sv[0], sv[1], NULL, 0, "unix");
+
+  // bad programs
+  struct sock_filter nortn[] = {BPF_STMT(BPF_LD | BPF_IMM, 1)};
+  CHECK(attach(sv[1], nortn, 1) < 0 && errno == EINVAL, "no return");
+  struct sock_filter outside[] = {
+      BPF_JUMP(BPF_JMP | BPF_JEQ | BPF_K, 0, 5, 0),
+      BPF_STMT(BPF_RET | BPF_K, 0),
+  };
+  CHECK(attach(sv[1], outside, 2) < 0 && errno == EINVAL, "jump outside");
+  CHECK(attach(sv[1], accept_a, 0) < 0 && errno == EINVAL, "empty");
+
+  // detach, then nothing to detach
+  int v = 0;
+  CHECK(setsockopt(sv[1], SOL_SOCKET, SO_DETACH_FILTER, &v, sizeof(v)) == 0,
+        "detach");
+  CHECK(setsockopt(sv[1], SOL_SOCKET, SO_DETACH_FILTER, &v, sizeof(v)) < 0 &&
+            errno == ENOENT,
+        "detach again");
+
+  // a locked filter can't be changed
+  CHECK(attach(sv[1], accept_a, 4) == 0, "attach again");
+  v = 1;
+  CHECK(setsockopt(sv[1], SOL_SOCKET, SO_LOCK_FILTER, &v, sizeof(v)) == 0,
+        "lock");
+  CHECK(setsockopt(sv[1], SOL_SOCKET, SO_DETACH_FILTER, &v, sizeof(v)) < 0 &&
+            errno == EPERM,
+        "detach locked");
+  close(sv[0]);
+  close(sv[1]);
+
+  // the host socket gets the program
+  int rx = socket(AF_INET, SOCK_DGRAM, 0);
+  int tx = socket(AF_INET, SOCK_DGRAM, 0);
+  struct sockaddr_in addr = {0};
+  socklen_t len = sizeof(addr);
+  addr.sin_family = AF_INET;
+  addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+  CHECK(bind(rx, (struct sockaddr *)&addr, sizeof(addr)) == 0, "bind");
+  getsockname(rx, (struct sockaddr *)&addr, &len);
+  CHECK(attach(rx, accept_a_udp, 4) == 0, "udp attach");
+  check(tx, rx, (struct sockaddr *)&addr, len, "udp");
+  close(tx);
+  close(rx);
+
+  if (failed) {
+    printf("sockfilter test fail\n");
+    return 1;
+  }
+
+  printf("sockfilter test pass\n");
+  return 0;
+}
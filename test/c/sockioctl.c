@@ -0,0 +1,138 @@
+// sockioctl test: FIONREAD/SIOCINQ and SIOCOUTQ on tcp, udp and unix
+// sockets, the SIOCGIF* interface ioctls, and ENOTTY for a non socket ioctl.
+
+#define _GNU_SOURCE
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include <errno.h>
+#include <termios.h>
+#include <net/if.h>
+#include <netinet/in.h>
+#include <arpa/inet.h>
+#include <sys/ioctl.h>
+#include <sys/socket.h>
+#include <sys/un.h>
+#include <linux/sockios.h>
+
+static int failed;
+
+#define CHECK(cond, ...)                                                       \
+  do {                                                                         \
+    if (!(cond)) {                                                             \
+      fprintf(stderr, __VA_ARGS__);                                            \
+      fprintf(stderr, " (line %d, errno %d)\n", __LINE__, errno);              \
+      failed = 1;                                                              \
+    }                                                                          \
+  } while (0)
+
+// waitinq waits until fd has n bytes to read and returns FIONREAD.
+static int waitinq(int fd, int n) {
+  int v = -1;
+  for (int i = 0; i < 200; i++) {
+    if (ioctl(fd, FIONREAD, &v) != 0)
+      return -1;
+    if (v >= n)
+      break;
+    usleep(5000);
+  }
+  return v;
+}
+
+static int waitoutq(int fd) {
+  int v = -1;
+  for (int i = 0; i < 200; i++) {
+    if (ioctl(fd, SIOCOUTQ, &v) != 0)
+      return -1;
+    if (v == 0)
+      break;
+    usleep(5000);
+  }
+  return v;
+}
+
+int main(int argc, char **argv) {
+  char buf[256];
+  int v;
+
+  // tcp over loopback
+  int l = socket(AF_INET, SOCK_STREAM, 0);
+  struct sockaddr_in addr = {0};
+  addr.sin_family = AF_INET;
+  addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+  socklen_t len = sizeof(addr);
+  CHECK(bind(l, (struct sockaddr *)&addr, sizeof(addr)) == 0, "bind");
+  CHECK(listen(l, 1) == 0, "listen");
+  getsockname(l, (struct sockaddr *)&addr, &len);
+  int c = socket(AF_INET, SOCK_STREAM, 0);
+  CHECK(connect(c, (struct sockaddr *)&addr, sizeof(addr)) == 0, "connect");
+  int a = accept(l, NULL, NULL);
+  CHECK(ioctl(a, FIONREAD, &v) == 0 && v == 0, "tcp FIONREAD empty %d", v);
+  CHECK(write(c, buf, 100) == 100, "write");
+  CHECK(waitinq(a, 100) == 100, "tcp FIONREAD");
+  CHECK(ioctl(a, SIOCINQ, &v) == 0 && v == 100, "tcp SIOCINQ %d", v);
+  CHECK(waitoutq(c) == 0, "tcp SIOCOUTQ");
+  read(a, buf, 40);
+  CHECK(waitinq(a, 60) == 60, "tcp FIONREAD after read");
+
+  // udp reports the size of the next datagram
+  int u1 = socket(AF_INET, SOCK_DGRAM, 0);
+  int u2 = socket(AF_INET, SOCK_DGRAM, 0);
+  addr.sin_port = 0;
+  bind(u1, (struct sockaddr *)&addr, sizeof(addr));
+  len = sizeof(addr);
+  getsockname(u1, (struct sockaddr *)&addr, &len);
+  sendto(u2, buf, 10, 0, (struct sockaddr *)&addr, sizeof(addr));
+  sendto(u2, buf, 20, 0, (struct sockaddr *)&addr, sizeof(addr));
+  CHECK(waitinq(u1, 10) == 10, "udp FIONREAD");
+
+  // unix stream pair
+  int sv[2];
+  CHECK(socketpair(AF_UNIX, SOCK_STREAM, 0, sv) == 0, "socketpair");
+  CHECK(ioctl(sv[1], FIONREAD, &v) == 0 && v == 0, "unix FIONREAD empty");
+  write(sv[0], buf, 30);
+  CHECK(ioctl(sv[1], FIONREAD, &v) == 0 && v == 30, "unix FIONREAD %d", v);
+  CHECK(ioctl(sv[0], SIOCOUTQ, &v) == 0 && v > 0, "unix SIOCOUTQ %d", v);
+  read(sv[1], buf, 30);
+  CHECK(ioctl(sv[0], SIOCOUTQ, &v) == 0 && v == 0, "unix SIOCOUTQ read %d",
+        v);
+
+  // a bound unix datagram socket which isn't connected
+  struct sockaddr_un ua = {0};
+  ua.sun_family = AF_UNIX;
+  snprintf(ua.sun_path, sizeof(ua.sun_path), "/tmp/sockioctl%d", getpid());
+  unlink(ua.sun_path);
+  int d1 = socket(AF_UNIX, SOCK_DGRAM, 0);
+  int d2 = socket(AF_UNIX, SOCK_DGRAM, 0);
+  CHECK(bind(d1, (struct sockaddr *)&ua, sizeof(ua)) == 0, "unix bind");
+  CHECK(ioctl(d1, FIONREAD, &v) == 0 && v == 0, "unix dgram FIONREAD empty");
+  CHECK(ioctl(d2, SIOCOUTQ, &v) == 0 && v == 0, "unix dgram SIOCOUTQ");
+  sendto(d2, buf, 12, 0, (struct sockaddr *)&ua, sizeof(ua));
+  CHECK(ioctl(d1, FIONREAD, &v) == 0 && v >= 12, "unix dgram FIONREAD %d", v);
+  unlink(ua.sun_path);
+
+  // interface ioctls on lo
+  struct ifreq ifr;
+  memset(&ifr, 0, sizeof(ifr));
+  strcpy(ifr.ifr_name, "lo");
+  CHECK(ioctl(u1, SIOCGIFADDR, &ifr) == 0, "SIOCGIFADDR");
+  struct sockaddr_in *sin = (struct sockaddr_in *)&ifr.ifr_addr;
+  CHECK(sin->sin_family == AF_INET &&
+            sin->sin_addr.s_addr == htonl(INADDR_LOOPBACK),
+        "lo address");
+  CHECK(ioctl(u1, SIOCGIFHWADDR, &ifr) == 0, "SIOCGIFHWADDR");
+  CHECK(ioctl(u1, SIOCGIFMTU, &ifr) == 0 && ifr.ifr_mtu > 0, "SIOCGIFMTU");
+  CHECK(ioctl(sv[0], SIOCGIFMTU, &ifr) == 0 && ifr.ifr_mtu > 0,
+        "unix SIOCGIFMTU");
+
+  // sockets are not terminals
+  struct termios t;
+  CHECK(ioctl(c, TCGETS, &t) == -1 && errno == ENOTTY, "TCGETS");
+
+  if (failed)
+    return 1;
+
+  printf("sockioctl ok\n");
+  return 0;
+}
@@ -0,0 +1,75 @@
+// statfs test: /proc and /sys report their filesystem types, a tmpfs has a
+// size, and the block counts of a directory are consistent.
+
+#define _GNU_SOURCE
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <sys/statfs.h>
+#include <linux/magic.h>
+
+static int failed;
+
+static void check(const char *path, long type) {
+  struct statfs st;
+
+  if (statfs(path, &st) < 0) {
+    fprintf(stderr, "statfs %s: %s\n", path, strerror(errno));
+    failed = 1;
+    return;
+  }
+
+  if (type != 0 && st.f_type != type) {
+    fprintf(stderr, "%s: type %lx, expect %lx\n", path, (long)st.f_type, type);
+    failed = 1;
+  }
+
+  if (st.f_bsize <= 0 || st.f_bavail > st.f_bfree ||
+      st.f_bfree > st.f_blocks) {
+    fprintf(stderr, "%s: bsize %ld blocks %lu free %lu avail %lu\n", path,
+            (long)st.f_bsize, st.f_blocks, st.f_bfree, st.f_bavail);
+    failed = 1;
+  }
+
+  if (type == TMPFS_MAGIC && st.f_blocks == 0) {
+    fprintf(stderr, "%s: tmpfs without size\n", path);
+    failed = 1;
+  }
+}
+
+int main(int argc, char **argv) {
+  const char *dir = argc > 1 ? argv[1] : "/tmp";
+  struct statfs st;
+
+  check("/proc", PROC_SUPER_MAGIC);
+  check("/proc/meminfo", PROC_SUPER_MAGIC);
+  check("/sys", SYSFS_MAGIC);
+  check(dir, 0);
+
+  if (statfs("/dev/shm", &st) == 0 && st.f_type == TMPFS_MAGIC)
+    check("/dev/shm", TMPFS_MAGIC);
+
+  int fd = open(dir, O_RDONLY | O_DIRECTORY);
+  if (fd < 0) {
+    perror("open");
+    return 1;
+  }
+  struct statfs fst;
+  if (fstatfs(fd, &fst) < 0 || statfs(dir, &st) < 0) {
+    perror("fstatfs");
+    failed = 1;
+  } else if (fst.f_type != st.f_type || fst.f_bsize != st.f_bsize) {
+    fprintf(stderr, "fstatfs and statfs differ\n");
+    failed = 1;
+  }
+  close(fd);
+
+  if (failed)
+    return 1;
+
+  printf("statfs ok\n");
+  return 0;
+}
@@ -0,0 +1,101 @@
+// syncfs test: sync and syncfs finish while other processes keep writing to
+// the same filesystem, and the data written before them reads back.
+
+#define _GNU_SOURCE
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <signal.h>
+#include <sys/wait.h>
+
+#define WRITERS 4
+#define ROUNDS 20
+
+static int failed;
+
+static void die(const char *msg) {
+  perror(msg);
+  exit(1);
+}
+
+static void writer(const char *dir, int id) {
+  char path[256], buf[4096];
+  snprintf(path, sizeof(path), "%s/writer%d", dir, id);
+  int fd = open(path, O_WRONLY | O_CREAT | O_TRUNC, 0644);
+  if (fd < 0)
+    die("open writer");
+
+  memset(buf, 'a' + id, sizeof(buf));
+  for (;;) {
+    if (pwrite(fd, buf, sizeof(buf), 0) != sizeof(buf))
+      die("pwrite");
+  }
+}
+
+int main(int argc, char **argv) {
+  const char *dir = argc > 1 ? argv[1] : "/tmp";
+  char path[256], buf[64], want[64];
+  pid_t pids[WRITERS];
+
+  snprintf(path, sizeof(path), "%s/syncfsXXXXXX", dir);
+  int fd = mkstemp(path);
+  if (fd < 0)
+    die("mkstemp");
+
+  for (int i = 0; i < WRITERS; i++) {
+    pids[i] = fork();
+    if (pids[i] < 0)
+      die("fork");
+    if (pids[i] == 0)
+      writer(dir, i);
+  }
+
+  for (int i = 0; i < ROUNDS; i++) {
+    snprintf(buf, sizeof(buf), "round %d", i);
+    if (pwrite(fd, buf, strlen(buf) + 1, 0) < 0)
+      die("pwrite");
+
+    if (syncfs(fd) < 0) {
+      perror("syncfs");
+      failed = 1;
+    }
+    sync();
+    if (fsync(fd) < 0) {
+      perror("fsync");
+      failed = 1;
+    }
+  }
+
+  for (int i = 0; i < WRITERS; i++) {
+    kill(pids[i], SIGKILL);
+    waitpid(pids[i], NULL, 0);
+    snprintf(buf, sizeof(buf), "%s/writer%d", dir, i);
+    unlink(buf);
+  }
+
+  memset(buf, 0, sizeof(buf));
+  if (pread(fd, buf, sizeof(buf) - 1, 0) < 0)
+    die("pread");
+  snprintf(want, sizeof(want), "round %d", ROUNDS - 1);
+  if (strcmp(buf, want) != 0) {
+    fprintf(stderr, "read back '%s'\n", buf);
+    failed = 1;
+  }
+
+  if (syncfs(-1) != -1 || errno != EBADF) {
+    fprintf(stderr, "syncfs on bad fd doesn't fail with EBADF\n");
+    failed = 1;
+  }
+
+  close(fd);
+  unlink(path);
+
+  if (failed)
+    return 1;
+
+  printf("syncfs test pass\n");
+  return 0;
+}
@@ -0,0 +1,71 @@
+// timerslack test: PR_SET_TIMERSLACK sets the slack of the thread, 0
+// restores the default which a child inherits from the slack of its parent,
+// and the sleeps still last at least their timeout.
+
+#define _GNU_SOURCE
+#include <stdio.h>
+#include <unistd.h>
+#include <errno.h>
+#include <poll.h>
+#include <time.h>
+#include <sys/prctl.h>
+#include <sys/wait.h>
+
+static int failed;
+
+#define CHECK(cond, ...)                                                       \
+  do {                                                                         \
+    if (!(cond)) {                                                             \
+      fprintf(stderr, __VA_ARGS__);                                            \
+      fprintf(stderr, " (line %d, errno %d)\n", __LINE__, errno);              \
+      failed = 1;                                                              \
+    }                                                                          \
+  } while (0)
+
+static long now_us() {
+  struct timespec ts;
+  clock_gettime(CLOCK_MONOTONIC, &ts);
+  return ts.tv_sec * 1000000 + ts.tv_nsec / 1000;
+}
+
+int main() {
+  long def = prctl(PR_GET_TIMERSLACK, 0, 0, 0, 0);
+  CHECK(def > 0, "default slack %ld", def);
+
+  CHECK(prctl(PR_SET_TIMERSLACK, 1000000, 0, 0, 0) == 0, "set slack");
+  CHECK(prctl(PR_GET_TIMERSLACK, 0, 0, 0, 0) == 1000000, "get slack");
+
+  // the slack only delays the expiry
+  long start = now_us();
+  usleep(10 * 1000);
+  CHECK(now_us() - start >= 10 * 1000, "usleep early");
+
+  start = now_us();
+  poll(NULL, 0, 10);
+  CHECK(now_us() - start >= 10 * 1000, "poll early");
+
+  pid_t pid = fork();
+  if (pid == 0) {
+    // the child starts with the parent's slack, which is also its default
+    if (prctl(PR_GET_TIMERSLACK, 0, 0, 0, 0) != 1000000)
+      _exit(1);
+    prctl(PR_SET_TIMERSLACK, 5000, 0, 0, 0);
+    prctl(PR_SET_TIMERSLACK, 0, 0, 0, 0);
+    _exit(prctl(PR_GET_TIMERSLACK, 0, 0, 0, 0) == 1000000 ? 0 : 2);
+  }
+
+  int status;
+  CHECK(waitpid(pid, &status, 0) == pid && WEXITSTATUS(status) == 0,
+        "child slack %d", WEXITSTATUS(status));
+
+  CHECK(prctl(PR_SET_TIMERSLACK, 0, 0, 0, 0) == 0, "reset slack");
+  CHECK(prctl(PR_GET_TIMERSLACK, 0, 0, 0, 0) == def, "default restored");
+
+  if (failed) {
+    printf("timerslack test fail\n");
+    return 1;
+  }
+
+  printf("timerslack test pass\n");
+  return 0;
+}
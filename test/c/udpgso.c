@@ -0,0 +1,156 @@
+// udpgso test: UDP_SEGMENT and UDP_GRO socket options and control
+// messages on udp sockets, as used by QUIC stacks.
+
+#define _GNU_SOURCE
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include <errno.h>
+#include <stdint.h>
+#include <netinet/in.h>
+#include <arpa/inet.h>
+#include <sys/socket.h>
+
+#ifndef SOL_UDP
+#define SOL_UDP 17
+#endif
+#ifndef UDP_SEGMENT
+#define UDP_SEGMENT 103
+#endif
+#ifndef UDP_GRO
+#define UDP_GRO 104
+#endif
+
+static int failed;
+
+#define CHECK(cond, ...)                                                       \
+  do {                                                                         \
+    if (!(cond)) {                                                             \
+      fprintf(stderr, __VA_ARGS__);                                            \
+      fprintf(stderr, " (line %d, errno %d)\n", __LINE__, errno);              \
+      failed = 1;                                                              \
+    }                                                                          \
+  } while (0)
+
+#define SEG 1000
+#define SEGS 4
+
+static char sendbuf[SEG * SEGS];
+static char recvbuf[65536];
+
+// sendgso sends SEGS segments with a UDP_SEGMENT control message.
+static int sendgso(int fd) {
+  char control[CMSG_SPACE(sizeof(uint16_t))] = {0};
+  struct iovec iov = {sendbuf, sizeof(sendbuf)};
+  struct msghdr msg = {0};
+  msg.msg_iov = &iov;
+  msg.msg_iovlen = 1;
+  msg.msg_control = control;
+  msg.msg_controllen = sizeof(control);
+
+  struct cmsghdr *cm = CMSG_FIRSTHDR(&msg);
+  cm->cmsg_level = SOL_UDP;
+  cm->cmsg_type = UDP_SEGMENT;
+  cm->cmsg_len = CMSG_LEN(sizeof(uint16_t));
+  *(uint16_t *)CMSG_DATA(cm) = SEG;
+
+  return sendmsg(fd, &msg, 0);
+}
+
+// recvgro receives what was sent and checks every datagram, either a
+// single segment or a coalesced one with its UDP_GRO segment size.
+static void recvgro(int fd) {
+  int total = 0;
+  while (total < (int)sizeof(sendbuf)) {
+    char control[CMSG_SPACE(sizeof(int))] = {0};
+    struct iovec iov = {recvbuf, sizeof(recvbuf)};
+    struct msghdr msg = {0};
+    msg.msg_iov = &iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control;
+    msg.msg_controllen = sizeof(control);
+
+    int n = recvmsg(fd, &msg, MSG_CMSG_CLOEXEC);
+    CHECK(n > 0, "recvmsg %d", n);
+    if (n <= 0)
+      return;
+    CHECK(!(msg.msg_flags & MSG_CTRUNC), "ctrunc");
+
+    int gso = n;
+    for (struct cmsghdr *cm = CMSG_FIRSTHDR(&msg); cm != NULL;
+         cm = CMSG_NXTHDR(&msg, cm)) {
+      if (cm->cmsg_level == SOL_UDP && cm->cmsg_type == UDP_GRO)
+        gso = *(int *)CMSG_DATA(cm);
+    }
+
+    CHECK(gso == SEG, "segment size %d of %d bytes", gso, n);
+    CHECK(memcmp(recvbuf, sendbuf + total, n) == 0, "data at %d", total);
+    total += n;
+  }
+
+  CHECK(total == (int)sizeof(sendbuf), "received %d", total);
+}
+
+int main() {
+  for (int i = 0; i < (int)sizeof(sendbuf); i++)
+    sendbuf[i] = i % 251;
+
+  int r = socket(AF_INET, SOCK_DGRAM, 0);
+  int s = socket(AF_INET, SOCK_DGRAM, 0);
+  struct sockaddr_in addr = {0};
+  addr.sin_family = AF_INET;
+  addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+  socklen_t alen = sizeof(addr);
+  CHECK(bind(r, (struct sockaddr *)&addr, alen) == 0, "bind");
+  CHECK(getsockname(r, (struct sockaddr *)&addr, &alen) == 0, "getsockname");
+  CHECK(connect(s, (struct sockaddr *)&addr, alen) == 0, "connect");
+
+  int one = 1;
+  if (setsockopt(r, SOL_UDP, UDP_GRO, &one, sizeof(one)) != 0) {
+    printf("udpgso test skip, no UDP_GRO on the host (errno %d)\n", errno);
+    return 0;
+  }
+
+  int v = 0;
+  socklen_t vlen = sizeof(v);
+  CHECK(getsockopt(r, SOL_UDP, UDP_GRO, &v, &vlen) == 0 && v == 1,
+        "get UDP_GRO %d", v);
+
+  // the segment size from the socket option
+  int seg = SEG;
+  CHECK(setsockopt(s, SOL_UDP, UDP_SEGMENT, &seg, sizeof(seg)) == 0,
+        "set UDP_SEGMENT");
+  v = 0;
+  vlen = sizeof(v);
+  CHECK(getsockopt(s, SOL_UDP, UDP_SEGMENT, &v, &vlen) == 0 && v == SEG,
+        "get UDP_SEGMENT %d", v);
+  CHECK(send(s, sendbuf, sizeof(sendbuf), 0) == sizeof(sendbuf), "send");
+  recvgro(r);
+
+  // the segment size from the control message
+  seg = 0;
+  CHECK(setsockopt(s, SOL_UDP, UDP_SEGMENT, &seg, sizeof(seg)) == 0,
+        "clear UDP_SEGMENT");
+  CHECK(sendgso(s) == sizeof(sendbuf), "sendmsg UDP_SEGMENT");
+  recvgro(r);
+
+  // UDP options are not valid on a tcp socket
+  int t = socket(AF_INET, SOCK_STREAM, 0);
+  errno = 0;
+  CHECK(setsockopt(t, SOL_UDP, UDP_SEGMENT, &seg, sizeof(seg)) == -1 &&
+            errno == ENOPROTOOPT,
+        "UDP_SEGMENT on tcp");
+
+  close(t);
+  close(s);
+  close(r);
+
+  if (failed) {
+    printf("udpgso test fail\n");
+    return 1;
+  }
+
+  printf("udpgso test pass\n");
+  return 0;
+}
@@ -0,0 +1,149 @@
+// utimensat test: explicit times, UTIME_NOW and UTIME_OMIT, futimens, symlinks
+// with AT_SYMLINK_NOFOLLOW, invalid arguments and the permission rules of a
+// caller that doesn't own the file.
+
+#define _GNU_SOURCE
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <time.h>
+#include <sys/stat.h>
+#include <sys/wait.h>
+
+static int failed;
+
+#define CHECK(cond, ...)                                                       \
+  do {                                                                         \
+    if (!(cond)) {                                                             \
+      fprintf(stderr, __VA_ARGS__);                                            \
+      fprintf(stderr, " (line %d, errno %d)\n", __LINE__, errno);              \
+      failed = 1;                                                              \
+    }                                                                          \
+  } while (0)
+
+static void settimes(struct timespec *ts, long asec, long ansec, long msec,
+                     long mnsec) {
+  ts[0].tv_sec = asec;
+  ts[0].tv_nsec = ansec;
+  ts[1].tv_sec = msec;
+  ts[1].tv_nsec = mnsec;
+}
+
+int main(int argc, char **argv) {
+  char file[] = "/tmp/utimensXXXXXX";
+  char link[64];
+  struct timespec ts[2];
+  struct stat st, lst;
+
+  int fd = mkstemp(file);
+  if (fd < 0) {
+    perror("mkstemp");
+    return 1;
+  }
+  snprintf(link, sizeof(link), "%s.lnk", file);
+  symlink(file, link);
+
+  settimes(ts, 1000, 123456789, 2000, 987654321);
+  CHECK(utimensat(AT_FDCWD, file, ts, 0) == 0, "utimensat");
+  stat(file, &st);
+  CHECK(st.st_atim.tv_sec == 1000 && st.st_atim.tv_nsec == 123456789 &&
+            st.st_mtim.tv_sec == 2000 && st.st_mtim.tv_nsec == 987654321,
+        "explicit times %ld.%ld %ld.%ld", st.st_atim.tv_sec,
+        st.st_atim.tv_nsec, st.st_mtim.tv_sec, st.st_mtim.tv_nsec);
+
+  // UTIME_OMIT keeps the access time, UTIME_NOW sets the modification time
+  time_t now = time(NULL);
+  settimes(ts, 0, UTIME_OMIT, 0, UTIME_NOW);
+  CHECK(futimens(fd, ts) == 0, "futimens");
+  fstat(fd, &st);
+  CHECK(st.st_atim.tv_sec == 1000, "UTIME_OMIT changed atime");
+  CHECK(st.st_mtim.tv_sec >= now - 1 && st.st_mtim.tv_sec <= now + 5,
+        "UTIME_NOW mtime %ld, now %ld", st.st_mtim.tv_sec, now);
+  CHECK(st.st_ctim.tv_sec >= now - 1, "ctime not updated");
+
+  // both omitted is a noop
+  settimes(ts, 0, UTIME_OMIT, 0, UTIME_OMIT);
+  CHECK(utimensat(AT_FDCWD, file, ts, 0) == 0, "UTIME_OMIT noop");
+
+  // the symlink itself
+  settimes(ts, 3000, 0, 4000, 0);
+  CHECK(utimensat(AT_FDCWD, link, ts, AT_SYMLINK_NOFOLLOW) == 0,
+        "utimensat nofollow");
+  lstat(link, &lst);
+  stat(file, &st);
+  CHECK(lst.st_mtim.tv_sec == 4000, "symlink mtime %ld", lst.st_mtim.tv_sec);
+  CHECK(st.st_mtim.tv_sec != 4000, "nofollow changed the target");
+
+  // AT_EMPTY_PATH on the fd
+  settimes(ts, 5000, 0, 6000, 0);
+  CHECK(utimensat(fd, "", ts, AT_EMPTY_PATH) == 0, "AT_EMPTY_PATH");
+  fstat(fd, &st);
+  CHECK(st.st_mtim.tv_sec == 6000, "AT_EMPTY_PATH mtime %ld",
+        st.st_mtim.tv_sec);
+
+  // invalid arguments
+  settimes(ts, 0, 1000000000, 0, 0);
+  CHECK(utimensat(AT_FDCWD, file, ts, 0) < 0 && errno == EINVAL,
+        "invalid nsec");
+  CHECK(utimensat(AT_FDCWD, file, NULL, 0x2) < 0 && errno == EINVAL,
+        "invalid flags");
+
+  // a caller other than the owner can only touch the file, and needs the
+  // write permission for it
+  if (geteuid() == 0) {
+    fchmod(fd, 0666);
+    pid_t pid = fork();
+    if (pid == 0) {
+      if (setgid(65534) < 0 || setuid(65534) < 0)
+        _exit(2);
+
+      int bad = 0;
+      settimes(ts, 7000, 0, 8000, 0);
+      if (utimensat(AT_FDCWD, file, ts, 0) == 0 || errno != EPERM)
+        bad = 1;
+      settimes(ts, 0, UTIME_NOW, 0, UTIME_OMIT);
+      if (utimensat(AT_FDCWD, file, ts, 0) == 0 || errno != EPERM)
+        bad = 1;
+      if (utimensat(AT_FDCWD, file, NULL, 0) != 0)
+        bad = 1;
+      _exit(bad);
+    }
+
+    int status;
+    waitpid(pid, &status, 0);
+    CHECK(WIFEXITED(status) && WEXITSTATUS(status) == 0,
+          "non-owner rules, status %x", status);
+
+    fchmod(fd, 0644);
+    pid = fork();
+    if (pid == 0) {
+      if (setgid(65534) < 0 || setuid(65534) < 0)
+        _exit(2);
+      if (utimensat(AT_FDCWD, file, NULL, 0) == 0 || errno != EACCES)
+        _exit(1);
+      _exit(0);
+    }
+    waitpid(pid, &status, 0);
+    CHECK(WIFEXITED(status) && WEXITSTATUS(status) == 0,
+          "non-owner without write permission, status %x", status);
+  }
+
+  struct statx stx;
+  if (statx(AT_FDCWD, file, 0, STATX_BTIME, &stx) == 0 &&
+      (stx.stx_mask & STATX_BTIME)) {
+    CHECK(stx.stx_btime.tv_sec >= now - 60, "btime %lld", stx.stx_btime.tv_sec);
+  }
+
+  close(fd);
+  unlink(link);
+  unlink(file);
+
+  if (failed)
+    return 1;
+
+  printf("utimens ok\n");
+  return 0;
+}
@@ -0,0 +1,69 @@
+// vfork test: the parent must stay suspended until the child exits or
+// execs, and the child must run in the parent's address space.
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <unistd.h>
+#include <time.h>
+#include <sys/types.h>
+#include <sys/wait.h>
+
+static volatile int shared;
+static int failed;
+
+static long now_ms(void) {
+  struct timespec ts;
+  clock_gettime(CLOCK_MONOTONIC, &ts);
+  return ts.tv_sec * 1000 + ts.tv_nsec / 1000000;
+}
+
+static void check_exit(pid_t pid, int code) {
+  int status;
+  if (waitpid(pid, &status, 0) != pid || !WIFEXITED(status) || WEXITSTATUS(status) != code) {
+    fprintf(stderr, "child %d: status %x, expect exit %d\n", pid, status, code);
+    failed = 1;
+  }
+}
+
+int main(void) {
+  // the child writes our memory before it exits
+  long start = now_ms();
+  pid_t pid = vfork();
+  if (pid < 0) {
+    perror("vfork");
+    return 1;
+  }
+  if (pid == 0) {
+    usleep(200000);
+    shared = 1;
+    _exit(3);
+  }
+
+  if (shared != 1 || now_ms() - start < 200) {
+    fprintf(stderr, "parent resumed before the child exited: shared %d\n", shared);
+    failed = 1;
+  }
+  check_exit(pid, 3);
+
+  // the parent resumes once the child has execed
+  shared = 0;
+  pid = vfork();
+  if (pid < 0) {
+    perror("vfork");
+    return 1;
+  }
+  if (pid == 0) {
+    shared = 2;
+    execl("/bin/sh", "sh", "-c", "exit 5", (char *)NULL);
+    _exit(127);
+  }
+
+  if (shared != 2) {
+    fprintf(stderr, "parent resumed before the child execed: shared %d\n", shared);
+    failed = 1;
+  }
+  check_exit(pid, 5);
+
+  printf("%s\n", failed ? "fail" : "pass");
+  return failed;
+}
@@ -0,0 +1,92 @@
+// waitid test: WNOWAIT leaves the child waitable, a nonblocking pidfd fails
+// with EAGAIN while the child runs and becomes readable once it exits, and
+// waitid(P_PIDFD) reaps it.
+
+#define _GNU_SOURCE
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+#include <errno.h>
+#include <poll.h>
+#include <sys/syscall.h>
+#include <sys/types.h>
+#include <sys/wait.h>
+
+#ifndef P_PIDFD
+#define P_PIDFD 3
+#endif
+
+#ifndef PIDFD_NONBLOCK
+#define PIDFD_NONBLOCK 04000
+#endif
+
+static int failed;
+
+static void die(const char *msg) {
+  perror(msg);
+  exit(1);
+}
+
+static void check(const char *what, siginfo_t *info, pid_t pid, int code, int status) {
+  if (info->si_pid != pid || info->si_code != code || info->si_status != status) {
+    fprintf(stderr, "%s: got pid %d code %d status %d, expect %d %d %d\n", what,
+            info->si_pid, info->si_code, info->si_status, pid, code, status);
+    failed = 1;
+  }
+}
+
+int main(void) {
+  int pipefd[2];
+  siginfo_t info;
+
+  if (pipe(pipefd) < 0)
+    die("pipe");
+
+  pid_t pid = fork();
+  if (pid < 0)
+    die("fork");
+  if (pid == 0) {
+    char c;
+    close(pipefd[1]);
+    read(pipefd[0], &c, 1);
+    exit(7);
+  }
+  close(pipefd[0]);
+
+  int pidfd = syscall(SYS_pidfd_open, pid, PIDFD_NONBLOCK);
+  if (pidfd < 0)
+    die("pidfd_open");
+
+  // the child is still running
+  memset(&info, 0, sizeof(info));
+  if (waitid(P_PIDFD, pidfd, &info, WEXITED) != -1 || errno != EAGAIN) {
+    fprintf(stderr, "nonblocking pidfd wait didn't fail with EAGAIN\n");
+    failed = 1;
+  }
+
+  close(pipefd[1]);
+  struct pollfd pfd = {pidfd, POLLIN, 0};
+  if (poll(&pfd, 1, 5000) != 1 || !(pfd.revents & POLLIN)) {
+    fprintf(stderr, "pidfd not readable after exit\n");
+    failed = 1;
+  }
+
+  memset(&info, 0, sizeof(info));
+  if (waitid(P_PID, pid, &info, WEXITED | WNOWAIT) < 0)
+    die("waitid WNOWAIT");
+  check("WNOWAIT", &info, pid, CLD_EXITED, 7);
+
+  memset(&info, 0, sizeof(info));
+  if (waitid(P_PIDFD, pidfd, &info, WEXITED) < 0)
+    die("waitid P_PIDFD");
+  check("P_PIDFD", &info, pid, CLD_EXITED, 7);
+
+  if (waitid(P_ALL, 0, &info, WEXITED | WNOHANG) != -1 || errno != ECHILD) {
+    fprintf(stderr, "child was not reaped\n");
+    failed = 1;
+  }
+
+  printf("%s\n", failed ? "fail" : "pass");
+  return failed;
+}
@@ -34,8 +34,8 @@ int __common_clock_gettime(clockid_t clock, struct timespec* ts) {
       ret = ClockRealtime(ts);
       break;
 
-    case CLOCK_BOOTTIME:
-      // Fallthrough, CLOCK_BOOTTIME is an alias for CLOCK_MONOTONIC
+    // CLOCK_BOOTTIME counts the host suspend time, it is left to the
+    // syscall.
     case CLOCK_MONOTONIC:
     case CLOCK_MONOTONIC_COARSE:
       ret = ClockMonotonic(ts);